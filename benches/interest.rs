@@ -0,0 +1,84 @@
+//! Benchmark to compare recomputing the room relevance of every entity from scratch with the
+//! incremental updates of the [`RoomManager`]
+use bevy::prelude::Entity;
+use bevy::utils::Duration;
+use criterion::{criterion_group, criterion_main, Criterion};
+use lightyear::prelude::server::{RoomId, RoomManager};
+use lightyear::prelude::ClientId;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+criterion_group!(interest_benches, room_relevance);
+criterion_main!(interest_benches);
+
+const NUM_CLIENTS: u64 = 200;
+const NUM_ENTITIES: u32 = 5000;
+/// Fraction of the entities that move to another cell every send interval
+const CHURN: f64 = 0.01;
+/// The map is a square grid of `GRID_SIZE * GRID_SIZE` cells, each cell is a room
+const GRID_SIZE: i64 = 20;
+
+fn cell_room(x: i64, y: i64) -> RoomId {
+    RoomId((x.rem_euclid(GRID_SIZE) * GRID_SIZE + y.rem_euclid(GRID_SIZE)) as u64)
+}
+
+/// Each client is in the 3x3 cells around its position, each entity is in a single cell
+fn setup(rng: &mut ChaCha8Rng) -> (RoomManager, Vec<(Entity, i64, i64)>) {
+    let mut manager = RoomManager::default();
+    for client in 0..NUM_CLIENTS {
+        let (x, y) = (rng.gen_range(0..GRID_SIZE), rng.gen_range(0..GRID_SIZE));
+        for dx in -1..=1 {
+            for dy in -1..=1 {
+                manager.add_client(ClientId::Netcode(client), cell_room(x + dx, y + dy));
+            }
+        }
+    }
+    let entities = (0..NUM_ENTITIES)
+        .map(|i| {
+            let entity = Entity::from_raw(i);
+            let (x, y) = (rng.gen_range(0..GRID_SIZE), rng.gen_range(0..GRID_SIZE));
+            manager.add_entity(entity, cell_room(x, y));
+            (entity, x, y)
+        })
+        .collect();
+    (manager, entities)
+}
+
+fn room_relevance(criterion: &mut Criterion) {
+    let mut group = criterion.benchmark_group(format!(
+        "interest/rooms/{NUM_CLIENTS}_clients_{NUM_ENTITIES}_entities"
+    ));
+    group.warm_up_time(Duration::from_millis(500));
+    group.measurement_time(Duration::from_millis(4000));
+    let num_moved = (NUM_ENTITIES as f64 * CHURN) as usize;
+
+    group.bench_function("full", |bencher| {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let (manager, entities) = setup(&mut rng);
+        bencher.iter(|| {
+            entities
+                .iter()
+                .map(|(entity, _, _)| manager.compute_relevant_clients(*entity).len())
+                .sum::<usize>()
+        });
+    });
+
+    group.bench_function("incremental", |bencher| {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let (mut manager, mut entities) = setup(&mut rng);
+        bencher.iter(|| {
+            let mut num_relevant = 0;
+            for _ in 0..num_moved {
+                // the entity moves to a neighbouring cell
+                let (entity, x, y) = &mut entities[rng.gen_range(0..NUM_ENTITIES as usize)];
+                manager.remove_entity(*entity, cell_room(*x, *y));
+                *x += rng.gen_range(-1..=1);
+                *y += rng.gen_range(-1..=1);
+                manager.add_entity(*entity, cell_room(*x, *y));
+                num_relevant += manager.relevant_clients(*entity).count();
+            }
+            num_relevant
+        });
+    });
+    group.finish();
+}
@@ -14,9 +14,10 @@ use lightyear::prelude::client::{
 };
 use lightyear::prelude::server::Replicate;
 use lightyear::prelude::{
-    client, server, MessageRegistry, Replicating, ReplicationGroup, Tick, TickManager,
+    client, server, MessageRegistry, Replicating, ReplicationGroup, ReplicationTarget, Tick,
+    TickManager,
 };
-use lightyear::prelude::{ClientId, SharedConfig, TickConfig};
+use lightyear::prelude::{ClientId, ParallelApplyConfig, SharedConfig, TickConfig};
 use lightyear::server::input::native::InputBuffers;
 use lightyear::shared::replication::network_target::NetworkTarget;
 use lightyear_benches::local_stepper::{LocalBevyStepper, Step as LocalStep};
@@ -35,7 +36,9 @@ criterion_group!(
     send_float_update_one_client,
     receive_float_insert,
     receive_float_update,
+    receive_snapshot_parallel_apply,
     send_float_insert_n_clients,
+    send_float_update_colocated_clients,
 );
 criterion_main!(replication_benches);
 
@@ -213,6 +216,66 @@ fn receive_float_insert(criterion: &mut Criterion) {
     group.finish();
 }
 
+const SNAPSHOT_NUM_ENTITIES: usize = 5000;
+
+/// Receiving a snapshot of N entities (each in its own replication group), with the component payloads
+/// deserialized on the main thread or in parallel on the compute task pool
+fn receive_snapshot_parallel_apply(criterion: &mut Criterion) {
+    let mut group = criterion.benchmark_group("replication/receive_snapshot/1_client");
+    group.warm_up_time(Duration::from_millis(500));
+    group.measurement_time(Duration::from_millis(4000));
+    for (name, parallel_apply) in [
+        ("sequential", ParallelApplyConfig::disabled()),
+        ("parallel", ParallelApplyConfig::default()),
+    ] {
+        group.bench_with_input(
+            criterion::BenchmarkId::new(name, SNAPSHOT_NUM_ENTITIES),
+            &SNAPSHOT_NUM_ENTITIES,
+            |bencher, n| {
+                bencher.iter_custom(|iter| {
+                    let mut elapsed = Duration::ZERO;
+                    for _ in 0..iter {
+                        let frame_duration = Duration::from_secs_f64(1.0 / 60.0);
+                        let tick_duration = Duration::from_secs_f64(1.0 / 64.0);
+                        let shared_config = SharedConfig {
+                            tick: TickConfig::new(tick_duration),
+                            ..default()
+                        };
+                        let mut stepper = LocalBevyStepper::new(
+                            1,
+                            shared_config,
+                            SyncConfig::default(),
+                            PredictionConfig::default(),
+                            InterpolationConfig::default(),
+                            frame_duration,
+                        );
+                        stepper
+                            .client_resource_mut::<client::ClientConfig>(ClientId::Netcode(0))
+                            .replication
+                            .parallel_apply = parallel_apply;
+                        stepper.init();
+                        let entities = vec![(Component1(1.0), Replicate::default()); *n];
+                        stepper.server_app.world_mut().spawn_batch(entities);
+
+                        // advance time by one frame
+                        stepper.advance_time(stepper.frame_duration);
+
+                        // buffer and send replication messages
+                        stepper.server_update();
+
+                        // receive messages
+                        let instant = Instant::now();
+                        stepper.client_update();
+                        elapsed += instant.elapsed();
+                    }
+                    elapsed
+                });
+            },
+        );
+    }
+    group.finish();
+}
+
 /// Replicating N entity spawn from server to channel, with a local io
 fn receive_float_update(criterion: &mut Criterion) {
     let mut group = criterion.benchmark_group("replication/receive_float_update/1_client");
@@ -318,3 +381,96 @@ fn send_float_insert_n_clients(criterion: &mut Criterion) {
     }
     group.finish();
 }
+
+const COLOCATED_NUM_CLIENTS: usize = 40;
+/// The entities are split between cells, each cell is a replication group
+const COLOCATED_NUM_CELLS: u64 = 10;
+
+/// Sending the updates of entities that are seen by 40 clients standing in the same area, with the update
+/// messages serialized for each client or shared between the clients that need the same message.
+/// Each client also sees an entity that is only replicated to it.
+fn send_float_update_colocated_clients(criterion: &mut Criterion) {
+    let mut group = criterion.benchmark_group(format!(
+        "replication/send_float_update/{COLOCATED_NUM_CLIENTS}_colocated_clients"
+    ));
+    group.warm_up_time(Duration::from_millis(500));
+    group.measurement_time(Duration::from_millis(4000));
+    for (name, coalesce_updates) in [("per_client", false), ("coalesced", true)] {
+        group.bench_with_input(
+            criterion::BenchmarkId::new(name, FIXED_NUM_ENTITIES),
+            &FIXED_NUM_ENTITIES,
+            |bencher, n| {
+                bencher.iter_custom(|iter| {
+                    let frame_duration = Duration::from_secs_f64(1.0 / 60.0);
+                    let tick_duration = Duration::from_secs_f64(1.0 / 64.0);
+                    let shared_config = SharedConfig {
+                        tick: TickConfig::new(tick_duration),
+                        ..default()
+                    };
+                    let mut stepper = LocalBevyStepper::new(
+                        COLOCATED_NUM_CLIENTS,
+                        shared_config,
+                        SyncConfig::default(),
+                        PredictionConfig::default(),
+                        InterpolationConfig::default(),
+                        frame_duration,
+                    );
+                    stepper
+                        .server_app
+                        .world_mut()
+                        .resource_mut::<server::ServerConfig>()
+                        .replication
+                        .coalesce_updates = coalesce_updates;
+                    stepper.init();
+                    let entities = (0..*n as u64).map(|i| {
+                        (
+                            Component1(0.0),
+                            Replicate {
+                                group: ReplicationGroup::new_id(i % COLOCATED_NUM_CELLS),
+                                ..default()
+                            },
+                        )
+                    });
+                    stepper.server_app.world_mut().spawn_batch(entities);
+                    for i in 0..COLOCATED_NUM_CLIENTS {
+                        stepper.server_app.world_mut().spawn((
+                            Component1(0.0),
+                            Replicate {
+                                target: ReplicationTarget {
+                                    target: NetworkTarget::Single(ClientId::Netcode(i as u64)),
+                                },
+                                ..default()
+                            },
+                        ));
+                    }
+                    stepper.update();
+
+                    let mut elapsed = Duration::ZERO;
+                    for _ in 0..iter {
+                        // update the entities
+                        for mut component in stepper
+                            .server_app
+                            .world_mut()
+                            .query_filtered::<&mut Component1, With<Replicating>>()
+                            .iter_mut(stepper.server_app.world_mut())
+                        {
+                            component.0 += 1.0;
+                        }
+
+                        // advance time by one frame
+                        stepper.advance_time(stepper.frame_duration);
+
+                        let instant = Instant::now();
+                        // buffer and send replication messages
+                        stepper.server_update();
+                        elapsed += instant.elapsed();
+
+                        stepper.client_update();
+                    }
+                    elapsed
+                });
+            },
+        );
+    }
+    group.finish();
+}
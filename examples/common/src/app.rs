@@ -26,6 +26,7 @@ use lightyear::shared::log::add_log_layer;
 use lightyear::transport::LOCAL_SOCKET;
 use serde::{Deserialize, Serialize};
 
+use crate::launcher;
 use crate::settings::*;
 use crate::shared::{shared_config, SERVER_REPLICATION_INTERVAL};
 
@@ -51,6 +52,16 @@ pub enum Cli {
         #[arg(short, long, default_value = None)]
         client_id: Option<u64>,
     },
+    /// The program will act as a client, and spawn a dedicated server in a child process.
+    /// The server uses the same settings, with a port picked by the OS.
+    ClientWithLocalServer {
+        #[arg(short, long, default_value = None)]
+        client_id: Option<u64>,
+    },
+    /// Dedicated server spawned by [`launcher::spawn_local_server`].
+    /// The settings are read from the file provided by the parent process.
+    #[command(hide = true)]
+    ChildServer,
 }
 
 /// App that is Send.
@@ -153,6 +164,24 @@ impl Apps {
                 let (app, config) = server_app(settings, vec![]);
                 Apps::Server { app, config }
             }
+            Cli::ClientWithLocalServer { client_id } => {
+                let server = launcher::spawn_local_server(&settings)
+                    .expect("Could not spawn the local server");
+                let settings = server.client_settings(&settings);
+                let client_id = client_id.unwrap_or(settings.client.client_id);
+                let net_config = get_client_net_config(&settings, client_id);
+                let (mut app, config) = client_app(settings, net_config);
+                // the server process is killed when the client app is dropped
+                app.insert_resource(server);
+                Apps::Client { app, config }
+            }
+            Cli::ChildServer => {
+                let settings = launcher::child_server_settings()
+                    .expect("Could not read the settings of the child server");
+                let (mut app, config) = server_app(settings, vec![]);
+                app.add_plugins(launcher::ChildServerPlugin);
+                Apps::Server { app, config }
+            }
             Cli::Client { client_id } => {
                 let server_addr = SocketAddr::new(
                     settings.client.server_addr.into(),
@@ -0,0 +1,264 @@
+//! Run the server in a separate process
+//!
+//! This is useful for example to launch a local server from an editor or a game menu, and connect to it
+//! with a client running in the current process.
+//!
+//! - [`spawn_local_server`] writes the [`Settings`] to a temporary file and re-launches the current executable
+//!   with the `child-server` command (see [`Cli::ChildServer`](crate::app::Cli::ChildServer)).
+//!   The child process reads the settings from that file, so that the client and the server always use
+//!   the same settings.
+//! - the UDP ports of the server are replaced with 0 so that the OS picks a free port; once the server is
+//!   listening, the child process reports its port on stdout and the parent uses it to connect.
+//! - the [`ChildServerHandle`] kills the child process when it is dropped. If the parent process crashes instead,
+//!   the child process detects that its stdin was closed and exits on its own, so that no orphan server is left
+//!   running. This works the same way on Windows, macOS and Linux.
+use std::fs;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{Ipv4Addr, SocketAddr};
+use std::path::PathBuf;
+use std::process::{Child, ChildStdin, Command, ExitStatus, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Context};
+use bevy::asset::ron;
+use bevy::prelude::*;
+use crossbeam_channel::{Receiver, TryRecvError};
+use lightyear::prelude::server::{NetServer, NetworkingState, ServerConnections};
+
+use crate::settings::{read_settings, ServerTransports, Settings};
+
+/// Environment variable containing the path of the settings file of the child server
+pub const SETTINGS_FILE_ENV: &str = "LIGHTYEAR_CHILD_SERVER_SETTINGS";
+
+/// Prefix of the line written on stdout by the child server once it is listening
+pub const SERVER_PORT_PREFIX: &str = "LIGHTYEAR_SERVER_PORT=";
+
+/// Maximum duration to wait for the child server to report its port
+pub const DEFAULT_STARTUP_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Handle to a server running in a child process.
+///
+/// The child process is killed when the handle is dropped.
+#[derive(Resource)]
+pub struct ChildServerHandle {
+    child: Child,
+    /// Closing the stdin of the child asks it to shut down
+    stdin: Option<ChildStdin>,
+    settings_file: PathBuf,
+    server_addr: SocketAddr,
+    exit_status: Option<ExitStatus>,
+}
+
+impl ChildServerHandle {
+    /// Address that the clients running on this machine can use to connect to the server
+    pub fn server_addr(&self) -> SocketAddr {
+        self.server_addr
+    }
+
+    /// OS identifier of the child process
+    pub fn id(&self) -> u32 {
+        self.child.id()
+    }
+
+    /// Returns true if the child process is still running
+    pub fn is_alive(&mut self) -> bool {
+        matches!(self.child.try_wait(), Ok(None))
+    }
+
+    /// Update the client settings so that the client connects to the child server
+    pub fn client_settings(&self, settings: &Settings) -> Settings {
+        let mut settings = settings.clone();
+        settings.client.server_addr = Ipv4Addr::LOCALHOST;
+        settings.client.server_port = self.server_addr.port();
+        settings
+    }
+
+    /// Ask the child server to exit, and kill it if it is still running after `timeout`.
+    pub fn shutdown(mut self, timeout: Duration) -> std::io::Result<ExitStatus> {
+        drop(self.stdin.take());
+        let start = Instant::now();
+        while start.elapsed() < timeout {
+            if let Some(status) = self.child.try_wait()? {
+                self.exit_status = Some(status);
+                return Ok(status);
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        self.stop()
+    }
+
+    /// Kill the child server, and wait for the process to be reaped
+    pub fn kill(mut self) -> std::io::Result<ExitStatus> {
+        self.stop()
+    }
+
+    fn stop(&mut self) -> std::io::Result<ExitStatus> {
+        if let Some(status) = self.exit_status {
+            return Ok(status);
+        }
+        if self.child.try_wait()?.is_none() {
+            self.child.kill()?;
+        }
+        // always wait, otherwise the process would stay as a zombie on unix
+        let status = self.child.wait()?;
+        self.exit_status = Some(status);
+        Ok(status)
+    }
+}
+
+impl Drop for ChildServerHandle {
+    fn drop(&mut self) {
+        if let Err(e) = self.stop() {
+            error!("Could not stop the child server process: {:?}", e);
+        }
+        let _ = fs::remove_file(&self.settings_file);
+    }
+}
+
+/// Spawn a server with the given settings in a child process running the current executable.
+///
+/// The executable must build its apps with [`Apps::new`](crate::app::Apps::new) from the [`Cli`](crate::app::Cli)
+/// so that it handles the `child-server` command.
+pub fn spawn_local_server(settings: &Settings) -> anyhow::Result<ChildServerHandle> {
+    let mut command = Command::new(std::env::current_exe()?);
+    command.arg("child-server");
+    spawn_server_command(command, settings, DEFAULT_STARTUP_TIMEOUT)
+}
+
+/// Spawn a server with the given settings by running `command`, and wait until it reports its port.
+///
+/// The command must end up building its server app with [`Cli::ChildServer`](crate::app::Cli::ChildServer).
+pub fn spawn_server_command(
+    mut command: Command,
+    settings: &Settings,
+    timeout: Duration,
+) -> anyhow::Result<ChildServerHandle> {
+    let mut settings = settings.clone();
+    for transport in settings.server.transport.iter_mut() {
+        if let ServerTransports::Udp { local_port } = transport {
+            *local_port = 0;
+        }
+    }
+    let settings_file = write_settings_file(&settings)?;
+    command
+        .env(SETTINGS_FILE_ENV, &settings_file)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped());
+    let mut child = match command.spawn() {
+        Ok(child) => child,
+        Err(e) => {
+            let _ = fs::remove_file(&settings_file);
+            return Err(e).context("Could not spawn the server process");
+        }
+    };
+    let stdout = child.stdout.take().unwrap();
+    let mut handle = ChildServerHandle {
+        stdin: child.stdin.take(),
+        child,
+        settings_file,
+        server_addr: SocketAddr::new(Ipv4Addr::LOCALHOST.into(), 0),
+        exit_status: None,
+    };
+
+    // read the port from the output of the child, and forward the rest of the output to our stdout
+    let (port_sender, port_receiver) = crossbeam_channel::bounded(1);
+    std::thread::spawn(move || {
+        for line in BufReader::new(stdout).lines() {
+            let Ok(line) = line else {
+                break;
+            };
+            match line
+                .strip_prefix(SERVER_PORT_PREFIX)
+                .and_then(|port| port.trim().parse::<u16>().ok())
+            {
+                Some(port) => {
+                    let _ = port_sender.try_send(port);
+                }
+                None => println!("{line}"),
+            }
+        }
+    });
+    // if the handle is dropped because of an error, the child process is killed
+    let port = port_receiver
+        .recv_timeout(timeout)
+        .map_err(|_| anyhow!("The server process did not report its port (exited or timed out)"))?;
+    handle.server_addr.set_port(port);
+    info!(pid = handle.id(), ?port, "Spawned local server process");
+    Ok(handle)
+}
+
+fn write_settings_file(settings: &Settings) -> anyhow::Result<PathBuf> {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let path = std::env::temp_dir().join(format!(
+        "lightyear_server_{}_{}.ron",
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    let contents = ron::ser::to_string_pretty(settings, ron::ser::PrettyConfig::default())?;
+    fs::write(&path, contents)?;
+    Ok(path)
+}
+
+/// Read the settings written by the parent process for the child server
+pub fn child_server_settings() -> anyhow::Result<Settings> {
+    let path = std::env::var(SETTINGS_FILE_ENV)
+        .with_context(|| format!("{SETTINGS_FILE_ENV} is not set"))?;
+    let contents = fs::read_to_string(&path)
+        .with_context(|| format!("Could not read the server settings file {path}"))?;
+    Ok(read_settings(&contents))
+}
+
+/// Returns true if the current process was spawned by [`spawn_local_server`]
+pub fn is_child_server() -> bool {
+    std::env::var_os(SETTINGS_FILE_ENV).is_some()
+}
+
+/// Plugin added to the server app of a child server process:
+/// - reports the port of the server to the parent process
+/// - exits when the parent process closes our stdin (because it exited or crashed)
+pub struct ChildServerPlugin;
+
+/// Notified when the stdin of the process is closed
+#[derive(Resource)]
+struct ParentLink(Receiver<()>);
+
+impl Plugin for ChildServerPlugin {
+    fn build(&self, app: &mut App) {
+        let (sender, receiver) = crossbeam_channel::bounded(1);
+        std::thread::spawn(move || {
+            let mut stdin = std::io::stdin();
+            let mut buffer = [0; 64];
+            while let Ok(n) = stdin.read(&mut buffer) {
+                if n == 0 {
+                    break;
+                }
+            }
+            let _ = sender.send(());
+        });
+        app.insert_resource(ParentLink(receiver));
+        app.add_systems(OnEnter(NetworkingState::Started), report_server_port);
+        app.add_systems(Update, exit_with_parent);
+    }
+}
+
+fn report_server_port(connections: Res<ServerConnections>) {
+    let Some(addr) = connections
+        .servers
+        .iter()
+        .find_map(|server| server.io().map(|io| io.local_addr()))
+    else {
+        error!("The child server has no transport with a local address");
+        return;
+    };
+    let mut stdout = std::io::stdout();
+    let _ = writeln!(stdout, "{SERVER_PORT_PREFIX}{}", addr.port());
+    let _ = stdout.flush();
+}
+
+fn exit_with_parent(link: Res<ParentLink>, mut exit: EventWriter<AppExit>) {
+    if !matches!(link.0.try_recv(), Err(TryRecvError::Empty)) {
+        info!("The parent process closed the connection, shutting down the server");
+        exit.send(AppExit::Success);
+    }
+}
@@ -57,6 +57,11 @@
 //! }
 //! ```
 //!
+//! ## Running the server in a separate process
+//!
+//! The [`launcher`] module can spawn the server in a child process that uses the same settings,
+//! and connect a client to it. Run with `cargo run -- client-with-local-server -c 1`
+//!
 //! ## Reading settings
 //!
 //! The settings are read from the [`settings`] module, which contains the [`Settings`](settings::Settings) struct.
@@ -70,5 +75,6 @@
 //! [`ServerConfig`]: lightyear::prelude::server::ServerConfig
 
 pub mod app;
+pub mod launcher;
 pub mod settings;
 pub mod shared;
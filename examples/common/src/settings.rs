@@ -13,7 +13,7 @@ use bevy::tasks::IoTaskPool;
 
 use lightyear::prelude::client::Authentication;
 use lightyear::prelude::client::{SocketConfig, SteamConfig};
-use lightyear::prelude::{CompressionConfig, LinkConditionerConfig};
+use lightyear::prelude::{CompressionConfig, LinkConditionerConfig, NetworkPreset};
 
 use lightyear::prelude::{client, server};
 
@@ -25,6 +25,11 @@ pub fn read_settings<T: DeserializeOwned>(settings_str: &str) -> T {
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 pub enum ClientTransports {
     Udp,
+    WebTransport {
+        /// Hex-encoded SHA-256 digest of the server certificate; leave empty for a CA-signed certificate
+        #[serde(default)]
+        certificate_digest: String,
+    },
     Steam {
         app_id: u32,
     },
@@ -35,7 +40,9 @@ pub enum ServerTransports {
     Udp {
         local_port: u16,
     },
-   
+    WebSocket {
+        local_port: u16,
+    },
     Steam {
         app_id: u32,
         server_ip: Ipv4Addr,
@@ -44,22 +51,34 @@ pub enum ServerTransports {
     },
 }
 
+/// Simulated network conditions, either the name of a preset (e.g. `Some("FourG")`)
+/// or custom values (e.g. `Some(Conditioner(latency_ms: 200, jitter_ms: 20, packet_loss: 0.05))`)
 #[derive(Clone, Debug, Deserialize, Serialize)]
-pub struct Conditioner {
-    /// One way latency in milliseconds
-    pub(crate) latency_ms: u16,
-    /// One way jitter in milliseconds
-    pub(crate) jitter_ms: u16,
-    /// Percentage of packet loss
-    pub(crate) packet_loss: f32,
+#[serde(untagged)]
+pub enum Conditioner {
+    Preset(NetworkPreset),
+    Custom {
+        /// One way latency in milliseconds
+        latency_ms: u16,
+        /// One way jitter in milliseconds
+        jitter_ms: u16,
+        /// Percentage of packet loss
+        packet_loss: f32,
+    },
 }
 
 impl Conditioner {
     pub fn build(&self) -> LinkConditionerConfig {
-        LinkConditionerConfig {
-            incoming_latency: Duration::from_millis(self.latency_ms as u64),
-            incoming_jitter: Duration::from_millis(self.jitter_ms as u64),
-            incoming_loss: self.packet_loss,
+        match self {
+            Conditioner::Preset(preset) => LinkConditionerConfig::preset(*preset),
+            Conditioner::Custom {
+                latency_ms,
+                jitter_ms,
+                packet_loss,
+            } => LinkConditionerConfig::default()
+                .with_latency(Duration::from_millis(*latency_ms as u64))
+                .with_jitter(Duration::from_millis(*jitter_ms as u64))
+                .with_loss(*packet_loss),
         }
     }
 }
@@ -128,13 +147,7 @@ pub(crate) fn build_server_netcode_config(
     shared: &SharedSettings,
     transport_config: server::ServerTransport,
 ) -> server::NetConfig {
-    let conditioner = conditioner.map_or(None, |c| {
-        Some(LinkConditionerConfig {
-            incoming_latency: Duration::from_millis(c.latency_ms as u64),
-            incoming_jitter: Duration::from_millis(c.jitter_ms as u64),
-            incoming_loss: c.packet_loss,
-        })
-    });
+    let conditioner = conditioner.map_or(None, |c| Some(c.build()));
     let netcode_config = server::NetcodeConfig::default()
         .with_protocol_id(shared.protocol_id)
         .with_key(shared.private_key);
@@ -165,6 +178,13 @@ pub(crate) fn get_server_net_configs(settings: &Settings) -> Vec<server::NetConf
                     *local_port,
                 )),
             ),
+            ServerTransports::WebSocket { local_port } => build_server_netcode_config(
+                settings.server.conditioner.as_ref(),
+                &settings.shared,
+                server::ServerTransport::WebSocketServer {
+                    server_addr: SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), *local_port),
+                },
+            ),
 
             ServerTransports::Steam {
                 app_id,
@@ -237,6 +257,17 @@ pub fn get_client_net_config(settings: &Settings, client_id: u64) -> client::Net
             &settings.shared,
             client::ClientTransport::UdpSocket(client_addr),
         ),
+        ClientTransports::WebTransport { certificate_digest } => build_client_netcode_config(
+            client_id,
+            server_addr,
+            settings.client.conditioner.as_ref(),
+            &settings.shared,
+            client::ClientTransport::WebTransportClient {
+                client_addr,
+                server_addr,
+                certificate_digest: certificate_digest.clone(),
+            },
+        ),
 
         ClientTransports::Steam { app_id } => client::NetConfig::Steam {
             steamworks_client: None,
@@ -0,0 +1,181 @@
+//! Spawn a server in a child process, connect to it and exchange a message.
+//!
+//! The test binary is re-executed as the child process: the `child_server` test only does something
+//! when it runs inside a process spawned by the launcher.
+use std::process::Command;
+use std::time::{Duration, Instant};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use lightyear::prelude::client::ClientCommands;
+use lightyear::prelude::server::ServerCommands;
+use lightyear::prelude::*;
+use lightyear_examples_common::app::{Apps, Cli};
+use lightyear_examples_common::launcher::{self, spawn_server_command};
+use lightyear_examples_common::settings::{read_settings, Settings};
+
+const SETTINGS: &str = r#"
+Settings(
+    client: ClientSettings(
+        inspector: false,
+        client_id: 1,
+        client_port: 0,
+        server_addr: "127.0.0.1",
+        conditioner: None,
+        server_port: 5000,
+        transport: Udp,
+    ),
+    server: ServerSettings(
+        headless: true,
+        inspector: false,
+        conditioner: None,
+        transport: [
+            Udp(
+                local_port: 5000
+            ),
+        ],
+    ),
+    shared: SharedSettings(
+        protocol_id: 0,
+        private_key: (0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0),
+        compression: None,
+    )
+)
+"#;
+
+#[derive(Channel)]
+struct Channel1;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+struct Echo(u32);
+
+#[derive(Clone)]
+struct ProtocolPlugin;
+
+impl Plugin for ProtocolPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_message::<Echo>(ChannelDirection::Bidirectional);
+        app.add_channel::<Channel1>(ChannelSettings {
+            mode: ChannelMode::OrderedReliable(ReliableSettings::default()),
+            ..default()
+        });
+    }
+}
+
+struct ServerPlugin;
+
+impl Plugin for ServerPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, |mut commands: Commands| commands.start_server());
+        app.add_systems(Update, echo);
+    }
+}
+
+/// Send the received messages back to the client
+fn echo(
+    mut events: EventReader<server::MessageEvent<Echo>>,
+    mut connection: ResMut<server::ConnectionManager>,
+) {
+    for event in events.read() {
+        connection
+            .send_message::<Channel1, Echo>(*event.context(), event.message())
+            .unwrap();
+    }
+}
+
+struct ClientPlugin;
+
+#[derive(Resource, Default)]
+struct Received(Vec<Echo>);
+
+impl Plugin for ClientPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Received>();
+        app.add_systems(Startup, |mut commands: Commands| commands.connect_client());
+        app.add_systems(OnEnter(client::NetworkingState::Connected), send_echo);
+        app.add_systems(Update, receive_echo);
+    }
+}
+
+fn send_echo(mut connection: ResMut<client::ConnectionManager>) {
+    connection
+        .send_message::<Channel1, Echo>(&Echo(42))
+        .unwrap();
+}
+
+fn receive_echo(
+    mut events: EventReader<client::MessageEvent<Echo>>,
+    mut received: ResMut<Received>,
+) {
+    received
+        .0
+        .extend(events.read().map(|event| event.message().clone()));
+}
+
+/// Runs the server when this binary is spawned by the launcher
+#[test]
+fn child_server() {
+    if !launcher::is_child_server() {
+        return;
+    }
+    let mut apps = Apps::new(read_settings(SETTINGS), Cli::ChildServer);
+    apps.add_lightyear_plugins()
+        .add_user_plugins(ClientPlugin, ServerPlugin, ProtocolPlugin);
+    apps.run();
+}
+
+fn spawn_child_server(settings: &Settings) -> launcher::ChildServerHandle {
+    let mut command = Command::new(std::env::current_exe().unwrap());
+    command.args(["child_server", "--exact", "--nocapture"]);
+    spawn_server_command(command, settings, Duration::from_secs(30)).unwrap()
+}
+
+/// Check that the process was reaped (i.e. it is not a zombie)
+#[allow(unused_variables)]
+fn assert_reaped(pid: u32) {
+    #[cfg(target_os = "linux")]
+    assert!(!std::path::Path::new(&format!("/proc/{pid}")).exists());
+}
+
+#[test]
+fn test_spawn_local_server() {
+    let settings: Settings = read_settings(SETTINGS);
+    let mut server = spawn_child_server(&settings);
+    assert!(server.is_alive());
+    // the OS picked a port for the server
+    assert_ne!(server.server_addr().port(), 0);
+
+    let client_settings = server.client_settings(&settings);
+    let mut apps = Apps::new(client_settings, Cli::Client { client_id: Some(1) });
+    apps.add_lightyear_plugins()
+        .add_user_plugins(ClientPlugin, ServerPlugin, ProtocolPlugin);
+    let Apps::Client { mut app, .. } = apps else {
+        unreachable!()
+    };
+    app.finish();
+    app.cleanup();
+    let start = Instant::now();
+    while app.world().resource::<Received>().0.is_empty()
+        && start.elapsed() < Duration::from_secs(10)
+    {
+        app.update();
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    assert_eq!(app.world().resource::<Received>().0, vec![Echo(42)]);
+
+    let pid = server.id();
+    server.kill().unwrap();
+    assert_reaped(pid);
+}
+
+/// If the parent closes the stdin of the child (for example because it crashed), the server exits on its own
+#[test]
+fn test_child_server_exits_with_parent() {
+    let settings: Settings = read_settings(SETTINGS);
+    let server = spawn_child_server(&settings);
+    let pid = server.id();
+    let status = server.shutdown(Duration::from_secs(10)).unwrap();
+    assert!(status.success());
+    assert_reaped(pid);
+}
@@ -0,0 +1,58 @@
+//! The client side of the example.
+//!
+//! The client connects to the server at Startup, and periodically logs the statistics of what it
+//! received from the server, using [`ConnectionManager::receive_stats`].
+use bevy::prelude::*;
+use bevy::time::common_conditions::on_timer;
+use bevy::utils::Duration;
+
+use lightyear::prelude::client::*;
+
+use crate::protocol::*;
+use crate::shared::*;
+
+const LOG_INTERVAL: Duration = Duration::from_secs(2);
+
+pub struct ExampleClientPlugin;
+
+impl Plugin for ExampleClientPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, connect_client);
+        app.add_systems(Update, log_receive_stats.run_if(on_timer(LOG_INTERVAL)));
+    }
+}
+
+fn connect_client(mut commands: Commands) {
+    commands.connect_client();
+}
+
+/// Log the bandwidth used since the last log, and the average number of updates of the closest
+/// and farthest entities of the room
+fn log_receive_stats(
+    connection: Res<ConnectionManager>,
+    query: Query<(Entity, &GridPosition, &Zone)>,
+    mut last_bytes: Local<u64>,
+) {
+    let stats = connection.receive_stats();
+    let bytes_per_second = (stats.bytes - *last_bytes) as f32 / LOG_INTERVAL.as_secs_f32();
+    *last_bytes = stats.bytes;
+
+    let max_distance = Vec2::new((ROOM_WIDTH - 1) as f32, (ROOM_HEIGHT - 1) as f32).length();
+    let (mut closest, mut farthest) = ((0, 0), (0, 0));
+    for (entity, position, zone) in query.iter() {
+        let distance = position.floor().distance(viewer_position(*zone));
+        let updates = stats.entity_updates(entity);
+        if distance < 3.0 {
+            closest = (closest.0 + updates, closest.1 + 1);
+        } else if distance > max_distance - 3.0 {
+            farthest = (farthest.0 + updates, farthest.1 + 1);
+        }
+    }
+    let mean = |(updates, count): (u32, u32)| updates as f32 / count.max(1) as f32;
+    info!(
+        entities = query.iter().count(),
+        "Received {bytes_per_second:.0} B/s (cap: {BANDWIDTH_CAP} B/s). Mean updates: closest entities {:.1}, farthest entities {:.1}",
+        mean(closest),
+        mean(farthest),
+    );
+}
@@ -0,0 +1,47 @@
+//! This example showcases per-entity priorities combined with interest management, under a bandwidth cap.
+//!
+//! The server spawns 1000 entities on a grid that is split in 4 rooms. Each client joins one of the rooms
+//! and only receives the entities of that room. The priority of an entity decreases with its distance
+//! to the corner of its room (where the client sits), so when the bandwidth cap is reached the closest
+//! entities are updated much more often than the farthest ones.
+//!
+//! Run with
+//! - `cargo run -- server`
+//! - `cargo run -- client -c 1`
+//! - `cargo run -- client -c 2`
+//!
+//! The clients periodically log how many bytes they receive and how often the closest and farthest
+//! entities of their room are updated.
+use bevy::prelude::*;
+use lightyear::prelude::server::ServerConfig;
+use lightyear_examples_common::app::{Apps, Cli};
+use lightyear_examples_common::settings::{read_settings, Settings};
+
+use crate::client::ExampleClientPlugin;
+use crate::server::ExampleServerPlugin;
+use crate::shared::{SharedPlugin, BANDWIDTH_CAP};
+
+mod client;
+mod protocol;
+mod server;
+mod shared;
+
+fn main() {
+    let cli = Cli::default();
+    let settings_str = include_str!("../assets/settings.ron");
+    let settings = read_settings::<Settings>(settings_str);
+    let mut apps = Apps::new(settings, cli);
+    // cap the bandwidth that the server can use for each client
+    apps.update_lightyear_server_config(|config: &mut ServerConfig| {
+        config.packet = config
+            .packet
+            .with_send_bandwidth_bytes_per_second_cap(BANDWIDTH_CAP)
+            .enable_bandwidth_cap();
+    });
+    apps.add_lightyear_plugins().add_user_plugins(
+        ExampleClientPlugin,
+        ExampleServerPlugin,
+        SharedPlugin,
+    );
+    apps.run();
+}
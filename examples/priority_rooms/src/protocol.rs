@@ -0,0 +1,22 @@
+//! This file contains the shared protocol: the components replicated from the server to the clients.
+use bevy::prelude::{App, Component, Deref, DerefMut, Plugin, Vec2};
+use serde::{Deserialize, Serialize};
+
+use lightyear::prelude::*;
+
+/// Position of the entity on the grid; it wobbles on every tick so that every entity always has updates to send
+#[derive(Component, Serialize, Deserialize, Clone, Debug, PartialEq, Deref, DerefMut)]
+pub struct GridPosition(pub Vec2);
+
+/// The room (quadrant of the grid) that the entity belongs to
+#[derive(Component, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Zone(pub u64);
+
+pub(crate) struct ProtocolPlugin;
+
+impl Plugin for ProtocolPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_component::<GridPosition>(ChannelDirection::ServerToClient);
+        app.register_component::<Zone>(ChannelDirection::ServerToClient);
+    }
+}
@@ -0,0 +1,74 @@
+//! The server side of the example.
+//!
+//! The server will:
+//! - spawn the entities of the grid, each in the room of its quadrant
+//! - put each client in a room when it connects
+//! - move the entities on every tick
+use bevy::prelude::*;
+use lightyear::prelude::server::*;
+use lightyear::prelude::*;
+
+use crate::protocol::*;
+use crate::shared::*;
+
+pub struct ExampleServerPlugin;
+
+impl Plugin for ExampleServerPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, (start_server, spawn_grid));
+        app.add_systems(FixedUpdate, wobble);
+        app.add_systems(Update, handle_connections);
+    }
+}
+
+/// Start the server
+fn start_server(mut commands: Commands) {
+    commands.start_server();
+}
+
+/// Spawn one entity per cell of the grid, in the room of its quadrant
+fn spawn_grid(mut commands: Commands, mut room_manager: ResMut<RoomManager>) {
+    for x in 0..GRID_WIDTH {
+        for y in 0..GRID_HEIGHT {
+            let cell = UVec2::new(x, y);
+            let zone = zone_of(cell);
+            let position = cell.as_vec2();
+            let distance = position.distance(viewer_position(zone));
+            let entity = commands
+                .spawn((
+                    GridPosition(position),
+                    zone,
+                    Replicate {
+                        // only the clients in the same room receive the entity
+                        relevance_mode: NetworkRelevanceMode::InterestManagement,
+                        group: ReplicationGroup::default().set_priority(priority(distance)),
+                        ..default()
+                    },
+                ))
+                .id();
+            room_manager.add_entity(entity, RoomId(zone.0));
+        }
+    }
+    info!("Spawned {} entities", GRID_WIDTH * GRID_HEIGHT);
+}
+
+/// Put each client in a room when it connects
+fn handle_connections(
+    mut connections: EventReader<ConnectEvent>,
+    mut room_manager: ResMut<RoomManager>,
+) {
+    for connection in connections.read() {
+        let client_id = connection.client_id;
+        let room = client_id.to_bits() % NUM_ROOMS;
+        info!("Client {client_id:?} joins room {room}");
+        room_manager.add_client(client_id, RoomId(room));
+    }
+}
+
+/// Move every entity slightly on every tick, so that they all want to send updates
+fn wobble(mut query: Query<(&mut GridPosition, &Zone)>, tick_manager: Res<TickManager>) {
+    let phase = tick_manager.tick().0 as f32 * 0.1;
+    for (mut position, zone) in query.iter_mut() {
+        position.y = position.y.floor() + 0.1 * (phase + zone.0 as f32).sin().abs();
+    }
+}
@@ -0,0 +1,63 @@
+//! This module contains the grid layout shared between the client and the server, and the rendering.
+use bevy::prelude::*;
+use bevy::render::RenderPlugin;
+
+use crate::protocol::*;
+
+/// Number of bytes per second that the server can send to each client
+pub const BANDWIDTH_CAP: u32 = 56_000;
+/// The grid is split in 4 rooms of `ROOM_WIDTH * ROOM_HEIGHT` cells
+pub const GRID_WIDTH: u32 = 50;
+pub const GRID_HEIGHT: u32 = 20;
+pub const ROOM_WIDTH: u32 = 25;
+pub const ROOM_HEIGHT: u32 = 10;
+pub const NUM_ROOMS: u64 = 4;
+const CELL_SIZE: f32 = 20.0;
+
+#[derive(Clone)]
+pub struct SharedPlugin;
+
+impl Plugin for SharedPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(ProtocolPlugin);
+        if app.is_plugin_added::<RenderPlugin>() {
+            app.add_systems(Startup, init_camera);
+            app.add_systems(Update, draw_entities);
+        }
+    }
+}
+
+/// The room that contains the cell
+pub fn zone_of(cell: UVec2) -> Zone {
+    Zone((cell.x / ROOM_WIDTH + 2 * (cell.y / ROOM_HEIGHT)) as u64)
+}
+
+/// The corner of the room, where the client of that room sits
+pub fn viewer_position(zone: Zone) -> Vec2 {
+    Vec2::new(
+        ((zone.0 % 2) as u32 * ROOM_WIDTH) as f32,
+        ((zone.0 / 2) as u32 * ROOM_HEIGHT) as f32,
+    )
+}
+
+/// The priority of an entity decreases with its distance to the client of its room
+pub fn priority(distance: f32) -> f32 {
+    1.0 / (1.0 + distance)
+}
+
+fn init_camera(mut commands: Commands) {
+    commands.spawn(Camera2dBundle {
+        transform: Transform::from_xyz(
+            GRID_WIDTH as f32 * CELL_SIZE / 2.0,
+            GRID_HEIGHT as f32 * CELL_SIZE / 2.0,
+            0.0,
+        ),
+        ..default()
+    });
+}
+
+fn draw_entities(mut gizmos: Gizmos, query: Query<&GridPosition>) {
+    for position in query.iter() {
+        gizmos.circle_2d(position.0 * CELL_SIZE, CELL_SIZE / 4.0, Color::WHITE);
+    }
+}
@@ -92,7 +92,6 @@ pub(crate) fn buffer_input(
     keypress: Res<ButtonInput<KeyCode>>,
 ) {
     let tick = tick_manager.tick();
-    let mut input = Inputs::None;
     let mut direction = Direction {
         up: false,
         down: false,
@@ -111,16 +110,24 @@ pub(crate) fn buffer_input(
     if keypress.pressed(KeyCode::KeyD) || keypress.pressed(KeyCode::ArrowRight) {
         direction.right = true;
     }
+    // several inputs can be added for the same tick, for example to move and spawn at the same time
+    let mut has_input = false;
     if !direction.is_none() {
-        input = Inputs::Direction(direction);
+        input_manager.add_input(Inputs::Direction(direction), tick);
+        has_input = true;
     }
     if keypress.pressed(KeyCode::Backspace) {
-        input = Inputs::Delete;
+        input_manager.add_input(Inputs::Delete, tick);
+        has_input = true;
     }
     if keypress.pressed(KeyCode::Space) {
-        input = Inputs::Spawn;
+        input_manager.add_input(Inputs::Spawn, tick);
+        has_input = true;
+    }
+    if !has_input {
+        // we still send an input, to differentiate between "no input" and "missing input packet"
+        input_manager.add_input(Inputs::None, tick);
     }
-    input_manager.add_input(input, tick)
 }
 
 /// The client input only gets applied to predicted entities that we own
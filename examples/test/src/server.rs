@@ -69,9 +69,8 @@ pub(crate) fn handle_connections(
         // let message = Message1(5);
         // server_net
         //     .send_message_to_target::<Channel1, Message1>(&Message1(5), NetworkTarget::All)
-        //     .unwrap_or_else(|e| {
-        //         error!("Failed to send message: {:?}", e);
-        //     });
+        //     .inspect_err(|e| error!("Failed to send message: {:?}", e))
+        //     .ok();
 
         // for test: send a too big message , size > 300kb
         let message = VeryLargeMessage::generate(400000);
@@ -80,9 +79,8 @@ pub(crate) fn handle_connections(
                 &message,
                 NetworkTarget::All,
             )
-            .unwrap_or_else(|e| {
-                error!("Failed to send message: {:?}", e);
-            });
+            .inspect_err(|e| error!("Failed to send message: {:?}", e))
+            .ok();
         info!("Large message sent len:{} to server:  {:?}... ",message.data.len(), &message.data[..10]);
     }
 }
@@ -98,9 +96,8 @@ pub(crate) fn send_message(
         info!("[Server] Send message: {:?}", message);
         server
             .send_message_to_target::<Channel1, Message1>(&Message1(5), NetworkTarget::All)
-            .unwrap_or_else(|e| {
-                error!("Failed to send message: {:?}", e);
-            });
+            .inspect_err(|e| error!("Failed to send message: {:?}", e))
+            .ok();
     }
 }
 
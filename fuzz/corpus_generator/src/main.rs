@@ -0,0 +1,16 @@
+//! Write the seed corpus of each fuzzing target in `fuzz/corpus/<target>`, which is where `cargo fuzz run` looks for it
+use std::fs;
+use std::path::Path;
+
+fn main() -> std::io::Result<()> {
+    let corpus = Path::new(env!("CARGO_MANIFEST_DIR")).join("../corpus");
+    for (target, seeds) in lightyear::fuzz::seed_corpus() {
+        let dir = corpus.join(target);
+        fs::create_dir_all(&dir)?;
+        for (i, seed) in seeds.iter().enumerate() {
+            fs::write(dir.join(format!("seed_{i}")), seed)?;
+        }
+        println!("{} seeds written in {}", seeds.len(), dir.display());
+    }
+    Ok(())
+}
@@ -0,0 +1,13 @@
+#!/usr/bin/env sh
+# Fuzz one of the decode paths of lightyear: `fuzz/run.sh <target> [libfuzzer options]`
+#
+# The targets are `packet`, `channel_receiver`, `fragment_reassembly` and `netcode_server`.
+# Requires a nightly toolchain and cargo-fuzz (`cargo install cargo-fuzz`).
+# A panic, an allocation bigger than 64MB, a memory usage above 512MB or an input that takes more than
+# 5 seconds are reported as crashes, and the input is saved in `fuzz/artifacts/<target>`.
+set -e
+cd "$(dirname "$0")"
+target="$1"
+shift
+cargo run --quiet -p lightyear-fuzz-corpus-generator
+exec cargo +nightly fuzz run "$target" -- -rss_limit_mb=512 -malloc_limit_mb=64 -timeout=5 "$@"
@@ -124,7 +124,17 @@ pub struct ChannelSettings {
     /// Set to `Duration::default()` to send messages every frame if possible.
     pub send_frequency: Duration,
     /// Sets the priority of the channel. The final priority of a message will be `MessagePriority * ChannelPriority`
+    ///
+    /// When the bandwidth is capped, the priority of a channel accumulates every tick where the channel has
+    /// messages to send, and is reset once one of its messages is included in a packet.
+    /// A low priority channel therefore still gets to send when higher priority channels use all the bandwidth.
     pub priority: f32,
+    /// Limits of the send buffer of the channel.
+    ///
+    /// When the limit is reached, sending a message on a reliable channel fails with
+    /// [`PacketError::ChannelFull`](crate::packet::error::PacketError::ChannelFull), and sending a message on an
+    /// unreliable channel drops the oldest buffered messages.
+    pub buffer_limits: BufferLimits,
 }
 
 impl Default for ChannelSettings {
@@ -133,6 +143,70 @@ impl Default for ChannelSettings {
             mode: ChannelMode::UnorderedUnreliable,
             send_frequency: Duration::default(),
             priority: 1.0,
+            buffer_limits: BufferLimits::default(),
+        }
+    }
+}
+
+/// Limits of the send buffer of a channel. The channel is unbounded by default.
+///
+/// For reliable channels, the messages stay buffered until they are acked, so the buffer keeps growing if the
+/// remote stops acking.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct BufferLimits {
+    /// Maximum number of buffered messages.
+    /// On unreliable channels, each fragment of a fragmented message is counted separately
+    pub max_messages: Option<usize>,
+    /// Maximum number of bytes of the buffered messages
+    pub max_bytes: Option<usize>,
+}
+
+impl BufferLimits {
+    pub fn with_max_messages(mut self, max_messages: usize) -> Self {
+        self.max_messages = Some(max_messages);
+        self
+    }
+
+    pub fn with_max_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_bytes = Some(max_bytes);
+        self
+    }
+
+    /// Returns true if buffering a message of `message_bytes` bytes would exceed the limits
+    pub(crate) fn would_exceed(&self, occupancy: &ChannelOccupancy, message_bytes: usize) -> bool {
+        self.max_messages
+            .is_some_and(|max| occupancy.messages + 1 > max)
+            || self
+                .max_bytes
+                .is_some_and(|max| occupancy.bytes + message_bytes > max)
+    }
+}
+
+/// Current occupancy of the send buffer of a channel, compared to its [`BufferLimits`]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ChannelOccupancy {
+    /// Number of buffered messages.
+    /// For reliable channels, this includes the messages that were sent but not acked yet
+    pub messages: usize,
+    /// Number of bytes of the buffered messages
+    pub bytes: usize,
+    pub limits: BufferLimits,
+}
+
+impl ChannelOccupancy {
+    /// Fraction of the most constraining limit that is used, or None if the channel is unbounded
+    pub fn ratio(&self) -> Option<f32> {
+        let messages = self
+            .limits
+            .max_messages
+            .map(|max| self.messages as f32 / max.max(1) as f32);
+        let bytes = self
+            .limits
+            .max_bytes
+            .map(|max| self.bytes as f32 / max.max(1) as f32);
+        match (messages, bytes) {
+            (Some(messages), Some(bytes)) => Some(messages.max(bytes)),
+            (messages, bytes) => messages.or(bytes),
         }
     }
 }
@@ -197,6 +271,11 @@ pub struct ReliableSettings {
     pub rtt_resend_factor: f32,
     /// Minimum duration to wait before resending a packet if it has not been acked
     pub rtt_resend_min_delay: Duration,
+    /// Maximum number of messages that the receiver buffers for this channel.
+    ///
+    /// If set, the receiver advertises which messages it can accept, and the sender stops sending new
+    /// messages when that window is exhausted. See [`flow_control`](crate::channel::flow_control).
+    pub receive_window: Option<u16>,
 }
 
 impl Default for ReliableSettings {
@@ -204,6 +283,7 @@ impl Default for ReliableSettings {
         Self {
             rtt_resend_factor: 1.5,
             rtt_resend_min_delay: Duration::default(),
+            receive_window: None,
         }
     }
 }
@@ -215,6 +295,39 @@ impl ReliableSettings {
     }
 }
 
+/// Settings of a channel that can be changed for a single connection, for example to resend the reliable
+/// messages less aggressively to mobile clients.
+///
+/// Each field that is `None` keeps the value of the [`ChannelSettings`] of the protocol.
+/// The overrides only change how the messages are scheduled by the sender: the [`ChannelMode`] (and therefore the
+/// reliability and ordering of the channel) is the same for every connection.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ChannelSettingsOverride {
+    /// Replaces [`ReliableSettings::rtt_resend_factor`]. Ignored for unreliable channels
+    pub rtt_resend_factor: Option<f32>,
+    /// Replaces [`ReliableSettings::rtt_resend_min_delay`]. Ignored for unreliable channels
+    pub rtt_resend_min_delay: Option<Duration>,
+    /// Maximum number of reliable messages that were sent but not acked yet.
+    /// The new messages stay buffered until some of those messages are acked; retransmissions are never blocked.
+    /// Ignored for unreliable channels
+    pub max_in_flight: Option<u16>,
+    /// Replaces [`ChannelSettings::priority`]
+    pub priority: Option<f32>,
+}
+
+impl ChannelSettingsOverride {
+    /// Apply the overrides to the [`ReliableSettings`] of the protocol
+    pub(crate) fn reliable_settings(&self, settings: &ReliableSettings) -> ReliableSettings {
+        ReliableSettings {
+            rtt_resend_factor: self.rtt_resend_factor.unwrap_or(settings.rtt_resend_factor),
+            rtt_resend_min_delay: self
+                .rtt_resend_min_delay
+                .unwrap_or(settings.rtt_resend_min_delay),
+            receive_window: settings.receive_window,
+        }
+    }
+}
+
 /// Default channel to replicate entity actions.
 /// This is an Unordered Reliable channel.
 /// (SpawnEntity, DespawnEntity, InsertComponent, RemoveComponent)
@@ -236,6 +349,60 @@ pub struct PingChannel;
 #[derive(ChannelInternal)]
 pub struct PongChannel;
 
+/// Default channel used to advertise the receive windows of the flow-controlled reliable channels.
+/// This is a Sequenced Unreliable channel, because only the latest window matters.
+#[derive(ChannelInternal)]
+pub struct FlowControlChannel;
+
+/// Default channel used to establish and invalidate the aliases of replicated entities.
+/// This is an Unordered Reliable channel, because an alias is only used once its assignment has been acked.
+#[derive(ChannelInternal)]
+pub struct EntityAliasChannel;
+
 #[derive(ChannelInternal)]
 /// Default channel to send inputs from client to server. This is a Sequenced Unreliable channel.
 pub struct InputChannel;
+
+/// Default channel used by the server to resolve the actions of the clients.
+/// This is an Unordered Reliable channel, because every resolution must be received but they are independent.
+#[derive(ChannelInternal)]
+pub struct ActionResolutionChannel;
+
+/// Default channel used by the clients to send interest hints, and by the server to report their outcome.
+/// This is an Ordered Reliable channel, so that enabling then disabling a hint is processed in order.
+#[derive(ChannelInternal)]
+pub struct InterestHintChannel;
+
+/// Default channel used by the server to tell a client which replication messages contain the entities that existed
+/// when it connected. This is an Unordered Reliable channel.
+#[derive(ChannelInternal)]
+pub struct JoinSnapshotChannel;
+
+/// Default channel used by the server to send to a client the range of pre-spawned entity ids reserved for it.
+/// This is an Unordered Reliable channel.
+#[derive(ChannelInternal)]
+pub struct PreSpawnIdChannel;
+
+/// Default channel used by the server to send timing information to a client:
+/// - the advice to change its input delay. Every advice is a relative change, so they must all be received, in order
+/// - the server's wall-clock time (see [`NetworkTime`](crate::shared::network_time::NetworkTime)).
+///   Each message is an absolute anchor (a tick and its UNIX time), so it is still valid if it is delayed
+///
+/// This is an Ordered Reliable channel.
+#[derive(ChannelInternal)]
+pub struct ServerTimingChannel;
+
+/// Default channel used to cancel the transfers of fragmented messages (see [`transfer`](crate::channel::transfer)).
+/// This is an Unordered Reliable channel, because every cancellation must be received but they are independent.
+#[derive(ChannelInternal)]
+pub struct TransferControlChannel;
+
+/// Default channel used to send requests and their responses (see [`request`](crate::shared::request)).
+/// This is an Unordered Reliable channel, because every request must be received but they are independent.
+#[derive(ChannelInternal)]
+pub struct RequestChannel;
+
+/// Default channel used by the peers to exchange the versions of their schemas when a client connects
+/// (see [`versioned`](crate::serialize::versioned)). This is an Unordered Reliable channel.
+#[derive(ChannelInternal)]
+pub struct SchemaChannel;
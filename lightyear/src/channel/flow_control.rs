@@ -0,0 +1,87 @@
+//! Flow control for reliable channels.
+//!
+//! A reliable channel can set [`ReliableSettings::receive_window`](crate::channel::builder::ReliableSettings)
+//! to limit the number of messages that the receiver has to buffer (for example to protect the ordering buffers of
+//! a slow client from a fast server).
+//!
+//! The receiver periodically advertises, for each of those channels, the end of the range of message ids that it
+//! can accept; the sender does not send new messages beyond that point, and resumes once the window reopens.
+//! Retransmissions of messages that were already sent are never blocked.
+//!
+//! The advertisements are sent on the internal [`FlowControlChannel`](crate::channel::builder::FlowControlChannel),
+//! which is unreliable and bypasses the bandwidth quota, so that a window can always reopen.
+//! Until it receives an advertisement, the sender assumes that the window starts at the first message id.
+use bevy::utils::Duration;
+use byteorder::WriteBytesExt;
+
+use crate::packet::message::MessageId;
+use crate::protocol::channel::ChannelId;
+use crate::serialize::reader::Reader;
+use crate::serialize::{SerializationError, ToBytes};
+
+/// Interval at which the receive windows are advertised again even if they didn't change,
+/// in case the previous advertisement was lost
+pub(crate) const WINDOW_RESEND_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Flow control state of the sender of a reliable channel
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct FlowControlStats {
+    /// Number of new messages that can be sent before exhausting the window advertised by the remote peer
+    pub available_window: u16,
+    /// Number of messages that were sent but not acked yet
+    pub in_flight: u16,
+    /// Number of buffered messages that are waiting for the window to reopen
+    pub blocked: u16,
+}
+
+/// End (exclusive) of the receive window of each flow-controlled channel
+#[derive(Debug, Default, Clone, PartialEq)]
+pub(crate) struct WindowAdvertisement(pub(crate) Vec<(ChannelId, MessageId)>);
+
+impl ToBytes for WindowAdvertisement {
+    fn len(&self) -> usize {
+        self.0
+            .iter()
+            .map(|(channel_id, window_end)| channel_id.len() + window_end.len())
+            .sum()
+    }
+
+    fn to_bytes<T: WriteBytesExt>(&self, buffer: &mut T) -> Result<(), SerializationError> {
+        for (channel_id, window_end) in &self.0 {
+            channel_id.to_bytes(buffer)?;
+            window_end.to_bytes(buffer)?;
+        }
+        Ok(())
+    }
+
+    fn from_bytes(buffer: &mut Reader) -> Result<Self, SerializationError>
+    where
+        Self: Sized,
+    {
+        let mut windows = vec![];
+        while buffer.has_remaining() {
+            let channel_id = ChannelId::from_bytes(buffer)?;
+            let window_end = MessageId::from_bytes(buffer)?;
+            windows.push((channel_id, window_end));
+        }
+        Ok(Self(windows))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_advertisement_serialization() {
+        let advertisement = WindowAdvertisement(vec![(3, MessageId(10)), (200, MessageId(65535))]);
+        let mut writer = vec![];
+        advertisement.to_bytes(&mut writer).unwrap();
+        assert_eq!(writer.len(), advertisement.len());
+        let mut reader = Reader::from(writer);
+        assert_eq!(
+            WindowAdvertisement::from_bytes(&mut reader).unwrap(),
+            advertisement
+        );
+    }
+}
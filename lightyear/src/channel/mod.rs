@@ -1,8 +1,9 @@
 /*! Channels are used to add reliability/ordering on top of the transport layer
 */
 pub mod builder;
+pub mod flow_control;
 pub(crate) mod receivers;
 pub(crate) mod senders;
 
-#[cfg(feature = "trace")]
 pub mod stats;
+pub mod transfer;
@@ -1,35 +1,108 @@
 use std::collections::HashMap;
 
 use bytes::Bytes;
-use tracing::trace;
+use tracing::{debug, trace};
 
+use crate::channel::transfer::{
+    TransferDirection, TransferHandle, TransferOutcome, TransferProgress,
+};
 use crate::packet::message::{FragmentData, MessageId};
 use crate::packet::packet::FRAGMENT_SIZE;
 use crate::prelude::Tick;
+use crate::protocol::channel::ChannelKind;
 use crate::shared::time_manager::WrappedTime;
 
+/// Duration during which the fragments of a cancelled message are discarded, since some of them
+/// might still be in flight when the cancellation is received
+const DISCARD_CANCELLED_AFTER: chrono::Duration = chrono::Duration::milliseconds(3000);
+
 /// `FragmentReceiver` is used to reconstruct fragmented messages
 #[derive(Debug)]
 pub struct FragmentReceiver {
     fragment_messages: HashMap<MessageId, FragmentConstructor>,
+    /// Messages that were cancelled, with the time of the cancellation
+    cancelled: HashMap<MessageId, WrappedTime>,
+    /// Messages that were completed, cancelled or discarded since the last call to `take_finished`
+    finished: Vec<(MessageId, TransferOutcome)>,
 }
 
 impl FragmentReceiver {
     pub fn new() -> Self {
         Self {
             fragment_messages: HashMap::new(),
+            cancelled: HashMap::new(),
+            finished: Vec::new(),
+        }
+    }
+
+    /// Progress of the messages that are being reconstructed
+    pub(crate) fn transfers(
+        &self,
+        channel: ChannelKind,
+        current_time: WrappedTime,
+    ) -> impl Iterator<Item = TransferProgress> + '_ {
+        self.fragment_messages.iter().map(move |(message_id, c)| {
+            TransferProgress::new(
+                TransferHandle::new(channel, *message_id, TransferDirection::Receive),
+                c.num_received_fragments,
+                c.num_fragments,
+                c.first_received,
+                current_time,
+            )
+        })
+    }
+
+    /// Number of messages that are being reconstructed
+    pub(crate) fn num_transfers(&self) -> usize {
+        self.fragment_messages.len()
+    }
+
+    /// Take the messages that were completed, cancelled or discarded since the last call
+    pub(crate) fn take_finished(&mut self) -> Vec<(MessageId, TransferOutcome)> {
+        std::mem::take(&mut self.finished)
+    }
+
+    /// Discard the fragments received for a message that was cancelled, and the fragments of this message
+    /// that will be received in the next few seconds
+    pub(crate) fn cancel(&mut self, message_id: MessageId, current_time: WrappedTime) {
+        self.cancelled
+            .retain(|_, t| *t > current_time - DISCARD_CANCELLED_AFTER);
+        self.cancelled.insert(message_id, current_time);
+        if self.fragment_messages.remove(&message_id).is_some() {
+            self.finished.push((message_id, TransferOutcome::Cancelled));
         }
     }
 
+    /// Discard the messages older than `message_id` that are being reconstructed.
+    ///
+    /// Used by the sequenced receivers once a more recent message was reconstructed, since the older
+    /// messages would be ignored anyway.
+    pub(crate) fn discard_older_than(&mut self, message_id: MessageId) {
+        let finished = &mut self.finished;
+        self.fragment_messages.retain(|id, _| {
+            let keep = *id >= message_id;
+            if !keep {
+                finished.push((*id, TransferOutcome::Superseded));
+            }
+            keep
+        })
+    }
+
     /// Discard all messages for which the latest fragment was received before the cleanup time
     /// (i.e. we probably lost some fragments and we will never complete the message)
     ///
     /// If we don't keep track of the last received time, we will never clean up the messages.
     pub fn cleanup(&mut self, cleanup_time: WrappedTime) {
-        self.fragment_messages.retain(|_, c| {
-            c.last_received
+        let finished = &mut self.finished;
+        self.fragment_messages.retain(|message_id, c| {
+            let keep = c
+                .last_received
                 .map(|t| t > cleanup_time)
-                .unwrap_or_else(|| true)
+                .unwrap_or_else(|| true);
+            if !keep {
+                finished.push((*message_id, TransferOutcome::TimedOut));
+            }
+            keep
         })
     }
 
@@ -43,20 +116,42 @@ impl FragmentReceiver {
         remote_sent_tick: Tick,
         current_time: Option<WrappedTime>,
     ) -> Option<(Tick, Bytes)> {
+        // the fragment comes from the network: discard it if it could not have been built by a FragmentSender
+        let num_fragments = fragment.num_fragments as usize;
+        let fragment_id = fragment.fragment_id as usize;
+        let is_last_fragment = fragment_id + 1 == num_fragments;
+        if fragment_id >= num_fragments
+            || fragment.bytes.len() > FRAGMENT_SIZE
+            || (!is_last_fragment && fragment.bytes.len() != FRAGMENT_SIZE)
+        {
+            debug!(?fragment.message_id, fragment_id, num_fragments, len = fragment.bytes.len(), "Discarding invalid fragment");
+            return None;
+        }
+        if self.cancelled.contains_key(&fragment.message_id) {
+            trace!(?fragment.message_id, "Discarding fragment of a cancelled message");
+            return None;
+        }
         let fragment_message = self
             .fragment_messages
             .entry(fragment.message_id)
-            .or_insert_with(|| {
-                FragmentConstructor::new(remote_sent_tick, fragment.num_fragments as usize)
-            });
+            .or_insert_with(|| FragmentConstructor::new(remote_sent_tick, num_fragments));
+        if fragment_message.num_fragments != num_fragments {
+            debug!(
+                ?fragment.message_id,
+                num_fragments,
+                expected = fragment_message.num_fragments,
+                "Discarding fragment with an inconsistent number of fragments"
+            );
+            return None;
+        }
 
         // completed the fragmented message!
-        if let Some(payload) = fragment_message.receive_fragment(
-            fragment.fragment_id as usize,
-            fragment.bytes.as_ref(),
-            current_time,
-        ) {
+        if let Some(payload) =
+            fragment_message.receive_fragment(fragment_id, fragment.bytes.as_ref(), current_time)
+        {
             self.fragment_messages.remove(&fragment.message_id);
+            self.finished
+                .push((fragment.message_id, TransferOutcome::Completed));
             return Some(payload);
         }
 
@@ -74,6 +169,7 @@ pub struct FragmentConstructor {
     bytes: Vec<u8>,
 
     tick: Tick,
+    first_received: Option<WrappedTime>,
     last_received: Option<WrappedTime>,
 }
 
@@ -83,35 +179,40 @@ impl FragmentConstructor {
             num_fragments,
             num_received_fragments: 0,
             received: vec![false; num_fragments],
-            bytes: vec![0; num_fragments * FRAGMENT_SIZE],
+            // the buffer grows as the fragments are received, so that a peer cannot make us
+            // allocate the maximum message size with a single fragment
+            bytes: Vec::new(),
             tick,
+            first_received: None,
             last_received: None,
         }
     }
 
+    /// Add a fragment to the message.
+    ///
+    /// The caller must check that `fragment_index < num_fragments`, and that the fragment is not
+    /// longer than [`FRAGMENT_SIZE`].
     pub fn receive_fragment(
         &mut self,
         fragment_index: usize,
         bytes: &[u8],
         received_time: Option<WrappedTime>,
     ) -> Option<(Tick, Bytes)> {
+        self.first_received = self.first_received.or(received_time);
         self.last_received = received_time;
 
         let is_last_fragment = fragment_index == self.num_fragments - 1;
 
-        // TODO: check sizes?
-
         if !self.received[fragment_index] {
             self.received[fragment_index] = true;
             self.num_received_fragments += 1;
 
-            if is_last_fragment {
-                let len = (self.num_fragments - 1) * FRAGMENT_SIZE + bytes.len();
-                self.bytes.resize(len, 0);
-            }
-
             let start = fragment_index * FRAGMENT_SIZE;
             let end = start + bytes.len();
+            // the last fragment determines the length of the message
+            if is_last_fragment || self.bytes.len() < end {
+                self.bytes.resize(end, 0);
+            }
             self.bytes[start..end].copy_from_slice(bytes);
         }
 
@@ -128,6 +229,7 @@ impl FragmentConstructor {
 #[cfg(test)]
 mod tests {
     use crate::channel::senders::fragment_sender::FragmentSender;
+    use crate::tests::protocol::Channel3;
 
     use super::*;
 
@@ -149,4 +251,113 @@ mod tests {
             Some((Tick(0), message_bytes.clone()))
         );
     }
+
+    /// Fragments that could not have been built by a [`FragmentSender`] are discarded instead of panicking
+    #[test]
+    fn test_receive_invalid_fragments() {
+        let mut receiver = FragmentReceiver::new();
+        let fragment = |fragment_id, num_fragments, len| FragmentData {
+            message_id: MessageId(0),
+            fragment_id,
+            num_fragments,
+            bytes: Bytes::from(vec![1u8; len]),
+        };
+        // no fragments
+        assert_eq!(
+            receiver.receive_fragment(fragment(0, 0, 10), Tick(0), None),
+            None
+        );
+        // fragment index out of bounds
+        assert_eq!(
+            receiver.receive_fragment(fragment(2, 2, 10), Tick(0), None),
+            None
+        );
+        // fragment too big
+        assert_eq!(
+            receiver.receive_fragment(fragment(1, 2, FRAGMENT_SIZE + 1), Tick(0), None),
+            None
+        );
+        // only the last fragment can be shorter than FRAGMENT_SIZE
+        assert_eq!(
+            receiver.receive_fragment(fragment(0, 2, 10), Tick(0), None),
+            None
+        );
+        assert!(receiver.fragment_messages.is_empty());
+
+        // the number of fragments must match the first fragment received
+        assert_eq!(
+            receiver.receive_fragment(fragment(0, 3, FRAGMENT_SIZE), Tick(0), None),
+            None
+        );
+        assert_eq!(
+            receiver.receive_fragment(fragment(1, 2, 10), Tick(0), None),
+            None
+        );
+        assert_eq!(
+            receiver.receive_fragment(fragment(2, 3, 10), Tick(0), None),
+            None
+        );
+        assert_eq!(
+            receiver.receive_fragment(fragment(1, 3, FRAGMENT_SIZE), Tick(0), None),
+            Some((Tick(0), Bytes::from(vec![1u8; 2 * FRAGMENT_SIZE + 10])))
+        );
+    }
+
+    /// A cancelled message discards its fragments, including the ones received after the cancellation
+    #[test]
+    fn test_cancel() {
+        let mut receiver = FragmentReceiver::new();
+        let message_bytes = Bytes::from(vec![1u8; FRAGMENT_SIZE * 3]);
+        let fragments = FragmentSender::new()
+            .build_fragments(MessageId(0), None, message_bytes.clone())
+            .unwrap();
+        let time = WrappedTime::new(1000);
+        assert_eq!(
+            receiver.receive_fragment(fragments[0].clone(), Tick(0), Some(time)),
+            None
+        );
+        assert_eq!(receiver.num_transfers(), 1);
+        let progress: Vec<_> = receiver
+            .transfers(ChannelKind::of::<Channel3>(), time)
+            .collect();
+        assert_eq!(progress[0].completed_fragments, 1);
+        assert_eq!(progress[0].num_fragments, 3);
+
+        receiver.cancel(MessageId(0), time);
+        assert_eq!(receiver.num_transfers(), 0);
+        assert_eq!(
+            receiver.take_finished(),
+            vec![(MessageId(0), TransferOutcome::Cancelled)]
+        );
+        for fragment in &fragments[1..] {
+            assert_eq!(
+                receiver.receive_fragment(fragment.clone(), Tick(0), Some(time)),
+                None
+            );
+        }
+        assert_eq!(receiver.num_transfers(), 0);
+
+        // the other messages are still received, or time out
+        let fragments = FragmentSender::new()
+            .build_fragments(MessageId(1), None, message_bytes.clone())
+            .unwrap();
+        receiver.receive_fragment(fragments[0].clone(), Tick(0), Some(time));
+        receiver.receive_fragment(fragments[1].clone(), Tick(0), Some(time));
+        assert_eq!(
+            receiver.receive_fragment(fragments[2].clone(), Tick(0), Some(time)),
+            Some((Tick(0), message_bytes.clone()))
+        );
+        let fragments = FragmentSender::new()
+            .build_fragments(MessageId(2), None, message_bytes)
+            .unwrap();
+        receiver.receive_fragment(fragments[0].clone(), Tick(0), Some(time));
+        receiver.cleanup(WrappedTime::new(2000));
+        assert_eq!(
+            receiver.take_finished(),
+            vec![
+                (MessageId(1), TransferOutcome::Completed),
+                (MessageId(2), TransferOutcome::TimedOut)
+            ]
+        );
+    }
 }
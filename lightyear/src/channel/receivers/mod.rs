@@ -2,11 +2,12 @@
 use bytes::Bytes;
 use enum_dispatch::enum_dispatch;
 
-use crate::packet::message::ReceiveMessage;
+use crate::packet::message::{MessageId, ReceiveMessage};
 use crate::prelude::Tick;
 use crate::shared::tick_manager::TickManager;
-use crate::shared::time_manager::TimeManager;
+use crate::shared::time_manager::{TimeManager, WrappedTime};
 use error::Result;
+use fragment_receiver::FragmentReceiver;
 
 /// Utilities to receive a Message from multiple fragment packets
 pub(crate) mod fragment_receiver;
@@ -38,6 +39,28 @@ pub trait ChannelReceive {
 
     /// Reads a message from the internal buffer to get its content
     fn read_message(&mut self) -> Option<(Tick, Bytes)>;
+
+    /// Oldest message id that the receiver might still have to buffer.
+    /// The receive window advertised to the sender starts at this message id.
+    ///
+    /// Returns None if the receiver doesn't support flow control.
+    fn window_start(&self) -> Option<MessageId> {
+        None
+    }
+
+    /// Reconstructs the fragmented messages received on the channel
+    fn fragment_receiver(&self) -> &FragmentReceiver;
+
+    fn fragment_receiver_mut(&mut self) -> &mut FragmentReceiver;
+
+    /// Discard a fragmented message that was cancelled.
+    ///
+    /// The receivers that deliver the messages in order must skip the message, so that the next messages
+    /// of the channel can be delivered.
+    fn cancel_message(&mut self, message_id: MessageId, current_time: WrappedTime) {
+        self.fragment_receiver_mut()
+            .cancel(message_id, current_time);
+    }
 }
 
 /// This enum contains the various types of receivers available
@@ -1,4 +1,4 @@
-use std::collections::{btree_map, BTreeMap};
+use std::collections::{btree_map, BTreeMap, HashSet};
 
 use bytes::Bytes;
 
@@ -9,6 +9,7 @@ use crate::packet::message::{MessageData, MessageId, ReceiveMessage};
 use crate::prelude::Tick;
 pub use crate::shared::tick_manager::TickManager;
 pub use crate::shared::time_manager::TimeManager;
+use crate::shared::time_manager::WrappedTime;
 
 /// Ordered Reliable receiver: make sure that all messages are received,
 /// and return them in order
@@ -21,6 +22,9 @@ pub struct OrderedReliableReceiver {
     /// Buffer of the messages that we received, but haven't processed yet
     recv_message_buffer: BTreeMap<MessageId, (Tick, Bytes)>,
     fragment_receiver: FragmentReceiver,
+    /// Messages that were cancelled by the sender, and that are skipped when their turn comes
+    cancelled_message_ids: HashSet<MessageId>,
+    current_time: WrappedTime,
 }
 
 impl OrderedReliableReceiver {
@@ -29,12 +33,16 @@ impl OrderedReliableReceiver {
             pending_recv_message_id: MessageId(0),
             recv_message_buffer: BTreeMap::new(),
             fragment_receiver: FragmentReceiver::new(),
+            cancelled_message_ids: HashSet::new(),
+            current_time: WrappedTime::default(),
         }
     }
 }
 
 impl ChannelReceive for OrderedReliableReceiver {
-    fn update(&mut self, _: &TimeManager, _: &TickManager) {}
+    fn update(&mut self, time_manager: &TimeManager, _: &TickManager) {
+        self.current_time = time_manager.current_time();
+    }
 
     /// Queues a received message in an internal buffer
     fn buffer_recv(&mut self, message: ReceiveMessage) -> Result<()> {
@@ -58,7 +66,7 @@ impl ChannelReceive for OrderedReliableReceiver {
                     if let Some(res) = self.fragment_receiver.receive_fragment(
                         fragment,
                         message.remote_sent_tick,
-                        None,
+                        Some(self.current_time),
                     ) {
                         entry.insert(res);
                     }
@@ -73,6 +81,13 @@ impl ChannelReceive for OrderedReliableReceiver {
     /// until we have received the message we are waiting for (the next expected MessageId)
     /// This assumes that the sender sends all message ids sequentially.
     fn read_message(&mut self) -> Option<(Tick, Bytes)> {
+        // the cancelled messages will never be received
+        while self
+            .cancelled_message_ids
+            .remove(&self.pending_recv_message_id)
+        {
+            self.pending_recv_message_id += 1;
+        }
         // Check if we have received the message we are waiting for
         let message = self
             .recv_message_buffer
@@ -83,6 +98,29 @@ impl ChannelReceive for OrderedReliableReceiver {
         self.pending_recv_message_id += 1;
         Some(message)
     }
+
+    fn window_start(&self) -> Option<MessageId> {
+        Some(self.pending_recv_message_id)
+    }
+
+    fn fragment_receiver(&self) -> &FragmentReceiver {
+        &self.fragment_receiver
+    }
+
+    fn fragment_receiver_mut(&mut self) -> &mut FragmentReceiver {
+        &mut self.fragment_receiver
+    }
+
+    fn cancel_message(&mut self, message_id: MessageId, current_time: WrappedTime) {
+        self.fragment_receiver.cancel(message_id, current_time);
+        // the message was already delivered, or it was completed before the cancellation arrived
+        if message_id < self.pending_recv_message_id
+            || self.recv_message_buffer.contains_key(&message_id)
+        {
+            return;
+        }
+        self.cancelled_message_ids.insert(message_id);
+    }
 }
 
 #[cfg(test)]
@@ -93,6 +131,7 @@ mod tests {
     use crate::channel::receivers::ChannelReceive;
     use crate::packet::message::{MessageId, ReceiveMessage, SingleData};
     use crate::prelude::{PacketError, Tick};
+    use crate::shared::time_manager::WrappedTime;
 
     #[test]
     fn test_ordered_reliable_receiver_internals() -> Result<(), PacketError> {
@@ -143,4 +182,35 @@ mod tests {
         );
         Ok(())
     }
+
+    /// The cancelled messages are skipped, so that the next messages are delivered
+    #[test]
+    fn test_cancel_message() -> Result<(), PacketError> {
+        let mut receiver = OrderedReliableReceiver::new();
+        let mut single = SingleData::new(None, Bytes::from("hello"));
+        single.id = Some(MessageId(1));
+        receiver.buffer_recv(ReceiveMessage {
+            data: single.clone().into(),
+            remote_sent_tick: Tick(1),
+        })?;
+        assert_eq!(receiver.read_message(), None);
+
+        // message 0 is cancelled before we received all its fragments
+        receiver.cancel_message(MessageId(0), WrappedTime::new(0));
+        assert_eq!(
+            receiver.read_message(),
+            Some((Tick(1), single.bytes.clone()))
+        );
+        assert_eq!(receiver.pending_recv_message_id, MessageId(2));
+
+        // cancelling a message that was already delivered has no effect
+        receiver.cancel_message(MessageId(1), WrappedTime::new(0));
+        single.id = Some(MessageId(2));
+        receiver.buffer_recv(ReceiveMessage {
+            data: single.clone().into(),
+            remote_sent_tick: Tick(2),
+        })?;
+        assert_eq!(receiver.read_message(), Some((Tick(2), single.bytes)));
+        Ok(())
+    }
 }
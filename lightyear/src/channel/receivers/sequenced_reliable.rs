@@ -1,4 +1,4 @@
-use std::collections::{btree_map, BTreeMap};
+use std::collections::BTreeMap;
 
 use bytes::Bytes;
 
@@ -9,33 +9,39 @@ use crate::channel::receivers::ChannelReceive;
 use crate::packet::message::{MessageData, MessageId, ReceiveMessage};
 use crate::prelude::Tick;
 use crate::shared::tick_manager::TickManager;
-use crate::shared::time_manager::TimeManager;
+use crate::shared::time_manager::{TimeManager, WrappedTime};
 
 /// Sequenced Reliable receiver: make sure that all messages are received,
-/// do not return them in order, but ignore the messages that are older than the most recent one received
+/// do not return them in order, but ignore the messages that are older than the most recent one received.
+///
+/// A fragmented message only counts as received once all its fragments were reassembled.
 #[derive(Debug)]
 pub struct SequencedReliableReceiver {
     // TODO: optimize via ring buffer?
     // TODO: actually do we even need a buffer? we might just need a buffer of 1
     /// Buffer of the messages that we received, but haven't processed yet
     recv_message_buffer: BTreeMap<MessageId, (Tick, Bytes)>,
-    /// Highest message id received so far
-    most_recent_message_id: MessageId,
+    /// Id following the most recent message received so far; the older messages are ignored
+    next_message_id: MessageId,
     fragment_receiver: FragmentReceiver,
+    current_time: WrappedTime,
 }
 
 impl SequencedReliableReceiver {
     pub fn new() -> Self {
         Self {
             recv_message_buffer: BTreeMap::new(),
-            most_recent_message_id: MessageId(0),
+            next_message_id: MessageId(0),
             fragment_receiver: FragmentReceiver::new(),
+            current_time: WrappedTime::default(),
         }
     }
 }
 
 impl ChannelReceive for SequencedReliableReceiver {
-    fn update(&mut self, _: &TimeManager, _: &TickManager) {}
+    fn update(&mut self, time_manager: &TimeManager, _: &TickManager) {
+        self.current_time = time_manager.current_time();
+    }
 
     /// Queues a received message in an internal buffer
     fn buffer_recv(&mut self, message: ReceiveMessage) -> Result<()> {
@@ -44,43 +50,47 @@ impl ChannelReceive for SequencedReliableReceiver {
             .message_id()
             .ok_or(ChannelReceiveError::MissingMessageId)?;
 
-        // if the message is too old, ignore it
-        if message_id < self.most_recent_message_id {
+        // if the message is too old (or was already received), ignore it
+        if message_id < self.next_message_id {
             return Ok(());
         }
 
-        // update the most recent message id
-        if message_id > self.most_recent_message_id {
-            self.most_recent_message_id = message_id;
-        }
-
-        // add the message to the buffer
-        if let btree_map::Entry::Vacant(entry) = self.recv_message_buffer.entry(message_id) {
-            match message.data {
-                MessageData::Single(single) => {
-                    entry.insert((message.remote_sent_tick, single.bytes));
-                }
-                MessageData::Fragment(fragment) => {
-                    if let Some(res) = self.fragment_receiver.receive_fragment(
-                        fragment,
-                        message.remote_sent_tick,
-                        None,
-                    ) {
-                        entry.insert(res);
-                    }
-                }
-            }
+        let received = match message.data {
+            MessageData::Single(single) => Some((message.remote_sent_tick, single.bytes)),
+            MessageData::Fragment(fragment) => self.fragment_receiver.receive_fragment(
+                fragment,
+                message.remote_sent_tick,
+                Some(self.current_time),
+            ),
+        };
+        // the message is only the most recent one once it is complete, so that the older messages
+        // are still received while its fragments are in flight
+        if let Some(received) = received {
+            self.next_message_id = message_id + 1;
+            // the older messages that were not read yet are superseded
+            self.recv_message_buffer.clear();
+            self.recv_message_buffer.insert(message_id, received);
+            self.fragment_receiver
+                .discard_older_than(self.next_message_id);
         }
         Ok(())
     }
     fn read_message(&mut self) -> Option<(Tick, Bytes)> {
-        // keep popping messages until we get one that is more recent than the last one we processed
-        loop {
-            let (message_id, message) = self.recv_message_buffer.pop_first()?;
-            if message_id >= self.most_recent_message_id {
-                return Some(message);
-            }
-        }
+        self.recv_message_buffer
+            .pop_first()
+            .map(|(_, message)| message)
+    }
+
+    fn window_start(&self) -> Option<MessageId> {
+        Some(self.next_message_id)
+    }
+
+    fn fragment_receiver(&self) -> &FragmentReceiver {
+        &self.fragment_receiver
+    }
+
+    fn fragment_receiver_mut(&mut self) -> &mut FragmentReceiver {
+        &mut self.fragment_receiver
     }
 }
 
@@ -89,7 +99,10 @@ mod tests {
     use bytes::Bytes;
 
     use crate::channel::receivers::ChannelReceive;
+    use crate::channel::senders::fragment_sender::FragmentSender;
+    use crate::channel::transfer::TransferOutcome;
     use crate::packet::message::SingleData;
+    use crate::packet::packet::FRAGMENT_SIZE;
 
     use super::*;
 
@@ -124,7 +137,7 @@ mod tests {
             receiver.read_message(),
             Some((Tick(2), single2.bytes.clone()))
         );
-        assert_eq!(receiver.most_recent_message_id, MessageId(1));
+        assert_eq!(receiver.next_message_id, MessageId(2));
 
         // receive message 0:
         // we don't care about receiving message 0 anymore, since we already have received a more recent message
@@ -138,4 +151,63 @@ mod tests {
         assert_eq!(receiver.read_message(), None);
         Ok(())
     }
+
+    /// A fragmented message only becomes the most recent message once it is reassembled, and the fragments
+    /// of an older message are discarded when a more recent message is received
+    #[test]
+    fn test_fragmented_messages() -> Result<()> {
+        let mut receiver = SequencedReliableReceiver::new();
+        let big_bytes = Bytes::from(vec![1u8; 2 * FRAGMENT_SIZE + 10]);
+        let fragments = |message_id| {
+            FragmentSender::new()
+                .build_fragments(message_id, None, big_bytes.clone())
+                .unwrap()
+        };
+        let receive = |receiver: &mut SequencedReliableReceiver, data: MessageData| {
+            receiver.buffer_recv(ReceiveMessage {
+                data,
+                remote_sent_tick: Tick(1),
+            })
+        };
+
+        // the first fragment of message 1 is received before message 0: message 0 is still received
+        let fragments_1 = fragments(MessageId(1));
+        receive(&mut receiver, fragments_1[0].clone().into())?;
+        let mut single = SingleData::new(Some(MessageId(0)), Bytes::from("hello"));
+        receive(&mut receiver, single.clone().into())?;
+        assert_eq!(
+            receiver.read_message(),
+            Some((Tick(1), single.bytes.clone()))
+        );
+
+        // message 1 is reassembled
+        receive(&mut receiver, fragments_1[1].clone().into())?;
+        assert_eq!(receiver.read_message(), None);
+        receive(&mut receiver, fragments_1[2].clone().into())?;
+        assert_eq!(receiver.read_message(), Some((Tick(1), big_bytes.clone())));
+
+        // a fragment that is resent because its ack was lost does not start a new message
+        receive(&mut receiver, fragments_1[2].clone().into())?;
+        assert_eq!(receiver.fragment_receiver.num_transfers(), 0);
+        assert_eq!(receiver.read_message(), None);
+
+        // message 3 is received while message 2 is being reassembled: message 2 is discarded
+        let fragments_2 = fragments(MessageId(2));
+        receive(&mut receiver, fragments_2[0].clone().into())?;
+        single.id = Some(MessageId(3));
+        receive(&mut receiver, single.clone().into())?;
+        assert_eq!(receiver.fragment_receiver.num_transfers(), 0);
+        assert_eq!(
+            receiver.fragment_receiver.take_finished().last(),
+            Some(&(MessageId(2), TransferOutcome::Superseded))
+        );
+        receive(&mut receiver, fragments_2[1].clone().into())?;
+        receive(&mut receiver, fragments_2[2].clone().into())?;
+        assert_eq!(
+            receiver.read_message(),
+            Some((Tick(1), single.bytes.clone()))
+        );
+        assert_eq!(receiver.read_message(), None);
+        Ok(())
+    }
 }
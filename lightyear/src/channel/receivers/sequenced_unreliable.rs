@@ -14,12 +14,14 @@ use crate::shared::time_manager::{TimeManager, WrappedTime};
 const DISCARD_AFTER: chrono::Duration = chrono::Duration::milliseconds(3000);
 
 /// Sequenced Unreliable receiver:
-/// do not return messages in order, but ignore the messages that are older than the most recent one received
+/// do not return messages in order, but ignore the messages that are older than the most recent one received.
+///
+/// A fragmented message only counts as received once all its fragments were reassembled.
 #[derive(Debug)]
 pub struct SequencedUnreliableReceiver {
     /// Buffer of the messages that we received, but haven't processed yet
     recv_message_buffer: VecDeque<(Tick, Bytes)>,
-    /// Highest message id received so far
+    /// Id of the most recent message received so far
     most_recent_message_id: MessageId,
     fragment_receiver: FragmentReceiver,
     current_time: WrappedTime,
@@ -56,25 +58,20 @@ impl ChannelReceive for SequencedUnreliableReceiver {
             return Ok(());
         }
 
-        // update the most recent message id
-        if message_id > self.most_recent_message_id {
+        let received = match message.data {
+            MessageData::Single(single) => Some((message.remote_sent_tick, single.bytes)),
+            MessageData::Fragment(fragment) => self.fragment_receiver.receive_fragment(
+                fragment,
+                message.remote_sent_tick,
+                Some(self.current_time),
+            ),
+        };
+        // a fragmented message becomes the most recent one when it is reassembled, not when its first
+        // fragment is received, otherwise a lost fragment would also discard the older messages
+        if let Some(received) = received {
             self.most_recent_message_id = message_id;
-        }
-
-        // add the message to the buffer
-        match message.data {
-            MessageData::Single(single) => self
-                .recv_message_buffer
-                .push_back((message.remote_sent_tick, single.bytes)),
-            MessageData::Fragment(fragment) => {
-                if let Some(res) = self.fragment_receiver.receive_fragment(
-                    fragment,
-                    message.remote_sent_tick,
-                    Some(self.current_time),
-                ) {
-                    self.recv_message_buffer.push_back(res);
-                }
-            }
+            self.recv_message_buffer.push_back(received);
+            self.fragment_receiver.discard_older_than(message_id);
         }
         Ok(())
     }
@@ -82,6 +79,14 @@ impl ChannelReceive for SequencedUnreliableReceiver {
         self.recv_message_buffer.pop_front()
         // TODO: naia does a more optimized version by return a Vec<Message> instead of Option<Message>
     }
+
+    fn fragment_receiver(&self) -> &FragmentReceiver {
+        &self.fragment_receiver
+    }
+
+    fn fragment_receiver_mut(&mut self) -> &mut FragmentReceiver {
+        &mut self.fragment_receiver
+    }
 }
 
 #[cfg(test)]
@@ -90,7 +95,9 @@ mod tests {
 
     use crate::channel::receivers::sequenced_unreliable::SequencedUnreliableReceiver;
     use crate::channel::receivers::ChannelReceive;
+    use crate::channel::senders::fragment_sender::FragmentSender;
     use crate::packet::message::{MessageId, ReceiveMessage, SingleData};
+    use crate::packet::packet::FRAGMENT_SIZE;
     use crate::prelude::{PacketError, Tick};
 
     #[test]
@@ -152,4 +159,50 @@ mod tests {
         assert_eq!(receiver.recv_message_buffer.len(), 0);
         Ok(())
     }
+
+    /// A fragmented message only becomes the most recent message once it is reassembled, so losing one of its
+    /// fragments does not discard the older messages
+    #[test]
+    fn test_fragmented_messages() -> Result<(), PacketError> {
+        let mut receiver = SequencedUnreliableReceiver::new();
+        let big_bytes = Bytes::from(vec![1u8; 2 * FRAGMENT_SIZE + 10]);
+        let fragments = FragmentSender::new()
+            .build_fragments(MessageId(2), None, big_bytes.clone())
+            .unwrap();
+        let mut single = SingleData::new(Some(MessageId(1)), Bytes::from("hello"));
+
+        // the fragments of message 2 are received before message 1, but one of them is lost
+        for fragment in &fragments[..2] {
+            receiver.buffer_recv(ReceiveMessage {
+                data: fragment.clone().into(),
+                remote_sent_tick: Tick(1),
+            })?;
+        }
+        receiver.buffer_recv(ReceiveMessage {
+            data: single.clone().into(),
+            remote_sent_tick: Tick(1),
+        })?;
+        assert_eq!(
+            receiver.read_message(),
+            Some((Tick(1), single.bytes.clone()))
+        );
+
+        // message 3 is received: message 2 will never be received and is discarded
+        single.id = Some(MessageId(3));
+        receiver.buffer_recv(ReceiveMessage {
+            data: single.clone().into(),
+            remote_sent_tick: Tick(2),
+        })?;
+        assert_eq!(receiver.fragment_receiver.num_transfers(), 0);
+        receiver.buffer_recv(ReceiveMessage {
+            data: fragments[2].clone().into(),
+            remote_sent_tick: Tick(1),
+        })?;
+        assert_eq!(
+            receiver.read_message(),
+            Some((Tick(2), single.bytes.clone()))
+        );
+        assert_eq!(receiver.read_message(), None);
+        Ok(())
+    }
 }
@@ -9,7 +9,7 @@ use crate::channel::receivers::ChannelReceive;
 use crate::packet::message::{MessageData, MessageId, ReceiveMessage};
 use crate::prelude::Tick;
 use crate::shared::tick_manager::TickManager;
-use crate::shared::time_manager::TimeManager;
+use crate::shared::time_manager::{TimeManager, WrappedTime};
 
 /// Unordered Reliable receiver: make sure that all messages are received,
 /// and return them in any order
@@ -25,6 +25,7 @@ pub struct UnorderedReliableReceiver {
     fragment_receiver: FragmentReceiver,
     /// Keep tracking of the message ids we have received, so we can update the oldest_pending_message_id
     received_message_ids: HashSet<MessageId>,
+    current_time: WrappedTime,
 }
 
 impl UnorderedReliableReceiver {
@@ -34,12 +35,27 @@ impl UnorderedReliableReceiver {
             recv_message_buffer: BTreeMap::new(),
             fragment_receiver: FragmentReceiver::new(),
             received_message_ids: HashSet::new(),
+            current_time: WrappedTime::default(),
+        }
+    }
+
+    /// Update the pending message id (skip through all message ids we have already received out of order)
+    fn skip_received_message_ids(&mut self) {
+        while self
+            .received_message_ids
+            .contains(&self.pending_recv_message_id)
+        {
+            self.received_message_ids
+                .remove(&self.pending_recv_message_id);
+            self.pending_recv_message_id += 1;
         }
     }
 }
 
 impl ChannelReceive for UnorderedReliableReceiver {
-    fn update(&mut self, _: &TimeManager, _: &TickManager) {}
+    fn update(&mut self, time_manager: &TimeManager, _: &TickManager) {
+        self.current_time = time_manager.current_time();
+    }
 
     /// Queues a received message in an internal buffer
     fn buffer_recv(&mut self, message: ReceiveMessage) -> Result<(), ChannelReceiveError> {
@@ -65,16 +81,18 @@ impl ChannelReceive for UnorderedReliableReceiver {
                     }
                 }
                 MessageData::Fragment(fragment) => {
+                    // ignore the fragments of a message that we have already received (or that was cancelled),
+                    // so that we don't start reconstructing it again
+                    if self.received_message_ids.contains(&message_id) {
+                        return Ok(());
+                    }
                     if let Some(res) = self.fragment_receiver.receive_fragment(
                         fragment,
                         message.remote_sent_tick,
-                        None,
+                        Some(self.current_time),
                     ) {
-                        // receive the message if we haven't received it already
-                        if !self.received_message_ids.contains(&message_id) {
-                            self.received_message_ids.insert(message_id);
-                            entry.insert(res);
-                        }
+                        self.received_message_ids.insert(message_id);
+                        entry.insert(res);
                     }
                 }
             }
@@ -88,20 +106,38 @@ impl ChannelReceive for UnorderedReliableReceiver {
 
         // this was the message we were waiting for (as a reliable receiver)
         if self.pending_recv_message_id == message_id {
-            // update the pending message id (skip through all message ids we have already received out of order)
-            while self
-                .received_message_ids
-                .contains(&self.pending_recv_message_id)
-            {
-                self.received_message_ids
-                    .remove(&self.pending_recv_message_id);
-                self.pending_recv_message_id += 1;
-            }
+            self.skip_received_message_ids();
         }
 
         // receive oldest message in the buffer
         Some(data)
     }
+
+    fn window_start(&self) -> Option<MessageId> {
+        Some(self.pending_recv_message_id)
+    }
+
+    fn fragment_receiver(&self) -> &FragmentReceiver {
+        &self.fragment_receiver
+    }
+
+    fn fragment_receiver_mut(&mut self) -> &mut FragmentReceiver {
+        &mut self.fragment_receiver
+    }
+
+    fn cancel_message(&mut self, message_id: MessageId, current_time: WrappedTime) {
+        self.fragment_receiver.cancel(message_id, current_time);
+        // the message was already received
+        if message_id < self.pending_recv_message_id
+            || !self.received_message_ids.insert(message_id)
+        {
+            return;
+        }
+        // the cancelled message counts as received, so that the receive window can move past it
+        if self.pending_recv_message_id == message_id {
+            self.skip_received_message_ids();
+        }
+    }
 }
 
 #[cfg(test)]
@@ -57,6 +57,14 @@ impl ChannelReceive for UnorderedUnreliableReceiver {
     fn read_message(&mut self) -> Option<(Tick, Bytes)> {
         self.recv_message_buffer.pop_front()
     }
+
+    fn fragment_receiver(&self) -> &FragmentReceiver {
+        &self.fragment_receiver
+    }
+
+    fn fragment_receiver_mut(&mut self) -> &mut FragmentReceiver {
+        &mut self.fragment_receiver
+    }
 }
 
 #[cfg(test)]
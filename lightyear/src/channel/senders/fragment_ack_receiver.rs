@@ -23,6 +23,11 @@ impl FragmentAckReceiver {
             .or_insert_with(|| FragmentAckTracker::new(num_fragments));
     }
 
+    /// Stop waiting for the acks of a fragmented message
+    pub fn remove(&mut self, message_id: MessageId) {
+        self.fragment_messages.remove(&message_id);
+    }
+
     /// Discard all messages for which the latest ack was received before the cleanup time
     /// (i.e. we probably lost some fragments and we will never get all the acks for this fragmented message)
     ///
@@ -5,6 +5,7 @@ use crossbeam_channel::Receiver;
 use enum_dispatch::enum_dispatch;
 
 use crate::packet::message::{MessageAck, MessageId, SendMessage};
+use crate::protocol::message::MessageKind;
 use crate::serialize::SerializationError;
 use crate::shared::ping::manager::PingManager;
 use crate::shared::tick_manager::TickManager;
@@ -17,6 +18,25 @@ pub(crate) mod sequenced_unreliable;
 pub(crate) mod unordered_unreliable;
 pub(crate) mod unordered_unreliable_with_acks;
 
+/// Key used to deduplicate the messages buffered on a channel: a message buffered with a key replaces
+/// the message with the same key that has not been transmitted yet.
+///
+/// The key is derived from the type of the message and an application-provided `u64`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DedupKey {
+    pub(crate) message: MessageKind,
+    pub(crate) key: u64,
+}
+
+impl DedupKey {
+    pub fn new<M: 'static>(key: u64) -> Self {
+        Self {
+            message: MessageKind::of::<M>(),
+            key,
+        }
+    }
+}
+
 // TODO: separate trait into multiple traits
 // - buffer send should be public
 // - all other methods should be private
@@ -42,9 +62,37 @@ pub trait ChannelSend {
         priority: f32,
     ) -> Result<Option<MessageId>, SerializationError>;
 
+    /// Queues a message to be transmitted, replacing the queued message with the same [`DedupKey`]
+    /// if it has not been transmitted yet. The messages that are already in flight are not affected.
+    ///
+    /// Returns the MessageId of the message that was queued, if there is one, and true if a message was replaced.
+    /// By default the message is simply queued: the unreliable senders transmit all their messages the next time
+    /// that packets are sent.
+    fn buffer_send_with_key(
+        &mut self,
+        message: Bytes,
+        priority: f32,
+        _key: DedupKey,
+    ) -> Result<(Option<MessageId>, bool), SerializationError> {
+        self.buffer_send(message, priority)
+            .map(|message_id| (message_id, false))
+    }
+
     /// Reads from the buffer of messages to send to prepare a list of Packets
     /// that can be sent over the network for this channel
-    fn send_packet(&mut self) -> (VecDeque<SendMessage>, VecDeque<SendMessage>);
+    fn send_packet(&mut self) -> (VecDeque<SendMessage>, VecDeque<SendMessage>) {
+        let (mut single, mut fragment) = (VecDeque::new(), VecDeque::new());
+        self.send_packet_into(&mut single, &mut fragment);
+        (single, fragment)
+    }
+
+    /// Same as [`send_packet`](ChannelSend::send_packet), but the messages are moved to the provided
+    /// buffers so that the allocations of both the channel's buffers and the provided buffers are reused
+    fn send_packet_into(
+        &mut self,
+        single: &mut VecDeque<SendMessage>,
+        fragment: &mut VecDeque<SendMessage>,
+    );
 
     /// Called when we receive acknowledgement that a Message has been received
     fn receive_ack(&mut self, message_ack: &MessageAck);
@@ -58,6 +106,42 @@ pub trait ChannelSend {
 
     /// Send nacks to the subscribers of nacks
     fn send_nacks(&mut self, nack: MessageId);
+
+    /// Number of messages that are still buffered in the sender.
+    /// The unreliable senders count each fragment of a fragmented message separately
+    fn num_buffered(&self) -> usize;
+
+    /// Number of bytes of the messages that are still buffered in the sender
+    fn buffered_bytes(&self) -> usize;
+
+    /// Drop the oldest buffered message to make room for a new message.
+    ///
+    /// Returns false if there was no message to drop. The reliable senders never drop their messages
+    fn drop_oldest(&mut self) -> bool {
+        false
+    }
+}
+
+/// Drop the oldest message buffered by an unreliable sender: a single message if there is one, otherwise
+/// all the fragments of the oldest fragmented message.
+///
+/// Returns the id and the number of bytes of the dropped message
+pub(crate) fn drop_oldest_message(
+    single_messages: &mut VecDeque<SendMessage>,
+    fragmented_messages: &mut VecDeque<SendMessage>,
+) -> Option<(Option<MessageId>, usize)> {
+    if let Some(message) = single_messages.pop_front() {
+        return Some((message.data.message_id(), message.data.bytes().len()));
+    }
+    let message_id = fragmented_messages.front()?.data.message_id();
+    let mut num_bytes = 0;
+    while fragmented_messages
+        .front()
+        .is_some_and(|fragment| fragment.data.message_id() == message_id)
+    {
+        num_bytes += fragmented_messages.pop_front().unwrap().data.bytes().len();
+    }
+    Some((message_id, num_bytes))
 }
 
 /// Enum dispatch lets us derive ChannelSend on each enum variant
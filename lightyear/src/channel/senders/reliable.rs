@@ -1,6 +1,6 @@
 use bevy::prelude::{Timer, TimerMode};
 use std::collections::VecDeque;
-use std::collections::{BTreeMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 
 use bevy::utils::Duration;
 use bytes::Bytes;
@@ -8,9 +8,14 @@ use crossbeam_channel::{Receiver, Sender};
 use tracing::trace;
 
 use crate::channel::builder::ReliableSettings;
+use crate::channel::flow_control::FlowControlStats;
 use crate::channel::senders::fragment_sender::FragmentSender;
-use crate::channel::senders::ChannelSend;
+use crate::channel::senders::{ChannelSend, DedupKey};
+use crate::channel::transfer::{
+    TransferDirection, TransferHandle, TransferOutcome, TransferProgress,
+};
 use crate::packet::message::{FragmentData, MessageAck, MessageId, SendMessage, SingleData};
+use crate::protocol::channel::ChannelKind;
 use crate::serialize::SerializationError;
 use crate::shared::ping::manager::PingManager;
 use crate::shared::tick_manager::TickManager;
@@ -35,11 +40,28 @@ pub enum UnackedMessage {
     Fragmented(Vec<FragmentAck>),
 }
 
+impl UnackedMessage {
+    /// Number of bytes of the message
+    fn num_bytes(&self) -> usize {
+        match self {
+            UnackedMessage::Single { bytes, .. } => bytes.len(),
+            UnackedMessage::Fragmented(fragment_acks) => {
+                fragment_acks.iter().map(|f| f.data.bytes.len()).sum()
+            }
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct UnackedMessageWithPriority {
     pub unacked_message: UnackedMessage,
     pub base_priority: f32,
     pub accumulated_priority: f32,
+    /// Key of the message if it can be replaced by a newer message with the same key.
+    /// It is removed when the message is transmitted for the first time
+    pub dedup_key: Option<DedupKey>,
+    /// Time at which the message was transmitted for the first time
+    pub first_sent: Option<WrappedTime>,
 }
 
 /// A sender that makes sure to resend messages until it receives an ack
@@ -76,6 +98,20 @@ pub struct ReliableSender {
     /// Factor that makes sure that the priority accumulates at the same right even the channel
     /// sends messages infrequently
     priority_multiplier: f32,
+    /// If flow control is enabled, end (exclusive) of the window of message ids that the receiver can accept.
+    /// Messages past this id are not sent until the receiver advertises a larger window.
+    window_end: Option<MessageId>,
+    /// Maximum number of messages that were sent but not acked yet
+    /// (see [`ChannelSettingsOverride::max_in_flight`](crate::channel::builder::ChannelSettingsOverride))
+    max_in_flight: Option<u16>,
+    /// Number of messages (or fragments) that were sent again since the last call to `take_num_resent`
+    num_resent: u64,
+    /// Messages buffered with a [`DedupKey`] that have not been transmitted yet
+    pending_dedup_keys: HashMap<DedupKey, MessageId>,
+    /// Fragmented messages that were acked or cancelled since the last call to `take_finished_transfers`
+    finished_transfers: Vec<(MessageId, TransferOutcome)>,
+    /// Number of bytes of the messages that are not acked yet
+    buffered_bytes: usize,
 }
 
 impl ReliableSender {
@@ -86,7 +122,6 @@ impl ReliableSender {
             Some(Timer::new(send_frequency, TimerMode::Repeating))
         };
         Self {
-            reliable_settings,
             unacked_messages: Default::default(),
             next_send_message_id: MessageId(0),
             single_messages_to_send: Default::default(),
@@ -99,7 +134,153 @@ impl ReliableSender {
             current_time: WrappedTime::default(),
             timer,
             priority_multiplier: 1.0,
+            window_end: reliable_settings.receive_window.map(MessageId),
+            reliable_settings,
+            max_in_flight: None,
+            num_resent: 0,
+            pending_dedup_keys: HashMap::new(),
+            finished_transfers: Vec::new(),
+            buffered_bytes: 0,
+        }
+    }
+
+    /// Replace the settings used to resend the messages
+    pub(crate) fn set_reliable_settings(&mut self, reliable_settings: ReliableSettings) {
+        self.reliable_settings = reliable_settings;
+    }
+
+    pub(crate) fn set_max_in_flight(&mut self, max_in_flight: Option<u16>) {
+        self.max_in_flight = max_in_flight;
+    }
+
+    /// Returns the number of messages (or fragments) that were sent again because they were not acked in time,
+    /// since the last call to this function
+    pub(crate) fn take_num_resent(&mut self) -> u64 {
+        std::mem::take(&mut self.num_resent)
+    }
+
+    fn build_unacked_message(
+        &mut self,
+        message_id: MessageId,
+        message: Bytes,
+    ) -> Result<UnackedMessage, SerializationError> {
+        Ok(if message.len() > self.fragment_sender.fragment_size {
+            let fragments = self
+                .fragment_sender
+                .build_fragments(message_id, None, message)?;
+            UnackedMessage::Fragmented(
+                fragments
+                    .into_iter()
+                    .map(|fragment| FragmentAck {
+                        data: fragment,
+                        acked: false,
+                        last_sent: None,
+                    })
+                    .collect(),
+            )
+        } else {
+            UnackedMessage::Single {
+                bytes: message,
+                last_sent: None,
+            }
+        })
+    }
+
+    /// Progress of the fragmented messages that are not acked yet
+    pub(crate) fn transfers(
+        &self,
+        channel: ChannelKind,
+    ) -> impl Iterator<Item = TransferProgress> + '_ {
+        self.unacked_messages
+            .iter()
+            .filter_map(move |(message_id, message)| {
+                let UnackedMessage::Fragmented(fragment_acks) = &message.unacked_message else {
+                    return None;
+                };
+                Some(TransferProgress::new(
+                    TransferHandle::new(channel, *message_id, TransferDirection::Send),
+                    fragment_acks.iter().filter(|f| f.acked).count(),
+                    fragment_acks.len(),
+                    message.first_sent,
+                    self.current_time,
+                ))
+            })
+    }
+
+    /// Number of fragmented messages that are not acked yet
+    pub(crate) fn num_transfers(&self) -> usize {
+        self.unacked_messages
+            .values()
+            .filter(|message| matches!(message.unacked_message, UnackedMessage::Fragmented(_)))
+            .count()
+    }
+
+    /// Take the fragmented messages that were acked or cancelled since the last call
+    pub(crate) fn take_finished_transfers(&mut self) -> Vec<(MessageId, TransferOutcome)> {
+        std::mem::take(&mut self.finished_transfers)
+    }
+
+    /// Stop sending a fragmented message that is not acked yet.
+    ///
+    /// Returns false if there is no such message.
+    pub(crate) fn cancel(&mut self, message_id: MessageId) -> bool {
+        if !self
+            .unacked_messages
+            .get(&message_id)
+            .is_some_and(|message| matches!(message.unacked_message, UnackedMessage::Fragmented(_)))
+        {
+            return false;
+        }
+        let message = self.remove_unacked_message(message_id).unwrap();
+        if let Some(key) = message.dedup_key {
+            self.pending_dedup_keys.remove(&key);
+        }
+        self.finished_transfers
+            .push((message_id, TransferOutcome::Cancelled));
+        true
+    }
+
+    /// Stop tracking a message that was acked or cancelled
+    fn remove_unacked_message(&mut self, message_id: MessageId) -> Option<UnackedMessageWithPriority> {
+        let message = self.unacked_messages.remove(&message_id)?;
+        self.buffered_bytes -= message.unacked_message.num_bytes();
+        Some(message)
+    }
+
+    /// Update the window of message ids that the receiver can accept
+    pub(crate) fn update_window_end(&mut self, window_end: MessageId) {
+        if let Some(current) = &mut self.window_end {
+            if window_end > *current {
+                *current = window_end;
+            }
+        }
+    }
+
+    /// Returns the flow control state of the channel, if flow control is enabled
+    pub(crate) fn flow_control_stats(&self) -> Option<FlowControlStats> {
+        let window_end = self.window_end?;
+        let mut stats = FlowControlStats::default();
+        let mut first_unsent = self.next_send_message_id;
+        for (message_id, unacked_message) in self.unacked_messages.iter() {
+            let sent = match &unacked_message.unacked_message {
+                UnackedMessage::Single { last_sent, .. } => last_sent.is_some(),
+                UnackedMessage::Fragmented(fragment_acks) => {
+                    fragment_acks.iter().any(|f| f.last_sent.is_some())
+                }
+            };
+            if sent {
+                stats.in_flight += 1;
+            } else {
+                if *message_id < first_unsent {
+                    first_unsent = *message_id;
+                }
+                if *message_id >= window_end {
+                    stats.blocked += 1;
+                }
+            }
         }
+        stats.available_window = (window_end - first_unsent).max(0) as u16;
+        Some(stats)
     }
 }
 
@@ -127,32 +308,16 @@ impl ChannelSend for ReliableSender {
         priority: f32,
     ) -> Result<Option<MessageId>, SerializationError> {
         let message_id = self.next_send_message_id;
-        let unacked_message = if message.len() > self.fragment_sender.fragment_size {
-            let fragments = self
-                .fragment_sender
-                .build_fragments(message_id, None, message)?;
-            UnackedMessage::Fragmented(
-                fragments
-                    .into_iter()
-                    .map(|fragment| FragmentAck {
-                        data: fragment,
-                        acked: false,
-                        last_sent: None,
-                    })
-                    .collect(),
-            )
-        } else {
-            UnackedMessage::Single {
-                bytes: message,
-                last_sent: None,
-            }
-        };
+        let unacked_message = self.build_unacked_message(message_id, message)?;
+        self.buffered_bytes += unacked_message.num_bytes();
         let unacked_message_with_priority = UnackedMessageWithPriority {
             unacked_message,
             base_priority: priority,
             // store with 0.0 accumulated priority because priority gets accumulated when we collect the messages
             // for sending (even the first time the message is sent)
             accumulated_priority: 0.0,
+            dedup_key: None,
+            first_sent: None,
         };
         self.unacked_messages
             .insert(message_id, unacked_message_with_priority);
@@ -160,12 +325,48 @@ impl ChannelSend for ReliableSender {
         Ok(Some(message_id))
     }
 
+    /// The replaced message keeps its [`MessageId`], so the new message takes its place in the order of the channel
+    fn buffer_send_with_key(
+        &mut self,
+        message: Bytes,
+        priority: f32,
+        key: DedupKey,
+    ) -> Result<(Option<MessageId>, bool), SerializationError> {
+        if let Some(message_id) = self.pending_dedup_keys.get(&key).copied() {
+            // the message was not transmitted yet, since its key is removed when it is sent
+            let unacked_message = self.build_unacked_message(message_id, message)?;
+            let pending = self
+                .unacked_messages
+                .get_mut(&message_id)
+                .expect("a message with a pending dedup key is not acked");
+            self.buffered_bytes += unacked_message.num_bytes();
+            self.buffered_bytes -= pending.unacked_message.num_bytes();
+            pending.unacked_message = unacked_message;
+            pending.base_priority = priority;
+            trace!(?message_id, "Replaced a message that was not sent yet");
+            return Ok((Some(message_id), true));
+        }
+        let message_id = self.buffer_send(message, priority)?;
+        if let Some(message_id) = message_id {
+            self.unacked_messages
+                .get_mut(&message_id)
+                .unwrap()
+                .dedup_key = Some(key);
+            self.pending_dedup_keys.insert(key, message_id);
+        }
+        Ok((message_id, false))
+    }
+
     /// Take messages from the buffer of messages to be sent, and build a list of packets
     /// to be sent
     /// The messages to be sent need to have been collected prior to this point.
-    fn send_packet(&mut self) -> (VecDeque<SendMessage>, VecDeque<SendMessage>) {
+    fn send_packet_into(
+        &mut self,
+        single: &mut VecDeque<SendMessage>,
+        fragment: &mut VecDeque<SendMessage>,
+    ) {
         if self.timer.as_ref().is_some_and(|t| !t.finished()) {
-            return (VecDeque::new(), VecDeque::new());
+            return;
         }
 
         // Collect the list of messages that need to be sent
@@ -184,6 +385,37 @@ impl ChannelSend for ReliableSender {
             }
         };
 
+        // messages that have never been sent are only sent if they fit in the receiver's window
+        // (retransmissions are always allowed)
+        let window_end = self.window_end;
+        let outside_window = |message_id: &MessageId| -> bool {
+            window_end.is_some_and(|window_end| *message_id >= window_end)
+        };
+        // same for the maximum number of messages in flight
+        let max_in_flight = self.max_in_flight;
+        let mut in_flight = match max_in_flight {
+            Some(_) => self
+                .unacked_messages
+                .values()
+                .filter(|message| match &message.unacked_message {
+                    UnackedMessage::Single { last_sent, .. } => last_sent.is_some(),
+                    UnackedMessage::Fragmented(fragment_acks) => {
+                        fragment_acks.iter().any(|f| f.last_sent.is_some())
+                    }
+                })
+                .count(),
+            None => 0,
+        };
+        let blocked = |message_id: &MessageId, in_flight: &mut usize| -> bool {
+            if outside_window(message_id)
+                || max_in_flight.is_some_and(|max| *in_flight >= max as usize)
+            {
+                return true;
+            }
+            *in_flight += 1;
+            false
+        };
+
         // Iterate through all unacked messages, oldest message ids first
         for (message_id, unacked_message_with_priority) in self.unacked_messages.iter_mut() {
             // accumulate the priority for all messages (including the ones that were just added, since we set the accumulated priority to 0.0)
@@ -202,8 +434,14 @@ impl ChannelSend for ReliableSender {
                     bytes,
                     ref mut last_sent,
                 } => {
+                    if last_sent.is_none() && blocked(message_id, &mut in_flight) {
+                        continue;
+                    }
                     if should_send(last_sent) {
                         trace!("Should send message {:?}", message_id);
+                        if let Some(key) = unacked_message_with_priority.dedup_key.take() {
+                            self.pending_dedup_keys.remove(&key);
+                        }
                         let message_info = MessageAck {
                             message_id: *message_id,
                             fragment_id: None,
@@ -215,11 +453,25 @@ impl ChannelSend for ReliableSender {
                                 priority: unacked_message_with_priority.accumulated_priority,
                             });
                             self.message_ids_to_send.insert(message_info);
+                            if last_sent.is_some() {
+                                self.num_resent += 1;
+                            }
                             *last_sent = Some(self.current_time);
                         }
                     }
                 }
                 UnackedMessage::Fragmented(fragment_acks) => {
+                    if fragment_acks.iter().all(|f| f.last_sent.is_none())
+                        && blocked(message_id, &mut in_flight)
+                    {
+                        continue;
+                    }
+                    if let Some(key) = unacked_message_with_priority.dedup_key.take() {
+                        self.pending_dedup_keys.remove(&key);
+                    }
+                    unacked_message_with_priority
+                        .first_sent
+                        .get_or_insert(self.current_time);
                     // only send the fragments that haven't been acked and should be resent
                     fragment_acks
                         .iter_mut()
@@ -236,6 +488,9 @@ impl ChannelSend for ReliableSender {
                                     priority: unacked_message_with_priority.accumulated_priority,
                                 });
                                 self.message_ids_to_send.insert(message_info);
+                                if f.last_sent.is_some() {
+                                    self.num_resent += 1;
+                                }
                                 f.last_sent = Some(self.current_time);
                             }
                         })
@@ -253,11 +508,8 @@ impl ChannelSend for ReliableSender {
             );
         }
 
-        // TODO: use double-buffer to reuse allocated memory?
-        (
-            std::mem::take(&mut self.single_messages_to_send),
-            std::mem::take(&mut self.fragmented_messages_to_send),
-        )
+        single.append(&mut self.single_messages_to_send);
+        fragment.append(&mut self.fragmented_messages_to_send);
 
         // TODO: handle if we couldn't send all messages?
         // TODO: update message_ids_to_send?
@@ -290,7 +542,7 @@ impl ChannelSend for ReliableSender {
                     for sender in &self.ack_senders {
                         sender.send(message_ack.message_id).unwrap();
                     }
-                    self.unacked_messages.remove(&message_ack.message_id);
+                    self.remove_unacked_message(message_ack.message_id);
                 }
                 UnackedMessage::Fragmented(fragment_acks) => {
                     let Some(fragment_id) = message_ack.fragment_id else {
@@ -301,7 +553,9 @@ impl ChannelSend for ReliableSender {
                         // TODO: use a variable to keep track of this?
                         // all fragments were acked
                         if fragment_acks.iter().all(|f| f.acked) {
-                            self.unacked_messages.remove(&message_ack.message_id);
+                            self.remove_unacked_message(message_ack.message_id);
+                            self.finished_transfers
+                                .push((message_ack.message_id, TransferOutcome::Completed));
                             for sender in &self.ack_senders {
                                 sender.send(message_ack.message_id).unwrap();
                             }
@@ -333,6 +587,15 @@ impl ChannelSend for ReliableSender {
             sender.send(nack).unwrap();
         }
     }
+
+    /// The reliable messages stay buffered until they are acked
+    fn num_buffered(&self) -> usize {
+        self.unacked_messages.len()
+    }
+
+    fn buffered_bytes(&self) -> usize {
+        self.buffered_bytes
+    }
 }
 
 #[cfg(test)]
@@ -351,6 +614,7 @@ mod tests {
             ReliableSettings {
                 rtt_resend_factor: 1.5,
                 rtt_resend_min_delay: Duration::from_millis(100),
+                receive_window: None,
             },
             Duration::default(),
         );
@@ -396,4 +660,96 @@ mod tests {
         let (single, _) = sender.send_packet();
         assert_eq!(single.len(), 0);
     }
+
+    #[test]
+    fn test_reliable_sender_receive_window() {
+        let mut sender = ReliableSender::new(
+            ReliableSettings {
+                rtt_resend_factor: 1.5,
+                rtt_resend_min_delay: Duration::from_millis(100),
+                receive_window: Some(2),
+            },
+            Duration::default(),
+        );
+        sender.current_rtt = Duration::from_millis(100);
+        sender.current_time = WrappedTime::new(0);
+        for _ in 0..3 {
+            sender.buffer_send(Bytes::from("hello"), 1.0).unwrap();
+        }
+
+        // only the messages inside the window are sent
+        let (single, _) = sender.send_packet();
+        assert_eq!(single.len(), 2);
+        assert_eq!(
+            sender.flow_control_stats(),
+            Some(FlowControlStats {
+                available_window: 0,
+                in_flight: 2,
+                blocked: 1,
+            })
+        );
+
+        // retransmissions are not blocked by the window
+        sender.current_time += Duration::from_millis(200);
+        let (single, _) = sender.send_packet();
+        assert_eq!(single.len(), 2);
+
+        // the window reopens
+        sender.update_window_end(MessageId(3));
+        let (single, _) = sender.send_packet();
+        assert_eq!(single.len(), 1);
+        assert_eq!(
+            single.front().unwrap().data.message_id(),
+            Some(MessageId(2))
+        );
+    }
+
+    #[test]
+    fn test_reliable_sender_dedup_key() {
+        let mut sender = ReliableSender::new(
+            ReliableSettings {
+                rtt_resend_factor: 1.5,
+                rtt_resend_min_delay: Duration::from_millis(100),
+                receive_window: None,
+            },
+            Duration::default(),
+        );
+        sender.current_rtt = Duration::from_millis(100);
+        sender.current_time = WrappedTime::new(0);
+        let key = DedupKey::new::<u8>(0);
+
+        // the queued message is replaced, and keeps its message id
+        assert_eq!(
+            sender
+                .buffer_send_with_key(Bytes::from("v1"), 1.0, key)
+                .unwrap(),
+            (Some(MessageId(0)), false)
+        );
+        assert_eq!(
+            sender
+                .buffer_send_with_key(Bytes::from("v2"), 1.0, key)
+                .unwrap(),
+            (Some(MessageId(0)), true)
+        );
+        // messages with another key are not replaced
+        sender
+            .buffer_send_with_key(Bytes::from("other"), 1.0, DedupKey::new::<u16>(0))
+            .unwrap();
+        assert_eq!(sender.unacked_messages.len(), 2);
+        let (single, _) = sender.send_packet();
+        assert_eq!(single.len(), 2);
+        assert_eq!(
+            single.front().unwrap().data,
+            SingleData::new(Some(MessageId(0)), Bytes::from("v2")).into()
+        );
+
+        // the message in flight is not replaced
+        assert_eq!(
+            sender
+                .buffer_send_with_key(Bytes::from("v3"), 1.0, key)
+                .unwrap(),
+            (Some(MessageId(2)), false)
+        );
+        assert_eq!(sender.unacked_messages.len(), 3);
+    }
 }
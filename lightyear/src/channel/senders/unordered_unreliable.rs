@@ -7,7 +7,7 @@ use bytes::Bytes;
 use crossbeam_channel::{Receiver, Sender};
 
 use crate::channel::senders::fragment_sender::FragmentSender;
-use crate::channel::senders::ChannelSend;
+use crate::channel::senders::{drop_oldest_message, ChannelSend};
 use crate::packet::message::{MessageAck, MessageData, MessageId, SendMessage, SingleData};
 use crate::serialize::SerializationError;
 use crate::shared::ping::manager::PingManager;
@@ -31,6 +31,8 @@ pub struct UnorderedUnreliableSender {
     nack_senders: Vec<Sender<MessageId>>,
     /// Internal timer to determine if the channel is ready to send messages
     timer: Option<Timer>,
+    /// Number of bytes of the buffered messages
+    buffered_bytes: usize,
 }
 
 impl UnorderedUnreliableSender {
@@ -47,6 +49,7 @@ impl UnorderedUnreliableSender {
             fragment_sender: FragmentSender::new(),
             nack_senders: vec![],
             timer,
+            buffered_bytes: 0,
         }
     }
 }
@@ -65,6 +68,7 @@ impl ChannelSend for UnorderedUnreliableSender {
         message: Bytes,
         priority: f32,
     ) -> Result<Option<MessageId>, SerializationError> {
+        self.buffered_bytes += message.len();
         if message.len() > self.fragment_sender.fragment_size {
             for fragment in self.fragment_sender.build_fragments(
                 self.next_send_fragmented_message_id,
@@ -89,14 +93,17 @@ impl ChannelSend for UnorderedUnreliableSender {
     }
 
     /// Take messages from the buffer of messages to be sent, and build a list of packets to be sent
-    fn send_packet(&mut self) -> (VecDeque<SendMessage>, VecDeque<SendMessage>) {
+    fn send_packet_into(
+        &mut self,
+        single: &mut VecDeque<SendMessage>,
+        fragment: &mut VecDeque<SendMessage>,
+    ) {
         if self.timer.as_ref().is_some_and(|t| !t.finished()) {
-            return (VecDeque::new(), VecDeque::new());
+            return;
         }
-        (
-            std::mem::take(&mut self.single_messages_to_send),
-            std::mem::take(&mut self.fragmented_messages_to_send),
-        )
+        single.append(&mut self.single_messages_to_send);
+        fragment.append(&mut self.fragmented_messages_to_send);
+        self.buffered_bytes = 0;
         // let messages_to_send = std::mem::take(&mut self.messages_to_send);
         // let (remaining_messages_to_send, _) =
         //     packet_manager.pack_messages_within_channel(messages_to_send);
@@ -123,6 +130,25 @@ impl ChannelSend for UnorderedUnreliableSender {
             sender.send(nack).unwrap();
         }
     }
+
+    fn num_buffered(&self) -> usize {
+        self.single_messages_to_send.len() + self.fragmented_messages_to_send.len()
+    }
+
+    fn buffered_bytes(&self) -> usize {
+        self.buffered_bytes
+    }
+
+    fn drop_oldest(&mut self) -> bool {
+        let Some((_, num_bytes)) = drop_oldest_message(
+            &mut self.single_messages_to_send,
+            &mut self.fragmented_messages_to_send,
+        ) else {
+            return false;
+        };
+        self.buffered_bytes -= num_bytes;
+        true
+    }
 }
 
 #[cfg(test)]
@@ -7,7 +7,7 @@ use crossbeam_channel::{Receiver, Sender};
 
 use crate::channel::senders::fragment_ack_receiver::FragmentAckReceiver;
 use crate::channel::senders::fragment_sender::FragmentSender;
-use crate::channel::senders::ChannelSend;
+use crate::channel::senders::{drop_oldest_message, ChannelSend};
 use crate::packet::message::{MessageAck, MessageData, MessageId, SendMessage, SingleData};
 use crate::serialize::SerializationError;
 use crate::shared::ping::manager::PingManager;
@@ -41,6 +41,8 @@ pub struct UnorderedUnreliableWithAcksSender {
     current_time: WrappedTime,
     /// Internal timer to determine if the channel is ready to send messages
     timer: Option<Timer>,
+    /// Number of bytes of the buffered messages
+    buffered_bytes: usize,
 }
 
 impl UnorderedUnreliableWithAcksSender {
@@ -60,6 +62,8 @@ impl UnorderedUnreliableWithAcksSender {
             fragment_ack_receiver: FragmentAckReceiver::new(),
             current_time: WrappedTime::default(),
             timer,
+
+            buffered_bytes: 0,
         }
     }
 }
@@ -82,6 +86,7 @@ impl ChannelSend for UnorderedUnreliableWithAcksSender {
         priority: f32,
     ) -> Result<Option<MessageId>, SerializationError> {
         let message_id = self.next_send_message_id;
+        self.buffered_bytes += message.len();
         if message.len() > self.fragment_sender.fragment_size {
             let fragments = self
                 .fragment_sender
@@ -106,14 +111,17 @@ impl ChannelSend for UnorderedUnreliableWithAcksSender {
     }
 
     /// Take messages from the buffer of messages to be sent, and build a list of packets to be sent
-    fn send_packet(&mut self) -> (VecDeque<SendMessage>, VecDeque<SendMessage>) {
+    fn send_packet_into(
+        &mut self,
+        single: &mut VecDeque<SendMessage>,
+        fragment: &mut VecDeque<SendMessage>,
+    ) {
         if self.timer.as_ref().is_some_and(|t| !t.finished()) {
-            return (VecDeque::new(), VecDeque::new());
+            return;
         }
-        (
-            std::mem::take(&mut self.single_messages_to_send),
-            std::mem::take(&mut self.fragmented_messages_to_send),
-        )
+        single.append(&mut self.single_messages_to_send);
+        fragment.append(&mut self.fragmented_messages_to_send);
+        self.buffered_bytes = 0;
         // let messages_to_send = std::mem::take(&mut self.messages_to_send);
         // let (remaining_messages_to_send, _) =
         //     packet_manager.pack_messages_within_channel(messages_to_send);
@@ -163,6 +171,29 @@ impl ChannelSend for UnorderedUnreliableWithAcksSender {
             sender.send(nack).unwrap();
         }
     }
+
+    fn num_buffered(&self) -> usize {
+        self.single_messages_to_send.len() + self.fragmented_messages_to_send.len()
+    }
+
+    fn buffered_bytes(&self) -> usize {
+        self.buffered_bytes
+    }
+
+    fn drop_oldest(&mut self) -> bool {
+        let Some((message_id, num_bytes)) = drop_oldest_message(
+            &mut self.single_messages_to_send,
+            &mut self.fragmented_messages_to_send,
+        ) else {
+            return false;
+        };
+        // the acks of a dropped fragmented message will never be complete
+        if let Some(message_id) = message_id {
+            self.fragment_ack_receiver.remove(message_id);
+        }
+        self.buffered_bytes -= num_bytes;
+        true
+    }
 }
 
 #[cfg(test)]
@@ -1,3 +1,183 @@
+//! Statistics about the messages sent and received on each channel
+use bevy::app::{App, Plugin};
+use bevy::diagnostic::{Diagnostic, DiagnosticPath, Diagnostics, RegisterDiagnostic};
+use bevy::prelude::{Reflect, Resource};
+use bevy::utils::{Duration, HashMap};
+
+use crate::protocol::channel::{ChannelKind, ChannelRegistry};
+
+/// Statistics about the messages sent and received on a channel of a connection.
+///
+/// They are reset when the connection is closed.
+#[derive(Default, Copy, Clone, Debug, PartialEq, Reflect)]
+pub struct ChannelStats {
+    /// Number of messages buffered to be sent. The retransmissions of reliable messages are not counted
+    pub messages_sent: u64,
+    /// Number of bytes of the messages buffered to be sent
+    pub bytes_sent: u64,
+    /// Number of messages received. A fragmented message is counted once, when its first fragment is received
+    pub messages_received: u64,
+    /// Number of bytes received, including the bytes of every fragment
+    pub bytes_received: u64,
+    /// Number of unreliable messages that were discarded because the bandwidth quota was reached,
+    /// or to make room in the send buffer (see [`BufferLimits`](crate::channel::builder::BufferLimits))
+    pub messages_dropped: u64,
+    /// Number of reliable messages that could not be sent because the send buffer was full
+    /// (see [`BufferLimits`](crate::channel::builder::BufferLimits))
+    pub messages_rejected: u64,
+    /// Number of times that a reliable message was sent again because it was not acked in time.
+    /// Each fragment of a fragmented message is counted
+    pub messages_resent: u64,
+    /// Number of messages sent with a dedup key that replaced a message with the same key
+    /// before it was transmitted
+    pub messages_replaced: u64,
+    /// Number of messages waiting in the sender the last time that packets were sent.
+    /// For reliable channels, this includes the messages that were sent but not acked yet.
+    /// For unreliable channels, each fragment of a fragmented message is counted
+    pub buffered: usize,
+    /// Number of fragmented messages being sent (on reliable channels) or received the last time that packets
+    /// were sent. They can be listed with `transfers()` on the `ConnectionManager`
+    /// (see [`transfer`](crate::channel::transfer))
+    pub active_transfers: usize,
+}
+
+impl ChannelStats {
+    /// Add the stats of another connection to these stats
+    pub(crate) fn merge(&mut self, other: &ChannelStats) {
+        self.messages_sent += other.messages_sent;
+        self.bytes_sent += other.bytes_sent;
+        self.messages_received += other.messages_received;
+        self.bytes_received += other.bytes_received;
+        self.messages_dropped += other.messages_dropped;
+        self.messages_rejected += other.messages_rejected;
+        self.messages_resent += other.messages_resent;
+        self.messages_replaced += other.messages_replaced;
+        self.buffered += other.buffered;
+        self.active_transfers += other.active_transfers;
+    }
+}
+
+/// Diagnostic paths of the stats of a channel
+#[derive(Debug, Clone)]
+struct ChannelStatsPaths {
+    messages_sent: DiagnosticPath,
+    bytes_sent: DiagnosticPath,
+    messages_received: DiagnosticPath,
+    bytes_received: DiagnosticPath,
+    messages_dropped: DiagnosticPath,
+    messages_rejected: DiagnosticPath,
+    messages_resent: DiagnosticPath,
+    messages_replaced: DiagnosticPath,
+    buffered: DiagnosticPath,
+    active_transfers: DiagnosticPath,
+}
+
+impl ChannelStatsPaths {
+    fn new(channel_name: &str) -> Self {
+        let path = |stat: &str| DiagnosticPath::new(format!("channel.{channel_name}.{stat}"));
+        Self {
+            messages_sent: path("messages_sent"),
+            bytes_sent: path("bytes_sent"),
+            messages_received: path("messages_received"),
+            bytes_received: path("bytes_received"),
+            messages_dropped: path("messages_dropped"),
+            messages_rejected: path("messages_rejected"),
+            messages_resent: path("messages_resent"),
+            messages_replaced: path("messages_replaced"),
+            buffered: path("buffered"),
+            active_transfers: path("active_transfers"),
+        }
+    }
+
+    fn all(&self) -> [&DiagnosticPath; 10] {
+        [
+            &self.messages_sent,
+            &self.bytes_sent,
+            &self.messages_received,
+            &self.bytes_received,
+            &self.messages_dropped,
+            &self.messages_rejected,
+            &self.messages_resent,
+            &self.messages_replaced,
+            &self.buffered,
+            &self.active_transfers,
+        ]
+    }
+}
+
+/// Diagnostic paths of every channel of the protocol
+#[derive(Resource, Debug, Default)]
+pub(crate) struct ChannelDiagnosticPaths(HashMap<ChannelKind, ChannelStatsPaths>);
+
+/// Plugin to expose the [`ChannelStats`] of every channel as diagnostics.
+///
+/// The diagnostic paths are `channel.<ChannelName>.<stat>`, for example `channel.EntityUpdatesChannel.bytes_sent`.
+/// On the server, the stats of all the clients are summed.
+pub struct ChannelDiagnosticsPlugin {
+    pub history_len: usize,
+    pub flush_interval: Duration,
+}
+
+impl Default for ChannelDiagnosticsPlugin {
+    fn default() -> Self {
+        Self {
+            history_len: 60,
+            flush_interval: Duration::from_millis(200),
+        }
+    }
+}
+
+impl ChannelDiagnosticsPlugin {
+    pub(crate) fn add_measurements<'a>(
+        paths: &ChannelDiagnosticPaths,
+        stats: impl Iterator<Item = (ChannelKind, &'a ChannelStats)>,
+        diagnostics: &mut Diagnostics,
+    ) {
+        for (kind, stats) in stats {
+            let Some(paths) = paths.0.get(&kind) else {
+                continue;
+            };
+            diagnostics.add_measurement(&paths.messages_sent, || stats.messages_sent as f64);
+            diagnostics.add_measurement(&paths.bytes_sent, || stats.bytes_sent as f64);
+            diagnostics
+                .add_measurement(&paths.messages_received, || stats.messages_received as f64);
+            diagnostics.add_measurement(&paths.bytes_received, || stats.bytes_received as f64);
+            diagnostics.add_measurement(&paths.messages_dropped, || stats.messages_dropped as f64);
+            diagnostics
+                .add_measurement(&paths.messages_rejected, || stats.messages_rejected as f64);
+            diagnostics.add_measurement(&paths.messages_resent, || stats.messages_resent as f64);
+            diagnostics
+                .add_measurement(&paths.messages_replaced, || stats.messages_replaced as f64);
+            diagnostics.add_measurement(&paths.buffered, || stats.buffered as f64);
+            diagnostics.add_measurement(&paths.active_transfers, || stats.active_transfers as f64);
+        }
+    }
+}
+
+impl Plugin for ChannelDiagnosticsPlugin {
+    fn build(&self, _: &mut App) {}
+
+    // the channels of the protocol are only all registered once the app is built
+    fn finish(&self, app: &mut App) {
+        let paths = ChannelDiagnosticPaths(
+            app.world()
+                .resource::<ChannelRegistry>()
+                .names()
+                .map(|(kind, name)| (kind, ChannelStatsPaths::new(name)))
+                .collect(),
+        );
+        for channel_paths in paths.0.values() {
+            for path in channel_paths.all() {
+                app.register_diagnostic(
+                    Diagnostic::new(path.clone()).with_max_history_length(self.history_len),
+                );
+            }
+        }
+        app.insert_resource(paths);
+    }
+}
+
+#[cfg(feature = "trace")]
 pub(crate) mod send {
     /// TODO: maybe this should be directly on the ChannelSender?
     #[derive(Default, Copy, Clone, Debug, PartialEq)]
@@ -0,0 +1,206 @@
+//! Transfers of the messages that are too big to fit in a single packet.
+//!
+//! A message bigger than [`FRAGMENT_SIZE`](crate::packet::packet::FRAGMENT_SIZE) is split into fragments, and
+//! can take several seconds to be delivered (snapshots, map data, etc.). Each of these messages is a transfer,
+//! identified by a [`TransferHandle`]:
+//! - on the sender, only the reliable channels track their transfers, since the unreliable channels send all the
+//!   fragments at once. The progress is the number of fragments acked by the receiver.
+//! - on the receiver, the progress is the number of fragments received.
+//!
+//! The transfers in progress are listed by `transfers()` on the `ConnectionManager` (to display a progress bar),
+//! and can be cancelled by either side with `cancel_transfer()`: the sender stops sending the remaining fragments
+//! and the receiver discards the fragments that it received. The cancellations are sent on the internal
+//! [`TransferControlChannel`](crate::channel::builder::TransferControlChannel), which is reliable.
+//! The ordered channels skip the cancelled message, so the next messages of the channel are still delivered.
+//!
+//! A `TransferEvent` is emitted when a transfer completes, is cancelled, or times out (the unreliable channels
+//! discard the messages that didn't receive any fragment for a few seconds).
+use bevy::utils::Duration;
+use byteorder::{ReadBytesExt, WriteBytesExt};
+
+use crate::packet::message::MessageId;
+use crate::protocol::channel::{ChannelId, ChannelKind};
+use crate::serialize::reader::Reader;
+use crate::serialize::{SerializationError, ToBytes};
+use crate::shared::time_manager::WrappedTime;
+
+/// Whether a transfer is sent or received by this peer
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TransferDirection {
+    Send,
+    Receive,
+}
+
+/// Identifies a fragmented message that is being sent or received on a channel of a connection
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TransferHandle {
+    channel: ChannelKind,
+    pub(crate) message_id: MessageId,
+    direction: TransferDirection,
+}
+
+impl TransferHandle {
+    pub(crate) fn new(
+        channel: ChannelKind,
+        message_id: MessageId,
+        direction: TransferDirection,
+    ) -> Self {
+        Self {
+            channel,
+            message_id,
+            direction,
+        }
+    }
+
+    /// The channel that the message is sent on
+    pub fn channel(&self) -> ChannelKind {
+        self.channel
+    }
+
+    pub fn direction(&self) -> TransferDirection {
+        self.direction
+    }
+}
+
+/// Progress of a transfer
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TransferProgress {
+    pub handle: TransferHandle,
+    /// Number of fragments acked by the receiver when sending, or received when receiving
+    pub completed_fragments: usize,
+    pub num_fragments: usize,
+    /// Time since the first fragment was sent or received
+    pub elapsed: Duration,
+    /// Estimated time until the transfer completes, at the throughput measured since it started.
+    /// None until a fragment is completed
+    pub eta: Option<Duration>,
+}
+
+impl TransferProgress {
+    pub(crate) fn new(
+        handle: TransferHandle,
+        completed_fragments: usize,
+        num_fragments: usize,
+        started: Option<WrappedTime>,
+        current_time: WrappedTime,
+    ) -> Self {
+        let elapsed = started
+            .and_then(|started| (current_time - started).to_std().ok())
+            .unwrap_or_default();
+        let eta = (completed_fragments > 0 && !elapsed.is_zero()).then(|| {
+            let remaining = num_fragments.saturating_sub(completed_fragments);
+            elapsed.mul_f64(remaining as f64 / completed_fragments as f64)
+        });
+        Self {
+            handle,
+            completed_fragments,
+            num_fragments,
+            elapsed,
+            eta,
+        }
+    }
+
+    /// Fraction (between 0 and 1) of the fragments that are completed
+    pub fn fraction(&self) -> f32 {
+        self.completed_fragments as f32 / self.num_fragments.max(1) as f32
+    }
+}
+
+/// How a transfer ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransferOutcome {
+    /// All the fragments were acked (when sending) or received (when receiving)
+    Completed,
+    /// The transfer was cancelled by this peer or by the remote
+    Cancelled,
+    /// The receiver of an unreliable channel did not receive a fragment for a while, and discarded the message
+    TimedOut,
+    /// The receiver of a sequenced channel reconstructed a more recent message first, and discarded this one
+    Superseded,
+}
+
+/// Transfers cancelled by a peer: the channel, the message, and the direction of the transfer for the
+/// peer that cancelled it
+#[derive(Debug, Default, Clone, PartialEq)]
+pub(crate) struct TransferCancellation(pub(crate) Vec<(ChannelId, MessageId, TransferDirection)>);
+
+impl ToBytes for TransferCancellation {
+    fn len(&self) -> usize {
+        self.0
+            .iter()
+            .map(|(channel_id, message_id, _)| channel_id.len() + message_id.len() + 1)
+            .sum()
+    }
+
+    fn to_bytes<T: WriteBytesExt>(&self, buffer: &mut T) -> Result<(), SerializationError> {
+        for (channel_id, message_id, direction) in &self.0 {
+            channel_id.to_bytes(buffer)?;
+            message_id.to_bytes(buffer)?;
+            buffer.write_u8(match direction {
+                TransferDirection::Send => 0,
+                TransferDirection::Receive => 1,
+            })?;
+        }
+        Ok(())
+    }
+
+    fn from_bytes(buffer: &mut Reader) -> Result<Self, SerializationError>
+    where
+        Self: Sized,
+    {
+        let mut cancellations = vec![];
+        while buffer.has_remaining() {
+            let channel_id = ChannelId::from_bytes(buffer)?;
+            let message_id = MessageId::from_bytes(buffer)?;
+            let direction = match buffer.read_u8()? {
+                0 => TransferDirection::Send,
+                1 => TransferDirection::Receive,
+                _ => return Err(SerializationError::InvalidValue),
+            };
+            cancellations.push((channel_id, message_id, direction));
+        }
+        Ok(Self(cancellations))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::protocol::Channel3;
+
+    use super::*;
+
+    #[test]
+    fn test_cancellation_serialization() {
+        let cancellation = TransferCancellation(vec![
+            (3, MessageId(10), TransferDirection::Send),
+            (200, MessageId(65535), TransferDirection::Receive),
+        ]);
+        let mut writer = vec![];
+        cancellation.to_bytes(&mut writer).unwrap();
+        assert_eq!(writer.len(), cancellation.len());
+        let mut reader = Reader::from(writer);
+        assert_eq!(
+            TransferCancellation::from_bytes(&mut reader).unwrap(),
+            cancellation
+        );
+    }
+
+    #[test]
+    fn test_progress_eta() {
+        let handle = TransferHandle::new(
+            ChannelKind::of::<Channel3>(),
+            MessageId(0),
+            TransferDirection::Send,
+        );
+        let started = WrappedTime::new(1000);
+        // not started yet
+        let progress = TransferProgress::new(handle, 0, 10, None, started);
+        assert_eq!(progress.elapsed, Duration::ZERO);
+        assert_eq!(progress.eta, None);
+        // 4 fragments in 200ms: the 6 remaining fragments need 300ms
+        let progress = TransferProgress::new(handle, 4, 10, Some(started), WrappedTime::new(1200));
+        assert_eq!(progress.elapsed, Duration::from_millis(200));
+        assert_eq!(progress.eta, Some(Duration::from_millis(300)));
+        assert_eq!(progress.fraction(), 0.4);
+    }
+}
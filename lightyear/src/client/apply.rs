@@ -0,0 +1,127 @@
+//! Custom storage for the components and messages received from the server.
+//!
+//! Some replicated data should not be stored as components: for example a minimap texture that is built from
+//! replicated tile updates. Instead of inserting the component on the entity and copying it to the texture
+//! in a later system, you can register an apply function that receives the decoded value and takes full
+//! responsibility for storing it:
+//!
+//! ```rust,ignore
+//! fn apply_tile(world: &mut World, entity: Entity, tile: Tile, tick: Tick) {
+//!     world.resource_mut::<Minimap>().set_tile(tile.position, tile.kind);
+//! }
+//!
+//! fn apply_chat(world: &mut World, message: ChatMessage, tick: Tick) {
+//!     world.resource_mut::<ChatLog>().push(message);
+//! }
+//!
+//! app.register_component::<Tile>(ChannelDirection::ServerToClient)
+//!     .add_client_apply(apply_tile);
+//! app.register_message::<ChatMessage>(ChannelDirection::ServerToClient)
+//!     .add_client_apply(apply_chat);
+//! ```
+//!
+//! The component is never inserted on the entity, and no component insert/update events are emitted for it.
+//! Similarly, no [`MessageEvent`](crate::client::events::MessageEvent) is emitted for the messages.
+//!
+//! ### Ordering
+//!
+//! Components:
+//! - the apply function is called with the `Confirmed` entity, during the receive system in `PreUpdate`
+//!   (in [`InternalMainSet::Receive`](crate::shared::sets::InternalMainSet)), at the point where the component
+//!   would have been inserted or updated
+//! - the replication messages are applied in order: all the entities of an actions message are spawned and
+//!   the entities contained in the value are mapped before the function is called, and the components
+//!   of an entity are applied in the order of the message: inserts, then removals, then updates
+//! - the function is never called after the despawn of the entity was received. The removal and the despawn
+//!   are not forwarded to the function: use the [`EntityDespawnEvent`](crate::client::events::EntityDespawnEvent)
+//!   to clean up your storage
+//! - the components of the join snapshot, which is sent when the client connects, go through the same path
+//! - the function must not despawn the entity. The `ConnectionManager` and `ComponentRegistry` resources are not
+//!   available in the `World` while it runs
+//! - the deltas of components with delta compression are still applied to the component
+//!
+//! Messages:
+//! - the apply function is called in `PreUpdate`, in [`InternalMainSet::EmitEvents`](crate::shared::sets::InternalMainSet),
+//!   after the replication messages received in the same frame were applied
+//! - the messages are applied in the order in which they were read from their channel. The `tick` is the server tick
+//!   of the packet that contained the message (or the latest server tick received, for the messages of a
+//!   [`SendGroup`](crate::shared::message_group::SendGroup)). A message that references an entity that was not
+//!   spawned yet is applied on a later frame, once the entity is spawned
+use bevy::prelude::{Entity, Mut, World};
+use tracing::error;
+
+use crate::client::connection::ConnectionManager;
+use crate::packet::message::Message;
+use crate::protocol::message::{MessageError, MessageKind, MessageRegistry};
+use crate::serialize::reader::Reader;
+use crate::shared::replication::deferred::bound_deferred_messages;
+use crate::shared::tick_manager::Tick;
+
+/// Function that stores a component value received from the server, instead of inserting it on
+/// the `Confirmed` entity.
+///
+/// It receives the `Confirmed` entity, the value and the server tick of the update.
+pub type ComponentApplyFn<C> = fn(world: &mut World, entity: Entity, value: C, tick: Tick);
+
+/// Function that handles a message received from the server, instead of emitting a
+/// [`MessageEvent`](crate::client::events::MessageEvent).
+///
+/// It receives the message and the server tick of the message (or the tick of the timeline, for the
+/// messages sent with `send_message_at_tick`).
+pub type MessageApplyFn<M> = fn(world: &mut World, message: M, tick: Tick);
+
+/// Call the apply function of the message `M` on the messages received from the server
+pub(crate) fn apply_messages<M: Message>(world: &mut World) {
+    world.resource_scope(|world, message_registry: Mut<MessageRegistry>| {
+        let Some(apply) = message_registry.client_apply::<M>() else {
+            return;
+        };
+        let kind = MessageKind::of::<M>();
+        let Some(net) = message_registry.kind_map.net_id(&kind).copied() else {
+            error!(
+                "Could not find the network id for the message kind: {:?}",
+                kind
+            );
+            return;
+        };
+        let Some(message_list) = world
+            .resource_mut::<ConnectionManager>()
+            .received_messages
+            .remove(&net)
+        else {
+            return;
+        };
+        let mut deferred = Vec::new();
+        for (message_bytes, tick, timeline) in message_list {
+            let mut reader = Reader::from(message_bytes.clone());
+            let message = world.resource_scope(|world, mut connection: Mut<ConnectionManager>| {
+                match message_registry.deserialize_spawned::<M>(
+                    &mut reader,
+                    &mut connection
+                        .replication_receiver
+                        .remote_entity_map
+                        .remote_to_local,
+                    world.entities(),
+                ) {
+                    Ok(message) => Some(message),
+                    Err(MessageError::UnspawnedEntity(_)) => {
+                        deferred.push((message_bytes, tick, timeline));
+                        None
+                    }
+                    Err(_) => {
+                        error!("Could not deserialize message");
+                        None
+                    }
+                }
+            });
+            if let Some(message) = message {
+                apply(world, message, tick);
+            }
+        }
+        if !deferred.is_empty() {
+            let mut connection = world.resource_mut::<ConnectionManager>();
+            bound_deferred_messages(&mut deferred, &mut connection.warnings);
+            connection.received_messages.insert(net, deferred);
+        }
+    });
+}
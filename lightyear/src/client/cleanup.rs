@@ -0,0 +1,237 @@
+//! Defines what happens to the entities received from the server once they stop being replicated.
+//!
+//! A replicated entity stops being replicated to the client when:
+//! - the entity lost its relevance for the client, or the client was removed from its replication target
+//!   ([`CleanupCause::InterestLoss`])
+//! - the entity was despawned on the server ([`CleanupCause::ServerDespawn`])
+//! - the client disconnected ([`CleanupCause::Disconnect`])
+//! - the client started a new connection without disconnecting first ([`CleanupCause::Reconnect`])
+//!
+//! For each cause, the [`ClientCleanupPolicy`] decides if the entity is despawned, or if only its networked
+//! components are removed. In the second case, the components that were added locally to the entity
+//! (meshes, sprites, sounds, gameplay state, etc.) are kept and the entity becomes a regular local entity.
+//!
+//! The networked components are:
+//! - the components registered in the protocol
+//! - the components that link the entity to the server: [`Replicated`], [`Confirmed`], [`Predicted`], [`Interpolated`]
+//! - the prediction and interpolation histories of the protocol components
+//!
+//! The cleanup is applied to the confirmed entity and to its [`Predicted`] and [`Interpolated`] entities.
+//! Children are despawned with their parent in [`CleanupAction::DespawnEntities`] mode, but are not
+//! affected in [`CleanupAction::StripNetworkedComponents`] mode.
+//!
+//! An [`EntityCleanupEvent`] is emitted for every cleaned up entity.
+use bevy::prelude::{
+    Commands, DespawnRecursiveExt, Entity, Event, Mut, Or, Query, Reflect, Res, With, World,
+};
+use tracing::debug;
+
+use crate::client::components::Confirmed;
+use crate::client::config::ClientConfig;
+use crate::client::events::EntityCleanupEvent;
+use crate::client::interpolation::resource::InterpolationManager;
+use crate::client::interpolation::Interpolated;
+use crate::client::prediction::despawn::PredictionDespawnMarker;
+use crate::client::prediction::resource::PredictionManager;
+use crate::client::prediction::Predicted;
+use crate::protocol::component::ComponentRegistry;
+use crate::shared::replication::components::Replicated;
+
+/// Reason why an entity received from the server is cleaned up
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Reflect)]
+pub enum CleanupCause {
+    /// The entity is still present on the server, but it is not replicated to the client anymore
+    InterestLoss,
+    /// The entity was despawned on the server
+    ServerDespawn,
+    /// The client disconnected from the server
+    Disconnect,
+    /// The client started a new connection while it was still connected
+    Reconnect,
+}
+
+/// What to do with an entity received from the server when it is cleaned up
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Reflect)]
+pub enum CleanupAction {
+    /// Despawn the entity, its children and its predicted/interpolated entities
+    #[default]
+    DespawnEntities,
+    /// Only remove the networked components, and keep the components that were added locally
+    StripNetworkedComponents,
+}
+
+/// Chooses the [`CleanupAction`] for each [`CleanupCause`].
+///
+/// By default, the entities are despawned for every cause.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Reflect)]
+pub struct ClientCleanupPolicy {
+    pub interest_loss: CleanupAction,
+    pub server_despawn: CleanupAction,
+    pub disconnect: CleanupAction,
+    pub reconnect: CleanupAction,
+}
+
+impl ClientCleanupPolicy {
+    /// Use the same action for every cause
+    pub fn all(action: CleanupAction) -> Self {
+        Self {
+            interest_loss: action,
+            server_despawn: action,
+            disconnect: action,
+            reconnect: action,
+        }
+    }
+
+    /// Set the action used for the given cause
+    pub fn with_action(mut self, cause: CleanupCause, action: CleanupAction) -> Self {
+        *self.action_mut(cause) = action;
+        self
+    }
+
+    /// Action used for the given cause
+    pub fn action(&self, cause: CleanupCause) -> CleanupAction {
+        match cause {
+            CleanupCause::InterestLoss => self.interest_loss,
+            CleanupCause::ServerDespawn => self.server_despawn,
+            CleanupCause::Disconnect => self.disconnect,
+            CleanupCause::Reconnect => self.reconnect,
+        }
+    }
+
+    fn action_mut(&mut self, cause: CleanupCause) -> &mut CleanupAction {
+        match cause {
+            CleanupCause::InterestLoss => &mut self.interest_loss,
+            CleanupCause::ServerDespawn => &mut self.server_despawn,
+            CleanupCause::Disconnect => &mut self.disconnect,
+            CleanupCause::Reconnect => &mut self.reconnect,
+        }
+    }
+}
+
+/// Triggered on an entity after its networked components were stripped, so that the observers of each
+/// protocol component can remove the prediction/interpolation components that they manage
+#[derive(Event)]
+pub(crate) struct NetworkedComponentsStripped;
+
+/// Apply the `action` to the `entity` and to its predicted/interpolated entities,
+/// then emit an [`EntityCleanupEvent`]
+pub(crate) fn cleanup_entity(
+    world: &mut World,
+    entity: Entity,
+    cause: CleanupCause,
+    action: CleanupAction,
+    component_registry: &ComponentRegistry,
+) {
+    let Some(entity_ref) = world.get_entity(entity) else {
+        return;
+    };
+    let (predicted, interpolated) = entity_ref
+        .get::<Confirmed>()
+        .map_or((None, None), |confirmed| {
+            (confirmed.predicted, confirmed.interpolated)
+        });
+    debug!(?entity, ?cause, ?action, "Cleaning up replicated entity");
+    match action {
+        CleanupAction::DespawnEntities => {
+            // the predicted and interpolated entities are despawned by the observers on `Confirmed`
+            world.entity_mut(entity).despawn_recursive();
+        }
+        CleanupAction::StripNetworkedComponents => {
+            // strip the confirmed entity first, so that the component removals are propagated
+            // to the predicted/interpolated entities while they are still linked to it
+            strip_networked_components(world, entity, component_registry);
+            predicted
+                .into_iter()
+                .chain(interpolated)
+                .for_each(|e| strip_networked_components(world, e, component_registry));
+        }
+    }
+    world.send_event(EntityCleanupEvent {
+        entity,
+        predicted,
+        interpolated,
+        cause,
+        action,
+    });
+}
+
+/// Remove the networked components of the entity, but keep the entity alive
+fn strip_networked_components(
+    world: &mut World,
+    entity: Entity,
+    component_registry: &ComponentRegistry,
+) {
+    // unlink the predicted and interpolated entities, so that they don't get despawned
+    // when `Confirmed` is removed
+    if let Some(mut manager) = world.get_resource_mut::<PredictionManager>() {
+        manager
+            .predicted_entity_map
+            .get_mut()
+            .confirmed_to_predicted
+            .remove(&entity);
+    }
+    if let Some(mut manager) = world.get_resource_mut::<InterpolationManager>() {
+        manager
+            .interpolated_entity_map
+            .get_mut()
+            .confirmed_to_interpolated
+            .remove(&entity);
+    }
+    let Some(mut entity_mut) = world.get_entity_mut(entity) else {
+        return;
+    };
+    component_registry.remove_all(&mut entity_mut);
+    entity_mut.remove::<(
+        Replicated,
+        Confirmed,
+        Predicted,
+        Interpolated,
+        PredictionDespawnMarker,
+    )>();
+    world.trigger_targets(NetworkedComponentsStripped, entity);
+    world.flush_commands();
+}
+
+/// Clean up all the entities received from the server when the connection is lost (or replaced)
+pub(crate) fn cleanup_received_entities(
+    cause: CleanupCause,
+    config: &ClientConfig,
+    commands: &mut Commands,
+    received_entities: &Query<
+        (Entity, Option<&Predicted>, Option<&Interpolated>),
+        Or<(With<Replicated>, With<Predicted>, With<Interpolated>)>,
+    >,
+) {
+    let action = config.cleanup.action(cause);
+    for (entity, predicted, interpolated) in received_entities.iter() {
+        // predicted and interpolated entities are cleaned up with their confirmed entity
+        let confirmed_entity = predicted
+            .and_then(|p| p.confirmed_entity)
+            .or(interpolated.map(|i| i.confirmed_entity));
+        if confirmed_entity.is_some_and(|e| received_entities.contains(e)) {
+            continue;
+        }
+        commands.add(move |world: &mut World| {
+            world.resource_scope(|world, component_registry: Mut<ComponentRegistry>| {
+                cleanup_entity(world, entity, cause, action, &component_registry);
+            });
+        });
+    }
+}
+
+/// Clean up the entities of the previous connection when the client reconnects without disconnecting first
+pub(crate) fn cleanup_on_reconnect(
+    config: Res<ClientConfig>,
+    mut commands: Commands,
+    received_entities: Query<
+        (Entity, Option<&Predicted>, Option<&Interpolated>),
+        Or<(With<Replicated>, With<Predicted>, With<Interpolated>)>,
+    >,
+) {
+    cleanup_received_entities(
+        CleanupCause::Reconnect,
+        &config,
+        &mut commands,
+        &received_entities,
+    );
+}
@@ -1,17 +1,25 @@
 //! Defines client-specific configuration options
 use bevy::prelude::Resource;
 use bevy::reflect::Reflect;
+use bevy::utils::Duration;
 use governor::Quota;
 use nonzero_ext::nonzero;
 
+use crate::client::cleanup::ClientCleanupPolicy;
+use crate::client::connect::ConnectConfig;
 use crate::client::input::native::InputConfig;
 use crate::client::interpolation::plugin::InterpolationConfig;
 use crate::client::prediction::plugin::PredictionConfig;
 use crate::client::sync::SyncConfig;
 use crate::connection::client::NetConfig;
+use crate::connection::netcode::FallbackTransport;
+use crate::packet::receive_queue::ReceiveQueueConfig;
+use crate::shared::action::ActionConfig;
+use crate::shared::request::RequestConfig;
 use crate::shared::config::SharedConfig;
 use crate::shared::ping::manager::PingConfig;
 use crate::shared::replication::plugin::ReplicationConfig;
+use crate::shared::serialized_size::SerializedSizeConfig;
 
 #[derive(Clone, Reflect)]
 /// Config related to the netcode protocol (abstraction of a connection over raw UDP-like transport)
@@ -26,6 +34,15 @@ pub struct NetcodeConfig {
     /// Set the duration in seconds after which the `ConnectToken` generated by the Client
     /// will expire. Set a negative value for the token to never expire.
     pub token_expire_secs: i32,
+    #[reflect(ignore)]
+    /// Transports that the client can switch to, in order, if the current transport stops receiving packets
+    /// while the session is connected. The next fallback transport is kept open and probed while connected.
+    pub fallback_transports: Vec<FallbackTransport>,
+    /// Switch to the fallback transport if nothing was received on the current transport for this long.
+    /// This should be lower than `client_timeout_secs`, or the connection will time out first.
+    pub fallback_unhealthy_after: Duration,
+    /// Interval between two probes of the fallback transport
+    pub fallback_probe_interval: Duration,
 }
 
 impl Default for NetcodeConfig {
@@ -35,6 +52,9 @@ impl Default for NetcodeConfig {
             keepalive_packet_send_rate: 1.0 / 10.0,
             client_timeout_secs: 3,
             token_expire_secs: 30,
+            fallback_transports: vec![],
+            fallback_unhealthy_after: Duration::from_millis(1000),
+            fallback_probe_interval: Duration::from_millis(250),
         }
     }
 }
@@ -60,6 +80,21 @@ pub struct PacketConfig {
     pub send_bandwidth_cap: Quota,
     /// If false, there is no bandwidth cap and all messages are sent as soon as possible
     pub bandwidth_cap_enabled: bool,
+    /// How long we wait for a missing unreliable message of a
+    /// [`SendGroup`](crate::shared::message_group::SendGroup) before delivering the next messages of the group
+    pub message_group_timeout: Duration,
+    #[reflect(ignore)]
+    /// Maximum rate at which packets can be sent to unconnected endpoints with
+    /// [`ConnectionManager::send_unconnected`](crate::client::connection::ConnectionManager::send_unconnected)
+    pub unconnected_send_quota: Quota,
+    /// Minimum interval between two logs of the same [`NetworkWarning`](crate::shared::warnings::NetworkWarning)
+    /// category; the warnings in-between are only counted
+    pub warning_log_interval: Duration,
+    /// Bounds of the queue of the packets received from the server that are waiting to be processed
+    /// by the channels
+    pub receive_queue: ReceiveQueueConfig,
+    /// Tracking of the serialized size of the messages and components that are sent
+    pub serialized_size: SerializedSizeConfig,
 }
 
 impl Default for PacketConfig {
@@ -69,6 +104,12 @@ impl Default for PacketConfig {
             // 56 KB/s bandwidth cap
             send_bandwidth_cap: Quota::per_second(nonzero!(56000u32)),
             bandwidth_cap_enabled: false,
+            message_group_timeout: Duration::from_millis(200),
+            // 10 packets per second
+            unconnected_send_quota: Quota::per_second(nonzero!(10u32)),
+            warning_log_interval: Duration::from_secs(5),
+            receive_queue: ReceiveQueueConfig::default(),
+            serialized_size: SerializedSizeConfig::default(),
         }
     }
 }
@@ -118,10 +159,18 @@ pub struct ClientConfig {
     pub shared: SharedConfig,
     pub packet: PacketConfig,
     pub net: NetConfig,
+    /// Timeout and retries of the connection attempts
+    pub connect: ConnectConfig,
     pub input: InputConfig,
     pub ping: PingConfig,
     pub sync: SyncConfig,
     pub replication: ReplicationConfig,
     pub prediction: PredictionConfig,
     pub interpolation: InterpolationConfig,
+    /// What to do with the entities received from the server once they stop being replicated
+    pub cleanup: ClientCleanupPolicy,
+    /// Timeout of the actions waiting for their resolution by the server
+    pub action: ActionConfig,
+    /// Timeout of the requests sent to the server
+    pub request: RequestConfig,
 }
@@ -0,0 +1,307 @@
+//! Control of the connection attempts of the client: timeout, cancellation and retries.
+//!
+//! A connection attempt starts when the client enters [`NetworkingState::Connecting`]. It fails if:
+//! - it takes longer than [`ConnectConfig::timeout`]
+//! - it is cancelled with [`ClientCommands::cancel_connect`](crate::client::networking::ClientCommands::cancel_connect)
+//! - the handshake or the transport fails
+//!
+//! Failed attempts are retried according to the [`ConnectRetryConfig`] (except cancelled ones). When the
+//! client gives up, a [`ConnectionFailedEvent`] is emitted.
+use std::sync::Arc;
+
+use bevy::prelude::{NextState, Real, Res, ResMut, Resource, State, Time, Timer, TimerMode, World};
+use bevy::reflect::Reflect;
+use bevy::utils::Duration;
+use tracing::{debug, info};
+
+use crate::client::config::ClientConfig;
+use crate::client::error::ConnectError;
+use crate::client::events::ConnectionFailedEvent;
+use crate::client::networking::NetworkingState;
+use crate::connection::client::{
+    Authentication, ClientConnection, ConnectionState, DisconnectReason, NetClient, NetConfig,
+};
+use crate::connection::netcode::ClientState;
+
+/// Configuration of the connection attempts
+#[derive(Clone, Copy, Debug, Default, PartialEq, Reflect)]
+pub struct ConnectConfig {
+    /// Maximum duration of a connection attempt, until the handshake with the server completes.
+    ///
+    /// This is independent of the expiry of the `ConnectToken`. If `None`, the attempt only fails
+    /// when the handshake times out.
+    pub timeout: Option<Duration>,
+    /// How failed connection attempts are retried
+    pub retry: ConnectRetryConfig,
+}
+
+impl ConnectConfig {
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn with_retry(mut self, retry: ConnectRetryConfig) -> Self {
+        self.retry = retry;
+        self
+    }
+}
+
+/// Retry policy for the connection attempts that fail.
+///
+/// Cancelled attempts are never retried.
+#[derive(Clone, Copy, Debug, PartialEq, Reflect)]
+pub struct ConnectRetryConfig {
+    /// Maximum number of connection attempts, including the first one
+    pub attempts: u32,
+    /// Delay before the first retry. The delay doubles for each subsequent retry.
+    pub backoff: Duration,
+}
+
+impl Default for ConnectRetryConfig {
+    fn default() -> Self {
+        Self {
+            attempts: 1,
+            backoff: Duration::from_millis(500),
+        }
+    }
+}
+
+impl ConnectRetryConfig {
+    /// Delay before starting the attempt that follows the attempt number `attempt` (starting at 1)
+    pub(crate) fn delay(&self, attempt: u32) -> Duration {
+        self.backoff
+            .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+    }
+}
+
+/// Hook that provides the [`Authentication`] of each connection attempt.
+///
+/// If this resource exists, it is called before every connection attempt, including the retries.
+/// The `Authentication` it returns replaces the one of the [`NetConfig::Netcode`] config, so that each
+/// attempt can use a fresh `ConnectToken`. If it returns `None`, the current `Authentication` is kept.
+#[derive(Resource, Clone)]
+pub struct ConnectTokenProvider(Arc<dyn Fn() -> Option<Authentication> + Send + Sync>);
+
+impl ConnectTokenProvider {
+    pub fn new(provider: impl Fn() -> Option<Authentication> + Send + Sync + 'static) -> Self {
+        Self(Arc::new(provider))
+    }
+}
+
+/// State of the current connection attempt
+#[derive(Resource, Debug, Default)]
+pub struct ConnectAttempt {
+    /// Number of the current (or last) attempt, starting at 1
+    attempt: u32,
+    /// Time spent in the current attempt
+    elapsed: Duration,
+    /// True while the client is connecting
+    in_progress: bool,
+    /// True if the next attempt is a retry of the previous one
+    retrying: bool,
+    /// Timer before the next retry, if a retry is scheduled
+    retry_timer: Option<Timer>,
+}
+
+impl ConnectAttempt {
+    /// Number of the current (or last) connection attempt, starting at 1
+    pub fn attempt(&self) -> u32 {
+        self.attempt
+    }
+
+    /// Returns true if the client is waiting before retrying to connect
+    pub fn is_retry_pending(&self) -> bool {
+        self.retry_timer.is_some()
+    }
+
+    /// Start a new attempt
+    pub(crate) fn start(&mut self) {
+        if std::mem::take(&mut self.retrying) {
+            self.attempt += 1;
+        } else {
+            self.attempt = 1;
+        }
+        self.elapsed = Duration::ZERO;
+        self.in_progress = true;
+        self.retry_timer = None;
+    }
+
+    /// The attempt succeeded
+    pub(crate) fn succeed(&mut self) {
+        self.in_progress = false;
+    }
+
+    /// Called when the client disconnects: if the client was connecting, the attempt failed.
+    ///
+    /// Returns the error if the client gives up connecting, or `None` if a retry was scheduled
+    /// (or if the client was not connecting).
+    pub(crate) fn fail(
+        &mut self,
+        reason: Option<&DisconnectReason>,
+        retry: &ConnectRetryConfig,
+    ) -> Option<ConnectError> {
+        if !std::mem::take(&mut self.in_progress) {
+            return None;
+        }
+        let error = connect_error(reason);
+        if error.is_retryable() && self.attempt < retry.attempts {
+            let delay = retry.delay(self.attempt);
+            info!(
+                ?error,
+                attempt = self.attempt,
+                ?delay,
+                "Connection attempt failed, retrying"
+            );
+            self.retry_timer = Some(Timer::new(delay, TimerMode::Once));
+            return None;
+        }
+        Some(error)
+    }
+}
+
+/// Error of a connection attempt that ended because of `reason`
+fn connect_error(reason: Option<&DisconnectReason>) -> ConnectError {
+    match reason {
+        // the user disconnected the client while it was connecting
+        None => ConnectError::Cancelled,
+        Some(DisconnectReason::Connect(error)) => error.clone(),
+        Some(DisconnectReason::Netcode(state)) => ConnectError::Netcode(*state),
+        Some(DisconnectReason::Denied(reason)) => ConnectError::Denied(reason.clone()),
+        // the server can only close the connection once it is established
+        Some(DisconnectReason::Kicked(_)) => ConnectError::Netcode(ClientState::Disconnected),
+        Some(DisconnectReason::Transport(error)) => ConnectError::Transport(error.to_string()),
+        #[cfg(all(feature = "steam", not(target_family = "wasm")))]
+        Some(DisconnectReason::Steam(end)) => ConnectError::Transport(format!("{end:?}")),
+    }
+}
+
+/// Replace the [`Authentication`] of the client config with the one given by the [`ConnectTokenProvider`]
+pub(crate) fn apply_token_provider(world: &mut World) {
+    let Some(provider) = world.get_resource::<ConnectTokenProvider>().cloned() else {
+        return;
+    };
+    let Some(new_auth) = (provider.0)() else {
+        return;
+    };
+    if let NetConfig::Netcode { auth, .. } = &mut world.resource_mut::<ClientConfig>().net {
+        debug!("Using a new Authentication from the ConnectTokenProvider");
+        *auth = new_auth;
+    }
+}
+
+/// Abort the connection attempt if it takes longer than [`ConnectConfig::timeout`]
+pub(crate) fn check_connect_timeout(
+    config: Res<ClientConfig>,
+    time: Res<Time<Real>>,
+    mut attempt: ResMut<ConnectAttempt>,
+    mut netclient: ResMut<ClientConnection>,
+    mut next_state: ResMut<NextState<NetworkingState>>,
+) {
+    if !attempt.in_progress {
+        return;
+    }
+    attempt.elapsed += time.delta();
+    let Some(timeout) = config.connect.timeout else {
+        return;
+    };
+    if attempt.elapsed >= timeout && matches!(netclient.state(), ConnectionState::Connecting) {
+        info!(?timeout, "Connection attempt timed out");
+        netclient.disconnect_reason =
+            Some(DisconnectReason::Connect(ConnectError::Timeout(timeout)));
+        next_state.set(NetworkingState::Disconnected);
+    }
+}
+
+/// Start the next connection attempt once the retry delay has elapsed
+pub(crate) fn retry_connect(
+    time: Res<Time<Real>>,
+    mut attempt: ResMut<ConnectAttempt>,
+    mut next_state: ResMut<NextState<NetworkingState>>,
+) {
+    let Some(timer) = attempt.retry_timer.as_mut() else {
+        return;
+    };
+    if timer.tick(time.delta()).finished() {
+        attempt.retry_timer = None;
+        attempt.retrying = true;
+        next_state.set(NetworkingState::Connecting);
+    }
+}
+
+/// Cancel the connection attempt, or the retry that is scheduled
+pub(crate) fn cancel_connect(world: &mut World) {
+    let state = *world.resource::<State<NetworkingState>>().get();
+    let connect_pending = matches!(
+        world.resource::<NextState<NetworkingState>>(),
+        NextState::Pending(NetworkingState::Connecting)
+    );
+    if state == NetworkingState::Connecting {
+        info!("Cancelling the connection attempt");
+        world.resource_mut::<ClientConnection>().disconnect_reason =
+            Some(DisconnectReason::Connect(ConnectError::Cancelled));
+        world
+            .resource_mut::<NextState<NetworkingState>>()
+            .set(NetworkingState::Disconnected);
+        return;
+    }
+    let retry_pending = world.resource::<ConnectAttempt>().is_retry_pending();
+    if state == NetworkingState::Disconnected && (connect_pending || retry_pending) {
+        // the client has not started connecting yet
+        info!("Cancelling the connection before it started");
+        let mut attempt = world.resource_mut::<ConnectAttempt>();
+        attempt.retry_timer = None;
+        attempt.retrying = false;
+        *world.resource_mut::<NextState<NetworkingState>>() = NextState::Unchanged;
+        world.send_event(ConnectionFailedEvent {
+            error: ConnectError::Cancelled,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_retry_delay() {
+        let retry = ConnectRetryConfig {
+            attempts: 4,
+            backoff: Duration::from_millis(100),
+        };
+        assert_eq!(retry.delay(1), Duration::from_millis(100));
+        assert_eq!(retry.delay(2), Duration::from_millis(200));
+        assert_eq!(retry.delay(3), Duration::from_millis(400));
+    }
+
+    #[test]
+    fn test_failed_attempts() {
+        let retry = ConnectRetryConfig {
+            attempts: 2,
+            backoff: Duration::from_millis(100),
+        };
+        let mut attempt = ConnectAttempt::default();
+        // the client was not connecting
+        assert_eq!(attempt.fail(None, &retry), None);
+
+        attempt.start();
+        let reason = DisconnectReason::Connect(ConnectError::Timeout(Duration::from_secs(1)));
+        assert_eq!(attempt.fail(Some(&reason), &retry), None);
+        assert!(attempt.is_retry_pending());
+
+        // the second attempt is the last one
+        attempt.retrying = true;
+        attempt.start();
+        assert_eq!(attempt.attempt(), 2);
+        assert_eq!(
+            attempt.fail(Some(&reason), &retry),
+            Some(ConnectError::Timeout(Duration::from_secs(1)))
+        );
+
+        // cancelled attempts are not retried
+        attempt.start();
+        assert_eq!(attempt.attempt(), 1);
+        assert_eq!(attempt.fail(None, &retry), Some(ConnectError::Cancelled));
+        assert!(!attempt.is_retry_pending());
+    }
+}
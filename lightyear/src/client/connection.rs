@@ -1,52 +1,116 @@
 //! Specify how a Client sends/receives messages with a Server
 use bevy::ecs::component::Tick as BevyTick;
-use bevy::ecs::entity::MapEntities;
-use bevy::prelude::{Mut, Resource, World};
+use bevy::ecs::entity::{EntityHashMap, EntityHashSet, MapEntities};
+use bevy::prelude::{Entity, Mut, Resource, World};
 use bevy::utils::{Duration, HashMap};
 use bytes::Bytes;
+use governor::DefaultDirectRateLimiter;
+use std::net::SocketAddr;
 use tracing::{debug, trace, trace_span};
 
 use crate::channel::builder::{
-    EntityActionsChannel, EntityUpdatesChannel, PingChannel, PongChannel,
+    ChannelOccupancy, EntityActionsChannel, EntityAliasChannel, EntityUpdatesChannel,
+    InterestHintChannel, PingChannel, PongChannel, RequestChannel,
 };
 
+use crate::channel::flow_control::FlowControlStats;
+use crate::channel::stats::ChannelStats;
+use crate::channel::transfer::{TransferHandle, TransferProgress};
 use crate::channel::receivers::ChannelReceive;
-use crate::channel::senders::ChannelSend;
-use crate::client::config::ClientConfig;
+use crate::channel::senders::{ChannelSend, DedupKey};
+use crate::client::config::{ClientConfig, PacketConfig};
 use crate::client::error::ClientError;
 use crate::client::sync::SyncConfig;
-use crate::connection::netcode::MAX_PACKET_SIZE;
+use crate::connection::client::NetConfig;
+use crate::connection::netcode::{
+    MAX_PACKET_SIZE, MAX_UNCONNECTED_PAYLOAD_SIZE, UNCONNECTED_PACKET_PREFIX,
+};
+use crate::packet::message::MessageId;
 use crate::packet::message_manager::MessageManager;
 use crate::packet::packet_builder::{Payload, RecvPayload};
 use crate::packet::priority_manager::PriorityConfig;
+use crate::packet::receive_queue::{ReceiveQueue, ReceiveQueueStats};
 use crate::prelude::client::PredictionConfig;
 use crate::prelude::{Channel, ChannelKind, ClientId, Message, ReplicationConfig};
 use crate::protocol::channel::ChannelRegistry;
 use crate::protocol::component::ComponentRegistry;
-use crate::protocol::message::{MessageRegistry, MessageType};
+use crate::protocol::message::{MessageError, MessageKind, MessageRegistry, MessageType};
 use crate::protocol::registry::NetId;
 use crate::serialize::reader::Reader;
 use crate::serialize::writer::Writer;
 use crate::serialize::{SerializationError, ToBytes};
 use crate::server::error::ServerError;
+use crate::server::relevance::hint::{InterestRequestMessage, NetworkEntityId};
+use crate::shared::clock::NetworkClock;
+use crate::shared::connection_stats::{ConnectionStats, ConnectionStatsTracker};
 use crate::shared::events::connection::ConnectionEvents;
 use crate::shared::message::MessageSend;
+use crate::shared::message_group::{
+    MessageGroupHeader, MessageGroupReceiver, MessageGroupSender, SendGroup,
+};
 use crate::shared::ping::manager::{PingConfig, PingManager};
 use crate::shared::ping::message::{Ping, Pong};
+use crate::shared::replication::alias::AliasMessage;
+use crate::shared::replication::classes::ReplicationClassStats;
 use crate::shared::replication::delta::DeltaManager;
-use crate::shared::replication::entity_map::EntityMap;
+use crate::shared::replication::entity_map::{EntityMap, RemoteEntityMap};
+use crate::shared::replication::limits::ReplicationLimitStats;
 use crate::shared::replication::network_target::NetworkTarget;
 use crate::shared::replication::receive::ReplicationReceiver;
 use crate::shared::replication::send::ReplicationSender;
 use crate::shared::replication::{EntityActionsMessage, EntityUpdatesMessage, ReplicationSend};
+use crate::shared::request::{PendingRequests, Request, RequestHandle, RequestMessage};
 use crate::shared::replication::{ReplicationPeer, ReplicationReceive};
+use crate::shared::schema::NegotiatedSchemas;
+use crate::shared::serialized_size::SerializedSizes;
+use crate::shared::session_summary::{SessionStats, SessionSummary};
 use crate::shared::sets::ClientMarker;
 use crate::shared::tick_manager::Tick;
 use crate::shared::tick_manager::TickManager;
 use crate::shared::time_manager::TimeManager;
+use crate::shared::timeline_message::{
+    TimelineDelivery, TimelineMessageBuffer, TimelineMessageHeader,
+};
+use crate::shared::warnings::{NetworkWarning, NetworkWarnings};
 
 use super::sync::SyncManager;
 
+/// Counters of the data received from the server, to check what the server actually sends to this client
+/// (for example when the server uses a bandwidth cap and per-entity priorities)
+#[derive(Debug, Default, Clone)]
+pub struct ReceiveStats {
+    /// Number of bytes received, without the netcode headers
+    pub bytes: u64,
+    /// Number of packets received
+    pub packets: u64,
+    /// Number of times each entity was spawned or had components inserted or updated by replication
+    entity_updates: EntityHashMap<u32>,
+}
+
+impl ReceiveStats {
+    /// Number of times the replicated entity was spawned or had components inserted or updated.
+    ///
+    /// Multiple components inserted or updated in the same frame count as one update.
+    pub fn entity_updates(&self, entity: Entity) -> u32 {
+        self.entity_updates.get(&entity).copied().unwrap_or_default()
+    }
+
+    /// Iterate over the replicated entities and their number of updates
+    pub fn iter_entity_updates(&self) -> impl Iterator<Item = (Entity, u32)> + '_ {
+        self.entity_updates.iter().map(|(e, count)| (*e, *count))
+    }
+
+    fn record_entity_updates(&mut self, events: &ConnectionEvents) {
+        let mut updated = EntityHashSet::default();
+        updated.extend(events.spawns.iter().copied());
+        updated.extend(events.component_inserts.values().flatten().copied());
+        updated.extend(events.component_updates.values().flatten().copied());
+        for entity in updated {
+            *self.entity_updates.entry(entity).or_default() += 1;
+        }
+    }
+}
+
 /// Wrapper that handles the connection with the server
 ///
 /// This is the main [`Resource`] to use to interact with the server (send inputs, messages, etc.)
@@ -76,19 +140,52 @@ pub struct ConnectionManager {
     pub(crate) events: ConnectionEvents,
     pub ping_manager: PingManager,
     pub(crate) sync_manager: SyncManager,
+    /// Recoverable errors encountered while processing the packets from the server
+    pub(crate) warnings: NetworkWarnings,
+    /// Serialized size of the messages and components sent to the server
+    pub(crate) serialized_sizes: SerializedSizes,
+    pub(crate) receive_stats: ReceiveStats,
+    /// Packets received from the server that are waiting to be processed by the channels
+    pub(crate) receive_queue: ReceiveQueue,
+    /// Network statistics of the session, to build the [`SessionSummary`]
+    pub(crate) session_stats: SessionStats,
+    /// Live network statistics of the connection
+    pub(crate) connection_stats: ConnectionStatsTracker,
+    /// Versions of the schemas negotiated with the server
+    pub(crate) schemas: NegotiatedSchemas,
+    /// Requests sent to the server that are waiting for their response
+    pub(crate) requests: PendingRequests,
 
     /// Used to read the leafwing InputMessages from other clients
     #[cfg(feature = "leafwing")]
     pub(crate) received_leafwing_input_messages: HashMap<NetId, Vec<Bytes>>,
-    /// Used to transfer raw bytes to a system that can convert the bytes to the actual type
-    pub(crate) received_messages: HashMap<NetId, Vec<Bytes>>,
+    /// Used to transfer raw bytes to a system that can convert the bytes to the actual type.
+    ///
+    /// The messages sent at a tick of a visual timeline are stored with that tick and their [`TimelineDelivery`]
+    pub(crate) received_messages: HashMap<NetId, Vec<(Bytes, Tick, Option<TimelineDelivery>)>>,
     pub(crate) writer: Writer,
 
-    /// Internal buffer of the messages that we want to send.
-    /// We use this so that:
-    /// - in host server mode, we deserialize the bytes and push them to the server's Message Events queue directly
-    /// - in non-host server mode, we buffer the bytes to the message manager as usual
-    pub(crate) messages_to_send: Vec<(Bytes, ChannelKind)>,
+    /// Internal buffer of the messages that we want to send in host server mode:
+    /// we deserialize the bytes and push them to the server's Message Events queue directly.
+    /// In non-host server mode, the messages are buffered to the message manager immediately so
+    /// that their [`MessageId`] can be returned.
+    ///
+    /// The messages sent with [`ConnectionManager::send_message_with_key`] also store their [`DedupKey`]
+    pub(crate) messages_to_send: Vec<(Bytes, ChannelKind, Option<DedupKey>)>,
+    /// True if the client is the local client of a host server
+    local: bool,
+
+    /// Id of the next [`SendGroup`] created by this connection
+    next_send_group: u16,
+    message_group_sender: MessageGroupSender,
+    /// Grouped messages received from the server that are waiting for earlier members of their group
+    message_group_receiver: MessageGroupReceiver<Bytes>,
+    /// Messages received from the server that are waiting for their timeline to reach their tick
+    timeline_messages: TimelineMessageBuffer<Bytes>,
+
+    /// Packets that we want to send to unconnected endpoints, already prefixed with [`UNCONNECTED_PACKET_PREFIX`]
+    pub(crate) unconnected_packets_to_send: Vec<(SocketAddr, Vec<u8>)>,
+    unconnected_rate_limiter: DefaultDirectRateLimiter,
 }
 
 // NOTE: useful when we sometimes need to create a temporary fake ConnectionManager
@@ -98,6 +195,7 @@ impl Default for ConnectionManager {
             crossbeam_channel::unbounded().1,
             crossbeam_channel::unbounded().1,
             crossbeam_channel::unbounded().1,
+            crossbeam_channel::unbounded().1,
             ReplicationConfig::default(),
             false,
         );
@@ -115,12 +213,31 @@ impl Default for ConnectionManager {
             replication_receiver,
             ping_manager: PingManager::new(PingConfig::default()),
             sync_manager: SyncManager::new(SyncConfig::default(), PredictionConfig::default()),
+            warnings: NetworkWarnings::default(),
+            serialized_sizes: SerializedSizes::default(),
+            receive_stats: ReceiveStats::default(),
+            receive_queue: ReceiveQueue::default(),
+            session_stats: SessionStats::default(),
+            connection_stats: ConnectionStatsTracker::default(),
+            schemas: NegotiatedSchemas::default(),
+            requests: PendingRequests::default(),
             events: ConnectionEvents::default(),
             #[cfg(feature = "leafwing")]
             received_leafwing_input_messages: HashMap::default(),
             received_messages: HashMap::default(),
             writer: Writer::with_capacity(0),
             messages_to_send: Vec::default(),
+            local: false,
+            next_send_group: 0,
+            message_group_sender: MessageGroupSender::default(),
+            message_group_receiver: MessageGroupReceiver::new(
+                PacketConfig::default().message_group_timeout,
+            ),
+            timeline_messages: TimelineMessageBuffer::default(),
+            unconnected_packets_to_send: Vec::default(),
+            unconnected_rate_limiter: DefaultDirectRateLimiter::direct(
+                PacketConfig::default().unconnected_send_quota,
+            ),
         }
     }
 }
@@ -132,13 +249,17 @@ impl ConnectionManager {
         message_registry: &MessageRegistry,
         channel_registry: &ChannelRegistry,
         client_config: &ClientConfig,
+        clock: NetworkClock,
     ) -> Self {
         let bandwidth_cap_enabled = client_config.packet.bandwidth_cap_enabled;
         // create the message manager and the channels
         let mut message_manager = MessageManager::new(
             channel_registry,
             client_config.packet.nack_rtt_multiple,
-            client_config.packet.into(),
+            PriorityConfig {
+                class_quotas: client_config.replication.class_quotas,
+                ..client_config.packet.into()
+            },
         );
         // get notified when a replication-update message gets acked/nacked
         let entity_updates_sender = &mut message_manager
@@ -151,14 +272,25 @@ impl ConnectionManager {
         // get a channel to get notified when a replication update message gets actually send (to update priority)
         let replication_update_send_receiver =
             message_manager.get_replication_update_send_receiver();
+        // get notified when an entity alias assignment/invalidation gets acked
+        let alias_acks_receiver = message_manager
+            .channels
+            .get_mut(&ChannelKind::of::<EntityAliasChannel>())
+            .unwrap()
+            .sender
+            .subscribe_acks();
         let replication_sender = ReplicationSender::new(
             update_acks_receiver,
             update_nacks_receiver,
             replication_update_send_receiver,
+            alias_acks_receiver,
             client_config.replication,
             bandwidth_cap_enabled,
         );
-        let replication_receiver = ReplicationReceiver::new();
+        let replication_receiver = ReplicationReceiver::new()
+            .with_limits(client_config.replication.limits)
+            .with_cleanup_policy(client_config.cleanup)
+            .with_parallel_apply(client_config.replication.parallel_apply);
         Self {
             component_registry: component_registry.clone(),
             message_registry: message_registry.clone(),
@@ -168,12 +300,32 @@ impl ConnectionManager {
             replication_receiver,
             ping_manager: PingManager::new(client_config.ping),
             sync_manager: SyncManager::new(client_config.sync, client_config.prediction),
+            warnings: NetworkWarnings::new(client_config.packet.warning_log_interval)
+                .with_clock(clock),
+            serialized_sizes: SerializedSizes::new(client_config.packet.serialized_size),
+            receive_stats: ReceiveStats::default(),
+            receive_queue: ReceiveQueue::new(client_config.packet.receive_queue),
+            session_stats: SessionStats::default(),
+            connection_stats: ConnectionStatsTracker::default(),
+            schemas: NegotiatedSchemas::default(),
+            requests: PendingRequests::default(),
             events: ConnectionEvents::default(),
             #[cfg(feature = "leafwing")]
             received_leafwing_input_messages: HashMap::default(),
             received_messages: HashMap::default(),
             writer: Writer::with_capacity(MAX_PACKET_SIZE),
             messages_to_send: Vec::default(),
+            local: matches!(client_config.net, NetConfig::Local { .. }),
+            next_send_group: 0,
+            message_group_sender: MessageGroupSender::default(),
+            message_group_receiver: MessageGroupReceiver::new(
+                client_config.packet.message_group_timeout,
+            ),
+            timeline_messages: TimelineMessageBuffer::default(),
+            unconnected_packets_to_send: Vec::default(),
+            unconnected_rate_limiter: DefaultDirectRateLimiter::direct(
+                client_config.packet.unconnected_send_quota,
+            ),
         }
     }
 
@@ -183,6 +335,124 @@ impl ConnectionManager {
         self.sync_manager.is_synced()
     }
 
+    /// Counters of the recoverable errors encountered while processing the packets from the server
+    pub fn warnings(&self) -> &NetworkWarnings {
+        &self.warnings
+    }
+
+    /// Serialized size of each message and component type sent to the server, if
+    /// [`SerializedSizeConfig::enabled`](crate::shared::serialized_size::SerializedSizeConfig::enabled) is true
+    pub fn serialized_sizes(&self) -> &SerializedSizes {
+        &self.serialized_sizes
+    }
+
+    /// Counters of what was received from the server since the connection was established
+    pub fn receive_stats(&self) -> &ReceiveStats {
+        &self.receive_stats
+    }
+
+    /// Statistics of the queue of the packets received from the server that are waiting to be processed
+    pub fn receive_queue_stats(&self) -> &ReceiveQueueStats {
+        self.receive_queue.stats()
+    }
+
+    /// Counters of the spawns and insertions that were rejected because of the
+    /// [`ReplicationLimits`](crate::shared::replication::limits::ReplicationLimits)
+    pub fn replication_limit_stats(&self) -> &ReplicationLimitStats {
+        self.replication_receiver.limiter.stats()
+    }
+
+    /// Declare that the server is about to replicate a snapshot of about `bytes` bytes (for example
+    /// when the client changes level), so that it doesn't exhaust
+    /// [`ReplicationLimits::max_bytes_per_second`](crate::shared::replication::limits::ReplicationLimits::max_bytes_per_second)
+    pub fn expect_replication_snapshot(&mut self, bytes: usize) {
+        self.replication_receiver.limiter.expect_snapshot(bytes);
+    }
+
+    /// Network statistics of the session since the connection was established
+    pub fn session_summary(&self) -> SessionSummary {
+        self.session_stats
+            .summary(self.message_manager.packet_stats())
+    }
+
+    /// Latest estimates of the network statistics of the connection to the server (RTT, jitter, packet loss, bandwidth),
+    /// refreshed every [`PingConfig::ping_interval`]
+    pub fn connection_stats(&self) -> &ConnectionStats {
+        self.connection_stats.stats()
+    }
+
+    /// Version of the schema of the component or message `T` negotiated with the server, i.e. the latest version
+    /// whose fields are known by both peers (see [`versioned`](crate::serialize::versioned)).
+    ///
+    /// Returns `None` if the schema of `T` cannot evolve, or until the versions are received from the server
+    pub fn schema_version<T: 'static>(&self) -> Option<u8> {
+        self.schemas.version::<T>()
+    }
+
+    /// Returns the flow control state of a reliable channel, if it uses flow control
+    /// (see [`ReliableSettings::receive_window`](crate::channel::builder::ReliableSettings))
+    pub fn channel_flow_control_stats<C: Channel>(&self) -> Option<FlowControlStats> {
+        self.message_manager.channel_flow_control_stats::<C>()
+    }
+
+    /// Returns the [`ChannelStats`] of a channel, since the client connected
+    pub fn channel_stats(&self, kind: ChannelKind) -> Option<&ChannelStats> {
+        self.message_manager.channel_stats(kind)
+    }
+
+    /// Returns the current occupancy of the send buffer of a channel, for example to stop sending
+    /// messages before the channel is full
+    pub fn channel_occupancy(&self, kind: ChannelKind) -> Option<ChannelOccupancy> {
+        self.message_manager.channel_occupancy(kind)
+    }
+
+    /// Returns the progress of the fragmented messages that are being sent to the server (on reliable channels)
+    /// or received from the server. See [`transfer`](crate::channel::transfer) for more details.
+    pub fn transfers(&self) -> Vec<TransferProgress> {
+        self.message_manager.transfers()
+    }
+
+    /// Returns the progress of a transfer, or None if it is finished
+    pub fn transfer(&self, handle: TransferHandle) -> Option<TransferProgress> {
+        self.message_manager.transfer(handle)
+    }
+
+    /// Cancel a transfer: the remaining fragments are not sent, and the receiver discards the fragments
+    /// that it received.
+    ///
+    /// Returns false if the transfer was already finished.
+    pub fn cancel_transfer(&mut self, handle: TransferHandle) -> Result<bool, ClientError> {
+        Ok(self.message_manager.cancel_transfer(handle)?)
+    }
+
+    /// Returns the bytes of the replication messages sent to the server for each [`ReplicationClass`]
+    ///
+    /// [`ReplicationClass`]: crate::shared::replication::classes::ReplicationClass
+    pub fn replication_class_stats(&self) -> &ReplicationClassStats {
+        self.message_manager.replication_class_stats()
+    }
+
+    /// Set the number of ticks of input delay, instead of computing it from the RTT with the [`PredictionConfig`].
+    /// Use `None` to compute it from the [`PredictionConfig`] again.
+    ///
+    /// The new input delay is used from the next tick, for example to adapt it to the measured RTT:
+    /// - the inputs that were already buffered keep the tick at which they will be applied
+    /// - if the input delay increases, the ticks that are skipped repeat the last inputs that were buffered
+    /// - if the input delay decreases, the ticks that were already buffered are not overwritten until the
+    ///   current tick plus the input delay catches up with them: the native inputs are added to the last buffered
+    ///   tick, and the leafwing `ActionState` is not buffered
+    ///
+    /// so that every tick has inputs, and no tick is sent twice with different inputs.
+    pub fn set_input_delay_ticks(&mut self, input_delay_ticks: Option<u16>) {
+        self.sync_manager.set_input_delay_override(input_delay_ticks);
+    }
+
+    /// Number of ticks between the tick at which an input is buffered and the tick at which it is applied
+    pub fn input_delay_ticks(&self, tick_manager: &TickManager) -> u16 {
+        self.sync_manager
+            .input_delay_ticks(self.ping_manager.rtt(), tick_manager.config.tick_duration)
+    }
+
     /// Returns true if we received a new server packet on this frame
     pub(crate) fn received_new_server_tick(&self) -> bool {
         self.sync_manager.duration_since_latest_received_server_tick == Duration::default()
@@ -205,24 +475,24 @@ impl ConnectionManager {
             .update(time_manager, &self.ping_manager, tick_manager);
         self.replication_sender.update(world_tick);
         self.ping_manager.update(time_manager);
+        self.session_stats.update(time_manager.delta());
+        self.connection_stats.update(time_manager);
 
         // (we update the sync manager in POST_UPDATE)
     }
 
     fn send_ping(&mut self, ping: Ping) -> Result<(), ClientError> {
         trace!("Sending ping {:?}", ping);
-        let mut writer = Writer::with_capacity(ping.len());
-        ping.to_bytes(&mut writer)?;
-        let message_bytes = writer.to_bytes();
+        ping.to_bytes(&mut self.writer)?;
+        let message_bytes = self.writer.split();
         self.message_manager
             .buffer_send(message_bytes, ChannelKind::of::<PingChannel>())?;
         Ok(())
     }
 
     fn send_pong(&mut self, pong: Pong) -> Result<(), ClientError> {
-        let mut writer = Writer::with_capacity(pong.len());
-        pong.to_bytes(&mut writer)?;
-        let message_bytes = writer.to_bytes();
+        pong.to_bytes(&mut self.writer)?;
+        let message_bytes = self.writer.split();
         self.message_manager
             .buffer_send(message_bytes, ChannelKind::of::<PongChannel>())?;
         Ok(())
@@ -240,41 +510,226 @@ impl ConnectionManager {
         &mut self.replication_receiver.remote_entity_map.local_to_remote
     }
 
+    /// Ask the server to make an entity relevant to this client (`enable = true`), even if the interest
+    /// management would not replicate it otherwise, or withdraw the request (`enable = false`).
+    ///
+    /// A granted request expires after the server's
+    /// [`InterestConfig::hint_ttl`](crate::prelude::server::InterestConfig::hint_ttl), so it must be sent again
+    /// periodically while the entity is needed. The outcome is reported with an
+    /// [`InterestHintEvent`](crate::client::events::InterestHintEvent).
+    pub fn request_interest(
+        &mut self,
+        entity: NetworkEntityId,
+        enable: bool,
+    ) -> Result<(), ClientError> {
+        self.send_message::<InterestHintChannel, _>(&InterestRequestMessage { entity, enable })?;
+        Ok(())
+    }
+
+    /// Id of a replicated entity in the server's World, from the local entity
+    pub fn network_entity_id(&self, local_entity: Entity) -> Option<NetworkEntityId> {
+        self.replication_receiver
+            .remote_entity_map
+            .get_remote(local_entity)
+            .map(|remote| NetworkEntityId(*remote))
+    }
+
     /// Send a [`Message`] to the server using a specific [`Channel`]
-    pub fn send_message<C: Channel, M: Message>(&mut self, message: &M) -> Result<(), ClientError> {
+    ///
+    /// Returns the [`MessageId`] of the message on the channel, if it has one. On the channels that
+    /// track acks (reliable channels and [`ChannelMode::UnorderedUnreliableWithAcks`](crate::prelude::ChannelMode::UnorderedUnreliableWithAcks)),
+    /// a [`MessageAckEvent`](crate::client::events::MessageAckEvent) with this id is emitted when the server acks the message.
+    /// In host-server mode, the message is received by the server directly and has no id.
+    pub fn send_message<C: Channel, M: Message>(
+        &mut self,
+        message: &M,
+    ) -> Result<Option<MessageId>, ClientError> {
         self.send_message_to_target::<C, M>(message, NetworkTarget::None)
     }
 
     /// Send a [`Message`] to the server using a specific [`Channel`]
     ///
-    /// The message will be sent to the server and re-broadcasted to all clients that match the [`NetworkTarget`]
+    /// The message will be sent to the server and re-broadcasted to all clients that match the [`NetworkTarget`].
+    /// The returned [`MessageId`] is acked by the server, not by the other clients.
     pub fn send_message_to_target<C: Channel, M: Message>(
         &mut self,
         message: &M,
         target: NetworkTarget,
-    ) -> Result<(), ClientError> {
+    ) -> Result<Option<MessageId>, ClientError> {
         self.erased_send_message_to_target(message, ChannelKind::of::<C>(), target)
     }
 
-    /// Serialize a message and buffer it internally so that it can be sent later
+    /// Send a [`Request`] to the server (see [`request`](crate::shared::request)).
+    ///
+    /// The returned [`RequestHandle`] receives the response of the server, or fails if the server did not respond
+    /// before the [`RequestConfig::timeout`](crate::shared::request::RequestConfig::timeout).
+    pub fn send_request<R: Request>(
+        &mut self,
+        request: R,
+    ) -> Result<RequestHandle<R::Response>, ClientError> {
+        let id = self.requests.next_id();
+        self.send_message::<RequestChannel, _>(&RequestMessage { id, request })?;
+        Ok(self.requests.insert(id, None))
+    }
+
+    /// Serialize a message and buffer it so that it can be sent later
     fn erased_send_message_to_target<M: Message>(
         &mut self,
         message: &M,
         channel_kind: ChannelKind,
         target: NetworkTarget,
-    ) -> Result<(), ClientError> {
+    ) -> Result<Option<MessageId>, ClientError> {
         // write the target first
         // NOTE: this is ok to do because most of the time (without rebroadcast, this just adds 1 byte)
         target.to_bytes(&mut self.writer)?;
         // then write the message
-        self.message_registry.serialize(message, &mut self.writer)?;
+        self.serialize_message(message)?;
         let message_bytes = self.writer.split();
 
         // TODO: emit logs/metrics about the message being buffered?
-        self.messages_to_send.push((message_bytes, channel_kind));
+        self.buffer_message(message_bytes, channel_kind, None)
+    }
+
+    /// Serialize the message in the writer, and record its size
+    fn serialize_message<M: Message>(&mut self, message: &M) -> Result<(), ClientError> {
+        let start = self.writer.len();
+        self.message_registry.serialize(message, &mut self.writer)?;
+        self.serialized_sizes.record_message(
+            MessageKind::of::<M>(),
+            &self.message_registry,
+            self.writer.len() - start,
+            &mut self.warnings,
+        );
         Ok(())
     }
 
+    /// Buffer the bytes of a message to the message manager, or internally in host-server mode
+    fn buffer_message(
+        &mut self,
+        message_bytes: Bytes,
+        channel_kind: ChannelKind,
+        key: Option<DedupKey>,
+    ) -> Result<Option<MessageId>, ClientError> {
+        if self.local {
+            self.messages_to_send
+                .push((message_bytes, channel_kind, key));
+            return Ok(None);
+        }
+        let message_id = match key {
+            Some(key) => {
+                self.message_manager
+                    .buffer_send_with_key(message_bytes, channel_kind, key)?
+            }
+            None => self
+                .message_manager
+                .buffer_send(message_bytes, channel_kind)?,
+        };
+        Ok(message_id)
+    }
+
+    /// Send a [`Message`] to the server using a specific [`Channel`], replacing the message of the same type
+    /// that was previously sent with the same `key` if it has not been transmitted yet.
+    ///
+    /// This is useful to send the latest version of some state on a reliable channel: during bursts, only
+    /// the newest version is actually sent. The messages that are already in flight are not affected.
+    /// The replacements are counted in [`ChannelStats::messages_replaced`].
+    ///
+    /// Returns the [`MessageId`] of the message like [`ConnectionManager::send_message`].
+    pub fn send_message_with_key<C: Channel, M: Message>(
+        &mut self,
+        message: &M,
+        key: u64,
+    ) -> Result<Option<MessageId>, ClientError> {
+        NetworkTarget::None.to_bytes(&mut self.writer)?;
+        self.serialize_message(message)?;
+        let message_bytes = self.writer.split();
+        self.buffer_message(
+            message_bytes,
+            ChannelKind::of::<C>(),
+            Some(DedupKey::new::<M>(key)),
+        )
+    }
+
+    /// Send a raw packet to an arbitrary address, using the same socket as the connection to the server.
+    ///
+    /// This is an escape hatch to implement NAT punch-through: the packets go through the client's socket,
+    /// so the NAT mapping that they open also applies to the game traffic.
+    /// - the payload must be at most [`MAX_UNCONNECTED_PAYLOAD_SIZE`] bytes
+    /// - the packets are rate-limited by [`PacketConfig::unconnected_send_quota`]
+    /// - the packets are prefixed with [`UNCONNECTED_PACKET_PREFIX`], so that a remote lightyear client
+    ///   surfaces them as an [`UnconnectedPacketEvent`](crate::client::events::UnconnectedPacketEvent)
+    ///   and a lightyear server ignores them
+    ///
+    /// The packets are sent at the end of the frame, and only if the netcode transport is used and the client
+    /// is connecting or connected.
+    pub fn send_unconnected(
+        &mut self,
+        addr: SocketAddr,
+        payload: &[u8],
+    ) -> Result<(), ClientError> {
+        if payload.len() > MAX_UNCONNECTED_PAYLOAD_SIZE {
+            return Err(ClientError::UnconnectedPacketTooLarge(payload.len()));
+        }
+        if self.unconnected_rate_limiter.check().is_err() {
+            return Err(ClientError::UnconnectedPacketRateLimited);
+        }
+        let mut packet = Vec::with_capacity(UNCONNECTED_PACKET_PREFIX.len() + payload.len());
+        packet.extend_from_slice(UNCONNECTED_PACKET_PREFIX);
+        packet.extend_from_slice(payload);
+        self.unconnected_packets_to_send.push((addr, packet));
+        Ok(())
+    }
+
+    /// Create a new [`SendGroup`], to order messages sent on different channels.
+    ///
+    /// See [`message_group`](crate::shared::message_group) for more details.
+    pub fn new_send_group(&mut self) -> SendGroup {
+        let group = SendGroup(self.next_send_group);
+        self.next_send_group = self.next_send_group.wrapping_add(1);
+        group
+    }
+
+    /// Send a [`Message`] to the server using a specific [`Channel`].
+    ///
+    /// The server will receive it only after all the messages that were previously sent with the same [`SendGroup`],
+    /// even if they were sent on other channels.
+    pub fn send_message_in_group<C: Channel, M: Message>(
+        &mut self,
+        message: &M,
+        group: SendGroup,
+    ) -> Result<Option<MessageId>, ClientError> {
+        self.send_message_to_target_in_group::<C, M>(message, NetworkTarget::None, group)
+    }
+
+    /// Send a [`Message`] to the server as part of a [`SendGroup`].
+    ///
+    /// The ordering only applies to the server: messages re-broadcasted to other clients are not part of the group.
+    pub fn send_message_to_target_in_group<C: Channel, M: Message>(
+        &mut self,
+        message: &M,
+        target: NetworkTarget,
+        group: SendGroup,
+    ) -> Result<Option<MessageId>, ClientError> {
+        let channel_kind = ChannelKind::of::<C>();
+        let reliable = self
+            .message_manager
+            .channel_registry
+            .get_builder_from_kind(&channel_kind)
+            .ok_or::<ClientError>(MessageError::NotRegistered.into())?
+            .settings
+            .mode
+            .is_reliable();
+        let header = self.message_group_sender.next_header(group, reliable);
+        target.to_bytes(&mut self.writer)?;
+        self.message_registry
+            .message_group_net_id()?
+            .to_bytes(&mut self.writer)?;
+        header.to_bytes(&mut self.writer)?;
+        self.serialize_message(message)?;
+        let message_bytes = self.writer.split();
+        self.buffer_message(message_bytes, channel_kind, None)
+    }
+
     pub(crate) fn buffer_replication_messages(
         &mut self,
         tick: Tick,
@@ -302,6 +757,7 @@ impl ConnectionManager {
             bevy_tick,
             &mut self.writer,
             &mut self.message_manager,
+            None,
         )?;
         Ok(())
     }
@@ -315,9 +771,10 @@ impl ConnectionManager {
         server_manager: &mut crate::server::connection::ConnectionManager,
     ) -> Result<(), ServerError> {
         // go through messages_to_send, deserialize them and make the server receive them
+        // (the dedup keys are ignored since the messages are received immediately)
         self.messages_to_send
             .drain(..)
-            .try_for_each(|(message_bytes, channel_kind)| {
+            .try_for_each(|(message_bytes, channel_kind, _)| {
                 server_manager
                     .connection_mut(local_client_id)?
                     .receive_message(
@@ -333,6 +790,7 @@ impl ConnectionManager {
     /// Send packets that are ready to be sent.
     /// In non-host-server mode:
     /// - go through messages_to_send, buffer them to the message manager and then send packets that are ready
+    ///   (the messages are normally buffered to the message manager as soon as they are sent)
     pub(crate) fn send_packets(
         &mut self,
         time_manager: &TimeManager,
@@ -345,6 +803,12 @@ impl ConnectionManager {
         // same thing, we want the correct send time for the ping
         // (and not have the delay between when we prepare the ping and when we send the packet)
         if let Some(ping) = self.ping_manager.maybe_prepare_ping(time_manager) {
+            // the connection stats are refreshed at every ping interval
+            self.connection_stats.refresh(
+                &self.ping_manager,
+                self.message_manager.packet_stats(),
+                self.message_manager.messages_resent(),
+            );
             self.send_ping(ping)?;
         }
 
@@ -364,20 +828,41 @@ impl ConnectionManager {
         // buffer the messages into the message manager
         self.messages_to_send
             .drain(..)
-            .try_for_each(|(message_bytes, channel_kind)| {
-                self.message_manager
-                    .buffer_send(message_bytes, channel_kind)?;
+            .try_for_each(|(message_bytes, channel_kind, key)| {
+                match key {
+                    Some(key) => self.message_manager.buffer_send_with_key(
+                        message_bytes,
+                        channel_kind,
+                        key,
+                    )?,
+                    None => self
+                        .message_manager
+                        .buffer_send(message_bytes, channel_kind)?,
+                };
                 Ok::<(), ClientError>(())
             })?;
 
         // get the payloads from the message manager
         let payloads = self.message_manager.send_packets(tick_manager.tick());
+        if let Ok(payloads) = &payloads {
+            payloads.iter().for_each(|payload| {
+                self.session_stats.record_sent(payload.len());
+                self.connection_stats.record_sent(payload.len());
+            });
+        }
 
         // update the replication sender about which messages were actually sent, and accumulate priority
         self.replication_sender.recv_send_notification();
+        self.writer.end_frame();
         payloads.map_err(Into::into)
     }
 
+    /// Give back the payloads returned by [`ConnectionManager::send_packets`] once they have been sent,
+    /// so that their allocations can be reused
+    pub(crate) fn recycle_payloads(&mut self, payloads: Vec<Payload>) {
+        self.message_manager.recycle_payloads(payloads);
+    }
+
     pub(crate) fn receive(
         &mut self,
         // TODO: use Commands to avoid blocking the world?
@@ -386,6 +871,9 @@ impl ConnectionManager {
         tick_manager: &TickManager,
     ) -> Result<(), ClientError> {
         let _span = trace_span!("receive").entered();
+        let group_net_id = self.message_registry.message_group_net_id().ok();
+        let timeline_net_id = self.message_registry.timeline_message_net_id().ok();
+        self.replication_receiver.limiter.update(time_manager.delta());
         self.message_manager
             .channels
             .iter_mut()
@@ -409,8 +897,13 @@ impl ConnectionManager {
                     } else if *channel_kind == ChannelKind::of::<PongChannel>() {
                         let pong = Pong::from_bytes(&mut reader)?;
                         // process the pong
-                        self.ping_manager
-                            .process_pong(&pong, time_manager.current_time());
+                        if let Some(rtt) = self.ping_manager.process_pong(
+                            &pong,
+                            time_manager.current_time(),
+                            &mut self.warnings,
+                        ) {
+                            self.session_stats.record_rtt(rtt);
+                        }
                         // TODO: a bit dangerous because we want:
                         // - real time when computing RTT
                         // - virtual time when computing the generation
@@ -427,15 +920,43 @@ impl ConnectionManager {
                             "Updated server pong generation"
                         )
                     } else if *channel_kind == ChannelKind::of::<EntityActionsChannel>() {
+                        self.replication_receiver.limiter.record_bytes(reader.len());
                         let actions = EntityActionsMessage::from_bytes(&mut reader)?;
                         self.replication_receiver.recv_actions(actions, tick);
                     } else if *channel_kind == ChannelKind::of::<EntityUpdatesChannel>() {
-                        let updates = EntityUpdatesMessage::from_bytes(&mut reader)?;
+                        self.replication_receiver.limiter.record_bytes(reader.len());
+                        let updates = EntityUpdatesMessage::from_bytes_with_aliases(
+                            &mut reader,
+                            &self.replication_receiver.entity_aliases,
+                            tick,
+                            &mut self.warnings,
+                        )?;
                         self.replication_receiver.recv_updates(updates, tick);
+                    } else if *channel_kind == ChannelKind::of::<EntityAliasChannel>() {
+                        let message = AliasMessage::from_bytes(&mut reader)?;
+                        self.replication_receiver.entity_aliases.receive(message);
                     } else {
                         // TODO: this code is copy-pasted from self.receive_message because of borrow checker limitations
                         // identify the type of message
                         let net_id = NetId::from_bytes(&mut reader)?;
+                        if group_net_id == Some(net_id) {
+                            // the message is part of a SendGroup: hold it until the earlier members are received
+                            let header = MessageGroupHeader::from_bytes(&mut reader)?;
+                            let message = reader.split_len(reader.remaining())?;
+                            self.message_group_receiver.recv(
+                                header,
+                                message,
+                                time_manager.current_time(),
+                            );
+                            continue;
+                        }
+                        if timeline_net_id == Some(net_id) {
+                            // the message is applied at a tick of a visual timeline: hold it until the timeline reaches it
+                            let header = TimelineMessageHeader::from_bytes(&mut reader)?;
+                            let message = reader.split_len(reader.remaining())?;
+                            self.timeline_messages.recv(header, message);
+                            continue;
+                        }
                         let single_data = reader.consume();
                         match self.message_registry.message_type(net_id) {
                             #[cfg(feature = "leafwing")]
@@ -449,16 +970,43 @@ impl ConnectionManager {
                                 todo!()
                             }
                             MessageType::Normal => {
-                                self.received_messages
-                                    .entry(net_id)
-                                    .or_default()
-                                    .push(single_data);
+                                self.received_messages.entry(net_id).or_default().push((
+                                    single_data,
+                                    tick,
+                                    None,
+                                ));
+                            }
+                            MessageType::Deprecated => {
+                                self.warnings.report(
+                                    NetworkWarning::DeprecatedKind,
+                                    format_args!(
+                                        "Skipped a message of the deprecated type {net_id}"
+                                    ),
+                                );
                             }
                         }
                     }
                 }
                 Ok::<(), SerializationError>(())
             })?;
+        for message in self
+            .message_group_receiver
+            .drain_ready(time_manager.current_time())
+        {
+            let tick = self.latest_received_server_tick();
+            self.receive_message(Reader::from(message), tick, None)?;
+        }
+        // the interpolation time is only updated in `PostUpdate`, so this is the tick that the interpolated
+        // entities display during this frame
+        if self.sync_manager.is_synced() {
+            let interpolation_tick = self.sync_manager.interpolation_tick(tick_manager);
+            for (message, delivery) in self
+                .timeline_messages
+                .drain_ready(tick_manager.tick() + 1, interpolation_tick)
+            {
+                self.receive_message(Reader::from(message), delivery.tick, Some(delivery))?;
+            }
+        }
 
         if self.sync_manager.is_synced() {
             world.resource_scope(|world, component_registry: Mut<ComponentRegistry>| {
@@ -469,14 +1017,23 @@ impl ConnectionManager {
                     component_registry.as_ref(),
                     tick_manager.tick(),
                     &mut self.events,
+                    &mut self.warnings,
                 );
             });
+            // the events are cleared every frame, so they only contain what was applied in this `receive`
+            self.receive_stats.record_entity_updates(&self.events);
         }
         Ok(())
     }
 
-    /// Receive a message from the server
-    pub(crate) fn receive_message(&mut self, mut reader: Reader) -> Result<(), SerializationError> {
+    /// Receive a message from the server, that was sent at the server tick `tick`
+    /// (or that is applied at the tick `tick` of the timeline of `timeline`)
+    pub(crate) fn receive_message(
+        &mut self,
+        mut reader: Reader,
+        tick: Tick,
+        timeline: Option<TimelineDelivery>,
+    ) -> Result<(), SerializationError> {
         // identify the type of message
         let net_id = NetId::from_bytes(&mut reader)?;
         let single_data = reader.consume();
@@ -492,29 +1049,54 @@ impl ConnectionManager {
                 todo!()
             }
             MessageType::Normal => {
-                self.received_messages
-                    .entry(net_id)
-                    .or_default()
-                    .push(single_data);
+                self.received_messages.entry(net_id).or_default().push((
+                    single_data,
+                    tick,
+                    timeline,
+                ));
+            }
+            MessageType::Deprecated => {
+                self.warnings.report(
+                    NetworkWarning::DeprecatedKind,
+                    format_args!("Skipped a message of the deprecated type {net_id}"),
+                );
             }
         }
         Ok(())
     }
 
+    /// Process the packets of the [`ReceiveQueue`], up to the per-frame quota
+    pub(crate) fn process_received_packets(
+        &mut self,
+        tick_manager: &TickManager,
+        component_registry: &ComponentRegistry,
+    ) -> Result<(), ClientError> {
+        let mut receive_queue = std::mem::take(&mut self.receive_queue);
+        let result = receive_queue
+            .process(|packet| self.recv_packet(packet, tick_manager, component_registry));
+        self.receive_queue = receive_queue;
+        result
+    }
+
     pub(crate) fn recv_packet(
         &mut self,
         packet: RecvPayload,
         tick_manager: &TickManager,
         component_registry: &ComponentRegistry,
     ) -> Result<(), ClientError> {
+        self.receive_stats.bytes += packet.len() as u64;
+        self.receive_stats.packets += 1;
+        self.session_stats.record_received(packet.len());
+        self.connection_stats.record_received(packet.len());
         // receive the packets, buffer them, update any sender that were waiting for their sent messages to be acked
         let tick = self.message_manager.recv_packet(packet)?;
         debug!("Received server packet with tick: {:?}", tick);
-        if self
-            .sync_manager
-            .latest_received_server_tick
-            .map_or(true, |server_tick| tick >= server_tick)
-        {
+        // packets with a compact header might not include the tick
+        if let Some(tick) = tick.filter(|tick| {
+            self.sync_manager
+                .latest_received_server_tick
+                .map_or(true, |server_tick| *tick >= server_tick)
+        }) {
             trace!("new last recv server tick: {:?}", tick);
             self.sync_manager.latest_received_server_tick = Some(tick);
             // TODO: add 'received_new_server_tick' ?
@@ -542,7 +1124,8 @@ impl MessageSend for ConnectionManager {
         message: &M,
         target: NetworkTarget,
     ) -> Result<(), ClientError> {
-        self.send_message_to_target::<C, M>(message, target)
+        self.send_message_to_target::<C, M>(message, target)?;
+        Ok(())
     }
 
     fn erased_send_message_to_target<M: Message>(
@@ -551,7 +1134,8 @@ impl MessageSend for ConnectionManager {
         channel_kind: ChannelKind,
         target: NetworkTarget,
     ) -> Result<(), ClientError> {
-        self.erased_send_message_to_target(message, channel_kind, target)
+        self.erased_send_message_to_target(message, channel_kind, target)?;
+        Ok(())
     }
 }
 
@@ -569,6 +1153,11 @@ impl ReplicationReceive for ConnectionManager {
     fn cleanup(&mut self, tick: Tick) {
         self.replication_receiver.cleanup(tick);
     }
+
+    fn remote_entity_map(&self, from: Option<ClientId>) -> Option<&RemoteEntityMap> {
+        from.is_none()
+            .then_some(&self.replication_receiver.remote_entity_map)
+    }
 }
 
 impl ReplicationSend for ConnectionManager {
@@ -591,9 +1180,16 @@ impl ReplicationSend for ConnectionManager {
 
 #[cfg(test)]
 mod tests {
+    use bevy::prelude::Events;
+
+    use crate::client::error::ClientError;
+    use crate::client::events::UnconnectedPacketEvent;
+    use crate::connection::netcode::{MAX_UNCONNECTED_PAYLOAD_SIZE, UNCONNECTED_PACKET_PREFIX};
+    use crate::connection::server::{NetServer, ServerConnections};
     use crate::prelude::{client, server, ClientConnectionManager};
-    use crate::tests::protocol::Message2;
+    use crate::tests::protocol::{Channel1, Message1, Message2};
     use crate::tests::stepper::{BevyStepper, Step};
+    use crate::transport::{PacketSender, LOCAL_SOCKET};
 
     /// Check that we can map entities from the local world to the remote world
     /// using the ConnectionManager
@@ -641,4 +1237,104 @@ mod tests {
             .map_entities_to_remote(&mut message);
         assert_eq!(message.0, server_entity);
     }
+
+    /// Check that packets can be exchanged with unconnected endpoints on the client's socket,
+    /// without affecting the game traffic
+    #[test]
+    fn test_unconnected_packets() {
+        let mut stepper = BevyStepper::default();
+
+        // client -> server: the server's netcode ignores the packet
+        stepper
+            .client_app
+            .world_mut()
+            .resource_mut::<ClientConnectionManager>()
+            .send_unconnected(LOCAL_SOCKET, b"punch")
+            .unwrap();
+        // server -> client: send a raw packet using the server's socket
+        let mut packet = UNCONNECTED_PACKET_PREFIX.to_vec();
+        packet.extend_from_slice(b"hello");
+        stepper
+            .server_app
+            .world_mut()
+            .resource_mut::<ServerConnections>()
+            .servers[0]
+            .io_mut()
+            .unwrap()
+            .send(&packet, &LOCAL_SOCKET)
+            .unwrap();
+        stepper.frame_step();
+
+        let events: Vec<_> = stepper
+            .client_app
+            .world_mut()
+            .resource_mut::<Events<UnconnectedPacketEvent>>()
+            .drain()
+            .collect();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].from, LOCAL_SOCKET);
+        assert_eq!(events[0].payload.as_ref(), b"hello");
+
+        // the game traffic is unaffected
+        stepper
+            .client_app
+            .world_mut()
+            .resource_mut::<ClientConnectionManager>()
+            .send_message::<Channel1, Message1>(&Message1("client".to_string()))
+            .unwrap();
+        stepper
+            .server_app
+            .world_mut()
+            .resource_mut::<server::ConnectionManager>()
+            .send_message_to_target::<Channel1, Message1>(
+                &Message1("server".to_string()),
+                crate::prelude::NetworkTarget::All,
+            )
+            .unwrap();
+        stepper.frame_step();
+        stepper.frame_step();
+        let server_messages: Vec<_> = stepper
+            .server_app
+            .world_mut()
+            .resource_mut::<Events<server::MessageEvent<Message1>>>()
+            .drain()
+            .map(|event| event.message().clone())
+            .collect();
+        assert_eq!(server_messages, vec![Message1("client".to_string())]);
+        let client_messages: Vec<_> = stepper
+            .client_app
+            .world_mut()
+            .resource_mut::<Events<client::MessageEvent<Message1>>>()
+            .drain()
+            .map(|event| event.message().clone())
+            .collect();
+        assert_eq!(client_messages, vec![Message1("server".to_string())]);
+    }
+
+    #[test]
+    fn test_unconnected_packets_limits() {
+        let mut stepper = BevyStepper::default();
+        let mut connection = stepper
+            .client_app
+            .world_mut()
+            .resource_mut::<ClientConnectionManager>();
+
+        // the payload is size-capped
+        assert!(matches!(
+            connection.send_unconnected(
+                LOCAL_SOCKET,
+                &[0; MAX_UNCONNECTED_PAYLOAD_SIZE + 1]
+            ),
+            Err(ClientError::UnconnectedPacketTooLarge(_))
+        ));
+
+        // the packets are rate-limited (the default quota allows a burst of 10 packets)
+        for _ in 0..10 {
+            connection.send_unconnected(LOCAL_SOCKET, b"punch").unwrap();
+        }
+        assert!(matches!(
+            connection.send_unconnected(LOCAL_SOCKET, b"punch"),
+            Err(ClientError::UnconnectedPacketRateLimited)
+        ));
+    }
 }
@@ -1,14 +1,23 @@
+use crate::channel::stats::{ChannelDiagnosticPaths, ChannelDiagnosticsPlugin};
 use crate::client::connection::ConnectionManager;
+use crate::client::interpolation::diagnostics::InterpolationDiagnosticsPlugin;
 use crate::client::prediction::diagnostics::PredictionDiagnosticsPlugin;
 use bevy::app::{App, Plugin, PostUpdate};
-use bevy::diagnostic::Diagnostics;
+use bevy::diagnostic::{Diagnostics, DiagnosticsStore};
 use bevy::prelude::{not, Condition, IntoSystemConfigs, Real, Res, ResMut, Time};
 use bevy::time::common_conditions::on_timer;
 use bevy::utils::Duration;
 
 use crate::connection::client::{ClientConnection, NetClient};
+use crate::packet::receive_queue::ReceiveQueueDiagnosticsPlugin;
 use crate::prelude::{client::is_disconnected, is_host_server};
+use crate::shared::clock::NetworkClock;
 use crate::shared::ping::diagnostics::PingDiagnosticsPlugin;
+use crate::shared::replication::diagnostics::ReplicationDiagnosticsPlugin;
+use crate::shared::serialized_size::{
+    SerializedSizeDiagnosticsPlugin, SerializedSizeDiagnosticsSettings,
+};
+use crate::shared::warnings::NetworkWarningsDiagnosticsPlugin;
 use crate::transport::io::IoDiagnosticsPlugin;
 
 // TODO: ideally make this a plugin group? but nested plugin groups are not supported
@@ -39,6 +48,50 @@ fn ping_diagnostics_system(connection: Res<ConnectionManager>, diagnostics: Diag
     PingDiagnosticsPlugin::add_measurements(&connection.ping_manager, diagnostics);
 }
 
+fn replication_diagnostics_system(
+    mut connection: ResMut<ConnectionManager>,
+    mut diagnostics: Diagnostics,
+) {
+    let class_stats = connection.message_manager.take_unflushed_class_stats();
+    ReplicationDiagnosticsPlugin::add_class_measurements(&class_stats, &mut diagnostics);
+    let stats = std::mem::take(&mut connection.replication_sender.stats);
+    ReplicationDiagnosticsPlugin::add_measurements(stats, diagnostics);
+}
+
+fn channel_diagnostics_system(
+    connection: Res<ConnectionManager>,
+    paths: Res<ChannelDiagnosticPaths>,
+    mut diagnostics: Diagnostics,
+) {
+    ChannelDiagnosticsPlugin::add_measurements(
+        &paths,
+        connection.message_manager.all_channel_stats(),
+        &mut diagnostics,
+    );
+}
+
+fn warnings_diagnostics_system(connection: Res<ConnectionManager>, diagnostics: Diagnostics) {
+    NetworkWarningsDiagnosticsPlugin::add_measurements(&connection.warnings, diagnostics);
+}
+
+fn receive_queue_diagnostics_system(connection: Res<ConnectionManager>, diagnostics: Diagnostics) {
+    ReceiveQueueDiagnosticsPlugin::add_measurements(connection.receive_queue_stats(), diagnostics);
+}
+
+fn serialized_size_diagnostics_system(
+    connection: Res<ConnectionManager>,
+    clock: Res<NetworkClock>,
+    mut settings: ResMut<SerializedSizeDiagnosticsSettings>,
+    mut store: ResMut<DiagnosticsStore>,
+) {
+    SerializedSizeDiagnosticsPlugin::add_measurements(
+        connection.serialized_sizes(),
+        &mut settings,
+        clock.now(),
+        &mut store,
+    );
+}
+
 impl Plugin for ClientDiagnosticsPlugin {
     fn build(&self, app: &mut App) {
         {
@@ -52,7 +105,76 @@ impl Plugin for ClientDiagnosticsPlugin {
                 ),
             );
         }
+        {
+            let replication_plugin = ReplicationDiagnosticsPlugin::default();
+            let flush_interval = replication_plugin.flush_interval;
+            // the plugin can already have been added by the server in host-server mode
+            if !app.is_plugin_added::<ReplicationDiagnosticsPlugin>() {
+                app.add_plugins(replication_plugin);
+            }
+            app.add_systems(
+                PostUpdate,
+                replication_diagnostics_system.run_if(
+                    on_timer(flush_interval).and_then(not(is_host_server.or_else(is_disconnected))),
+                ),
+            );
+        }
+        {
+            let warnings_plugin = NetworkWarningsDiagnosticsPlugin::default();
+            let flush_interval = warnings_plugin.flush_interval;
+            // the plugin can already have been added by the server in host-server mode
+            if !app.is_plugin_added::<NetworkWarningsDiagnosticsPlugin>() {
+                app.add_plugins(warnings_plugin);
+            }
+            app.add_systems(
+                PostUpdate,
+                warnings_diagnostics_system.run_if(
+                    on_timer(flush_interval).and_then(not(is_host_server.or_else(is_disconnected))),
+                ),
+            );
+        }
+        {
+            let receive_queue_plugin = ReceiveQueueDiagnosticsPlugin::default();
+            let flush_interval = receive_queue_plugin.flush_interval;
+            // the plugin can already have been added by the server in host-server mode
+            if !app.is_plugin_added::<ReceiveQueueDiagnosticsPlugin>() {
+                app.add_plugins(receive_queue_plugin);
+            }
+            app.add_systems(
+                PostUpdate,
+                receive_queue_diagnostics_system.run_if(
+                    on_timer(flush_interval).and_then(not(is_host_server.or_else(is_disconnected))),
+                ),
+            );
+        }
+        {
+            let channel_plugin = ChannelDiagnosticsPlugin::default();
+            let flush_interval = channel_plugin.flush_interval;
+            // the plugin can already have been added by the server in host-server mode
+            if !app.is_plugin_added::<ChannelDiagnosticsPlugin>() {
+                app.add_plugins(channel_plugin);
+            }
+            app.add_systems(
+                PostUpdate,
+                channel_diagnostics_system.run_if(
+                    on_timer(flush_interval).and_then(not(is_host_server.or_else(is_disconnected))),
+                ),
+            );
+        }
+        {
+            // the plugin can already have been added by the user, or by the server in host-server mode
+            if !app.is_plugin_added::<SerializedSizeDiagnosticsPlugin>() {
+                app.add_plugins(SerializedSizeDiagnosticsPlugin::default());
+            }
+            // the flush interval of the plugin is handled by the system itself
+            app.add_systems(
+                PostUpdate,
+                serialized_size_diagnostics_system
+                    .run_if(not(is_host_server.or_else(is_disconnected))),
+            );
+        }
         app.add_plugins(PredictionDiagnosticsPlugin::default());
+        app.add_plugins(InterpolationDiagnosticsPlugin::default());
 
         {
             app.add_plugins(IoDiagnosticsPlugin);
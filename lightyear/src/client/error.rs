@@ -1,6 +1,11 @@
 //! Errors that can happen on the client
 
+use bevy::utils::Duration;
+
+use crate::connection::netcode::{ClientState, MAX_UNCONNECTED_PAYLOAD_SIZE};
+use crate::connection::server::DeniedReason;
 use crate::serialize::SerializationError;
+use crate::shared::replication::limits::ReplicationLimit;
 
 pub type Result<T> = std::result::Result<T, ClientError>;
 
@@ -16,4 +21,50 @@ pub enum ClientError {
     MessageProtocolError(#[from] crate::protocol::message::MessageError),
     #[error(transparent)]
     ComponentProtocolError(#[from] crate::protocol::component::ComponentError),
+    #[error("unconnected packet payload of {0} bytes is bigger than the maximum of {MAX_UNCONNECTED_PAYLOAD_SIZE} bytes")]
+    UnconnectedPacketTooLarge(usize),
+    #[error("unconnected packet dropped because the send rate limit was exceeded")]
+    UnconnectedPacketRateLimited,
+}
+
+/// Errors that make the client abort a connection to the server
+#[derive(thiserror::Error, Debug, Clone, PartialEq)]
+pub enum ConnectError {
+    /// The server runs with a different tick duration than the client, and the client is not allowed to adopt it
+    /// (see [`SyncConfig::accept_server_tick_rate`](crate::client::sync::SyncConfig::accept_server_tick_rate))
+    #[error("the server tick duration is {server:?}, but the client is configured with {client:?}")]
+    TickRateMismatch { client: Duration, server: Duration },
+    /// The connection attempt was cancelled with
+    /// [`ClientCommands::cancel_connect`](crate::client::networking::ClientCommands::cancel_connect)
+    #[error("the connection attempt was cancelled")]
+    Cancelled,
+    /// The connection was not established within [`ConnectConfig::timeout`](crate::client::connect::ConnectConfig::timeout)
+    #[error("the connection was not established within {0:?}")]
+    Timeout(Duration),
+    /// The netcode handshake failed (connection denied, request timed out, token expired...)
+    #[error("the netcode handshake failed: {0:?}")]
+    Netcode(ClientState),
+    /// The server denied the connection request (server full, client id already connected...)
+    #[error("the server denied the connection: {0:?}")]
+    Denied(DeniedReason),
+    /// The transport failed while connecting
+    #[error("the transport failed while connecting: {0}")]
+    Transport(String),
+    /// The server exceeded one of the [`ReplicationLimits`](crate::shared::replication::limits::ReplicationLimits),
+    /// and [`ReplicationLimits::disconnect`](crate::shared::replication::limits::ReplicationLimits::disconnect) is enabled
+    #[error("the server exceeded the replication limit {0:?}")]
+    ReplicationLimitExceeded(ReplicationLimit),
+}
+
+impl ConnectError {
+    /// Returns true if a new connection attempt could succeed after this error
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            ConnectError::Timeout(_)
+                | ConnectError::Netcode(_)
+                | ConnectError::Denied(_)
+                | ConnectError::Transport(_)
+        )
+    }
 }
@@ -13,14 +13,27 @@
 //! }
 //! ```
 
+use std::net::SocketAddr;
+
 use bevy::app::{App, Plugin, PreUpdate};
-use bevy::prelude::{Component, Event, IntoSystemConfigs};
+use bevy::prelude::{Component, Entity, Event, IntoSystemConfigs};
+use bevy::utils::Duration;
+use bytes::Bytes;
 
+use crate::channel::builder::ChannelOccupancy;
+use crate::channel::transfer::{TransferHandle, TransferOutcome};
+use crate::client::cleanup::{CleanupAction, CleanupCause};
 use crate::client::connection::ConnectionManager;
+use crate::client::error::ConnectError;
 use crate::connection::client::DisconnectReason;
-use crate::prelude::ClientId;
+use crate::packet::message::MessageId;
+use crate::prelude::{ChannelKind, ClientId, Tick};
+use crate::server::relevance::hint::{InterestHintOutcome, NetworkEntityId};
+use crate::shared::action::{ActionId, ActionVerdict};
 use crate::shared::events::plugin::EventsPlugin;
 use crate::shared::events::systems::push_component_events;
+use crate::shared::replication::limits::ReplicationLimit;
+use crate::shared::session_summary::SessionSummary;
 use crate::shared::sets::{ClientMarker, InternalMainSet};
 
 /// Plugin that handles generating bevy [`Events`](Event) related to networking and replication
@@ -33,6 +46,17 @@ impl Plugin for ClientEventsPlugin {
             // EVENTS
             .add_event::<ConnectEvent>()
             .add_event::<DisconnectEvent>()
+            .add_event::<ConnectionFailedEvent>()
+            .add_event::<ReplicationLimitExceededEvent>()
+            .add_event::<EntityCleanupEvent>()
+            .add_event::<TickStallEvent>()
+            .add_event::<ActionResolvedEvent>()
+            .add_event::<InterestHintEvent>()
+            .add_event::<UnconnectedPacketEvent>()
+            .add_event::<TransportMigrationEvent>()
+            .add_event::<TransferEvent>()
+            .add_event::<MessageAckEvent>()
+            .add_event::<ChannelOccupancyEvent>()
             // PLUGIN
             .add_plugins(EventsPlugin::<ConnectionManager>::default());
     }
@@ -69,6 +93,127 @@ impl ConnectEvent {
 #[derive(Event, Default)]
 pub struct DisconnectEvent {
     pub reason: Option<DisconnectReason>,
+    /// Network statistics of the session that just ended
+    pub summary: SessionSummary,
+}
+
+/// Bevy [`Event`] emitted on the client when it gives up connecting to the server: the connection attempt
+/// failed (after all the retries of the [`ConnectRetryConfig`](crate::client::connect::ConnectRetryConfig)),
+/// or it was cancelled.
+///
+/// A [`DisconnectEvent`] is also emitted for each attempt that failed after the client started connecting.
+#[derive(Event, Debug, Clone, PartialEq)]
+pub struct ConnectionFailedEvent {
+    pub error: ConnectError,
+}
+
+/// Bevy [`Event`] emitted on the client on the frames where some of the replicated data sent by the server
+/// was rejected because of the [`ReplicationLimits`](crate::shared::replication::limits::ReplicationLimits)
+#[derive(Event, Debug, Clone, PartialEq)]
+pub struct ReplicationLimitExceededEvent {
+    /// The limit that was exceeded
+    pub limit: ReplicationLimit,
+    /// Number of spawns (or component insertions for [`ReplicationLimit::ComponentsPerEntity`]) rejected during the frame
+    pub rejected: u32,
+}
+
+/// Bevy [`Event`] emitted on the client when a frame lasted too long for all its ticks to be simulated,
+/// because of [`SyncConfig::max_catch_up_ticks`](crate::client::sync::SyncConfig::max_catch_up_ticks).
+///
+/// The client tick snaps forward to the sync objective at the end of the frame.
+#[derive(Event, Debug, Clone, PartialEq)]
+pub struct TickStallEvent {
+    /// Real duration of the frame
+    pub frame_duration: Duration,
+    /// Number of ticks that were not simulated
+    pub skipped_ticks: u16,
+}
+
+/// Bevy [`Event`] emitted on the client when an entity received from the server is cleaned up,
+/// according to the [`ClientCleanupPolicy`](crate::client::cleanup::ClientCleanupPolicy)
+#[derive(Event, Debug, Clone, PartialEq)]
+pub struct EntityCleanupEvent {
+    /// The confirmed entity (or the predicted/interpolated entity, if it had no confirmed entity)
+    pub entity: Entity,
+    /// The predicted entity that was cleaned up with the confirmed entity
+    pub predicted: Option<Entity>,
+    /// The interpolated entity that was cleaned up with the confirmed entity
+    pub interpolated: Option<Entity>,
+    pub cause: CleanupCause,
+    pub action: CleanupAction,
+}
+
+/// Bevy [`Event`] emitted on the client when an action started with
+/// [`ActionTracker::begin_action`](crate::shared::action::ActionTracker::begin_action) is resolved
+#[derive(Event, Debug, Clone, PartialEq)]
+pub struct ActionResolvedEvent {
+    pub action_id: ActionId,
+    pub verdict: ActionVerdict,
+    /// Server tick at which the action was processed, or `None` if the action timed out
+    pub server_tick: Option<Tick>,
+}
+
+/// Bevy [`Event`] emitted on the client when the server grants or denies an interest hint sent with
+/// [`ConnectionManager::request_interest`](crate::client::connection::ConnectionManager::request_interest),
+/// or when a granted hint expires
+#[derive(Event, Debug, Clone, PartialEq)]
+pub struct InterestHintEvent {
+    /// The entity of the hint, in the server's World
+    pub entity: NetworkEntityId,
+    pub outcome: InterestHintOutcome,
+}
+
+/// Bevy [`Event`] emitted on the client when a packet is received from an unconnected endpoint
+///
+/// These are packets that were sent with
+/// [`ConnectionManager::send_unconnected`](crate::client::connection::ConnectionManager::send_unconnected)
+/// (for example during a NAT punch-through handshake). They are never interpreted by the netcode protocol.
+#[derive(Event, Debug, Clone)]
+pub struct UnconnectedPacketEvent {
+    /// Address of the sender
+    pub from: SocketAddr,
+    /// Payload of the packet, without the unconnected packet prefix
+    pub payload: Bytes,
+}
+
+/// Bevy [`Event`] emitted on the client when the connection switched to one of the
+/// [`fallback_transports`](crate::prelude::client::NetcodeConfig::fallback_transports)
+/// because the current transport stopped receiving packets.
+///
+/// The session (and all the channel state) is kept; only the transport changed.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct TransportMigrationEvent {
+    /// Address of the server on the new transport
+    pub server_addr: SocketAddr,
+}
+
+/// Bevy [`Event`] emitted on the client when the transfer of a fragmented message to or from the server
+/// completes, is cancelled, or times out (see [`transfer`](crate::channel::transfer))
+#[derive(Event, Debug, Clone, Copy, PartialEq)]
+pub struct TransferEvent {
+    pub handle: TransferHandle,
+    pub outcome: TransferOutcome,
+}
+
+/// Bevy [`Event`] emitted on the client when the server acked a message that was sent with
+/// [`ConnectionManager::send_message`](crate::client::connection::ConnectionManager::send_message)
+/// on a channel that tracks acks (a reliable channel or [`ChannelMode::UnorderedUnreliableWithAcks`](crate::prelude::ChannelMode::UnorderedUnreliableWithAcks)).
+///
+/// A fragmented message is only acked once all its fragments are acked.
+#[derive(Event, Debug, Clone, Copy, PartialEq)]
+pub struct MessageAckEvent {
+    pub message_id: MessageId,
+    pub channel_kind: ChannelKind,
+}
+
+/// Bevy [`Event`] emitted on the client when the send buffer of a channel goes above 80% of its
+/// [`BufferLimits`](crate::channel::builder::BufferLimits).
+///
+/// It is emitted again once the occupancy went back below the threshold and exceeds it again.
+#[derive(Event, Debug, Clone, Copy, PartialEq)]
+pub struct ChannelOccupancyEvent {
+    pub channel_kind: ChannelKind,
+    pub occupancy: ChannelOccupancy,
 }
 
 /// Bevy [`Event`] emitted on the client to indicate the user input for the tick
@@ -83,5 +228,11 @@ pub type ComponentUpdateEvent<C> = crate::shared::events::components::ComponentU
 pub type ComponentInsertEvent<C> = crate::shared::events::components::ComponentInsertEvent<C, ()>;
 /// Bevy [`Event`] emitted on the client when a ComponentRemove replication message is received
 pub type ComponentRemoveEvent<C> = crate::shared::events::components::ComponentRemoveEvent<C, ()>;
+/// Bevy [`Event`] emitted on the client when a replicated resource is inserted or updated
+pub type ResourceUpdateEvent<R> = crate::shared::events::components::ResourceUpdateEvent<R, ()>;
+/// Bevy [`Event`] emitted on the client when a replicated resource is removed
+pub type ResourceRemoveEvent<R> = crate::shared::events::components::ResourceRemoveEvent<R, ()>;
 /// Bevy [`Event`] emitted on the client when a (non-replication) message is received
 pub type MessageEvent<M> = crate::shared::events::components::MessageEvent<M, ()>;
+/// Bevy [`Event`] emitted on the client when a request is received from the server
+pub type RequestEvent<R> = crate::shared::request::RequestEvent<R, ()>;
@@ -33,6 +33,15 @@
 //!
 //! Currently, global inputs (that are stored in a [`Resource`] instead of being attached to a specific [`Entity`] are not supported)
 //!
+//! ### Local players
+//!
+//! Inputs are buffered and sent separately for each entity that has an [`InputMap`], so multiple players sharing
+//! the same connection (for example for splitscreen) each need their own entity with their own [`InputMap`] and [`ActionState`].
+//! On the server, set [`ControlledBy::local_player`](crate::prelude::server::ControlledBy::local_player) to identify
+//! which local player controls the entity: the client's entity will have the corresponding [`LocalPlayerId`](crate::prelude::LocalPlayerId)
+//! component, which you can use to add the correct [`InputMap`]. The server updates the [`ActionState`] of each entity
+//! independently, and during rollbacks each predicted entity replays the inputs from its own buffer.
+//!
 //! There are some edge-cases to be careful of:
 //! - the `leafwing_input_manager` crate handles inputs every frame, but `lightyear` needs to store and send inputs for each tick.
 //!   This can cause issues if we have multiple ticks in a single frame, or multiple frames in a single tick.
@@ -51,7 +60,7 @@ use crate::channel::builder::InputChannel;
 use crate::client::components::Confirmed;
 use crate::client::config::ClientConfig;
 use crate::client::connection::ConnectionManager;
-use crate::client::prediction::plugin::{is_in_rollback, PredictionSet};
+use crate::client::prediction::plugin::{is_in_rollback, InputDelayConfig, PredictionSet};
 use crate::client::prediction::resource::PredictionManager;
 use crate::client::prediction::rollback::Rollback;
 use crate::client::prediction::Predicted;
@@ -136,8 +145,13 @@ impl<A> Default for LeafwingInputPlugin<A> {
 }
 
 /// Returns true if there is input delay present
-fn is_input_delay(config: Res<ClientConfig>) -> bool {
-    config.prediction.minimum_input_delay_ticks > 0
+fn is_input_delay(config: Res<ClientConfig>, connection: Option<Res<ConnectionManager>>) -> bool {
+    connection.is_some_and(|connection| connection.sync_manager.input_delay_override.is_some())
+        || matches!(
+            config.prediction.input_delay,
+            InputDelayConfig::Automatic { .. }
+        )
+        || config.prediction.minimum_input_delay_ticks > 0
         || config.prediction.maximum_input_delay_before_prediction > 0
         || config.prediction.maximum_predicted_ticks < 30
 }
@@ -352,7 +366,6 @@ fn add_action_state_buffer<A: LeafwingUserAction>(
 /// At the start of the frame, restore the ActionState to the latest-action state in buffer
 /// (e.g. the delayed action state) because all inputs (i.e. diffs) are applied to the delayed action-state.
 fn get_delayed_action_state<A: LeafwingUserAction>(
-    config: Res<ClientConfig>,
     tick_manager: Res<TickManager>,
     connection_manager: Res<ConnectionManager>,
     // global_input_buffer: Res<InputBuffer<A>>,
@@ -362,10 +375,7 @@ fn get_delayed_action_state<A: LeafwingUserAction>(
         With<InputMap<A>>,
     >,
 ) {
-    let input_delay_ticks = config.prediction.input_delay_ticks(
-        connection_manager.ping_manager.rtt(),
-        config.shared.tick.tick_duration,
-    ) as i16;
+    let input_delay_ticks = connection_manager.input_delay_ticks(&tick_manager) as i16;
     let delayed_tick = tick_manager.tick() + input_delay_ticks;
     for (entity, mut action_state, input_buffer) in action_state_query.iter_mut() {
         // TODO: lots of clone + is complicated. Shouldn't we just have a DelayedActionState component + resource?
@@ -394,7 +404,6 @@ fn get_delayed_action_state<A: LeafwingUserAction>(
 ///
 /// We do not need to buffer inputs during rollback, as they have already been buffered
 fn buffer_action_state<A: LeafwingUserAction>(
-    config: Res<ClientConfig>,
     connection_manager: Res<ConnectionManager>,
     tick_manager: Res<TickManager>,
     // mut global_input_buffer: ResMut<InputBuffer<A>>,
@@ -404,14 +413,17 @@ fn buffer_action_state<A: LeafwingUserAction>(
         With<InputMap<A>>,
     >,
 ) {
-    // TODO: if the input delay changes, this could override a previous tick's input in the InputBuffer
-    //  or leave gaps
-    let input_delay_ticks = config.prediction.input_delay_ticks(
-        connection_manager.ping_manager.rtt(),
-        config.shared.tick.tick_duration,
-    ) as i16;
+    let input_delay_ticks = connection_manager.input_delay_ticks(&tick_manager) as i16;
     let tick = tick_manager.tick() + input_delay_ticks;
     for (entity, action_state, mut input_buffer) in action_state_query.iter_mut() {
+        // if the input delay decreased, the ticks that were buffered with the previous input delay keep their
+        // ActionState. (if it increased, `set` fills the skipped ticks with the previous ActionState)
+        if input_buffer
+            .end_tick()
+            .is_some_and(|end_tick| tick <= end_tick)
+        {
+            continue;
+        }
         input_buffer.set(tick, action_state);
         debug!(
             ?entity,
@@ -554,10 +566,7 @@ fn prepare_input_message<A: LeafwingUserAction>(
         With<InputMap<A>>,
     >,
 ) {
-    let input_delay_ticks = config.prediction.input_delay_ticks(
-        connection.ping_manager.rtt(),
-        config.shared.tick.tick_duration,
-    ) as i16;
+    let input_delay_ticks = connection.input_delay_ticks(&tick_manager) as i16;
     let tick = tick_manager.tick() + input_delay_ticks;
     // TODO: the number of messages should be in SharedConfig
     trace!(tick = ?tick, "prepare_input_message");
@@ -653,9 +662,10 @@ fn send_input_messages<A: LeafwingUserAction>(
     for message in message_buffer.0.drain(..) {
         connection
             .send_message::<InputChannel, InputMessage<A>>(&message)
-            .unwrap_or_else(|err| {
+            .inspect_err(|err| {
                 error!("Error while sending input message: {:?}", err);
-            });
+            })
+            .ok();
     }
 }
 
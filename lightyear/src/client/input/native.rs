@@ -40,25 +40,48 @@
 //! - handle inputs in your game logic in systems that run in the `FixedUpdate` schedule. These systems
 //! will read the inputs using the [`InputEvent`] event.
 //!
+//! [`add_input`](InputManager::add_input) can be called several times for the same tick, for example if the player
+//! moves and shoots at the same time: an [`InputEvent`] is emitted for each input of the tick, in the order in which
+//! they were added. If there is no input for a tick, a single [`InputEvent`] without input is emitted.
+//!
+//! ### Catch-up ticks
+//!
+//! When a frame lasts longer than a tick, several ticks are simulated during the same frame to catch up
+//! (see [`TickDebt`]). The inputs sampled during these catch-up ticks all come from the same key state, so
+//! they would be sent as if the player pressed the keys again on each of them. [`InputConfig::catch_up_inputs`]
+//! controls how they are handled.
+//!
+//! ### Local players
+//!
+//! Multiple players can share the same connection (for example for splitscreen). Each local player is identified
+//! by a [`LocalPlayerId`], and their inputs are buffered with [`add_local_player_input`](InputManager::add_local_player_input).
+//! The inputs of all local players are sent in the same message, and an [`InputEvent`] is emitted for each local player
+//! every tick (including during rollbacks). [`InputEvent::entity`] is the entity controlled by that local player.
+//!
 //! NOTE: I would advise to activate the `leafwing` feature to handle inputs via the `input_leafwing` module, instead.
 //! That module is more up-to-date and has more features.
 //! This module is kept for simplicity but might get removed in the future.
 use bevy::prelude::*;
 use bevy::reflect::Reflect;
-use bevy::utils::Duration;
+use bevy::utils::{Duration, HashMap};
 use tracing::{debug, error, trace};
 
 use crate::channel::builder::InputChannel;
 use crate::client::config::ClientConfig;
 use crate::client::connection::ConnectionManager;
 use crate::client::events::InputEvent;
+use crate::client::interpolation::Interpolated;
+use crate::client::prediction::capture::PredictionRecorder;
 use crate::client::prediction::plugin::is_in_rollback;
 use crate::client::prediction::rollback::Rollback;
+use crate::client::prediction::Predicted;
 use crate::client::run_conditions::is_synced;
-use crate::client::sync::SyncSet;
-use crate::inputs::native::input_buffer::InputBuffer;
-use crate::inputs::native::UserAction;
+use crate::client::sync::{SyncSet, TickDebt};
+use crate::inputs::native::input_buffer::{InputBuffer, TickInputs};
+use crate::inputs::native::{InputMessage, UserAction};
+use crate::inputs::LocalPlayerId;
 use crate::prelude::{is_host_server, ChannelKind, ChannelRegistry, Tick, TickManager};
+use crate::shared::replication::components::Controlled;
 use crate::shared::sets::{ClientMarker, InternalMainSet};
 use crate::shared::tick_manager::TickEvent;
 
@@ -72,35 +95,123 @@ pub struct InputConfig {
     /// How often do we send input messages to the server?
     /// Duration::default() means that we will send input messages every frame.
     pub send_interval: Duration,
+    /// How the inputs of the catch-up ticks (the ticks after the first one simulated in a frame) are buffered
+    pub catch_up_inputs: CatchUpInputs,
+}
+
+/// How the inputs added during a catch-up tick are handled
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Reflect)]
+pub enum CatchUpInputs {
+    /// The inputs added during the catch-up ticks are buffered like on any other tick
+    #[default]
+    Sample,
+    /// The inputs added during the catch-up ticks are ignored, and the inputs of the previous tick are repeated.
+    /// They are sent as unchanged inputs instead of new ones.
+    Repeat,
 }
 
+/// Maximum number of ticks of inputs contained in an input message, when the message must cover all the ticks
+/// simulated since the previous message
+const MAX_INPUT_MESSAGE_TICKS: i16 = 64;
+
 /// Resource that handles buffering and sending inputs to the server
 ///
 /// Note: it is advised to enable the feature `leafwing` and  switch to the `LeafwingInputPlugin`,
 /// which is more up-to-date and has more features.
 #[derive(Debug, Resource)]
 pub struct InputManager<A> {
-    pub(crate) input_buffer: InputBuffer<A>,
+    /// One buffer per local player. The buffer of the default [`LocalPlayerId`] is always present.
+    pub(crate) input_buffers: HashMap<LocalPlayerId, InputBuffer<TickInputs<A>>>,
+    /// Number of ticks between the tick where an input is added and the tick where it is applied.
+    /// It is updated at the start of every tick from the [`ConnectionManager`].
+    pub(crate) input_delay_ticks: u16,
+    /// For each local player, the last tick where an input was added, and the tick where it was buffered
+    last_delayed_ticks: HashMap<LocalPlayerId, (Tick, Tick)>,
+    /// True if the inputs added during the current tick are ignored, because it is a catch-up tick
+    ignore_inputs: bool,
+    /// Last tick contained in the last input message that was sent
+    last_sent_end_tick: Option<Tick>,
 }
 
 impl<A> Default for InputManager<A> {
     fn default() -> Self {
+        let mut input_buffers = HashMap::default();
+        input_buffers.insert(LocalPlayerId::default(), InputBuffer::default());
         Self {
-            input_buffer: InputBuffer::default(),
+            input_buffers,
+            input_delay_ticks: 0,
+            last_delayed_ticks: HashMap::default(),
+            ignore_inputs: false,
+            last_sent_end_tick: None,
         }
     }
 }
 
 impl<A: UserAction> InputManager<A> {
-    /// Get a cloned version of the input (we might not want to pop from the buffer because we want
-    /// to keep it for rollback)
-    pub(crate) fn get_input(&self, tick: Tick) -> Option<A> {
-        self.input_buffer.get(tick).cloned()
+    /// Buffer a user action for the given tick.
+    ///
+    /// Several actions can be buffered for the same tick, an [`InputEvent`] is emitted for each of them.
+    ///
+    /// If there is input delay, the action is applied `input_delay_ticks` ticks later
+    /// (see [`ConnectionManager::set_input_delay_ticks`]).
+    pub fn add_input(&mut self, input: A, tick: Tick) {
+        self.add_local_player_input(LocalPlayerId::default(), input, tick);
     }
 
-    /// Buffer a user action for the given tick
-    pub fn add_input(&mut self, input: A, tick: Tick) {
-        self.input_buffer.set(tick, Some(input));
+    /// Buffer a user action of one of the local players for the given tick
+    pub fn add_local_player_input(&mut self, local_player: LocalPlayerId, input: A, tick: Tick) {
+        if self.ignore_inputs {
+            trace!(?tick, "ignoring an input added during a catch-up tick");
+            return;
+        }
+        let input_buffer = self.input_buffers.entry(local_player).or_default();
+        let mut delayed_tick = tick + self.input_delay_ticks as i16;
+        if let Some(&(last_tick, last_delayed_tick)) = self.last_delayed_ticks.get(&local_player) {
+            if last_tick == tick || delayed_tick <= last_delayed_tick {
+                // the input delay decreased: the inputs are added to the last buffered tick,
+                // so that the ticks buffered with the previous delay keep their inputs
+                delayed_tick = last_delayed_tick;
+            } else if last_tick + 1 == tick {
+                // the input delay increased: repeat the last inputs on the ticks that were skipped,
+                // so that there is no gap in the inputs
+                if let Some(last_inputs) = input_buffer.get(last_delayed_tick).cloned() {
+                    for delta in 1..(delayed_tick - last_delayed_tick) {
+                        input_buffer.set(last_delayed_tick + delta, Some(last_inputs.clone()));
+                    }
+                }
+            }
+        }
+        input_buffer.push(delayed_tick, input);
+        self.last_delayed_ticks
+            .insert(local_player, (tick, delayed_tick));
+    }
+
+    /// Repeat the last buffered inputs of every local player on the given tick
+    fn repeat_last_inputs(&mut self, tick: Tick) {
+        let delayed_tick = tick + self.input_delay_ticks as i16;
+        for (local_player, (last_tick, last_delayed_tick)) in self.last_delayed_ticks.iter_mut() {
+            if *last_tick + 1 != tick || delayed_tick <= *last_delayed_tick {
+                continue;
+            }
+            let Some(input_buffer) = self.input_buffers.get_mut(local_player) else {
+                continue;
+            };
+            if let Some(last_inputs) = input_buffer.get(*last_delayed_tick).cloned() {
+                for delta in 1..=(delayed_tick - *last_delayed_tick) {
+                    input_buffer.set(*last_delayed_tick + delta, Some(last_inputs.clone()));
+                }
+            }
+            *last_tick = tick;
+            *last_delayed_tick = delayed_tick;
+        }
+    }
+
+    /// Last tick where an input of one of the local players was buffered
+    fn last_delayed_tick(&self) -> Option<Tick> {
+        self.last_delayed_ticks
+            .values()
+            .map(|(_, delayed_tick)| *delayed_tick)
+            .max()
     }
 }
 
@@ -109,6 +220,7 @@ impl Default for InputConfig {
         InputConfig {
             packet_redundancy: 10,
             send_interval: Duration::default(),
+            catch_up_inputs: CatchUpInputs::default(),
         }
     }
 }
@@ -133,13 +245,6 @@ impl<A: UserAction> Default for InputPlugin<A> {
     }
 }
 
-/// Input of the user for the current tick
-pub struct CurrentInput<A: UserAction> {
-    // TODO: should we allow a Vec of inputs? for example if a user presses multiple buttons?
-    //  or would that be encoded as a combination?
-    input: A,
-}
-
 impl<A: UserAction> Plugin for InputPlugin<A> {
     fn build(&self, app: &mut App) {
         // REGISTRATION
@@ -174,6 +279,19 @@ impl<A: UserAction> Plugin for InputPlugin<A> {
                 .chain(),
         );
         // SYSTEMS
+        app.add_systems(
+            FixedPreUpdate,
+            update_input_delay::<A>
+                .before(InputSystemSet::BufferInputs)
+                .run_if(not(is_host_server)),
+        );
+        app.add_systems(
+            FixedPreUpdate,
+            handle_catch_up_tick::<A>
+                .after(update_input_delay::<A>)
+                .before(InputSystemSet::BufferInputs)
+                .run_if(not(is_in_rollback)),
+        );
 
         // Host server mode only!
         app.add_systems(
@@ -235,12 +353,84 @@ fn write_input_event<A: UserAction>(
     input_manager: Res<InputManager<A>>,
     mut client_input_events: EventWriter<InputEvent<A>>,
     rollback: Option<Res<Rollback>>,
+    controlled: Query<ControlledQueryData, ControlledQueryFilter>,
+    mut recorder: Option<ResMut<PredictionRecorder>>,
 ) {
     let tick = rollback.map_or(tick_manager.tick(), |r| {
         tick_manager.tick_or_rollback_tick(r.as_ref())
     });
-    let input = input_manager.get_input(tick);
-    client_input_events.send(InputEvent::new(input_manager.get_input(tick), ()));
+    for (local_player, input_buffer) in input_manager.input_buffers.iter() {
+        // we get a cloned version of the inputs because we want to keep them in the buffer for rollbacks
+        let inputs = input_buffer.get(tick).cloned().unwrap_or_default();
+        if let Some(recorder) = recorder.as_mut() {
+            recorder.record_inputs(
+                tick,
+                *local_player,
+                inputs.iter().map(|input| format!("{input:?}")).collect(),
+            );
+        }
+        client_input_events.send_batch(InputEvent::from_tick_inputs(
+            inputs,
+            (),
+            *local_player,
+            controlled_entity(&controlled, *local_player),
+        ));
+    }
+}
+
+type ControlledQueryData = (Entity, Option<&'static LocalPlayerId>, Has<Predicted>);
+type ControlledQueryFilter = (With<Controlled>, Without<Interpolated>);
+
+/// Find the entity controlled by a local player. The predicted entity is preferred over the confirmed entity.
+fn controlled_entity(
+    controlled: &Query<ControlledQueryData, ControlledQueryFilter>,
+    local_player: LocalPlayerId,
+) -> Option<Entity> {
+    let mut confirmed = None;
+    for (entity, player, is_predicted) in controlled.iter() {
+        if player.copied().unwrap_or_default() != local_player {
+            continue;
+        }
+        if is_predicted {
+            return Some(entity);
+        }
+        confirmed.get_or_insert(entity);
+    }
+    confirmed
+}
+
+/// Update the input delay used to buffer the inputs of this tick
+fn update_input_delay<A: UserAction>(
+    connection: Option<Res<ConnectionManager>>,
+    tick_manager: Res<TickManager>,
+    mut input_manager: ResMut<InputManager<A>>,
+) {
+    let Some(connection) = connection else {
+        return;
+    };
+    let input_delay_ticks = connection.input_delay_ticks(&tick_manager);
+    if input_manager.input_delay_ticks != input_delay_ticks {
+        debug!(
+            old = input_manager.input_delay_ticks,
+            new = input_delay_ticks,
+            "input delay updated"
+        );
+        input_manager.input_delay_ticks = input_delay_ticks;
+    }
+}
+
+/// Repeat the previous inputs during a catch-up tick, if [`InputConfig::catch_up_inputs`] is [`CatchUpInputs::Repeat`]
+fn handle_catch_up_tick<A: UserAction>(
+    config: Res<ClientConfig>,
+    tick_manager: Res<TickManager>,
+    tick_debt: Res<TickDebt>,
+    mut input_manager: ResMut<InputManager<A>>,
+) {
+    input_manager.ignore_inputs =
+        tick_debt.is_catch_up_tick() && config.input.catch_up_inputs == CatchUpInputs::Repeat;
+    if input_manager.ignore_inputs {
+        input_manager.repeat_last_inputs(tick_manager.tick());
+    }
 }
 
 /// Receive an [`TickEvent`] signifying that the local tick has been updated,
@@ -252,13 +442,22 @@ fn receive_tick_events<A: UserAction>(
     match trigger.event() {
         TickEvent::TickSnap { old_tick, new_tick } => {
             // if the tick got updated, update our inputs to match our new ticks
-            if let Some(start_tick) = input_manager.input_buffer.start_tick {
-                trace!(
-                    "Receive tick snap event {:?}. Updating input buffer start_tick!",
-                    trigger.event()
-                );
-                input_manager.input_buffer.start_tick = Some(start_tick + (*new_tick - *old_tick));
-            };
+            for input_buffer in input_manager.input_buffers.values_mut() {
+                if let Some(start_tick) = input_buffer.start_tick {
+                    trace!(
+                        "Receive tick snap event {:?}. Updating input buffer start_tick!",
+                        trigger.event()
+                    );
+                    input_buffer.start_tick = Some(start_tick + (*new_tick - *old_tick));
+                };
+            }
+            for (last_tick, last_delayed_tick) in input_manager.last_delayed_ticks.values_mut() {
+                *last_tick = *last_tick + (*new_tick - *old_tick);
+                *last_delayed_tick = *last_delayed_tick + (*new_tick - *old_tick);
+            }
+            if let Some(last_sent_end_tick) = &mut input_manager.last_sent_end_tick {
+                *last_sent_end_tick = *last_sent_end_tick + (*new_tick - *old_tick);
+            }
         }
     }
 }
@@ -294,15 +493,28 @@ fn prepare_input_message<A: UserAction>(
             .unwrap();
     let redundancy = config.input.packet_redundancy;
     // let redundancy = 3;
-    let message_len = redundancy * num_tick;
+    let mut message_len = redundancy * num_tick;
     // TODO: we can either:
     //  - buffer an input message at every tick, and not require that much redundancy
     //  - buffer an input every frame; and require some redundancy (number of tick per frame)
     //  - or buffer an input only when we are sending, and require more redundancy
     // let message_len = 20 as u16;
-    let message = input_manager
-        .input_buffer
-        .create_message(tick_manager.tick(), message_len);
+    // the inputs are buffered up to the delayed tick. If the input delay decreased, the inputs that
+    // were buffered with the previous delay can be further ahead
+    let delayed_tick = current_tick + input_manager.input_delay_ticks as i16;
+    let end_tick = input_manager
+        .last_delayed_tick()
+        .map_or(delayed_tick, |tick| tick.max(delayed_tick));
+    // the message also covers all the ticks simulated since the previous message (for example when several ticks
+    // were simulated during the last frame), so that a single message is sent for them
+    if let Some(last_sent_end_tick) = input_manager.last_sent_end_tick {
+        let unsent_ticks = (end_tick - last_sent_end_tick).clamp(0, MAX_INPUT_MESSAGE_TICKS) as u16;
+        message_len = message_len.max(unsent_ticks);
+    }
+    let mut message = InputMessage::new(end_tick);
+    for (local_player, input_buffer) in input_manager.input_buffers.iter() {
+        message.add_inputs(*local_player, message_len, input_buffer);
+    }
     // all inputs are absent
     if !message.is_empty() {
         // TODO: should we provide variants of each user-facing function, so that it pushes the error
@@ -313,9 +525,11 @@ fn prepare_input_message<A: UserAction>(
         );
         connection
             .send_message::<InputChannel, _>(&message)
-            .unwrap_or_else(|err| {
+            .inspect_err(|err| {
                 error!("Error while sending input message: {:?}", err);
             })
+            .ok();
+        input_manager.last_sent_end_tick = Some(end_tick);
     }
     // NOTE: actually we keep the input values! because they might be needed when we rollback for client prediction
     // TODO: figure out when we can delete old inputs. Basically when the oldest prediction group tick has passed?
@@ -323,7 +537,9 @@ fn prepare_input_message<A: UserAction>(
 
     // delete old input values
     let interpolation_tick = connection.sync_manager.interpolation_tick(&tick_manager);
-    input_manager.input_buffer.pop(interpolation_tick);
+    for input_buffer in input_manager.input_buffers.values_mut() {
+        input_buffer.pop(interpolation_tick);
+    }
     // .pop(current_tick - (message_len + 1));
 }
 
@@ -333,8 +549,16 @@ fn send_input_directly_to_client_events<A: UserAction>(
     tick_manager: Res<TickManager>,
     mut input_manager: ResMut<InputManager<A>>,
     mut client_input_events: EventWriter<InputEvent<A>>,
+    controlled: Query<ControlledQueryData, ControlledQueryFilter>,
 ) {
     let tick = tick_manager.tick();
-    let input = input_manager.input_buffer.pop(tick);
-    client_input_events.send(InputEvent::new(input, ()));
+    for (local_player, input_buffer) in input_manager.input_buffers.iter_mut() {
+        let inputs = input_buffer.pop(tick).unwrap_or_default();
+        client_input_events.send_batch(InputEvent::from_tick_inputs(
+            inputs,
+            (),
+            *local_player,
+            controlled_entity(&controlled, *local_player),
+        ));
+    }
 }
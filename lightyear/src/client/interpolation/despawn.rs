@@ -1,18 +1,26 @@
-use bevy::prelude::{Commands, DespawnRecursiveExt, OnRemove, Query, ResMut, Trigger};
+use bevy::prelude::{Commands, DespawnRecursiveExt, OnRemove, Query, ResMut, Trigger, With};
 
+use crate::client::cleanup::NetworkedComponentsStripped;
 use crate::client::components::{Confirmed, SyncComponent};
 use crate::client::interpolation::interpolate::InterpolateStatus;
 use crate::client::interpolation::interpolation_history::ConfirmedHistory;
 use crate::client::interpolation::resource::InterpolationManager;
+use crate::shared::replication::handoff::HandoffSource;
 
 /// Remove the component from interpolated entities when it gets removed from confirmed
+///
+/// The source of a handoff keeps its components until it is replaced.
 pub(crate) fn removed_components<C: SyncComponent>(
     trigger: Trigger<OnRemove, C>,
     mut commands: Commands,
     query: Query<&Confirmed>,
+    handoff_sources: Query<(), With<HandoffSource>>,
 ) {
     if let Ok(confirmed) = query.get(trigger.entity()) {
         if let Some(interpolated) = confirmed.interpolated {
+            if handoff_sources.contains(interpolated) {
+                return;
+            }
             if let Some(mut entity) = commands.get_entity(interpolated) {
                 entity.remove::<(C, ConfirmedHistory<C>, InterpolateStatus<C>)>();
             }
@@ -20,7 +28,21 @@ pub(crate) fn removed_components<C: SyncComponent>(
     }
 }
 
+/// Remove the interpolation components of `C` from an interpolated entity that stops being interpolated
+/// (see [`CleanupAction::StripNetworkedComponents`](crate::client::cleanup::CleanupAction::StripNetworkedComponents))
+pub(crate) fn remove_interpolation_components_on_strip<C: SyncComponent>(
+    trigger: Trigger<NetworkedComponentsStripped>,
+    mut commands: Commands,
+) {
+    if let Some(mut entity) = commands.get_entity(trigger.entity()) {
+        entity.remove::<(ConfirmedHistory<C>, InterpolateStatus<C>)>();
+    }
+}
+
 /// Despawn interpolated entities when the confirmed entity gets despawned
+///
+/// The source of a handoff is only despawned when the interpolation reaches the handoff tick
+/// (see [`handoff`](crate::shared::replication::handoff)).
 // TODO: we should despawn interpolated only when it reaches the latest confirmed snapshot?
 //  I suppose  we could add a DespawnedMarker, and the entity would get despawned as soon as it reaches the end of interpolation...
 //  not super priority but would be a nice to have
@@ -28,6 +50,7 @@ pub(crate) fn despawn_interpolated(
     trigger: Trigger<OnRemove, Confirmed>,
     mut manager: ResMut<InterpolationManager>,
     mut commands: Commands,
+    handoff_sources: Query<(), With<HandoffSource>>,
 ) {
     if let Some(interpolated) = manager
         .interpolated_entity_map
@@ -35,6 +58,9 @@ pub(crate) fn despawn_interpolated(
         .confirmed_to_interpolated
         .remove(&trigger.entity())
     {
+        if handoff_sources.contains(interpolated) {
+            return;
+        }
         if let Some(entity_mut) = commands.get_entity(interpolated) {
             entity_mut.despawn_recursive();
         }
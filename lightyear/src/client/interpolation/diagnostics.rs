@@ -0,0 +1,80 @@
+//! Collect diagnostics for the interpolation systems.
+
+use crate::prelude::{client::is_disconnected, is_host_server};
+use bevy::diagnostic::{Diagnostic, DiagnosticPath, Diagnostics, RegisterDiagnostic};
+use bevy::prelude::*;
+use bevy::time::common_conditions::on_timer;
+use bevy::utils::Duration;
+
+/// Plugin in charge of collecting diagnostics for the interpolation systems.
+pub struct InterpolationDiagnosticsPlugin {
+    /// Number of diagnostics to keep in history
+    history_length: usize,
+    /// How often to flush the stored data into the Diagnostics
+    flush_interval: Duration,
+}
+
+impl Default for InterpolationDiagnosticsPlugin {
+    fn default() -> Self {
+        Self {
+            history_length: 60,
+            flush_interval: Duration::from_millis(200),
+        }
+    }
+}
+
+impl InterpolationDiagnosticsPlugin {
+    /// Average number of server snapshots buffered ahead of the interpolation tick, per interpolated component
+    pub const BUFFERED_SNAPSHOTS: DiagnosticPath =
+        DiagnosticPath::const_new("replication.interpolation.buffered_snapshots");
+
+    /// Number of times the interpolation ran out of server snapshots
+    pub const STARVATIONS: DiagnosticPath =
+        DiagnosticPath::const_new("replication.interpolation.starvations");
+
+    fn flush_measurements(mut metrics: ResMut<InterpolationMetrics>, mut diagnostics: Diagnostics) {
+        let metrics = std::mem::take(metrics.as_mut());
+        diagnostics.add_measurement(&Self::BUFFERED_SNAPSHOTS, || {
+            if metrics.samples == 0 {
+                0.0
+            } else {
+                metrics.buffered_snapshots as f64 / metrics.samples as f64
+            }
+        });
+        diagnostics.add_measurement(&Self::STARVATIONS, || metrics.starvations as f64);
+    }
+}
+
+/// Interpolation metrics since the last flush. Flushed to Diagnostics system periodically.
+#[derive(Default, Resource, Debug, Reflect)]
+#[reflect(Resource)]
+pub struct InterpolationMetrics {
+    /// Sum of the number of snapshots buffered ahead of the interpolation tick, over all the samples
+    pub buffered_snapshots: u64,
+    /// Number of interpolated components sampled, once per frame
+    pub samples: u32,
+    /// Incremented once per [`InterpolationStarvedEvent`](super::InterpolationStarvedEvent)
+    pub starvations: u32,
+}
+
+impl Plugin for InterpolationDiagnosticsPlugin {
+    fn build(&self, app: &mut App) {
+        let should_run =
+            on_timer(self.flush_interval).and_then(not(is_host_server.or_else(is_disconnected)));
+
+        app.register_type::<InterpolationMetrics>();
+
+        app.init_resource::<InterpolationMetrics>();
+        app.add_systems(PostUpdate, Self::flush_measurements.run_if(should_run));
+        app.register_diagnostic(
+            Diagnostic::new(Self::BUFFERED_SNAPSHOTS)
+                .with_suffix("snapshots buffered ahead of the interpolation tick")
+                .with_max_history_length(self.history_length),
+        );
+        app.register_diagnostic(
+            Diagnostic::new(Self::STARVATIONS)
+                .with_suffix("interpolation starvations")
+                .with_max_history_length(self.history_length),
+        );
+    }
+}
@@ -1,11 +1,14 @@
-use bevy::prelude::{Commands, Component, Entity, Query, Res, Without};
+use bevy::prelude::{Commands, Component, Entity, Event, EventWriter, Query, Res, ResMut, Without};
 use tracing::{debug, trace};
 
 use crate::client::components::SyncComponent;
 use crate::client::config::ClientConfig;
 use crate::client::connection::ConnectionManager;
+use crate::client::interpolation::diagnostics::InterpolationMetrics;
 use crate::client::interpolation::interpolation_history::ConfirmedHistory;
+use crate::client::interpolation::plugin::StarvationPolicy;
 use crate::prelude::{ComponentRegistry, TickManager};
+use crate::shared::replication::handoff::HandoffTarget;
 use crate::shared::tick_manager::Tick;
 
 // if we haven't received updates since UPDATE_INTERPOLATION_START_TICK_FACTOR * send_interval
@@ -31,6 +34,24 @@ pub struct InterpolateStatus<C: Component> {
     pub current_tick: Tick,
     /// for more accurate interpolation, this is the fraction between [current_tick, current_tick + 1[
     pub current_overstep: f32,
+    /// number of server snapshots buffered with a tick > current_tick (including `end`)
+    pub snapshots_ahead: usize,
+    /// number of server snapshots kept with a tick <= current_tick (`start` and `previous`)
+    pub snapshots_behind: usize,
+    /// snapshot that was used as `start` before the current one, used to extrapolate the component
+    pub previous: Option<(Tick, C)>,
+    /// last extrapolated value, used to blend back to the interpolated value when a snapshot arrives
+    pub(crate) extrapolation: Option<Extrapolation<C>>,
+}
+
+/// Value of a component that was extrapolated because the interpolation ran out of snapshots
+#[derive(PartialEq, Debug)]
+pub(crate) struct Extrapolation<C> {
+    value: C,
+    /// number of ticks that the component was extrapolated for, which is also the duration of the blend
+    ticks: f32,
+    /// interpolation tick and overstep when the interpolation resumed
+    resumed_at: Option<(Tick, f32)>,
 }
 
 impl<C: Component> InterpolateStatus<C> {
@@ -48,17 +69,36 @@ impl<C: Component> InterpolateStatus<C> {
     }
 }
 
+/// Event emitted on the client when the interpolation of a component of an interpolated entity reaches the
+/// last server snapshot that was received, and has to hold the component value because the next snapshot
+/// hasn't arrived yet.
+///
+/// This happens if the interpolation delay is too small compared to the jitter or the packet loss, but also
+/// once after the entity stops receiving updates (for example because the component stopped changing on the server).
+/// It is emitted once per component, until the interpolation resumes.
+#[derive(Event, Debug, Clone, PartialEq)]
+pub struct InterpolationStarvedEvent {
+    /// The interpolated entity
+    pub entity: Entity,
+    /// Interpolation tick for which no later snapshot was available
+    pub missing_tick: Tick,
+}
+
 /// At the end of each frame, interpolate the components between the last 2 confirmed server states
 /// Invariant: start_tick <= current_interpolate_tick + overstep < end_tick
 pub(crate) fn update_interpolate_status<C: SyncComponent>(
     config: Res<ClientConfig>,
+    component_registry: Res<ComponentRegistry>,
     connection: Res<ConnectionManager>,
     tick_manager: Res<TickManager>,
+    mut metrics: Option<ResMut<InterpolationMetrics>>,
+    mut starved_events: EventWriter<InterpolationStarvedEvent>,
     mut query: Query<(
         Entity,
         Option<&mut C>,
         &mut InterpolateStatus<C>,
         &mut ConfirmedHistory<C>,
+        Option<&HandoffTarget>,
     )>,
 ) {
     let kind = std::any::type_name::<C>();
@@ -68,6 +108,15 @@ pub(crate) fn update_interpolate_status<C: SyncComponent>(
         * config.shared.server_replication_send_interval.as_secs_f32()
         / config.shared.tick.tick_duration.as_secs_f32()) as i16
         + 1;
+    // keep the start while the component is extrapolated from it
+    let extrapolation_delta_tick = match config.interpolation.on_starvation {
+        StarvationPolicy::Extrapolate { max_ticks }
+            if component_registry.has_extrapolation::<C>() =>
+        {
+            max_ticks as i16
+        }
+        _ => 0,
+    };
 
     let current_interpolate_tick = connection
         .sync_manager
@@ -75,9 +124,11 @@ pub(crate) fn update_interpolate_status<C: SyncComponent>(
     let current_interpolate_overstep = connection
         .sync_manager
         .interpolation_overstep(tick_manager.as_ref());
-    for (entity, component, mut status, mut history) in query.iter_mut() {
+    for (entity, component, mut status, mut history, handoff) in query.iter_mut() {
         let mut start = status.start.take();
         let mut end = status.end.take();
+        let mut previous = status.previous.take();
+        let was_interpolating = end.is_some();
 
         // if the interpolation tick is beyond the previous end tick,
         // we need to replace start with end, and clear end
@@ -89,7 +140,7 @@ pub(crate) fn update_interpolate_status<C: SyncComponent>(
                     ?current_interpolate_tick,
                     "interpolation is beyond previous end tick"
                 );
-                start.clone_from(&end);
+                previous = std::mem::replace(&mut start, end.clone());
                 // TODO: this clone should be avoidable
                 if let Some(mut component) = component {
                     *component = end_value.clone();
@@ -113,7 +164,7 @@ pub(crate) fn update_interpolate_status<C: SyncComponent>(
                     old_start = ?start.as_ref().map(|(tick, _)| tick),
                     new_start = ?new_tick,
                     "found more recent tick between start and interpolation tick");
-                start = new_start;
+                previous = std::mem::replace(&mut start, new_start);
             }
         }
 
@@ -168,14 +219,19 @@ pub(crate) fn update_interpolate_status<C: SyncComponent>(
         // (so that we wait again until interpolation_tick is between two server updates)
         // otherwise the interpolation will seem weird because the start tick is very old
         // Only do this when end_tick is None, otherwise it could affect the currently running
-        // interpolation
-        if end.is_none() {
+        // interpolation.
+        // The target of a handoff keeps its start, so that it can be displayed at the handoff tick
+        if end.is_none() && handoff.is_none() {
             let temp_start = std::mem::take(&mut start);
             if let Some((start_tick, _)) = temp_start {
-                if current_interpolate_tick - start_tick < send_interval_delta_tick {
+                if current_interpolate_tick - start_tick
+                    < send_interval_delta_tick + extrapolation_delta_tick
+                {
                     start = temp_start;
+                } else {
+                    // (if it's been too long), reset the server tick to None
+                    previous = None;
                 }
-                // else (if it's been too long), reset the server tick to None
             }
         }
 
@@ -188,10 +244,28 @@ pub(crate) fn update_interpolate_status<C: SyncComponent>(
             start_tick = ?start.as_ref().map(|(tick, _)| tick),
             end_tick = ?end.as_ref().map(|(tick, _) | tick),
             "update_interpolate_status");
+        // we were interpolating towards a snapshot that we reached, and the next one hasn't arrived
+        let starved = was_interpolating && start.is_some() && end.is_none();
+        if starved {
+            trace!(?entity, component = ?kind, ?current_interpolate_tick, "interpolation is starved");
+            starved_events.send(InterpolationStarvedEvent {
+                entity,
+                missing_tick: current_interpolate_tick,
+            });
+        }
         status.start = start;
         status.end = end;
+        status.previous = previous;
         status.current_tick = current_interpolate_tick;
         status.current_overstep = current_interpolate_overstep;
+        status.snapshots_ahead = history.buffer.len() + status.end.is_some() as usize;
+        status.snapshots_behind =
+            status.start.is_some() as usize + status.previous.is_some() as usize;
+        if let Some(metrics) = metrics.as_mut() {
+            metrics.buffered_snapshots += status.snapshots_ahead as u64;
+            metrics.samples += 1;
+            metrics.starvations += starved as u32;
+        }
         if status.start.is_none() {
             trace!("no lerp start tick");
         }
@@ -211,12 +285,14 @@ pub(crate) fn update_interpolate_status<C: SyncComponent>(
 /// - or at least SEND_INTERVAL_TICK_FACTOR * send_interval has passed. (this is to deal with the case where we only receive
 /// one update; for example if we spawn the player and then they don't move. If we didn't do this the interpolated entity would
 /// simply not appear)
+///
+/// The target of a handoff gets the component exactly when the interpolation reaches the handoff tick.
 pub(crate) fn insert_interpolated_component<C: SyncComponent>(
     component_registry: Res<ComponentRegistry>,
     config: Res<ClientConfig>,
     tick_manager: Res<TickManager>,
     mut commands: Commands,
-    mut query: Query<(Entity, &InterpolateStatus<C>), Without<C>>,
+    mut query: Query<(Entity, &InterpolateStatus<C>, Option<&HandoffTarget>), Without<C>>,
 ) {
     let tick = tick_manager.tick();
     // how many ticks between each interpolation update (add 1 to roughly take the ceil)
@@ -225,7 +301,10 @@ pub(crate) fn insert_interpolated_component<C: SyncComponent>(
         * config.shared.server_replication_send_interval.as_secs_f32()
         / config.shared.tick.tick_duration.as_secs_f32()) as i16
         + 1;
-    for (entity, status) in query.iter_mut() {
+    for (entity, status, handoff) in query.iter_mut() {
+        if handoff.is_some_and(|handoff| status.current_tick < handoff.tick) {
+            continue;
+        }
         trace!("checking if we need to insert the component on the Interpolated entity");
         let mut entity_commands = commands.entity(entity);
         // NOTE: it is possible that we reach start_tick when end_tick is not set
@@ -241,7 +320,8 @@ pub(crate) fn insert_interpolated_component<C: SyncComponent>(
                 entity_commands.insert(value);
             } else {
                 // we only have one update, but enough time has passed that we should add the component anyway
-                if tick - *start_tick >= send_interval_delta_tick {
+                // (or the handoff tick was reached)
+                if handoff.is_some() || tick - *start_tick >= send_interval_delta_tick {
                     trace!("insert interpolated comp value because enough time has passed");
                     entity_commands.insert(start_value.clone());
                 }
@@ -252,24 +332,76 @@ pub(crate) fn insert_interpolated_component<C: SyncComponent>(
 
 /// Update the component value on the Interpolate entity
 pub(crate) fn interpolate<C: Component + Clone>(
+    config: Res<ClientConfig>,
     component_registry: Res<ComponentRegistry>,
-    mut query: Query<(&mut C, &InterpolateStatus<C>)>,
+    mut query: Query<(&mut C, &mut InterpolateStatus<C>)>,
 ) {
-    for (mut component, status) in query.iter_mut() {
+    let max_extrapolation_ticks = match config.interpolation.on_starvation {
+        StarvationPolicy::Extrapolate { max_ticks }
+            if component_registry.has_extrapolation::<C>() =>
+        {
+            Some(max_ticks)
+        }
+        _ => None,
+    };
+    for (mut component, mut status) in query.iter_mut() {
         debug!("checking if we do interpolation");
+        let InterpolateStatus {
+            start,
+            end,
+            previous,
+            current_tick,
+            current_overstep,
+            extrapolation,
+            ..
+        } = status.as_mut();
         // NOTE: it is possible that we reach start_tick when end_tick is not set
-        if let Some((start_tick, start_value)) = &status.start {
-            if let Some((end_tick, end_value)) = &status.end {
-                debug!(?start_tick, interpolate_tick=?status.current_tick, ?end_tick, "doing interpolation!");
-                assert!(status.current_tick < *end_tick);
-                if start_tick != end_tick {
-                    let t = status.interpolation_fraction().unwrap();
-                    let value = component_registry.interpolate(start_value, end_value, t);
-                    *component = value;
+        let Some((start_tick, start_value)) = start else {
+            continue;
+        };
+        if let Some((end_tick, end_value)) = end {
+            debug!(?start_tick, interpolate_tick=?current_tick, ?end_tick, "doing interpolation!");
+            assert!(*current_tick < *end_tick);
+            let mut value = if start_tick != end_tick {
+                let t = ((*current_tick - *start_tick) as f32 + *current_overstep)
+                    / (*end_tick - *start_tick) as f32;
+                component_registry.interpolate(start_value, end_value, t)
+            } else {
+                start_value.clone()
+            };
+            // blend back from the extrapolated value instead of snapping to the interpolated value
+            if let Some(blend) = extrapolation.as_mut() {
+                let (resumed_tick, resumed_overstep) = *blend
+                    .resumed_at
+                    .get_or_insert((*current_tick, *current_overstep));
+                let t = ((*current_tick - resumed_tick) as f32 + *current_overstep
+                    - resumed_overstep)
+                    / blend.ticks.max(1.0);
+                if t < 1.0 {
+                    value = component_registry.interpolate(&blend.value, &value, t);
                 } else {
-                    *component = start_value.clone();
+                    *extrapolation = None;
                 }
             }
+            *component = value;
+        } else if let (Some(max_ticks), Some((previous_tick, previous_value))) =
+            (max_extrapolation_ticks, previous)
+        {
+            if previous_tick == start_tick {
+                continue;
+            }
+            // continue the motion between the last 2 snapshots
+            let ticks = ((*current_tick - *start_tick) as f32 + *current_overstep)
+                .clamp(0.0, max_ticks as f32);
+            let t = 1.0 + ticks / (*start_tick - *previous_tick) as f32;
+            let value = component_registry.interpolate(previous_value, start_value, t);
+            trace!(?start_tick, interpolate_tick=?current_tick, ?ticks, "doing extrapolation!");
+            *extrapolation = Some(Extrapolation {
+                value: value.clone(),
+                ticks,
+                resumed_at: None,
+            });
+            *component = value;
         }
     }
 }
@@ -527,3 +659,413 @@ pub(crate) fn interpolate<C: Component + Clone>(
 //         Ok(())
 //     }
 // }
+
+#[cfg(test)]
+mod lerp_tests {
+    use bevy::diagnostic::{DiagnosticPath, DiagnosticsStore};
+    use bevy::prelude::{default, Entity, EventReader, Last, ResMut, Resource};
+    use bevy::utils::Duration;
+    use lightyear_macros::LerpInternal;
+
+    use crate::client::components::Confirmed;
+    use crate::client::interpolation::diagnostics::InterpolationDiagnosticsPlugin;
+    use crate::prelude::server::{Replicate, SyncTarget};
+    use crate::prelude::{
+        client, ComponentRegistry, Lerp, NetworkTarget, SharedConfig, TickConfig,
+    };
+    use crate::tests::protocol::*;
+    use crate::tests::stepper::{BevyStepper, Step};
+
+    use super::{InterpolateStatus, InterpolationStarvedEvent};
+
+    #[test]
+    fn test_lerp_derive() {
+        let start = Component8 {
+            position: 0.0,
+            stance: Stance::Standing,
+        };
+        let end = Component8 {
+            position: 2.0,
+            stance: Stance::Crouching,
+        };
+        assert_eq!(
+            Component8::lerp(&start, &end, 0.5),
+            Component8 {
+                position: 1.0,
+                stance: Stance::Standing,
+            }
+        );
+        assert_eq!(
+            Component8::lerp(&start, &end, 0.99).stance,
+            Stance::Standing
+        );
+        assert_eq!(Component8::lerp(&start, &end, 1.0), end);
+    }
+
+    /// Quadratic ease-in
+    fn ease_in(start: &f32, end: &f32, t: f32) -> f32 {
+        start + (end - start) * t * t
+    }
+
+    #[derive(Clone, Debug, PartialEq, LerpInternal)]
+    struct Eased {
+        #[lerp(with = "ease_in")]
+        eased: f32,
+        linear: f32,
+    }
+
+    #[derive(Clone, Copy, Debug, PartialEq, LerpInternal)]
+    #[lerp(with = "Door::lerp_halfway")]
+    enum Door {
+        Open,
+        Closed,
+    }
+
+    impl Door {
+        fn lerp_halfway(start: &Self, end: &Self, t: f32) -> Self {
+            if t < 0.5 {
+                *start
+            } else {
+                *end
+            }
+        }
+    }
+
+    #[test]
+    fn test_lerp_derive_with_function() {
+        let start = Eased {
+            eased: 0.0,
+            linear: 0.0,
+        };
+        let end = Eased {
+            eased: 2.0,
+            linear: 2.0,
+        };
+        assert_eq!(
+            Eased::lerp(&start, &end, 0.5),
+            Eased {
+                eased: 0.5,
+                linear: 1.0,
+            }
+        );
+        assert_eq!(Door::lerp(&Door::Open, &Door::Closed, 0.4), Door::Open);
+        assert_eq!(Door::lerp(&Door::Open, &Door::Closed, 0.6), Door::Closed);
+    }
+
+    fn interpolated_entity(stepper: &BevyStepper, server_entity: Entity) -> Option<Entity> {
+        let confirmed = *stepper
+            .client_app
+            .world()
+            .resource::<client::ConnectionManager>()
+            .replication_receiver
+            .remote_entity_map
+            .get_local(server_entity)?;
+        stepper
+            .client_app
+            .world()
+            .get::<Confirmed>(confirmed)?
+            .interpolated
+    }
+
+    /// Check that a snapped field changes exactly at the tick where it changed on the server,
+    /// on the interpolation timeline
+    #[test]
+    fn test_snapped_field_changes_at_server_tick() {
+        let frame_duration = Duration::from_millis(10);
+        let shared_config = SharedConfig {
+            tick: TickConfig::new(frame_duration),
+            ..default()
+        };
+        // the updates are sent every tick, so the interpolation needs a minimum delay to have an end value
+        let client_config = client::ClientConfig {
+            interpolation: client::InterpolationConfig {
+                delay: client::InterpolationDelay::default()
+                    .with_min_delay(Duration::from_millis(50)),
+                ..default()
+            },
+            ..default()
+        };
+        let mut stepper = BevyStepper::new(shared_config, client_config, frame_duration);
+        stepper.init();
+        let server_entity = stepper
+            .server_app
+            .world_mut()
+            .spawn((
+                Component8 {
+                    position: 0.0,
+                    stance: Stance::Standing,
+                },
+                Replicate {
+                    sync: SyncTarget {
+                        interpolation: NetworkTarget::All,
+                        ..default()
+                    },
+                    ..default()
+                },
+            ))
+            .id();
+
+        let mut change_tick = None;
+        let mut observed = vec![];
+        for i in 0..80 {
+            {
+                let mut component = stepper
+                    .server_app
+                    .world_mut()
+                    .get_mut::<Component8>(server_entity)
+                    .unwrap();
+                component.position += 1.0;
+                if i == 30 {
+                    component.stance = Stance::Crouching;
+                }
+            }
+            stepper.frame_step();
+            if i == 30 {
+                // the update is sent with the tick of the server at the end of the frame
+                change_tick = Some(stepper.server_tick());
+            }
+            let Some(interpolated) = interpolated_entity(&stepper, server_entity) else {
+                continue;
+            };
+            let world = stepper.client_app.world();
+            if let (Some(component), Some(status)) = (
+                world.get::<Component8>(interpolated),
+                world.get::<InterpolateStatus<Component8>>(interpolated),
+            ) {
+                observed.push((status.current_tick, component.stance));
+            }
+        }
+
+        let change_tick = change_tick.unwrap();
+        assert!(observed.iter().any(|(tick, _)| *tick < change_tick));
+        assert!(observed.iter().any(|(tick, _)| *tick >= change_tick));
+        for (tick, stance) in observed {
+            let expected = if tick < change_tick {
+                Stance::Standing
+            } else {
+                Stance::Crouching
+            };
+            assert_eq!(
+                stance, expected,
+                "wrong stance at interpolation tick {tick:?}"
+            );
+        }
+    }
+
+    #[derive(Resource, Default)]
+    struct StarvedEvents(Vec<InterpolationStarvedEvent>);
+
+    fn collect_starved_events(
+        mut events: EventReader<InterpolationStarvedEvent>,
+        mut collected: ResMut<StarvedEvents>,
+    ) {
+        collected.0.extend(events.read().cloned());
+    }
+
+    /// Check that the buffered snapshots are tracked in the status, and that an event is emitted once
+    /// when the interpolation runs out of snapshots
+    #[test]
+    fn test_interpolation_starved() {
+        let frame_duration = Duration::from_millis(10);
+        let shared_config = SharedConfig {
+            tick: TickConfig::new(frame_duration),
+            ..default()
+        };
+        let client_config = client::ClientConfig {
+            interpolation: client::InterpolationConfig {
+                delay: client::InterpolationDelay::default()
+                    .with_min_delay(Duration::from_millis(50)),
+                ..default()
+            },
+            ..default()
+        };
+        let mut stepper = BevyStepper::new(shared_config, client_config, frame_duration);
+        stepper.init();
+        stepper.client_app.init_resource::<StarvedEvents>();
+        stepper.client_app.add_systems(Last, collect_starved_events);
+        let server_entity = stepper
+            .server_app
+            .world_mut()
+            .spawn((
+                Component8 {
+                    position: 0.0,
+                    stance: Stance::Standing,
+                },
+                Replicate {
+                    sync: SyncTarget {
+                        interpolation: NetworkTarget::All,
+                        ..default()
+                    },
+                    ..default()
+                },
+            ))
+            .id();
+
+        // the server sends an update every tick: the interpolation always has snapshots ahead
+        let mut max_snapshots_ahead = 0;
+        for _ in 0..60 {
+            stepper
+                .server_app
+                .world_mut()
+                .get_mut::<Component8>(server_entity)
+                .unwrap()
+                .position += 1.0;
+            stepper.frame_step();
+            let Some(interpolated) = interpolated_entity(&stepper, server_entity) else {
+                continue;
+            };
+            if let Some(status) = stepper
+                .client_app
+                .world()
+                .get::<InterpolateStatus<Component8>>(interpolated)
+            {
+                max_snapshots_ahead = max_snapshots_ahead.max(status.snapshots_ahead);
+            }
+        }
+        assert!(max_snapshots_ahead > 1);
+        assert!(stepper
+            .client_app
+            .world()
+            .resource::<StarvedEvents>()
+            .0
+            .is_empty());
+
+        // the updates stop: the interpolation consumes the buffered snapshots, then is starved
+        for _ in 0..40 {
+            stepper.frame_step();
+        }
+        let interpolated = interpolated_entity(&stepper, server_entity).unwrap();
+        let world = stepper.client_app.world();
+        let status = world
+            .get::<InterpolateStatus<Component8>>(interpolated)
+            .unwrap();
+        assert_eq!(status.snapshots_ahead, 0);
+        let events = &world.resource::<StarvedEvents>().0;
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].entity, interpolated);
+
+        // the buffer occupancy and the starvations are reported in the diagnostics
+        let diagnostics = world.resource::<DiagnosticsStore>();
+        let values = |path: DiagnosticPath| -> Vec<f64> {
+            diagnostics.get(&path).unwrap().values().copied().collect()
+        };
+        assert!(values(InterpolationDiagnosticsPlugin::BUFFERED_SNAPSHOTS)
+            .iter()
+            .any(|v| *v > 1.0));
+        assert_eq!(
+            values(InterpolationDiagnosticsPlugin::STARVATIONS)
+                .iter()
+                .sum::<f64>(),
+            1.0
+        );
+    }
+
+    /// The server moves the entity by 1.0 per tick for 60 frames, stops for 40 frames, then moves again.
+    /// Returns the server position when it stopped, and the positions of the interpolated entity
+    /// on every frame after that
+    fn run_starvation(
+        on_starvation: client::StarvationPolicy,
+        extrapolation: bool,
+    ) -> (f32, Vec<f32>) {
+        let frame_duration = Duration::from_millis(10);
+        let shared_config = SharedConfig {
+            tick: TickConfig::new(frame_duration),
+            ..default()
+        };
+        let client_config = client::ClientConfig {
+            interpolation: client::InterpolationConfig::default()
+                .with_delay(
+                    client::InterpolationDelay::default().with_min_delay(Duration::from_millis(50)),
+                )
+                .with_starvation_policy(on_starvation),
+            ..default()
+        };
+        let mut stepper = BevyStepper::new(shared_config, client_config, frame_duration);
+        stepper.init();
+        if extrapolation {
+            stepper
+                .client_app
+                .world_mut()
+                .resource_mut::<ComponentRegistry>()
+                .set_extrapolation::<Component8>();
+        }
+        let server_entity = stepper
+            .server_app
+            .world_mut()
+            .spawn((
+                Component8 {
+                    position: 0.0,
+                    stance: Stance::Standing,
+                },
+                Replicate {
+                    sync: SyncTarget {
+                        interpolation: NetworkTarget::All,
+                        ..default()
+                    },
+                    ..default()
+                },
+            ))
+            .id();
+
+        let move_entity = |stepper: &mut BevyStepper| {
+            stepper
+                .server_app
+                .world_mut()
+                .get_mut::<Component8>(server_entity)
+                .unwrap()
+                .position += 1.0;
+        };
+        for _ in 0..60 {
+            move_entity(&mut stepper);
+            stepper.frame_step();
+        }
+        let stopped_position = stepper
+            .server_app
+            .world()
+            .get::<Component8>(server_entity)
+            .unwrap()
+            .position;
+        let interpolated = interpolated_entity(&stepper, server_entity).unwrap();
+        let mut positions = vec![];
+        for i in 0..80 {
+            if i >= 40 {
+                move_entity(&mut stepper);
+            }
+            stepper.frame_step();
+            positions.push(
+                stepper
+                    .client_app
+                    .world()
+                    .get::<Component8>(interpolated)
+                    .unwrap()
+                    .position,
+            );
+        }
+        (stopped_position, positions)
+    }
+
+    /// Check that the components that opted in are extrapolated for at most `max_ticks` ticks when the
+    /// interpolation runs out of snapshots, and blend back smoothly when the snapshots arrive again
+    #[test]
+    fn test_extrapolation_on_starvation() {
+        let policy = client::StarvationPolicy::Extrapolate { max_ticks: 5 };
+
+        // by default the component holds the value of the last snapshot
+        let (stopped_position, positions) = run_starvation(policy, false);
+        assert_eq!(positions[35], stopped_position);
+
+        let (stopped_position, positions) = run_starvation(policy, true);
+        // the motion continues for 5 ticks, then the component holds
+        assert!(
+            (positions[35] - (stopped_position + 5.0)).abs() < 0.01,
+            "{positions:?}"
+        );
+        assert!(positions[..40]
+            .iter()
+            .all(|position| *position <= stopped_position + 5.0 + 0.01));
+        // when the snapshots arrive again, the component blends back instead of snapping back
+        for window in positions[35..].windows(2) {
+            assert!((window[1] - window[0]).abs() < 2.0, "{positions:?}");
+        }
+    }
+}
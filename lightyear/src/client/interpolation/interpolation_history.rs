@@ -81,7 +81,7 @@ pub(crate) fn add_component_history<C: SyncComponent>(
     tick_manager: Res<TickManager>,
     mut commands: Commands,
     connection: Res<ConnectionManager>,
-    interpolated_entities: Query<Entity, (Without<ConfirmedHistory<C>>, With<Interpolated>)>,
+    interpolated_entities: Query<(Entity, Ref<Interpolated>), Without<ConfirmedHistory<C>>>,
     confirmed_entities: Query<(&Confirmed, Ref<C>)>,
 ) {
     let current_tick = connection
@@ -92,8 +92,10 @@ pub(crate) fn add_component_history<C: SyncComponent>(
         .interpolation_overstep(tick_manager.as_ref());
     for (confirmed_entity, confirmed_component) in confirmed_entities.iter() {
         if let Some(p) = confirmed_entity.interpolated {
-            if let Ok(interpolated_entity) = interpolated_entities.get(p) {
-                if confirmed_component.is_added() {
+            if let Ok((interpolated_entity, interpolated)) = interpolated_entities.get(p) {
+                // the interpolated entity can be spawned for a confirmed entity that already existed
+                // (if the client was added to the interpolation target)
+                if confirmed_component.is_added() || interpolated.is_added() {
                     // safety: we know the entity exists
                     let mut interpolated_entity_mut =
                         commands.get_entity(interpolated_entity).unwrap();
@@ -116,6 +118,10 @@ pub(crate) fn add_component_history<C: SyncComponent>(
                                     end: None,
                                     current_tick,
                                     current_overstep,
+                                    snapshots_ahead: 0,
+                                    snapshots_behind: 1,
+                                    previous: None,
+                                    extrapolation: None,
                                 },
                             ));
                         }
@@ -3,7 +3,7 @@ use std::ops::{Add, Mul};
 
 use bevy::prelude::{Component, Entity, Reflect};
 
-pub use interpolate::InterpolateStatus;
+pub use interpolate::{InterpolateStatus, InterpolationStarvedEvent};
 pub use interpolation_history::ConfirmedHistory;
 pub use plugin::{add_interpolation_systems, add_prepare_interpolation_systems};
 pub use visual_interpolation::{VisualInterpolateStatus, VisualInterpolationPlugin};
@@ -11,10 +11,11 @@ pub use visual_interpolation::{VisualInterpolateStatus, VisualInterpolationPlugi
 use crate::client::components::LerpFn;
 
 mod despawn;
+pub mod diagnostics;
 pub mod interpolate;
 pub mod interpolation_history;
 pub mod plugin;
-mod resource;
+pub(crate) mod resource;
 mod spawn;
 pub mod visual_interpolation;
 
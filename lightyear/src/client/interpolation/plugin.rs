@@ -2,15 +2,21 @@ use bevy::prelude::*;
 use bevy::utils::Duration;
 
 use crate::client::components::{ComponentSyncMode, SyncComponent};
-use crate::client::interpolation::despawn::{despawn_interpolated, removed_components};
+use crate::client::interpolation::despawn::{
+    despawn_interpolated, remove_interpolation_components_on_strip, removed_components,
+};
+use crate::client::prediction::adaptive::seed_interpolated_from_predicted;
 use crate::client::interpolation::interpolate::{
     insert_interpolated_component, interpolate, update_interpolate_status,
 };
 use crate::client::interpolation::resource::InterpolationManager;
 use crate::client::interpolation::spawn::spawn_interpolated_entity;
-use crate::client::interpolation::Interpolated;
+use crate::client::interpolation::{Interpolated, InterpolationStarvedEvent};
 use crate::client::run_conditions::is_synced;
 use crate::prelude::is_host_server;
+use crate::shared::replication::handoff::{
+    complete_handoffs, link_handoffs, seed_handoff_history, HandoffSource, HandoffTarget,
+};
 
 use super::interpolation_history::{
     add_component_history, apply_confirmed_update_mode_full, apply_confirmed_update_mode_simple,
@@ -58,10 +64,29 @@ impl InterpolationDelay {
     }
 }
 
+/// What the interpolated components do when the interpolation reaches the last server snapshot that was
+/// received, and the next snapshot hasn't arrived yet
+#[derive(Clone, Copy, Debug, Default, PartialEq, Reflect)]
+pub enum StarvationPolicy {
+    /// The components keep the value of the last snapshot
+    #[default]
+    Hold,
+    /// The components that were registered with
+    /// [`add_extrapolation`](crate::protocol::component::ComponentRegistration::add_extrapolation) continue their motion,
+    /// using the velocity implied by the last two snapshots, for at most `max_ticks` ticks; then they hold.
+    /// The other components hold.
+    ///
+    /// When the next snapshot arrives, the components blend back from the extrapolated value to the interpolated
+    /// value over as many ticks as they were extrapolated, instead of snapping.
+    Extrapolate { max_ticks: u16 },
+}
+
 /// Config to specify how the snapshot interpolation should behave
 #[derive(Clone, Copy, Reflect)]
 pub struct InterpolationConfig {
     pub delay: InterpolationDelay,
+    /// What to do when the interpolation runs out of server snapshots
+    pub on_starvation: StarvationPolicy,
     // How long are we keeping the history of the confirmed entities so we can interpolate between them?
     // pub(crate) interpolation_buffer_size: Duration,
 }
@@ -71,6 +96,7 @@ impl Default for InterpolationConfig {
     fn default() -> Self {
         Self {
             delay: InterpolationDelay::default(),
+            on_starvation: StarvationPolicy::default(),
             // interpolation_buffer_size: Duration::from_millis(100),
         }
     }
@@ -81,6 +107,11 @@ impl InterpolationConfig {
         self.delay = delay;
         self
     }
+
+    pub fn with_starvation_policy(mut self, on_starvation: StarvationPolicy) -> Self {
+        self.on_starvation = on_starvation;
+        self
+    }
 }
 
 #[derive(Default)]
@@ -129,14 +160,21 @@ pub fn add_prepare_interpolation_systems<C: SyncComponent>(
     // TODO: maybe create an overarching prediction set that contains all others?
     app.add_systems(
         Update,
-        add_component_history::<C>.in_set(InterpolationSet::SpawnHistory),
+        (
+            add_component_history::<C>,
+            // seed the interpolation if the local player got demoted from prediction
+            seed_interpolated_from_predicted::<C>,
+        )
+            .in_set(InterpolationSet::SpawnHistory),
     );
     app.observe(removed_components::<C>);
+    app.observe(remove_interpolation_components_on_strip::<C>);
     match interpolation_mode {
         ComponentSyncMode::Full => {
             app.add_systems(
                 Update,
                 (
+                    seed_handoff_history::<C>,
                     apply_confirmed_update_mode_full::<C>,
                     update_interpolate_status::<C>.run_if(is_synced),
                     // TODO: that means we could insert the component twice, here and then in interpolate...
@@ -174,7 +212,13 @@ impl Plugin for InterpolationPlugin {
         // REFLECT
         app.register_type::<InterpolationConfig>()
             .register_type::<InterpolationDelay>()
-            .register_type::<Interpolated>();
+            .register_type::<StarvationPolicy>()
+            .register_type::<Interpolated>()
+            .register_type::<HandoffSource>()
+            .register_type::<HandoffTarget>();
+
+        // EVENTS
+        app.add_event::<InterpolationStarvedEvent>();
 
         // RESOURCES
         app.init_resource::<InterpolationManager>();
@@ -197,7 +241,15 @@ impl Plugin for InterpolationPlugin {
         // SYSTEMS
         app.add_systems(
             Update,
-            spawn_interpolated_entity.in_set(InterpolationSet::SpawnInterpolation),
+            (
+                spawn_interpolated_entity,
+                link_handoffs.after(spawn_interpolated_entity),
+            )
+                .in_set(InterpolationSet::SpawnInterpolation),
+        );
+        app.add_systems(
+            Update,
+            complete_handoffs.in_set(InterpolationSet::Interpolate),
         );
         app.observe(despawn_interpolated);
     }
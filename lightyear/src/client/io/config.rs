@@ -1,18 +1,18 @@
 use crate::client::io::transport::{ClientTransportBuilder, ClientTransportBuilderEnum};
 use crate::client::io::{Io, IoContext};
-use crate::prelude::CompressionConfig;
+use crate::shared::clock::NetworkClock;
 use crate::transport::config::SharedIoConfig;
 use crate::transport::dummy::DummyIo;
 use crate::transport::error::Result;
 use crate::transport::io::{BaseIo, IoStats};
 use crate::transport::local::LocalChannelBuilder;
-#[cfg(feature = "zstd")]
-use crate::transport::middleware::compression::zstd::compression::ZstdCompressor;
-#[cfg(feature = "zstd")]
-use crate::transport::middleware::compression::zstd::decompression::ZstdDecompressor;
+use crate::transport::middleware::compression::negotiation::{self, CompressionRole};
+use crate::transport::middleware::compression::CompressionCapabilities;
 use crate::transport::middleware::conditioner::LinkConditioner;
 use crate::transport::middleware::PacketReceiverWrapper;
 use crate::transport::udp::UdpSocketBuilder;
+#[cfg(feature = "webtransport")]
+use crate::transport::webtransport::WebTransportClientSocketBuilder;
 
 use crate::transport::{BoxedReceiver, Transport, LOCAL_SOCKET};
 use bevy::prelude::TypePath;
@@ -25,7 +25,18 @@ use std::net::SocketAddr;
 pub enum ClientTransport {
     /// Use a [`UdpSocket`](std::net::UdpSocket)
     UdpSocket(SocketAddr),
-
+    /// Use [`WebTransport`](https://wicg.github.io/web-transport/) as a transport layer
+    #[cfg(feature = "webtransport")]
+    WebTransportClient {
+        /// Address to bind the client socket to. On wasm, the browser picks the socket
+        client_addr: SocketAddr,
+        server_addr: SocketAddr,
+        /// Hex-encoded SHA-256 digest of the server certificate (the bytes can be separated by `:`).
+        ///
+        /// Required to connect to a server with a self-signed certificate from a browser. If empty,
+        /// the certificate is validated by the browser on wasm, and is not validated at all on native.
+        certificate_digest: String,
+    },
     /// Use a crossbeam_channel as a transport. This is useful for testing.
     /// This is mostly for clients.
     LocalChannel {
@@ -42,7 +53,16 @@ impl ClientTransport {
             ClientTransport::UdpSocket(addr) => {
                 ClientTransportBuilderEnum::UdpSocket(UdpSocketBuilder { local_addr: addr })
             }
-           
+            #[cfg(feature = "webtransport")]
+            ClientTransport::WebTransportClient {
+                client_addr,
+                server_addr,
+                certificate_digest,
+            } => ClientTransportBuilderEnum::WebTransportClient(WebTransportClientSocketBuilder {
+                client_addr,
+                server_addr,
+                certificate_digest,
+            }),
             ClientTransport::LocalChannel { recv, send } => {
                 ClientTransportBuilderEnum::LocalChannel(LocalChannelBuilder { recv, send })
             }
@@ -59,38 +79,27 @@ impl Default for ClientTransport {
 
 impl SharedIoConfig<ClientTransport> {
     pub fn connect(self) -> Result<Io> {
+        self.connect_with_clock(&NetworkClock::default())
+    }
+
+    /// Connect the transport, the link conditioner reads the time from `clock`
+    pub fn connect_with_clock(self, clock: &NetworkClock) -> Result<Io> {
         let (transport, state, io_rx, network_tx) = self.transport.build().connect()?;
         let local_addr = transport.local_addr();
-        #[allow(unused_mut)]
-        let (mut sender, receiver) = transport.split();
-        #[allow(unused_mut)]
-        let mut receiver: BoxedReceiver = if let Some(conditioner_config) = self.conditioner {
-            let conditioner = LinkConditioner::new(conditioner_config);
+        let (sender, receiver) = transport.split();
+        let receiver: BoxedReceiver = if let Some(conditioner_config) = self.conditioner {
+            let conditioner = LinkConditioner::new(conditioner_config, clock.clone());
             Box::new(conditioner.wrap(receiver))
         } else {
             Box::new(receiver)
         };
-        match self.compression {
-            CompressionConfig::None => {}
-            #[cfg(feature = "zstd")]
-            CompressionConfig::Zstd { level } => {
-                use crate::transport::middleware::PacketSenderWrapper;
-                let compressor = ZstdCompressor::new(level);
-                sender = Box::new(compressor.wrap(sender));
-                let decompressor = ZstdDecompressor::new();
-                receiver = Box::new(decompressor.wrap(receiver));
-            }
-            #[cfg(feature = "lz4")]
-            CompressionConfig::Lz4 => {
-                use crate::transport::middleware::PacketSenderWrapper;
-                let compressor =
-                    crate::transport::middleware::compression::lz4::Compressor::default();
-                sender = Box::new(compressor.wrap(sender));
-                let decompressor =
-                    crate::transport::middleware::compression::lz4::Decompressor::default();
-                receiver = Box::new(decompressor.wrap(receiver));
-            }
-        }
+        let (sender, receiver) = negotiation::wrap(
+            sender,
+            receiver,
+            self.compression,
+            CompressionRole::Client,
+            CompressionCapabilities::supported(),
+        );
         Ok(BaseIo {
             local_addr,
             sender,
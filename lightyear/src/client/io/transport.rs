@@ -4,6 +4,8 @@ use crate::transport::error::Error as TransportError;
 use crate::transport::io::IoState;
 use crate::transport::local::{LocalChannel, LocalChannelBuilder};
 use crate::transport::udp::{UdpSocket, UdpSocketBuilder};
+#[cfg(feature = "webtransport")]
+use crate::transport::webtransport::{WebTransportClientSocket, WebTransportClientSocketBuilder};
 
 use enum_dispatch::enum_dispatch;
 
@@ -30,6 +32,8 @@ pub(crate) trait ClientTransportBuilder: Send + Sync {
 #[enum_dispatch(ClientTransportBuilder)]
 pub(crate) enum ClientTransportBuilderEnum {
     UdpSocket(UdpSocketBuilder),
+    #[cfg(feature = "webtransport")]
+    WebTransportClient(WebTransportClientSocketBuilder),
     LocalChannel(LocalChannelBuilder),
     Dummy(DummyIo),
 }
@@ -38,6 +42,8 @@ pub(crate) enum ClientTransportBuilderEnum {
 #[enum_dispatch(Transport)]
 pub(crate) enum ClientTransportEnum {
     UdpSocket(UdpSocket),
+    #[cfg(feature = "webtransport")]
+    WebTransportClient(WebTransportClientSocket),
     LocalChannel(LocalChannel),
     Dummy(DummyIo),
 }
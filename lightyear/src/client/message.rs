@@ -1,16 +1,20 @@
 //! Defines the [`ClientMessage`] enum used to send messages from the client to the server
 
+use std::ops::DerefMut;
+
+use bevy::ecs::entity::Entities;
 use bevy::prelude::{App, EventWriter, IntoSystemConfigs, PreUpdate, Res, ResMut};
 use byteorder::WriteBytesExt;
 use bytes::Bytes;
-use tracing::error;
+use tracing::{error, trace};
 
 use crate::client::connection::ConnectionManager;
 use crate::client::events::MessageEvent;
 use crate::prelude::{client::is_connected, Message};
-use crate::protocol::message::{MessageKind, MessageRegistry};
+use crate::protocol::message::{MessageError, MessageKind, MessageRegistry};
 use crate::serialize::reader::Reader;
 use crate::serialize::{SerializationError, ToBytes};
+use crate::shared::replication::deferred::bound_deferred_messages;
 use crate::shared::replication::network_target::NetworkTarget;
 use crate::shared::sets::{ClientMarker, InternalMainSet};
 
@@ -40,17 +44,24 @@ impl ToBytes for ClientMessage {
         let target = NetworkTarget::from_bytes(buffer)?;
         // NOTE: this only works if the reader only contains the ClientMessage bytes!
         let remaining = buffer.remaining();
-        let message = buffer.split_len(remaining);
+        let message = buffer.split_len(remaining)?;
         Ok(Self { message, target })
     }
 }
 
 /// Read the message received from the server and emit the MessageEvent event
+///
+/// The messages that reference entities that were not spawned yet are read again on the next frame.
 fn read_message<M: Message>(
     message_registry: Res<MessageRegistry>,
     mut connection: ResMut<ConnectionManager>,
     mut event: EventWriter<MessageEvent<M>>,
+    entities: &Entities,
 ) {
+    // the messages are handled by the apply function instead
+    if message_registry.client_apply::<M>().is_some() {
+        return;
+    }
     let kind = MessageKind::of::<M>();
     let Some(net) = message_registry.kind_map.net_id(&kind).copied() else {
         error!(
@@ -59,21 +70,39 @@ fn read_message<M: Message>(
         );
         return;
     };
+    // re-borrow to allow split borrows
+    let connection = connection.deref_mut();
     if let Some(message_list) = connection.received_messages.remove(&net) {
-        for message in message_list {
-            let mut reader = Reader::from(message);
+        let mut deferred = Vec::new();
+        for (message_bytes, tick, timeline) in message_list {
+            let mut reader = Reader::from(message_bytes.clone());
             // we have to re-decode the net id
-            let Ok(message) = message_registry.deserialize::<M>(
+            match message_registry.deserialize_spawned::<M>(
                 &mut reader,
                 &mut connection
                     .replication_receiver
                     .remote_entity_map
                     .remote_to_local,
-            ) else {
-                error!("Could not deserialize message");
-                continue;
-            };
-            event.send(MessageEvent::new(message, ()));
+                entities,
+            ) {
+                Ok(message) => {
+                    event.send(MessageEvent::new(message, ()).with_timeline(timeline));
+                }
+                Err(MessageError::UnspawnedEntity(entity)) => {
+                    trace!(
+                        ?entity,
+                        "Deferred a message that references an entity that was not spawned yet"
+                    );
+                    deferred.push((message_bytes, tick, timeline));
+                }
+                Err(_) => {
+                    error!("Could not deserialize message");
+                }
+            }
+        }
+        if !deferred.is_empty() {
+            bound_deferred_messages(&mut deferred, &mut connection.warnings);
+            connection.received_messages.insert(net, deferred);
         }
     }
 }
@@ -1,10 +1,16 @@
 /*! Modules related to the client
 */
 
+pub mod apply;
+
+pub mod cleanup;
+
 pub mod components;
 
 pub mod config;
 
+pub mod connect;
+
 pub mod connection;
 
 pub mod events;
@@ -5,29 +5,55 @@ use async_channel::TryRecvError;
 use bevy::ecs::system::{RunSystemOnce, SystemChangeTick};
 use bevy::prelude::ResMut;
 use bevy::prelude::*;
-use tracing::{error, trace};
+use bevy::time::TimeSystem;
+use tracing::{error, info, trace};
 
+use crate::client::cleanup::{cleanup_on_reconnect, cleanup_received_entities, CleanupCause};
 use crate::client::config::ClientConfig;
+use crate::client::connect::{
+    apply_token_provider, cancel_connect, check_connect_timeout, retry_connect, ConnectAttempt,
+};
 use crate::client::connection::ConnectionManager;
-use crate::client::events::{ConnectEvent, DisconnectEvent};
+use crate::client::error::ConnectError;
+use crate::client::events::{
+    ChannelOccupancyEvent, ConnectEvent, ConnectionFailedEvent, DisconnectEvent, MessageAckEvent,
+    MessageEvent, TransferEvent, TransportMigrationEvent, UnconnectedPacketEvent,
+};
 use crate::client::interpolation::Interpolated;
 use crate::client::io::ClientIoEvent;
 use crate::client::networking::utils::AppStateExt;
+use crate::client::prediction::plugin::is_in_rollback;
 use crate::client::prediction::Predicted;
 use crate::client::replication::send::ReplicateToServer;
-use crate::client::run_conditions::is_disconnected;
-use crate::client::sync::SyncSet;
-use crate::connection::client::{ClientConnection, ConnectionState, DisconnectReason, NetClient};
-use crate::connection::server::IoConfig;
+use crate::client::run_conditions::{is_connected, is_disconnected};
+use crate::client::sync::{
+    count_frame_ticks, detect_tick_stall, limit_catch_up_ticks, SyncSet, TickDebt,
+};
+use crate::connection::client::{
+    ClientConnection, ConnectionState, DisconnectReason, NetClient, NetClientDispatch,
+};
+use crate::connection::server::{DisconnectReason as ServerDisconnectReason, IoConfig};
 use crate::prelude::{
     is_host_server, ChannelRegistry, MainSet, MessageRegistry, TickManager, TimeManager,
 };
 use crate::protocol::component::ComponentRegistry;
+use crate::protocol::registry::NetIdReport;
 use crate::server::clients::ControlledEntities;
+use crate::server::relevance::hint::receive_interest_responses;
+use crate::shared::action::{resolve_actions, ActionTracker};
+use crate::shared::clock::NetworkClock;
 use crate::shared::config::Mode;
+use crate::shared::hooks::{PostSync, RunNetworkHooks};
+use crate::shared::input::jitter::receive_input_delay_advice;
+use crate::shared::network_time::{update_client_network_time, ServerTimeMessage};
 use crate::shared::replication::components::Replicated;
+use crate::shared::replication::prespawn_ids::{self, PreSpawnIdAllocator};
+use crate::shared::request::expire_client_requests;
+use crate::shared::schema::{receive_server_schemas, send_client_schemas};
+use crate::shared::session_summary::SessionSummary;
 use crate::shared::sets::{ClientMarker, InternalMainSet};
 use crate::transport::io::IoState;
+use crate::transport::PacketSender;
 
 #[derive(Default)]
 pub(crate) struct ClientNetworkingPlugin;
@@ -42,6 +68,10 @@ impl Plugin for ClientNetworkingPlugin {
             .init_state_without_entering(NetworkingState::Disconnected)
             // RESOURCE
             .init_resource::<HostServerMetadata>()
+            .init_resource::<ConnectAttempt>()
+            .init_resource::<ActionTracker>()
+            .init_resource::<PreSpawnIdAllocator>()
+            .init_resource::<TickDebt>()
             // SYSTEM SETS
             .configure_sets(
                 PreUpdate,
@@ -80,6 +110,48 @@ impl Plugin for ClientNetworkingPlugin {
                 PreUpdate,
                 (listen_io_state, receive).in_set(InternalMainSet::<ClientMarker>::Receive),
             )
+            .add_systems(
+                PreUpdate,
+                (
+                    emit_transfer_events,
+                    emit_message_ack_events,
+                    emit_channel_occupancy_events,
+                )
+                    .in_set(InternalMainSet::<ClientMarker>::EmitEvents),
+            )
+            .add_systems(
+                PreUpdate,
+                (
+                    check_connect_timeout
+                        .after(InternalMainSet::<ClientMarker>::Receive)
+                        .run_if(in_state(NetworkingState::Connecting)),
+                    retry_connect.run_if(is_disconnected),
+                )
+                    .run_if(not(is_host_server)),
+            )
+            // runs even when disconnected, so that the pending actions time out
+            .add_systems(
+                PreUpdate,
+                (
+                    resolve_actions,
+                    expire_client_requests,
+                    receive_interest_responses,
+                    prespawn_ids::receive_range,
+                    receive_input_delay_advice,
+                    receive_server_schemas,
+                )
+                    .after(InternalMainSet::<ClientMarker>::EmitEvents),
+            )
+            .add_systems(FixedFirst, prespawn_ids::start_client_tick)
+            // TICK DEBT
+            .add_systems(
+                First,
+                (
+                    limit_catch_up_ticks.before(TimeSystem),
+                    detect_tick_stall.after(TimeSystem),
+                ),
+            )
+            .add_systems(FixedFirst, count_frame_ticks.run_if(not(is_in_rollback)))
             // TODO: make HostServer a computed state?
             .add_systems(
                 PostUpdate,
@@ -90,17 +162,35 @@ impl Plugin for ClientNetworkingPlugin {
                     )
                         .in_set(InternalMainSet::<ClientMarker>::Send),
                     // TODO: update virtual time with Time<Real> so we have more accurate time at Send time.
-                    sync_update.in_set(SyncSet),
+                    (check_server_tick_rate, sync_update)
+                        .chain()
+                        .in_set(SyncSet),
+                    update_client_network_time
+                        .after(SyncSet)
+                        .before(InternalMainSet::<ClientMarker>::Send)
+                        .run_if(is_connected.and_then(not(is_host_server))),
                 ),
             );
 
         // CONNECTING
         app.add_systems(OnEnter(NetworkingState::Connecting), connect);
+        // the entities of the previous connection are not cleaned up by `on_disconnect` if we reconnect directly
+        app.add_systems(
+            OnTransition {
+                exited: NetworkingState::Connected,
+                entered: NetworkingState::Connecting,
+            },
+            cleanup_on_reconnect,
+        );
 
         // CONNECTED
         app.add_systems(
             OnEnter(NetworkingState::Connected),
-            (on_connect, on_connect_host_server.run_if(is_host_server)),
+            (
+                on_connect,
+                on_connect_host_server.run_if(is_host_server),
+                send_client_schemas.run_if(not(is_host_server)),
+            ),
         );
 
         // DISCONNECTED
@@ -109,6 +199,7 @@ impl Plugin for ClientNetworkingPlugin {
             (
                 on_disconnect,
                 on_disconnect_host_server.run_if(is_host_server),
+                prespawn_ids::clear_client_range,
             ),
         );
     }
@@ -152,6 +243,14 @@ pub(crate) fn receive(world: &mut World) {
                                                                 });
                                                         }
 
+                                                        // packets from unconnected endpoints are not handled by the netcode protocol
+                                                        while let Some((from, payload)) = netclient.recv_unconnected() {
+                                                            world.send_event(UnconnectedPacketEvent { from, payload });
+                                                        }
+                                                        while let Some(server_addr) = netclient.recv_transport_migration() {
+                                                            world.send_event(TransportMigrationEvent { server_addr });
+                                                        }
+
                                                         if matches!(netclient.state(), ConnectionState::Connected) {
                                                             // we just connected, do a state transition
                                                             if state.get() != &NetworkingState::Connected {
@@ -176,10 +275,11 @@ pub(crate) fn receive(world: &mut World) {
 
                                                         // RECV PACKETS: buffer packets into message managers
                                                         while let Some(packet) = netclient.recv() {
-                                                            connection
-                                                                .recv_packet(packet, tick_manager.as_ref(), world.resource::<ComponentRegistry>())
-                                                                .unwrap();
+                                                            connection.receive_queue.push(packet);
                                                         }
+                                                        connection
+                                                            .process_received_packets(tick_manager.as_ref(), world.resource::<ComponentRegistry>())
+                                                            .unwrap();
                                                         // RECEIVE: receive packets from message managers
                                                         let _ = connection.receive(world, time_manager.as_ref(), tick_manager.as_ref()).inspect_err(|e| error!("Error receiving packets: {}", e));
                                                     });
@@ -203,13 +303,31 @@ pub(crate) fn send(
 ) {
     trace!("Send packets to server");
     // SEND_PACKETS: send buffered packets to io
-    let packet_bytes = connection
-        .send_packets(time_manager.as_ref(), tick_manager.as_ref())
-        .unwrap();
-    for packet_byte in packet_bytes {
-        let _ = netcode.send(packet_byte.as_slice()).map_err(|e| {
-            error!("Error sending packet: {}", e);
-        });
+    // (the transport would drop them while the connection is still being established)
+    if matches!(netcode.state(), ConnectionState::Connected) {
+        let packet_bytes = connection
+            .send_packets(time_manager.as_ref(), tick_manager.as_ref())
+            .unwrap();
+        for packet_byte in &packet_bytes {
+            let _ = netcode.send(packet_byte.as_slice()).map_err(|e| {
+                error!("Error sending packet: {}", e);
+            });
+        }
+        connection.recycle_payloads(packet_bytes);
+    }
+
+    // SEND_UNCONNECTED: send the raw packets to unconnected endpoints using the same socket
+    if !connection.unconnected_packets_to_send.is_empty() {
+        if let Some(io) = netcode.io_mut() {
+            for (addr, packet) in connection.unconnected_packets_to_send.drain(..) {
+                let _ = io.send(packet.as_slice(), &addr).inspect_err(|e| {
+                    error!("Error sending unconnected packet to {}: {}", addr, e);
+                });
+            }
+        } else {
+            error!("Cannot send unconnected packets: the client transport does not use an Io");
+            connection.unconnected_packets_to_send.clear();
+        }
     }
 
     // no need to clear the connection, because we already std::mem::take it
@@ -249,6 +367,7 @@ pub(crate) fn sync_update(
     mut virtual_time: ResMut<Time<Virtual>>,
 ) {
     let connection = connection.into_inner();
+    let was_synced = connection.sync_manager.is_synced();
     // NOTE: this triggers change detection
     // Handle pongs, update RTT estimates, update client prediction time
     if let Some(tick_event) = connection.sync_manager.update(
@@ -261,13 +380,26 @@ pub(crate) fn sync_update(
     ) {
         commands.trigger(tick_event);
     }
+    if !was_synced && connection.sync_manager.is_synced() {
+        commands.add(RunNetworkHooks::<PostSync>(()));
+    }
 
     if connection.sync_manager.is_synced() {
-        if let Some(tick_event) = connection.sync_manager.update_prediction_time(
-            time_manager.deref_mut(),
-            tick_manager.deref_mut(),
-            &connection.ping_manager,
-        ) {
+        // some ticks were not simulated this frame: snap the client tick to the sync objective
+        let tick_event = if std::mem::take(&mut connection.sync_manager.resync_requested) {
+            connection.sync_manager.finalize(
+                time_manager.deref_mut(),
+                tick_manager.deref_mut(),
+                &connection.ping_manager,
+            )
+        } else {
+            connection.sync_manager.update_prediction_time(
+                time_manager.deref_mut(),
+                tick_manager.deref_mut(),
+                &connection.ping_manager,
+            )
+        };
+        if let Some(tick_event) = tick_event {
             commands.trigger(tick_event);
         }
         let relative_speed = time_manager.get_relative_speed();
@@ -275,6 +407,75 @@ pub(crate) fn sync_update(
     }
 }
 
+/// Check the tick duration sent by the server before the sync can complete.
+///
+/// If it is different from the client's, the client either adopts the server's tick duration
+/// (when [`SyncConfig::accept_server_tick_rate`](crate::client::sync::SyncConfig::accept_server_tick_rate)
+/// is enabled), or aborts the connection with [`ConnectError::TickRateMismatch`].
+///
+/// If the server changes its tick duration at runtime, the client always adopts it and re-syncs with the server.
+pub(crate) fn check_server_tick_rate(
+    mut config: ResMut<ClientConfig>,
+    mut connection: ResMut<ConnectionManager>,
+    mut netclient: ResMut<ClientConnection>,
+    mut tick_manager: ResMut<TickManager>,
+    mut fixed_time: ResMut<Time<Fixed>>,
+    mut next_state: ResMut<NextState<NetworkingState>>,
+    mut events: EventReader<MessageEvent<ServerTimeMessage>>,
+) {
+    let Some(server) = events
+        .read()
+        .last()
+        .map(|event| event.message.tick_duration)
+    else {
+        return;
+    };
+    let client = config.shared.tick.tick_duration;
+    match connection.sync_manager.server_tick_duration {
+        Some(previous) if previous == server => return,
+        Some(previous) => {
+            info!(
+                ?previous,
+                new = ?server,
+                "The server changed its tick duration, re-syncing"
+            );
+        }
+        None if server != client && !config.sync.accept_server_tick_rate => {
+            error!(
+                ?client,
+                ?server,
+                "The tick duration of the server is different from the client's, aborting the connection"
+            );
+            netclient.disconnect_reason =
+                Some(DisconnectReason::Connect(ConnectError::TickRateMismatch {
+                    client,
+                    server,
+                }));
+            next_state.set(NetworkingState::Disconnected);
+            return;
+        }
+        None if server != client => {
+            info!(?client, ?server, "Using the tick duration of the server");
+        }
+        None => {}
+    }
+    if server != client {
+        // the input delay settings are expressed in ticks, rescale them to keep the same durations
+        config.prediction = config.prediction.rescale_ticks(client, server);
+        connection
+            .sync_manager
+            .set_prediction_config(config.prediction);
+        let rtt = connection.ping_manager.rtt();
+        connection
+            .sync_manager
+            .change_tick_duration(client, server, rtt);
+        config.shared.tick.tick_duration = server;
+        tick_manager.config.tick_duration = server;
+        fixed_time.set_timestep(server);
+    }
+    connection.sync_manager.server_tick_duration = Some(server);
+}
+
 /// Bevy [`State`] representing the networking state of the client.
 #[derive(States, Default, Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum NetworkingState {
@@ -287,6 +488,54 @@ pub enum NetworkingState {
     Connected,
 }
 
+/// Emit a [`TransferEvent`] for each transfer with the server that finished since the last frame
+fn emit_transfer_events(
+    mut connection: ResMut<ConnectionManager>,
+    mut events: EventWriter<TransferEvent>,
+) {
+    events.send_batch(
+        connection
+            .message_manager
+            .take_transfer_events()
+            .into_iter()
+            .map(|(handle, outcome)| TransferEvent { handle, outcome }),
+    );
+}
+
+/// Emit a [`MessageAckEvent`] for each message that the server acked since the last frame
+fn emit_message_ack_events(
+    mut connection: ResMut<ConnectionManager>,
+    mut events: EventWriter<MessageAckEvent>,
+) {
+    events.send_batch(
+        connection
+            .message_manager
+            .take_message_acks()
+            .into_iter()
+            .map(|(channel_kind, message_id)| MessageAckEvent {
+                message_id,
+                channel_kind,
+            }),
+    );
+}
+
+/// Emit a [`ChannelOccupancyEvent`] for each channel whose send buffer went above the warning threshold
+fn emit_channel_occupancy_events(
+    mut connection: ResMut<ConnectionManager>,
+    mut events: EventWriter<ChannelOccupancyEvent>,
+) {
+    events.send_batch(
+        connection
+            .message_manager
+            .take_occupancy_warnings()
+            .into_iter()
+            .map(|(channel_kind, occupancy)| ChannelOccupancyEvent {
+                channel_kind,
+                occupancy,
+            }),
+    );
+}
+
 /// Listen to [`ClientIoEvent`]s and update the [`IoState`] and [`NetworkingState`] accordingly
 fn listen_io_state(
     mut next_state: ResMut<NextState<NetworkingState>>,
@@ -343,8 +592,10 @@ fn on_connect(
     mut connect_event_writer: EventWriter<ConnectEvent>,
     mut commands: Commands,
     netcode: Res<ClientConnection>,
+    mut attempt: ResMut<ConnectAttempt>,
     mut query: Query<&mut ReplicateToServer>,
 ) {
+    attempt.succeed();
     // Set all the ReplicateToServer ticks to changed, so that we replicate existing entities to the server
     for mut replicate in query.iter_mut() {
         // TODO: ideally set is_added instead of simply changed
@@ -380,22 +631,30 @@ fn on_connect_host_server(
 /// System that runs when we enter the Disconnected state
 /// Updates the DisconnectEvent events
 fn on_disconnect(
+    config: Res<ClientConfig>,
     mut connection_manager: ResMut<ConnectionManager>,
     mut disconnect_event_writer: EventWriter<DisconnectEvent>,
+    mut connection_failed_writer: EventWriter<ConnectionFailedEvent>,
+    mut attempt: ResMut<ConnectAttempt>,
     mut netclient: ResMut<ClientConnection>,
     mut commands: Commands,
-    received_entities: Query<Entity, Or<(With<Replicated>, With<Predicted>, With<Interpolated>)>>,
+    received_entities: Query<
+        (Entity, Option<&Predicted>, Option<&Interpolated>),
+        Or<(With<Replicated>, With<Predicted>, With<Interpolated>)>,
+    >,
 ) {
     info!("Running OnDisconnect schedule");
-    // despawn any entities that were spawned from replication
-    received_entities.iter().for_each(|e| {
-        if let Some(commands) = commands.get_entity(e) {
-            commands.despawn_recursive();
-        }
-    });
+    // clean up the entities that were spawned from replication
+    cleanup_received_entities(
+        CleanupCause::Disconnect,
+        &config,
+        &mut commands,
+        &received_entities,
+    );
 
     // set synced to false
     connection_manager.sync_manager.synced = false;
+    connection_manager.message_manager.reset_channel_stats();
 
     // try to disconnect again to close io tasks (in case the disconnection is from the io)
     let _ = netclient.disconnect();
@@ -403,7 +662,14 @@ fn on_disconnect(
     // no need to update the io state, because we will recreate a new `ClientConnection`
     // for the next connection attempt
     let reason = std::mem::take(&mut netclient.disconnect_reason);
-    disconnect_event_writer.send(DisconnectEvent { reason });
+    // if we were still connecting, the connection attempt failed: retry or give up
+    if let Some(error) = attempt.fail(reason.as_ref(), &config.connect.retry) {
+        info!(?error, "Connection failed");
+        connection_failed_writer.send(ConnectionFailedEvent { error });
+    }
+    let mut summary = connection_manager.session_summary();
+    summary.disconnect_reason = reason.as_ref().map(|reason| format!("{reason:?}"));
+    disconnect_event_writer.send(DisconnectEvent { reason, summary });
     // commands.trigger(DisconnectEvent { reason });
     // TODO: remove ClientConnection and ConnectionManager resources?
 }
@@ -418,6 +684,9 @@ fn on_disconnect_host_server(
         server_disconnect_event_writer.send(crate::server::events::DisconnectEvent {
             client_id,
             entity: client_entity,
+            reason: ServerDisconnectReason::ClientRequested,
+            // the local client does not use the network
+            summary: SessionSummary::default(),
         });
     }
 }
@@ -443,13 +712,22 @@ fn rebuild_client_connection(world: &mut World) {
         world.resource::<MessageRegistry>(),
         world.resource::<ChannelRegistry>(),
         &client_config,
+        world.resource::<NetworkClock>().clone(),
     );
     world.insert_resource(connection_manager);
 
     // drop the previous client connection to make sure we release any resources before creating the new one
     world.remove_resource::<ClientConnection>();
     // insert the new client connection
-    let client_connection = client_config.net.build_client();
+    let mut client_connection = client_config
+        .net
+        .build_client(world.resource::<NetworkClock>());
+    // the server denies the connection if its protocol does not have the same net ids
+    if let NetClientDispatch::Netcode(client) = &mut client_connection.client {
+        client
+            .client
+            .set_protocol_hash(NetIdReport::from_world(world).hash());
+    }
     world.insert_resource(client_connection);
 }
 
@@ -471,7 +749,9 @@ fn connect(world: &mut World) {
     // - this allows us to take into account any changes to the client config (when building a
     // new client connection and connection manager, which want to do because we need to reset
     // the internal time, sync, priority, message numbers, etc.)
+    apply_token_provider(world);
     rebuild_client_connection(world);
+    world.resource_mut::<ConnectAttempt>().start();
     let _ = world
         .resource_mut::<ClientConnection>()
         .connect()
@@ -500,6 +780,13 @@ pub trait ClientCommands {
 
     /// Disconnect the client
     fn disconnect_client(&mut self);
+
+    /// Abort the connection attempt (or the pending retry) and go back to
+    /// [`Disconnected`](NetworkingState::Disconnected).
+    ///
+    /// The transport is closed and a [`ConnectionFailedEvent`] with
+    /// [`ConnectError::Cancelled`] is emitted. This does nothing if the client is not connecting.
+    fn cancel_connect(&mut self);
 }
 
 impl ClientCommands for Commands<'_, '_> {
@@ -510,6 +797,10 @@ impl ClientCommands for Commands<'_, '_> {
     fn disconnect_client(&mut self) {
         self.insert_resource(NextState::Pending(NetworkingState::Disconnected));
     }
+
+    fn cancel_connect(&mut self) {
+        self.add(cancel_connect);
+    }
 }
 
 mod utils {
@@ -19,6 +19,7 @@ use crate::client::prediction::plugin::PredictionPlugin;
 use crate::client::replication::{
     receive::ClientReplicationReceivePlugin, send::ClientReplicationSendPlugin,
 };
+use crate::shared::config_check::add_client_checks;
 use crate::shared::plugin::SharedPlugin;
 
 use super::config::ClientConfig;
@@ -90,5 +91,6 @@ impl Plugin for SetupPlugin {
                     config: self.config.shared,
                 });
         }
+        add_client_checks(app);
     }
 }
@@ -0,0 +1,761 @@
+//! Automatically switch the locally-controlled entities between prediction and interpolation
+//! depending on the network conditions.
+//!
+//! When the latency gets too high (or when we are constantly rolling back), predicting the local player
+//! can feel worse than simply showing the server state: the corrections become large and frequent.
+//! The [`AdaptivePredictionPlugin`] monitors the RTT and the rollback frequency and can:
+//! - demote the [`Controlled`] entities from [`Predicted`] to [`Interpolated`]. Inputs are still sent to the server,
+//!   but the local player is not simulated on the client anymore. If [`AdaptivePredictionConfig::input_ghost`] is enabled,
+//!   the previous predicted entity is kept as an [`InputGhost`]: it keeps running the local simulation without any
+//!   corrections, which can be used to display the raw intent of the player's inputs.
+//! - promote them back to [`Predicted`] when the conditions improve.
+//!
+//! The switch is smooth:
+//! - on demotion, the interpolation starts from the current predicted value and blends towards the confirmed state
+//! - on promotion, the predicted entity starts from the interpolated value and we rollback from the confirmed tick;
+//!   the usual [`Correction`](crate::client::prediction::correction::Correction) smooths out the difference.
+//!
+//! Switches are rate-limited, and every switch emits a [`PredictionModeChanged`] event so that the game can explain
+//! the change to the player.
+use bevy::prelude::*;
+use bevy::utils::Duration;
+use tracing::debug;
+
+use crate::client::components::{ComponentSyncMode, Confirmed, SyncComponent};
+use crate::client::connection::ConnectionManager;
+use crate::client::interpolation::interpolate::InterpolateStatus;
+use crate::client::interpolation::interpolation_history::ConfirmedHistory;
+use crate::client::interpolation::resource::InterpolationManager;
+use crate::client::interpolation::Interpolated;
+use crate::client::prediction::diagnostics::PredictionMetrics;
+use crate::client::prediction::predicted_history::PredictionHistory;
+use crate::client::prediction::resource::PredictionManager;
+use crate::client::prediction::rollback::Rollback;
+use crate::client::prediction::spawn::spawn_predicted_entity;
+use crate::client::prediction::Predicted;
+use crate::prelude::client::{InterpolationSet, PredictionSet};
+use crate::prelude::{ComponentRegistry, TickManager};
+use crate::shared::replication::components::Controlled;
+
+/// Thresholds used to decide if the locally-controlled entities should be predicted or interpolated.
+///
+/// The demotion and promotion thresholds are separate to add some hysteresis.
+#[derive(Resource, Debug, Clone, Reflect)]
+#[reflect(Resource)]
+pub struct AdaptivePredictionConfig {
+    /// Switch to interpolation if the RTT is greater or equal than this value
+    pub demote_rtt: Duration,
+    /// Switch back to prediction only if the RTT is strictly lower than this value
+    pub promote_rtt: Duration,
+    /// Switch to interpolation if the number of rollbacks per second is greater or equal than this value.
+    ///
+    /// None means that the rollback frequency is not used to demote
+    pub demote_rollbacks_per_second: Option<f32>,
+    /// Switch back to prediction only if the number of rollbacks per second is strictly lower than this value.
+    ///
+    /// Note that while the local player is interpolated, its entity does not cause rollbacks anymore.
+    pub promote_rollbacks_per_second: Option<f32>,
+    /// Time window over which the rollback frequency is averaged
+    pub rollback_window: Duration,
+    /// The conditions must stay good for this long before we switch back to prediction
+    pub promote_delay: Duration,
+    /// Minimum duration between two switches, to avoid flapping between the two modes
+    pub min_switch_interval: Duration,
+    /// If true, the predicted entity is kept as an [`InputGhost`] while the local player is interpolated,
+    /// instead of being despawned.
+    ///
+    /// The ghost keeps being simulated with the local inputs, without any rollbacks or corrections.
+    /// Keep this enabled if your inputs are stored on the predicted entity (for example leafwing's `ActionState`),
+    /// otherwise they would get despawned along with it.
+    pub input_ghost: bool,
+}
+
+impl Default for AdaptivePredictionConfig {
+    fn default() -> Self {
+        Self {
+            demote_rtt: Duration::from_millis(300),
+            promote_rtt: Duration::from_millis(200),
+            demote_rollbacks_per_second: None,
+            promote_rollbacks_per_second: None,
+            rollback_window: Duration::from_secs(2),
+            promote_delay: Duration::from_secs(2),
+            min_switch_interval: Duration::from_secs(5),
+            input_ghost: false,
+        }
+    }
+}
+
+/// How the locally-controlled entities are currently displayed
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Reflect)]
+pub enum LocalPredictionMode {
+    #[default]
+    Predicted,
+    Interpolated,
+}
+
+/// Why the [`LocalPredictionMode`] changed
+#[derive(Debug, Clone, Copy, PartialEq, Reflect)]
+pub enum PredictionModeChangeReason {
+    /// The RTT went above [`AdaptivePredictionConfig::demote_rtt`]
+    HighLatency { rtt: Duration },
+    /// The rollback frequency went above [`AdaptivePredictionConfig::demote_rollbacks_per_second`]
+    FrequentRollbacks { rollbacks_per_second: f32 },
+    /// The network conditions have been good for at least [`AdaptivePredictionConfig::promote_delay`]
+    ConditionsImproved { rtt: Duration },
+}
+
+/// Event emitted whenever a locally-controlled entity switches between prediction and interpolation
+#[derive(Event, Debug, Clone, Copy, PartialEq)]
+pub struct PredictionModeChanged {
+    /// The confirmed entity
+    pub entity: Entity,
+    pub mode: LocalPredictionMode,
+    pub reason: PredictionModeChangeReason,
+}
+
+/// Current state of the adaptive prediction policy
+#[derive(Resource, Debug, Default, Reflect)]
+#[reflect(Resource)]
+pub struct AdaptivePredictionState {
+    pub mode: LocalPredictionMode,
+    /// Reason of the latest switch
+    pub reason: Option<PredictionModeChangeReason>,
+    /// Average number of rollbacks per second over [`AdaptivePredictionConfig::rollback_window`]
+    pub rollbacks_per_second: f32,
+    /// Time of the latest switch
+    last_switch: Option<Duration>,
+    /// Time since which the conditions are good enough to promote
+    good_since: Option<Duration>,
+    /// Value of [`PredictionMetrics::rollbacks`] at the previous frame
+    last_rollbacks: u32,
+}
+
+/// Marker for the previous predicted entity of a locally-controlled entity, while that entity is interpolated.
+///
+/// It keeps being simulated with the local inputs, but isn't rolled back or corrected.
+#[derive(Component, Debug, Reflect)]
+pub struct InputGhost {
+    pub confirmed_entity: Entity,
+}
+
+/// Marker on a confirmed entity that was demoted from prediction to interpolation
+#[derive(Component, Debug)]
+pub(crate) struct DemotedToInterpolation;
+
+/// Marker on an interpolated entity that was spawned by a demotion
+#[derive(Component, Debug)]
+pub(crate) struct AdaptiveInterpolated;
+
+/// Added on the new interpolated entity for the frame of the demotion: the interpolation state is seeded from
+/// this predicted entity
+#[derive(Component, Debug)]
+pub(crate) struct DemotedFrom(pub(crate) Entity);
+
+/// Added on the new predicted entity for the frame of the promotion: the predicted components are seeded from
+/// this interpolated entity
+#[derive(Component, Debug)]
+pub(crate) struct PromotedFrom(pub(crate) Option<Entity>);
+
+/// Plugin that switches the locally-controlled entities between prediction and interpolation
+/// depending on the network conditions.
+#[derive(Default)]
+pub struct AdaptivePredictionPlugin {
+    pub config: AdaptivePredictionConfig,
+}
+
+impl AdaptivePredictionPlugin {
+    pub fn new(config: AdaptivePredictionConfig) -> Self {
+        Self { config }
+    }
+}
+
+impl Plugin for AdaptivePredictionPlugin {
+    fn build(&self, app: &mut App) {
+        // REFLECTION
+        app.register_type::<AdaptivePredictionConfig>()
+            .register_type::<AdaptivePredictionState>()
+            .register_type::<InputGhost>();
+
+        // RESOURCES
+        app.insert_resource(self.config.clone());
+        app.init_resource::<AdaptivePredictionState>();
+
+        // EVENTS
+        app.add_event::<PredictionModeChanged>();
+
+        // SYSTEMS
+        // the per-component systems that seed the new predicted/interpolated entities are added
+        // in `add_prediction_systems` and `add_prepare_interpolation_systems`
+        app.add_systems(
+            PreUpdate,
+            (
+                update_local_prediction_mode
+                    .after(spawn_predicted_entity)
+                    .in_set(PredictionSet::SpawnPrediction),
+                finish_promotion.in_set(PredictionSet::CheckRollback),
+            ),
+        );
+        app.add_systems(
+            Update,
+            finish_demotion.in_set(InterpolationSet::PrepareInterpolation),
+        );
+        app.observe(despawn_input_ghost);
+    }
+}
+
+/// Update the rollback frequency estimate, decide which mode the locally-controlled entities should be in,
+/// and switch the entities that are not in that mode yet.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn update_local_prediction_mode(
+    time: Res<Time>,
+    config: Res<AdaptivePredictionConfig>,
+    mut state: ResMut<AdaptivePredictionState>,
+    metrics: Option<Res<PredictionMetrics>>,
+    connection: Res<ConnectionManager>,
+    tick_manager: Res<TickManager>,
+    rollback: Res<Rollback>,
+    mut prediction_manager: ResMut<PredictionManager>,
+    mut interpolation_manager: ResMut<InterpolationManager>,
+    mut commands: Commands,
+    mut confirmed_query: Query<
+        (Entity, &mut Confirmed, Has<DemotedToInterpolation>),
+        With<Controlled>,
+    >,
+    adaptive_interpolated: Query<(), With<AdaptiveInterpolated>>,
+    ghosts: Query<(Entity, &InputGhost)>,
+    mut events: EventWriter<PredictionModeChanged>,
+) {
+    let now = time.elapsed();
+    let state = &mut *state;
+
+    // 1. update the rollback frequency (exponential moving average over the rollback window)
+    let rollbacks = metrics.map_or(0, |m| m.rollbacks);
+    let new_rollbacks = rollbacks.wrapping_sub(state.last_rollbacks) as f32;
+    state.last_rollbacks = rollbacks;
+    let delta = time.delta_seconds();
+    let window = config.rollback_window.as_secs_f32();
+    if delta > 0.0 {
+        if delta >= window {
+            state.rollbacks_per_second = new_rollbacks / delta;
+        } else {
+            state.rollbacks_per_second =
+                state.rollbacks_per_second * (1.0 - delta / window) + new_rollbacks / window;
+        }
+    }
+
+    // 2. check if we should switch mode
+    let rtt = connection.ping_manager.rtt();
+    let reason = match state.mode {
+        LocalPredictionMode::Predicted => {
+            if rtt >= config.demote_rtt {
+                Some(PredictionModeChangeReason::HighLatency { rtt })
+            } else if config
+                .demote_rollbacks_per_second
+                .is_some_and(|max| state.rollbacks_per_second >= max)
+            {
+                Some(PredictionModeChangeReason::FrequentRollbacks {
+                    rollbacks_per_second: state.rollbacks_per_second,
+                })
+            } else {
+                None
+            }
+        }
+        LocalPredictionMode::Interpolated => {
+            let good = rtt < config.promote_rtt
+                && config
+                    .promote_rollbacks_per_second
+                    .map_or(true, |max| state.rollbacks_per_second < max);
+            if good {
+                let good_since = *state.good_since.get_or_insert(now);
+                (now - good_since >= config.promote_delay)
+                    .then_some(PredictionModeChangeReason::ConditionsImproved { rtt })
+            } else {
+                state.good_since = None;
+                None
+            }
+        }
+    };
+    let rate_limited = state
+        .last_switch
+        .is_some_and(|last| now - last < config.min_switch_interval);
+    if let Some(reason) = reason.filter(|_| !rate_limited) {
+        state.mode = match state.mode {
+            LocalPredictionMode::Predicted => LocalPredictionMode::Interpolated,
+            LocalPredictionMode::Interpolated => LocalPredictionMode::Predicted,
+        };
+        debug!(mode = ?state.mode, ?reason, "switching local prediction mode");
+        state.reason = Some(reason);
+        state.last_switch = Some(now);
+        state.good_since = None;
+    }
+    let Some(reason) = state.reason else {
+        return;
+    };
+
+    // 3. switch the entities that are not in the correct mode
+    // (this also handles controlled entities that get spawned while we are interpolating)
+    let current_tick = tick_manager.tick();
+    for (confirmed_entity, mut confirmed, demoted) in confirmed_query.iter_mut() {
+        match state.mode {
+            LocalPredictionMode::Interpolated => {
+                let Some(predicted) = confirmed.predicted.take() else {
+                    continue;
+                };
+                prediction_manager
+                    .predicted_entity_map
+                    .get_mut()
+                    .confirmed_to_predicted
+                    .remove(&confirmed_entity);
+                if confirmed.interpolated.is_none() {
+                    let interpolated = commands
+                        .spawn((
+                            Interpolated { confirmed_entity },
+                            AdaptiveInterpolated,
+                            DemotedFrom(predicted),
+                        ))
+                        .id();
+                    interpolation_manager
+                        .interpolated_entity_map
+                        .get_mut()
+                        .confirmed_to_interpolated
+                        .insert(confirmed_entity, interpolated);
+                    confirmed.interpolated = Some(interpolated);
+                    if config.input_ghost {
+                        commands
+                            .entity(predicted)
+                            .insert(InputGhost { confirmed_entity });
+                    }
+                } else if config.input_ghost {
+                    // the entity was already interpolated, there is nothing to seed
+                    commands
+                        .entity(predicted)
+                        .insert(InputGhost { confirmed_entity });
+                } else if let Some(entity_mut) = commands.get_entity(predicted) {
+                    entity_mut.despawn_recursive();
+                }
+                commands
+                    .entity(confirmed_entity)
+                    .insert(DemotedToInterpolation);
+                debug!(?confirmed_entity, ?predicted, "demoted to interpolation");
+            }
+            LocalPredictionMode::Predicted => {
+                if !demoted || confirmed.predicted.is_some() {
+                    continue;
+                }
+                commands
+                    .entity(confirmed_entity)
+                    .remove::<DemotedToInterpolation>();
+                // only remove the interpolated entity if it was spawned by the demotion
+                let interpolated = confirmed
+                    .interpolated
+                    .filter(|e| adaptive_interpolated.contains(*e));
+                if interpolated.is_some() {
+                    confirmed.interpolated = None;
+                    interpolation_manager
+                        .interpolated_entity_map
+                        .get_mut()
+                        .confirmed_to_interpolated
+                        .remove(&confirmed_entity);
+                }
+                // re-use the input ghost if there is one
+                let predicted = match ghosts
+                    .iter()
+                    .find(|(_, ghost)| ghost.confirmed_entity == confirmed_entity)
+                {
+                    Some((ghost, _)) => {
+                        commands
+                            .entity(ghost)
+                            .remove::<InputGhost>()
+                            .insert(PromotedFrom(interpolated));
+                        ghost
+                    }
+                    None => commands
+                        .spawn((
+                            Predicted {
+                                confirmed_entity: Some(confirmed_entity),
+                            },
+                            PromotedFrom(interpolated),
+                        ))
+                        .id(),
+                };
+                prediction_manager
+                    .predicted_entity_map
+                    .get_mut()
+                    .confirmed_to_predicted
+                    .insert(confirmed_entity, predicted);
+                confirmed.predicted = Some(predicted);
+                // resimulate from the confirmed state up to the current tick
+                if confirmed.tick <= current_tick {
+                    rollback.set_rollback_tick(confirmed.tick + 1);
+                }
+                debug!(?confirmed_entity, ?predicted, "promoted to prediction");
+            }
+        }
+        events.send(PredictionModeChanged {
+            entity: confirmed_entity,
+            mode: state.mode,
+            reason,
+        });
+    }
+}
+
+/// Seed the interpolation state of a newly demoted entity from the predicted entity, so that the
+/// interpolation starts from what the player was seeing and blends towards the confirmed state.
+pub(crate) fn seed_interpolated_from_predicted<C: SyncComponent>(
+    component_registry: Res<ComponentRegistry>,
+    manager: Res<InterpolationManager>,
+    tick_manager: Res<TickManager>,
+    connection: Res<ConnectionManager>,
+    mut commands: Commands,
+    interpolated_query: Query<(Entity, &Interpolated, &DemotedFrom), Without<ConfirmedHistory<C>>>,
+    predicted_query: Query<&C, With<Predicted>>,
+    confirmed_query: Query<(&Confirmed, &C)>,
+) {
+    let current_tick = connection
+        .sync_manager
+        .interpolation_tick(tick_manager.as_ref());
+    let current_overstep = connection
+        .sync_manager
+        .interpolation_overstep(tick_manager.as_ref());
+    for (entity, interpolated, demoted_from) in interpolated_query.iter() {
+        let Ok((confirmed, confirmed_component)) =
+            confirmed_query.get(interpolated.confirmed_entity)
+        else {
+            continue;
+        };
+        let mut new_component = confirmed_component.clone();
+        let _ = manager.map_entities(&mut new_component, component_registry.as_ref());
+        match component_registry.interpolation_mode::<C>() {
+            ComponentSyncMode::Full => {
+                let start = predicted_query
+                    .get(demoted_from.0)
+                    .map_or_else(|_| new_component.clone(), |c| c.clone());
+                // the confirmed tick is usually ahead of the interpolation tick; if it isn't, we blend
+                // to the confirmed value over one tick
+                let end_tick = if confirmed.tick > current_tick {
+                    confirmed.tick
+                } else {
+                    current_tick + 1
+                };
+                let mut history = ConfirmedHistory::<C>::new();
+                history.buffer.push(end_tick, new_component);
+                commands.entity(entity).insert((
+                    start.clone(),
+                    history,
+                    InterpolateStatus::<C> {
+                        start: Some((current_tick, start)),
+                        end: None,
+                        current_tick,
+                        current_overstep,
+                        snapshots_ahead: 1,
+                        snapshots_behind: 1,
+                        previous: None,
+                        extrapolation: None,
+                    },
+                ));
+            }
+            ComponentSyncMode::Once | ComponentSyncMode::Simple => {
+                commands.entity(entity).insert(new_component);
+            }
+            ComponentSyncMode::None => {}
+        }
+    }
+}
+
+/// Seed the components of a newly promoted predicted entity.
+///
+/// Full components start from the interpolated value; the rollback triggered by the promotion then
+/// snaps them to the confirmed state and re-simulates up to the current tick (with the usual correction).
+pub(crate) fn seed_predicted_from_interpolated<C: SyncComponent>(
+    component_registry: Res<ComponentRegistry>,
+    manager: Res<PredictionManager>,
+    mut commands: Commands,
+    predicted_query: Query<(Entity, &Predicted, &PromotedFrom)>,
+    interpolated_query: Query<&C, With<Interpolated>>,
+    confirmed_query: Query<&C, With<Confirmed>>,
+) {
+    for (entity, predicted, promoted_from) in predicted_query.iter() {
+        let Some(confirmed_entity) = predicted.confirmed_entity else {
+            continue;
+        };
+        let mut entity_mut = commands.entity(entity);
+        let Ok(confirmed_component) = confirmed_query.get(confirmed_entity) else {
+            // the entity could be a re-used input ghost
+            entity_mut.remove::<C>();
+            continue;
+        };
+        let mut new_component = confirmed_component.clone();
+        let _ = manager.map_entities(&mut new_component, component_registry.as_ref());
+        match component_registry.prediction_mode::<C>() {
+            ComponentSyncMode::Full => {
+                let start = promoted_from
+                    .0
+                    .and_then(|e| interpolated_query.get(e).ok())
+                    .map_or(new_component, |c| c.clone());
+                entity_mut.insert((start, PredictionHistory::<C>::default()));
+            }
+            ComponentSyncMode::Once | ComponentSyncMode::Simple => {
+                entity_mut.insert(new_component);
+            }
+            ComponentSyncMode::None => {}
+        }
+    }
+}
+
+/// Despawn the predicted entity that was used to seed the interpolation (unless it's kept as an input ghost)
+pub(crate) fn finish_demotion(
+    mut commands: Commands,
+    query: Query<(Entity, &DemotedFrom)>,
+    ghosts: Query<(), With<InputGhost>>,
+) {
+    for (entity, demoted_from) in query.iter() {
+        commands.entity(entity).remove::<DemotedFrom>();
+        if !ghosts.contains(demoted_from.0) {
+            if let Some(entity_mut) = commands.get_entity(demoted_from.0) {
+                entity_mut.despawn_recursive();
+            }
+        }
+    }
+}
+
+/// Despawn the interpolated entity that was used to seed the prediction
+pub(crate) fn finish_promotion(mut commands: Commands, query: Query<(Entity, &PromotedFrom)>) {
+    for (entity, promoted_from) in query.iter() {
+        commands.entity(entity).remove::<PromotedFrom>();
+        if let Some(entity_mut) = promoted_from.0.and_then(|e| commands.get_entity(e)) {
+            entity_mut.despawn_recursive();
+        }
+    }
+}
+
+/// Despawn the input ghost when the confirmed entity gets despawned
+pub(crate) fn despawn_input_ghost(
+    trigger: Trigger<OnRemove, Confirmed>,
+    mut commands: Commands,
+    ghosts: Query<(Entity, &InputGhost)>,
+) {
+    for (entity, ghost) in ghosts.iter() {
+        if ghost.confirmed_entity == trigger.entity() {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::config::ClientConfig;
+    use crate::prelude::server::{ControlledBy, Replicate, SyncTarget};
+    use crate::prelude::{NetworkTarget, SharedConfig, TickConfig};
+    use crate::tests::protocol::*;
+    use crate::tests::stepper::{BevyStepper, Step};
+
+    fn received_events(stepper: &mut BevyStepper) -> Vec<PredictionModeChanged> {
+        stepper
+            .client_app
+            .world_mut()
+            .resource_mut::<Events<PredictionModeChanged>>()
+            .drain()
+            .collect()
+    }
+
+    /// Check that the local player gets demoted to interpolation when the RTT is too high,
+    /// and promoted back to prediction when the conditions improve
+    #[test]
+    fn test_switch_prediction_mode() {
+        let frame_duration = Duration::from_millis(10);
+        let shared_config = SharedConfig {
+            tick: TickConfig::new(frame_duration),
+            ..Default::default()
+        };
+        let mut stepper = BevyStepper::new(shared_config, ClientConfig::default(), frame_duration);
+        stepper
+            .client_app
+            .add_plugins(AdaptivePredictionPlugin::new(AdaptivePredictionConfig {
+                // never demote or promote until we change the config
+                demote_rtt: Duration::from_secs(100),
+                promote_rtt: Duration::ZERO,
+                promote_delay: Duration::ZERO,
+                min_switch_interval: Duration::ZERO,
+                input_ghost: true,
+                ..default()
+            }));
+        stepper.init();
+
+        let server_entity = stepper
+            .server_app
+            .world_mut()
+            .spawn((
+                Component1(1.0),
+                Replicate {
+                    sync: SyncTarget {
+                        prediction: NetworkTarget::All,
+                        ..default()
+                    },
+                    controlled_by: ControlledBy {
+                        target: NetworkTarget::All,
+                        ..default()
+                    },
+                    ..default()
+                },
+            ))
+            .id();
+        stepper.frame_step();
+        stepper.frame_step();
+        let confirmed_entity = *stepper
+            .client_app
+            .world()
+            .resource::<ConnectionManager>()
+            .replication_receiver
+            .remote_entity_map
+            .get_local(server_entity)
+            .expect("entity was not replicated to client");
+        let predicted = stepper
+            .client_app
+            .world()
+            .get::<Confirmed>(confirmed_entity)
+            .unwrap()
+            .predicted
+            .expect("the entity should be predicted");
+        assert!(received_events(&mut stepper).is_empty());
+
+        // demote
+        stepper
+            .client_app
+            .world_mut()
+            .resource_mut::<AdaptivePredictionConfig>()
+            .demote_rtt = Duration::ZERO;
+        stepper.frame_step();
+        let confirmed = stepper
+            .client_app
+            .world()
+            .get::<Confirmed>(confirmed_entity)
+            .unwrap();
+        assert!(confirmed.predicted.is_none());
+        let interpolated = confirmed
+            .interpolated
+            .expect("the entity should be interpolated");
+        // the interpolation was seeded from the predicted entity
+        assert!(stepper
+            .client_app
+            .world()
+            .get::<ConfirmedHistory<Component1>>(interpolated)
+            .is_some());
+        assert_eq!(
+            stepper.client_app.world().get::<Component1>(interpolated),
+            Some(&Component1(1.0))
+        );
+        // the predicted entity is kept as an input ghost
+        assert_eq!(
+            stepper
+                .client_app
+                .world()
+                .get::<InputGhost>(predicted)
+                .unwrap()
+                .confirmed_entity,
+            confirmed_entity
+        );
+        let events = received_events(&mut stepper);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].entity, confirmed_entity);
+        assert_eq!(events[0].mode, LocalPredictionMode::Interpolated);
+        assert!(matches!(
+            events[0].reason,
+            PredictionModeChangeReason::HighLatency { .. }
+        ));
+        // the mode is stable while the conditions don't change
+        stepper.frame_step();
+        assert!(received_events(&mut stepper).is_empty());
+
+        // promote
+        stepper
+            .client_app
+            .world_mut()
+            .resource_mut::<AdaptivePredictionConfig>()
+            .promote_rtt = Duration::from_secs(100);
+        stepper.frame_step();
+        let confirmed = stepper
+            .client_app
+            .world()
+            .get::<Confirmed>(confirmed_entity)
+            .unwrap();
+        // the input ghost got re-used as the predicted entity
+        assert_eq!(confirmed.predicted, Some(predicted));
+        assert!(confirmed.interpolated.is_none());
+        assert!(stepper
+            .client_app
+            .world()
+            .get_entity(interpolated)
+            .is_none());
+        assert!(stepper
+            .client_app
+            .world()
+            .get::<InputGhost>(predicted)
+            .is_none());
+        assert!(stepper
+            .client_app
+            .world()
+            .get::<PredictionHistory<Component1>>(predicted)
+            .is_some());
+        let events = received_events(&mut stepper);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].mode, LocalPredictionMode::Predicted);
+    }
+
+    /// Check that switches are rate-limited
+    #[test]
+    fn test_switch_rate_limit() {
+        let frame_duration = Duration::from_millis(10);
+        let shared_config = SharedConfig {
+            tick: TickConfig::new(frame_duration),
+            ..Default::default()
+        };
+        let mut stepper = BevyStepper::new(shared_config, ClientConfig::default(), frame_duration);
+        stepper
+            .client_app
+            .add_plugins(AdaptivePredictionPlugin::new(AdaptivePredictionConfig {
+                demote_rtt: Duration::ZERO,
+                promote_rtt: Duration::from_secs(100),
+                promote_delay: Duration::ZERO,
+                min_switch_interval: Duration::from_millis(500),
+                ..default()
+            }));
+        stepper.init();
+        stepper.frame_step();
+        assert_eq!(
+            stepper
+                .client_app
+                .world()
+                .resource::<AdaptivePredictionState>()
+                .mode,
+            LocalPredictionMode::Interpolated
+        );
+
+        // the conditions are good again, but we switched too recently
+        stepper
+            .client_app
+            .world_mut()
+            .resource_mut::<AdaptivePredictionConfig>()
+            .demote_rtt = Duration::from_secs(100);
+        stepper.frame_step();
+        assert_eq!(
+            stepper
+                .client_app
+                .world()
+                .resource::<AdaptivePredictionState>()
+                .mode,
+            LocalPredictionMode::Interpolated
+        );
+        for _ in 0..50 {
+            stepper.frame_step();
+        }
+        assert_eq!(
+            stepper
+                .client_app
+                .world()
+                .resource::<AdaptivePredictionState>()
+                .mode,
+            LocalPredictionMode::Predicted
+        );
+    }
+}
@@ -0,0 +1,866 @@
+//! Capture of the recent prediction history of an entity, to analyze desyncs after the fact.
+//!
+//! When a player reports that they "got teleported", the rollback that caused it is long gone. The
+//! [`PredictionCapturePlugin`] keeps a rolling record of the last [`PredictionCaptureConfig::retention`] of:
+//! - the predicted and confirmed values of the components registered with
+//!   [`add_prediction_capture`](AppPredictionCaptureExt::add_prediction_capture)
+//! - the inputs of the [`InputPlugin`](crate::prelude::InputPlugin) applied on each tick
+//! - the rollbacks ([`RollbackEvent`]) and the tick adjustments of the sync ([`TickEvent`])
+//!
+//! [`PredictionRecorder::capture_history`] snapshots everything that concerns an entity at one instant into a
+//! [`PredictionCapture`], which can be serialized with [`ToBytes`] and sent to a server or saved to a file.
+//! A capture is also emitted automatically as a [`PredictionCaptureEvent`] after a rollback deeper than
+//! [`PredictionCaptureConfig::auto_capture_rollback_depth`].
+//!
+//! Offline, [`analyze_capture`] renders the capture as a diff per tick, and finds the first tick where
+//! a predicted value diverged from the confirmed value.
+//!
+//! ```rust,ignore
+//! app.add_plugins(PredictionCapturePlugin::default());
+//! app.add_prediction_capture::<Position>();
+//!
+//! fn capture_on_hotkey(
+//!     keys: Res<ButtonInput<KeyCode>>,
+//!     recorder: Res<PredictionRecorder>,
+//!     player: Query<Entity, (With<PlayerId>, With<Predicted>)>,
+//! ) {
+//!     if keys.just_pressed(KeyCode::F9) {
+//!         let capture = recorder.capture_history(player.single());
+//!         let mut bytes = Vec::new();
+//!         capture.to_bytes(&mut bytes).unwrap();
+//!         // upload the bytes...
+//!     }
+//! }
+//!
+//! // later, in a tool
+//! let capture = PredictionCapture::from_bytes(&mut Reader::from(bytes))?;
+//! println!("{}", analyze_capture(&capture));
+//! ```
+use std::collections::VecDeque;
+use std::fmt::{Debug, Display, Formatter};
+
+use bevy::ecs::entity::EntityHashMap;
+use bevy::prelude::*;
+use bevy::utils::{Duration, HashMap};
+use byteorder::WriteBytesExt;
+use serde::{Deserialize, Serialize};
+
+use crate::client::components::{Confirmed, SyncComponent};
+use crate::client::prediction::plugin::{is_in_rollback, PredictionSet};
+use crate::client::prediction::predicted_history::PredictionHistory;
+use crate::client::prediction::rollback::{Rollback, RollbackEvent};
+use crate::client::prediction::Predicted;
+use crate::inputs::LocalPlayerId;
+use crate::prelude::{ComponentRegistry, Tick, TickManager};
+use crate::protocol::component::ComponentKind;
+use crate::serialize::reader::Reader;
+use crate::serialize::{SerializationError, ToBytes};
+use crate::shared::tick_manager::TickEvent;
+
+/// Configuration of the [`PredictionCapturePlugin`]
+#[derive(Resource, Debug, Clone, Copy, Reflect)]
+pub struct PredictionCaptureConfig {
+    /// How much history is kept
+    pub retention: Duration,
+    /// If set, a [`PredictionCaptureEvent`] is emitted for each recorded entity after a rollback
+    /// that resimulated at least this number of ticks
+    pub auto_capture_rollback_depth: Option<u16>,
+}
+
+impl Default for PredictionCaptureConfig {
+    fn default() -> Self {
+        Self {
+            retention: Duration::from_secs(2),
+            auto_capture_rollback_depth: None,
+        }
+    }
+}
+
+impl PredictionCaptureConfig {
+    pub fn with_retention(mut self, retention: Duration) -> Self {
+        self.retention = retention;
+        self
+    }
+
+    pub fn with_auto_capture_rollback_depth(mut self, depth: u16) -> Self {
+        self.auto_capture_rollback_depth = Some(depth);
+        self
+    }
+}
+
+/// Plugin that records the prediction history needed to build [`PredictionCapture`]s.
+///
+/// See [`capture`](crate::client::prediction::capture) for more details.
+#[derive(Default)]
+pub struct PredictionCapturePlugin {
+    pub config: PredictionCaptureConfig,
+}
+
+/// Event emitted automatically after a deep rollback, see [`PredictionCaptureConfig::auto_capture_rollback_depth`]
+#[derive(Event, Debug, Clone, PartialEq)]
+pub struct PredictionCaptureEvent {
+    pub capture: PredictionCapture,
+}
+
+/// Values of a component on each tick. `None` means that the component was absent.
+pub type ComponentValues = Vec<(Tick, Option<String>)>;
+
+/// History of a component of the captured entity. The values are stored with their [`Debug`] representation,
+/// so that the capture can be read without the protocol.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ComponentCapture {
+    pub name: String,
+    /// Latest value predicted for each tick (including the values predicted during rollbacks)
+    pub predicted: ComponentValues,
+    /// Values received from the server
+    pub confirmed: ComponentValues,
+}
+
+/// Inputs of a local player applied on a tick
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct InputCapture {
+    pub tick: Tick,
+    pub local_player: LocalPlayerId,
+    pub inputs: Vec<String>,
+}
+
+/// A rollback, see [`RollbackEvent`]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RollbackCapture {
+    pub rollback_tick: Tick,
+    pub current_tick: Tick,
+    pub num_resimulated_ticks: u16,
+    pub mismatched_components: Vec<String>,
+}
+
+/// The client tick was snapped by the sync, see [`TickEvent::TickSnap`]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct SyncAdjustment {
+    pub old_tick: Tick,
+    pub new_tick: Tick,
+}
+
+/// Snapshot of the prediction history of an entity, returned by [`PredictionRecorder::capture_history`]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PredictionCapture {
+    pub entity: Entity,
+    /// Client tick when the capture was taken
+    pub tick: Tick,
+    pub components: Vec<ComponentCapture>,
+    pub inputs: Vec<InputCapture>,
+    pub rollbacks: Vec<RollbackCapture>,
+    pub sync_adjustments: Vec<SyncAdjustment>,
+}
+
+impl ToBytes for PredictionCapture {
+    fn len(&self) -> usize {
+        bincode::serde::encode_to_vec(self, bincode::config::standard()).map_or(0, |v| v.len())
+    }
+
+    fn to_bytes<T: WriteBytesExt>(&self, buffer: &mut T) -> Result<(), SerializationError> {
+        bincode::serde::encode_into_std_write(self, buffer, bincode::config::standard())?;
+        Ok(())
+    }
+
+    fn from_bytes(buffer: &mut Reader) -> Result<Self, SerializationError>
+    where
+        Self: Sized,
+    {
+        Ok(bincode::serde::decode_from_std_read(
+            buffer,
+            bincode::config::standard(),
+        )?)
+    }
+}
+
+/// Rolling record of the prediction history, inserted by the [`PredictionCapturePlugin`]
+#[derive(Resource, Debug, Default)]
+pub struct PredictionRecorder {
+    /// Current client tick
+    tick: Tick,
+    components: EntityHashMap<HashMap<ComponentKind, ComponentCapture>>,
+    inputs: VecDeque<InputCapture>,
+    rollbacks: VecDeque<RollbackCapture>,
+    sync_adjustments: VecDeque<SyncAdjustment>,
+}
+
+impl PredictionRecorder {
+    /// Snapshot everything that was recorded for the entity
+    pub fn capture_history(&self, entity: Entity) -> PredictionCapture {
+        let mut components: Vec<_> = self
+            .components
+            .get(&entity)
+            .map(|components| components.values().cloned().collect())
+            .unwrap_or_default();
+        components.sort_by(|a: &ComponentCapture, b| a.name.cmp(&b.name));
+        PredictionCapture {
+            entity,
+            tick: self.tick,
+            components,
+            inputs: self.inputs.iter().cloned().collect(),
+            rollbacks: self.rollbacks.iter().cloned().collect(),
+            sync_adjustments: self.sync_adjustments.iter().copied().collect(),
+        }
+    }
+
+    /// Entities whose components were recorded
+    pub fn entities(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.components.keys().copied()
+    }
+
+    fn component(
+        &mut self,
+        entity: Entity,
+        kind: ComponentKind,
+        name: &str,
+    ) -> &mut ComponentCapture {
+        self.components
+            .entry(entity)
+            .or_default()
+            .entry(kind)
+            .or_insert_with(|| ComponentCapture {
+                name: name.to_string(),
+                predicted: Vec::new(),
+                confirmed: Vec::new(),
+            })
+    }
+
+    /// Discard the records that are older than `earliest_tick`
+    fn trim(&mut self, earliest_tick: Tick) {
+        for components in self.components.values_mut() {
+            for component in components.values_mut() {
+                component
+                    .predicted
+                    .retain(|(tick, _)| *tick >= earliest_tick);
+                component
+                    .confirmed
+                    .retain(|(tick, _)| *tick >= earliest_tick);
+            }
+            components.retain(|_, component| {
+                !component.predicted.is_empty() || !component.confirmed.is_empty()
+            });
+        }
+        self.components
+            .retain(|_, components| !components.is_empty());
+        while self
+            .inputs
+            .front()
+            .is_some_and(|input| input.tick < earliest_tick)
+        {
+            self.inputs.pop_front();
+        }
+        while self
+            .rollbacks
+            .front()
+            .is_some_and(|rollback| rollback.current_tick < earliest_tick)
+        {
+            self.rollbacks.pop_front();
+        }
+        while self
+            .sync_adjustments
+            .front()
+            .is_some_and(|sync| sync.new_tick < earliest_tick)
+        {
+            self.sync_adjustments.pop_front();
+        }
+    }
+
+    /// Record the inputs applied on a tick. During rollbacks, the inputs of the resimulated ticks replace
+    /// the ones that were recorded.
+    pub(crate) fn record_inputs(
+        &mut self,
+        tick: Tick,
+        local_player: LocalPlayerId,
+        inputs: Vec<String>,
+    ) {
+        if let Some(existing) = self
+            .inputs
+            .iter_mut()
+            .rev()
+            .find(|input| input.tick == tick && input.local_player == local_player)
+        {
+            existing.inputs = inputs;
+            return;
+        }
+        let index = self
+            .inputs
+            .iter()
+            .rposition(|input| input.tick <= tick)
+            .map_or(0, |i| i + 1);
+        self.inputs.insert(
+            index,
+            InputCapture {
+                tick,
+                local_player,
+                inputs,
+            },
+        );
+    }
+}
+
+/// Record the value of a tick, replacing the value previously recorded for that tick
+fn record_value(values: &mut ComponentValues, tick: Tick, value: Option<String>) {
+    match values.iter().rposition(|(t, _)| *t <= tick) {
+        Some(index) if values[index].0 == tick => values[index].1 = value,
+        Some(index) => values.insert(index + 1, (tick, value)),
+        None => values.insert(0, (tick, value)),
+    }
+}
+
+pub trait AppPredictionCaptureExt {
+    /// Record the predicted and confirmed values of the component `C` in the [`PredictionRecorder`].
+    ///
+    /// The component must be predicted with [`ComponentSyncMode::Full`](crate::prelude::client::ComponentSyncMode::Full).
+    fn add_prediction_capture<C: SyncComponent + Debug>(&mut self) -> &mut Self;
+}
+
+impl AppPredictionCaptureExt for App {
+    fn add_prediction_capture<C: SyncComponent + Debug>(&mut self) -> &mut Self {
+        self.add_systems(
+            PreUpdate,
+            record_confirmed_component::<C>
+                .in_set(PredictionSet::CheckRollback)
+                .run_if(resource_exists::<PredictionRecorder>),
+        );
+        self.add_systems(
+            FixedPostUpdate,
+            record_predicted_component::<C>
+                .in_set(PredictionSet::UpdateHistory)
+                .run_if(resource_exists::<PredictionRecorder>),
+        );
+        self
+    }
+}
+
+/// Record the value of the component on the predicted entities for the current tick (or rollback tick)
+fn record_predicted_component<C: SyncComponent + Debug>(
+    component_registry: Res<ComponentRegistry>,
+    tick_manager: Res<TickManager>,
+    rollback: Res<Rollback>,
+    mut recorder: ResMut<PredictionRecorder>,
+    query: Query<(Entity, Option<&C>), (With<Predicted>, With<PredictionHistory<C>>)>,
+) {
+    let tick = tick_manager.tick_or_rollback_tick(rollback.as_ref());
+    let kind = ComponentKind::of::<C>();
+    let name = component_registry.name(kind);
+    for (entity, component) in query.iter() {
+        let values = &mut recorder.component(entity, kind, name).predicted;
+        record_value(values, tick, component.map(|c| format!("{c:?}")));
+    }
+}
+
+/// Record the value of the component on the confirmed entities when a server update is received
+fn record_confirmed_component<C: SyncComponent + Debug>(
+    component_registry: Res<ComponentRegistry>,
+    mut recorder: ResMut<PredictionRecorder>,
+    confirmed_query: Query<(Option<&C>, Ref<Confirmed>)>,
+    predicted_query: Query<(), (With<Predicted>, With<PredictionHistory<C>>)>,
+) {
+    let kind = ComponentKind::of::<C>();
+    let name = component_registry.name(kind);
+    for (component, confirmed) in confirmed_query.iter() {
+        if !confirmed.is_changed() {
+            continue;
+        }
+        let Some(predicted) = confirmed
+            .predicted
+            .filter(|predicted| predicted_query.contains(*predicted))
+        else {
+            continue;
+        };
+        let values = &mut recorder.component(predicted, kind, name).confirmed;
+        record_value(values, confirmed.tick, component.map(|c| format!("{c:?}")));
+    }
+}
+
+/// Update the current tick of the recorder and discard the records that are older than the retention
+fn trim_recorder(
+    config: Res<PredictionCaptureConfig>,
+    tick_manager: Res<TickManager>,
+    mut recorder: ResMut<PredictionRecorder>,
+) {
+    let tick = tick_manager.tick();
+    let retention_ticks = (config.retention.as_secs_f64()
+        / tick_manager.config.tick_duration.as_secs_f64())
+    .ceil()
+    .min(i16::MAX as f64) as u16;
+    recorder.tick = tick;
+    recorder.trim(tick - retention_ticks);
+}
+
+/// Record the rollbacks, and capture the history of all the recorded entities after a deep rollback
+fn record_rollbacks(
+    config: Res<PredictionCaptureConfig>,
+    component_registry: Res<ComponentRegistry>,
+    mut recorder: ResMut<PredictionRecorder>,
+    mut rollback_events: EventReader<RollbackEvent>,
+    mut capture_events: EventWriter<PredictionCaptureEvent>,
+) {
+    for event in rollback_events.read() {
+        recorder.rollbacks.push_back(RollbackCapture {
+            rollback_tick: event.rollback_tick,
+            current_tick: event.current_tick,
+            num_resimulated_ticks: event.num_resimulated_ticks,
+            mismatched_components: event
+                .mismatched_components
+                .iter()
+                .map(|kind| component_registry.name(*kind).to_string())
+                .collect(),
+        });
+        if config
+            .auto_capture_rollback_depth
+            .is_some_and(|depth| event.num_resimulated_ticks >= depth)
+        {
+            capture_events.send_batch(recorder.entities().map(|entity| PredictionCaptureEvent {
+                capture: recorder.capture_history(entity),
+            }));
+        }
+    }
+}
+
+/// Record the tick adjustments of the sync
+fn record_tick_snap(trigger: Trigger<TickEvent>, recorder: Option<ResMut<PredictionRecorder>>) {
+    let Some(mut recorder) = recorder else {
+        return;
+    };
+    let TickEvent::TickSnap { old_tick, new_tick } = *trigger.event();
+    recorder
+        .sync_adjustments
+        .push_back(SyncAdjustment { old_tick, new_tick });
+}
+
+impl Plugin for PredictionCapturePlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<PredictionCaptureConfig>();
+        app.insert_resource(self.config);
+        app.init_resource::<PredictionRecorder>();
+        app.add_event::<PredictionCaptureEvent>();
+        app.add_systems(
+            PreUpdate,
+            record_rollbacks
+                .after(PredictionSet::Rollback)
+                .in_set(PredictionSet::All),
+        );
+        app.add_systems(
+            FixedPostUpdate,
+            trim_recorder
+                .after(PredictionSet::UpdateHistory)
+                .in_set(PredictionSet::All)
+                .run_if(not(is_in_rollback)),
+        );
+        app.observe(record_tick_snap);
+    }
+}
+
+/// Difference between the predicted and the confirmed value of a component on a tick
+#[derive(Debug, Clone, PartialEq)]
+pub struct ComponentDiff {
+    pub component: String,
+    pub predicted: Option<String>,
+    pub confirmed: Option<String>,
+    /// Largest difference between the numbers of the predicted and confirmed values, if they have
+    /// the same structure
+    pub magnitude: Option<f64>,
+}
+
+impl ComponentDiff {
+    pub fn diverged(&self) -> bool {
+        self.predicted != self.confirmed
+    }
+}
+
+/// Everything that happened on a tick of a [`PredictionCapture`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct TickReport {
+    pub tick: Tick,
+    pub inputs: Vec<InputCapture>,
+    /// The components whose confirmed value was received for this tick
+    pub components: Vec<ComponentDiff>,
+    /// The rollbacks that happened on this tick
+    pub rollbacks: Vec<RollbackCapture>,
+    /// The tick snaps to this tick
+    pub sync_adjustments: Vec<SyncAdjustment>,
+}
+
+/// Result of [`analyze_capture`]. It is rendered as a human-readable report with [`Display`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct CaptureAnalysis {
+    pub entity: Entity,
+    pub tick: Tick,
+    /// The ticks that have a confirmed value, a rollback or a sync adjustment, in order
+    pub ticks: Vec<TickReport>,
+}
+
+impl CaptureAnalysis {
+    /// The first tick where a predicted value was different from the confirmed value, with the
+    /// components that diverged
+    pub fn first_divergence(&self) -> Option<(&TickReport, Vec<&ComponentDiff>)> {
+        self.ticks.iter().find_map(|report| {
+            let diverged: Vec<_> = report.components.iter().filter(|c| c.diverged()).collect();
+            (!diverged.is_empty()).then_some((report, diverged))
+        })
+    }
+}
+
+/// Extract the numbers contained in the [`Debug`] representation of a value
+fn numbers(value: &str) -> Vec<f64> {
+    value
+        .split(|c: char| !(c.is_ascii_digit() || matches!(c, '.' | '-' | '+' | 'e' | 'E')))
+        .filter_map(|token| token.parse().ok())
+        .collect()
+}
+
+fn magnitude(predicted: Option<&String>, confirmed: Option<&String>) -> Option<f64> {
+    let (predicted, confirmed) = (numbers(predicted?), numbers(confirmed?));
+    if predicted.is_empty() || predicted.len() != confirmed.len() {
+        return None;
+    }
+    predicted
+        .iter()
+        .zip(confirmed.iter())
+        .map(|(p, c)| (p - c).abs())
+        .reduce(f64::max)
+}
+
+/// Compare the predicted and confirmed values of a [`PredictionCapture`] on each tick.
+///
+/// The magnitude of a divergence is the largest difference between the numbers of the [`Debug`]
+/// representations of the values (for example the coordinates of a position).
+pub fn analyze_capture(capture: &PredictionCapture) -> CaptureAnalysis {
+    let mut ticks: Vec<Tick> = capture
+        .components
+        .iter()
+        .flat_map(|component| component.confirmed.iter().map(|(tick, _)| *tick))
+        .chain(capture.rollbacks.iter().map(|r| r.current_tick))
+        .chain(capture.sync_adjustments.iter().map(|s| s.new_tick))
+        .collect();
+    ticks.sort();
+    ticks.dedup();
+    let ticks = ticks
+        .into_iter()
+        .map(|tick| TickReport {
+            tick,
+            inputs: capture
+                .inputs
+                .iter()
+                .filter(|input| input.tick == tick)
+                .cloned()
+                .collect(),
+            components: capture
+                .components
+                .iter()
+                .filter_map(|component| {
+                    let (_, confirmed) = component.confirmed.iter().find(|(t, _)| *t == tick)?;
+                    let (_, predicted) = component.predicted.iter().find(|(t, _)| *t == tick)?;
+                    Some(ComponentDiff {
+                        component: component.name.clone(),
+                        predicted: predicted.clone(),
+                        confirmed: confirmed.clone(),
+                        magnitude: magnitude(predicted.as_ref(), confirmed.as_ref()),
+                    })
+                })
+                .collect(),
+            rollbacks: capture
+                .rollbacks
+                .iter()
+                .filter(|r| r.current_tick == tick)
+                .cloned()
+                .collect(),
+            sync_adjustments: capture
+                .sync_adjustments
+                .iter()
+                .filter(|s| s.new_tick == tick)
+                .copied()
+                .collect(),
+        })
+        .collect();
+    CaptureAnalysis {
+        entity: capture.entity,
+        tick: capture.tick,
+        ticks,
+    }
+}
+
+fn display_value(value: &Option<String>) -> &str {
+    value.as_deref().unwrap_or("<absent>")
+}
+
+fn display_inputs(inputs: &[InputCapture]) -> String {
+    let inputs: Vec<_> = inputs
+        .iter()
+        .map(|input| {
+            format!(
+                "player {}: [{}]",
+                input.local_player.0,
+                input.inputs.join(", ")
+            )
+        })
+        .collect();
+    if inputs.is_empty() {
+        "<none recorded>".to_string()
+    } else {
+        inputs.join("; ")
+    }
+}
+
+impl Display for CaptureAnalysis {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "Prediction capture of entity {:?} at tick {}",
+            self.entity, self.tick.0
+        )?;
+        match self.first_divergence() {
+            None => writeln!(
+                f,
+                "No divergence between the predicted and confirmed values"
+            )?,
+            Some((report, components)) => {
+                for diff in components {
+                    write!(
+                        f,
+                        "First divergence at tick {}: {}",
+                        report.tick.0, diff.component
+                    )?;
+                    if let Some(magnitude) = diff.magnitude {
+                        write!(f, " by {magnitude}")?;
+                    }
+                    writeln!(f, " (input: {})", display_inputs(&report.inputs))?;
+                }
+            }
+        }
+        for report in &self.ticks {
+            writeln!(
+                f,
+                "tick {}: input {}",
+                report.tick.0,
+                display_inputs(&report.inputs)
+            )?;
+            for diff in &report.components {
+                if diff.diverged() {
+                    write!(
+                        f,
+                        "  {} DIVERGED: predicted {} / confirmed {}",
+                        diff.component,
+                        display_value(&diff.predicted),
+                        display_value(&diff.confirmed)
+                    )?;
+                    match diff.magnitude {
+                        Some(magnitude) => writeln!(f, " (by {magnitude})")?,
+                        None => writeln!(f)?,
+                    }
+                } else {
+                    writeln!(
+                        f,
+                        "  {} ok: {}",
+                        diff.component,
+                        display_value(&diff.confirmed)
+                    )?;
+                }
+            }
+            for rollback in &report.rollbacks {
+                writeln!(
+                    f,
+                    "  rollback to tick {} ({} ticks resimulated, mismatched: [{}])",
+                    rollback.rollback_tick.0,
+                    rollback.num_resimulated_ticks,
+                    rollback.mismatched_components.join(", ")
+                )?;
+            }
+            for sync in &report.sync_adjustments {
+                writeln!(
+                    f,
+                    "  tick snapped from {} to {}",
+                    sync.old_tick.0, sync.new_tick.0
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::client::config::ClientConfig;
+    use crate::client::prediction::rollback::test_utils::received_confirmed_update;
+    use crate::prelude::{SharedConfig, TickConfig};
+    use crate::tests::protocol::Component1;
+    use crate::tests::stepper::{BevyStepper, Step};
+
+    fn values(values: &[(u16, f32)]) -> ComponentValues {
+        values
+            .iter()
+            .map(|(tick, value)| (Tick(*tick), Some(format!("{:?}", Component1(*value)))))
+            .collect()
+    }
+
+    /// Capture where the prediction diverges from tick 12, and is corrected by a rollback
+    fn synthetic_capture() -> PredictionCapture {
+        PredictionCapture {
+            entity: Entity::from_raw(1),
+            tick: Tick(20),
+            components: vec![ComponentCapture {
+                name: "Component1".to_string(),
+                predicted: values(&[(10, 1.0), (11, 2.0), (12, 3.0), (13, 4.0), (14, 5.0)]),
+                confirmed: values(&[(10, 1.0), (11, 2.0), (12, 3.5), (14, 7.0)]),
+            }],
+            inputs: (10..15)
+                .map(|tick| InputCapture {
+                    tick: Tick(tick),
+                    local_player: LocalPlayerId(0),
+                    inputs: vec![format!("Jump({tick})")],
+                })
+                .collect(),
+            rollbacks: vec![RollbackCapture {
+                rollback_tick: Tick(12),
+                current_tick: Tick(16),
+                num_resimulated_ticks: 4,
+                mismatched_components: vec!["Component1".to_string()],
+            }],
+            sync_adjustments: vec![SyncAdjustment {
+                old_tick: Tick(5),
+                new_tick: Tick(9),
+            }],
+        }
+    }
+
+    /// The divergence is attributed to the first tick where the predicted value differs from the
+    /// confirmed value, with the input that was applied on that tick
+    #[test]
+    fn test_first_divergence() {
+        let analysis = analyze_capture(&synthetic_capture());
+        let ticks: Vec<_> = analysis.ticks.iter().map(|report| report.tick).collect();
+        assert_eq!(
+            ticks,
+            vec![Tick(9), Tick(10), Tick(11), Tick(12), Tick(14), Tick(16)]
+        );
+
+        let (report, components) = analysis.first_divergence().unwrap();
+        assert_eq!(report.tick, Tick(12));
+        assert_eq!(report.inputs[0].inputs, vec!["Jump(12)".to_string()]);
+        assert_eq!(components.len(), 1);
+        assert_eq!(components[0].component, "Component1");
+        assert_eq!(components[0].magnitude, Some(0.5));
+
+        let rendered = analysis.to_string();
+        assert!(
+            rendered.contains(
+                "First divergence at tick 12: Component1 by 0.5 (input: player 0: [Jump(12)])"
+            ),
+            "{rendered}"
+        );
+        assert!(rendered.contains("rollback to tick 12"), "{rendered}");
+        assert!(rendered.contains("tick snapped from 5 to 9"), "{rendered}");
+    }
+
+    #[test]
+    fn test_capture_serialization() {
+        let capture = synthetic_capture();
+        let mut writer = crate::serialize::writer::Writer::default();
+        capture.to_bytes(&mut writer).unwrap();
+        let bytes = writer.to_bytes();
+        assert_eq!(bytes.len(), capture.len());
+        let mut reader = Reader::from(bytes);
+        assert_eq!(PredictionCapture::from_bytes(&mut reader).unwrap(), capture);
+    }
+
+    fn increment_component(mut query: Query<&mut Component1, With<Predicted>>) {
+        for mut component in query.iter_mut() {
+            component.0 += 1.0;
+        }
+    }
+
+    /// A misprediction is recorded with the rollback that it caused, and the capture attributes it
+    /// to the tick of the confirmed update
+    #[test]
+    fn test_record_misprediction() {
+        let frame_duration = Duration::from_millis(10);
+        let shared_config = SharedConfig {
+            tick: TickConfig::new(frame_duration),
+            ..default()
+        };
+        let mut stepper = BevyStepper::new(shared_config, ClientConfig::default(), frame_duration);
+        stepper
+            .client_app
+            .add_plugins(PredictionCapturePlugin {
+                config: PredictionCaptureConfig::default().with_auto_capture_rollback_depth(1),
+            })
+            .add_prediction_capture::<Component1>()
+            .add_systems(FixedUpdate, increment_component);
+        stepper.init();
+        let confirmed = stepper
+            .client_app
+            .world_mut()
+            .spawn((Confirmed::default(), Component1(0.0)))
+            .id();
+        let predicted = stepper
+            .client_app
+            .world_mut()
+            .spawn(Predicted {
+                confirmed_entity: Some(confirmed),
+            })
+            .id();
+        stepper
+            .client_app
+            .world_mut()
+            .get_mut::<Confirmed>(confirmed)
+            .unwrap()
+            .predicted = Some(predicted);
+        for _ in 0..5 {
+            stepper.frame_step();
+        }
+        // the entity is rolled back when its history starts
+        stepper
+            .client_app
+            .world_mut()
+            .resource_mut::<Events<PredictionCaptureEvent>>()
+            .clear();
+
+        // the server disagrees with the prediction
+        let tick = stepper.client_tick() - 1;
+        stepper
+            .client_app
+            .world_mut()
+            .get_mut::<Component1>(confirmed)
+            .unwrap()
+            .0 = -10.0;
+        received_confirmed_update(&mut stepper, confirmed, tick);
+        stepper.frame_step();
+
+        let events: Vec<_> = stepper
+            .client_app
+            .world_mut()
+            .resource_mut::<Events<PredictionCaptureEvent>>()
+            .drain()
+            .collect();
+        assert_eq!(events.len(), 1);
+        let capture = &events[0].capture;
+        assert_eq!(capture.entity, predicted);
+        let rollback = capture.rollbacks.last().unwrap();
+        assert_eq!(rollback.rollback_tick, tick);
+        assert_eq!(
+            rollback.mismatched_components,
+            vec![std::any::type_name::<Component1>().to_string()]
+        );
+
+        let analysis = analyze_capture(capture);
+        let (report, components) = analysis.first_divergence().unwrap();
+        assert_eq!(report.tick, tick);
+        assert_eq!(
+            components[0].confirmed,
+            Some(format!("{:?}", Component1(-10.0)))
+        );
+
+        // the manual capture contains the resimulated ticks
+        let capture = stepper
+            .client_app
+            .world()
+            .resource::<PredictionRecorder>()
+            .capture_history(predicted);
+        let (last_tick, last_value) = capture.components[0].predicted.last().unwrap();
+        assert_eq!(*last_tick, stepper.client_tick());
+        assert!(last_value.is_some());
+    }
+}
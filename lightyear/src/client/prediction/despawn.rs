@@ -6,8 +6,11 @@ use bevy::prelude::{
 };
 use tracing::{debug, error, trace};
 
+use crate::client::cleanup::NetworkedComponentsStripped;
 use crate::client::components::{ComponentSyncMode, Confirmed, SyncComponent};
 use crate::client::config::ClientConfig;
+use crate::client::prediction::correction::Correction;
+use crate::client::prediction::predicted_history::PredictionHistory;
 use crate::client::prediction::resource::PredictionManager;
 use crate::client::prediction::Predicted;
 use crate::prelude::{ComponentRegistry, Mode, ShouldBePredicted, TickManager};
@@ -103,6 +106,17 @@ pub(crate) fn despawn_confirmed(
     }
 }
 
+/// Remove the prediction components of `C` from a predicted entity that stops being predicted
+/// (see [`CleanupAction::StripNetworkedComponents`](crate::client::cleanup::CleanupAction::StripNetworkedComponents))
+pub(crate) fn remove_prediction_components_on_strip<C: SyncComponent>(
+    trigger: Trigger<NetworkedComponentsStripped>,
+    mut commands: Commands,
+) {
+    if let Some(mut entity_commands) = commands.get_entity(trigger.entity()) {
+        entity_commands.remove::<(PredictionHistory<C>, Correction<C>, RemovedCache<C>)>();
+    }
+}
+
 #[derive(Component)]
 pub(crate) struct RemovedCache<C: Component>(pub Option<C>);
 
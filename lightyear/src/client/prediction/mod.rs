@@ -2,6 +2,8 @@
 use bevy::prelude::{Component, Entity, Reflect};
 use std::fmt::Debug;
 
+pub mod adaptive;
+pub mod capture;
 pub mod correction;
 pub mod despawn;
 pub mod diagnostics;
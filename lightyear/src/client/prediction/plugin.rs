@@ -7,16 +7,19 @@ use bevy::transform::TransformSystem;
 use std::time::Duration;
 
 use crate::client::components::{ComponentSyncMode, Confirmed, SyncComponent};
+use crate::client::prediction::adaptive::seed_predicted_from_interpolated;
 use crate::client::prediction::correction::{
     get_visually_corrected_state, restore_corrected_state,
 };
 use crate::client::prediction::despawn::{
     despawn_confirmed, remove_component_for_despawn_predicted, remove_despawn_marker,
-    restore_components_if_despawn_rolled_back, PredictionDespawnMarker,
+    remove_prediction_components_on_strip, restore_components_if_despawn_rolled_back,
+    PredictionDespawnMarker,
 };
 use crate::client::prediction::predicted_history::{
-    add_prespawned_component_history, apply_component_removal_confirmed,
-    apply_component_removal_predicted, update_prediction_history,
+    add_predicted_component_history, add_prespawned_component_history,
+    apply_component_removal_confirmed, apply_component_removal_predicted,
+    trim_local_component_history, trim_prediction_history, update_prediction_history,
 };
 use crate::client::prediction::prespawn::{
     PreSpawnedPlayerObjectPlugin, PreSpawnedPlayerObjectSet,
@@ -29,8 +32,9 @@ use crate::shared::sets::{ClientMarker, InternalMainSet};
 use super::pre_prediction::{PrePredictionPlugin, PrePredictionSet};
 use super::predicted_history::{add_component_history, apply_confirmed_update};
 use super::rollback::{
-    check_rollback, increment_rollback_tick, prepare_rollback, prepare_rollback_prespawn,
-    run_rollback, Rollback, RollbackState,
+    check_rollback, increment_rollback_tick, prepare_rollback, prepare_rollback_local,
+    prepare_rollback_prespawn, run_rollback, send_rollback_window_exceeded_events, Rollback,
+    RollbackEvent, RollbackState, RollbackWindowExceededEvent,
 };
 use super::spawn::spawn_predicted_entity;
 
@@ -74,6 +78,46 @@ pub struct PredictionConfig {
     /// (i.e. if the client is 10 ticks head and correction_ticks is 1.0, then the correction will be done over 10 ticks)
     // Number of ticks it will take to visually update the Predicted state to the new Corrected state
     pub correction_ticks_factor: f32,
+    /// How the number of ticks of input delay is chosen.
+    ///
+    /// By default, the input delay is computed from the RTT with `minimum_input_delay_ticks` and
+    /// `maximum_input_delay_before_prediction`.
+    pub input_delay: InputDelayConfig,
+    /// Number of ticks of prediction history that are kept for the rollbacks.
+    ///
+    /// If a confirmed update arrives for a tick that is older than that (after a long stall or with a very high RTT),
+    /// the predicted entity cannot be rolled back: it is snapped to the confirmed state instead, and a
+    /// [`RollbackWindowExceededEvent`](crate::prelude::client::RollbackWindowExceededEvent) is emitted.
+    ///
+    /// The default value is 200 ticks (about 3 seconds at 64Hz)
+    pub max_rollback_ticks: u16,
+    /// Number of ticks after which an entity pre-spawned with a
+    /// [`PreSpawnedPlayerObject`](crate::prelude::PreSpawnedPlayerObject) is despawned, if it was not matched
+    /// with an entity spawned by the server.
+    ///
+    /// If `None`, the entity is despawned once the server entity should have been received: after twice the
+    /// number of ticks between the current tick and the interpolation tick.
+    pub prespawn_cleanup_ticks: Option<u16>,
+}
+
+/// How the client picks the number of ticks of input delay
+#[derive(Debug, Default, Clone, Copy, PartialEq, Reflect)]
+pub enum InputDelayConfig {
+    /// The input delay is computed from the RTT with the `minimum_input_delay_ticks`, `maximum_input_delay_before_prediction`
+    /// and `maximum_predicted_ticks` settings of the [`PredictionConfig`]
+    #[default]
+    Fixed,
+    /// The input delay covers the whole round trip: it is the number of ticks in `RTT + rtt_margin`, clamped to `max_ticks`.
+    ///
+    /// The value only decreases once the RTT went down by more than half a tick, so that it doesn't oscillate
+    /// with the jitter. The current value is returned by
+    /// [`ConnectionManager::input_delay_ticks`](crate::client::connection::ConnectionManager::input_delay_ticks).
+    Automatic {
+        /// Maximum number of ticks of input delay; the rest of the latency is covered by prediction
+        max_ticks: u16,
+        /// Added to the RTT estimate before converting it to ticks
+        rtt_margin: Duration,
+    },
 }
 
 impl Default for PredictionConfig {
@@ -92,6 +136,9 @@ impl Default for PredictionConfig {
             maximum_input_delay_before_prediction: 0,
             maximum_predicted_ticks: 100,
             correction_ticks_factor: 1.0,
+            input_delay: InputDelayConfig::Fixed,
+            max_rollback_ticks: 200,
+            prespawn_cleanup_ticks: None,
         }
     }
 }
@@ -114,6 +161,41 @@ impl PredictionConfig {
         self
     }
 
+    /// Choose how the number of ticks of input delay is computed
+    pub fn with_input_delay(mut self, input_delay: InputDelayConfig) -> Self {
+        self.input_delay = input_delay;
+        self
+    }
+
+    /// Update the number of ticks of prediction history kept for the rollbacks
+    pub fn with_max_rollback_ticks(mut self, ticks: u16) -> Self {
+        self.max_rollback_ticks = ticks;
+        self
+    }
+
+    /// Despawn the unmatched pre-spawned entities after a fixed number of ticks
+    pub fn with_prespawn_cleanup_ticks(mut self, ticks: u16) -> Self {
+        self.prespawn_cleanup_ticks = Some(ticks);
+        self
+    }
+
+    /// Rescale the settings that are expressed in ticks, so that they keep the same duration
+    /// when the tick duration changes from `from` to `to`
+    pub(crate) fn rescale_ticks(mut self, from: Duration, to: Duration) -> Self {
+        let rescale =
+            |ticks: u16| (ticks as f64 * from.as_secs_f64() / to.as_secs_f64()).round() as u16;
+        self.minimum_input_delay_ticks = rescale(self.minimum_input_delay_ticks);
+        self.maximum_input_delay_before_prediction =
+            rescale(self.maximum_input_delay_before_prediction);
+        self.maximum_predicted_ticks = rescale(self.maximum_predicted_ticks);
+        self.max_rollback_ticks = rescale(self.max_rollback_ticks);
+        self.prespawn_cleanup_ticks = self.prespawn_cleanup_ticks.map(rescale);
+        if let InputDelayConfig::Automatic { max_ticks, .. } = &mut self.input_delay {
+            *max_ticks = rescale(*max_ticks);
+        }
+        self
+    }
+
     /// Compute the amount of input delay that should be applied, considering the current RTT
     pub fn input_delay_ticks(&self, rtt: Duration, tick_interval: Duration) -> u16 {
         let rtt_ticks = rtt.as_nanos() as f32 / tick_interval.as_nanos() as f32;
@@ -188,6 +270,8 @@ pub fn add_prediction_systems<C: SyncComponent>(app: &mut App, prediction_mode:
         (
             // handle components being added
             add_component_history::<C>.in_set(PredictionSet::SpawnHistory),
+            // seed the predicted entity if the local player got promoted from interpolation
+            seed_predicted_from_interpolated::<C>.in_set(PredictionSet::SpawnHistory),
         ),
     );
     match prediction_mode {
@@ -212,9 +296,19 @@ pub fn add_prediction_systems<C: SyncComponent>(app: &mut App, prediction_mode:
             app.add_systems(
                 FixedPostUpdate,
                 (
-                    add_prespawned_component_history::<C>.in_set(PredictionSet::SpawnHistory),
+                    (
+                        add_prespawned_component_history::<C>,
+                        add_predicted_component_history::<C>,
+                    )
+                        .in_set(PredictionSet::SpawnHistory),
                     // we need to run this during fixed update to know accurately the history for each tick
-                    update_prediction_history::<C>.in_set(PredictionSet::UpdateHistory),
+                    (
+                        update_prediction_history::<C>,
+                        // only the histories that are compared with the server state are trimmed
+                        trim_prediction_history::<C>,
+                    )
+                        .chain()
+                        .in_set(PredictionSet::UpdateHistory),
                 ),
             );
             app.add_systems(
@@ -251,6 +345,30 @@ pub fn add_prediction_systems<C: SyncComponent>(app: &mut App, prediction_mode:
         FixedPostUpdate,
         remove_component_for_despawn_predicted::<C>.in_set(PredictionSet::EntityDespawn),
     );
+    app.observe(remove_prediction_components_on_strip::<C>);
+}
+
+/// Add the systems needed to roll back a component that is not replicated, but is simulated locally
+/// on the predicted entities.
+///
+/// The history of the component (including its insertions and removals) is recorded every tick, and on rollback the
+/// component is restored to the state it had at the rollback tick.
+pub fn add_rollback_systems<C: SyncComponent>(app: &mut App) {
+    app.observe(apply_component_removal_predicted::<C>);
+    app.add_systems(
+        PreUpdate,
+        (
+            trim_local_component_history::<C>.in_set(PredictionSet::CheckRollback),
+            prepare_rollback_local::<C>.in_set(PredictionSet::PrepareRollback),
+        ),
+    );
+    app.add_systems(
+        FixedPostUpdate,
+        (
+            add_predicted_component_history::<C>.in_set(PredictionSet::SpawnHistory),
+            update_prediction_history::<C>.in_set(PredictionSet::UpdateHistory),
+        ),
+    );
 }
 
 impl Plugin for PredictionPlugin {
@@ -273,6 +391,10 @@ impl Plugin for PredictionPlugin {
         app.init_resource::<PredictionManager>();
         app.insert_resource(Rollback::new(RollbackState::Default));
 
+        // EVENTS
+        app.add_event::<RollbackEvent>();
+        app.add_event::<RollbackWindowExceededEvent>();
+
         // PreUpdate systems:
         // 1. Receive confirmed entities, add Confirmed and Predicted components
         // 2. (in prediction_systems) add ComponentHistory
@@ -309,6 +431,10 @@ impl Plugin for PredictionPlugin {
                     .after(PreSpawnedPlayerObjectSet::Spawn)
                     .after(PrePredictionSet::Spawn)
                     .in_set(PredictionSet::SpawnPrediction),
+                send_rollback_window_exceeded_events
+                    .after(PredictionSet::CheckRollback)
+                    .before(PredictionSet::PrepareRollback)
+                    .in_set(PredictionSet::All),
                 run_rollback.in_set(PredictionSet::Rollback),
             ),
         );
@@ -371,6 +497,9 @@ mod tests {
             maximum_input_delay_before_prediction: 3,
             maximum_predicted_ticks: 7,
             correction_ticks_factor: 0.0,
+            input_delay: InputDelayConfig::Fixed,
+            max_rollback_ticks: 200,
+            prespawn_cleanup_ticks: None,
         };
         // 1. Test the minimum input delay
         assert_eq!(
@@ -394,4 +523,30 @@ mod tests {
             12
         );
     }
+
+    #[test]
+    fn test_rescale_ticks() {
+        let config = PredictionConfig {
+            always_rollback: false,
+            minimum_input_delay_ticks: 2,
+            maximum_input_delay_before_prediction: 4,
+            maximum_predicted_ticks: 7,
+            correction_ticks_factor: 0.0,
+            input_delay: InputDelayConfig::Fixed,
+            max_rollback_ticks: 200,
+            prespawn_cleanup_ticks: Some(20),
+        };
+        // from 64Hz to 32Hz: the same durations fit in half as many ticks
+        let rescaled = config.rescale_ticks(Duration::from_millis(15), Duration::from_millis(30));
+        assert_eq!(rescaled.minimum_input_delay_ticks, 1);
+        assert_eq!(rescaled.maximum_input_delay_before_prediction, 2);
+        // rounded to the closest number of ticks
+        assert_eq!(rescaled.maximum_predicted_ticks, 4);
+        assert_eq!(rescaled.prespawn_cleanup_ticks, Some(10));
+        // the rescaled settings cover the same latency
+        assert_eq!(
+            config.input_delay_ticks(Duration::from_millis(150), Duration::from_millis(15)) * 15,
+            rescaled.input_delay_ticks(Duration::from_millis(150), Duration::from_millis(30)) * 30,
+        );
+    }
 }
@@ -9,6 +9,7 @@ use bevy::prelude::{
 use tracing::{debug, trace};
 
 use crate::client::components::{ComponentSyncMode, Confirmed, SyncComponent};
+use crate::client::config::ClientConfig;
 use crate::client::prediction::resource::PredictionManager;
 use crate::client::prediction::rollback::Rollback;
 use crate::client::prediction::Predicted;
@@ -35,12 +36,16 @@ pub(crate) struct PredictionHistory<C: PartialEq> {
 
     // We will only store the history for the ticks where the component got updated
     pub buffer: ReadyBuffer<Tick, ComponentState<C>>,
+    /// Earliest tick of the history, if the values older than this tick were discarded because they were
+    /// more than [`max_rollback_ticks`](crate::prelude::client::PredictionConfig::max_rollback_ticks) in the past
+    pub(crate) earliest_retained_tick: Option<Tick>,
 }
 
 impl<C: PartialEq> Default for PredictionHistory<C> {
     fn default() -> Self {
         Self {
             buffer: ReadyBuffer::new(),
+            earliest_retained_tick: None,
         }
     }
 }
@@ -59,6 +64,7 @@ impl<C: SyncComponent> PredictionHistory<C> {
     /// Reset the history for this component
     pub(crate) fn clear(&mut self) {
         self.buffer = ReadyBuffer::new();
+        self.earliest_retained_tick = None;
     }
 
     /// Add to the buffer that we received an update for the component at the given tick
@@ -87,6 +93,19 @@ impl<C: SyncComponent> PredictionHistory<C> {
             state
         })
     }
+
+    /// Discard the values that are older than `tick`.
+    ///
+    /// The most recent value older than `tick` is kept as the value at `tick`, so that the history
+    /// still knows the state of the component from `tick` onwards.
+    pub(crate) fn discard_before(&mut self, tick: Tick) {
+        if self.buffer.heap.peek().is_some_and(|item| item.key < tick) {
+            if let Some((_, state)) = self.buffer.pop_until(&tick) {
+                self.buffer.push(tick, state);
+            }
+            self.earliest_retained_tick = Some(tick);
+        }
+    }
 }
 
 /// Add component history for entities that are predicted
@@ -98,12 +117,9 @@ pub(crate) fn add_component_history<C: SyncComponent>(
     mut commands: Commands,
     tick_manager: Res<TickManager>,
     predicted_entities: Query<
-        (Entity, Option<Ref<C>>),
-        (
-            Without<PredictionHistory<C>>,
-            // for all types of predicted entities, we want to add the component history to enable them to be rolled-back
-            With<Predicted>,
-        ),
+        (Entity, Option<Ref<C>>, Ref<Predicted>),
+        // for all types of predicted entities, we want to add the component history to enable them to be rolled-back
+        Without<PredictionHistory<C>>,
     >,
     confirmed_entities: Query<(Entity, &Confirmed, Option<Ref<C>>)>,
 ) {
@@ -111,7 +127,9 @@ pub(crate) fn add_component_history<C: SyncComponent>(
     let tick = tick_manager.tick();
     for (confirmed_entity, confirmed, confirmed_component) in confirmed_entities.iter() {
         if let Some(p) = confirmed.predicted {
-            if let Ok((predicted_entity, predicted_component)) = predicted_entities.get(p) {
+            if let Ok((predicted_entity, predicted_component, predicted)) =
+                predicted_entities.get(p)
+            {
                 // if component got added on predicted side, add history
                 add_history::<C>(
                     component_registry.as_ref(),
@@ -121,11 +139,12 @@ pub(crate) fn add_component_history<C: SyncComponent>(
                     &mut commands,
                 );
 
-                // if component got added on confirmed side
+                // if component got added on confirmed side, or if the predicted entity was just spawned
+                // for a confirmed entity that already existed (the client was added to the prediction target)
                 // - full: sync component and add history
                 // - simple/once: sync component
                 if let Some(confirmed_component) = confirmed_component {
-                    if confirmed_component.is_added() {
+                    if confirmed_component.is_added() || predicted.is_added() {
                         trace!(?kind, "Component added on confirmed side");
                         // safety: we know the entity exists
                         let mut predicted_entity_mut =
@@ -196,6 +215,62 @@ pub(crate) fn add_prespawned_component_history<C: SyncComponent>(
     }
 }
 
+/// Add the history for components that get inserted on predicted entities during FixedUpdate
+/// (for example by the systems that handle inputs), so that the insertion is recorded at the exact tick
+/// where it happened, including when it happens while re-simulating during a rollback.
+pub(crate) fn add_predicted_component_history<C: SyncComponent>(
+    mut commands: Commands,
+    tick_manager: Res<TickManager>,
+    rollback: Res<Rollback>,
+    predicted_query: Query<(Entity, Ref<C>), (Without<PredictionHistory<C>>, With<Predicted>)>,
+) {
+    let kind = std::any::type_name::<C>();
+    let tick = tick_manager.tick_or_rollback_tick(rollback.as_ref());
+    for (predicted_entity, predicted_component) in predicted_query.iter() {
+        if predicted_component.is_added() {
+            debug!(?kind, ?tick, ?predicted_entity, "Adding prediction history");
+            let mut history = PredictionHistory::<C>::default();
+            history.add_update(tick, predicted_component.deref().clone());
+            commands.entity(predicted_entity).insert(history);
+        }
+    }
+}
+
+/// For components that are only simulated locally (registered with
+/// [`add_rollback`](crate::prelude::AppComponentExt::add_rollback)), there is no server update to compare against,
+/// so we discard the history that is older than the confirmed tick: we will never roll back further than that.
+pub(crate) fn trim_local_component_history<C: SyncComponent>(
+    mut predicted_query: Query<(&Predicted, &mut PredictionHistory<C>)>,
+    confirmed_query: Query<&Confirmed>,
+) {
+    for (predicted, mut history) in predicted_query.iter_mut() {
+        if let Some(confirmed) = predicted
+            .confirmed_entity
+            .and_then(|entity| confirmed_query.get(entity).ok())
+        {
+            history.pop_until_tick(confirmed.tick);
+        }
+    }
+}
+
+/// Only keep the last [`max_rollback_ticks`](crate::prelude::client::PredictionConfig::max_rollback_ticks)
+/// ticks of history, so that the history doesn't grow forever when we don't receive any server update.
+pub(crate) fn trim_prediction_history<C: SyncComponent>(
+    config: Res<ClientConfig>,
+    tick_manager: Res<TickManager>,
+    rollback: Res<Rollback>,
+    mut predicted_query: Query<&mut PredictionHistory<C>, (With<Predicted>, Without<Confirmed>)>,
+) {
+    // the history of the ticks that are being re-simulated is still needed
+    if rollback.is_rollback() {
+        return;
+    }
+    let earliest_tick = tick_manager.tick() - config.prediction.max_rollback_ticks;
+    for mut history in predicted_query.iter_mut() {
+        history.discard_before(earliest_tick);
+    }
+}
+
 /// Add a PredictionHistory component to the predicted entity
 fn add_history<C: SyncComponent>(
     component_registry: &ComponentRegistry,
@@ -370,6 +445,40 @@ mod tests {
         assert_eq!(component_history.buffer.len(), 0);
     }
 
+    /// Test discarding the history that is older than the rollback window
+    #[test]
+    fn test_discard_before() {
+        let mut component_history = PredictionHistory::<Component1>::default();
+        component_history.add_update(Tick(1), Component1(1.0));
+        component_history.add_update(Tick(3), Component1(3.0));
+        component_history.add_update(Tick(6), Component1(6.0));
+
+        // nothing is older than the tick
+        component_history.discard_before(Tick(1));
+        assert_eq!(component_history.buffer.len(), 3);
+        assert_eq!(component_history.earliest_retained_tick, None);
+
+        // the value at tick 3 is kept as the value at tick 5
+        component_history.discard_before(Tick(5));
+        assert_eq!(component_history.buffer.len(), 2);
+        assert_eq!(component_history.earliest_retained_tick, Some(Tick(5)));
+        assert_eq!(
+            component_history.buffer.heap.peek(),
+            Some(&ItemWithReadyKey {
+                key: Tick(5),
+                item: ComponentState::Updated(Component1(3.0))
+            })
+        );
+        // the state in-between is still known
+        assert_eq!(
+            component_history.pop_until_tick(Tick(5)),
+            Some(ComponentState::Updated(Component1(3.0)))
+        );
+
+        component_history.clear();
+        assert_eq!(component_history.earliest_retained_tick, None);
+    }
+
     /// Test adding the component history to the predicted entity
     /// 1. Add the history for ComponentSyncMode::Full that was added to the confirmed entity
     /// 2. Add the history for ComponentSyncMode::Full that was added to the predicted entity
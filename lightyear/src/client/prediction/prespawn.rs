@@ -5,6 +5,7 @@ use serde::{Deserialize, Serialize};
 use tracing::{debug, trace};
 
 use crate::client::components::Confirmed;
+use crate::client::config::ClientConfig;
 use crate::client::connection::ConnectionManager;
 use crate::client::events::ComponentInsertEvent;
 use crate::client::prediction::resource::PredictionManager;
@@ -266,6 +267,7 @@ impl PreSpawnedPlayerObjectPlugin {
     /// Cleanup the client prespawned entities for which we couldn't find a mapped server entity
     pub(crate) fn pre_spawned_player_object_cleanup(
         mut commands: Commands,
+        config: Res<ClientConfig>,
         tick_manager: Res<TickManager>,
         connection: Res<ConnectionManager>,
         mut manager: ResMut<PredictionManager>,
@@ -287,7 +289,10 @@ impl PreSpawnedPlayerObjectPlugin {
         //     tick,
         //     interpolation_tick
         // );
-        let tick_diff = (tick - interpolation_tick).saturating_mul(2) as u16;
+        let tick_diff = config
+            .prediction
+            .prespawn_cleanup_ticks
+            .unwrap_or_else(|| (tick - interpolation_tick).saturating_mul(2) as u16);
         let past_tick = tick - tick_diff;
         // remove all the prespawned entities that have not been matched with a server entity
         for (_, hash) in manager.prespawn_tick_to_hash.drain_until(&past_tick) {
@@ -327,6 +332,11 @@ impl PreSpawnedPlayerObjectPlugin {
 /// // User-provided custom hash
 /// let custom_hash: u64 = compute_hash();
 /// PreSpawnedPlayerObject::new(hash);
+///
+/// // Id allocated from the range of pre-spawned entity ids reserved for the client
+/// // (see [`prespawn_ids`](crate::shared::replication::prespawn_ids))
+/// let id: u64 = allocator.allocate().unwrap();
+/// PreSpawnedPlayerObject::new(id);
 /// ``````
 pub struct PreSpawnedPlayerObject {
     /// The hash that will identify the spawned entity
@@ -474,7 +484,7 @@ mod tests {
 
         let current_tick = stepper.client_app.world().resource::<TickManager>().tick();
         let prediction_manager = stepper.client_app.world().resource::<PredictionManager>();
-        let expected_hash: u64 = 1572575978495317502;
+        let expected_hash: u64 = 2124740011946866177;
         assert_eq!(
             prediction_manager
                 .prespawn_hash_to_entities
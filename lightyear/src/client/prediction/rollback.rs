@@ -5,8 +5,8 @@ use bevy::app::FixedMain;
 use bevy::ecs::entity::EntityHashSet;
 use bevy::ecs::reflect::ReflectResource;
 use bevy::prelude::{
-    Commands, DespawnRecursiveExt, DetectChanges, Entity, Query, Ref, Res, ResMut, Resource, With,
-    Without, World,
+    Commands, DespawnRecursiveExt, DetectChanges, Entity, Event, EventWriter, Query, Ref, Res,
+    ResMut, Resource, With, Without, World,
 };
 use bevy::reflect::Reflect;
 use parking_lot::RwLock;
@@ -20,6 +20,8 @@ use crate::client::prediction::diagnostics::PredictionMetrics;
 use crate::client::prediction::predicted_history::ComponentState;
 use crate::client::prediction::resource::PredictionManager;
 use crate::prelude::{ComponentRegistry, PreSpawnedPlayerObject, Tick, TickManager};
+use crate::protocol::component::ComponentKind;
+use crate::shared::hooks::{run_network_hooks, PostRollback};
 
 use super::predicted_history::PredictionHistory;
 use super::Predicted;
@@ -34,6 +36,77 @@ pub struct Rollback {
     /// in parallel.
     pub state: RwLock<RollbackState>,
     // pub rollback_groups: EntityHashMap<ReplicationGroupId, RollbackState>,
+    #[reflect(ignore)]
+    /// Components whose predicted value didn't match the confirmed value, reported in the [`RollbackEvent`]
+    mismatched_components: RwLock<Vec<ComponentKind>>,
+    #[reflect(ignore)]
+    /// Predicted entities that were snapped to their confirmed state because the confirmed update was
+    /// older than their history
+    window_exceeded: RwLock<Vec<RollbackWindowExceededEvent>>,
+}
+
+/// Event emitted on the client whenever a rollback is triggered, before the ticks are resimulated.
+///
+/// The systems that run during the resimulation (in `FixedUpdate`) can read it to know that they are
+/// running as part of a rollback, for example to avoid spawning visual effects twice.
+///
+/// ```rust,ignore
+/// /// Times of the rollbacks of the last second
+/// #[derive(Resource, Default)]
+/// struct RecentRollbacks(VecDeque<Duration>);
+///
+/// fn log_rollbacks(
+///     mut events: EventReader<RollbackEvent>,
+///     registry: Res<ComponentRegistry>,
+///     time: Res<Time<Real>>,
+///     mut recent: ResMut<RecentRollbacks>,
+/// ) {
+///     let now = time.elapsed();
+///     for event in events.read() {
+///         let components: Vec<_> = event
+///             .mismatched_components
+///             .iter()
+///             .map(|kind| registry.name(*kind))
+///             .collect();
+///         info!(?components, "Rolled back to tick {:?}", event.rollback_tick);
+///         recent.0.push_back(now);
+///     }
+///     while recent.0.front().is_some_and(|t| now - *t > Duration::from_secs(1)) {
+///         recent.0.pop_front();
+///     }
+///     // `recent.0.len()` is the number of rollbacks per second
+/// }
+/// ```
+#[derive(Event, Debug, Clone, PartialEq)]
+pub struct RollbackEvent {
+    /// Tick of the confirmed state that the predicted entities were reset to
+    pub rollback_tick: Tick,
+    /// Tick of the client when the rollback happened. The ticks between `rollback_tick` (excluded)
+    /// and `current_tick` (included) are resimulated.
+    pub current_tick: Tick,
+    pub num_resimulated_ticks: u16,
+    /// Components whose predicted value was different from the confirmed value at `rollback_tick`.
+    ///
+    /// It can be empty if the rollback was not caused by a misprediction (for example when an entity
+    /// switches from interpolation to prediction).
+    pub mismatched_components: Vec<ComponentKind>,
+}
+
+/// Event emitted on the client when a predicted entity receives a confirmed update for a tick that is older than
+/// the prediction history that we kept (see [`PredictionConfig::max_rollback_ticks`](crate::prelude::client::PredictionConfig::max_rollback_ticks)),
+/// for example after a long stall or with a very high RTT.
+///
+/// The entity cannot be rolled back that far: instead its components are snapped to the confirmed state
+/// (without any visual correction) and its history restarts from `confirmed_tick`.
+/// This is usually visible as a teleport, so it is worth logging.
+#[derive(Event, Debug, Clone, PartialEq)]
+pub struct RollbackWindowExceededEvent {
+    /// The predicted entity
+    pub entity: Entity,
+    /// Tick of the confirmed update
+    pub confirmed_tick: Tick,
+    /// Earliest tick of the prediction history of the entity when the update was received
+    pub earliest_history_tick: Tick,
 }
 
 /// Resource that will track whether we should do rollback or not
@@ -56,6 +129,8 @@ impl Rollback {
     pub(crate) fn new(state: RollbackState) -> Self {
         Self {
             state: RwLock::new(state),
+            mismatched_components: RwLock::default(),
+            window_exceeded: RwLock::default(),
         }
     }
 
@@ -88,6 +163,27 @@ impl Rollback {
     /// Set the rollback state back to non-rollback
     pub(crate) fn set_non_rollback(&self) {
         *self.state.write().deref_mut() = RollbackState::Default;
+        self.mismatched_components.write().clear();
+    }
+
+    /// Record that the predicted value of a component didn't match the confirmed value
+    pub(crate) fn add_mismatch(&self, kind: ComponentKind) {
+        let mut mismatched_components = self.mismatched_components.write();
+        if !mismatched_components.contains(&kind) {
+            mismatched_components.push(kind);
+        }
+    }
+
+    /// Record that a predicted entity was snapped to its confirmed state. Each entity is only reported once,
+    /// even if several of its components exceeded the rollback window.
+    pub(crate) fn add_window_exceeded(&self, event: RollbackWindowExceededEvent) {
+        let mut window_exceeded = self.window_exceeded.write();
+        if !window_exceeded
+            .iter()
+            .any(|other| other.entity == event.entity)
+        {
+            window_exceeded.push(event);
+        }
     }
 
     /// Set the rollback state to `ShouldRollback` with the given tick
@@ -104,10 +200,14 @@ impl Rollback {
 pub(crate) fn check_rollback<C: SyncComponent>(
     component_registry: Res<ComponentRegistry>,
     // TODO: have a way to only get the updates of entities that are predicted?
+    mut commands: Commands,
     tick_manager: Res<TickManager>,
     connection: Res<ConnectionManager>,
-    // We also snap the value of the component to the server state if we are in rollback
-    mut predicted_query: Query<&mut PredictionHistory<C>, (With<Predicted>, Without<Confirmed>)>,
+    // We also snap the value of the component to the server state if the rollback window is exceeded
+    mut predicted_query: Query<
+        (Option<&mut C>, &mut PredictionHistory<C>),
+        (With<Predicted>, Without<Confirmed>),
+    >,
     // We use Option<> because the predicted component could have been removed while it still exists in Confirmed
     confirmed_query: Query<(Entity, Option<&C>, Ref<Confirmed>)>,
     rollback: Res<Rollback>,
@@ -147,7 +247,7 @@ pub(crate) fn check_rollback<C: SyncComponent>(
         let Some(p) = confirmed.predicted else {
             continue;
         };
-        let Ok(mut predicted_history) = predicted_query.get_mut(p) else {
+        let Ok((predicted_component, mut predicted_history)) = predicted_query.get_mut(p) else {
             debug!(
                 "Predicted entity {:?} was not found when checking rollback for {:?}",
                 confirmed.predicted,
@@ -171,38 +271,82 @@ pub(crate) fn check_rollback<C: SyncComponent>(
             continue;
         }
 
-        // 3.a We are still not sure if we should do rollback. Compare history against confirmed
-        // We rollback if there's no history (newly added predicted entity, or if there is a mismatch)
-        if !rollback.is_rollback() {
-            let history_value = predicted_history.pop_until_tick(tick);
-            let predicted_exist = history_value.is_some();
-            let confirmed_exist = confirmed_component.is_some();
-            let should_rollback = match confirmed_component {
-                // TODO: history-value should not be empty here; should we panic if it is?
-                // confirm does not exist. rollback if history value is not Removed
-                None => history_value.map_or(false, |history_value| {
-                    history_value != ComponentState::Removed
-                }),
-                // confirm exist. rollback if history value is different
-                Some(c) => history_value.map_or(true, |history_value| match history_value {
-                    ComponentState::Updated(history_value) => {
-                        component_registry.should_rollback(&history_value, c)
+        // 3. The confirmed tick is older than the history that we kept, so we cannot roll back that far.
+        //  Snap the predicted entity to the confirmed state instead, and rebuild the history from there
+        if let Some(earliest_history_tick) = predicted_history
+            .earliest_retained_tick
+            .filter(|earliest| tick < *earliest)
+        {
+            debug!(
+                ?confirmed_entity,
+                ?tick,
+                ?earliest_history_tick,
+                ?kind,
+                "Rollback window exceeded: snapping the predicted entity to the confirmed state"
+            );
+            rollback.add_window_exceeded(RollbackWindowExceededEvent {
+                entity: p,
+                confirmed_tick: tick,
+                earliest_history_tick,
+            });
+            predicted_history.clear();
+            let mut entity_mut = commands.entity(p);
+            entity_mut.remove::<Correction<C>>();
+            match confirmed_component {
+                None => {
+                    predicted_history.add_remove(tick);
+                    entity_mut.remove::<C>();
+                }
+                Some(c) => {
+                    predicted_history.add_update(tick, c.clone());
+                    match predicted_component {
+                        Some(mut predicted_component) => *predicted_component = c.clone(),
+                        None => {
+                            entity_mut.insert(c.clone());
+                        }
                     }
-                    ComponentState::Removed => true,
-                }),
-            };
-            if should_rollback {
-                debug!(
-                   ?predicted_exist, ?confirmed_exist,
-                   "Rollback check: mismatch for component between predicted and confirmed {:?} on tick {:?} for component {:?}. Current tick: {:?}",
-                   confirmed_entity, tick, kind, current_tick
-                   );
+                }
+            }
+            continue;
+        }
+
+        // 4. Compare history against confirmed
+        // We rollback if there's no history (newly added predicted entity, or if there is a mismatch)
+        // NOTE: we compare even if we already know that we should rollback (because of another entity/component),
+        //  so that the RollbackEvent lists all the mismatched components. The history is cleared when preparing
+        //  the rollback anyway.
+        let history_value = predicted_history.pop_until_tick(tick);
+        let predicted_exist = history_value.is_some();
+        let confirmed_exist = confirmed_component.is_some();
+        let should_rollback = match confirmed_component {
+            // TODO: history-value should not be empty here; should we panic if it is?
+            // confirm does not exist. rollback if history value is not Removed
+            None => history_value.map_or(false, |history_value| {
+                history_value != ComponentState::Removed
+            }),
+            // confirm exist. rollback if history value is different
+            Some(c) => history_value.map_or(true, |history_value| match history_value {
+                ComponentState::Updated(history_value) => {
+                    component_registry.should_rollback(&history_value, c)
+                }
+                ComponentState::Removed => true,
+            }),
+        };
+        if should_rollback {
+            debug!(
+               ?predicted_exist, ?confirmed_exist,
+               "Rollback check: mismatch for component between predicted and confirmed {:?} on tick {:?} for component {:?}. Current tick: {:?}",
+               confirmed_entity, tick, kind, current_tick
+               );
+            rollback.add_mismatch(ComponentKind::of::<C>());
+            // 4.a We were not sure if we should do rollback, start the rollback
+            if !rollback.is_rollback() {
                 // we already rolled-back the state for the entity's latest_tick
                 // after this we will start right away with a physics update, so we need to start taking the inputs from the next tick
                 rollback.set_rollback_tick(tick + 1);
             }
-        } else {
-            // 3.b We already know we should do rollback (because of another entity/component), start the rollback
+        } else if rollback.is_rollback() {
+            // 4.b We already know we should do rollback (because of another entity/component)
             trace!(
                    "Rollback check: should roll back for component between predicted and confirmed on tick {:?} for component {:?}. Current tick: {:?}",
                    tick, kind, current_tick
@@ -487,6 +631,66 @@ pub(crate) fn prepare_rollback_prespawn<C: SyncComponent>(
     }
 }
 
+/// For components that are only simulated locally (registered with
+/// [`add_rollback`](crate::prelude::AppComponentExt::add_rollback)), there is no Confirmed state to snap to:
+/// we restore the value (or the absence) of the component that was recorded in the history at the rollback tick.
+/// The insertions and removals that happened after that tick will be re-applied during the re-simulation.
+#[allow(clippy::type_complexity)]
+pub(crate) fn prepare_rollback_local<C: SyncComponent>(
+    mut commands: Commands,
+    mut predicted_query: Query<
+        (Entity, Option<&mut C>, &mut PredictionHistory<C>),
+        (With<Predicted>, Without<Confirmed>),
+    >,
+    rollback: Res<Rollback>,
+) {
+    let kind = std::any::type_name::<C>();
+    let _span = trace_span!("client prepare rollback for local components");
+
+    let Some(rollback_tick_plus_one) = rollback.get_rollback_tick() else {
+        error!("prepare_rollback_local should only be called when we are in rollback");
+        return;
+    };
+    // careful, the current_tick is already incremented by 1 in the check_rollback stage...
+    let rollback_tick = rollback_tick_plus_one - 1;
+
+    for (predicted_entity, predicted_component, mut predicted_history) in predicted_query.iter_mut()
+    {
+        let state = predicted_history.pop_until_tick(rollback_tick);
+        // we need to clear the history so we can write a new one
+        predicted_history.clear();
+        match state {
+            None | Some(ComponentState::Removed) => {
+                predicted_history.add_remove(rollback_tick);
+                if predicted_component.is_some() {
+                    debug!(
+                        ?predicted_entity,
+                        ?kind,
+                        "Component didn't exist at time of rollback, removing it"
+                    );
+                    commands.entity(predicted_entity).remove::<C>();
+                }
+            }
+            Some(ComponentState::Updated(c)) => {
+                predicted_history.add_update(rollback_tick, c.clone());
+                match predicted_component {
+                    Some(mut predicted_component) => {
+                        *predicted_component = c;
+                    }
+                    None => {
+                        debug!(
+                            ?predicted_entity,
+                            ?kind,
+                            "Component existed at time of rollback, inserting it"
+                        );
+                        commands.entity(predicted_entity).insert(c);
+                    }
+                }
+            }
+        }
+    }
+}
+
 pub(crate) fn run_rollback(world: &mut World) {
     let tick_manager = world.get_resource::<TickManager>().unwrap();
     let rollback = world.get_resource::<Rollback>().unwrap();
@@ -508,6 +712,15 @@ pub(crate) fn run_rollback(world: &mut World) {
         "Rollback between {:?} and {:?}",
         current_rollback_tick, current_tick
     );
+    let mismatched_components = std::mem::take(rollback.mismatched_components.write().deref_mut());
+
+    // notify the user before the resimulation starts
+    world.send_event(RollbackEvent {
+        rollback_tick: current_rollback_tick - 1,
+        current_tick,
+        num_resimulated_ticks: num_rollback_ticks as u16,
+        mismatched_components,
+    });
 
     // run the physics fixed update schedule (which should contain ALL predicted/rollback components)
     for i in 0..num_rollback_ticks {
@@ -521,10 +734,25 @@ pub(crate) fn run_rollback(world: &mut World) {
     let mut metrics = world.get_resource_mut::<PredictionMetrics>().unwrap();
     metrics.rollbacks += 1;
     metrics.rollback_ticks += num_rollback_ticks as u32;
+    if let Some(mut connection) = world.get_resource_mut::<ConnectionManager>() {
+        connection
+            .session_stats
+            .record_rollback(num_rollback_ticks as u32);
+    }
 
     // revert the state of Rollback for the next frame
     let rollback = world.get_resource_mut::<Rollback>().unwrap();
     rollback.set_non_rollback();
+
+    run_network_hooks::<PostRollback>(world, ());
+}
+
+/// Notify the user of the predicted entities that were snapped to their confirmed state
+pub(crate) fn send_rollback_window_exceeded_events(
+    rollback: Res<Rollback>,
+    mut events: EventWriter<RollbackWindowExceededEvent>,
+) {
+    events.send_batch(std::mem::take(rollback.window_exceeded.write().deref_mut()));
 }
 
 pub(crate) fn increment_rollback_tick(rollback: Res<Rollback>) {
@@ -542,7 +770,7 @@ pub(super) mod test_utils {
     use std::time::Duration;
 
     /// Helper function to simulate that we received a server message
-    pub(super) fn received_confirmed_update(
+    pub(crate) fn received_confirmed_update(
         stepper: &mut BevyStepper,
         confirmed: Entity,
         tick: Tick,
@@ -729,6 +957,8 @@ mod integration_tests {
     use bevy::prelude::*;
 
     use crate::prelude::client::*;
+    use crate::prelude::{AppNetworkHookExt, PostRollback};
+    use crate::protocol::component::ComponentKind;
 
     use crate::tests::protocol::*;
     use crate::tests::stepper::{BevyStepper, Step};
@@ -774,6 +1004,128 @@ mod integration_tests {
         (stepper, confirmed, predicted)
     }
 
+    fn rollback_events(stepper: &mut BevyStepper) -> Vec<RollbackEvent> {
+        stepper
+            .client_app
+            .world_mut()
+            .resource_mut::<Events<RollbackEvent>>()
+            .drain()
+            .collect()
+    }
+
+    /// Test that a RollbackEvent listing the mismatched components is emitted when we rollback
+    #[test]
+    fn test_rollback_event() {
+        let (mut stepper, confirmed, _) = setup();
+        stepper
+            .client_app
+            .world_mut()
+            .entity_mut(confirmed)
+            .insert(Component1(0.0));
+        stepper.frame_step();
+        stepper.frame_step();
+        assert!(rollback_events(&mut stepper).is_empty());
+
+        // the confirmed value does not match what we predicted
+        let tick = stepper.client_tick();
+        stepper
+            .client_app
+            .world_mut()
+            .get_mut::<Component1>(confirmed)
+            .unwrap()
+            .0 = -10.0;
+        received_confirmed_update(&mut stepper, confirmed, tick - 1);
+        stepper.frame_step();
+
+        let events = rollback_events(&mut stepper);
+        assert_eq!(events.len(), 1);
+        let event = &events[0];
+        assert_eq!(event.rollback_tick, tick - 1);
+        assert_eq!(
+            event.num_resimulated_ticks as i16,
+            event.current_tick - event.rollback_tick
+        );
+        assert_eq!(
+            event.mismatched_components,
+            vec![ComponentKind::of::<Component1>()]
+        );
+
+        // no rollback if the confirmed value matches the prediction
+        stepper.frame_step();
+        assert!(rollback_events(&mut stepper).is_empty());
+    }
+
+    /// Number of ticks resimulated during the rollbacks, and value of that counter each time the hook ran
+    #[derive(Resource, Default)]
+    struct RollbackHookCalls {
+        resimulated_ticks: usize,
+        calls: Vec<usize>,
+    }
+
+    fn count_resimulated_ticks(rollback: Res<Rollback>, mut calls: ResMut<RollbackHookCalls>) {
+        if rollback.is_rollback() {
+            calls.resimulated_ticks += 1;
+        }
+    }
+
+    fn post_rollback_hook(rollback: Res<Rollback>, mut calls: ResMut<RollbackHookCalls>) {
+        assert!(!rollback.is_rollback());
+        let resimulated_ticks = calls.resimulated_ticks;
+        calls.calls.push(resimulated_ticks);
+    }
+
+    /// The PostRollback hook runs once per rollback, after all the ticks were resimulated
+    #[test]
+    fn test_post_rollback_hook() {
+        let (mut stepper, confirmed, _) = setup();
+        stepper
+            .client_app
+            .init_resource::<RollbackHookCalls>()
+            .add_systems(FixedUpdate, count_resimulated_ticks)
+            .add_network_hook(PostRollback, post_rollback_hook);
+        stepper
+            .client_app
+            .world_mut()
+            .entity_mut(confirmed)
+            .insert(Component1(0.0));
+        stepper.frame_step();
+        stepper.frame_step();
+        stepper.frame_step();
+        assert!(stepper
+            .client_app
+            .world()
+            .resource::<RollbackHookCalls>()
+            .calls
+            .is_empty());
+
+        // rollback 2 ticks
+        let tick = stepper.client_tick();
+        stepper
+            .client_app
+            .world_mut()
+            .get_mut::<Component1>(confirmed)
+            .unwrap()
+            .0 = -10.0;
+        received_confirmed_update(&mut stepper, confirmed, tick - 2);
+        stepper.frame_step();
+        let events = rollback_events(&mut stepper);
+        assert_eq!(events.len(), 1);
+        let calls = &stepper.client_app.world().resource::<RollbackHookCalls>();
+        assert_eq!(calls.calls, vec![events[0].num_resimulated_ticks as usize]);
+        assert!(calls.resimulated_ticks > 1);
+
+        stepper.frame_step();
+        assert_eq!(
+            stepper
+                .client_app
+                .world()
+                .resource::<RollbackHookCalls>()
+                .calls
+                .len(),
+            1
+        );
+    }
+
     /// Test that:
     /// - we remove a component from the predicted entity
     /// - rolling back before the remove should re-add it
@@ -946,3 +1298,160 @@ mod integration_tests {
             .0 = 4.0;
     }
 }
+
+/// Tests for components that get inserted/removed by the client's own simulation (for example by input handling)
+#[cfg(test)]
+mod structural_tests {
+    use super::test_utils::*;
+
+    use bevy::prelude::*;
+
+    use crate::client::prediction::diagnostics::PredictionMetrics;
+    use crate::prelude::client::*;
+    use crate::prelude::{AppComponentExt, Tick, TickManager};
+    use crate::tests::protocol::*;
+    use crate::tests::stepper::{BevyStepper, Step};
+
+    /// Marker that is inserted while the player is dashing
+    #[derive(Component, Clone, Debug, PartialEq)]
+    struct Dashing;
+
+    /// Distance travelled while dashing
+    #[derive(Component, Clone, Debug, PartialEq)]
+    struct Distance(u32);
+
+    /// Ticks (relative to the start of the test) at which the dash starts and stops
+    #[derive(Resource)]
+    struct DashSchedule {
+        start: Tick,
+        insert_at: Option<i16>,
+        remove_at: Option<i16>,
+    }
+
+    fn dash(
+        mut commands: Commands,
+        tick_manager: Res<TickManager>,
+        rollback: Res<Rollback>,
+        schedule: Res<DashSchedule>,
+        mut query: Query<(Entity, Option<&Dashing>, &mut Distance), With<Predicted>>,
+    ) {
+        let offset = tick_manager.tick_or_rollback_tick(rollback.as_ref()) - schedule.start;
+        for (entity, dashing, mut distance) in query.iter_mut() {
+            if dashing.is_some() {
+                distance.0 += 1;
+            }
+            // we cannot start a new dash while we are already dashing
+            if schedule.insert_at == Some(offset) && dashing.is_none() {
+                commands.entity(entity).insert(Dashing);
+            }
+            if schedule.remove_at == Some(offset) {
+                commands.entity(entity).remove::<Dashing>();
+            }
+        }
+    }
+
+    /// Run the dash scenario for 10 ticks and return the final state of the predicted entity.
+    /// If `rollback` is true, a rollback of 6 ticks is triggered on the last frame.
+    fn run(
+        insert_at: Option<i16>,
+        remove_at: Option<i16>,
+        rollback: bool,
+    ) -> (Option<Dashing>, Distance) {
+        let mut stepper = BevyStepper::default();
+        stepper.client_app.add_rollback::<Dashing>();
+        stepper.client_app.add_rollback::<Distance>();
+        stepper.client_app.add_systems(FixedUpdate, dash);
+
+        let confirmed = stepper
+            .client_app
+            .world_mut()
+            .spawn((Confirmed::default(), Component1(0.0)))
+            .id();
+        let predicted = stepper
+            .client_app
+            .world_mut()
+            .spawn((
+                Predicted {
+                    confirmed_entity: Some(confirmed),
+                },
+                Distance(0),
+            ))
+            .id();
+        stepper
+            .client_app
+            .world_mut()
+            .entity_mut(confirmed)
+            .get_mut::<Confirmed>()
+            .unwrap()
+            .predicted = Some(predicted);
+        let start = stepper.client_tick();
+        stepper.client_app.insert_resource(DashSchedule {
+            start,
+            insert_at,
+            remove_at,
+        });
+        for _ in 0..10 {
+            stepper.frame_step();
+        }
+
+        let rollbacks = stepper
+            .client_app
+            .world()
+            .resource::<PredictionMetrics>()
+            .rollbacks;
+        if rollback {
+            // the confirmed state doesn't match the predicted history: roll back to `tick - 6`
+            let tick = stepper.client_tick();
+            stepper
+                .client_app
+                .world_mut()
+                .get_mut::<Component1>(confirmed)
+                .unwrap()
+                .0 = 1.0;
+            received_confirmed_update(&mut stepper, confirmed, tick - 6);
+        }
+        stepper.frame_step();
+        if rollback {
+            assert_eq!(
+                stepper
+                    .client_app
+                    .world()
+                    .resource::<PredictionMetrics>()
+                    .rollbacks,
+                rollbacks + 1
+            );
+        }
+
+        let world = stepper.client_app.world();
+        (
+            world.get::<Dashing>(predicted).cloned(),
+            world.get::<Distance>(predicted).unwrap().clone(),
+        )
+    }
+
+    /// The component is inserted after the rollback tick: it must be absent when the rollback starts,
+    /// and get re-inserted during the re-simulation
+    #[test]
+    fn test_insert_during_rollback_window() {
+        let expected = run(Some(7), None, false);
+        assert_eq!(expected, (Some(Dashing), Distance(4)));
+        assert_eq!(run(Some(7), None, true), expected);
+    }
+
+    /// The component is removed after the rollback tick: it must be present when the rollback starts,
+    /// and get removed again during the re-simulation
+    #[test]
+    fn test_remove_during_rollback_window() {
+        let expected = run(Some(1), Some(7), false);
+        assert_eq!(expected, (None, Distance(6)));
+        assert_eq!(run(Some(1), Some(7), true), expected);
+    }
+
+    /// The component is inserted and removed after the rollback tick
+    #[test]
+    fn test_insert_and_remove_during_rollback_window() {
+        let expected = run(Some(6), Some(8), false);
+        assert_eq!(expected, (None, Distance(2)));
+        assert_eq!(run(Some(6), Some(8), true), expected);
+    }
+}
@@ -5,14 +5,23 @@ use bevy::utils::Duration;
 use crate::client::connection::ConnectionManager;
 use crate::shared::replication::plugin::receive::ReplicationReceivePlugin;
 use crate::shared::replication::plugin::send::ReplicationSendPlugin;
-use crate::shared::sets::{ClientMarker, InternalReplicationSet};
+use crate::shared::sets::{ClientMarker, InternalMainSet, InternalReplicationSet};
 
 pub(crate) mod receive {
     use super::*;
+    use crate::client::error::ConnectError;
+    use crate::client::events::{MessageEvent, ReplicationLimitExceededEvent};
+    use crate::client::networking::NetworkingState;
+    use crate::connection::client::{ClientConnection, DisconnectReason};
     use crate::prelude::{
         client::{is_connected, is_synced},
         is_host_server,
     };
+    use crate::client::prediction::plugin::PredictionSet;
+    use crate::shared::hooks::{JoinSnapshotMessage, PostJoinSnapshot, RunNetworkHooks};
+    use crate::shared::replication::lifetime::{
+        expire_predicted_entities, expire_received_entities,
+    };
     #[derive(Default)]
     pub struct ClientReplicationReceivePlugin {
         pub tick_interval: Duration,
@@ -38,6 +47,77 @@ pub(crate) mod receive {
                         .and_then(not(is_host_server)),
                 ),
             );
+
+            // SYSTEMS
+            app.add_systems(
+                PreUpdate,
+                (
+                    handle_replication_limits.after(InternalMainSet::<ClientMarker>::Receive),
+                    handle_join_snapshot.after(InternalMainSet::<ClientMarker>::EmitEvents),
+                )
+                    .run_if(is_connected.and_then(not(is_host_server))),
+            );
+            app.add_systems(
+                PreUpdate,
+                expire_received_entities
+                    .after(InternalMainSet::<ClientMarker>::Receive)
+                    .run_if(
+                        is_connected
+                            .and_then(is_synced)
+                            .and_then(not(is_host_server)),
+                    ),
+            );
+            app.add_systems(
+                FixedPostUpdate,
+                expire_predicted_entities
+                    .before(PredictionSet::EntityDespawn)
+                    .run_if(
+                        is_connected
+                            .and_then(is_synced)
+                            .and_then(not(is_host_server)),
+                    ),
+            );
+        }
+    }
+
+    /// Run the [`PostJoinSnapshot`] hooks once the entities that existed on the server when we connected
+    /// have been spawned
+    pub(crate) fn handle_join_snapshot(
+        mut commands: Commands,
+        mut connection: ResMut<ConnectionManager>,
+        mut messages: EventReader<MessageEvent<JoinSnapshotMessage>>,
+    ) {
+        for message in messages.read() {
+            connection
+                .replication_receiver
+                .expect_join_snapshot(message.message.groups.clone());
+        }
+        if connection.replication_receiver.take_applied_join_snapshot() {
+            debug!("The join snapshot has been applied");
+            commands.add(RunNetworkHooks::<PostJoinSnapshot>(()));
+        }
+    }
+
+    /// Emit a [`ReplicationLimitExceededEvent`] for each replication limit that was exceeded during the frame,
+    /// and disconnect from the server if [`ReplicationLimits::disconnect`](crate::shared::replication::limits::ReplicationLimits::disconnect)
+    /// is enabled
+    pub(crate) fn handle_replication_limits(
+        mut connection: ResMut<ConnectionManager>,
+        mut netclient: ResMut<ClientConnection>,
+        mut next_state: ResMut<NextState<NetworkingState>>,
+        mut events: EventWriter<ReplicationLimitExceededEvent>,
+    ) {
+        let limiter = &mut connection.replication_receiver.limiter;
+        let disconnect = limiter.limits().disconnect;
+        for (limit, rejected) in limiter.drain_exceeded() {
+            events.send(ReplicationLimitExceededEvent { limit, rejected });
+            if disconnect && netclient.disconnect_reason.is_none() {
+                error!(?limit, "The server exceeded a replication limit, disconnecting");
+                netclient.disconnect_reason = Some(DisconnectReason::Connect(
+                    ConnectError::ReplicationLimitExceeded(limit),
+                ));
+                next_state.set(NetworkingState::Disconnected);
+            }
         }
     }
 }
@@ -57,6 +137,7 @@ pub(crate) mod send {
     };
     use crate::protocol::component::ComponentKind;
 
+    use crate::shared::replication::classes::ReplicationClass;
     use crate::shared::replication::components::{Replicating, ReplicationGroupId};
 
     use crate::shared::replication::archetypes::{get_erased_component, ReplicatedArchetypes};
@@ -249,6 +330,7 @@ pub(crate) mod send {
                     g.group_id(Some(entity.id()))
                 });
                 let priority = group.map_or(1.0, |g| g.priority());
+                let class = group.map_or(ReplicationClass::default(), |g| g.class());
                 let target_entity = entity_ref.get::<TargetEntity>();
                 // SAFETY: we know that the entity has the ReplicationTarget component
                 // because the archetype is in replicated_archetypes
@@ -275,6 +357,7 @@ pub(crate) mod send {
                         entity.id(),
                         group_id,
                         priority,
+                        class,
                         target_entity,
                         &mut sender,
                     );
@@ -334,6 +417,7 @@ pub(crate) mod send {
         entity: Entity,
         group_id: ReplicationGroupId,
         priority: f32,
+        class: ReplicationClass,
         target_entity: Option<&TargetEntity>,
         sender: &mut ConnectionManager,
     ) {
@@ -347,10 +431,11 @@ pub(crate) mod send {
                 .replication_sender
                 .prepare_entity_spawn(entity, group_id);
         }
-        // also set the priority for the group when we spawn it
+        // also set the priority and the class for the group when we spawn it
         sender
             .replication_sender
             .update_base_priority(group_id, priority);
+        sender.replication_sender.update_class(group_id, class);
     }
 
     /// Send entity despawn if:
@@ -438,6 +523,13 @@ pub(crate) mod send {
                     component_registry.erased_serialize(component_data, writer, component_kind)?;
                 };
                 let raw_data = writer.split();
+                sender.serialized_sizes.record_component(
+                    component_kind,
+                    component_registry,
+                    raw_data.len(),
+                    entity,
+                    &mut sender.warnings,
+                );
                 sender.replication_sender.prepare_component_insert(
                     entity,
                     group_id,
@@ -488,6 +580,13 @@ pub(crate) mod send {
                             component_kind,
                         )?;
                         let raw_data = writer.split();
+                        sender.serialized_sizes.record_component(
+                            component_kind,
+                            component_registry,
+                            raw_data.len(),
+                            entity,
+                            &mut sender.warnings,
+                        );
                         sender
                             .replication_sender
                             .prepare_component_update(entity, group_id, raw_data);
@@ -1,13 +1,18 @@
 /*! Handles syncing the time between the client and the server
 */
-use bevy::prelude::{Reflect, SystemSet};
+use bevy::prelude::{EventWriter, Real, Reflect, Res, ResMut, Resource, SystemSet, Time, Virtual};
+use bevy::time::Fixed;
 use bevy::utils::Duration;
 use chrono::Duration as ChronoDuration;
 use tracing::{debug, trace};
 
+use crate::client::config::ClientConfig;
+use crate::client::connection::ConnectionManager;
+use crate::client::events::TickStallEvent;
 use crate::client::interpolation::plugin::InterpolationDelay;
 use crate::packet::packet::PacketId;
-use crate::prelude::client::PredictionConfig;
+use crate::prelude::client::{InputDelayConfig, PredictionConfig};
+use crate::shared::input::jitter::InputDelayAdvice;
 use crate::shared::ping::manager::PingManager;
 use crate::shared::tick_manager::TickManager;
 use crate::shared::tick_manager::{Tick, TickEvent};
@@ -45,6 +50,26 @@ pub struct SyncConfig {
     // TODO: instead of constant speedup_factor, the speedup should be linear w.r.t the offset
     /// By how much should we speed up the simulation to make ticks stay in sync with server?
     pub speedup_factor: f32,
+    /// What to do if the server runs with a different tick duration than the one in the client's
+    /// [`SharedConfig`](crate::prelude::SharedConfig).
+    ///
+    /// If true, the client adopts the server's tick duration before the sync completes, and rescales
+    /// the prediction settings that are expressed in ticks so that they keep the same duration.
+    /// If false, the connection fails with [`ConnectError::TickRateMismatch`](crate::client::error::ConnectError).
+    ///
+    /// This only applies when connecting: the changes made by the server at runtime with
+    /// [`ServerCommands::set_tick_duration`](crate::prelude::server::ServerCommands::set_tick_duration)
+    /// are always adopted.
+    pub accept_server_tick_rate: bool,
+    /// Maximum number of ticks simulated in a single frame to catch up with the time.
+    ///
+    /// When a frame takes a long time (for example because the main thread stalled), the `FixedUpdate` schedule
+    /// runs once for every tick that should have happened during the frame. With a maximum, the ticks beyond it are
+    /// not simulated: a [`TickStallEvent`](crate::client::events::TickStallEvent) is emitted and the client tick
+    /// snaps forward to the tick it should be at, like when the client is too far from its sync objective.
+    ///
+    /// If `None`, every tick is simulated (up to bevy's [`Time<Virtual>`](bevy::time::Virtual) maximum delta).
+    pub max_catch_up_ticks: Option<u16>,
 
     // Integration
     pub server_time_estimate_smoothing: f32,
@@ -59,6 +84,8 @@ impl Default for SyncConfig {
             error_margin: 0.5,
             max_error_margin: 5.0,
             speedup_factor: 1.05,
+            accept_server_tick_rate: false,
+            max_catch_up_ticks: None,
             // server_time_estimate_smoothing: 0.0,
             server_time_estimate_smoothing: 0.2,
         }
@@ -70,6 +97,105 @@ impl SyncConfig {
         self.speedup_factor = speedup_factor;
         self
     }
+
+    pub fn accept_server_tick_rate(mut self, accept_server_tick_rate: bool) -> Self {
+        self.accept_server_tick_rate = accept_server_tick_rate;
+        self
+    }
+
+    pub fn max_catch_up_ticks(mut self, max_catch_up_ticks: u16) -> Self {
+        self.max_catch_up_ticks = Some(max_catch_up_ticks);
+        self
+    }
+}
+
+/// Number of ticks simulated during the current frame.
+///
+/// When a frame lasts longer than a tick, several ticks are simulated in the same frame to catch up.
+/// The ticks after the first one are catch-up ticks: the inputs sampled during them come from the same
+/// frame (and the same key state) as the inputs of the first tick.
+#[derive(Resource, Debug, Default)]
+pub struct TickDebt {
+    ticks_this_frame: u16,
+}
+
+impl TickDebt {
+    /// Number of ticks simulated so far during the current frame (the rollback ticks are not counted)
+    pub fn ticks_this_frame(&self) -> u16 {
+        self.ticks_this_frame
+    }
+
+    /// True if the current tick is not the first tick simulated during the current frame
+    pub fn is_catch_up_tick(&self) -> bool {
+        self.ticks_this_frame > 1
+    }
+}
+
+/// Limit the time that the fixed-update loop can simulate during this frame to [`SyncConfig::max_catch_up_ticks`]
+///
+/// Runs in `First`, before bevy updates the virtual time.
+pub(crate) fn limit_catch_up_ticks(
+    config: Res<ClientConfig>,
+    fixed_time: Res<Time<Fixed>>,
+    mut virtual_time: ResMut<Time<Virtual>>,
+    mut tick_debt: ResMut<TickDebt>,
+) {
+    tick_debt.ticks_this_frame = 0;
+    let Some(max_ticks) = config.sync.max_catch_up_ticks else {
+        return;
+    };
+    let speed = virtual_time.relative_speed_f64();
+    if max_ticks == 0 || speed <= 0.0 || virtual_time.is_paused() {
+        return;
+    }
+    // the time left in the fixed-update accumulator counts towards the budget
+    let budget = fixed_time.timestep() * max_ticks as u32;
+    let max_delta = budget.saturating_sub(fixed_time.overstep()).div_f64(speed);
+    if max_delta > Duration::ZERO {
+        virtual_time.set_max_delta(max_delta);
+    }
+}
+
+/// Detect the frames where some ticks were not simulated because of [`SyncConfig::max_catch_up_ticks`],
+/// and snap the client tick forward
+///
+/// Runs in `First`, after bevy updates the virtual time.
+pub(crate) fn detect_tick_stall(
+    config: Res<ClientConfig>,
+    real_time: Res<Time<Real>>,
+    virtual_time: Res<Time<Virtual>>,
+    connection: Option<ResMut<ConnectionManager>>,
+    mut events: EventWriter<TickStallEvent>,
+) {
+    if config.sync.max_catch_up_ticks.is_none() {
+        return;
+    }
+    let skipped_time = real_time
+        .delta()
+        .saturating_sub(virtual_time.max_delta())
+        .mul_f64(virtual_time.effective_speed_f64());
+    let skipped_ticks =
+        (skipped_time.as_nanos() / config.shared.tick.tick_duration.as_nanos()) as u16;
+    if skipped_ticks == 0 {
+        return;
+    }
+    debug!(
+        frame_duration = ?real_time.delta(),
+        ?skipped_ticks,
+        "The simulation stalled, skipping the ticks that were not simulated"
+    );
+    events.send(TickStallEvent {
+        frame_duration: real_time.delta(),
+        skipped_ticks,
+    });
+    if let Some(mut connection) = connection {
+        connection.sync_manager.resync_requested = true;
+    }
+}
+
+/// Count the ticks simulated during the frame
+pub(crate) fn count_frame_ticks(mut tick_debt: ResMut<TickDebt>) {
+    tick_debt.ticks_this_frame += 1;
 }
 
 #[derive(Default)]
@@ -85,14 +211,28 @@ impl SentPacketStore {
     }
 }
 
+/// Number of ticks by which the latency must go down before the automatic input delay decreases
+const AUTOMATIC_INPUT_DELAY_HYSTERESIS: f32 = 0.5;
+
 /// In charge of syncing the client's tick/time with the server's tick/time
 /// right after the connection is established
 #[derive(Debug)]
 pub struct SyncManager {
     config: SyncConfig,
     prediction_config: PredictionConfig,
+    /// Input delay set at runtime, which replaces the one computed from the [`PredictionConfig`]
+    pub(crate) input_delay_override: Option<u16>,
+    /// Input delay computed from the RTT with [`InputDelayConfig::Automatic`]
+    automatic_input_delay: u16,
+    /// Ticks added to the automatic input delay on the advice of the server
+    advised_input_delay_offset: i16,
     /// whether the handshake is finalized
     pub(crate) synced: bool,
+    /// Snap the client tick to the sync objective during the next update, because some ticks were not simulated
+    pub(crate) resync_requested: bool,
+    /// Tick duration of the server, once it has been received and accepted.
+    /// The handshake cannot be finalized before that.
+    pub(crate) server_tick_duration: Option<Duration>,
 
     // time
     server_time_estimate: WrappedTime,
@@ -119,7 +259,12 @@ impl SyncManager {
         Self {
             config,
             prediction_config,
+            input_delay_override: None,
+            automatic_input_delay: 0,
+            advised_input_delay_offset: 0,
             synced: false,
+            resync_requested: false,
+            server_tick_duration: None,
             // time
             server_time_estimate: WrappedTime::default(),
             interpolation_time: WrappedTime::default(),
@@ -149,9 +294,13 @@ impl SyncManager {
         self.duration_since_latest_received_server_tick += time_manager.delta();
         self.server_time_estimate += time_manager.delta();
         self.interpolation_time += time_manager.delta().mul_f32(self.interpolation_speed_ratio);
+        self.update_automatic_input_delay(ping_manager.rtt(), tick_manager.config.tick_duration);
 
         // check if we are ready to finalize the handshake
-        if !self.synced && ping_manager.sync_stats.len() >= self.config.handshake_pings as usize {
+        if !self.synced
+            && self.server_tick_duration.is_some()
+            && ping_manager.sync_stats.len() >= self.config.handshake_pings as usize
+        {
             self.synced = true;
             self.interpolation_time = self.interpolation_objective(
                 interpolation_delay,
@@ -175,6 +324,105 @@ impl SyncManager {
         self.synced
     }
 
+    /// Update the prediction settings, after they were rescaled to a new tick duration
+    pub(crate) fn set_prediction_config(&mut self, prediction_config: PredictionConfig) {
+        self.prediction_config = prediction_config;
+        // the automatic input delay is expressed in ticks of the previous duration
+        self.automatic_input_delay = 0;
+        self.advised_input_delay_offset = 0;
+    }
+
+    /// The tick duration changed from `from` to `to`: recompute the times that were derived from the ticks,
+    /// and snap the client tick to the sync objective during the next update.
+    ///
+    /// The interpolation time is converted so that it stays at the same tick; it then catches up with
+    /// its objective as usual.
+    pub(crate) fn change_tick_duration(&mut self, from: Duration, to: Duration, rtt: Duration) {
+        if self.latest_received_server_tick.is_some() {
+            self.server_time_estimate = WrappedTime::default();
+            self.update_server_time_estimate(to, rtt);
+        }
+        let tick = self.interpolation_time.to_tick(from);
+        let overstep = self.interpolation_time.tick_overstep(from);
+        let generation = self.interpolation_time.tick_generation(from, tick);
+        self.interpolation_time =
+            WrappedTime::from_tick(tick, generation, to) + to.mul_f32(overstep);
+        self.resync_requested = true;
+    }
+
+    pub(crate) fn set_input_delay_override(&mut self, input_delay_ticks: Option<u16>) {
+        self.input_delay_override = input_delay_ticks;
+    }
+
+    /// Number of ticks of input delay: the value set at runtime if any, otherwise the one computed
+    /// from the RTT with the [`PredictionConfig`]
+    pub(crate) fn input_delay_ticks(&self, rtt: Duration, tick_duration: Duration) -> u16 {
+        self.input_delay_override
+            .unwrap_or_else(|| match self.prediction_config.input_delay {
+                InputDelayConfig::Fixed => {
+                    self.prediction_config.input_delay_ticks(rtt, tick_duration)
+                }
+                InputDelayConfig::Automatic { max_ticks, .. } => {
+                    self.advised_automatic_input_delay(max_ticks) as u16
+                }
+            })
+    }
+
+    /// Automatic input delay, with the changes advised by the server
+    fn advised_automatic_input_delay(&self, max_ticks: u16) -> i16 {
+        (self.automatic_input_delay as i16 + self.advised_input_delay_offset)
+            .clamp(0, max_ticks as i16)
+    }
+
+    /// Apply a change of input delay advised by the server.
+    ///
+    /// The advice is only followed with [`InputDelayConfig::Automatic`]: the change is kept on top of the
+    /// delay computed from the RTT, within `max_ticks`.
+    pub(crate) fn apply_input_delay_advice(&mut self, advice: InputDelayAdvice) {
+        let InputDelayConfig::Automatic { max_ticks, .. } = self.prediction_config.input_delay
+        else {
+            debug!(?advice, "Ignore the input delay advice of the server");
+            return;
+        };
+        let current = self.advised_automatic_input_delay(max_ticks);
+        let advised = match advice {
+            InputDelayAdvice::Increase => current + 1,
+            InputDelayAdvice::Decrease => current - 1,
+        }
+        .clamp(0, max_ticks as i16);
+        debug!(?advice, previous = ?current, new = ?advised, "Apply the input delay advice of the server");
+        self.advised_input_delay_offset = advised - self.automatic_input_delay as i16;
+    }
+
+    /// Update the input delay of [`InputDelayConfig::Automatic`] from the latest RTT estimate.
+    ///
+    /// The delay increases as soon as it doesn't cover `rtt + rtt_margin` anymore, but only decreases
+    /// (one tick at a time) once the latency went down by more than [`AUTOMATIC_INPUT_DELAY_HYSTERESIS`] ticks,
+    /// so that it doesn't oscillate when the RTT is close to a multiple of the tick duration.
+    pub(crate) fn update_automatic_input_delay(&mut self, rtt: Duration, tick_duration: Duration) {
+        let InputDelayConfig::Automatic {
+            max_ticks,
+            rtt_margin,
+        } = self.prediction_config.input_delay
+        else {
+            return;
+        };
+        let ticks = (rtt + rtt_margin).as_secs_f32() / tick_duration.as_secs_f32();
+        let current = self.automatic_input_delay as f32;
+        let delay = if ticks > current {
+            ticks.ceil() as u16
+        } else if ticks <= current - 1.0 - AUTOMATIC_INPUT_DELAY_HYSTERESIS {
+            self.automatic_input_delay - 1
+        } else {
+            self.automatic_input_delay
+        };
+        let delay = delay.min(max_ticks);
+        if delay != self.automatic_input_delay {
+            debug!(?rtt, previous = ?self.automatic_input_delay, new = ?delay, "Update the automatic input delay");
+            self.automatic_input_delay = delay;
+        }
+    }
+
     /// Compute the current client time; we will make sure that the client tick is ahead of the server tick
     /// Even if it is wrapped around.
     /// (i.e. if client tick is 1, and server tick is 65535, we act as if the client tick was 65537)
@@ -423,9 +671,7 @@ impl SyncManager {
         let current_prediction_time = self.current_prediction_time(tick_manager, time_manager);
 
         // client ideal time
-        let input_delay_ticks = self
-            .prediction_config
-            .input_delay_ticks(rtt, tick_manager.config.tick_duration);
+        let input_delay_ticks = self.input_delay_ticks(rtt, tick_manager.config.tick_duration);
         let client_ideal_time = self.client_ideal_time(
             rtt,
             tick_manager.config.tick_duration,
@@ -518,9 +764,7 @@ impl SyncManager {
         self.update_server_time_estimate(tick_duration, rtt);
 
         // Compute how many ticks the client must be compared to server
-        let input_delay_ticks = self
-            .prediction_config
-            .input_delay_ticks(rtt, tick_manager.config.tick_duration);
+        let input_delay_ticks = self.input_delay_ticks(rtt, tick_manager.config.tick_duration);
         let client_ideal_time =
             self.client_ideal_time(rtt, tick_duration, jitter, input_delay_ticks);
 
@@ -647,4 +891,82 @@ mod tests {
             &Component1(1.0)
         );
     }
+
+    /// The time of both apps is driven by the mock clock of the stepper, so the ticks
+    /// only depend on the simulated time
+    #[test]
+    fn test_sync_with_mock_clock() {
+        let mut stepper = BevyStepper::default();
+        assert!(stepper
+            .client_app
+            .world()
+            .resource::<client::ConnectionManager>()
+            .sync_manager
+            .is_synced());
+
+        // simulate 10 seconds
+        let start = stepper.clock.elapsed();
+        for _ in 0..1000 {
+            stepper.frame_step();
+        }
+        assert_eq!(stepper.clock.elapsed() - start, Duration::from_secs(10));
+        let expected_ticks = stepper.clock.expected_ticks(stepper.tick_duration);
+        let server_tick = stepper.server_tick().0 as i32;
+        assert!(
+            (server_tick - expected_ticks as i32).abs() <= 1,
+            "server tick: {server_tick}, expected: {expected_ticks}"
+        );
+        // the client stays ahead of the server
+        assert!(stepper.client_tick() >= stepper.server_tick());
+        assert!(stepper
+            .client_app
+            .world()
+            .resource::<client::ConnectionManager>()
+            .sync_manager
+            .is_synced());
+    }
+
+    #[test]
+    fn test_automatic_input_delay() {
+        let tick = Duration::from_millis(10);
+        let mut sync_manager = SyncManager::new(
+            SyncConfig::default(),
+            PredictionConfig::default().with_input_delay(InputDelayConfig::Automatic {
+                max_ticks: 8,
+                rtt_margin: Duration::from_millis(5),
+            }),
+        );
+        let input_delay =
+            |sync_manager: &SyncManager| sync_manager.input_delay_ticks(Duration::default(), tick);
+
+        // the delay covers the RTT and the margin
+        sync_manager.update_automatic_input_delay(Duration::from_millis(30), tick);
+        assert_eq!(input_delay(&sync_manager), 4);
+
+        // the jitter around the RTT doesn't change the delay
+        for rtt in [26, 34, 28, 33, 30] {
+            sync_manager.update_automatic_input_delay(Duration::from_millis(rtt), tick);
+            assert_eq!(input_delay(&sync_manager), 4);
+        }
+
+        // the delay increases as soon as it doesn't cover the RTT
+        sync_manager.update_automatic_input_delay(Duration::from_millis(36), tick);
+        assert_eq!(input_delay(&sync_manager), 5);
+
+        // it decreases one tick at a time once the RTT went down by more than the hysteresis
+        sync_manager.update_automatic_input_delay(Duration::from_millis(32), tick);
+        assert_eq!(input_delay(&sync_manager), 5);
+        sync_manager.update_automatic_input_delay(Duration::from_millis(0), tick);
+        assert_eq!(input_delay(&sync_manager), 4);
+        sync_manager.update_automatic_input_delay(Duration::from_millis(0), tick);
+        assert_eq!(input_delay(&sync_manager), 3);
+
+        // the delay is clamped
+        sync_manager.update_automatic_input_delay(Duration::from_millis(200), tick);
+        assert_eq!(input_delay(&sync_manager), 8);
+
+        // the runtime override takes precedence
+        sync_manager.set_input_delay_override(Some(1));
+        assert_eq!(input_delay(&sync_manager), 1);
+    }
 }
@@ -21,6 +21,7 @@ use crate::prelude::client::ClientTransport;
 #[cfg(all(feature = "steam", not(target_family = "wasm")))]
 use crate::prelude::LinkConditionerConfig;
 use crate::prelude::{generate_key, Key};
+use crate::shared::clock::NetworkClock;
 use crate::transport::config::SharedIoConfig;
 
 #[derive(Debug)]
@@ -54,6 +55,21 @@ pub trait NetClient: Send + Sync {
     /// Receive a packet from the server
     fn recv(&mut self) -> Option<RecvPayload>;
 
+    /// Receive a packet that was sent by an unconnected endpoint, along with the address of the sender.
+    ///
+    /// Only the netcode client shares its socket with unconnected endpoints.
+    fn recv_unconnected(&mut self) -> Option<(SocketAddr, RecvPayload)> {
+        None
+    }
+
+    /// Returns the server address of the new transport if the client switched to one of its
+    /// fallback transports since the last call.
+    ///
+    /// Only the netcode client supports fallback transports.
+    fn recv_transport_migration(&mut self) -> Option<SocketAddr> {
+        None
+    }
+
     /// Send a packet to the server
     fn send(&mut self, buf: &[u8]) -> Result<(), ConnectionError>;
 
@@ -91,6 +107,12 @@ pub struct ClientConnection {
 pub enum DisconnectReason {
     Transport(crate::transport::error::Error),
     Netcode(super::netcode::ClientState),
+    /// The server denied the connection request
+    Denied(crate::connection::server::DeniedReason),
+    /// The client aborted the connection
+    Connect(crate::client::error::ConnectError),
+    /// The server closed the connection, with the reason that it provided (for example "banned")
+    Kicked(Option<String>),
     #[cfg(all(feature = "steam", not(target_family = "wasm")))]
     Steam(steamworks::networking_types::NetConnectionEnd),
 }
@@ -133,7 +155,8 @@ impl Default for NetConfig {
 }
 
 impl NetConfig {
-    pub fn build_client(self) -> ClientConnection {
+    /// Build the connection, which reads the time from `clock`
+    pub fn build_client(self, clock: &NetworkClock) -> ClientConnection {
         match self {
             NetConfig::Netcode {
                 auth,
@@ -141,16 +164,18 @@ impl NetConfig {
                 io: io_config,
             } => {
                 let token = auth
-                    .get_token(config.client_timeout_secs, config.token_expire_secs)
+                    .get_token(config.client_timeout_secs, config.token_expire_secs, clock)
                     .expect("could not generate token");
                 let token_bytes = token.try_into_bytes().unwrap();
-                let netcode =
+                let mut netcode =
                     super::netcode::NetcodeClient::with_config(&token_bytes, config.build())
                         .expect("could not create netcode client");
+                netcode.set_clock(clock.clone());
                 let client = super::netcode::Client {
                     client: netcode,
                     io_config,
                     io: None,
+                    migration: super::netcode::migration::TransportMigration::new(&config),
                 };
                 ClientConnection {
                     client: NetClientDispatch::Netcode(client),
@@ -207,6 +232,14 @@ impl NetClient for ClientConnection {
         self.client.recv()
     }
 
+    fn recv_unconnected(&mut self) -> Option<(SocketAddr, RecvPayload)> {
+        self.client.recv_unconnected()
+    }
+
+    fn recv_transport_migration(&mut self) -> Option<SocketAddr> {
+        self.client.recv_transport_migration()
+    }
+
     fn send(&mut self, buf: &[u8]) -> Result<(), ConnectionError> {
         self.client.send(buf)
     }
@@ -277,10 +310,12 @@ impl Authentication {
         !matches!(self, Authentication::None)
     }
 
+    /// Returns the [`ConnectToken`], generating it with the timestamps of `clock` if needed
     pub fn get_token(
         self,
         client_timeout_secs: i32,
         token_expire_secs: i32,
+        clock: &NetworkClock,
     ) -> Option<ConnectToken> {
         match self {
             Authentication::Token(token) => Some(token),
@@ -292,6 +327,7 @@ impl Authentication {
             } => ConnectToken::build(server_addr, protocol_id, client_id, private_key)
                 .timeout_seconds(client_timeout_secs)
                 .expire_seconds(token_expire_secs)
+                .clock(clock.clone())
                 .generate()
                 .ok(),
             Authentication::None => {
@@ -303,6 +339,7 @@ impl Authentication {
                     generate_key(),
                 )
                 .timeout_seconds(client_timeout_secs)
+                .clock(clock.clone())
                 .generate()
                 .ok()
             }
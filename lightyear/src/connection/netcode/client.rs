@@ -8,7 +8,9 @@ use crate::connection::client::{
     ConnectionError, ConnectionState, DisconnectReason, IoConfig, NetClient,
 };
 use crate::connection::id;
+use crate::connection::server::DeniedReason;
 use crate::packet::packet_builder::RecvPayload;
+use crate::shared::clock::NetworkClock;
 use crate::transport::io::IoState;
 use crate::transport::{PacketReceiver, PacketSender, LOCAL_SOCKET};
 use crate::utils::pool::Pool;
@@ -17,11 +19,12 @@ use super::{
     bytes::Bytes,
     error::{Error, Result},
     packet::{
-        DisconnectPacket, KeepAlivePacket, Packet, PayloadPacket, RequestPacket, ResponsePacket,
+        DisconnectPacket, KeepAlivePacket, MigratePacket, Packet, PayloadPacket, RequestPacket,
+        ResponsePacket,
     },
     replay::ReplayProtection,
     token::{ChallengeToken, ConnectToken},
-    utils, ClientId, MAX_PACKET_SIZE, MAX_PKT_BUF_SIZE, PACKET_SEND_RATE_SEC,
+    ClientId, MAX_PACKET_SIZE, MAX_PKT_BUF_SIZE, PACKET_SEND_RATE_SEC, UNCONNECTED_PACKET_PREFIX,
 };
 
 type Callback<Ctx> = Box<dyn FnMut(ClientState, ClientState, &mut Ctx) + Send + Sync + 'static>;
@@ -185,6 +188,9 @@ pub struct NetcodeClient<Ctx = ()> {
     last_send_time: f64,
     last_receive_time: f64,
     server_addr_idx: usize,
+    /// Address of the server on the current transport path, if the session was migrated
+    /// to a different transport than the one from the connect token
+    migrated_server_addr: Option<SocketAddr>,
     sequence: u64,
     challenge_token_sequence: u64,
     challenge_token_data: [u8; ChallengeToken::SIZE],
@@ -192,8 +198,18 @@ pub struct NetcodeClient<Ctx = ()> {
     replay_protection: ReplayProtection,
     should_disconnect: bool,
     should_disconnect_state: ClientState,
+    /// Reason sent by the server the last time it denied the connection request
+    denied_reason: Option<DeniedReason>,
+    /// Set if the server closed the connection, with the reason that it provided (if any)
+    kick_reason: Option<Option<String>>,
     packet_queue: VecDeque<RecvPayload>,
+    /// Packets received from unconnected endpoints, with the [`UNCONNECTED_PACKET_PREFIX`] stripped
+    unconnected_packet_queue: VecDeque<(SocketAddr, RecvPayload)>,
     buffer_pool: Pool<Vec<u8>>,
+    /// Hash of the protocol, sent to the server in the connection request
+    protocol_hash: u64,
+    /// Clock used to check the timestamps of the packets
+    clock: NetworkClock,
     cfg: ClientConfig<Ctx>,
 }
 
@@ -220,6 +236,7 @@ impl<Ctx> NetcodeClient<Ctx> {
             last_send_time: f64::NEG_INFINITY,
             last_receive_time: f64::NEG_INFINITY,
             server_addr_idx: 0,
+            migrated_server_addr: None,
             sequence: 0,
             challenge_token_sequence: 0,
             challenge_token_data: [0u8; ChallengeToken::SIZE],
@@ -227,8 +244,13 @@ impl<Ctx> NetcodeClient<Ctx> {
             replay_protection: ReplayProtection::new(),
             should_disconnect: false,
             should_disconnect_state: ClientState::Disconnected,
+            denied_reason: None,
+            kick_reason: None,
             packet_queue: VecDeque::new(),
+            unconnected_packet_queue: VecDeque::new(),
             buffer_pool: Pool::new(10, || vec![0u8; MAX_PKT_BUF_SIZE]),
+            protocol_hash: 0,
+            clock: NetworkClock::default(),
             cfg,
         })
     }
@@ -291,7 +313,8 @@ impl<Ctx> NetcodeClient<Ctx> {
         | 1 << Packet::CHALLENGE
         | 1 << Packet::KEEP_ALIVE
         | 1 << Packet::PAYLOAD
-        | 1 << Packet::DISCONNECT;
+        | 1 << Packet::DISCONNECT
+        | 1 << Packet::MIGRATE;
     fn set_state(&mut self, state: ClientState) {
         debug!("client state changing from {:?} to {:?}", self.state, state);
         if let Some(ref mut cb) = self.cfg.on_state_change {
@@ -312,6 +335,7 @@ impl<Ctx> NetcodeClient<Ctx> {
         self.sequence = 0;
         self.start_time = 0.0;
         self.server_addr_idx = 0;
+        self.migrated_server_addr = None;
         self.set_state(new_state);
         self.reset_connection();
         debug!("client disconnected");
@@ -328,6 +352,7 @@ impl<Ctx> NetcodeClient<Ctx> {
                     self.token.expire_timestamp,
                     self.token.nonce,
                     self.token.private_data,
+                    self.protocol_hash,
                 )
             }
             ClientState::SendingChallengeResponse => {
@@ -352,6 +377,12 @@ impl<Ctx> NetcodeClient<Ctx> {
         Ok(())
     }
     fn send_packet(&mut self, packet: Packet, io: &mut Io) -> Result<()> {
+        let server_addr = self.server_addr();
+        self.send_packet_to(packet, io, server_addr)?;
+        self.last_send_time = self.time;
+        Ok(())
+    }
+    fn send_packet_to(&mut self, packet: Packet, io: &mut Io, addr: SocketAddr) -> Result<()> {
         let mut buf = [0u8; MAX_PKT_BUF_SIZE];
         let size = packet.write(
             &mut buf,
@@ -359,14 +390,92 @@ impl<Ctx> NetcodeClient<Ctx> {
             &self.token.client_to_server_key,
             self.token.protocol_id,
         )?;
-        io.send(&buf[..size], &self.server_addr())?;
-        self.last_send_time = self.time;
+        io.send(&buf[..size], &addr)?;
         self.sequence += 1;
         Ok(())
     }
 
+    /// Set the hash of the protocol that is sent in the connection request.
+    /// The server denies the connection if it does not match its own.
+    pub(crate) fn set_protocol_hash(&mut self, protocol_hash: u64) {
+        self.protocol_hash = protocol_hash;
+    }
+
+    /// Set the clock used to check the timestamps of the packets
+    pub(crate) fn set_clock(&mut self, clock: NetworkClock) {
+        self.clock = clock;
+    }
+
+    pub(crate) fn clock(&self) -> &NetworkClock {
+        &self.clock
+    }
+
     pub fn server_addr(&self) -> SocketAddr {
-        self.token.server_addresses[self.server_addr_idx]
+        self.migrated_server_addr
+            .unwrap_or(self.token.server_addresses[self.server_addr_idx])
+    }
+
+    /// Send a [`MigratePacket`] to the server over a different transport than the current one.
+    ///
+    /// With `commit: false` this only probes the path; with `commit: true` the server will send
+    /// all the following packets on that path.
+    pub(crate) fn send_migrate(
+        &mut self,
+        io: &mut Io,
+        server_addr: SocketAddr,
+        commit: bool,
+    ) -> Result<()> {
+        if self.state != ClientState::Connected {
+            return Ok(());
+        }
+        self.send_packet_to(MigratePacket::create(self.id, commit), io, server_addr)
+    }
+
+    /// Receive the packets of a transport that is not the current one.
+    ///
+    /// Only the replies to [`send_migrate`](Self::send_migrate) are processed: returns `Some(commit)`
+    /// if the server acknowledged a migrate packet. Any other packet is dropped; if the
+    /// server already moved the session to this path they will be resent by the reliable channels.
+    pub(crate) fn recv_migrate_acks(&mut self, io: &mut Io) -> Result<Option<bool>> {
+        let now = self.clock.unix_time().as_secs();
+        let mut ack = None;
+        while let Some((buf, addr)) = io.recv()? {
+            if buf.len() <= 1 || buf.starts_with(UNCONNECTED_PACKET_PREFIX) {
+                continue;
+            }
+            match Packet::read(
+                buf,
+                self.token.protocol_id,
+                now,
+                self.token.server_to_client_key,
+                Some(&mut self.replay_protection),
+                Self::ALLOWED_PACKETS,
+            ) {
+                Ok(Packet::Migrate(pkt)) if pkt.client_id == self.id => {
+                    trace!(?addr, commit = pkt.commit, "client received migrate ack");
+                    ack = Some(ack.unwrap_or(false) || pkt.commit);
+                }
+                Ok(packet) => {
+                    trace!(?addr, "client dropped {packet} received on a standby transport");
+                }
+                Err(e) => {
+                    debug!(?addr, "client ignored packet on a standby transport: {e}");
+                }
+            }
+        }
+        Ok(ack)
+    }
+
+    /// Switch the session to the server address of a new transport, once the server acknowledged the migration
+    pub(crate) fn complete_migration(&mut self, server_addr: SocketAddr) {
+        info!("client migrated to server address {server_addr}");
+        self.migrated_server_addr = Some(server_addr);
+        self.last_receive_time = self.time;
+    }
+
+    /// Number of seconds since the last packet received from the server on the current transport
+    pub(crate) fn time_since_last_receive(&self) -> f64 {
+        self.time - self.last_receive_time
     }
     fn process_packet(&mut self, addr: SocketAddr, packet: Packet) -> Result<()> {
         if addr != self.server_addr() {
@@ -384,6 +493,7 @@ impl<Ctx> NetcodeClient<Ctx> {
                 );
                 self.should_disconnect = true;
                 self.should_disconnect_state = ClientState::ConnectionDenied;
+                self.denied_reason = Some(pkt.reason);
             }
             (Packet::Challenge(pkt), ClientState::SendingConnectionRequest) => {
                 debug!("client received connection challenge packet from server");
@@ -423,10 +533,14 @@ impl<Ctx> NetcodeClient<Ctx> {
                 // TODO: control the size/memory of the packet queue?
                 self.packet_queue.push_back(buf);
             }
-            (Packet::Disconnect(_), ClientState::Connected) => {
-                debug!("client received disconnect packet from server");
+            (Packet::Migrate(_), ClientState::Connected) => {
+                trace!("client received migrate packet from server");
+            }
+            (Packet::Disconnect(pkt), ClientState::Connected) => {
+                debug!(reason = ?pkt.reason, "client received disconnect packet from server");
                 self.should_disconnect = true;
                 self.should_disconnect_state = ClientState::Disconnected;
+                self.kick_reason = Some(pkt.reason);
             }
             _ => return Ok(()),
         }
@@ -483,6 +597,13 @@ impl<Ctx> NetcodeClient<Ctx> {
             // Too small to be a packet
             return Ok(());
         }
+        if let Some(payload) = buf.strip_prefix(UNCONNECTED_PACKET_PREFIX.as_slice()) {
+            // not a netcode packet, surface it to the user
+            trace!("client received unconnected packet from {addr}");
+            self.unconnected_packet_queue
+                .push_back((addr, RecvPayload::copy_from_slice(payload)));
+            return Ok(());
+        }
         let packet = match Packet::read(
             buf,
             self.token.protocol_id,
@@ -506,7 +627,7 @@ impl<Ctx> NetcodeClient<Ctx> {
 
     fn recv_packets(&mut self, io: &mut Io) -> Result<()> {
         // number of seconds since unix epoch
-        let now = utils::now();
+        let now = self.clock.unix_time().as_secs();
         while let Some((buf, addr)) = io.recv()? {
             self.recv_packet(buf, now, addr)?;
         }
@@ -523,6 +644,8 @@ impl<Ctx> NetcodeClient<Ctx> {
     /// This function does not perform any IO, it only readies the client to send/receive packets on the next call to [`update`](NetcodeClient::update). <br>
     pub fn connect(&mut self) {
         self.reset_connection();
+        self.denied_reason = None;
+        self.kick_reason = None;
         self.set_state(ClientState::SendingConnectionRequest);
         info!(
             "client connecting to server {} [{}/{}]",
@@ -596,6 +719,14 @@ impl<Ctx> NetcodeClient<Ctx> {
         self.packet_queue.pop_front()
     }
 
+    /// Receives a packet that was sent by an unconnected endpoint (with the [`UNCONNECTED_PACKET_PREFIX`] stripped),
+    /// along with the address of the sender.
+    ///
+    /// These packets are never interpreted by the netcode protocol.
+    pub fn recv_unconnected(&mut self) -> Option<(SocketAddr, RecvPayload)> {
+        self.unconnected_packet_queue.pop_front()
+    }
+
     /// Sends a packet to the server.
     ///
     /// The provided buffer must be smaller than [`MAX_PACKET_SIZE`].
@@ -613,6 +744,7 @@ impl<Ctx> NetcodeClient<Ctx> {
     /// Disconnects the client from the server.
     ///
     /// The client will send a number of redundant disconnect packets to the server before transitioning to `Disconnected`.
+    /// The packets are sent on a best-effort basis: the client disconnects even if they could not be sent.
     pub fn disconnect(&mut self, io: &mut Io) -> Result<()> {
         debug!(
             "client sending {} disconnect packets to server",
@@ -620,13 +752,24 @@ impl<Ctx> NetcodeClient<Ctx> {
         );
         if io.state == IoState::Connected {
             for _ in 0..self.cfg.num_disconnect_packets {
-                self.send_packet(DisconnectPacket::create(), io)?;
+                // we do not use ? here because we want to continue even if the send fails
+                let _ = self
+                    .send_packet(DisconnectPacket::create(None), io)
+                    .inspect_err(|e| error!("client failed to send disconnect packet: {e}"));
             }
         }
         self.reset(ClientState::Disconnected);
         Ok(())
     }
 
+    /// Returns the reason sent by the server if it denied the last connection request
+    pub fn denied_reason(&self) -> Option<&DeniedReason> {
+        self.denied_reason.as_ref()
+    }
+    /// Returns `Some` if the server closed the last connection, with the reason that it provided (if any)
+    pub fn kick_reason(&self) -> Option<Option<&str>> {
+        self.kick_reason.as_ref().map(Option::as_deref)
+    }
     /// Gets the current state of the client.
     pub fn state(&self) -> ClientState {
         self.state
@@ -652,6 +795,7 @@ impl<Ctx> NetcodeClient<Ctx> {
 
 pub(crate) mod connection {
     use super::*;
+    use crate::connection::netcode::migration::TransportMigration;
     use core::result::Result;
 
     /// Client that can establish a connection to the Server
@@ -660,13 +804,15 @@ pub(crate) mod connection {
         pub client: NetcodeClient<Ctx>,
         pub io_config: IoConfig,
         pub io: Option<Io>,
+        pub(crate) migration: TransportMigration,
     }
 
     impl<Ctx: Send + Sync> NetClient for Client<Ctx> {
         fn connect(&mut self) -> Result<(), ConnectionError> {
             let io_config = self.io_config.clone();
-            let io = io_config.connect()?;
+            let io = io_config.connect_with_clock(&self.client.clock)?;
             self.io = Some(io);
+            self.migration.reset();
             self.client.connect();
             Ok(())
         }
@@ -681,6 +827,7 @@ pub(crate) mod connection {
             } else {
                 self.client.reset(ClientState::Disconnected);
             }
+            self.migration.reset();
             Ok(())
         }
 
@@ -691,7 +838,15 @@ pub(crate) mod connection {
                 }
                 ClientState::Connected => ConnectionState::Connected,
                 _ => ConnectionState::Disconnected {
-                    reason: Some(DisconnectReason::Netcode(self.client.state)),
+                    reason: Some(match (self.client.state, &self.client.denied_reason) {
+                        (ClientState::ConnectionDenied, Some(reason)) => {
+                            DisconnectReason::Denied(reason.clone())
+                        }
+                        (ClientState::Disconnected, _) if self.client.kick_reason.is_some() => {
+                            DisconnectReason::Kicked(self.client.kick_reason.clone().flatten())
+                        }
+                        _ => DisconnectReason::Netcode(self.client.state),
+                    }),
                 },
             }
         }
@@ -701,6 +856,9 @@ pub(crate) mod connection {
             self.client
                 .try_update(delta_ms, io)
                 .inspect_err(|e| error!("error updating netcode client: {:?}", e))?;
+            self.migration
+                .update(delta_ms, &mut self.client, &mut self.io)
+                .inspect_err(|e| error!("error updating the standby transport: {:?}", e))?;
             Ok(())
         }
 
@@ -708,6 +866,14 @@ pub(crate) mod connection {
             self.client.recv()
         }
 
+        fn recv_unconnected(&mut self) -> Option<(SocketAddr, RecvPayload)> {
+            self.client.recv_unconnected()
+        }
+
+        fn recv_transport_migration(&mut self) -> Option<SocketAddr> {
+            self.migration.recv_migration()
+        }
+
         fn send(&mut self, buf: &[u8]) -> Result<(), ConnectionError> {
             let io = self.io.as_mut().ok_or(ConnectionError::IoNotInitialized)?;
             self.client.send(buf, io)?;
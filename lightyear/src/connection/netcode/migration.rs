@@ -0,0 +1,197 @@
+//! Migration of a netcode session to a fallback transport.
+//!
+//! The client keeps a connection to the server open on a standby transport (the next entry of
+//! [`NetcodeConfig::fallback_transports`](crate::prelude::client::NetcodeConfig::fallback_transports))
+//! and periodically probes it with a [`MigratePacket`](super::packet::MigratePacket).
+//! If the current transport stops receiving packets while the standby transport still answers the probes,
+//! the client asks the server to move the session to the standby transport.
+//!
+//! Only the netcode path changes: the session keys, sequence numbers and all the channel state
+//! are kept, so the reliable messages that were lost on the failing transport are simply resent on the new one.
+//!
+//! The server must be reachable from both transports via the same netcode server (i.e. the same
+//! [`ServerTransport`](crate::prelude::server::ServerTransport)); a session cannot move between two
+//! different server [`NetConfig`](crate::prelude::server::NetConfig)s.
+use std::collections::VecDeque;
+use std::net::SocketAddr;
+
+use tracing::{debug, error};
+
+use crate::client::config::NetcodeConfig;
+use crate::client::io::{ClientIoEvent, Io};
+use crate::connection::client::IoConfig;
+use crate::transport::io::IoState;
+
+use super::client::NetcodeClient;
+use super::error::Result;
+
+/// A transport that the client can switch to if the current transport stops working
+#[derive(Clone)]
+pub struct FallbackTransport {
+    /// The io used to reach the server on this transport
+    pub io: IoConfig,
+    /// The address of the server on this transport.
+    ///
+    /// If `None`, the address of the server on the current transport is used.
+    pub server_addr: Option<SocketAddr>,
+}
+
+impl FallbackTransport {
+    pub fn new(io: IoConfig) -> Self {
+        Self {
+            io,
+            server_addr: None,
+        }
+    }
+
+    pub fn with_server_addr(mut self, server_addr: SocketAddr) -> Self {
+        self.server_addr = Some(server_addr);
+        self
+    }
+}
+
+struct Standby {
+    io: Io,
+    server_addr: SocketAddr,
+    last_probe_send_time: f64,
+    last_probe_ack_time: Option<f64>,
+    last_commit_send_time: Option<f64>,
+}
+
+/// Keeps the standby transport of a client and decides when to switch to it
+pub(crate) struct TransportMigration {
+    fallbacks: Vec<FallbackTransport>,
+    next_fallback: usize,
+    standby: Option<Standby>,
+    unhealthy_after: f64,
+    probe_interval: f64,
+    time: f64,
+    /// Server addresses of the transports that the client migrated to, waiting to be emitted as events
+    migrations: VecDeque<SocketAddr>,
+}
+
+impl TransportMigration {
+    pub(crate) fn new(config: &NetcodeConfig) -> Self {
+        Self {
+            fallbacks: config.fallback_transports.clone(),
+            next_fallback: 0,
+            standby: None,
+            unhealthy_after: config.fallback_unhealthy_after.as_secs_f64(),
+            probe_interval: config.fallback_probe_interval.as_secs_f64(),
+            time: 0.0,
+            migrations: VecDeque::new(),
+        }
+    }
+
+    /// Drop the standby transport and start again from the first fallback transport
+    pub(crate) fn reset(&mut self) {
+        if let Some(mut standby) = self.standby.take() {
+            let _ = standby
+                .io
+                .close()
+                .inspect_err(|e| error!("Error closing standby transport: {e:?}"));
+        }
+        self.next_fallback = 0;
+        self.time = 0.0;
+    }
+
+    pub(crate) fn recv_migration(&mut self) -> Option<SocketAddr> {
+        self.migrations.pop_front()
+    }
+
+    /// Open the next fallback transport as the standby transport
+    fn open_standby<Ctx>(&mut self, client: &NetcodeClient<Ctx>) {
+        while let Some(fallback) = self.fallbacks.get(self.next_fallback) {
+            self.next_fallback += 1;
+            match fallback.io.clone().connect_with_clock(client.clock()) {
+                Ok(io) => {
+                    let server_addr = fallback.server_addr.unwrap_or(client.server_addr());
+                    debug!(?server_addr, "opened standby transport");
+                    self.standby = Some(Standby {
+                        io,
+                        server_addr,
+                        // probe immediately
+                        last_probe_send_time: f64::NEG_INFINITY,
+                        last_probe_ack_time: None,
+                        last_commit_send_time: None,
+                    });
+                    return;
+                }
+                Err(e) => {
+                    error!("Could not open fallback transport: {e:?}");
+                }
+            }
+        }
+    }
+
+    /// Probe the standby transport, and switch to it if the current transport is unhealthy.
+    ///
+    /// On switch, `active_io` is replaced by the io of the standby transport.
+    pub(crate) fn update<Ctx>(
+        &mut self,
+        delta: f64,
+        client: &mut NetcodeClient<Ctx>,
+        active_io: &mut Option<Io>,
+    ) -> Result<()> {
+        self.time += delta;
+        if !client.is_connected() {
+            return Ok(());
+        }
+        if self.standby.is_none() {
+            self.open_standby(client);
+        }
+        let Some(standby) = self.standby.as_mut() else {
+            return Ok(());
+        };
+        // the io events of the current transport are handled by the networking systems,
+        // but we need to track the state of the standby transport ourselves
+        if let Some(receiver) = standby.io.context.event_receiver.as_mut() {
+            match receiver.try_recv() {
+                Ok(ClientIoEvent::Connected) => standby.io.state = IoState::Connected,
+                Ok(ClientIoEvent::Disconnected(e)) => {
+                    error!("Standby transport disconnected: {e}");
+                    // the next update will open the next fallback transport
+                    self.standby = None;
+                    return Ok(());
+                }
+                Err(_) => {}
+            }
+        }
+        match client.recv_migrate_acks(&mut standby.io)? {
+            Some(true) => {
+                let standby = self.standby.take().unwrap();
+                client.complete_migration(standby.server_addr);
+                if let Some(mut previous) = active_io.replace(standby.io) {
+                    let _ = previous
+                        .close()
+                        .inspect_err(|e| error!("Error closing previous transport: {e:?}"));
+                }
+                self.migrations.push_back(standby.server_addr);
+                return Ok(());
+            }
+            Some(false) => standby.last_probe_ack_time = Some(self.time),
+            None => {}
+        }
+
+        let unhealthy = client.time_since_last_receive() >= self.unhealthy_after;
+        // the standby transport answered one of the last two probes
+        let standby_healthy = standby
+            .last_probe_ack_time
+            .is_some_and(|t| self.time - t <= 2.0 * self.probe_interval);
+        if unhealthy && standby_healthy {
+            // keep sending the commit until the server acknowledges it
+            if standby
+                .last_commit_send_time
+                .map_or(true, |t| self.time - t >= self.probe_interval)
+            {
+                debug!(server_addr = ?standby.server_addr, "current transport is unhealthy, migrating to the standby transport");
+                client.send_migrate(&mut standby.io, standby.server_addr, true)?;
+                standby.last_commit_send_time = Some(self.time);
+            }
+        } else if self.time - standby.last_probe_send_time >= self.probe_interval {
+            client.send_migrate(&mut standby.io, standby.server_addr, false)?;
+            standby.last_probe_send_time = self.time;
+        }
+        Ok(())
+    }
+}
@@ -124,6 +124,7 @@ loop {
 pub use client::{connection::Client, ClientConfig, ClientState, NetcodeClient};
 pub use crypto::{generate_key, try_generate_key, Key};
 pub use error::{Error, Result};
+pub use migration::FallbackTransport;
 pub use server::{connection::Server, Callback, ClientId, NetcodeServer, ServerConfig};
 pub use token::{ConnectToken, ConnectTokenBuilder, InvalidTokenError};
 
@@ -131,11 +132,11 @@ mod bytes;
 mod client;
 mod crypto;
 pub(crate) mod error;
-mod packet;
+pub(crate) mod migration;
+pub(crate) mod packet;
 mod replay;
 mod server;
 mod token;
-mod utils;
 
 pub(crate) const MAC_BYTES: usize = 16;
 pub(crate) const MAX_PKT_BUF_SIZE: usize = 1300;
@@ -152,3 +153,10 @@ pub const CONNECT_TOKEN_BYTES: usize = 2048;
 pub const MAX_PACKET_SIZE: usize = 1200;
 /// The version of the netcode protocol implemented by this crate.
 pub const NETCODE_VERSION: &[u8; 13] = b"NETCODE 1.02\0";
+/// Prefix of the packets sent to/from unconnected endpoints (for example for NAT punch-through).
+///
+/// The first byte can never be the prefix byte of a netcode packet (whose packet type is at most 6), so these
+/// packets are never interpreted by the netcode protocol.
+pub const UNCONNECTED_PACKET_PREFIX: &[u8; 4] = b"\xffLYU";
+/// The maximum size of the payload of an unconnected packet in bytes.
+pub const MAX_UNCONNECTED_PAYLOAD_SIZE: usize = 512;
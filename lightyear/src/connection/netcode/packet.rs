@@ -81,6 +81,8 @@ pub struct RequestPacket {
     pub expire_timestamp: u64,
     pub token_nonce: XNonce,
     pub token_data: Box<[u8; ConnectTokenPrivate::SIZE]>,
+    /// Hash of the ids of the types of the client's protocol, see [`NetIdReport`](crate::prelude::NetIdReport)
+    pub protocol_hash: u64,
 }
 
 impl RequestPacket {
@@ -89,6 +91,7 @@ impl RequestPacket {
         expire_timestamp: u64,
         token_nonce: XNonce,
         token_data: [u8; ConnectTokenPrivate::SIZE],
+        protocol_hash: u64,
     ) -> Packet<'static> {
         Packet::Request(RequestPacket {
             version_info: *NETCODE_VERSION,
@@ -96,6 +99,7 @@ impl RequestPacket {
             expire_timestamp,
             token_nonce,
             token_data: Box::new(token_data),
+            protocol_hash,
         })
     }
     pub fn validate(&self, protocol_id: u64, current_timestamp: u64) -> Result<(), Error> {
@@ -136,6 +140,7 @@ impl Bytes for RequestPacket {
         writer.write_u64::<LittleEndian>(self.expire_timestamp)?;
         writer.write_all(&self.token_nonce)?;
         writer.write_all(&self.token_data[..])?;
+        writer.write_u64::<LittleEndian>(self.protocol_hash)?;
         Ok(())
     }
 
@@ -149,12 +154,14 @@ impl Bytes for RequestPacket {
         let token_nonce = XNonce::from_slice(&nonce).to_owned();
         let mut token_data = [0; ConnectTokenPrivate::SIZE];
         reader.read_exact(&mut token_data)?;
+        let protocol_hash = reader.read_u64::<LittleEndian>()?;
         Ok(Self {
             version_info,
             protocol_id,
             expire_timestamp,
             token_nonce,
             token_data: Box::new(token_data),
+            protocol_hash,
         })
     }
 }
@@ -192,6 +199,9 @@ impl Bytes for DeniedReason {
             DeniedReason::InvalidToken => {
                 writer.write_u8(5)?;
             }
+            DeniedReason::ProtocolMismatch => {
+                writer.write_u8(7)?;
+            }
             DeniedReason::Custom(reason) => {
                 writer.write_u8(6)?;
                 // the reason cannot exceed u8::MAX in size
@@ -235,6 +245,8 @@ impl Bytes for DeniedReason {
             let reason_str = String::from_utf8(string_buf)
                 .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "invalid denied reason"))?;
             Ok(DeniedReason::Custom(reason_str))
+        } else if variant == 7 {
+            Ok(DeniedReason::ProtocolMismatch)
         } else {
             Err(io::Error::new(
                 io::ErrorKind::InvalidData,
@@ -350,22 +362,80 @@ impl PayloadPacket<'_> {
     }
 }
 
-pub struct DisconnectPacket {}
+pub struct DisconnectPacket {
+    /// Reason provided by the server when it disconnects a client (for example "banned").
+    /// It cannot exceed `u8::MAX` bytes.
+    pub reason: Option<String>,
+}
 
 impl DisconnectPacket {
-    pub fn create() -> Packet<'static> {
-        Packet::Disconnect(Self {})
+    pub fn create(reason: Option<String>) -> Packet<'static> {
+        Packet::Disconnect(Self { reason })
     }
 }
 
 impl Bytes for DisconnectPacket {
     type Error = io::Error;
-    fn write_to(&self, _writer: &mut impl WriteBytesExt) -> Result<(), Self::Error> {
+    fn write_to(&self, writer: &mut impl WriteBytesExt) -> Result<(), Self::Error> {
+        let Some(reason) = &self.reason else {
+            writer.write_u8(0)?;
+            return Ok(());
+        };
+        if reason.len() > u8::MAX as usize {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "disconnect reason too long",
+            ));
+        }
+        writer.write_u8(1)?;
+        writer.write_u8(reason.len() as u8)?;
+        writer.write_all(reason.as_bytes())?;
+        Ok(())
+    }
+
+    fn read_from(reader: &mut impl byteorder::ReadBytesExt) -> Result<Self, io::Error> {
+        if reader.read_u8()? == 0 {
+            return Ok(Self { reason: None });
+        }
+        let len = reader.read_u8()? as usize;
+        let mut string_buf = vec![0; len];
+        reader.read_exact(&mut string_buf)?;
+        let reason = String::from_utf8(string_buf)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "invalid disconnect reason"))?;
+        Ok(Self {
+            reason: Some(reason),
+        })
+    }
+}
+
+/// Sent by a client over a different transport to move its session to that transport.
+///
+/// The server acknowledges with a `MigratePacket` of its own, sent on the new path.
+/// A probe (`commit: false`) only checks that the path works; a commit makes the server send
+/// all the following packets of the session to the new address.
+pub struct MigratePacket {
+    pub client_id: ClientId,
+    pub commit: bool,
+}
+
+impl MigratePacket {
+    pub fn create(client_id: ClientId, commit: bool) -> Packet<'static> {
+        Packet::Migrate(MigratePacket { client_id, commit })
+    }
+}
+
+impl Bytes for MigratePacket {
+    type Error = io::Error;
+    fn write_to(&self, writer: &mut impl WriteBytesExt) -> Result<(), Self::Error> {
+        writer.write_u64::<LittleEndian>(self.client_id)?;
+        writer.write_u8(self.commit as u8)?;
         Ok(())
     }
 
-    fn read_from(_reader: &mut impl byteorder::ReadBytesExt) -> Result<Self, io::Error> {
-        Ok(Self {})
+    fn read_from(reader: &mut impl byteorder::ReadBytesExt) -> Result<Self, io::Error> {
+        let client_id = reader.read_u64::<LittleEndian>()?;
+        let commit = reader.read_u8()? != 0;
+        Ok(Self { client_id, commit })
     }
 }
 
@@ -377,6 +447,7 @@ pub enum Packet<'p> {
     KeepAlive(KeepAlivePacket),
     Payload(PayloadPacket<'p>),
     Disconnect(DisconnectPacket),
+    Migrate(MigratePacket),
 }
 
 impl std::fmt::Display for Packet<'_> {
@@ -389,6 +460,7 @@ impl std::fmt::Display for Packet<'_> {
             Packet::Disconnect(_) => write!(f, "disconnect packet"),
             Packet::Denied(_) => write!(f, "denied packet"),
             Packet::Challenge(_) => write!(f, "challenge packet"),
+            Packet::Migrate(_) => write!(f, "migrate packet"),
         }
     }
 }
@@ -403,6 +475,7 @@ impl<'p> Packet<'p> {
     pub const KEEP_ALIVE: PacketKind = 4;
     pub const PAYLOAD: PacketKind = 5;
     pub const DISCONNECT: PacketKind = 6;
+    pub const MIGRATE: PacketKind = 7;
     fn kind(&self) -> PacketKind {
         match self {
             Packet::Request(_) => Packet::REQUEST,
@@ -412,6 +485,7 @@ impl<'p> Packet<'p> {
             Packet::KeepAlive(_) => Packet::KEEP_ALIVE,
             Packet::Payload(_) => Packet::PAYLOAD,
             Packet::Disconnect(_) => Packet::DISCONNECT,
+            Packet::Migrate(_) => Packet::MIGRATE,
         }
     }
     fn set_prefix(&self, sequence: u64) -> u8 {
@@ -434,6 +508,18 @@ impl<'p> Packet<'p> {
     pub fn get_prefix(prefix_byte: u8) -> (usize, PacketKind) {
         ((prefix_byte >> 4) as usize, prefix_byte & 0xF)
     }
+    /// Read the client id of a migrate packet without decrypting it.
+    ///
+    /// Migrate packets arrive from an address that the server does not know yet, so the client id
+    /// is also written in plaintext to let the server pick the decryption key.
+    pub fn peek_migrate_client_id(buf: &[u8]) -> Option<ClientId> {
+        let (_, pkt_kind) = Packet::get_prefix(*buf.first()?);
+        if pkt_kind != Packet::MIGRATE {
+            return None;
+        }
+        let bytes = buf.get(1..1 + size_of::<ClientId>())?;
+        Some(ClientId::from_le_bytes(bytes.try_into().ok()?))
+    }
     pub fn write(
         &self,
         out: &mut [u8],
@@ -449,6 +535,9 @@ impl<'p> Packet<'p> {
             return Ok(cursor.position() as usize);
         }
         cursor.write_u8(self.set_prefix(sequence))?;
+        if let Packet::Migrate(pkt) = self {
+            cursor.write_u64::<LittleEndian>(pkt.client_id)?;
+        }
         cursor.write_sequence(sequence)?;
         let encryption_start = cursor.position() as usize;
         match self {
@@ -457,6 +546,7 @@ impl<'p> Packet<'p> {
             Packet::Response(pkt) => pkt.write_to(&mut cursor)?,
             Packet::KeepAlive(pkt) => pkt.write_to(&mut cursor)?,
             Packet::Disconnect(pkt) => pkt.write_to(&mut cursor)?,
+            Packet::Migrate(pkt) => pkt.write_to(&mut cursor)?,
             Packet::Payload(PayloadPacket { buf }) => cursor.write_all(buf)?,
             _ => unreachable!(), // Packet::Request variant is handled above
         }
@@ -492,6 +582,9 @@ impl<'p> Packet<'p> {
         let mut cursor = std::io::Cursor::new(&mut buf[..]);
         let prefix_byte = cursor.read_u8()?;
         let (sequence_len, pkt_kind) = Packet::get_prefix(prefix_byte);
+        if pkt_kind > Packet::MIGRATE {
+            return Err(Error::InvalidType(pkt_kind).into());
+        }
         if allowed_packets & (1 << pkt_kind) == 0 {
             debug!("ignoring packet of type {}, not allowed", pkt_kind);
         }
@@ -502,10 +595,24 @@ impl<'p> Packet<'p> {
             packet.decrypt_token_data(key)?;
             return Ok(Packet::Request(packet));
         }
-        if buf_len < size_of::<u8>() + sequence_len + MAC_BYTES {
+        if !(1..=8).contains(&sequence_len) {
+            return Err(Error::InvalidSequenceBytes(sequence_len as u8).into());
+        }
+        let client_id_len = if pkt_kind == Packet::MIGRATE {
+            size_of::<ClientId>()
+        } else {
+            0
+        };
+        if buf_len < size_of::<u8>() + client_id_len + sequence_len + MAC_BYTES {
             // should at least have prefix byte, sequence and mac
             return Err(Error::TooSmall.into());
         }
+        // migrate packets also carry the client id in plaintext
+        let plaintext_client_id = if pkt_kind == Packet::MIGRATE {
+            Some(cursor.read_u64::<LittleEndian>()?)
+        } else {
+            None
+        };
         let sequence = cursor.read_sequence(sequence_len)?;
 
         // Replay protection
@@ -539,6 +646,14 @@ impl<'p> Packet<'p> {
             Packet::RESPONSE => Packet::Response(ResponsePacket::read_from(&mut cursor)?),
             Packet::KEEP_ALIVE => Packet::KeepAlive(KeepAlivePacket::read_from(&mut cursor)?),
             Packet::DISCONNECT => Packet::Disconnect(DisconnectPacket::read_from(&mut cursor)?),
+            Packet::MIGRATE => {
+                let packet = MigratePacket::read_from(&mut cursor)?;
+                // the plaintext client id must match the authenticated one
+                if plaintext_client_id != Some(packet.client_id) {
+                    return Err(Error::InvalidPayload.into());
+                }
+                Packet::Migrate(packet)
+            }
             Packet::PAYLOAD => {
                 buf.copy_within(decryption_start..(decryption_end - MAC_BYTES), 0);
                 Packet::Payload(PayloadPacket {
@@ -586,6 +701,32 @@ mod tests {
         assert_eq!(cursor.read_sequence(8).unwrap(), sequence);
     }
 
+    #[test]
+    fn read_invalid_prefix() {
+        let key = generate_key();
+        let protocol_id = 0x1234_5678_9abc_def0;
+        let read = |buf: &mut [u8]| Packet::read(buf, protocol_id, 0, key, None, 0xff).map(|_| ());
+
+        // invalid packet type
+        let mut buf = [0x1f; 100];
+        assert!(matches!(
+            read(&mut buf),
+            Err(NetcodeError::Packet(Error::InvalidType(0xf)))
+        ));
+        // the sequence is at most 8 bytes long
+        let mut buf = [0; 100];
+        buf[0] = 0x9 << 4 | Packet::KEEP_ALIVE;
+        assert!(matches!(
+            read(&mut buf),
+            Err(NetcodeError::Packet(Error::InvalidSequenceBytes(9)))
+        ));
+        buf[0] = Packet::KEEP_ALIVE;
+        assert!(matches!(
+            read(&mut buf),
+            Err(NetcodeError::Packet(Error::InvalidSequenceBytes(0)))
+        ));
+    }
+
     #[test]
     fn request_packet() {
         let client_id = 0x1234;
@@ -618,6 +759,7 @@ mod tests {
             expire_timestamp,
             token_nonce: nonce,
             token_data: Box::new(token_data),
+            protocol_hash: 0xdead_beef,
         });
 
         let mut buf = [0u8; MAX_PACKET_SIZE];
@@ -643,6 +785,7 @@ mod tests {
         assert_eq!(req_pkt.protocol_id, protocol_id);
         assert_eq!(req_pkt.expire_timestamp, expire_timestamp);
         assert_eq!(req_pkt.token_nonce, nonce);
+        assert_eq!(req_pkt.protocol_hash, 0xdead_beef);
 
         let mut reader = std::io::Cursor::new(&req_pkt.token_data[..]);
         let connect_token_private = ConnectTokenPrivate::read_from(&mut reader).unwrap();
@@ -788,19 +931,35 @@ mod tests {
     }
 
     #[test]
-    pub fn disconnect_packet() {
+    pub fn migrate_packet() {
         let packet_key = generate_key();
         let protocol_id = 0x1234_5678_9abc_def0;
-        let sequence = 0u64;
+        let sequence = 42u64;
+        let client_id = 0x1234;
         let mut replay_protection = ReplayProtection::new();
 
-        let packet = Packet::Disconnect(DisconnectPacket {});
+        let packet = MigratePacket::create(client_id, true);
 
         let mut buf = [0u8; MAX_PKT_BUF_SIZE];
         let size = packet
             .write(&mut buf, sequence, &packet_key, protocol_id)
             .unwrap();
 
+        assert_eq!(Packet::peek_migrate_client_id(&buf[..size]), Some(client_id));
+
+        // tampering with the plaintext client id is detected
+        let mut tampered = buf;
+        tampered[1] ^= 1;
+        assert!(Packet::read(
+            &mut tampered[..size],
+            protocol_id,
+            0,
+            packet_key,
+            Some(&mut ReplayProtection::new()),
+            0xff,
+        )
+        .is_err());
+
         let packet = Packet::read(
             &mut buf[..size],
             protocol_id,
@@ -811,9 +970,43 @@ mod tests {
         )
         .unwrap();
 
-        let Packet::Disconnect(_disconnect_pkt) = packet else {
+        let Packet::Migrate(migrate_pkt) = packet else {
             panic!("wrong packet type");
         };
+
+        assert_eq!(migrate_pkt.client_id, client_id);
+        assert!(migrate_pkt.commit);
+    }
+
+    #[test]
+    pub fn disconnect_packet() {
+        let packet_key = generate_key();
+        let protocol_id = 0x1234_5678_9abc_def0;
+        let mut replay_protection = ReplayProtection::new();
+
+        for (sequence, reason) in [(0u64, None), (1, Some("banned".to_string()))] {
+            let packet = DisconnectPacket::create(reason.clone());
+
+            let mut buf = [0u8; MAX_PKT_BUF_SIZE];
+            let size = packet
+                .write(&mut buf, sequence, &packet_key, protocol_id)
+                .unwrap();
+
+            let packet = Packet::read(
+                &mut buf[..size],
+                protocol_id,
+                0,
+                packet_key,
+                Some(&mut replay_protection),
+                0xff,
+            )
+            .unwrap();
+
+            let Packet::Disconnect(disconnect_pkt) = packet else {
+                panic!("wrong packet type");
+            };
+            assert_eq!(disconnect_pkt.reason, reason);
+        }
     }
 
     #[test]
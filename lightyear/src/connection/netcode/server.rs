@@ -1,7 +1,6 @@
 use std::collections::{HashMap, VecDeque};
 use std::net::SocketAddr;
 use std::sync::Arc;
-use std::time::{SystemTime, UNIX_EPOCH};
 
 use bevy::prelude::Resource;
 use tracing::{debug, error, trace};
@@ -12,11 +11,13 @@ use tracing::{instrument, Level};
 use crate::connection::id;
 use crate::connection::netcode::token::TOKEN_EXPIRE_SEC;
 use crate::connection::server::{
-    ConnectionRequestHandler, DefaultConnectionRequestHandler, DeniedReason, IoConfig, NetServer,
+    ConnectionRequestHandler, DefaultConnectionRequestHandler, DeniedReason, DenyList,
+    DisconnectReason, IoConfig, NetServer,
 };
 use crate::packet::packet_builder::RecvPayload;
-use crate::server::config::NetcodeConfig;
+use crate::server::config::{DuplicateIdPolicy, DuplicateSession, NetcodeConfig};
 use crate::server::io::{Io, ServerIoEvent, ServerNetworkEventSender};
+use crate::shared::clock::NetworkClock;
 use crate::transport::{PacketReceiver, PacketSender};
 
 use super::{
@@ -24,12 +25,12 @@ use super::{
     crypto::{self, Key},
     error::{Error, Result},
     packet::{
-        ChallengePacket, DeniedPacket, DisconnectPacket, KeepAlivePacket, Packet, PayloadPacket,
-        RequestPacket, ResponsePacket,
+        ChallengePacket, DeniedPacket, DisconnectPacket, KeepAlivePacket, MigratePacket, Packet,
+        PayloadPacket, RequestPacket, ResponsePacket,
     },
     replay::ReplayProtection,
     token::{ChallengeToken, ConnectToken, ConnectTokenBuilder, ConnectTokenPrivate},
-    MAC_BYTES, MAX_PACKET_SIZE, MAX_PKT_BUF_SIZE, PACKET_SEND_RATE_SEC,
+    MAC_BYTES, MAX_PACKET_SIZE, MAX_PKT_BUF_SIZE, PACKET_SEND_RATE_SEC, UNCONNECTED_PACKET_PREFIX,
 };
 
 pub const MAX_CLIENTS: usize = 256;
@@ -149,13 +150,11 @@ impl ConnectionCache {
         send_key: Key,
         receive_key: Key,
     ) {
-        if let Some((_, ref mut existing)) = self.find_by_addr(&addr) {
-            existing.client_id = client_id;
-            existing.timeout = timeout;
-            existing.send_key = send_key;
-            existing.receive_key = receive_key;
-            existing.last_access_time = self.time;
-            return;
+        // a new connection request from the address of a pending connection replaces it
+        // (the client retried with a new connect token)
+        if let Some(previous_id) = self.client_id_map.remove(&addr) {
+            self.clients.remove(&previous_id);
+            self.replay_protection.remove(&previous_id);
         }
         let conn = Connection {
             confirmed: false,
@@ -200,6 +199,16 @@ impl ConnectionCache {
     fn find_by_id(&self, client_id: ClientId) -> Option<Connection> {
         self.clients.get(&client_id).cloned()
     }
+    /// Send the packets of a client to a new address. Returns the previous address.
+    fn migrate(&mut self, client_id: ClientId, addr: SocketAddr) -> Option<SocketAddr> {
+        let conn = self.clients.get_mut(&client_id)?;
+        let previous = std::mem::replace(&mut conn.addr, addr);
+        if self.client_id_map.get(&previous) == Some(&client_id) {
+            self.client_id_map.remove(&previous);
+        }
+        self.client_id_map.insert(addr, client_id);
+        Some(previous)
+    }
     fn update(&mut self, delta_ms: f64) {
         self.time += delta_ms;
     }
@@ -215,6 +224,10 @@ impl ConnectionCache {
 }
 
 pub type Callback<Ctx> = Box<dyn FnMut(ClientId, SocketAddr, &mut Ctx) + Send + Sync + 'static>;
+pub type DisconnectCallback<Ctx> =
+    Box<dyn FnMut(ClientId, SocketAddr, DisconnectReason, &mut Ctx) + Send + Sync + 'static>;
+pub type DuplicateCallback<Ctx> =
+    Box<dyn FnMut(ClientId, DuplicateSession, &mut Ctx) + Send + Sync + 'static>;
 
 /// Configuration for a server.
 ///
@@ -222,6 +235,8 @@ pub type Callback<Ctx> = Box<dyn FnMut(ClientId, SocketAddr, &mut Ctx) + Send +
 /// * `keep_alive_send_rate` - The rate at which keep-alive packets will be sent to clients.
 /// * `on_connect` - A callback that will be called when a client is connected to the server.
 /// * `on_disconnect` - A callback that will be called when a client is disconnected from the server.
+/// * `on_migrate` - A callback that will be called when a client moves its session to a new address.
+/// * `on_duplicate` - A callback that will be called when a client connects with the id of a client that is still connected.
 ///
 /// # Example
 /// ```
@@ -245,10 +260,13 @@ pub struct ServerConfig<Ctx> {
     token_expire_secs: i32,
     client_timeout_secs: i32,
     connection_request_handler: Arc<dyn ConnectionRequestHandler>,
+    duplicate_id_policy: DuplicateIdPolicy,
     server_addr: SocketAddr,
     context: Ctx,
     on_connect: Option<Callback<Ctx>>,
-    on_disconnect: Option<Callback<Ctx>>,
+    on_disconnect: Option<DisconnectCallback<Ctx>>,
+    on_migrate: Option<Callback<Ctx>>,
+    on_duplicate: Option<DuplicateCallback<Ctx>>,
 }
 
 impl Default for ServerConfig<()> {
@@ -259,10 +277,13 @@ impl Default for ServerConfig<()> {
             token_expire_secs: TOKEN_EXPIRE_SEC,
             client_timeout_secs: CLIENT_TIMEOUT_SECS,
             connection_request_handler: Arc::new(DefaultConnectionRequestHandler),
+            duplicate_id_policy: DuplicateIdPolicy::default(),
             server_addr: SocketAddr::from(([0, 0, 0, 0], 0)),
             context: (),
             on_connect: None,
             on_disconnect: None,
+            on_migrate: None,
+            on_duplicate: None,
         }
     }
 }
@@ -280,10 +301,13 @@ impl<Ctx> ServerConfig<Ctx> {
             token_expire_secs: TOKEN_EXPIRE_SEC,
             client_timeout_secs: CLIENT_TIMEOUT_SECS,
             connection_request_handler: Arc::new(DefaultConnectionRequestHandler),
+            duplicate_id_policy: DuplicateIdPolicy::default(),
             server_addr: SocketAddr::from(([0, 0, 0, 0], 0)),
             context: ctx,
             on_connect: None,
             on_disconnect: None,
+            on_migrate: None,
+            on_duplicate: None,
         }
     }
     /// Set the number of redundant disconnect packets that will be sent to a client when the server is disconnecting it. <br>
@@ -310,6 +334,12 @@ impl<Ctx> ServerConfig<Ctx> {
         self.token_expire_secs = expire_secs;
         self
     }
+    /// Set what happens when a client connects with the id of a client that is still connected.
+    /// The default is [`DuplicateIdPolicy::RejectNew`].
+    pub fn duplicate_id_policy(mut self, policy: DuplicateIdPolicy) -> Self {
+        self.duplicate_id_policy = policy;
+        self
+    }
     /// Set the socket address of the server.
     // TODO: This actually NEEDS to be set, change the API to force this
     pub fn server_addr(mut self, server_addr: SocketAddr) -> Self {
@@ -328,16 +358,37 @@ impl<Ctx> ServerConfig<Ctx> {
         self
     }
     /// Provide a callback that will be called when a client is disconnected from the server. <br>
-    /// The callback will be called with the client index and the context that was provided (provide a `None` context if you don't need one).
+    /// The callback will be called with the client index, the reason of the disconnection and the context that was provided
+    /// (provide a `None` context if you don't need one).
     ///
     /// See [`ServerConfig`] for an example.
     pub fn on_disconnect<F>(mut self, cb: F) -> Self
     where
-        F: FnMut(ClientId, SocketAddr, &mut Ctx) + Send + Sync + 'static,
+        F: FnMut(ClientId, SocketAddr, DisconnectReason, &mut Ctx) + Send + Sync + 'static,
     {
         self.on_disconnect = Some(Box::new(cb));
         self
     }
+    /// Provide a callback that will be called when a connected client moves its session to a new address
+    /// (for example because it switched to a fallback transport). <br>
+    /// The callback will be called with the client index, the new address and the context that was provided.
+    pub fn on_migrate<F>(mut self, cb: F) -> Self
+    where
+        F: FnMut(ClientId, SocketAddr, &mut Ctx) + Send + Sync + 'static,
+    {
+        self.on_migrate = Some(Box::new(cb));
+        self
+    }
+    /// Provide a callback that will be called when a client connects with the id of a client that is still connected. <br>
+    /// The callback will be called with the client index, the session that was kept by the
+    /// [`DuplicateIdPolicy`] and the context that was provided.
+    pub fn on_duplicate<F>(mut self, cb: F) -> Self
+    where
+        F: FnMut(ClientId, DuplicateSession, &mut Ctx) + Send + Sync + 'static,
+    {
+        self.on_duplicate = Some(Box::new(cb));
+        self
+    }
 }
 
 /// The `netcode` server.
@@ -383,6 +434,12 @@ pub struct NetcodeServer<Ctx = ()> {
     protocol_id: u64,
     conn_cache: ConnectionCache,
     token_entries: TokenEntries,
+    /// Hash of the protocol, which must match the one sent by the clients in their connection request
+    protocol_hash: u64,
+    /// Clients whose connection requests are denied
+    deny_list: DenyList,
+    /// Clock used to check the timestamps of the packets and the expiry of the connect tokens
+    clock: NetworkClock,
     cfg: ServerConfig<Ctx>,
 }
 
@@ -401,6 +458,9 @@ impl NetcodeServer {
             challenge_key: crypto::generate_key(),
             conn_cache: ConnectionCache::new(0.0),
             token_entries: TokenEntries::new(),
+            protocol_hash: 0,
+            deny_list: DenyList::default(),
+            clock: NetworkClock::default(),
             cfg: ServerConfig::default(),
         };
         // info!("server started on {}", server.io.local_addr());
@@ -435,6 +495,9 @@ impl<Ctx> NetcodeServer<Ctx> {
             challenge_key: crypto::generate_key(),
             conn_cache: ConnectionCache::new(0.0),
             token_entries: TokenEntries::new(),
+            protocol_hash: 0,
+            deny_list: DenyList::default(),
+            clock: NetworkClock::default(),
             cfg,
         };
         // info!("server started on {}", server.addr());
@@ -447,17 +510,28 @@ impl<Ctx> NetcodeServer<Ctx> {
         | 1 << Packet::RESPONSE
         | 1 << Packet::KEEP_ALIVE
         | 1 << Packet::PAYLOAD
-        | 1 << Packet::DISCONNECT;
+        | 1 << Packet::DISCONNECT
+        | 1 << Packet::MIGRATE;
     fn on_connect(&mut self, client_id: ClientId, addr: SocketAddr) {
         if let Some(cb) = self.cfg.on_connect.as_mut() {
             cb(client_id, addr, &mut self.cfg.context)
         }
     }
-    fn on_disconnect(&mut self, client_id: ClientId, addr: SocketAddr) {
+    fn on_disconnect(&mut self, client_id: ClientId, addr: SocketAddr, reason: DisconnectReason) {
         if let Some(cb) = self.cfg.on_disconnect.as_mut() {
+            cb(client_id, addr, reason, &mut self.cfg.context)
+        }
+    }
+    fn on_migrate(&mut self, client_id: ClientId, addr: SocketAddr) {
+        if let Some(cb) = self.cfg.on_migrate.as_mut() {
             cb(client_id, addr, &mut self.cfg.context)
         }
     }
+    fn on_duplicate(&mut self, client_id: ClientId, winner: DuplicateSession) {
+        if let Some(cb) = self.cfg.on_duplicate.as_mut() {
+            cb(client_id, winner, &mut self.cfg.context)
+        }
+    }
     fn touch_client(&mut self, client_id: Option<ClientId>) -> Result<()> {
         let Some(id) = client_id else {
             return Ok(());
@@ -509,14 +583,49 @@ impl<Ctx> NetcodeServer<Ctx> {
             Packet::Disconnect(_) => {
                 if let Some(idx) = client_id {
                     debug!("server disconnected client {idx}");
-                    self.on_disconnect(idx, addr);
+                    self.on_disconnect(idx, addr, DisconnectReason::ClientRequested);
                     self.conn_cache.remove(idx);
                 }
                 Ok(())
             }
+            Packet::Migrate(packet) => self.process_migrate(addr, packet, sender),
             _ => unreachable!("packet should have been filtered out by `ALLOWED_PACKETS`"),
         }
     }
+    fn process_migrate(
+        &mut self,
+        from_addr: SocketAddr,
+        packet: MigratePacket,
+        sender: &mut impl PacketSender,
+    ) -> Result<()> {
+        let id = packet.client_id;
+        if !self
+            .conn_cache
+            .find_by_id(id)
+            .is_some_and(|conn| conn.is_connected())
+        {
+            debug!("server ignored migrate packet. client {id} is not connected");
+            return Ok(());
+        }
+        // the packet was authenticated with the client's key, so the new path is a valid
+        // way of reaching the same session
+        self.touch_client(Some(id))?;
+        if packet.commit {
+            if let Some(previous) = self.conn_cache.migrate(id, from_addr) {
+                if previous != from_addr {
+                    debug!("server migrated client {id} from {previous} to {from_addr}");
+                    self.on_migrate(id, from_addr);
+                }
+            }
+        }
+        // acknowledge on the path that the packet came from
+        self.send_to_client_at(
+            MigratePacket::create(id, packet.commit),
+            id,
+            from_addr,
+            sender,
+        )
+    }
     fn send_to_addr(
         &mut self,
         packet: Packet,
@@ -535,6 +644,22 @@ impl<Ctx> NetcodeServer<Ctx> {
         packet: Packet,
         id: ClientId,
         sender: &mut impl PacketSender,
+    ) -> Result<()> {
+        let addr = self
+            .conn_cache
+            .clients
+            .get(&id)
+            .expect("invalid client id")
+            .addr;
+        self.send_to_client_at(packet, id, addr, sender)
+    }
+    /// Send a packet to a client on a specific address, which might not be the client's current address
+    fn send_to_client_at(
+        &mut self,
+        packet: Packet,
+        id: ClientId,
+        addr: SocketAddr,
+        sender: &mut impl PacketSender,
     ) -> Result<()> {
         let mut buf = [0u8; MAX_PKT_BUF_SIZE];
         let conn = &mut self
@@ -544,7 +669,7 @@ impl<Ctx> NetcodeServer<Ctx> {
             .expect("invalid client id");
         let size = packet.write(&mut buf, conn.sequence, &conn.send_key, self.protocol_id)?;
         sender
-            .send(&buf[..size], &conn.addr)
+            .send(&buf[..size], &addr)
             // .inspect_err(|e| error!("ERROR SENDING: {:?}", e))
             .map_err(Error::from)?;
         conn.last_access_time = self.time;
@@ -586,14 +711,6 @@ impl<Ctx> NetcodeServer<Ctx> {
             debug!("server ignored connection request. a client with this address is already connected");
             return Ok(());
         };
-        if self
-            .conn_cache
-            .find_by_id(token.client_id)
-            .is_some_and(|conn| conn.is_connected())
-        {
-            debug!("server ignored connection request. a client with this id is already connected");
-            return Ok(());
-        };
         let entry = TokenEntry {
             time: self.time,
             addr: from_addr,
@@ -606,6 +723,66 @@ impl<Ctx> NetcodeServer<Ctx> {
             debug!("server ignored connection request. connect token has already been used");
             return Ok(());
         };
+        if packet.protocol_hash != self.protocol_hash {
+            debug!(
+                client_hash = packet.protocol_hash,
+                server_hash = self.protocol_hash,
+                "server denied connection request. the client's protocol does not match"
+            );
+            self.send_to_addr(
+                DeniedPacket::create(DeniedReason::ProtocolMismatch),
+                from_addr,
+                token.server_to_client_key,
+                sender,
+            )?;
+            return Ok(());
+        };
+        if self
+            .deny_list
+            .contains(id::ClientId::Netcode(token.client_id))
+        {
+            debug!("server denied connection request. the client is banned");
+            self.send_to_addr(
+                DeniedPacket::create(DeniedReason::Banned),
+                from_addr,
+                token.server_to_client_key,
+                sender,
+            )?;
+            return Ok(());
+        };
+        if let Some(conn) = self
+            .conn_cache
+            .find_by_id(token.client_id)
+            .filter(|conn| conn.is_connected())
+        {
+            let replace = match self.cfg.duplicate_id_policy {
+                DuplicateIdPolicy::RejectNew => false,
+                DuplicateIdPolicy::DisconnectOld => true,
+                DuplicateIdPolicy::ReplaceIfStale { idle_threshold } => {
+                    self.time - conn.last_receive_time >= idle_threshold.as_secs_f64()
+                }
+            };
+            if !replace {
+                debug!(
+                    "server denied connection request. a client with this id is already connected"
+                );
+                self.send_to_addr(
+                    DeniedPacket::create(DeniedReason::AlreadyConnected),
+                    from_addr,
+                    token.server_to_client_key,
+                    sender,
+                )?;
+                self.on_duplicate(token.client_id, DuplicateSession::Old);
+                return Ok(());
+            }
+            debug!("server disconnecting client {} to replace it with a new connection with the same id", token.client_id);
+            self.disconnect_with(
+                token.client_id,
+                DisconnectReason::ServerKicked(None),
+                sender,
+            );
+            self.on_duplicate(token.client_id, DuplicateSession::New);
+        };
         if self.num_connected_clients() >= MAX_CLIENTS {
             debug!("server denied connection request. server is full");
             self.send_to_addr(
@@ -720,7 +897,7 @@ impl<Ctx> NetcodeServer<Ctx> {
                 && client.last_receive_time + (client.timeout as f64) < self.time
             {
                 debug!("server timed out client {id}");
-                self.on_disconnect(id, addr);
+                self.on_disconnect(id, addr, DisconnectReason::Timeout);
                 self.conn_cache.remove(id);
             }
         }
@@ -742,7 +919,7 @@ impl<Ctx> NetcodeServer<Ctx> {
         }
         Ok(())
     }
-    fn recv_packet(
+    pub(crate) fn recv_packet(
         &mut self,
         buf: &mut [u8],
         now: u64,
@@ -757,11 +934,26 @@ impl<Ctx> NetcodeServer<Ctx> {
             // Too small to be a packet
             return Ok(());
         }
-        let (key, replay_protection) = match self.conn_cache.find_by_addr(&addr) {
+        if buf.starts_with(UNCONNECTED_PACKET_PREFIX) {
+            // packets sent by clients to unconnected endpoints (e.g. for NAT punch-through) are not netcode packets
+            trace!("server ignored unconnected packet from {addr}");
+            return Ok(());
+        }
+        // migrate packets can come from an address that we don't know yet: the client id is in the packet
+        let migrate_client_id = Packet::peek_migrate_client_id(buf);
+        let client_id = match migrate_client_id {
+            Some(client_id) => self
+                .conn_cache
+                .clients
+                .contains_key(&client_id)
+                .then_some(client_id),
+            None => self.conn_cache.find_by_addr(&addr).map(|(id, _)| id),
+        };
+        let (key, replay_protection) = match client_id {
             // Regardless of whether an entry in the connection cache exists for the client or not,
             // if the packet is a connection request we need to use the server's private key to decrypt it.
             _ if buf[0] == Packet::REQUEST => (self.private_key, None),
-            Some((client_id, _)) => (
+            Some(client_id) => (
                 // If the packet is not a connection request, use the receive key to decrypt it.
                 self.conn_cache
                     .clients
@@ -802,7 +994,7 @@ impl<Ctx> NetcodeServer<Ctx> {
         sender: &mut impl PacketSender,
         receiver: &mut impl PacketReceiver,
     ) -> Result<()> {
-        let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        let now = self.clock.unix_time().as_secs();
         while let Some((buf, addr)) = receiver.recv().map_err(Error::from)? {
             self.recv_packet(buf, now, addr, sender)?;
         }
@@ -935,7 +1127,8 @@ impl<Ctx> NetcodeServer<Ctx> {
         server_addr: SocketAddr,
     ) -> ConnectTokenBuilder<SocketAddr> {
         let token_builder =
-            ConnectToken::build(server_addr, self.protocol_id, client_id, self.private_key);
+            ConnectToken::build(server_addr, self.protocol_id, client_id, self.private_key)
+                .clock(self.clock.clone());
         self.token_sequence += 1;
         token_builder
     }
@@ -944,37 +1137,72 @@ impl<Ctx> NetcodeServer<Ctx> {
     ///
     /// The server will send a number of redundant disconnect packets to the client, and then remove its connection info.
     pub fn disconnect(&mut self, client_id: ClientId, io: &mut Io) -> Result<()> {
+        self.disconnect_with(client_id, DisconnectReason::ServerKicked(None), io);
+        Ok(())
+    }
+
+    /// Disconnects a client, and sends it the reason of the disconnection (for example "banned").
+    ///
+    /// The reason is carried by the redundant disconnect packets; it cannot exceed `u8::MAX` bytes.
+    pub fn disconnect_with_reason(
+        &mut self,
+        client_id: ClientId,
+        reason: String,
+        io: &mut Io,
+    ) -> Result<()> {
+        if reason.len() > u8::MAX as usize {
+            return Err(Error::SizeMismatch(u8::MAX as usize, reason.len()));
+        }
+        self.disconnect_with(client_id, DisconnectReason::ServerKicked(Some(reason)), io);
+        Ok(())
+    }
+
+    /// Disconnects a client, and notifies the client unless the connection was lost because of the transport
+    fn disconnect_with(
+        &mut self,
+        client_id: ClientId,
+        reason: DisconnectReason,
+        sender: &mut impl PacketSender,
+    ) {
         let Some(conn) = self.conn_cache.clients.get_mut(&client_id) else {
-            return Ok(());
+            return;
         };
         if !conn.is_connected() {
-            return Ok(());
+            return;
         }
         let addr = conn.addr;
-        debug!("server disconnecting client {client_id}");
-        self.on_disconnect(client_id, addr);
-        for _ in 0..self.cfg.num_disconnect_packets {
-            // self.send_to_client(DisconnectPacket::create(), client_id, io)?;
-
-            // we do not use ? here because we want to continue even if the send fails
-            let _ = self
-                .send_to_client(DisconnectPacket::create(), client_id, io)
-                .inspect_err(|e| {
-                    error!("server failed to send disconnect packet: {e}");
-                });
+        debug!(?reason, "server disconnecting client {client_id}");
+        let kick_reason = match &reason {
+            DisconnectReason::ServerKicked(kick_reason) => Some(kick_reason.clone()),
+            _ => None,
+        };
+        self.on_disconnect(client_id, addr, reason);
+        if let Some(kick_reason) = kick_reason {
+            for _ in 0..self.cfg.num_disconnect_packets {
+                // we do not use ? here because we want to continue even if the send fails
+                let _ = self
+                    .send_to_client(
+                        DisconnectPacket::create(kick_reason.clone()),
+                        client_id,
+                        sender,
+                    )
+                    .inspect_err(|e| {
+                        error!("server failed to send disconnect packet: {e}");
+                    });
+            }
         }
         self.conn_cache.remove(client_id);
-        Ok(())
     }
 
-    /// Disconnects a client.
+    /// Disconnects a client whose transport failed.
     ///
-    /// The server will send a number of redundant disconnect packets to the client, and then remove its connection info.
+    /// No disconnect packets are sent, since the transport cannot reach the client anymore.
     pub(crate) fn disconnect_by_addr(&mut self, addr: SocketAddr, io: &mut Io) -> Result<()> {
-        let Some(client_id) = self.conn_cache.client_id_map.get(&addr) else {
+        let Some(client_id) = self.conn_cache.client_id_map.get(&addr).copied() else {
             return Err(Error::ClientNotFound);
         };
-        self.disconnect(*client_id, io)
+        self.disconnect_with(client_id, DisconnectReason::TransportError, io);
+        Ok(())
     }
 
     /// Disconnects all clients.
@@ -1016,6 +1244,22 @@ impl<Ctx> NetcodeServer<Ctx> {
         self.conn_cache.clients.get(&client_id).map(|c| c.addr)
     }
 
+    /// Set the hash of the protocol. Connection requests with a different hash are denied
+    /// with [`DeniedReason::ProtocolMismatch`].
+    pub(crate) fn set_protocol_hash(&mut self, protocol_hash: u64) {
+        self.protocol_hash = protocol_hash;
+    }
+
+    /// Set the list of clients whose connection requests are denied with [`DeniedReason::Banned`]
+    pub(crate) fn set_deny_list(&mut self, deny_list: DenyList) {
+        self.deny_list = deny_list;
+    }
+
+    /// Set the clock used to check the timestamps of the packets and the expiry of the connect tokens
+    pub(crate) fn set_clock(&mut self, clock: NetworkClock) {
+        self.clock = clock;
+    }
+
     /// Gets the address of the server
     pub fn local_addr(&self) -> SocketAddr {
         self.cfg.server_addr
@@ -1025,11 +1269,17 @@ impl<Ctx> NetcodeServer<Ctx> {
 pub(crate) mod connection {
     use super::*;
     use crate::connection::server::ConnectionError;
+    use crate::server::events::DuplicateClientIdEvent;
     use core::result::Result;
     #[derive(Default)]
     pub(crate) struct NetcodeServerContext {
         pub(crate) connections: Vec<id::ClientId>,
-        pub(crate) disconnections: Vec<id::ClientId>,
+        pub(crate) disconnections: Vec<(id::ClientId, DisconnectReason)>,
+        pub(crate) migrations: Vec<id::ClientId>,
+        pub(crate) duplicates: Vec<DuplicateClientIdEvent>,
+        /// Clients disconnected with [`NetServer::disconnect`] since the last update,
+        /// they are added to the disconnections of the next update
+        pending_disconnections: Vec<(id::ClientId, DisconnectReason)>,
         sender: Option<ServerNetworkEventSender>,
     }
 
@@ -1043,7 +1293,7 @@ pub(crate) mod connection {
     impl NetServer for Server {
         fn start(&mut self) -> Result<(), ConnectionError> {
             let io_config = self.io_config.clone();
-            let io = io_config.start()?;
+            let io = io_config.start_with_clock(&self.server.clock)?;
             self.server
                 .cfg
                 .context
@@ -1073,15 +1323,15 @@ pub(crate) mod connection {
         /// Disconnect a client from the server
         /// (also adds the client_id to the list of newly disconnected clients)
         fn disconnect(&mut self, client_id: id::ClientId) -> Result<(), ConnectionError> {
-            match client_id {
-                id::ClientId::Netcode(id) => {
-                    if let Some(io) = self.io.as_mut() {
-                        self.server.disconnect(id, io)?
-                    }
-                    Ok(())
-                }
-                _ => Err(ConnectionError::InvalidConnectionType),
-            }
+            self.kick(client_id, None)
+        }
+
+        fn disconnect_with_reason(
+            &mut self,
+            client_id: id::ClientId,
+            reason: String,
+        ) -> Result<(), ConnectionError> {
+            self.kick(client_id, Some(reason))
         }
 
         fn connected_client_ids(&self) -> Vec<id::ClientId> {
@@ -1094,8 +1344,11 @@ pub(crate) mod connection {
         fn try_update(&mut self, delta_ms: f64) -> Result<(), ConnectionError> {
             let io = self.io.as_mut().ok_or(ConnectionError::IoNotInitialized)?;
             // reset the new connections/disconnections
-            self.server.cfg.context.connections.clear();
-            self.server.cfg.context.disconnections.clear();
+            let context = &mut self.server.cfg.context;
+            context.connections.clear();
+            context.disconnections = std::mem::take(&mut context.pending_disconnections);
+            context.migrations.clear();
+            context.duplicates.clear();
 
             self.server.try_update(delta_ms, io)?;
             Ok(())
@@ -1120,10 +1373,18 @@ pub(crate) mod connection {
             self.server.cfg.context.connections.clone()
         }
 
-        fn new_disconnections(&self) -> Vec<id::ClientId> {
+        fn new_disconnections(&self) -> Vec<(id::ClientId, DisconnectReason)> {
             self.server.cfg.context.disconnections.clone()
         }
 
+        fn new_migrations(&self) -> Vec<id::ClientId> {
+            self.server.cfg.context.migrations.clone()
+        }
+
+        fn new_duplicate_connections(&self) -> Vec<DuplicateClientIdEvent> {
+            self.server.cfg.context.duplicates.clone()
+        }
+
         fn io(&self) -> Option<&Io> {
             self.io.as_ref()
         }
@@ -1136,11 +1397,12 @@ pub(crate) mod connection {
         pub(crate) fn new(config: NetcodeConfig, io_config: IoConfig) -> Self {
             // create context
             let context = NetcodeServerContext::default();
+            let policy = config.duplicate_id_policy;
             let mut cfg = ServerConfig::with_context(context)
                 .on_connect(|id, addr, ctx| {
                     ctx.connections.push(id::ClientId::Netcode(id));
                 })
-                .on_disconnect(|id, addr, ctx| {
+                .on_disconnect(|id, addr, reason, ctx| {
                     // notify the io that a client got disconnected
                     if let Some(sender) = &mut ctx.sender {
                         debug!("Notify the io that client {id:?} got disconnected, so that we can stop the corresponding task");
@@ -1150,11 +1412,22 @@ pub(crate) mod connection {
                                 error!("Error sending 'ClientDisconnected' event to io: {:?}", e)
                             });
                     }
-                    ctx.disconnections.push(id::ClientId::Netcode(id));
+                    ctx.disconnections.push((id::ClientId::Netcode(id), reason));
+                })
+                .on_migrate(|id, addr, ctx| {
+                    ctx.migrations.push(id::ClientId::Netcode(id));
+                })
+                .on_duplicate(move |id, winner, ctx| {
+                    ctx.duplicates.push(DuplicateClientIdEvent {
+                        client_id: id::ClientId::Netcode(id),
+                        policy,
+                        winner,
+                    });
                 });
             cfg = cfg.keep_alive_send_rate(config.keep_alive_send_rate);
             cfg = cfg.num_disconnect_packets(config.num_disconnect_packets);
             cfg = cfg.client_timeout_secs(config.client_timeout_secs);
+            cfg = cfg.duplicate_id_policy(policy);
             cfg.connection_request_handler = config.connection_request_handler;
             let server = NetcodeServer::with_config(config.protocol_id, config.private_key, cfg)
                 .expect("Could not create server netcode");
@@ -1166,6 +1439,35 @@ pub(crate) mod connection {
             }
         }
 
+        /// Disconnect a client from the server, with the reason sent to the client if any
+        fn kick(
+            &mut self,
+            client_id: id::ClientId,
+            reason: Option<String>,
+        ) -> Result<(), ConnectionError> {
+            let id::ClientId::Netcode(id) = client_id else {
+                return Err(ConnectionError::InvalidConnectionType);
+            };
+            if let Some(io) = self.io.as_mut() {
+                match reason {
+                    Some(reason) => self.server.disconnect_with_reason(id, reason, io)?,
+                    None => self.server.disconnect(id, io)?,
+                }
+                // the disconnections are reset at the start of the next update, so keep
+                // this one aside until then
+                let context = &mut self.server.cfg.context;
+                if let Some(index) = context
+                    .disconnections
+                    .iter()
+                    .position(|(c, _)| *c == client_id)
+                {
+                    let disconnection = context.disconnections.remove(index);
+                    context.pending_disconnections.push(disconnection);
+                }
+            }
+            Ok(())
+        }
+
         /// Disconnect a client from the server
         /// (also adds the client_id to the list of newly disconnected clients)
         pub(crate) fn disconnect_by_addr(
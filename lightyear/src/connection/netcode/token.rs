@@ -12,9 +12,10 @@ use super::{
     bytes::Bytes,
     crypto::{self, Key},
     error::Error,
-    utils, CONNECTION_TIMEOUT_SEC, CONNECT_TOKEN_BYTES, NETCODE_VERSION, PRIVATE_KEY_BYTES,
+    CONNECTION_TIMEOUT_SEC, CONNECT_TOKEN_BYTES, NETCODE_VERSION, PRIVATE_KEY_BYTES,
     USER_DATA_BYTES,
 };
+use crate::shared::clock::NetworkClock;
 use crate::utils::free_list::{FreeList, FreeListIter};
 
 const MAX_SERVERS_PER_CONNECT: usize = 32;
@@ -328,6 +329,7 @@ pub struct ConnectTokenBuilder<A: ToSocketAddrs> {
     public_server_addresses: A,
     internal_server_addresses: Option<AddressList>,
     user_data: [u8; USER_DATA_BYTES],
+    clock: NetworkClock,
 }
 
 impl<A: ToSocketAddrs> ConnectTokenBuilder<A> {
@@ -341,6 +343,7 @@ impl<A: ToSocketAddrs> ConnectTokenBuilder<A> {
             public_server_addresses: server_addresses,
             internal_server_addresses: None,
             user_data: [0; USER_DATA_BYTES],
+            clock: NetworkClock::default(),
         }
     }
     /// Sets the time in seconds that the token will be valid for.
@@ -357,6 +360,13 @@ impl<A: ToSocketAddrs> ConnectTokenBuilder<A> {
         self.timeout_seconds = timeout_seconds;
         self
     }
+    /// Sets the clock used to compute the create and expire timestamps of the token.
+    ///
+    /// The system clock is used by default.
+    pub fn clock(mut self, clock: NetworkClock) -> Self {
+        self.clock = clock;
+        self
+    }
     /// Sets the user data that will be added to the token, this can be any data you want.
     pub fn user_data(mut self, user_data: [u8; USER_DATA_BYTES]) -> Self {
         self.user_data = user_data;
@@ -377,7 +387,7 @@ impl<A: ToSocketAddrs> ConnectTokenBuilder<A> {
     /// Generates the token and consumes the builder.
     pub fn generate(self) -> Result<ConnectToken, Error> {
         // number of seconds since unix epoch
-        let now = utils::now();
+        let now = self.clock.unix_time().as_secs();
         let expire_timestamp = if self.expire_seconds < 0 {
             u64::MAX
         } else {
@@ -518,6 +528,7 @@ impl Bytes for ConnectToken {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::shared::clock::{Clock, MockClock};
 
     #[test]
     fn encrypt_decrypt_private_token() {
@@ -715,4 +726,21 @@ mod tests {
                 assert_eq!(have, expected);
             });
     }
+
+    #[test]
+    fn connect_token_builder_clock() {
+        let clock = MockClock::new();
+        clock.advance(std::time::Duration::from_secs(3600));
+        let connect_token = ConnectToken::build("127.0.0.1:12345", 1, 4, [0x42; PRIVATE_KEY_BYTES])
+            .expire_seconds(6)
+            .clock(NetworkClock::new(clock.clone()))
+            .generate()
+            .unwrap();
+
+        assert_eq!(connect_token.create_timestamp, clock.unix_time().as_secs());
+        assert_eq!(
+            connect_token.expire_timestamp,
+            connect_token.create_timestamp + 6
+        );
+    }
 }
@@ -1,7 +1,6 @@
 use bevy::prelude::Resource;
-use bevy::utils::HashMap;
+use bevy::utils::{HashMap, HashSet};
 use enum_dispatch::enum_dispatch;
-#[cfg(all(feature = "steam", not(target_family = "wasm")))]
 use parking_lot::RwLock;
 use serde::{Deserialize, Serialize};
 use std::fmt::Debug;
@@ -15,6 +14,7 @@ use crate::prelude::server::ServerTransport;
 #[cfg(all(feature = "steam", not(target_family = "wasm")))]
 use crate::prelude::LinkConditionerConfig;
 use crate::server::config::NetcodeConfig;
+use crate::server::events::DuplicateClientIdEvent;
 use crate::server::io::Io;
 use crate::transport::config::SharedIoConfig;
 
@@ -24,12 +24,59 @@ pub enum DeniedReason {
     ServerFull,
     Banned,
     InternalError,
+    /// A client with the same id is already connected
+    /// (see [`DuplicateIdPolicy`](crate::server::config::DuplicateIdPolicy))
     AlreadyConnected,
     TokenAlreadyUsed,
     InvalidToken,
+    /// The client and the server do not have the same protocol: they registered different
+    /// channels, components or messages, or assigned them different ids
+    ProtocolMismatch,
     Custom(String),
 }
 
+/// Reasons for a client to be disconnected from the server
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum DisconnectReason {
+    /// The server did not receive any packet from the client for too long
+    Timeout,
+    /// The client closed the connection
+    ClientRequested,
+    /// The server disconnected the client (or stopped), with the reason that was sent to the client, if any
+    ServerKicked(Option<String>),
+    /// The transport of the client's connection failed
+    TransportError,
+}
+
+/// Clients that are not allowed to connect to the server.
+///
+/// The connection requests of these clients are denied with [`DeniedReason::Banned`] during the handshake,
+/// before any state is allocated for the connection. The list is shared between the [`ServerConfig`](crate::prelude::server::ServerConfig)
+/// (so it can be filled before the server starts, and is kept when the server restarts) and the server connections,
+/// so the clones of a [`DenyList`] all refer to the same list.
+#[derive(Debug, Clone, Default)]
+pub struct DenyList(Arc<RwLock<HashSet<ClientId>>>);
+
+impl DenyList {
+    /// Add a client to the list. Returns false if it was already in the list
+    pub fn insert(&self, client_id: ClientId) -> bool {
+        self.0.write().insert(client_id)
+    }
+
+    /// Remove a client from the list. Returns false if it was not in the list
+    pub fn remove(&self, client_id: ClientId) -> bool {
+        self.0.write().remove(&client_id)
+    }
+
+    pub fn contains(&self, client_id: ClientId) -> bool {
+        self.0.read().contains(&client_id)
+    }
+
+    pub fn client_ids(&self) -> Vec<ClientId> {
+        self.0.read().iter().copied().collect()
+    }
+}
+
 /// Trait for handling connection requests from clients.
 pub trait ConnectionRequestHandler: Debug + Send + Sync {
     /// Handle a connection request from a client.
@@ -64,6 +111,16 @@ pub trait NetServer: Send + Sync {
     /// Is also responsible for adding the client to the list of new disconnections.
     fn disconnect(&mut self, client_id: ClientId) -> Result<(), ConnectionError>;
 
+    /// Disconnect a specific client, and send it the reason of the disconnection
+    /// (if the connection supports it)
+    fn disconnect_with_reason(
+        &mut self,
+        client_id: ClientId,
+        reason: String,
+    ) -> Result<(), ConnectionError> {
+        self.disconnect(client_id)
+    }
+
     /// Return the list of connected clients
     fn connected_client_ids(&self) -> Vec<ClientId>;
 
@@ -78,7 +135,18 @@ pub trait NetServer: Send + Sync {
 
     fn new_connections(&self) -> Vec<ClientId>;
 
-    fn new_disconnections(&self) -> Vec<ClientId>;
+    /// Clients that got disconnected during the last update, with the reason of the disconnection
+    fn new_disconnections(&self) -> Vec<(ClientId, DisconnectReason)>;
+
+    /// Clients that moved their session to a new transport path during the last update
+    fn new_migrations(&self) -> Vec<ClientId> {
+        vec![]
+    }
+
+    /// Connection requests received during the last update with the id of a client that was already connected
+    fn new_duplicate_connections(&self) -> Vec<DuplicateClientIdEvent> {
+        vec![]
+    }
 
     fn io(&self) -> Option<&Io>;
 
@@ -227,6 +295,23 @@ impl ServerConnections {
         )
     }
 
+    /// Disconnect a specific client, and send it the reason of the disconnection (for example "banned").
+    /// The client receives it in its [`DisconnectReason::Kicked`](crate::connection::client::DisconnectReason::Kicked).
+    ///
+    /// The reason cannot exceed 255 bytes with the netcode connection, and is not sent with Steam.
+    pub fn disconnect_with_reason(
+        &mut self,
+        client_id: ClientId,
+        reason: impl Into<String>,
+    ) -> Result<(), ConnectionError> {
+        let reason = reason.into();
+        self.client_server_map
+            .get(&client_id)
+            .map_or(Err(ConnectionError::ConnectionNotFound), |&server_idx| {
+                self.servers[server_idx].disconnect_with_reason(client_id, reason)
+            })
+    }
+
     /// Returns true if the server is currently listening for client packets
     pub(crate) fn is_listening(&self) -> bool {
         self.is_listening
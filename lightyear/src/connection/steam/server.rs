@@ -1,7 +1,8 @@
 use crate::connection::id::ClientId;
 use crate::connection::netcode::MAX_PACKET_SIZE;
 use crate::connection::server::{
-    ConnectionError, ConnectionRequestHandler, DefaultConnectionRequestHandler, NetServer,
+    ConnectionError, ConnectionRequestHandler, DefaultConnectionRequestHandler, DenyList,
+    DisconnectReason, NetServer,
 };
 use crate::packet::packet_builder::RecvPayload;
 use crate::prelude::LinkConditionerConfig;
@@ -76,8 +77,10 @@ pub struct Server {
     connections: HashMap<ClientId, NetConnection<ClientManager>>,
     packet_queue: VecDeque<(RecvPayload, ClientId)>,
     new_connections: Vec<ClientId>,
-    new_disconnections: Vec<ClientId>,
+    new_disconnections: Vec<(ClientId, DisconnectReason)>,
     conditioner: Option<LinkConditionerConfig>,
+    /// Clients whose connection requests are rejected
+    deny_list: DenyList,
 }
 
 impl Server {
@@ -114,8 +117,14 @@ impl Server {
             new_connections: Vec::new(),
             new_disconnections: Vec::new(),
             conditioner,
+            deny_list: DenyList::default(),
         })
     }
+
+    /// Set the list of clients whose connection requests are rejected
+    pub(crate) fn set_deny_list(&mut self, deny_list: DenyList) {
+        self.deny_list = deny_list;
+    }
 }
 
 impl NetServer for Server {
@@ -162,7 +171,8 @@ impl NetServer for Server {
         self.listen_socket = None;
         for (client_id, connection) in self.connections.drain() {
             let _ = connection.close(NetConnectionEnd::AppGeneric, None, true);
-            self.new_disconnections.push(client_id);
+            self.new_disconnections
+                .push((client_id, DisconnectReason::ServerKicked(None)));
         }
         info!("Steam socket has been closed.");
         Ok(())
@@ -173,7 +183,8 @@ impl NetServer for Server {
             ClientId::Steam(id) => {
                 if let Some(connection) = self.connections.remove(&client_id) {
                     let _ = connection.close(NetConnectionEnd::AppGeneric, None, true);
-                    self.new_disconnections.push(client_id);
+                    self.new_disconnections
+                        .push((client_id, DisconnectReason::ServerKicked(None)));
                 }
                 Ok(())
             }
@@ -220,9 +231,15 @@ impl NetServer for Server {
                             client_id,
                             event.end_reason()
                         );
+                        // the clients close the connection with `AppGeneric`, the other reasons
+                        // come from the steam transport
+                        let reason = match event.end_reason() {
+                            Some(NetConnectionEnd::AppGeneric) => DisconnectReason::ClientRequested,
+                            _ => DisconnectReason::TransportError,
+                        };
                         if let Some(connection) = self.connections.remove(&client_id) {
                             let _ = connection.close(NetConnectionEnd::AppGeneric, None, true);
-                            self.new_disconnections.push(client_id);
+                            self.new_disconnections.push((client_id, reason));
                         }
                     } else {
                         error!("Received disconnection attempt from invalid steam id");
@@ -238,6 +255,10 @@ impl NetServer for Server {
                         continue;
                     };
                     info!("Client with id: {:?} requesting connection!", steam_id);
+                    if self.deny_list.contains(ClientId::Steam(steam_id.raw())) {
+                        event.reject(NetConnectionEnd::AppGeneric, Some("Banned"));
+                        continue;
+                    }
                     if let Some(denied_reason) = self
                         .config
                         .connection_request_handler
@@ -292,7 +313,7 @@ impl NetServer for Server {
         self.new_connections.clone()
     }
 
-    fn new_disconnections(&self) -> Vec<ClientId> {
+    fn new_disconnections(&self) -> Vec<(ClientId, DisconnectReason)> {
         self.new_disconnections.clone()
     }
 
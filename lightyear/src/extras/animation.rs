@@ -0,0 +1,539 @@
+//! Sync the state of animations (or of any state machine driven by time) over the network.
+//!
+//! Replicating the local playback time of an animation doesn't work well: it changes every frame, and the
+//! predicted and interpolated entities are not on the same timeline as the server. Instead, the
+//! [`NetworkAnimation`] component only contains the clip that is playing and the [`Tick`] at which it started;
+//! it only changes when a new clip starts.
+//!
+//! The [`NetworkAnimationPlugin`] registers [`NetworkAnimation`] in the protocol and computes the local
+//! [`AnimationPlayback`] of each entity from the timeline of that entity:
+//! - predicted entities use the current client tick, so a clip that started at tick 100 on the server is at the
+//!   same offset on the predicted entity at tick 100. An animation triggered locally (for example by an input)
+//!   is predicted like any other component, and is rolled back if the server did not trigger it
+//! - interpolated entities use the interpolation tick. The clip is switched (never interpolated) once the
+//!   interpolation tick reaches the server update that changed it
+//! - the other entities (on the server, or entities that are not replicated) use the current tick
+//!
+//! ```rust,ignore
+//! // add the plugin on both the client and the server, after the lightyear plugins
+//! app.add_plugins(NetworkAnimationPlugin::default());
+//!
+//! // start the thruster animation on the tick where the input is applied
+//! fn thrust(tick_manager: Res<TickManager>, mut query: Query<&mut NetworkAnimation>) {
+//!     for mut animation in query.iter_mut() {
+//!         *animation = NetworkAnimation::new(THRUSTER_CLIP, tick_manager.tick()).looping();
+//!     }
+//! }
+//!
+//! // sample the clip on the client
+//! fn play(query: Query<&AnimationPlayback>) {
+//!     for playback in query.iter() {
+//!         let time = playback.time_in_clip(clip_duration(playback.clip));
+//!     }
+//! }
+//! ```
+use bevy::prelude::*;
+use bevy::utils::Duration;
+use serde::{Deserialize, Serialize};
+
+use crate::client::components::{ComponentSyncMode, Confirmed};
+use crate::client::config::ClientConfig;
+use crate::client::interpolation::Interpolated;
+use crate::client::run_conditions::is_synced;
+use crate::prelude::{ChannelDirection, Tick};
+use crate::protocol::component::AppComponentExt;
+use crate::server::config::ServerConfig;
+use crate::shared::tick_manager::TickManager;
+use crate::shared::time_manager::TimeManager;
+
+/// Replicated state of the animation played by an entity
+#[derive(Component, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Reflect)]
+pub struct NetworkAnimation {
+    /// Identifier of the clip
+    pub clip: u16,
+    /// Tick at which the clip started playing
+    pub start_tick: Tick,
+    /// Playback speed, in 1/256th of the normal speed (see [`NetworkAnimation::speed`])
+    pub speed: u16,
+    /// Combination of [`NetworkAnimation::LOOP`] and of user-defined flags
+    pub flags: u8,
+}
+
+impl NetworkAnimation {
+    /// The clip restarts from the beginning when it ends
+    pub const LOOP: u8 = 1;
+
+    /// Play `clip` from `start_tick`, at normal speed and without looping
+    pub fn new(clip: u16, start_tick: Tick) -> Self {
+        Self {
+            clip,
+            start_tick,
+            speed: 256,
+            flags: 0,
+        }
+    }
+
+    /// Set the playback speed. It is quantized to 1/256th, and clamped to `[0.0, 256.0[`
+    pub fn with_speed(mut self, speed: f32) -> Self {
+        self.speed = (speed * 256.0).round().clamp(0.0, u16::MAX as f32) as u16;
+        self
+    }
+
+    pub fn looping(mut self) -> Self {
+        self.flags |= Self::LOOP;
+        self
+    }
+
+    pub fn speed(&self) -> f32 {
+        self.speed as f32 / 256.0
+    }
+
+    pub fn is_looping(&self) -> bool {
+        self.flags & Self::LOOP != 0
+    }
+
+    /// Time elapsed in the clip at `tick` (plus `overstep`, the fraction of tick elapsed since `tick`),
+    /// taking the playback speed into account. This is zero before the clip started.
+    pub fn elapsed(&self, tick: Tick, overstep: f32, tick_duration: Duration) -> Duration {
+        let ticks = (tick - self.start_tick) as f64 + overstep as f64;
+        let nanos = tick_duration.as_nanos() as f64 * ticks * self.speed() as f64;
+        Duration::from_nanos(nanos.max(0.0).round() as u64)
+    }
+}
+
+/// Local playback state of the [`NetworkAnimation`] of an entity, updated every frame by the
+/// [`NetworkAnimationPlugin`] in [`PostUpdate`].
+#[derive(Component, Debug, Clone, Copy, PartialEq, Default, Reflect)]
+pub struct AnimationPlayback {
+    pub clip: u16,
+    /// Time elapsed since the clip started (it keeps growing after the end of the clip)
+    pub elapsed: Duration,
+    pub looping: bool,
+}
+
+impl AnimationPlayback {
+    /// Position in a clip of duration `clip_duration`: wraps around for looping clips, and stops
+    /// at the end of the clip otherwise
+    pub fn time_in_clip(&self, clip_duration: Duration) -> Duration {
+        if clip_duration.is_zero() {
+            return Duration::ZERO;
+        }
+        if self.looping {
+            Duration::from_nanos((self.elapsed.as_nanos() % clip_duration.as_nanos()) as u64)
+        } else {
+            self.elapsed.min(clip_duration)
+        }
+    }
+}
+
+/// Plugin that replicates [`NetworkAnimation`] and computes the [`AnimationPlayback`] of each entity.
+///
+/// It must be added to both the client and the server apps, after the [`ClientPlugins`](crate::prelude::client::ClientPlugins)
+/// or [`ServerPlugins`](crate::prelude::server::ServerPlugins).
+pub struct NetworkAnimationPlugin {
+    pub direction: ChannelDirection,
+}
+
+impl Default for NetworkAnimationPlugin {
+    fn default() -> Self {
+        Self {
+            direction: ChannelDirection::ServerToClient,
+        }
+    }
+}
+
+impl Plugin for NetworkAnimationPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<NetworkAnimation>()
+            .register_type::<AnimationPlayback>();
+        app.register_component::<NetworkAnimation>(self.direction)
+            .add_prediction(ComponentSyncMode::Full)
+            .add_interpolation(ComponentSyncMode::Full)
+            .add_interpolation_fn(snap);
+
+        let is_client = app.world().get_resource::<ClientConfig>().is_some();
+        let is_server = app.world().get_resource::<ServerConfig>().is_some();
+        if is_server {
+            app.add_systems(PostUpdate, (update_local_playback, remove_playback));
+        }
+        if is_client {
+            app.add_systems(
+                PostUpdate,
+                (update_client_playback.run_if(is_synced), remove_playback),
+            );
+        }
+    }
+}
+
+/// Animation states are never interpolated: the interpolated entity switches to the new state
+/// once the interpolation tick reaches the tick of the server update
+fn snap(start: &NetworkAnimation, end: &NetworkAnimation, t: f32) -> NetworkAnimation {
+    if t >= 1.0 {
+        *end
+    } else {
+        *start
+    }
+}
+
+fn set_playback(
+    commands: &mut Commands,
+    entity: Entity,
+    playback: Option<Mut<AnimationPlayback>>,
+    new_playback: AnimationPlayback,
+) {
+    match playback {
+        Some(mut playback) => {
+            playback.set_if_neq(new_playback);
+        }
+        None => {
+            commands.entity(entity).insert(new_playback);
+        }
+    }
+}
+
+fn playback_at(
+    animation: &NetworkAnimation,
+    tick: Tick,
+    overstep: f32,
+    tick_duration: Duration,
+) -> AnimationPlayback {
+    AnimationPlayback {
+        clip: animation.clip,
+        elapsed: animation.elapsed(tick, overstep, tick_duration),
+        looping: animation.is_looping(),
+    }
+}
+
+/// Compute the playback of the entities on the current tick
+fn update_local_playback(
+    mut commands: Commands,
+    tick_manager: Res<TickManager>,
+    time_manager: Res<TimeManager>,
+    mut query: Query<(Entity, &NetworkAnimation, Option<&mut AnimationPlayback>)>,
+) {
+    let tick = tick_manager.tick();
+    let overstep = time_manager.overstep();
+    let tick_duration = tick_manager.config.tick_duration;
+    for (entity, animation, playback) in query.iter_mut() {
+        let new_playback = playback_at(animation, tick, overstep, tick_duration);
+        set_playback(&mut commands, entity, playback, new_playback);
+    }
+}
+
+/// Compute the playback of the entities on the client, using the timeline of each entity:
+/// the current tick for predicted and local entities, and the interpolation tick for interpolated entities.
+///
+/// The confirmed entities don't have a playback, since they are only updated when a server update is received.
+fn update_client_playback(
+    mut commands: Commands,
+    tick_manager: Res<TickManager>,
+    time_manager: Res<TimeManager>,
+    connection: Res<crate::client::connection::ConnectionManager>,
+    mut query: Query<
+        (
+            Entity,
+            &NetworkAnimation,
+            Option<&mut AnimationPlayback>,
+            Has<Interpolated>,
+        ),
+        Without<Confirmed>,
+    >,
+) {
+    let tick = tick_manager.tick();
+    let overstep = time_manager.overstep();
+    let interpolation_tick = connection
+        .sync_manager
+        .interpolation_tick(tick_manager.as_ref());
+    let interpolation_overstep = connection
+        .sync_manager
+        .interpolation_overstep(tick_manager.as_ref());
+    let tick_duration = tick_manager.config.tick_duration;
+    for (entity, animation, playback, interpolated) in query.iter_mut() {
+        let new_playback = if interpolated {
+            playback_at(
+                animation,
+                interpolation_tick,
+                interpolation_overstep,
+                tick_duration,
+            )
+        } else {
+            playback_at(animation, tick, overstep, tick_duration)
+        };
+        set_playback(&mut commands, entity, playback, new_playback);
+    }
+}
+
+/// Remove the playback of the entities that stopped playing an animation
+fn remove_playback(
+    mut commands: Commands,
+    mut removed: RemovedComponents<NetworkAnimation>,
+    query: Query<(), (With<AnimationPlayback>, Without<NetworkAnimation>)>,
+) {
+    for entity in removed.read() {
+        if query.get(entity).is_ok() {
+            commands.entity(entity).remove::<AnimationPlayback>();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::client::{IoConfig, NetConfig};
+    use crate::prelude::server::{Replicate, SyncTarget};
+    use crate::prelude::{client, LinkConditionerConfig, NetworkTarget, SharedConfig, TickConfig};
+    use crate::tests::stepper::{BevyStepper, Step};
+
+    use super::*;
+
+    #[test]
+    fn test_elapsed() {
+        let tick_duration = Duration::from_millis(10);
+        let animation = NetworkAnimation::new(1, Tick(100));
+        assert_eq!(
+            animation.elapsed(Tick(110), 0.5, tick_duration),
+            Duration::from_millis(105)
+        );
+        // the clip didn't start yet
+        assert_eq!(
+            animation.elapsed(Tick(90), 0.0, tick_duration),
+            Duration::ZERO
+        );
+        let animation = animation.with_speed(0.5);
+        assert_eq!(animation.speed, 128);
+        assert_eq!(
+            animation.elapsed(Tick(110), 0.0, tick_duration),
+            Duration::from_millis(50)
+        );
+        // the start tick can be on the other side of the tick wrapping
+        let animation = NetworkAnimation::new(1, Tick(u16::MAX - 4));
+        assert_eq!(
+            animation.elapsed(Tick(5), 0.0, tick_duration),
+            Duration::from_millis(100)
+        );
+    }
+
+    #[test]
+    fn test_time_in_clip() {
+        let clip_duration = Duration::from_millis(300);
+        let playback = AnimationPlayback {
+            clip: 1,
+            elapsed: Duration::from_millis(700),
+            looping: false,
+        };
+        assert_eq!(playback.time_in_clip(clip_duration), clip_duration);
+        let playback = AnimationPlayback {
+            looping: true,
+            ..playback
+        };
+        assert_eq!(
+            playback.time_in_clip(clip_duration),
+            Duration::from_millis(100)
+        );
+    }
+
+    fn setup(latency: Duration) -> BevyStepper {
+        let frame_duration = Duration::from_millis(10);
+        let shared_config = SharedConfig {
+            tick: TickConfig::new(frame_duration),
+            ..Default::default()
+        };
+        let client_config = client::ClientConfig {
+            net: NetConfig::Netcode {
+                auth: Default::default(),
+                config: Default::default(),
+                io: IoConfig::default().with_conditioner(LinkConditionerConfig {
+                    incoming_latency: latency,
+                    incoming_jitter: Duration::default(),
+                    incoming_loss: 0.0,
+                    seed: None,
+                }),
+            },
+            // the updates are sent every tick, so the interpolation needs a minimum delay to have an end value
+            interpolation: client::InterpolationConfig {
+                delay: client::InterpolationDelay::default()
+                    .with_min_delay(Duration::from_millis(50)),
+                ..default()
+            },
+            ..default()
+        };
+        let mut stepper = BevyStepper::new(shared_config, client_config, frame_duration);
+        stepper
+            .client_app
+            .add_plugins(NetworkAnimationPlugin::default());
+        stepper
+            .server_app
+            .add_plugins(NetworkAnimationPlugin::default());
+        stepper.init();
+        stepper
+    }
+
+    /// Spawn an entity that is predicted and interpolated, and return the (server, predicted, interpolated) entities
+    fn spawn(stepper: &mut BevyStepper) -> (Entity, Entity, Entity) {
+        let tick = stepper.server_tick();
+        let server_entity = stepper
+            .server_app
+            .world_mut()
+            .spawn((
+                Replicate {
+                    sync: SyncTarget {
+                        prediction: NetworkTarget::All,
+                        interpolation: NetworkTarget::All,
+                    },
+                    ..default()
+                },
+                NetworkAnimation::new(0, tick),
+            ))
+            .id();
+        for _ in 0..100 {
+            stepper.frame_step();
+            let entities = stepper
+                .client_app
+                .world()
+                .resource::<client::ConnectionManager>()
+                .replication_receiver
+                .remote_entity_map
+                .get_local(server_entity)
+                .and_then(|confirmed| stepper.client_app.world().get::<Confirmed>(*confirmed))
+                .and_then(|confirmed| confirmed.predicted.zip(confirmed.interpolated));
+            if let Some((predicted, interpolated)) = entities {
+                return (server_entity, predicted, interpolated);
+            }
+        }
+        panic!("entity was not replicated to client");
+    }
+
+    fn assert_close(actual: Duration, expected: Duration, tolerance: Duration) {
+        let error = if actual > expected {
+            actual - expected
+        } else {
+            expected - actual
+        };
+        assert!(
+            error <= tolerance,
+            "actual: {actual:?}, expected: {expected:?}"
+        );
+    }
+
+    /// The predicted entity plays the clip on the client timeline, and the interpolated entity on the
+    /// interpolation timeline, so that a clip that started at tick `T` on the server is at the same
+    /// offset as on the server at tick `T + n` on both entities
+    #[test]
+    fn test_playback_offset() {
+        for latency_ms in [0, 50, 150] {
+            let mut stepper = setup(Duration::from_millis(latency_ms));
+            let tick_duration = stepper.tick_duration;
+            let (server_entity, predicted, interpolated) = spawn(&mut stepper);
+
+            let start_tick = stepper.server_tick();
+            stepper
+                .server_app
+                .world_mut()
+                .entity_mut(server_entity)
+                .insert(NetworkAnimation::new(1, start_tick));
+
+            let mut interpolated_started = false;
+            for _ in 0..100 {
+                stepper.frame_step();
+                let client_tick = stepper.client_tick();
+                let interpolation_tick = stepper.interpolation_tick();
+                let world = stepper.client_app.world();
+                let predicted_playback = world
+                    .get::<AnimationPlayback>(predicted)
+                    .expect("playback missing on the predicted entity");
+                if predicted_playback.clip == 1 {
+                    assert_close(
+                        predicted_playback.elapsed,
+                        tick_duration * (client_tick - start_tick) as u32,
+                        tick_duration,
+                    );
+                }
+
+                let Some(interpolated_playback) = world.get::<AnimationPlayback>(interpolated)
+                else {
+                    continue;
+                };
+                if interpolated_playback.clip == 1 {
+                    interpolated_started = true;
+                    // the clip only starts once the interpolation timeline reaches the start tick
+                    assert!(
+                        interpolation_tick >= start_tick,
+                        "latency: {latency_ms}ms, interpolation tick: {interpolation_tick:?}, start tick: {start_tick:?}"
+                    );
+                    assert_close(
+                        interpolated_playback.elapsed,
+                        tick_duration * (interpolation_tick - start_tick) as u32,
+                        tick_duration,
+                    );
+                } else {
+                    assert!(!interpolated_started, "latency: {latency_ms}ms");
+                }
+            }
+            assert_eq!(
+                stepper
+                    .client_app
+                    .world()
+                    .get::<AnimationPlayback>(predicted)
+                    .unwrap()
+                    .clip,
+                1,
+                "latency: {latency_ms}ms"
+            );
+            assert!(interpolated_started, "latency: {latency_ms}ms");
+        }
+    }
+
+    /// An animation triggered locally on the predicted entity that the server did not trigger is
+    /// rolled back to the server's animation
+    #[test]
+    fn test_mispredicted_animation_rollback() {
+        let mut stepper = setup(Duration::from_millis(50));
+        let (server_entity, predicted, _) = spawn(&mut stepper);
+
+        // the client predicts that clip 7 starts
+        let client_tick = stepper.client_tick();
+        stepper
+            .client_app
+            .world_mut()
+            .entity_mut(predicted)
+            .insert(NetworkAnimation::new(7, client_tick));
+        stepper.frame_step();
+        assert_eq!(
+            stepper
+                .client_app
+                .world()
+                .get::<AnimationPlayback>(predicted)
+                .unwrap()
+                .clip,
+            7
+        );
+
+        // the server starts clip 2 instead
+        let start_tick = stepper.server_tick();
+        stepper
+            .server_app
+            .world_mut()
+            .entity_mut(server_entity)
+            .insert(NetworkAnimation::new(2, start_tick));
+        for _ in 0..50 {
+            stepper.frame_step();
+        }
+        assert_eq!(
+            stepper
+                .client_app
+                .world()
+                .get::<NetworkAnimation>(predicted),
+            Some(&NetworkAnimation::new(2, start_tick))
+        );
+        let playback = stepper
+            .client_app
+            .world()
+            .get::<AnimationPlayback>(predicted)
+            .unwrap();
+        assert_eq!(playback.clip, 2);
+        assert_close(
+            playback.elapsed,
+            stepper.tick_duration * (stepper.client_tick() - start_tick) as u32,
+            stepper.tick_duration,
+        );
+    }
+}
@@ -0,0 +1,7 @@
+//! Optional plugins built on top of lightyear's core networking.
+//!
+//! These are not part of [`ServerPlugins`](crate::prelude::server::ServerPlugins) or
+//! [`ClientPlugins`](crate::prelude::client::ClientPlugins); add them to your app explicitly
+//! (after the lightyear plugins) if you need them.
+pub mod animation;
+pub mod relay;
@@ -0,0 +1,429 @@
+//! Relay messages from one client to other clients through the server.
+//!
+//! Chat, emotes or map pings all follow the same pattern: a client sends a message to the server,
+//! the server checks that the client is allowed to send it, and then forwards it to a set of other clients.
+//!
+//! The [`RelayPlugin`] implements this pattern for any message type `M`:
+//! - clients send a [`RelayedMessage<M>`] to the server, containing the message and the [`NetworkTarget`]
+//!   it should be relayed to
+//! - the server rate-limits each client (see [`RelayConfig`]) and runs the optional [`RelayFilter<M>`] hook,
+//!   which can reject the message, modify it or clamp its target
+//! - the server sends the message to the recipients, along with the sender's [`ClientId`] and the server [`Tick`]
+//! - recipients receive a [`RelayedEvent<M>`] bevy [`Event`]
+//!
+//! ```rust,ignore
+//! // add the plugin on both the client and the server, after the lightyear plugins
+//! app.add_plugins(RelayPlugin::<ChatChannel, ChatMessage>::new(RelayConfig::default()));
+//!
+//! // client: send a message to every other client
+//! connection_manager.send_message::<ChatChannel, _>(&RelayedMessage::new(
+//!     ChatMessage("hello".to_string()),
+//!     NetworkTarget::AllExceptSingle(local_client_id),
+//! ))?;
+//!
+//! // client: read relayed messages
+//! fn read_chat(mut events: EventReader<RelayedEvent<ChatMessage>>) {
+//!     for event in events.read() {
+//!         info!("{:?} said {:?} at tick {:?}", event.sender, event.message, event.tick);
+//!     }
+//! }
+//! ```
+use std::marker::PhantomData;
+
+use bevy::prelude::*;
+use bevy::utils::{Duration, HashMap};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, error};
+
+use crate::client::config::ClientConfig;
+use crate::client::run_conditions::is_connected;
+use crate::prelude::{Channel, ChannelDirection, ClientId, Message, NetworkTarget, Tick};
+use crate::protocol::message::AppMessageExt;
+use crate::server::config::ServerConfig;
+use crate::server::connection::ConnectionManager;
+use crate::server::run_conditions::is_started;
+use crate::shared::sets::{ClientMarker, InternalMainSet, ServerMarker};
+use crate::shared::tick_manager::TickManager;
+
+/// Message sent by a client to the server, asking the server to relay `message` to `target`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RelayedMessage<M> {
+    pub target: NetworkTarget,
+    pub message: M,
+}
+
+impl<M> RelayedMessage<M> {
+    pub fn new(message: M, target: NetworkTarget) -> Self {
+        Self { target, message }
+    }
+}
+
+/// Message relayed by the server to the recipients of a [`RelayedMessage`].
+///
+/// It is also emitted as a bevy [`Event`] on the recipients.
+#[derive(Event, Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RelayedEvent<M> {
+    /// Client that originally sent the message
+    pub sender: ClientId,
+    /// Server tick at which the message was relayed
+    pub tick: Tick,
+    pub message: M,
+}
+
+/// Per-client rate limit applied by the server to relayed messages.
+///
+/// Each client has a bucket of `burst` tokens that refills at `refill_per_second` tokens per second;
+/// relaying a message consumes one token. Messages received when the bucket is empty are dropped.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RelayConfig {
+    /// Maximum number of messages a client can send in a single burst
+    pub burst: u32,
+    /// Number of messages per second a client can send on average
+    pub refill_per_second: f32,
+}
+
+impl Default for RelayConfig {
+    fn default() -> Self {
+        Self {
+            burst: 10,
+            refill_per_second: 2.0,
+        }
+    }
+}
+
+/// Server-side hook run on every [`RelayedMessage`] that passed the rate limit.
+///
+/// The hook receives the sender, and can modify the message and its target (for example to
+/// censor profanity, or to restrict the recipients to the sender's team).
+/// Returning `false` drops the message.
+#[derive(Resource)]
+pub struct RelayFilter<M> {
+    filter: Box<dyn Fn(ClientId, &mut NetworkTarget, &mut M) -> bool + Send + Sync>,
+}
+
+impl<M> RelayFilter<M> {
+    pub fn new(
+        filter: impl Fn(ClientId, &mut NetworkTarget, &mut M) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            filter: Box::new(filter),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct TokenBucket {
+    tokens: f32,
+    last_refill: Duration,
+}
+
+/// Tracks the remaining relay budget of each client
+#[derive(Resource)]
+struct RelayRateLimiter<M> {
+    config: RelayConfig,
+    buckets: HashMap<ClientId, TokenBucket>,
+    marker: PhantomData<fn() -> M>,
+}
+
+impl<M> RelayRateLimiter<M> {
+    fn new(config: RelayConfig) -> Self {
+        Self {
+            config,
+            buckets: HashMap::default(),
+            marker: PhantomData,
+        }
+    }
+
+    /// Try to consume a token for the client. Returns false if the client is over its quota.
+    fn try_consume(&mut self, client_id: ClientId, now: Duration) -> bool {
+        let config = self.config;
+        let bucket = self.buckets.entry(client_id).or_insert(TokenBucket {
+            tokens: config.burst as f32,
+            last_refill: now,
+        });
+        let elapsed = now.saturating_sub(bucket.last_refill).as_secs_f32();
+        bucket.tokens =
+            (bucket.tokens + elapsed * config.refill_per_second).min(config.burst as f32);
+        bucket.last_refill = now;
+        if bucket.tokens < 1.0 {
+            return false;
+        }
+        bucket.tokens -= 1.0;
+        true
+    }
+}
+
+/// Plugin that relays messages of type `M` between clients, through the server, on channel `C`.
+///
+/// It must be added to both the client and the server apps, after the [`ClientPlugins`](crate::prelude::client::ClientPlugins)
+/// or [`ServerPlugins`](crate::prelude::server::ServerPlugins).
+pub struct RelayPlugin<C, M> {
+    pub config: RelayConfig,
+    marker: PhantomData<fn() -> (C, M)>,
+}
+
+impl<C, M> RelayPlugin<C, M> {
+    pub fn new(config: RelayConfig) -> Self {
+        Self {
+            config,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<C, M> Default for RelayPlugin<C, M> {
+    fn default() -> Self {
+        Self::new(RelayConfig::default())
+    }
+}
+
+impl<C: Channel, M: Message + Serialize + DeserializeOwned> Plugin for RelayPlugin<C, M> {
+    fn build(&self, app: &mut App) {
+        app.register_message::<RelayedMessage<M>>(ChannelDirection::ClientToServer);
+        app.register_message::<RelayedEvent<M>>(ChannelDirection::ServerToClient);
+
+        let is_client = app.world().get_resource::<ClientConfig>().is_some();
+        let is_server = app.world().get_resource::<ServerConfig>().is_some();
+        if is_server {
+            app.insert_resource(RelayRateLimiter::<M>::new(self.config));
+            app.add_systems(
+                PreUpdate,
+                (
+                    handle_disconnections::<M>,
+                    relay_messages::<C, M>.run_if(is_started),
+                )
+                    .chain()
+                    .after(InternalMainSet::<ServerMarker>::EmitEvents),
+            );
+        }
+        if is_client {
+            app.add_event::<RelayedEvent<M>>();
+            app.add_systems(
+                PreUpdate,
+                emit_relayed_events::<M>
+                    .after(InternalMainSet::<ClientMarker>::EmitEvents)
+                    .run_if(is_connected),
+            );
+        }
+    }
+}
+
+/// Stop tracking the rate limit of disconnected clients
+fn handle_disconnections<M: Message>(
+    mut limiter: ResMut<RelayRateLimiter<M>>,
+    mut events: EventReader<crate::server::events::DisconnectEvent>,
+) {
+    for event in events.read() {
+        limiter.buckets.remove(&event.client_id);
+    }
+}
+
+/// Read the [`RelayedMessage`]s received from clients, and relay the ones that pass the rate limit
+/// and the [`RelayFilter`] to their target
+fn relay_messages<C: Channel, M: Message + Serialize + DeserializeOwned>(
+    time: Res<Time>,
+    tick_manager: Res<TickManager>,
+    filter: Option<Res<RelayFilter<M>>>,
+    mut limiter: ResMut<RelayRateLimiter<M>>,
+    mut connection_manager: ResMut<ConnectionManager>,
+    mut messages: ResMut<Events<crate::server::events::MessageEvent<RelayedMessage<M>>>>,
+) {
+    let now = time.elapsed();
+    let tick = tick_manager.tick();
+    for event in messages.drain() {
+        let sender = event.context;
+        let RelayedMessage {
+            mut target,
+            mut message,
+        } = event.message;
+        if !limiter.try_consume(sender, now) {
+            debug!(
+                ?sender,
+                "Dropping relayed message {}: rate limit exceeded",
+                std::any::type_name::<M>()
+            );
+            continue;
+        }
+        if let Some(filter) = filter.as_ref() {
+            if !(filter.filter)(sender, &mut target, &mut message) {
+                debug!(
+                    ?sender,
+                    "Dropping relayed message {}: rejected by filter",
+                    std::any::type_name::<M>()
+                );
+                continue;
+            }
+        }
+        let relayed = RelayedEvent {
+            sender,
+            tick,
+            message,
+        };
+        let _ = connection_manager
+            .send_message_to_target::<C, RelayedEvent<M>>(&relayed, target)
+            .map_err(|e| error!("Could not relay message: {:?}", e));
+    }
+}
+
+/// Re-emit the [`RelayedEvent`]s received from the server as bevy [`Event`]s
+fn emit_relayed_events<M: Message>(
+    mut messages: ResMut<Events<crate::client::events::MessageEvent<RelayedEvent<M>>>>,
+    mut events: EventWriter<RelayedEvent<M>>,
+) {
+    events.send_batch(messages.drain().map(|event| event.message));
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::{client, LightyearTestPair};
+    use crate::tests::protocol::*;
+
+    use super::*;
+
+    const NUM_CLIENTS: usize = 3;
+
+    #[derive(Clone)]
+    struct RelayProtocolPlugin(RelayConfig);
+
+    impl Plugin for RelayProtocolPlugin {
+        fn build(&self, app: &mut App) {
+            app.add_plugins(ProtocolPlugin);
+            app.add_plugins(RelayPlugin::<Channel1, Message1>::new(self.0));
+        }
+    }
+
+    /// Server with 3 clients that relay [`Message1`]s. The [`RelayFilter`] can be inserted before connecting.
+    fn setup(config: RelayConfig) -> LightyearTestPair {
+        LightyearTestPair::builder()
+            .protocol(RelayProtocolPlugin(config))
+            .tick_duration(Duration::from_millis(10))
+            .clients(NUM_CLIENTS)
+            .build_disconnected()
+    }
+
+    fn send(pair: &mut LightyearTestPair, sender: usize, message: &str, target: NetworkTarget) {
+        pair.client_world_mut(sender)
+            .resource_mut::<client::ConnectionManager>()
+            .send_message::<Channel1, _>(&RelayedMessage::new(
+                Message1(message.to_string()),
+                target,
+            ))
+            .unwrap();
+    }
+
+    /// Step the apps and collect the relayed events received by each client
+    fn collect(pair: &mut LightyearTestPair) -> Vec<Vec<RelayedEvent<Message1>>> {
+        let mut received = vec![vec![]; NUM_CLIENTS];
+        for _ in 0..5 {
+            pair.frame_step();
+            for (index, events) in received.iter_mut().enumerate() {
+                events.extend(
+                    pair.client_world_mut(index)
+                        .resource_mut::<Events<RelayedEvent<Message1>>>()
+                        .drain(),
+                );
+            }
+        }
+        received
+    }
+
+    /// Messages received by a client, with their sender, sorted by sender
+    fn messages(events: &[RelayedEvent<Message1>]) -> Vec<(ClientId, &str)> {
+        let mut messages: Vec<_> = events
+            .iter()
+            .map(|event| (event.sender, event.message.0.as_str()))
+            .collect();
+        messages.sort_by_key(|(sender, _)| sender.to_bits());
+        messages
+    }
+
+    #[test]
+    fn test_relay_message() {
+        let mut pair = setup(RelayConfig::default());
+        pair.connect();
+        let [client_1, client_2, client_3] = [0, 1, 2].map(|index| pair.client_id(index));
+
+        // the first two clients send a message to everyone except themselves
+        let tick_before = pair.server_world().resource::<TickManager>().tick();
+        send(
+            &mut pair,
+            0,
+            "hello",
+            NetworkTarget::AllExceptSingle(client_1),
+        );
+        send(&mut pair, 1, "hi", NetworkTarget::AllExceptSingle(client_2));
+        let received = collect(&mut pair);
+        let tick_after = pair.server_world().resource::<TickManager>().tick();
+        assert_eq!(messages(&received[0]), vec![(client_2, "hi")]);
+        assert_eq!(messages(&received[1]), vec![(client_1, "hello")]);
+        assert_eq!(
+            messages(&received[2]),
+            vec![(client_1, "hello"), (client_2, "hi")]
+        );
+        // every recipient receives the server tick at which the message was relayed
+        for event in received.iter().flatten() {
+            assert!(tick_before <= event.tick && event.tick <= tick_after);
+            let copy = received[2]
+                .iter()
+                .find(|other| other.sender == event.sender)
+                .unwrap();
+            assert_eq!(event, copy);
+        }
+
+        // the sender can also target everyone, including itself
+        send(&mut pair, 2, "everyone", NetworkTarget::All);
+        let received = collect(&mut pair);
+        for events in &received {
+            assert_eq!(messages(events), vec![(client_3, "everyone")]);
+            assert_eq!(events[0], received[0][0]);
+        }
+    }
+
+    /// Each client has its own rate limit
+    #[test]
+    fn test_relay_rate_limit() {
+        let mut pair = setup(RelayConfig {
+            burst: 2,
+            refill_per_second: 0.0,
+        });
+        pair.connect();
+        for i in 0..5 {
+            send(&mut pair, 0, &i.to_string(), NetworkTarget::All);
+        }
+        send(&mut pair, 1, "hi", NetworkTarget::All);
+        let received = collect(&mut pair);
+        let senders: Vec<_> = messages(&received[2])
+            .into_iter()
+            .map(|(sender, _)| sender)
+            .collect();
+        assert_eq!(
+            senders,
+            vec![pair.client_id(0), pair.client_id(0), pair.client_id(1)]
+        );
+    }
+
+    #[test]
+    fn test_relay_filter() {
+        let mut pair = setup(RelayConfig::default());
+        let allowed = pair.client_id(1);
+        pair.server_app.insert_resource(RelayFilter::new(
+            move |_, target: &mut NetworkTarget, message: &mut Message1| {
+                // only allow messages to be relayed to the second client, and censor forbidden words
+                target.intersection(&NetworkTarget::Single(allowed));
+                if message.0.contains("spam") {
+                    return false;
+                }
+                message.0 = message.0.replace("darn", "****");
+                true
+            },
+        ));
+        pair.connect();
+        send(&mut pair, 0, "spam", NetworkTarget::All);
+        send(&mut pair, 0, "darn it", NetworkTarget::All);
+        let received = collect(&mut pair);
+        assert!(received[0].is_empty());
+        assert!(received[2].is_empty());
+        assert_eq!(messages(&received[1]), vec![(pair.client_id(0), "**** it")]);
+    }
+}
@@ -0,0 +1,280 @@
+/*! Entry points of the fuzzing targets of the `fuzz` crate
+
+Each function decodes bytes received from the network the same way a client or a server does.
+Whatever the input, they must not panic, allocate unbounded amounts of memory, or take a long time to return.
+
+This module is only compiled with the `fuzzing` feature. To fuzz one of the targets (`packet`, `channel_receiver`,
+`fragment_reassembly` or `netcode_server`), run from the root of the repository:
+
+```sh
+fuzz/run.sh packet
+```
+
+The script generates a seed corpus from real packets with [`seed_corpus`], then runs the target with
+`cargo +nightly fuzz run`, with memory and time limits so that OOMs and timeouts are reported like panics.
+*/
+use std::net::{Ipv4Addr, SocketAddr};
+
+use bevy::utils::Duration;
+use byteorder::ReadBytesExt;
+use bytes::Bytes;
+
+use crate::channel::builder::{ChannelContainer, ChannelSettings};
+use crate::channel::receivers::fragment_receiver::FragmentReceiver;
+use crate::channel::receivers::ChannelReceive;
+use crate::channel::senders::fragment_sender::FragmentSender;
+use crate::connection::netcode::packet::{KeepAlivePacket, Packet, RequestPacket};
+use crate::connection::netcode::{ConnectToken, Key, NetcodeServer, MAX_PACKET_SIZE};
+use crate::packet::header::PacketHeaderMode;
+use crate::packet::message::{FragmentData, MessageData, MessageId, ReceiveMessage, SingleData};
+use crate::packet::message_manager::MessageManager;
+use crate::packet::packet::FRAGMENT_SIZE;
+use crate::packet::priority_manager::PriorityConfig;
+use crate::prelude::{ChannelMode, ChannelRegistry, ReliableSettings, Tick};
+use crate::serialize::reader::Reader;
+use crate::serialize::ToBytes;
+use crate::shared::time_manager::WrappedTime;
+use crate::transport::error::Result as TransportResult;
+use crate::transport::PacketSender;
+
+const PROTOCOL_ID: u64 = 0x1234_5678_9abc_def0;
+const PRIVATE_KEY: Key = [7; 32];
+/// Timestamp at which the netcode server receives the packets
+const TIMESTAMP: u64 = 1_700_000_000;
+
+/// The channels of the internal protocol, which use every [`ChannelMode`]
+fn channel_registry(mode: PacketHeaderMode) -> ChannelRegistry {
+    let mut registry = ChannelRegistry::new(Duration::default());
+    registry.set_packet_header_mode(mode);
+    registry
+}
+
+fn header_mode(byte: u8) -> PacketHeaderMode {
+    if byte % 2 == 0 {
+        PacketHeaderMode::Standard
+    } else {
+        PacketHeaderMode::Compact
+    }
+}
+
+fn channel_mode(byte: u8) -> ChannelMode {
+    match byte % 6 {
+        0 => ChannelMode::UnorderedUnreliableWithAcks,
+        1 => ChannelMode::UnorderedUnreliable,
+        2 => ChannelMode::SequencedUnreliable,
+        3 => ChannelMode::UnorderedReliable(ReliableSettings::default()),
+        4 => ChannelMode::SequencedReliable(ReliableSettings::default()),
+        _ => ChannelMode::OrderedReliable(ReliableSettings::default()),
+    }
+}
+
+/// Receive a packet with a [`MessageManager`], then read all the messages that it contains.
+///
+/// The first byte selects the [`PacketHeaderMode`], the rest is the packet.
+pub fn packet(data: &[u8]) {
+    let Some((mode, packet)) = data.split_first() else {
+        return;
+    };
+    let mut manager = MessageManager::new(
+        &channel_registry(header_mode(*mode)),
+        1.5,
+        PriorityConfig::default(),
+    );
+    let _ = manager.recv_packet(Bytes::copy_from_slice(packet));
+    for channel in manager.channels.values_mut() {
+        while channel.receiver.read_message().is_some() {}
+    }
+}
+
+/// Buffer a list of messages in the receiver of a channel, and read them.
+///
+/// The first byte selects the [`ChannelMode`]. It is followed by a list of messages, each one is a byte
+/// that is even for a [`SingleData`] and odd for a [`FragmentData`], followed by the serialized message.
+pub fn channel_receiver(data: &[u8]) {
+    let Some((mode, messages)) = data.split_first() else {
+        return;
+    };
+    let mut channel = ChannelContainer::new(ChannelSettings {
+        mode: channel_mode(*mode),
+        ..Default::default()
+    });
+    let mut reader = Reader::from(messages.to_vec());
+    while let Ok(kind) = reader.read_u8() {
+        let message: MessageData = if kind % 2 == 0 {
+            let Ok(single) = SingleData::from_bytes(&mut reader) else {
+                return;
+            };
+            single.into()
+        } else {
+            let Ok(fragment) = FragmentData::from_bytes(&mut reader) else {
+                return;
+            };
+            fragment.into()
+        };
+        let _ = channel.receiver.buffer_recv(ReceiveMessage {
+            data: message,
+            remote_sent_tick: Tick(0),
+        });
+        while channel.receiver.read_message().is_some() {}
+    }
+}
+
+/// Reassemble a list of serialized [`FragmentData`] with a [`FragmentReceiver`]
+pub fn fragment_reassembly(data: &[u8]) {
+    let mut receiver = FragmentReceiver::new();
+    let mut reader = Reader::from(data.to_vec());
+    let mut time = 0;
+    while let Ok(fragment) = FragmentData::from_bytes(&mut reader) {
+        time += 10;
+        receiver.receive_fragment(fragment, Tick(0), Some(WrappedTime::new(time)));
+        if time % 1000 == 0 {
+            receiver.cleanup(WrappedTime::new(time - 500));
+        }
+    }
+}
+
+/// [`PacketSender`] that drops the packets sent by the netcode server
+struct NoopSender;
+
+impl PacketSender for NoopSender {
+    fn send(&mut self, _: &[u8], _: &SocketAddr) -> TransportResult<()> {
+        Ok(())
+    }
+}
+
+fn client_addr() -> SocketAddr {
+    SocketAddr::from((Ipv4Addr::LOCALHOST, 5000))
+}
+
+/// Process a packet received by a [`NetcodeServer`] from an unknown client
+pub fn netcode_server(data: &[u8]) {
+    let mut server = NetcodeServer::new(PROTOCOL_ID, PRIVATE_KEY).unwrap();
+    let mut buf = data.to_vec();
+    let _ = server.recv_packet(&mut buf, TIMESTAMP, client_addr(), &mut NoopSender);
+}
+
+/// Seed inputs for each target, built from real packets
+pub fn seed_corpus() -> Vec<(&'static str, Vec<Vec<u8>>)> {
+    vec![
+        ("packet", packet_seeds()),
+        ("channel_receiver", channel_receiver_seeds()),
+        ("fragment_reassembly", fragment_reassembly_seeds()),
+        ("netcode_server", netcode_server_seeds()),
+    ]
+}
+
+/// A small message, and a message big enough to be fragmented
+fn messages() -> [Bytes; 2] {
+    [
+        Bytes::from_static(b"hello"),
+        Bytes::from(vec![1; FRAGMENT_SIZE * 2 + 10]),
+    ]
+}
+
+fn packet_seeds() -> Vec<Vec<u8>> {
+    let mut seeds = vec![];
+    for mode in [0, 1] {
+        let registry = channel_registry(header_mode(mode));
+        let mut manager = MessageManager::new(&registry, 1.5, PriorityConfig::default());
+        for (kind, _) in registry.names() {
+            for message in messages() {
+                manager.buffer_send(message, kind).unwrap();
+            }
+        }
+        for tick in 0..2 {
+            for payload in manager.send_packets(Tick(tick)).unwrap() {
+                seeds.push([vec![mode], payload].concat());
+            }
+        }
+    }
+    seeds
+}
+
+fn fragments(message_id: MessageId) -> Vec<FragmentData> {
+    let [_, big] = messages();
+    FragmentSender::new()
+        .build_fragments(message_id, None, big)
+        .unwrap()
+}
+
+fn channel_receiver_seeds() -> Vec<Vec<u8>> {
+    (0..6)
+        .map(|mode| {
+            let mut seed = vec![mode];
+            for id in 0..3 {
+                seed.push(0);
+                SingleData::new(Some(MessageId(id)), messages()[0].clone())
+                    .to_bytes(&mut seed)
+                    .unwrap();
+            }
+            for fragment in fragments(MessageId(3)) {
+                seed.push(1);
+                fragment.to_bytes(&mut seed).unwrap();
+            }
+            seed
+        })
+        .collect()
+}
+
+fn fragment_reassembly_seeds() -> Vec<Vec<u8>> {
+    let mut in_order = vec![];
+    let mut interleaved = vec![];
+    for fragment in fragments(MessageId(0)) {
+        fragment.to_bytes(&mut in_order).unwrap();
+    }
+    for (first, second) in fragments(MessageId(1))
+        .into_iter()
+        .rev()
+        .zip(fragments(MessageId(2)))
+    {
+        first.to_bytes(&mut interleaved).unwrap();
+        second.to_bytes(&mut interleaved).unwrap();
+    }
+    vec![in_order, interleaved]
+}
+
+fn netcode_server_seeds() -> Vec<Vec<u8>> {
+    let token = ConnectToken::build(client_addr(), PROTOCOL_ID, 1, PRIVATE_KEY)
+        .expire_seconds(-1)
+        .generate()
+        .unwrap();
+    let request = RequestPacket::create(
+        token.protocol_id,
+        token.expire_timestamp,
+        token.nonce,
+        token.private_data,
+        0,
+    );
+    let keep_alive = KeepAlivePacket::create(1);
+    [(request, 0), (keep_alive, 1 << 40)]
+        .into_iter()
+        .map(|(packet, sequence): (Packet, u64)| {
+            let mut buf = [0; MAX_PACKET_SIZE];
+            let len = packet
+                .write(&mut buf, sequence, &PRIVATE_KEY, PROTOCOL_ID)
+                .unwrap();
+            buf[..len].to_vec()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The seeds are valid inputs for their target
+    #[test]
+    fn test_seed_corpus() {
+        for (target, seeds) in seed_corpus() {
+            assert!(!seeds.is_empty());
+            for seed in seeds {
+                match target {
+                    "packet" => packet(&seed),
+                    "channel_receiver" => channel_receiver(&seed),
+                    "fragment_reassembly" => fragment_reassembly(&seed),
+                    _ => netcode_server(&seed),
+                }
+            }
+        }
+    }
+}
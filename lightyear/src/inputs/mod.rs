@@ -1,8 +1,36 @@
 //! Handles networking client inputs
 
+use bevy::prelude::{Component, Reflect, ReflectComponent};
+use serde::{Deserialize, Serialize};
+
 // TODO: import this as inputs, check how xwt/party does it
 #[cfg(feature = "leafwing")]
 #[cfg_attr(docsrs, doc(cfg(feature = "leafwing")))]
 pub mod leafwing;
 
 pub mod native;
+
+/// Identifies one of the players that share a single connection (for example in splitscreen couch co-op).
+///
+/// Connections with a single player only use `LocalPlayerId(0)`.
+///
+/// On the server, [`ControlledBy::local_player`](crate::prelude::server::ControlledBy::local_player) specifies
+/// which local player of the client controls an entity. This component is then added on the client's entity,
+/// so that each local player can find the entity they control.
+#[derive(
+    Component,
+    Serialize,
+    Deserialize,
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    Hash,
+    PartialOrd,
+    Ord,
+    Reflect,
+)]
+#[reflect(Component)]
+pub struct LocalPlayerId(pub u8);
@@ -1,17 +1,32 @@
 use std::collections::VecDeque;
 use std::fmt::Debug;
+use std::io::Read;
 
 use bevy::prelude::{Reflect, Resource};
+use byteorder::{ReadBytesExt, WriteBytesExt};
 use serde::{Deserialize, Serialize};
+use smallvec::{smallvec, SmallVec};
 
+use crate::inputs::LocalPlayerId;
+use crate::serialize::reader::Reader;
+use crate::serialize::varint::{VarIntReadExt, VarIntWriteExt};
+use crate::serialize::writer::Writer;
+use crate::serialize::{SerializationError, ToBytes};
 use crate::shared::tick_manager::Tick;
 
 use super::UserAction;
 
+/// Inputs buffered for a single tick, in the order in which they were added.
+///
+/// Most ticks contain at most one input, so it is stored inline.
+pub type TickInputs<T> = SmallVec<[T; 1]>;
+
 #[derive(Resource, Debug)]
 pub struct InputBuffer<T> {
     pub buffer: VecDeque<Option<T>>,
     pub start_tick: Option<Tick>,
+    /// Last tick of the latest input message used to update the buffer
+    last_received_tick: Option<Tick>,
 }
 
 // TODO: add encode directive to encode even more efficiently
@@ -23,26 +38,159 @@ pub(crate) enum InputData<T> {
     Input(T),
 }
 
+impl<T: Clone> InputData<T> {
+    /// Returns the input of each tick of a stream of inputs that ends at `end_tick`, with the
+    /// [`InputData::SameAsPrecedent`] inputs replaced by the previous input
+    pub(crate) fn resolve(end_tick: Tick, inputs: &[Self]) -> Vec<(Tick, Option<T>)> {
+        let start_tick = end_tick - inputs.len() as u16 + 1;
+        let mut prev_value = None;
+        inputs
+            .iter()
+            .enumerate()
+            .map(|(delta, input)| {
+                match input {
+                    InputData::Absent => prev_value = None,
+                    InputData::SameAsPrecedent => {}
+                    InputData::Input(input) => prev_value = Some(input.clone()),
+                }
+                (start_tick + Tick(delta as u16), prev_value.clone())
+            })
+            .collect()
+    }
+}
+
 // TODO: use Mode to specify how to serialize a message (serde vs bitcode)! + can specify custom serialize function as well (similar to interpolation mode)
 #[derive(Serialize, Deserialize, Clone, PartialEq, Debug, Reflect)]
 /// Message that we use to send the client inputs to the server
 /// We will store the last N inputs starting from start_tick (in case of packet loss)
+///
+/// The message contains a separate stream of inputs for each local player of the client.
+///
+/// It is serialized with [`InputMessage::serialize_packed`]: a tick whose inputs are the same as the previous
+/// one only costs a bit.
 pub struct InputMessage<T> {
     pub(crate) end_tick: Tick,
-    // first element is tick end_tick-N+1, last element is end_tick
-    pub(crate) inputs: Vec<InputData<T>>,
+    // for each local player: first element is tick end_tick-N+1, last element is end_tick.
+    // A tick without inputs is sent as `InputData::Absent`
+    pub(crate) inputs: Vec<(LocalPlayerId, Vec<InputData<TickInputs<T>>>)>,
 }
 
 impl<T: UserAction> InputMessage<T> {
+    pub fn new(end_tick: Tick) -> Self {
+        Self {
+            end_tick,
+            inputs: vec![],
+        }
+    }
+
     pub fn is_empty(&self) -> bool {
-        if self.inputs.len() == 0 {
-            return true;
+        self.inputs.is_empty()
+    }
+
+    /// Add the inputs of a local player for the last `num_ticks` ticks up to `self.end_tick` included.
+    ///
+    /// Nothing is added if all the inputs are absent.
+    pub(crate) fn add_inputs(
+        &mut self,
+        local_player: LocalPlayerId,
+        num_ticks: u16,
+        input_buffer: &InputBuffer<TickInputs<T>>,
+    ) {
+        let mut inputs = Vec::new();
+        // start with the first value
+        let start_tick = Tick(self.end_tick.0) - num_ticks + 1;
+        inputs.push(
+            input_buffer
+                .get(start_tick)
+                .map_or(InputData::Absent, |input| InputData::Input(input.clone())),
+        );
+        // keep track of the previous value to avoid sending the same value multiple times
+        let mut prev_value_idx = 0;
+        for delta in 1..num_ticks {
+            let tick = start_tick + Tick(delta);
+            // safe because we keep pushing elements
+            let value = input_buffer
+                .get(tick)
+                .map_or(InputData::Absent, |input| InputData::Input(input.clone()));
+            // safe before prev_value_idx is always present
+            if inputs.get(prev_value_idx).unwrap() == &value {
+                inputs.push(InputData::SameAsPrecedent);
+            } else {
+                prev_value_idx = inputs.len();
+                inputs.push(value);
+            }
+        }
+        // all inputs are absent
+        if inputs[0] == InputData::Absent
+            && inputs[1..].iter().all(|x| x == &InputData::SameAsPrecedent)
+        {
+            return;
         }
-        let mut iter = self.inputs.iter();
-        if iter.next().unwrap() == &InputData::Absent {
-            return iter.all(|x| x == &InputData::SameAsPrecedent);
+        self.inputs.push((local_player, inputs));
+    }
+
+    /// Serialize the message with a bitmask for each local player, which marks the ticks whose inputs follow.
+    ///
+    /// The ticks with the same inputs as the previous tick are only encoded by their bit in the bitmask.
+    pub(crate) fn serialize_packed(
+        message: &Self,
+        writer: &mut Writer,
+    ) -> Result<(), SerializationError> {
+        message.end_tick.to_bytes(writer)?;
+        writer.write_varint(message.inputs.len() as u64)?;
+        for (local_player, inputs) in &message.inputs {
+            writer.write_u8(local_player.0)?;
+            writer.write_varint(inputs.len() as u64)?;
+            for chunk in inputs.chunks(8) {
+                let mask = chunk
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, input)| !matches!(input, InputData::SameAsPrecedent))
+                    .fold(0u8, |mask, (i, _)| mask | (1 << i));
+                writer.write_u8(mask)?;
+            }
+            for input in inputs {
+                let value = match input {
+                    InputData::SameAsPrecedent => continue,
+                    InputData::Absent => None,
+                    InputData::Input(value) => Some(value),
+                };
+                bincode::serde::encode_into_std_write(value, writer, bincode::config::standard())?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Deserialize a message serialized with [`InputMessage::serialize_packed`]
+    pub(crate) fn deserialize_packed(reader: &mut Reader) -> Result<Self, SerializationError> {
+        let end_tick = Tick::from_bytes(reader)?;
+        let num_players = reader.read_varint()?;
+        if num_players > u8::MAX as u64 + 1 {
+            return Err(SerializationError::InvalidValue);
+        }
+        let mut message = Self::new(end_tick);
+        for _ in 0..num_players {
+            let local_player = LocalPlayerId(reader.read_u8()?);
+            let len = reader.read_varint()?;
+            if len > u16::MAX as u64 {
+                return Err(SerializationError::InvalidValue);
+            }
+            let len = len as usize;
+            let mut masks = vec![0; len.div_ceil(8)];
+            reader.read_exact(&mut masks)?;
+            let mut inputs = Vec::with_capacity(len);
+            for i in 0..len {
+                if masks[i / 8] & (1 << (i % 8)) == 0 {
+                    inputs.push(InputData::SameAsPrecedent);
+                    continue;
+                }
+                let value: Option<TickInputs<T>> =
+                    bincode::serde::decode_from_std_read(reader, bincode::config::standard())?;
+                inputs.push(value.map_or(InputData::Absent, InputData::Input));
+            }
+            message.inputs.push((local_player, inputs));
         }
-        false
+        Ok(message)
     }
 }
 
@@ -52,6 +200,7 @@ impl<T> Default for InputBuffer<T> {
             // buffer: SequenceBuffer::new(),
             buffer: VecDeque::new(),
             start_tick: None,
+            last_received_tick: None,
             // end_tick: Tick(0),
         }
     }
@@ -103,6 +252,20 @@ impl<T: UserAction> InputBuffer<T> {
             .as_ref()
     }
 
+    pub(crate) fn get_mut(&mut self, tick: Tick) -> Option<&mut T> {
+        let start_tick = self.start_tick?;
+        if self.buffer.is_empty() {
+            return None;
+        }
+        if tick < start_tick || tick > start_tick + (self.buffer.len() as i16 - 1) {
+            return None;
+        }
+        self.buffer
+            .get_mut((tick - start_tick) as usize)
+            .unwrap()
+            .as_mut()
+    }
+
     pub(crate) fn set(&mut self, tick: Tick, value: Option<T>) {
         let Some(start_tick) = self.start_tick else {
             // initialize the buffer
@@ -126,26 +289,36 @@ impl<T: UserAction> InputBuffer<T> {
         *self.buffer.get_mut((tick - start_tick) as usize).unwrap() = value;
     }
 
-    /// We received a new input message from the user, and use it to update the input buffer
-    /// TODO: should we keep track of which inputs in the input buffer are absent and only update those?
-    ///  The current tick is the current server tick, no need to update the buffer for ticks that are older than that
-    pub(crate) fn update_from_message(&mut self, message: InputMessage<T>) {
-        let message_start_tick = Tick(message.end_tick.0) - message.inputs.len() as u16 + 1;
+    /// We received a new input message from the user, and use the inputs of one of its local players
+    /// to update the input buffer.
+    ///
+    /// The messages contain the inputs of the last ticks for redundancy: the ticks that were already received
+    /// in a previous message are skipped. A message that ends before the previous one (because it was reordered,
+    /// or because the client tick snapped back) is applied entirely.
+    pub(crate) fn update_from_message(&mut self, end_tick: Tick, inputs: Vec<InputData<T>>) {
+        let message_start_tick = Tick(end_tick.0) - inputs.len() as u16 + 1;
+        let received_until = self.last_received_tick.filter(|tick| *tick <= end_tick);
+        self.last_received_tick = Some(end_tick);
         let mut prev_value = None;
 
-        for (delta, input) in message.inputs.into_iter().enumerate() {
+        for (delta, input) in inputs.into_iter().enumerate() {
             let tick = message_start_tick + Tick(delta as u16);
+            let already_received = received_until.is_some_and(|received| tick <= received);
             match input {
                 InputData::Absent => {
                     prev_value = None;
-                    self.set(tick, None);
+                    if !already_received {
+                        self.set(tick, None);
+                    }
                 }
                 InputData::SameAsPrecedent => {
-                    self.set(tick, prev_value.clone());
+                    if !already_received {
+                        self.set(tick, prev_value.clone());
+                    }
                 }
                 InputData::Input(input) => {
                     prev_value = Some(input);
-                    if self.get(tick) == prev_value.as_ref() {
+                    if already_received || self.get(tick) == prev_value.as_ref() {
                         continue;
                     } else {
                         self.set(tick, prev_value.clone());
@@ -154,34 +327,16 @@ impl<T: UserAction> InputBuffer<T> {
             }
         }
     }
+}
 
-    // Convert the last N ticks up to end_tick included into a compressed message that we can send to the server
-    // Return None if the last N inputs are all Absent
-    pub(crate) fn create_message(&self, end_tick: Tick, num_ticks: u16) -> InputMessage<T> {
-        let mut inputs = Vec::new();
-        // start with the first value
-        let start_tick = Tick(end_tick.0) - num_ticks + 1;
-        inputs.push(
-            self.get(start_tick)
-                .map_or(InputData::Absent, |input| InputData::Input(input.clone())),
-        );
-        // keep track of the previous value to avoid sending the same value multiple times
-        let mut prev_value_idx = 0;
-        for delta in 1..num_ticks {
-            let tick = start_tick + Tick(delta);
-            // safe because we keep pushing elements
-            let value = self
-                .get(tick)
-                .map_or(InputData::Absent, |input| InputData::Input(input.clone()));
-            // safe before prev_value_idx is always present
-            if inputs.get(prev_value_idx).unwrap() == &value {
-                inputs.push(InputData::SameAsPrecedent);
-            } else {
-                prev_value_idx = inputs.len();
-                inputs.push(value);
-            }
+impl<T: UserAction> InputBuffer<TickInputs<T>> {
+    /// Add an input for the given tick, after the inputs that were already buffered for that tick
+    pub(crate) fn push(&mut self, tick: Tick, input: T) {
+        if let Some(inputs) = self.get_mut(tick) {
+            inputs.push(input);
+            return;
         }
-        InputMessage { inputs, end_tick }
+        self.set(tick, Some(smallvec![input]));
     }
 }
 
@@ -209,51 +364,72 @@ mod tests {
         assert_eq!(input_buffer.buffer.len(), 0);
     }
 
+    #[test]
+    fn test_push_multiple_inputs() {
+        let mut input_buffer: InputBuffer<TickInputs<i32>> = InputBuffer::default();
+
+        input_buffer.push(Tick(4), 0);
+        input_buffer.push(Tick(4), 1);
+        input_buffer.push(Tick(6), 2);
+
+        assert_eq!(input_buffer.get(Tick(4)), Some(&smallvec![0, 1]));
+        assert_eq!(input_buffer.get(Tick(5)), None);
+        assert_eq!(input_buffer.get(Tick(6)), Some(&smallvec![2]));
+        assert_eq!(input_buffer.pop(Tick(4)), Some(smallvec![0, 1]));
+    }
+
     #[test]
     fn test_create_message() {
-        let mut input_buffer = InputBuffer::default();
+        let mut input_buffer: InputBuffer<TickInputs<i32>> = InputBuffer::default();
 
-        input_buffer.set(Tick(4), Some(0));
-        input_buffer.set(Tick(6), Some(1));
-        input_buffer.set(Tick(7), Some(1));
+        input_buffer.push(Tick(4), 0);
+        input_buffer.push(Tick(6), 1);
+        input_buffer.push(Tick(6), 2);
+        input_buffer.push(Tick(7), 1);
+        input_buffer.push(Tick(7), 2);
 
-        let message = input_buffer.create_message(Tick(10), 8);
+        let mut message = InputMessage::new(Tick(10));
+        message.add_inputs(LocalPlayerId(0), 8, &input_buffer);
         assert_eq!(
             message,
             InputMessage {
                 end_tick: Tick(10),
-                inputs: vec![
-                    InputData::Absent,
-                    InputData::Input(0),
-                    InputData::Absent,
-                    InputData::Input(1),
-                    InputData::SameAsPrecedent,
-                    InputData::Absent,
-                    InputData::SameAsPrecedent,
-                    InputData::SameAsPrecedent,
-                ],
+                inputs: vec![(
+                    LocalPlayerId(0),
+                    vec![
+                        InputData::Absent,
+                        InputData::Input(smallvec![0]),
+                        InputData::Absent,
+                        InputData::Input(smallvec![1, 2]),
+                        InputData::SameAsPrecedent,
+                        InputData::Absent,
+                        InputData::SameAsPrecedent,
+                        InputData::SameAsPrecedent,
+                    ]
+                )],
             }
         );
+
+        // the inputs of a local player are not added if they are all absent
+        message.add_inputs(LocalPlayerId(1), 8, &InputBuffer::default());
+        assert_eq!(message.inputs.len(), 1);
     }
 
     #[test]
     fn test_update_from_message() {
         let mut input_buffer = InputBuffer::default();
 
-        let message = InputMessage {
-            end_tick: Tick(20),
-            inputs: vec![
-                InputData::Absent,
-                InputData::Input(0),
-                InputData::Absent,
-                InputData::Input(1),
-                InputData::SameAsPrecedent,
-                InputData::Absent,
-                InputData::SameAsPrecedent,
-                InputData::SameAsPrecedent,
-            ],
-        };
-        input_buffer.update_from_message(message);
+        let inputs = vec![
+            InputData::Absent,
+            InputData::Input(0),
+            InputData::Absent,
+            InputData::Input(1),
+            InputData::SameAsPrecedent,
+            InputData::Absent,
+            InputData::SameAsPrecedent,
+            InputData::SameAsPrecedent,
+        ];
+        input_buffer.update_from_message(Tick(20), inputs);
 
         assert_eq!(input_buffer.get(Tick(20)), None);
         assert_eq!(input_buffer.get(Tick(19)), None);
@@ -264,4 +440,92 @@ mod tests {
         assert_eq!(input_buffer.get(Tick(14)), Some(&0));
         assert_eq!(input_buffer.get(Tick(13)), None);
     }
+
+    #[test]
+    fn test_resolve_input_data() {
+        let inputs = vec![
+            InputData::SameAsPrecedent,
+            InputData::Input(1),
+            InputData::SameAsPrecedent,
+            InputData::Absent,
+            InputData::Input(2),
+        ];
+        assert_eq!(
+            InputData::resolve(Tick(10), &inputs),
+            vec![
+                (Tick(6), None),
+                (Tick(7), Some(1)),
+                (Tick(8), Some(1)),
+                (Tick(9), None),
+                (Tick(10), Some(2)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_update_from_message_skips_received_ticks() {
+        let mut input_buffer = InputBuffer::default();
+        input_buffer.update_from_message(Tick(10), vec![InputData::Input(0), InputData::Input(1)]);
+        // the server overrides the input of tick 10 (for example with `InputBuffers::correct_input`)
+        input_buffer.set(Tick(10), Some(5));
+
+        // the redundant copy of tick 10 is skipped, tick 11 is new
+        input_buffer.update_from_message(Tick(11), vec![InputData::Input(1), InputData::Input(2)]);
+        assert_eq!(input_buffer.get(Tick(10)), Some(&5));
+        assert_eq!(input_buffer.get(Tick(11)), Some(&2));
+
+        // a duplicated message is skipped entirely
+        input_buffer.set(Tick(11), Some(6));
+        input_buffer.update_from_message(Tick(11), vec![InputData::Input(1), InputData::Input(2)]);
+        assert_eq!(input_buffer.get(Tick(11)), Some(&6));
+
+        // the client tick snapped back: the message is applied entirely
+        input_buffer.update_from_message(Tick(10), vec![InputData::Input(3), InputData::Input(4)]);
+        assert_eq!(input_buffer.get(Tick(9)), Some(&3));
+        assert_eq!(input_buffer.get(Tick(10)), Some(&4));
+    }
+
+    #[test]
+    fn test_serialize_packed() {
+        let mut input_buffer: InputBuffer<TickInputs<i32>> = InputBuffer::default();
+        input_buffer.push(Tick(4), 0);
+        input_buffer.push(Tick(6), 1);
+        input_buffer.push(Tick(6), 2);
+        for tick in 7..20 {
+            input_buffer.push(Tick(tick), 3);
+        }
+        let mut message = InputMessage::new(Tick(19));
+        message.add_inputs(LocalPlayerId(0), 20, &input_buffer);
+        message.add_inputs(LocalPlayerId(2), 3, &input_buffer);
+
+        let mut writer = Writer::default();
+        InputMessage::serialize_packed(&message, &mut writer).unwrap();
+        let bytes = writer.split();
+        let mut reader = Reader::from(bytes.clone());
+        assert_eq!(
+            InputMessage::deserialize_packed(&mut reader).unwrap(),
+            message
+        );
+
+        assert!(
+            bytes.len()
+                < bincode::serde::encode_to_vec(&message, bincode::config::standard())
+                    .unwrap()
+                    .len()
+        );
+
+        // the ticks with the same inputs as the previous tick only cost their bit in the bitmask
+        let mut input_buffer: InputBuffer<TickInputs<i32>> = InputBuffer::default();
+        for tick in 0..100 {
+            input_buffer.push(Tick(tick), 3);
+        }
+        let packed_len = |num_ticks: u16| {
+            let mut message = InputMessage::new(Tick(99));
+            message.add_inputs(LocalPlayerId(0), num_ticks, &input_buffer);
+            let mut writer = Writer::default();
+            InputMessage::serialize_packed(&message, &mut writer).unwrap();
+            writer.split().len()
+        };
+        assert_eq!(packed_len(56) - packed_len(8), 48 / 8);
+    }
 }
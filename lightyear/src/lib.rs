@@ -181,51 +181,118 @@ pub(crate) mod _internal {
 
 /// Prelude containing commonly used types
 pub mod prelude {
-    pub use lightyear_macros::Channel;
+    pub use bevy::ecs::entity::{EntityMapper, MapEntities};
+    pub use lightyear_macros::{Channel, KeyedDiffable, Lerp, MapEntities, Quantize, Versioned};
     pub use serde::{Deserialize, Serialize};
 
     pub use crate::channel::builder::{
-        Channel, ChannelBuilder, ChannelContainer, ChannelDirection, ChannelMode, ChannelSettings,
-        InputChannel, ReliableSettings,
+        BufferLimits, Channel, ChannelBuilder, ChannelContainer, ChannelDirection, ChannelMode,
+        ChannelOccupancy, ChannelSettings, ChannelSettingsOverride, InputChannel, ReliableSettings,
+    };
+    pub use crate::channel::flow_control::FlowControlStats;
+    pub use crate::channel::stats::{ChannelDiagnosticsPlugin, ChannelStats};
+    pub use crate::channel::transfer::{
+        TransferDirection, TransferHandle, TransferOutcome, TransferProgress,
     };
     pub use crate::client::prediction::prespawn::PreSpawnedPlayerObject;
     pub use crate::connection::id::ClientId;
     pub use crate::connection::netcode::{generate_key, ConnectToken, Key};
     #[cfg(feature = "leafwing")]
     pub use crate::inputs::leafwing::{input_message::InputMessage, LeafwingUserAction};
-    pub use crate::inputs::native::UserAction;
+    pub use crate::inputs::native::{TickInputs, UserAction};
+    pub use crate::inputs::LocalPlayerId;
     pub use crate::packet::error::PacketError;
-    pub use crate::packet::message::Message;
+    pub use crate::packet::header::PacketHeaderMode;
+    pub use crate::packet::message::{Message, MessageId};
+    pub use crate::packet::receive_queue::{
+        OverflowPolicy, ReceiveQueueConfig, ReceiveQueueDiagnosticsPlugin, ReceiveQueueStats,
+    };
     pub use crate::protocol::channel::{AppChannelExt, ChannelKind, ChannelRegistry};
-    pub use crate::protocol::component::{AppComponentExt, ComponentRegistry, Linear};
+    pub use crate::protocol::component::{
+        AppComponentExt, ComponentKind, ComponentRegistry, Lerp, Linear,
+    };
     pub use crate::protocol::message::{AppMessageExt, MessageRegistry};
+    pub use crate::protocol::registry::{AppNetIdExt, NetIdReport};
     pub use crate::protocol::serialize::AppSerializeExt;
+    pub use crate::serialize::quantize::{Quantization, Quantize};
+    pub use crate::serialize::versioned::Versioned;
+    pub use crate::server::relevance::hint::{InterestHintOutcome, NetworkEntityId};
+    pub use crate::shared::action::{ActionConfig, ActionId, ActionTracker, ActionVerdict};
+    #[cfg(feature = "mock_time")]
+    pub use crate::shared::clock::MockClock;
+    pub use crate::shared::clock::{Clock, NetworkClock, RealClock};
     pub use crate::shared::config::{Mode, SharedConfig};
+    pub use crate::shared::config_check::{
+        AppConfigCheckExt, ConfigIssue, ConfigReport, ConfigSeverity,
+    };
+    pub use crate::shared::connection_stats::ConnectionStats;
+    #[cfg(any(test, feature = "test_utils"))]
+    pub use crate::test_utils::{LightyearTestPair, LightyearTestPairBuilder};
+    pub use crate::shared::input::jitter::{InputDelayAdvice, InputMarginStats};
     #[cfg(feature = "leafwing")]
     pub use crate::shared::input::leafwing::LeafwingInputPlugin;
     pub use crate::shared::input::native::InputPlugin;
+    pub use crate::shared::message_group::SendGroup;
+    pub use crate::shared::timeline_message::{TimelineDelivery, VisualTimeline};
+    pub use crate::shared::network_time::{NetworkTime, NetworkTimeConfig};
     pub use crate::shared::ping::manager::PingConfig;
     pub use crate::shared::plugin::{NetworkIdentity, SharedPlugin};
+    pub use crate::shared::request::{
+        AppRequestExt, Request, RequestConfig, RequestError, RequestHandle, RequestId,
+        ResponseSender,
+    };
+    pub use crate::shared::hooks::{
+        AppNetworkHookExt, NetworkHookPoint, PostJoinSnapshot, PostRollback, PostSync,
+        PreReplicationSend,
+    };
+    pub use crate::shared::replication::changes::{
+        ReplicationChange, ReplicationChangeFilter, ReplicationChangeType, ReplicationChangesPlugin,
+    };
+    pub use crate::shared::replication::classes::{
+        ReplicationClass, ReplicationClassQuotas, ReplicationClassStats,
+    };
     pub use crate::shared::replication::components::{
         DeltaCompression, DisabledComponent, NetworkRelevanceMode, OverrideTargetComponent,
         PrePredicted, ReplicateHierarchy, ReplicateOnceComponent, Replicated, Replicating,
         ReplicationGroup, ReplicationTarget, ShouldBePredicted, TargetEntity,
     };
-    pub use crate::shared::replication::entity_map::RemoteEntityMap;
+    pub use crate::shared::replication::entity_map::{MapEntityField, RemoteEntityMap};
+    pub use crate::shared::replication::frequency::ReplicationMode;
+    pub use crate::shared::replication::handoff::ReplicatedHandoff;
     pub use crate::shared::replication::hierarchy::ParentSync;
+    pub use crate::shared::replication::keyed::{KeyedChange, KeyedDiffable};
+    pub use crate::shared::replication::keyframe::KeyframeConfig;
+    pub use crate::shared::replication::lifetime::ReplicatedLifetime;
+    pub use crate::shared::replication::limits::{
+        ReplicationLimit, ReplicationLimitStats, ReplicationLimits,
+    };
     pub use crate::shared::replication::network_target::NetworkTarget;
+    pub use crate::shared::replication::parallel::ParallelApplyConfig;
     pub use crate::shared::replication::plugin::ReplicationConfig;
     pub use crate::shared::replication::plugin::SendUpdatesMode;
+    pub use crate::shared::replication::prespawn_ids::{
+        PreSpawnIdAllocator, PreSpawnIdConfig, PreSpawnIdError, PreSpawnIdRange,
+    };
     pub use crate::shared::replication::resources::{
         ReplicateResourceExt, ReplicateResourceMetadata, StopReplicateResourceExt,
     };
     pub use crate::shared::run_conditions::*;
+    pub use crate::shared::serialized_size::{
+        SerializedSizeConfig, SerializedSizeDiagnosticsPlugin, SerializedSizeStats, SerializedSizes,
+    };
+    pub use crate::shared::session_summary::{RttSummary, SessionSummary};
     pub use crate::shared::sets::{FixedUpdateSet, MainSet};
     pub use crate::shared::tick_manager::TickManager;
     pub use crate::shared::tick_manager::{Tick, TickConfig};
     pub use crate::shared::time_manager::TimeManager;
-    pub use crate::transport::middleware::compression::CompressionConfig;
-    pub use crate::transport::middleware::conditioner::LinkConditionerConfig;
+    pub use crate::shared::warnings::{NetworkWarning, NetworkWarnings};
+    pub use crate::transport::middleware::compression::{
+        CompressionAlgorithm, CompressionCapabilities, CompressionConfig,
+    };
+    pub use crate::transport::middleware::conditioner::{
+        LinkConditionerConfig, NetworkPreset, TimeVaryingConditioner,
+    };
+    pub use crate::utils::fixed::{Fixed32, FixedVec2, FixedVec3};
 
     mod rename {
         pub use crate::client::events::ComponentInsertEvent as ClientComponentInsertEvent;
@@ -253,73 +320,128 @@ pub mod prelude {
     pub use rename::*;
 
     pub mod client {
+        pub use crate::client::apply::{ComponentApplyFn, MessageApplyFn};
+        pub use crate::client::cleanup::{CleanupAction, CleanupCause, ClientCleanupPolicy};
         pub use crate::client::components::{
             ComponentSyncMode, Confirmed, LerpFn, SyncComponent, SyncMetadata,
         };
         pub use crate::client::config::{ClientConfig, NetcodeConfig, PacketConfig};
-        pub use crate::client::connection::ConnectionManager;
-        pub use crate::client::error::ClientError;
+        pub use crate::client::connect::{
+            ConnectAttempt, ConnectConfig, ConnectRetryConfig, ConnectTokenProvider,
+        };
+        pub use crate::client::connection::{ConnectionManager, ReceiveStats};
+        pub use crate::client::error::{ClientError, ConnectError};
         pub use crate::client::events::{
-            ComponentInsertEvent, ComponentRemoveEvent, ComponentUpdateEvent, ConnectEvent,
-            DisconnectEvent, EntityDespawnEvent, EntitySpawnEvent, InputEvent, MessageEvent,
+            ActionResolvedEvent, ChannelOccupancyEvent, ComponentInsertEvent, ComponentRemoveEvent,
+            ComponentUpdateEvent, ConnectEvent, ConnectionFailedEvent, DisconnectEvent,
+            EntityCleanupEvent, EntityDespawnEvent, EntitySpawnEvent, InputEvent, InterestHintEvent,
+            MessageAckEvent, MessageEvent, ReplicationLimitExceededEvent, RequestEvent,
+            ResourceRemoveEvent, ResourceUpdateEvent, TickStallEvent, TransferEvent,
+            TransportMigrationEvent, UnconnectedPacketEvent,
         };
         #[cfg(feature = "leafwing")]
         pub use crate::client::input::leafwing::LeafwingInputConfig;
-        pub use crate::client::input::native::{InputConfig, InputManager, InputSystemSet};
+        pub use crate::client::input::native::{
+            CatchUpInputs, InputConfig, InputManager, InputSystemSet,
+        };
         pub use crate::client::interpolation::interpolation_history::ConfirmedHistory;
         pub use crate::client::interpolation::plugin::{
-            InterpolationConfig, InterpolationDelay, InterpolationSet,
+            InterpolationConfig, InterpolationDelay, InterpolationSet, StarvationPolicy,
         };
         pub use crate::client::interpolation::{
-            InterpolateStatus, Interpolated, VisualInterpolateStatus, VisualInterpolationPlugin,
+            InterpolateStatus, Interpolated, InterpolationStarvedEvent, VisualInterpolateStatus,
+            VisualInterpolationPlugin,
         };
+        pub use crate::shared::replication::handoff::{HandoffFn, HandoffSource, HandoffTarget};
         pub use crate::client::io::config::ClientTransport;
         pub use crate::client::io::Io;
         pub use crate::client::networking::{ClientCommands, NetworkingState};
         pub use crate::client::plugin::ClientPlugins;
+        pub use crate::client::prediction::adaptive::{
+            AdaptivePredictionConfig, AdaptivePredictionPlugin, AdaptivePredictionState, InputGhost,
+            LocalPredictionMode, PredictionModeChangeReason, PredictionModeChanged,
+        };
+        pub use crate::client::prediction::capture::{
+            analyze_capture, AppPredictionCaptureExt, PredictionCapture, PredictionCaptureConfig,
+            PredictionCaptureEvent, PredictionCapturePlugin, PredictionRecorder,
+        };
         pub use crate::client::prediction::correction::Correction;
         pub use crate::client::prediction::despawn::PredictionDespawnCommandsExt;
         pub use crate::client::prediction::plugin::is_in_rollback;
-        pub use crate::client::prediction::plugin::{PredictionConfig, PredictionSet};
-        pub use crate::client::prediction::rollback::{Rollback, RollbackState};
+        pub use crate::client::prediction::plugin::{
+            InputDelayConfig, PredictionConfig, PredictionSet,
+        };
+        pub use crate::client::prediction::rollback::{
+            Rollback, RollbackEvent, RollbackState, RollbackWindowExceededEvent,
+        };
         pub use crate::client::prediction::Predicted;
         pub use crate::client::replication::commands::DespawnReplicationCommandExt;
         pub use crate::client::replication::send::Replicate;
         pub use crate::client::run_conditions::{is_connected, is_disconnected, is_synced};
-        pub use crate::client::sync::SyncConfig;
+        pub use crate::client::sync::{SyncConfig, TickDebt};
         pub use crate::connection::client::{
-            Authentication, ClientConnection, IoConfig, NetClient, NetConfig,
+            Authentication, ClientConnection, DisconnectReason, IoConfig, NetClient, NetConfig,
         };
+        pub use crate::connection::netcode::FallbackTransport;
         #[cfg(all(feature = "steam"))]
         pub use crate::connection::steam::client::{SocketConfig, SteamConfig};
     }
     pub mod server {
     
         pub use crate::connection::server::{
-            IoConfig, NetConfig, NetServer, ServerConnection, ServerConnections,
+            DenyList, DisconnectReason, IoConfig, NetConfig, NetServer, ServerConnection,
+            ServerConnections,
         };
         #[cfg(all(feature = "steam"))]
         pub use crate::connection::steam::server::{SocketConfig, SteamConfig};
+        pub use crate::server::channel_settings::AppChannelSettingsExt;
+        pub use crate::server::behavior::{
+            AppClientBehaviorExt, BehaviorAnalysisConfig, BehaviorAnalysisPlugin,
+            BehaviorAnalysisSet, ClientBehaviorHistory, SuspicionEvent,
+        };
         pub use crate::server::clients::ControlledEntities;
-        pub use crate::server::config::{NetcodeConfig, PacketConfig, ServerConfig};
+        pub use crate::server::config::{
+            DuplicateIdPolicy, DuplicateSession, KickPolicy, MissingInputPolicy, NetcodeConfig,
+            PacketConfig, ServerConfig,
+        };
         pub use crate::server::connection::ConnectionManager;
         pub use crate::server::error::ServerError;
         pub use crate::server::events::{
-            ComponentInsertEvent, ComponentRemoveEvent, ComponentUpdateEvent, ConnectEvent,
-            DisconnectEvent, EntityDespawnEvent, EntitySpawnEvent, InputEvent, MessageEvent,
+            ChannelOccupancyEvent, ComponentInsertEvent, ComponentRemoveEvent, ComponentUpdateEvent,
+            ConnectEvent, DisconnectEvent, DuplicateClientIdEvent, EntityDespawnEvent,
+            EntitySpawnEvent, InputEvent, LateInputEvent, MessageAckEvent, MessageEvent,
+            ReplicationLimitExceededEvent, RequestEvent, ResourceRemoveEvent, ResourceUpdateEvent,
+            TransferEvent, TransportMigrationEvent,
         };
+        #[cfg(not(target_family = "wasm"))]
+        pub use crate::server::headless::{HeadlessServer, ServerHandle};
         pub use crate::server::io::config::ServerTransport;
         pub use crate::server::io::Io;
         pub use crate::server::networking::{NetworkingState, ServerCommands};
         pub use crate::server::plugin::ServerPlugins;
-        pub use crate::server::relevance::immediate::RelevanceManager;
-        pub use crate::server::relevance::room::{RoomId, RoomManager};
-        pub use crate::server::replication::commands::DespawnReplicationCommandExt;
+        pub use crate::server::relevance::distance::{
+            ClientViewPosition, DistanceRelevance, ReplicationRelevance,
+        };
+        pub use crate::server::relevance::hint::AppInterestHintExt;
+        pub use crate::server::relevance::immediate::{InterestConfig, RelevanceManager};
+        pub use crate::server::relevance::predicate::PredicateScope;
+        pub use crate::server::relevance::room::{RoomId, RoomManager, RoomMut};
+        pub use crate::server::replication::commands::{
+            DespawnReplicationCommandExt, ReplaceReplicationCommandExt,
+        };
         pub use crate::server::replication::{
             send::{ControlledBy, Lifetime, Replicate, ServerFilter, SyncTarget},
             ReplicationSet, ServerReplicationSet,
         };
+        pub use crate::server::rewind::{RewindCommands, RewindPlugin, SnapshotConfig};
+        pub use crate::shared::input::jitter::InputJitterConfig;
+        pub use crate::shared::replication::handoff::HandoffCommandsExt;
+        pub use crate::shared::replication::prespawn_ids::PreSpawnIdRanges;
         pub use crate::server::run_conditions::{is_started, is_stopped};
+        pub use crate::server::validation::{
+            reject_non_finite, AppClientSpawnValidatorExt, ClientUpdateViolations, FloatFields,
+            ValidationVerdict,
+        };
     }
 
     #[cfg(all(feature = "steam"))]
@@ -332,6 +454,12 @@ pub mod client;
 
 pub mod connection;
 
+/// Optional plugins that are not included in the default plugin groups
+pub mod extras;
+
+#[cfg(feature = "fuzzing")]
+pub mod fuzz;
+
 pub mod inputs;
 pub mod packet;
 
@@ -343,6 +471,9 @@ pub mod server;
 
 pub mod shared;
 
+#[cfg(any(test, feature = "test_utils"))]
+pub mod test_utils;
+
 #[cfg(test)]
 pub(crate) mod tests;
 
@@ -10,6 +10,9 @@ pub enum PacketError {
     Serialization(#[from] SerializationError),
     #[error("channel was not found")]
     ChannelNotFound,
+    /// The send buffer of a reliable channel reached its [`BufferLimits`](crate::channel::builder::BufferLimits)
+    #[error("the send buffer of the channel is full")]
+    ChannelFull,
     #[error("receiver channel error: {0}")]
     ChannelReceiveError(#[from] ChannelReceiveError),
 }
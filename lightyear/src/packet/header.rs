@@ -1,6 +1,7 @@
+use bevy::reflect::Reflect;
 use bevy::utils::HashMap;
 use byteorder::NetworkEndian;
-use byteorder::ReadBytesExt;
+use byteorder::{ReadBytesExt, WriteBytesExt};
 use ringbuffer::{ConstGenericRingBuffer, RingBuffer};
 use tracing::trace;
 
@@ -14,6 +15,33 @@ use crate::shared::ping::manager::PingManager;
 use crate::shared::tick_manager::Tick;
 use crate::shared::time_manager::WrappedTime;
 
+/// Layout of the header written at the start of every packet.
+///
+/// The mode is part of the protocol: it is stored in the [`ChannelRegistry`](crate::prelude::ChannelRegistry)
+/// and must be identical on the client and the server. A peer rejects the packets written with a
+/// different mode.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Reflect)]
+pub enum PacketHeaderMode {
+    /// 11 bytes: packet type, 16-bit packet id, 16-bit ack id, 32-bit ack bitfield and 16-bit tick
+    #[default]
+    Standard,
+    /// Minimal-overhead header for bandwidth-constrained games: 7 bytes, or 9 bytes if the packet
+    /// contains messages that need the tick at which the packet was sent.
+    ///
+    /// - the packet id and the ack id are narrowed to 8 bits, and expanded back by the receiver.
+    ///   This is only valid if less than 128 packets are in flight at any time.
+    /// - the tick is only written if the packet contains replication messages or pongs
+    /// - channel ids are written on a single byte if the protocol has at most 256 channels
+    Compact,
+}
+
+/// Bit set on the first byte of compact headers, so that packets written with the wrong mode are rejected
+const COMPACT_MARKER: u8 = 0b1000_0000;
+/// Bit set on the first byte of compact headers that contain the tick
+const COMPACT_TICK_FLAG: u8 = 0b0100_0000;
+/// Number of bytes of a compact header, before the tick
+const COMPACT_TICK_OFFSET: usize = 7;
+
 /// Header included at the start of all packets
 #[derive(Debug, Clone, PartialEq)]
 pub(crate) struct PacketHeader {
@@ -28,8 +56,8 @@ pub(crate) struct PacketHeader {
     /// (this means that in total we send acks for 33 packet-ids)
     /// See more information at: [GafferOnGames](https://gafferongames.com/post/reliability_ordering_and_congestion_avoidance_over_udp/)
     ack_bitfield: u32,
-    /// Current tick
-    pub(crate) tick: Tick,
+    /// Current tick. It is always present in [`PacketHeaderMode::Standard`] headers.
+    pub(crate) tick: Option<Tick>,
 }
 
 impl ToBytes for PacketHeader {
@@ -45,7 +73,7 @@ impl ToBytes for PacketHeader {
         buffer.write_u16::<NetworkEndian>(self.packet_id.0)?;
         buffer.write_u16::<NetworkEndian>(self.last_ack_packet_id.0)?;
         buffer.write_u32::<NetworkEndian>(self.ack_bitfield)?;
-        buffer.write_u16::<NetworkEndian>(self.tick.0)?;
+        buffer.write_u16::<NetworkEndian>(self.tick.unwrap_or_default().0)?;
         Ok(())
     }
 
@@ -63,7 +91,7 @@ impl ToBytes for PacketHeader {
             packet_id: PacketId(packet_id),
             last_ack_packet_id: PacketId(last_ack_packet_id),
             ack_bitfield,
-            tick: Tick(tick),
+            tick: Some(Tick(tick)),
         })
     }
 }
@@ -81,6 +109,44 @@ impl PacketHeader {
     pub fn get_packet_type(&self) -> PacketType {
         self.packet_type
     }
+
+    /// Write the header with the [`PacketHeaderMode::Compact`] layout.
+    ///
+    /// Only the low byte of the packet ids is written.
+    fn write_compact<T: WriteBytesExt>(&self, buffer: &mut T) -> Result<(), SerializationError> {
+        let mut first_byte = COMPACT_MARKER | self.packet_type as u8;
+        if self.tick.is_some() {
+            first_byte |= COMPACT_TICK_FLAG;
+        }
+        buffer.write_u8(first_byte)?;
+        buffer.write_u8(self.packet_id.0 as u8)?;
+        buffer.write_u8(self.last_ack_packet_id.0 as u8)?;
+        buffer.write_u32::<NetworkEndian>(self.ack_bitfield)?;
+        if let Some(tick) = self.tick {
+            buffer.write_u16::<NetworkEndian>(tick.0)?;
+        }
+        Ok(())
+    }
+
+    /// Remove the tick from a packet that starts with a compact header
+    pub(crate) fn remove_compact_tick(payload: &mut Vec<u8>) {
+        if payload.first().is_some_and(|b| b & COMPACT_TICK_FLAG != 0) {
+            payload[0] &= !COMPACT_TICK_FLAG;
+            payload.drain(COMPACT_TICK_OFFSET..COMPACT_TICK_OFFSET + 2);
+        }
+    }
+}
+
+/// Expand a packet id from its low byte, by picking the id closest to `reference`
+fn expand_nearest(low_byte: u8, reference: PacketId) -> PacketId {
+    let diff = low_byte.wrapping_sub(reference.0 as u8) as i8;
+    PacketId(reference.0.wrapping_add_signed(diff as i16))
+}
+
+/// Expand a packet id from its low byte, knowing that it cannot be more recent than `latest`
+fn expand_before(low_byte: u8, latest: PacketId) -> PacketId {
+    let diff = (latest.0 as u8).wrapping_sub(low_byte);
+    PacketId(latest.0.wrapping_sub(diff as u16))
 }
 
 // we can only send acks for the last 32 packets ids before the last received packet
@@ -122,6 +188,9 @@ pub struct PacketHeaderManager {
     /// The default is 1.5; i.e. after 1.5 times the round trip time, we consider a packet lost if
     /// we haven't received an ACK for it.
     nack_rtt_multiple: f32,
+    mode: PacketHeaderMode,
+    /// Most recent ack id received from the remote, used to expand the compact ack ids
+    last_recv_ack_packet_id: Option<PacketId>,
 }
 
 impl PacketHeaderManager {
@@ -138,9 +207,78 @@ impl PacketHeaderManager {
             // ack_notification_receiver,
             current_time: WrappedTime::default(),
             nack_rtt_multiple,
+            mode: PacketHeaderMode::default(),
+            last_recv_ack_packet_id: None,
+        }
+    }
+
+    pub(crate) fn with_mode(mut self, mode: PacketHeaderMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    pub(crate) fn mode(&self) -> PacketHeaderMode {
+        self.mode
+    }
+
+    /// Write the header of a packet, using the layout of the current [`PacketHeaderMode`]
+    pub(crate) fn write_header<T: WriteBytesExt>(
+        &self,
+        header: &PacketHeader,
+        buffer: &mut T,
+    ) -> Result<(), SerializationError> {
+        match self.mode {
+            PacketHeaderMode::Standard => header.to_bytes(buffer),
+            PacketHeaderMode::Compact => header.write_compact(buffer),
         }
     }
 
+    /// Read the header of a received packet, using the layout of the current [`PacketHeaderMode`]
+    ///
+    /// The compact packet ids are expanded back to full ids:
+    /// - the packet id is the one closest to the last packet id received
+    /// - the ack id refers to one of the packets we sent, so it cannot be more recent than the last packet sent.
+    ///   If it is unchanged, the remote hasn't received any new packet and we keep the previous ack id, so that
+    ///   stale acks are never interpreted as acks for more recent packets.
+    pub(crate) fn read_header(
+        &self,
+        buffer: &mut Reader,
+    ) -> Result<PacketHeader, SerializationError> {
+        if self.mode == PacketHeaderMode::Standard {
+            return PacketHeader::from_bytes(buffer);
+        }
+        let first_byte = buffer.read_u8()?;
+        if first_byte & COMPACT_MARKER == 0 {
+            return Err(SerializationError::InvalidPacketType);
+        }
+        let packet_type = PacketType::try_from(first_byte & !(COMPACT_MARKER | COMPACT_TICK_FLAG))?;
+        let packet_id = buffer.read_u8()?;
+        let last_ack_packet_id = buffer.read_u8()?;
+        let ack_bitfield = buffer.read_u32::<NetworkEndian>()?;
+        let tick = if first_byte & COMPACT_TICK_FLAG != 0 {
+            Some(Tick(buffer.read_u16::<NetworkEndian>()?))
+        } else {
+            None
+        };
+
+        // the first packet id sent is 0
+        let packet_id = match self.recv_buffer.last_recv_packet_id {
+            Some(last) => expand_nearest(packet_id, last),
+            None => PacketId(packet_id as u16),
+        };
+        let last_ack_packet_id = match self.last_recv_ack_packet_id {
+            Some(last) if last.0 as u8 == last_ack_packet_id => last,
+            _ => expand_before(last_ack_packet_id, self.next_packet_id - 1),
+        };
+        Ok(PacketHeader {
+            packet_type,
+            packet_id,
+            last_ack_packet_id,
+            ack_bitfield,
+            tick,
+        })
+    }
+
     /// Internal bookkeeping.
     /// Returns a list of packets that are considered NACKed (i.e. acknowledged as losts)
     pub(crate) fn update(
@@ -180,6 +318,11 @@ impl PacketHeaderManager {
         self.next_packet_id
     }
 
+    /// Statistics of the packets sent and received
+    pub(crate) fn stats(&self) -> &PacketStatsManager {
+        &self.stats_manager
+    }
+
     #[cfg(test)]
     pub fn sent_packets_not_acked(&self) -> &HashMap<PacketId, WrappedTime> {
         &self.sent_packets_not_acked
@@ -197,6 +340,12 @@ impl PacketHeaderManager {
         // update the receive buffer
         self.stats_manager.received_packet();
         self.recv_buffer.recv_packet(header.packet_id);
+        if self
+            .last_recv_ack_packet_id
+            .map_or(true, |last| header.last_ack_packet_id > last)
+        {
+            self.last_recv_ack_packet_id = Some(header.last_ack_packet_id);
+        }
 
         let mut newly_acked_packets = Vec::new();
 
@@ -249,7 +398,7 @@ impl PacketHeaderManager {
             last_ack_packet_id,
             ack_bitfield: self.recv_buffer.get_bitfield(),
             // TODO: we send the tick, later. Seems a bit dangerous...
-            tick: Tick(0),
+            tick: None,
         };
         // we build the header only when we actually send the packet, so computing the stats here is valid
         self.stats_manager.sent_packet();
@@ -411,7 +560,7 @@ mod tests {
             packet_id: PacketId(27),
             last_ack_packet_id: PacketId(13),
             ack_bitfield: 3,
-            tick: Tick(6),
+            tick: Some(Tick(6)),
         };
         let mut writer = Vec::new();
         header.to_bytes(&mut writer)?;
@@ -422,4 +571,78 @@ mod tests {
         assert_eq!(header, read_header);
         Ok(())
     }
+
+    /// Send packets with compact headers through the 8-bit id space multiple times (with some packets lost),
+    /// and check that the packet ids and ack ids are expanded correctly on both sides
+    #[test]
+    fn test_compact_header_wrap_around() -> Result<(), SerializationError> {
+        let mut sender = PacketHeaderManager::new(1.5).with_mode(PacketHeaderMode::Compact);
+        let mut receiver = PacketHeaderManager::new(1.5).with_mode(PacketHeaderMode::Compact);
+        for i in 0..1000u16 {
+            let mut header = sender.prepare_send_packet_header(PacketType::Data);
+            assert_eq!(header.packet_id, PacketId(i));
+            if i % 3 == 0 {
+                // the packet is lost
+                continue;
+            }
+            header.tick = (i % 2 == 0).then_some(Tick(i));
+            let mut writer = Vec::new();
+            sender.write_header(&header, &mut writer)?;
+            assert_eq!(writer.len(), if header.tick.is_some() { 9 } else { 7 });
+            let mut reader: Reader = writer.into();
+            let read_header = receiver.read_header(&mut reader)?;
+            assert_eq!(header, read_header);
+            receiver.process_recv_packet_header(&read_header);
+
+            // the receiver acks the packet
+            let ack_header = receiver.prepare_send_packet_header(PacketType::Data);
+            let mut writer = Vec::new();
+            receiver.write_header(&ack_header, &mut writer)?;
+            let mut reader: Reader = writer.into();
+            let read_ack_header = sender.read_header(&mut reader)?;
+            assert_eq!(read_ack_header.last_ack_packet_id, PacketId(i));
+            assert_eq!(
+                sender.process_recv_packet_header(&read_ack_header),
+                vec![PacketId(i)]
+            );
+        }
+
+        // the receiver doesn't receive any of the next 300 packets: its ack id stays the same,
+        // and must not be interpreted as an ack for one of the lost packets
+        for _ in 0..300 {
+            sender.prepare_send_packet_header(PacketType::Data);
+        }
+        let ack_header = receiver.prepare_send_packet_header(PacketType::Data);
+        let mut writer = Vec::new();
+        receiver.write_header(&ack_header, &mut writer)?;
+        let mut reader: Reader = writer.into();
+        let read_ack_header = sender.read_header(&mut reader)?;
+        assert_eq!(read_ack_header.last_ack_packet_id, PacketId(998));
+        assert!(sender
+            .process_recv_packet_header(&read_ack_header)
+            .is_empty());
+        Ok(())
+    }
+
+    /// A packet written with a different header mode is rejected
+    #[test]
+    fn test_header_mode_mismatch() -> Result<(), SerializationError> {
+        let mut standard = PacketHeaderManager::new(1.5);
+        let mut compact = PacketHeaderManager::new(1.5).with_mode(PacketHeaderMode::Compact);
+
+        let mut header = standard.prepare_send_packet_header(PacketType::Data);
+        header.tick = Some(Tick(0));
+        let mut writer = Vec::new();
+        standard.write_header(&header, &mut writer)?;
+        let mut reader: Reader = writer.into();
+        assert!(compact.read_header(&mut reader).is_err());
+
+        let mut header = compact.prepare_send_packet_header(PacketType::Data);
+        header.tick = Some(Tick(0));
+        let mut writer = Vec::new();
+        compact.write_header(&header, &mut writer)?;
+        let mut reader: Reader = writer.into();
+        assert!(standard.read_header(&mut reader).is_err());
+        Ok(())
+    }
 }
@@ -1,4 +1,4 @@
-use std::collections::{HashMap, VecDeque};
+use std::collections::{HashMap, HashSet};
 
 use bytes::Bytes;
 use crossbeam_channel::{Receiver, Sender};
@@ -6,29 +6,37 @@ use tracing::trace;
 #[cfg(feature = "trace")]
 use tracing::{instrument, Level};
 
-use crate::channel::builder::ChannelContainer;
+use crate::channel::builder::{
+    ChannelContainer, ChannelMode, ChannelOccupancy, ChannelSettingsOverride, FlowControlChannel,
+    TransferControlChannel,
+};
+use crate::channel::flow_control::{FlowControlStats, WindowAdvertisement, WINDOW_RESEND_INTERVAL};
 use crate::channel::receivers::ChannelReceive;
-use crate::channel::senders::ChannelSend;
+use crate::channel::senders::{ChannelSend, ChannelSender, DedupKey};
 #[cfg(feature = "trace")]
 use crate::channel::stats::send::ChannelSendStats;
-use crate::packet::error::PacketError;
-use crate::packet::header::PacketHeader;
-use crate::packet::message::{
-    FragmentData, MessageAck, MessageId, ReceiveMessage, SendMessage, SingleData,
+use crate::channel::stats::ChannelStats;
+use crate::channel::transfer::{
+    TransferCancellation, TransferDirection, TransferHandle, TransferOutcome, TransferProgress,
 };
+use crate::packet::error::PacketError;
+use crate::packet::message::{FragmentData, MessageAck, MessageId, ReceiveMessage, SingleData};
 use crate::packet::packet::PacketId;
-use crate::packet::packet_builder::{PacketBuilder, Payload, RecvPayload};
+use crate::packet::packet_builder::{PacketBuilder, PacketFormat, Payload, RecvPayload};
 use crate::packet::packet_type::PacketType;
 use crate::packet::priority_manager::{PriorityConfig, PriorityManager};
+use crate::packet::send_buffers::SendBuffers;
+use crate::packet::stats_manager::packet::PacketStatsManager;
 use crate::protocol::channel::{ChannelId, ChannelKind, ChannelRegistry};
-use crate::protocol::registry::NetId;
 use crate::serialize::reader::Reader;
 use crate::serialize::varint::VarIntReadExt;
 use crate::serialize::ToBytes;
 use crate::shared::ping::manager::PingManager;
+use crate::shared::replication::classes::{ReplicationClass, ReplicationClassStats};
 use crate::shared::tick_manager::Tick;
 use crate::shared::tick_manager::TickManager;
-use crate::shared::time_manager::TimeManager;
+use crate::shared::time_manager::{TimeManager, WrappedTime};
+use crate::utils::buffer_pool::BufferPool;
 #[cfg(test)]
 use crate::utils::captures::Captures;
 
@@ -37,6 +45,10 @@ use crate::utils::captures::Captures;
 
 pub const DEFAULT_MESSAGE_PRIORITY: f32 = 1.0;
 
+/// Fraction of the [`BufferLimits`](crate::channel::builder::BufferLimits) of a channel above which a
+/// warning is emitted
+pub const OCCUPANCY_WARNING_THRESHOLD: f32 = 0.8;
+
 /// Wrapper to: send/receive messages via channels to a remote address
 /// By splitting the data into packets and sending them through a given transport
 #[derive(Debug)]
@@ -46,11 +58,34 @@ pub struct MessageManager {
     priority_manager: PriorityManager,
     pub(crate) channels: HashMap<ChannelKind, ChannelContainer>,
     pub(crate) channel_registry: ChannelRegistry,
+    /// Statistics about the messages sent and received on each channel
+    channel_stats: HashMap<ChannelKind, ChannelStats>,
+    /// Settings of the channels that are different for this connection
+    channel_overrides: HashMap<ChannelKind, ChannelSettingsOverride>,
     // TODO: can use Vec<ChannelKind, Vec<MessageId>> to be more efficient?
     /// Map to keep track of which messages have been sent in which packets, so that
     /// reliable senders can stop trying to send a message that has already been received
     packet_to_message_ack_map: HashMap<PacketId, Vec<(ChannelKind, MessageAck)>>,
+    /// Lists of message acks of the packets that have been acked or lost, reused for the next packets
+    ack_lists: BufferPool<Vec<(ChannelKind, MessageAck)>>,
+    /// Lists of messages to send, reused across frames
+    send_buffers: SendBuffers,
+    /// List of payloads returned by [`MessageManager::send_packets`], reused across frames
+    payloads: Vec<Payload>,
     nack_senders: Vec<Sender<MessageId>>,
+    /// Receivers notified when a message sent on a user channel is acked by the remote
+    message_ack_receivers: Vec<(ChannelKind, Receiver<MessageId>)>,
+    /// Channels whose occupancy is above [`OCCUPANCY_WARNING_THRESHOLD`]
+    saturated_channels: HashSet<ChannelKind>,
+    /// Channels that went above [`OCCUPANCY_WARNING_THRESHOLD`] since the last call to `take_occupancy_warnings`
+    occupancy_warnings: Vec<(ChannelKind, ChannelOccupancy)>,
+    /// Most recent tick received from the remote, used for the messages of packets that don't include a tick
+    last_recv_tick: Tick,
+    /// Receive windows of the flow-controlled channels that were last advertised to the remote
+    advertised_windows: HashMap<ChannelId, MessageId>,
+    /// Time at which we last advertised the receive windows
+    last_window_advertisement: Option<WrappedTime>,
+    current_time: WrappedTime,
 }
 
 impl MessageManager {
@@ -59,16 +94,46 @@ impl MessageManager {
         nack_rtt_multiple: f32,
         priority_config: PriorityConfig,
     ) -> Self {
+        let mut channels = channel_registry.channels();
+        // the messages sent on the user channels that track acks are reported with a `MessageAckEvent`
+        let message_ack_receivers = channels
+            .iter_mut()
+            .filter(|(kind, container)| {
+                !channel_registry.is_internal(kind) && container.setting.mode.is_watching_acks()
+            })
+            .map(|(kind, container)| (*kind, container.sender.subscribe_acks()))
+            .collect();
         Self {
-            packet_manager: PacketBuilder::new(nack_rtt_multiple),
+            packet_manager: PacketBuilder::new(nack_rtt_multiple)
+                .with_format(PacketFormat::new(channel_registry)),
             priority_manager: PriorityManager::new(priority_config),
-            channels: channel_registry.channels(),
+            channels,
             channel_registry: channel_registry.clone(),
+            channel_stats: channel_registry
+                .names()
+                .map(|(kind, _)| (kind, ChannelStats::default()))
+                .collect(),
+            channel_overrides: HashMap::new(),
             packet_to_message_ack_map: HashMap::new(),
+            ack_lists: BufferPool::default(),
+            send_buffers: SendBuffers::default(),
+            payloads: Vec::new(),
             nack_senders: vec![],
+            message_ack_receivers,
+            saturated_channels: HashSet::new(),
+            occupancy_warnings: Vec::new(),
+            last_recv_tick: Tick(0),
+            advertised_windows: HashMap::new(),
+            last_window_advertisement: None,
+            current_time: WrappedTime::default(),
         }
     }
 
+    /// Statistics of the packets sent and received on this connection
+    pub(crate) fn packet_stats(&self) -> &PacketStatsManager {
+        self.packet_manager.header_manager.stats()
+    }
+
     pub(crate) fn get_replication_update_send_receiver(&mut self) -> Receiver<MessageId> {
         self.priority_manager
             .subscribe_replication_update_sent_messages()
@@ -82,6 +147,8 @@ impl MessageManager {
         ping_manager: &PingManager,
         tick_manager: &TickManager,
     ) {
+        self.current_time = time_manager.current_time();
+        self.priority_manager.advance(time_manager.delta());
         // on the sender side, gather the list of packets that haven't been received by the remote peer
         let lost_packets = self
             .packet_manager
@@ -89,8 +156,8 @@ impl MessageManager {
             .update(time_manager, ping_manager);
         // notify that some messages have been lost
         for lost_packet in lost_packets {
-            if let Some(message_map) = self.packet_to_message_ack_map.remove(&lost_packet) {
-                for (channel_kind, message_ack) in message_map {
+            if let Some(mut message_map) = self.packet_to_message_ack_map.remove(&lost_packet) {
+                for (channel_kind, message_ack) in message_map.drain(..) {
                     let channel = self
                         .channels
                         .get_mut(&channel_kind)
@@ -104,6 +171,7 @@ impl MessageManager {
                     );
                     channel.sender.send_nacks(message_ack.message_id);
                 }
+                self.ack_lists.give_back(message_map);
             }
         }
         for channel in self.channels.values_mut() {
@@ -142,11 +210,134 @@ impl MessageManager {
         channel_kind: ChannelKind,
         priority: f32,
     ) -> Result<Option<MessageId>, PacketError> {
+        let num_bytes = message.len();
+        self.make_room(channel_kind, num_bytes)?;
+        let channel = self
+            .channels
+            .get_mut(&channel_kind)
+            .ok_or(PacketError::ChannelNotFound)?;
+        let message_id = channel.sender.buffer_send(message, priority)?;
+        let stats = self.channel_stats.entry(channel_kind).or_default();
+        stats.messages_sent += 1;
+        stats.bytes_sent += num_bytes as u64;
+        stats.buffered = channel.sender.num_buffered();
+        self.check_occupancy(channel_kind);
+        Ok(message_id)
+    }
+
+    /// Make sure that a message of `num_bytes` bytes fits in the [`BufferLimits`](crate::channel::builder::BufferLimits)
+    /// of the channel: the oldest messages of an unreliable channel are dropped, and a reliable channel returns
+    /// [`PacketError::ChannelFull`]
+    fn make_room(&mut self, channel_kind: ChannelKind, num_bytes: usize) -> Result<(), PacketError> {
+        let channel = self
+            .channels
+            .get_mut(&channel_kind)
+            .ok_or(PacketError::ChannelNotFound)?;
+        let limits = channel.setting.buffer_limits;
+        let occupancy = |channel: &ChannelContainer| ChannelOccupancy {
+            messages: channel.sender.num_buffered(),
+            bytes: channel.sender.buffered_bytes(),
+            limits,
+        };
+        if !limits.would_exceed(&occupancy(channel), num_bytes) {
+            return Ok(());
+        }
+        let stats = self.channel_stats.entry(channel_kind).or_default();
+        if channel.setting.mode.is_reliable() {
+            stats.messages_rejected += 1;
+            return Err(PacketError::ChannelFull);
+        }
+        while limits.would_exceed(&occupancy(channel), num_bytes) && channel.sender.drop_oldest() {
+            stats.messages_dropped += 1;
+        }
+        Ok(())
+    }
+
+    /// Emit an occupancy warning if the channel just went above [`OCCUPANCY_WARNING_THRESHOLD`]
+    fn check_occupancy(&mut self, channel_kind: ChannelKind) {
+        let Some(occupancy) = self.channel_occupancy(channel_kind) else {
+            return;
+        };
+        if occupancy
+            .ratio()
+            .is_some_and(|ratio| ratio > OCCUPANCY_WARNING_THRESHOLD)
+        {
+            if self.saturated_channels.insert(channel_kind) {
+                self.occupancy_warnings.push((channel_kind, occupancy));
+            }
+        } else {
+            self.saturated_channels.remove(&channel_kind);
+        }
+    }
+
+    /// Current occupancy of the send buffer of a channel
+    pub fn channel_occupancy(&self, channel_kind: ChannelKind) -> Option<ChannelOccupancy> {
+        let channel = self.channels.get(&channel_kind)?;
+        Some(ChannelOccupancy {
+            messages: channel.sender.num_buffered(),
+            bytes: channel.sender.buffered_bytes(),
+            limits: channel.setting.buffer_limits,
+        })
+    }
+
+    /// Take the channels that went above [`OCCUPANCY_WARNING_THRESHOLD`] since the last call.
+    ///
+    /// A channel is only reported again once its occupancy went back below the threshold.
+    pub(crate) fn take_occupancy_warnings(&mut self) -> Vec<(ChannelKind, ChannelOccupancy)> {
+        std::mem::take(&mut self.occupancy_warnings)
+    }
+
+    /// Send a replication message that was buffered on this connection with the budget of `class`
+    pub(crate) fn set_message_class(
+        &mut self,
+        channel_kind: ChannelKind,
+        message_id: MessageId,
+        class: ReplicationClass,
+    ) {
+        if let Some(channel_id) = self.channel_registry.get_net_from_kind(&channel_kind) {
+            self.priority_manager
+                .set_message_class(*channel_id, message_id, class);
+        }
+    }
+
+    /// Bytes of the replication messages sent for each [`ReplicationClass`] on this connection
+    pub(crate) fn replication_class_stats(&self) -> &ReplicationClassStats {
+        &self.priority_manager.class_stats
+    }
+
+    /// Take the bytes of the replication messages sent for each [`ReplicationClass`] since the last call
+    pub(crate) fn take_unflushed_class_stats(&mut self) -> ReplicationClassStats {
+        std::mem::take(&mut self.priority_manager.unflushed_class_stats)
+    }
+
+    /// Buffer a message to be sent on this connection, replacing the message buffered with the same
+    /// [`DedupKey`] on this channel if it has not been transmitted yet
+    /// Returns the message id associated with the message, if there is one
+    pub fn buffer_send_with_key(
+        &mut self,
+        message: Bytes,
+        channel_kind: ChannelKind,
+        key: DedupKey,
+    ) -> Result<Option<MessageId>, PacketError> {
+        let num_bytes = message.len();
+        self.make_room(channel_kind, num_bytes)?;
         let channel = self
             .channels
             .get_mut(&channel_kind)
             .ok_or(PacketError::ChannelNotFound)?;
-        Ok(channel.sender.buffer_send(message, priority)?)
+        let (message_id, replaced) =
+            channel
+                .sender
+                .buffer_send_with_key(message, DEFAULT_MESSAGE_PRIORITY, key)?;
+        let stats = self.channel_stats.entry(channel_kind).or_default();
+        stats.messages_sent += 1;
+        stats.bytes_sent += num_bytes as u64;
+        if replaced {
+            stats.messages_replaced += 1;
+        }
+        stats.buffered = channel.sender.num_buffered();
+        self.check_occupancy(channel_kind);
+        Ok(message_id)
     }
 
     /// Prepare buckets from the internal send buffers, and return the bytes to send
@@ -155,40 +346,78 @@ impl MessageManager {
     //  maybe be generic over a Context ?
     #[cfg_attr(feature = "trace", instrument(level = Level::INFO, skip_all))]
     pub fn send_packets(&mut self, current_tick: Tick) -> Result<Vec<Payload>, PacketError> {
+        self.buffer_window_advertisement()?;
+
         // Step 1. Get the list of packets to send from all channels
         // for each channel, prepare packets using the buffered messages that are ready to be sent
         // TODO: iterate through the channels in order of channel priority? (with accumulation)
-        let mut data_to_send: Vec<(NetId, (VecDeque<SendMessage>, VecDeque<SendMessage>))> = vec![];
-        let mut has_data_to_send = false;
         for (channel_kind, channel) in self.channels.iter_mut() {
             let channel_id = self
                 .channel_registry
                 .get_net_from_kind(channel_kind)
                 .ok_or(PacketError::ChannelNotFound)?;
-            let (single_data, fragment_data) = channel.sender.send_packet();
+            let mut single_data = self.send_buffers.send_messages.take();
+            let mut fragment_data = self.send_buffers.send_messages.take();
+            channel
+                .sender
+                .send_packet_into(&mut single_data, &mut fragment_data);
+            let stats = self.channel_stats.entry(*channel_kind).or_default();
+            stats.buffered = channel.sender.num_buffered();
+            stats.active_transfers = channel.receiver.fragment_receiver().num_transfers();
+            if let ChannelSender::Reliable(sender) = &mut channel.sender {
+                stats.messages_resent += sender.take_num_resent();
+                stats.active_transfers += sender.num_transfers();
+            }
 
             if !single_data.is_empty() || !fragment_data.is_empty() {
                 trace!(?channel_id, "send message with channel_id");
-                data_to_send.push((*channel_id, (single_data, fragment_data)));
-                has_data_to_send = true;
+                self.send_buffers
+                    .channel_messages
+                    .push((*channel_id, (single_data, fragment_data)));
+            } else {
+                self.send_buffers.send_messages.give_back(single_data);
+                self.send_buffers.send_messages.give_back(fragment_data);
             }
         }
         // return early if there are no messages to send
-        if !has_data_to_send {
-            return Ok(vec![]);
+        if self.send_buffers.channel_messages.is_empty() {
+            self.end_frame();
+            return Ok(std::mem::take(&mut self.payloads));
         }
 
         // priority manager: get the list of messages we can send according to the rate limiter
         //  (the other messages are stored in an internal buffer)
-        let (single_data, fragment_data, num_bytes_added_to_limiter) = self
-            .priority_manager
-            .priority_filter(data_to_send, &self.channel_registry, current_tick);
+        let (channels, channel_registry, channel_stats) = (
+            &self.channels,
+            &self.channel_registry,
+            &mut self.channel_stats,
+        );
+        let num_bytes_added_to_limiter = self.priority_manager.priority_filter(
+            &mut self.send_buffers,
+            channel_registry,
+            current_tick,
+            |channel_id| {
+                // the reliable messages are not lost, they will be sent again later
+                let Some(channel_kind) = channel_registry.get_kind_from_net_id(channel_id) else {
+                    return;
+                };
+                if channels
+                    .get(channel_kind)
+                    .is_some_and(|channel| !channel.setting.mode.is_reliable())
+                {
+                    channel_stats
+                        .entry(*channel_kind)
+                        .or_default()
+                        .messages_dropped += 1;
+                }
+            },
+        );
 
         #[cfg(feature = "trace")]
         {
             // NOTE: we don't know the actual exact amount of bytes sent (because we don't take into account the ids, etc.),
             // but we could during build_packet?
-            for (channel_id, data) in &single_data {
+            for (channel_id, data) in &self.send_buffers.single_data {
                 let channel_stats = &mut self
                     .channels
                     .get_mut(
@@ -201,7 +430,7 @@ impl MessageManager {
                 channel_stats.add_bytes_sent(data.iter().fold(0, |acc, d| acc + d.bytes.len()));
                 channel_stats.add_single_message_sent(data.len());
             }
-            for (channel_id, data) in &fragment_data {
+            for (channel_id, data) in &self.send_buffers.fragment_data {
                 let channel_stats = &mut self
                     .channels
                     .get_mut(
@@ -216,44 +445,48 @@ impl MessageManager {
             }
         }
 
-        let packets =
-            self.packet_manager
-                .build_packets(current_tick, single_data, fragment_data)?;
+        let packets = self.packet_manager.build_packets(
+            current_tick,
+            &mut self.send_buffers.single_data,
+            &mut self.send_buffers.fragment_data,
+        );
+        self.send_buffers.recycle();
+        let mut packets = packets?;
 
-        let mut bytes = Vec::new();
-        for mut packet in packets {
+        let mut bytes = std::mem::take(&mut self.payloads);
+        for mut packet in packets.drain(..) {
             trace!(packet_id = ?packet.packet_id, num_messages = ?packet.num_messages(), "sending packet");
             // TODO: should we update this to include fragment info as well?
             // Step 2. Update the packet_to_message_id_map (only for channels that care about acks)
-            std::mem::take(&mut packet.message_acks)
-                .into_iter()
-                .try_for_each(|(channel_id, message_ack)| {
-                    let channel_kind = self
-                        .channel_registry
-                        .get_kind_from_net_id(channel_id)
-                        .ok_or(PacketError::ChannelNotFound)?;
-                    let channel = self
-                        .channels
-                        .get(channel_kind)
-                        .ok_or(PacketError::ChannelNotFound)?;
-                    if channel.setting.mode.is_watching_acks() {
-                        trace!(
-                            "Registering message ack (ChannelId:{:?} {:?}) for packet {:?}",
-                            channel_id,
-                            message_ack,
-                            packet.packet_id
-                        );
-                        self.packet_to_message_ack_map
-                            .entry(packet.packet_id)
-                            .or_default()
-                            .push((*channel_kind, message_ack));
-                    }
-                    Ok::<(), PacketError>(())
-                })?;
+            for (channel_id, message_ack) in packet.message_acks.drain(..) {
+                let channel_kind = self
+                    .channel_registry
+                    .get_kind_from_net_id(channel_id)
+                    .ok_or(PacketError::ChannelNotFound)?;
+                let channel = self
+                    .channels
+                    .get(channel_kind)
+                    .ok_or(PacketError::ChannelNotFound)?;
+                if channel.setting.mode.is_watching_acks() {
+                    trace!(
+                        "Registering message ack (ChannelId:{:?} {:?}) for packet {:?}",
+                        channel_id,
+                        message_ack,
+                        packet.packet_id
+                    );
+                    self.packet_to_message_ack_map
+                        .entry(packet.packet_id)
+                        .or_insert_with(|| self.ack_lists.take())
+                        .push((*channel_kind, message_ack));
+                }
+            }
+            self.packet_manager
+                .recycle_message_acks(packet.message_acks);
 
             // Step 3. Get the packets to send over the network
             bytes.push(packet.payload);
         }
+        self.packet_manager.recycle_packets(packets);
 
         // adjust the real amount of bytes that we sent through the limiter (to account for the actual packet size)
         if self.priority_manager.config.enabled {
@@ -261,27 +494,58 @@ impl MessageManager {
             if let Ok(remaining_bytes_to_add) =
                 (total_bytes_sent - num_bytes_added_to_limiter).try_into()
             {
-                let _ = self
-                    .priority_manager
-                    .limiter
-                    .check_n(remaining_bytes_to_add);
+                self.priority_manager.charge(remaining_bytes_to_add);
             }
         }
 
+        self.end_frame();
         Ok(bytes)
     }
 
+    /// Give back the payloads returned by [`MessageManager::send_packets`] once they have been sent,
+    /// so that their allocations are reused for the next packets
+    pub(crate) fn recycle_payloads(&mut self, mut payloads: Vec<Payload>) {
+        for payload in payloads.drain(..) {
+            self.packet_manager.recycle_payload(payload);
+        }
+        self.payloads = payloads;
+    }
+
+    /// Release the send buffers that were not needed during the recent frames
+    fn end_frame(&mut self) {
+        self.send_buffers.end_frame();
+        self.packet_manager.end_frame();
+        self.ack_lists.end_frame();
+    }
+
+    /// Allocate new buffers for every packet instead of reusing them
+    #[cfg(any(test, feature = "test_utils"))]
+    pub(crate) fn disable_buffer_reuse(&mut self) {
+        self.send_buffers = SendBuffers::disabled();
+        self.ack_lists = BufferPool::disabled();
+        self.packet_manager.disable_buffer_reuse();
+    }
+
     /// Process packet received over the network as raw bytes
     /// Update the acks, and put the messages from the packets in internal buffers
-    /// Returns the tick of the packet
+    /// Returns the tick of the packet, if the header contains one
+    /// (compact headers only include it when the packet contains tick-dependent messages)
     #[cfg_attr(feature = "trace", instrument(level = Level::INFO, skip_all))]
-    pub fn recv_packet(&mut self, packet: RecvPayload) -> Result<Tick, PacketError> {
+    pub fn recv_packet(&mut self, packet: RecvPayload) -> Result<Option<Tick>, PacketError> {
         trace!(?packet, "Received packet");
         let mut cursor = Reader::from(packet);
 
         // Step 1. Parse the packet
-        let header = PacketHeader::from_bytes(&mut cursor)?;
-        let tick = header.tick;
+        let header = self
+            .packet_manager
+            .header_manager
+            .read_header(&mut cursor)?;
+        if let Some(tick) = header.tick {
+            if tick > self.last_recv_tick {
+                self.last_recv_tick = tick;
+            }
+        }
+        let tick = header.tick.unwrap_or(self.last_recv_tick);
 
         // TODO: if it's fragmented, put it in a buffer? while we wait for all the parts to be ready?
         //  maybe the channel can handle the fragmentation?
@@ -299,8 +563,8 @@ impl MessageManager {
         // Step 3. Update the list of messages that have been acked
         for acked_packet in acked_packets {
             trace!("Acked packet {:?}", acked_packet);
-            if let Some(message_acks) = self.packet_to_message_ack_map.remove(&acked_packet) {
-                for (channel_kind, message_ack) in message_acks {
+            if let Some(mut message_acks) = self.packet_to_message_ack_map.remove(&acked_packet) {
+                for (channel_kind, message_ack) in message_acks.drain(..) {
                     let channel_name = self
                         .channel_registry
                         .name(&channel_kind)
@@ -315,7 +579,13 @@ impl MessageManager {
                         .get_mut(&channel_kind)
                         .ok_or(PacketError::ChannelNotFound)?;
                     channel.sender.receive_ack(&message_ack);
+                    if let Some(channel_id) = self.channel_registry.get_net_from_kind(&channel_kind)
+                    {
+                        self.priority_manager
+                            .message_acked(*channel_id, message_ack.message_id);
+                    }
                 }
+                self.ack_lists.give_back(message_acks);
             }
         }
 
@@ -324,8 +594,13 @@ impl MessageManager {
         // TODO: maybe do this in a helper function?
         if header.get_packet_type() == PacketType::DataFragment {
             // read the fragment data
-            let channel_id = ChannelId::from_bytes(&mut cursor)?;
+            let channel_id = self.packet_manager.format.read_channel_id(&mut cursor)?;
             let fragment_data = FragmentData::from_bytes(&mut cursor)?;
+            self.record_received(
+                channel_id,
+                fragment_data.fragment_id == 0,
+                fragment_data.bytes.len(),
+            )?;
             self.get_channel_mut(channel_id)?
                 .receiver
                 .buffer_recv(ReceiveMessage {
@@ -335,10 +610,11 @@ impl MessageManager {
         }
         // read single message data
         while cursor.has_remaining() {
-            let channel_id = ChannelId::from_bytes(&mut cursor)?;
+            let channel_id = self.packet_manager.format.read_channel_id(&mut cursor)?;
             let num_messages = cursor.read_varint()?;
             for i in 0..num_messages {
                 let single_data = SingleData::from_bytes(&mut cursor)?;
+                self.record_received(channel_id, true, single_data.bytes.len())?;
                 self.get_channel_mut(channel_id)?
                     .receiver
                     .buffer_recv(ReceiveMessage {
@@ -353,7 +629,232 @@ impl MessageManager {
         //         channel_kind
         //     );
         // TODO: use channel_id 0 as end of packet or just check that we are at the end of the packet?
-        Ok(tick)
+        self.receive_window_advertisements()?;
+        self.receive_transfer_cancellations()?;
+        Ok(header.tick)
+    }
+
+    /// Buffer a message advertising the receive windows of the flow-controlled channels,
+    /// if they changed or if we haven't advertised them for a while
+    fn buffer_window_advertisement(&mut self) -> Result<(), PacketError> {
+        let flow_control_kind = ChannelKind::of::<FlowControlChannel>();
+        if !self.channels.contains_key(&flow_control_kind) {
+            return Ok(());
+        }
+        let mut windows = vec![];
+        let mut changed = false;
+        for (channel_kind, channel) in self.channels.iter() {
+            let (ChannelMode::UnorderedReliable(settings)
+            | ChannelMode::SequencedReliable(settings)
+            | ChannelMode::OrderedReliable(settings)) = &channel.setting.mode
+            else {
+                continue;
+            };
+            let (Some(receive_window), Some(window_start)) =
+                (settings.receive_window, channel.receiver.window_start())
+            else {
+                continue;
+            };
+            let channel_id = *self
+                .channel_registry
+                .get_net_from_kind(channel_kind)
+                .ok_or(PacketError::ChannelNotFound)?;
+            let window_end = window_start + MessageId(receive_window);
+            if self.advertised_windows.get(&channel_id) != Some(&window_end) {
+                changed = true;
+                self.advertised_windows.insert(channel_id, window_end);
+            }
+            windows.push((channel_id, window_end));
+        }
+        if windows.is_empty() {
+            return Ok(());
+        }
+        let resend = self.last_window_advertisement.map_or(true, |last| {
+            last + WINDOW_RESEND_INTERVAL <= self.current_time
+        });
+        if !changed && !resend {
+            return Ok(());
+        }
+        trace!(?windows, "advertising receive windows");
+        let advertisement = WindowAdvertisement(windows);
+        let mut bytes = Vec::with_capacity(advertisement.len());
+        advertisement.to_bytes(&mut bytes)?;
+        self.last_window_advertisement = Some(self.current_time);
+        self.buffer_send(bytes.into(), flow_control_kind)?;
+        Ok(())
+    }
+
+    /// Apply the receive windows advertised by the remote to our reliable senders
+    fn receive_window_advertisements(&mut self) -> Result<(), PacketError> {
+        let Some(channel) = self
+            .channels
+            .get_mut(&ChannelKind::of::<FlowControlChannel>())
+        else {
+            return Ok(());
+        };
+        let mut windows = vec![];
+        while let Some((_, bytes)) = channel.receiver.read_message() {
+            let mut reader = Reader::from(bytes);
+            windows.extend(WindowAdvertisement::from_bytes(&mut reader)?.0);
+        }
+        for (channel_id, window_end) in windows {
+            if let ChannelSender::Reliable(sender) = &mut self.get_channel_mut(channel_id)?.sender {
+                sender.update_window_end(window_end);
+            }
+        }
+        Ok(())
+    }
+
+    /// Apply the cancellations of the transfers sent by the remote
+    fn receive_transfer_cancellations(&mut self) -> Result<(), PacketError> {
+        let Some(channel) = self
+            .channels
+            .get_mut(&ChannelKind::of::<TransferControlChannel>())
+        else {
+            return Ok(());
+        };
+        let mut cancellations = vec![];
+        while let Some((_, bytes)) = channel.receiver.read_message() {
+            let mut reader = Reader::from(bytes);
+            cancellations.extend(TransferCancellation::from_bytes(&mut reader)?.0);
+        }
+        let current_time = self.current_time;
+        for (channel_id, message_id, direction) in cancellations {
+            trace!(
+                ?channel_id,
+                ?message_id,
+                ?direction,
+                "transfer cancelled by the remote"
+            );
+            let channel = self.get_channel_mut(channel_id)?;
+            match direction {
+                // the remote is the sender of the message
+                TransferDirection::Send => {
+                    channel.receiver.cancel_message(message_id, current_time)
+                }
+                // the remote is the receiver of the message
+                TransferDirection::Receive => {
+                    if let ChannelSender::Reliable(sender) = &mut channel.sender {
+                        sender.cancel(message_id);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Progress of the fragmented messages that are being sent (on reliable channels) or received
+    pub fn transfers(&self) -> Vec<TransferProgress> {
+        let mut transfers = vec![];
+        for (channel_kind, channel) in self.channels.iter() {
+            if let ChannelSender::Reliable(sender) = &channel.sender {
+                transfers.extend(sender.transfers(*channel_kind));
+            }
+            transfers.extend(
+                channel
+                    .receiver
+                    .fragment_receiver()
+                    .transfers(*channel_kind, self.current_time),
+            );
+        }
+        transfers
+    }
+
+    /// Progress of a transfer, or None if it is finished
+    pub fn transfer(&self, handle: TransferHandle) -> Option<TransferProgress> {
+        let channel = self.channels.get(&handle.channel())?;
+        match handle.direction() {
+            TransferDirection::Send => match &channel.sender {
+                ChannelSender::Reliable(sender) => sender
+                    .transfers(handle.channel())
+                    .find(|transfer| transfer.handle == handle),
+                _ => None,
+            },
+            TransferDirection::Receive => channel
+                .receiver
+                .fragment_receiver()
+                .transfers(handle.channel(), self.current_time)
+                .find(|transfer| transfer.handle == handle),
+        }
+    }
+
+    /// Cancel a transfer: the sender stops sending the remaining fragments, and the receiver discards
+    /// the fragments that it received.
+    ///
+    /// Returns false if the transfer was already finished.
+    pub fn cancel_transfer(&mut self, handle: TransferHandle) -> Result<bool, PacketError> {
+        if self.transfer(handle).is_none() {
+            return Ok(false);
+        }
+        let channel_id = *self
+            .channel_registry
+            .get_net_from_kind(&handle.channel())
+            .ok_or(PacketError::ChannelNotFound)?;
+        let current_time = self.current_time;
+        let channel = self
+            .channels
+            .get_mut(&handle.channel())
+            .ok_or(PacketError::ChannelNotFound)?;
+        match (handle.direction(), &mut channel.sender) {
+            (TransferDirection::Send, ChannelSender::Reliable(sender)) => {
+                sender.cancel(handle.message_id);
+            }
+            _ => channel
+                .receiver
+                .cancel_message(handle.message_id, current_time),
+        }
+        // notify the remote so that it also stops the transfer
+        let control_kind = ChannelKind::of::<TransferControlChannel>();
+        if self.channels.contains_key(&control_kind) {
+            let cancellation =
+                TransferCancellation(vec![(channel_id, handle.message_id, handle.direction())]);
+            let mut bytes = Vec::with_capacity(cancellation.len());
+            cancellation.to_bytes(&mut bytes)?;
+            self.buffer_send(bytes.into(), control_kind)?;
+        }
+        Ok(true)
+    }
+
+    /// Take the transfers that were completed, cancelled or timed out since the last call
+    pub(crate) fn take_transfer_events(&mut self) -> Vec<(TransferHandle, TransferOutcome)> {
+        let mut events = vec![];
+        for (channel_kind, channel) in self.channels.iter_mut() {
+            if let ChannelSender::Reliable(sender) = &mut channel.sender {
+                events.extend(sender.take_finished_transfers().into_iter().map(
+                    |(message_id, outcome)| {
+                        let handle =
+                            TransferHandle::new(*channel_kind, message_id, TransferDirection::Send);
+                        (handle, outcome)
+                    },
+                ));
+            }
+            events.extend(
+                channel
+                    .receiver
+                    .fragment_receiver_mut()
+                    .take_finished()
+                    .into_iter()
+                    .map(|(message_id, outcome)| {
+                        let handle = TransferHandle::new(
+                            *channel_kind,
+                            message_id,
+                            TransferDirection::Receive,
+                        );
+                        (handle, outcome)
+                    }),
+            );
+        }
+        events
+    }
+
+    /// Take the messages sent on the user channels that were acked by the remote since the last call.
+    ///
+    /// A fragmented message is only acked once all its fragments are acked.
+    pub(crate) fn take_message_acks(&mut self) -> Vec<(ChannelKind, MessageId)> {
+        self.message_ack_receivers
+            .iter()
+            .flat_map(|(kind, receiver)| receiver.try_iter().map(|id| (*kind, id)))
+            .collect()
     }
 
     /// Read all the messages in the internal buffers that are ready to be processed
@@ -414,6 +915,125 @@ impl MessageManager {
             .ok_or(PacketError::ChannelNotFound)
     }
 
+    /// Get the flow control state of a given reliable channel, if the channel uses flow control
+    pub fn channel_flow_control_stats<C: crate::prelude::Channel>(
+        &self,
+    ) -> Option<FlowControlStats> {
+        match &self.channels.get(&ChannelKind::of::<C>())?.sender {
+            ChannelSender::Reliable(sender) => sender.flow_control_stats(),
+            _ => None,
+        }
+    }
+
+    /// Change the settings of a channel for this connection only.
+    ///
+    /// The fields of `settings_override` that are `None` use the [`ChannelSettings`](crate::channel::builder::ChannelSettings)
+    /// of the protocol: applying [`ChannelSettingsOverride::default`] removes the overrides of the channel.
+    pub fn set_channel_settings_override(
+        &mut self,
+        channel_kind: ChannelKind,
+        settings_override: ChannelSettingsOverride,
+    ) -> Result<(), PacketError> {
+        let settings = &self
+            .channel_registry
+            .get_builder_from_kind(&channel_kind)
+            .ok_or(PacketError::ChannelNotFound)?
+            .settings;
+        let channel_id = *self
+            .channel_registry
+            .get_net_from_kind(&channel_kind)
+            .ok_or(PacketError::ChannelNotFound)?;
+        let channel = self
+            .channels
+            .get_mut(&channel_kind)
+            .ok_or(PacketError::ChannelNotFound)?;
+        // the mode stays the same, only the parameters of the reliable sender change
+        channel.setting.mode = match &settings.mode {
+            ChannelMode::UnorderedReliable(reliable_settings) => ChannelMode::UnorderedReliable(
+                settings_override.reliable_settings(reliable_settings),
+            ),
+            ChannelMode::SequencedReliable(reliable_settings) => ChannelMode::SequencedReliable(
+                settings_override.reliable_settings(reliable_settings),
+            ),
+            ChannelMode::OrderedReliable(reliable_settings) => {
+                ChannelMode::OrderedReliable(settings_override.reliable_settings(reliable_settings))
+            }
+            mode => mode.clone(),
+        };
+        channel.setting.priority = settings_override.priority.unwrap_or(settings.priority);
+        if let (
+            ChannelSender::Reliable(sender),
+            ChannelMode::UnorderedReliable(reliable_settings)
+            | ChannelMode::SequencedReliable(reliable_settings)
+            | ChannelMode::OrderedReliable(reliable_settings),
+        ) = (&mut channel.sender, &channel.setting.mode)
+        {
+            sender.set_reliable_settings(reliable_settings.clone());
+            sender.set_max_in_flight(settings_override.max_in_flight);
+        }
+        self.priority_manager
+            .set_channel_priority(channel_id, settings_override.priority);
+        if settings_override == ChannelSettingsOverride::default() {
+            self.channel_overrides.remove(&channel_kind);
+        } else {
+            self.channel_overrides
+                .insert(channel_kind, settings_override);
+        }
+        Ok(())
+    }
+
+    /// Get the settings of a channel that are different for this connection, if any
+    pub fn channel_settings_override(
+        &self,
+        channel_kind: ChannelKind,
+    ) -> Option<&ChannelSettingsOverride> {
+        self.channel_overrides.get(&channel_kind)
+    }
+
+    /// Update the stats of a channel after receiving a message, or a fragment of a message
+    fn record_received(
+        &mut self,
+        channel_id: ChannelId,
+        new_message: bool,
+        num_bytes: usize,
+    ) -> Result<(), PacketError> {
+        let channel_kind = self
+            .channel_registry
+            .get_kind_from_net_id(channel_id)
+            .ok_or(PacketError::ChannelNotFound)?;
+        let stats = self.channel_stats.entry(*channel_kind).or_default();
+        stats.messages_received += u64::from(new_message);
+        stats.bytes_received += num_bytes as u64;
+        Ok(())
+    }
+
+    /// Get the [`ChannelStats`] of a given channel
+    pub fn channel_stats(&self, channel_kind: ChannelKind) -> Option<&ChannelStats> {
+        self.channel_stats.get(&channel_kind)
+    }
+
+    /// Iterate through the [`ChannelStats`] of every channel
+    pub(crate) fn all_channel_stats(&self) -> impl Iterator<Item = (ChannelKind, &ChannelStats)> {
+        self.channel_stats
+            .iter()
+            .map(|(kind, stats)| (*kind, stats))
+    }
+
+    /// Number of reliable messages (or fragments) that were sent again on every channel
+    pub(crate) fn messages_resent(&self) -> u64 {
+        self.channel_stats
+            .values()
+            .map(|stats| stats.messages_resent)
+            .sum()
+    }
+
+    /// Reset the [`ChannelStats`] of every channel
+    pub(crate) fn reset_channel_stats(&mut self) {
+        self.channel_stats
+            .values_mut()
+            .for_each(|stats| *stats = ChannelStats::default());
+    }
+
     /// Get the ChannelSendStats of a given channel
     #[cfg(feature = "trace")]
     pub fn channel_send_stats<C: crate::prelude::Channel>(&self) -> Option<&ChannelSendStats> {
@@ -430,18 +1050,23 @@ mod tests {
     use std::collections::HashMap;
 
     use bevy::prelude::default;
+    use bevy::utils::Duration;
 
+    use crate::channel::builder::{EntityUpdatesChannel, PingChannel, PongChannel};
     use crate::packet::message::MessageId;
     use crate::packet::packet::FRAGMENT_SIZE;
     use crate::packet::priority_manager::PriorityConfig;
     use crate::prelude::*;
+    use governor::Quota;
+    use nonzero_ext::nonzero;
 
     use crate::tests::protocol::*;
 
     use super::*;
 
-    fn setup() -> (MessageManager, MessageManager) {
+    fn setup(header_mode: PacketHeaderMode) -> (MessageManager, MessageManager) {
         let mut channel_registry = ChannelRegistry::default();
+        channel_registry.set_packet_header_mode(header_mode);
         channel_registry.add_channel::<Channel1>(ChannelSettings {
             mode: ChannelMode::UnorderedUnreliable,
             ..default()
@@ -466,220 +1091,637 @@ mod tests {
         //     .with_span_events(FmtSpan::ENTER)
         //     .with_max_level(tracing::Level::TRACE)
         //     .init();
-        let (mut client_message_manager, mut server_message_manager) = setup();
+        for header_mode in [PacketHeaderMode::Standard, PacketHeaderMode::Compact] {
+            let (mut client_message_manager, mut server_message_manager) = setup(header_mode);
 
-        // client: buffer send messages, and then send
-        let message: Bytes = vec![0, 1].into();
-        let channel_kind_1 = ChannelKind::of::<Channel1>();
-        let channel_kind_2 = ChannelKind::of::<Channel2>();
-        client_message_manager.buffer_send(message.clone(), channel_kind_1)?;
-        client_message_manager.buffer_send(message.clone(), channel_kind_2)?;
-        let payloads = client_message_manager.send_packets(Tick(0))?;
-        assert_eq!(
-            client_message_manager.packet_to_message_ack_map,
-            HashMap::from([(
-                PacketId(0),
-                vec![(
-                    channel_kind_2,
-                    MessageAck {
-                        message_id: MessageId(0),
-                        fragment_id: None,
-                    }
-                )]
-            )])
-        );
+            // client: buffer send messages, and then send
+            let message: Bytes = vec![0, 1].into();
+            let channel_kind_1 = ChannelKind::of::<Channel1>();
+            let channel_kind_2 = ChannelKind::of::<Channel2>();
+            client_message_manager.buffer_send(message.clone(), channel_kind_1)?;
+            client_message_manager.buffer_send(message.clone(), channel_kind_2)?;
+            let payloads = client_message_manager.send_packets(Tick(0))?;
+            assert_eq!(
+                client_message_manager.packet_to_message_ack_map,
+                HashMap::from([(
+                    PacketId(0),
+                    vec![(
+                        channel_kind_2,
+                        MessageAck {
+                            message_id: MessageId(0),
+                            fragment_id: None,
+                        }
+                    )]
+                )])
+            );
 
-        // server: receive bytes from the sent messages, then process them into messages
-        for payload in payloads {
-            server_message_manager.recv_packet(payload.into())?;
-        }
-        let it = server_message_manager.read_messages();
-        let data = MessageManager::collect_messages(it);
+            // server: receive bytes from the sent messages, then process them into messages
+            for payload in payloads {
+                server_message_manager.recv_packet(payload.into())?;
+            }
+            let it = server_message_manager.read_messages();
+            let data = MessageManager::collect_messages(it);
 
-        assert_eq!(
-            data.get(&channel_kind_1).unwrap(),
-            &vec![(Tick(0), message.clone())]
-        );
-        assert_eq!(
-            data.get(&channel_kind_2).unwrap(),
-            &vec![(Tick(0), message.clone())]
-        );
+            assert_eq!(
+                data.get(&channel_kind_1).unwrap(),
+                &vec![(Tick(0), message.clone())]
+            );
+            assert_eq!(
+                data.get(&channel_kind_2).unwrap(),
+                &vec![(Tick(0), message.clone())]
+            );
 
-        // Confirm what happens if we try to receive but there is nothing on the io
-        let it = server_message_manager.read_messages();
-        let data = MessageManager::collect_messages(it);
-        assert!(data.is_empty());
+            // Confirm what happens if we try to receive but there is nothing on the io
+            let it = server_message_manager.read_messages();
+            let data = MessageManager::collect_messages(it);
+            assert!(data.is_empty());
 
-        // Check the state of the packet headers
-        assert_eq!(
-            client_message_manager
+            // Check the state of the packet headers
+            assert_eq!(
+                client_message_manager
+                    .packet_manager
+                    .header_manager
+                    .next_packet_id(),
+                PacketId(1)
+            );
+            assert!(client_message_manager
                 .packet_manager
                 .header_manager
-                .next_packet_id(),
-            PacketId(1)
-        );
-        assert!(client_message_manager
-            .packet_manager
-            .header_manager
-            .sent_packets_not_acked()
-            .contains_key(&PacketId(0)));
+                .sent_packets_not_acked()
+                .contains_key(&PacketId(0)));
 
-        // Server sends back a message
-        server_message_manager.buffer_send(message.clone(), channel_kind_1)?;
-        let payloads = server_message_manager.send_packets(Tick(0))?;
+            // Server sends back a message
+            server_message_manager.buffer_send(message.clone(), channel_kind_1)?;
+            let payloads = server_message_manager.send_packets(Tick(0))?;
 
-        // On client side: keep looping to receive bytes on the network, then process them into messages
-        for payload in payloads {
-            client_message_manager.recv_packet(payload.into())?;
-        }
+            // On client side: keep looping to receive bytes on the network, then process them into messages
+            for payload in payloads {
+                client_message_manager.recv_packet(payload.into())?;
+            }
 
-        // Check that reliability works correctly
-        assert_eq!(client_message_manager.packet_to_message_ack_map.len(), 0);
-        // TODO: check that client_channel_1's sender's unacked messages is empty
-        // let client_channel_1 = client_connection.channels.get(&channel_kind_1).unwrap();
-        // assert_eq!(client_channel_1.sender.)
+            // Check that reliability works correctly
+            assert_eq!(client_message_manager.packet_to_message_ack_map.len(), 0);
+            // TODO: check that client_channel_1's sender's unacked messages is empty
+            // let client_channel_1 = client_connection.channels.get(&channel_kind_1).unwrap();
+            // assert_eq!(client_channel_1.sender.)
+        }
         Ok(())
     }
 
     #[test]
     /// We want to test that we can send/receive messages over a connection
     fn test_message_manager_fragment_message() -> Result<(), PacketError> {
-        let (mut client_message_manager, mut server_message_manager) = setup();
+        for header_mode in [PacketHeaderMode::Standard, PacketHeaderMode::Compact] {
+            let (mut client_message_manager, mut server_message_manager) = setup(header_mode);
+
+            // client: buffer send messages, and then send
+            const MESSAGE_SIZE: usize = (1.5 * FRAGMENT_SIZE as f32) as usize;
+
+            let message = Bytes::copy_from_slice(&[0; MESSAGE_SIZE]);
+            let channel_kind_1 = ChannelKind::of::<Channel1>();
+            let channel_kind_2 = ChannelKind::of::<Channel2>();
+            client_message_manager.buffer_send(message.clone(), channel_kind_1)?;
+            client_message_manager.buffer_send(message.clone(), channel_kind_2)?;
+            let payloads = client_message_manager.send_packets(Tick(0))?;
+            assert_eq!(payloads.len(), 4);
+            // the order of the packets is not guaranteed
+            let packets_with_acks = client_message_manager
+                .packet_to_message_ack_map
+                .keys()
+                .copied()
+                .collect::<Vec<_>>();
+            let acks: Vec<_> = client_message_manager
+                .packet_to_message_ack_map
+                .clone()
+                .into_values()
+                .collect();
+            assert!(acks.contains(&vec![(
+                channel_kind_2,
+                MessageAck {
+                    message_id: MessageId(0),
+                    fragment_id: Some(0),
+                }
+            )]));
+            assert!(acks.contains(&vec![(
+                channel_kind_2,
+                MessageAck {
+                    message_id: MessageId(0),
+                    fragment_id: Some(1),
+                }
+            )]));
+
+            // server: receive bytes from the sent messages, then process them into messages
+            for payload in payloads {
+                server_message_manager.recv_packet(payload.into())?;
+            }
+            let it = server_message_manager.read_messages();
+            let data = MessageManager::collect_messages(it);
+            assert_eq!(
+                data.get(&channel_kind_1).unwrap(),
+                &vec![(Tick(0), message.clone())]
+            );
+            assert_eq!(
+                data.get(&channel_kind_2).unwrap(),
+                &vec![(Tick(0), message.clone())]
+            );
+
+            // Confirm what happens if we try to receive but there is nothing on the io
+            let it = server_message_manager.read_messages();
+            let data = MessageManager::collect_messages(it);
+            assert!(data.is_empty());
+
+            // Check the state of the packet headers
+            assert_eq!(
+                client_message_manager
+                    .packet_manager
+                    .header_manager
+                    .next_packet_id(),
+                PacketId(4)
+            );
+            for packet in packets_with_acks {
+                assert!(client_message_manager
+                    .packet_manager
+                    .header_manager
+                    .sent_packets_not_acked()
+                    .contains_key(&packet));
+            }
+
+            // Server sends back a message
+            server_message_manager.buffer_send(vec![1].into(), channel_kind_1)?;
+            let payloads = server_message_manager.send_packets(Tick(0))?;
+
+            // On client side: keep looping to receive bytes on the network, then process them into messages
+            for payload in payloads {
+                client_message_manager.recv_packet(payload.into())?;
+            }
+
+            // Check that reliability works correctly
+            assert_eq!(client_message_manager.packet_to_message_ack_map.len(), 0);
+            // TODO: check that client_channel_1's sender's unacked messages is empty
+            // let client_channel_1 = client_connection.channels.get(&channel_kind_1).unwrap();
+            // assert_eq!(client_channel_1.sender.)
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_notify_ack() -> Result<(), PacketError> {
+        for header_mode in [PacketHeaderMode::Standard, PacketHeaderMode::Compact] {
+            let (mut client_message_manager, mut server_message_manager) = setup(header_mode);
+
+            let update_acks_tracker = client_message_manager
+                .channels
+                .get_mut(&ChannelKind::of::<Channel2>())
+                .unwrap()
+                .sender
+                .subscribe_acks();
+
+            let message_id = client_message_manager
+                .buffer_send(vec![0].into(), Channel2::kind())?
+                .unwrap();
+            assert_eq!(message_id, MessageId(0));
+            let payloads = client_message_manager.send_packets(Tick(0))?;
+            assert_eq!(
+                client_message_manager.packet_to_message_ack_map,
+                HashMap::from([(
+                    PacketId(0),
+                    vec![(
+                        Channel2::kind(),
+                        MessageAck {
+                            message_id,
+                            fragment_id: None,
+                        }
+                    )]
+                )])
+            );
+
+            // server: receive bytes from the sent messages, then process them into messages
+            for payload in payloads {
+                server_message_manager.recv_packet(payload.into())?;
+            }
+
+            // Server sends back a message (to ack the message)
+            server_message_manager.buffer_send(vec![1].into(), Channel2::kind())?;
+            let payloads = server_message_manager.send_packets(Tick(0))?;
+
+            // On client side: keep looping to receive bytes on the network, then process them into messages
+            for payload in payloads {
+                client_message_manager.recv_packet(payload.into())?;
+            }
+
+            assert_eq!(update_acks_tracker.try_recv().unwrap(), message_id);
+            assert_eq!(
+                client_message_manager.take_message_acks(),
+                vec![(Channel2::kind(), message_id)]
+            );
+            assert!(client_message_manager.take_message_acks().is_empty());
+        }
+        Ok(())
+    }
+
+    /// Simulate the traffic of the `simple_box` example: every tick the server sends the position
+    /// update of the player entity and the client sends its inputs; pings and pongs are exchanged every 10 ticks.
+    ///
+    /// Returns the number of bytes and packets sent by the server and by the client.
+    fn simple_box_traffic(
+        header_mode: PacketHeaderMode,
+    ) -> Result<((usize, usize), (usize, usize)), PacketError> {
+        let mut channel_registry = ChannelRegistry::new(Duration::default());
+        channel_registry.set_packet_header_mode(header_mode);
+        let mut client_message_manager =
+            MessageManager::new(&channel_registry, 1.5, PriorityConfig::default());
+        let mut server_message_manager =
+            MessageManager::new(&channel_registry, 1.5, PriorityConfig::default());
+        let (mut server_bytes, mut server_packets) = (0, 0);
+        let (mut client_bytes, mut client_packets) = (0, 0);
+        for i in 0..100 {
+            // entity + position update
+            server_message_manager.buffer_send(vec![0; 14].into(), EntityUpdatesChannel::kind())?;
+            // inputs
+            client_message_manager.buffer_send(vec![0; 8].into(), InputChannel::kind())?;
+            if i % 10 == 0 {
+                client_message_manager.buffer_send(vec![0; 6].into(), PingChannel::kind())?;
+                server_message_manager.buffer_send(vec![0; 10].into(), PongChannel::kind())?;
+            }
+            for payload in server_message_manager.send_packets(Tick(i))? {
+                server_bytes += payload.len();
+                server_packets += 1;
+                client_message_manager.recv_packet(payload.into())?;
+            }
+            for payload in client_message_manager.send_packets(Tick(i))? {
+                client_bytes += payload.len();
+                client_packets += 1;
+                server_message_manager.recv_packet(payload.into())?;
+            }
+        }
+        // check that all the entity updates were acked
+        assert!(server_message_manager.packet_to_message_ack_map.is_empty());
+        Ok((
+            (server_bytes, server_packets),
+            (client_bytes, client_packets),
+        ))
+    }
+
+    /// Compact headers reduce the number of bytes per packet, without changing the number of packets
+    #[test]
+    fn test_compact_header_bytes_per_packet() -> Result<(), PacketError> {
+        let (server_standard, client_standard) = simple_box_traffic(PacketHeaderMode::Standard)?;
+        let (server_compact, client_compact) = simple_box_traffic(PacketHeaderMode::Compact)?;
+        for (name, standard, compact, saved_per_packet) in [
+            // the server packets contain replication updates, so they still include the tick
+            ("server", server_standard, server_compact, 2),
+            // the client packets only contain inputs and pings: the tick is omitted
+            ("client", client_standard, client_compact, 4),
+        ] {
+            let (standard_bytes, standard_packets) = standard;
+            let (compact_bytes, compact_packets) = compact;
+            assert_eq!(standard_packets, compact_packets, "{name}");
+            assert!(compact_bytes < standard_bytes, "{name}");
+            assert_eq!(
+                standard_bytes - compact_bytes,
+                saved_per_packet * standard_packets,
+                "{name}: {standard_bytes} bytes with standard headers, {compact_bytes} with compact headers, \
+                 in {standard_packets} packets"
+            );
+        }
+        Ok(())
+    }
 
-        // client: buffer send messages, and then send
-        const MESSAGE_SIZE: usize = (1.5 * FRAGMENT_SIZE as f32) as usize;
+    /// The receiver can only buffer 2 messages: the sender should only send new messages when the window reopens
+    #[test]
+    fn test_flow_control_tiny_receive_window() -> Result<(), PacketError> {
+        let mut channel_registry = ChannelRegistry::default();
+        channel_registry.add_channel::<FlowControlChannel>(ChannelSettings {
+            mode: ChannelMode::SequencedUnreliable,
+            priority: f32::INFINITY,
+            ..default()
+        });
+        channel_registry.add_channel::<Channel3>(ChannelSettings {
+            mode: ChannelMode::OrderedReliable(ReliableSettings {
+                receive_window: Some(2),
+                ..default()
+            }),
+            ..default()
+        });
+        let mut client_message_manager =
+            MessageManager::new(&channel_registry, 1.5, PriorityConfig::default());
+        let mut server_message_manager =
+            MessageManager::new(&channel_registry, 1.5, PriorityConfig::default());
+        let channel_kind = ChannelKind::of::<Channel3>();
 
-        let message = Bytes::copy_from_slice(&[0; MESSAGE_SIZE]);
+        for i in 0..10u8 {
+            client_message_manager.buffer_send(vec![i].into(), channel_kind)?;
+        }
+        let mut received = vec![];
+        for round in 0..5 {
+            for payload in client_message_manager.send_packets(Tick(round))? {
+                server_message_manager.recv_packet(payload.into())?;
+            }
+            let channel = server_message_manager
+                .channels
+                .get_mut(&channel_kind)
+                .unwrap();
+            let mut received_this_round = 0;
+            while let Some((_, bytes)) = channel.receiver.read_message() {
+                received.push(bytes[0]);
+                received_this_round += 1;
+            }
+            // the sender paces itself: no more messages than the window are sent at once
+            assert_eq!(received_this_round, 2);
+            let stats = client_message_manager
+                .channel_flow_control_stats::<Channel3>()
+                .unwrap();
+            assert_eq!(
+                stats,
+                FlowControlStats {
+                    available_window: 0,
+                    in_flight: 2,
+                    blocked: 8 - 2 * round,
+                }
+            );
+
+            // the server acks the messages and advertises its new window
+            for payload in server_message_manager.send_packets(Tick(round))? {
+                client_message_manager.recv_packet(payload.into())?;
+            }
+            let stats = client_message_manager
+                .channel_flow_control_stats::<Channel3>()
+                .unwrap();
+            assert_eq!(stats.in_flight, 0);
+            assert_eq!(stats.available_window, 2);
+        }
+        assert_eq!(received, (0..10).collect::<Vec<_>>());
+        Ok(())
+    }
+
+    /// Two channels compete for a 1KB/s bandwidth cap, which only leaves room for one message per tick:
+    /// the low priority channel accumulates priority until it gets to send
+    #[test]
+    fn test_channel_priority_accumulation() -> Result<(), PacketError> {
+        let mut channel_registry = ChannelRegistry::default();
+        channel_registry.add_channel::<Channel1>(ChannelSettings {
+            mode: ChannelMode::UnorderedUnreliable,
+            priority: 3.0,
+            ..default()
+        });
+        channel_registry.add_channel::<Channel2>(ChannelSettings {
+            mode: ChannelMode::UnorderedUnreliable,
+            priority: 1.0,
+            ..default()
+        });
+        let priority_config = PriorityConfig {
+            bandwidth_quota: Quota::per_second(nonzero!(1000u32)).allow_burst(nonzero!(200u32)),
+            enabled: true,
+            ..default()
+        };
+        let mut client_message_manager =
+            MessageManager::new(&channel_registry, 1.5, priority_config);
+        let mut server_message_manager =
+            MessageManager::new(&channel_registry, 1.5, PriorityConfig::default());
+        let high_priority = ChannelKind::of::<Channel1>();
+        let low_priority = ChannelKind::of::<Channel2>();
+
+        let mut received = HashMap::<ChannelKind, usize>::new();
+        for tick in 0..40 {
+            client_message_manager
+                .priority_manager
+                .advance(Duration::from_millis(200));
+            client_message_manager.buffer_send(vec![0; 150].into(), high_priority)?;
+            client_message_manager.buffer_send(vec![1; 150].into(), low_priority)?;
+            for payload in client_message_manager.send_packets(Tick(tick))? {
+                server_message_manager.recv_packet(payload.into())?;
+            }
+            for (channel_kind, messages) in
+                MessageManager::collect_messages(server_message_manager.read_messages())
+            {
+                *received.entry(channel_kind).or_default() += messages.len();
+            }
+        }
+        let high = received.get(&high_priority).copied().unwrap_or_default();
+        let low = received.get(&low_priority).copied().unwrap_or_default();
+        // the bandwidth cap only lets one message through per tick
+        assert!(high + low <= 40);
+        // the low priority channel is not starved, but sends less often
+        assert!(low > 0);
+        assert!(high > low);
+        Ok(())
+    }
+
+    #[test]
+    /// The channel stats count the messages and bytes sent and received on each channel
+    fn test_channel_stats() -> Result<(), PacketError> {
+        let (mut client_message_manager, mut server_message_manager) =
+            setup(PacketHeaderMode::Standard);
         let channel_kind_1 = ChannelKind::of::<Channel1>();
         let channel_kind_2 = ChannelKind::of::<Channel2>();
-        client_message_manager.buffer_send(message.clone(), channel_kind_1)?;
-        client_message_manager.buffer_send(message.clone(), channel_kind_2)?;
-        let payloads = client_message_manager.send_packets(Tick(0))?;
-        assert_eq!(payloads.len(), 4);
-        // the order of the packets is not guaranteed
-        let packets_with_acks = client_message_manager
-            .packet_to_message_ack_map
-            .keys()
-            .copied()
-            .collect::<Vec<_>>();
-        let acks: Vec<_> = client_message_manager
-            .packet_to_message_ack_map
-            .clone()
-            .into_values()
-            .collect();
-        assert!(acks.contains(&vec![(
-            channel_kind_2,
-            MessageAck {
-                message_id: MessageId(0),
-                fragment_id: Some(0),
-            }
-        )]));
-        assert!(acks.contains(&vec![(
-            channel_kind_2,
-            MessageAck {
-                message_id: MessageId(0),
-                fragment_id: Some(1),
-            }
-        )]));
-
-        // server: receive bytes from the sent messages, then process them into messages
-        for payload in payloads {
+        let small_message: Bytes = vec![0, 1].into();
+        let big_message: Bytes = vec![1u8; FRAGMENT_SIZE * 2 + 10].into();
+        client_message_manager.buffer_send(small_message.clone(), channel_kind_1)?;
+        client_message_manager.buffer_send(small_message.clone(), channel_kind_1)?;
+        client_message_manager.buffer_send(big_message.clone(), channel_kind_1)?;
+        let total_bytes = (2 * small_message.len() + big_message.len()) as u64;
+        assert_eq!(
+            client_message_manager.channel_stats(channel_kind_1),
+            Some(&ChannelStats {
+                messages_sent: 3,
+                bytes_sent: total_bytes,
+                // the big message is split into 3 fragments
+                buffered: 5,
+                ..default()
+            })
+        );
+
+        for payload in client_message_manager.send_packets(Tick(0))? {
             server_message_manager.recv_packet(payload.into())?;
         }
-        let it = server_message_manager.read_messages();
-        let data = MessageManager::collect_messages(it);
+        // the unreliable messages are not buffered anymore once they are sent
         assert_eq!(
-            data.get(&channel_kind_1).unwrap(),
-            &vec![(Tick(0), message.clone())]
+            client_message_manager
+                .channel_stats(channel_kind_1)
+                .unwrap()
+                .buffered,
+            0
         );
         assert_eq!(
-            data.get(&channel_kind_2).unwrap(),
-            &vec![(Tick(0), message.clone())]
+            server_message_manager.channel_stats(channel_kind_1),
+            Some(&ChannelStats {
+                messages_received: 3,
+                bytes_received: total_bytes,
+                ..default()
+            })
+        );
+        assert_eq!(
+            server_message_manager.channel_stats(channel_kind_2),
+            Some(&ChannelStats::default())
         );
 
-        // Confirm what happens if we try to receive but there is nothing on the io
-        let it = server_message_manager.read_messages();
-        let data = MessageManager::collect_messages(it);
-        assert!(data.is_empty());
-
-        // Check the state of the packet headers
+        server_message_manager.reset_channel_stats();
         assert_eq!(
-            client_message_manager
-                .packet_manager
-                .header_manager
-                .next_packet_id(),
-            PacketId(4)
+            server_message_manager.channel_stats(channel_kind_1),
+            Some(&ChannelStats::default())
         );
-        for packet in packets_with_acks {
-            assert!(client_message_manager
-                .packet_manager
-                .header_manager
-                .sent_packets_not_acked()
-                .contains_key(&packet));
-        }
+        Ok(())
+    }
 
-        // Server sends back a message
-        server_message_manager.buffer_send(vec![1].into(), channel_kind_1)?;
-        let payloads = server_message_manager.send_packets(Tick(0))?;
+    /// Buffering a message on a full reliable channel fails instead of growing the buffer
+    #[test]
+    fn test_buffer_limits_reliable_channel_full() -> Result<(), PacketError> {
+        let mut channel_registry = ChannelRegistry::default();
+        channel_registry.add_channel::<Channel3>(ChannelSettings {
+            mode: ChannelMode::OrderedReliable(ReliableSettings::default()),
+            buffer_limits: BufferLimits::default().with_max_messages(2),
+            ..default()
+        });
+        let mut message_manager =
+            MessageManager::new(&channel_registry, 1.5, PriorityConfig::default());
+        let channel_kind = ChannelKind::of::<Channel3>();
 
-        // On client side: keep looping to receive bytes on the network, then process them into messages
-        for payload in payloads {
-            client_message_manager.recv_packet(payload.into())?;
-        }
+        message_manager.buffer_send(vec![0].into(), channel_kind)?;
+        message_manager.buffer_send(vec![1].into(), channel_kind)?;
+        assert!(matches!(
+            message_manager.buffer_send(vec![2].into(), channel_kind),
+            Err(PacketError::ChannelFull)
+        ));
+        let stats = message_manager.channel_stats(channel_kind).unwrap();
+        assert_eq!(stats.messages_sent, 2);
+        assert_eq!(stats.messages_rejected, 1);
 
-        // Check that reliability works correctly
-        assert_eq!(client_message_manager.packet_to_message_ack_map.len(), 0);
-        // TODO: check that client_channel_1's sender's unacked messages is empty
-        // let client_channel_1 = client_connection.channels.get(&channel_kind_1).unwrap();
-        // assert_eq!(client_channel_1.sender.)
+        // the messages stay buffered until they are acked, even after being sent
+        message_manager.send_packets(Tick(0))?;
+        assert_eq!(
+            message_manager.channel_occupancy(channel_kind),
+            Some(ChannelOccupancy {
+                messages: 2,
+                bytes: 2,
+                limits: BufferLimits::default().with_max_messages(2),
+            })
+        );
         Ok(())
     }
 
+    /// Buffering a message on a full unreliable channel drops the oldest buffered messages
     #[test]
-    fn test_notify_ack() -> Result<(), PacketError> {
-        let (mut client_message_manager, mut server_message_manager) = setup();
-
-        let update_acks_tracker = client_message_manager
-            .channels
-            .get_mut(&ChannelKind::of::<Channel2>())
-            .unwrap()
-            .sender
-            .subscribe_acks();
+    fn test_buffer_limits_unreliable_drop_oldest() -> Result<(), PacketError> {
+        let mut channel_registry = ChannelRegistry::default();
+        channel_registry.add_channel::<Channel1>(ChannelSettings {
+            mode: ChannelMode::UnorderedUnreliable,
+            buffer_limits: BufferLimits::default().with_max_bytes(20),
+            ..default()
+        });
+        let mut client_message_manager =
+            MessageManager::new(&channel_registry, 1.5, PriorityConfig::default());
+        let mut server_message_manager =
+            MessageManager::new(&channel_registry, 1.5, PriorityConfig::default());
+        let channel_kind = ChannelKind::of::<Channel1>();
 
-        let message_id = client_message_manager
-            .buffer_send(vec![0].into(), Channel2::kind())?
-            .unwrap();
-        assert_eq!(message_id, MessageId(0));
-        let payloads = client_message_manager.send_packets(Tick(0))?;
+        for i in 0..4u8 {
+            client_message_manager.buffer_send(vec![i; 10].into(), channel_kind)?;
+        }
         assert_eq!(
-            client_message_manager.packet_to_message_ack_map,
-            HashMap::from([(
-                PacketId(0),
-                vec![(
-                    Channel2::kind(),
-                    MessageAck {
-                        message_id,
-                        fragment_id: None,
-                    }
-                )]
-            )])
+            client_message_manager
+                .channel_stats(channel_kind)
+                .unwrap()
+                .messages_dropped,
+            2
         );
-
-        // server: receive bytes from the sent messages, then process them into messages
-        for payload in payloads {
+        for payload in client_message_manager.send_packets(Tick(0))? {
             server_message_manager.recv_packet(payload.into())?;
         }
+        let channel = server_message_manager
+            .channels
+            .get_mut(&channel_kind)
+            .unwrap();
+        let mut received = vec![];
+        while let Some((_, bytes)) = channel.receiver.read_message() {
+            received.push(bytes[0]);
+        }
+        received.sort();
+        assert_eq!(received, vec![2, 3]);
+        Ok(())
+    }
 
-        // Server sends back a message (to ack the message)
-        server_message_manager.buffer_send(vec![1].into(), Channel2::kind())?;
-        let payloads = server_message_manager.send_packets(Tick(0))?;
+    /// A warning is emitted once when the occupancy of a channel goes above the threshold
+    #[test]
+    fn test_buffer_limits_occupancy_warning() -> Result<(), PacketError> {
+        let mut channel_registry = ChannelRegistry::default();
+        channel_registry.add_channel::<Channel1>(ChannelSettings {
+            mode: ChannelMode::UnorderedUnreliable,
+            buffer_limits: BufferLimits::default().with_max_messages(10),
+            ..default()
+        });
+        let mut message_manager =
+            MessageManager::new(&channel_registry, 1.5, PriorityConfig::default());
+        let channel_kind = ChannelKind::of::<Channel1>();
+
+        for i in 0..8u8 {
+            message_manager.buffer_send(vec![i].into(), channel_kind)?;
+        }
+        assert!(message_manager.take_occupancy_warnings().is_empty());
+        message_manager.buffer_send(vec![8].into(), channel_kind)?;
+        message_manager.buffer_send(vec![9].into(), channel_kind)?;
+        let warnings = message_manager.take_occupancy_warnings();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].0, channel_kind);
+        assert_eq!(warnings[0].1.messages, 9);
+        assert_eq!(warnings[0].1.ratio(), Some(0.9));
 
-        // On client side: keep looping to receive bytes on the network, then process them into messages
-        for payload in payloads {
-            client_message_manager.recv_packet(payload.into())?;
+        // the channel is only reported again after its occupancy went back below the threshold
+        message_manager.send_packets(Tick(0))?;
+        message_manager.buffer_send(vec![0].into(), channel_kind)?;
+        for i in 0..9u8 {
+            message_manager.buffer_send(vec![i].into(), channel_kind)?;
         }
+        assert_eq!(message_manager.take_occupancy_warnings().len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    /// Reusing the send buffers across frames must not change the packets that are sent
+    fn test_send_buffer_reuse_identical_packets() -> Result<(), PacketError> {
+        let (mut pooled_sender, mut pooled_receiver) = setup(PacketHeaderMode::Standard);
+        let (mut sender, mut receiver) = setup(PacketHeaderMode::Standard);
+        sender.disable_buffer_reuse();
+        // all messages are sent on the same channel, since the order in which channels are
+        // written in packets depends on the iteration order of the channels map
+        let channel_kind = ChannelKind::of::<Channel2>();
+        for tick in 0..100u16 {
+            for i in 0..(tick as usize % 7) * 20 {
+                let size = if i % 50 == 7 {
+                    // big message that needs to be fragmented
+                    2 * FRAGMENT_SIZE + i
+                } else {
+                    (i * 37 + tick as usize * 13) % 300 + 1
+                };
+                let message = Bytes::from(vec![i as u8; size]);
+                pooled_sender.buffer_send(message.clone(), channel_kind)?;
+                sender.buffer_send(message, channel_kind)?;
+            }
+            let pooled_payloads = pooled_sender.send_packets(Tick(tick))?;
+            let payloads = sender.send_packets(Tick(tick))?;
+            assert_eq!(pooled_payloads, payloads);
 
-        assert_eq!(update_acks_tracker.try_recv().unwrap(), message_id);
+            for payload in &pooled_payloads {
+                pooled_receiver.recv_packet(Bytes::copy_from_slice(payload))?;
+            }
+            for payload in payloads {
+                receiver.recv_packet(payload.into())?;
+            }
+            pooled_sender.recycle_payloads(pooled_payloads);
+
+            // the receivers send a message back, so that the packets get acked
+            pooled_receiver.buffer_send(Bytes::from_static(&[0]), channel_kind)?;
+            for payload in pooled_receiver.send_packets(Tick(tick))? {
+                pooled_sender.recv_packet(payload.into())?;
+            }
+            receiver.buffer_send(Bytes::from_static(&[0]), channel_kind)?;
+            for payload in receiver.send_packets(Tick(tick))? {
+                sender.recv_packet(payload.into())?;
+            }
+            assert_eq!(
+                pooled_sender.packet_to_message_ack_map,
+                sender.packet_to_message_ack_map
+            );
+        }
         Ok(())
     }
 }
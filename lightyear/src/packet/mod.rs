@@ -35,4 +35,6 @@ pub(crate) mod packet_builder;
 /// Defines the [`PacketType`](packet_type::PacketType) enum
 mod packet_type;
 pub(crate) mod priority_manager;
+pub mod receive_queue;
+pub(crate) mod send_buffers;
 pub(crate) mod stats_manager;
@@ -3,12 +3,12 @@ use crate::connection::netcode::MAX_PACKET_SIZE;
 use crate::packet::message::MessageAck;
 use crate::packet::packet_builder::Payload;
 use crate::protocol::channel::ChannelId;
-use crate::serialize::ToBytes;
 use crate::utils::wrapping_id::wrapping_id;
 
 cfg_if::cfg_if!(
     if #[cfg(test)] {
         use bytes::Bytes;
+        use crate::serialize::ToBytes;
         use crate::serialize::varint::VarIntReadExt;
         use crate::prelude::PacketError;
         use crate::packet::header::PacketHeader;
@@ -41,6 +41,8 @@ pub(crate) struct Packet {
     pub(crate) packet_id: PacketId,
     // How many bytes we know we are going to have to write in the packet, but haven't written yet
     pub(crate) prewritten_size: usize,
+    /// True if the packet contains messages that need the tick at which the packet was sent
+    pub(crate) needs_tick: bool,
 }
 
 impl Packet {
@@ -51,10 +53,10 @@ impl Packet {
 
     /// Check if we can write a channel_id + the number of messages in the packet.
     /// If we can, reserve some space for it
-    pub(crate) fn can_fit_channel(&mut self, channel_id: ChannelId) -> bool {
-        let size = channel_id.len() + 1;
+    pub(crate) fn can_fit_channel(&mut self, channel_id_len: usize) -> bool {
         // size of the channel + 1 for the number of messages
-        let can_fit = self.can_fit(channel_id.len() + 1);
+        let size = channel_id_len + 1;
+        let can_fit = self.can_fit(size);
         if can_fit {
             // reserve the space to write the channel
             self.prewritten_size += size;
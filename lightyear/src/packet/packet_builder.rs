@@ -1,20 +1,21 @@
 //! Module to take a buffer of messages to send and build packets
 use crate::connection::netcode::MAX_PACKET_SIZE;
-use byteorder::WriteBytesExt;
+use byteorder::{ReadBytesExt, WriteBytesExt};
 use bytes::Bytes;
 use std::collections::VecDeque;
 #[cfg(feature = "trace")]
 use tracing::{instrument, Level};
 
-use crate::packet::header::PacketHeaderManager;
+use crate::packet::header::{PacketHeader, PacketHeaderManager, PacketHeaderMode};
 use crate::packet::message::{FragmentData, MessageAck, SingleData};
 use crate::packet::packet::{Packet, FRAGMENT_SIZE};
 use crate::packet::packet_type::PacketType;
 use crate::prelude::Tick;
-use crate::protocol::channel::ChannelId;
+use crate::protocol::channel::{ChannelId, ChannelRegistry};
 use crate::protocol::registry::NetId;
-use crate::serialize::varint::varint_len;
+use crate::serialize::reader::Reader;
 use crate::serialize::{SerializationError, ToBytes};
+use crate::utils::buffer_pool::BufferPool;
 
 pub type Payload = Vec<u8>;
 
@@ -25,12 +26,82 @@ pub type Payload = Vec<u8>;
 /// store subslices in receiver channels without allocating.
 pub type RecvPayload = Bytes;
 
+/// Wire format of the packets, derived from the [`ChannelRegistry`] so that it is identical on both peers
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct PacketFormat {
+    pub(crate) header_mode: PacketHeaderMode,
+    /// Channel ids are written on a single byte instead of a varint
+    short_channel_ids: bool,
+    /// Channels whose messages need the tick at which the packet was sent
+    tick_channels: Vec<ChannelId>,
+}
+
+impl PacketFormat {
+    pub(crate) fn new(channel_registry: &ChannelRegistry) -> Self {
+        let header_mode = channel_registry.packet_header_mode();
+        if header_mode == PacketHeaderMode::Standard {
+            return Self::default();
+        }
+        Self {
+            header_mode,
+            short_channel_ids: channel_registry.len() <= u8::MAX as usize + 1,
+            tick_channels: channel_registry.tick_channels().collect(),
+        }
+    }
+
+    fn channel_id_len(&self, channel_id: ChannelId) -> usize {
+        if self.short_channel_ids {
+            1
+        } else {
+            channel_id.len()
+        }
+    }
+
+    fn write_channel_id<T: WriteBytesExt>(
+        &self,
+        channel_id: ChannelId,
+        buffer: &mut T,
+    ) -> Result<(), SerializationError> {
+        if self.short_channel_ids {
+            buffer.write_u8(channel_id as u8)?;
+            Ok(())
+        } else {
+            channel_id.to_bytes(buffer)
+        }
+    }
+
+    pub(crate) fn read_channel_id(
+        &self,
+        buffer: &mut Reader,
+    ) -> Result<ChannelId, SerializationError> {
+        if self.short_channel_ids {
+            Ok(buffer.read_u8()? as ChannelId)
+        } else {
+            ChannelId::from_bytes(buffer)
+        }
+    }
+
+    /// Returns true if the packets containing messages from this channel need to include the tick.
+    ///
+    /// The tick is always included in standard headers.
+    fn needs_tick(&self, channel_id: ChannelId) -> bool {
+        self.header_mode == PacketHeaderMode::Standard || self.tick_channels.contains(&channel_id)
+    }
+}
+
 /// `PacketBuilder` handles the process of creating a packet (writing the header and packing the
 /// messages into packets)
 #[derive(Debug)]
 pub(crate) struct PacketBuilder {
     pub(crate) header_manager: PacketHeaderManager,
+    pub(crate) format: PacketFormat,
     current_packet: Option<Packet>,
+    /// Payloads that have been sent, reused for the next packets
+    payloads: BufferPool<Payload>,
+    /// Lists of message acks of the packets that have been sent, reused for the next packets
+    message_acks: BufferPool<Vec<(ChannelId, MessageAck)>>,
+    /// List of packets returned by [`PacketBuilder::build_packets`], reused across frames
+    packets: Vec<Packet>,
     // Pre-allocated buffer to encode/decode without allocation.
     // TODO: should this be associated with Packet?
     // cursor: Vec<u8>,
@@ -44,7 +115,11 @@ impl PacketBuilder {
     pub fn new(nack_rtt_multiple: f32) -> Self {
         Self {
             header_manager: PacketHeaderManager::new(nack_rtt_multiple),
+            format: PacketFormat::default(),
             current_packet: None,
+            payloads: BufferPool::new(MAX_PACKET_SIZE),
+            message_acks: BufferPool::default(),
+            packets: Vec::new(),
             // cursor: Vec::with_capacity(PACKET_BUFFER_CAPACITY),
             // acks: Vec::new(),
 
@@ -55,9 +130,44 @@ impl PacketBuilder {
         }
     }
 
-    // TODO: get the vec from a pool of preallocated buffers
-    fn get_new_buffer(&self) -> Payload {
-        Vec::with_capacity(MAX_PACKET_SIZE)
+    pub(crate) fn with_format(mut self, format: PacketFormat) -> Self {
+        self.header_manager = self.header_manager.with_mode(format.header_mode);
+        self.format = format;
+        self
+    }
+
+    fn get_new_buffer(&mut self) -> Payload {
+        self.payloads
+            .take_or_else(|| Vec::with_capacity(MAX_PACKET_SIZE))
+    }
+
+    /// Give back the payload of a packet once it has been sent, so that its allocation can be reused
+    pub(crate) fn recycle_payload(&mut self, payload: Payload) {
+        self.payloads.give_back(payload);
+    }
+
+    /// Give back the list of message acks of a packet once it has been read
+    pub(crate) fn recycle_message_acks(&mut self, message_acks: Vec<(ChannelId, MessageAck)>) {
+        self.message_acks.give_back(message_acks);
+    }
+
+    /// Give back the list returned by [`PacketBuilder::build_packets`] once it has been emptied
+    pub(crate) fn recycle_packets(&mut self, packets: Vec<Packet>) {
+        debug_assert!(packets.is_empty());
+        self.packets = packets;
+    }
+
+    /// Release the buffers that were not needed during the recent frames
+    pub(crate) fn end_frame(&mut self) {
+        self.payloads.end_frame();
+        self.message_acks.end_frame();
+    }
+
+    /// Allocate new buffers for every packet instead of reusing them
+    #[cfg(any(test, feature = "test_utils"))]
+    pub(crate) fn disable_buffer_reuse(&mut self) {
+        self.payloads = BufferPool::disabled();
+        self.message_acks = BufferPool::disabled();
     }
 
     /// Start building new packet, we start with an empty packet
@@ -73,13 +183,14 @@ impl PacketBuilder {
             .header_manager
             .prepare_send_packet_header(PacketType::Data);
         // set the tick at which the packet will be sent
-        header.tick = current_tick;
-        header.to_bytes(&mut cursor)?;
+        header.tick = Some(current_tick);
+        self.header_manager.write_header(&header, &mut cursor)?;
         self.current_packet = Some(Packet {
             payload: cursor,
-            message_acks: vec![],
+            message_acks: self.message_acks.take(),
             packet_id: header.packet_id,
             prewritten_size: 0,
+            needs_tick: false,
         });
         Ok(())
     }
@@ -96,22 +207,24 @@ impl PacketBuilder {
             .header_manager
             .prepare_send_packet_header(PacketType::DataFragment);
         // set the tick at which the packet will be sent
-        header.tick = current_tick;
-        header.to_bytes(&mut cursor)?;
-        channel_id.to_bytes(&mut cursor)?;
+        header.tick = Some(current_tick);
+        self.header_manager.write_header(&header, &mut cursor)?;
+        self.format.write_channel_id(channel_id, &mut cursor)?;
         fragment_data.to_bytes(&mut cursor)?;
+        let mut message_acks = self.message_acks.take();
+        message_acks.push((
+            ChannelId::from(channel_id),
+            MessageAck {
+                message_id: fragment_data.message_id,
+                fragment_id: Some(fragment_data.fragment_id),
+            },
+        ));
         self.current_packet = Some(Packet {
             payload: cursor,
-            // TODO: reuse this vec allocation instead of newly allocating!
-            message_acks: vec![(
-                ChannelId::from(channel_id),
-                MessageAck {
-                    message_id: fragment_data.message_id,
-                    fragment_id: Some(fragment_data.fragment_id),
-                },
-            )],
+            message_acks,
             packet_id: header.packet_id,
             prewritten_size: 0,
+            needs_tick: self.format.needs_tick(channel_id),
         });
         Ok(())
 
@@ -139,8 +252,9 @@ impl PacketBuilder {
 
     pub fn finish_packet(&mut self) -> Packet {
         let mut packet = self.current_packet.take().unwrap();
-        packet.payload.shrink_to_fit();
-        // TODO: should we use bytes so this clone is cheap?
+        if self.format.header_mode == PacketHeaderMode::Compact && !packet.needs_tick {
+            PacketHeader::remove_compact_tick(&mut packet.payload);
+        }
         packet
     }
 
@@ -150,14 +264,16 @@ impl PacketBuilder {
     /// - sort the single data messages from smallest to largest
     /// - write the fragment data first. Big fragments take the entire packet. Small fragments have
     ///   some room to spare for small messages
+    ///
+    /// The messages are popped from the lists, but the lists themselves are kept so that they can be reused.
     #[cfg_attr(feature = "trace", instrument(level = Level::INFO, skip_all))]
     pub fn build_packets(
         &mut self,
         current_tick: Tick,
-        mut single_data: Vec<(ChannelId, VecDeque<SingleData>)>,
-        fragment_data: Vec<(ChannelId, VecDeque<FragmentData>)>,
+        single_data: &mut [(ChannelId, VecDeque<SingleData>)],
+        fragment_data: &mut [(ChannelId, VecDeque<FragmentData>)],
     ) -> Result<Vec<Packet>, SerializationError> {
-        let mut packets: Vec<Packet> = vec![];
+        let mut packets = std::mem::take(&mut self.packets);
 
         // indices in the main vec
         let mut single_data_idx = 0;
@@ -166,11 +282,12 @@ impl PacketBuilder {
             // sort from smallest to largest each array of small messages
             single_messages
                 .make_contiguous()
-                .sort_by_key(|message| message.bytes.len());
+                .sort_unstable_by_key(|message| message.bytes.len());
         }
 
         // try to fill the packet with fragment messages first
-        for (channel_id, mut fragment_messages) in fragment_data.into_iter() {
+        for (channel_id, fragment_messages) in fragment_data.iter_mut() {
+            let channel_id = *channel_id;
             while let Some(fragment_data) = fragment_messages.pop_front() {
                 debug_assert!(fragment_data.bytes.len() <= FRAGMENT_SIZE);
                 self.build_new_fragment_packet(channel_id, &fragment_data, current_tick)?;
@@ -182,7 +299,7 @@ impl PacketBuilder {
                     // it's a smaller fragment, fill it with small messages
                     'out: while single_data_idx < single_data.len() {
                         // if we don't even have space for a new channel, return the packet immediately
-                        if !packet.can_fit_channel(channel_id) {
+                        if !packet.can_fit_channel(self.format.channel_id_len(channel_id)) {
                             break;
                         }
 
@@ -195,6 +312,7 @@ impl PacketBuilder {
                             // no more messages to send in this channel, try to fill with messages from the next channels
                             if num_messages == single_messages.len() {
                                 Self::write_single_messages(
+                                    &self.format,
                                     &mut packet,
                                     single_messages,
                                     &mut num_messages,
@@ -211,6 +329,7 @@ impl PacketBuilder {
                                 // can't add any more messages (since we sorted messages from smallest to largest)
                                 // finish packet and go back to trying to write fragment messages
                                 Self::write_single_messages(
+                                    &self.format,
                                     &mut packet,
                                     single_messages,
                                     &mut num_messages,
@@ -239,7 +358,7 @@ impl PacketBuilder {
 
             let mut packet = self.current_packet.take().unwrap();
             // we need to call this to preassign the channel_id
-            if !packet.can_fit_channel(*channel_id) {
+            if !packet.can_fit_channel(self.format.channel_id_len(*channel_id)) {
                 // can't add any more messages (since we sorted messages from smallest to largest)
                 // finish packet and go back to trying to write fragment messages
                 self.current_packet = Some(packet);
@@ -254,6 +373,7 @@ impl PacketBuilder {
                 // no more messages to send in this channel, try to fill with messages from the next channels
                 if num_messages == single_messages.len() {
                     Self::write_single_messages(
+                        &self.format,
                         &mut packet,
                         single_messages,
                         &mut num_messages,
@@ -272,6 +392,7 @@ impl PacketBuilder {
                     // can't add any more messages (since we sorted messages from smallest to largest)
                     // finish packet and go back to trying to write fragment messages
                     Self::write_single_messages(
+                        &self.format,
                         &mut packet,
                         single_messages,
                         &mut num_messages,
@@ -293,6 +414,7 @@ impl PacketBuilder {
 
     /// Helper function to fill the current packet with single data message from the current channel
     fn write_single_messages(
+        format: &PacketFormat,
         packet: &mut Packet,
         messages: &mut VecDeque<SingleData>,
         num_messages: &mut usize,
@@ -300,10 +422,11 @@ impl PacketBuilder {
     ) -> Result<(), SerializationError> {
         packet.prewritten_size = packet
             .prewritten_size
-            .checked_sub(varint_len(channel_id as u64) + 1)
+            .checked_sub(format.channel_id_len(channel_id) + 1)
             .ok_or(SerializationError::SubstractionOverflow)?;
         if *num_messages > 0 {
-            channel_id.to_bytes(&mut packet.payload)?;
+            packet.needs_tick |= format.needs_tick(channel_id);
+            format.write_channel_id(channel_id, &mut packet.payload)?;
             // write the number of messages for the current channel
             packet.payload.write_u8(*num_messages as u8).unwrap();
             // write the messages
@@ -466,7 +589,7 @@ mod tests {
         let small_bytes = Bytes::from(vec![7u8; 10]);
         let small_message = SingleData::new(None, small_bytes.clone());
 
-        let single_data = vec![
+        let mut single_data = vec![
             (*channel_id1, VecDeque::from(vec![small_message.clone()])),
             (
                 *channel_id2,
@@ -474,8 +597,8 @@ mod tests {
             ),
             (*channel_id3, VecDeque::from(vec![small_message.clone()])),
         ];
-        let fragment_data = vec![];
-        let mut packets = manager.build_packets(Tick(0), single_data, fragment_data)?;
+        let mut fragment_data = vec![];
+        let mut packets = manager.build_packets(Tick(0), &mut single_data, &mut fragment_data)?;
         assert_eq!(packets.len(), 1);
         let packet = packets.pop().unwrap();
         assert_eq!(packet.message_acks, vec![]);
@@ -513,21 +636,21 @@ mod tests {
         let small_message = SingleData::new(None, small_bytes.clone());
 
         {
-            let single_data = vec![(
+            let mut single_data = vec![(
                 *channel_id1,
                 VecDeque::from(vec![small_message.clone(), small_message.clone()]),
             )];
-            let fragment_data = vec![];
-            let packets = manager.build_packets(Tick(0), single_data, fragment_data)?;
+            let mut fragment_data = vec![];
+            let packets = manager.build_packets(Tick(0), &mut single_data, &mut fragment_data)?;
             assert_eq!(packets.len(), 2);
         }
         {
-            let single_data = vec![
+            let mut single_data = vec![
                 (*channel_id1, VecDeque::from(vec![small_message.clone()])),
                 (*channel_id2, VecDeque::from(vec![small_message.clone()])),
             ];
-            let fragment_data = vec![];
-            let packets = manager.build_packets(Tick(0), single_data, fragment_data)?;
+            let mut fragment_data = vec![];
+            let packets = manager.build_packets(Tick(0), &mut single_data, &mut fragment_data)?;
             assert_eq!(packets.len(), 2);
         }
         Ok(())
@@ -548,7 +671,7 @@ mod tests {
         let small_bytes = Bytes::from(vec![7u8; 10]);
         let small_message = SingleData::new(None, small_bytes.clone());
 
-        let single_data = vec![
+        let mut single_data = vec![
             (
                 *channel_id1,
                 VecDeque::from(vec![small_message.clone(); 200]),
@@ -562,8 +685,8 @@ mod tests {
                 VecDeque::from(vec![small_message.clone(); 200]),
             ),
         ];
-        let fragment_data = vec![];
-        let packets = manager.build_packets(Tick(0), single_data, fragment_data)?;
+        let mut fragment_data = vec![];
+        let packets = manager.build_packets(Tick(0), &mut single_data, &mut fragment_data)?;
         assert_eq!(packets.len(), 7);
         Ok(())
     }
@@ -583,7 +706,7 @@ mod tests {
         let small_bytes = Bytes::from(vec![7u8; 500]);
         let small_message = SingleData::new(None, small_bytes.clone());
 
-        let single_data = vec![
+        let mut single_data = vec![
             (*channel_id1, VecDeque::from(vec![small_message.clone()])),
             (
                 *channel_id2,
@@ -591,8 +714,8 @@ mod tests {
             ),
             (*channel_id3, VecDeque::from(vec![small_message.clone()])),
         ];
-        let fragment_data = vec![];
-        let packets = manager.build_packets(Tick(0), single_data, fragment_data)?;
+        let mut fragment_data = vec![];
+        let packets = manager.build_packets(Tick(0), &mut single_data, &mut fragment_data)?;
         assert_eq!(packets.len(), 2);
         Ok(())
     }
@@ -623,7 +746,7 @@ mod tests {
         let small_bytes = Bytes::from(vec![7u8; 10]);
         let small_message = SingleData::new(None, small_bytes.clone());
 
-        let single_data = vec![
+        let mut single_data = vec![
             (*channel_id1, VecDeque::from(vec![small_message.clone()])),
             (
                 *channel_id2,
@@ -631,8 +754,8 @@ mod tests {
             ),
             (*channel_id3, VecDeque::from(vec![small_message.clone()])),
         ];
-        let fragment_data = vec![(*channel_id2, fragments.clone().into())];
-        let packets = manager.build_packets(Tick(0), single_data, fragment_data)?;
+        let mut fragment_data = vec![(*channel_id2, fragments.clone().into())];
+        let packets = manager.build_packets(Tick(0), &mut single_data, &mut fragment_data)?;
         assert_eq!(packets.len(), 2);
 
         let mut packets_queue: VecDeque<_> = packets.into();
@@ -1,24 +1,35 @@
-use bevy::utils::HashMap;
+use bevy::utils::{Duration, HashMap};
 use std::collections::VecDeque;
 use std::num::NonZeroU32;
 
 use crossbeam_channel::{Receiver, Sender};
-use governor::{DefaultDirectRateLimiter, Quota};
+use governor::clock::{Clock, FakeRelativeClock};
+use governor::middleware::NoOpMiddleware;
+use governor::state::{InMemoryState, NotKeyed};
+use governor::{Quota, RateLimiter};
 use nonzero_ext::*;
 use tracing::{debug, error, trace};
 #[cfg(feature = "trace")]
 use tracing::{instrument, Level};
 
-use crate::packet::message::{FragmentData, MessageData, MessageId, SendMessage, SingleData};
+use crate::packet::message::{MessageData, MessageId};
+use crate::packet::send_buffers::SendBuffers;
 use crate::prelude::{ChannelRegistry, Tick};
 use crate::protocol::channel::ChannelId;
 use crate::protocol::registry::NetId;
+use crate::shared::replication::classes::{
+    ClassScheduler, ReplicationClass, ReplicationClassQuotas, ReplicationClassStats,
+    MAX_REPLICATION_CLASSES,
+};
+use crate::utils::buffer_pool::BufferPool;
 
 const BYPASS_QUOTA_PRIORITY: f32 = 100000.0;
 
 #[derive(Debug)]
 pub struct BufferedMessage {
     priority: f32,
+    /// True if the message is sent even if the bandwidth quota is exhausted
+    bypass_quota: bool,
     channel_net_id: NetId,
     data: MessageData,
 }
@@ -29,6 +40,8 @@ pub struct PriorityConfig {
     pub bandwidth_quota: Quota,
     /// If false, there is no bandwidth cap and all messages are sent as soon as possible
     pub enabled: bool,
+    /// Share of the bandwidth quota reserved for the replication messages of each class
+    pub class_quotas: ReplicationClassQuotas,
 }
 
 // this is mostly for testing
@@ -38,6 +51,7 @@ impl Default for PriorityConfig {
             // 56 KB/s bandwidth cap
             bandwidth_quota: Quota::per_second(nonzero!(56000u32)),
             enabled: false,
+            class_quotas: ReplicationClassQuotas::default(),
         }
     }
 }
@@ -47,6 +61,7 @@ impl From<crate::client::config::PacketConfig> for PriorityConfig {
         Self {
             bandwidth_quota: value.send_bandwidth_cap,
             enabled: value.bandwidth_cap_enabled,
+            class_quotas: ReplicationClassQuotas::default(),
         }
     }
 }
@@ -56,32 +71,121 @@ impl From<crate::server::config::PacketConfig> for PriorityConfig {
         Self {
             bandwidth_quota: value.per_client_send_bandwidth_cap,
             enabled: value.bandwidth_cap_enabled,
+            class_quotas: ReplicationClassQuotas::default(),
         }
     }
 }
 
+/// Rate limiter whose clock is advanced with the networking time (see [`PriorityManager::advance`]),
+/// so that the bandwidth quota is consistent with the time used by the rest of the connection
+type BandwidthLimiter = RateLimiter<
+    NotKeyed,
+    InMemoryState,
+    FakeRelativeClock,
+    NoOpMiddleware<<FakeRelativeClock as Clock>::Instant>,
+>;
+
 #[derive(Debug)]
 pub(crate) struct PriorityManager {
     pub(crate) config: PriorityConfig,
     // TODO: can I do without this limiter?
-    pub(crate) limiter: DefaultDirectRateLimiter,
+    limiter: BandwidthLimiter,
+    clock: FakeRelativeClock,
+    /// Bytes that were sent while the quota was exhausted (packet headers, etc.).
+    /// They have to be paid back before any message that doesn't bypass the quota can be sent.
+    overdraft: u32,
     // // Internal buffer of data that we want to send
     // // Reuse allocation across frames
     // data_to_send: BTreeMap<ChannelId, (VecDeque<SendMessage>, VecDeque<SendMessage>)>,
-    // Messages that could not be sent because of the bandwidth quota
-    // buffered_data: Vec<BufferedMessage>,
+    /// Messages sorted by priority, kept to reuse the allocation across frames
+    buffered_messages: Vec<BufferedMessage>,
+    /// Priority accumulated by each channel since it last got a message into a packet.
+    ///
+    /// Every time a channel has messages to send, its priority is added to its accumulated priority,
+    /// so that a channel that was starved by higher priority channels eventually gets to send.
+    accumulated_channel_priority: HashMap<ChannelId, f32>,
+    /// Priorities that replace the priority of the channel in the [`ChannelRegistry`] for this connection
+    channel_priority_overrides: HashMap<ChannelId, f32>,
     /// List of senders to notify when a replication update message is actually sent (included in packet)
     replication_update_senders: Vec<Sender<MessageId>>,
+    /// Shares the bandwidth between the replication classes, if class quotas are configured
+    class_scheduler: Option<ClassScheduler>,
+    /// Class of the replication messages that are waiting to be sent (or to be acked, for reliable channels)
+    message_classes: HashMap<(ChannelId, MessageId), ReplicationClass>,
+    /// Messages of each replication class, sorted by priority, waiting for the bandwidth left by the other messages
+    class_queues: [VecDeque<BufferedMessage>; MAX_REPLICATION_CLASSES],
+    /// Bytes sent for each replication class since the connection started
+    pub(crate) class_stats: ReplicationClassStats,
+    /// Bytes sent for each replication class since the last diagnostics flush
+    pub(crate) unflushed_class_stats: ReplicationClassStats,
 }
 
 impl PriorityManager {
     pub(crate) fn new(config: PriorityConfig) -> Self {
+        let clock = FakeRelativeClock::default();
+        let class_scheduler = (config.enabled && config.class_quotas.is_enabled())
+            .then(|| ClassScheduler::new(config.class_quotas));
         Self {
             config: config.clone(),
-            limiter: DefaultDirectRateLimiter::direct(config.bandwidth_quota),
+            limiter: RateLimiter::direct_with_clock(config.bandwidth_quota, &clock),
+            clock,
+            overdraft: 0,
             // data_to_send: BTreeMap::new(),
-            // buffered_data: Vec::new(),
+            buffered_messages: Vec::new(),
+            accumulated_channel_priority: HashMap::default(),
+            channel_priority_overrides: HashMap::default(),
             replication_update_senders: Vec::new(),
+            class_scheduler,
+            message_classes: HashMap::default(),
+            class_queues: Default::default(),
+            class_stats: ReplicationClassStats::default(),
+            unflushed_class_stats: ReplicationClassStats::default(),
+        }
+    }
+
+    /// Use the budget of `class` to send a replication message, if class quotas are configured
+    pub(crate) fn set_message_class(
+        &mut self,
+        channel_id: ChannelId,
+        message_id: MessageId,
+        class: ReplicationClass,
+    ) {
+        if self.class_scheduler.is_some() {
+            self.message_classes.insert((channel_id, message_id), class);
+        }
+    }
+
+    /// Forget the class of a reliable message once it has been acked
+    pub(crate) fn message_acked(&mut self, channel_id: ChannelId, message_id: MessageId) {
+        if self.class_scheduler.is_some() {
+            self.message_classes.remove(&(channel_id, message_id));
+        }
+    }
+
+    /// Replace the priority of a channel for this connection, or use the priority of the [`ChannelRegistry`]
+    /// again if `priority` is `None`
+    pub(crate) fn set_channel_priority(&mut self, channel_id: ChannelId, priority: Option<f32>) {
+        match priority {
+            Some(priority) => self.channel_priority_overrides.insert(channel_id, priority),
+            None => self.channel_priority_overrides.remove(&channel_id),
+        };
+    }
+
+    /// Advance the clock of the rate limiter, which replenishes the bandwidth quota
+    pub(crate) fn advance(&mut self, delta: Duration) {
+        self.clock.advance(delta);
+    }
+
+    /// Add bytes that were already sent to the rate limiter.
+    ///
+    /// If the quota is exhausted, the bytes are kept as an overdraft that will be paid back before
+    /// sending new messages, so that the bytes sent never exceed the quota over time.
+    pub(crate) fn charge(&mut self, bytes: NonZeroU32) {
+        if !matches!(self.limiter.check_n(bytes), Ok(Ok(()))) {
+            self.overdraft = self
+                .overdraft
+                .saturating_add(bytes.get())
+                .min(self.config.bandwidth_quota.burst_size().get());
         }
     }
 
@@ -93,148 +197,257 @@ impl PriorityManager {
         receiver
     }
 
+    /// Class of a replication message, if class quotas are configured
+    fn message_class(&self, message: &BufferedMessage) -> Option<ReplicationClass> {
+        self.class_scheduler.as_ref()?;
+        let message_id = message.data.message_id()?;
+        self.message_classes
+            .get(&(message.channel_net_id, message_id))
+            .copied()
+    }
+
+    /// Check that the bandwidth quota allows sending the message.
+    ///
+    /// Returns the number of bytes added to the rate limiter, or `None` if the message cannot be sent.
+    fn check_quota(&mut self, message: &BufferedMessage) -> Option<u32> {
+        if self.overdraft > 0 && !message.bypass_quota {
+            debug!("Bandwidth quota overdrawn, no more messages can be sent this tick");
+            return None;
+        }
+        // we don't use the exact size of the message, but the size of the bytes
+        // we will adjust for this later
+        let message_bytes = message.data.len() as u32;
+        let nonzero_message_bytes = NonZeroU32::try_from(message_bytes).unwrap();
+        let Ok(result) = self.limiter.check_n(nonzero_message_bytes) else {
+            error!("the bandwidth does not have enough capacity for a message of this size!");
+            return None;
+        };
+        // above BYPASS_QUOTA_PRIORITY, we still send the message
+        if !message.bypass_quota && result.is_err() {
+            debug!("Bandwidth quota reached, no more messages can be sent this tick");
+            return None;
+        }
+        // keep track of the bytes we added to the rate limiter
+        // (the messages that bypassed an exhausted quota will be charged once the packet is built)
+        Some(if result.is_ok() { message_bytes } else { 0 })
+    }
+
+    /// Select the queued messages of the replication classes, sharing the bandwidth between the classes
+    /// in proportion to their quotas.
+    ///
+    /// Returns the amount of bytes we used in the rate limiter.
+    fn select_class_messages(
+        &mut self,
+        buffers: &mut SendBuffers,
+        channel_registry: &ChannelRegistry,
+    ) -> u32 {
+        let Some(mut scheduler) = self.class_scheduler.take() else {
+            return 0;
+        };
+        let mut bytes_used = 0;
+        loop {
+            let queues = &self.class_queues;
+            // the classes without quota only use the bandwidth that the other classes did not use
+            let Some(class) = scheduler
+                .next_class(|class| !queues[class.index() as usize].is_empty())
+                .or_else(|| {
+                    (0..MAX_REPLICATION_CLASSES as u8)
+                        .map(ReplicationClass::new)
+                        .filter_map(|class| Some((class, queues[class.index() as usize].front()?)))
+                        .max_by(|(_, a), (_, b)| a.priority.total_cmp(&b.priority))
+                        .map(|(class, _)| class)
+                })
+            else {
+                break;
+            };
+            let queue = &mut self.class_queues[class.index() as usize];
+            let buffered_message = queue.pop_front().unwrap();
+            let Some(message_bytes) = self.check_quota(&buffered_message) else {
+                self.buffered_messages.push(buffered_message);
+                break;
+            };
+            bytes_used += message_bytes;
+            scheduler.record(class, buffered_message.data.len() as u32);
+            self.select_message(buffered_message, Some(class), buffers, channel_registry);
+        }
+        self.class_scheduler = Some(scheduler);
+        // the messages that don't fit in the bandwidth quota are discarded
+        for queue in self.class_queues.iter_mut() {
+            self.buffered_messages.extend(queue.drain(..));
+        }
+        bytes_used
+    }
+
+    /// Add a message that is allowed by the bandwidth quota to the list of messages to send
+    fn select_message(
+        &mut self,
+        buffered_message: BufferedMessage,
+        class: Option<ReplicationClass>,
+        buffers: &mut SendBuffers,
+        channel_registry: &ChannelRegistry,
+    ) {
+        trace!(channel=?buffered_message.channel_net_id, "Sending message with priority {:?}", buffered_message.priority);
+        let channel_net_id = buffered_message.channel_net_id;
+        if let Some(class) = class {
+            let message_bytes = buffered_message.data.len() as u32;
+            self.class_stats.add(class, message_bytes);
+            self.unflushed_class_stats.add(class, message_bytes);
+            // the reliable messages keep their class until they are acked, in case they are sent again
+            if !is_reliable(channel_registry, channel_net_id) {
+                if let Some(message_id) = buffered_message.data.message_id() {
+                    self.message_classes.remove(&(channel_net_id, message_id));
+                }
+            }
+        }
+
+        // notify the replication sender that the message was actually sent
+        if channel_registry.is_replication_update_channel(channel_net_id) {
+            // SAFETY: we are guaranteed in this situation to have a message id (because we use the unreliable with acks sender)
+            let message_id = buffered_message.data.message_id().unwrap();
+            for sender in self.replication_update_senders.iter() {
+                trace!(
+                    ?message_id,
+                    "notifying replication sender that a message was actually sent."
+                );
+                let _ = sender.send(message_id).map_err(|e| {
+                    error!(
+                        "error notifying replication sender that a message was actually sent: {:?}",
+                        e
+                    )
+                });
+            }
+        }
+
+        // the message is allowed, add it to the list of messages to send
+        match buffered_message.data {
+            MessageData::Single(single) => {
+                channel_messages(
+                    &mut buffers.single_data,
+                    &mut buffers.single_messages,
+                    channel_net_id,
+                )
+                .push_back(single);
+            }
+            MessageData::Fragment(fragment) => {
+                channel_messages(
+                    &mut buffers.fragment_data,
+                    &mut buffers.fragment_messages,
+                    channel_net_id,
+                )
+                .push_back(fragment);
+            }
+        }
+    }
+
     // TODO: maybe accumulate the used_bytes in the priority_manager instead of returning here?
     /// Filter the messages by priority and bandwidth quota
-    /// Returns the list of messages that we can send, along with the amount of bytes we used
-    /// in the rate limiter.
+    ///
+    /// The messages are taken from `buffers.channel_messages`, and the messages that we can send are
+    /// added to `buffers.single_data` and `buffers.fragment_data`.
+    /// `on_discard` is called with the channel of each message that did not fit in the bandwidth quota.
+    /// Returns the amount of bytes we used in the rate limiter.
     #[cfg_attr(feature = "trace", instrument(level = Level::INFO, skip_all))]
     pub(crate) fn priority_filter(
         &mut self,
-        data: Vec<(ChannelId, (VecDeque<SendMessage>, VecDeque<SendMessage>))>,
+        buffers: &mut SendBuffers,
         channel_registry: &ChannelRegistry,
         tick: Tick,
-    ) -> (
-        Vec<(ChannelId, VecDeque<SingleData>)>,
-        Vec<(ChannelId, VecDeque<FragmentData>)>,
-        u32,
-    ) {
+        mut on_discard: impl FnMut(ChannelId),
+    ) -> u32 {
         // if the bandwidth quota is disabled, just pass all messages through
         // As an optimization: no need to send the tick of the message, it is the same as the header tick
         if !self.config.enabled {
-            let mut single_data = vec![];
-            let mut fragment_data = vec![];
-            for (net_id, (single, fragment)) in data {
-                single_data.push((
-                    net_id,
+            for (net_id, (single, fragment)) in buffers.channel_messages.iter_mut() {
+                let mut single_data = buffers.single_messages.take();
+                single_data.extend(single.drain(..).map(|message| {
+                    let MessageData::Single(single) = message.data else {
+                        unreachable!()
+                    };
                     single
-                        .into_iter()
-                        .map(|message| {
-                            let MessageData::Single(single) = message.data else {
-                                unreachable!()
-                            };
-                            single
-                        })
-                        .collect(),
-                ));
-                fragment_data.push((
-                    net_id,
+                }));
+                buffers.single_data.push((*net_id, single_data));
+                let mut fragment_data = buffers.fragment_messages.take();
+                fragment_data.extend(fragment.drain(..).map(|message| {
+                    let MessageData::Fragment(fragment) = message.data else {
+                        unreachable!()
+                    };
                     fragment
-                        .into_iter()
-                        .map(|message| {
-                            let MessageData::Fragment(fragment) = message.data else {
-                                unreachable!()
-                            };
-                            fragment
-                        })
-                        .collect(),
-                ));
+                }));
+                buffers.fragment_data.push((*net_id, fragment_data));
             }
-            return (single_data, fragment_data, 0);
+            return 0;
         }
 
         // compute the priority of each new message
-        let mut all_messages = data
-            .into_iter()
-            .flat_map(|(net_id, (single, fragment))| {
-                let channel_priority = channel_registry
-                    .get_builder_from_net_id(net_id)
-                    .unwrap()
-                    .settings
-                    .priority;
-                trace!(?channel_priority, num_single=?single.len(), "channel priority");
-                single
-                    .into_iter()
-                    .map(move |single| BufferedMessage {
-                        priority: single.priority * channel_priority,
+        let accumulated_channel_priority = &mut self.accumulated_channel_priority;
+        let channel_priority_overrides = &self.channel_priority_overrides;
+        self.buffered_messages
+            .extend(buffers.channel_messages.iter_mut().flat_map(
+                |(net_id, (single, fragment))| {
+                    let net_id = *net_id;
+                    let channel_priority = channel_priority_overrides
+                        .get(&net_id)
+                        .copied()
+                        .unwrap_or_else(|| channel_registry.priority(net_id).unwrap());
+                    // the channel gains priority every tick where it has messages to send
+                    let accumulated = accumulated_channel_priority.entry(net_id).or_default();
+                    *accumulated += channel_priority;
+                    let accumulated = *accumulated;
+                    trace!(?channel_priority, ?accumulated, num_single=?single.len(), "channel priority");
+                    // only the base priority decides if the quota is bypassed, so that a starved channel
+                    // does not end up bypassing the quota
+                    let message = move |priority: f32, data: MessageData| BufferedMessage {
+                        priority: priority * accumulated,
+                        bypass_quota: priority * channel_priority >= BYPASS_QUOTA_PRIORITY,
                         channel_net_id: net_id,
-                        data: single.data,
-                    })
-                    .chain(fragment.into_iter().map(move |fragment| {
-                        // TODO (IMPORTANT): we should split fragments AFTER priority filtering
-                        //  because if we don't send one fragment, it's over..
-                        BufferedMessage {
-                            priority: fragment.priority * channel_priority,
-                            channel_net_id: net_id,
-                            data: fragment.data,
-                        }
-                    }))
-            })
-            .collect::<Vec<_>>();
+                        data,
+                    };
+                    single
+                        .drain(..)
+                        .map(move |single| message(single.priority, single.data))
+                        .chain(fragment.drain(..).map(move |fragment| {
+                            // TODO (IMPORTANT): we should split fragments AFTER priority filtering
+                            //  because if we don't send one fragment, it's over..
+                            message(fragment.priority, fragment.data)
+                        }))
+                },
+            ));
 
         // sort from highest priority to lower
-        all_messages.sort_by(|a, b| a.priority.partial_cmp(&b.priority).unwrap());
+        self.buffered_messages.sort_by(|a, b| {
+            a.bypass_quota
+                .cmp(&b.bypass_quota)
+                .then(a.priority.partial_cmp(&b.priority).unwrap())
+        });
         debug!(
             "all messages to send, sorted by priority: {:?}",
-            all_messages
+            self.buffered_messages
         );
 
         // select the top messages with the rate limiter
-        let mut single_data: HashMap<ChannelId, VecDeque<SingleData>> = HashMap::new();
-        let mut fragment_data: HashMap<ChannelId, VecDeque<FragmentData>> = HashMap::new();
         let mut bytes_used = 0;
-        while let Some(buffered_message) = all_messages.pop() {
-            // we don't use the exact size of the message, but the size of the bytes
-            // we will adjust for this later
-            let message_bytes = buffered_message.data.len() as u32;
-            let nonzero_message_bytes = NonZeroU32::try_from(message_bytes).unwrap();
-            let Ok(result) = self.limiter.check_n(nonzero_message_bytes) else {
-                error!("the bandwidth does not have enough capacity for a message of this size!");
+        // pay back the overdraft first
+        if let Some(overdraft) = NonZeroU32::new(self.overdraft) {
+            if let Ok(Ok(())) = self.limiter.check_n(overdraft) {
+                self.overdraft = 0;
+            }
+        }
+        while let Some(buffered_message) = self.buffered_messages.pop() {
+            // the replication messages of a class are queued, to share the bandwidth that is left
+            // once the other messages are selected
+            let class = self.message_class(&buffered_message);
+            if let (Some(class), false) = (class, buffered_message.bypass_quota) {
+                self.class_queues[class.index() as usize].push_back(buffered_message);
+                continue;
+            }
+            let Some(message_bytes) = self.check_quota(&buffered_message) else {
+                self.buffered_messages.push(buffered_message);
                 break;
             };
-
-            // above BYPASS_QUOTA_PRIORITY, we still send the message
-            if buffered_message.priority < BYPASS_QUOTA_PRIORITY {
-                let Ok(()) = result else {
-                    debug!("Bandwidth quota reached, no more messages can be sent this tick");
-                    break;
-                };
-            }
-            trace!(channel=?buffered_message.channel_net_id, "Sending message with priority {:?}", buffered_message.priority);
-
-            // keep track of the bytes we added to the rate limiter
             bytes_used += message_bytes;
-
-            // notify the replication sender that the message was actually sent
-            if channel_registry.is_replication_update_channel(buffered_message.channel_net_id) {
-                // SAFETY: we are guaranteed in this situation to have a message id (because we use the unreliable with acks sender)
-                let message_id = buffered_message.data.message_id().unwrap();
-                for sender in self.replication_update_senders.iter() {
-                    trace!(
-                        ?message_id,
-                        "notifying replication sender that a message was actually sent."
-                    );
-                    let _ = sender.send(message_id).map_err(|e| {
-                        error!("error notifying replication sender that a message was actually sent: {:?}", e)
-                    });
-                }
-            }
-
-            // the message is allowed, add it to the list of messages to send
-            match buffered_message.data {
-                MessageData::Single(single) => {
-                    single_data
-                        .entry(buffered_message.channel_net_id)
-                        .or_default()
-                        .push_back(single);
-                }
-                MessageData::Fragment(fragment) => {
-                    fragment_data
-                        .entry(buffered_message.channel_net_id)
-                        .or_default()
-                        .push_back(fragment);
-                }
-            }
+            self.select_message(buffered_message, class, buffers, channel_registry);
         }
+        bytes_used += self.select_class_messages(buffers, channel_registry);
 
         // all the other messages that don't make the cut, we just drop
         // - unreliable messages: they are unreliable so it's ok
@@ -243,18 +456,64 @@ impl PriorityManager {
         //   - PROBLEM: we could have the entity action not get sent (bandwidth), and then the priority still drops because the entity update
         //     was sent right after...
         // - reliable entity actions:
-        let num_messages_sent = single_data.values().map(|data| data.len()).sum::<usize>()
-            + fragment_data.values().map(|data| data.len()).sum::<usize>();
+        let num_messages_sent = buffers
+            .single_data
+            .iter()
+            .map(|(_, data)| data.len())
+            .sum::<usize>()
+            + buffers
+                .fragment_data
+                .iter()
+                .map(|(_, data)| data.len())
+                .sum::<usize>();
         debug!(
             bytes_sent = ?bytes_used,
             ?num_messages_sent,
-            num_messages_discarded = ?all_messages.len(),
+            num_messages_discarded = ?self.buffered_messages.len(),
             "priority filter done.");
+        // the channels that got messages into the packet start accumulating priority again
+        for channel_id in buffers
+            .single_data
+            .iter()
+            .map(|(id, _)| id)
+            .chain(buffers.fragment_data.iter().map(|(id, _)| id))
+        {
+            self.accumulated_channel_priority.insert(*channel_id, 0.0);
+        }
 
-        (
-            single_data.into_iter().collect(),
-            fragment_data.into_iter().collect(),
-            bytes_used,
-        )
+        for message in self.buffered_messages.drain(..) {
+            // the unreliable messages that are discarded are never sent again
+            if !is_reliable(channel_registry, message.channel_net_id) {
+                if let Some(message_id) = message.data.message_id() {
+                    self.message_classes
+                        .remove(&(message.channel_net_id, message_id));
+                }
+            }
+            on_discard(message.channel_net_id);
+        }
+
+        bytes_used
     }
 }
+
+fn is_reliable(channel_registry: &ChannelRegistry, channel_id: ChannelId) -> bool {
+    channel_registry
+        .get_builder_from_net_id(channel_id)
+        .is_some_and(|builder| builder.settings.mode.is_reliable())
+}
+
+/// Get the list of messages selected for a channel, adding it if needed
+fn channel_messages<'a, T>(
+    lists: &'a mut Vec<(ChannelId, VecDeque<T>)>,
+    pool: &mut BufferPool<VecDeque<T>>,
+    channel_id: ChannelId,
+) -> &'a mut VecDeque<T> {
+    let index = match lists.iter().position(|(id, _)| *id == channel_id) {
+        Some(index) => index,
+        None => {
+            lists.push((channel_id, pool.take()));
+            lists.len() - 1
+        }
+    };
+    &mut lists[index].1
+}
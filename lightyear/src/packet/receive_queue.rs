@@ -0,0 +1,272 @@
+//! Bounded queue between the netcode layer and the channels.
+//!
+//! The packets received and decrypted by the netcode layer are pushed to the [`ReceiveQueue`] of the connection,
+//! which then processes them (the channels buffer the messages that they contain) during the same frame, up to
+//! [`ReceiveQueueConfig::max_packets_per_frame`]. The packets that could not be processed are deferred to the next frame.
+//!
+//! The [`ReceiveQueueStats`] tell whether the packets pile up between the two stages. They can be read from the
+//! connection with `receive_queue_stats()`, or via the [`ReceiveQueueDiagnosticsPlugin`].
+use std::collections::VecDeque;
+
+use bevy::app::{App, Plugin};
+use bevy::diagnostic::{Diagnostic, DiagnosticPath, Diagnostics, RegisterDiagnostic};
+use bevy::reflect::Reflect;
+use bevy::utils::Duration;
+
+use crate::packet::packet_builder::RecvPayload;
+
+/// What to do with a packet received while the [`ReceiveQueue`] is full
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Reflect)]
+pub enum OverflowPolicy {
+    /// Drop the oldest packet of the queue to make room for the new one
+    #[default]
+    DropOldest,
+    /// Drop the new packet
+    DropNewest,
+}
+
+/// Configuration of the [`ReceiveQueue`] of a connection
+#[derive(Clone, Copy, Debug, PartialEq, Reflect)]
+pub struct ReceiveQueueConfig {
+    /// Maximum number of packets waiting to be processed
+    pub capacity: usize,
+    /// Maximum number of packets processed per frame. If None, all the packets received are processed
+    /// during the frame
+    pub max_packets_per_frame: Option<usize>,
+    /// What to do with the packets received while the queue is full
+    pub overflow_policy: OverflowPolicy,
+}
+
+impl Default for ReceiveQueueConfig {
+    fn default() -> Self {
+        Self {
+            capacity: 4096,
+            max_packets_per_frame: None,
+            overflow_policy: OverflowPolicy::default(),
+        }
+    }
+}
+
+impl ReceiveQueueConfig {
+    pub fn with_capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity;
+        self
+    }
+
+    pub fn with_max_packets_per_frame(mut self, max_packets_per_frame: usize) -> Self {
+        self.max_packets_per_frame = Some(max_packets_per_frame);
+        self
+    }
+
+    pub fn with_overflow_policy(mut self, overflow_policy: OverflowPolicy) -> Self {
+        self.overflow_policy = overflow_policy;
+        self
+    }
+}
+
+/// Statistics of the [`ReceiveQueue`] of a connection
+#[derive(Clone, Copy, Debug, Default, PartialEq, Reflect)]
+pub struct ReceiveQueueStats {
+    /// Number of packets pushed to the queue during the last frame
+    pub entered: u64,
+    /// Number of packets processed by the channels during the last frame
+    pub processed: u64,
+    /// Number of packets left in the queue at the end of the last frame because of
+    /// [`ReceiveQueueConfig::max_packets_per_frame`]
+    pub deferred: u64,
+    /// Total number of packets dropped because the queue was full
+    pub dropped: u64,
+    /// Maximum number of packets that were waiting in the queue at the same time
+    pub max_depth: usize,
+}
+
+impl ReceiveQueueStats {
+    /// Sum the stats of multiple connections; the `max_depth` is the maximum of the two
+    pub(crate) fn merge(&mut self, other: &ReceiveQueueStats) {
+        self.entered += other.entered;
+        self.processed += other.processed;
+        self.deferred += other.deferred;
+        self.dropped += other.dropped;
+        self.max_depth = self.max_depth.max(other.max_depth);
+    }
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct ReceiveQueue {
+    config: ReceiveQueueConfig,
+    packets: VecDeque<RecvPayload>,
+    /// Stats of the last completed frame
+    stats: ReceiveQueueStats,
+    /// Number of packets pushed during the current frame
+    entered: u64,
+}
+
+impl ReceiveQueue {
+    pub(crate) fn new(config: ReceiveQueueConfig) -> Self {
+        Self {
+            config,
+            ..Default::default()
+        }
+    }
+
+    pub(crate) fn stats(&self) -> &ReceiveQueueStats {
+        &self.stats
+    }
+
+    /// Add a packet received from the netcode layer
+    pub(crate) fn push(&mut self, packet: RecvPayload) {
+        self.entered += 1;
+        if self.packets.len() >= self.config.capacity {
+            self.stats.dropped += 1;
+            match self.config.overflow_policy {
+                OverflowPolicy::DropOldest => {
+                    self.packets.pop_front();
+                }
+                OverflowPolicy::DropNewest => return,
+            }
+        }
+        self.packets.push_back(packet);
+        self.stats.max_depth = self.stats.max_depth.max(self.packets.len());
+    }
+
+    /// Process the packets of the queue, up to [`ReceiveQueueConfig::max_packets_per_frame`],
+    /// and update the stats of the frame.
+    ///
+    /// Processing stops at the first error; the packets that were not processed stay in the queue.
+    pub(crate) fn process<E>(
+        &mut self,
+        mut f: impl FnMut(RecvPayload) -> Result<(), E>,
+    ) -> Result<(), E> {
+        let quota = self.config.max_packets_per_frame.unwrap_or(usize::MAX);
+        let mut processed = 0;
+        let mut result = Ok(());
+        while processed < quota {
+            let Some(packet) = self.packets.pop_front() else {
+                break;
+            };
+            processed += 1;
+            result = f(packet);
+            if result.is_err() {
+                break;
+            }
+        }
+        self.stats.entered = std::mem::take(&mut self.entered);
+        self.stats.processed = processed as u64;
+        self.stats.deferred = self.packets.len() as u64;
+        result
+    }
+}
+
+/// Plugin to expose the [`ReceiveQueueStats`] as diagnostics.
+///
+/// On the server, the stats of all the clients are summed.
+pub struct ReceiveQueueDiagnosticsPlugin {
+    pub history_len: usize,
+    pub flush_interval: Duration,
+}
+
+impl Default for ReceiveQueueDiagnosticsPlugin {
+    fn default() -> Self {
+        Self {
+            history_len: 60,
+            flush_interval: Duration::from_millis(200),
+        }
+    }
+}
+
+impl ReceiveQueueDiagnosticsPlugin {
+    pub const ENTERED: DiagnosticPath = DiagnosticPath::const_new("receive_queue.entered");
+    pub const PROCESSED: DiagnosticPath = DiagnosticPath::const_new("receive_queue.processed");
+    pub const DEFERRED: DiagnosticPath = DiagnosticPath::const_new("receive_queue.deferred");
+    pub const DROPPED: DiagnosticPath = DiagnosticPath::const_new("receive_queue.dropped");
+    pub const MAX_DEPTH: DiagnosticPath = DiagnosticPath::const_new("receive_queue.max_depth");
+
+    pub(crate) fn add_measurements(stats: &ReceiveQueueStats, mut diagnostics: Diagnostics) {
+        diagnostics.add_measurement(&Self::ENTERED, || stats.entered as f64);
+        diagnostics.add_measurement(&Self::PROCESSED, || stats.processed as f64);
+        diagnostics.add_measurement(&Self::DEFERRED, || stats.deferred as f64);
+        diagnostics.add_measurement(&Self::DROPPED, || stats.dropped as f64);
+        diagnostics.add_measurement(&Self::MAX_DEPTH, || stats.max_depth as f64);
+    }
+}
+
+impl Plugin for ReceiveQueueDiagnosticsPlugin {
+    fn build(&self, app: &mut App) {
+        for path in [
+            Self::ENTERED,
+            Self::PROCESSED,
+            Self::DEFERRED,
+            Self::DROPPED,
+            Self::MAX_DEPTH,
+        ] {
+            app.register_diagnostic(
+                Diagnostic::new(path).with_max_history_length(self.history_len),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn packet(i: u8) -> RecvPayload {
+        vec![i].into()
+    }
+
+    #[test]
+    fn test_overflow_policy() {
+        for (policy, expected) in [
+            (OverflowPolicy::DropOldest, vec![2, 3]),
+            (OverflowPolicy::DropNewest, vec![0, 1]),
+        ] {
+            let mut queue = ReceiveQueue::new(
+                ReceiveQueueConfig::default()
+                    .with_capacity(2)
+                    .with_overflow_policy(policy),
+            );
+            (0..4).for_each(|i| queue.push(packet(i)));
+            let mut processed = vec![];
+            queue
+                .process(|packet| {
+                    processed.push(packet[0]);
+                    Ok::<(), ()>(())
+                })
+                .unwrap();
+            assert_eq!(processed, expected);
+            assert_eq!(
+                queue.stats(),
+                &ReceiveQueueStats {
+                    entered: 4,
+                    processed: 2,
+                    deferred: 0,
+                    dropped: 2,
+                    max_depth: 2,
+                }
+            );
+        }
+    }
+
+    #[test]
+    fn test_max_packets_per_frame() {
+        let mut queue =
+            ReceiveQueue::new(ReceiveQueueConfig::default().with_max_packets_per_frame(3));
+        let mut processed = vec![];
+        // 5 packets are received per frame, but only 3 can be processed
+        for frame in 0..4u8 {
+            (0..5).for_each(|i| queue.push(packet(frame * 5 + i)));
+            queue
+                .process(|packet| {
+                    processed.push(packet[0]);
+                    Ok::<(), ()>(())
+                })
+                .unwrap();
+            assert_eq!(queue.stats().entered, 5);
+            assert_eq!(queue.stats().processed, 3);
+            assert_eq!(queue.stats().deferred, 2 * (frame as u64 + 1));
+        }
+        assert_eq!(queue.stats().max_depth, 11);
+        // the packets are processed in order, and none are lost
+        assert_eq!(processed, (0..12).collect::<Vec<_>>());
+    }
+}
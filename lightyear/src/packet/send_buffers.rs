@@ -0,0 +1,68 @@
+//! Buffers used to prepare the packets to send, that are reused across frames so that
+//! the send path doesn't allocate new lists of messages every time.
+use std::collections::VecDeque;
+
+use crate::packet::message::{FragmentData, SendMessage, SingleData};
+use crate::protocol::channel::ChannelId;
+use crate::utils::buffer_pool::BufferPool;
+
+/// Message lists that grew beyond this number of messages (during a burst) are freed instead of being retained
+const MAX_RETAINED_MESSAGES: usize = 1024;
+
+#[derive(Debug)]
+pub(crate) struct SendBuffers {
+    /// Messages taken from each channel that has something to send
+    pub(crate) channel_messages: Vec<(ChannelId, (VecDeque<SendMessage>, VecDeque<SendMessage>))>,
+    /// Messages selected by the priority manager, to be written in packets
+    pub(crate) single_data: Vec<(ChannelId, VecDeque<SingleData>)>,
+    pub(crate) fragment_data: Vec<(ChannelId, VecDeque<FragmentData>)>,
+    pub(crate) send_messages: BufferPool<VecDeque<SendMessage>>,
+    pub(crate) single_messages: BufferPool<VecDeque<SingleData>>,
+    pub(crate) fragment_messages: BufferPool<VecDeque<FragmentData>>,
+}
+
+impl Default for SendBuffers {
+    fn default() -> Self {
+        Self {
+            channel_messages: Vec::new(),
+            single_data: Vec::new(),
+            fragment_data: Vec::new(),
+            send_messages: BufferPool::new(MAX_RETAINED_MESSAGES),
+            single_messages: BufferPool::new(MAX_RETAINED_MESSAGES),
+            fragment_messages: BufferPool::new(MAX_RETAINED_MESSAGES),
+        }
+    }
+}
+
+impl SendBuffers {
+    /// Buffers that are never reused
+    #[cfg(any(test, feature = "test_utils"))]
+    pub(crate) fn disabled() -> Self {
+        Self {
+            send_messages: BufferPool::disabled(),
+            single_messages: BufferPool::disabled(),
+            fragment_messages: BufferPool::disabled(),
+            ..Self::default()
+        }
+    }
+
+    /// Give back the message lists to their pools, once their messages have been written in packets
+    pub(crate) fn recycle(&mut self) {
+        for (_, (single, fragment)) in self.channel_messages.drain(..) {
+            self.send_messages.give_back(single);
+            self.send_messages.give_back(fragment);
+        }
+        for (_, single) in self.single_data.drain(..) {
+            self.single_messages.give_back(single);
+        }
+        for (_, fragment) in self.fragment_data.drain(..) {
+            self.fragment_messages.give_back(fragment);
+        }
+    }
+
+    pub(crate) fn end_frame(&mut self) {
+        self.send_messages.end_frame();
+        self.single_messages.end_frame();
+        self.fragment_messages.end_frame();
+    }
+}
@@ -49,6 +49,8 @@ pub(crate) mod packet {
         /// Duration of the rolling buffer of stats to compute packet statistics
         stats_buffer_duration: Duration,
         final_stats: FinalStats,
+        /// stats accumulated since the creation of the connection
+        total_stats: PacketStats,
     }
 
     impl Default for PacketStatsManager {
@@ -67,6 +69,7 @@ pub(crate) mod packet {
                 current_stats: PacketStats::default(),
                 stats_buffer_duration,
                 final_stats: FinalStats::default(),
+                total_stats: PacketStats::default(),
             }
         }
 
@@ -81,6 +84,7 @@ pub(crate) mod packet {
             // add the current stats to the rolling stats
             let current_stats = std::mem::take(&mut self.current_stats);
             self.rolling_stats += current_stats;
+            self.total_stats += current_stats;
             self.stats_buffer
                 .push(time_manager.current_time(), current_stats);
 
@@ -99,6 +103,27 @@ pub(crate) mod packet {
             }
         }
 
+        /// Fraction of the packets sent over the stats buffer duration that were lost
+        pub(crate) fn packet_loss(&self) -> f32 {
+            self.final_stats.packet_loss
+        }
+
+        /// Number of packets sent since the creation of the connection
+        pub(crate) fn total_sent_packets(&self) -> u64 {
+            (self.total_stats.num_sent_packets + self.current_stats.num_sent_packets) as u64
+        }
+
+        /// Number of sent packets that were lost since the creation of the connection
+        pub(crate) fn total_sent_packets_lost(&self) -> u64 {
+            (self.total_stats.num_sent_packets_lost + self.current_stats.num_sent_packets_lost)
+                as u64
+        }
+
+        /// Number of packets received since the creation of the connection
+        pub(crate) fn total_received_packets(&self) -> u64 {
+            (self.total_stats.num_received_packets + self.current_stats.num_received_packets) as u64
+        }
+
         // TODO: we could just emit raw stats, and then compute packet loss over an interval using prometheus/grafana
         /// Notify that a packet was sent
         pub(crate) fn sent_packet(&mut self) {
@@ -1,13 +1,18 @@
 use bevy::app::App;
-use bevy::prelude::{Resource, TypePath};
+use bevy::prelude::{default, Resource, TypePath};
 use bevy::utils::Duration;
 use std::any::TypeId;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
-use crate::channel::builder::{Channel, ChannelBuilder, ChannelSettings, PongChannel};
+use crate::channel::builder::{
+    ActionResolutionChannel, Channel, ChannelBuilder, ChannelSettings, EntityAliasChannel,
+    FlowControlChannel, InterestHintChannel, JoinSnapshotChannel, PongChannel, PreSpawnIdChannel,
+    RequestChannel, SchemaChannel, ServerTimingChannel, TransferControlChannel,
+};
 use crate::channel::builder::{
     ChannelContainer, EntityActionsChannel, EntityUpdatesChannel, InputChannel, PingChannel,
 };
+use crate::packet::header::PacketHeaderMode;
 use crate::prelude::{ChannelMode, ReliableSettings};
 use crate::protocol::registry::{NetId, TypeKind, TypeMapper};
 
@@ -55,13 +60,30 @@ impl From<TypeId> for ChannelKind {
 /// # }
 /// ```
 ///
+/// ### Packet header
+///
+/// The registry also defines the [`PacketHeaderMode`] used to write the packets, since it must be identical
+/// on the client and the server. Use [`PacketHeaderMode::Compact`] to reduce the per-packet overhead:
 ///
+/// ```rust
+/// use lightyear::prelude::*;
+/// use bevy::prelude::*;
+///
+/// # fn main() {
+/// #  let mut app = App::new();
+/// #  app.init_resource::<ChannelRegistry>();
+///    app.set_packet_header_mode(PacketHeaderMode::Compact);
+/// # }
+/// ```
 #[derive(Resource, Default, Clone, Debug, PartialEq, TypePath)]
 pub struct ChannelRegistry {
     // we only store the ChannelBuilder because we might want to create multiple instances of the same channel
     pub(in crate::protocol) builder_map: HashMap<ChannelKind, ChannelBuilder>,
     pub(in crate::protocol) kind_map: TypeMapper<ChannelKind>,
     pub(in crate::protocol) name_map: HashMap<ChannelKind, String>,
+    /// Channels used internally by lightyear, as opposed to the channels added by the user
+    internal: HashSet<ChannelKind>,
+    packet_header_mode: PacketHeaderMode,
     built: bool,
 }
 
@@ -71,6 +93,8 @@ impl ChannelRegistry {
             builder_map: HashMap::new(),
             kind_map: TypeMapper::new(),
             name_map: HashMap::new(),
+            internal: HashSet::new(),
+            packet_header_mode: PacketHeaderMode::default(),
             built: false,
         };
         registry.add_channel::<EntityUpdatesChannel>(ChannelSettings {
@@ -81,6 +105,7 @@ impl ChannelRegistry {
             // directly on the replication_sender
             send_frequency: Duration::default(),
             priority: 1.0,
+            ..default()
         });
         registry.add_channel::<EntityActionsChannel>(ChannelSettings {
             mode: ChannelMode::UnorderedReliable(ReliableSettings::default()),
@@ -91,28 +116,102 @@ impl ChannelRegistry {
             send_frequency: Duration::default(),
             // we want to send the entity actions as soon as possible
             priority: 10.0,
+            ..default()
         });
         registry.add_channel::<PingChannel>(ChannelSettings {
             mode: ChannelMode::SequencedUnreliable,
             send_frequency: Duration::default(),
             // we always want to include the ping in the packet
             priority: f32::INFINITY,
+            ..default()
         });
         registry.add_channel::<PongChannel>(ChannelSettings {
             mode: ChannelMode::SequencedUnreliable,
             send_frequency: Duration::default(),
             // we always want to include the pong in the packet
             priority: f32::INFINITY,
+            ..default()
+        });
+        registry.add_channel::<FlowControlChannel>(ChannelSettings {
+            mode: ChannelMode::SequencedUnreliable,
+            send_frequency: Duration::default(),
+            // the window advertisements must bypass the bandwidth quota, otherwise
+            // a sender blocked by flow control could never be unblocked
+            priority: f32::INFINITY,
+            ..default()
+        });
+        registry.add_channel::<EntityAliasChannel>(ChannelSettings {
+            mode: ChannelMode::UnorderedReliable(ReliableSettings::default()),
+            send_frequency: Duration::default(),
+            // the aliases must be established before they can be used in the entity updates
+            priority: 10.0,
+            ..default()
         });
         registry.add_channel::<InputChannel>(ChannelSettings {
             mode: ChannelMode::UnorderedUnreliable,
             send_frequency: input_send_interval,
             // we always want to include the inputs in the packet
             priority: f32::INFINITY,
+            ..default()
+        });
+        registry.add_channel::<ActionResolutionChannel>(ChannelSettings {
+            mode: ChannelMode::UnorderedReliable(ReliableSettings::default()),
+            send_frequency: Duration::default(),
+            priority: 1.0,
+            ..default()
+        });
+        registry.add_channel::<InterestHintChannel>(ChannelSettings {
+            mode: ChannelMode::OrderedReliable(ReliableSettings::default()),
+            send_frequency: Duration::default(),
+            priority: 1.0,
+            ..default()
+        });
+        registry.add_channel::<JoinSnapshotChannel>(ChannelSettings {
+            mode: ChannelMode::UnorderedReliable(ReliableSettings::default()),
+            send_frequency: Duration::default(),
+            priority: 1.0,
+            ..default()
+        });
+        registry.add_channel::<PreSpawnIdChannel>(ChannelSettings {
+            mode: ChannelMode::UnorderedReliable(ReliableSettings::default()),
+            send_frequency: Duration::default(),
+            priority: 1.0,
+            ..default()
+        });
+        registry.add_channel::<ServerTimingChannel>(ChannelSettings {
+            mode: ChannelMode::OrderedReliable(ReliableSettings::default()),
+            send_frequency: Duration::default(),
+            priority: 1.0,
+            ..default()
+        });
+        registry.add_channel::<TransferControlChannel>(ChannelSettings {
+            mode: ChannelMode::UnorderedReliable(ReliableSettings::default()),
+            send_frequency: Duration::default(),
+            // a cancellation frees up bandwidth, so it must not wait for the bandwidth quota
+            priority: f32::INFINITY,
+            ..default()
+        });
+        registry.add_channel::<RequestChannel>(ChannelSettings {
+            mode: ChannelMode::UnorderedReliable(ReliableSettings::default()),
+            send_frequency: Duration::default(),
+            priority: 1.0,
+            ..default()
+        });
+        registry.add_channel::<SchemaChannel>(ChannelSettings {
+            mode: ChannelMode::UnorderedReliable(ReliableSettings::default()),
+            send_frequency: Duration::default(),
+            priority: 1.0,
+            ..default()
         });
+        registry.internal = registry.builder_map.keys().copied().collect();
         registry
     }
 
+    /// Returns true if the channel is used internally by lightyear
+    pub(crate) fn is_internal(&self, kind: &ChannelKind) -> bool {
+        self.internal.contains(kind)
+    }
+
     /// Returns true if the net_id corresponds to a channel that is used for replication
     pub(crate) fn is_replication_channel(&self, net_id: NetId) -> bool {
         self.kind_map.kind(net_id).map_or(false, |kind| {
@@ -128,6 +227,26 @@ impl ChannelRegistry {
         })
     }
 
+    /// Returns the ids of the channels whose messages use the tick at which the remote sent the packet
+    pub(crate) fn tick_channels(&self) -> impl Iterator<Item = ChannelId> + '_ {
+        [
+            ChannelKind::of::<EntityUpdatesChannel>(),
+            ChannelKind::of::<EntityActionsChannel>(),
+            ChannelKind::of::<PongChannel>(),
+        ]
+        .into_iter()
+        .filter_map(|kind| self.kind_map.net_id(&kind).copied())
+    }
+
+    pub fn packet_header_mode(&self) -> PacketHeaderMode {
+        self.packet_header_mode
+    }
+
+    /// Set the layout of the packet headers. The client and the server must use the same mode.
+    pub fn set_packet_header_mode(&mut self, mode: PacketHeaderMode) {
+        self.packet_header_mode = mode;
+    }
+
     /// Build all the channels in the registry
     pub fn channels(&self) -> HashMap<ChannelKind, ChannelContainer> {
         let mut channels = HashMap::new();
@@ -166,13 +285,29 @@ impl ChannelRegistry {
         self.name_map.get(kind).map(|s| s.as_str())
     }
 
+    /// Iterate through the names of all the channels in the registry
+    pub(crate) fn names(&self) -> impl Iterator<Item = (ChannelKind, &str)> {
+        self.name_map
+            .iter()
+            .map(|(kind, name)| (*kind, name.as_str()))
+    }
+
     pub fn get_builder_from_net_id(&self, channel_id: ChannelId) -> Option<&ChannelBuilder> {
         let channel_kind = self.get_kind_from_net_id(channel_id)?;
         self.get_builder_from_kind(channel_kind)
     }
 
-    #[cfg(test)]
-    fn len(&self) -> usize {
+    /// Base priority of the channel.
+    ///
+    /// When the bandwidth is capped, a channel accumulates its priority every tick where it has messages
+    /// that don't make it into a packet, so that low priority channels are not starved forever.
+    pub fn priority(&self, channel_id: ChannelId) -> Option<f32> {
+        self.get_builder_from_net_id(channel_id)
+            .map(|builder| builder.settings.priority)
+    }
+
+    /// Number of channels in the registry
+    pub(crate) fn len(&self) -> usize {
         self.kind_map.len()
     }
 }
@@ -180,6 +315,14 @@ impl ChannelRegistry {
 /// Add a message to the list of messages that can be sent
 pub trait AppChannelExt {
     fn add_channel<C: Channel>(&mut self, settings: ChannelSettings);
+
+    /// Set the [`PacketHeaderMode`] of the protocol
+    fn set_packet_header_mode(&mut self, mode: PacketHeaderMode);
+
+    /// Derive the network id of the channel from `name` instead of its fully-qualified type name,
+    /// for example to keep the same id after renaming the channel.
+    /// See [`NetIdReport`](crate::prelude::NetIdReport).
+    fn set_channel_protocol_name<C: Channel>(&mut self, name: impl Into<String>);
 }
 
 impl AppChannelExt for App {
@@ -187,6 +330,18 @@ impl AppChannelExt for App {
         let mut registry = self.world_mut().resource_mut::<ChannelRegistry>();
         registry.add_channel::<C>(settings);
     }
+
+    fn set_packet_header_mode(&mut self, mode: PacketHeaderMode) {
+        let mut registry = self.world_mut().resource_mut::<ChannelRegistry>();
+        registry.set_packet_header_mode(mode);
+    }
+
+    fn set_channel_protocol_name<C: Channel>(&mut self, name: impl Into<String>) {
+        let mut registry = self.world_mut().resource_mut::<ChannelRegistry>();
+        registry
+            .kind_map
+            .set_protocol_name(ChannelKind::of::<C>(), name);
+    }
 }
 
 #[cfg(test)]
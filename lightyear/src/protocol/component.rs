@@ -1,11 +1,14 @@
 use bevy::ecs::component::ComponentId;
-use bevy::ecs::entity::MapEntities;
-use std::any::TypeId;
+use bevy::ecs::entity::{Entities, MapEntities};
+use std::any::{Any, TypeId};
 use std::fmt::Debug;
 use std::hash::Hash;
 use std::ops::{Add, Mul};
 
-use bevy::prelude::{App, Component, EntityWorldMut, Mut, Resource, TypePath, World};
+use bevy::math::{Quat, Vec2, Vec3, Vec3A, Vec4};
+use bevy::prelude::{
+    App, Component, Entity, EntityRef, EntityWorldMut, Mut, Resource, TypePath, World,
+};
 use bevy::ptr::Ptr;
 use bevy::utils::HashMap;
 use serde::de::DeserializeOwned;
@@ -13,21 +16,32 @@ use serde::Serialize;
 
 use tracing::{debug, error, trace};
 
+use crate::client::apply::ComponentApplyFn;
 use crate::client::components::ComponentSyncMode;
 use crate::client::config::ClientConfig;
 use crate::client::interpolation::{add_interpolation_systems, add_prepare_interpolation_systems};
-use crate::client::prediction::plugin::add_prediction_systems;
+use crate::client::prediction::plugin::{add_prediction_systems, add_rollback_systems};
 use crate::prelude::client::SyncComponent;
 use crate::prelude::server::ServerConfig;
-use crate::prelude::{ChannelDirection, Message, Tick};
+use crate::prelude::{ChannelDirection, ClientId, Message, Tick};
 use crate::protocol::delta::ErasedDeltaFns;
 use crate::protocol::registry::{NetId, TypeKind, TypeMapper};
 use crate::protocol::serialize::{ErasedSerializeFns, SerializeFns};
 use crate::serialize::reader::Reader;
+use crate::serialize::versioned::{Schema, Versioned};
 use crate::serialize::SerializationError;
+use crate::server::behavior::{track_value, ClientBehaviorHistory, TrackValueFn, UpdateRecord};
+use crate::server::personalized::PersonalizedComponents;
+use crate::server::relevance::predicate::{PredicateScope, ReplicationPredicates};
+use crate::server::validation::{
+    ClientUpdateValidatorFn, ClientUpdateViolations, ValidationVerdict,
+};
 use crate::shared::events::connection::ConnectionEvents;
 use crate::shared::replication::delta::{DeltaMessage, Diffable};
 use crate::shared::replication::entity_map::EntityMap;
+use crate::shared::replication::frequency::ReplicationMode;
+use crate::shared::replication::handoff::HandoffFn;
+use crate::shared::replication::keyframe::KeyframeConfig;
 
 pub type ComponentNetId = NetId;
 
@@ -35,6 +49,8 @@ pub type ComponentNetId = NetId;
 pub enum ComponentError {
     #[error("component is not registered in the protocol")]
     NotRegistered,
+    #[error("the type of the component was removed from the protocol")]
+    Deprecated,
     #[error("missing replication functions for component")]
     MissingReplicationFns,
     #[error("missing serialization functions for component")]
@@ -45,6 +61,8 @@ pub enum ComponentError {
     DeltaCompressionError(String),
     #[error("component error: {0}")]
     SerializationError(#[from] SerializationError),
+    #[error("the component references the entity {0:?} that was not spawned yet")]
+    UnspawnedEntity(Entity),
 }
 
 /// A [`Resource`] that will keep track of all the [`Components`](Component) that can be replicated.
@@ -80,6 +98,10 @@ pub enum ComponentError {
 ///
 /// Provided that your type implements [`MapEntities`], you can extend the protocol to support this behaviour, by
 /// calling the [`add_map_entities`](ComponentRegistration::add_map_entities) method.
+/// [`MapEntities`] can be derived with [`#[derive(MapEntities)]`](lightyear_macros::MapEntities).
+///
+/// If the component references a remote entity that was not spawned locally yet, it is written to the entity
+/// later, once the referenced entity is spawned.
 ///
 /// #### Prediction
 /// When client-prediction is enabled, we create two distinct entities on the client when the server replicates an entity: a Confirmed entity and a Predicted entity.
@@ -141,6 +163,12 @@ pub struct ComponentRegistry {
     prediction_map: HashMap<ComponentKind, PredictionMetadata>,
     serialize_fns_map: HashMap<ComponentKind, ErasedSerializeFns>,
     delta_fns_map: HashMap<ComponentKind, ErasedDeltaFns>,
+    /// Validators applied to the component values received from clients
+    validation_map: HashMap<ComponentKind, unsafe fn()>,
+    /// Functions that store the component values received from the server, instead of inserting them
+    client_apply_map: HashMap<ComponentKind, unsafe fn()>,
+    /// Functions that record the component values received from clients in the behavior history
+    behavior_map: HashMap<ComponentKind, unsafe fn()>,
     pub(crate) kind_map: TypeMapper<ComponentKind>,
 }
 
@@ -151,8 +179,17 @@ pub struct ReplicationMetadata {
     pub replicate_once_id: ComponentId,
     pub override_target_id: ComponentId,
     pub disabled_id: ComponentId,
+    /// If set, the full value of the component is periodically re-sent even if it didn't change
+    pub keyframe: Option<KeyframeConfig>,
+    /// How often the updates of the component are sent
+    pub mode: ReplicationMode,
     pub write: RawWriteFn,
+    /// Function used to deserialize the component ahead of time, separately from writing it to the World.
+    /// `None` if the component can only be deserialized when it is written (e.g. delta-compressed components)
+    pub decode: Option<RawDecodeFn>,
     pub remove: Option<RawRemoveFn>,
+    /// Function used to restore the component from a server-side snapshot
+    pub restore: Option<RawRestoreFn>,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -184,6 +221,12 @@ impl PredictionMetadata {
 pub struct InterpolationMetadata {
     pub interpolation_mode: ComponentSyncMode,
     pub interpolation: Option<unsafe fn()>,
+    /// If true, the component is extrapolated when the interpolation runs out of snapshots
+    /// (see [`StarvationPolicy`](crate::client::interpolation::plugin::StarvationPolicy))
+    pub extrapolation: bool,
+    /// Function used to seed the interpolation of the target of a handoff with the samples of its source
+    /// (see [`handoff`](crate::shared::replication::handoff))
+    pub handoff: Option<unsafe fn()>,
 }
 
 type RawRemoveFn = fn(&ComponentRegistry, &mut EntityWorldMut);
@@ -192,11 +235,43 @@ type RawWriteFn = fn(
     &mut Reader,
     ComponentNetId,
     Tick,
+    Option<ClientId>,
     &mut EntityWorldMut,
     &mut EntityMap,
     &mut ConnectionEvents,
 ) -> Result<(), ComponentError>;
 
+type RawDecodeFn =
+    fn(&ComponentRegistry, &mut Reader, ComponentNetId) -> Result<DecodedComponent, ComponentError>;
+
+type RawWriteDecodedFn = fn(
+    &ComponentRegistry,
+    DecodedComponent,
+    Tick,
+    Option<ClientId>,
+    &mut EntityWorldMut,
+    &mut EntityMap,
+    &mut ConnectionEvents,
+) -> Result<(), ComponentError>;
+
+/// Component value that was deserialized before being written to the World.
+///
+/// The entities contained in the value are not mapped yet, because the entities spawned by the same
+/// replication messages might not be in the entity map when the value is deserialized.
+#[derive(Debug)]
+pub(crate) struct DecodedComponent {
+    pub(crate) net_id: ComponentNetId,
+    value: Box<dyn Any + Send>,
+    write: RawWriteDecodedFn,
+}
+
+type RawRestoreFn = fn(
+    &ComponentRegistry,
+    &mut Reader,
+    ComponentNetId,
+    &mut EntityWorldMut,
+) -> Result<(), ComponentError>;
+
 /// Function used to interpolate from one component state (`start`) to another (`other`)
 /// t goes from 0.0 (`start`) to 1.0 (`other`)
 pub type LerpFn<C> = fn(start: &C, other: &C, t: f32) -> C;
@@ -219,6 +294,53 @@ where
     }
 }
 
+/// Interpolation that can be derived field by field with [`#[derive(Lerp)]`](lightyear_macros::Lerp),
+/// for components where only some of the fields can be interpolated.
+///
+/// Use [`add_lerp_interpolation_fn`](ComponentRegistration::add_lerp_interpolation_fn) and
+/// [`add_lerp_correction_fn`](ComponentRegistration::add_lerp_correction_fn) to use it for a component.
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` cannot be interpolated",
+    label = "`{Self}` does not implement `Lerp`",
+    note = "in a `#[derive(Lerp)]` struct, mark the fields that cannot be interpolated with `#[lerp(snap)]`"
+)]
+pub trait Lerp {
+    /// Interpolate from `start` to `other`; t goes from 0.0 (`start`) to 1.0 (`other`)
+    fn lerp(start: &Self, other: &Self, t: f32) -> Self;
+}
+
+impl Lerp for f32 {
+    fn lerp(start: &Self, other: &Self, t: f32) -> Self {
+        start * (1.0 - t) + other * t
+    }
+}
+
+impl Lerp for f64 {
+    fn lerp(start: &Self, other: &Self, t: f32) -> Self {
+        start * (1.0 - t as f64) + other * t as f64
+    }
+}
+
+macro_rules! impl_lerp_for_vec {
+    ($($ty:ty),*) => {
+        $(
+            impl Lerp for $ty {
+                fn lerp(start: &Self, other: &Self, t: f32) -> Self {
+                    start.lerp(*other, t)
+                }
+            }
+        )*
+    };
+}
+
+impl_lerp_for_vec!(Vec2, Vec3, Vec3A, Vec4);
+
+impl Lerp for Quat {
+    fn lerp(start: &Self, other: &Self, t: f32) -> Self {
+        start.slerp(*other, t)
+    }
+}
+
 impl ComponentRegistry {
     pub fn net_id<C: 'static>(&self) -> ComponentNetId {
         self.kind_map
@@ -235,6 +357,22 @@ impl ComponentRegistry {
         self.serialize_fns_map.get(&kind).unwrap().type_name
     }
 
+    /// Return the kind of the replicated component whose payloads use the net id.
+    ///
+    /// The payloads of a delta-compressed component use the net id of its delta, which is
+    /// resolved to the component.
+    pub(crate) fn replicated_kind(&self, net_id: ComponentNetId) -> Option<ComponentKind> {
+        let kind = *self.kind_map.kind(net_id)?;
+        Some(
+            self.delta_fns_map
+                .iter()
+                .find_map(|(component_kind, delta_fns)| {
+                    (delta_fns.delta_kind == kind).then_some(*component_kind)
+                })
+                .unwrap_or(kind),
+        )
+    }
+
     pub fn is_registered<C: 'static>(&self) -> bool {
         self.kind_map.net_id(&ComponentKind::of::<C>()).is_some()
     }
@@ -308,6 +446,54 @@ mod serialize {
             erased_fns.add_map_entities::<C>();
         }
 
+        pub(crate) fn set_versioned<C: Versioned + 'static>(&mut self) {
+            let kind = ComponentKind::of::<C>();
+            let erased_fns = self.serialize_fns_map.get_mut(&kind).unwrap_or_else(|| {
+                panic!(
+                    "Component {} is not part of the protocol",
+                    std::any::type_name::<C>()
+                )
+            });
+            erased_fns.set_versioned::<C>();
+        }
+
+        pub(crate) fn set_size_warning_threshold<C: 'static>(&mut self, bytes: usize) {
+            let kind = ComponentKind::of::<C>();
+            let erased_fns = self.serialize_fns_map.get_mut(&kind).unwrap_or_else(|| {
+                panic!(
+                    "Component {} is not part of the protocol",
+                    std::any::type_name::<C>()
+                )
+            });
+            erased_fns.size_warning_threshold = Some(bytes);
+        }
+
+        /// Type name of the component, and size above which its serializations are reported
+        pub(crate) fn serialized_size_info(
+            &self,
+            kind: &ComponentKind,
+        ) -> Option<(&'static str, Option<usize>)> {
+            let erased_fns = self.serialize_fns_map.get(kind)?;
+            Some((erased_fns.type_name, erased_fns.size_warning_threshold))
+        }
+
+        /// Schema of the component, if it is serialized with its [`Versioned`] implementation
+        pub(crate) fn schema(&self, kind: &ComponentKind) -> Option<&Schema> {
+            self.serialize_fns_map.get(kind)?.schema.as_ref()
+        }
+
+        /// The components that are serialized with their [`Versioned`] implementation, with their [`NetId`]
+        /// and their type name
+        pub(crate) fn schemas(&self) -> impl Iterator<Item = (NetId, &'static str, &Schema)> {
+            self.serialize_fns_map.iter().filter_map(|(kind, fns)| {
+                Some((
+                    *self.kind_map.net_id(kind)?,
+                    fns.type_name,
+                    fns.schema.as_ref()?,
+                ))
+            })
+        }
+
         pub(crate) fn serialize<C: 'static>(
             &self,
             component: &C,
@@ -389,6 +575,24 @@ mod serialize {
             erased_fns.map_entities(component, entity_map);
             Ok(())
         }
+
+        /// Map the entities of the component, or return [`ComponentError::UnspawnedEntity`] if it references
+        /// a remote entity that was not spawned locally yet
+        pub(crate) fn map_spawned_entities<C: 'static>(
+            &self,
+            component: &mut C,
+            entity_map: &mut EntityMap,
+            entities: &Entities,
+        ) -> Result<(), ComponentError> {
+            let kind = ComponentKind::of::<C>();
+            let erased_fns = self
+                .serialize_fns_map
+                .get(&kind)
+                .ok_or(ComponentError::MissingSerializationFns)?;
+            erased_fns
+                .map_spawned_entities(component, entity_map, entities)
+                .map_err(ComponentError::UnspawnedEntity)
+        }
     }
 }
 
@@ -487,6 +691,8 @@ mod interpolation {
                 .or_insert_with(|| InterpolationMetadata {
                     interpolation_mode: mode,
                     interpolation: None,
+                    extrapolation: false,
+                    handoff: None,
                 });
         }
 
@@ -501,6 +707,8 @@ mod interpolation {
                 .or_insert_with(|| InterpolationMetadata {
                     interpolation_mode: ComponentSyncMode::Full,
                     interpolation: None,
+                    extrapolation: false,
+                    handoff: None,
                 })
                 .interpolation = Some(unsafe {
                 std::mem::transmute::<for<'a, 'b> fn(&'a C, &'b C, f32) -> C, unsafe fn()>(
@@ -508,6 +716,37 @@ mod interpolation {
                 )
             });
         }
+
+        pub(crate) fn set_extrapolation<C: Component>(&mut self) {
+            let kind = ComponentKind::of::<C>();
+            self.interpolation_map
+                .get_mut(&kind)
+                .expect(
+                    "the interpolation must be enabled on the component before the extrapolation",
+                )
+                .extrapolation = true;
+        }
+
+        pub(crate) fn set_handoff<C: Component>(&mut self, handoff_fn: HandoffFn<C>) {
+            let kind = ComponentKind::of::<C>();
+            self.interpolation_map
+                .get_mut(&kind)
+                .expect(
+                    "the interpolation must be enabled on the component before the handoff function",
+                )
+                .handoff = Some(unsafe {
+                std::mem::transmute::<for<'a> fn(&'a C) -> C, unsafe fn()>(handoff_fn)
+            });
+        }
+
+        pub(crate) fn handoff_fn<C: Component>(&self) -> Option<HandoffFn<C>> {
+            let kind = ComponentKind::of::<C>();
+            self.interpolation_map
+                .get(&kind)
+                .and_then(|metadata| metadata.handoff)
+                .map(|handoff| unsafe { std::mem::transmute::<unsafe fn(), HandoffFn<C>>(handoff) })
+        }
+
         pub(crate) fn interpolation_mode<C: Component>(&self) -> ComponentSyncMode {
             let kind = ComponentKind::of::<C>();
             self.interpolation_map
@@ -516,6 +755,13 @@ mod interpolation {
                     metadata.interpolation_mode
                 })
         }
+        pub(crate) fn has_extrapolation<C: Component>(&self) -> bool {
+            let kind = ComponentKind::of::<C>();
+            self.interpolation_map
+                .get(&kind)
+                .is_some_and(|metadata| metadata.extrapolation)
+        }
+
         pub(crate) fn interpolate<C: Component>(&self, start: &C, end: &C, t: f32) -> C {
             let kind = ComponentKind::of::<C>();
             let interpolation_metadata = self
@@ -535,13 +781,17 @@ mod replication {
         DeltaCompression, DisabledComponent, OverrideTargetComponent, ReplicateOnceComponent,
     };
     use crate::serialize::reader::Reader;
+    use crate::serialize::writer::Writer;
     use crate::serialize::ToBytes;
+    use bevy::prelude::EntityRef;
 
     impl ComponentRegistry {
         pub(crate) fn set_replication_fns<C: Component + PartialEq>(&mut self, world: &mut World) {
             let kind = ComponentKind::of::<C>();
             let write: RawWriteFn = Self::write::<C>;
+            let decode: RawDecodeFn = Self::decode::<C>;
             let remove: RawRemoveFn = Self::remove::<C>;
+            let restore: RawRestoreFn = Self::restore::<C>;
             self.replication_map.insert(
                 kind,
                 ReplicationMetadata {
@@ -550,22 +800,48 @@ mod replication {
                     replicate_once_id: world.init_component::<ReplicateOnceComponent<C>>(),
                     override_target_id: world.init_component::<OverrideTargetComponent<C>>(),
                     disabled_id: world.init_component::<DisabledComponent<C>>(),
+                    keyframe: None,
+                    mode: ReplicationMode::default(),
                     write,
+                    decode: Some(decode),
                     remove: Some(remove),
+                    restore: Some(restore),
                 },
             );
         }
 
+        pub(crate) fn set_keyframes<C: Component>(&mut self, config: KeyframeConfig) {
+            let kind = ComponentKind::of::<C>();
+            let replication_metadata = self
+                .replication_map
+                .get_mut(&kind)
+                .expect("the component is not part of the protocol");
+            replication_metadata.keyframe = Some(config);
+        }
+
+        pub(crate) fn set_replication_mode<C: Component>(&mut self, mode: ReplicationMode) {
+            let kind = ComponentKind::of::<C>();
+            let replication_metadata = self
+                .replication_map
+                .get_mut(&kind)
+                .expect("the component is not part of the protocol");
+            replication_metadata.mode = mode;
+        }
+
         /// SAFETY: the ReadWordBuffer must contain bytes corresponding to the correct component type
         pub(crate) fn raw_write(
             &self,
             reader: &mut Reader,
             entity_world_mut: &mut EntityWorldMut,
             tick: Tick,
+            remote: Option<ClientId>,
             entity_map: &mut EntityMap,
             events: &mut ConnectionEvents,
         ) -> Result<(), ComponentError> {
             let net_id = ComponentNetId::from_bytes(reader).map_err(SerializationError::from)?;
+            if self.kind_map.is_deprecated(net_id) {
+                return Err(ComponentError::Deprecated);
+            }
             let kind = self
                 .kind_map
                 .kind(net_id)
@@ -579,6 +855,7 @@ mod replication {
                 reader,
                 net_id,
                 tick,
+                remote,
                 entity_world_mut,
                 entity_map,
                 events,
@@ -590,13 +867,145 @@ mod replication {
             reader: &mut Reader,
             net_id: ComponentNetId,
             tick: Tick,
+            remote: Option<ClientId>,
             entity_world_mut: &mut EntityWorldMut,
             entity_map: &mut EntityMap,
             events: &mut ConnectionEvents,
         ) -> Result<(), ComponentError> {
             trace!("Writing component {} to entity", std::any::type_name::<C>());
-            let component = self.raw_deserialize::<C>(reader, net_id, entity_map)?;
+            let kind = self
+                .kind_map
+                .kind(net_id)
+                .ok_or(ComponentError::NotRegistered)?;
+            let erased_fns = self
+                .serialize_fns_map
+                .get(kind)
+                .ok_or(ComponentError::MissingSerializationFns)?;
+            // SAFETY: the ErasedFns corresponds to type C
+            let mut component = unsafe { erased_fns.deserialize_unmapped::<C>(reader) }?;
+            self.map_spawned_entities(
+                &mut component,
+                entity_map,
+                entity_world_mut.world().entities(),
+            )?;
+            self.write_value(component, net_id, tick, remote, entity_world_mut, events)
+        }
+
+        /// Deserialize the component value ahead of time (the ComponentNetId has already been read).
+        ///
+        /// Returns `None` if the component cannot be deserialized separately from being written.
+        pub(crate) fn raw_decode(
+            &self,
+            reader: &mut Reader,
+            net_id: ComponentNetId,
+        ) -> Result<Option<DecodedComponent>, ComponentError> {
+            let kind = self
+                .kind_map
+                .kind(net_id)
+                .ok_or(ComponentError::NotRegistered)?;
+            let replication_metadata = self
+                .replication_map
+                .get(kind)
+                .ok_or(ComponentError::MissingReplicationFns)?;
+            replication_metadata
+                .decode
+                .map(|decode| decode(self, reader, net_id))
+                .transpose()
+        }
+
+        pub(crate) fn decode<C: Component + PartialEq>(
+            &self,
+            reader: &mut Reader,
+            net_id: ComponentNetId,
+        ) -> Result<DecodedComponent, ComponentError> {
+            let kind = self
+                .kind_map
+                .kind(net_id)
+                .ok_or(ComponentError::NotRegistered)?;
+            let erased_fns = self
+                .serialize_fns_map
+                .get(kind)
+                .ok_or(ComponentError::MissingSerializationFns)?;
+            // SAFETY: the ErasedFns corresponds to type C
+            let component = unsafe { erased_fns.deserialize_unmapped::<C>(reader) }?;
+            Ok(DecodedComponent {
+                net_id,
+                value: Box::new(component),
+                write: Self::write_decoded::<C>,
+            })
+        }
+
+        /// Write a component that was deserialized with [`raw_decode`](Self::raw_decode)
+        pub(crate) fn raw_write_decoded(
+            &self,
+            decoded: DecodedComponent,
+            entity_world_mut: &mut EntityWorldMut,
+            tick: Tick,
+            remote: Option<ClientId>,
+            entity_map: &mut EntityMap,
+            events: &mut ConnectionEvents,
+        ) -> Result<(), ComponentError> {
+            let write = decoded.write;
+            write(
+                self,
+                decoded,
+                tick,
+                remote,
+                entity_world_mut,
+                entity_map,
+                events,
+            )
+        }
+
+        fn write_decoded<C: Component + PartialEq>(
+            &self,
+            decoded: DecodedComponent,
+            tick: Tick,
+            remote: Option<ClientId>,
+            entity_world_mut: &mut EntityWorldMut,
+            entity_map: &mut EntityMap,
+            events: &mut ConnectionEvents,
+        ) -> Result<(), ComponentError> {
+            trace!(
+                "Writing decoded component {} to entity",
+                std::any::type_name::<C>()
+            );
+            let net_id = decoded.net_id;
+            let mut component = *decoded
+                .value
+                .downcast::<C>()
+                .expect("the decoded value does not match the type of the write function");
+            self.map_spawned_entities(
+                &mut component,
+                entity_map,
+                entity_world_mut.world().entities(),
+            )?;
+            self.write_value(component, net_id, tick, remote, entity_world_mut, events)
+        }
+
+        fn write_value<C: Component + PartialEq>(
+            &self,
+            mut component: C,
+            net_id: ComponentNetId,
+            tick: Tick,
+            remote: Option<ClientId>,
+            entity_world_mut: &mut EntityWorldMut,
+            events: &mut ConnectionEvents,
+        ) -> Result<(), ComponentError> {
+            if let Some(client_id) = remote {
+                self.record_client_update(client_id, tick, entity_world_mut, &component);
+                if !self.validate(client_id, entity_world_mut, &mut component) {
+                    return Ok(());
+                }
+            }
             let entity = entity_world_mut.id();
+            // the values received from the server can be stored by a custom apply function
+            if remote.is_none() {
+                if let Some(apply) = self.client_apply::<C>() {
+                    entity_world_mut.world_scope(|world| apply(world, entity, component, tick));
+                    return Ok(());
+                }
+            }
             // TODO: should we send the event based on on the message type (Insert/Update) or based on whether the component was actually inserted?
             if let Some(mut c) = entity_world_mut.get_mut::<C>() {
                 // only apply the update if the component is different, to not trigger change detection
@@ -630,6 +1039,120 @@ mod replication {
         pub(crate) fn remove<C: Component>(&self, entity_world_mut: &mut EntityWorldMut) {
             entity_world_mut.remove::<C>();
         }
+
+        /// Remove all the replicated components of the entity, except the ones in `kept`
+        pub(crate) fn remove_missing(
+            &self,
+            kept: &[ComponentNetId],
+            entity_world_mut: &mut EntityWorldMut,
+            tick: Tick,
+            events: &mut ConnectionEvents,
+        ) {
+            for (kind, replication_metadata) in self.replication_map.iter() {
+                let Some(remove) = replication_metadata.remove else {
+                    continue;
+                };
+                let Some(net_id) = self.kind_map.net_id(kind) else {
+                    continue;
+                };
+                if kept.contains(net_id)
+                    || !entity_world_mut.contains_id(replication_metadata.component_id)
+                {
+                    continue;
+                }
+                events.push_remove_component(entity_world_mut.id(), *net_id, tick);
+                remove(self, entity_world_mut);
+            }
+        }
+
+        /// Remove all the replicated components of the entity, without emitting removal events
+        pub(crate) fn remove_all(&self, entity_world_mut: &mut EntityWorldMut) {
+            for replication_metadata in self.replication_map.values() {
+                let Some(remove) = replication_metadata.remove else {
+                    continue;
+                };
+                if entity_world_mut.contains_id(replication_metadata.component_id) {
+                    remove(self, entity_world_mut);
+                }
+            }
+        }
+
+        /// Serialize all the replicated components of the entity, so that they can be restored later
+        /// with [`restore_entity`](Self::restore_entity)
+        pub(crate) fn snapshot_entity(
+            &self,
+            entity_ref: EntityRef,
+            writer: &mut Writer,
+        ) -> Result<(), ComponentError> {
+            for (kind, replication_metadata) in self.replication_map.iter() {
+                if replication_metadata.restore.is_none() {
+                    continue;
+                }
+                let Some(component) = entity_ref.get_by_id(replication_metadata.component_id)
+                else {
+                    continue;
+                };
+                self.erased_serialize(component, writer, *kind)?;
+            }
+            Ok(())
+        }
+
+        /// Restore the replicated components of the entity from the bytes written by
+        /// [`snapshot_entity`](Self::snapshot_entity).
+        ///
+        /// The replicated components that are not part of the snapshot are removed from the entity.
+        pub(crate) fn restore_entity(
+            &self,
+            reader: &mut Reader,
+            entity_world_mut: &mut EntityWorldMut,
+        ) -> Result<(), ComponentError> {
+            let mut kept = vec![];
+            while reader.has_remaining() {
+                let net_id = ComponentNetId::from_bytes(reader)?;
+                let restore = self
+                    .kind_map
+                    .kind(net_id)
+                    .and_then(|kind| self.replication_map.get(kind))
+                    .and_then(|replication_metadata| replication_metadata.restore)
+                    .ok_or(ComponentError::MissingReplicationFns)?;
+                restore(self, reader, net_id, entity_world_mut)?;
+                kept.push(net_id);
+            }
+            for (kind, replication_metadata) in self.replication_map.iter() {
+                let (Some(remove), Some(_)) =
+                    (replication_metadata.remove, replication_metadata.restore)
+                else {
+                    continue;
+                };
+                let Some(net_id) = self.kind_map.net_id(kind) else {
+                    continue;
+                };
+                if kept.contains(net_id)
+                    || !entity_world_mut.contains_id(replication_metadata.component_id)
+                {
+                    continue;
+                }
+                remove(self, entity_world_mut);
+            }
+            Ok(())
+        }
+
+        pub(crate) fn restore<C: Component>(
+            &self,
+            reader: &mut Reader,
+            net_id: ComponentNetId,
+            entity_world_mut: &mut EntityWorldMut,
+        ) -> Result<(), ComponentError> {
+            let component = self.raw_deserialize::<C>(reader, net_id, &mut EntityMap::default())?;
+            // always overwrite the value (even if it is equal) so that the component is
+            // marked as changed and replicated again
+            if let Some(mut c) = entity_world_mut.get_mut::<C>() {
+                *c = component;
+            } else {
+                entity_world_mut.insert(component);
+            }
+            Ok(())
+        }
     }
 }
 
@@ -666,8 +1189,12 @@ mod delta {
                     replicate_once_id: ComponentId::new(0),
                     override_target_id: ComponentId::new(0),
                     disabled_id: ComponentId::new(0),
+                    keyframe: None,
+                    mode: ReplicationMode::default(),
                     write,
+                    decode: None,
                     remove: None,
+                    restore: None,
                 },
             );
         }
@@ -746,6 +1273,7 @@ mod delta {
             reader: &mut Reader,
             net_id: ComponentNetId,
             tick: Tick,
+            remote: Option<ClientId>,
             entity_world_mut: &mut EntityWorldMut,
             entity_map: &mut EntityMap,
             events: &mut ConnectionEvents,
@@ -777,11 +1305,25 @@ mod delta {
                     // TODO: is it possible to have one clone instead of 2?
                     let mut new_value = past_value.clone();
                     new_value.apply_diff(&delta.delta);
+                    let stale = history.is_stale(tick);
                     // we can remove all the values strictly older than previous_tick in the component history
                     // (since we now that server has receive an ack for previous_tick)
                     history.buffer = history.buffer.split_off(&previous_tick);
                     // store the new value in the history
+                    // (we store the value sent by the remote even if it fails validation, since
+                    // the remote will compute its next diffs from it)
                     history.buffer.insert(tick, new_value.clone());
+                    // a delta that arrives after a more recent value (for example a keyframe) is only kept
+                    // in the history, since the remote could compute its next diffs from it
+                    if stale {
+                        return Ok(());
+                    }
+                    if let Some(client_id) = remote {
+                        self.record_client_update(client_id, tick, entity_world_mut, &new_value);
+                        if !self.validate(client_id, entity_world_mut, &mut new_value) {
+                            return Ok(());
+                        }
+                    }
                     let Some(mut c) = entity_world_mut.get_mut::<C>() else {
                         return Err(ComponentError::DeltaCompressionError(
                             format!("Entity {entity:?} does not have a {} component, but we received a diff for delta-compression",
@@ -795,15 +1337,30 @@ mod delta {
                     let mut new_value = C::base_value();
                     new_value.apply_diff(&delta.delta);
                     let value = new_value.clone();
-                    if let Some(mut c) = entity_world_mut.get_mut::<C>() {
-                        // only apply the update if the component is different, to not trigger change detection
-                        if c.as_ref() != &new_value {
-                            *c = new_value;
-                            events.push_update_component(entity, net_id, tick);
+                    let stale = entity_world_mut
+                        .get::<DeltaComponentHistory<C>>()
+                        .is_some_and(|history| history.is_stale(tick));
+                    let valid = !stale
+                        && remote.map_or(true, |client_id| {
+                            self.record_client_update(
+                                client_id,
+                                tick,
+                                entity_world_mut,
+                                &new_value,
+                            );
+                            self.validate(client_id, entity_world_mut, &mut new_value)
+                        });
+                    if valid {
+                        if let Some(mut c) = entity_world_mut.get_mut::<C>() {
+                            // only apply the update if the component is different, to not trigger change detection
+                            if c.as_ref() != &new_value {
+                                *c = new_value;
+                                events.push_update_component(entity, net_id, tick);
+                            }
+                        } else {
+                            entity_world_mut.insert(new_value);
+                            events.push_insert_component(entity, net_id, tick);
                         }
-                    } else {
-                        entity_world_mut.insert(new_value);
-                        events.push_insert_component(entity, net_id, tick);
                     }
                     // store the component value in the delta component history, so that we can compute
                     // diffs from it
@@ -824,6 +1381,128 @@ mod delta {
     }
 }
 
+mod validation {
+    use super::*;
+
+    impl ComponentRegistry {
+        pub(crate) fn set_client_update_validator<C: Component>(
+            &mut self,
+            validator: ClientUpdateValidatorFn<C>,
+        ) {
+            let kind = ComponentKind::of::<C>();
+            self.validation_map.insert(kind, unsafe {
+                std::mem::transmute::<
+                    for<'a, 'b> fn(ClientId, Option<&'a C>, &'b mut C) -> ValidationVerdict,
+                    unsafe fn(),
+                >(validator)
+            });
+        }
+
+        /// Run the validator registered for the component on a value received from a client.
+        ///
+        /// Returns false if the value should be discarded.
+        pub(crate) fn validate<C: Component>(
+            &self,
+            client_id: ClientId,
+            entity_world_mut: &mut EntityWorldMut,
+            new: &mut C,
+        ) -> bool {
+            let Some(validator) = self.validation_map.get(&ComponentKind::of::<C>()) else {
+                return true;
+            };
+            let validator: ClientUpdateValidatorFn<C> = unsafe { std::mem::transmute(*validator) };
+            let verdict = validator(client_id, entity_world_mut.get::<C>(), new);
+            if verdict != ValidationVerdict::Accept {
+                debug!(
+                    ?client_id,
+                    ?verdict,
+                    "update for component {} did not pass validation",
+                    std::any::type_name::<C>()
+                );
+                entity_world_mut.world_scope(|world| {
+                    if let Some(mut violations) = world.get_resource_mut::<ClientUpdateViolations>()
+                    {
+                        violations.record(client_id, verdict);
+                    }
+                });
+            }
+            verdict != ValidationVerdict::Reject
+        }
+    }
+}
+
+mod behavior {
+    use super::*;
+
+    impl ComponentRegistry {
+        pub(crate) fn set_client_value_tracking<C: Component + Clone>(&mut self) {
+            let kind = ComponentKind::of::<C>();
+            let track: TrackValueFn<C> = track_value::<C>;
+            self.behavior_map.insert(kind, unsafe {
+                std::mem::transmute::<TrackValueFn<C>, unsafe fn()>(track)
+            });
+        }
+
+        /// Record a component value received from a client in the [`ClientBehaviorHistory`], if it exists
+        pub(crate) fn record_client_update<C: Component>(
+            &self,
+            client_id: ClientId,
+            tick: Tick,
+            entity_world_mut: &mut EntityWorldMut,
+            value: &C,
+        ) {
+            if !entity_world_mut
+                .world()
+                .contains_resource::<ClientBehaviorHistory>()
+            {
+                return;
+            }
+            let kind = ComponentKind::of::<C>();
+            let track = self.behavior_map.get(&kind).map(|track| unsafe {
+                std::mem::transmute::<unsafe fn(), TrackValueFn<C>>(*track)
+            });
+            let entity = entity_world_mut.id();
+            entity_world_mut.world_scope(|world| {
+                let mut history = world.resource_mut::<ClientBehaviorHistory>();
+                let record = UpdateRecord {
+                    tick,
+                    received_tick: history.tick,
+                    entity,
+                    kind,
+                };
+                history.record_update(client_id, record);
+                if let Some(track) = track {
+                    track(&mut history, client_id, record, value);
+                }
+            });
+        }
+    }
+}
+
+mod client_apply {
+    use super::*;
+
+    impl ComponentRegistry {
+        pub(crate) fn set_client_apply<C: Component>(&mut self, apply: ComponentApplyFn<C>) {
+            let kind = ComponentKind::of::<C>();
+            self.client_apply_map.insert(kind, unsafe {
+                std::mem::transmute::<for<'a> fn(&'a mut World, Entity, C, Tick), unsafe fn()>(
+                    apply,
+                )
+            });
+        }
+
+        /// Function that stores the values of the component `C` received from the server, if any
+        pub(crate) fn client_apply<C: Component>(&self) -> Option<ComponentApplyFn<C>> {
+            self.client_apply_map
+                .get(&ComponentKind::of::<C>())
+                .map(|apply| unsafe {
+                    std::mem::transmute::<unsafe fn(), ComponentApplyFn<C>>(*apply)
+                })
+        }
+    }
+}
+
 fn register_component_send<C: Component>(app: &mut App, direction: ChannelDirection) {
     let is_client = app.world().get_resource::<ClientConfig>().is_some();
     let is_server = app.world().get_resource::<ServerConfig>().is_some();
@@ -877,16 +1556,37 @@ pub trait AppComponentExt {
         serialize_fns: SerializeFns<C>,
     ) -> ComponentRegistration<'_, C>;
 
+    /// Keep the network id of a component that was removed from the protocol, so that the peers that
+    /// still replicate it can connect: the values of that component that they send are skipped and counted
+    /// as [`NetworkWarning::DeprecatedKind`](crate::shared::warnings::NetworkWarning::DeprecatedKind).
+    ///
+    /// `protocol_name` is the name from which the id of the component was derived: its fully-qualified type
+    /// name, or the name set with [`ComponentRegistration::with_protocol_name`]. The type doesn't exist
+    /// anymore, so the component cannot be sent.
+    /// The component must not have had [`add_schema_evolution`](ComponentRegistration::add_schema_evolution),
+    /// whose fields are part of the protocol hash.
+    fn deprecate_component(&mut self, protocol_name: &str);
+
     /// Enable prediction systems for this component.
     /// You can specify the prediction [`ComponentSyncMode`]
     fn add_prediction<C: SyncComponent>(&mut self, prediction_mode: ComponentSyncMode);
 
+    /// Enable rollback for a component that is not replicated, but is simulated locally on predicted entities
+    /// (for example a marker inserted by your input handling systems).
+    ///
+    /// The insertions, updates and removals of the component are recorded in its prediction history, and
+    /// during a rollback the component is restored to the state it had at the rollback tick.
+    fn add_rollback<C: SyncComponent>(&mut self);
+
     /// Add a `Correction` behaviour to this component by using a linear interpolation function.
     fn add_linear_correction_fn<C: SyncComponent + Linear>(&mut self);
 
     /// Add a `Correction` behaviour to this component.
     fn add_correction_fn<C: SyncComponent>(&mut self, correction_fn: LerpFn<C>);
 
+    /// Add a `Correction` behaviour to this component by using its [`Lerp`] implementation.
+    fn add_lerp_correction_fn<C: SyncComponent + Lerp>(&mut self);
+
     /// Add a custom function to use for checking if a rollback is needed.
     ///
     /// (By default we use the PartialEq::ne function, but you can use this to override the
@@ -907,10 +1607,70 @@ pub trait AppComponentExt {
     /// Add a `Interpolation` behaviour to this component.
     fn add_interpolation_fn<C: SyncComponent>(&mut self, interpolation_fn: LerpFn<C>);
 
+    /// Add a `Interpolation` behaviour to this component by using its [`Lerp`] implementation.
+    fn add_lerp_interpolation_fn<C: SyncComponent + Lerp>(&mut self);
+
+    /// Extrapolate this component when the interpolation runs out of server snapshots, if the
+    /// [`StarvationPolicy`](crate::client::interpolation::plugin::StarvationPolicy) is `Extrapolate`.
+    ///
+    /// The interpolation function is called with `t > 1.0`, so it must support extrapolation
+    /// (like a linear interpolation).
+    fn add_extrapolation<C: SyncComponent>(&mut self);
+
+    /// Seed the interpolation of the targets of a handoff with the samples of the source of the handoff,
+    /// transformed by `handoff_fn`.
+    ///
+    /// See [`handoff`](crate::shared::replication::handoff) for more details.
+    fn add_handoff_fn<C: SyncComponent>(&mut self, handoff_fn: HandoffFn<C>);
+
     /// Enable delta compression when serializing this component
     fn add_delta_compression<C: Component + PartialEq + Diffable>(&mut self)
     where
         C::Delta: Serialize + DeserializeOwned;
+
+    /// Periodically re-send the full value of this component, even if it didn't change
+    fn add_keyframes<C: Component>(&mut self, config: KeyframeConfig);
+
+    /// Set how often the updates of this component are sent
+    fn set_replication_mode<C: Component>(&mut self, mode: ReplicationMode);
+
+    /// Replicate this component with a value computed separately for each client.
+    ///
+    /// See [`personalized`](crate::server::personalized) for more details.
+    fn add_personalized<C: Component>(
+        &mut self,
+        generator: impl Fn(Entity, ClientId, &World) -> Option<C> + Send + Sync + 'static,
+    );
+
+    /// Validate the values of this component that are received from clients, before they are written
+    /// to the server's World.
+    ///
+    /// See [`validation`](crate::server::validation) for more details.
+    fn add_client_update_validator<C: Component>(&mut self, validator: ClientUpdateValidatorFn<C>);
+
+    /// Store the values of this component received from the server with `apply`, instead of inserting
+    /// the component on the `Confirmed` entity.
+    ///
+    /// See [`apply`](crate::client::apply) for more details.
+    fn add_client_apply_fn<C: Component>(&mut self, apply: ComponentApplyFn<C>);
+
+    /// Do not replicate the entities that have this component to the clients for which `predicate` returns `false`.
+    ///
+    /// See [`predicate`](crate::server::relevance::predicate) for more details.
+    fn register_replication_predicate<C: Component>(
+        &mut self,
+        predicate: impl Fn(EntityRef, ClientId) -> bool + Send + Sync + 'static,
+    ) {
+        self.register_replication_predicate_with_scope::<C>(PredicateScope::Entity, predicate);
+    }
+
+    /// Same as [`register_replication_predicate`](AppComponentExt::register_replication_predicate), but
+    /// the [`PredicateScope`] controls whether the whole entity or only the component is hidden from the client.
+    fn register_replication_predicate_with_scope<C: Component>(
+        &mut self,
+        scope: PredicateScope,
+        predicate: impl Fn(EntityRef, ClientId) -> bool + Send + Sync + 'static,
+    );
 }
 
 pub struct ComponentRegistration<'a, C> {
@@ -930,6 +1690,47 @@ impl<C> ComponentRegistration<'_, C> {
         self
     }
 
+    /// Serialize the component with its [`Versioned`] implementation, so that fields can be added to it
+    /// without breaking the compatibility with the peers that use an older version of the component.
+    ///
+    /// See [`versioned`](crate::serialize::versioned) for more details.
+    pub fn add_schema_evolution(self) -> Self
+    where
+        C: Versioned + 'static,
+    {
+        let mut registry = self.app.world_mut().resource_mut::<ComponentRegistry>();
+        registry.set_versioned::<C>();
+        self
+    }
+
+    /// Derive the network id of the component from `name` instead of its fully-qualified type name.
+    ///
+    /// Use this after renaming the component or moving it to another module, so that it keeps the same id
+    /// and stays compatible with peers that use the previous version of the protocol.
+    /// See [`NetIdReport`](crate::prelude::NetIdReport).
+    pub fn with_protocol_name(self, name: impl Into<String>) -> Self
+    where
+        C: 'static,
+    {
+        let mut registry = self.app.world_mut().resource_mut::<ComponentRegistry>();
+        registry
+            .kind_map
+            .set_protocol_name(ComponentKind::of::<C>(), name);
+        self
+    }
+
+    /// Report the serializations of the component bigger than `bytes` as a
+    /// [`NetworkWarning::OversizedSerialization`](crate::shared::warnings::NetworkWarning::OversizedSerialization),
+    /// instead of using the global [`SerializedSizeConfig::warning_threshold`](crate::shared::serialized_size::SerializedSizeConfig::warning_threshold)
+    pub fn with_size_warning_threshold(self, bytes: usize) -> Self
+    where
+        C: 'static,
+    {
+        let mut registry = self.app.world_mut().resource_mut::<ComponentRegistry>();
+        registry.set_size_warning_threshold::<C>(bytes);
+        self
+    }
+
     /// Enable prediction systems for this component.
     /// You can specify the prediction [`ComponentSyncMode`]
     pub fn add_prediction(self, prediction_mode: ComponentSyncMode) -> Self
@@ -958,6 +1759,15 @@ impl<C> ComponentRegistration<'_, C> {
         self
     }
 
+    /// Add a `Correction` behaviour to this component by using its [`Lerp`] implementation.
+    pub fn add_lerp_correction_fn(self) -> Self
+    where
+        C: SyncComponent + Lerp,
+    {
+        self.app.add_lerp_correction_fn::<C>();
+        self
+    }
+
     /// Add a custom function to use for checking if a rollback is needed.
     ///
     /// (By default we use the PartialEq::ne function, but you can use this to override the
@@ -1008,6 +1818,38 @@ impl<C> ComponentRegistration<'_, C> {
         self
     }
 
+    /// Add a `Interpolation` behaviour to this component by using its [`Lerp`] implementation.
+    pub fn add_lerp_interpolation_fn(self) -> Self
+    where
+        C: SyncComponent + Lerp,
+    {
+        self.app.add_lerp_interpolation_fn::<C>();
+        self
+    }
+
+    /// Extrapolate the component when the interpolation runs out of server snapshots.
+    ///
+    /// See [`StarvationPolicy`](crate::client::interpolation::plugin::StarvationPolicy).
+    pub fn add_extrapolation(self) -> Self
+    where
+        C: SyncComponent,
+    {
+        self.app.add_extrapolation::<C>();
+        self
+    }
+
+    /// Seed the interpolation of the targets of a handoff with the samples of the source of the handoff,
+    /// transformed by `handoff_fn` (for example to add the offset of a seat).
+    ///
+    /// See [`handoff`](crate::shared::replication::handoff).
+    pub fn add_handoff_fn(self, handoff_fn: HandoffFn<C>) -> Self
+    where
+        C: SyncComponent,
+    {
+        self.app.add_handoff_fn::<C>(handoff_fn);
+        self
+    }
+
     /// Enable delta compression when serializing this component
     pub fn add_delta_compression(self) -> Self
     where
@@ -1017,6 +1859,81 @@ impl<C> ComponentRegistration<'_, C> {
         self.app.add_delta_compression::<C>();
         self
     }
+
+    /// Periodically re-send the full value of this component, even if it didn't change.
+    ///
+    /// This guarantees that the remote eventually gets the correct value even if the packet
+    /// containing the last change was lost.
+    pub fn add_keyframes(self, config: KeyframeConfig) -> Self
+    where
+        C: Component,
+    {
+        self.app.add_keyframes::<C>(config);
+        self
+    }
+
+    /// Set how often the updates of this component are sent, for example
+    /// `ReplicationMode::EveryInterval(Duration::from_secs_f64(1.0 / 30.0))` to replicate it at 30Hz.
+    ///
+    /// See [`ReplicationMode`] for more details.
+    pub fn replication_mode(self, mode: ReplicationMode) -> Self
+    where
+        C: Component,
+    {
+        self.app.set_replication_mode::<C>(mode);
+        self
+    }
+
+    /// Replicate this component with a value computed separately for each client.
+    ///
+    /// The server never reads the component from the entity: instead it calls `generator` for each
+    /// replicated entity and each client, and replicates the returned value to that client (or removes
+    /// the component if it returns `None`). See [`personalized`](crate::server::personalized) for the cost model.
+    pub fn add_personalized(
+        self,
+        generator: impl Fn(Entity, ClientId, &World) -> Option<C> + Send + Sync + 'static,
+    ) -> Self
+    where
+        C: Component,
+    {
+        self.app.add_personalized::<C>(generator);
+        self
+    }
+
+    /// Validate the values of this component that are received from clients, before they are written
+    /// to the server's World. The validator can accept the value, clamp it in place, or reject it.
+    pub fn add_client_update_validator(self, validator: ClientUpdateValidatorFn<C>) -> Self
+    where
+        C: Component,
+    {
+        self.app.add_client_update_validator::<C>(validator);
+        self
+    }
+
+    /// Store the values of this component received from the server with `apply`, instead of inserting
+    /// the component on the `Confirmed` entity.
+    pub fn add_client_apply(self, apply: ComponentApplyFn<C>) -> Self
+    where
+        C: Component,
+    {
+        self.app.add_client_apply_fn::<C>(apply);
+        self
+    }
+
+    /// Do not replicate the entity (or only this component, depending on `scope`) to the clients
+    /// for which `predicate` returns `false`.
+    pub fn add_replication_predicate(
+        self,
+        scope: PredicateScope,
+        predicate: impl Fn(EntityRef, ClientId) -> bool + Send + Sync + 'static,
+    ) -> Self
+    where
+        C: Component,
+    {
+        self.app
+            .register_replication_predicate_with_scope::<C>(scope, predicate);
+        self
+    }
 }
 
 impl AppComponentExt for App {
@@ -1059,6 +1976,12 @@ impl AppComponentExt for App {
         }
     }
 
+    fn deprecate_component(&mut self, protocol_name: &str) {
+        let mut registry = self.world_mut().resource_mut::<ComponentRegistry>();
+        registry.kind_map.add_deprecated(protocol_name);
+        debug!("deprecate component {protocol_name}");
+    }
+
     fn add_prediction<C: SyncComponent>(&mut self, prediction_mode: ComponentSyncMode) {
         let mut registry = self.world_mut().resource_mut::<ComponentRegistry>();
         registry.set_prediction_mode::<C>(prediction_mode);
@@ -1070,6 +1993,13 @@ impl AppComponentExt for App {
         }
     }
 
+    fn add_rollback<C: SyncComponent>(&mut self) {
+        let is_client = self.world().get_resource::<ClientConfig>().is_some();
+        if is_client {
+            add_rollback_systems::<C>(self);
+        }
+    }
+
     fn add_linear_correction_fn<C: SyncComponent + Linear>(&mut self) {
         let mut registry = self.world_mut().resource_mut::<ComponentRegistry>();
         registry.set_linear_correction::<C>();
@@ -1081,6 +2011,10 @@ impl AppComponentExt for App {
         registry.set_correction::<C>(correction_fn);
     }
 
+    fn add_lerp_correction_fn<C: SyncComponent + Lerp>(&mut self) {
+        self.add_correction_fn::<C>(<C as Lerp>::lerp);
+    }
+
     fn add_should_rollback_fn<C: SyncComponent>(&mut self, rollback_check: ShouldRollbackFn<C>) {
         let mut registry = self.world_mut().resource_mut::<ComponentRegistry>();
         registry.set_should_rollback::<C>(rollback_check);
@@ -1123,6 +2057,20 @@ impl AppComponentExt for App {
         registry.set_interpolation::<C>(interpolation_fn);
     }
 
+    fn add_lerp_interpolation_fn<C: SyncComponent + Lerp>(&mut self) {
+        self.add_interpolation_fn::<C>(<C as Lerp>::lerp);
+    }
+
+    fn add_extrapolation<C: SyncComponent>(&mut self) {
+        let mut registry = self.world_mut().resource_mut::<ComponentRegistry>();
+        registry.set_extrapolation::<C>();
+    }
+
+    fn add_handoff_fn<C: SyncComponent>(&mut self, handoff_fn: HandoffFn<C>) {
+        let mut registry = self.world_mut().resource_mut::<ComponentRegistry>();
+        registry.set_handoff::<C>(handoff_fn);
+    }
+
     fn add_delta_compression<C: Component + PartialEq + Diffable>(&mut self)
     where
         C::Delta: Serialize + DeserializeOwned,
@@ -1130,6 +2078,54 @@ impl AppComponentExt for App {
         let mut registry = self.world_mut().resource_mut::<ComponentRegistry>();
         registry.set_delta_compression::<C>();
     }
+
+    fn add_keyframes<C: Component>(&mut self, config: KeyframeConfig) {
+        let mut registry = self.world_mut().resource_mut::<ComponentRegistry>();
+        registry.set_keyframes::<C>(config);
+    }
+
+    fn set_replication_mode<C: Component>(&mut self, mode: ReplicationMode) {
+        let mut registry = self.world_mut().resource_mut::<ComponentRegistry>();
+        registry.set_replication_mode::<C>(mode);
+    }
+
+    fn add_personalized<C: Component>(
+        &mut self,
+        generator: impl Fn(Entity, ClientId, &World) -> Option<C> + Send + Sync + 'static,
+    ) {
+        // the generator only runs on the server
+        if self.world().get_resource::<ServerConfig>().is_none() {
+            return;
+        }
+        self.world_mut()
+            .get_resource_or_insert_with(PersonalizedComponents::default)
+            .add::<C>(Box::new(generator));
+    }
+
+    fn add_client_update_validator<C: Component>(&mut self, validator: ClientUpdateValidatorFn<C>) {
+        let mut registry = self.world_mut().resource_mut::<ComponentRegistry>();
+        registry.set_client_update_validator::<C>(validator);
+    }
+
+    fn add_client_apply_fn<C: Component>(&mut self, apply: ComponentApplyFn<C>) {
+        let mut registry = self.world_mut().resource_mut::<ComponentRegistry>();
+        registry.set_client_apply::<C>(apply);
+    }
+
+    fn register_replication_predicate_with_scope<C: Component>(
+        &mut self,
+        scope: PredicateScope,
+        predicate: impl Fn(EntityRef, ClientId) -> bool + Send + Sync + 'static,
+    ) {
+        // the predicates only run on the server
+        if self.world().get_resource::<ServerConfig>().is_none() {
+            return;
+        }
+        let component_id = self.world_mut().init_component::<C>();
+        self.world_mut()
+            .get_resource_or_insert_with(ReplicationPredicates::default)
+            .add::<C>(component_id, scope, Box::new(predicate));
+    }
 }
 
 /// [`ComponentKind`] is an internal wrapper around the type of the component
@@ -1174,4 +2170,35 @@ mod tests {
             .unwrap();
         assert_eq!(component, read);
     }
+
+    /// The components of a deprecated type sent by an older peer are skipped
+    #[test]
+    fn test_deprecated_component() {
+        let mut old = ComponentRegistry::default();
+        old.register_component::<Component1>();
+        old.register_component::<Component3>();
+        let mut writer = Writer::default();
+        old.serialize(&Component1(1.0), &mut writer).unwrap();
+        let data = writer.to_bytes();
+
+        let mut new = ComponentRegistry::default();
+        new.register_component::<Component3>();
+        new.kind_map
+            .add_deprecated(std::any::type_name::<Component1>());
+        assert_eq!(new.net_id::<Component3>(), old.net_id::<Component3>());
+        assert!(new.kind_map.is_deprecated(old.net_id::<Component1>()));
+
+        let mut world = World::new();
+        let mut entity_world_mut = world.spawn_empty();
+        let result = new.raw_write(
+            &mut Reader::from(data),
+            &mut entity_world_mut,
+            Tick(0),
+            None,
+            &mut EntityMap::default(),
+            &mut ConnectionEvents::default(),
+        );
+        assert!(matches!(result, Err(ComponentError::Deprecated)));
+        assert!(entity_world_mut.get::<Component1>().is_none());
+    }
 }
@@ -1,11 +1,12 @@
-use bevy::ecs::entity::MapEntities;
+use bevy::ecs::entity::{Entities, MapEntities};
 use std::any::TypeId;
 use std::fmt::Debug;
 
+use crate::client::apply::{apply_messages, MessageApplyFn};
 use crate::client::config::ClientConfig;
 use crate::client::message::add_client_receive_message_from_server;
 use crate::prelude::{client, server};
-use bevy::prelude::{App, Resource, TypePath};
+use bevy::prelude::{App, Entity, IntoSystemConfigs, PreUpdate, Resource, TypePath, World};
 use bevy::utils::HashMap;
 use serde::de::DeserializeOwned;
 use serde::Serialize;
@@ -17,11 +18,16 @@ use crate::prelude::ChannelDirection;
 use crate::protocol::registry::{NetId, TypeKind, TypeMapper};
 use crate::protocol::serialize::{ErasedSerializeFns, SerializeFns};
 use crate::serialize::reader::Reader;
+use crate::serialize::versioned::{Schema, Versioned};
 use crate::serialize::writer::Writer;
 use crate::serialize::ToBytes;
 use crate::server::message::add_server_receive_message_from_client;
+use crate::shared::message_group::MessageGroupHeader;
 use crate::shared::replication::entity_map::EntityMap;
 use crate::shared::replication::resources::DespawnResource;
+use crate::shared::sets::{ClientMarker, InternalMainSet};
+use crate::shared::tick_manager::Tick;
+use crate::shared::timeline_message::TimelineMessageHeader;
 
 #[derive(thiserror::Error, Debug)]
 pub enum MessageError {
@@ -31,6 +37,8 @@ pub enum MessageError {
     MissingSerializationFns,
     #[error(transparent)]
     Serialization(#[from] crate::serialize::SerializationError),
+    #[error("the message references the entity {0:?} that was not spawned yet")]
+    UnspawnedEntity(Entity),
 }
 
 #[derive(Clone, Copy, Debug, PartialEq)]
@@ -42,6 +50,9 @@ pub(crate) enum MessageType {
     NativeInput,
     /// This is not an input message, but a regular [`Message`]
     Normal,
+    /// The type of the message was removed from the protocol (see [`AppMessageExt::deprecate_message`]):
+    /// the message is skipped
+    Deprecated,
 }
 
 /// A [`Resource`] that will keep track of all the [`Message`]s that can be sent over the network.
@@ -74,6 +85,10 @@ pub(crate) enum MessageType {
 ///
 /// Provided that your type implements [`MapEntities`], you can extend the protocol to support this behaviour, by
 /// calling the [`add_map_entities`](MessageRegistration::add_map_entities) method.
+/// [`MapEntities`] can be derived with [`#[derive(MapEntities)]`](lightyear_macros::MapEntities).
+///
+/// If the message references a remote entity that was not spawned locally yet, it is received on a later
+/// frame, once the referenced entity is spawned.
 ///
 /// ```rust
 /// use bevy::ecs::entity::{EntityMapper, MapEntities};
@@ -99,6 +114,8 @@ pub(crate) enum MessageType {
 pub struct MessageRegistry {
     typed_map: HashMap<MessageKind, MessageType>,
     serialize_fns_map: HashMap<MessageKind, ErasedSerializeFns>,
+    /// Functions that handle the messages received from the server, instead of emitting events
+    client_apply_map: HashMap<MessageKind, unsafe fn()>,
     pub(crate) kind_map: TypeMapper<MessageKind>,
 }
 
@@ -198,6 +215,69 @@ impl<M> MessageRegistration<'_, M> {
         registry.add_map_entities::<M>();
         self
     }
+
+    /// Serialize the message with its [`Versioned`] implementation, so that fields can be added to it
+    /// without breaking the compatibility with the peers that use an older version of the message.
+    ///
+    /// See [`versioned`](crate::serialize::versioned) for more details.
+    pub fn add_schema_evolution(self) -> Self
+    where
+        M: Versioned + 'static,
+    {
+        let mut registry = self.app.world_mut().resource_mut::<MessageRegistry>();
+        registry.set_versioned::<M>();
+        self
+    }
+
+    /// Derive the network id of the message from `name` instead of its fully-qualified type name.
+    ///
+    /// Use this after renaming the message or moving it to another module, so that it keeps the same id
+    /// and stays compatible with peers that use the previous version of the protocol.
+    /// See [`NetIdReport`](crate::prelude::NetIdReport).
+    pub fn with_protocol_name(self, name: impl Into<String>) -> Self
+    where
+        M: 'static,
+    {
+        let mut registry = self.app.world_mut().resource_mut::<MessageRegistry>();
+        registry
+            .kind_map
+            .set_protocol_name(MessageKind::of::<M>(), name);
+        self
+    }
+
+    /// Report the serializations of the message bigger than `bytes` as a
+    /// [`NetworkWarning::OversizedSerialization`](crate::shared::warnings::NetworkWarning::OversizedSerialization),
+    /// instead of using the global [`SerializedSizeConfig::warning_threshold`](crate::shared::serialized_size::SerializedSizeConfig::warning_threshold)
+    pub fn with_size_warning_threshold(self, bytes: usize) -> Self
+    where
+        M: 'static,
+    {
+        let mut registry = self.app.world_mut().resource_mut::<MessageRegistry>();
+        registry.set_size_warning_threshold::<M>(bytes);
+        self
+    }
+
+    /// Handle the messages received from the server with `apply` instead of emitting
+    /// [`MessageEvent`](crate::client::events::MessageEvent)s.
+    ///
+    /// See [`apply`](crate::client::apply) for more details.
+    pub fn add_client_apply(self, apply: MessageApplyFn<M>) -> Self
+    where
+        M: Message,
+    {
+        let mut registry = self.app.world_mut().resource_mut::<MessageRegistry>();
+        registry.set_client_apply::<M>(apply);
+        // the apply function only runs on the client
+        if self.app.world().get_resource::<ClientConfig>().is_some() {
+            self.app.add_systems(
+                PreUpdate,
+                apply_messages::<M>
+                    .in_set(InternalMainSet::<ClientMarker>::EmitEvents)
+                    .run_if(client::is_connected),
+            );
+        }
+        self
+    }
 }
 
 pub(crate) trait AppMessageInternalExt {
@@ -290,6 +370,17 @@ pub trait AppMessageExt {
         direction: ChannelDirection,
         serialize_fns: SerializeFns<R>,
     );
+
+    /// Keep the network id of a message that was removed from the protocol, so that the peers that
+    /// still send it can connect: the messages of that type that they send are skipped and counted
+    /// as [`NetworkWarning::DeprecatedKind`](crate::shared::warnings::NetworkWarning::DeprecatedKind).
+    ///
+    /// `protocol_name` is the name from which the id of the message was derived: its fully-qualified type
+    /// name, or the name set with [`MessageRegistration::with_protocol_name`]. The type doesn't exist
+    /// anymore, so the message cannot be sent.
+    /// The message must not have had [`add_schema_evolution`](MessageRegistration::add_schema_evolution),
+    /// whose fields are part of the protocol hash.
+    fn deprecate_message(&mut self, protocol_name: &str);
 }
 
 impl AppMessageExt for App {
@@ -328,10 +419,19 @@ impl AppMessageExt for App {
         self.register_message::<DespawnResource<R>>(direction);
         register_resource_send::<R>(self, direction)
     }
+
+    fn deprecate_message(&mut self, protocol_name: &str) {
+        let mut registry = self.world_mut().resource_mut::<MessageRegistry>();
+        registry.kind_map.add_deprecated(protocol_name);
+        debug!("deprecate message {protocol_name}");
+    }
 }
 
 impl MessageRegistry {
     pub(crate) fn message_type(&self, net_id: NetId) -> MessageType {
+        if self.kind_map.is_deprecated(net_id) {
+            return MessageType::Deprecated;
+        }
         let kind = self.kind_map.kind(net_id).unwrap();
         self.typed_map
             .get(kind)
@@ -365,6 +465,50 @@ impl MessageRegistry {
         self.typed_map.insert(message_kind, message_type);
     }
 
+    /// Reserve the [`NetId`] used to identify the messages that are part of a
+    /// [`SendGroup`](crate::shared::message_group::SendGroup)
+    pub(crate) fn add_message_group_header(&mut self) {
+        self.kind_map.add::<MessageGroupHeader>();
+    }
+
+    /// [`NetId`] written in front of the messages that are part of a
+    /// [`SendGroup`](crate::shared::message_group::SendGroup)
+    pub(crate) fn message_group_net_id(&self) -> Result<NetId, MessageError> {
+        self.kind_map
+            .net_id(&MessageKind::of::<MessageGroupHeader>())
+            .copied()
+            .ok_or(MessageError::NotRegistered)
+    }
+
+    /// Reserve the [`NetId`] used to identify the messages that are applied at a tick of a
+    /// [`VisualTimeline`](crate::shared::timeline_message::VisualTimeline)
+    pub(crate) fn add_timeline_message_header(&mut self) {
+        self.kind_map.add::<TimelineMessageHeader>();
+    }
+
+    /// [`NetId`] written in front of the messages that are applied at a tick of a
+    /// [`VisualTimeline`](crate::shared::timeline_message::VisualTimeline)
+    pub(crate) fn timeline_message_net_id(&self) -> Result<NetId, MessageError> {
+        self.kind_map
+            .net_id(&MessageKind::of::<TimelineMessageHeader>())
+            .copied()
+            .ok_or(MessageError::NotRegistered)
+    }
+
+    pub(crate) fn set_client_apply<M: Message>(&mut self, apply: MessageApplyFn<M>) {
+        let kind = MessageKind::of::<M>();
+        self.client_apply_map.insert(kind, unsafe {
+            std::mem::transmute::<for<'a> fn(&'a mut World, M, Tick), unsafe fn()>(apply)
+        });
+    }
+
+    /// Function that handles the messages `M` received from the server, if any
+    pub(crate) fn client_apply<M: Message>(&self) -> Option<MessageApplyFn<M>> {
+        self.client_apply_map
+            .get(&MessageKind::of::<M>())
+            .map(|apply| unsafe { std::mem::transmute::<unsafe fn(), MessageApplyFn<M>>(*apply) })
+    }
+
     pub(crate) fn try_add_map_entities<M: MapEntities + 'static>(&mut self) {
         let kind = MessageKind::of::<M>();
         if let Some(erased_fns) = self.serialize_fns_map.get_mut(&kind) {
@@ -381,6 +525,50 @@ impl MessageRegistry {
         erased_fns.add_map_entities::<M>();
     }
 
+    pub(crate) fn set_versioned<M: Versioned + 'static>(&mut self) {
+        let kind = MessageKind::of::<M>();
+        let erased_fns = self
+            .serialize_fns_map
+            .get_mut(&kind)
+            .expect("the message is not part of the protocol");
+        erased_fns.set_versioned::<M>();
+    }
+
+    pub(crate) fn set_size_warning_threshold<M: 'static>(&mut self, bytes: usize) {
+        let kind = MessageKind::of::<M>();
+        let erased_fns = self
+            .serialize_fns_map
+            .get_mut(&kind)
+            .expect("the message is not part of the protocol");
+        erased_fns.size_warning_threshold = Some(bytes);
+    }
+
+    /// Type name of the message, and size above which its serializations are reported
+    pub(crate) fn serialized_size_info(
+        &self,
+        kind: &MessageKind,
+    ) -> Option<(&'static str, Option<usize>)> {
+        let erased_fns = self.serialize_fns_map.get(kind)?;
+        Some((erased_fns.type_name, erased_fns.size_warning_threshold))
+    }
+
+    /// Schema of the message, if it is serialized with its [`Versioned`] implementation
+    pub(crate) fn schema(&self, kind: &MessageKind) -> Option<&Schema> {
+        self.serialize_fns_map.get(kind)?.schema.as_ref()
+    }
+
+    /// The messages that are serialized with their [`Versioned`] implementation, with their [`NetId`]
+    /// and their type name
+    pub(crate) fn schemas(&self) -> impl Iterator<Item = (NetId, &'static str, &Schema)> {
+        self.serialize_fns_map.iter().filter_map(|(kind, fns)| {
+            Some((
+                *self.kind_map.net_id(kind)?,
+                fns.type_name,
+                fns.schema.as_ref()?,
+            ))
+        })
+    }
+
     pub(crate) fn serialize<M: Message>(
         &self,
         message: &M,
@@ -417,6 +605,31 @@ impl MessageRegistry {
         // SAFETY: the ErasedSerializeFns was created for the type M
         unsafe { erased_fns.deserialize(reader, entity_map) }.map_err(Into::into)
     }
+
+    /// Deserialize the message and map its entities, or return [`MessageError::UnspawnedEntity`] if it
+    /// references a remote entity that was not spawned locally yet
+    pub(crate) fn deserialize_spawned<M: Message>(
+        &self,
+        reader: &mut Reader,
+        entity_map: &mut EntityMap,
+        entities: &Entities,
+    ) -> Result<M, MessageError> {
+        let net_id = NetId::from_bytes(reader)?;
+        let kind = self
+            .kind_map
+            .kind(net_id)
+            .ok_or(MessageError::NotRegistered)?;
+        let erased_fns = self
+            .serialize_fns_map
+            .get(kind)
+            .ok_or(MessageError::MissingSerializationFns)?;
+        // SAFETY: the ErasedSerializeFns was created for the type M
+        let mut message = unsafe { erased_fns.deserialize_unmapped::<M>(reader) }?;
+        erased_fns
+            .map_spawned_entities(&mut message, entity_map, entities)
+            .map_err(MessageError::UnspawnedEntity)?;
+        Ok(message)
+    }
 }
 
 /// [`MessageKind`] is an internal wrapper around the type of the message
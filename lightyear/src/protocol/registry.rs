@@ -1,6 +1,11 @@
+use crate::protocol::channel::ChannelRegistry;
+use crate::protocol::component::ComponentRegistry;
+use crate::protocol::message::MessageRegistry;
 use crate::serialize::reader::Reader;
 use crate::serialize::varint::{varint_len, VarIntReadExt, VarIntWriteExt};
+use crate::serialize::versioned::Schema;
 use crate::serialize::{SerializationError, ToBytes};
+use bevy::prelude::{App, World};
 use bevy::utils::HashMap;
 use byteorder::WriteBytesExt;
 use std::any::TypeId;
@@ -29,12 +34,55 @@ impl ToBytes for NetId {
 
 pub trait TypeKind: From<TypeId> + Copy + PartialEq + Eq + Hash {}
 
+/// Name of a registered type, from which its [`NetId`] is derived
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct ProtocolName {
+    /// Name of the rust type
+    pub(crate) type_name: &'static str,
+    /// Name used to derive the [`NetId`]: the fully-qualified type name, unless it was overridden
+    pub(crate) name: String,
+}
+
+impl ProtocolName {
+    fn hash(&self) -> u64 {
+        seahash::hash(self.name.as_bytes())
+    }
+}
+
+/// Type name shown in the [`NetIdReport`] for the deprecated types, which don't have a rust type anymore
+pub(crate) const DEPRECATED_TYPE_NAME: &str = "<deprecated>";
+
+/// Entry of a [`TypeMapper`] that gets a [`NetId`]
+#[derive(Clone, Copy)]
+enum MappedEntry<K> {
+    Type(K),
+    /// Index of the protocol name in [`TypeMapper::deprecated`]
+    Deprecated(usize),
+}
+
 /// Struct to map a type to an id that can be serialized over the network
+///
+/// The [`NetId`] of a type does not depend on the order in which the types are registered: each type has
+/// a stable 64-bit hash of its fully-qualified name, and the types are numbered in the order of their hashes,
+/// so that the ids stay small. Two types with the same hash make the registration panic.
+///
+/// Two peers that register the same set of types always agree on the ids. Adding or removing a type changes
+/// the ids of the types whose hash is greater, so peers with different sets of types are rejected during the
+/// handshake because the hash of their [`NetIdReport`] differs.
+///
+/// The ids are only final once all the types are registered.
+///
+/// A type that is removed from the protocol can be kept as a deprecated protocol name: it keeps its [`NetId`],
+/// so that the ids of the other types don't change and the data of that type sent by older peers can be skipped.
 #[derive(Clone, Debug, PartialEq)]
 pub struct TypeMapper<K: TypeKind> {
-    pub(crate) next_net_id: NetId,
     pub(crate) kind_map: HashMap<K, NetId>,
     pub(crate) id_map: HashMap<NetId, K>,
+    pub(crate) names: HashMap<K, ProtocolName>,
+    /// Protocol names of the deprecated types
+    deprecated: Vec<String>,
+    /// [`NetId`]s of the deprecated types, with their protocol name
+    pub(crate) deprecated_ids: HashMap<NetId, String>,
 }
 
 impl<K: TypeKind> Default for TypeMapper<K> {
@@ -46,9 +94,11 @@ impl<K: TypeKind> Default for TypeMapper<K> {
 impl<K: TypeKind> TypeMapper<K> {
     pub fn new() -> Self {
         Self {
-            next_net_id: 0,
             kind_map: HashMap::new(),
             id_map: HashMap::new(),
+            names: HashMap::new(),
+            deprecated: Vec::new(),
+            deprecated_ids: HashMap::new(),
         }
     }
 
@@ -58,13 +108,100 @@ impl<K: TypeKind> TypeMapper<K> {
         if self.kind_map.contains_key(&kind) {
             panic!("Type {:?} already registered", std::any::type_name::<T>());
         }
-        let net_id = self.next_net_id;
-        self.kind_map.insert(kind, net_id);
-        self.id_map.insert(net_id, kind);
-        self.next_net_id += 1;
+        let type_name = std::any::type_name::<T>();
+        self.names.insert(
+            kind,
+            ProtocolName {
+                type_name,
+                name: type_name.to_string(),
+            },
+        );
+        self.assign_net_ids();
         kind
     }
 
+    /// Derive the [`NetId`] of the type from `name` instead of its type name, for example
+    /// to keep the same id after the type was renamed or moved to another crate.
+    ///
+    /// # Panics
+    /// Panics if the type is not registered.
+    pub(crate) fn set_protocol_name(&mut self, kind: K, name: impl Into<String>) {
+        let protocol_name = self
+            .names
+            .get_mut(&kind)
+            .expect("the type must be registered before its protocol name is set");
+        protocol_name.name = name.into();
+        self.assign_net_ids();
+    }
+
+    /// Keep the [`NetId`] of a type that was removed from the protocol.
+    ///
+    /// `name` is the protocol name of the removed type: its fully-qualified type name, unless it was overridden.
+    pub(crate) fn add_deprecated(&mut self, name: impl Into<String>) {
+        let name = name.into();
+        if self.deprecated.contains(&name) {
+            panic!("Protocol name {name:?} already deprecated");
+        }
+        self.deprecated.push(name);
+        self.assign_net_ids();
+    }
+
+    /// Type name and protocol name of an entry
+    fn describe(&self, entry: MappedEntry<K>) -> (&'static str, &str) {
+        match entry {
+            MappedEntry::Type(kind) => {
+                let name = &self.names[&kind];
+                (name.type_name, &name.name)
+            }
+            MappedEntry::Deprecated(index) => (DEPRECATED_TYPE_NAME, &self.deprecated[index]),
+        }
+    }
+
+    /// Number the registered and deprecated types in the order of the hashes of their protocol names.
+    ///
+    /// # Panics
+    /// Panics if two types have the same hash.
+    fn assign_net_ids(&mut self) {
+        let mut hashes: Vec<(u64, MappedEntry<K>)> = self
+            .names
+            .iter()
+            .map(|(kind, name)| (name.hash(), MappedEntry::Type(*kind)))
+            .chain(self.deprecated.iter().enumerate().map(|(index, name)| {
+                (
+                    seahash::hash(name.as_bytes()),
+                    MappedEntry::Deprecated(index),
+                )
+            }))
+            .collect();
+        hashes.sort_unstable_by_key(|(hash, _)| *hash);
+        for pair in hashes.windows(2) {
+            if pair[0].0 == pair[1].0 {
+                let (a, b) = (self.describe(pair[0].1), self.describe(pair[1].1));
+                panic!(
+                    "The types {:?} and {:?} would get the same net id (protocol names {:?} and {:?}). \
+                    Use a different protocol name for one of them.",
+                    a.0, b.0, a.1, b.1
+                );
+            }
+        }
+        self.kind_map.clear();
+        self.id_map.clear();
+        self.deprecated_ids.clear();
+        for (net_id, (_, entry)) in hashes.into_iter().enumerate() {
+            let net_id = NetId::try_from(net_id).expect("too many types registered");
+            match entry {
+                MappedEntry::Type(kind) => {
+                    self.kind_map.insert(kind, net_id);
+                    self.id_map.insert(net_id, kind);
+                }
+                MappedEntry::Deprecated(index) => {
+                    self.deprecated_ids
+                        .insert(net_id, self.deprecated[index].clone());
+                }
+            }
+        }
+    }
+
     pub fn kind(&self, net_id: NetId) -> Option<&K> {
         self.id_map.get(&net_id)
     }
@@ -73,8 +210,283 @@ impl<K: TypeKind> TypeMapper<K> {
         self.kind_map.get(kind)
     }
 
-    #[cfg(test)]
+    /// Returns true if the [`NetId`] belongs to a type that was removed from the protocol
+    pub fn is_deprecated(&self, net_id: NetId) -> bool {
+        self.deprecated_ids.contains_key(&net_id)
+    }
+
     pub(in crate::protocol) fn len(&self) -> usize {
         self.kind_map.len()
     }
+
+    /// Iterate through the registered types, in the order of their [`NetId`]
+    fn report_entries<'a>(
+        &'a self,
+        registry: &'static str,
+        schema: impl Fn(&K) -> Option<&'a Schema> + 'a,
+    ) -> impl Iterator<Item = NetIdEntry> + 'a {
+        let len = self.len() + self.deprecated_ids.len();
+        (0..len as NetId).map(move |net_id| {
+            let Some(kind) = self.id_map.get(&net_id) else {
+                return NetIdEntry {
+                    registry,
+                    net_id,
+                    protocol_name: self.deprecated_ids[&net_id].clone(),
+                    type_name: DEPRECATED_TYPE_NAME,
+                    schema: None,
+                    deprecated: true,
+                };
+            };
+            let name = &self.names[kind];
+            NetIdEntry {
+                registry,
+                net_id,
+                protocol_name: name.name.clone(),
+                type_name: name.type_name,
+                schema: schema(kind).map(Schema::base_fields),
+                deprecated: false,
+            }
+        })
+    }
+}
+
+/// Id assigned to one type of the protocol
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NetIdEntry {
+    /// Registry of the type: `"channel"`, `"component"` or `"message"`
+    pub registry: &'static str,
+    pub net_id: u16,
+    /// Name from which the id is derived
+    pub protocol_name: String,
+    pub type_name: &'static str,
+    /// Fields of the first version of the type, if its schema can evolve (see [`Versioned`](crate::prelude::Versioned)).
+    /// The fields added in later versions are not included, so that adding fields keeps the protocol compatible
+    pub schema: Option<String>,
+    /// True if the type was removed from the protocol, but its id is kept so that the data of that type
+    /// sent by older peers can be skipped
+    pub deprecated: bool,
+}
+
+impl NetIdEntry {
+    /// Write the entry, without the deprecation marker
+    fn write_mapping(&self, f: &mut impl std::fmt::Write) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {} {}",
+            self.registry, self.net_id, self.protocol_name
+        )?;
+        if let Some(schema) = &self.schema {
+            write!(f, " {schema}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Mapping between the types of the protocol and their [`NetId`]s.
+///
+/// Two peers can only exchange data if they have the same mapping: its [`hash`](NetIdReport::hash) is checked
+/// when a client connects. The [`Display`](std::fmt::Display) output lists one type per line, and can be used
+/// as a golden file to detect changes of the protocol that would break compatibility with other versions.
+/// The types whose schema can evolve also list the fields of their first version (see [`Versioned`](crate::prelude::Versioned)),
+/// and the deprecated types are marked as `deprecated`:
+///
+/// ```rust,ignore
+/// let report = app.net_id_report().to_string();
+/// assert_eq!(report, include_str!("protocol.txt"));
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NetIdReport {
+    /// The channels, then the components, then the messages, each in the order of their ids
+    pub entries: Vec<NetIdEntry>,
+}
+
+impl NetIdReport {
+    /// Build the report from the registries of the [`World`]
+    pub fn from_world(world: &World) -> Self {
+        let mut entries = Vec::new();
+        if let Some(registry) = world.get_resource::<ChannelRegistry>() {
+            entries.extend(registry.kind_map.report_entries("channel", |_| None));
+        }
+        if let Some(registry) = world.get_resource::<ComponentRegistry>() {
+            entries.extend(
+                registry
+                    .kind_map
+                    .report_entries("component", |kind| registry.schema(kind)),
+            );
+        }
+        if let Some(registry) = world.get_resource::<MessageRegistry>() {
+            entries.extend(
+                registry
+                    .kind_map
+                    .report_entries("message", |kind| registry.schema(kind)),
+            );
+        }
+        Self { entries }
+    }
+
+    /// Stable hash of the mapping, which identifies the protocol during the connection handshake.
+    ///
+    /// Deprecating a type doesn't change the hash, so that the peers that still use the type can connect.
+    pub fn hash(&self) -> u64 {
+        let mut mapping = String::new();
+        for entry in &self.entries {
+            // writing to a String cannot fail
+            let _ = entry.write_mapping(&mut mapping);
+            mapping.push('\n');
+        }
+        seahash::hash(mapping.as_bytes())
+    }
+}
+
+impl std::fmt::Display for NetIdReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for entry in &self.entries {
+            entry.write_mapping(f)?;
+            if entry.deprecated {
+                write!(f, " deprecated")?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+/// Extension trait to inspect the ids assigned to the protocol
+pub trait AppNetIdExt {
+    /// Returns the [`NetIdReport`] of the protocol registered in the app
+    fn net_id_report(&self) -> NetIdReport;
+}
+
+impl AppNetIdExt for App {
+    fn net_id_report(&self) -> NetIdReport {
+        NetIdReport::from_world(self.world())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::component::ComponentKind;
+
+    struct A;
+    struct B;
+    struct C;
+
+    #[test]
+    fn test_net_ids_do_not_depend_on_registration_order() {
+        let mut first = TypeMapper::<ComponentKind>::new();
+        first.add::<A>();
+        first.add::<B>();
+        first.add::<C>();
+        let mut second = TypeMapper::<ComponentKind>::new();
+        second.add::<C>();
+        second.add::<A>();
+        second.add::<B>();
+        assert_eq!(first.kind_map, second.kind_map);
+        let mut ids: Vec<NetId> = first.kind_map.values().copied().collect();
+        ids.sort();
+        assert_eq!(ids, vec![0, 1, 2]);
+    }
+
+    /// Adding a type keeps the ids compact, and changes the hash of the protocol that the peers
+    /// compare during the handshake
+    #[test]
+    fn test_adding_type_changes_protocol_hash() {
+        use bevy::prelude::{Component, Reflect};
+        use lightyear_macros::ChannelInternal;
+        use serde::{Deserialize, Serialize};
+
+        use crate::prelude::*;
+        use crate::tests::protocol::ProtocolPlugin;
+
+        #[derive(Component, Serialize, Deserialize, Clone, Debug, PartialEq)]
+        struct ExtraComponent;
+        #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+        struct ExtraMessage;
+        #[derive(ChannelInternal, Reflect)]
+        struct ExtraChannel;
+
+        let mut app = LightyearTestPair::builder()
+            .protocol(ProtocolPlugin)
+            .build_disconnected()
+            .server_app;
+        let before = app.net_id_report();
+        app.register_component::<ExtraComponent>(ChannelDirection::ServerToClient);
+        app.register_message::<ExtraMessage>(ChannelDirection::ServerToClient);
+        app.add_channel::<ExtraChannel>(ChannelSettings::default());
+        let after = app.net_id_report();
+
+        assert_eq!(after.entries.len(), before.entries.len() + 3);
+        assert_ne!(after.hash(), before.hash());
+        for registry in ["channel", "component", "message"] {
+            let mut ids: Vec<NetId> = after
+                .entries
+                .iter()
+                .filter(|entry| entry.registry == registry)
+                .map(|entry| entry.net_id)
+                .collect();
+            ids.sort_unstable();
+            assert!(
+                ids.iter().copied().eq(0..ids.len() as NetId),
+                "{registry}: {ids:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_protocol_name_override() {
+        let mut renamed = TypeMapper::<ComponentKind>::new();
+        renamed.add::<A>();
+        renamed.add::<C>();
+        renamed.set_protocol_name(ComponentKind::of::<C>(), std::any::type_name::<B>());
+        let mut original = TypeMapper::<ComponentKind>::new();
+        original.add::<A>();
+        original.add::<B>();
+        assert_eq!(
+            renamed.net_id(&ComponentKind::of::<C>()),
+            original.net_id(&ComponentKind::of::<B>())
+        );
+    }
+
+    #[test]
+    fn test_deprecated_keeps_net_ids() {
+        let mut original = TypeMapper::<ComponentKind>::new();
+        original.add::<A>();
+        original.add::<B>();
+        original.add::<C>();
+        let mut deprecated = TypeMapper::<ComponentKind>::new();
+        deprecated.add::<A>();
+        deprecated.add_deprecated(std::any::type_name::<B>());
+        deprecated.add::<C>();
+        for kind in [ComponentKind::of::<A>(), ComponentKind::of::<C>()] {
+            assert_eq!(deprecated.net_id(&kind), original.net_id(&kind));
+        }
+        let net_id = *original.net_id(&ComponentKind::of::<B>()).unwrap();
+        assert!(deprecated.is_deprecated(net_id));
+        assert!(deprecated.kind(net_id).is_none());
+
+        let entries: Vec<_> = deprecated.report_entries("component", |_| None).collect();
+        assert_eq!(entries.len(), 3);
+        assert!(entries[net_id as usize].deprecated);
+        assert_eq!(entries[net_id as usize].type_name, DEPRECATED_TYPE_NAME);
+        // the deprecation doesn't change the hash of the protocol
+        let report = |entries: Vec<NetIdEntry>| NetIdReport { entries };
+        let original_entries: Vec<_> = original.report_entries("component", |_| None).collect();
+        assert_eq!(
+            report(entries.clone()).hash(),
+            report(original_entries.clone()).hash()
+        );
+        assert!(report(entries)
+            .to_string()
+            .contains(&format!("{} deprecated", std::any::type_name::<B>())));
+    }
+
+    #[test]
+    #[should_panic(expected = "would get the same net id")]
+    fn test_collision() {
+        let mut mapper = TypeMapper::<ComponentKind>::new();
+        mapper.add::<A>();
+        mapper.add::<B>();
+        mapper.set_protocol_name(ComponentKind::of::<B>(), std::any::type_name::<A>());
+    }
 }
@@ -1,8 +1,10 @@
 use crate::prelude::{ComponentRegistry, Message, MessageRegistry};
+use crate::serialize::versioned::{Schema, Versioned};
 use crate::serialize::{reader::Reader, writer::Writer, SerializationError};
-use crate::shared::replication::entity_map::EntityMap;
+use crate::shared::replication::entity_map::{DynEntityMapper, EntityMap, UnspawnedEntityProbe};
 use bevy::app::App;
-use bevy::ecs::entity::MapEntities;
+use bevy::ecs::entity::{Entities, EntityMapper, MapEntities};
+use bevy::prelude::Entity;
 use bevy::ptr::{Ptr, PtrMut};
 use serde::de::DeserializeOwned;
 use serde::Serialize;
@@ -18,6 +20,11 @@ pub struct ErasedSerializeFns {
     pub erased_serialize: ErasedSerializeFn,
     pub deserialize: unsafe fn(),
     pub map_entities: Option<ErasedMapEntitiesFn>,
+    /// Schema of the type, if it is serialized with its [`Versioned`] implementation
+    pub(crate) schema: Option<Schema>,
+    /// Size in bytes above which a serialization of the type is reported
+    /// (see [`serialized_size`](crate::shared::serialized_size))
+    pub(crate) size_warning_threshold: Option<usize>,
 }
 
 pub struct SerializeFns<M> {
@@ -33,7 +40,8 @@ type ErasedSerializeFn = unsafe fn(
 type SerializeFn<M> = fn(message: &M, writer: &mut Writer) -> Result<(), SerializationError>;
 type DeserializeFn<M> = fn(reader: &mut Reader) -> Result<M, SerializationError>;
 
-pub(crate) type ErasedMapEntitiesFn = unsafe fn(message: PtrMut, entity_map: &mut EntityMap);
+pub(crate) type ErasedMapEntitiesFn =
+    unsafe fn(message: PtrMut, entity_mapper: &mut dyn EntityMapper);
 
 unsafe fn erased_serialize_fn<M: Message>(
     erased_serialize_fn: &ErasedSerializeFns,
@@ -65,10 +73,10 @@ fn default_deserialize<M: Message + DeserializeOwned>(
 /// SAFETY: the PtrMut must be a valid pointer to a value of type M
 unsafe fn erased_map_entities<M: MapEntities + 'static>(
     message: PtrMut,
-    entity_map: &mut EntityMap,
+    entity_mapper: &mut dyn EntityMapper,
 ) {
     let data = message.deref_mut::<M>();
-    M::map_entities(data, entity_map);
+    M::map_entities(data, &mut DynEntityMapper(entity_mapper));
 }
 
 impl ErasedSerializeFns {
@@ -84,6 +92,8 @@ impl ErasedSerializeFns {
             serialize: unsafe { std::mem::transmute(serialize_fns.serialize) },
             deserialize: unsafe { std::mem::transmute(serialize_fns.deserialize) },
             map_entities: None,
+            schema: None,
+            size_warning_threshold: None,
         }
     }
 
@@ -95,6 +105,8 @@ impl ErasedSerializeFns {
             serialize: unsafe { std::mem::transmute(serialize_fns.serialize) },
             deserialize: unsafe { std::mem::transmute(serialize_fns.deserialize) },
             map_entities: None,
+            schema: None,
+            size_warning_threshold: None,
         }
     }
 
@@ -121,6 +133,15 @@ impl ErasedSerializeFns {
         self.map_entities = Some(erased_map_entities::<M>);
     }
 
+    /// Serialize the type with its [`Versioned`] implementation, so that its schema can evolve
+    pub(crate) fn set_versioned<M: Versioned + 'static>(&mut self) {
+        debug_assert_eq!(self.type_id, TypeId::of::<M>());
+        let serialize_fns = SerializeFns::<M>::versioned();
+        self.serialize = unsafe { std::mem::transmute(serialize_fns.serialize) };
+        self.deserialize = unsafe { std::mem::transmute(serialize_fns.deserialize) };
+        self.schema = Some(M::SCHEMA);
+    }
+
     pub(crate) fn map_entities<M: 'static>(&self, message: &mut M, entity_map: &mut EntityMap) {
         let ptr = PtrMut::from(message);
         if let Some(map_entities_fn) = self.map_entities {
@@ -128,6 +149,32 @@ impl ErasedSerializeFns {
         }
     }
 
+    /// Map the entities of the value, unless it references a remote entity that was not spawned locally yet.
+    ///
+    /// Returns the first entity that was not spawned yet, in which case the value is not modified.
+    pub(crate) fn map_spawned_entities<M: 'static>(
+        &self,
+        message: &mut M,
+        entity_map: &mut EntityMap,
+        entities: &Entities,
+    ) -> Result<(), Entity> {
+        let Some(map_entities_fn) = self.map_entities else {
+            return Ok(());
+        };
+        let mut probe = UnspawnedEntityProbe {
+            entity_map,
+            entities,
+            unspawned: None,
+        };
+        // SAFETY: the ErasedSerializeFns must be created for the type M
+        unsafe { map_entities_fn(PtrMut::from(&mut *message), &mut probe) };
+        if let Some(entity) = probe.unspawned {
+            return Err(entity);
+        }
+        unsafe { map_entities_fn(PtrMut::from(message), entity_map) };
+        Ok(())
+    }
+
     /// SAFETY: the ErasedSerializeFns must be created for the type of the Ptr
     pub(crate) unsafe fn erased_serialize(
         &self,
@@ -155,13 +202,23 @@ impl ErasedSerializeFns {
         reader: &mut Reader,
         entity_map: &mut EntityMap,
     ) -> Result<M, SerializationError> {
-        let fns = unsafe { self.typed::<M>() };
-        let mut message = (fns.deserialize)(reader)?;
+        let mut message = self.deserialize_unmapped::<M>(reader)?;
         if let Some(map_entities) = self.map_entities {
             map_entities(PtrMut::from(&mut message), entity_map);
         }
         Ok(message)
     }
+
+    /// Deserialize the message value from the reader, without mapping the entities it contains
+    ///
+    /// SAFETY: the ErasedSerializeFns must be created for the type M
+    pub(crate) unsafe fn deserialize_unmapped<M: 'static>(
+        &self,
+        reader: &mut Reader,
+    ) -> Result<M, SerializationError> {
+        let fns = unsafe { self.typed::<M>() };
+        (fns.deserialize)(reader)
+    }
 }
 
 pub trait AppSerializeExt {
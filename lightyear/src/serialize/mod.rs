@@ -7,8 +7,10 @@ use bytes::Bytes;
 use hashbrown::HashMap;
 use std::hash::{BuildHasher, Hash};
 
+pub mod quantize;
 pub mod reader;
 pub(crate) mod varint;
+pub mod versioned;
 pub mod writer;
 
 pub type RawData = Vec<u8>;
@@ -96,7 +98,7 @@ impl ToBytes for Bytes {
         Self: Sized,
     {
         let len = buffer.read_varint()? as usize;
-        let bytes = buffer.split_len(len);
+        let bytes = buffer.split_len(len)?;
         Ok(bytes)
     }
 }
@@ -146,8 +148,9 @@ impl<M: ToBytes> ToBytes for Vec<M> {
         Self: Sized,
     {
         let len = buffer.read_u64::<byteorder::NetworkEndian>()? as usize;
-        // TODO: if we know the MIN_LEN we can preallocate
-        let mut vec = Vec::with_capacity(len);
+        // the length is read from the network: each item is at least one byte long, so we cannot
+        // preallocate more items than there are bytes remaining
+        let mut vec = Vec::with_capacity(len.min(buffer.remaining()));
         for _ in 0..len {
             vec.push(M::from_bytes(buffer)?);
         }
@@ -175,8 +178,8 @@ impl<K: ToBytes + Eq + Hash, V: ToBytes, S: Default + BuildHasher> ToBytes for H
         Self: Sized,
     {
         let len = buffer.read_u64::<byteorder::NetworkEndian>()? as usize;
-        // TODO: if we know the MIN_LEN we can preallocate
-        let mut res = HashMap::with_capacity_and_hasher(len, S::default());
+        // the length is read from the network, do not trust it to preallocate
+        let mut res = HashMap::with_capacity_and_hasher(len.min(buffer.remaining()), S::default());
         for _ in 0..len {
             let key = K::from_bytes(buffer)?;
             let value = V::from_bytes(buffer)?;
@@ -190,6 +193,7 @@ impl<K: ToBytes + Eq + Hash, V: ToBytes, S: Default + BuildHasher> ToBytes for H
 mod tests {
     use super::*;
     use crate::serialize::writer::Writer;
+    use std::io::Write;
 
     #[test]
     fn test_serialize_bytes() {
@@ -201,4 +205,26 @@ mod tests {
         let read = Bytes::from_bytes(&mut reader).unwrap();
         assert_eq!(a, read);
     }
+
+    /// The lengths read from the network are not trusted
+    #[test]
+    fn test_deserialize_invalid_length() {
+        // the Bytes are shorter than their length
+        let mut writer = Writer::with_capacity(5);
+        writer.write_varint(100).unwrap();
+        writer.write_all(&[7; 10]).unwrap();
+        let mut reader = Reader::from(writer.to_bytes());
+        assert!(Bytes::from_bytes(&mut reader).is_err());
+
+        // the number of items is much bigger than the number of bytes
+        let mut writer = Writer::with_capacity(5);
+        writer
+            .write_u64::<byteorder::NetworkEndian>(u64::MAX)
+            .unwrap();
+        let bytes = writer.to_bytes();
+        let mut reader = Reader::from(bytes.clone());
+        assert!(Vec::<Bytes>::from_bytes(&mut reader).is_err());
+        let mut reader = Reader::from(bytes);
+        assert!(HashMap::<Bytes, Bytes>::from_bytes(&mut reader).is_err());
+    }
 }
@@ -0,0 +1,393 @@
+//! Quantization of the float fields of the components and messages.
+//!
+//! Positions and velocities usually don't need the full precision of an `f32`: a value in a known range
+//! can be encoded on fewer bits. Derive [`Quantize`](lightyear_macros::Quantize) and mark the float fields
+//! with the range of their values and the number of bits used to encode them:
+//!
+//! ```rust,ignore
+//! #[derive(Component, Serialize, Deserialize, Clone, PartialEq, Quantize)]
+//! struct Position {
+//!     // each coordinate is encoded on 16 bits, with a precision of 2000 / 65535 ~= 0.03
+//!     #[quantize(range = "-1000.0..1000.0", bits = 16)]
+//!     value: Vec2,
+//!     // the other fields are serialized as usual
+//!     sector: u8,
+//! }
+//!
+//! app.register_component_custom_serde::<Position>(ChannelDirection::ServerToClient, SerializeFns::quantized());
+//! ```
+//!
+//! The quantized fields are packed together on the minimum number of bytes, before the other fields.
+//! Values outside of the range are clamped to the range (with a debug assertion), and the ends
+//! of the range are always encoded exactly.
+use bevy::math::{Vec2, Vec3, Vec4};
+use byteorder::{ReadBytesExt, WriteBytesExt};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::protocol::serialize::SerializeFns;
+use crate::serialize::reader::Reader;
+use crate::serialize::writer::Writer;
+use crate::serialize::SerializationError;
+
+/// Encoding of a float in the range `min..max` on `bits` bits
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Quantization {
+    min: f32,
+    max: f32,
+    bits: u8,
+}
+
+impl Quantization {
+    /// Panics if the range is empty or if `bits` is not between 1 and 32
+    pub fn new(min: f32, max: f32, bits: u8) -> Self {
+        assert!(min < max, "the quantization range {min}..{max} is empty");
+        assert!(
+            (1..=32).contains(&bits),
+            "a value can be quantized on 1 to 32 bits, not {bits}"
+        );
+        Self { min, max, bits }
+    }
+
+    pub fn min(&self) -> f32 {
+        self.min
+    }
+
+    pub fn max(&self) -> f32 {
+        self.max
+    }
+
+    pub fn bits(&self) -> u8 {
+        self.bits
+    }
+
+    /// Largest encoded value
+    fn max_level(&self) -> u64 {
+        (1u64 << self.bits) - 1
+    }
+
+    /// Encode the value. Values outside of the range are clamped to the range
+    pub fn encode(&self, value: f32) -> u32 {
+        debug_assert!(
+            (self.min..=self.max).contains(&value),
+            "the value {value} is outside of the quantization range {}..{}",
+            self.min,
+            self.max
+        );
+        let value = value.clamp(self.min, self.max) as f64;
+        let (min, max) = (self.min as f64, self.max as f64);
+        ((value - min) / (max - min) * self.max_level() as f64).round() as u32
+    }
+
+    /// Decode a value encoded with [`Quantization::encode`]
+    pub fn decode(&self, encoded: u32) -> f32 {
+        let (min, max) = (self.min as f64, self.max as f64);
+        let level = (encoded as u64).min(self.max_level());
+        (min + level as f64 / self.max_level() as f64 * (max - min)) as f32
+    }
+}
+
+/// Packs values of arbitrary bit lengths into bytes
+pub struct BitWriter<'a> {
+    writer: &'a mut Writer,
+    pending: u64,
+    pending_bits: u32,
+}
+
+impl<'a> BitWriter<'a> {
+    pub fn new(writer: &'a mut Writer) -> Self {
+        Self {
+            writer,
+            pending: 0,
+            pending_bits: 0,
+        }
+    }
+
+    /// Write the `bits` lowest bits of the value
+    pub fn write_bits(&mut self, value: u32, bits: u8) -> Result<(), SerializationError> {
+        let mask = (1u64 << bits) - 1;
+        self.pending |= (value as u64 & mask) << self.pending_bits;
+        self.pending_bits += bits as u32;
+        while self.pending_bits >= 8 {
+            self.writer.write_u8(self.pending as u8)?;
+            self.pending >>= 8;
+            self.pending_bits -= 8;
+        }
+        Ok(())
+    }
+
+    /// Write the remaining bits, padded to a full byte
+    pub fn finish(self) -> Result<(), SerializationError> {
+        if self.pending_bits > 0 {
+            self.writer.write_u8(self.pending as u8)?;
+        }
+        Ok(())
+    }
+}
+
+/// Reads values written with a [`BitWriter`]
+pub struct BitReader<'a> {
+    reader: &'a mut Reader,
+    pending: u64,
+    pending_bits: u32,
+}
+
+impl<'a> BitReader<'a> {
+    pub fn new(reader: &'a mut Reader) -> Self {
+        Self {
+            reader,
+            pending: 0,
+            pending_bits: 0,
+        }
+    }
+
+    /// Read a value of `bits` bits. The padding of the last byte is discarded when the reader is dropped
+    pub fn read_bits(&mut self, bits: u8) -> Result<u32, SerializationError> {
+        while self.pending_bits < bits as u32 {
+            self.pending |= (self.reader.read_u8()? as u64) << self.pending_bits;
+            self.pending_bits += 8;
+        }
+        let value = self.pending & ((1u64 << bits) - 1);
+        self.pending >>= bits;
+        self.pending_bits -= bits as u32;
+        Ok(value as u32)
+    }
+}
+
+/// Float types that can be quantized with the `#[quantize]` attribute.
+///
+/// Every coordinate of a vector is quantized with the same range.
+#[diagnostic::on_unimplemented(
+    message = "`#[quantize]` can only be applied to float fields",
+    label = "`{Self}` is not a float type",
+    note = "the supported types are f32, f64, Vec2, Vec3, Vec4, Fixed32, FixedVec2 and FixedVec3"
+)]
+pub trait QuantizedFloat: Sized {
+    fn write_quantized(
+        &self,
+        quantization: Quantization,
+        writer: &mut BitWriter,
+    ) -> Result<(), SerializationError>;
+
+    fn read_quantized(
+        quantization: Quantization,
+        reader: &mut BitReader,
+    ) -> Result<Self, SerializationError>;
+}
+
+impl QuantizedFloat for f32 {
+    fn write_quantized(
+        &self,
+        quantization: Quantization,
+        writer: &mut BitWriter,
+    ) -> Result<(), SerializationError> {
+        writer.write_bits(quantization.encode(*self), quantization.bits)
+    }
+
+    fn read_quantized(
+        quantization: Quantization,
+        reader: &mut BitReader,
+    ) -> Result<Self, SerializationError> {
+        Ok(quantization.decode(reader.read_bits(quantization.bits)?))
+    }
+}
+
+impl QuantizedFloat for f64 {
+    fn write_quantized(
+        &self,
+        quantization: Quantization,
+        writer: &mut BitWriter,
+    ) -> Result<(), SerializationError> {
+        (*self as f32).write_quantized(quantization, writer)
+    }
+
+    fn read_quantized(
+        quantization: Quantization,
+        reader: &mut BitReader,
+    ) -> Result<Self, SerializationError> {
+        f32::read_quantized(quantization, reader).map(f64::from)
+    }
+}
+
+macro_rules! impl_quantized_vec {
+    ($vec: ty) => {
+        impl QuantizedFloat for $vec {
+            fn write_quantized(
+                &self,
+                quantization: Quantization,
+                writer: &mut BitWriter,
+            ) -> Result<(), SerializationError> {
+                self.to_array()
+                    .iter()
+                    .try_for_each(|value| value.write_quantized(quantization, writer))
+            }
+
+            fn read_quantized(
+                quantization: Quantization,
+                reader: &mut BitReader,
+            ) -> Result<Self, SerializationError> {
+                let mut array = <$vec>::ZERO.to_array();
+                for value in array.iter_mut() {
+                    *value = f32::read_quantized(quantization, reader)?;
+                }
+                Ok(<$vec>::from_array(array))
+            }
+        }
+    };
+}
+
+impl_quantized_vec!(Vec2);
+impl_quantized_vec!(Vec3);
+impl_quantized_vec!(Vec4);
+
+/// Serialization of a type with quantized fields, derived with [`#[derive(Quantize)]`](lightyear_macros::Quantize)
+pub trait Quantize: Sized {
+    fn quantize(&self, writer: &mut Writer) -> Result<(), SerializationError>;
+
+    fn dequantize(reader: &mut Reader) -> Result<Self, SerializationError>;
+}
+
+impl<M: Quantize> SerializeFns<M> {
+    /// Serialize the type with its [`Quantize`] implementation
+    pub fn quantized() -> Self {
+        Self {
+            serialize: M::quantize,
+            deserialize: M::dequantize,
+        }
+    }
+}
+
+/// Serialize a field that is not quantized, the same way as the fields of a non-quantized type
+#[doc(hidden)]
+pub fn serialize_field<T: Serialize>(
+    value: &T,
+    writer: &mut Writer,
+) -> Result<(), SerializationError> {
+    bincode::serde::encode_into_std_write(value, writer, bincode::config::standard())?;
+    Ok(())
+}
+
+#[doc(hidden)]
+pub fn deserialize_field<T: DeserializeOwned>(
+    reader: &mut Reader,
+) -> Result<T, SerializationError> {
+    Ok(bincode::serde::decode_from_std_read(
+        reader,
+        bincode::config::standard(),
+    )?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lightyear_macros::QuantizeInternal;
+    use serde::Deserialize;
+
+    #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, QuantizeInternal)]
+    struct Ship {
+        #[quantize(range = "-1000.0..1000.0", bits = 16)]
+        position: Vec2,
+        #[quantize(range = "-50.0..50.0", bits = 12)]
+        velocity: Vec2,
+        #[quantize(range = "0.0..6.2831855", bits = 10)]
+        rotation: f32,
+        name: String,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, QuantizeInternal)]
+    struct Health(#[quantize(range = "0.0..100.0", bits = 7)] f32, u8);
+
+    fn round_trip<T: Quantize>(value: &T) -> (T, usize) {
+        let mut writer = Writer::default();
+        value.quantize(&mut writer).unwrap();
+        let bytes = writer.to_bytes();
+        let len = bytes.len();
+        let mut reader = Reader::from(bytes);
+        (T::dequantize(&mut reader).unwrap(), len)
+    }
+
+    #[test]
+    fn test_encode_decode_is_symmetric() {
+        for bits in [1, 7, 8, 12, 16, 24, 32] {
+            let quantization = Quantization::new(-1000.0, 1000.0, bits);
+            let levels = (1u64 << bits) - 1;
+            // above 16 bits the steps are finer than the precision of a f32 around 1000.0
+            if bits <= 16 {
+                for level in [0, 1, levels / 3, levels / 2, levels - 1, levels] {
+                    let level = level as u32;
+                    assert_eq!(quantization.encode(quantization.decode(level)), level);
+                }
+            }
+            // the ends of the range are exact
+            assert_eq!(quantization.decode(quantization.encode(-1000.0)), -1000.0);
+            assert_eq!(quantization.decode(quantization.encode(1000.0)), 1000.0);
+            // the error is at most half a step
+            let step = 2000.0 / levels as f32;
+            let value = 123.456;
+            let error = (quantization.decode(quantization.encode(value)) - value).abs();
+            assert!(error <= step / 2.0 + 1e-4, "bits: {bits}, error: {error}");
+        }
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic(expected = "outside of the quantization range")]
+    fn test_out_of_range_debug_assert() {
+        Quantization::new(0.0, 1.0, 8).encode(2.0);
+    }
+
+    #[cfg(not(debug_assertions))]
+    #[test]
+    fn test_out_of_range_clamps() {
+        let quantization = Quantization::new(0.0, 1.0, 8);
+        assert_eq!(quantization.decode(quantization.encode(2.0)), 1.0);
+        assert_eq!(quantization.decode(quantization.encode(-2.0)), 0.0);
+    }
+
+    #[test]
+    fn test_bit_packing() {
+        let mut writer = Writer::default();
+        let mut bits = BitWriter::new(&mut writer);
+        let values = [(1, 1), (5, 3), (1000, 10), (u32::MAX, 32), (3, 2)];
+        for (value, len) in values {
+            bits.write_bits(value, len).unwrap();
+        }
+        bits.finish().unwrap();
+        writer.write_u8(42).unwrap();
+        let bytes = writer.to_bytes();
+        // 48 bits of packed values, followed by the byte-aligned value
+        assert_eq!(bytes.len(), 7);
+
+        let mut reader = Reader::from(bytes);
+        let mut bits = BitReader::new(&mut reader);
+        for (value, len) in values {
+            assert_eq!(bits.read_bits(len).unwrap(), value);
+        }
+        assert_eq!(reader.read_u8().unwrap(), 42);
+    }
+
+    #[test]
+    fn test_derive_round_trip() {
+        let ship = Ship {
+            position: Vec2::new(-1000.0, 250.0),
+            velocity: Vec2::new(50.0, -12.5),
+            rotation: 1.5,
+            name: "ship".to_string(),
+        };
+        let (read, len) = round_trip(&ship);
+        assert_eq!(read.name, ship.name);
+        assert!(read.position.abs_diff_eq(ship.position, 0.02));
+        assert!(read.velocity.abs_diff_eq(ship.velocity, 0.02));
+        assert!((read.rotation - ship.rotation).abs() < 0.01);
+        // 16 * 2 + 12 * 2 + 10 = 66 bits are packed on 9 bytes, and the name takes 5 bytes
+        assert_eq!(len, 9 + 5);
+        let mut writer = Writer::default();
+        serialize_field(&ship, &mut writer).unwrap();
+        assert_eq!(writer.to_bytes().len(), 5 * 4 + 5);
+
+        let (read, len) = round_trip(&Health(42.0, 3));
+        assert_eq!(read.1, 3);
+        assert!((read.0 - 42.0).abs() < 0.5);
+        assert_eq!(len, 2);
+    }
+}
@@ -1,6 +1,8 @@
 use bytes::{Buf, Bytes};
 use std::io::{Cursor, Read, Seek, SeekFrom};
 
+use crate::serialize::SerializationError;
+
 pub struct Reader(Cursor<Bytes>);
 
 impl From<Bytes> for Reader {
@@ -41,14 +43,19 @@ impl Reader {
     /// Split of the next `len` bytes from the reader into a separate Bytes.
     ///
     /// This doesn't allocate and just increases some reference counts. O(1) cost.
-    pub(crate) fn split_len(&mut self, len: usize) -> Bytes {
+    ///
+    /// Returns an error if the reader contains less than `len` bytes.
+    pub(crate) fn split_len(&mut self, len: usize) -> Result<Bytes, SerializationError> {
+        if len > self.remaining() {
+            return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into());
+        }
         let current_pos = self.0.position() as usize;
         let new_pos = current_pos + len;
         // slice off the subset into a separate Bytes
         let bytes = self.0.get_ref().slice(current_pos..new_pos);
         // increment the position
         self.0.set_position(new_pos as u64);
-        bytes
+        Ok(bytes)
     }
 
     pub(crate) fn has_remaining(&self) -> bool {
@@ -0,0 +1,352 @@
+//! Schema evolution of the components and messages.
+//!
+//! A live game is updated while the clients of the previous release are still connected. Derive
+//! [`Versioned`](lightyear_macros::Versioned) and mark every field added after the first release with
+//! the version of the schema that introduced it:
+//!
+//! ```rust,ignore
+//! #[derive(Component, Serialize, Deserialize, Clone, PartialEq, Versioned)]
+//! struct Health {
+//!     current: u16,
+//!     max: u16,
+//!     // peers with an older schema don't send this field: it is filled with its default value
+//!     #[net(added_in = 2, default)]
+//!     shield: u16,
+//!     #[net(added_in = 3, default = "default_regen")]
+//!     regen: f32,
+//! }
+//!
+//! app.register_component::<Health>(ChannelDirection::ServerToClient)
+//!     .add_schema_evolution();
+//! ```
+//!
+//! The version of the encoder is written first, then the fields of the first version, then for every
+//! later version the fields added in that version, prefixed by their length. A decoder with an older schema
+//! skips the fields that it doesn't know, and a decoder with a newer schema fills the fields that were not
+//! sent with their default value.
+//!
+//! Only the fields of the first version are part of the protocol hash (see [`NetIdReport`]): adding fields
+//! keeps the peers compatible, but removing or changing the type of a field of the first version is refused
+//! during the handshake. When they connect, the peers exchange the versions of their schemas: the negotiated
+//! version (`schema_version()` on the `ConnectionManager`) is the latest version known by both peers,
+//! i.e. the remote peer fills in all the fields added up to that version. If the peers disagree on the fields
+//! added in a version that they both know, the server disconnects the client.
+//!
+//! [`NetIdReport`]: crate::protocol::registry::NetIdReport
+use byteorder::{ReadBytesExt, WriteBytesExt};
+
+use crate::protocol::serialize::SerializeFns;
+use crate::serialize::reader::Reader;
+use crate::serialize::varint::{VarIntReadExt, VarIntWriteExt};
+use crate::serialize::writer::Writer;
+use crate::serialize::SerializationError;
+
+#[doc(hidden)]
+pub use crate::serialize::quantize::{deserialize_field, serialize_field};
+
+/// A field of a [`Versioned`] type
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FieldSchema {
+    pub name: &'static str,
+    /// The type of the field, as written in the source
+    pub type_name: &'static str,
+    /// Version of the schema that introduced the field; 1 for the fields of the first version
+    pub added_in: u8,
+}
+
+/// Schema of a [`Versioned`] type: its fields and the version in which each of them was added
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Schema {
+    /// Latest version of the schema
+    pub version: u8,
+    pub fields: &'static [FieldSchema],
+}
+
+impl Schema {
+    /// Fields added in `version`, in the order in which they are serialized
+    fn describe(&self, version: u8) -> String {
+        self.fields
+            .iter()
+            .filter(|field| field.added_in == version)
+            .map(|field| format!("{}: {}", field.name, field.type_name))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// Description of the fields of the first version, which is part of the protocol hash
+    pub fn base_fields(&self) -> String {
+        format!("{{{}}}", self.describe(1))
+    }
+
+    /// Fingerprint of the fields added in each version after the first, used to check that two peers agree on
+    /// the versions that they both know
+    pub(crate) fn fingerprints(&self) -> Vec<u64> {
+        (2..=self.version)
+            .map(|version| seahash::hash(self.describe(version).as_bytes()))
+            .collect()
+    }
+}
+
+/// Serialization of a type whose schema can evolve, derived with [`#[derive(Versioned)]`](lightyear_macros::Versioned)
+pub trait Versioned: Sized {
+    const SCHEMA: Schema;
+
+    fn serialize_versioned(&self, writer: &mut Writer) -> Result<(), SerializationError>;
+
+    /// Deserialize a value serialized by a peer with any version of the schema
+    fn deserialize_versioned(reader: &mut Reader) -> Result<Self, SerializationError>;
+}
+
+impl<M: Versioned> SerializeFns<M> {
+    /// Serialize the type with its [`Versioned`] implementation
+    pub fn versioned() -> Self {
+        Self {
+            serialize: M::serialize_versioned,
+            deserialize: M::deserialize_versioned,
+        }
+    }
+}
+
+#[doc(hidden)]
+pub fn write_version(writer: &mut Writer, version: u8) -> Result<(), SerializationError> {
+    writer.write_u8(version)?;
+    Ok(())
+}
+
+#[doc(hidden)]
+pub fn read_version(reader: &mut Reader) -> Result<u8, SerializationError> {
+    match reader.read_u8()? {
+        0 => Err(SerializationError::InvalidValue),
+        version => Ok(version),
+    }
+}
+
+/// Write the fields added in a version, prefixed by their length
+#[doc(hidden)]
+pub fn write_group(writer: &mut Writer, group: Writer) -> Result<(), SerializationError> {
+    let bytes = group.to_bytes();
+    writer.write_varint(bytes.len() as u64)?;
+    std::io::Write::write_all(writer, &bytes)?;
+    Ok(())
+}
+
+/// Read the fields added in a version
+#[doc(hidden)]
+pub fn read_group(reader: &mut Reader) -> Result<Reader, SerializationError> {
+    let len = reader.read_varint()? as usize;
+    Ok(Reader::from(reader.split_len(len)?))
+}
+
+/// Skip the fields added in the versions of the encoder that are more recent than `version`
+#[doc(hidden)]
+pub fn skip_groups(
+    reader: &mut Reader,
+    encoder_version: u8,
+    version: u8,
+) -> Result<(), SerializationError> {
+    for _ in version..encoder_version {
+        read_group(reader)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lightyear_macros::VersionedInternal;
+
+    /// The same component in three successive releases of a game
+    mod v1 {
+        use super::*;
+
+        #[derive(Debug, Clone, PartialEq, VersionedInternal)]
+        pub struct Health {
+            pub current: u16,
+            pub max: u16,
+        }
+    }
+
+    mod v2 {
+        use super::*;
+
+        #[derive(Debug, Clone, PartialEq, VersionedInternal)]
+        pub struct Health {
+            pub current: u16,
+            #[net(added_in = 2, default)]
+            pub shield: u16,
+            pub max: u16,
+        }
+    }
+
+    mod v3 {
+        use super::*;
+
+        pub fn default_regen() -> f32 {
+            1.5
+        }
+
+        #[derive(Debug, Clone, PartialEq, VersionedInternal)]
+        pub struct Health {
+            pub current: u16,
+            #[net(added_in = 2, default)]
+            pub shield: u16,
+            pub max: u16,
+            #[net(added_in = 3, default = "default_regen")]
+            pub regen: f32,
+            #[net(added_in = 3, default)]
+            pub tags: Vec<String>,
+        }
+    }
+
+    /// The first version, with a field retyped
+    mod retyped {
+        use super::*;
+
+        #[derive(Debug, Clone, PartialEq, VersionedInternal)]
+        pub struct Health {
+            pub current: u32,
+            pub max: u16,
+        }
+    }
+
+    /// The third version, skipping the second one
+    #[derive(Debug, Clone, PartialEq, VersionedInternal)]
+    struct Sparse(u8, #[net(added_in = 3, default)] u8);
+
+    /// Encode the value followed by a sentinel byte, and check that the decoder consumed exactly the bytes of the value
+    fn encode_decode<E: Versioned, D: Versioned>(value: &E) -> D {
+        let mut writer = Writer::default();
+        value.serialize_versioned(&mut writer).unwrap();
+        writer.write_u8(0xAB).unwrap();
+        let mut reader = Reader::from(writer.to_bytes());
+        let decoded = D::deserialize_versioned(&mut reader).unwrap();
+        assert_eq!(reader.read_u8().unwrap(), 0xAB);
+        decoded
+    }
+
+    fn health_v1() -> v1::Health {
+        v1::Health {
+            current: 80,
+            max: 100,
+        }
+    }
+
+    fn health_v2() -> v2::Health {
+        v2::Health {
+            current: 80,
+            shield: 20,
+            max: 100,
+        }
+    }
+
+    fn health_v3() -> v3::Health {
+        v3::Health {
+            current: 80,
+            shield: 20,
+            max: 100,
+            regen: 0.5,
+            tags: vec!["boss".to_string()],
+        }
+    }
+
+    #[test]
+    fn test_schema() {
+        assert_eq!(v1::Health::SCHEMA.version, 1);
+        assert_eq!(v2::Health::SCHEMA.version, 2);
+        assert_eq!(v3::Health::SCHEMA.version, 3);
+        assert_eq!(Sparse::SCHEMA.version, 3);
+        assert_eq!(
+            v3::Health::SCHEMA.fields[3],
+            FieldSchema {
+                name: "regen",
+                type_name: "f32",
+                added_in: 3,
+            }
+        );
+        // additive changes keep the fields of the first version
+        assert_eq!(v1::Health::SCHEMA.base_fields(), "{current: u16, max: u16}");
+        assert_eq!(
+            v2::Health::SCHEMA.base_fields(),
+            v1::Health::SCHEMA.base_fields()
+        );
+        assert_eq!(
+            v3::Health::SCHEMA.base_fields(),
+            v1::Health::SCHEMA.base_fields()
+        );
+        assert_ne!(
+            retyped::Health::SCHEMA.base_fields(),
+            v1::Health::SCHEMA.base_fields()
+        );
+        // the fields added in the second version are the same in both schemas
+        assert_eq!(
+            v3::Health::SCHEMA.fingerprints()[..1],
+            v2::Health::SCHEMA.fingerprints()[..]
+        );
+        assert_eq!(Sparse::SCHEMA.fingerprints().len(), 2);
+    }
+
+    #[test]
+    fn test_same_version() {
+        assert_eq!(encode_decode::<_, v1::Health>(&health_v1()), health_v1());
+        assert_eq!(encode_decode::<_, v2::Health>(&health_v2()), health_v2());
+        assert_eq!(encode_decode::<_, v3::Health>(&health_v3()), health_v3());
+        assert_eq!(encode_decode::<_, Sparse>(&Sparse(1, 2)), Sparse(1, 2));
+    }
+
+    /// The fields that the old encoder doesn't know are filled with their default value
+    #[test]
+    fn test_old_encoder_new_decoder() {
+        assert_eq!(
+            encode_decode::<_, v2::Health>(&health_v1()),
+            v2::Health {
+                shield: 0,
+                ..health_v2()
+            }
+        );
+        assert_eq!(
+            encode_decode::<_, v3::Health>(&health_v1()),
+            v3::Health {
+                shield: 0,
+                regen: 1.5,
+                tags: vec![],
+                ..health_v3()
+            }
+        );
+        assert_eq!(
+            encode_decode::<_, v3::Health>(&health_v2()),
+            v3::Health {
+                regen: 1.5,
+                tags: vec![],
+                ..health_v3()
+            }
+        );
+    }
+
+    /// The fields that the old decoder doesn't know are skipped
+    #[test]
+    fn test_new_encoder_old_decoder() {
+        assert_eq!(encode_decode::<_, v1::Health>(&health_v2()), health_v1());
+        assert_eq!(encode_decode::<_, v1::Health>(&health_v3()), health_v1());
+        assert_eq!(encode_decode::<_, v2::Health>(&health_v3()), health_v2());
+    }
+
+    #[test]
+    fn test_wire_format() {
+        let mut writer = Writer::default();
+        health_v2().serialize_versioned(&mut writer).unwrap();
+        // the version, the two fields of the first version, then the length and the fields of the second version
+        assert_eq!(writer.to_bytes().as_ref(), &[2, 80, 100, 1, 20]);
+
+        // the second version did not add any field
+        let mut writer = Writer::default();
+        Sparse(1, 2).serialize_versioned(&mut writer).unwrap();
+        assert_eq!(writer.to_bytes().as_ref(), &[3, 1, 0, 1, 2]);
+
+        // the version 0 does not exist
+        let mut reader = Reader::from(vec![0, 80, 100]);
+        assert!(v1::Health::deserialize_versioned(&mut reader).is_err());
+        // the group is longer than the remaining bytes
+        let mut reader = Reader::from(vec![2, 80, 100, 5, 20]);
+        assert!(v1::Health::deserialize_versioned(&mut reader).is_err());
+    }
+}
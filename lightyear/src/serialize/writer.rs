@@ -8,16 +8,31 @@
 use bytes::{BufMut, Bytes, BytesMut};
 use std::io::Write;
 
+use crate::utils::buffer_pool::HighWaterMark;
+
+/// The buffer is re-allocated with a smaller size if its capacity is this many times bigger than
+/// the number of bytes written per frame
+const SHRINK_FACTOR: usize = 4;
+
+/// Buffers smaller than this are never shrunk
+const MIN_SHRINK_CAPACITY: usize = 4096;
+
 #[derive(Debug)]
-pub struct Writer(bytes::buf::Writer<BytesMut>);
+pub struct Writer {
+    inner: bytes::buf::Writer<BytesMut>,
+    /// Number of bytes split since the last call to [`Writer::end_frame`]
+    frame_bytes: usize,
+    /// Largest number of bytes split during a frame recently
+    high_water_mark: HighWaterMark,
+}
 
 impl Write for Writer {
     fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
-        self.0.write(buf)
+        self.inner.write(buf)
     }
 
     fn flush(&mut self) -> std::io::Result<()> {
-        self.0.flush()
+        self.inner.flush()
     }
 }
 
@@ -29,15 +44,43 @@ impl Default for Writer {
 }
 impl Writer {
     pub(crate) fn with_capacity(capacity: usize) -> Self {
-        Self(BytesMut::with_capacity(capacity).writer())
+        Self {
+            inner: BytesMut::with_capacity(capacity).writer(),
+            frame_bytes: 0,
+            high_water_mark: HighWaterMark::default(),
+        }
     }
 
-    // TODO: how do reduce capacity over time?
     /// Split the current bytes written as a separate [`Bytes`].
     ///
     /// Retains any additional capacity. O(1) operation.
     pub(crate) fn split(&mut self) -> Bytes {
-        self.0.get_mut().split().freeze()
+        let bytes = self.inner.get_mut().split().freeze();
+        self.frame_bytes += bytes.len();
+        bytes
+    }
+
+    /// Number of bytes written since the last split
+    pub(crate) fn len(&self) -> usize {
+        self.inner.get_ref().len()
+    }
+
+    /// Prepare the buffer for the next frame, once the bytes split during this frame have been sent.
+    ///
+    /// The buffer is sized to hold the largest number of bytes written in a frame recently. If all the
+    /// [`Bytes`] split from the buffer have been dropped, its allocation is reclaimed instead of allocating
+    /// a new one. The buffer is re-allocated with a smaller size when it is much bigger than needed.
+    pub(crate) fn end_frame(&mut self) {
+        self.high_water_mark.record(self.frame_bytes);
+        self.frame_bytes = 0;
+        self.high_water_mark.end_frame();
+        let needed = self.high_water_mark.get();
+        let buffer = self.inner.get_mut();
+        buffer.reserve(needed);
+        if buffer.is_empty() && buffer.capacity() > SHRINK_FACTOR * needed.max(MIN_SHRINK_CAPACITY)
+        {
+            *buffer = BytesMut::with_capacity(needed);
+        }
     }
 
     // TODO: normally there is no need to reset, because once all the messages that have been split
@@ -46,11 +89,65 @@ impl Writer {
     //  senders, think about what to do for that! Maybe do a clone there to drop the message?
     /// Reset the writer but keeps the underlying allocation
     pub(crate) fn reset(&mut self) {
-        self.0.get_mut().clear();
+        self.inner.get_mut().clear();
     }
 
     /// Consume the writer to get the RawData
     pub(crate) fn to_bytes(self) -> Bytes {
-        self.0.into_inner().into()
+        self.inner.into_inner().into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_frame(writer: &mut Writer) -> Vec<Bytes> {
+        (0..10u8)
+            .map(|i| {
+                writer.write_all(&[i; 100]).unwrap();
+                writer.split()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_reclaim_buffer() {
+        let mut writer = Writer::with_capacity(0);
+        let mut first_message_ptrs = vec![];
+        for _ in 0..4 {
+            let frame = write_frame(&mut writer);
+            assert_eq!(frame[3], Bytes::from(vec![3; 100]));
+            first_message_ptrs.push(frame[0].as_ptr());
+            drop(frame);
+            writer.end_frame();
+        }
+        // once the buffer can hold a whole frame, its allocation is reused after the messages are dropped
+        assert_eq!(first_message_ptrs[2], first_message_ptrs[3]);
+
+        // while the messages are still alive, the next frame uses a new buffer that can hold a whole frame
+        let frame = write_frame(&mut writer);
+        writer.end_frame();
+        assert!(writer.inner.get_ref().capacity() >= 1000);
+        let next_frame = write_frame(&mut writer);
+        assert_ne!(next_frame[0].as_ptr(), frame[0].as_ptr());
+        assert_eq!(frame, next_frame);
+    }
+
+    #[test]
+    fn test_shrink_after_burst() {
+        let mut writer = Writer::with_capacity(0);
+        writer.write_all(&[0; 100_000]).unwrap();
+        drop(writer.split());
+        writer.end_frame();
+        assert!(writer.inner.get_ref().capacity() >= 100_000);
+
+        // the burst is forgotten after a few seconds of normal traffic
+        for _ in 0..1000 {
+            writer.write_all(&[0; 10]).unwrap();
+            drop(writer.split());
+            writer.end_frame();
+        }
+        assert!(writer.inner.get_ref().capacity() <= SHRINK_FACTOR * MIN_SHRINK_CAPACITY);
     }
 }
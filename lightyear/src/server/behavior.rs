@@ -0,0 +1,649 @@
+//! Analysis of the behavior of the clients over time, to detect cheats such as speed hacks or teleports.
+//!
+//! The validators (see [`validation`](crate::server::validation)) check every value received from a client
+//! on its own, but some cheats can only be detected over time. The [`BehaviorAnalysisPlugin`] records,
+//! for each client, a rolling history of the input messages and of the component updates that the client sent
+//! (see [`ClientBehaviorHistory`]), and runs the registered analyzers on it every
+//! [`BehaviorAnalysisConfig::analysis_interval`], outside of the systems that receive the packets.
+//! An analyzer is a system that reads the history and sends a [`SuspicionEvent`] for each client that
+//! behaves suspiciously; what to do with a suspicious client is left to the game.
+//!
+//! ```rust,ignore
+//! fn teleports(history: Res<ClientBehaviorHistory>, mut events: EventWriter<SuspicionEvent>) {
+//!     for (client_id, client_history) in history.iter() {
+//!         // ...
+//!     }
+//! }
+//!
+//! app.add_client_behavior_analyzer(teleports);
+//! // built-in analyzers
+//! app.add_max_displacement_analyzer::<Position>(MAX_SPEED_PER_TICK, |a, b| a.0.distance(b.0));
+//! app.add_input_rate_analyzer(10);
+//! ```
+//!
+//! The histories are bounded both in number of ticks and in number of records
+//! (see [`BehaviorAnalysisConfig`]), and their memory usage is reported by the
+//! [`ServerDiagnosticsPlugin`](crate::server::diagnostics::ServerDiagnosticsPlugin).
+//! Nothing is recorded until an analyzer is added.
+use std::any::Any;
+use std::collections::VecDeque;
+
+use bevy::prelude::*;
+use bevy::time::common_conditions::on_timer;
+use bevy::utils::{Duration, HashMap};
+
+use crate::prelude::server::{DisconnectEvent, ServerConfig};
+use crate::prelude::{ClientId, ComponentRegistry, Tick, TickManager};
+use crate::protocol::component::ComponentKind;
+use crate::server::run_conditions::is_started;
+use crate::shared::sets::{InternalMainSet, ServerMarker};
+
+/// Configuration of the [`BehaviorAnalysisPlugin`]
+#[derive(Resource, Debug, Clone, Copy, Reflect)]
+pub struct BehaviorAnalysisConfig {
+    /// Number of ticks for which the records of a client are kept
+    pub history_ticks: u16,
+    /// Maximum number of records of each kind kept for a client, so that the memory stays bounded
+    /// even if a client floods the server
+    pub max_records: usize,
+    /// How often the analyzers run
+    pub analysis_interval: Duration,
+}
+
+impl Default for BehaviorAnalysisConfig {
+    fn default() -> Self {
+        Self {
+            history_ticks: 256,
+            max_records: 1024,
+            analysis_interval: Duration::from_secs(1),
+        }
+    }
+}
+
+impl BehaviorAnalysisConfig {
+    pub fn with_history_ticks(mut self, history_ticks: u16) -> Self {
+        self.history_ticks = history_ticks;
+        self
+    }
+
+    pub fn with_max_records(mut self, max_records: usize) -> Self {
+        self.max_records = max_records;
+        self
+    }
+
+    pub fn with_analysis_interval(mut self, analysis_interval: Duration) -> Self {
+        self.analysis_interval = analysis_interval;
+        self
+    }
+}
+
+/// Event sent by an analyzer when a client behaves suspiciously
+#[derive(Event, Debug, Clone, PartialEq)]
+pub struct SuspicionEvent {
+    pub client_id: ClientId,
+    /// Name of the rule that the client broke
+    pub rule: &'static str,
+    /// How much the client exceeded the bound of the rule. The built-in analyzers use the ratio between
+    /// the observed value and the bound, which is always greater than 1.0
+    pub score: f32,
+}
+
+/// An input message received from a client
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InputRecord {
+    /// The server tick when the message was received
+    pub received_tick: Tick,
+    /// The last tick for which the message contains inputs
+    pub end_tick: Tick,
+}
+
+/// A component update received from a client
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UpdateRecord {
+    /// The client tick of the update
+    pub tick: Tick,
+    /// The server tick when the update was received
+    pub received_tick: Tick,
+    pub entity: Entity,
+    pub kind: ComponentKind,
+}
+
+/// A value of a component received from a client, recorded for the components that are tracked by an analyzer
+/// (see [`AppClientBehaviorExt::track_client_values`])
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValueRecord<C> {
+    /// The client tick of the update
+    pub tick: Tick,
+    /// The server tick when the update was received
+    pub received_tick: Tick,
+    pub entity: Entity,
+    pub value: C,
+}
+
+trait Record {
+    fn received_tick(&self) -> Tick;
+}
+
+impl Record for InputRecord {
+    fn received_tick(&self) -> Tick {
+        self.received_tick
+    }
+}
+
+impl Record for UpdateRecord {
+    fn received_tick(&self) -> Tick {
+        self.received_tick
+    }
+}
+
+impl<C> Record for ValueRecord<C> {
+    fn received_tick(&self) -> Tick {
+        self.received_tick
+    }
+}
+
+/// Push a record, and drop the records that are too old or exceed the maximum number of records
+fn push_bounded<R: Record>(records: &mut VecDeque<R>, record: R, config: &BehaviorAnalysisConfig) {
+    let received_tick = record.received_tick();
+    records.push_back(record);
+    while records.len() > config.max_records
+        || records
+            .front()
+            .is_some_and(|r| received_tick - r.received_tick() >= config.history_ticks as i16)
+    {
+        records.pop_front();
+    }
+}
+
+/// The values of a tracked component, with the type erased
+trait ErasedValues: Send + Sync {
+    fn as_any(&self) -> &dyn Any;
+
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+
+    fn memory_usage(&self) -> usize;
+}
+
+impl<C: Send + Sync + 'static> ErasedValues for VecDeque<ValueRecord<C>> {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn memory_usage(&self) -> usize {
+        self.capacity() * std::mem::size_of::<ValueRecord<C>>()
+    }
+}
+
+/// The rolling history of a client, ordered from oldest to newest
+#[derive(Default)]
+pub struct ClientHistory {
+    inputs: VecDeque<InputRecord>,
+    updates: VecDeque<UpdateRecord>,
+    values: HashMap<ComponentKind, Box<dyn ErasedValues>>,
+}
+
+impl ClientHistory {
+    /// The input messages received from the client
+    pub fn inputs(&self) -> impl DoubleEndedIterator<Item = &InputRecord> + ExactSizeIterator {
+        self.inputs.iter()
+    }
+
+    /// The component updates received from the client
+    pub fn updates(&self) -> impl DoubleEndedIterator<Item = &UpdateRecord> + ExactSizeIterator {
+        self.updates.iter()
+    }
+
+    /// The values of the component `C` received from the client.
+    ///
+    /// Empty if the values of the component are not tracked.
+    pub fn values<C: Component>(&self) -> impl DoubleEndedIterator<Item = &ValueRecord<C>> {
+        self.values
+            .get(&ComponentKind::of::<C>())
+            .and_then(|values| values.as_any().downcast_ref::<VecDeque<ValueRecord<C>>>())
+            .into_iter()
+            .flatten()
+    }
+
+    /// Memory used by the records of the client, in bytes
+    pub fn memory_usage(&self) -> usize {
+        self.inputs.capacity() * std::mem::size_of::<InputRecord>()
+            + self.updates.capacity() * std::mem::size_of::<UpdateRecord>()
+            + self
+                .values
+                .values()
+                .map(|values| values.memory_usage())
+                .sum::<usize>()
+    }
+}
+
+/// Rolling history of the input messages and component updates received from each connected client.
+///
+/// It is only added once an analyzer is registered; the history of a client is removed when it disconnects.
+#[derive(Resource, Default)]
+pub struct ClientBehaviorHistory {
+    clients: HashMap<ClientId, ClientHistory>,
+    config: BehaviorAnalysisConfig,
+    /// The server tick at which the packets of the current frame are received
+    pub(crate) tick: Tick,
+}
+
+impl ClientBehaviorHistory {
+    fn new(config: BehaviorAnalysisConfig) -> Self {
+        Self {
+            clients: HashMap::default(),
+            config,
+            tick: Tick(0),
+        }
+    }
+
+    /// Get the history of a given client
+    pub fn get(&self, client_id: ClientId) -> Option<&ClientHistory> {
+        self.clients.get(&client_id)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&ClientId, &ClientHistory)> {
+        self.clients.iter()
+    }
+
+    /// Memory used by the histories of all the clients, in bytes
+    pub fn memory_usage(&self) -> usize {
+        self.clients.values().map(ClientHistory::memory_usage).sum()
+    }
+
+    pub(crate) fn record_input(
+        &mut self,
+        client_id: ClientId,
+        end_tick: Tick,
+        received_tick: Tick,
+    ) {
+        let history = self.clients.entry(client_id).or_default();
+        push_bounded(
+            &mut history.inputs,
+            InputRecord {
+                received_tick,
+                end_tick,
+            },
+            &self.config,
+        );
+    }
+
+    pub(crate) fn record_update(&mut self, client_id: ClientId, record: UpdateRecord) {
+        let history = self.clients.entry(client_id).or_default();
+        push_bounded(&mut history.updates, record, &self.config);
+    }
+
+    fn remove(&mut self, client_id: ClientId) {
+        self.clients.remove(&client_id);
+    }
+}
+
+/// Function that records the value of a tracked component in the history
+pub(crate) type TrackValueFn<C> =
+    for<'a, 'b> fn(&'a mut ClientBehaviorHistory, ClientId, UpdateRecord, &'b C);
+
+/// Record the value of a component received from a client
+pub(crate) fn track_value<C: Component + Clone>(
+    history: &mut ClientBehaviorHistory,
+    client_id: ClientId,
+    record: UpdateRecord,
+    value: &C,
+) {
+    let ClientBehaviorHistory {
+        clients, config, ..
+    } = history;
+    let Some(values) = clients
+        .entry(client_id)
+        .or_default()
+        .values
+        .entry(record.kind)
+        .or_insert_with(|| Box::new(VecDeque::<ValueRecord<C>>::new()))
+        .as_any_mut()
+        .downcast_mut::<VecDeque<ValueRecord<C>>>()
+    else {
+        return;
+    };
+    push_bounded(
+        values,
+        ValueRecord {
+            tick: record.tick,
+            received_tick: record.received_tick,
+            entity: record.entity,
+            value: value.clone(),
+        },
+        config,
+    );
+}
+
+/// System set in `PostUpdate` containing the analyzers. It runs every
+/// [`BehaviorAnalysisConfig::analysis_interval`].
+#[derive(SystemSet, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BehaviorAnalysisSet;
+
+/// Plugin that records the behavior of the clients and runs the analyzers.
+///
+/// It is not part of the [`ServerPlugins`](crate::server::plugin::ServerPlugins): it is added with the default
+/// configuration by [`add_client_behavior_analyzer`](AppClientBehaviorExt::add_client_behavior_analyzer)
+/// if it was not added before.
+#[derive(Debug, Default)]
+pub struct BehaviorAnalysisPlugin {
+    pub config: BehaviorAnalysisConfig,
+}
+
+impl BehaviorAnalysisPlugin {
+    pub fn new(config: BehaviorAnalysisConfig) -> Self {
+        Self { config }
+    }
+}
+
+impl Plugin for BehaviorAnalysisPlugin {
+    fn build(&self, app: &mut App) {
+        // RESOURCES
+        app.insert_resource(self.config);
+        app.insert_resource(ClientBehaviorHistory::new(self.config));
+        // EVENTS
+        app.add_event::<SuspicionEvent>();
+        // SETS
+        app.configure_sets(
+            PostUpdate,
+            BehaviorAnalysisSet
+                .run_if(on_timer(self.config.analysis_interval).and_then(is_started)),
+        );
+        // SYSTEMS
+        app.add_systems(
+            PreUpdate,
+            update_history_tick
+                .before(InternalMainSet::<ServerMarker>::Receive)
+                .run_if(is_started),
+        );
+        // OBSERVERS
+        app.observe(remove_client_history);
+    }
+}
+
+/// Keep track of the current tick, since the [`TickManager`] is not available while the packets are received
+fn update_history_tick(tick_manager: Res<TickManager>, mut history: ResMut<ClientBehaviorHistory>) {
+    history.tick = tick_manager.tick();
+}
+
+/// Remove the history of a client when it disconnects
+fn remove_client_history(
+    trigger: Trigger<DisconnectEvent>,
+    mut history: ResMut<ClientBehaviorHistory>,
+) {
+    history.remove(trigger.event().client_id);
+}
+
+/// Extension trait to analyze the behavior of the clients
+pub trait AppClientBehaviorExt {
+    /// Add a system that analyzes the [`ClientBehaviorHistory`] and sends [`SuspicionEvent`]s.
+    ///
+    /// See [`behavior`](crate::server::behavior) for more details.
+    fn add_client_behavior_analyzer<M>(&mut self, analyzer: impl IntoSystemConfigs<M>)
+        -> &mut Self;
+
+    /// Record the values of the component `C` received from the clients, so that they can be read
+    /// with [`ClientHistory::values`]
+    fn track_client_values<C: Component + Clone>(&mut self) -> &mut Self;
+
+    /// Add an analyzer that suspects the clients that move an entity by more than `max_per_tick` per tick.
+    ///
+    /// The displacement between two values of the component `C` is computed with `distance`.
+    fn add_max_displacement_analyzer<C: Component + Clone>(
+        &mut self,
+        max_per_tick: f32,
+        distance: fn(&C, &C) -> f32,
+    ) -> &mut Self;
+
+    /// Add an analyzer that suspects the clients that send inputs for more ticks than the ticks that elapsed
+    /// on the server (for example because they run their clock faster), with a tolerance of `tolerance_ticks`.
+    fn add_input_rate_analyzer(&mut self, tolerance_ticks: u16) -> &mut Self;
+}
+
+impl AppClientBehaviorExt for App {
+    fn add_client_behavior_analyzer<M>(
+        &mut self,
+        analyzer: impl IntoSystemConfigs<M>,
+    ) -> &mut Self {
+        // the analyzers only run on the server
+        if self.world().get_resource::<ServerConfig>().is_none() {
+            return self;
+        }
+        if !self.is_plugin_added::<BehaviorAnalysisPlugin>() {
+            self.add_plugins(BehaviorAnalysisPlugin::default());
+        }
+        self.add_systems(PostUpdate, analyzer.in_set(BehaviorAnalysisSet))
+    }
+
+    fn track_client_values<C: Component + Clone>(&mut self) -> &mut Self {
+        if self.world().get_resource::<ServerConfig>().is_none() {
+            return self;
+        }
+        self.world_mut()
+            .resource_mut::<ComponentRegistry>()
+            .set_client_value_tracking::<C>();
+        self
+    }
+
+    fn add_max_displacement_analyzer<C: Component + Clone>(
+        &mut self,
+        max_per_tick: f32,
+        distance: fn(&C, &C) -> f32,
+    ) -> &mut Self {
+        self.insert_resource(MaxDisplacementRule::<C> {
+            max_per_tick,
+            distance,
+        });
+        self.track_client_values::<C>()
+            .add_client_behavior_analyzer(max_displacement_analyzer::<C>)
+    }
+
+    fn add_input_rate_analyzer(&mut self, tolerance_ticks: u16) -> &mut Self {
+        self.insert_resource(InputRateRule { tolerance_ticks });
+        self.add_client_behavior_analyzer(input_rate_analyzer)
+    }
+}
+
+/// Bound of the [`max_displacement_analyzer`] for the component `C`
+#[derive(Resource)]
+struct MaxDisplacementRule<C> {
+    max_per_tick: f32,
+    distance: fn(&C, &C) -> f32,
+}
+
+/// Suspect the clients that moved an entity by more than the maximum displacement per tick, between two
+/// consecutive values of the component `C`.
+///
+/// Every analysis covers the whole history: a client stays suspicious as long as the suspicious values are in it.
+fn max_displacement_analyzer<C: Component>(
+    rule: Res<MaxDisplacementRule<C>>,
+    history: Res<ClientBehaviorHistory>,
+    mut events: EventWriter<SuspicionEvent>,
+) {
+    for (client_id, client_history) in history.iter() {
+        let mut previous = HashMap::<Entity, &ValueRecord<C>>::default();
+        let mut score = 0.0_f32;
+        for record in client_history.values::<C>() {
+            let Some(previous) = previous.insert(record.entity, record) else {
+                continue;
+            };
+            let ticks = record.tick - previous.tick;
+            // duplicated or reordered update
+            if ticks <= 0 {
+                continue;
+            }
+            let displacement = (rule.distance)(&previous.value, &record.value) / ticks as f32;
+            score = score.max(displacement / rule.max_per_tick);
+        }
+        if score > 1.0 {
+            events.send(SuspicionEvent {
+                client_id: *client_id,
+                rule: "max_displacement",
+                score,
+            });
+        }
+    }
+}
+
+/// Tolerance of the [`input_rate_analyzer`]
+#[derive(Resource)]
+struct InputRateRule {
+    tolerance_ticks: u16,
+}
+
+/// Suspect the clients whose input messages covered more ticks than the ticks that elapsed on the server
+/// between the first and the last input message of the history
+fn input_rate_analyzer(
+    rule: Res<InputRateRule>,
+    history: Res<ClientBehaviorHistory>,
+    mut events: EventWriter<SuspicionEvent>,
+) {
+    for (client_id, client_history) in history.iter() {
+        let (Some(first), Some(last)) = (
+            client_history.inputs().next(),
+            client_history.inputs().next_back(),
+        ) else {
+            continue;
+        };
+        let input_ticks = (last.end_tick - first.end_tick) as f32;
+        let elapsed_ticks = (last.received_tick - first.received_tick).max(0) as f32;
+        let bound = (elapsed_ticks + rule.tolerance_ticks as f32).max(1.0);
+        if input_ticks > bound {
+            events.send(SuspicionEvent {
+                client_id: *client_id,
+                rule: "input_rate",
+                score: input_ticks / bound,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+
+    #[derive(Component, Clone, Debug, PartialEq)]
+    struct Position(f32);
+
+    const CLIENT: ClientId = ClientId::Netcode(1);
+
+    fn config() -> BehaviorAnalysisConfig {
+        BehaviorAnalysisConfig::default()
+            .with_history_ticks(100)
+            .with_max_records(8)
+    }
+
+    fn analyze<M>(
+        history: ClientBehaviorHistory,
+        analyzer: impl IntoSystem<(), (), M>,
+    ) -> Vec<SuspicionEvent> {
+        let mut world = World::new();
+        world.init_resource::<Events<SuspicionEvent>>();
+        world.insert_resource(history);
+        world.insert_resource(InputRateRule { tolerance_ticks: 5 });
+        world.insert_resource(MaxDisplacementRule::<Position> {
+            max_per_tick: 2.0,
+            distance: |a, b| (a.0 - b.0).abs(),
+        });
+        world.run_system_once(analyzer);
+        world
+            .resource_mut::<Events<SuspicionEvent>>()
+            .drain()
+            .collect()
+    }
+
+    /// History of the positions of entities: (client tick, entity, x)
+    fn positions(values: &[(u16, Entity, f32)]) -> ClientBehaviorHistory {
+        let mut history = ClientBehaviorHistory::new(config());
+        for (tick, entity, x) in values {
+            let record = UpdateRecord {
+                tick: Tick(*tick),
+                received_tick: Tick(*tick + 3),
+                entity: *entity,
+                kind: ComponentKind::of::<Position>(),
+            };
+            history.record_update(CLIENT, record);
+            track_value(&mut history, CLIENT, record, &Position(*x));
+        }
+        history
+    }
+
+    #[test]
+    fn test_history_is_bounded() {
+        let mut history = ClientBehaviorHistory::new(config());
+        for tick in 0..20 {
+            history.record_input(CLIENT, Tick(tick), Tick(tick));
+        }
+        // bounded by the number of records
+        let client_history = history.get(CLIENT).unwrap();
+        assert_eq!(client_history.inputs().len(), 8);
+        assert_eq!(client_history.inputs().next().unwrap().end_tick, Tick(12));
+
+        // bounded by the number of ticks
+        history.record_input(CLIENT, Tick(20), Tick(200));
+        assert_eq!(history.get(CLIENT).unwrap().inputs().len(), 1);
+        assert!(history.memory_usage() > 0);
+
+        history.remove(CLIENT);
+        assert!(history.get(CLIENT).is_none());
+        assert_eq!(history.memory_usage(), 0);
+    }
+
+    #[test]
+    fn test_max_displacement() {
+        let entity = Entity::from_raw(1);
+        let other = Entity::from_raw(2);
+        let mut values = vec![
+            (0, entity, 0.0),
+            (0, other, 100.0),
+            // 2 units per tick
+            (5, entity, 10.0),
+            // the displacement is computed per entity
+            (6, other, 99.0),
+        ];
+        let history = positions(&values);
+        assert_eq!(history.get(CLIENT).unwrap().updates().len(), 4);
+        assert_eq!(history.get(CLIENT).unwrap().values::<Position>().count(), 4);
+        assert!(analyze(history, max_displacement_analyzer::<Position>).is_empty());
+
+        // teleport
+        values.push((6, entity, 18.0));
+        assert_eq!(
+            analyze(positions(&values), max_displacement_analyzer::<Position>),
+            vec![SuspicionEvent {
+                client_id: CLIENT,
+                rule: "max_displacement",
+                score: 4.0,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_input_rate() {
+        // the client sends the inputs of 2 ticks every tick, but the first inputs are within the tolerance
+        let mut history = ClientBehaviorHistory::new(config().with_max_records(100));
+        for tick in 0..5 {
+            history.record_input(CLIENT, Tick(10 + 2 * tick), Tick(tick));
+        }
+        assert!(analyze(history, input_rate_analyzer).is_empty());
+
+        let mut history = ClientBehaviorHistory::new(config().with_max_records(100));
+        for tick in 0..21 {
+            history.record_input(CLIENT, Tick(10 + 2 * tick), Tick(tick));
+        }
+        // 40 ticks of inputs in 20 ticks, with a tolerance of 5 ticks
+        assert_eq!(
+            analyze(history, input_rate_analyzer),
+            vec![SuspicionEvent {
+                client_id: CLIENT,
+                rule: "input_rate",
+                score: 1.6,
+            }]
+        );
+    }
+}
@@ -0,0 +1,94 @@
+/*! Channel settings that are different for some clients
+
+All the connections use the [`ChannelSettings`](crate::channel::builder::ChannelSettings) of the protocol, but
+the server can change how the messages of a channel are scheduled for a single client with a
+[`ChannelSettingsOverride`], for example to resend the reliable messages less aggressively to mobile clients:
+
+```rust,ignore
+fn on_mobile_detected(trigger: Trigger<MobileClient>, mut manager: ResMut<ConnectionManager>) {
+    manager
+        .set_channel_settings_override(
+            trigger.event().client_id,
+            ChannelKind::of::<MyReliableChannel>(),
+            ChannelSettingsOverride {
+                rtt_resend_factor: Some(3.0),
+                max_in_flight: Some(16),
+                ..default()
+            },
+        )
+        .unwrap();
+}
+```
+
+To apply the overrides as soon as a client connects, before any message is sent to it, use
+[`set_channel_settings_override_hook`](AppChannelSettingsExt::set_channel_settings_override_hook).
+
+The overrides cannot change the [`ChannelMode`](crate::channel::builder::ChannelMode) of a channel, so the client
+does not need to know about them. They can be inspected with
+[`ConnectionManager::channel_settings_override`].
+*/
+use bevy::prelude::*;
+use tracing::error;
+
+use crate::channel::builder::ChannelSettingsOverride;
+use crate::prelude::server::ServerConfig;
+use crate::prelude::{ChannelKind, ClientId};
+use crate::server::connection::ConnectionManager;
+use crate::server::events::ConnectEvent;
+
+/// Function that returns the channel settings overrides of a client that just connected
+pub type ChannelSettingsOverrideFn =
+    Box<dyn Fn(ClientId) -> Vec<(ChannelKind, ChannelSettingsOverride)> + Send + Sync + 'static>;
+
+#[derive(Resource)]
+struct ChannelSettingsOverrideHook(ChannelSettingsOverrideFn);
+
+/// Extension trait to change the channel settings of some clients
+pub trait AppChannelSettingsExt {
+    /// Set the function that returns the channel settings overrides of each client, when it connects.
+    ///
+    /// The overrides are applied before any message is sent to the client.
+    fn set_channel_settings_override_hook(
+        &mut self,
+        hook: impl Fn(ClientId) -> Vec<(ChannelKind, ChannelSettingsOverride)> + Send + Sync + 'static,
+    );
+}
+
+impl AppChannelSettingsExt for App {
+    fn set_channel_settings_override_hook(
+        &mut self,
+        hook: impl Fn(ClientId) -> Vec<(ChannelKind, ChannelSettingsOverride)> + Send + Sync + 'static,
+    ) {
+        // the overrides are only applied on the server
+        if self.world().get_resource::<ServerConfig>().is_none() {
+            return;
+        }
+        if !self
+            .world()
+            .contains_resource::<ChannelSettingsOverrideHook>()
+        {
+            self.observe(apply_channel_settings_override_hook);
+        }
+        self.insert_resource(ChannelSettingsOverrideHook(Box::new(hook)));
+    }
+}
+
+/// Apply the overrides returned by the hook to the client that connected
+fn apply_channel_settings_override_hook(
+    trigger: Trigger<ConnectEvent>,
+    hook: Res<ChannelSettingsOverrideHook>,
+    mut manager: ResMut<ConnectionManager>,
+) {
+    let client_id = trigger.event().client_id;
+    for (kind, settings_override) in (hook.0)(client_id) {
+        let _ = manager
+            .set_channel_settings_override(client_id, kind, settings_override)
+            .inspect_err(|e| {
+                error!(
+                    ?client_id,
+                    ?kind,
+                    "Could not override the channel settings: {e:?}"
+                )
+            });
+    }
+}
@@ -27,8 +27,10 @@ pub(crate) struct ClientsMetadataPlugin;
 
 mod systems {
     use super::*;
-    use crate::prelude::server::ControlledBy;
+    use crate::prelude::server::{ControlledBy, ServerConfig, SyncTarget};
+    use crate::prelude::{ClientId, NetworkTarget, ReplicationTarget};
     use crate::server::clients::ControlledEntities;
+    use crate::server::config::KickPolicy;
     use crate::server::connection::ConnectionManager;
     use crate::server::events::DisconnectEvent;
     use tracing::{debug, trace};
@@ -102,15 +104,26 @@ mod systems {
     }
 
     /// When a client disconnects, we despawn all the entities it controlled if the lifetime
-    /// is SesssionBased
+    /// is SesssionBased.
+    ///
+    /// If the client was kicked with [`ConnectionManager::disconnect_client`], the entities are handled
+    /// according to the [`KickPolicy`] instead, and the client is removed from the replication targets.
     pub(super) fn handle_client_disconnect(
         trigger: Trigger<DisconnectEvent>,
         mut commands: Commands,
         client_query: Query<&ControlledEntities>,
+        mut connection_manager: ResMut<ConnectionManager>,
+        config: Res<ServerConfig>,
+        mut controlled_by_query: Query<&mut ControlledBy>,
+        mut target_query: Query<(&mut ReplicationTarget, Option<&mut SyncTarget>)>,
     ) {
         // TODO: should directly we use the client entity as the trigger entity?
         let client_entity = trigger.event().entity;
         let client_id = trigger.event().client_id;
+        let kick_policy = connection_manager
+            .kicked_clients
+            .remove(&client_id)
+            .then_some(config.kick_policy);
         // despawn all the controlled entities for the disconnected client
         if let Ok(controlled_entities) = client_query.get(client_entity) {
             debug!(
@@ -118,7 +131,12 @@ mod systems {
                 client_id
             );
             for (entity, lifetime) in controlled_entities.iter() {
-                if lifetime == &Lifetime::SessionBased {
+                let despawn = match kick_policy {
+                    Some(KickPolicy::DespawnOwned) => true,
+                    Some(KickPolicy::KeepOwned) => false,
+                    None => lifetime == &Lifetime::SessionBased,
+                };
+                if despawn {
                     trace!(
                         "Despawning entity {entity:?} controlled by disconnected client {:?}",
                         client_id
@@ -126,6 +144,32 @@ mod systems {
                     if let Some(command) = commands.get_entity(*entity) {
                         command.despawn_recursive();
                     }
+                } else if kick_policy.is_some() {
+                    // release the ownership of the entity
+                    if let Ok(mut controlled_by) = controlled_by_query.get_mut(*entity) {
+                        if lists_client(&controlled_by.target, client_id) {
+                            controlled_by
+                                .target
+                                .exclude(&NetworkTarget::Single(client_id));
+                        }
+                    }
+                }
+            }
+        }
+        if kick_policy.is_some() {
+            let kicked = NetworkTarget::Single(client_id);
+            for (mut replication_target, sync_target) in target_query.iter_mut() {
+                if lists_client(&replication_target.target, client_id) {
+                    replication_target.target.exclude(&kicked);
+                }
+                let Some(mut sync_target) = sync_target else {
+                    continue;
+                };
+                if lists_client(&sync_target.prediction, client_id) {
+                    sync_target.prediction.exclude(&kicked);
+                }
+                if lists_client(&sync_target.interpolation, client_id) {
+                    sync_target.interpolation.exclude(&kicked);
                 }
             }
         }
@@ -134,6 +178,17 @@ mod systems {
             command.despawn_recursive();
         };
     }
+
+    /// Returns true if the target lists the client explicitly. The kicked clients are only removed from these
+    /// targets: the other targets (for example [`NetworkTarget::All`]) are kept, so that the client is targeted
+    /// again if it reconnects
+    fn lists_client(target: &NetworkTarget, client_id: ClientId) -> bool {
+        match target {
+            NetworkTarget::Single(id) => *id == client_id,
+            NetworkTarget::Only(ids) => ids.contains(&client_id),
+            _ => false,
+        }
+    }
 }
 
 impl Plugin for ClientsMetadataPlugin {
@@ -293,6 +348,7 @@ mod tests {
                 controlled_by: ControlledBy {
                     target: NetworkTarget::All,
                     lifetime: Lifetime::Persistent,
+                    ..default()
                 },
                 ..default()
             })
@@ -1,16 +1,81 @@
 //! Defines server-specific configuration options
 use bevy::prelude::Resource;
+use bevy::utils::Duration;
 use governor::Quota;
 use nonzero_ext::nonzero;
 use std::sync::Arc;
 
 use crate::connection::netcode::{Key, PRIVATE_KEY_BYTES};
 use crate::connection::server::{
-    ConnectionRequestHandler, DefaultConnectionRequestHandler, NetConfig,
+    ConnectionRequestHandler, DefaultConnectionRequestHandler, DenyList, NetConfig,
 };
+use crate::packet::receive_queue::ReceiveQueueConfig;
 use crate::prelude::ReplicationConfig;
 use crate::shared::config::SharedConfig;
+use crate::shared::input::jitter::InputJitterConfig;
+use crate::shared::network_time::NetworkTimeConfig;
 use crate::shared::ping::manager::PingConfig;
+use crate::shared::request::RequestConfig;
+use crate::shared::serialized_size::SerializedSizeConfig;
+
+/// What the server does when a client sends a connection request with the [`ClientId`](crate::prelude::ClientId)
+/// of a client that is still connected, for example because the player's previous client crashed and
+/// its session did not time out yet.
+///
+/// The outcome is reported with a [`DuplicateClientIdEvent`](crate::server::events::DuplicateClientIdEvent).
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum DuplicateIdPolicy {
+    /// The new connection is denied with
+    /// [`DeniedReason::AlreadyConnected`](crate::connection::server::DeniedReason::AlreadyConnected)
+    #[default]
+    RejectNew,
+    /// The previous session is disconnected immediately, and the new connection is accepted.
+    ///
+    /// The entities of the previous session are handled like on any disconnection
+    /// (see [`Lifetime`](crate::prelude::server::Lifetime)).
+    DisconnectOld,
+    /// The previous session is disconnected only if the server did not receive any packet from it
+    /// for `idle_threshold`. Otherwise the new connection is denied like with [`DuplicateIdPolicy::RejectNew`].
+    ReplaceIfStale { idle_threshold: Duration },
+}
+
+/// Session that was kept after applying the [`DuplicateIdPolicy`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DuplicateSession {
+    /// The previous session stays connected, the new connection was denied
+    Old,
+    /// The previous session was disconnected, the new connection goes on with the handshake
+    New,
+}
+
+/// Input applied by the server on the ticks where the input of a client is missing, because it was lost
+/// or arrived too late.
+///
+/// The [`InputEvent`](crate::prelude::server::InputEvent)s emitted for these ticks are marked as predicted.
+/// If the real input arrives later, a [`LateInputEvent`](crate::prelude::server::LateInputEvent) is emitted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MissingInputPolicy {
+    /// The last input received from the client is applied again
+    #[default]
+    RepeatLast,
+    /// No input is applied
+    None,
+}
+
+/// What happens to the entities controlled by a client that is kicked with
+/// [`ConnectionManager::disconnect_client`](crate::server::connection::ConnectionManager::disconnect_client)
+/// or [`ConnectionManager::ban_client`](crate::server::connection::ConnectionManager::ban_client).
+///
+/// On the other disconnections, the entities are handled according to their [`Lifetime`](crate::prelude::server::Lifetime).
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum KickPolicy {
+    /// All the entities controlled by the client are despawned, even the ones with a
+    /// [`Lifetime::Persistent`](crate::prelude::server::Lifetime::Persistent)
+    #[default]
+    DespawnOwned,
+    /// The entities are kept, and the client is removed from their [`ControlledBy`](crate::prelude::server::ControlledBy)
+    KeepOwned,
+}
 
 #[derive(Debug, Clone)]
 pub struct NetcodeConfig {
@@ -24,6 +89,8 @@ pub struct NetcodeConfig {
     pub private_key: Key,
     /// A closure that will be used to accept or reject incoming connections
     pub connection_request_handler: Arc<dyn ConnectionRequestHandler>,
+    /// What to do when a client connects with the id of a client that is still connected
+    pub duplicate_id_policy: DuplicateIdPolicy,
 }
 
 impl Default for NetcodeConfig {
@@ -35,6 +102,7 @@ impl Default for NetcodeConfig {
             protocol_id: 0,
             private_key: [0; PRIVATE_KEY_BYTES],
             connection_request_handler: Arc::new(DefaultConnectionRequestHandler),
+            duplicate_id_policy: DuplicateIdPolicy::default(),
         }
     }
 }
@@ -53,6 +121,11 @@ impl NetcodeConfig {
         self.client_timeout_secs = client_timeout_secs;
         self
     }
+
+    pub fn with_duplicate_id_policy(mut self, policy: DuplicateIdPolicy) -> Self {
+        self.duplicate_id_policy = policy;
+        self
+    }
 }
 
 /// Configuration related to sending packets
@@ -67,6 +140,17 @@ pub struct PacketConfig {
     pub per_client_send_bandwidth_cap: Quota,
     /// If false, there is no bandwidth cap and all messages are sent as soon as possible
     pub bandwidth_cap_enabled: bool,
+    /// How long we wait for a missing unreliable message of a
+    /// [`SendGroup`](crate::shared::message_group::SendGroup) before delivering the next messages of the group
+    pub message_group_timeout: Duration,
+    /// Minimum interval between two logs of the same [`NetworkWarning`](crate::shared::warnings::NetworkWarning)
+    /// category; the warnings in-between are only counted
+    pub warning_log_interval: Duration,
+    /// Bounds of the queue of the packets received from each client that are waiting to be processed
+    /// by the channels
+    pub receive_queue: ReceiveQueueConfig,
+    /// Tracking of the serialized size of the messages and components that are sent
+    pub serialized_size: SerializedSizeConfig,
 }
 
 impl Default for PacketConfig {
@@ -76,6 +160,10 @@ impl Default for PacketConfig {
             // 56 KB/s bandwidth cap
             per_client_send_bandwidth_cap: Quota::per_second(nonzero!(56000u32)),
             bandwidth_cap_enabled: false,
+            message_group_timeout: Duration::from_millis(200),
+            warning_log_interval: Duration::from_secs(5),
+            receive_queue: ReceiveQueueConfig::default(),
+            serialized_size: SerializedSizeConfig::default(),
         }
     }
 }
@@ -113,6 +201,17 @@ pub struct ServerConfig {
     pub packet: PacketConfig,
     pub replication: ReplicationConfig,
     pub ping: PingConfig,
+    pub network_time: NetworkTimeConfig,
+    /// See [`jitter`](crate::shared::input::jitter) for more details.
+    pub input_jitter: InputJitterConfig,
+    /// How the missing inputs of the clients are predicted
+    pub missing_input: MissingInputPolicy,
+    /// Clients that are not allowed to connect. The list is shared with the running server, so clients can be
+    /// banned at any time with [`ConnectionManager::ban_client`](crate::server::connection::ConnectionManager::ban_client)
+    pub deny_list: DenyList,
+    pub kick_policy: KickPolicy,
+    /// Timeout of the requests sent to the clients
+    pub request: RequestConfig,
 }
 
 #[cfg(test)]
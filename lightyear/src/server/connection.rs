@@ -3,24 +3,33 @@ use bevy::ecs::component::Tick as BevyTick;
 use bevy::ecs::entity::{EntityHash, MapEntities};
 use bevy::prelude::{Component, Entity, Mut, Resource, World};
 use bevy::ptr::Ptr;
-use bevy::utils::{Duration, HashMap};
+use bevy::utils::{Duration, HashMap, HashSet};
 use bytes::Bytes;
 use hashbrown::hash_map::Entry;
+use std::io::Write;
 use tracing::{debug, info, info_span, trace, trace_span};
 #[cfg(feature = "trace")]
 use tracing::{instrument, Level};
 
 use crate::channel::builder::{
-    EntityActionsChannel, EntityUpdatesChannel, PingChannel, PongChannel,
+    ChannelOccupancy, ChannelSettingsOverride, EntityActionsChannel, EntityAliasChannel,
+    EntityUpdatesChannel, PingChannel, PongChannel, RequestChannel,
 };
 
+use crate::channel::flow_control::FlowControlStats;
 use crate::channel::receivers::ChannelReceive;
-use crate::channel::senders::ChannelSend;
+use crate::channel::senders::{ChannelSend, DedupKey};
+use crate::channel::stats::ChannelStats;
+use crate::channel::transfer::{TransferHandle, TransferProgress};
 use crate::client::message::ClientMessage;
 use crate::connection::id::ClientId;
 use crate::connection::netcode::MAX_PACKET_SIZE;
+use crate::connection::server::{DenyList, DisconnectReason};
+use crate::packet::message::MessageId;
 use crate::packet::message_manager::MessageManager;
 use crate::packet::packet_builder::{Payload, RecvPayload};
+use crate::packet::priority_manager::PriorityConfig;
+use crate::packet::receive_queue::{ReceiveQueue, ReceiveQueueStats};
 use crate::prelude::server::{DisconnectEvent, RoomId, RoomManager};
 use crate::prelude::{
     Channel, ChannelKind, Message, PreSpawnedPlayerObject, ReplicationConfig, ReplicationGroup,
@@ -30,7 +39,7 @@ use crate::protocol::channel::ChannelRegistry;
 use crate::protocol::component::{
     ComponentError, ComponentKind, ComponentNetId, ComponentRegistry,
 };
-use crate::protocol::message::{MessageError, MessageRegistry, MessageType};
+use crate::protocol::message::{MessageError, MessageKind, MessageRegistry, MessageType};
 use crate::protocol::registry::NetId;
 use crate::serialize::reader::Reader;
 use crate::serialize::writer::Writer;
@@ -39,22 +48,41 @@ use crate::server::config::PacketConfig;
 use crate::server::error::ServerError;
 use crate::server::events::{ConnectEvent, ServerEvents};
 use crate::server::relevance::error::RelevanceError;
+use crate::shared::action::{ActionId, ActionVerdict, PendingActionResolutions};
+use crate::shared::clock::NetworkClock;
+use crate::shared::connection_stats::{ConnectionStats, ConnectionStatsTracker};
 use crate::shared::events::connection::ConnectionEvents;
+use crate::shared::input::jitter::{InputMarginStats, InputMargins};
 use crate::shared::message::MessageSend;
+use crate::shared::message_group::{
+    MessageGroupHeader, MessageGroupReceiver, MessageGroupSender, SendGroup,
+};
 use crate::shared::ping::manager::{PingConfig, PingManager};
 use crate::shared::ping::message::{Ping, Pong};
+use crate::shared::replication::alias::AliasMessage;
+use crate::shared::replication::classes::ReplicationClassStats;
+use crate::shared::replication::coalesce::UpdateMessageCache;
 use crate::shared::replication::components::ReplicationGroupId;
 use crate::shared::replication::delta::DeltaManager;
-use crate::shared::replication::entity_map::EntityMap;
+use crate::shared::replication::entity_map::{EntityMap, RemoteEntityMap};
+use crate::shared::replication::keyframe::KeyframeBudget;
+use crate::shared::replication::limits::ReplicationLimitStats;
 use crate::shared::replication::network_target::NetworkTarget;
 use crate::shared::replication::receive::ReplicationReceiver;
 use crate::shared::replication::send::ReplicationSender;
 use crate::shared::replication::{EntityActionsMessage, EntityUpdatesMessage, ReplicationPeer};
 use crate::shared::replication::{ReplicationReceive, ReplicationSend};
+use crate::shared::request::{PendingRequests, Request, RequestHandle, RequestMessage};
+use crate::shared::schema::NegotiatedSchemas;
+use crate::shared::serialized_size::SerializedSizes;
+use crate::shared::session_summary::{SessionStats, SessionSummary};
 use crate::shared::sets::ServerMarker;
 use crate::shared::tick_manager::Tick;
 use crate::shared::tick_manager::TickManager;
 use crate::shared::time_manager::TimeManager;
+use crate::shared::timeline_message::{TimelineMessageHeader, VisualTimeline};
+use crate::shared::warnings::{NetworkWarning, NetworkWarnings};
+use crate::transport::middleware::conditioner::{LinkConditioner, LinkConditionerConfig};
 
 type EntityHashMap<K, V> = hashbrown::HashMap<K, V, EntityHash>;
 
@@ -70,11 +98,30 @@ pub struct ConnectionManager {
     // (we want to keep track of them because we need to replicate the entire world state to them)
     pub(crate) new_clients: Vec<ClientId>,
     pub(crate) writer: Writer,
+    /// Id of the next [`SendGroup`] created by the server
+    next_send_group: u16,
+    /// Verdicts given with [`acknowledge_action`](Self::acknowledge_action) that have not been sent yet
+    pub(crate) action_resolutions: PendingActionResolutions,
+    /// Requests sent to the clients that are waiting for their response
+    pub(crate) requests: PendingRequests,
+    /// Update messages serialized during the current replication send, shared between the connections
+    update_message_cache: UpdateMessageCache,
+    /// Clients to disconnect during the next receive, with the reason sent to them
+    pub(crate) pending_kicks: Vec<(ClientId, Option<String>)>,
+    /// Clients that were kicked, whose entities are handled with the [`KickPolicy`](crate::server::config::KickPolicy)
+    /// when they disconnect
+    pub(crate) kicked_clients: HashSet<ClientId>,
+    deny_list: DenyList,
+    /// Recoverable errors encountered while preparing the messages shared by all the clients
+    pub(crate) warnings: NetworkWarnings,
+    /// Serialized size of the messages and components sent to the clients
+    pub(crate) serialized_sizes: SerializedSizes,
 
     // CONFIG
     replication_config: ReplicationConfig,
     packet_config: PacketConfig,
     ping_config: PingConfig,
+    clock: NetworkClock,
 }
 
 // This is useful in cases where we need to temporarily store a fake ConnectionManager
@@ -86,6 +133,8 @@ impl Default for ConnectionManager {
             ReplicationConfig::default(),
             PacketConfig::default(),
             PingConfig::default(),
+            NetworkClock::default(),
+            DenyList::default(),
         )
     }
 }
@@ -97,6 +146,8 @@ impl ConnectionManager {
         replication_config: ReplicationConfig,
         packet_config: PacketConfig,
         ping_config: PingConfig,
+        clock: NetworkClock,
+        deny_list: DenyList,
     ) -> Self {
         Self {
             connections: HashMap::default(),
@@ -106,9 +157,20 @@ impl ConnectionManager {
             delta_manager: DeltaManager::default(),
             new_clients: vec![],
             writer: Writer::with_capacity(MAX_PACKET_SIZE),
+            next_send_group: 0,
+            action_resolutions: PendingActionResolutions::default(),
+            requests: PendingRequests::default(),
+            update_message_cache: UpdateMessageCache::default(),
+            pending_kicks: vec![],
+            kicked_clients: HashSet::default(),
+            deny_list,
+            warnings: NetworkWarnings::new(packet_config.warning_log_interval)
+                .with_clock(clock.clone()),
+            serialized_sizes: SerializedSizes::new(packet_config.serialized_size),
             replication_config,
             packet_config,
             ping_config,
+            clock,
         }
     }
 
@@ -117,6 +179,75 @@ impl ConnectionManager {
         self.connection(client_id).map(|c| c.entity)
     }
 
+    /// Resolve an action of the client, identified by the [`ActionId`] that the client sent
+    /// along with the action (see [`action`](crate::shared::action)).
+    ///
+    /// The verdict is sent to the client along with the current tick, which should be the tick at which
+    /// the action was processed.
+    pub fn acknowledge_action(
+        &mut self,
+        client_id: ClientId,
+        action_id: ActionId,
+        verdict: ActionVerdict,
+    ) -> Result<(), ServerError> {
+        self.connection(client_id)?;
+        self.action_resolutions.push(client_id, action_id, verdict);
+        Ok(())
+    }
+
+    /// Send a [`Request`] to a client (see [`request`](crate::shared::request)).
+    ///
+    /// The returned [`RequestHandle`] receives the response of the client, or fails if the client did not respond
+    /// before the [`RequestConfig::timeout`](crate::shared::request::RequestConfig::timeout) or disconnected.
+    pub fn send_request<R: Request>(
+        &mut self,
+        client_id: ClientId,
+        request: R,
+    ) -> Result<RequestHandle<R::Response>, ServerError> {
+        self.connection(client_id)?;
+        let id = self.requests.next_id();
+        self.send_message_to_target::<RequestChannel, _>(
+            &RequestMessage { id, request },
+            NetworkTarget::Single(client_id),
+        )?;
+        Ok(self.requests.insert(id, Some(client_id)))
+    }
+
+    /// Disconnect a client, and send it the reason of the disconnection if any (for example "banned").
+    ///
+    /// The client is disconnected at the start of the next frame: its connection is removed and a
+    /// [`DisconnectEvent`] is emitted. The client is removed from the [`ReplicationTarget`](crate::prelude::ReplicationTarget)s
+    /// that list it explicitly, and the entities that it controlled are despawned or kept according to the
+    /// [`KickPolicy`](crate::server::config::KickPolicy) of the [`ServerConfig`](crate::prelude::server::ServerConfig).
+    pub fn disconnect_client(
+        &mut self,
+        client_id: ClientId,
+        reason: Option<String>,
+    ) -> Result<(), ServerError> {
+        self.connection(client_id)?;
+        self.pending_kicks.push((client_id, reason));
+        Ok(())
+    }
+
+    /// Add a client to the [`DenyList`] so that its connection requests are denied, and disconnect it
+    /// (see [`disconnect_client`](Self::disconnect_client)) if it is connected
+    pub fn ban_client(&mut self, client_id: ClientId, reason: Option<String>) {
+        self.deny_list.insert(client_id);
+        if self.connections.contains_key(&client_id) {
+            self.pending_kicks.push((client_id, reason));
+        }
+    }
+
+    /// Allow a banned client to connect again. Returns false if the client was not banned
+    pub fn unban_client(&mut self, client_id: ClientId) -> bool {
+        self.deny_list.remove(client_id)
+    }
+
+    /// Clients that are not allowed to connect to the server
+    pub fn deny_list(&self) -> &DenyList {
+        &self.deny_list
+    }
+
     /// Return the list of connected [`ClientId`]s
     pub fn connected_clients(&self) -> impl Iterator<Item = ClientId> + '_ {
         self.connections.keys().copied()
@@ -162,12 +293,192 @@ impl ConnectionManager {
     }
 
     /// Queues up a message to be sent to a client
+    ///
+    /// Returns the [`MessageId`] of the message on the channel, if it has one. On the channels that
+    /// track acks (reliable channels and [`ChannelMode::UnorderedUnreliableWithAcks`](crate::prelude::ChannelMode::UnorderedUnreliableWithAcks)),
+    /// a [`MessageAckEvent`](crate::server::events::MessageAckEvent) with this id is emitted when the client acks the message.
+    /// Nothing is sent if the client is not connected.
     pub fn send_message<C: Channel, M: Message>(
         &mut self,
         client_id: ClientId,
         message: &M,
+    ) -> Result<Option<MessageId>, ServerError> {
+        if !self.connections.contains_key(&client_id) {
+            return Ok(None);
+        }
+        self.serialize_message(message)?;
+        let message_bytes = self.writer.split();
+        let connection = self.connections.get_mut(&client_id).unwrap();
+        // for local clients, we don't want to buffer messages in the MessageManager since
+        // there is no io
+        if connection.is_local_client() {
+            connection.local_messages_to_send.push(message_bytes);
+            return Ok(None);
+        }
+        connection.buffer_message(message_bytes, ChannelKind::of::<C>())
+    }
+
+    /// Queues up a message to be sent to all clients matching the specific [`NetworkTarget`], replacing
+    /// the message of the same type that was previously sent with the same `key` if it has not been transmitted yet.
+    ///
+    /// This is useful to send the latest version of some state on a reliable channel: during bursts, only
+    /// the newest version is actually sent. The messages that are already in flight are not affected.
+    /// The replacements are counted in [`ChannelStats::messages_replaced`].
+    pub fn send_message_to_target_with_key<C: Channel, M: Message>(
+        &mut self,
+        message: &M,
+        key: u64,
+        target: NetworkTarget,
+    ) -> Result<(), ServerError> {
+        let channel_kind = ChannelKind::of::<C>();
+        let key = DedupKey::new::<M>(key);
+        self.serialize_message(message)?;
+        let message_bytes = self.writer.split();
+        self.connections
+            .iter_mut()
+            .filter(|(id, _)| target.targets(id))
+            .try_for_each(|(_, c)| {
+                // messages to the local client are received immediately, so there is nothing to replace
+                if c.is_local_client() {
+                    c.local_messages_to_send.push(message_bytes.clone());
+                    return Ok(());
+                }
+                c.buffer_message_with_key(message_bytes.clone(), channel_kind, key)?;
+                Ok(())
+            })
+    }
+
+    /// Queues up a message to be sent to a client, replacing the message of the same type that was
+    /// previously sent with the same `key` if it has not been transmitted yet.
+    ///
+    /// See [`ConnectionManager::send_message_to_target_with_key`]. Returns the [`MessageId`] of the message
+    /// like [`ConnectionManager::send_message`].
+    pub fn send_message_with_key<C: Channel, M: Message>(
+        &mut self,
+        client_id: ClientId,
+        message: &M,
+        key: u64,
+    ) -> Result<Option<MessageId>, ServerError> {
+        if !self.connections.contains_key(&client_id) {
+            return Ok(None);
+        }
+        self.serialize_message(message)?;
+        let message_bytes = self.writer.split();
+        let connection = self.connections.get_mut(&client_id).unwrap();
+        // messages to the local client are received immediately, so there is nothing to replace
+        if connection.is_local_client() {
+            connection.local_messages_to_send.push(message_bytes);
+            return Ok(None);
+        }
+        connection.buffer_message_with_key(
+            message_bytes,
+            ChannelKind::of::<C>(),
+            DedupKey::new::<M>(key),
+        )
+    }
+
+    /// Create a new [`SendGroup`], to order messages sent on different channels.
+    ///
+    /// See [`message_group`](crate::shared::message_group) for more details.
+    pub fn new_send_group(&mut self) -> SendGroup {
+        let group = SendGroup(self.next_send_group);
+        self.next_send_group = self.next_send_group.wrapping_add(1);
+        group
+    }
+
+    /// Queues up a message to be sent to all clients matching the specific [`NetworkTarget`].
+    ///
+    /// Each client will receive it only after all the messages that were previously sent to that client
+    /// with the same [`SendGroup`], even if they were sent on other channels.
+    pub fn send_message_to_target_in_group<C: Channel, M: Message>(
+        &mut self,
+        message: &M,
+        target: NetworkTarget,
+        group: SendGroup,
     ) -> Result<(), ServerError> {
-        self.send_message_to_target::<C, M>(message, NetworkTarget::Single(client_id))
+        let channel_kind = ChannelKind::of::<C>();
+        let reliable = self
+            .channel_registry
+            .get_builder_from_kind(&channel_kind)
+            .ok_or::<ServerError>(MessageError::NotRegistered.into())?
+            .settings
+            .mode
+            .is_reliable();
+        let group_net_id = self.message_registry.message_group_net_id()?;
+        self.serialize_message(message)?;
+        let message_bytes = self.writer.split();
+        self.connections
+            .iter_mut()
+            .filter(|(id, _)| target.targets(id))
+            .try_for_each(|(_, c)| {
+                // messages to the local client are never reordered
+                if c.is_local_client() {
+                    c.local_messages_to_send.push(message_bytes.clone());
+                    return Ok(());
+                }
+                // the sequence of the group is tracked separately for each client
+                let header = c.message_group_sender.next_header(group, reliable);
+                group_net_id.to_bytes(&mut c.writer)?;
+                header.to_bytes(&mut c.writer)?;
+                c.writer
+                    .write_all(&message_bytes)
+                    .map_err(SerializationError::from)?;
+                let grouped_bytes = c.writer.split();
+                c.buffer_message(grouped_bytes, channel_kind)?;
+                Ok(())
+            })
+    }
+
+    /// Queues up a message to be sent to a client as part of a [`SendGroup`]
+    pub fn send_message_in_group<C: Channel, M: Message>(
+        &mut self,
+        client_id: ClientId,
+        message: &M,
+        group: SendGroup,
+    ) -> Result<(), ServerError> {
+        self.send_message_to_target_in_group::<C, M>(
+            message,
+            NetworkTarget::Single(client_id),
+            group,
+        )
+    }
+
+    /// Queues up a message to be sent to all clients matching the specific [`NetworkTarget`], that the
+    /// clients will emit when their [`VisualTimeline`] reaches `tick`.
+    ///
+    /// For example, a hit confirmation sent with [`VisualTimeline::Interpolated`] and the tick of the hit is
+    /// emitted on the frame where the client displays the interpolated entities at the tick of the hit.
+    /// See [`timeline_message`](crate::shared::timeline_message) for more details.
+    pub fn send_message_at_tick<C: Channel, M: Message>(
+        &mut self,
+        message: &M,
+        tick: Tick,
+        timeline: VisualTimeline,
+        target: NetworkTarget,
+    ) -> Result<(), ServerError> {
+        let channel_kind = ChannelKind::of::<C>();
+        let timeline_net_id = self.message_registry.timeline_message_net_id()?;
+        self.serialize_message(message)?;
+        let message_bytes = self.writer.split();
+        // the header is the same for all the clients
+        timeline_net_id.to_bytes(&mut self.writer)?;
+        TimelineMessageHeader { tick, timeline }.to_bytes(&mut self.writer)?;
+        self.writer
+            .write_all(&message_bytes)
+            .map_err(SerializationError::from)?;
+        let timeline_bytes = self.writer.split();
+        self.connections
+            .iter_mut()
+            .filter(|(id, _)| target.targets(id))
+            .try_for_each(|(_, c)| {
+                // the local client displays the server world directly, so it receives the message immediately
+                if c.is_local_client() {
+                    c.local_messages_to_send.push(message_bytes.clone());
+                    return Ok(());
+                }
+                c.buffer_message(timeline_bytes.clone(), channel_kind)?;
+                Ok(())
+            })
     }
 
     /// Update the priority of a `ReplicationGroup` that is replicated to a given client
@@ -242,6 +553,171 @@ impl ConnectionManager {
             .ok_or(ServerError::ClientIdNotFound(client_id))
     }
 
+    /// Returns the [`ChannelStats`] of a channel for the connection of a client
+    pub fn channel_stats(&self, client_id: ClientId, kind: ChannelKind) -> Option<&ChannelStats> {
+        self.connections.get(&client_id)?.channel_stats(kind)
+    }
+
+    /// Returns the current occupancy of the send buffer of a channel for the connection of a client,
+    /// for example to stop sending messages to that client before the channel is full
+    pub fn channel_occupancy(
+        &self,
+        client_id: ClientId,
+        kind: ChannelKind,
+    ) -> Option<ChannelOccupancy> {
+        self.connections.get(&client_id)?.channel_occupancy(kind)
+    }
+
+    /// Returns the latest [`ConnectionStats`] of the connection of a client
+    pub fn connection_stats(&self, client_id: ClientId) -> Option<&ConnectionStats> {
+        Some(self.connections.get(&client_id)?.connection_stats())
+    }
+
+    /// Counters of the recoverable errors encountered while preparing the messages and replication
+    /// updates that are shared by all the clients, such as the
+    /// [`NetworkWarning::OversizedSerialization`]s. The warnings of each client are in [`Connection::warnings`]
+    pub fn warnings(&self) -> &NetworkWarnings {
+        &self.warnings
+    }
+
+    /// Serialized size of each message and component type sent to the clients, if
+    /// [`SerializedSizeConfig::enabled`](crate::shared::serialized_size::SerializedSizeConfig::enabled) is true
+    pub fn serialized_sizes(&self) -> &SerializedSizes {
+        &self.serialized_sizes
+    }
+
+    /// Statistics of the queue of the packets received from a client that are waiting to be processed
+    pub fn receive_queue_stats(&self, client_id: ClientId) -> Option<&ReceiveQueueStats> {
+        Some(self.connections.get(&client_id)?.receive_queue_stats())
+    }
+
+    /// Version of the schema of the component or message `T` negotiated with a client, i.e. the latest version
+    /// whose fields are known by both peers (see [`versioned`](crate::serialize::versioned)).
+    ///
+    /// Returns `None` if the schema of `T` cannot evolve, or until the versions are received from the client
+    pub fn schema_version<T: 'static>(&self, client_id: ClientId) -> Option<u8> {
+        self.connections.get(&client_id)?.schemas.version::<T>()
+    }
+
+    /// Returns the bytes of the replication messages sent to a client for each [`ReplicationClass`]
+    ///
+    /// [`ReplicationClass`]: crate::shared::replication::classes::ReplicationClass
+    pub fn replication_class_stats(&self, client_id: ClientId) -> Option<&ReplicationClassStats> {
+        Some(
+            self.connections
+                .get(&client_id)?
+                .message_manager
+                .replication_class_stats(),
+        )
+    }
+
+    /// Returns the distribution of the margins of the input messages received from a client,
+    /// over the last completed window. See [`jitter`](crate::shared::input::jitter) for more details.
+    pub fn input_margin_stats(&self, client_id: ClientId) -> Option<InputMarginStats> {
+        self.connections.get(&client_id)?.input_margins.stats()
+    }
+
+    /// Returns the counters of the spawns of a client that were rejected because of the
+    /// [`ReplicationLimits`](crate::shared::replication::limits::ReplicationLimits)
+    pub fn replication_limit_stats(&self, client_id: ClientId) -> Option<ReplicationLimitStats> {
+        Some(
+            *self
+                .connections
+                .get(&client_id)?
+                .replication_receiver
+                .limiter
+                .stats(),
+        )
+    }
+
+    /// Returns the number of entities replicated by a client that currently exist on the server, which is
+    /// the value compared to [`ReplicationLimits::max_entities`](crate::shared::replication::limits::ReplicationLimits::max_entities)
+    pub fn client_replicated_entities(&self, client_id: ClientId) -> Option<usize> {
+        Some(
+            self.connections
+                .get(&client_id)?
+                .replication_receiver
+                .limiter
+                .entities(),
+        )
+    }
+
+    /// Returns the progress of the fragmented messages that are being sent to a client (on reliable channels)
+    /// or received from a client. See [`transfer`](crate::channel::transfer) for more details.
+    pub fn transfers(&self, client_id: ClientId) -> Vec<TransferProgress> {
+        self.connections
+            .get(&client_id)
+            .map(|connection| connection.message_manager.transfers())
+            .unwrap_or_default()
+    }
+
+    /// Returns the progress of a transfer with a client, or None if it is finished
+    pub fn transfer(
+        &self,
+        client_id: ClientId,
+        handle: TransferHandle,
+    ) -> Option<TransferProgress> {
+        self.connections
+            .get(&client_id)?
+            .message_manager
+            .transfer(handle)
+    }
+
+    /// Cancel a transfer with a client: the remaining fragments are not sent, and the receiver discards
+    /// the fragments that it received.
+    ///
+    /// Returns false if the transfer was already finished.
+    pub fn cancel_transfer(
+        &mut self,
+        client_id: ClientId,
+        handle: TransferHandle,
+    ) -> Result<bool, ServerError> {
+        Ok(self
+            .connection_mut(client_id)?
+            .message_manager
+            .cancel_transfer(handle)?)
+    }
+
+    /// Change the settings of a channel for the connection of a client only,
+    /// see [`Connection::set_channel_settings_override`]
+    pub fn set_channel_settings_override(
+        &mut self,
+        client_id: ClientId,
+        kind: ChannelKind,
+        settings_override: ChannelSettingsOverride,
+    ) -> Result<(), ServerError> {
+        self.connection_mut(client_id)?
+            .set_channel_settings_override(kind, settings_override)
+    }
+
+    /// Returns the settings of a channel that are different for the connection of a client, if any
+    pub fn channel_settings_override(
+        &self,
+        client_id: ClientId,
+        kind: ChannelKind,
+    ) -> Option<&ChannelSettingsOverride> {
+        self.connections
+            .get(&client_id)?
+            .channel_settings_override(kind)
+    }
+
+    /// Add latency, jitter and loss to the packets of a client only, on top of the conditioner
+    /// of the [`IoConfig`](crate::connection::server::IoConfig) that applies to all the clients.
+    /// See [`Connection::set_conditioner`]
+    pub fn set_conditioner(
+        &mut self,
+        client_id: ClientId,
+        config: Option<LinkConditionerConfig>,
+    ) -> Result<(), ServerError> {
+        self.connection_mut(client_id)?.set_conditioner(config);
+        Ok(())
+    }
+
+    /// Returns the conditioner applied to the packets of a client only, if any
+    pub fn conditioner(&self, client_id: ClientId) -> Option<&LinkConditionerConfig> {
+        self.connections.get(&client_id)?.conditioner()
+    }
+
     pub fn connection_mut(&mut self, client_id: ClientId) -> Result<&mut Connection, ServerError> {
         self.connections
             .get_mut(&client_id)
@@ -273,6 +749,7 @@ impl ConnectionManager {
                 self.replication_config,
                 self.packet_config,
                 self.ping_config,
+                self.clock.clone(),
             );
             self.events.add_connect_event(ConnectEvent {
                 client_id,
@@ -287,17 +764,26 @@ impl ConnectionManager {
 
     /// Remove the connection associated with the given [`ClientId`],
     /// and returns the [`Entity`] associated with the client
-    pub(crate) fn remove(&mut self, client_id: ClientId) -> Entity {
+    pub(crate) fn remove(&mut self, client_id: ClientId, reason: DisconnectReason) -> Entity {
         #[cfg(feature = "metrics")]
         metrics::gauge!("connected_clients").decrement(1.0);
 
-        info!("Client {} disconnected", client_id);
+        info!(?reason, "Client {} disconnected", client_id);
         let entity = self
             .client_entity(client_id)
             .expect("client entity not found");
-        self.events
-            .add_disconnect_event(DisconnectEvent { client_id, entity });
-        self.connections.remove(&client_id);
+        self.requests.disconnect(client_id);
+        let summary = self
+            .connections
+            .remove(&client_id)
+            .map(|connection| connection.session_summary())
+            .unwrap_or_default();
+        self.events.add_disconnect_event(DisconnectEvent {
+            client_id,
+            entity,
+            reason,
+            summary,
+        });
         entity
     }
 
@@ -323,13 +809,26 @@ impl ConnectionManager {
             })
     }
 
+    /// Serialize the message in the writer, and record its size
+    fn serialize_message<M: Message>(&mut self, message: &M) -> Result<(), ServerError> {
+        let start = self.writer.len();
+        self.message_registry.serialize(message, &mut self.writer)?;
+        self.serialized_sizes.record_message(
+            MessageKind::of::<M>(),
+            &self.message_registry,
+            self.writer.len() - start,
+            &mut self.warnings,
+        );
+        Ok(())
+    }
+
     pub(crate) fn erased_send_message_to_target<M: Message>(
         &mut self,
         message: &M,
         channel_kind: ChannelKind,
         target: NetworkTarget,
     ) -> Result<(), ServerError> {
-        self.message_registry.serialize(message, &mut self.writer)?;
+        self.serialize_message(message)?;
         let message_bytes = self.writer.split();
         self.buffer_message(message_bytes, channel_kind, target)
     }
@@ -345,9 +844,14 @@ impl ConnectionManager {
         time_manager: &TimeManager,
     ) -> Result<(), ServerError> {
         let _span = info_span!("buffer_replication_messages").entered();
-        self.connections
-            .values_mut()
-            .try_for_each(move |c| c.buffer_replication_messages(tick, bevy_tick, time_manager))
+        // sharing the serialized messages is only useful if there are several clients
+        let mut cache = (self.replication_config.coalesce_updates && self.connections.len() > 1)
+            .then_some(&mut self.update_message_cache);
+        let result = self.connections.values_mut().try_for_each(|c| {
+            c.buffer_replication_messages(tick, bevy_tick, time_manager, cache.as_deref_mut())
+        });
+        self.update_message_cache.clear();
+        result
     }
 
     #[cfg_attr(feature = "trace", instrument(level = Level::INFO, skip_all))]
@@ -405,6 +909,13 @@ impl ConnectionManager {
         // We store the Bytes in a hashmap, maybe more efficient to write the replication message directly?
         component_registry.serialize(data, &mut self.writer)?;
         let raw_data = self.writer.split();
+        self.serialized_sizes.record_component(
+            ComponentKind::of::<C>(),
+            component_registry,
+            raw_data.len(),
+            entity,
+            &mut self.warnings,
+        );
         self.connection_mut(client_id)?
             .replication_sender
             .prepare_component_insert(entity, group_id, raw_data, bevy_tick);
@@ -412,6 +923,50 @@ impl ConnectionManager {
     }
 }
 
+/// [`LinkConditioner`]s applied to the packets of a single client
+struct ConnectionConditioner {
+    /// None if the conditioner was removed while some packets were still delayed
+    config: Option<LinkConditionerConfig>,
+    incoming: LinkConditioner<RecvPayload>,
+    outgoing: LinkConditioner<Payload>,
+}
+
+impl ConnectionConditioner {
+    fn new(config: LinkConditionerConfig, clock: NetworkClock) -> Self {
+        Self {
+            incoming: LinkConditioner::new(config.clone(), clock.clone()),
+            outgoing: LinkConditioner::new(config.clone(), clock),
+            config: Some(config),
+        }
+    }
+
+    fn set_config(&mut self, config: Option<LinkConditionerConfig>) {
+        if let Some(config) = &config {
+            self.incoming.set_config(config.clone());
+            self.outgoing.set_config(config.clone());
+        }
+        self.config = config;
+    }
+
+    /// Delay the payloads assembled for the client, and return the payloads that are ready to be sent
+    fn delay_outgoing(&mut self, mut payloads: Vec<Payload>) -> Vec<Payload> {
+        if self.config.is_some() {
+            payloads
+                .drain(..)
+                .for_each(|payload| self.outgoing.condition_packet(payload));
+        }
+        while let Some(payload) = self.outgoing.pop_packet() {
+            payloads.push(payload);
+        }
+        payloads
+    }
+
+    /// Returns true if the conditioner was removed and doesn't delay any packet anymore
+    fn is_removed(&self) -> bool {
+        self.config.is_none() && self.incoming.is_empty() && self.outgoing.is_empty()
+    }
+}
+
 /// Wrapper that handles the connection between the server and a client
 pub struct Connection {
     client_id: ClientId,
@@ -423,6 +978,22 @@ pub struct Connection {
     pub(crate) replication_receiver: ReplicationReceiver,
     pub(crate) events: ConnectionEvents,
     pub(crate) ping_manager: PingManager,
+    /// Recoverable errors encountered while processing the packets from the client
+    pub(crate) warnings: NetworkWarnings,
+    /// Network statistics of the session, to build the [`SessionSummary`]
+    pub(crate) session_stats: SessionStats,
+    /// Live network statistics of the connection
+    pub(crate) connection_stats: ConnectionStatsTracker,
+    /// Margins with which the input messages of the client arrived
+    pub(crate) input_margins: InputMargins,
+    /// Versions of the schemas negotiated with the client
+    pub(crate) schemas: NegotiatedSchemas,
+    /// Conditioner applied to the packets of this client only
+    conditioner: Option<ConnectionConditioner>,
+    /// Clock used by the conditioner
+    clock: NetworkClock,
+    /// Packets received from the client that are waiting to be processed by the channels
+    receive_queue: ReceiveQueue,
 
     // TODO: maybe don't do any replication until connection is synced?
     /// Used to transfer raw bytes to a system that can convert the bytes to the actual type
@@ -438,6 +1009,9 @@ pub struct Connection {
     is_local_client: bool,
     /// Messages to send to the local client (we don't buffer them in the MessageManager because there is no io)
     pub(crate) local_messages_to_send: Vec<Bytes>,
+    message_group_sender: MessageGroupSender,
+    /// Grouped messages received from the client that are waiting for earlier members of their group
+    message_group_receiver: MessageGroupReceiver<(Bytes, NetworkTarget, ChannelKind)>,
 }
 
 impl Connection {
@@ -448,13 +1022,17 @@ impl Connection {
         replication_config: ReplicationConfig,
         packet_config: PacketConfig,
         ping_config: PingConfig,
+        clock: NetworkClock,
     ) -> Self {
         let bandwidth_cap_enabled = packet_config.bandwidth_cap_enabled;
         // create the message manager and the channels
         let mut message_manager = MessageManager::new(
             channel_registry,
             packet_config.nack_rtt_multiple,
-            packet_config.into(),
+            PriorityConfig {
+                class_quotas: replication_config.class_quotas,
+                ..packet_config.into()
+            },
         );
         // get notified about acks/nacks for replication-update messages
         let entity_updates_sender = &mut message_manager
@@ -467,14 +1045,24 @@ impl Connection {
         // get a channel to get notified when a replication update message gets actually send (to update priority)
         let replication_update_send_receiver =
             message_manager.get_replication_update_send_receiver();
+        // get notified when an entity alias assignment/invalidation gets acked
+        let alias_acks_receiver = message_manager
+            .channels
+            .get_mut(&ChannelKind::of::<EntityAliasChannel>())
+            .unwrap()
+            .sender
+            .subscribe_acks();
         let replication_sender = ReplicationSender::new(
             update_acks_receiver,
             update_nacks_receiver,
             replication_update_send_receiver,
+            alias_acks_receiver,
             replication_config,
             bandwidth_cap_enabled,
         );
-        let replication_receiver = ReplicationReceiver::new();
+        let replication_receiver = ReplicationReceiver::new()
+            .with_limits(replication_config.limits)
+            .with_parallel_apply(replication_config.parallel_apply);
         Self {
             client_id,
             entity,
@@ -482,6 +1070,15 @@ impl Connection {
             replication_sender,
             replication_receiver,
             ping_manager: PingManager::new(ping_config),
+            warnings: NetworkWarnings::new(packet_config.warning_log_interval)
+                .with_clock(clock.clone()),
+            session_stats: SessionStats::default(),
+            connection_stats: ConnectionStatsTracker::default(),
+            input_margins: InputMargins::default(),
+            schemas: NegotiatedSchemas::default(),
+            conditioner: None,
+            clock,
+            receive_queue: ReceiveQueue::new(packet_config.receive_queue),
             events: ConnectionEvents::default(),
             received_messages: HashMap::default(),
             received_input_messages: HashMap::default(),
@@ -491,6 +1088,8 @@ impl Connection {
             messages_to_rebroadcast: vec![],
             is_local_client: false,
             local_messages_to_send: vec![],
+            message_group_sender: MessageGroupSender::default(),
+            message_group_receiver: MessageGroupReceiver::new(packet_config.message_group_timeout),
         }
     }
 
@@ -519,6 +1118,90 @@ impl Connection {
         self.ping_manager.jitter()
     }
 
+    /// Counters of the recoverable errors encountered while processing the packets from this client
+    pub fn warnings(&self) -> &NetworkWarnings {
+        &self.warnings
+    }
+
+    /// Network statistics of the session since the client connected
+    pub fn session_summary(&self) -> SessionSummary {
+        self.session_stats
+            .summary(self.message_manager.packet_stats())
+    }
+
+    /// Latest estimates of the network statistics of the connection (RTT, jitter, packet loss, bandwidth),
+    /// refreshed every [`PingConfig::ping_interval`]
+    pub fn connection_stats(&self) -> &ConnectionStats {
+        self.connection_stats.stats()
+    }
+
+    /// Statistics of the queue of the packets received from the client that are waiting to be processed
+    pub fn receive_queue_stats(&self) -> &ReceiveQueueStats {
+        self.receive_queue.stats()
+    }
+
+    /// Returns the flow control state of a reliable channel, if it uses flow control
+    /// (see [`ReliableSettings::receive_window`](crate::channel::builder::ReliableSettings))
+    pub fn channel_flow_control_stats<C: Channel>(&self) -> Option<FlowControlStats> {
+        self.message_manager.channel_flow_control_stats::<C>()
+    }
+
+    /// Returns the [`ChannelStats`] of a channel, since the client connected
+    pub fn channel_stats(&self, kind: ChannelKind) -> Option<&ChannelStats> {
+        self.message_manager.channel_stats(kind)
+    }
+
+    /// Returns the current occupancy of the send buffer of a channel
+    pub fn channel_occupancy(&self, kind: ChannelKind) -> Option<ChannelOccupancy> {
+        self.message_manager.channel_occupancy(kind)
+    }
+
+    /// Change the settings of a channel for this client only, for example to resend the reliable messages
+    /// less often to a mobile client. The other clients keep the settings of the protocol.
+    ///
+    /// The new settings are used from the next time that packets are sent. Applying
+    /// [`ChannelSettingsOverride::default`] restores the settings of the protocol.
+    pub fn set_channel_settings_override(
+        &mut self,
+        kind: ChannelKind,
+        settings_override: ChannelSettingsOverride,
+    ) -> Result<(), ServerError> {
+        Ok(self
+            .message_manager
+            .set_channel_settings_override(kind, settings_override)?)
+    }
+
+    /// Returns the settings of a channel that are different for this client, if any
+    pub fn channel_settings_override(&self, kind: ChannelKind) -> Option<&ChannelSettingsOverride> {
+        self.message_manager.channel_settings_override(kind)
+    }
+
+    /// Add latency, jitter and loss to the packets sent to and received from this client,
+    /// for example to measure the advantage that a lower latency gives in a game.
+    ///
+    /// The packets are delayed after they are assembled for the client, and before they are processed
+    /// when they are received from the client. The config is applied in both directions, so the RTT
+    /// of the client increases by about twice the `incoming_latency`.
+    ///
+    /// The conditioner can be replaced or removed (with `None`) at any time: the packets that are already
+    /// delayed keep their delay. Its latency is reported by the
+    /// [`conditioner_latency`](crate::server::diagnostics::ServerDiagnosticsPlugin::conditioner_latency) diagnostic
+    pub fn set_conditioner(&mut self, config: Option<LinkConditionerConfig>) {
+        info!(client_id = ?self.client_id, ?config, "Set the link conditioner of the client");
+        match (&mut self.conditioner, config) {
+            (Some(conditioner), config) => conditioner.set_config(config),
+            (None, Some(config)) => {
+                self.conditioner = Some(ConnectionConditioner::new(config, self.clock.clone()))
+            }
+            (None, None) => {}
+        }
+    }
+
+    /// Returns the conditioner applied to the packets of this client, if any
+    pub fn conditioner(&self) -> Option<&LinkConditionerConfig> {
+        self.conditioner.as_ref()?.config.as_ref()
+    }
+
     pub(crate) fn update(
         &mut self,
         world_tick: BevyTick,
@@ -532,13 +1215,17 @@ impl Connection {
             .update(time_manager, &self.ping_manager, tick_manager);
         self.replication_sender.update(world_tick);
         self.ping_manager.update(time_manager);
+        self.session_stats.update(time_manager.delta());
+        self.connection_stats.update(time_manager);
     }
 
+    /// Buffer a message to be sent to the client
+    /// Returns the message id associated with the message, if there is one
     pub(crate) fn buffer_message(
         &mut self,
         message: Bytes,
         channel: ChannelKind,
-    ) -> Result<(), ServerError> {
+    ) -> Result<Option<MessageId>, ServerError> {
         // TODO: i know channel names never change so i should be able to get them as static
         // TODO: just have a channel registry enum as well?
         let channel_name = self
@@ -547,8 +1234,18 @@ impl Connection {
             .name(&channel)
             .ok_or::<ServerError>(MessageError::NotRegistered.into())?;
         // message.emit_send_logs(&channel_name);
-        self.message_manager.buffer_send(message, channel)?;
-        Ok(())
+        Ok(self.message_manager.buffer_send(message, channel)?)
+    }
+
+    pub(crate) fn buffer_message_with_key(
+        &mut self,
+        message: Bytes,
+        channel: ChannelKind,
+        key: DedupKey,
+    ) -> Result<Option<MessageId>, ServerError> {
+        Ok(self
+            .message_manager
+            .buffer_send_with_key(message, channel, key)?)
     }
 
     #[cfg_attr(feature = "trace", instrument(level = Level::INFO, skip_all))]
@@ -557,6 +1254,7 @@ impl Connection {
         tick: Tick,
         bevy_tick: BevyTick,
         time_manager: &TimeManager,
+        update_message_cache: Option<&mut UpdateMessageCache>,
     ) -> Result<(), ServerError> {
         self.replication_sender.accumulate_priority(time_manager);
         self.replication_sender.send_actions_messages(
@@ -570,6 +1268,7 @@ impl Connection {
             bevy_tick,
             &mut self.writer,
             &mut self.message_manager,
+            update_message_cache,
         )?;
         Ok(())
     }
@@ -607,6 +1306,12 @@ impl Connection {
         // same thing, we want the correct send time for the ping
         // (and not have the delay between when we prepare the ping and when we send the packet)
         if let Some(ping) = self.ping_manager.maybe_prepare_ping(time_manager) {
+            // the connection stats are refreshed at every ping interval
+            self.connection_stats.refresh(
+                &self.ping_manager,
+                self.message_manager.packet_stats(),
+                self.message_manager.messages_resent(),
+            );
             self.send_ping(ping)?;
         }
 
@@ -622,12 +1327,34 @@ impl Connection {
                 Ok::<(), ServerError>(())
             })?;
         let payloads = self.message_manager.send_packets(tick_manager.tick())?;
+        payloads.iter().for_each(|payload| {
+            self.session_stats.record_sent(payload.len());
+            self.connection_stats.record_sent(payload.len());
+        });
+        let payloads = match &mut self.conditioner {
+            Some(conditioner) => conditioner.delay_outgoing(payloads),
+            None => payloads,
+        };
+        if self
+            .conditioner
+            .as_ref()
+            .is_some_and(ConnectionConditioner::is_removed)
+        {
+            self.conditioner = None;
+        }
 
         // update the replication sender about which messages were actually sent, and accumulate priority
         self.replication_sender.recv_send_notification();
+        self.writer.end_frame();
         Ok(payloads)
     }
 
+    /// Give back the payloads returned by [`Connection::send_packets`] once they have been sent,
+    /// so that their allocations can be reused
+    pub(crate) fn recycle_payloads(&mut self, payloads: Vec<Payload>) {
+        self.message_manager.recycle_payloads(payloads);
+    }
+
     pub fn receive(
         &mut self,
         world: &mut World,
@@ -637,6 +1364,10 @@ impl Connection {
     ) -> Result<ConnectionEvents, ServerError> {
         let _span = trace_span!("receive").entered();
         let message_registry = world.resource::<MessageRegistry>();
+        let group_net_id = message_registry.message_group_net_id().ok();
+        self.replication_receiver
+            .limiter
+            .update(time_manager.delta());
         self.message_manager
             .channels
             .iter_mut()
@@ -662,18 +1393,33 @@ impl Connection {
                     } else if channel_kind == &ChannelKind::of::<PongChannel>() {
                         let pong = Pong::from_bytes(&mut reader)?;
                         // process the pong
-                        self.ping_manager
-                            .process_pong(&pong, time_manager.current_time());
+                        if let Some(rtt) = self.ping_manager.process_pong(
+                            &pong,
+                            time_manager.current_time(),
+                            &mut self.warnings,
+                        ) {
+                            self.session_stats.record_rtt(rtt);
+                        }
                     } else if channel_kind == &ChannelKind::of::<EntityActionsChannel>() {
+                        self.replication_receiver.limiter.record_bytes(reader.len());
                         let actions = EntityActionsMessage::from_bytes(&mut reader)?;
                         trace!(?tick, ?actions, "received replication actions message");
                         // buffer the replication message
                         self.replication_receiver.recv_actions(actions, tick);
                     } else if channel_kind == &ChannelKind::of::<EntityUpdatesChannel>() {
-                        let updates = EntityUpdatesMessage::from_bytes(&mut reader)?;
+                        self.replication_receiver.limiter.record_bytes(reader.len());
+                        let updates = EntityUpdatesMessage::from_bytes_with_aliases(
+                            &mut reader,
+                            &self.replication_receiver.entity_aliases,
+                            tick,
+                            &mut self.warnings,
+                        )?;
                         trace!(?tick, ?updates, "received replication updates message");
                         // buffer the replication message
                         self.replication_receiver.recv_updates(updates, tick);
+                    } else if channel_kind == &ChannelKind::of::<EntityAliasChannel>() {
+                        let message = AliasMessage::from_bytes(&mut reader)?;
+                        self.replication_receiver.entity_aliases.receive(message);
                     } else {
                         // TODO: THIS IS DUPLICATED FROM THE `receive_message` FUNCTION BUT THERE ARE BORROW CHECKER
                         //  BECAUSE SPLIT BORROWS ARE NOT WELL HANDLED!
@@ -685,6 +1431,17 @@ impl Connection {
 
                         let mut reader = Reader::from(message);
                         let net_id = NetId::from_bytes(&mut reader)?;
+                        if group_net_id == Some(net_id) {
+                            // the message is part of a SendGroup: hold it until the earlier members are received
+                            let header = MessageGroupHeader::from_bytes(&mut reader)?;
+                            let message = reader.split_len(reader.remaining())?;
+                            self.message_group_receiver.recv(
+                                header,
+                                (message, target, *channel_kind),
+                                time_manager.current_time(),
+                            );
+                            continue;
+                        }
                         // we are also sending target and channel kind so the message can be
                         // rebroadcasted to other clients after we have converted the entities from the
                         // client World to the server World
@@ -709,11 +1466,26 @@ impl Connection {
                             MessageType::Normal => {
                                 self.received_messages.entry(net_id).or_default().push(data);
                             }
+                            MessageType::Deprecated => {
+                                self.warnings.report(
+                                    NetworkWarning::DeprecatedKind,
+                                    format_args!(
+                                        "Skipped a message of the deprecated type {net_id}"
+                                    ),
+                                );
+                            }
                         }
                     }
                 }
                 Ok::<(), SerializationError>(())
             })?;
+        for (message, target, channel_kind) in self
+            .message_group_receiver
+            .drain_ready(time_manager.current_time())
+        {
+            let net_id = NetId::from_bytes(&mut Reader::from(message.clone()))?;
+            self.push_received_message(net_id, (message, target, channel_kind), message_registry);
+        }
 
         // Check if we have any replication messages we can apply to the World (and emit events)
         self.replication_receiver.apply_world(
@@ -722,6 +1494,7 @@ impl Connection {
             component_registry,
             tick_manager.tick(),
             &mut self.events,
+            &mut self.warnings,
         );
 
         // TODO: do i really need this? I could just create events in this function directly?
@@ -744,7 +1517,16 @@ impl Connection {
         let ClientMessage { message, target } = ClientMessage::from_bytes(&mut reader)?;
 
         let mut reader = Reader::from(message);
-        let net_id = NetId::from_bytes(&mut reader)?;
+        let mut net_id = NetId::from_bytes(&mut reader)?;
+        if message_registry
+            .message_group_net_id()
+            .is_ok_and(|group_net_id| group_net_id == net_id)
+        {
+            // messages from the local client are never reordered, so we can ignore the SendGroup
+            MessageGroupHeader::from_bytes(&mut reader)?;
+            reader = Reader::from(reader.split_len(reader.remaining())?);
+            net_id = NetId::from_bytes(&mut reader)?;
+        }
         // we are also sending target and channel kind so the message can be
         // rebroadcasted to other clients after we have converted the entities from the
         // client World to the server World
@@ -753,6 +1535,17 @@ impl Connection {
         //  or it matters for input messages?
         // TODO: avoid clone with Arc<[u8]>?
         let data = (reader.consume(), target, channel_kind);
+        self.push_received_message(net_id, data, message_registry);
+        Ok(())
+    }
+
+    /// Buffer the bytes of a received message so that they can be decoded into the correct type
+    fn push_received_message(
+        &mut self,
+        net_id: NetId,
+        data: (Bytes, NetworkTarget, ChannelKind),
+        message_registry: &MessageRegistry,
+    ) {
         match message_registry.message_type(net_id) {
             #[cfg(feature = "leafwing")]
             MessageType::LeafwingInput => self
@@ -769,8 +1562,49 @@ impl Connection {
             MessageType::Normal => {
                 self.received_messages.entry(net_id).or_default().push(data);
             }
+            MessageType::Deprecated => {
+                self.warnings.report(
+                    NetworkWarning::DeprecatedKind,
+                    format_args!("Skipped a message of the deprecated type {net_id}"),
+                );
+            }
         }
-        Ok(())
+    }
+
+    /// Add a packet from the client to the [`ReceiveQueue`], or delay it if the connection has a conditioner
+    pub(crate) fn recv_conditioned_packet(&mut self, packet: RecvPayload) {
+        if let Some(conditioner) = self
+            .conditioner
+            .as_mut()
+            .filter(|conditioner| conditioner.config.is_some())
+        {
+            conditioner.incoming.condition_packet(packet);
+            return;
+        }
+        self.receive_queue.push(packet);
+    }
+
+    /// Add the packets from the client that were delayed by the conditioner and are now ready to the [`ReceiveQueue`],
+    /// then process the packets of the queue, up to the per-frame quota
+    pub(crate) fn process_received_packets(
+        &mut self,
+        tick_manager: &TickManager,
+        component_registry: &ComponentRegistry,
+        delta_manager: &mut DeltaManager,
+    ) -> Result<(), ServerError> {
+        while let Some(packet) = self
+            .conditioner
+            .as_mut()
+            .and_then(|conditioner| conditioner.incoming.pop_packet())
+        {
+            self.receive_queue.push(packet);
+        }
+        let mut receive_queue = std::mem::take(&mut self.receive_queue);
+        let result = receive_queue.process(|packet| {
+            self.recv_packet(packet, tick_manager, component_registry, delta_manager)
+        });
+        self.receive_queue = receive_queue;
+        result
     }
 
     pub fn recv_packet(
@@ -780,6 +1614,8 @@ impl Connection {
         component_registry: &ComponentRegistry,
         delta_manager: &mut DeltaManager,
     ) -> Result<(), ServerError> {
+        self.session_stats.record_received(packet.len());
+        self.connection_stats.record_received(packet.len());
         // receive the packets, buffer them, update any sender that were waiting for their sent messages to be acked
         let tick = self.message_manager.recv_packet(packet)?;
         // notify the replication sender that some sent messages were received
@@ -811,6 +1647,38 @@ impl ConnectionManager {
         })
     }
 
+    /// Despawn an entity that is replaced by another entity, see
+    /// [`ReplaceReplicationCommandExt`](crate::server::replication::commands::ReplaceReplicationCommandExt)
+    pub(crate) fn prepare_entity_despawn_replaced(
+        &mut self,
+        entity: Entity,
+        group_id: ReplicationGroupId,
+        target: NetworkTarget,
+    ) -> Result<(), ServerError> {
+        self.connected_targets(target).try_for_each(|client_id| {
+            self.connection_mut(client_id)?
+                .replication_sender
+                .prepare_entity_despawn_replaced(entity, group_id);
+            Ok(())
+        })
+    }
+
+    /// Stop replicating an entity to the clients, without despawning it on the server
+    /// (the entity lost its relevance, or the clients were removed from its replication target)
+    pub(crate) fn prepare_entity_interest_lost(
+        &mut self,
+        entity: Entity,
+        group_id: ReplicationGroupId,
+        target: NetworkTarget,
+    ) -> Result<(), ServerError> {
+        self.connected_targets(target).try_for_each(|client_id| {
+            self.connection_mut(client_id)?
+                .replication_sender
+                .prepare_entity_interest_lost(entity, group_id);
+            Ok(())
+        })
+    }
+
     pub(crate) fn prepare_component_remove(
         &mut self,
         entity: Entity,
@@ -835,6 +1703,16 @@ impl ConnectionManager {
         })
     }
 
+    /// Stop tracking the keyframes of a component that was removed from an entity
+    pub(crate) fn remove_component_keyframes(&mut self, entity: Entity, kind: ComponentKind) {
+        self.connections.values_mut().for_each(|connection| {
+            connection
+                .replication_sender
+                .keyframe_manager
+                .remove_component(entity, kind);
+        });
+    }
+
     // TODO: perf gain if we batch this? (send vec of components) (same for update/removes)
     #[allow(clippy::too_many_arguments)]
     pub(crate) fn prepare_component_insert(
@@ -899,6 +1777,13 @@ impl ConnectionManager {
             component_registry.erased_serialize(component_data, &mut self.writer, kind)?;
         };
         let raw_data = self.writer.split();
+        self.serialized_sizes.record_component(
+            kind,
+            component_registry,
+            raw_data.len(),
+            entity,
+            &mut self.warnings,
+        );
         self.connected_targets(actual_target)
             .try_for_each(|client_id| {
                 // trace!(
@@ -936,6 +1821,8 @@ impl ConnectionManager {
         system_current_tick: BevyTick,
         tick: Tick,
         delta_compression: bool,
+        keyframe: Option<KeyframeBudget>,
+        send_interval: Option<i16>,
     ) -> Result<(), ServerError> {
         let mut num_targets = 0;
         let mut existing_bytes: Option<Bytes> = None;
@@ -954,10 +1841,20 @@ impl ConnectionManager {
                 ?send_tick,
                 "prepare entity update changed check (we want the component-change-tick to be higher than send_tick)"
             );
-
-            if send_tick.map_or(true, |tick| {
+            let mut changed = send_tick.map_or(true, |tick| {
                 component_change_tick.is_newer_than(tick, system_current_tick)
-            }) {
+            });
+            // components with a send interval accumulate their changes until the interval elapses
+            if let Some(interval_ticks) = send_interval {
+                changed = replication_sender.send_intervals.should_send(
+                    entity,
+                    kind,
+                    interval_ticks,
+                    tick,
+                    changed,
+                );
+            }
+            if changed {
                 num_targets += 1;
                 trace!(
                     ?entity,
@@ -972,11 +1869,60 @@ impl ConnectionManager {
                     // serialize only if there is at least one client that needs the update
                     if existing_bytes.is_none() {
                         registry.erased_serialize(component, &mut self.writer, kind)?;
-                        existing_bytes = Some(self.writer.split());
+                        let raw_data = self.writer.split();
+                        self.serialized_sizes.record_component(
+                            kind,
+                            registry,
+                            raw_data.len(),
+                            entity,
+                            &mut self.warnings,
+                        );
+                        existing_bytes = Some(raw_data);
                     }
                     let raw_data = existing_bytes.clone().unwrap();
                     replication_sender.prepare_component_update(entity, group_id, raw_data);
                 }
+            } else if keyframe.is_some_and(|budget| {
+                replication_sender
+                    .keyframe_manager
+                    .should_send_keyframe(entity, kind, budget, tick)
+            }) {
+                // the component didn't change, but we periodically send its full value
+                // in case the last change was lost
+                num_targets += 1;
+                trace!(
+                    ?entity,
+                    ?tick,
+                    name = ?registry.name(kind),
+                    "Sending keyframe for single component"
+                );
+                if delta_compression {
+                    replication_sender.prepare_delta_component_keyframe(
+                        entity,
+                        group_id,
+                        kind,
+                        component,
+                        registry,
+                        &mut self.writer,
+                        tick,
+                    )?;
+                } else {
+                    if existing_bytes.is_none() {
+                        registry.erased_serialize(component, &mut self.writer, kind)?;
+                        let raw_data = self.writer.split();
+                        self.serialized_sizes.record_component(
+                            kind,
+                            registry,
+                            raw_data.len(),
+                            entity,
+                            &mut self.warnings,
+                        );
+                        existing_bytes = Some(raw_data);
+                    }
+                    let raw_data = existing_bytes.clone().unwrap();
+                    replication_sender
+                        .prepare_component_keyframe(entity, group_id, kind, raw_data, tick);
+                }
             }
             Ok::<(), ServerError>(())
         })?;
@@ -1039,6 +1985,16 @@ impl ReplicationReceive for ConnectionManager {
             connection.replication_receiver.cleanup(tick);
         }
     }
+
+    fn remote_entity_map(&self, from: Option<ClientId>) -> Option<&RemoteEntityMap> {
+        Some(
+            &self
+                .connections
+                .get(&from?)?
+                .replication_receiver
+                .remote_entity_map,
+        )
+    }
 }
 
 impl ReplicationSend for ConnectionManager {
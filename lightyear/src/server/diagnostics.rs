@@ -0,0 +1,375 @@
+//! Diagnostics computed on the server
+use bevy::app::{App, Plugin, PostUpdate};
+use bevy::diagnostic::{
+    Diagnostic, DiagnosticPath, Diagnostics, DiagnosticsStore, RegisterDiagnostic,
+};
+use bevy::prelude::{Condition, IntoSystemConfigs, Res, ResMut, Trigger};
+use bevy::time::common_conditions::on_timer;
+use bevy::utils::{Duration, HashMap};
+
+use crate::channel::stats::{ChannelDiagnosticPaths, ChannelDiagnosticsPlugin, ChannelStats};
+use crate::packet::receive_queue::{ReceiveQueueDiagnosticsPlugin, ReceiveQueueStats};
+use crate::prelude::ClientId;
+use crate::server::behavior::ClientBehaviorHistory;
+use crate::server::connection::ConnectionManager;
+use crate::server::events::{ConnectEvent, DisconnectEvent};
+use crate::server::run_conditions::is_started;
+use crate::server::validation::ClientUpdateViolations;
+use crate::shared::clock::NetworkClock;
+use crate::shared::replication::classes::ReplicationClassStats;
+use crate::shared::replication::diagnostics::ReplicationDiagnosticsPlugin;
+use crate::shared::replication::send::ReplicationSendStats;
+use crate::shared::serialized_size::{
+    SerializedSizeDiagnosticsPlugin, SerializedSizeDiagnosticsSettings,
+};
+use crate::shared::warnings::NetworkWarningsDiagnosticsPlugin;
+
+/// Plugin computing diagnostics about the server connections
+#[derive(Debug, Default)]
+pub struct ServerDiagnosticsPlugin;
+
+impl ServerDiagnosticsPlugin {
+    /// Number of component updates from clients that were clamped by a validator
+    pub const CLAMPED_CLIENT_UPDATES: DiagnosticPath =
+        DiagnosticPath::const_new("server.validation.clamped");
+
+    /// Number of component updates from clients that were rejected by a validator
+    pub const REJECTED_CLIENT_UPDATES: DiagnosticPath =
+        DiagnosticPath::const_new("server.validation.rejected");
+
+    /// Number of entity spawns from clients that were rejected by the client spawn validator
+    pub const REJECTED_CLIENT_SPAWNS: DiagnosticPath =
+        DiagnosticPath::const_new("server.validation.rejected_spawns");
+
+    /// Memory used by the histories of the [`ClientBehaviorHistory`], in bytes
+    pub const BEHAVIOR_HISTORY_BYTES: DiagnosticPath =
+        DiagnosticPath::const_new("server.behavior.history_bytes");
+
+    /// Median margin of the input messages of a client, in ticks, at the path
+    /// `server.input.client_<id>.margin_p50`
+    pub fn input_margin_p50(client_id: ClientId) -> DiagnosticPath {
+        DiagnosticPath::new(format!(
+            "server.input.client_{}.margin_p50",
+            client_id.to_bits()
+        ))
+    }
+
+    /// Margin that 99% of the input messages of a client arrived with, in ticks, at the path
+    /// `server.input.client_<id>.margin_p99`
+    pub fn input_margin_p99(client_id: ClientId) -> DiagnosticPath {
+        DiagnosticPath::new(format!(
+            "server.input.client_{}.margin_p99",
+            client_id.to_bits()
+        ))
+    }
+
+    /// Number of entities replicated by a client that currently exist on the server, at the path
+    /// `server.replication_limits.client_<id>.entities`
+    pub fn client_replicated_entities(client_id: ClientId) -> DiagnosticPath {
+        DiagnosticPath::new(format!(
+            "server.replication_limits.client_{}.entities",
+            client_id.to_bits()
+        ))
+    }
+
+    /// Total number of entity spawns of a client that were rejected because of the
+    /// [`ReplicationLimits`](crate::shared::replication::limits::ReplicationLimits), at the path
+    /// `server.replication_limits.client_<id>.rejected_spawns`
+    pub fn client_rejected_spawns(client_id: ClientId) -> DiagnosticPath {
+        DiagnosticPath::new(format!(
+            "server.replication_limits.client_{}.rejected_spawns",
+            client_id.to_bits()
+        ))
+    }
+
+    /// Latency added to the packets of a client by its conditioner, in milliseconds, at the path
+    /// `server.conditioner.client_<id>.latency`. It is 0 if the client has no conditioner
+    /// (see [`ConnectionManager::set_conditioner`])
+    pub fn conditioner_latency(client_id: ClientId) -> DiagnosticPath {
+        DiagnosticPath::new(format!(
+            "server.conditioner.client_{}.latency",
+            client_id.to_bits()
+        ))
+    }
+}
+
+fn replication_diagnostics_system(
+    mut connection_manager: ResMut<ConnectionManager>,
+    mut diagnostics: Diagnostics,
+) {
+    // sum the stats of all clients, and reset them
+    let mut class_stats = ReplicationClassStats::default();
+    for connection in connection_manager.connections.values_mut() {
+        class_stats.merge(&connection.message_manager.take_unflushed_class_stats());
+    }
+    ReplicationDiagnosticsPlugin::add_class_measurements(&class_stats, &mut diagnostics);
+    let stats = connection_manager.connections.values_mut().fold(
+        ReplicationSendStats::default(),
+        |mut stats, connection| {
+            let client_stats = std::mem::take(&mut connection.replication_sender.stats);
+            stats.update_bytes += client_stats.update_bytes;
+            stats.keyframe_bytes += client_stats.keyframe_bytes;
+            stats.alias_saved_bytes += client_stats.alias_saved_bytes;
+            stats.coalesced_bytes += client_stats.coalesced_bytes;
+            stats
+        },
+    );
+    ReplicationDiagnosticsPlugin::add_measurements(stats, diagnostics);
+}
+
+fn warnings_diagnostics_system(
+    connection_manager: Res<ConnectionManager>,
+    diagnostics: Diagnostics,
+) {
+    // sum the warnings of all clients, and of the messages shared by all clients
+    let warnings = connection_manager.connections.values().fold(
+        connection_manager.warnings().clone(),
+        |mut warnings, connection| {
+            warnings.merge_counts(&connection.warnings);
+            warnings
+        },
+    );
+    NetworkWarningsDiagnosticsPlugin::add_measurements(&warnings, diagnostics);
+}
+
+fn receive_queue_diagnostics_system(
+    connection_manager: Res<ConnectionManager>,
+    diagnostics: Diagnostics,
+) {
+    // sum the stats of all clients
+    let stats = connection_manager.connections.values().fold(
+        ReceiveQueueStats::default(),
+        |mut stats, connection| {
+            stats.merge(connection.receive_queue_stats());
+            stats
+        },
+    );
+    ReceiveQueueDiagnosticsPlugin::add_measurements(&stats, diagnostics);
+}
+
+fn serialized_size_diagnostics_system(
+    connection_manager: Res<ConnectionManager>,
+    clock: Res<NetworkClock>,
+    mut settings: ResMut<SerializedSizeDiagnosticsSettings>,
+    mut store: ResMut<DiagnosticsStore>,
+) {
+    SerializedSizeDiagnosticsPlugin::add_measurements(
+        connection_manager.serialized_sizes(),
+        &mut settings,
+        clock.now(),
+        &mut store,
+    );
+}
+
+fn channel_diagnostics_system(
+    connection_manager: Res<ConnectionManager>,
+    paths: Res<ChannelDiagnosticPaths>,
+    mut diagnostics: Diagnostics,
+) {
+    // sum the stats of all clients
+    let mut stats = HashMap::<_, ChannelStats>::default();
+    for connection in connection_manager.connections.values() {
+        for (kind, channel_stats) in connection.message_manager.all_channel_stats() {
+            stats.entry(kind).or_default().merge(channel_stats);
+        }
+    }
+    ChannelDiagnosticsPlugin::add_measurements(
+        &paths,
+        stats.iter().map(|(kind, stats)| (*kind, stats)),
+        &mut diagnostics,
+    );
+}
+
+fn validation_diagnostics_system(
+    mut violations: ResMut<ClientUpdateViolations>,
+    mut diagnostics: Diagnostics,
+) {
+    let unflushed = std::mem::take(&mut violations.unflushed);
+    diagnostics.add_measurement(&ServerDiagnosticsPlugin::CLAMPED_CLIENT_UPDATES, || {
+        unflushed.clamped as f64
+    });
+    diagnostics.add_measurement(&ServerDiagnosticsPlugin::REJECTED_CLIENT_UPDATES, || {
+        unflushed.rejected as f64
+    });
+    diagnostics.add_measurement(&ServerDiagnosticsPlugin::REJECTED_CLIENT_SPAWNS, || {
+        unflushed.rejected_spawns as f64
+    });
+}
+
+fn behavior_diagnostics_system(
+    history: Option<Res<ClientBehaviorHistory>>,
+    mut diagnostics: Diagnostics,
+) {
+    // the history only exists if an analyzer was added
+    let Some(history) = history else {
+        return;
+    };
+    diagnostics.add_measurement(&ServerDiagnosticsPlugin::BEHAVIOR_HISTORY_BYTES, || {
+        history.memory_usage() as f64
+    });
+}
+
+fn input_margin_diagnostics_system(
+    mut connection_manager: ResMut<ConnectionManager>,
+    mut diagnostics: Diagnostics,
+) {
+    for (client_id, connection) in connection_manager.connections.iter_mut() {
+        if !std::mem::take(&mut connection.input_margins.unflushed) {
+            continue;
+        }
+        let Some(stats) = connection.input_margins.stats() else {
+            continue;
+        };
+        diagnostics.add_measurement(
+            &ServerDiagnosticsPlugin::input_margin_p50(*client_id),
+            || stats.p50 as f64,
+        );
+        diagnostics.add_measurement(
+            &ServerDiagnosticsPlugin::input_margin_p99(*client_id),
+            || stats.p99 as f64,
+        );
+    }
+}
+
+fn replication_limits_diagnostics_system(
+    connection_manager: Res<ConnectionManager>,
+    mut diagnostics: Diagnostics,
+) {
+    for (client_id, connection) in connection_manager.connections.iter() {
+        let limiter = &connection.replication_receiver.limiter;
+        let entities = limiter.entities();
+        let rejected_spawns = limiter.stats().rejected_spawns;
+        diagnostics.add_measurement(
+            &ServerDiagnosticsPlugin::client_replicated_entities(*client_id),
+            || entities as f64,
+        );
+        diagnostics.add_measurement(
+            &ServerDiagnosticsPlugin::client_rejected_spawns(*client_id),
+            || rejected_spawns as f64,
+        );
+    }
+}
+
+fn conditioner_diagnostics_system(
+    connection_manager: Res<ConnectionManager>,
+    mut diagnostics: Diagnostics,
+) {
+    for (client_id, connection) in connection_manager.connections.iter() {
+        let latency = connection
+            .conditioner()
+            .map_or(Duration::ZERO, |config| config.incoming_latency);
+        diagnostics.add_measurement(
+            &ServerDiagnosticsPlugin::conditioner_latency(*client_id),
+            || latency.as_secs_f64() * 1000.0,
+        );
+    }
+}
+
+/// Register the per-client diagnostics when a client connects
+fn register_client_diagnostics(
+    trigger: Trigger<ConnectEvent>,
+    mut store: ResMut<DiagnosticsStore>,
+) {
+    let client_id = trigger.event().client_id;
+    let history_len = ReplicationDiagnosticsPlugin::default().history_len;
+    for (path, suffix) in [
+        (
+            ServerDiagnosticsPlugin::input_margin_p50(client_id),
+            "ticks",
+        ),
+        (
+            ServerDiagnosticsPlugin::input_margin_p99(client_id),
+            "ticks",
+        ),
+        (
+            ServerDiagnosticsPlugin::client_replicated_entities(client_id),
+            "",
+        ),
+        (
+            ServerDiagnosticsPlugin::client_rejected_spawns(client_id),
+            "",
+        ),
+        (
+            ServerDiagnosticsPlugin::conditioner_latency(client_id),
+            "ms",
+        ),
+    ] {
+        if store.get(&path).is_none() {
+            store.add(
+                Diagnostic::new(path)
+                    .with_suffix(suffix)
+                    .with_max_history_length(history_len),
+            );
+        }
+    }
+}
+
+/// Remove the violation counters of a client when it disconnects
+fn clear_client_violations(
+    trigger: Trigger<DisconnectEvent>,
+    mut violations: ResMut<ClientUpdateViolations>,
+) {
+    violations.remove(trigger.event().client_id);
+}
+
+impl Plugin for ServerDiagnosticsPlugin {
+    fn build(&self, app: &mut App) {
+        let replication_plugin = ReplicationDiagnosticsPlugin::default();
+        let flush_interval = replication_plugin.flush_interval;
+        let history_len = replication_plugin.history_len;
+        // the plugin can already have been added by the client in host-server mode
+        if !app.is_plugin_added::<ReplicationDiagnosticsPlugin>() {
+            app.add_plugins(replication_plugin);
+        }
+        if !app.is_plugin_added::<NetworkWarningsDiagnosticsPlugin>() {
+            app.add_plugins(NetworkWarningsDiagnosticsPlugin::default());
+        }
+        if !app.is_plugin_added::<ChannelDiagnosticsPlugin>() {
+            app.add_plugins(ChannelDiagnosticsPlugin::default());
+        }
+        if !app.is_plugin_added::<ReceiveQueueDiagnosticsPlugin>() {
+            app.add_plugins(ReceiveQueueDiagnosticsPlugin::default());
+        }
+        // the plugin can already have been added by the user, or by the client in host-server mode
+        if !app.is_plugin_added::<SerializedSizeDiagnosticsPlugin>() {
+            app.add_plugins(SerializedSizeDiagnosticsPlugin::default());
+        }
+        app.init_resource::<ClientUpdateViolations>();
+        app.register_diagnostic(
+            Diagnostic::new(Self::CLAMPED_CLIENT_UPDATES).with_max_history_length(history_len),
+        );
+        app.register_diagnostic(
+            Diagnostic::new(Self::REJECTED_CLIENT_UPDATES).with_max_history_length(history_len),
+        );
+        app.register_diagnostic(
+            Diagnostic::new(Self::REJECTED_CLIENT_SPAWNS).with_max_history_length(history_len),
+        );
+        app.register_diagnostic(
+            Diagnostic::new(Self::BEHAVIOR_HISTORY_BYTES)
+                .with_suffix("B")
+                .with_max_history_length(history_len),
+        );
+        app.observe(clear_client_violations);
+        app.observe(register_client_diagnostics);
+        app.add_systems(
+            PostUpdate,
+            (
+                replication_diagnostics_system,
+                channel_diagnostics_system,
+                validation_diagnostics_system,
+                warnings_diagnostics_system,
+                receive_queue_diagnostics_system,
+                input_margin_diagnostics_system,
+                replication_limits_diagnostics_system,
+                conditioner_diagnostics_system,
+                behavior_diagnostics_system,
+            )
+                .run_if(on_timer(flush_interval).and_then(is_started)),
+        );
+        // the flush interval of the plugin is handled by the system itself
+        app.add_systems(
+            PostUpdate,
+            serialized_size_diagnostics_system.run_if(is_started),
+        );
+    }
+}
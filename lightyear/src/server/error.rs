@@ -1,6 +1,6 @@
 //! Errors that can happen on the server
 
-use crate::prelude::ClientId;
+use crate::prelude::{ClientId, Tick};
 
 pub type Result<T> = std::result::Result<T, ServerError>;
 
@@ -24,4 +24,6 @@ pub enum ServerError {
     RelevanceError(#[from] crate::server::relevance::error::RelevanceError),
     #[error(transparent)]
     ReplicationError(#[from] crate::shared::replication::error::ReplicationError),
+    #[error("there is no snapshot old enough to rewind to tick {0:?}")]
+    NoSnapshot(Tick),
 }
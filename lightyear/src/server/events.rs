@@ -3,8 +3,14 @@ use bevy::ecs::entity::EntityHash;
 use bevy::prelude::*;
 use bevy::utils::HashMap;
 
+use crate::channel::builder::ChannelOccupancy;
+use crate::channel::transfer::{TransferHandle, TransferOutcome};
 use crate::connection::id::ClientId;
-use crate::prelude::ComponentRegistry;
+use crate::connection::server::DisconnectReason;
+use crate::inputs::LocalPlayerId;
+use crate::packet::message::MessageId;
+use crate::prelude::{ChannelKind, ComponentRegistry, Tick};
+use crate::server::config::{DuplicateIdPolicy, DuplicateSession};
 use crate::server::connection::ConnectionManager;
 use crate::shared::events::connection::{
     ConnectionEvents, IterComponentInsertEvent, IterComponentRemoveEvent, IterComponentUpdateEvent,
@@ -12,6 +18,8 @@ use crate::shared::events::connection::{
 };
 use crate::shared::events::plugin::EventsPlugin;
 use crate::shared::events::systems::push_component_events;
+use crate::shared::replication::limits::ReplicationLimit;
+use crate::shared::session_summary::SessionSummary;
 use crate::shared::sets::{InternalMainSet, ServerMarker};
 
 type EntityHashMap<K, V> = hashbrown::HashMap<K, V, EntityHash>;
@@ -26,6 +34,12 @@ impl Plugin for ServerEventsPlugin {
             // EVENTS
             .add_event::<ConnectEvent>()
             .add_event::<DisconnectEvent>()
+            .add_event::<TransportMigrationEvent>()
+            .add_event::<DuplicateClientIdEvent>()
+            .add_event::<TransferEvent>()
+            .add_event::<MessageAckEvent>()
+            .add_event::<ChannelOccupancyEvent>()
+            .add_event::<ReplicationLimitExceededEvent>()
             // PLUGIN
             .add_plugins(EventsPlugin::<ConnectionManager>::default());
     }
@@ -117,8 +131,8 @@ impl ServerEvents {
     }
 
     pub(crate) fn add_disconnect_event(&mut self, disconnect_event: DisconnectEvent) {
-        self.disconnections.push(disconnect_event);
         self.events.remove(&disconnect_event.client_id);
+        self.disconnections.push(disconnect_event);
         self.empty = false;
     }
 
@@ -215,10 +229,93 @@ pub struct ConnectEvent {
 }
 
 /// Bevy [`Event`] emitted on the server on the frame where a client is disconnected
-#[derive(Event, Debug, Copy, Clone)]
+#[derive(Event, Debug, Clone)]
 pub struct DisconnectEvent {
     pub client_id: ClientId,
     pub entity: Entity,
+    /// Why the client was disconnected
+    pub reason: DisconnectReason,
+    /// Network statistics of the session that just ended
+    pub summary: SessionSummary,
+}
+
+/// Bevy [`Event`] emitted on the server on the frame where a connected client moved its session to a new transport
+#[derive(Event, Debug, Copy, Clone)]
+pub struct TransportMigrationEvent {
+    pub client_id: ClientId,
+}
+
+/// Bevy [`Event`] emitted on the server when the transfer of a fragmented message to or from a client
+/// completes, is cancelled, or times out (see [`transfer`](crate::channel::transfer))
+#[derive(Event, Debug, Clone, Copy, PartialEq)]
+pub struct TransferEvent {
+    pub client_id: ClientId,
+    pub handle: TransferHandle,
+    pub outcome: TransferOutcome,
+}
+
+/// Bevy [`Event`] emitted on the server when a client acked a message that was sent with
+/// [`ConnectionManager::send_message`](crate::server::connection::ConnectionManager::send_message)
+/// on a channel that tracks acks (a reliable channel or [`ChannelMode::UnorderedUnreliableWithAcks`](crate::prelude::ChannelMode::UnorderedUnreliableWithAcks)).
+///
+/// A fragmented message is only acked once all its fragments are acked.
+#[derive(Event, Debug, Clone, Copy, PartialEq)]
+pub struct MessageAckEvent {
+    pub client_id: ClientId,
+    pub message_id: MessageId,
+    pub channel_kind: ChannelKind,
+}
+
+/// Bevy [`Event`] emitted on the server when the send buffer of a channel of a client goes above 80% of its
+/// [`BufferLimits`](crate::channel::builder::BufferLimits).
+///
+/// It is emitted again once the occupancy went back below the threshold and exceeds it again.
+#[derive(Event, Debug, Clone, Copy, PartialEq)]
+pub struct ChannelOccupancyEvent {
+    pub client_id: ClientId,
+    pub channel_kind: ChannelKind,
+    pub occupancy: ChannelOccupancy,
+}
+
+/// Bevy [`Event`] emitted on the server on the frame where a client sent a connection request with the id
+/// of a client that was still connected
+#[derive(Event, Debug, Copy, Clone, PartialEq)]
+pub struct DuplicateClientIdEvent {
+    pub client_id: ClientId,
+    /// The policy that was applied
+    pub policy: DuplicateIdPolicy,
+    /// The session that was kept.
+    ///
+    /// If it is [`DuplicateSession::New`], the previous session also emits a [`DisconnectEvent`]
+    pub winner: DuplicateSession,
+}
+
+/// Bevy [`Event`] emitted on the server on the frames where some of the entities spawned by a client were
+/// rejected because of the [`ReplicationLimits`](crate::shared::replication::limits::ReplicationLimits)
+/// of the server. The rejected entities are never spawned on the server.
+#[derive(Event, Debug, Clone, PartialEq)]
+pub struct ReplicationLimitExceededEvent {
+    pub client_id: ClientId,
+    /// The limit that was exceeded
+    pub limit: ReplicationLimit,
+    /// Number of spawns (or component insertions for [`ReplicationLimit::ComponentsPerEntity`]) rejected during the frame
+    pub rejected: u32,
+}
+
+/// Bevy [`Event`] emitted on the server when the input of a client arrives for a tick that was already simulated
+/// with a predicted input (see [`MissingInputPolicy`](crate::server::config::MissingInputPolicy)).
+///
+/// The game can use it to correct the simulation (for example with
+/// [`InputBuffers::correct_input`](crate::server::input::native::InputBuffers::correct_input)), or ignore it.
+#[derive(Event, Debug, Clone, Copy, PartialEq)]
+pub struct LateInputEvent {
+    pub client_id: ClientId,
+    /// The local player (of the connection) that generated the input
+    pub local_player: LocalPlayerId,
+    /// The tick of the input
+    pub tick: Tick,
+    /// True if the predicted input was the same as the real input
+    pub matched: bool,
 }
 
 /// Bevy [`Event`] emitted on the server on the frame where an input message from a client is received
@@ -236,9 +333,17 @@ pub type ComponentInsertEvent<C> =
 /// Bevy [`Event`] emitted on the server on the frame where a ComponentRemove replication message is received
 pub type ComponentRemoveEvent<C> =
     crate::shared::events::components::ComponentRemoveEvent<C, ClientId>;
+/// Bevy [`Event`] emitted on the server on the frame where a replicated resource is inserted or updated
+pub type ResourceUpdateEvent<R> =
+    crate::shared::events::components::ResourceUpdateEvent<R, ClientId>;
+/// Bevy [`Event`] emitted on the server on the frame where a replicated resource is removed
+pub type ResourceRemoveEvent<R> =
+    crate::shared::events::components::ResourceRemoveEvent<R, ClientId>;
 
 /// Bevy [`Event`] emitted on the server on the frame where a (non-replication) message is received
 pub type MessageEvent<M> = crate::shared::events::components::MessageEvent<M, ClientId>;
+/// Bevy [`Event`] emitted on the server when a request is received from a client
+pub type RequestEvent<R> = crate::shared::request::RequestEvent<R, ClientId>;
 
 #[cfg(test)]
 mod tests {
@@ -0,0 +1,315 @@
+//! Run the server inside an existing (non-Bevy) application.
+//!
+//! A [`HeadlessServer`] owns a Bevy [`App`] with the [`MinimalPlugins`] and the [`ServerPlugins`], and drives
+//! its schedule at the server's tick rate on a dedicated thread. The rest of the application talks to
+//! the server through [`ServerHandle`]s, which can be cloned and used from any thread or async task:
+//! each operation is sent to the server thread and executed at the start of the next frame.
+//!
+//! ```rust,ignore
+//! let server = HeadlessServer::new(server_config).with_setup(|app| {
+//!     app.add_plugins(ProtocolPlugin);
+//! });
+//! let handle = server.handle();
+//! let shutdown = CancellationToken::new();
+//! tokio::spawn(server.run(shutdown.clone()));
+//!
+//! // from any task: query the server, or act on it
+//! let diagnostics = handle.diagnostics().await?;
+//! handle.send_message::<Channel1, _>(Message1("hello".to_string()), NetworkTarget::All)?;
+//! let entity = handle.spawn((Replicate::default(), Component1(1.0))).await?;
+//!
+//! // stop the server
+//! shutdown.cancel();
+//! ```
+use bevy::app::{App, PluginsState, ScheduleRunnerPlugin};
+use bevy::diagnostic::{DiagnosticPath, DiagnosticsStore};
+use bevy::ecs::system::RunSystemOnce;
+use bevy::prelude::{Bundle, Commands, Entity, NextState, PluginGroup, World};
+use bevy::state::app::StatesPlugin;
+use bevy::utils::{Duration, Instant};
+use bevy::MinimalPlugins;
+use crossbeam_channel::{Receiver, Sender};
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info};
+
+use crate::connection::server::ServerConnections;
+use crate::prelude::{Channel, ClientId, Message, NetworkTarget, Tick, TickManager};
+use crate::server::config::ServerConfig;
+use crate::server::connection::ConnectionManager;
+use crate::server::networking::{NetworkingState, ServerCommands};
+use crate::server::plugin::ServerPlugins;
+
+/// Operation sent by a [`ServerHandle`], executed on the server thread
+type ServerCommand = Box<dyn FnOnce(&mut World) + Send>;
+
+/// Function that configures the [`App`] of a [`HeadlessServer`]
+type SetupFn = Box<dyn FnOnce(&mut App) + Send>;
+
+#[derive(thiserror::Error, Debug)]
+pub enum HeadlessServerError {
+    #[error("could not spawn the server thread: {0}")]
+    Spawn(#[from] std::io::Error),
+    #[error("the server thread panicked")]
+    Panicked,
+}
+
+#[derive(thiserror::Error, Debug, Clone, Copy, PartialEq)]
+pub enum ServerHandleError {
+    #[error("the server is not running anymore")]
+    Stopped,
+}
+
+/// A server running on its own thread, for applications that don't use Bevy's runner.
+///
+/// The [`App`] is built on the server thread when [`HeadlessServer::run`] is called, since it cannot be
+/// moved across threads.
+pub struct HeadlessServer {
+    config: ServerConfig,
+    setup: Vec<SetupFn>,
+    command_sender: Sender<ServerCommand>,
+    command_receiver: Receiver<ServerCommand>,
+}
+
+impl HeadlessServer {
+    pub fn new(config: ServerConfig) -> Self {
+        let (command_sender, command_receiver) = crossbeam_channel::unbounded();
+        Self {
+            config,
+            setup: vec![],
+            command_sender,
+            command_receiver,
+        }
+    }
+
+    /// Add a function that configures the server [`App`] (protocol, game systems...).
+    ///
+    /// It is called on the server thread, after the [`ServerPlugins`] have been added.
+    pub fn with_setup(mut self, setup: impl FnOnce(&mut App) + Send + 'static) -> Self {
+        self.setup.push(Box::new(setup));
+        self
+    }
+
+    /// Create a handle to interact with the server.
+    ///
+    /// The operations sent before the server starts running are executed in its first frame.
+    pub fn handle(&self) -> ServerHandle {
+        ServerHandle {
+            commands: self.command_sender.clone(),
+        }
+    }
+
+    /// Start the server and run it until `shutdown` is cancelled.
+    ///
+    /// The server runs on a dedicated thread, one frame per tick. When `shutdown` is cancelled, the
+    /// server is stopped (disconnecting the clients) and the future completes.
+    pub async fn run(self, shutdown: CancellationToken) -> Result<(), HeadlessServerError> {
+        let (done_sender, done_receiver) = tokio::sync::oneshot::channel();
+        let thread = std::thread::Builder::new()
+            .name("lightyear-server".to_string())
+            .spawn(move || {
+                self.run_blocking(&shutdown);
+                let _ = done_sender.send(());
+            })?;
+        // the sender is dropped without sending if the thread panicked
+        let result = done_receiver.await;
+        let join_result = thread.join();
+        match (result, join_result) {
+            (Ok(()), Ok(())) => Ok(()),
+            _ => Err(HeadlessServerError::Panicked),
+        }
+    }
+
+    fn build_app(config: ServerConfig, setup: Vec<SetupFn>) -> App {
+        let mut app = App::new();
+        // we drive the schedule ourselves
+        app.add_plugins((
+            MinimalPlugins.build().disable::<ScheduleRunnerPlugin>(),
+            StatesPlugin,
+            ServerPlugins::new(config),
+        ));
+        for setup in setup {
+            setup(&mut app);
+        }
+        while app.plugins_state() == PluginsState::Adding {
+            bevy::tasks::tick_global_task_pools_on_main_thread();
+        }
+        app.finish();
+        app.cleanup();
+        app
+    }
+
+    fn run_blocking(self, shutdown: &CancellationToken) {
+        let frame_duration = self.config.shared.tick.tick_duration;
+        let mut app = Self::build_app(self.config, self.setup);
+        app.world_mut()
+            .run_system_once(|mut commands: Commands| commands.start_server());
+        info!("Headless server started");
+
+        let mut next_frame = Instant::now();
+        while !shutdown.is_cancelled() {
+            while let Ok(command) = self.command_receiver.try_recv() {
+                command(app.world_mut());
+            }
+            app.update();
+            if app.should_exit().is_some() {
+                break;
+            }
+            next_frame += frame_duration;
+            let now = Instant::now();
+            if next_frame > now {
+                std::thread::sleep(next_frame - now);
+            } else {
+                // we are late: don't try to catch up with a burst of frames
+                next_frame = now;
+            }
+        }
+
+        // run one more frame to stop the server, which disconnects the clients
+        app.world_mut()
+            .insert_resource(NextState::Pending(NetworkingState::Stopped));
+        app.update();
+        info!("Headless server stopped");
+    }
+}
+
+/// Thread-safe handle to a [`HeadlessServer`].
+///
+/// Operations are executed on the server thread at the start of the next frame. The methods that
+/// don't return a value don't wait for the operation to be executed; errors that happen on the server
+/// thread are logged.
+#[derive(Clone)]
+pub struct ServerHandle {
+    commands: Sender<ServerCommand>,
+}
+
+impl ServerHandle {
+    /// Run `f` on the server thread, without waiting for it to be executed
+    pub fn execute(
+        &self,
+        f: impl FnOnce(&mut World) + Send + 'static,
+    ) -> Result<(), ServerHandleError> {
+        self.commands
+            .send(Box::new(f))
+            .map_err(|_| ServerHandleError::Stopped)
+    }
+
+    /// Run `f` on the server thread and return its result
+    pub async fn query<T: Send + 'static>(
+        &self,
+        f: impl FnOnce(&mut World) -> T + Send + 'static,
+    ) -> Result<T, ServerHandleError> {
+        let (sender, receiver) = tokio::sync::oneshot::channel();
+        self.execute(move |world| {
+            let _ = sender.send(f(world));
+        })?;
+        // the command is dropped without being executed if the server stops
+        receiver.await.map_err(|_| ServerHandleError::Stopped)
+    }
+
+    /// Send a message to the clients in `target`
+    pub fn send_message<C: Channel, M: Message>(
+        &self,
+        message: M,
+        target: NetworkTarget,
+    ) -> Result<(), ServerHandleError> {
+        self.execute(move |world| {
+            let _ = world
+                .resource_mut::<ConnectionManager>()
+                .send_message_to_target::<C, M>(&message, target)
+                .inspect_err(|e| error!("Could not send message from a ServerHandle: {:?}", e));
+        })
+    }
+
+    /// Disconnect a client
+    pub fn kick(&self, client_id: ClientId) -> Result<(), ServerHandleError> {
+        self.execute(move |world| {
+            let _ = world
+                .resource_mut::<ServerConnections>()
+                .disconnect(client_id)
+                .inspect_err(|e| error!("Could not disconnect client {:?}: {:?}", client_id, e));
+        })
+    }
+
+    /// Disconnect a client, and send it the reason of the disconnection (for example "banned")
+    pub fn kick_with_reason(
+        &self,
+        client_id: ClientId,
+        reason: impl Into<String>,
+    ) -> Result<(), ServerHandleError> {
+        let reason = reason.into();
+        self.execute(move |world| {
+            let _ = world
+                .resource_mut::<ServerConnections>()
+                .disconnect_with_reason(client_id, reason)
+                .inspect_err(|e| error!("Could not disconnect client {:?}: {:?}", client_id, e));
+        })
+    }
+
+    /// Spawn an entity; use a bundle containing [`Replicate`](crate::prelude::server::Replicate)
+    /// to replicate it to the clients
+    pub async fn spawn<B: Bundle>(&self, bundle: B) -> Result<Entity, ServerHandleError> {
+        self.query(move |world| world.spawn(bundle).id()).await
+    }
+
+    /// Despawn an entity, without waiting for it to be despawned.
+    ///
+    /// Replicated entities are despawned on the clients as well.
+    pub fn despawn(&self, entity: Entity) -> Result<(), ServerHandleError> {
+        self.execute(move |world| {
+            world.despawn(entity);
+        })
+    }
+
+    /// Get the current state of the server connections and the latest value of the diagnostics
+    pub async fn diagnostics(&self) -> Result<ServerDiagnostics, ServerHandleError> {
+        self.query(|world| {
+            let clients = world
+                .resource::<ConnectionManager>()
+                .connections
+                .iter()
+                .map(|(client_id, connection)| ClientDiagnostics {
+                    client_id: *client_id,
+                    rtt: connection.rtt(),
+                    jitter: connection.jitter(),
+                })
+                .collect();
+            let measurements = world
+                .get_resource::<DiagnosticsStore>()
+                .map(|store| {
+                    store
+                        .iter()
+                        .filter_map(|diagnostic| {
+                            diagnostic
+                                .smoothed()
+                                .map(|value| (diagnostic.path().clone(), value))
+                        })
+                        .collect()
+                })
+                .unwrap_or_default();
+            ServerDiagnostics {
+                tick: world.resource::<TickManager>().tick(),
+                clients,
+                measurements,
+            }
+        })
+        .await
+    }
+}
+
+/// Snapshot of the state of a [`HeadlessServer`], returned by [`ServerHandle::diagnostics`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct ServerDiagnostics {
+    /// Current tick of the server
+    pub tick: Tick,
+    /// Clients that are currently connected
+    pub clients: Vec<ClientDiagnostics>,
+    /// Smoothed value of each diagnostic that has measurements
+    pub measurements: Vec<(DiagnosticPath, f64)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClientDiagnostics {
+    pub client_id: ClientId,
+    pub rtt: Duration,
+    pub jitter: Duration,
+}
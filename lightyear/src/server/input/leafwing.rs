@@ -12,9 +12,11 @@ use crate::prelude::{server::is_started, InputMessage, MessageRegistry, Mode, Ti
 use crate::protocol::message::MessageKind;
 use crate::serialize::reader::Reader;
 use crate::server::config::ServerConfig;
+use crate::server::behavior::ClientBehaviorHistory;
 use crate::server::connection::ConnectionManager;
 use crate::shared::replication::network_target::NetworkTarget;
 use crate::shared::sets::{InternalMainSet, ServerMarker};
+use crate::shared::warnings::NetworkWarning;
 
 pub struct LeafwingInputPlugin<A> {
     marker: std::marker::PhantomData<A>,
@@ -92,8 +94,10 @@ fn add_action_diff_buffer<A: LeafwingUserAction>(
 
 /// Read the input messages from the server events to update the InputBuffers
 fn receive_input_message<A: LeafwingUserAction>(
+    tick_manager: Res<TickManager>,
     message_registry: Res<MessageRegistry>,
     mut connection_manager: ResMut<ConnectionManager>,
+    mut behavior_history: Option<ResMut<ClientBehaviorHistory>>,
     // TODO: currently we do not handle entities that are controlled by multiple clients
     mut query: Query<Option<&mut InputBuffer<A>>>,
     mut commands: Commands,
@@ -122,6 +126,12 @@ fn receive_input_message<A: LeafwingUserAction>(
                 ) {
                     Ok(message) => {
                         debug!(?client_id, action = ?A::short_type_path(), ?message.end_tick, ?message.diffs, "received input message");
+                        connection
+                            .input_margins
+                            .record(message.end_tick, tick_manager.tick());
+                        if let Some(history) = behavior_history.as_mut() {
+                            history.record_input(*client_id, message.end_tick, tick_manager.tick());
+                        }
                         // TODO: UPDATE THIS
                         for (target, start, diffs) in &message.diffs {
                             match target {
@@ -187,9 +197,12 @@ fn receive_input_message<A: LeafwingUserAction>(
                         }
                         events.send(MessageEvent::new(message, *client_id));
                     }
-                    Err(e) => {
-                        error!(?e, "could not deserialize leafwing input message");
-                    }
+                    Err(e) => connection.warnings.report(
+                        NetworkWarning::InvalidInputMessage,
+                        format_args!(
+                            "could not deserialize leafwing input message from {client_id:?}: {e:?}"
+                        ),
+                    ),
                 }
             }
         }
@@ -226,10 +239,10 @@ mod tests {
     use crate::inputs::leafwing::input_buffer::InputBuffer;
     use leafwing_input_manager::prelude::ActionState;
 
-    use crate::prelude::client;
     use crate::prelude::server::*;
+    use crate::prelude::{client, ClientId, LocalPlayerId, NetworkTarget};
     use crate::tests::protocol::*;
-    use crate::tests::stepper::{BevyStepper, Step};
+    use crate::tests::stepper::{BevyStepper, Step, TEST_CLIENT_ID};
 
     #[test]
     fn test_leafwing_inputs() {
@@ -333,4 +346,106 @@ mod tests {
             .unwrap()
             .released(&LeafwingInput1::Jump));
     }
+
+    /// One connection drives two entities, each with the ActionState of a different local player
+    #[test]
+    fn test_leafwing_inputs_local_players() {
+        let mut stepper = BevyStepper::default();
+        let client_id = ClientId::Netcode(TEST_CLIENT_ID);
+        let spawn_controlled = |stepper: &mut BevyStepper, local_player| {
+            stepper
+                .server_app
+                .world_mut()
+                .spawn((
+                    ActionState::<LeafwingInput1>::default(),
+                    Replicate {
+                        controlled_by: ControlledBy {
+                            target: NetworkTarget::Single(client_id),
+                            local_player,
+                            ..default()
+                        },
+                        ..default()
+                    },
+                ))
+                .id()
+        };
+        let server_entity_0 = spawn_controlled(&mut stepper, LocalPlayerId(0));
+        let server_entity_1 = spawn_controlled(&mut stepper, LocalPlayerId(1));
+        stepper.frame_step();
+        stepper.frame_step();
+
+        // each local player uses a different key
+        let client_entity = |stepper: &BevyStepper, server_entity| {
+            *stepper
+                .client_app
+                .world()
+                .resource::<client::ConnectionManager>()
+                .replication_receiver
+                .remote_entity_map
+                .get_local(server_entity)
+                .unwrap()
+        };
+        let client_entity_0 = client_entity(&stepper, server_entity_0);
+        let client_entity_1 = client_entity(&stepper, server_entity_1);
+        assert_eq!(
+            stepper
+                .client_app
+                .world()
+                .get::<LocalPlayerId>(client_entity_1),
+            Some(&LocalPlayerId(1))
+        );
+        stepper
+            .client_app
+            .world_mut()
+            .entity_mut(client_entity_0)
+            .insert(InputMap::<LeafwingInput1>::new([(
+                LeafwingInput1::Jump,
+                KeyCode::KeyA,
+            )]));
+        stepper
+            .client_app
+            .world_mut()
+            .entity_mut(client_entity_1)
+            .insert(InputMap::<LeafwingInput1>::new([(
+                LeafwingInput1::Jump,
+                KeyCode::KeyB,
+            )]));
+        stepper.frame_step();
+
+        // only the first local player presses the key
+        stepper
+            .client_app
+            .world_mut()
+            .resource_mut::<ButtonInput<KeyCode>>()
+            .press(KeyCode::KeyA);
+        stepper.frame_step();
+        let client_tick = stepper.client_tick();
+        let server_input = |stepper: &BevyStepper, server_entity, tick| {
+            stepper
+                .server_app
+                .world()
+                .get::<InputBuffer<LeafwingInput1>>(server_entity)
+                .unwrap()
+                .get(tick)
+                .unwrap()
+                .pressed(&LeafwingInput1::Jump)
+        };
+        assert!(server_input(&stepper, server_entity_0, client_tick));
+        assert!(!server_input(&stepper, server_entity_1, client_tick));
+
+        // then only the second local player presses the key
+        stepper
+            .client_app
+            .world_mut()
+            .resource_mut::<ButtonInput<KeyCode>>()
+            .release(KeyCode::KeyA);
+        stepper
+            .client_app
+            .world_mut()
+            .resource_mut::<ButtonInput<KeyCode>>()
+            .press(KeyCode::KeyB);
+        stepper.frame_step();
+        assert!(!server_input(&stepper, server_entity_0, client_tick + 1));
+        assert!(server_input(&stepper, server_entity_1, client_tick + 1));
+    }
 }
@@ -1,17 +1,37 @@
 //! Handles client-generated inputs
+//!
+//! If multiple players share the same connection, the client sends a separate stream of inputs for each
+//! [`LocalPlayerId`]. Every tick, an [`InputEvent`] is emitted for each input of each stream (or a single event
+//! without input if the stream has no input for the tick), with the entity whose [`ControlledBy`] matches
+//! the client and the local player.
+//!
+//! If the [`RewindPlugin`](crate::server::rewind::RewindPlugin) is enabled, the inputs that were applied
+//! on each tick are kept in a history, so that they can be applied again when the simulation is replayed.
+//!
+//! If the input of a client for a tick has not arrived when the tick is simulated, the server predicts it
+//! according to the [`MissingInputPolicy`] and marks the [`InputEvent`]s as predicted. A [`LateInputEvent`] is
+//! emitted if the real input arrives later.
+use std::collections::VecDeque;
+
 use bevy::prelude::*;
 use bevy::utils::HashMap;
 
-use crate::inputs::native::input_buffer::InputBuffer;
+use crate::inputs::native::input_buffer::{InputBuffer, InputData, TickInputs};
 use crate::inputs::native::InputMessage;
-use crate::prelude::server::DisconnectEvent;
+use crate::inputs::LocalPlayerId;
+use crate::prelude::server::{ControlledBy, ControlledEntities, DisconnectEvent};
 use crate::prelude::{server::is_started, ClientId, MessageRegistry, TickManager, UserAction};
 use crate::protocol::message::MessageKind;
 use crate::serialize::reader::Reader;
+use crate::server::behavior::ClientBehaviorHistory;
+use crate::server::config::{MissingInputPolicy, ServerConfig};
 use crate::server::connection::ConnectionManager;
-use crate::server::events::InputEvent;
+use crate::server::events::{InputEvent, LateInputEvent};
+use crate::server::rewind::SnapshotConfig;
+use crate::shared::tick_manager::Tick;
 use crate::shared::replication::network_target::NetworkTarget;
 use crate::shared::sets::{InternalMainSet, ServerMarker};
+use crate::shared::warnings::NetworkWarning;
 
 pub struct InputPlugin<A> {
     _marker: std::marker::PhantomData<A>,
@@ -19,19 +39,51 @@ pub struct InputPlugin<A> {
 
 #[derive(Resource, Debug)]
 pub struct InputBuffers<A> {
-    /// The first element stores the last input we have received from the client.
-    /// In case we are missing the client input for a tick, we will fallback to using this.
-    buffers: HashMap<ClientId, (Option<A>, InputBuffer<A>)>,
+    /// The first element stores the last inputs we have received from the client.
+    /// In case we are missing the client inputs for a tick, we will fallback to using them.
+    buffers: HashMap<(ClientId, LocalPlayerId), (TickInputs<A>, InputBuffer<TickInputs<A>>)>,
+    /// The inputs that were applied on the most recent ticks, used to replay the simulation.
+    /// Only populated if the [`SnapshotConfig`] resource exists.
+    history: HashMap<(ClientId, LocalPlayerId), VecDeque<(Tick, TickInputs<A>)>>,
+    /// The inputs that were predicted because the inputs of the client were missing, and whose real
+    /// inputs have not arrived yet
+    predicted: HashMap<(ClientId, LocalPlayerId), VecDeque<(Tick, TickInputs<A>)>>,
 }
 
+/// Maximum number of predicted ticks that are kept for each local player to detect the late inputs
+const MAX_PREDICTED_TICKS: usize = 64;
+
 impl<A> Default for InputBuffers<A> {
     fn default() -> Self {
         Self {
             buffers: HashMap::default(),
+            history: HashMap::default(),
+            predicted: HashMap::default(),
         }
     }
 }
 
+impl<A: UserAction> InputBuffers<A> {
+    /// Replace the inputs that were applied for a local player of a client on a past tick.
+    ///
+    /// All the inputs recorded for the tick are replaced by `input`. The corrected input is used the
+    /// next time the tick is re-simulated (see [`rewind_to`](crate::server::rewind::rewind_to)).
+    /// Returns the inputs that were previously recorded, or `None` if the tick is not in the history.
+    pub fn correct_input(
+        &mut self,
+        client_id: ClientId,
+        local_player: LocalPlayerId,
+        tick: Tick,
+        input: Option<A>,
+    ) -> Option<TickInputs<A>> {
+        self.history
+            .get_mut(&(client_id, local_player))?
+            .iter_mut()
+            .find(|(t, _)| *t == tick)
+            .map(|(_, recorded)| std::mem::replace(recorded, input.into_iter().collect()))
+    }
+}
+
 impl<A> Default for InputPlugin<A> {
     fn default() -> Self {
         Self {
@@ -56,6 +108,7 @@ impl<A: UserAction> Plugin for InputPlugin<A> {
         app.init_resource::<InputBuffers<A>>();
         // EVENTS
         app.add_event::<InputEvent<A>>();
+        app.add_event::<LateInputEvent>();
         // SETS
         app.configure_sets(
             PreUpdate,
@@ -93,14 +146,26 @@ fn handle_client_disconnect<A: UserAction>(
     trigger: Trigger<DisconnectEvent>,
     mut input_buffers: ResMut<InputBuffers<A>>,
 ) {
-    input_buffers.buffers.remove(&trigger.event().client_id);
+    let client_id = trigger.event().client_id;
+    input_buffers
+        .buffers
+        .retain(|(buffer_client_id, _), _| *buffer_client_id != client_id);
+    input_buffers
+        .history
+        .retain(|(buffer_client_id, _), _| *buffer_client_id != client_id);
+    input_buffers
+        .predicted
+        .retain(|(buffer_client_id, _), _| *buffer_client_id != client_id);
 }
 
 /// Read the message received from the client and emit the MessageEvent event
 fn receive_input_message<A: UserAction>(
+    tick_manager: Res<TickManager>,
     message_registry: Res<MessageRegistry>,
     mut connection_manager: ResMut<ConnectionManager>,
+    mut behavior_history: Option<ResMut<ClientBehaviorHistory>>,
     mut input_buffers: ResMut<InputBuffers<A>>,
+    mut late_input_events: EventWriter<LateInputEvent>,
 ) {
     let kind = MessageKind::of::<InputMessage<A>>();
     let Some(net) = message_registry.kind_map.net_id(&kind).copied() else {
@@ -123,12 +188,44 @@ fn receive_input_message<A: UserAction>(
                 ) {
                     Ok(message) => {
                         debug!("Received input message: {:?}", message);
-                        input_buffers
-                            .buffers
-                            .entry(*client_id)
-                            .or_default()
-                            .1
-                            .update_from_message(message);
+                        connection
+                            .input_margins
+                            .record(message.end_tick, tick_manager.tick());
+                        if let Some(history) = behavior_history.as_mut() {
+                            history.record_input(*client_id, message.end_tick, tick_manager.tick());
+                        }
+                        for (local_player, inputs) in message.inputs {
+                            // compare the inputs that arrived too late with the predicted inputs
+                            if let Some(predicted) =
+                                input_buffers.predicted.get_mut(&(*client_id, local_player))
+                            {
+                                // the first ticks of the message can be absent because the client already
+                                // discarded their inputs, so they are not compared
+                                for (tick, input) in InputData::resolve(message.end_tick, &inputs)
+                                    .into_iter()
+                                    .skip_while(|(_, input)| input.is_none())
+                                {
+                                    let Some(index) =
+                                        predicted.iter().position(|(t, _)| *t == tick)
+                                    else {
+                                        continue;
+                                    };
+                                    let (_, predicted_input) = predicted.remove(index).unwrap();
+                                    late_input_events.send(LateInputEvent {
+                                        client_id: *client_id,
+                                        local_player,
+                                        tick,
+                                        matched: input.unwrap_or_default() == predicted_input,
+                                    });
+                                }
+                            }
+                            input_buffers
+                                .buffers
+                                .entry((*client_id, local_player))
+                                .or_default()
+                                .1
+                                .update_from_message(message.end_tick, inputs);
+                        }
                         if target != NetworkTarget::None {
                             // NOTE: we can re-send the same bytes directly because InputMessage does not include any Entity references
                             connection.messages_to_rebroadcast.push((
@@ -139,9 +236,10 @@ fn receive_input_message<A: UserAction>(
                             ));
                         }
                     }
-                    Err(e) => {
-                        error!("Error deserializing input message: {:?}", e);
-                    }
+                    Err(e) => connection.warnings.report(
+                        NetworkWarning::InvalidInputMessage,
+                        format_args!("Error deserializing input message from {client_id:?}: {e:?}"),
+                    ),
                 }
             }
         }
@@ -153,40 +251,102 @@ fn receive_input_message<A: UserAction>(
 // Do it in this system because we want an input for every tick
 fn write_input_event<A: UserAction>(
     tick_manager: Res<TickManager>,
+    connection_manager: Res<ConnectionManager>,
     mut input_buffers: ResMut<InputBuffers<A>>,
     mut input_events: EventWriter<InputEvent<A>>,
+    client_query: Query<&ControlledEntities>,
+    controlled_query: Query<&ControlledBy>,
+    snapshot_config: Option<Res<SnapshotConfig>>,
+    config: Res<ServerConfig>,
 ) {
     let tick = tick_manager.tick();
-    input_buffers
-        .buffers
+    let history_len = snapshot_config.map_or(0, |config| config.input_history_len());
+    let InputBuffers {
+        buffers,
+        history,
+        predicted,
+    } = input_buffers.as_mut();
+    buffers
         .iter_mut()
-        .for_each(move |(client_id, (last_input, input_buffer))| {
-            debug!(?input_buffer, ?tick, ?client_id, "input buffer for client");
-            let received_input = input_buffer.pop(tick);
-            let fallback = received_input.is_none();
-
-            // NOTE: if there is no input for this tick, we should use the last input that we have
-            //  as a best-effort fallback.
-            let input = match received_input {
-                None => last_input.clone(),
-                Some(i) => {
-                    *last_input = Some(i.clone());
-                    Some(i)
+        .for_each(|((client_id, local_player), (last_input, input_buffer))| {
+            debug!(
+                ?input_buffer,
+                ?tick,
+                ?client_id,
+                ?local_player,
+                "input buffer for client"
+            );
+            // if the tick is already in the history, we are replaying the simulation:
+            // apply the same input as the first time
+            let replayed_inputs = history
+                .get(&(*client_id, *local_player))
+                .and_then(|inputs| inputs.iter().find(|(t, _)| *t == tick))
+                .map(|(_, inputs)| inputs.clone());
+            let (tick_inputs, fallback) = if let Some(inputs) = replayed_inputs {
+                (inputs, false)
+            } else {
+                let received_inputs = input_buffer.pop(tick);
+                let fallback = received_inputs.is_none();
+
+                // if there is no input for this tick, predict it according to the policy
+                let tick_inputs = match received_inputs {
+                    None => match config.missing_input {
+                        MissingInputPolicy::RepeatLast => last_input.clone(),
+                        MissingInputPolicy::None => TickInputs::new(),
+                    },
+                    Some(i) => {
+                        *last_input = i.clone();
+                        i
+                    }
+                };
+                if fallback {
+                    // TODO: do not log this while clients are syncing..
+                    debug!(
+                    ?client_id,
+                    ?tick,
+                    fallback_input = ?&tick_inputs,
+                    "Missed client input!"
+                    );
+                    // keep the prediction to compare it with the real input if it arrives late
+                    let predicted = predicted.entry((*client_id, *local_player)).or_default();
+                    predicted.push_back((tick, tick_inputs.clone()));
+                    if predicted.len() > MAX_PREDICTED_TICKS {
+                        predicted.pop_front();
+                    }
                 }
+                if history_len > 0 {
+                    let inputs = history.entry((*client_id, *local_player)).or_default();
+                    if inputs.back().map_or(true, |(t, _)| *t < tick) {
+                        inputs.push_back((tick, tick_inputs.clone()));
+                    }
+                    while inputs
+                        .front()
+                        .is_some_and(|(t, _)| tick - *t >= history_len as i16)
+                    {
+                        inputs.pop_front();
+                    }
+                }
+                (tick_inputs, fallback)
             };
-            if fallback {
-                // TODO: do not log this while clients are syncing..
-                debug!(
-                ?client_id,
-                ?tick,
-                fallback_input = ?&input,
-                "Missed client input!"
-                )
-            }
             // TODO: We should also let the user know that it needs to send inputs a bit earlier so that
             //  we have more of a buffer. Send a SyncMessage to tell the user to speed up?
             //  See Overwatch GDC video
-            input_events.send(InputEvent::new(input, *client_id));
+            // find the entity controlled by this local player of the client
+            let entity = connection_manager
+                .client_entity(*client_id)
+                .ok()
+                .and_then(|client_entity| client_query.get(client_entity).ok())
+                .and_then(|controlled_entities| {
+                    controlled_entities.keys().copied().find(|entity| {
+                        controlled_query
+                            .get(*entity)
+                            .is_ok_and(|controlled_by| controlled_by.local_player == *local_player)
+                    })
+                });
+            input_events.send_batch(
+                InputEvent::from_tick_inputs(tick_inputs, *client_id, *local_player, entity)
+                    .map(|event| event.with_predicted(fallback)),
+            );
         });
 }
 
@@ -195,3 +355,205 @@ fn write_input_event<A: UserAction>(
 fn clear_input_events<A: UserAction>(mut input_events: EventReader<InputEvent<A>>) {
     input_events.clear();
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::client::{self, InputManager};
+    use crate::prelude::server::Replicate;
+    use crate::tests::protocol::*;
+    use crate::tests::stepper::{BevyStepper, Step, TEST_CLIENT_ID};
+
+    fn buffer_local_player_inputs(
+        mut input_manager: ResMut<InputManager<MyInput>>,
+        tick_manager: Res<TickManager>,
+    ) {
+        let tick = tick_manager.tick();
+        input_manager.add_local_player_input(LocalPlayerId(0), MyInput(1), tick);
+        input_manager.add_local_player_input(LocalPlayerId(1), MyInput(2), tick);
+    }
+
+    fn apply_inputs(
+        mut query: Query<&mut Component1>,
+        mut events: EventReader<InputEvent<MyInput>>,
+    ) {
+        for event in events.read() {
+            if let (Some(input), Some(entity)) = (event.input(), event.entity()) {
+                if let Ok(mut component) = query.get_mut(entity) {
+                    component.0 += input.0 as f32;
+                }
+            }
+        }
+    }
+
+    /// One connection drives two entities, one for each local player
+    #[test]
+    fn test_local_players_inputs() {
+        let mut stepper = BevyStepper::default();
+        let client_id = ClientId::Netcode(TEST_CLIENT_ID);
+        let spawn_controlled = |stepper: &mut BevyStepper, local_player| {
+            stepper
+                .server_app
+                .world_mut()
+                .spawn((
+                    Component1(0.0),
+                    Replicate {
+                        controlled_by: ControlledBy {
+                            target: NetworkTarget::Single(client_id),
+                            local_player,
+                            ..default()
+                        },
+                        ..default()
+                    },
+                ))
+                .id()
+        };
+        let server_entity_0 = spawn_controlled(&mut stepper, LocalPlayerId(0));
+        let server_entity_1 = spawn_controlled(&mut stepper, LocalPlayerId(1));
+        stepper.frame_step();
+        stepper.frame_step();
+
+        // the client knows which local player controls each entity
+        let client_entity_1 = *stepper
+            .client_app
+            .world()
+            .resource::<client::ConnectionManager>()
+            .replication_receiver
+            .remote_entity_map
+            .get_local(server_entity_1)
+            .unwrap();
+        assert_eq!(
+            stepper
+                .client_app
+                .world()
+                .get::<LocalPlayerId>(client_entity_1),
+            Some(&LocalPlayerId(1))
+        );
+
+        stepper.client_app.add_systems(
+            FixedPreUpdate,
+            buffer_local_player_inputs.in_set(client::InputSystemSet::BufferInputs),
+        );
+        stepper.server_app.add_systems(FixedUpdate, apply_inputs);
+        for _ in 0..20 {
+            stepper.frame_step();
+        }
+
+        // each entity only received the inputs of its own local player
+        let value_0 = stepper
+            .server_app
+            .world()
+            .get::<Component1>(server_entity_0)
+            .unwrap()
+            .0;
+        let value_1 = stepper
+            .server_app
+            .world()
+            .get::<Component1>(server_entity_1)
+            .unwrap()
+            .0;
+        assert!(value_0 > 0.0);
+        assert_eq!(value_1, 2.0 * value_0);
+    }
+
+    fn buffer_multiple_inputs(
+        mut input_manager: ResMut<InputManager<MyInput>>,
+        tick_manager: Res<TickManager>,
+    ) {
+        let tick = tick_manager.tick();
+        input_manager.add_input(MyInput(1), tick);
+        input_manager.add_input(MyInput(10), tick);
+    }
+
+    #[derive(Resource, Default)]
+    struct ReceivedInputs(Vec<(Tick, Vec<i16>)>);
+
+    fn record_inputs(
+        tick_manager: Res<TickManager>,
+        mut received: ResMut<ReceivedInputs>,
+        mut events: EventReader<InputEvent<MyInput>>,
+    ) {
+        let inputs: Vec<_> = events
+            .read()
+            .filter_map(|event| event.input().as_ref().map(|input| input.0))
+            .collect();
+        if !inputs.is_empty() {
+            received.0.push((tick_manager.tick(), inputs));
+        }
+    }
+
+    /// All the inputs added by the client for a tick are emitted on the server, in order
+    #[test]
+    fn test_multiple_inputs_per_tick() {
+        let mut stepper = BevyStepper::default();
+        stepper.client_app.add_systems(
+            FixedPreUpdate,
+            buffer_multiple_inputs.in_set(client::InputSystemSet::BufferInputs),
+        );
+        stepper.server_app.init_resource::<ReceivedInputs>();
+        stepper.server_app.add_systems(FixedUpdate, record_inputs);
+        for _ in 0..20 {
+            stepper.frame_step();
+        }
+
+        let received = &stepper.server_app.world().resource::<ReceivedInputs>().0;
+        assert!(!received.is_empty());
+        for (tick, inputs) in received {
+            assert_eq!(inputs, &vec![1, 10], "unexpected inputs on tick {tick:?}");
+        }
+    }
+
+    fn buffer_tick_inputs(
+        mut input_manager: ResMut<InputManager<MyInput>>,
+        tick_manager: Res<TickManager>,
+    ) {
+        let tick = tick_manager.tick();
+        input_manager.add_input(MyInput(tick.0 as i16), tick);
+    }
+
+    /// Increasing the input delay during the session does not leave gaps in the inputs received by the server
+    #[test]
+    fn test_change_input_delay() {
+        let mut stepper = BevyStepper::default();
+        stepper.client_app.add_systems(
+            FixedPreUpdate,
+            buffer_tick_inputs.in_set(client::InputSystemSet::BufferInputs),
+        );
+        stepper.server_app.init_resource::<ReceivedInputs>();
+        stepper.server_app.add_systems(FixedUpdate, record_inputs);
+        for _ in 0..20 {
+            stepper.frame_step();
+        }
+        stepper
+            .client_app
+            .world_mut()
+            .resource_mut::<client::ConnectionManager>()
+            .set_input_delay_ticks(Some(3));
+        for _ in 0..20 {
+            stepper.frame_step();
+        }
+
+        let received = &stepper.server_app.world().resource::<ReceivedInputs>().0;
+        let (first_tick, first_inputs) = received.first().unwrap();
+        let (last_tick, last_inputs) = received.last().unwrap();
+        // the inputs were applied without delay, then with 3 ticks of delay
+        assert_eq!(first_inputs, &vec![first_tick.0 as i16]);
+        assert_eq!(last_inputs, &vec![(*last_tick - 3).0 as i16]);
+        for window in received.windows(2) {
+            let [(tick, inputs), (next_tick, next_inputs)] = window else {
+                unreachable!()
+            };
+            // every tick has a single input
+            assert_eq!(*next_tick, *tick + 1);
+            assert_eq!(next_inputs.len(), 1);
+            // every input is applied, the inputs of the last tick before the change are repeated
+            // on the ticks skipped by the increase of the input delay
+            assert!([0, 1].contains(&(next_inputs[0] - inputs[0])));
+        }
+        let repeated = received
+            .windows(2)
+            .filter(|window| window[0].1 == window[1].1)
+            .count();
+        assert_eq!(repeated, 3);
+    }
+}
@@ -1,17 +1,17 @@
 use super::*;
-use crate::prelude::CompressionConfig;
 use crate::server::io::transport::{ServerTransportBuilder, ServerTransportBuilderEnum};
+use crate::shared::clock::NetworkClock;
 use crate::transport::channels::Channels;
 use crate::transport::config::SharedIoConfig;
 use crate::transport::dummy::DummyIo;
 use crate::transport::io::IoStats;
-#[cfg(feature = "zstd")]
-use crate::transport::middleware::compression::zstd::compression::ZstdCompressor;
-#[cfg(feature = "zstd")]
-use crate::transport::middleware::compression::zstd::decompression::ZstdDecompressor;
+use crate::transport::middleware::compression::negotiation::{self, CompressionRole};
+use crate::transport::middleware::compression::CompressionCapabilities;
 use crate::transport::middleware::conditioner::LinkConditioner;
 use crate::transport::middleware::PacketReceiverWrapper;
 use crate::transport::udp::UdpSocketBuilder;
+#[cfg(all(feature = "websocket", not(target_family = "wasm")))]
+use crate::transport::websocket::server::WebSocketServerSocketBuilder;
 
 use crate::transport::BoxedReceiver;
 use crate::transport::Transport;
@@ -22,7 +22,12 @@ use std::net::IpAddr;
 pub enum ServerTransport {
     /// Use a [`UdpSocket`](std::net::UdpSocket)
     UdpSocket(SocketAddr),
-
+    /// Listen for WebSocket connections, for example from browsers that do not support WebTransport.
+    ///
+    /// Each connection is identified by a fake [`SocketAddr`] instead of the address of the peer,
+    /// so that clients behind the same NAT are kept apart.
+    #[cfg(all(feature = "websocket", not(target_family = "wasm")))]
+    WebSocketServer { server_addr: SocketAddr },
     /// Use a crossbeam_channel as a transport. This is useful for testing.
     /// This is server-only: each tuple corresponds to a different client.
     Channels {
@@ -44,6 +49,12 @@ impl Clone for ServerTransport {
             ServerTransport::UdpSocket(__self_0) => {
                 ServerTransport::UdpSocket(Clone::clone(__self_0))
             }
+            #[cfg(all(feature = "websocket", not(target_family = "wasm")))]
+            ServerTransport::WebSocketServer {
+                server_addr: __self_0,
+            } => ServerTransport::WebSocketServer {
+                server_addr: Clone::clone(__self_0),
+            },
 
             ServerTransport::Channels { channels: __self_0 } => ServerTransport::Channels {
                 channels: Clone::clone(__self_0),
@@ -59,6 +70,12 @@ impl ServerTransport {
             ServerTransport::UdpSocket(addr) => {
                 ServerTransportBuilderEnum::UdpSocket(UdpSocketBuilder { local_addr: addr })
             }
+            #[cfg(all(feature = "websocket", not(target_family = "wasm")))]
+            ServerTransport::WebSocketServer { server_addr } => {
+                ServerTransportBuilderEnum::WebSocketServer(WebSocketServerSocketBuilder {
+                    server_addr,
+                })
+            }
 
             ServerTransport::Channels { channels } => {
                 ServerTransportBuilderEnum::Channels(Channels::new(channels))
@@ -76,38 +93,27 @@ impl Default for ServerTransport {
 
 impl SharedIoConfig<ServerTransport> {
     pub fn start(self) -> Result<Io> {
+        self.start_with_clock(&NetworkClock::default())
+    }
+
+    /// Start the transport, the link conditioner reads the time from `clock`
+    pub fn start_with_clock(self, clock: &NetworkClock) -> Result<Io> {
         let (transport, state, io_rx, network_tx) = self.transport.build().start()?;
         let local_addr = transport.local_addr();
-        #[allow(unused_mut)]
-        let (mut sender, receiver) = transport.split();
-        #[allow(unused_mut)]
-        let mut receiver: BoxedReceiver = if let Some(conditioner_config) = self.conditioner {
-            let conditioner = LinkConditioner::new(conditioner_config);
+        let (sender, receiver) = transport.split();
+        let receiver: BoxedReceiver = if let Some(conditioner_config) = self.conditioner {
+            let conditioner = LinkConditioner::new(conditioner_config, clock.clone());
             Box::new(conditioner.wrap(receiver))
         } else {
             Box::new(receiver)
         };
-        match self.compression {
-            CompressionConfig::None => {}
-            #[cfg(feature = "zstd")]
-            CompressionConfig::Zstd { level } => {
-                use crate::transport::middleware::PacketSenderWrapper;
-                let compressor = ZstdCompressor::new(level);
-                sender = Box::new(compressor.wrap(sender));
-                let decompressor = ZstdDecompressor::new();
-                receiver = Box::new(decompressor.wrap(receiver));
-            }
-            #[cfg(feature = "lz4")]
-            CompressionConfig::Lz4 => {
-                use crate::transport::middleware::PacketSenderWrapper;
-                let compressor =
-                    crate::transport::middleware::compression::lz4::Compressor::default();
-                sender = Box::new(compressor.wrap(sender));
-                let decompressor =
-                    crate::transport::middleware::compression::lz4::Decompressor::default();
-                receiver = Box::new(decompressor.wrap(receiver));
-            }
-        }
+        let (sender, receiver) = negotiation::wrap(
+            sender,
+            receiver,
+            self.compression,
+            CompressionRole::Server,
+            CompressionCapabilities::supported(),
+        );
         Ok(BaseIo {
             local_addr,
             sender,
@@ -4,6 +4,8 @@ use crate::transport::dummy::DummyIo;
 use crate::transport::error::Result;
 use crate::transport::io::IoState;
 use crate::transport::udp::{UdpSocket, UdpSocketBuilder};
+#[cfg(all(feature = "websocket", not(target_family = "wasm")))]
+use crate::transport::websocket::server::{WebSocketServerSocket, WebSocketServerSocketBuilder};
 
 use enum_dispatch::enum_dispatch;
 
@@ -23,7 +25,8 @@ pub(crate) trait ServerTransportBuilder: Send + Sync {
 #[enum_dispatch(ServerTransportBuilder)]
 pub(crate) enum ServerTransportBuilderEnum {
     UdpSocket(UdpSocketBuilder),
-
+    #[cfg(all(feature = "websocket", not(target_family = "wasm")))]
+    WebSocketServer(WebSocketServerSocketBuilder),
     Channels(Channels),
     Dummy(DummyIo),
 }
@@ -32,7 +35,8 @@ pub(crate) enum ServerTransportBuilderEnum {
 #[enum_dispatch(Transport)]
 pub(crate) enum ServerTransportEnum {
     UdpSocket(UdpSocket),
-
+    #[cfg(all(feature = "websocket", not(target_family = "wasm")))]
+    WebSocketServer(WebSocketServerSocket),
     Channels(Channels),
     Dummy(DummyIo),
 }
@@ -1,21 +1,26 @@
 use std::ops::DerefMut;
 
 use crate::prelude::{server::is_started, Message};
-use crate::protocol::message::{MessageKind, MessageRegistry};
+use crate::protocol::message::{MessageError, MessageKind, MessageRegistry};
 use crate::serialize::reader::Reader;
 use crate::server::connection::ConnectionManager;
 use crate::server::events::MessageEvent;
+use crate::shared::replication::deferred::bound_deferred_messages;
 use crate::shared::replication::network_target::NetworkTarget;
 use crate::shared::sets::{InternalMainSet, ServerMarker};
 use bevy::app::{App, PreUpdate};
+use bevy::ecs::entity::Entities;
 use bevy::prelude::{EventWriter, IntoSystemConfigs, Res, ResMut};
 use tracing::{error, trace};
 
 /// Read the messages received from the clients and emit the MessageEvent event
+///
+/// The messages that reference entities that were not spawned yet are read again on the next frame.
 fn read_message<M: Message>(
     message_registry: Res<MessageRegistry>,
     mut connection_manager: ResMut<ConnectionManager>,
     mut event: EventWriter<MessageEvent<M>>,
+    entities: &Entities,
 ) {
     let kind = MessageKind::of::<M>();
     let Some(net) = message_registry.kind_map.net_id(&kind).copied() else {
@@ -29,14 +34,16 @@ fn read_message<M: Message>(
     let connection_manager = connection_manager.deref_mut();
     for (client_id, connection) in connection_manager.connections.iter_mut() {
         if let Some(message_list) = connection.received_messages.remove(&net) {
+            let mut deferred = Vec::new();
             for (message_bytes, target, channel_kind) in message_list {
-                let mut reader = Reader::from(message_bytes);
-                match message_registry.deserialize::<M>(
+                let mut reader = Reader::from(message_bytes.clone());
+                match message_registry.deserialize_spawned::<M>(
                     &mut reader,
                     &mut connection
                         .replication_receiver
                         .remote_entity_map
                         .remote_to_local,
+                    entities,
                 ) {
                     Ok(message) => {
                         // rebroadcast
@@ -50,6 +57,13 @@ fn read_message<M: Message>(
                         event.send(MessageEvent::new(message, *client_id));
                         trace!("Received message: {:?}", std::any::type_name::<M>());
                     }
+                    Err(MessageError::UnspawnedEntity(entity)) => {
+                        trace!(
+                            ?entity,
+                            "Deferred a message that references an entity that was not spawned yet"
+                        );
+                        deferred.push((message_bytes, target, channel_kind));
+                    }
                     Err(e) => {
                         error!(
                             "Could not deserialize message {}: {:?}",
@@ -59,6 +73,10 @@ fn read_message<M: Message>(
                     }
                 }
             }
+            if !deferred.is_empty() {
+                bound_deferred_messages(&mut deferred, &mut connection.warnings);
+                connection.received_messages.insert(net, deferred);
+            }
         }
     }
 }
@@ -3,16 +3,27 @@
 //! # Server
 //! The server module contains all the code that is used to run the server.
 
+pub mod behavior;
+
+pub mod channel_settings;
+
 pub mod config;
 
 pub mod connection;
 
+pub mod diagnostics;
+
 pub mod error;
 
 pub mod events;
 
+#[cfg(not(target_family = "wasm"))]
+pub mod headless;
+
 pub mod input;
 
+pub mod personalized;
+
 pub(crate) mod io;
 
 pub mod plugin;
@@ -24,4 +35,6 @@ pub mod clients;
 pub(crate) mod networking;
 pub mod relevance;
 pub mod replication;
+pub mod rewind;
 pub mod run_conditions;
+pub mod validation;
@@ -5,18 +5,29 @@ use crate::prelude::{
     TimeManager,
 };
 use crate::protocol::component::ComponentRegistry;
+use crate::protocol::registry::NetIdReport;
 use crate::serialize::reader::Reader;
 use crate::server::clients::ControlledEntities;
 use crate::server::config::ServerConfig;
 use crate::server::connection::ConnectionManager;
 use crate::server::error::ServerError;
-use crate::server::events::{ConnectEvent, DisconnectEvent};
+use crate::server::events::{
+    ChannelOccupancyEvent, ConnectEvent, DisconnectEvent, MessageAckEvent, TransferEvent,
+    TransportMigrationEvent,
+};
 use crate::server::io::ServerIoEvent;
+use crate::shared::action::send_action_resolutions;
+use crate::shared::clock::NetworkClock;
+use crate::shared::input::jitter::advise_input_delay;
+use crate::shared::network_time::{send_server_time, NetworkTime};
+use crate::shared::request::expire_server_requests;
+use crate::shared::schema::{receive_client_schemas, send_server_schemas};
 use crate::shared::sets::{InternalMainSet, ServerMarker};
 use async_channel::TryRecvError;
 use bevy::ecs::system::{RunSystemOnce, SystemChangeTick};
 use bevy::prelude::*;
-use tracing::{debug, error, trace};
+use bevy::utils::Duration;
+use tracing::{debug, error, info, trace};
 
 /// Plugin handling the server networking systems: sending/receiving packets to clients
 #[derive(Default)]
@@ -52,13 +63,37 @@ impl Plugin for ServerNetworkingPlugin {
             // SYSTEMS //
             .add_systems(
                 PreUpdate,
-                receive.in_set(InternalMainSet::<ServerMarker>::Receive),
+                (
+                    receive.in_set(InternalMainSet::<ServerMarker>::Receive),
+                    (
+                        emit_transfer_events,
+                        emit_message_ack_events,
+                        emit_channel_occupancy_events,
+                    )
+                        .in_set(InternalMainSet::<ServerMarker>::EmitEvents),
+                    (receive_client_schemas, expire_server_requests)
+                        .after(InternalMainSet::<ServerMarker>::EmitEvents)
+                        .run_if(is_started),
+                ),
             )
             .add_systems(
                 PostUpdate,
-                (send, send_host_server.run_if(is_host_server))
-                    .in_set(InternalMainSet::<ServerMarker>::Send),
-            );
+                (
+                    (
+                        send_server_time,
+                        send_action_resolutions,
+                        advise_input_delay,
+                    )
+                        .before(InternalMainSet::<ServerMarker>::Send)
+                        .run_if(is_started),
+                    (send, send_host_server.run_if(is_host_server))
+                        .in_set(InternalMainSet::<ServerMarker>::Send),
+                ),
+            )
+            // send the verdicts given during each fixed update step with the tick of that step
+            .add_systems(FixedPostUpdate, send_action_resolutions.run_if(is_started));
+
+        app.observe(send_server_schemas);
 
         // ON_START
         app.add_systems(OnEnter(NetworkingState::Started), on_start);
@@ -93,6 +128,20 @@ pub(crate) fn receive(world: &mut World) {
                                             time_manager.update(delta);
                                             trace!(time = ?time_manager.current_time(), tick = ?tick_manager.tick(), "receive");
 
+                                            // disconnect the clients kicked with `ConnectionManager::disconnect_client`
+                                            for (client_id, reason) in std::mem::take(&mut connection_manager.pending_kicks) {
+                                                let result = match reason {
+                                                    Some(reason) => netservers.disconnect_with_reason(client_id, reason),
+                                                    None => netservers.disconnect(client_id),
+                                                };
+                                                match result {
+                                                    Ok(()) => {
+                                                        connection_manager.kicked_clients.insert(client_id);
+                                                    }
+                                                    Err(e) => error!("Could not disconnect client {:?}: {:?}", client_id, e),
+                                                }
+                                            }
+
                                             // update server net connections
                                             // reborrow trick to enable split borrows
                                             let netservers = &mut *netservers;
@@ -143,10 +192,10 @@ pub(crate) fn receive(world: &mut World) {
                                                         }
                                                     })
                                                 }
-                                                // disconnects because we received a disconnect message
-                                                for client_id in netserver.new_disconnections().iter().copied() {
+                                                // disconnects because we received a disconnect message, the client timed out, etc.
+                                                for (client_id, reason) in netserver.new_disconnections() {
                                                     if netservers.client_server_map.remove(&client_id).is_some() {
-                                                        connection_manager.remove(client_id);
+                                                        connection_manager.remove(client_id, reason);
                                                         // NOTE: we don't despawn the entity right away to let the user react to
                                                         // the disconnect event
                                                         // TODO: use observers/component_hooks to react automatically on the client despawn?
@@ -155,6 +204,16 @@ pub(crate) fn receive(world: &mut World) {
                                                         error!("Client disconnected but could not map client_id to the corresponding netserver");
                                                     }
                                                 };
+                                                // the client is still connected, but now on a different transport
+                                                for client_id in netserver.new_migrations() {
+                                                    debug!("Client {client_id} migrated to a new transport");
+                                                    world.send_event(TransportMigrationEvent { client_id });
+                                                }
+                                                // a client connected with the id of a client that was already connected
+                                                for event in netserver.new_duplicate_connections() {
+                                                    debug!(?event, "Client connected with an id that is already in use");
+                                                    world.send_event(event);
+                                                }
                                             }
 
                                             // update connections
@@ -171,12 +230,11 @@ pub(crate) fn receive(world: &mut World) {
                                                     // TODO: use connection to apply on BOTH message manager and replication manager
                                                     if let Some(connection) = connection_manager
                                                         .connections.get_mut(&client_id) {
-                                                        let component_registry = world.resource::<ComponentRegistry>();
-                                                        connection.recv_packet(payload, tick_manager.as_ref(), component_registry, &mut connection_manager.delta_manager).expect("could not receive packet");
+                                                        connection.recv_conditioned_packet(payload);
                                                     } else {
                                                         // it's still possible to receive some packets from a client that just disconnected.
                                                         // (multiple packets arrived at the same time from that client)
-                                                        if netserver.new_disconnections().contains(&client_id) {
+                                                        if netserver.new_disconnections().iter().any(|(id, _)| *id == client_id) {
                                                             trace!("received packet from client that just got disconnected. Ignoring.");
                                                             // we ignore packets from disconnected clients
                                                             // this is not an error
@@ -187,6 +245,11 @@ pub(crate) fn receive(world: &mut World) {
                                                     }
                                                 }
                                             }
+                                            // process the packets of each client (including the packets delayed by its conditioner)
+                                            for connection in connection_manager.connections.values_mut() {
+                                                let component_registry = world.resource::<ComponentRegistry>();
+                                                connection.process_received_packets(tick_manager.as_ref(), component_registry, &mut connection_manager.delta_manager).expect("could not receive packet");
+                                            }
 
                                             // RECEIVE: read messages and parse them into events
                                             connection_manager
@@ -213,7 +276,7 @@ pub(crate) fn receive(world: &mut World) {
                                                 if connection_manager.events.has_disconnections() {
                                                     for disconnect_event in connection_manager.events.iter_disconnections() {
                                                         debug!("Client disconnected event: {}", disconnect_event.client_id);
-                                                        world.resource_mut::<Events<DisconnectEvent>>().send(disconnect_event);
+                                                        world.resource_mut::<Events<DisconnectEvent>>().send(disconnect_event.clone());
                                                         // TODO: trigger all events in batch? https://github.com/bevyengine/bevy/pull/13953
                                                         // NOTE: we don't trigger the event immediately because we're inside world.resource_scope
                                                         //  so a bunch of Resources have been removed from the World
@@ -228,6 +291,67 @@ pub(crate) fn receive(world: &mut World) {
     });
 }
 
+/// Emit a [`TransferEvent`] for each transfer with a client that finished since the last frame
+fn emit_transfer_events(
+    mut connection_manager: ResMut<ConnectionManager>,
+    mut events: EventWriter<TransferEvent>,
+) {
+    for (client_id, connection) in connection_manager.connections.iter_mut() {
+        events.send_batch(
+            connection
+                .message_manager
+                .take_transfer_events()
+                .into_iter()
+                .map(|(handle, outcome)| TransferEvent {
+                    client_id: *client_id,
+                    handle,
+                    outcome,
+                }),
+        );
+    }
+}
+
+/// Emit a [`MessageAckEvent`] for each message that a client acked since the last frame
+fn emit_message_ack_events(
+    mut connection_manager: ResMut<ConnectionManager>,
+    mut events: EventWriter<MessageAckEvent>,
+) {
+    for (client_id, connection) in connection_manager.connections.iter_mut() {
+        events.send_batch(
+            connection
+                .message_manager
+                .take_message_acks()
+                .into_iter()
+                .map(|(channel_kind, message_id)| MessageAckEvent {
+                    client_id: *client_id,
+                    message_id,
+                    channel_kind,
+                }),
+        );
+    }
+}
+
+/// Emit a [`ChannelOccupancyEvent`] for each channel of a client whose send buffer went above the
+/// warning threshold
+fn emit_channel_occupancy_events(
+    mut connection_manager: ResMut<ConnectionManager>,
+    mut events: EventWriter<ChannelOccupancyEvent>,
+) {
+    for (client_id, connection) in connection_manager.connections.iter_mut() {
+        events.send_batch(
+            connection
+                .message_manager
+                .take_occupancy_warnings()
+                .into_iter()
+                .map(|(channel_kind, occupancy)| ChannelOccupancyEvent {
+                    client_id: *client_id,
+                    channel_kind,
+                    occupancy,
+                }),
+        );
+    }
+}
+
 // or do additional send stuff here
 pub(crate) fn send(
     change_tick: SystemChangeTick,
@@ -254,14 +378,17 @@ pub(crate) fn send(
                 .servers
                 .get_mut(netserver_idx)
                 .ok_or(ServerError::ServerConnectionNotFound)?;
-            for packet_byte in connection.send_packets(&time_manager, &tick_manager)? {
+            let packet_bytes = connection.send_packets(&time_manager, &tick_manager)?;
+            for packet_byte in &packet_bytes {
                 netserver.send(packet_byte.as_slice(), *client_id)?;
             }
+            connection.recycle_payloads(packet_bytes);
             Ok(())
         })
         .unwrap_or_else(|e: ServerError| {
             error!("Error sending packets: {}", e);
         });
+    connection_manager.writer.end_frame();
 }
 
 /// When running in host-server mode, we also need to send messages to the local client.
@@ -269,7 +396,10 @@ pub(crate) fn send(
 pub(crate) fn send_host_server(
     mut connection_manager: ResMut<ConnectionManager>,
     mut client_manager: ResMut<crate::client::connection::ConnectionManager>,
+    tick_manager: Res<TickManager>,
 ) {
+    // the host-server shares the tick of the server
+    let tick = tick_manager.tick();
     let _ = connection_manager
         .connections
         .iter_mut()
@@ -278,7 +408,9 @@ pub(crate) fn send_host_server(
             connection
                 .local_messages_to_send
                 .drain(..)
-                .try_for_each(|message| client_manager.receive_message(Reader::from(message)))
+                .try_for_each(|message| {
+                    client_manager.receive_message(Reader::from(message), tick, None)
+                })
         })
         .inspect_err(|e| error!("Error sending messages to local client: {:?}", e));
 }
@@ -312,6 +444,8 @@ fn rebuild_server_connections(world: &mut World) {
         server_config.replication,
         server_config.packet,
         server_config.ping,
+        world.resource::<NetworkClock>().clone(),
+        server_config.deny_list.clone(),
     );
     // // make sure the previous replication metadata is ported over to the new manager
     // if let Some(mut previous_manager) = world.get_resource_mut::<ConnectionManager>() {
@@ -321,7 +455,23 @@ fn rebuild_server_connections(world: &mut World) {
     world.insert_resource(connection_manager);
 
     // rebuild the server connections and insert them
-    let server_connections = ServerConnections::new(server_config.net);
+    let mut server_connections = ServerConnections::new(server_config.net);
+    // deny the clients whose protocol does not have the same net ids
+    let protocol_hash = NetIdReport::from_world(world).hash();
+    let clock = world.resource::<NetworkClock>().clone();
+    for server in server_connections.servers.iter_mut() {
+        match server {
+            ServerConnection::Netcode(server) => {
+                server.server.set_protocol_hash(protocol_hash);
+                server.server.set_clock(clock.clone());
+                server.server.set_deny_list(server_config.deny_list.clone());
+            }
+            #[cfg(all(feature = "steam", not(target_family = "wasm")))]
+            ServerConnection::Steam(server) => {
+                server.set_deny_list(server_config.deny_list.clone());
+            }
+        }
+    }
     world.insert_resource(server_connections);
 }
 
@@ -349,10 +499,42 @@ fn on_stop(mut server_connections: ResMut<ServerConnections>) {
         .inspect_err(|e| error!("Error stopping server connections: {:?}", e));
 }
 
+/// Change the tick duration of the server, and send it to the clients.
+///
+/// The tick number keeps increasing from the current tick at the new rate. The new duration is sent to the
+/// clients with the server time, on a sequenced channel, so the anchors of the server time computed with the
+/// previous duration are discarded once it is received. The clients then adopt the new duration and re-sync
+/// their prediction and interpolation timelines.
+pub(crate) fn set_tick_duration(world: &mut World, tick_duration: Duration) {
+    if tick_duration.is_zero() {
+        error!("The tick duration cannot be zero");
+        return;
+    }
+    let previous = world.resource::<TickManager>().config.tick_duration;
+    if previous == tick_duration {
+        return;
+    }
+    let tick = world.resource::<TickManager>().tick();
+    info!(?previous, new = ?tick_duration, ?tick, "Changing the tick duration");
+    world
+        .resource_mut::<ServerConfig>()
+        .shared
+        .tick
+        .tick_duration = tick_duration;
+    world.resource_mut::<TickManager>().config.tick_duration = tick_duration;
+    world
+        .resource_mut::<Time<Fixed>>()
+        .set_timestep(tick_duration);
+    world.resource_mut::<NetworkTime>().broadcast_now();
+}
+
 pub trait ServerCommands {
     fn start_server(&mut self);
 
     fn stop_server(&mut self);
+
+    /// Change the tick duration at runtime; the clients adopt it and re-sync with the server
+    fn set_tick_duration(&mut self, tick_duration: Duration);
 }
 
 impl ServerCommands for Commands<'_, '_> {
@@ -363,4 +545,8 @@ impl ServerCommands for Commands<'_, '_> {
     fn stop_server(&mut self) {
         self.insert_resource(NextState::Pending(NetworkingState::Stopped));
     }
+
+    fn set_tick_duration(&mut self, tick_duration: Duration) {
+        self.add(move |world: &mut World| set_tick_duration(world, tick_duration));
+    }
 }
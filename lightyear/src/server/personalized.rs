@@ -0,0 +1,85 @@
+//! Personalized replication: replicate a component whose value is different for each client.
+//!
+//! Instead of reading the component from the server entity, the server calls a generator function
+//! for each (replicated entity, client) pair, and replicates the returned value to that client.
+//! This is useful for example for fog-of-war, where each client should only see the information
+//! that is relevant to them.
+//!
+//! ```rust,ignore
+//! app.register_component::<RelativeIntel>(ChannelDirection::ServerToClient)
+//!     .add_personalized(|entity, client_id, world| {
+//!         let threat_level = compute_threat(entity, client_id, world)?;
+//!         Some(RelativeIntel { threat_level })
+//!     });
+//! ```
+//!
+//! # Cost
+//!
+//! Every time the server replicates, each generator is run once per replicated entity and per client that
+//! the entity is replicated to. With `N` entities, `M` clients and `G` generators, that is `N * M * G`
+//! calls (and serializations) per send interval, so generators should return `None` early for entities
+//! that they don't apply to. The serialized value is compared with the last value sent to the client,
+//! and only sent if it changed. Personalized values are sent on the reliable entity actions channel.
+use bevy::prelude::{Component, Entity, Resource, World};
+use bytes::Bytes;
+
+use crate::prelude::ClientId;
+use crate::protocol::component::{ComponentKind, ComponentRegistry};
+use crate::serialize::writer::Writer;
+
+/// Function that computes the value of a personalized component for a given entity and client.
+/// Returns `None` if the client should not have the component.
+pub type PersonalizedFn<C> =
+    Box<dyn Fn(Entity, ClientId, &World) -> Option<C> + Send + Sync + 'static>;
+
+type ErasedPersonalizedFn = Box<
+    dyn Fn(Entity, ClientId, &World, &ComponentRegistry, &mut Writer) -> Option<Bytes>
+        + Send
+        + Sync
+        + 'static,
+>;
+
+pub(crate) struct PersonalizedGenerator {
+    pub(crate) kind: ComponentKind,
+    generate: ErasedPersonalizedFn,
+}
+
+impl PersonalizedGenerator {
+    /// Compute the serialized value of the component for the given entity and client
+    pub(crate) fn generate(
+        &self,
+        entity: Entity,
+        client_id: ClientId,
+        world: &World,
+        component_registry: &ComponentRegistry,
+        writer: &mut Writer,
+    ) -> Option<Bytes> {
+        (self.generate)(entity, client_id, world, component_registry, writer)
+    }
+}
+
+/// Server-side list of the components that are replicated with a personalized value
+#[derive(Resource, Default)]
+pub(crate) struct PersonalizedComponents {
+    pub(crate) generators: Vec<PersonalizedGenerator>,
+}
+
+impl PersonalizedComponents {
+    pub(crate) fn add<C: Component>(&mut self, generator: PersonalizedFn<C>) {
+        self.generators.push(PersonalizedGenerator {
+            kind: ComponentKind::of::<C>(),
+            generate: Box::new(
+                move |entity, client_id, world, component_registry, writer| {
+                    let value = generator(entity, client_id, world)?;
+                    component_registry
+                        .serialize(&value, writer)
+                        .inspect_err(|e| {
+                            tracing::error!("could not serialize personalized component: {e:?}")
+                        })
+                        .ok()?;
+                    Some(writer.split())
+                },
+            ),
+        });
+    }
+}
@@ -12,6 +12,7 @@ use crate::server::clients::ClientsMetadataPlugin;
 use bevy::app::PluginGroupBuilder;
 use bevy::prelude::*;
 
+use crate::server::diagnostics::ServerDiagnosticsPlugin;
 use crate::server::events::ServerEventsPlugin;
 use crate::server::networking::ServerNetworkingPlugin;
 use crate::server::relevance::immediate::NetworkRelevancePlugin;
@@ -19,6 +20,7 @@ use crate::server::relevance::room::RoomPlugin;
 use crate::server::replication::{
     receive::ServerReplicationReceivePlugin, send::ServerReplicationSendPlugin,
 };
+use crate::shared::config_check::add_server_checks;
 use crate::shared::plugin::SharedPlugin;
 
 use super::config::ServerConfig;
@@ -29,6 +31,7 @@ use super::config::ServerConfig;
 /// - [`SetupPlugin`]: Adds the [`ServerConfig`] resource and the [`SharedPlugin`] plugin.
 /// - [`ServerEventsPlugin`]: Adds the server network event
 /// - [`ServerNetworkingPlugin`]: Handles the network state (starting/stopping the server, sending/receiving packets)
+/// - [`ServerDiagnosticsPlugin`]: Computes diagnostics about the server connections. Can be disabled if you don't need it.
 /// - [`NetworkRelevancePlugin`]: Handles the network relevance systems. This can be disabled if you don't need fine-grained interest management.
 /// - [`RoomPlugin`]: Handles the room system, which is an addition to the visibility system. This can be disabled if you don't need rooms.
 /// - [`ServerReplicationReceivePlugin`]: Handles the replication of entities and resources from clients to the server. This can be
@@ -41,12 +44,6 @@ pub struct ServerPlugins {
 
 impl ServerPlugins {
     pub fn new(config: ServerConfig) -> Self {
-        if config.shared.server_replication_send_interval != config.replication.send_interval {
-            error!(
-                "The config.shared.server_replication_send_interval {:?} is different from the config.replication.send_interval {:?}. This can cause issues. They should be set to the same value",
-                config.shared.server_replication_send_interval, config.replication.send_interval
-            );
-        }
         Self { config }
     }
 }
@@ -61,6 +58,7 @@ impl PluginGroup for ServerPlugins {
             })
             .add(ServerEventsPlugin)
             .add(ServerNetworkingPlugin)
+            .add(ServerDiagnosticsPlugin)
             .add(NetworkRelevancePlugin)
             .add(RoomPlugin)
             .add(ClientsMetadataPlugin)
@@ -87,5 +85,6 @@ impl Plugin for SetupPlugin {
                 config: self.config.shared,
             });
         }
+        add_server_checks(app);
     }
 }
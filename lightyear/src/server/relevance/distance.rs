@@ -0,0 +1,294 @@
+/*! Distance-based network relevance: replicate an entity only to the clients that are close to it
+
+Add a [`ReplicationRelevance`] component to an entity that uses [`NetworkRelevanceMode::InterestManagement`]
+to replicate it only to the clients whose view position is within a radius of the entity.
+The position of the entity is the translation of its [`Transform`].
+
+The view position of a client is either:
+- the [`ClientViewPosition`] of the entities that the client controls (see [`ControlledBy`]). If the client
+  controls several entities with a view position, the entity is relevant if it is close to any of them.
+- or a position set directly with [`DistanceRelevance::set_view_position`]
+
+```rust
+use bevy::prelude::*;
+use lightyear::prelude::*;
+use lightyear::prelude::server::*;
+
+fn spawn_bullet(mut commands: Commands) {
+    commands.spawn((
+        Transform::default(),
+        // only the clients within 50.0 units receive the bullet
+        ReplicationRelevance::Radius(50.0),
+        Replicate {
+            relevance_mode: NetworkRelevanceMode::InterestManagement,
+            ..default()
+        },
+    ));
+}
+
+fn move_camera(mut distance: ResMut<DistanceRelevance>) {
+    distance.set_view_position(ClientId::Netcode(1), Vec3::new(100.0, 0.0, 0.0));
+}
+```
+
+# Hysteresis
+
+An entity becomes relevant to a client when the distance is below the radius `r`, but only stops being relevant
+when the distance goes above `r * `[`InterestConfig::distance_hysteresis`], so that an entity that moves along
+the boundary is not spawned and despawned repeatedly on the client.
+
+# Cost
+
+The distances are only checked every [`InterestConfig::distance_check_interval`]: with `N` entities with a
+[`ReplicationRelevance`] and `M` view positions, that is `N * M` distance computations per interval.
+New entities are checked right away, so that they are not spawned late on the clients.
+
+The distance is an additional source of relevance, like the [rooms](super::room): use the rooms for some entities
+and a radius for others, but do not add an entity with a [`ReplicationRelevance`] to a room, since the rooms would
+make it relevant to clients that are far away. Removing the [`ReplicationRelevance`] component keeps the current
+relevance of the entity.
+
+[`NetworkRelevanceMode::InterestManagement`]: crate::prelude::NetworkRelevanceMode::InterestManagement
+[`InterestConfig::distance_hysteresis`]: super::immediate::InterestConfig::distance_hysteresis
+[`InterestConfig::distance_check_interval`]: super::immediate::InterestConfig::distance_check_interval
+*/
+use bevy::ecs::entity::{EntityHashMap, EntityHashSet};
+use bevy::prelude::*;
+use bevy::utils::{Duration, HashMap, HashSet};
+use tracing::trace;
+
+use crate::prelude::server::ServerConfig;
+use crate::prelude::ClientId;
+use crate::server::connection::ConnectionManager;
+use crate::server::events::DisconnectEvent;
+use crate::server::relevance::immediate::{
+    CachedNetworkRelevance, InterestRecompute, RelevanceManager,
+};
+use crate::server::replication::send::ControlledBy;
+
+/// Server component that makes an entity relevant only to the clients that are close to it
+#[derive(Component, Clone, Copy, Debug, PartialEq, Reflect)]
+pub enum ReplicationRelevance {
+    /// The entity is relevant to the clients whose view position is within this distance of the entity
+    Radius(f32),
+}
+
+/// Server component that sets the view position of the clients that control the entity
+#[derive(Component, Clone, Copy, Debug, Default, PartialEq, Reflect)]
+pub struct ClientViewPosition(pub Vec3);
+
+/// Server [`Resource`] that keeps track of the entities that are close to each client
+#[derive(Resource, Debug, Default)]
+pub struct DistanceRelevance {
+    /// View positions set with [`DistanceRelevance::set_view_position`]
+    view_positions: HashMap<ClientId, Vec3>,
+    /// Clients that each entity with a [`ReplicationRelevance`] is relevant to
+    inside: EntityHashMap<HashSet<ClientId>>,
+    /// Elapsed time at the last check of all the entities
+    last_check: Option<Duration>,
+}
+
+impl DistanceRelevance {
+    /// Set the view position of a client, in addition to the [`ClientViewPosition`] of the entities it controls
+    pub fn set_view_position(&mut self, client_id: ClientId, position: Vec3) {
+        self.view_positions.insert(client_id, position);
+    }
+
+    /// Remove the view position that was set with [`DistanceRelevance::set_view_position`]
+    pub fn remove_view_position(&mut self, client_id: ClientId) {
+        self.view_positions.remove(&client_id);
+    }
+
+    /// Returns true if the entity is currently relevant to the client because of its distance
+    pub fn is_relevant(&self, client_id: ClientId, entity: Entity) -> bool {
+        self.inside
+            .get(&entity)
+            .is_some_and(|clients| clients.contains(&client_id))
+    }
+}
+
+/// Check the distance between the entities with a [`ReplicationRelevance`] and the view positions of the clients,
+/// and send relevance events for the (client, entity) pairs that entered or left the radius.
+///
+/// It runs before the interest hints, so that a hinted entity stays relevant even if it is far from the client.
+pub(crate) fn update_distance_relevance(
+    config: Res<ServerConfig>,
+    time: Res<Time>,
+    recompute: Res<InterestRecompute>,
+    sender: Res<ConnectionManager>,
+    mut distance: ResMut<DistanceRelevance>,
+    mut relevance_manager: ResMut<RelevanceManager>,
+    viewers: Query<(&ClientViewPosition, &ControlledBy)>,
+    entities: Query<(
+        Entity,
+        &ReplicationRelevance,
+        &Transform,
+        Ref<CachedNetworkRelevance>,
+    )>,
+) {
+    let interest = config.replication.interest;
+    let now = time.elapsed();
+    let check_all = distance.last_check.map_or(true, |last_check| {
+        now.saturating_sub(last_check) >= interest.distance_check_interval
+    });
+    if check_all {
+        distance.last_check = Some(now);
+    }
+
+    // view positions of the connected clients
+    let mut view_positions: Vec<(ClientId, Vec3)> = Vec::new();
+    for client_id in sender.connected_clients() {
+        if let Some(position) = distance.view_positions.get(&client_id) {
+            view_positions.push((client_id, *position));
+        }
+        view_positions.extend(
+            viewers
+                .iter()
+                .filter(|(_, controlled_by)| controlled_by.targets(&client_id))
+                .map(|(position, _)| (client_id, position.0)),
+        );
+    }
+
+    let distance = &mut *distance;
+    let mut alive = EntityHashSet::default();
+    for (entity, relevance, transform, cached) in entities.iter() {
+        alive.insert(entity);
+        let is_new = !distance.inside.contains_key(&entity);
+        let inside = distance.inside.entry(entity).or_default();
+        // the relevance events are lost if they are sent before the entity has a relevance cache
+        if cached.is_added() || recompute.due {
+            for client_id in inside.iter() {
+                relevance_manager.gain_relevance(*client_id, entity);
+            }
+        }
+        if !check_all && !is_new {
+            continue;
+        }
+        let ReplicationRelevance::Radius(radius) = *relevance;
+        let enter = radius * radius;
+        let leave = (radius * interest.distance_hysteresis).powi(2);
+        let position = transform.translation;
+        // squared distance to the closest view position of each client
+        let mut closest: HashMap<ClientId, f32> = HashMap::default();
+        for (client_id, view_position) in view_positions.iter() {
+            let distance_squared = position.distance_squared(*view_position);
+            closest
+                .entry(*client_id)
+                .and_modify(|d| *d = d.min(distance_squared))
+                .or_insert(distance_squared);
+        }
+        inside.retain(|client_id| {
+            if closest.get(client_id).is_some_and(|d| *d <= leave) {
+                return true;
+            }
+            trace!(?client_id, ?entity, "Entity left the relevance radius");
+            relevance_manager.lose_relevance(*client_id, entity);
+            false
+        });
+        for (client_id, distance_squared) in closest {
+            if distance_squared <= enter && inside.insert(client_id) {
+                trace!(?client_id, ?entity, "Entity entered the relevance radius");
+                relevance_manager.gain_relevance(client_id, entity);
+            }
+        }
+    }
+    // forget the entities that were despawned or lost their ReplicationRelevance
+    if check_all {
+        distance.inside.retain(|entity, _| alive.contains(entity));
+    }
+}
+
+/// Remove the view position of a client that disconnected
+pub(crate) fn handle_client_disconnect(
+    trigger: Trigger<DisconnectEvent>,
+    mut distance: ResMut<DistanceRelevance>,
+) {
+    let client_id = trigger.event().client_id;
+    distance.remove_view_position(client_id);
+    for clients in distance.inside.values_mut() {
+        clients.remove(&client_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::server::Replicate;
+    use crate::prelude::*;
+    use crate::tests::protocol::*;
+
+    use super::*;
+
+    fn client_has(pair: &LightyearTestPair, index: usize, server_entity: Entity) -> bool {
+        pair.client_world(index)
+            .resource::<client::ConnectionManager>()
+            .replication_receiver
+            .remote_entity_map
+            .get_local(server_entity)
+            .is_some_and(|entity| pair.client_world(index).get_entity(*entity).is_some())
+    }
+
+    fn move_to(pair: &mut LightyearTestPair, entity: Entity, x: f32) {
+        pair.server_world_mut()
+            .get_mut::<Transform>(entity)
+            .unwrap()
+            .translation
+            .x = x;
+        pair.frame_steps(5);
+    }
+
+    /// The entity is replicated to the clients that are within the radius, and only despawned
+    /// once it is further than the radius with hysteresis
+    #[test]
+    fn test_distance_relevance() {
+        let mut config = ServerConfig::default();
+        config.replication.interest.distance_check_interval = Duration::default();
+        let mut pair = LightyearTestPair::builder()
+            .protocol(ProtocolPlugin)
+            .clients(2)
+            .server_config(config)
+            .build();
+        // the first client views from the entity it controls, the second one from a position set directly
+        let first_client = pair.client_id(0);
+        let second_client = pair.client_id(1);
+        pair.server_world_mut().spawn((
+            ClientViewPosition(Vec3::ZERO),
+            ControlledBy {
+                target: NetworkTarget::Single(first_client),
+                ..default()
+            },
+        ));
+        pair.server_world_mut()
+            .resource_mut::<DistanceRelevance>()
+            .set_view_position(second_client, Vec3::new(100.0, 0.0, 0.0));
+        let entity = pair
+            .server_world_mut()
+            .spawn((
+                Component1(1.0),
+                Transform::from_xyz(5.0, 0.0, 0.0),
+                ReplicationRelevance::Radius(10.0),
+                Replicate {
+                    relevance_mode: NetworkRelevanceMode::InterestManagement,
+                    ..default()
+                },
+            ))
+            .id();
+        pair.frame_steps(5);
+        assert!(client_has(&pair, 0, entity));
+        assert!(!client_has(&pair, 1, entity));
+
+        // within the hysteresis margin: the entity stays relevant
+        move_to(&mut pair, entity, 11.0);
+        assert!(client_has(&pair, 0, entity));
+        move_to(&mut pair, entity, 13.0);
+        assert!(!client_has(&pair, 0, entity));
+        // the entity must be within the radius to become relevant again
+        move_to(&mut pair, entity, 11.0);
+        assert!(!client_has(&pair, 0, entity));
+        move_to(&mut pair, entity, 9.0);
+        assert!(client_has(&pair, 0, entity));
+
+        move_to(&mut pair, entity, 95.0);
+        assert!(!client_has(&pair, 0, entity));
+        assert!(client_has(&pair, 1, entity));
+    }
+}
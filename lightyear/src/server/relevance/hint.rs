@@ -0,0 +1,260 @@
+/*! Interest hints: the client asks the server to make an entity relevant to it
+
+Sometimes the client knows that it needs an entity before the server's interest management can infer it,
+for example when the player opens the profile panel of another player that is far away.
+The client can then send an interest hint with
+[`ConnectionManager::request_interest`](crate::client::connection::ConnectionManager::request_interest):
+
+```rust,ignore
+fn open_profile(mut manager: ResMut<ConnectionManager>, profile: Res<InspectedProfile>) {
+    manager.request_interest(profile.stats_entity, true).unwrap();
+}
+```
+
+The server grants the hint only if the entity exists, is replicated, and the authorization function set with
+[`set_interest_hint_authorization`](AppInterestHintExt::set_interest_hint_authorization) (if any) accepts it.
+A granted hint makes the entity relevant to the client for [`InterestConfig::hint_ttl`]: the client must send the
+request again before the hint expires to keep receiving the entity. The outcome of every request, and the
+expiration of the hints, are reported on the client with an [`InterestHintEvent`](crate::client::events::InterestHintEvent).
+
+The hints are an additional source of relevance, next to the [rooms](super::room) and the
+[`RelevanceManager`]: a hinted entity stays relevant even if it leaves the rooms of the client, and an expired hint
+does not hide an entity that shares a room with the client. The [replication predicates](super::predicate) still
+apply to the hinted entities.
+
+[`InterestConfig::hint_ttl`]: super::immediate::InterestConfig::hint_ttl
+*/
+use bevy::prelude::*;
+use bevy::utils::{Duration, HashMap};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, error, trace};
+
+use crate::channel::builder::InterestHintChannel;
+use crate::client::events::{InterestHintEvent, MessageEvent as ClientMessageEvent};
+use crate::prelude::server::ServerConfig;
+use crate::prelude::{ClientId, NetworkTarget, ReplicationTarget};
+use crate::server::connection::ConnectionManager;
+use crate::server::events::{DisconnectEvent, MessageEvent};
+use crate::server::relevance::immediate::RelevanceManager;
+use crate::server::relevance::room::RoomManager;
+
+/// Identifier of an entity in the server's World.
+///
+/// The client can get it from a message or a component sent by the server, or from an entity that it already
+/// replicates with [`ConnectionManager::network_entity_id`](crate::client::connection::ConnectionManager::network_entity_id).
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, Reflect)]
+pub struct NetworkEntityId(pub Entity);
+
+impl From<Entity> for NetworkEntityId {
+    fn from(entity: Entity) -> Self {
+        Self(entity)
+    }
+}
+
+/// Outcome of an interest hint, reported to the client
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Reflect)]
+pub enum InterestHintOutcome {
+    /// The entity is relevant to the client until the hint expires
+    Granted,
+    /// The server refused to make the entity relevant to the client
+    Denied,
+    /// The hint was not refreshed in time: the entity is not relevant to the client anymore,
+    /// unless the interest management of the server makes it relevant
+    Expired,
+}
+
+/// Message sent by the client to enable or disable an interest hint
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub(crate) struct InterestRequestMessage {
+    pub(crate) entity: NetworkEntityId,
+    pub(crate) enable: bool,
+}
+
+/// Message sent by the server to report the outcome of an interest hint
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub(crate) struct InterestResponseMessage {
+    pub(crate) entity: NetworkEntityId,
+    pub(crate) outcome: InterestHintOutcome,
+}
+
+/// Function that returns `false` if the client is not allowed to receive the entity
+pub type InterestHintAuthorizationFn =
+    Box<dyn Fn(EntityRef, ClientId) -> bool + Send + Sync + 'static>;
+
+/// Server [`Resource`] that keeps track of the interest hints that were granted
+#[derive(Resource, Default)]
+pub(crate) struct InterestHints {
+    /// Elapsed time at which each granted hint expires
+    granted: HashMap<(ClientId, Entity), Duration>,
+    authorization: Option<InterestHintAuthorizationFn>,
+}
+
+/// Extension trait to control the interest hints of the clients
+pub trait AppInterestHintExt {
+    /// Set the function that decides if a client is allowed to receive an entity that it requested with an
+    /// interest hint. Without an authorization function, the hints are granted for every replicated entity.
+    fn set_interest_hint_authorization(
+        &mut self,
+        authorization: impl Fn(EntityRef, ClientId) -> bool + Send + Sync + 'static,
+    );
+}
+
+impl AppInterestHintExt for App {
+    fn set_interest_hint_authorization(
+        &mut self,
+        authorization: impl Fn(EntityRef, ClientId) -> bool + Send + Sync + 'static,
+    ) {
+        // the hints are only handled on the server
+        if self.world().get_resource::<ServerConfig>().is_none() {
+            return;
+        }
+        self.world_mut()
+            .get_resource_or_insert_with(InterestHints::default)
+            .authorization = Some(Box::new(authorization));
+    }
+}
+
+/// Remove a hint, and make the entity irrelevant to the client if no room makes it relevant
+fn revoke(world: &mut World, client_id: ClientId, entity: Entity) {
+    let in_room = world
+        .get_resource::<RoomManager>()
+        .is_some_and(|rooms| rooms.is_relevant(client_id, entity));
+    if !in_room {
+        world
+            .resource_mut::<RelevanceManager>()
+            .lose_relevance(client_id, entity);
+    }
+}
+
+fn send_response(
+    manager: &mut ConnectionManager,
+    client_id: ClientId,
+    entity: NetworkEntityId,
+    outcome: InterestHintOutcome,
+) {
+    let _ = manager
+        .send_message_to_target::<InterestHintChannel, _>(
+            &InterestResponseMessage { entity, outcome },
+            NetworkTarget::Single(client_id),
+        )
+        .inspect_err(|e| error!("Could not send the interest hint response: {e:?}"));
+}
+
+/// Server system that grants or denies the interest hints received from the clients
+pub(crate) fn receive_interest_requests(world: &mut World) {
+    let Some(mut events) = world.get_resource_mut::<Events<MessageEvent<InterestRequestMessage>>>()
+    else {
+        return;
+    };
+    let requests: Vec<_> = events
+        .drain()
+        .map(|event| (event.context, event.message))
+        .collect();
+    if requests.is_empty() {
+        return;
+    }
+    let expiry = world.resource::<Time>().elapsed()
+        + world
+            .resource::<ServerConfig>()
+            .replication
+            .interest
+            .hint_ttl;
+    world.resource_scope(|world, mut hints: Mut<InterestHints>| {
+        for (client_id, request) in requests {
+            let entity = request.entity.0;
+            if !request.enable {
+                if hints.granted.remove(&(client_id, entity)).is_some() {
+                    trace!(?client_id, ?entity, "Interest hint disabled by the client");
+                    revoke(world, client_id, entity);
+                }
+                continue;
+            }
+            let authorized = world.get_entity(entity).is_some_and(|entity_ref| {
+                entity_ref.contains::<ReplicationTarget>()
+                    && hints
+                        .authorization
+                        .as_ref()
+                        .map_or(true, |authorization| authorization(entity_ref, client_id))
+            });
+            let outcome = if authorized {
+                hints.granted.insert((client_id, entity), expiry);
+                world
+                    .resource_mut::<RelevanceManager>()
+                    .gain_relevance(client_id, entity);
+                InterestHintOutcome::Granted
+            } else {
+                // a denied refresh also removes the previous grant
+                if hints.granted.remove(&(client_id, entity)).is_some() {
+                    revoke(world, client_id, entity);
+                }
+                InterestHintOutcome::Denied
+            };
+            debug!(?client_id, ?entity, ?outcome, "Interest hint received");
+            send_response(
+                &mut world.resource_mut::<ConnectionManager>(),
+                client_id,
+                request.entity,
+                outcome,
+            );
+        }
+    });
+}
+
+/// Server system that expires the hints that were not refreshed, and keeps the other hinted entities relevant
+/// even if they left the rooms of the client.
+///
+/// It runs after the room events were converted into relevance events, and before they are applied.
+pub(crate) fn update_interest_hints(world: &mut World) {
+    let now = world.resource::<Time>().elapsed();
+    world.resource_scope(|world, mut hints: Mut<InterestHints>| {
+        let mut expired = vec![];
+        hints.granted.retain(|(client_id, entity), expiry| {
+            if *expiry > now && world.get_entity(*entity).is_some() {
+                return true;
+            }
+            expired.push((*client_id, *entity));
+            false
+        });
+        for (client_id, entity) in expired {
+            debug!(?client_id, ?entity, "Interest hint expired");
+            if world.get_entity(entity).is_some() {
+                revoke(world, client_id, entity);
+            }
+            send_response(
+                &mut world.resource_mut::<ConnectionManager>(),
+                client_id,
+                NetworkEntityId(entity),
+                InterestHintOutcome::Expired,
+            );
+        }
+        // the room events could have made a hinted entity irrelevant
+        let mut relevance_manager = world.resource_mut::<RelevanceManager>();
+        for (client_id, entity) in hints.granted.keys() {
+            relevance_manager.gain_relevance(*client_id, *entity);
+        }
+    });
+}
+
+/// Remove the hints of a client that disconnected
+pub(crate) fn handle_client_disconnect(
+    trigger: Trigger<DisconnectEvent>,
+    mut hints: ResMut<InterestHints>,
+) {
+    let disconnected = trigger.event().client_id;
+    hints
+        .granted
+        .retain(|(client_id, _), _| *client_id != disconnected);
+}
+
+/// Client system that emits an [`InterestHintEvent`] for each interest hint response received from the server
+pub(crate) fn receive_interest_responses(
+    mut messages: EventReader<ClientMessageEvent<InterestResponseMessage>>,
+    mut events: EventWriter<InterestHintEvent>,
+) {
+    for message in messages.read().map(|event| event.message) {
+        events.send(InterestHintEvent {
+            entity: message.entity,
+            outcome: message.outcome,
+        });
+    }
+}
@@ -22,14 +22,84 @@ fn my_system(
     relevance_manager.lose_relevance(ClientId::Netcode(2), Entity::PLACEHOLDER);
 }
 ```
+
+The entities can also be made relevant to the clients that are close to them, see the [`distance`](super::distance) module.
+
+# Incremental updates
+
+The relevance is updated incrementally: the rooms, the [`RelevanceManager`] and the
+[replication predicates](super::predicate) only produce changes for the (client, entity) pairs that were
+affected since the last send interval, instead of recomputing the relevance of every entity for every client.
+As a safety net, the rooms and the predicates are fully recomputed every
+[`InterestConfig::full_recompute_interval`].
 */
+use crate::prelude::server::ServerConfig;
 use crate::prelude::{server::is_started, ClientId};
-use crate::shared::sets::{InternalReplicationSet, ServerMarker};
+use crate::server::relevance::distance::{self, update_distance_relevance, DistanceRelevance};
+use crate::server::relevance::hint::{
+    handle_client_disconnect, receive_interest_requests, update_interest_hints, InterestHints,
+};
+use crate::server::relevance::predicate::evaluate_replication_predicates;
+use crate::shared::sets::{InternalMainSet, InternalReplicationSet, ServerMarker};
 use bevy::ecs::entity::EntityHashSet;
 use bevy::prelude::*;
-use bevy::utils::HashMap;
+use bevy::utils::{Duration, HashMap};
 use tracing::trace;
 
+/// Configuration of the interest management
+#[derive(Clone, Copy, Debug, PartialEq, Reflect)]
+pub struct InterestConfig {
+    /// How often the relevance computed incrementally by the rooms and the replication predicates is
+    /// recomputed from scratch, in case some change was missed.
+    /// For example, a predicate that reads a component other than the one it is registered for only notices the
+    /// changes of that component during a full recompute.
+    ///
+    /// Set to `None` to never recompute the relevance from scratch.
+    pub full_recompute_interval: Option<Duration>,
+    /// How long an interest hint granted to a client stays valid. The client must send the hint again
+    /// before it expires to keep receiving the entity.
+    pub hint_ttl: Duration,
+    /// How often the distance between the entities with a
+    /// [`ReplicationRelevance`](super::distance::ReplicationRelevance) and the clients is checked.
+    ///
+    /// Set to `Duration::default()` to check the distances every send interval.
+    pub distance_check_interval: Duration,
+    /// An entity with a [`ReplicationRelevance::Radius`](super::distance::ReplicationRelevance::Radius) `r` becomes
+    /// relevant to a client within the distance `r`, and stops being relevant beyond the distance `r * distance_hysteresis`.
+    /// Must be greater than or equal to 1.0.
+    pub distance_hysteresis: f32,
+}
+
+impl Default for InterestConfig {
+    fn default() -> Self {
+        Self {
+            full_recompute_interval: Some(Duration::from_secs(1)),
+            hint_ttl: Duration::from_secs(5),
+            distance_check_interval: Duration::from_millis(100),
+            distance_hysteresis: 1.2,
+        }
+    }
+}
+
+/// Tracks when the relevance must be recomputed from scratch
+#[derive(Resource, Debug)]
+pub(crate) struct InterestRecompute {
+    /// Elapsed time at the last full recompute
+    last_full_recompute: Duration,
+    /// True if the relevance must be recomputed from scratch during the current send interval
+    pub(crate) due: bool,
+}
+
+impl Default for InterestRecompute {
+    fn default() -> Self {
+        // the first send interval computes everything
+        Self {
+            last_full_recompute: Duration::ZERO,
+            due: true,
+        }
+    }
+}
+
 /// Event related to [`Entities`](Entity) which are relevant to a client
 #[derive(Debug, PartialEq, Clone, Copy, Reflect)]
 pub(crate) enum ClientRelevance {
@@ -192,6 +262,15 @@ pub(super) mod systems {
     /// - Relevance Lost gets removed from the cache
     pub fn update_cached_relevance(mut query: Query<(Entity, &mut CachedNetworkRelevance)>) {
         for (entity, mut replicate) in query.iter_mut() {
+            // do not touch the caches that did not change, so that their change ticks only
+            // reflect actual relevance changes
+            if replicate
+                .clients_cache
+                .values()
+                .all(|relevance| *relevance == ClientRelevance::Maintained)
+            {
+                continue;
+            }
             replicate
                 .clients_cache
                 .retain(|client_id, relevance| match relevance {
@@ -211,6 +290,24 @@ pub(super) mod systems {
             // error!("replicate.clients_cache: {0:?}", replicate.clients_cache);
         }
     }
+
+    /// After replication, check if the relevance must be recomputed from scratch during the next send interval
+    pub(crate) fn schedule_full_recompute(
+        config: Res<ServerConfig>,
+        time: Res<Time>,
+        mut recompute: ResMut<InterestRecompute>,
+    ) {
+        let now = time.elapsed();
+        if recompute.due {
+            recompute.due = false;
+            recompute.last_full_recompute = now;
+        }
+        if let Some(interval) = config.replication.interest.full_recompute_interval {
+            if now.saturating_sub(recompute.last_full_recompute) >= interval {
+                recompute.due = true;
+            }
+        }
+    }
 }
 
 /// System sets related to Network Relevance
@@ -218,6 +315,8 @@ pub(super) mod systems {
 pub enum NetworkRelevanceSet {
     /// Update the relevance cache based on the relevance events
     UpdateRelevance,
+    /// Evaluate the replication predicates, after the relevance cache was updated
+    EvaluatePredicates,
     /// Perform bookkeeping for the relevance caches
     RelevanceCleanup,
 }
@@ -230,6 +329,9 @@ impl Plugin for NetworkRelevancePlugin {
     fn build(&self, app: &mut App) {
         // RESOURCES
         app.init_resource::<RelevanceManager>();
+        app.init_resource::<InterestRecompute>();
+        app.init_resource::<InterestHints>();
+        app.init_resource::<DistanceRelevance>();
         // SETS
         app.configure_sets(
             PostUpdate,
@@ -238,6 +340,7 @@ impl Plugin for NetworkRelevancePlugin {
                     // update replication caches must happen before replication, but after we add CachedNetworkRelevance
                     InternalReplicationSet::<ServerMarker>::BeforeBuffer,
                     NetworkRelevanceSet::UpdateRelevance,
+                    NetworkRelevanceSet::EvaluatePredicates,
                     InternalReplicationSet::<ServerMarker>::Buffer,
                     NetworkRelevanceSet::RelevanceCleanup,
                 )
@@ -246,6 +349,7 @@ impl Plugin for NetworkRelevancePlugin {
                 // the relevance systems can run every send_interval
                 (
                     NetworkRelevanceSet::UpdateRelevance,
+                    NetworkRelevanceSet::EvaluatePredicates,
                     NetworkRelevanceSet::RelevanceCleanup,
                 )
                     .in_set(InternalReplicationSet::<ServerMarker>::SendMessages),
@@ -262,10 +366,29 @@ impl Plugin for NetworkRelevancePlugin {
             (
                 systems::add_cached_network_relevance
                     .in_set(InternalReplicationSet::<ServerMarker>::BeforeBuffer),
-                systems::update_relevance_from_events.in_set(NetworkRelevanceSet::UpdateRelevance),
-                systems::update_cached_relevance.in_set(NetworkRelevanceSet::RelevanceCleanup),
+                (
+                    update_distance_relevance,
+                    update_interest_hints,
+                    systems::update_relevance_from_events,
+                )
+                    .chain()
+                    .in_set(NetworkRelevanceSet::UpdateRelevance),
+                evaluate_replication_predicates.in_set(NetworkRelevanceSet::EvaluatePredicates),
+                (
+                    systems::update_cached_relevance,
+                    systems::schedule_full_recompute,
+                )
+                    .in_set(NetworkRelevanceSet::RelevanceCleanup),
             ),
         );
+        app.add_systems(
+            PreUpdate,
+            receive_interest_requests
+                .after(InternalMainSet::<ServerMarker>::EmitEvents)
+                .run_if(is_started),
+        );
+        app.observe(handle_client_disconnect);
+        app.observe(distance::handle_client_disconnect);
     }
 }
 
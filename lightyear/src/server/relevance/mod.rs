@@ -1,4 +1,7 @@
+pub mod distance;
+pub mod hint;
 pub mod immediate;
+pub mod predicate;
 
 pub mod error;
 pub mod room;
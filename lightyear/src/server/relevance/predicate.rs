@@ -0,0 +1,342 @@
+/*! Replication predicates: hide an entity (or one of its components) from some clients depending on the value of a component
+
+Rooms and the [`RelevanceManager`](super::immediate::RelevanceManager) decide which clients an entity is relevant to,
+but some rules depend on the state of the entity itself, for example "only replicate stealthed players to their own team".
+A replication predicate is registered for a component kind, and is evaluated at send time for every entity that has the
+component:
+
+```rust,ignore
+app.register_replication_predicate::<Stealth>(|entity_ref, client_id| {
+    entity_ref
+        .get::<Stealth>()
+        .map_or(true, |stealth| stealth.team.contains(&client_id))
+});
+```
+
+If the predicate returns `false` for a client, the entity is not replicated to that client during this send interval
+([`PredicateScope::Entity`]): it is despawned on the client if the client had it, and spawned again once the predicate
+returns `true`. With [`PredicateScope::Component`] (see
+[`register_replication_predicate_with_scope`](crate::prelude::AppComponentExt::register_replication_predicate_with_scope)),
+only the component is not sent to the client.
+
+# Cost
+
+The predicates run last, after the replication target, the rooms and the relevance events: they are only evaluated
+for the clients that the entity would be replicated to otherwise. With `N` entities that have a predicated component,
+`M` clients that these entities are relevant to and `P` predicates per entity, that is `N * M * P` calls per send
+interval. The entity-scoped predicates are evaluated before the component-scoped ones, and the component-scoped
+predicates are skipped for the clients that the entity is hidden from.
+
+To keep this cost down, the predicates of an entity are only evaluated again if something they could depend on
+changed since the previous send interval:
+- one of the components that have a predicate was changed, inserted or removed
+- the relevance of the entity (from the rooms or the [`RelevanceManager`](super::immediate::RelevanceManager)) or
+  its [`ReplicationTarget`] changed, or a client connected or disconnected
+
+A predicate that reads other components of the entity only sees the changes of these components when all the
+predicates are evaluated from scratch, every
+[`InterestConfig::full_recompute_interval`](crate::prelude::server::InterestConfig::full_recompute_interval).
+*/
+use bevy::ecs::archetype::ArchetypeId;
+use bevy::ecs::component::ComponentId;
+use bevy::prelude::*;
+use bevy::utils::{HashMap, HashSet};
+
+use crate::prelude::server::ConnectionManager;
+use crate::prelude::{ClientId, NetworkTarget, ReplicationTarget};
+use crate::protocol::component::ComponentKind;
+use crate::server::relevance::immediate::{
+    CachedNetworkRelevance, ClientRelevance, InterestRecompute,
+};
+
+/// Function that returns `false` if the entity (or the component) should not be replicated to the client
+pub type ReplicationPredicateFn = Box<dyn Fn(EntityRef, ClientId) -> bool + Send + Sync + 'static>;
+
+/// What is hidden from a client when a replication predicate returns `false`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Reflect)]
+pub enum PredicateScope {
+    /// The whole entity is not replicated to the client
+    Entity,
+    /// Only the component that the predicate is registered for is not sent to the client.
+    /// The client keeps the last value of the component that it received.
+    Component,
+}
+
+struct ReplicationPredicate {
+    component_id: ComponentId,
+    kind: ComponentKind,
+    scope: PredicateScope,
+    predicate: ReplicationPredicateFn,
+}
+
+/// Server-side list of the registered replication predicates
+#[derive(Resource, Default)]
+pub(crate) struct ReplicationPredicates {
+    predicates: Vec<ReplicationPredicate>,
+    /// Clients that were connected when the predicates were last evaluated
+    connected_clients: HashSet<ClientId>,
+}
+
+impl ReplicationPredicates {
+    pub(crate) fn add<C: Component>(
+        &mut self,
+        component_id: ComponentId,
+        scope: PredicateScope,
+        predicate: ReplicationPredicateFn,
+    ) {
+        self.predicates.push(ReplicationPredicate {
+            component_id,
+            kind: ComponentKind::of::<C>(),
+            scope,
+            predicate,
+        });
+        // evaluate the cheaper entity-scoped predicates first
+        self.predicates
+            .sort_by_key(|p| p.scope == PredicateScope::Component);
+    }
+}
+
+/// Relevance of an entity after the replication predicates were evaluated.
+///
+/// Added to every entity that has a component with a predicate. The replication systems use it instead
+/// of the [`CachedNetworkRelevance`] of the entity.
+#[derive(Component, Debug, Default)]
+pub(crate) struct PredicateRelevance {
+    /// Clients that have the entity
+    visible: HashSet<ClientId>,
+    /// Relevance of the entity for each client, computed during the current send interval
+    pub(crate) relevance: CachedNetworkRelevance,
+    /// Clients that a component is hidden from
+    hidden_components: HashMap<ComponentKind, Vec<ClientId>>,
+    /// Clients that a component was hidden from during the previous send interval, but not anymore.
+    /// The component is sent to them as an insert.
+    revealed_components: HashMap<ComponentKind, Vec<ClientId>>,
+    /// Archetype of the entity when the predicates were evaluated, to detect that a predicated
+    /// component was inserted or removed
+    archetype: Option<ArchetypeId>,
+}
+
+impl PredicateRelevance {
+    /// Remove the clients that the component is hidden from, and send the component to the
+    /// clients that it was just revealed to
+    pub(crate) fn filter_component(
+        &self,
+        kind: ComponentKind,
+        insert_target: &mut NetworkTarget,
+        update_target: &mut NetworkTarget,
+    ) {
+        if let Some(hidden) = self.hidden_components.get(&kind) {
+            let hidden = NetworkTarget::from(hidden.clone());
+            insert_target.exclude(&hidden);
+            update_target.exclude(&hidden);
+        }
+        if let Some(revealed) = self.revealed_components.get(&kind) {
+            let revealed = NetworkTarget::from(revealed.clone());
+            insert_target.union(&revealed);
+            update_target.exclude(&revealed);
+        }
+    }
+
+    fn is_component_hidden(&self, kind: ComponentKind, client_id: &ClientId) -> bool {
+        self.hidden_components
+            .get(&kind)
+            .is_some_and(|clients| clients.contains(client_id))
+    }
+
+    /// Returns true if the relevance computed during the previous send interval contains some transient state
+    fn needs_settling(&self) -> bool {
+        !self.revealed_components.is_empty()
+            || self
+                .relevance
+                .clients_cache
+                .values()
+                .any(|relevance| *relevance != ClientRelevance::Maintained)
+    }
+
+    /// Carry over the result of the previous evaluation to the current send interval, when nothing that
+    /// the predicates depend on changed
+    fn settle(&mut self) {
+        self.revealed_components.clear();
+        self.relevance
+            .clients_cache
+            .retain(|_, relevance| match relevance {
+                ClientRelevance::Gained => {
+                    *relevance = ClientRelevance::Maintained;
+                    true
+                }
+                ClientRelevance::Lost => false,
+                ClientRelevance::Maintained => true,
+            });
+    }
+}
+
+/// Evaluate the replication predicates of each entity for the clients that the entity is relevant to,
+/// and update its [`PredicateRelevance`].
+///
+/// Only the entities for which something that the predicates depend on changed are evaluated again.
+pub(crate) fn evaluate_replication_predicates(world: &mut World) {
+    let Some(predicates) = world.get_resource::<ReplicationPredicates>() else {
+        return;
+    };
+    let Some(replication_target_id) = world.component_id::<ReplicationTarget>() else {
+        return;
+    };
+    let predicate_relevance_id = world.component_id::<PredicateRelevance>();
+    let sender = world.resource::<ConnectionManager>();
+    // in an exclusive system, the last change tick is the last time that the system ran
+    let last_run = world.last_change_tick();
+    let this_run = world.read_change_tick();
+
+    let full_recompute = world
+        .get_resource::<InterestRecompute>()
+        .map_or(true, |recompute| recompute.due);
+    let connected_clients: HashSet<ClientId> =
+        sender.connected_targets(NetworkTarget::All).collect();
+    let clients_changed = connected_clients != predicates.connected_clients;
+    // the components whose changes can affect the result of the predicates
+    let watched_ids: Vec<ComponentId> = predicates
+        .predicates
+        .iter()
+        .map(|p| p.component_id)
+        .chain(std::iter::once(replication_target_id))
+        .chain(world.component_id::<CachedNetworkRelevance>())
+        .collect();
+
+    let mut updates = Vec::new();
+    let mut settled = Vec::new();
+    for archetype in world.archetypes().iter() {
+        if !archetype.contains(replication_target_id) {
+            continue;
+        }
+        // keep evaluating the entities whose predicated components were removed, so that they
+        // get replicated again to the clients they were hidden from
+        if !predicates
+            .predicates
+            .iter()
+            .any(|p| archetype.contains(p.component_id))
+            && !predicate_relevance_id.is_some_and(|id| archetype.contains(id))
+        {
+            continue;
+        }
+        for entity in archetype.entities() {
+            let entity_ref = world.entity(entity.id());
+            let previous = entity_ref.get::<PredicateRelevance>();
+            let dirty = full_recompute
+                || clients_changed
+                || previous.map_or(true, |previous| previous.archetype != Some(archetype.id()))
+                || watched_ids.iter().any(|id| {
+                    entity_ref
+                        .get_change_ticks_by_id(*id)
+                        .is_some_and(|ticks| ticks.is_changed(last_run, this_run))
+                });
+            if dirty {
+                let mut relevance = evaluate_entity(entity_ref, predicates, sender);
+                relevance.archetype = Some(archetype.id());
+                updates.push((entity.id(), relevance));
+            } else if previous.is_some_and(PredicateRelevance::needs_settling) {
+                settled.push(entity.id());
+            }
+        }
+    }
+    if clients_changed {
+        world
+            .resource_mut::<ReplicationPredicates>()
+            .connected_clients = connected_clients;
+    }
+    for entity in settled {
+        // SAFETY: the entity was only settled if it had a PredicateRelevance
+        world
+            .get_mut::<PredicateRelevance>(entity)
+            .unwrap()
+            .settle();
+    }
+    for (entity, relevance) in updates {
+        world.entity_mut(entity).insert(relevance);
+    }
+}
+
+fn evaluate_entity(
+    entity_ref: EntityRef,
+    predicates: &ReplicationPredicates,
+    sender: &ConnectionManager,
+) -> PredicateRelevance {
+    let default = PredicateRelevance::default();
+    let previous = entity_ref.get::<PredicateRelevance>().unwrap_or(&default);
+    // the clients that the entity would be replicated to without the predicates
+    let candidates: Vec<(ClientId, ClientRelevance)> =
+        match entity_ref.get::<CachedNetworkRelevance>() {
+            Some(cached) => cached
+                .clients_cache
+                .iter()
+                .map(|(client_id, relevance)| (*client_id, *relevance))
+                .collect(),
+            None => {
+                // SAFETY: the archetype contains the ReplicationTarget component
+                let target = entity_ref.get::<ReplicationTarget>().unwrap();
+                sender
+                    .connected_targets(target.target.clone())
+                    .map(|client_id| (client_id, ClientRelevance::Maintained))
+                    .collect()
+            }
+        };
+    let entity_predicates: Vec<&ReplicationPredicate> = predicates
+        .predicates
+        .iter()
+        .filter(|p| entity_ref.contains_id(p.component_id))
+        .collect();
+
+    let mut relevance = PredicateRelevance::default();
+    for (client_id, upstream) in candidates {
+        let was_visible = previous.visible.contains(&client_id);
+        if upstream == ClientRelevance::Lost {
+            if was_visible {
+                relevance
+                    .relevance
+                    .clients_cache
+                    .insert(client_id, ClientRelevance::Lost);
+            }
+            continue;
+        }
+        let visible = entity_predicates
+            .iter()
+            .filter(|p| p.scope == PredicateScope::Entity)
+            .all(|p| (p.predicate)(entity_ref, client_id));
+        if !visible {
+            if was_visible {
+                relevance
+                    .relevance
+                    .clients_cache
+                    .insert(client_id, ClientRelevance::Lost);
+            }
+            continue;
+        }
+        relevance.visible.insert(client_id);
+        relevance.relevance.clients_cache.insert(
+            client_id,
+            if was_visible {
+                upstream
+            } else {
+                ClientRelevance::Gained
+            },
+        );
+        for p in entity_predicates
+            .iter()
+            .filter(|p| p.scope == PredicateScope::Component)
+        {
+            if !(p.predicate)(entity_ref, client_id) {
+                relevance
+                    .hidden_components
+                    .entry(p.kind)
+                    .or_default()
+                    .push(client_id);
+            } else if was_visible && previous.is_component_hidden(p.kind, &client_id) {
+                relevance
+                    .revealed_components
+                    .entry(p.kind)
+                    .or_default()
+                    .push(client_id);
+            }
+        }
+    }
+    relevance
+}
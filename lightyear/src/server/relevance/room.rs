@@ -27,14 +27,29 @@ fn room_system(mut manager: ResMut<RoomManager>) {
    // the entity will now be visible to the client
    manager.add_client(ClientId::Netcode(0), RoomId(0));
    manager.add_entity(Entity::PLACEHOLDER, RoomId(0));
+   // or equivalently, by editing the room directly
+   manager
+       .room_mut(RoomId(1))
+       .add_client(ClientId::Netcode(0))
+       .add_entity(Entity::PLACEHOLDER);
 }
 ```
 
+When an entity stops sharing a room with a client, it is despawned on that client (the server entity is not despawned).
+If it shares a room with the client again later, it is spawned again on the client with all its replicated components,
+as a new entity (with new `Predicted`/`Interpolated` entities if it is predicted or interpolated by the client).
+
 ## Implementation
 
 Under the hood, the [`RoomManager`] uses the same functions as in the immediate-mode [`RelevanceManager`],
 it just caches the room metadata to keep track of the relevance of entities.
 
+The [`RoomManager`] keeps track of the number of rooms shared by each entity and client, and updates it every time
+a room changes. Only the (client, entity) pairs that start or stop sharing a room produce relevance events,
+so moving an entity between two rooms (for example between two cells of a grid) does not require recomputing
+the relevance of every entity. The shared rooms are recomputed from scratch every
+[`InterestConfig::full_recompute_interval`](crate::prelude::server::InterestConfig::full_recompute_interval).
+
 */
 
 use bevy::app::App;
@@ -44,12 +59,14 @@ use bevy::reflect::Reflect;
 use bevy::utils::{HashMap, HashSet};
 
 use serde::{Deserialize, Serialize};
-use tracing::trace;
+use tracing::{debug, trace};
 
 use crate::connection::id::ClientId;
 use crate::prelude::server::is_started;
 
-use crate::server::relevance::immediate::{NetworkRelevanceSet, RelevanceManager};
+use crate::server::relevance::immediate::{
+    InterestRecompute, NetworkRelevanceSet, RelevanceManager,
+};
 use crate::shared::sets::{InternalReplicationSet, ServerMarker};
 
 type EntityHashMap<K, V> = hashbrown::HashMap<K, V, EntityHash>;
@@ -71,18 +88,19 @@ impl From<ClientId> for RoomId {
     }
 }
 
-/// Resource that will track any changes in the rooms
+/// Relevance changes caused by the room updates, for each (client, entity) pair
 /// (we cannot use bevy `Events` directly because we don't need to send this every frame.
 /// Also, we only need to keep track of updates for each send_interval frame. That means that if an entity
 /// leaves then re-joins a room within the same send_interval period, we don't need to send any update)
 ///
+/// Only the pairs whose relevance actually changed are tracked: an entity that moves from a room to another room
+/// only produces events for the clients that are in one of the two rooms but not the other.
+///
 /// This will be cleared every time the Server sends updates to the Client (every send_interval)
 #[derive(Resource, Debug, Default)]
 struct RoomEvents {
-    client_enter_room: HashMap<ClientId, HashSet<RoomId>>,
-    client_leave_room: HashMap<ClientId, HashSet<RoomId>>,
-    entity_enter_room: EntityHashMap<Entity, HashSet<RoomId>>,
-    entity_leave_room: EntityHashMap<Entity, HashSet<RoomId>>,
+    gained: HashMap<ClientId, EntityHashSet<Entity>>,
+    lost: HashMap<ClientId, EntityHashSet<Entity>>,
 }
 
 #[derive(Resource, Debug, Default)]
@@ -91,6 +109,10 @@ struct VisibilityEvents {
     lost: HashMap<ClientId, Entity>,
 }
 
+/// Number of rooms shared by each entity and client.
+/// Pairs that share no room are not stored.
+type SharedRooms = EntityHashMap<Entity, HashMap<ClientId, u32>>;
+
 #[derive(Default, Debug)]
 struct RoomData {
     /// List of rooms that a client is in
@@ -99,6 +121,9 @@ struct RoomData {
     entity_to_rooms: EntityHashMap<Entity, HashSet<RoomId>>,
     /// Mapping from [`RoomId`] to the [`Room`]
     rooms: HashMap<RoomId, Room>,
+    /// Number of rooms shared by each entity and client, updated every time a room changes.
+    /// The entity is relevant to the client if they share at least one room
+    shared_rooms: SharedRooms,
 }
 
 /// A [`Room`] is a data structure that is used to perform interest management.
@@ -123,6 +148,43 @@ pub struct RoomManager {
     data: RoomData,
 }
 
+/// Mutable handle on a [`Room`], returned by [`RoomManager::room_mut`]
+pub struct RoomMut<'a> {
+    id: RoomId,
+    manager: &'a mut RoomManager,
+}
+
+impl RoomMut<'_> {
+    /// Id of the [`Room`]
+    pub fn id(&self) -> RoomId {
+        self.id
+    }
+
+    /// Add a client to the [`Room`]
+    pub fn add_client(&mut self, client_id: ClientId) -> &mut Self {
+        self.manager.add_client_internal(self.id, client_id);
+        self
+    }
+
+    /// Remove a client from the [`Room`]
+    pub fn remove_client(&mut self, client_id: ClientId) -> &mut Self {
+        self.manager.remove_client_internal(self.id, client_id);
+        self
+    }
+
+    /// Add an entity to the [`Room`]
+    pub fn add_entity(&mut self, entity: Entity) -> &mut Self {
+        self.manager.add_entity_internal(self.id, entity);
+        self
+    }
+
+    /// Remove an entity from the [`Room`]
+    pub fn remove_entity(&mut self, entity: Entity) -> &mut Self {
+        self.manager.remove_entity_internal(self.id, entity);
+        self
+    }
+}
+
 /// Plugin used to handle interest managements via [`Room`]s
 #[derive(Default)]
 pub struct RoomPlugin;
@@ -139,6 +201,7 @@ impl Plugin for RoomPlugin {
     fn build(&self, app: &mut App) {
         // RESOURCES
         app.init_resource::<RoomManager>();
+        app.init_resource::<InterestRecompute>();
         // SETS
         app.configure_sets(
             PostUpdate,
@@ -159,9 +222,11 @@ impl Plugin for RoomPlugin {
         app.add_systems(
             PostUpdate,
             (
-                systems::buffer_room_relevance_events
-                    .in_set(RoomSystemSets::UpdateReplicationCaches),
-            ),
+                systems::recompute_room_relevance,
+                systems::buffer_room_relevance_events,
+            )
+                .chain()
+                .in_set(RoomSystemSets::UpdateReplicationCaches),
         );
         app.observe(systems::handle_client_disconnect);
         app.observe(systems::clean_entity_despawns);
@@ -171,7 +236,7 @@ impl Plugin for RoomPlugin {
 impl RoomManager {
     /// Remove the client from all the rooms it was in
     fn client_disconnect(&mut self, client_id: ClientId) {
-        if let Some(rooms) = self.data.client_to_rooms.remove(&client_id) {
+        if let Some(rooms) = self.data.client_to_rooms.get(&client_id).cloned() {
             for room_id in rooms {
                 self.remove_client_internal(room_id, client_id);
             }
@@ -180,7 +245,7 @@ impl RoomManager {
 
     /// Remove the entity from all the rooms it was in
     fn entity_despawn(&mut self, entity: Entity) {
-        if let Some(rooms) = self.data.entity_to_rooms.remove(&entity) {
+        if let Some(rooms) = self.data.entity_to_rooms.get(&entity).cloned() {
             for room_id in rooms {
                 self.remove_entity_internal(room_id, entity);
             }
@@ -229,64 +294,177 @@ impl RoomManager {
         self.data.rooms.get(&room_id).unwrap()
     }
 
-    fn add_client_internal(&mut self, room_id: RoomId, client_id: ClientId) {
+    /// Get a mutable handle on a room by its [`RoomId`], to add or remove clients and entities.
+    ///
+    /// The room is created when the first client or entity is added to it.
+    pub fn room_mut(&mut self, room_id: RoomId) -> RoomMut<'_> {
+        RoomMut {
+            id: room_id,
+            manager: self,
+        }
+    }
+
+    /// Returns true if the entity and the client share at least one [`Room`]
+    pub fn is_relevant(&self, client_id: ClientId, entity: Entity) -> bool {
         self.data
-            .client_to_rooms
-            .entry(client_id)
-            .or_default()
-            .insert(room_id);
+            .shared_rooms
+            .get(&entity)
+            .is_some_and(|clients| clients.contains_key(&client_id))
+    }
+
+    /// Iterate through the clients that share at least one [`Room`] with the entity.
+    ///
+    /// This is kept up-to-date every time a room changes, so it is cheap to call.
+    pub fn relevant_clients(&self, entity: Entity) -> impl Iterator<Item = ClientId> + '_ {
         self.data
-            .rooms
-            .entry(room_id)
-            .or_default()
-            .clients
-            .insert(client_id);
-        self.events.client_enter_room(room_id, client_id);
+            .shared_rooms
+            .get(&entity)
+            .into_iter()
+            .flat_map(|clients| clients.keys().copied())
     }
 
-    fn remove_client_internal(&mut self, room_id: RoomId, client_id: ClientId) {
+    /// Compute from scratch the clients that share at least one [`Room`] with the entity,
+    /// by going through all the rooms of the entity.
+    ///
+    /// This returns the same clients as [`relevant_clients`](Self::relevant_clients), but is more expensive.
+    pub fn compute_relevant_clients(&self, entity: Entity) -> HashSet<ClientId> {
         self.data
+            .entity_to_rooms
+            .get(&entity)
+            .into_iter()
+            .flatten()
+            .filter_map(|room_id| self.data.rooms.get(room_id))
+            .flat_map(|room| room.clients.iter().copied())
+            .collect()
+    }
+
+    /// Recompute from scratch the number of rooms shared by each entity and client,
+    /// and buffer relevance events for the pairs where the incremental state was wrong.
+    ///
+    /// Returns the number of pairs that were corrected.
+    fn recompute_shared_rooms(&mut self) -> usize {
+        let mut shared_rooms = SharedRooms::default();
+        for room in self.data.rooms.values() {
+            if room.clients.is_empty() {
+                continue;
+            }
+            for entity in room.entities.iter() {
+                let clients = shared_rooms.entry(*entity).or_default();
+                for client_id in room.clients.iter() {
+                    *clients.entry(*client_id).or_default() += 1;
+                }
+            }
+        }
+        let mut corrections = 0;
+        for (entity, clients) in shared_rooms.iter() {
+            for client_id in clients.keys() {
+                if !self.is_relevant(*client_id, *entity) {
+                    self.events.gain(*client_id, *entity);
+                    corrections += 1;
+                }
+            }
+        }
+        for (entity, clients) in self.data.shared_rooms.iter() {
+            for client_id in clients.keys() {
+                if !shared_rooms
+                    .get(entity)
+                    .is_some_and(|clients| clients.contains_key(client_id))
+                {
+                    self.events.lose(*client_id, *entity);
+                    corrections += 1;
+                }
+            }
+        }
+        self.data.shared_rooms = shared_rooms;
+        corrections
+    }
+
+    fn add_client_internal(&mut self, room_id: RoomId, client_id: ClientId) {
+        if !self
+            .data
             .client_to_rooms
             .entry(client_id)
             .or_default()
-            .remove(&room_id);
-        self.data
-            .rooms
-            .entry(room_id)
-            .or_default()
-            .clients
-            .remove(&client_id);
-        self.events.client_leave_room(room_id, client_id);
+            .insert(room_id)
+        {
+            return;
+        }
+        let room = self.data.rooms.entry(room_id).or_default();
+        room.clients.insert(client_id);
+        for entity in room.entities.iter() {
+            share_room(
+                &mut self.data.shared_rooms,
+                &mut self.events,
+                *entity,
+                client_id,
+            );
+        }
+    }
+
+    fn remove_client_internal(&mut self, room_id: RoomId, client_id: ClientId) {
+        let Some(rooms) = self.data.client_to_rooms.get_mut(&client_id) else {
+            return;
+        };
+        if !rooms.remove(&room_id) {
+            return;
+        }
+        if rooms.is_empty() {
+            self.data.client_to_rooms.remove(&client_id);
+        }
+        let room = self.data.rooms.entry(room_id).or_default();
+        room.clients.remove(&client_id);
+        for entity in room.entities.iter() {
+            unshare_room(
+                &mut self.data.shared_rooms,
+                &mut self.events,
+                *entity,
+                client_id,
+            );
+        }
     }
 
     fn add_entity_internal(&mut self, room_id: RoomId, entity: Entity) {
-        self.data
+        if !self
+            .data
             .entity_to_rooms
             .entry(entity)
             .or_default()
-            .insert(room_id);
-        self.data
-            .rooms
-            .entry(room_id)
-            .or_default()
-            .entities
-            .insert(entity);
-        self.events.entity_enter_room(room_id, entity);
+            .insert(room_id)
+        {
+            return;
+        }
+        let room = self.data.rooms.entry(room_id).or_default();
+        room.entities.insert(entity);
+        for client_id in room.clients.iter() {
+            share_room(
+                &mut self.data.shared_rooms,
+                &mut self.events,
+                entity,
+                *client_id,
+            );
+        }
     }
 
     fn remove_entity_internal(&mut self, room_id: RoomId, entity: Entity) {
-        self.data
-            .entity_to_rooms
-            .entry(entity)
-            .or_default()
-            .remove(&room_id);
-        self.data
-            .rooms
-            .entry(room_id)
-            .or_default()
-            .entities
-            .remove(&entity);
-        self.events.entity_leave_room(room_id, entity);
+        let Some(rooms) = self.data.entity_to_rooms.get_mut(&entity) else {
+            return;
+        };
+        if !rooms.remove(&room_id) {
+            return;
+        }
+        if rooms.is_empty() {
+            self.data.entity_to_rooms.remove(&entity);
+        }
+        let room = self.data.rooms.entry(room_id).or_default();
+        room.entities.remove(&entity);
+        for client_id in room.clients.iter() {
+            unshare_room(
+                &mut self.data.shared_rooms,
+                &mut self.events,
+                entity,
+                *client_id,
+            );
+        }
     }
 
     fn has_entity_internal(&self, room_id: RoomId, entity: Entity) -> bool {
@@ -305,101 +483,82 @@ impl RoomManager {
     }
 }
 
-impl RoomEvents {
-    fn is_empty(&self) -> bool {
-        self.client_enter_room.is_empty()
-            && self.client_leave_room.is_empty()
-            && self.entity_enter_room.is_empty()
-            && self.entity_leave_room.is_empty()
-    }
-
-    fn clear(&mut self) {
-        self.client_enter_room.clear();
-        self.client_leave_room.clear();
-        self.entity_enter_room.clear();
-        self.entity_leave_room.clear();
+/// The entity and the client now share one more room
+fn share_room(
+    shared_rooms: &mut SharedRooms,
+    events: &mut RoomEvents,
+    entity: Entity,
+    client_id: ClientId,
+) {
+    let count = shared_rooms
+        .entry(entity)
+        .or_default()
+        .entry(client_id)
+        .or_default();
+    *count += 1;
+    if *count == 1 {
+        events.gain(client_id, entity);
     }
+}
 
-    /// A client joined a room
-    pub fn client_enter_room(&mut self, room_id: RoomId, client_id: ClientId) {
-        // if the client had left the room and re-entered, no need to track the enter
-        if !self
-            .client_leave_room
-            .entry(client_id)
-            .or_default()
-            .remove(&room_id)
-        {
-            self.client_enter_room
-                .entry(client_id)
-                .or_default()
-                .insert(room_id);
+/// The entity and the client now share one less room
+fn unshare_room(
+    shared_rooms: &mut SharedRooms,
+    events: &mut RoomEvents,
+    entity: Entity,
+    client_id: ClientId,
+) {
+    let Some(clients) = shared_rooms.get_mut(&entity) else {
+        return;
+    };
+    let Some(count) = clients.get_mut(&client_id) else {
+        return;
+    };
+    *count -= 1;
+    if *count == 0 {
+        clients.remove(&client_id);
+        if clients.is_empty() {
+            shared_rooms.remove(&entity);
         }
+        events.lose(client_id, entity);
     }
+}
 
-    pub fn client_leave_room(&mut self, room_id: RoomId, client_id: ClientId) {
-        // if the client had entered the room and left, no need to track the leaving
-        if !self
-            .client_enter_room
-            .entry(client_id)
-            .or_default()
-            .remove(&room_id)
-        {
-            self.client_leave_room
-                .entry(client_id)
-                .or_default()
-                .insert(room_id);
-        }
+impl RoomEvents {
+    fn is_empty(&self) -> bool {
+        self.gained.values().all(|entities| entities.is_empty())
+            && self.lost.values().all(|entities| entities.is_empty())
     }
 
-    pub fn entity_enter_room(&mut self, room_id: RoomId, entity: Entity) {
+    /// The entity became relevant to the client
+    fn gain(&mut self, client_id: ClientId, entity: Entity) {
+        // if the entity had stopped being relevant during the same send_interval, the two events cancel out
         if !self
-            .entity_leave_room
-            .entry(entity)
-            .or_default()
-            .remove(&room_id)
+            .lost
+            .get_mut(&client_id)
+            .is_some_and(|entities| entities.remove(&entity))
         {
-            self.entity_enter_room
-                .entry(entity)
-                .or_default()
-                .insert(room_id);
+            self.gained.entry(client_id).or_default().insert(entity);
         }
     }
 
-    pub fn entity_leave_room(&mut self, room_id: RoomId, entity: Entity) {
+    /// The entity stopped being relevant to the client
+    fn lose(&mut self, client_id: ClientId, entity: Entity) {
         if !self
-            .entity_enter_room
-            .entry(entity)
-            .or_default()
-            .remove(&room_id)
+            .gained
+            .get_mut(&client_id)
+            .is_some_and(|entities| entities.remove(&entity))
         {
-            self.entity_leave_room
-                .entry(entity)
-                .or_default()
-                .insert(room_id);
+            self.lost.entry(client_id).or_default().insert(entity);
         }
     }
-
-    fn iter_client_enter_room(&self) -> impl Iterator<Item = (&ClientId, &HashSet<RoomId>)> {
-        self.client_enter_room.iter()
-    }
-
-    fn iter_client_leave_room(&self) -> impl Iterator<Item = (&ClientId, &HashSet<RoomId>)> {
-        self.client_leave_room.iter()
-    }
-
-    fn iter_entity_enter_room(&self) -> impl Iterator<Item = (&Entity, &HashSet<RoomId>)> {
-        self.entity_enter_room.iter()
-    }
-
-    fn iter_entity_leave_room(&self) -> impl Iterator<Item = (&Entity, &HashSet<RoomId>)> {
-        self.entity_leave_room.iter()
-    }
 }
 
 pub(super) mod systems {
     use super::*;
     use crate::prelude::ReplicationGroup;
     use crate::server::events::DisconnectEvent;
+    use crate::server::relevance::immediate::CachedNetworkRelevance;
     use bevy::prelude::Trigger;
 
     /// Clear the internal room buffers when a client disconnects
@@ -410,64 +569,56 @@ pub(super) mod systems {
         room_manager.client_disconnect(trigger.event().client_id);
     }
 
-    // TODO: (perf) split this into 4 separate functions that access RoomManager in parallel?
-    //  (we only use the ids in events, so we can read them in parallel)
-    /// Update each entities' replication-client-list based on the room events
-    /// Note that the rooms' entities/clients have already been updated at this point
-    pub fn buffer_room_relevance_events(
+    /// Correct the room relevance that could not be kept up-to-date incrementally:
+    /// - when a full recompute is due, the shared rooms are recomputed from scratch
+    /// - the entities that just got a [`CachedNetworkRelevance`] could have been added to their rooms before
+    ///   the cache existed, in which case the relevance events were lost
+    pub fn recompute_room_relevance(
         mut room_manager: ResMut<RoomManager>,
-        mut relevance_manager: ResMut<RelevanceManager>,
+        recompute: Res<InterestRecompute>,
+        added: Query<Entity, Added<CachedNetworkRelevance>>,
     ) {
-        if !room_manager.events.is_empty() {
-            trace!(?room_manager.events, "Room events");
+        if recompute.due {
+            let corrections = room_manager.recompute_shared_rooms();
+            if corrections > 0 {
+                debug!(
+                    ?corrections,
+                    "The full recompute of the rooms corrected the relevance of some (client, entity) pairs"
+                );
+            }
         }
         // enable split borrows by reborrowing Mut
         let room_manager = &mut *room_manager;
-
-        // NOTE: we handle leave room events before join room events so that if an entity leaves room 1 to join room 2
-        //  and the client is in both rooms, the entity does not get despawned
-
-        // entity left room
-        for (entity, rooms) in room_manager.events.entity_leave_room.drain() {
-            // for each room left, update the entity's client relevance list if the client was in the room
-            rooms.into_iter().for_each(|room_id| {
-                let room = room_manager.data.rooms.get(&room_id).unwrap();
-                room.clients.iter().for_each(|client_id| {
-                    trace!("entity {entity:?} left room {room:?}. Sending lost relevance to client {client_id:?}");
-                    relevance_manager.lose_relevance(*client_id, entity);
-                });
-            });
+        for entity in added.iter() {
+            if let Some(clients) = room_manager.data.shared_rooms.get(&entity) {
+                for client_id in clients.keys() {
+                    room_manager.events.gain(*client_id, entity);
+                }
+            }
         }
-        // entity joined room
-        for (entity, rooms) in room_manager.events.entity_enter_room.drain() {
-            // for each room joined, update the entity's client relevance list
-            rooms.into_iter().for_each(|room_id| {
-                let room = room_manager.data.rooms.get(&room_id).unwrap();
-                room.clients.iter().for_each(|client_id| {
-                    trace!("entity {entity:?} joined room {room:?}. Sending gained relevance to client {client_id:?}");
-                    relevance_manager.gain_relevance(*client_id, entity);
-                });
-            });
+    }
+
+    /// Forward the relevance changes caused by the room updates to the [`RelevanceManager`].
+    /// Note that the rooms' entities/clients have already been updated at this point
+    pub fn buffer_room_relevance_events(
+        mut room_manager: ResMut<RoomManager>,
+        mut relevance_manager: ResMut<RelevanceManager>,
+    ) {
+        if room_manager.events.is_empty() {
+            return;
         }
-        // client left room: update all the entities that are in that room
-        for (client_id, rooms) in room_manager.events.client_leave_room.drain() {
-            rooms.into_iter().for_each(|room_id| {
-                let room = room_manager.data.rooms.get(&room_id).unwrap();
-                room.entities.iter().for_each(|entity| {
-                    trace!("client {client_id:?} left room {room:?}. Sending lost relevance to entity {entity:?}");
-                    relevance_manager.lose_relevance(client_id, *entity);
-                });
-            });
+        trace!(?room_manager.events, "Room events");
+        for (client_id, entities) in room_manager.events.lost.drain() {
+            for entity in entities {
+                trace!("entity {entity:?} no longer shares a room with client {client_id:?}. Sending lost relevance");
+                relevance_manager.lose_relevance(client_id, entity);
+            }
         }
-        // client joined room: update all the entities that are in that room
-        for (client_id, rooms) in room_manager.events.client_enter_room.drain() {
-            rooms.into_iter().for_each(|room_id| {
-                let room = room_manager.data.rooms.get(&room_id).unwrap();
-                room.entities.iter().for_each(|entity| {
-                    trace!("client {client_id:?} joined room {room:?}. Sending gained relevance to entity {entity:?}");
-                    relevance_manager.gain_relevance(client_id, *entity);
-                });
-            });
+        for (client_id, entities) in room_manager.events.gained.drain() {
+            for entity in entities {
+                trace!("entity {entity:?} now shares a room with client {client_id:?}. Sending gained relevance");
+                relevance_manager.gain_relevance(client_id, entity);
+            }
         }
     }
 
@@ -487,14 +638,16 @@ mod tests {
     use bevy::prelude::Events;
     use bevy::utils::HashMap;
 
+    use crate::client::prediction::resource::PredictionManager;
     use crate::prelude::client::*;
-    use crate::prelude::server::Replicate;
+    use crate::prelude::server::{Replicate, SyncTarget};
     use crate::prelude::*;
     use crate::server::relevance::immediate::systems::{
         add_cached_network_relevance, update_relevance_from_events,
     };
     use crate::server::relevance::immediate::{CachedNetworkRelevance, ClientRelevance};
     use crate::shared::replication::components::NetworkRelevanceMode;
+    use crate::tests::protocol::*;
     use crate::tests::stepper::{BevyStepper, Step};
 
     use super::systems::buffer_room_relevance_events;
@@ -547,10 +700,10 @@ mod tests {
             .world()
             .resource::<RoomManager>()
             .events
-            .entity_enter_room
-            .get(&server_entity)
+            .gained
+            .get(&client_id)
             .unwrap()
-            .contains(&room_id));
+            .contains(&server_entity));
         // Run update replication cache once
         stepper
             .server_app
@@ -627,10 +780,10 @@ mod tests {
             .world()
             .resource::<RoomManager>()
             .events
-            .entity_leave_room
-            .get(&server_entity)
+            .lost
+            .get(&client_id)
             .unwrap()
-            .contains(&room_id));
+            .contains(&server_entity));
         stepper
             .server_app
             .world_mut()
@@ -723,10 +876,10 @@ mod tests {
             .world()
             .resource::<RoomManager>()
             .events
-            .client_enter_room
+            .gained
             .get(&client_id)
             .unwrap()
-            .contains(&room_id));
+            .contains(&server_entity));
         // Run update replication cache once
         stepper
             .server_app
@@ -796,10 +949,10 @@ mod tests {
             .world()
             .resource::<RoomManager>()
             .events
-            .client_leave_room
+            .lost
             .get(&client_id)
             .unwrap()
-            .contains(&room_id));
+            .contains(&server_entity));
         stepper
             .server_app
             .world_mut()
@@ -1160,4 +1313,260 @@ mod tests {
     }
 
     // TODO: check that entity despawn/client disconnect cleans the room metadata
+
+    /// Compute from scratch the (client, entity) pairs that share at least one room
+    fn relevant_pairs_from_scratch(
+        room_manager: &RoomManager,
+        entities: &[Entity],
+    ) -> HashSet<(ClientId, Entity)> {
+        entities
+            .iter()
+            .flat_map(|entity| {
+                room_manager
+                    .compute_relevant_clients(*entity)
+                    .into_iter()
+                    .map(|client_id| (client_id, *entity))
+            })
+            .collect()
+    }
+
+    /// Apply random room updates, and check that the relevance obtained by applying the incremental
+    /// events matches the relevance computed from scratch after every send interval
+    #[test]
+    fn test_incremental_relevance_matches_full_recompute() {
+        use rand::rngs::StdRng;
+        use rand::{Rng, SeedableRng};
+
+        for seed in 0..20 {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let mut room_manager = RoomManager::default();
+            let clients: Vec<ClientId> = (0..6).map(ClientId::Netcode).collect();
+            let entities: Vec<Entity> = (0..30).map(Entity::from_raw).collect();
+            let num_rooms = 8;
+            // relevance as seen by the receiver of the events
+            let mut relevant = HashSet::<(ClientId, Entity)>::default();
+
+            for _ in 0..50 {
+                // a send interval with a random number of updates
+                for _ in 0..rng.gen_range(0..40) {
+                    let room_id = RoomId(rng.gen_range(0..num_rooms));
+                    let client_id = clients[rng.gen_range(0..clients.len())];
+                    let entity = entities[rng.gen_range(0..entities.len())];
+                    match rng.gen_range(0..10) {
+                        0..=2 => room_manager.add_entity(entity, room_id),
+                        3..=5 => room_manager.remove_entity(entity, room_id),
+                        6 => room_manager.add_client(client_id, room_id),
+                        7 => room_manager.remove_client(client_id, room_id),
+                        8 => {
+                            // the entity moves to another cell
+                            if let Some(old_room) = room_manager
+                                .data
+                                .entity_to_rooms
+                                .get(&entity)
+                                .and_then(|rooms| rooms.iter().next().copied())
+                            {
+                                room_manager.remove_entity(entity, old_room);
+                            }
+                            room_manager.add_entity(entity, room_id);
+                        }
+                        _ => {
+                            if rng.gen_bool(0.5) {
+                                room_manager.client_disconnect(client_id);
+                            } else {
+                                room_manager.entity_despawn(entity);
+                            }
+                        }
+                    }
+                }
+
+                // apply the events
+                for (client_id, lost) in room_manager.events.lost.drain() {
+                    for entity in lost {
+                        assert!(
+                            relevant.remove(&(client_id, entity)),
+                            "seed {seed}: lost relevance of a pair that was not relevant"
+                        );
+                    }
+                }
+                for (client_id, gained) in room_manager.events.gained.drain() {
+                    for entity in gained {
+                        assert!(
+                            relevant.insert((client_id, entity)),
+                            "seed {seed}: gained relevance of a pair that was already relevant"
+                        );
+                    }
+                }
+
+                let expected = relevant_pairs_from_scratch(&room_manager, &entities);
+                assert_eq!(relevant, expected, "seed {seed}");
+                for entity in entities.iter() {
+                    assert_eq!(
+                        room_manager
+                            .relevant_clients(*entity)
+                            .collect::<HashSet<_>>(),
+                        room_manager.compute_relevant_clients(*entity),
+                        "seed {seed}"
+                    );
+                }
+                // the full recompute has nothing to correct
+                assert_eq!(room_manager.recompute_shared_rooms(), 0, "seed {seed}");
+                assert!(room_manager.events.is_empty());
+            }
+        }
+    }
+
+    /// If the incremental state is wrong, the full recompute emits the events that fix the relevance
+    #[test]
+    fn test_full_recompute_corrects_relevance() {
+        let mut room_manager = RoomManager::default();
+        let client_id = ClientId::Netcode(0);
+        let entity = Entity::from_raw(0);
+        let other_entity = Entity::from_raw(1);
+        room_manager.add_client(client_id, RoomId(0));
+        room_manager.add_entity(entity, RoomId(0));
+        room_manager.events = RoomEvents::default();
+
+        // corrupt the incremental state
+        room_manager.data.shared_rooms.remove(&entity);
+        room_manager
+            .data
+            .shared_rooms
+            .entry(other_entity)
+            .or_default()
+            .insert(client_id, 1);
+
+        assert_eq!(room_manager.recompute_shared_rooms(), 2);
+        assert!(room_manager.is_relevant(client_id, entity));
+        assert!(!room_manager.is_relevant(client_id, other_entity));
+        assert!(room_manager.events.gained[&client_id].contains(&entity));
+        assert!(room_manager.events.lost[&client_id].contains(&other_entity));
+    }
+
+    /// Returns the confirmed and predicted entities of the server entity on the client
+    fn client_entities(stepper: &BevyStepper, server_entity: Entity) -> Option<(Entity, Entity)> {
+        let confirmed = *stepper
+            .client_app
+            .world()
+            .resource::<client::ConnectionManager>()
+            .replication_receiver
+            .remote_entity_map
+            .get_local(server_entity)?;
+        let predicted = stepper
+            .client_app
+            .world()
+            .get::<Confirmed>(confirmed)?
+            .predicted?;
+        Some((confirmed, predicted))
+    }
+
+    #[test]
+    // a predicted entity leaves the room of the client, is updated, then re-enters the room
+    fn test_reenter_room_predicted() {
+        let mut stepper = BevyStepper::default();
+        let client_id = ClientId::Netcode(111);
+        let room_id = RoomId(0);
+        let server_entity = stepper
+            .server_app
+            .world_mut()
+            .spawn((
+                Component1(1.0),
+                Replicate {
+                    sync: SyncTarget {
+                        prediction: NetworkTarget::All,
+                        ..default()
+                    },
+                    relevance_mode: NetworkRelevanceMode::InterestManagement,
+                    ..default()
+                },
+            ))
+            .id();
+        stepper
+            .server_app
+            .world_mut()
+            .resource_mut::<RoomManager>()
+            .room_mut(room_id)
+            .add_client(client_id)
+            .add_entity(server_entity);
+        for _ in 0..5 {
+            stepper.frame_step();
+        }
+        let (confirmed, predicted) = client_entities(&stepper, server_entity)
+            .expect("the entity was not replicated with prediction");
+        assert_eq!(
+            stepper.client_app.world().get::<Component1>(predicted),
+            Some(&Component1(1.0))
+        );
+
+        // the entity leaves the room: it is despawned on the client, but not on the server
+        stepper
+            .server_app
+            .world_mut()
+            .resource_mut::<RoomManager>()
+            .room_mut(room_id)
+            .remove_entity(server_entity);
+        for _ in 0..5 {
+            stepper.frame_step();
+        }
+        assert!(stepper
+            .server_app
+            .world()
+            .get_entity(server_entity)
+            .is_some());
+        assert!(stepper.client_app.world().get_entity(confirmed).is_none());
+        assert!(stepper.client_app.world().get_entity(predicted).is_none());
+        assert!(client_entities(&stepper, server_entity).is_none());
+
+        // the entity changes while it is not relevant, then re-enters the room
+        stepper
+            .server_app
+            .world_mut()
+            .get_mut::<Component1>(server_entity)
+            .unwrap()
+            .0 = 2.0;
+        stepper
+            .server_app
+            .world_mut()
+            .resource_mut::<RoomManager>()
+            .room_mut(room_id)
+            .add_entity(server_entity);
+        for _ in 0..5 {
+            stepper.frame_step();
+        }
+
+        // it is spawned again with its current state, with a new Confirmed/Predicted pair
+        let (new_confirmed, new_predicted) =
+            client_entities(&stepper, server_entity).expect("the entity was not replicated again");
+        assert_ne!(new_confirmed, confirmed);
+        assert_ne!(new_predicted, predicted);
+        assert_eq!(
+            stepper.client_app.world().get::<Component1>(new_confirmed),
+            Some(&Component1(2.0))
+        );
+        assert_eq!(
+            stepper.client_app.world().get::<Component1>(new_predicted),
+            Some(&Component1(2.0))
+        );
+        assert_eq!(
+            stepper
+                .client_app
+                .world()
+                .get::<Predicted>(new_predicted)
+                .unwrap()
+                .confirmed_entity,
+            Some(new_confirmed)
+        );
+        let mut prediction_manager = stepper
+            .client_app
+            .world_mut()
+            .resource_mut::<PredictionManager>();
+        let confirmed_to_predicted = &prediction_manager
+            .predicted_entity_map
+            .get_mut()
+            .confirmed_to_predicted;
+        assert_eq!(
+            confirmed_to_predicted.get(&new_confirmed),
+            Some(&new_predicted)
+        );
+        assert_eq!(confirmed_to_predicted.get(&confirmed), None);
+    }
 }
@@ -25,6 +25,8 @@ pub type ReplicationSet = InternalReplicationSet<ServerMarker>;
 
 pub(crate) mod receive {
     use super::*;
+    use crate::server::events::ReplicationLimitExceededEvent;
+    use crate::shared::replication::prespawn_ids::{self, PreSpawnIdRanges};
 
     #[derive(Default)]
     pub struct ServerReplicationReceivePlugin {
@@ -45,23 +47,99 @@ pub(crate) mod receive {
                         .run_if(is_started)
                         .after(InternalMainSet::<ServerMarker>::EmitEvents),
                 );
+
+            // PRE-SPAWNED ENTITY IDS
+            let prespawn_ids = app
+                .world()
+                .resource::<ServerConfig>()
+                .replication
+                .prespawn_ids;
+            app.insert_resource(PreSpawnIdRanges::new(prespawn_ids))
+                .add_systems(FixedFirst, prespawn_ids::start_server_tick)
+                .add_systems(
+                    PreUpdate,
+                    prespawn_ids::reject_foreign_ids
+                        .run_if(is_started)
+                        .after(InternalMainSet::<ServerMarker>::EmitEvents)
+                        .before(ServerReplicationSet::ClientReplication),
+                );
+            app.observe(prespawn_ids::assign_client_range);
+            app.observe(prespawn_ids::release_client_range);
+            // LIMITS
+            app.add_systems(
+                PreUpdate,
+                handle_replication_limits
+                    .run_if(is_started)
+                    .after(InternalMainSet::<ServerMarker>::Receive),
+            );
+        }
+    }
+
+    /// Emit a [`ReplicationLimitExceededEvent`] for each replication limit that a client exceeded during the frame,
+    /// and disconnect the client if [`ReplicationLimits::disconnect`](crate::shared::replication::limits::ReplicationLimits::disconnect)
+    /// is enabled
+    pub(crate) fn handle_replication_limits(
+        mut connection_manager: ResMut<ConnectionManager>,
+        mut events: EventWriter<ReplicationLimitExceededEvent>,
+    ) {
+        let connection_manager = &mut *connection_manager;
+        let mut kicked = vec![];
+        for (client_id, connection) in connection_manager.connections.iter_mut() {
+            let limiter = &mut connection.replication_receiver.limiter;
+            let disconnect = limiter.limits().disconnect;
+            for (limit, rejected) in limiter.drain_exceeded() {
+                events.send(ReplicationLimitExceededEvent {
+                    client_id: *client_id,
+                    limit,
+                    rejected,
+                });
+                // the client can keep exceeding the limits until it is disconnected at the start of the next frame
+                let already_kicked = kicked.contains(client_id)
+                    || connection_manager.kicked_clients.contains(client_id)
+                    || connection_manager
+                        .pending_kicks
+                        .iter()
+                        .any(|(kicked_id, _)| kicked_id == client_id);
+                if disconnect && !already_kicked {
+                    error!(
+                        ?client_id,
+                        ?limit,
+                        "The client exceeded a replication limit, disconnecting"
+                    );
+                    kicked.push(*client_id);
+                }
+            }
+        }
+        for client_id in kicked {
+            let _ = connection_manager
+                .disconnect_client(client_id, Some("replication limit exceeded".to_string()));
         }
     }
 }
 
 pub(crate) mod send {
     use super::*;
+    use crate::channel::builder::JoinSnapshotChannel;
+    use crate::packet::message::MessageId;
     use crate::prelude::{
-        is_host_server, ClientId, ComponentRegistry, DisabledComponent, NetworkRelevanceMode,
-        OverrideTargetComponent, ReplicateHierarchy, ReplicationGroup, ShouldBePredicted,
+        is_host_server, ClientId, ComponentRegistry, DisabledComponent, LocalPlayerId,
+        NetworkRelevanceMode, OverrideTargetComponent, ReplicateHierarchy, ReplicationGroup, ShouldBePredicted,
         TargetEntity, Tick, TickManager, TimeManager,
     };
-    use crate::protocol::component::ComponentKind;
+    use crate::protocol::component::{ComponentError, ComponentKind};
     use crate::server::error::ServerError;
+    use crate::server::personalized::PersonalizedComponents;
     use crate::server::relevance::immediate::{CachedNetworkRelevance, ClientRelevance};
+    use crate::server::relevance::predicate::PredicateRelevance;
+    use crate::shared::hooks::{
+        has_network_hooks, run_network_hooks, JoinSnapshotMessage, PreReplicationSend,
+    };
     use crate::shared::replication::archetypes::{get_erased_component, ReplicatedArchetypes};
+    use crate::shared::replication::classes::ReplicationClass;
+    use crate::shared::replication::keyframe::KeyframeBudget;
+    use crate::shared::replication::lifetime::despawn_expired_entities;
     use crate::shared::replication::components::{
-        Cached, Controlled, Replicating, ReplicationGroupId, ReplicationTarget,
+        Cached, Controlled, ReplacedBy, Replicating, ReplicationGroupId, ReplicationTarget,
         ShouldBeInterpolated,
     };
     use crate::shared::replication::network_target::NetworkTarget;
@@ -118,12 +196,29 @@ pub(crate) mod send {
                         .in_set(InternalReplicationSet::<ServerMarker>::BufferEntityUpdates)
                         .in_set(InternalReplicationSet::<ServerMarker>::BufferComponentUpdates),
                     (
-                        handle_replication_target_update,
+                        update_cached::<ReplicationTarget>,
+                        update_cached::<SyncTarget>,
+                        update_cached::<ControlledBy>,
+                        handle_replication_group_update,
                         buffer_replication_messages,
                     )
                         .in_set(InternalReplicationSet::<ServerMarker>::AfterBuffer),
                 ),
             );
+            // HOOKS
+            app.add_systems(
+                PostUpdate,
+                run_pre_replication_send_hooks
+                    .run_if(has_network_hooks::<PreReplicationSend>)
+                    .after(InternalReplicationSet::<ServerMarker>::BeforeBuffer)
+                    .before(InternalReplicationSet::<ServerMarker>::Buffer)
+                    .in_set(InternalReplicationSet::<ServerMarker>::SendMessages)
+                    .in_set(InternalReplicationSet::<ServerMarker>::All),
+            );
+            app.add_systems(
+                FixedPostUpdate,
+                despawn_expired_entities.run_if(is_started),
+            );
             // HOST-SERVER
             app.add_systems(
                 PostUpdate,
@@ -165,6 +260,9 @@ pub(crate) mod send {
         pub target: NetworkTarget,
         /// What happens to the entity if the controlling client disconnects?
         pub lifetime: Lifetime,
+        /// Which of the client's local players controls the entity, if multiple players share
+        /// the same connection
+        pub local_player: LocalPlayerId,
     }
 
     impl ControlledBy {
@@ -172,6 +270,11 @@ pub(crate) mod send {
         pub fn targets(&self, client_id: &ClientId) -> bool {
             self.target.targets(client_id)
         }
+
+        /// Returns true if the entity is controlled by the specified local player of the client
+        pub fn targets_local_player(&self, client_id: &ClientId, local_player: LocalPlayerId) -> bool {
+            self.local_player == local_player && self.targets(client_id)
+        }
     }
 
     #[derive(Clone, Copy, Debug, Default, PartialEq, Reflect)]
@@ -200,13 +303,23 @@ pub(crate) mod send {
     /// - [`SyncTarget`] to specify which clients should predict/interpolate the entity
     /// - [`ControlledBy`] to specify which client controls the entity
     /// - [`NetworkRelevanceMode`] to specify if we should replicate the entity to all clients in the
-    /// replication target, or if we should apply interest management logic to determine which clients
+    ///   replication target, or if we should apply interest management logic to determine which clients
     /// - [`ReplicationGroup`] to group entities together for replication. Entities in the same group
-    /// will be sent together in the same message.
+    ///   will be sent together in the same message.
     /// - [`ReplicateHierarchy`] to specify how the hierarchy of the entity should be replicated
     ///
-    /// Some of the components can be updated at runtime even after the entity has been replicated.
-    /// For example you can update the [`ReplicationTarget`] to change which clients should receive the entity.
+    /// The components can be updated at runtime even after the entity has been replicated:
+    /// - [`ReplicationTarget`]: the entity is spawned on the clients that were added to the target, and
+    ///   despawned on the clients that were removed from it
+    /// - [`SyncTarget`]: clients that were added to the prediction (or interpolation) target spawn a
+    ///   predicted (or interpolated) entity. Removing a client from the target is not supported and logs
+    ///   a warning: remove the client from the [`ReplicationTarget`] and add it back instead.
+    /// - [`ControlledBy`]: [`Controlled`] is inserted on the clients that gain control of the entity,
+    ///   and removed on the clients that lose it
+    /// - [`NetworkRelevanceMode`] and [`ReplicateHierarchy`] are taken into account immediately
+    /// - [`ReplicationGroup`]: the priority and send frequency can be changed. If the group id changes, the
+    ///   entity is moved to the new group: the clients that already have it receive all its components in
+    ///   the new group, and ignore the messages of the previous group that still arrive for it.
     #[derive(Bundle, Clone, Default, PartialEq, Debug, Reflect)]
     pub struct Replicate {
         /// Which clients should this entity be replicated to?
@@ -219,8 +332,8 @@ pub(crate) mod send {
         pub controlled_by: ControlledBy,
         /// The replication group defines how entities are grouped (sent as a single message) for replication.
         ///
-        /// After the entity is first replicated, the replication group of the entity should not be modified.
-        /// (but more entities can be added to the replication group)
+        /// After the entity is first replicated, the group id of the entity cannot be modified: the entity
+        /// keeps being replicated in its original group. (but more entities can be added to the replication group)
         pub group: ReplicationGroup,
         /// How should the hierarchy of the entity (parents/children) be replicated?
         pub hierarchy: ReplicateHierarchy,
@@ -243,6 +356,7 @@ pub(crate) mod send {
             .unwrap_or_else(|e| {
                 error!("Error preparing replicate send: {}", e);
             });
+        send_join_snapshots(&mut connection_manager);
         // TODO: how to handle this for replication groups that update less frequently?
         //  only component updates should update less frequently, but entity spawns/removals
         //  should be sent with the same frequency!
@@ -250,6 +364,46 @@ pub(crate) mod send {
         connection_manager.new_clients.clear();
     }
 
+    /// Send a [`JoinSnapshotMessage`] to the clients that connected since the last replication send,
+    /// with the last actions message of each replication group, which spawned the entities that already existed
+    fn send_join_snapshots(connection_manager: &mut ConnectionManager) {
+        for client_id in connection_manager.new_clients.clone() {
+            let Some(connection) = connection_manager.connections.get(&client_id) else {
+                continue;
+            };
+            if connection.is_local_client() {
+                continue;
+            }
+            let groups = connection
+                .replication_sender
+                .group_channels
+                .iter()
+                .filter(|(_, channel)| channel.actions_next_send_message_id != MessageId(0))
+                .map(|(group_id, channel)| (*group_id, channel.actions_next_send_message_id - 1))
+                .collect();
+            let _ = connection_manager
+                .send_message_to_target::<JoinSnapshotChannel, _>(
+                    &JoinSnapshotMessage { groups },
+                    NetworkTarget::Single(client_id),
+                )
+                .inspect_err(|e| error!("Could not send the join snapshot: {e:?}"));
+        }
+    }
+
+    /// Run the [`PreReplicationSend`] hooks for each client, before the replication messages are buffered
+    fn run_pre_replication_send_hooks(world: &mut World) {
+        let client_ids: Vec<ClientId> = world
+            .resource::<ConnectionManager>()
+            .connections
+            .iter()
+            .filter(|(_, connection)| !connection.is_local_client())
+            .map(|(client_id, _)| *client_id)
+            .collect();
+        for client_id in client_ids {
+            run_network_hooks::<PreReplicationSend>(world, client_id);
+        }
+    }
+
     /// In HostServer mode, we will add the Predicted/Interpolated components to the server entities
     /// So that client code can still query for them
     fn add_prediction_interpolation_components(
@@ -257,7 +411,7 @@ pub(crate) mod send {
         query: Query<(
             Entity,
             Ref<ReplicationTarget>,
-            &SyncTarget,
+            Ref<SyncTarget>,
             Option<Ref<ControlledBy>>,
             Option<&PrePredicted>,
         )>,
@@ -274,9 +428,16 @@ pub(crate) mod send {
                         // NOTE: do not replicate this Controlled to other clients, or they will
                         // think they control this entity
                         .insert((Controlled, DisabledComponent::<Controlled>::default()));
+                    if controlled_by.local_player != LocalPlayerId::default() {
+                        commands.entity(entity).insert((
+                            controlled_by.local_player,
+                            DisabledComponent::<LocalPlayerId>::default(),
+                        ));
+                    }
                 }
             }
-            if (replication_target.is_changed()) && replication_target.target.targets(&local_client)
+            if (replication_target.is_changed() || sync_target.is_changed())
+                && replication_target.target.targets(&local_client)
             {
                 if pre_predicted.is_some_and(|pre_predicted| pre_predicted.client_entity.is_none())
                 {
@@ -316,28 +477,69 @@ pub(crate) mod send {
         pub(crate) replication_clients_cache: Vec<ClientId>,
     }
 
-    /// Keep a cached version of the [`ReplicationTarget`] component so that when it gets updated
-    /// we can compute a diff with the previous value.
+    /// Keep a cached version of a replication component ([`ReplicationTarget`], [`SyncTarget`], [`ControlledBy`])
+    /// so that when it gets updated we can compute a diff with the previous value.
     ///
     /// This needs to run after we compute the diff, so after the `replicate` system runs
-    pub(crate) fn handle_replication_target_update(
+    pub(crate) fn update_cached<C: Component + Clone>(
+        mut commands: Commands,
+        mut query: Query<(Entity, &C, Option<&mut Cached<C>>), Changed<C>>,
+    ) {
+        for (entity, component, cached) in query.iter_mut() {
+            if let Some(mut cached) = cached {
+                cached.value = component.clone();
+            } else {
+                commands.entity(entity).insert(Cached {
+                    value: component.clone(),
+                });
+            }
+        }
+    }
+
+    /// Group id that the entity is replicated with.
+    ///
+    /// The clients that already have the entity keep it in this group until they receive the
+    /// [`SpawnAction::Regroup`](crate::shared::replication::SpawnAction) that moves it to a new group.
+    #[derive(Component, Debug, PartialEq)]
+    pub(crate) struct ActiveReplicationGroup {
+        pub(crate) group_id: ReplicationGroupId,
+        /// Group that the entity was replicated with before the [`ReplicationGroup`] id was modified
+        pub(crate) previous: Option<ReplicationGroupId>,
+    }
+
+    /// Record the group id that the entity is replicated with; if the user modifies it, the entity
+    /// is moved to the new group during the next send.
+    pub(crate) fn handle_replication_group_update(
         mut commands: Commands,
         mut query: Query<
             (
                 Entity,
-                &ReplicationTarget,
-                Option<&mut Cached<ReplicationTarget>>,
+                &ReplicationGroup,
+                Option<&mut ActiveReplicationGroup>,
             ),
-            Changed<ReplicationTarget>,
+            (Changed<ReplicationGroup>, With<Replicating>),
         >,
     ) {
-        for (entity, replication_target, cached) in query.iter_mut() {
-            if let Some(mut cached) = cached {
-                cached.value = replication_target.clone();
-            } else {
-                commands.entity(entity).insert(Cached {
-                    value: replication_target.clone(),
+        for (entity, group, active_group) in query.iter_mut() {
+            let group_id = group.group_id(Some(entity));
+            let Some(mut active_group) = active_group else {
+                commands.entity(entity).insert(ActiveReplicationGroup {
+                    group_id,
+                    previous: None,
                 });
+                continue;
+            };
+            // the group also changes every time its send_frequency timer is ticked: only mutate
+            // the component when the id changes, since `replicate` uses its change ticks
+            if active_group.group_id != group_id {
+                debug!(
+                    ?entity,
+                    previous = ?active_group.group_id,
+                    new = ?group_id,
+                    "The ReplicationGroup id of the entity was modified, moving it to the new group"
+                );
+                active_group.previous = Some(active_group.group_id);
+                active_group.group_id = group_id;
             }
         }
     }
@@ -381,12 +583,33 @@ pub(crate) mod send {
                 let entity_ref = world.entity(entity.id());
                 let group = entity_ref.get::<ReplicationGroup>();
 
-                let group_id = group.map_or(ReplicationGroupId::default(), |g| {
-                    g.group_id(Some(entity.id()))
-                });
+                let active_group = entity_ref.get::<ActiveReplicationGroup>();
+                let group_id = match active_group {
+                    Some(active_group) => active_group.group_id,
+                    None => group.map_or(ReplicationGroupId::default(), |g| {
+                        g.group_id(Some(entity.id()))
+                    }),
+                };
+                // the group id was modified since the last send
+                let previous_group_id = active_group
+                    .and_then(|active_group| active_group.previous)
+                    .filter(|_| {
+                        entity_ref
+                            .get_change_ticks::<ActiveReplicationGroup>()
+                            .is_some_and(|ticks| {
+                                ticks.is_changed(system_ticks.last_run(), system_ticks.this_run())
+                                    && !ticks
+                                        .is_added(system_ticks.last_run(), system_ticks.this_run())
+                            })
+                    });
                 let priority = group.map_or(1.0, |g| g.priority());
+                let class = group.map_or(ReplicationClass::default(), |g| g.class());
                 let cached_replication_target = entity_ref.get::<Cached<ReplicationTarget>>();
-                let visibility = entity_ref.get::<CachedNetworkRelevance>();
+                // the replication predicates can hide the entity from some of the clients it is relevant to
+                let predicate_relevance = entity_ref.get::<PredicateRelevance>();
+                let visibility = predicate_relevance
+                    .map(|p| &p.relevance)
+                    .or_else(|| entity_ref.get::<CachedNetworkRelevance>());
                 let sync_target = entity_ref.get::<SyncTarget>();
                 let target_entity = entity_ref.get::<TargetEntity>();
                 let controlled_by = entity_ref.get::<ControlledBy>();
@@ -431,6 +654,7 @@ pub(crate) mod send {
                     cached_replication_target,
                     group_id,
                     priority,
+                    class,
                     controlled_by,
                     sync_target,
                     target_entity,
@@ -439,8 +663,41 @@ pub(crate) mod send {
                     &system_ticks,
                 );
 
+                // c''. move the entity to its new group for the clients that already have it
+                let regroup_target = match previous_group_id {
+                    Some(previous_group_id) => replicate_entity_regroup(
+                        entity.id(),
+                        previous_group_id,
+                        group_id,
+                        priority,
+                        class,
+                        &replication_target,
+                        cached_replication_target,
+                        visibility,
+                        &mut sender,
+                    ),
+                    None => NetworkTarget::None,
+                };
+
+                // c'. send the changes of SyncTarget and ControlledBy to the clients that already have the entity
+                replicate_sync_target_and_control_updates(
+                    &component_registry,
+                    entity.id(),
+                    group_id,
+                    &replication_target,
+                    cached_replication_target,
+                    visibility,
+                    sync_target,
+                    entity_ref.get::<Cached<SyncTarget>>(),
+                    controlled_by,
+                    entity_ref.get::<Cached<ControlledBy>>(),
+                    &mut sender,
+                    &system_ticks,
+                );
+
                 // If the group is not set to send, skip sending updates for this entity
-                if group.is_some_and(|g| !g.should_send) {
+                // (the entity still needs to be sent to the clients that it was moved to a new group for)
+                if group.is_some_and(|g| !g.should_send) && regroup_target.is_empty() {
                     continue;
                 }
 
@@ -473,22 +730,104 @@ pub(crate) mod send {
                         &replication_target,
                         sync_target,
                         group_id,
+                        &regroup_target,
                         visibility,
+                        predicate_relevance,
                         replicated_component.delta_compression,
                         replicated_component.replicate_once,
+                        replicated_component
+                            .keyframe
+                            .map(|config| config.budget(tick_manager.config.tick_duration)),
+                        replicated_component
+                            .mode
+                            .interval_ticks(tick_manager.config.tick_duration),
                         override_target,
                         &system_ticks,
                         &mut sender,
                     );
                 }
 
-                // e. add all removed components
+                // e. add all personalized components
+                if let Some(personalized) = world.get_resource::<PersonalizedComponents>() {
+                    replicate_personalized_components(
+                        world,
+                        personalized,
+                        &component_registry,
+                        entity.id(),
+                        &replication_target,
+                        group_id,
+                        visibility,
+                        &system_ticks,
+                        &mut sender,
+                    );
+                }
             }
         }
 
         *set.p1() = sender;
     }
 
+    /// Compute the personalized components of the entity for each client that the entity is replicated to,
+    /// and send the ones that changed since the last send
+    pub(crate) fn replicate_personalized_components(
+        world: &World,
+        personalized: &PersonalizedComponents,
+        component_registry: &ComponentRegistry,
+        entity: Entity,
+        replication_target: &ReplicationTarget,
+        group_id: ReplicationGroupId,
+        visibility: Option<&CachedNetworkRelevance>,
+        system_ticks: &SystemChangeTick,
+        sender: &mut ConnectionManager,
+    ) {
+        let target = match visibility {
+            Some(visibility) => NetworkTarget::from(
+                visibility
+                    .clients_cache
+                    .iter()
+                    .filter(|(client_id, relevance)| {
+                        replication_target.target.targets(client_id)
+                            && !matches!(relevance, ClientRelevance::Lost)
+                    })
+                    .map(|(client_id, _)| *client_id)
+                    .collect::<Vec<_>>(),
+            ),
+            None => replication_target.target.clone(),
+        };
+        for client_id in sender.connected_targets(target) {
+            for generator in personalized.generators.iter() {
+                let Some(net_id) = component_registry.kind_map.net_id(&generator.kind).copied()
+                else {
+                    continue;
+                };
+                let value = generator.generate(
+                    entity,
+                    client_id,
+                    world,
+                    component_registry,
+                    &mut sender.writer,
+                );
+                let _ = sender
+                    .connection_mut(client_id)
+                    .map(|connection| {
+                        connection
+                            .replication_sender
+                            .prepare_personalized_component(
+                                entity,
+                                group_id,
+                                generator.kind,
+                                net_id,
+                                value,
+                                system_ticks.this_run(),
+                            )
+                    })
+                    .inspect_err(|e| {
+                        error!("error sending personalized component: {:?}", e);
+                    });
+            }
+        }
+    }
+
     /// Send entity spawn replication messages to clients
     /// Also handles:
     /// - newly_connected_clients should receive the entity spawn message even if the entity was not just spawned
@@ -501,6 +840,7 @@ pub(crate) mod send {
         cached_replication_target: Option<&Cached<ReplicationTarget>>,
         group_id: ReplicationGroupId,
         priority: f32,
+        class: ReplicationClass,
         controlled_by: Option<&ControlledBy>,
         sync_target: Option<&SyncTarget>,
         target_entity: Option<&TargetEntity>,
@@ -524,6 +864,9 @@ pub(crate) mod send {
                                         ?client_id,
                                         "send entity spawn to client who just gained visibility"
                                     );
+                                    if let Ok(connection) = sender.connection_mut(*client_id) {
+                                        connection.session_stats.record_relevance_spawn();
+                                    }
                                     return Some(*client_id);
                                 }
                                 ClientRelevance::Lost => {}
@@ -583,7 +926,7 @@ pub(crate) mod send {
             .connected_targets(target)
             .try_for_each(|client_id| {
                 // let the client know that this entity is controlled by them
-                if controlled_by.is_some_and(|c| c.targets(&client_id)) {
+                if let Some(controlled_by) = controlled_by.filter(|c| c.targets(&client_id)) {
                     sender.prepare_typed_component_insert(
                         entity,
                         group_id,
@@ -592,6 +935,17 @@ pub(crate) mod send {
                         &Controlled,
                         system_ticks.this_run(),
                     )?;
+                    // and which of their local players controls it
+                    if controlled_by.local_player != LocalPlayerId::default() {
+                        sender.prepare_typed_component_insert(
+                            entity,
+                            group_id,
+                            client_id,
+                            component_registry,
+                            &controlled_by.local_player,
+                            system_ticks.this_run(),
+                        )?;
+                    }
                 }
                 // if we need to do prediction/interpolation, send a marker component to indicate that to the client
                 if sync_target.is_some_and(|sync| sync.prediction.targets(&client_id)) {
@@ -621,6 +975,11 @@ pub(crate) mod send {
                         .connection_mut(client_id)?
                         .replication_sender
                         .prepare_entity_spawn_reuse(entity, group_id, *remote_entity);
+                } else if let Some(TargetEntity::Replace(replaced_entity)) = target_entity {
+                    sender
+                        .connection_mut(client_id)?
+                        .replication_sender
+                        .prepare_entity_spawn_replace(entity, group_id, *replaced_entity);
                 } else {
                     sender
                         .connection_mut(client_id)?
@@ -628,11 +987,10 @@ pub(crate) mod send {
                         .prepare_entity_spawn(entity, group_id);
                 }
 
-                // also set the priority for the group when we spawn it
-                sender
-                    .connection_mut(client_id)?
-                    .replication_sender
-                    .update_base_priority(group_id, priority);
+                // also set the priority and the class for the group when we spawn it
+                let replication_sender = &mut sender.connection_mut(client_id)?.replication_sender;
+                replication_sender.update_base_priority(group_id, priority);
+                replication_sender.update_class(group_id, class);
                 Ok(())
             })
             .inspect_err(|e: &ServerError| {
@@ -640,6 +998,224 @@ pub(crate) mod send {
             });
     }
 
+    /// Send a [`SpawnAction::Regroup`](crate::shared::replication::SpawnAction) to the clients that already have
+    /// the entity, to move it from its previous group to its new group.
+    /// (the clients that receive the entity spawn in this send are handled by [`replicate_entity_spawn`])
+    ///
+    /// Returns the clients that the entity was moved for: they must receive all the components of the entity
+    /// as inserts in the new group.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn replicate_entity_regroup(
+        entity: Entity,
+        previous_group_id: ReplicationGroupId,
+        group_id: ReplicationGroupId,
+        priority: f32,
+        class: ReplicationClass,
+        replication_target: &Ref<ReplicationTarget>,
+        cached_replication_target: Option<&Cached<ReplicationTarget>>,
+        visibility: Option<&CachedNetworkRelevance>,
+        sender: &mut ConnectionManager,
+    ) -> NetworkTarget {
+        let target = match visibility {
+            Some(visibility) => NetworkTarget::from(
+                visibility
+                    .clients_cache
+                    .iter()
+                    .filter(|(client_id, visibility)| {
+                        replication_target.target.targets(client_id)
+                            && matches!(visibility, ClientRelevance::Maintained)
+                            && !replication_target.is_added()
+                    })
+                    .map(|(client_id, _)| *client_id)
+                    .collect::<Vec<_>>(),
+            ),
+            None => {
+                if replication_target.is_added() {
+                    return NetworkTarget::None;
+                }
+                let mut target = replication_target.target.clone();
+                // the clients that were added to the replication target receive the spawn in the new group
+                if replication_target.is_changed() {
+                    if let Some(cached_target) = cached_replication_target {
+                        target.intersection(&cached_target.value.target);
+                    }
+                }
+                let new_connected_clients = sender.new_connected_clients();
+                if !new_connected_clients.is_empty() {
+                    target.exclude(&NetworkTarget::Only(new_connected_clients));
+                }
+                target
+            }
+        };
+        if target.is_empty() {
+            return target;
+        }
+        debug!(
+            ?entity,
+            ?previous_group_id,
+            ?group_id,
+            "Prepare entity regroup"
+        );
+        let clients: Vec<_> = sender.connected_targets(target).collect();
+        for client_id in clients.iter() {
+            let Ok(connection) = sender.connection_mut(*client_id) else {
+                continue;
+            };
+            let replication_sender = &mut connection.replication_sender;
+            replication_sender.prepare_entity_regroup(entity, previous_group_id, group_id);
+            replication_sender.update_base_priority(group_id, priority);
+            replication_sender.update_class(group_id, class);
+        }
+        NetworkTarget::from(clients)
+    }
+
+    /// Send the changes of [`SyncTarget`] and [`ControlledBy`] to the clients that had already received the entity.
+    /// (the clients that receive the entity spawn in this send are handled by [`replicate_entity_spawn`])
+    ///
+    /// - clients added to the prediction/interpolation target receive [`ShouldBePredicted`]/[`ShouldBeInterpolated`],
+    ///   which makes them spawn a predicted/interpolated entity
+    /// - clients removed from the prediction/interpolation target are not updated: we log a warning
+    /// - clients that gain control of the entity receive [`Controlled`], clients that lose it get it removed
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn replicate_sync_target_and_control_updates(
+        component_registry: &ComponentRegistry,
+        entity: Entity,
+        group_id: ReplicationGroupId,
+        replication_target: &ReplicationTarget,
+        cached_replication_target: Option<&Cached<ReplicationTarget>>,
+        visibility: Option<&CachedNetworkRelevance>,
+        sync_target: Option<&SyncTarget>,
+        cached_sync_target: Option<&Cached<SyncTarget>>,
+        controlled_by: Option<&ControlledBy>,
+        cached_controlled_by: Option<&Cached<ControlledBy>>,
+        sender: &mut ConnectionManager,
+        system_ticks: &SystemChangeTick,
+    ) {
+        let sync_changed =
+            cached_sync_target.is_some_and(|cached| Some(&cached.value) != sync_target);
+        let control_changed =
+            cached_controlled_by.is_some_and(|cached| Some(&cached.value) != controlled_by);
+        if !sync_changed && !control_changed {
+            return;
+        }
+        // the clients that already had the entity before this send
+        let Some(cached_replication_target) = cached_replication_target else {
+            return;
+        };
+        let mut target = cached_replication_target.value.target.clone();
+        target.intersection(&replication_target.target);
+        if let Some(visibility) = visibility {
+            target.intersection(&NetworkTarget::Only(
+                visibility
+                    .clients_cache
+                    .iter()
+                    .filter(|(_, relevance)| matches!(relevance, ClientRelevance::Maintained))
+                    .map(|(client_id, _)| *client_id)
+                    .collect(),
+            ));
+        }
+        target.exclude(&NetworkTarget::Only(sender.new_connected_clients()));
+        if target.is_empty() {
+            return;
+        }
+
+        let default_sync_target = SyncTarget::default();
+        let sync_target = sync_target.unwrap_or(&default_sync_target);
+        let previous_sync_target = cached_sync_target.map_or(sync_target, |cached| &cached.value);
+        let previous_controlled_by = cached_controlled_by.map(|cached| &cached.value);
+        let clients: Vec<ClientId> = sender.connected_targets(target).collect();
+        let _ = clients
+            .into_iter()
+            .try_for_each(|client_id| {
+                let prediction = sync_target.prediction.targets(&client_id);
+                let was_predicted = previous_sync_target.prediction.targets(&client_id);
+                if prediction && !was_predicted {
+                    debug!(?entity, ?client_id, "client was added to the prediction target");
+                    sender.prepare_typed_component_insert(
+                        entity,
+                        group_id,
+                        client_id,
+                        component_registry,
+                        &ShouldBePredicted,
+                        system_ticks.this_run(),
+                    )?;
+                }
+                let interpolation = sync_target.interpolation.targets(&client_id);
+                let was_interpolated = previous_sync_target.interpolation.targets(&client_id);
+                if interpolation && !was_interpolated {
+                    debug!(?entity, ?client_id, "client was added to the interpolation target");
+                    sender.prepare_typed_component_insert(
+                        entity,
+                        group_id,
+                        client_id,
+                        component_registry,
+                        &ShouldBeInterpolated,
+                        system_ticks.this_run(),
+                    )?;
+                }
+                if (was_predicted && !prediction) || (was_interpolated && !interpolation) {
+                    warn!(
+                        ?entity,
+                        ?client_id,
+                        "Removing a client from the SyncTarget of an entity that was already replicated to it is not supported; \
+                        the client keeps its predicted/interpolated entity. Remove the client from the ReplicationTarget and add it back instead."
+                    );
+                }
+
+                let controlled = controlled_by.is_some_and(|c| c.targets(&client_id));
+                let previous = previous_controlled_by.filter(|c| c.targets(&client_id));
+                let local_player = controlled_by
+                    .filter(|_| controlled)
+                    .map_or(LocalPlayerId::default(), |c| c.local_player);
+                let previous_local_player =
+                    previous.map_or(LocalPlayerId::default(), |c| c.local_player);
+                if controlled && previous.is_none() {
+                    debug!(?entity, ?client_id, "client gained control of the entity");
+                    sender.prepare_typed_component_insert(
+                        entity,
+                        group_id,
+                        client_id,
+                        component_registry,
+                        &Controlled,
+                        system_ticks.this_run(),
+                    )?;
+                } else if !controlled && previous.is_some() {
+                    debug!(?entity, ?client_id, "client lost control of the entity");
+                    let net_id = component_registry
+                        .get_net_id::<Controlled>()
+                        .ok_or::<ServerError>(ComponentError::NotRegistered.into())?;
+                    sender
+                        .connection_mut(client_id)?
+                        .replication_sender
+                        .prepare_component_remove(entity, group_id, net_id);
+                }
+                if local_player != previous_local_player {
+                    if local_player != LocalPlayerId::default() {
+                        sender.prepare_typed_component_insert(
+                            entity,
+                            group_id,
+                            client_id,
+                            component_registry,
+                            &local_player,
+                            system_ticks.this_run(),
+                        )?;
+                    } else {
+                        let net_id = component_registry
+                            .get_net_id::<LocalPlayerId>()
+                            .ok_or::<ServerError>(ComponentError::NotRegistered.into())?;
+                        sender
+                            .connection_mut(client_id)?
+                            .replication_sender
+                            .prepare_component_remove(entity, group_id, net_id);
+                    }
+                }
+                Ok(())
+            })
+            .inspect_err(|e: &ServerError| {
+                error!("error sending sync target or control update: {:?}", e);
+            });
+    }
+
     /// Despawn entities when the entity gets despawned on local world
     pub(crate) fn replicate_entity_local_despawn(
         // we use the removal of ReplicationGroup to detect the despawn
@@ -650,31 +1226,47 @@ pub(crate) mod send {
                 &ReplicationGroup,
                 &ReplicationTarget,
                 Option<&CachedNetworkRelevance>,
+                Option<&PredicateRelevance>,
+                Option<&ReplacedBy>,
+                Option<&ActiveReplicationGroup>,
             ),
             With<Replicating>,
         >,
         sender: Option<ResMut<ConnectionManager>>,
     ) {
         let entity = trigger.entity();
-        if let Ok((replication_group, network_target, cached_relevance)) = query.get(entity) {
+        if let Ok((
+            replication_group,
+            network_target,
+            cached_relevance,
+            predicate_relevance,
+            replaced_by,
+            active_group,
+        )) = query.get(entity)
+        {
             if let Some(mut sender) = sender {
                 trace!(?entity, "Replicate entity despawn");
                 // only send the despawn to clients who were in the target of the entity
                 let mut target = network_target.clone().target;
                 // only send the despawn to clients that had visibility of the entity
-                if let Some(network_relevance) = cached_relevance {
+                if let Some(network_relevance) =
+                    predicate_relevance.map(|p| &p.relevance).or(cached_relevance)
+                {
                     // TODO: optimize this in cases like All/None/Single/ExceptSingle
                     target.intersection(&NetworkTarget::Only(
                         network_relevance.clients_cache.keys().copied().collect(),
                     ))
                 }
                 trace!(?entity, ?target, "send entity despawn");
-                let _ = sender
-                    .prepare_entity_despawn(
-                        entity,
-                        replication_group.group_id(Some(entity)),
-                        target,
-                    )
+                let group_id = active_group.map_or_else(
+                    || replication_group.group_id(Some(entity)),
+                    |active_group| active_group.group_id,
+                );
+                let _ = if replaced_by.is_some() {
+                    sender.prepare_entity_despawn_replaced(entity, group_id, target)
+                } else {
+                    sender.prepare_entity_despawn(entity, group_id, target)
+                }
                     // TODO: bubble up errors to user via ConnectionEvents?
                     .inspect_err(|e| {
                         error!("error sending entity despawn: {:?}", e);
@@ -707,6 +1299,9 @@ pub(crate) mod send {
                                 "sending entity despawn for entity: {:?} because ClientVisibility::Lost",
                                 entity
                             );
+                            if let Ok(connection) = sender.connection_mut(*client_id) {
+                                connection.session_stats.record_relevance_despawn();
+                            }
                             return Some(*client_id);
                         }
                         None
@@ -727,7 +1322,7 @@ pub(crate) mod send {
         }
         if !target.is_empty() {
             let _ = sender
-                .prepare_entity_despawn(entity, group_id, target)
+                .prepare_entity_interest_lost(entity, group_id, target)
                 .inspect_err(|e| {
                     error!("error sending entity despawn: {:?}", e);
                 });
@@ -743,7 +1338,12 @@ pub(crate) mod send {
     /// Updates are sent only for any components that were changed since the most recent of:
     /// - last time we sent an action for that group
     /// - last time we sent an update for that group which got acked.
-    /// (currently we only check for the second condition, which is enough but less efficient)
+    ///   (currently we only check for the second condition, which is enough but less efficient)
+    ///
+    /// If keyframes are enabled for the component, its full value is also periodically re-sent
+    /// to the clients even if it didn't change.
+    ///
+    /// If the component has a send interval, its changes are sent to each client at most once per interval.
     ///
     /// NOTE: cannot use ConnectEvents because they are reset every frame
     pub(crate) fn replicate_component_updates(
@@ -756,9 +1356,13 @@ pub(crate) mod send {
         replication_target: &Ref<ReplicationTarget>,
         sync_target: Option<&SyncTarget>,
         group_id: ReplicationGroupId,
+        regroup_target: &NetworkTarget,
         visibility: Option<&CachedNetworkRelevance>,
+        predicate_relevance: Option<&PredicateRelevance>,
         delta_compression: bool,
         replicate_once: bool,
+        keyframe: Option<KeyframeBudget>,
+        send_interval: Option<i16>,
         override_target: Option<&NetworkTarget>,
         system_ticks: &SystemChangeTick,
         sender: &mut ConnectionManager,
@@ -768,7 +1372,7 @@ pub(crate) mod send {
         let target = override_target.map_or(&replication_target.target, |override_target| {
             override_target
         });
-        let (insert_target, mut update_target): (NetworkTarget, NetworkTarget) = match visibility {
+        let (mut insert_target, mut update_target) = match visibility {
             Some(visibility) => {
                 let mut insert_clients = vec![];
                 let mut update_clients = vec![];
@@ -822,15 +1426,14 @@ pub(crate) mod send {
                 {
                     trace!("component is added or replication_target is added");
                     insert_target.union(target);
-                } else {
+                } else if replicate_once {
                     // do not send updates for these components, only inserts/removes
-                    if replicate_once {
-                        trace!(?entity,
-                                "not replicating updates for {:?} because it is marked as replicate_once",
-                                "COMPONENT_KIND"
-                            );
-                        return;
-                    }
+                    // (but the newly connected clients below still get the component)
+                    trace!(?entity,
+                            "not replicating updates for {:?} because it is marked as replicate_once",
+                            "COMPONENT_KIND"
+                        );
+                } else {
                     // otherwise send an update for all components that changed since the
                     // last update we have ack-ed
                     update_target.union(target);
@@ -848,6 +1451,19 @@ pub(crate) mod send {
                 (insert_target, update_target)
             }
         };
+        // the clients that the entity was moved to a new group for receive all its components again
+        if !regroup_target.is_empty() {
+            let mut regroup_target = regroup_target.clone();
+            regroup_target.intersection(target);
+            insert_target.union(&regroup_target);
+        }
+        if let Some(predicate_relevance) = predicate_relevance {
+            predicate_relevance.filter_component(
+                component_kind,
+                &mut insert_target,
+                &mut update_target,
+            );
+        }
 
         // do not send a component as both update and insert
         update_target.exclude(&insert_target);
@@ -884,6 +1500,8 @@ pub(crate) mod send {
                         system_ticks.this_run(),
                         current_tick,
                         delta_compression,
+                        keyframe,
+                        send_interval,
                     )
                     .inspect_err(|e| {
                         error!("error sending component update: {:?}", e);
@@ -912,6 +1530,8 @@ pub(crate) mod send {
     ) {
         let kind = registry.net_id::<C>();
         removed.read().for_each(|entity| {
+            // the component is gone, stop tracking its keyframes
+            sender.remove_component_keyframes(entity, ComponentKind::of::<C>());
             if let Ok((replication_target, group, visibility, disabled, override_target)) =
                 query.get(entity)
             {
@@ -981,8 +1601,9 @@ pub(crate) mod send {
         use crate::prelude::client::Confirmed;
         use crate::prelude::server::{ControlledBy, NetConfig, RelevanceManager, Replicate};
         use crate::prelude::{
-            client, server, DeltaCompression, LinkConditionerConfig, ReplicateOnceComponent,
-            Replicated,
+            client, server, AppComponentExt, DeltaCompression, KeyframeConfig,
+            LinkConditionerConfig, ReplicateOnceComponent, Replicated, ReplicationMode,
+            SharedConfig, TickConfig,
         };
         use crate::server::replication::send::SyncTarget;
         use crate::shared::replication::components::{Controlled, ReplicationGroupId};
@@ -2014,23 +2635,25 @@ pub(crate) mod send {
             );
         }
 
+        /// Check that keyframes re-send the component even if it didn't change, so that
+        /// a client that missed an update eventually gets the correct value
         #[test]
-        fn test_component_update_delta() {
+        fn test_component_update_keyframe() {
             let mut stepper = BevyStepper::default();
+            // send a keyframe every 5 ticks
+            stepper
+                .server_app
+                .world_mut()
+                .resource_mut::<ComponentRegistry>()
+                .set_keyframes::<Component1>(KeyframeConfig::new(Duration::from_millis(50)));
 
             // spawn an entity on server
             let server_entity = stepper
                 .server_app
                 .world_mut()
-                .spawn((
-                    Replicate::default(),
-                    Component6(vec![1, 2]),
-                    DeltaCompression::<Component6>::default(),
-                ))
+                .spawn((Replicate::default(), Component1(1.0)))
                 .id();
-            let group_id = ReplicationGroupId(server_entity.to_bits());
             stepper.frame_step();
-            let insert_tick = stepper.server_tick();
             stepper.frame_step();
             let client_entity = *stepper
                 .client_app
@@ -2040,8 +2663,216 @@ pub(crate) mod send {
                 .remote_entity_map
                 .get_local(server_entity)
                 .expect("entity was not replicated to client");
-            // check that the component was replicated
-            assert_eq!(
+
+            // emulate a client that missed the last update
+            stepper
+                .client_app
+                .world_mut()
+                .entity_mut(client_entity)
+                .insert(Component1(2.0));
+            stepper.frame_step();
+            stepper.frame_step();
+            // the component didn't change on the server, and the keyframe interval hasn't elapsed
+            assert_eq!(
+                stepper
+                    .client_app
+                    .world()
+                    .entity(client_entity)
+                    .get::<Component1>()
+                    .expect("component missing"),
+                &Component1(2.0)
+            );
+
+            for _ in 0..6 {
+                stepper.frame_step();
+            }
+            // the keyframe restored the server value
+            assert_eq!(
+                stepper
+                    .client_app
+                    .world()
+                    .entity(client_entity)
+                    .get::<Component1>()
+                    .expect("component missing"),
+                &Component1(1.0)
+            );
+        }
+
+        /// Check that a component replicated with `ReplicationMode::EveryInterval` is sent at most
+        /// once per interval, and that its last change is sent once the interval elapses
+        #[test]
+        fn test_component_update_every_interval() {
+            let mut stepper = BevyStepper::default();
+            // send the updates at most every 5 ticks
+            stepper
+                .server_app
+                .world_mut()
+                .resource_mut::<ComponentRegistry>()
+                .set_replication_mode::<Component1>(ReplicationMode::EveryInterval(
+                    Duration::from_millis(50),
+                ));
+
+            let server_entity = stepper
+                .server_app
+                .world_mut()
+                .spawn((Replicate::default(), Component1(1.0)))
+                .id();
+            stepper.frame_step();
+            stepper.frame_step();
+            let client_entity = *stepper
+                .client_app
+                .world()
+                .resource::<client::ConnectionManager>()
+                .replication_receiver
+                .remote_entity_map
+                .get_local(server_entity)
+                .expect("entity was not replicated to client");
+
+            // the component changes every tick
+            let mut received = vec![];
+            for i in 0..10 {
+                stepper
+                    .server_app
+                    .world_mut()
+                    .entity_mut(server_entity)
+                    .insert(Component1(2.0 + i as f32));
+                stepper.frame_step();
+                let value = stepper
+                    .client_app
+                    .world()
+                    .get::<Component1>(client_entity)
+                    .expect("component missing")
+                    .0;
+                if received.last() != Some(&value) {
+                    received.push(value);
+                }
+            }
+            // the initial value, then at most one update per interval
+            assert!(received.len() <= 3, "received {received:?}");
+
+            // the last change is sent even though the component does not change anymore
+            for _ in 0..6 {
+                stepper.frame_step();
+            }
+            assert_eq!(
+                stepper
+                    .client_app
+                    .world()
+                    .get::<Component1>(client_entity)
+                    .expect("component missing"),
+                &Component1(11.0)
+            );
+        }
+
+        /// Under packet loss, keyframes guarantee that the client converges to the server value
+        #[test]
+        fn test_component_update_keyframe_with_packet_loss() {
+            let frame_duration = Duration::from_millis(10);
+            let shared_config = SharedConfig {
+                tick: TickConfig::new(frame_duration),
+                ..Default::default()
+            };
+            let client_config = client::ClientConfig {
+                net: client::NetConfig::Netcode {
+                    auth: Default::default(),
+                    config: Default::default(),
+                    io: client::IoConfig::default().with_conditioner(LinkConditionerConfig {
+                        incoming_latency: Duration::default(),
+                        incoming_jitter: Duration::default(),
+                        incoming_loss: 0.3,
+                        seed: None,
+                    }),
+                },
+                ..default()
+            };
+            let mut stepper = BevyStepper::new(shared_config, client_config, frame_duration);
+            stepper.init();
+            // send a keyframe every 5 ticks
+            stepper
+                .server_app
+                .world_mut()
+                .resource_mut::<ComponentRegistry>()
+                .set_keyframes::<Component1>(KeyframeConfig::new(Duration::from_millis(50)));
+
+            let server_entity = stepper
+                .server_app
+                .world_mut()
+                .spawn((Replicate::default(), Component1(1.0)))
+                .id();
+            // entity spawns are reliable, so the entity will be replicated eventually
+            let mut client_entity = None;
+            for _ in 0..100 {
+                stepper.frame_step();
+                client_entity = stepper
+                    .client_app
+                    .world()
+                    .resource::<client::ConnectionManager>()
+                    .replication_receiver
+                    .remote_entity_map
+                    .get_local(server_entity)
+                    .copied();
+                if client_entity.is_some() {
+                    break;
+                }
+            }
+            let client_entity = client_entity.expect("entity was not replicated to client");
+
+            // update the component once, and emulate a client that lost that update
+            stepper
+                .server_app
+                .world_mut()
+                .entity_mut(server_entity)
+                .insert(Component1(2.0));
+            stepper.frame_step();
+            stepper
+                .client_app
+                .world_mut()
+                .entity_mut(client_entity)
+                .insert(Component1(0.0));
+
+            // after 10 keyframe intervals, the probability that all keyframes were lost is 0.3^10
+            for _ in 0..50 {
+                stepper.frame_step();
+            }
+            assert_eq!(
+                stepper
+                    .client_app
+                    .world()
+                    .entity(client_entity)
+                    .get::<Component1>()
+                    .expect("component missing"),
+                &Component1(2.0)
+            );
+        }
+
+        #[test]
+        fn test_component_update_delta() {
+            let mut stepper = BevyStepper::default();
+
+            // spawn an entity on server
+            let server_entity = stepper
+                .server_app
+                .world_mut()
+                .spawn((
+                    Replicate::default(),
+                    Component6(vec![1, 2]),
+                    DeltaCompression::<Component6>::default(),
+                ))
+                .id();
+            let group_id = ReplicationGroupId(server_entity.to_bits());
+            stepper.frame_step();
+            let insert_tick = stepper.server_tick();
+            stepper.frame_step();
+            let client_entity = *stepper
+                .client_app
+                .world()
+                .resource::<client::ConnectionManager>()
+                .replication_receiver
+                .remote_entity_map
+                .get_local(server_entity)
+                .expect("entity was not replicated to client");
+            // check that the component was replicated
+            assert_eq!(
                 stepper
                     .client_app
                     .world()
@@ -2159,7 +2990,6 @@ pub(crate) mod send {
         /// One component is delta, the other is not
         /// This fails to work if we don't have an ack tick specific to the delta component
         #[test]
-        #[ignore]
         fn test_component_update_delta_with_non_delta_component() {
             let mut stepper = BevyStepper::default();
 
@@ -2177,7 +3007,6 @@ pub(crate) mod send {
             let group_id = ReplicationGroupId(server_entity.to_bits());
             stepper.frame_step();
             let insert_tick = stepper.server_tick();
-            dbg!(insert_tick);
             stepper.frame_step();
             let client_entity = *stepper
                 .client_app
@@ -2314,12 +3143,92 @@ pub(crate) mod send {
                 .is_none());
         }
 
+        /// Under packet loss, the diffs are only computed from values that the client received,
+        /// so the client converges to the server value
+        #[test]
+        fn test_component_update_delta_with_packet_loss() {
+            let frame_duration = Duration::from_millis(10);
+            let shared_config = SharedConfig {
+                tick: TickConfig::new(frame_duration),
+                ..Default::default()
+            };
+            let client_config = client::ClientConfig {
+                net: client::NetConfig::Netcode {
+                    auth: Default::default(),
+                    config: Default::default(),
+                    io: client::IoConfig::default().with_conditioner(LinkConditionerConfig {
+                        incoming_latency: Duration::default(),
+                        incoming_jitter: Duration::default(),
+                        incoming_loss: 0.3,
+                        seed: None,
+                    }),
+                },
+                ..default()
+            };
+            let mut stepper = BevyStepper::new(shared_config, client_config, frame_duration);
+            stepper.init();
+
+            let server_entity = stepper
+                .server_app
+                .world_mut()
+                .spawn((
+                    Replicate::default(),
+                    Component6(vec![1]),
+                    DeltaCompression::<Component6>::default(),
+                ))
+                .id();
+            // entity spawns are reliable, so the entity will be replicated eventually
+            let mut client_entity = None;
+            for _ in 0..100 {
+                stepper.frame_step();
+                client_entity = stepper
+                    .client_app
+                    .world()
+                    .resource::<client::ConnectionManager>()
+                    .replication_receiver
+                    .remote_entity_map
+                    .get_local(server_entity)
+                    .copied();
+                if client_entity.is_some() {
+                    break;
+                }
+            }
+            let client_entity = client_entity.expect("entity was not replicated to client");
+
+            // the component changes every tick
+            for i in 2..30 {
+                stepper
+                    .server_app
+                    .world_mut()
+                    .entity_mut(server_entity)
+                    .get_mut::<Component6>()
+                    .unwrap()
+                    .0
+                    .push(i);
+                stepper.frame_step();
+            }
+            // the lost updates are sent again
+            for _ in 0..100 {
+                stepper.frame_step();
+            }
+            assert_eq!(
+                stepper
+                    .client_app
+                    .world()
+                    .entity(client_entity)
+                    .get::<Component6>()
+                    .expect("component missing"),
+                &Component6((1..30).collect())
+            );
+        }
+
         /// We want to test the following case:
         /// - server sends a diff between ticks 1-3
         /// - client receives that and applies it
         /// - server sends a diff between ticks 1-5 (because the server hasn't received the
-        /// ack for tick 3 yet)
+        ///   ack for tick 3 yet)
         /// - client receives that, applies it, and it still works even if client was already on tick 3
+        ///
         /// We can emulate this by adding some delay on the server receiving client packets via the link conditioner.
         #[test]
         fn test_component_update_delta_non_idempotent_slow_ack() {
@@ -2343,6 +3252,7 @@ pub(crate) mod send {
                     incoming_latency: Duration::from_millis(30),
                     incoming_jitter: Default::default(),
                     incoming_loss: 0.0,
+                    seed: None,
                 })
             }
             stepper.start();
@@ -2492,8 +3402,9 @@ pub(crate) mod send {
         /// - server sends a diff between ticks 1-3
         /// - client receives that and applies it
         /// - server sends a diff between ticks 1-5 (because the server hasn't received the
-        /// ack for tick 3 yet)
+        ///   ack for tick 3 yet)
         /// - client receives that, applies it, and it still works even if client was already on tick 3
+        ///
         /// We can emulate this by adding some delay on the server receiving client packets via the link conditioner.
         #[test]
         fn test_component_update_delta_idempotent_slow_ack() {
@@ -2514,6 +3425,7 @@ pub(crate) mod send {
                     incoming_latency: Duration::from_millis(30),
                     incoming_jitter: Default::default(),
                     incoming_loss: 0.0,
+                    seed: None,
                 })
             }
             stepper.start();
@@ -2983,13 +3895,119 @@ pub(crate) mod send {
                 1
             );
         }
+
+        /// Check that a personalized component is replicated with a different value to each client,
+        /// and is only re-sent when the generated value changes
+        #[test]
+        fn test_personalized_component() {
+            let mut stepper = MultiBevyStepper::default();
+            stepper
+                .server_app
+                .add_personalized::<Component1>(|entity, client_id, world| {
+                    let offset = world.get::<Component2>(entity)?.0;
+                    Some(Component1(client_id.to_bits() as f32 + offset))
+                });
+
+            let server_entity = stepper
+                .server_app
+                .world_mut()
+                .spawn((Replicate::default(), Component2(0.0)))
+                .id();
+            stepper.frame_step();
+            stepper.frame_step();
+
+            let client_entity_1 = *stepper
+                .client_app_1
+                .world()
+                .resource::<client::ConnectionManager>()
+                .replication_receiver
+                .remote_entity_map
+                .get_local(server_entity)
+                .expect("entity was not replicated to client 1");
+            let client_entity_2 = *stepper
+                .client_app_2
+                .world()
+                .resource::<client::ConnectionManager>()
+                .replication_receiver
+                .remote_entity_map
+                .get_local(server_entity)
+                .expect("entity was not replicated to client 2");
+            assert_eq!(
+                stepper
+                    .client_app_1
+                    .world()
+                    .get::<Component1>(client_entity_1),
+                Some(&Component1(TEST_CLIENT_ID_1 as f32))
+            );
+            assert_eq!(
+                stepper
+                    .client_app_2
+                    .world()
+                    .get::<Component1>(client_entity_2),
+                Some(&Component1(TEST_CLIENT_ID_2 as f32))
+            );
+
+            // the value did not change: no update is sent
+            #[derive(Resource, Default)]
+            struct Counter(u32);
+            stepper.client_app_1.init_resource::<Counter>();
+            stepper.client_app_1.add_systems(
+                Update,
+                |mut events: EventReader<ComponentUpdateEvent<Component1>>,
+                 mut counter: ResMut<Counter>| {
+                    counter.0 += events.read().count() as u32;
+                },
+            );
+            stepper.frame_step();
+            stepper.frame_step();
+            assert_eq!(stepper.client_app_1.world().resource::<Counter>().0, 0);
+
+            // the generated value changes for both clients
+            stepper
+                .server_app
+                .world_mut()
+                .entity_mut(server_entity)
+                .insert(Component2(10.0));
+            stepper.frame_step();
+            stepper.frame_step();
+            assert_eq!(stepper.client_app_1.world().resource::<Counter>().0, 1);
+            assert_eq!(
+                stepper
+                    .client_app_1
+                    .world()
+                    .get::<Component1>(client_entity_1),
+                Some(&Component1(TEST_CLIENT_ID_1 as f32 + 10.0))
+            );
+            assert_eq!(
+                stepper
+                    .client_app_2
+                    .world()
+                    .get::<Component1>(client_entity_2),
+                Some(&Component1(TEST_CLIENT_ID_2 as f32 + 10.0))
+            );
+
+            // the generator returns None: the component is removed
+            stepper
+                .server_app
+                .world_mut()
+                .entity_mut(server_entity)
+                .remove::<Component2>();
+            stepper.frame_step();
+            stepper.frame_step();
+            assert!(stepper
+                .client_app_1
+                .world()
+                .get::<Component1>(client_entity_1)
+                .is_none());
+        }
     }
 }
 
 pub(crate) mod commands {
-    use crate::prelude::Replicating;
+    use crate::prelude::{Replicating, TargetEntity};
+    use crate::shared::replication::components::ReplacedBy;
     use bevy::ecs::system::EntityCommands;
-    use bevy::prelude::{Entity, World};
+    use bevy::prelude::{DespawnRecursiveExt, Entity, World};
 
     fn despawn_without_replication(entity: Entity, world: &mut World) {
         // remove replicating separately so that when we despawn the entity and trigger the observer
@@ -3010,11 +4028,42 @@ pub(crate) mod commands {
         }
     }
 
+    fn replicate_as_replacement_of(replaced: Entity) -> impl FnOnce(Entity, &mut World) {
+        move |entity: Entity, world: &mut World| {
+            if let Some(mut entity_mut) = world.get_entity_mut(entity) {
+                entity_mut.insert(TargetEntity::Replace(replaced));
+            }
+            if let Some(mut replaced_mut) = world.get_entity_mut(replaced) {
+                replaced_mut.insert(ReplacedBy(entity));
+                replaced_mut.despawn_recursive();
+            }
+        }
+    }
+
+    pub trait ReplaceReplicationCommandExt {
+        /// Despawn the `replaced` entity and replicate this entity as its replacement.
+        ///
+        /// Clients re-use the entity that they had spawned for `replaced` instead of despawning it and
+        /// spawning a new one, so there is no flicker and the prediction/interpolation history is kept.
+        /// The components of the replacement are applied all at once, and the components of `replaced`
+        /// that the replacement doesn't have are removed.
+        ///
+        /// If a client doesn't receive the replacement (for example because it is not in the replication
+        /// target of the new entity), the replaced entity is despawned on that client after a short delay.
+        fn replicate_as_replacement_of(&mut self, replaced: Entity);
+    }
+    impl ReplaceReplicationCommandExt for EntityCommands<'_> {
+        fn replicate_as_replacement_of(&mut self, replaced: Entity) {
+            self.add(replicate_as_replacement_of(replaced));
+        }
+    }
+
     #[cfg(test)]
     mod tests {
-        use bevy::prelude::With;
+        use bevy::prelude::{default, With};
 
         use crate::prelude::server::Replicate;
+        use crate::prelude::{client, NetworkTarget, ReplicationTarget};
         use crate::tests::protocol::*;
         use crate::tests::stepper::{BevyStepper, Step};
 
@@ -3083,5 +4132,125 @@ pub(crate) mod commands {
                 .get_single(stepper.client_app.world())
                 .is_ok());
         }
+
+        /// The client re-uses the local entity of the replaced entity, and the components
+        /// of the replaced entity that the replacement doesn't have are removed
+        #[test]
+        fn test_replicate_as_replacement() {
+            let mut stepper = BevyStepper::default();
+
+            let old_entity = stepper
+                .server_app
+                .world_mut()
+                .spawn((Component1(1.0), Component2(1.0), Replicate::default()))
+                .id();
+            stepper.frame_step();
+            stepper.frame_step();
+            let client_entity = stepper
+                .client_app
+                .world_mut()
+                .query_filtered::<Entity, With<Component1>>()
+                .get_single(stepper.client_app.world())
+                .unwrap();
+
+            let new_entity = stepper
+                .server_app
+                .world_mut()
+                .spawn((Component1(2.0), Replicate::default()))
+                .id();
+            replicate_as_replacement_of(old_entity)(new_entity, stepper.server_app.world_mut());
+            assert!(stepper.server_app.world().get_entity(old_entity).is_none());
+
+            // the client entity is never despawned
+            for _ in 0..3 {
+                stepper.frame_step();
+                assert!(stepper
+                    .client_app
+                    .world()
+                    .get_entity(client_entity)
+                    .is_some());
+            }
+            let connection = stepper
+                .client_app
+                .world()
+                .resource::<client::ConnectionManager>();
+            assert_eq!(
+                connection
+                    .replication_receiver
+                    .remote_entity_map
+                    .get_local(new_entity),
+                Some(&client_entity)
+            );
+            assert!(connection
+                .replication_receiver
+                .remote_entity_map
+                .get_local(old_entity)
+                .is_none());
+            assert_eq!(
+                stepper
+                    .client_app
+                    .world()
+                    .get::<Component1>(client_entity),
+                Some(&Component1(2.0))
+            );
+            assert!(stepper
+                .client_app
+                .world()
+                .get::<Component2>(client_entity)
+                .is_none());
+        }
+
+        /// If the replacement is never received, the replaced entity is despawned after a timeout
+        #[test]
+        fn test_replicate_as_replacement_timeout() {
+            let mut stepper = BevyStepper::default();
+
+            let old_entity = stepper
+                .server_app
+                .world_mut()
+                .spawn((Component1(1.0), Replicate::default()))
+                .id();
+            stepper.frame_step();
+            stepper.frame_step();
+            let client_entity = stepper
+                .client_app
+                .world_mut()
+                .query_filtered::<Entity, With<Component1>>()
+                .get_single(stepper.client_app.world())
+                .unwrap();
+
+            // the replacement is not replicated to the client
+            let new_entity = stepper
+                .server_app
+                .world_mut()
+                .spawn((
+                    Component1(2.0),
+                    Replicate {
+                        target: ReplicationTarget {
+                            target: NetworkTarget::None,
+                        },
+                        ..default()
+                    },
+                ))
+                .id();
+            replicate_as_replacement_of(old_entity)(new_entity, stepper.server_app.world_mut());
+            for _ in 0..5 {
+                stepper.frame_step();
+            }
+            assert!(stepper
+                .client_app
+                .world()
+                .get_entity(client_entity)
+                .is_some());
+
+            for _ in 0..80 {
+                stepper.frame_step();
+            }
+            assert!(stepper
+                .client_app
+                .world()
+                .get_entity(client_entity)
+                .is_none());
+        }
     }
 }
@@ -0,0 +1,435 @@
+//! Rewind and replay of the server simulation.
+//!
+//! The [`RewindPlugin`] periodically takes a snapshot of all the replicated components of the entities
+//! that are being replicated (at the end of the tick), and keeps the most recent ones in a ring buffer.
+//! The inputs that the server applied on each tick are also kept in a history
+//! (see [`InputBuffers`](crate::server::input::native::InputBuffers)).
+//!
+//! This makes it possible to go back in time and re-simulate the last few ticks, for example after
+//! correcting an input that arrived late:
+//!
+//! ```rust,ignore
+//! fn handle_late_input(mut commands: Commands, mut input_buffers: ResMut<InputBuffers<MyInput>>) {
+//!     input_buffers.correct_input(client_id, LocalPlayerId::default(), tick, Some(late_input));
+//!     commands.rewind_to(tick);
+//! }
+//! ```
+//!
+//! Rewinding to a tick restores the most recent snapshot taken before that tick, then runs the `FixedMain`
+//! schedule again until the current tick is reached. Every component restored from the snapshot is marked as changed,
+//! so the clients receive the re-simulated values of all the entities of the snapshot.
+//!
+//! Only the systems that run in `FixedMain` are replayed, and the re-simulation is only deterministic if
+//! they only depend on the replicated components and on the inputs.
+//! Other limitations:
+//! - the replicated entities that were spawned after the snapshot are despawned. They will be spawned again
+//!   only if they were spawned by a system running in `FixedMain`
+//! - entities that were despawned after the snapshot are not restored
+//! - only native inputs are kept in the input history
+use std::collections::VecDeque;
+
+use bevy::app::FixedMain;
+use bevy::ecs::entity::EntityHashSet;
+use bevy::prelude::*;
+use bytes::Bytes;
+use tracing::{debug, error};
+
+use crate::prelude::{ComponentRegistry, Replicating, Tick, TickManager};
+use crate::serialize::reader::Reader;
+use crate::serialize::writer::Writer;
+use crate::server::error::ServerError;
+use crate::server::run_conditions::is_started;
+use crate::shared::sets::{InternalReplicationSet, ServerMarker};
+
+/// Configuration of the snapshots taken by the [`RewindPlugin`]
+#[derive(Resource, Debug, Clone, Copy, Reflect)]
+pub struct SnapshotConfig {
+    /// Number of ticks between two snapshots
+    pub snapshot_interval: u16,
+    /// Number of snapshots to keep
+    pub snapshot_count: usize,
+}
+
+impl Default for SnapshotConfig {
+    fn default() -> Self {
+        Self {
+            snapshot_interval: 10,
+            snapshot_count: 6,
+        }
+    }
+}
+
+impl SnapshotConfig {
+    pub fn with_snapshot_interval(mut self, snapshot_interval: u16) -> Self {
+        self.snapshot_interval = snapshot_interval;
+        self
+    }
+
+    pub fn with_snapshot_count(mut self, snapshot_count: usize) -> Self {
+        self.snapshot_count = snapshot_count;
+        self
+    }
+
+    /// Number of ticks for which the applied inputs must be kept, so that we can replay
+    /// from the oldest snapshot
+    pub(crate) fn input_history_len(&self) -> u16 {
+        self.snapshot_interval
+            .saturating_mul(self.snapshot_count as u16 + 1)
+    }
+}
+
+/// State of the replicated entities at the end of a tick
+#[derive(Debug)]
+pub struct Snapshot {
+    tick: Tick,
+    /// The serialized replicated components of each entity
+    entities: Vec<(Entity, Bytes)>,
+}
+
+impl Snapshot {
+    /// The tick at the end of which the snapshot was taken
+    pub fn tick(&self) -> Tick {
+        self.tick
+    }
+
+    /// Number of entities in the snapshot
+    pub fn len(&self) -> usize {
+        self.entities.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entities.is_empty()
+    }
+}
+
+/// Ring buffer of the most recent [`Snapshot`]s, ordered from oldest to newest
+#[derive(Resource, Debug, Default)]
+pub struct SnapshotBuffer {
+    snapshots: VecDeque<Snapshot>,
+    /// Tick requested via [`RewindCommands::rewind_to`], applied at the end of the frame
+    pending_rewind: Option<Tick>,
+}
+
+impl SnapshotBuffer {
+    pub fn iter(&self) -> impl Iterator<Item = &Snapshot> {
+        self.snapshots.iter()
+    }
+
+    /// The most recent snapshot that can be used to re-simulate the given tick
+    fn latest_before(&self, tick: Tick) -> Option<&Snapshot> {
+        self.snapshots.iter().rev().find(|s| s.tick < tick)
+    }
+}
+
+/// Plugin that takes periodic snapshots of the replicated entities, so that the server can
+/// rewind and replay the simulation.
+///
+/// It is not part of the [`ServerPlugins`](crate::server::plugin::ServerPlugins) and must be added
+/// separately.
+#[derive(Debug, Default)]
+pub struct RewindPlugin {
+    pub config: SnapshotConfig,
+}
+
+impl RewindPlugin {
+    pub fn new(config: SnapshotConfig) -> Self {
+        Self { config }
+    }
+}
+
+impl Plugin for RewindPlugin {
+    fn build(&self, app: &mut App) {
+        // RESOURCES
+        app.insert_resource(self.config);
+        app.init_resource::<SnapshotBuffer>();
+        // SYSTEMS
+        app.add_systems(FixedLast, take_snapshot.run_if(is_started));
+        app.add_systems(
+            PostUpdate,
+            apply_pending_rewind
+                .run_if(is_started)
+                .before(InternalReplicationSet::<ServerMarker>::All),
+        );
+    }
+}
+
+/// Take a snapshot of the replicated entities at the end of the tick, every `snapshot_interval` ticks
+fn take_snapshot(world: &mut World) {
+    let tick = world.resource::<TickManager>().tick();
+    let config = *world.resource::<SnapshotConfig>();
+    let buffer = world.resource::<SnapshotBuffer>();
+    if buffer
+        .snapshots
+        .back()
+        .is_some_and(|last| tick - last.tick < config.snapshot_interval as i16)
+    {
+        return;
+    }
+    let mut query = world.query_filtered::<EntityRef, With<Replicating>>();
+    let component_registry = world.resource::<ComponentRegistry>();
+    let mut entities = vec![];
+    for entity_ref in query.iter(world) {
+        let mut writer = Writer::default();
+        if let Err(e) = component_registry.snapshot_entity(entity_ref, &mut writer) {
+            error!(?e, entity = ?entity_ref.id(), "could not snapshot entity");
+            continue;
+        }
+        entities.push((entity_ref.id(), writer.to_bytes()));
+    }
+    debug!(?tick, num_entities = entities.len(), "taking snapshot");
+    let mut buffer = world.resource_mut::<SnapshotBuffer>();
+    buffer.snapshots.push_back(Snapshot { tick, entities });
+    while buffer.snapshots.len() > config.snapshot_count {
+        buffer.snapshots.pop_front();
+    }
+}
+
+fn apply_pending_rewind(world: &mut World) {
+    let Some(tick) = world.resource_mut::<SnapshotBuffer>().pending_rewind.take() else {
+        return;
+    };
+    if let Err(e) = rewind_to(world, tick) {
+        error!(?e, "could not rewind the simulation");
+    }
+}
+
+/// Restore the state of the replicated entities before `tick`, then re-simulate every tick
+/// from `tick` up to the current tick (included), using the inputs from the input history.
+///
+/// This runs the `FixedMain` schedule, so it must not be called from a system that runs in `FixedMain`;
+/// use [`RewindCommands::rewind_to`] instead, which applies the rewind at the end of the frame.
+///
+/// Returns an error if there is no snapshot older than `tick`.
+pub fn rewind_to(world: &mut World, tick: Tick) -> Result<(), ServerError> {
+    let current_tick = world.resource::<TickManager>().tick();
+    if tick > current_tick {
+        debug!(?tick, ?current_tick, "cannot rewind to a future tick");
+        return Ok(());
+    }
+    let mut buffer = world.resource_mut::<SnapshotBuffer>();
+    let Some(snapshot) = buffer.latest_before(tick) else {
+        return Err(ServerError::NoSnapshot(tick));
+    };
+    let snapshot_tick = snapshot.tick;
+    let entities = snapshot.entities.clone();
+    // the newer snapshots will be taken again during the replay
+    buffer.snapshots.retain(|s| s.tick <= snapshot_tick);
+
+    // restore the snapshot
+    world.resource_scope(|world, component_registry: Mut<ComponentRegistry>| {
+        let snapshot_entities: EntityHashSet = entities.iter().map(|(entity, _)| *entity).collect();
+        let spawned: Vec<Entity> = world
+            .query_filtered::<Entity, With<Replicating>>()
+            .iter(world)
+            .filter(|entity| !snapshot_entities.contains(entity))
+            .collect();
+        for entity in spawned {
+            world.despawn(entity);
+        }
+        for (entity, bytes) in entities {
+            let Some(mut entity_world_mut) = world.get_entity_mut(entity) else {
+                continue;
+            };
+            let mut reader = Reader::from(bytes);
+            component_registry.restore_entity(&mut reader, &mut entity_world_mut)?;
+        }
+        Ok::<(), ServerError>(())
+    })?;
+
+    // replay the simulation up to the current tick
+    let num_ticks = current_tick - snapshot_tick;
+    debug!(
+        ?snapshot_tick,
+        ?current_tick,
+        "Rewinding the simulation by {num_ticks} ticks"
+    );
+    world
+        .resource_mut::<TickManager>()
+        .set_tick_to(snapshot_tick);
+    let time = *world.resource::<Time>();
+    *world.resource_mut::<Time>() = world.resource::<Time<Fixed>>().as_generic();
+    for _ in 0..num_ticks {
+        world.run_schedule(FixedMain);
+    }
+    *world.resource_mut::<Time>() = time;
+    Ok(())
+}
+
+pub trait RewindCommands {
+    /// Rewind the simulation to `tick` and replay it up to the current tick.
+    /// The rewind is applied at the end of the frame, before replication.
+    fn rewind_to(&mut self, tick: Tick);
+}
+
+impl RewindCommands for Commands<'_, '_> {
+    fn rewind_to(&mut self, tick: Tick) {
+        self.add(move |world: &mut World| {
+            let mut buffer = world.resource_mut::<SnapshotBuffer>();
+            // if multiple rewinds are requested, we only need to replay from the oldest tick
+            buffer.pending_rewind = Some(buffer.pending_rewind.map_or(tick, |t| t.min(tick)));
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::inputs::LocalPlayerId;
+    use crate::prelude::client::{self, InputManager, InputSystemSet};
+    use crate::prelude::server::{InputEvent, Replicate};
+    use crate::prelude::{ClientId, SharedConfig, TickConfig};
+    use crate::server::input::native::InputBuffers;
+    use crate::tests::protocol::*;
+    use crate::tests::stepper::{BevyStepper, Step, TEST_CLIENT_ID};
+    use bevy::ecs::system::RunSystemOnce;
+    use bevy::utils::Duration;
+
+    fn press_input(
+        mut input_manager: ResMut<InputManager<MyInput>>,
+        tick_manager: Res<TickManager>,
+    ) {
+        let tick = tick_manager.tick();
+        input_manager.add_input(MyInput(1 + (tick.0 % 3) as i16), tick);
+    }
+
+    /// Deterministic simulation: the inputs are accumulated in Component1, and Component2
+    /// depends on the previous value of both components
+    fn simulate(
+        mut query: Query<(&mut Component1, &mut Component2)>,
+        mut events: EventReader<InputEvent<MyInput>>,
+    ) {
+        let total: f32 = events
+            .read()
+            .filter_map(|event| event.input().as_ref().map(|input| input.0 as f32))
+            .sum();
+        for (mut c1, mut c2) in query.iter_mut() {
+            c1.0 += total;
+            c2.0 = c2.0 * 0.5 + c1.0;
+        }
+    }
+
+    fn setup() -> (BevyStepper, Entity) {
+        let tick_duration = Duration::from_millis(10);
+        let shared_config = SharedConfig {
+            tick: TickConfig::new(tick_duration),
+            ..default()
+        };
+        let mut stepper = BevyStepper::new(
+            shared_config,
+            client::ClientConfig::default(),
+            tick_duration,
+        );
+        stepper.server_app.add_plugins(RewindPlugin::new(
+            SnapshotConfig::default()
+                .with_snapshot_interval(5)
+                .with_snapshot_count(4),
+        ));
+        stepper.init();
+        stepper.client_app.add_systems(
+            FixedPreUpdate,
+            press_input.in_set(InputSystemSet::BufferInputs),
+        );
+        stepper.server_app.add_systems(FixedUpdate, simulate);
+        let server_entity = stepper
+            .server_app
+            .world_mut()
+            .spawn((Component1(0.0), Component2(0.0), Replicate::default()))
+            .id();
+        for _ in 0..50 {
+            stepper.frame_step();
+        }
+        (stepper, server_entity)
+    }
+
+    fn state(stepper: &BevyStepper, entity: Entity) -> (Component1, Component2) {
+        let world = stepper.server_app.world();
+        (
+            world.get::<Component1>(entity).unwrap().clone(),
+            world.get::<Component2>(entity).unwrap().clone(),
+        )
+    }
+
+    /// Rewinding and replaying with the same inputs ends in the same state as without the rewind
+    #[test]
+    fn test_rewind_replay_is_deterministic() {
+        let (mut stepper, server_entity) = setup();
+        let tick = stepper.server_tick();
+        let expected = state(&stepper, server_entity);
+        assert!(expected.0 .0 > 0.0);
+        assert_eq!(
+            stepper
+                .server_app
+                .world()
+                .resource::<SnapshotBuffer>()
+                .iter()
+                .count(),
+            4
+        );
+
+        // spawn an entity that did not exist in the snapshot
+        let spawned = stepper
+            .server_app
+            .world_mut()
+            .spawn((Component1(0.0), Replicate::default()))
+            .id();
+
+        rewind_to(stepper.server_app.world_mut(), tick - 12).unwrap();
+        assert_eq!(stepper.server_tick(), tick);
+        assert_eq!(state(&stepper, server_entity), expected);
+        assert!(stepper.server_app.world().get_entity(spawned).is_none());
+
+        // the simulation keeps running normally after the rewind
+        stepper.frame_step();
+        assert_eq!(stepper.server_tick(), tick + 1);
+        assert!(state(&stepper, server_entity).0 .0 > expected.0 .0);
+    }
+
+    /// Correcting a past input and rewinding applies the corrected input
+    #[test]
+    fn test_rewind_with_corrected_input() {
+        let (mut stepper, server_entity) = setup();
+        let tick = stepper.server_tick();
+        let (c1, _) = state(&stepper, server_entity);
+
+        let previous = stepper
+            .server_app
+            .world_mut()
+            .resource_mut::<InputBuffers<MyInput>>()
+            .correct_input(
+                ClientId::Netcode(TEST_CLIENT_ID),
+                LocalPlayerId::default(),
+                tick - 3,
+                Some(MyInput(10)),
+            )
+            .expect("the tick should be in the input history");
+        let previous = previous
+            .first()
+            .cloned()
+            .expect("an input should have been applied");
+
+        // request the rewind with a command
+        stepper
+            .server_app
+            .world_mut()
+            .run_system_once(move |mut commands: Commands| commands.rewind_to(tick - 3));
+        stepper
+            .server_app
+            .world_mut()
+            .run_system_once(apply_pending_rewind);
+        assert_eq!(stepper.server_tick(), tick);
+        assert_eq!(
+            state(&stepper, server_entity).0,
+            Component1(c1.0 + 10.0 - previous.0 as f32)
+        );
+    }
+
+    #[test]
+    fn test_rewind_without_snapshot() {
+        let (mut stepper, _) = setup();
+        let tick = stepper.server_tick();
+        assert!(matches!(
+            rewind_to(stepper.server_app.world_mut(), tick - 40),
+            Err(ServerError::NoSnapshot(_))
+        ));
+    }
+}
@@ -0,0 +1,403 @@
+//! Validation of the component values that clients replicate to the server.
+//!
+//! When clients have authority over some components (cursor positions, vehicle telemetry, etc.),
+//! the server should not blindly trust the values it receives. You can register a validator for a component
+//! that will be called on every value received from a client, before it is written to the server's World
+//! (and therefore before it gets replicated to the other clients).
+//!
+//! ```rust,ignore
+//! app.add_client_update_validator::<Position>(|client_id, old, new| {
+//!     if !new.0.is_finite() {
+//!         return ValidationVerdict::Reject;
+//!     }
+//!     if new.0.length() > MAX_DISTANCE {
+//!         new.0 = new.0.clamp_length_max(MAX_DISTANCE);
+//!         return ValidationVerdict::Clamp;
+//!     }
+//!     ValidationVerdict::Accept
+//! });
+//! ```
+//!
+//! If the entity didn't have the component yet, a rejected value is not inserted: this is how components
+//! can be stripped from the entities spawned by clients.
+//!
+//! The entities that clients replicate to the server can also be rejected as a whole with
+//! [`set_client_spawn_validator`](AppClientSpawnValidatorExt::set_client_spawn_validator). The validator is called
+//! with the components of the entity before it is spawned in the server's World:
+//!
+//! ```rust,ignore
+//! app.set_client_spawn_validator(|client_id, components| {
+//!     components.contains(&ComponentKind::of::<Marker>())
+//! });
+//! ```
+//!
+//! A rejected entity is never spawned on the server: its later updates and its despawn are ignored.
+//! The entities that are accepted are tagged with [`Replicated`](crate::prelude::Replicated), which contains the
+//! [`ClientId`] of the client that spawned them. The server can relay them to the other clients by adding
+//! a server [`Replicate`](crate::prelude::server::Replicate) component.
+//!
+//! Every value that is clamped or rejected (and every entity spawn that is rejected) is counted in the
+//! [`ClientUpdateViolations`] resource, and reported by the
+//! [`ServerDiagnosticsPlugin`](crate::server::diagnostics::ServerDiagnosticsPlugin).
+use bevy::prelude::{App, Reflect, Resource, World};
+use bevy::utils::HashMap;
+
+use crate::prelude::server::ServerConfig;
+use crate::prelude::ClientId;
+use crate::protocol::component::ComponentKind;
+
+/// Outcome of the validation of a component value received from a client
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Reflect)]
+pub enum ValidationVerdict {
+    /// The value is valid and is written as is
+    Accept,
+    /// The value was modified in place by the validator, and the modified value is written
+    Clamp,
+    /// The value is discarded: the entity keeps its previous value (or doesn't get the component
+    /// if it didn't have it yet)
+    Reject,
+}
+
+/// Function used to validate a component value received from a client.
+///
+/// It receives the client that sent the update, the current value of the component on the server (if any)
+/// and the new value, which can be modified in place.
+pub type ClientUpdateValidatorFn<C> =
+    fn(client_id: ClientId, old: Option<&C>, new: &mut C) -> ValidationVerdict;
+
+/// Function used to accept or reject an entity that a client replicates to the server.
+///
+/// It receives the client that spawned the entity and the components that the entity is spawned with,
+/// and returns `false` if the entity should not be spawned on the server.
+pub type ClientSpawnValidatorFn = fn(client_id: ClientId, components: &[ComponentKind]) -> bool;
+
+#[derive(Resource)]
+struct ClientSpawnValidator(ClientSpawnValidatorFn);
+
+/// Extension trait to validate the entities that clients replicate to the server
+pub trait AppClientSpawnValidatorExt {
+    /// Set the function that accepts or rejects the entities spawned by clients, before they are spawned
+    /// in the server's World.
+    ///
+    /// See [`validation`](crate::server::validation) for more details.
+    fn set_client_spawn_validator(&mut self, validator: ClientSpawnValidatorFn);
+}
+
+impl AppClientSpawnValidatorExt for App {
+    fn set_client_spawn_validator(&mut self, validator: ClientSpawnValidatorFn) {
+        // the validator only runs on the server
+        if self.world().get_resource::<ServerConfig>().is_none() {
+            return;
+        }
+        self.insert_resource(ClientSpawnValidator(validator));
+    }
+}
+
+/// Run the [`ClientSpawnValidatorFn`] on an entity spawned by a client.
+///
+/// The components of the entity are only computed if a validator is set.
+/// Returns false if the spawn should be rejected.
+pub(crate) fn validate_client_spawn(
+    world: &mut World,
+    client_id: ClientId,
+    components: impl FnOnce() -> Vec<ComponentKind>,
+) -> bool {
+    let Some(validator) = world
+        .get_resource::<ClientSpawnValidator>()
+        .map(|validator| validator.0)
+    else {
+        return true;
+    };
+    if validator(client_id, &components()) {
+        return true;
+    }
+    if let Some(mut violations) = world.get_resource_mut::<ClientUpdateViolations>() {
+        violations.record_rejected_spawn(client_id);
+    }
+    false
+}
+
+/// Exposes the floating-point fields of a component, so that it can be used with
+/// the [`reject_non_finite`] validator.
+///
+/// ```rust,ignore
+/// impl FloatFields for Position {
+///     fn float_fields(&self) -> impl IntoIterator<Item = f32> {
+///         [self.0.x, self.0.y]
+///     }
+/// }
+///
+/// app.add_client_update_validator::<Position>(reject_non_finite);
+/// ```
+pub trait FloatFields {
+    fn float_fields(&self) -> impl IntoIterator<Item = f32>;
+}
+
+/// Built-in validator that rejects any value containing a NaN or infinite float
+pub fn reject_non_finite<C: FloatFields>(
+    _: ClientId,
+    _: Option<&C>,
+    new: &mut C,
+) -> ValidationVerdict {
+    if new.float_fields().into_iter().all(f32::is_finite) {
+        ValidationVerdict::Accept
+    } else {
+        ValidationVerdict::Reject
+    }
+}
+
+/// Number of component updates from a client that did not pass validation
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Reflect)]
+pub struct ViolationCount {
+    pub clamped: u32,
+    pub rejected: u32,
+    /// Number of entity spawns rejected by the [`ClientSpawnValidatorFn`]
+    pub rejected_spawns: u32,
+}
+
+impl ViolationCount {
+    fn record(&mut self, verdict: ValidationVerdict) {
+        match verdict {
+            ValidationVerdict::Accept => {}
+            ValidationVerdict::Clamp => self.clamped += 1,
+            ValidationVerdict::Reject => self.rejected += 1,
+        }
+    }
+}
+
+/// Keeps track, for each connected client, of the component updates that were clamped or rejected
+/// by a validator.
+///
+/// The counters of a client are removed when it disconnects.
+/// This resource is added by the [`ServerDiagnosticsPlugin`](crate::server::diagnostics::ServerDiagnosticsPlugin);
+/// violations are not counted if that plugin is disabled.
+#[derive(Resource, Debug, Default)]
+pub struct ClientUpdateViolations {
+    per_client: HashMap<ClientId, ViolationCount>,
+    /// Violations since the last time the diagnostics were computed
+    pub(crate) unflushed: ViolationCount,
+}
+
+impl ClientUpdateViolations {
+    /// Get the violations for a given client
+    pub fn get(&self, client_id: ClientId) -> ViolationCount {
+        self.per_client.get(&client_id).copied().unwrap_or_default()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&ClientId, &ViolationCount)> {
+        self.per_client.iter()
+    }
+
+    pub(crate) fn record(&mut self, client_id: ClientId, verdict: ValidationVerdict) {
+        self.per_client
+            .entry(client_id)
+            .or_default()
+            .record(verdict);
+        self.unflushed.record(verdict);
+    }
+
+    pub(crate) fn record_rejected_spawn(&mut self, client_id: ClientId) {
+        self.per_client
+            .entry(client_id)
+            .or_default()
+            .rejected_spawns += 1;
+        self.unflushed.rejected_spawns += 1;
+    }
+
+    pub(crate) fn remove(&mut self, client_id: ClientId) {
+        self.per_client.remove(&client_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::client::Replicate;
+    use crate::prelude::{server, AppComponentExt, Replicated};
+    use crate::tests::protocol::Component1;
+    use crate::tests::stepper::{BevyStepper, Step, TEST_CLIENT_ID};
+
+    impl FloatFields for Component1 {
+        fn float_fields(&self) -> impl IntoIterator<Item = f32> {
+            [self.0]
+        }
+    }
+
+    #[test]
+    fn test_client_update_validator() {
+        let mut stepper = BevyStepper::default();
+        stepper
+            .server_app
+            .add_client_update_validator::<Component1>(|client_id, old, new| {
+                if reject_non_finite(client_id, old, new) == ValidationVerdict::Reject {
+                    return ValidationVerdict::Reject;
+                }
+                if new.0 > 10.0 {
+                    new.0 = 10.0;
+                    return ValidationVerdict::Clamp;
+                }
+                ValidationVerdict::Accept
+            });
+        let client_id = ClientId::Netcode(TEST_CLIENT_ID);
+
+        let client_entity = stepper
+            .client_app
+            .world_mut()
+            .spawn((Replicate::default(), Component1(1.0)))
+            .id();
+        for _ in 0..10 {
+            stepper.frame_step();
+        }
+        let server_entity = *stepper
+            .server_app
+            .world()
+            .resource::<server::ConnectionManager>()
+            .connection(client_id)
+            .unwrap()
+            .replication_receiver
+            .remote_entity_map
+            .get_local(client_entity)
+            .expect("entity was not replicated to server");
+        assert_eq!(
+            stepper.server_app.world().get::<Component1>(server_entity),
+            Some(&Component1(1.0))
+        );
+
+        // the value is clamped
+        stepper
+            .client_app
+            .world_mut()
+            .entity_mut(client_entity)
+            .insert(Component1(20.0));
+        for _ in 0..10 {
+            stepper.frame_step();
+        }
+        assert_eq!(
+            stepper.server_app.world().get::<Component1>(server_entity),
+            Some(&Component1(10.0))
+        );
+        assert_eq!(
+            stepper
+                .server_app
+                .world()
+                .resource::<ClientUpdateViolations>()
+                .get(client_id),
+            ViolationCount {
+                clamped: 1,
+                rejected: 0,
+                rejected_spawns: 0,
+            }
+        );
+
+        // the value is rejected: the server keeps the previous value
+        stepper
+            .client_app
+            .world_mut()
+            .entity_mut(client_entity)
+            .insert(Component1(f32::NAN));
+        for _ in 0..10 {
+            stepper.frame_step();
+        }
+        assert_eq!(
+            stepper.server_app.world().get::<Component1>(server_entity),
+            Some(&Component1(10.0))
+        );
+        assert_eq!(
+            stepper
+                .server_app
+                .world()
+                .resource::<ClientUpdateViolations>()
+                .get(client_id),
+            ViolationCount {
+                clamped: 1,
+                rejected: 1,
+                rejected_spawns: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn test_client_spawn_validator() {
+        let mut stepper = BevyStepper::default();
+        // reject the entities that are spawned without Component1
+        stepper
+            .server_app
+            .set_client_spawn_validator(|_, components| {
+                components.contains(&ComponentKind::of::<Component1>())
+            });
+        let client_id = ClientId::Netcode(TEST_CLIENT_ID);
+
+        let accepted = stepper
+            .client_app
+            .world_mut()
+            .spawn((Replicate::default(), Component1(1.0)))
+            .id();
+        let rejected = stepper
+            .client_app
+            .world_mut()
+            .spawn(Replicate::default())
+            .id();
+        for _ in 0..10 {
+            stepper.frame_step();
+        }
+        let connection = stepper
+            .server_app
+            .world()
+            .resource::<server::ConnectionManager>()
+            .connection(client_id)
+            .unwrap();
+        let server_entity = *connection
+            .replication_receiver
+            .remote_entity_map
+            .get_local(accepted)
+            .expect("entity was not replicated to server");
+        assert!(connection
+            .replication_receiver
+            .remote_entity_map
+            .get_local(rejected)
+            .is_none());
+        assert_eq!(
+            stepper.server_app.world().get::<Replicated>(server_entity),
+            Some(&Replicated {
+                from: Some(client_id)
+            })
+        );
+        assert_eq!(
+            stepper
+                .server_app
+                .world()
+                .resource::<ClientUpdateViolations>()
+                .get(client_id)
+                .rejected_spawns,
+            1
+        );
+
+        // the later actions of the rejected entity are ignored
+        stepper
+            .client_app
+            .world_mut()
+            .entity_mut(rejected)
+            .insert(Component1(2.0));
+        for _ in 0..10 {
+            stepper.frame_step();
+        }
+        assert_eq!(
+            stepper
+                .server_app
+                .world_mut()
+                .query::<&Component1>()
+                .iter(stepper.server_app.world())
+                .collect::<Vec<_>>(),
+            vec![&Component1(1.0)]
+        );
+        stepper.client_app.world_mut().despawn(rejected);
+        for _ in 0..10 {
+            stepper.frame_step();
+        }
+        assert!(stepper
+            .server_app
+            .world()
+            .get_entity(server_entity)
+            .is_some());
+    }
+}
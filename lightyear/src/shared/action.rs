@@ -0,0 +1,174 @@
+//! Authoritative resolution of the actions that the client cannot fully predict.
+//!
+//! Some actions (opening a shop, picking up a contested item, etc.) can only be validated by the server,
+//! but the client still wants to give immediate cosmetic feedback. The pattern is:
+//! - the client calls [`ActionTracker::begin_action`] to get a new [`ActionId`], shows the cosmetic feedback,
+//!   and includes the [`ActionId`] in the input or message that triggers the action
+//! - when the server processes the action, it calls
+//!   [`ConnectionManager::acknowledge_action`](crate::server::connection::ConnectionManager::acknowledge_action)
+//!   with the [`ActionVerdict`]. The verdict is sent back to the client on a reliable channel, along with
+//!   the server tick at which the action was processed
+//! - the client receives an [`ActionResolvedEvent`](crate::client::events::ActionResolvedEvent), and
+//!   confirms or rolls back the cosmetic feedback.
+//!
+//! If the server does not resolve the action before the [`ActionConfig::timeout`], the action is
+//! resolved with [`ActionVerdict::Unresolved`], so that the client never waits forever.
+use bevy::prelude::*;
+use bevy::utils::{Duration, HashMap, Instant};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, error};
+
+use crate::channel::builder::ActionResolutionChannel;
+use crate::client::config::ClientConfig;
+use crate::client::events::{ActionResolvedEvent, MessageEvent};
+use crate::prelude::{ClientId, NetworkTarget, Tick, TickManager};
+use crate::server::connection::ConnectionManager as ServerConnectionManager;
+use crate::shared::clock::NetworkClock;
+
+/// Identifier of an action started by the client, allocated by [`ActionTracker::begin_action`]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, Reflect)]
+pub struct ActionId(pub u32);
+
+/// Authoritative outcome of an action
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Reflect)]
+pub enum ActionVerdict {
+    /// The server accepted the action
+    Accepted,
+    /// The server rejected the action
+    Denied,
+    /// The server did not resolve the action before the timeout
+    Unresolved,
+}
+
+/// Configuration of the action resolution on the client
+#[derive(Clone, Copy, Debug, Reflect)]
+pub struct ActionConfig {
+    /// Duration after which an action that was not resolved by the server is resolved
+    /// with [`ActionVerdict::Unresolved`]
+    pub timeout: Duration,
+}
+
+impl Default for ActionConfig {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(2),
+        }
+    }
+}
+
+/// Message sent by the server to resolve an action of the client
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub(crate) struct ActionResolutionMessage {
+    pub(crate) action_id: ActionId,
+    pub(crate) verdict: ActionVerdict,
+    /// Server tick at which the action was processed
+    pub(crate) tick: Tick,
+}
+
+/// Verdicts given by the server that have not been sent yet
+#[derive(Debug, Default)]
+pub(crate) struct PendingActionResolutions(Vec<(ClientId, ActionId, ActionVerdict)>);
+
+impl PendingActionResolutions {
+    pub(crate) fn push(
+        &mut self,
+        client_id: ClientId,
+        action_id: ActionId,
+        verdict: ActionVerdict,
+    ) {
+        self.0.push((client_id, action_id, verdict));
+    }
+}
+
+/// Client [`Resource`] that allocates the [`ActionId`]s and keeps track of the actions
+/// that are waiting for their resolution
+#[derive(Resource, Debug, Default)]
+pub struct ActionTracker {
+    next_id: u32,
+    /// Deadline of each pending action. The deadline is set on the first frame after the action started.
+    pending: HashMap<ActionId, Option<Instant>>,
+}
+
+impl ActionTracker {
+    /// Start a new action. The returned [`ActionId`] must be sent to the server with the action.
+    pub fn begin_action(&mut self) -> ActionId {
+        let action_id = ActionId(self.next_id);
+        self.next_id = self.next_id.wrapping_add(1);
+        self.pending.insert(action_id, None);
+        action_id
+    }
+
+    /// Returns true if the action is still waiting for its resolution
+    pub fn is_pending(&self, action_id: ActionId) -> bool {
+        self.pending.contains_key(&action_id)
+    }
+
+    /// Number of actions waiting for their resolution
+    pub fn num_pending(&self) -> usize {
+        self.pending.len()
+    }
+}
+
+/// Server system that sends the verdicts given since the last run, along with the current tick.
+///
+/// It runs after every fixed update step, so that the tick of the verdicts given during input processing
+/// is the tick at which the input was processed.
+pub(crate) fn send_action_resolutions(
+    tick_manager: Res<TickManager>,
+    mut connection_manager: ResMut<ServerConnectionManager>,
+) {
+    let tick = tick_manager.tick();
+    for (client_id, action_id, verdict) in
+        std::mem::take(&mut connection_manager.action_resolutions.0)
+    {
+        let message = ActionResolutionMessage {
+            action_id,
+            verdict,
+            tick,
+        };
+        let _ = connection_manager
+            .send_message_to_target::<ActionResolutionChannel, _>(
+                &message,
+                NetworkTarget::Single(client_id),
+            )
+            .inspect_err(|e| error!("Could not send action resolution: {e:?}"));
+    }
+}
+
+/// Client system that resolves the pending actions with the verdicts received from the server,
+/// and with [`ActionVerdict::Unresolved`] once they time out
+pub(crate) fn resolve_actions(
+    config: Res<ClientConfig>,
+    clock: Res<NetworkClock>,
+    mut tracker: ResMut<ActionTracker>,
+    mut messages: EventReader<MessageEvent<ActionResolutionMessage>>,
+    mut events: EventWriter<ActionResolvedEvent>,
+) {
+    for message in messages.read().map(|event| event.message) {
+        // the action could already have timed out
+        if tracker.pending.remove(&message.action_id).is_none() {
+            debug!(action_id = ?message.action_id, "Received the resolution of an unknown action");
+            continue;
+        }
+        events.send(ActionResolvedEvent {
+            action_id: message.action_id,
+            verdict: message.verdict,
+            server_tick: Some(message.tick),
+        });
+    }
+    let now = clock.now();
+    let timeout = config.action.timeout;
+    tracker.pending.retain(|action_id, deadline| {
+        let deadline = *deadline.get_or_insert(now + timeout);
+        if now < deadline {
+            return true;
+        }
+        debug!(?action_id, "The action was not resolved by the server");
+        events.send(ActionResolvedEvent {
+            action_id: *action_id,
+            verdict: ActionVerdict::Unresolved,
+            server_tick: None,
+        });
+        false
+    });
+}
@@ -0,0 +1,208 @@
+/*! Clock used by lightyear to read the time
+
+All the reads of the current time inside lightyear's systems, the netcode timestamps and the link conditioner
+go through the [`NetworkClock`] resource, which uses the system clock by default.
+
+With the `mock_time` feature, a [`MockClock`] can be shared by several `App`s (for example a client and a server
+in the same test), so that their time only advances when [`MockClock::advance`] is called:
+```rust,ignore
+let clock = MockClock::new();
+clock.install(&mut client_app);
+clock.install(&mut server_app);
+// add the lightyear plugins after installing the clock
+...
+clock.advance(Duration::from_millis(10));
+client_app.update();
+server_app.update();
+```
+*/
+use std::fmt::{Debug, Formatter};
+use std::sync::Arc;
+
+use bevy::prelude::Resource;
+use bevy::utils::{Duration, Instant};
+
+/// A source of time
+pub trait Clock: Send + Sync + 'static {
+    /// The current instant
+    fn now(&self) -> Instant;
+
+    /// The current time since the UNIX epoch
+    fn unix_time(&self) -> Duration;
+}
+
+/// [`Clock`] that reads the system clock
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RealClock;
+
+impl Clock for RealClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn unix_time(&self) -> Duration {
+        bevy::utils::SystemTime::now()
+            .duration_since(bevy::utils::SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+    }
+}
+
+/// Resource containing the [`Clock`] used by lightyear.
+///
+/// It must be inserted before the lightyear plugins are added to the `App`; by default the [`RealClock`] is used.
+#[derive(Resource, Clone)]
+pub struct NetworkClock(Arc<dyn Clock>);
+
+impl NetworkClock {
+    pub fn new(clock: impl Clock) -> Self {
+        Self(Arc::new(clock))
+    }
+
+    /// The current instant
+    pub fn now(&self) -> Instant {
+        self.0.now()
+    }
+
+    /// The current time since the UNIX epoch
+    pub fn unix_time(&self) -> Duration {
+        self.0.unix_time()
+    }
+}
+
+impl Default for NetworkClock {
+    fn default() -> Self {
+        Self::new(RealClock)
+    }
+}
+
+impl Debug for NetworkClock {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("NetworkClock").finish()
+    }
+}
+
+#[cfg(any(test, feature = "mock_time"))]
+pub use mock::MockClock;
+
+#[cfg(any(test, feature = "mock_time"))]
+mod mock {
+    use bevy::app::{App, First};
+    use bevy::prelude::{IntoSystemConfigs, Res, ResMut};
+    use bevy::time::{TimeSystem, TimeUpdateStrategy};
+    use parking_lot::Mutex;
+
+    use super::*;
+
+    /// [`Clock`] that only advances when [`MockClock::advance`] is called.
+    ///
+    /// The clones of a `MockClock` share the same time.
+    #[derive(Debug, Clone)]
+    pub struct MockClock {
+        start: Instant,
+        start_unix_time: Duration,
+        elapsed: Arc<Mutex<Duration>>,
+    }
+
+    impl Default for MockClock {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+
+    impl MockClock {
+        /// Create a clock that starts at the current system time
+        pub fn new() -> Self {
+            Self {
+                start: Instant::now(),
+                start_unix_time: RealClock.unix_time(),
+                elapsed: Arc::default(),
+            }
+        }
+
+        /// Advance the time of all the clones of this clock
+        pub fn advance(&self, duration: Duration) {
+            *self.elapsed.lock() += duration;
+        }
+
+        /// Time elapsed since the clock was created
+        pub fn elapsed(&self) -> Duration {
+            *self.elapsed.lock()
+        }
+
+        /// Number of fixed timesteps of duration `tick_duration` contained in the elapsed time,
+        /// i.e. the number of ticks that an `App` updated with this clock has run
+        pub fn expected_ticks(&self, tick_duration: Duration) -> u32 {
+            (self.elapsed().as_nanos() / tick_duration.as_nanos()) as u32
+        }
+
+        /// Use this clock in the `App`: for lightyear's time reads, and for bevy's [`Time`](bevy::time::Time)
+        /// which is updated with the instant of the clock at the start of each frame.
+        ///
+        /// Must be called before the lightyear plugins are added.
+        pub fn install(&self, app: &mut App) {
+            app.insert_resource(NetworkClock::new(self.clone()))
+                .insert_resource(TimeUpdateStrategy::ManualInstant(self.now()))
+                .add_systems(First, update_time_strategy.before(TimeSystem));
+        }
+    }
+
+    impl Clock for MockClock {
+        fn now(&self) -> Instant {
+            self.start + self.elapsed()
+        }
+
+        fn unix_time(&self) -> Duration {
+            self.start_unix_time + self.elapsed()
+        }
+    }
+
+    /// Update bevy's time with the instant of the clock
+    fn update_time_strategy(clock: Res<NetworkClock>, mut strategy: ResMut<TimeUpdateStrategy>) {
+        *strategy = TimeUpdateStrategy::ManualInstant(clock.now());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::*;
+
+    use super::*;
+
+    #[test]
+    fn test_mock_clock_is_shared() {
+        let clock = MockClock::new();
+        let network_clock = NetworkClock::new(clock.clone());
+        let start = network_clock.now();
+        let start_unix_time = network_clock.unix_time();
+
+        clock.clone().advance(Duration::from_secs(10));
+        assert_eq!(network_clock.now() - start, Duration::from_secs(10));
+        assert_eq!(
+            network_clock.unix_time() - start_unix_time,
+            Duration::from_secs(10)
+        );
+        assert_eq!(clock.expected_ticks(Duration::from_millis(16)), 625);
+    }
+
+    #[test]
+    fn test_mock_clock_drives_apps() {
+        let clock = MockClock::new();
+        let mut apps = [App::new(), App::new()];
+        for app in apps.iter_mut() {
+            app.add_plugins(MinimalPlugins);
+            clock.install(app);
+            // initialize the time with the start of the clock
+            app.update();
+        }
+        for _ in 0..3 {
+            clock.advance(Duration::from_millis(100));
+            apps.iter_mut().for_each(App::update);
+        }
+        for app in apps.iter() {
+            assert_eq!(
+                app.world().resource::<Time<Real>>().elapsed(),
+                Duration::from_millis(300)
+            );
+        }
+    }
+}
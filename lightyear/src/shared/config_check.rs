@@ -0,0 +1,1094 @@
+/*! Validation of the networking configuration when the app starts
+
+Some combinations of settings are valid on their own but don't work together: an interpolation delay shorter than
+the interval between two replication updates, a bandwidth cap that is used up by the pings alone, a link conditioner
+that delays the packets for longer than the connection timeout... They don't produce errors, but confusing symptoms
+(stuttering entities, messages that are never sent, connections that time out).
+
+When the client and server plugins are built, every registered check runs against the [`ClientConfig`], the
+[`ServerConfig`], their [`SharedConfig`] and the [`ChannelRegistry`]. Each check reports [`ConfigIssue`]s, with the
+offending values and the suggested fix:
+- a [`ConfigSeverity::Error`] is a configuration that cannot work: the app refuses to start and panics with the list of errors
+- a [`ConfigSeverity::Warning`] is a configuration that works, but probably not as intended: it is logged
+
+All the issues are stored in the [`ConfigReport`] resource.
+
+Plugins can register their own checks with [`AppConfigCheckExt::add_config_check`]. The checks must be added
+before the plugins are finished (for example in [`Plugin::build`](bevy::prelude::Plugin::build)):
+
+```rust,ignore
+app.add_config_check(|world| {
+    let Some(config) = world.get_resource::<MyConfig>() else {
+        return vec![];
+    };
+    let mut issues = vec![];
+    if config.radius <= 0.0 {
+        issues.push(ConfigIssue::error(
+            "my_config.radius",
+            format!("the radius is {}", config.radius),
+            "set MyConfig::radius to a positive value",
+        ));
+    }
+    issues
+});
+```
+*/
+use std::fmt::{Display, Formatter};
+
+use bevy::prelude::{App, Reflect, Resource, World};
+use bevy::utils::Duration;
+use governor::Quota;
+use tracing::{error, warn};
+
+use crate::channel::builder::{ChannelMode, ChannelSettings};
+use crate::client::config::ClientConfig;
+use crate::client::prediction::plugin::{InputDelayConfig, PredictionConfig};
+use crate::client::sync::SyncConfig;
+use crate::connection::client::NetConfig as ClientNetConfig;
+use crate::connection::server::NetConfig as ServerNetConfig;
+use crate::protocol::channel::ChannelRegistry;
+use crate::server::config::ServerConfig;
+use crate::shared::config::SharedConfig;
+use crate::shared::ping::manager::PingConfig;
+use crate::shared::replication::plugin::ReplicationConfig;
+use crate::transport::middleware::conditioner::LinkConditionerConfig;
+
+/// How bad a [`ConfigIssue`] is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Reflect)]
+pub enum ConfigSeverity {
+    /// The configuration works, but probably not as intended
+    Warning,
+    /// The configuration cannot work: the app refuses to start
+    Error,
+}
+
+/// A problem found in the networking configuration
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigIssue {
+    pub severity: ConfigSeverity,
+    /// Name of the check that found the issue, usually the path of the offending setting
+    pub check: &'static str,
+    /// What is wrong, with the offending values
+    pub message: String,
+    /// How to fix the configuration
+    pub fix: String,
+}
+
+impl ConfigIssue {
+    pub fn error(check: &'static str, message: impl Into<String>, fix: impl Into<String>) -> Self {
+        Self {
+            severity: ConfigSeverity::Error,
+            check,
+            message: message.into(),
+            fix: fix.into(),
+        }
+    }
+
+    pub fn warning(
+        check: &'static str,
+        message: impl Into<String>,
+        fix: impl Into<String>,
+    ) -> Self {
+        Self {
+            severity: ConfigSeverity::Warning,
+            check,
+            message: message.into(),
+            fix: fix.into(),
+        }
+    }
+}
+
+impl Display for ConfigIssue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[{}] {}. Fix: {}", self.check, self.message, self.fix)
+    }
+}
+
+/// Function that checks a part of the configuration stored in the [`World`]
+pub type ConfigCheckFn = Box<dyn Fn(&World) -> Vec<ConfigIssue> + Send + Sync + 'static>;
+
+/// The checks that run when the plugins are finished
+#[derive(Resource, Default)]
+pub(crate) struct ConfigChecks(Vec<ConfigCheckFn>);
+
+/// The issues found in the networking configuration when the app started
+#[derive(Resource, Debug, Clone, Default, PartialEq)]
+pub struct ConfigReport {
+    pub issues: Vec<ConfigIssue>,
+}
+
+impl ConfigReport {
+    pub fn errors(&self) -> impl Iterator<Item = &ConfigIssue> {
+        self.issues
+            .iter()
+            .filter(|issue| issue.severity == ConfigSeverity::Error)
+    }
+
+    pub fn warnings(&self) -> impl Iterator<Item = &ConfigIssue> {
+        self.issues
+            .iter()
+            .filter(|issue| issue.severity == ConfigSeverity::Warning)
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.errors().next().is_some()
+    }
+}
+
+/// Extension trait to validate the networking configuration when the app starts
+pub trait AppConfigCheckExt {
+    /// Add a check that runs once all the plugins are built.
+    ///
+    /// The check should return no issue if the resources that it checks are absent.
+    fn add_config_check(
+        &mut self,
+        check: impl Fn(&World) -> Vec<ConfigIssue> + Send + Sync + 'static,
+    );
+}
+
+impl AppConfigCheckExt for App {
+    fn add_config_check(
+        &mut self,
+        check: impl Fn(&World) -> Vec<ConfigIssue> + Send + Sync + 'static,
+    ) {
+        self.world_mut()
+            .get_resource_or_insert_with(ConfigChecks::default)
+            .0
+            .push(Box::new(check));
+    }
+}
+
+/// Run all the registered checks
+pub fn validate_config(world: &World) -> ConfigReport {
+    let issues = world
+        .get_resource::<ConfigChecks>()
+        .map(|checks| checks.0.iter().flat_map(|check| check(world)).collect())
+        .unwrap_or_default();
+    ConfigReport { issues }
+}
+
+/// Run all the registered checks, log the issues, and panic if the configuration has errors
+pub(crate) fn run_config_checks(app: &mut App) {
+    let report = validate_config(app.world());
+    for issue in report.warnings() {
+        warn!("Networking configuration warning: {issue}");
+    }
+    for issue in report.errors() {
+        error!("Networking configuration error: {issue}");
+    }
+    if report.has_errors() {
+        let errors: Vec<_> = report.errors().map(|issue| issue.to_string()).collect();
+        panic!(
+            "The networking configuration is invalid:\n- {}",
+            errors.join("\n- ")
+        );
+    }
+    app.insert_resource(report);
+}
+
+/// Add the checks of the [`SharedConfig`] and of the [`ChannelRegistry`]
+pub(crate) fn add_shared_checks(app: &mut App) {
+    app.add_config_check(|world| {
+        let mut issues = vec![];
+        // in HostServer mode, the SharedConfig of the server is the one used by the SharedPlugin
+        let shared = world
+            .get_resource::<ServerConfig>()
+            .map(|config| config.shared)
+            .or_else(|| {
+                world
+                    .get_resource::<ClientConfig>()
+                    .map(|config| config.shared)
+            });
+        if let Some(shared) = shared {
+            issues.extend(tick_duration(&shared));
+            issues.extend(replication_send_interval(&shared));
+        }
+        if let (Some(client), Some(server)) = (
+            world.get_resource::<ClientConfig>(),
+            world.get_resource::<ServerConfig>(),
+        ) {
+            issues.extend(host_server_shared_config(&client.shared, &server.shared));
+        }
+        if let Some(registry) = world.get_resource::<ChannelRegistry>() {
+            for (kind, name) in registry.names() {
+                let Some(builder) = registry.get_builder_from_kind(&kind) else {
+                    continue;
+                };
+                issues.extend(channel_priority(name, &builder.settings));
+                issues.extend(reliable_resend_factor(name, &builder.settings));
+                issues.extend(receive_window(name, &builder.settings));
+            }
+        }
+        issues
+    });
+}
+
+/// Add the checks of the [`ClientConfig`]
+pub(crate) fn add_client_checks(app: &mut App) {
+    app.add_config_check(|world| {
+        let Some(config) = world.get_resource::<ClientConfig>() else {
+            return vec![];
+        };
+        [
+            interpolation_delay(config),
+            input_redundancy(config),
+            input_delay_bounds(&config.prediction),
+            automatic_input_delay(&config.prediction),
+            rollback_window(&config.prediction),
+            client_bandwidth_cap(config),
+            client_link_conditioner(config),
+            fallback_transport_timeout(config),
+            replication_class_quotas(
+                "client.replication.class_quotas",
+                &config.replication,
+                config.packet.bandwidth_cap_enabled,
+            ),
+            ping_stats_buffer(&config.ping, &config.sync),
+            sync_speedup_factor(&config.sync),
+            sync_error_margin(&config.sync),
+            sync_catch_up_ticks(&config.sync),
+        ]
+        .into_iter()
+        .flatten()
+        .collect()
+    });
+}
+
+/// Add the checks of the [`ServerConfig`]
+pub(crate) fn add_server_checks(app: &mut App) {
+    app.add_config_check(|world| {
+        let Some(config) = world.get_resource::<ServerConfig>() else {
+            return vec![];
+        };
+        let mut issues: Vec<_> = [
+            server_replication_send_interval(config),
+            server_bandwidth_cap(config),
+            distance_hysteresis(config),
+            replication_class_quotas(
+                "server.replication.class_quotas",
+                &config.replication,
+                config.packet.bandwidth_cap_enabled,
+            ),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+        issues.extend(server_link_conditioner(config));
+        issues
+    });
+}
+
+/// Number of bytes of a packet that only contains a ping or a pong
+const PING_PACKET_BYTES: f64 = 32.0;
+
+/// Bytes per second used by the pings and pongs, which are sent even if the bandwidth quota is exhausted
+fn ping_bytes_per_second(ping: &PingConfig, tick_duration: Duration) -> Option<f64> {
+    // the pings are sent at most once per frame, which is usually not shorter than a tick
+    let interval = ping.ping_interval.max(tick_duration);
+    if interval.is_zero() {
+        return None;
+    }
+    // every interval, we send a ping and answer the ping of the remote with a pong
+    Some(2.0 * PING_PACKET_BYTES / interval.as_secs_f64())
+}
+
+fn quota_bytes_per_second(quota: &Quota) -> f64 {
+    1.0 / quota.replenish_interval().as_secs_f64()
+}
+
+// SHARED
+
+/// The tick duration must not be zero
+pub(crate) fn tick_duration(shared: &SharedConfig) -> Option<ConfigIssue> {
+    shared.tick.tick_duration.is_zero().then(|| {
+        ConfigIssue::error(
+            "shared.tick.tick_duration",
+            "the tick duration is zero",
+            "set the tick duration to the duration of a FixedUpdate step, for example TickConfig::new(Duration::from_secs_f64(1.0 / 64.0))",
+        )
+    })
+}
+
+/// The replication updates are sent at most once per tick
+pub(crate) fn replication_send_interval(shared: &SharedConfig) -> Option<ConfigIssue> {
+    let send_interval = shared.server_replication_send_interval;
+    let tick_duration = shared.tick.tick_duration;
+    (!send_interval.is_zero() && send_interval < tick_duration).then(|| {
+        ConfigIssue::warning(
+            "shared.server_replication_send_interval",
+            format!(
+                "the replication send interval ({send_interval:?}) is shorter than the tick duration ({tick_duration:?}), but updates are sent at most once per tick"
+            ),
+            format!("set the send interval to Duration::default() to send updates every tick, or to a multiple of {tick_duration:?}"),
+        )
+    })
+}
+
+/// When the client and the server run in the same app, they must use the same [`SharedConfig`]
+pub(crate) fn host_server_shared_config(
+    client: &SharedConfig,
+    server: &SharedConfig,
+) -> Option<ConfigIssue> {
+    (client.tick.tick_duration != server.tick.tick_duration
+        || client.server_replication_send_interval != server.server_replication_send_interval
+        || client.mode != server.mode)
+        .then(|| {
+            ConfigIssue::error(
+                "shared",
+                format!(
+                    "the client and the server run in the same app with different shared configs (client: {client:?}, server: {server:?})"
+                ),
+                "use the same SharedConfig in the ClientConfig and the ServerConfig",
+            )
+        })
+}
+
+// CHANNELS
+
+/// A channel with a priority that is not positive never accumulates priority, so it is starved when the
+/// bandwidth is capped
+pub(crate) fn channel_priority(name: &str, settings: &ChannelSettings) -> Option<ConfigIssue> {
+    let priority = settings.priority;
+    (priority.is_nan() || priority <= 0.0).then(|| {
+        ConfigIssue::error(
+            "channel.priority",
+            format!("the channel {name} has a priority of {priority}, so its messages might never be sent"),
+            format!("set the priority of {name} to a positive value (1.0 is the default)"),
+        )
+    })
+}
+
+/// Resending the reliable messages sooner than one RTT resends messages that were received
+pub(crate) fn reliable_resend_factor(
+    name: &str,
+    settings: &ChannelSettings,
+) -> Option<ConfigIssue> {
+    let (ChannelMode::UnorderedReliable(reliable)
+    | ChannelMode::SequencedReliable(reliable)
+    | ChannelMode::OrderedReliable(reliable)) = &settings.mode
+    else {
+        return None;
+    };
+    let factor = reliable.rtt_resend_factor;
+    (factor < 1.0).then(|| {
+        ConfigIssue::warning(
+            "channel.rtt_resend_factor",
+            format!(
+                "the reliable channel {name} resends its messages after {factor} RTT, before their ack can be received"
+            ),
+            format!("set ReliableSettings::rtt_resend_factor of {name} to at least 1.0 (1.5 is the default)"),
+        )
+    })
+}
+
+/// The sender of a reliable channel without receive window can never send a message
+pub(crate) fn receive_window(name: &str, settings: &ChannelSettings) -> Option<ConfigIssue> {
+    let (ChannelMode::UnorderedReliable(reliable)
+    | ChannelMode::SequencedReliable(reliable)
+    | ChannelMode::OrderedReliable(reliable)) = &settings.mode
+    else {
+        return None;
+    };
+    (reliable.receive_window == Some(0)).then(|| {
+        ConfigIssue::error(
+            "channel.receive_window",
+            format!("the receive window of the channel {name} is 0, so no message can ever be sent on it"),
+            format!("set ReliableSettings::receive_window of {name} to None (no flow control) or to a positive value"),
+        )
+    })
+}
+
+// CLIENT
+
+/// The interpolation delay must cover the interval between two replication updates, otherwise the
+/// interpolated entities often have no update to interpolate towards
+pub(crate) fn interpolation_delay(config: &ClientConfig) -> Option<ConfigIssue> {
+    let send_interval = config.shared.server_replication_send_interval;
+    let delay = config.interpolation.delay.to_duration(send_interval);
+    (!send_interval.is_zero() && delay < send_interval).then(|| {
+        ConfigIssue::warning(
+            "client.interpolation.delay",
+            format!(
+                "the interpolation delay ({delay:?}) is shorter than the replication send interval ({send_interval:?}), so the interpolated entities will stutter"
+            ),
+            format!(
+                "set InterpolationDelay::send_interval_ratio to at least 1.0 (2.0 is the default), or InterpolationDelay::min_delay to at least {send_interval:?}"
+            ),
+        )
+    })
+}
+
+/// The input messages must contain at least one tick of inputs
+pub(crate) fn input_redundancy(config: &ClientConfig) -> Option<ConfigIssue> {
+    (config.input.packet_redundancy == 0).then(|| {
+        ConfigIssue::error(
+            "client.input.packet_redundancy",
+            "the packet redundancy is 0, so the input messages contain no inputs",
+            "set InputConfig::packet_redundancy to at least 1 (10 is the default)",
+        )
+    })
+}
+
+/// With a minimum input delay greater than the maximum input delay before prediction, the input delay drops
+/// below the minimum as soon as the RTT exceeds it
+pub(crate) fn input_delay_bounds(prediction: &PredictionConfig) -> Option<ConfigIssue> {
+    let minimum = prediction.minimum_input_delay_ticks;
+    let maximum = prediction.maximum_input_delay_before_prediction;
+    (prediction.input_delay == InputDelayConfig::Fixed && minimum > maximum).then(|| {
+        ConfigIssue::warning(
+            "client.prediction.minimum_input_delay_ticks",
+            format!(
+                "the minimum input delay ({minimum} ticks) is greater than the maximum input delay before prediction ({maximum} ticks), so the input delay falls to {maximum} ticks when the RTT exceeds {minimum} ticks"
+            ),
+            format!("set PredictionConfig::maximum_input_delay_before_prediction to at least {minimum}"),
+        )
+    })
+}
+
+/// An automatic input delay that is clamped to 0 ticks never applies any delay
+pub(crate) fn automatic_input_delay(prediction: &PredictionConfig) -> Option<ConfigIssue> {
+    matches!(
+        prediction.input_delay,
+        InputDelayConfig::Automatic { max_ticks: 0, .. }
+    )
+    .then(|| {
+        ConfigIssue::warning(
+            "client.prediction.input_delay",
+            "the automatic input delay has a max_ticks of 0, so no input delay is ever applied",
+            "use InputDelayConfig::Fixed, or set max_ticks to the maximum number of ticks of input delay",
+        )
+    })
+}
+
+/// The client predicts up to `maximum_predicted_ticks` ahead of the server, so the confirmed updates can be that old.
+/// If the prediction history is shorter, these updates snap the predicted entities instead of rolling them back
+pub(crate) fn rollback_window(prediction: &PredictionConfig) -> Option<ConfigIssue> {
+    let window = prediction.max_rollback_ticks;
+    let predicted = prediction.maximum_predicted_ticks;
+    (prediction.input_delay == InputDelayConfig::Fixed && window < predicted).then(|| {
+        ConfigIssue::warning(
+            "client.prediction.max_rollback_ticks",
+            format!(
+                "the prediction history is kept for {window} ticks, but the client can predict up to {predicted} ticks ahead: the late confirmed updates will snap the predicted entities instead of rolling them back"
+            ),
+            format!("set PredictionConfig::max_rollback_ticks to at least {predicted}"),
+        )
+    })
+}
+
+/// The pings and pongs are sent even when the bandwidth quota is exhausted, and the bytes that exceed the quota
+/// are paid back before any other message is sent: with a cap below the ping traffic, no other message is ever sent
+pub(crate) fn client_bandwidth_cap(config: &ClientConfig) -> Option<ConfigIssue> {
+    if !config.packet.bandwidth_cap_enabled {
+        return None;
+    }
+    let cap = quota_bytes_per_second(&config.packet.send_bandwidth_cap);
+    let pings = ping_bytes_per_second(&config.ping, config.shared.tick.tick_duration)?;
+    (cap < pings).then(|| {
+        ConfigIssue::error(
+            "client.packet.send_bandwidth_cap",
+            format!(
+                "the bandwidth cap ({cap:.0} bytes/s) is lower than the traffic of the pings ({pings:.0} bytes/s), so no other message can be sent"
+            ),
+            format!(
+                "increase PacketConfig::send_bandwidth_cap above {pings:.0} bytes/s, or increase PingConfig::ping_interval"
+            ),
+        )
+    })
+}
+
+/// If the conditioner delays the packets for longer than the connection timeout, the connection always times out
+pub(crate) fn client_link_conditioner(config: &ClientConfig) -> Option<ConfigIssue> {
+    let ClientNetConfig::Netcode {
+        config: netcode,
+        io,
+        ..
+    } = &config.net
+    else {
+        return None;
+    };
+    link_conditioner(
+        "client.net.io.conditioner",
+        io.conditioner.as_ref()?,
+        netcode.client_timeout_secs,
+    )
+}
+
+/// The fallback transport must be used before the connection times out
+pub(crate) fn fallback_transport_timeout(config: &ClientConfig) -> Option<ConfigIssue> {
+    let ClientNetConfig::Netcode {
+        config: netcode, ..
+    } = &config.net
+    else {
+        return None;
+    };
+    if netcode.fallback_transports.is_empty() || netcode.client_timeout_secs < 0 {
+        return None;
+    }
+    let unhealthy_after = netcode.fallback_unhealthy_after;
+    let timeout = Duration::from_secs(netcode.client_timeout_secs as u64);
+    (unhealthy_after >= timeout).then(|| {
+        ConfigIssue::warning(
+            "client.net.config.fallback_unhealthy_after",
+            format!(
+                "the transport is considered unhealthy after {unhealthy_after:?}, but the connection times out after {timeout:?}, so the fallback transports are never used"
+            ),
+            format!("set NetcodeConfig::fallback_unhealthy_after below {timeout:?}"),
+        )
+    })
+}
+
+/// The stats buffer must be able to hold enough pongs to complete the sync
+pub(crate) fn ping_stats_buffer(ping: &PingConfig, sync: &SyncConfig) -> Option<ConfigIssue> {
+    let required = ping.ping_interval * sync.handshake_pings as u32;
+    let buffer = ping.stats_buffer_duration;
+    (buffer < required).then(|| {
+        ConfigIssue::error(
+            "client.ping.stats_buffer_duration",
+            format!(
+                "the ping stats buffer keeps {buffer:?} of pongs, but the sync needs {} pongs sent every {:?}, so the client never finishes syncing",
+                sync.handshake_pings, ping.ping_interval
+            ),
+            format!("set PingConfig::stats_buffer_duration to at least {required:?}, or reduce the ping interval"),
+        )
+    })
+}
+
+/// A speedup factor below 1.0 makes the client slow down when it should speed up
+pub(crate) fn sync_speedup_factor(sync: &SyncConfig) -> Option<ConfigIssue> {
+    let factor = sync.speedup_factor;
+    (factor.is_nan() || factor < 1.0).then(|| {
+        ConfigIssue::error(
+            "client.sync.speedup_factor",
+            format!("the speedup factor is {factor}, so the client drifts away from the server time instead of catching up"),
+            "set SyncConfig::speedup_factor to a value slightly above 1.0 (1.05 is the default), or to 1.0 to disable the time adjustments",
+        )
+    })
+}
+
+/// The prediction time is snapped every time the error exceeds `max_error_margin`: it must be bigger than the
+/// margin in which the time is not adjusted at all
+pub(crate) fn sync_error_margin(sync: &SyncConfig) -> Option<ConfigIssue> {
+    let margin = sync.error_margin;
+    let max_margin = sync.max_error_margin;
+    (max_margin <= margin).then(|| {
+        ConfigIssue::warning(
+            "client.sync.max_error_margin",
+            format!(
+                "the max error margin ({max_margin} ticks) is not greater than the error margin ({margin} ticks), so the client time snaps instead of being adjusted smoothly"
+            ),
+            format!("set SyncConfig::max_error_margin above {margin} (5.0 is the default)"),
+        )
+    })
+}
+
+/// At least one tick must be simulated every frame, otherwise the client tick never advances
+pub(crate) fn sync_catch_up_ticks(sync: &SyncConfig) -> Option<ConfigIssue> {
+    (sync.max_catch_up_ticks == Some(0)).then(|| {
+        ConfigIssue::error(
+            "client.sync.max_catch_up_ticks",
+            "the maximum number of catch-up ticks is 0, so no tick could be simulated (the limit is ignored)",
+            "set SyncConfig::max_catch_up_ticks to None (no limit) or to at least 1",
+        )
+    })
+}
+
+// SERVER
+
+/// The replication send interval of the server should match the one of the [`SharedConfig`], which the clients use
+/// to compute the interpolation delay
+pub(crate) fn server_replication_send_interval(config: &ServerConfig) -> Option<ConfigIssue> {
+    let shared = config.shared.server_replication_send_interval;
+    let replication = config.replication.send_interval;
+    (shared != replication).then(|| {
+        ConfigIssue::warning(
+            "server.replication.send_interval",
+            format!(
+                "the replication send interval ({replication:?}) is different from shared.server_replication_send_interval ({shared:?}), which the clients use to compute their interpolation delay"
+            ),
+            "set ServerConfig::replication.send_interval and SharedConfig::server_replication_send_interval to the same value",
+        )
+    })
+}
+
+/// See [`client_bandwidth_cap`]
+pub(crate) fn server_bandwidth_cap(config: &ServerConfig) -> Option<ConfigIssue> {
+    if !config.packet.bandwidth_cap_enabled {
+        return None;
+    }
+    let cap = quota_bytes_per_second(&config.packet.per_client_send_bandwidth_cap);
+    let pings = ping_bytes_per_second(&config.ping, config.shared.tick.tick_duration)?;
+    (cap < pings).then(|| {
+        ConfigIssue::error(
+            "server.packet.per_client_send_bandwidth_cap",
+            format!(
+                "the bandwidth cap ({cap:.0} bytes/s per client) is lower than the traffic of the pings ({pings:.0} bytes/s), so no other message can be sent"
+            ),
+            format!(
+                "increase PacketConfig::per_client_send_bandwidth_cap above {pings:.0} bytes/s, or increase PingConfig::ping_interval"
+            ),
+        )
+    })
+}
+
+/// The class quotas cannot reserve more than the whole bandwidth, and are only enforced with a bandwidth cap
+pub(crate) fn replication_class_quotas(
+    field: &'static str,
+    replication: &ReplicationConfig,
+    bandwidth_cap_enabled: bool,
+) -> Option<ConfigIssue> {
+    let quotas = &replication.class_quotas;
+    let total = quotas.total();
+    if total > 100 {
+        return Some(ConfigIssue::error(
+            field,
+            format!("the replication class quotas reserve {total}% of the bandwidth"),
+            "lower the quotas of the ReplicationClassQuotas so that their sum is at most 100",
+        ));
+    }
+    (quotas.is_enabled() && !bandwidth_cap_enabled).then(|| {
+        ConfigIssue::warning(
+            field,
+            "replication class quotas are configured, but the bandwidth cap is disabled so they are not enforced",
+            "enable the bandwidth cap with PacketConfig::enable_bandwidth_cap",
+        )
+    })
+}
+
+/// With a hysteresis factor below 1.0, an entity that enters the relevance radius immediately leaves it again
+pub(crate) fn distance_hysteresis(config: &ServerConfig) -> Option<ConfigIssue> {
+    let factor = config.replication.interest.distance_hysteresis;
+    (factor.is_nan() || factor < 1.0).then(|| {
+        ConfigIssue::error(
+            "server.replication.interest.distance_hysteresis",
+            format!("the distance hysteresis is {factor}, so the entities at the edge of their relevance radius are spawned and despawned on every check"),
+            "set InterestConfig::distance_hysteresis to 1.0 or more (1.2 is the default)",
+        )
+    })
+}
+
+/// See [`client_link_conditioner`]
+pub(crate) fn server_link_conditioner(config: &ServerConfig) -> Vec<ConfigIssue> {
+    config
+        .net
+        .iter()
+        .filter_map(|net| {
+            #[allow(irrefutable_let_patterns)]
+            let ServerNetConfig::Netcode {
+                config: netcode,
+                io,
+            } = net
+            else {
+                return None;
+            };
+            link_conditioner(
+                "server.net.io.conditioner",
+                io.conditioner.as_ref()?,
+                netcode.client_timeout_secs,
+            )
+        })
+        .collect()
+}
+
+fn link_conditioner(
+    check: &'static str,
+    conditioner: &LinkConditionerConfig,
+    client_timeout_secs: i32,
+) -> Option<ConfigIssue> {
+    // a negative timeout means that the connection never times out
+    if client_timeout_secs < 0 {
+        return None;
+    }
+    // the conditioner delays the incoming packets by up to `latency + jitter`
+    let delay = conditioner.incoming_latency + conditioner.incoming_jitter;
+    let timeout = Duration::from_secs(client_timeout_secs as u64);
+    (delay >= timeout).then(|| {
+        ConfigIssue::error(
+            check,
+            format!(
+                "the link conditioner delays the packets by up to {delay:?}, which is longer than the connection timeout ({timeout:?})"
+            ),
+            format!(
+                "reduce LinkConditionerConfig::incoming_latency + incoming_jitter below {timeout:?}, or increase NetcodeConfig::client_timeout_secs"
+            ),
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::default;
+    use nonzero_ext::nonzero;
+
+    use crate::client::interpolation::plugin::{InterpolationConfig, InterpolationDelay};
+    use crate::prelude::client::{self, FallbackTransport};
+    use crate::prelude::server;
+    use crate::prelude::{ReliableSettings, TickConfig};
+    use crate::shared::replication::classes::{ReplicationClass, ReplicationClassQuotas};
+
+    use super::*;
+
+    fn shared(tick_ms: u64, send_interval_ms: u64) -> SharedConfig {
+        SharedConfig {
+            server_replication_send_interval: Duration::from_millis(send_interval_ms),
+            tick: TickConfig::new(Duration::from_millis(tick_ms)),
+            ..default()
+        }
+    }
+
+    fn reliable(settings: ReliableSettings) -> ChannelSettings {
+        ChannelSettings {
+            mode: ChannelMode::OrderedReliable(settings),
+            ..default()
+        }
+    }
+
+    fn conditioner(latency_ms: u64) -> LinkConditionerConfig {
+        LinkConditionerConfig {
+            incoming_latency: Duration::from_millis(latency_ms),
+            incoming_jitter: Duration::from_millis(100),
+            incoming_loss: 0.0,
+            seed: None,
+        }
+    }
+
+    #[test]
+    fn test_default_config_is_valid() {
+        let client = ClientConfig::default();
+        let server = ServerConfig::default();
+        let mut app = App::new();
+        app.insert_resource(client);
+        app.insert_resource(server);
+        app.insert_resource(ChannelRegistry::new(Duration::default()));
+        add_shared_checks(&mut app);
+        add_client_checks(&mut app);
+        add_server_checks(&mut app);
+        assert_eq!(validate_config(app.world()), ConfigReport::default());
+    }
+
+    #[test]
+    fn test_custom_check() {
+        #[derive(Resource)]
+        struct Radius(f32);
+
+        let mut app = App::new();
+        app.insert_resource(Radius(-1.0));
+        app.add_config_check(|world| {
+            let radius = world.resource::<Radius>().0;
+            if radius > 0.0 {
+                return vec![];
+            }
+            vec![ConfigIssue::error(
+                "radius",
+                format!("the radius is {radius}"),
+                "set a positive radius",
+            )]
+        });
+        let report = validate_config(app.world());
+        assert!(report.has_errors());
+        assert_eq!(report.warnings().count(), 0);
+        assert_eq!(
+            report.issues[0].to_string(),
+            "[radius] the radius is -1. Fix: set a positive radius"
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "The networking configuration is invalid")]
+    fn test_errors_refuse_to_start() {
+        let mut app = App::new();
+        app.insert_resource(ClientConfig {
+            sync: SyncConfig::default().speedup_factor(0.9),
+            ..default()
+        });
+        add_client_checks(&mut app);
+        run_config_checks(&mut app);
+    }
+
+    #[test]
+    fn test_warnings_are_reported() {
+        let mut app = App::new();
+        app.insert_resource(ServerConfig {
+            shared: shared(16, 100),
+            ..default()
+        });
+        add_server_checks(&mut app);
+        run_config_checks(&mut app);
+        let report = app.world().resource::<ConfigReport>();
+        assert!(!report.has_errors());
+        assert_eq!(
+            report.warnings().next().unwrap().check,
+            "server.replication.send_interval"
+        );
+    }
+
+    #[test]
+    fn test_tick_duration() {
+        assert!(tick_duration(&shared(16, 0)).is_none());
+        assert_eq!(
+            tick_duration(&shared(0, 0)).unwrap().severity,
+            ConfigSeverity::Error
+        );
+    }
+
+    #[test]
+    fn test_replication_send_interval() {
+        assert!(replication_send_interval(&shared(16, 0)).is_none());
+        assert!(replication_send_interval(&shared(16, 32)).is_none());
+        assert!(replication_send_interval(&shared(16, 10)).is_some());
+    }
+
+    #[test]
+    fn test_host_server_shared_config() {
+        assert!(host_server_shared_config(&shared(16, 0), &shared(16, 0)).is_none());
+        assert!(host_server_shared_config(&shared(16, 0), &shared(10, 0)).is_some());
+        assert!(host_server_shared_config(&shared(16, 0), &shared(16, 100)).is_some());
+    }
+
+    #[test]
+    fn test_channel_priority() {
+        let settings = |priority| ChannelSettings {
+            priority,
+            ..default()
+        };
+        assert!(channel_priority("channel", &settings(1.0)).is_none());
+        assert!(channel_priority("channel", &settings(f32::INFINITY)).is_none());
+        assert!(channel_priority("channel", &settings(0.0)).is_some());
+        assert!(channel_priority("channel", &settings(f32::NAN)).is_some());
+    }
+
+    #[test]
+    fn test_reliable_resend_factor() {
+        assert!(reliable_resend_factor("channel", &reliable(default())).is_none());
+        let issue = reliable_resend_factor(
+            "channel",
+            &reliable(ReliableSettings {
+                rtt_resend_factor: 0.5,
+                ..default()
+            }),
+        )
+        .unwrap();
+        assert_eq!(issue.severity, ConfigSeverity::Warning);
+        // unreliable channels don't resend
+        assert!(reliable_resend_factor("channel", &ChannelSettings::default()).is_none());
+    }
+
+    #[test]
+    fn test_receive_window() {
+        let window = |receive_window| {
+            reliable(ReliableSettings {
+                receive_window,
+                ..default()
+            })
+        };
+        assert!(receive_window("channel", &window(None)).is_none());
+        assert!(receive_window("channel", &window(Some(8))).is_none());
+        assert!(receive_window("channel", &window(Some(0))).is_some());
+    }
+
+    #[test]
+    fn test_interpolation_delay() {
+        let config = |send_interval_ratio, min_delay_ms| ClientConfig {
+            shared: shared(16, 100),
+            interpolation: InterpolationConfig {
+                delay: InterpolationDelay::default()
+                    .with_send_interval_ratio(send_interval_ratio)
+                    .with_min_delay(Duration::from_millis(min_delay_ms)),
+                ..default()
+            },
+            ..default()
+        };
+        assert!(interpolation_delay(&config(2.0, 0)).is_none());
+        assert!(interpolation_delay(&config(0.0, 100)).is_none());
+        let issue = interpolation_delay(&config(0.0, 50)).unwrap();
+        assert!(issue.message.contains("50ms"));
+        assert!(issue.message.contains("100ms"));
+        // updates are sent every tick
+        assert!(interpolation_delay(&ClientConfig::default()).is_none());
+    }
+
+    #[test]
+    fn test_input_redundancy() {
+        let mut config = ClientConfig::default();
+        assert!(input_redundancy(&config).is_none());
+        config.input.packet_redundancy = 0;
+        assert!(input_redundancy(&config).is_some());
+    }
+
+    #[test]
+    fn test_input_delay_bounds() {
+        let config = PredictionConfig {
+            minimum_input_delay_ticks: 4,
+            maximum_input_delay_before_prediction: 6,
+            ..default()
+        };
+        assert!(input_delay_bounds(&config).is_none());
+        assert!(input_delay_bounds(&config.with_minimum_input_delay_ticks(8)).is_some());
+    }
+
+    #[test]
+    fn test_rollback_window() {
+        let config = PredictionConfig::default();
+        assert!(rollback_window(&config).is_none());
+        assert!(rollback_window(&config.with_max_rollback_ticks(10)).is_some());
+    }
+
+    #[test]
+    fn test_automatic_input_delay() {
+        let automatic = |max_ticks| {
+            PredictionConfig::default().with_input_delay(InputDelayConfig::Automatic {
+                max_ticks,
+                rtt_margin: Duration::default(),
+            })
+        };
+        assert!(automatic_input_delay(&automatic(6)).is_none());
+        assert!(automatic_input_delay(&automatic(0)).is_some());
+    }
+
+    #[test]
+    fn test_bandwidth_cap() {
+        let mut client = ClientConfig::default();
+        client.packet = client
+            .packet
+            .with_send_bandwidth_cap(Quota::per_second(nonzero!(1000u32)))
+            .enable_bandwidth_cap();
+        client.ping.ping_interval = Duration::from_millis(100);
+        // 2 packets of 32 bytes every 100ms
+        assert!(client_bandwidth_cap(&client).is_none());
+        client.ping.ping_interval = Duration::default();
+        // 2 packets of 32 bytes every tick
+        assert!(client_bandwidth_cap(&client).is_some());
+
+        let mut server = ServerConfig::default();
+        server.packet = server
+            .packet
+            .with_send_bandwidth_cap(Quota::per_second(nonzero!(1000u32)))
+            .enable_bandwidth_cap();
+        server.ping.ping_interval = Duration::default();
+        assert!(server_bandwidth_cap(&server).is_some());
+        server.packet.bandwidth_cap_enabled = false;
+        assert!(server_bandwidth_cap(&server).is_none());
+    }
+
+    #[test]
+    fn test_distance_hysteresis() {
+        let mut config = ServerConfig::default();
+        assert!(distance_hysteresis(&config).is_none());
+        config.replication.interest.distance_hysteresis = 0.8;
+        assert!(distance_hysteresis(&config).is_some());
+    }
+
+    #[test]
+    fn test_replication_class_quotas() {
+        let (players, world) = (ReplicationClass::new(1), ReplicationClass::new(2));
+        let mut config = ServerConfig::default();
+        let check = |config: &ServerConfig| {
+            replication_class_quotas(
+                "server.replication.class_quotas",
+                &config.replication,
+                config.packet.bandwidth_cap_enabled,
+            )
+        };
+        assert!(check(&config).is_none());
+        config.replication.class_quotas = ReplicationClassQuotas::default()
+            .with_quota(players, 70)
+            .with_quota(world, 30);
+        assert_eq!(check(&config).unwrap().severity, ConfigSeverity::Warning);
+        config.packet = config.packet.enable_bandwidth_cap();
+        assert!(check(&config).is_none());
+        config.replication.class_quotas = config.replication.class_quotas.with_quota(world, 40);
+        assert_eq!(check(&config).unwrap().severity, ConfigSeverity::Error);
+    }
+
+    #[test]
+    fn test_link_conditioner() {
+        let client = |latency_ms| {
+            let mut config = ClientConfig::default();
+            let ClientNetConfig::Netcode { io, .. } = &mut config.net else {
+                unreachable!()
+            };
+            io.conditioner = Some(conditioner(latency_ms));
+            config
+        };
+        assert!(client_link_conditioner(&client(200)).is_none());
+        let issue = client_link_conditioner(&client(3000)).unwrap();
+        assert_eq!(issue.severity, ConfigSeverity::Error);
+        assert!(issue.message.contains("3.1s"));
+
+        let server = ServerConfig {
+            net: vec![ServerNetConfig::Netcode {
+                config: default(),
+                io: server::IoConfig::from_transport(server::ServerTransport::Dummy)
+                    .with_conditioner(conditioner(5000)),
+            }],
+            ..default()
+        };
+        assert_eq!(server_link_conditioner(&server).len(), 1);
+    }
+
+    #[test]
+    fn test_fallback_transport_timeout() {
+        let config = |fallback: bool, unhealthy_after_secs| ClientConfig {
+            net: ClientNetConfig::Netcode {
+                auth: default(),
+                config: client::NetcodeConfig {
+                    fallback_transports: fallback
+                        .then(|| FallbackTransport {
+                            io: client::IoConfig::default(),
+                            server_addr: None,
+                        })
+                        .into_iter()
+                        .collect(),
+                    fallback_unhealthy_after: Duration::from_secs(unhealthy_after_secs),
+                    ..default()
+                },
+                io: default(),
+            },
+            ..default()
+        };
+        assert!(fallback_transport_timeout(&config(true, 1)).is_none());
+        assert!(fallback_transport_timeout(&config(true, 5)).is_some());
+        // no fallback transport
+        assert!(fallback_transport_timeout(&config(false, 5)).is_none());
+    }
+
+    #[test]
+    fn test_ping_stats_buffer() {
+        let sync = SyncConfig::default();
+        let ping = |interval_ms| PingConfig {
+            ping_interval: Duration::from_millis(interval_ms),
+            stats_buffer_duration: Duration::from_secs(1),
+        };
+        assert!(ping_stats_buffer(&ping(100), &sync).is_none());
+        assert!(ping_stats_buffer(&ping(500), &sync).is_some());
+    }
+
+    #[test]
+    fn test_sync() {
+        assert!(sync_speedup_factor(&SyncConfig::default()).is_none());
+        assert!(sync_speedup_factor(&SyncConfig::default().speedup_factor(1.0)).is_none());
+        assert!(sync_speedup_factor(&SyncConfig::default().speedup_factor(0.95)).is_some());
+        let sync = SyncConfig {
+            error_margin: 2.0,
+            max_error_margin: 1.0,
+            ..default()
+        };
+        assert!(sync_error_margin(&SyncConfig::default()).is_none());
+        assert!(sync_error_margin(&sync).is_some());
+    }
+
+    #[test]
+    fn test_sync_catch_up_ticks() {
+        assert!(sync_catch_up_ticks(&SyncConfig::default()).is_none());
+        assert!(sync_catch_up_ticks(&SyncConfig::default().max_catch_up_ticks(4)).is_none());
+        assert!(sync_catch_up_ticks(&SyncConfig::default().max_catch_up_ticks(0)).is_some());
+    }
+
+    #[test]
+    fn test_server_replication_send_interval() {
+        let mut config = ServerConfig::default();
+        assert!(server_replication_send_interval(&config).is_none());
+        config.replication.send_interval = Duration::from_millis(100);
+        assert!(server_replication_send_interval(&config).is_some());
+    }
+}
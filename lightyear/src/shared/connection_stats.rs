@@ -0,0 +1,135 @@
+//! Live statistics of a connection, for example to display a network HUD.
+//!
+//! The [`ConnectionStats`] are refreshed every [`PingConfig::ping_interval`](crate::shared::ping::manager::PingConfig::ping_interval),
+//! when the connection sends a ping to the remote. The RTT keeps being measured with the pings after the client is synced.
+//! They can be retrieved with `connection_stats()` on the client's `ConnectionManager`, or on the server's
+//! `ConnectionManager` for each client.
+use bevy::reflect::Reflect;
+use bevy::utils::Duration;
+
+use crate::packet::stats_manager::packet::PacketStatsManager;
+use crate::shared::ping::manager::PingManager;
+use crate::shared::time_manager::{TimeManager, WrappedTime};
+use crate::utils::ready_buffer::ReadyBuffer;
+
+/// Duration of the window over which the bandwidth is measured
+const BANDWIDTH_WINDOW: Duration = Duration::from_secs(1);
+
+/// Latest estimates of the network statistics of a connection
+#[derive(Debug, Clone, Copy, Default, PartialEq, Reflect)]
+pub struct ConnectionStats {
+    /// Round-trip time, averaged over the last pongs received
+    pub rtt: Duration,
+    /// Jitter of the one-way delay, computed over the last pongs received
+    pub jitter: Duration,
+    /// Percentage (between 0 and 100) of the recently sent packets that were not acked by the remote
+    pub packet_loss: f32,
+    /// Kilobytes per second received from the remote over the last second, without the netcode headers
+    pub kbps_received: f32,
+    /// Kilobytes per second sent to the remote over the last second, without the netcode headers
+    pub kbps_sent: f32,
+    /// Number of times that the reliable channels sent a message again because it was not acked in time
+    pub packets_resent: u64,
+}
+
+/// Bytes sent and received during a frame
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct FrameBytes {
+    sent: u64,
+    received: u64,
+}
+
+/// Accumulates the bytes of a connection, and computes its [`ConnectionStats`]
+#[derive(Debug, Default)]
+pub(crate) struct ConnectionStatsTracker {
+    stats: ConnectionStats,
+    /// Bytes of the frames of the last [`BANDWIDTH_WINDOW`]
+    window: ReadyBuffer<WrappedTime, FrameBytes>,
+    /// Sum of the bytes in the window
+    window_bytes: FrameBytes,
+    /// Bytes of the current frame
+    current: FrameBytes,
+}
+
+impl ConnectionStatsTracker {
+    pub(crate) fn stats(&self) -> &ConnectionStats {
+        &self.stats
+    }
+
+    pub(crate) fn record_sent(&mut self, bytes: usize) {
+        self.current.sent += bytes as u64;
+    }
+
+    pub(crate) fn record_received(&mut self, bytes: usize) {
+        self.current.received += bytes as u64;
+    }
+
+    /// Add the bytes of the current frame to the window, and remove the frames that are too old
+    pub(crate) fn update(&mut self, time_manager: &TimeManager) {
+        let current_time = time_manager.current_time();
+        for (_, bytes) in self.window.drain_until(&(current_time - BANDWIDTH_WINDOW)) {
+            self.window_bytes.sent -= bytes.sent;
+            self.window_bytes.received -= bytes.received;
+        }
+        let current = std::mem::take(&mut self.current);
+        self.window_bytes.sent += current.sent;
+        self.window_bytes.received += current.received;
+        self.window.push(current_time, current);
+    }
+
+    /// Recompute the [`ConnectionStats`] from the latest measurements
+    pub(crate) fn refresh(
+        &mut self,
+        ping_manager: &PingManager,
+        packet_stats: &PacketStatsManager,
+        packets_resent: u64,
+    ) {
+        let window = BANDWIDTH_WINDOW.as_secs_f32();
+        self.stats = ConnectionStats {
+            rtt: ping_manager.rtt(),
+            jitter: ping_manager.jitter(),
+            packet_loss: 100.0 * packet_stats.packet_loss(),
+            kbps_received: self.window_bytes.received as f32 / 1000.0 / window,
+            kbps_sent: self.window_bytes.sent as f32 / 1000.0 / window,
+            packets_resent,
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shared::ping::manager::PingConfig;
+
+    #[test]
+    fn test_bandwidth_window() {
+        let mut time_manager = TimeManager::default();
+        let ping_manager = PingManager::new(PingConfig::default());
+        let packet_stats = PacketStatsManager::default();
+        let mut tracker = ConnectionStatsTracker::default();
+
+        // 100 frames of 10ms, with 20 bytes sent and 50 bytes received per frame
+        for _ in 0..100 {
+            time_manager.update(Duration::from_millis(10));
+            tracker.record_sent(20);
+            tracker.record_received(50);
+            tracker.update(&time_manager);
+        }
+        // the stats are only computed when they are refreshed
+        assert_eq!(tracker.stats(), &ConnectionStats::default());
+        tracker.refresh(&ping_manager, &packet_stats, 3);
+        assert_eq!(tracker.stats().kbps_sent, 2.0);
+        assert_eq!(tracker.stats().kbps_received, 5.0);
+        assert_eq!(tracker.stats().packets_resent, 3);
+        assert_eq!(tracker.stats().rtt, ping_manager.rtt());
+
+        // the older frames leave the window
+        for _ in 0..50 {
+            time_manager.update(Duration::from_millis(10));
+            tracker.update(&time_manager);
+        }
+        tracker.refresh(&ping_manager, &packet_stats, 3);
+        assert_eq!(tracker.stats().kbps_sent, 1.0);
+        assert_eq!(tracker.stats().kbps_received, 2.5);
+    }
+}
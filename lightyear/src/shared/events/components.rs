@@ -2,20 +2,39 @@
 
 use std::marker::PhantomData;
 
-use bevy::prelude::{Component, Entity, Event};
+use bevy::prelude::{Component, Entity, Event, Resource};
 
+use crate::inputs::native::TickInputs;
+use crate::inputs::LocalPlayerId;
 use crate::packet::message::Message;
+use crate::shared::timeline_message::TimelineDelivery;
 
 /// This event is emitted whenever we receive a message from the remote
 #[derive(Event)]
 pub struct MessageEvent<M: Message, Ctx = ()> {
     pub message: M,
     pub context: Ctx,
+    timeline: Option<TimelineDelivery>,
 }
 
 impl<M: Message, Ctx> MessageEvent<M, Ctx> {
     pub fn new(message: M, context: Ctx) -> Self {
-        Self { message, context }
+        Self {
+            message,
+            context,
+            timeline: None,
+        }
+    }
+
+    pub(crate) fn with_timeline(mut self, timeline: Option<TimelineDelivery>) -> Self {
+        self.timeline = timeline;
+        self
+    }
+
+    /// How the message was delivered, if it was sent at a tick of a
+    /// [`VisualTimeline`](crate::shared::timeline_message::VisualTimeline)
+    pub fn timeline(&self) -> Option<&TimelineDelivery> {
+        self.timeline.as_ref()
     }
 
     pub fn message(&self) -> &M {
@@ -32,11 +51,58 @@ impl<M: Message, Ctx> MessageEvent<M, Ctx> {
 pub struct InputEvent<I: crate::inputs::native::UserAction, Ctx = ()> {
     input: Option<I>,
     context: Ctx,
+    local_player: LocalPlayerId,
+    entity: Option<Entity>,
+    predicted: bool,
 }
 
 impl<I: crate::inputs::native::UserAction, Ctx> InputEvent<I, Ctx> {
     pub fn new(input: Option<I>, context: Ctx) -> Self {
-        Self { input, context }
+        Self {
+            input,
+            context,
+            local_player: LocalPlayerId::default(),
+            entity: None,
+            predicted: false,
+        }
+    }
+
+    /// Create the events for the inputs of a local player on a tick: one event per input,
+    /// or a single event without input if there are no inputs for the tick
+    pub(crate) fn from_tick_inputs(
+        inputs: TickInputs<I>,
+        context: Ctx,
+        local_player: LocalPlayerId,
+        entity: Option<Entity>,
+    ) -> impl Iterator<Item = Self>
+    where
+        Ctx: Clone,
+    {
+        let inputs: TickInputs<Option<I>> = if inputs.is_empty() {
+            TickInputs::from_elem(None, 1)
+        } else {
+            inputs.into_iter().map(Some).collect()
+        };
+        inputs.into_iter().map(move |input| {
+            Self::new(input, context.clone()).with_local_player(local_player, entity)
+        })
+    }
+
+    /// Specify which local player the input is for, and the entity controlled by that player (if any)
+    pub(crate) fn with_local_player(
+        mut self,
+        local_player: LocalPlayerId,
+        entity: Option<Entity>,
+    ) -> Self {
+        self.local_player = local_player;
+        self.entity = entity;
+        self
+    }
+
+    /// Mark the input as predicted by the server, because the input of the client was missing
+    pub(crate) fn with_predicted(mut self, predicted: bool) -> Self {
+        self.predicted = predicted;
+        self
     }
 
     pub fn input(&self) -> &Option<I> {
@@ -46,6 +112,26 @@ impl<I: crate::inputs::native::UserAction, Ctx> InputEvent<I, Ctx> {
     pub fn context(&self) -> &Ctx {
         &self.context
     }
+
+    /// The local player (of the connection) that generated this input
+    pub fn local_player(&self) -> LocalPlayerId {
+        self.local_player
+    }
+
+    /// The entity controlled by the local player that generated this input.
+    ///
+    /// On the server, this is the entity whose [`ControlledBy`](crate::prelude::server::ControlledBy) matches
+    /// the client and the local player. On the client, this is the predicted entity of the local player
+    /// (or the confirmed entity if it is not predicted).
+    pub fn entity(&self) -> Option<Entity> {
+        self.entity
+    }
+
+    /// Returns true if the server did not receive the input of the client for the tick in time, and the
+    /// input was predicted according to the [`MissingInputPolicy`](crate::server::config::MissingInputPolicy)
+    pub fn is_predicted(&self) -> bool {
+        self.predicted
+    }
 }
 
 #[derive(Event)]
@@ -170,3 +256,45 @@ impl<C: Component, Ctx> ComponentRemoveEvent<C, Ctx> {
         &self.context
     }
 }
+
+/// Event emitted whenever we insert or update a replicated resource from the remote world
+#[derive(Event)]
+pub struct ResourceUpdateEvent<R: Resource, Ctx = ()> {
+    context: Ctx,
+
+    _marker: PhantomData<R>,
+}
+
+impl<R: Resource, Ctx> ResourceUpdateEvent<R, Ctx> {
+    pub fn new(context: Ctx) -> Self {
+        Self {
+            context,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn context(&self) -> &Ctx {
+        &self.context
+    }
+}
+
+/// Event emitted whenever we remove a replicated resource because it was removed in the remote world
+#[derive(Event)]
+pub struct ResourceRemoveEvent<R: Resource, Ctx = ()> {
+    context: Ctx,
+
+    _marker: PhantomData<R>,
+}
+
+impl<R: Resource, Ctx> ResourceRemoveEvent<R, Ctx> {
+    pub fn new(context: Ctx) -> Self {
+        Self {
+            context,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn context(&self) -> &Ctx {
+        &self.context
+    }
+}
@@ -9,6 +9,7 @@ use tracing::trace;
 use crate::prelude::{ComponentRegistry, Tick};
 use crate::protocol::component::ComponentNetId;
 use crate::protocol::EventContext;
+use crate::shared::replication::changes::{ReplicationChangeType, ReplicationChanges};
 
 // TODO: don't make fields pub but instead make accessors
 #[derive(Debug, Resource)]
@@ -36,6 +37,8 @@ pub struct ConnectionEvents {
 
     // How can i easily get the events (inserts/adds/removes) for a given entity? add components on that entity
     // that track that?
+    /// Ordered changes, recorded only for the [`ReplicationChangesPlugin`](crate::shared::replication::changes::ReplicationChangesPlugin)
+    pub(crate) changes: ReplicationChanges,
     empty: bool,
 }
 
@@ -50,6 +53,7 @@ impl ClearEvents for ConnectionEvents {
         self.component_inserts.clear();
         self.component_removes.clear();
         self.component_updates.clear();
+        self.changes.clear();
         self.empty = true;
     }
 }
@@ -69,6 +73,7 @@ impl ConnectionEvents {
             component_inserts: Default::default(),
             component_removes: Default::default(),
             component_updates: Default::default(),
+            changes: ReplicationChanges::default(),
             // bookkeeping
             empty: true,
         }
@@ -110,7 +115,8 @@ impl ConnectionEvents {
             metrics::counter!("component_insert", "kind" => kind.to_string()).increment(1);
         }
         self.component_inserts.entry(kind).or_default().push(entity);
-        // .push((entity, tick));
+        self.changes
+            .record(entity, kind, ReplicationChangeType::Inserted, tick);
         self.empty = false;
     }
 
@@ -126,7 +132,8 @@ impl ConnectionEvents {
             metrics::counter!("component_remove", "kind" => kind.to_string()).increment(1);
         }
         self.component_removes.entry(kind).or_default().push(entity);
-        // .push((entity, tick));
+        self.changes
+            .record(entity, kind, ReplicationChangeType::Removed, tick);
         self.empty = false;
     }
 
@@ -155,7 +162,8 @@ impl ConnectionEvents {
         //     .or_insert(tick);
 
         self.component_updates.entry(kind).or_default().push(entity);
-        // .push((entity, tick));
+        self.changes
+            .record(entity, kind, ReplicationChangeType::Updated, tick);
         self.empty = false;
     }
 }
@@ -0,0 +1,306 @@
+/*! Network hooks: user systems that run at precise points of the networking lifecycle
+
+Some bookkeeping must run at a point that the public [`SystemSet`](bevy::prelude::SystemSet)s cannot express,
+for example once after a rollback instead of once per resimulated tick. Register a system at one of the
+hook points with [`AppNetworkHookExt::add_network_hook`]; the system is run as a one-shot system, and its
+commands are applied right after it runs. Several hooks registered at the same point run in the order
+in which they were registered.
+
+| Hook point | Peer | When |
+|---|---|---|
+| [`PostRollback`] | client | In `PreUpdate`, in [`PredictionSet::Rollback`], once the `FixedMain` schedule has been run for all the resimulated ticks. Runs once per rollback. |
+| [`PostSync`] | client | In `PostUpdate`, after the [`SyncSet`] of the frame where the client becomes synced with the server, and before [`MainSet::Send`]. Runs once per connection. |
+| [`PreReplicationSend`] | server | In `PostUpdate`, on the frames where the server sends replication messages (every [`ReplicationConfig::send_interval`]), once for each connected client, before the replication messages are assembled. The system receives the [`ClientId`] of the client. |
+| [`PostJoinSnapshot`] | client | In `PreUpdate`, after [`MainSet::EmitEvents`] of the frame where the client has applied all the entities that the server replicated when the client connected. Runs once per connection. |
+
+```rust
+use bevy::prelude::*;
+use lightyear::prelude::*;
+
+fn rebuild_spatial_cache() {}
+
+fn personalize(In(client_id): In<ClientId>) {
+    // update the data that will be replicated to `client_id`
+}
+
+let mut app = App::new();
+app.add_network_hook(PostRollback, rebuild_spatial_cache);
+app.add_network_hook(PreReplicationSend, personalize);
+```
+
+The hooks do not run in host-server mode for the local client: it is never rolled back, synced or sent a snapshot.
+
+[`PredictionSet::Rollback`]: crate::prelude::client::PredictionSet::Rollback
+[`SyncSet`]: crate::client::sync::SyncSet
+[`MainSet::Send`]: crate::prelude::MainSet::Send
+[`MainSet::EmitEvents`]: crate::prelude::MainSet::EmitEvents
+[`ReplicationConfig::send_interval`]: crate::prelude::ReplicationConfig::send_interval
+*/
+use bevy::ecs::system::SystemId;
+use bevy::ecs::world::Command;
+use bevy::prelude::{App, IntoSystem, Res, Resource, World};
+use serde::{Deserialize, Serialize};
+use tracing::error;
+
+use crate::packet::message::MessageId;
+use crate::prelude::ClientId;
+use crate::shared::replication::components::ReplicationGroupId;
+
+/// A point of the networking lifecycle where hooks can be registered.
+///
+/// The hooks registered at this point are systems that take [`NetworkHookPoint::Input`] as input.
+pub trait NetworkHookPoint: Send + Sync + 'static {
+    /// Input of the hook systems
+    type Input: Clone + Send + Sync + 'static;
+}
+
+/// Client hook point: right after a rollback has resimulated all the ticks
+#[derive(Debug, Clone, Copy)]
+pub struct PostRollback;
+
+impl NetworkHookPoint for PostRollback {
+    type Input = ();
+}
+
+/// Client hook point: right after the client becomes synced with the server
+#[derive(Debug, Clone, Copy)]
+pub struct PostSync;
+
+impl NetworkHookPoint for PostSync {
+    type Input = ();
+}
+
+/// Server hook point: right before the replication messages for a client are assembled.
+/// The hook systems receive the [`ClientId`] of the client.
+#[derive(Debug, Clone, Copy)]
+pub struct PreReplicationSend;
+
+impl NetworkHookPoint for PreReplicationSend {
+    type Input = ClientId;
+}
+
+/// Client hook point: right after the client has applied the entities that existed on the server when it connected
+#[derive(Debug, Clone, Copy)]
+pub struct PostJoinSnapshot;
+
+impl NetworkHookPoint for PostJoinSnapshot {
+    type Input = ();
+}
+
+/// Systems registered at a hook point, in registration order
+#[derive(Resource)]
+pub(crate) struct NetworkHooks<H: NetworkHookPoint> {
+    systems: Vec<SystemId<H::Input>>,
+}
+
+impl<H: NetworkHookPoint> Default for NetworkHooks<H> {
+    fn default() -> Self {
+        Self {
+            systems: Vec::new(),
+        }
+    }
+}
+
+/// Extension trait to register [network hooks](self) on the [`App`]
+pub trait AppNetworkHookExt {
+    /// Run the system every time the networking lifecycle reaches the hook point
+    fn add_network_hook<H: NetworkHookPoint, M>(
+        &mut self,
+        point: H,
+        system: impl IntoSystem<H::Input, (), M> + 'static,
+    ) -> &mut Self;
+}
+
+impl AppNetworkHookExt for App {
+    fn add_network_hook<H: NetworkHookPoint, M>(
+        &mut self,
+        _point: H,
+        system: impl IntoSystem<H::Input, (), M> + 'static,
+    ) -> &mut Self {
+        let world = self.world_mut();
+        let system_id = world.register_system(system);
+        world
+            .get_resource_or_insert_with(NetworkHooks::<H>::default)
+            .systems
+            .push(system_id);
+        self
+    }
+}
+
+/// Run the hooks registered at the hook point
+pub(crate) fn run_network_hooks<H: NetworkHookPoint>(world: &mut World, input: H::Input) {
+    let Some(hooks) = world.get_resource::<NetworkHooks<H>>() else {
+        return;
+    };
+    for system_id in hooks.systems.clone() {
+        if let Err(e) = world.run_system_with_input(system_id, input.clone()) {
+            error!(
+                "Could not run the {:?} network hook: {e}",
+                std::any::type_name::<H>()
+            );
+        }
+    }
+}
+
+/// Run condition that returns true if some hooks are registered at the hook point
+pub(crate) fn has_network_hooks<H: NetworkHookPoint>(hooks: Option<Res<NetworkHooks<H>>>) -> bool {
+    hooks.is_some_and(|hooks| !hooks.systems.is_empty())
+}
+
+/// [`Command`] that runs the hooks registered at the hook point, for the systems that do not have access to the [`World`]
+pub(crate) struct RunNetworkHooks<H: NetworkHookPoint>(pub(crate) H::Input);
+
+impl<H: NetworkHookPoint> Command for RunNetworkHooks<H> {
+    fn apply(self, world: &mut World) {
+        run_network_hooks::<H>(world, self.0);
+    }
+}
+
+/// Message sent by the server to a client that just connected, after the replication messages that spawn the
+/// entities that already existed.
+///
+/// The snapshot is applied once the client has received, for each replication group, the actions message
+/// with the given id.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub(crate) struct JoinSnapshotMessage {
+    pub(crate) groups: Vec<(ReplicationGroupId, MessageId)>,
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::{default, In, Query, ResMut, With};
+    use bevy::utils::Duration;
+
+    use crate::prelude::client::ConnectionManager;
+    use crate::prelude::server::{Replicate, ServerConfig};
+    use crate::prelude::*;
+    use crate::tests::protocol::*;
+
+    use super::*;
+
+    /// Calls of the hooks, with the number of entities replicated to the client when the hook ran
+    #[derive(Resource, Default)]
+    struct HookCalls(Vec<usize>);
+
+    fn record_synced(connection: Res<ConnectionManager>, mut calls: ResMut<HookCalls>) {
+        assert!(connection.is_synced());
+        calls.0.push(0);
+    }
+
+    #[test]
+    fn test_post_sync_hook() {
+        let mut pair = LightyearTestPair::builder()
+            .protocol(ProtocolPlugin)
+            .build_disconnected();
+        pair.client_apps[0]
+            .init_resource::<HookCalls>()
+            .add_network_hook(PostSync, record_synced);
+        pair.connect();
+        assert!(pair
+            .client_world(0)
+            .resource::<ConnectionManager>()
+            .is_synced());
+        assert_eq!(pair.client_world(0).resource::<HookCalls>().0.len(), 1);
+        pair.frame_steps(10);
+        assert_eq!(pair.client_world(0).resource::<HookCalls>().0.len(), 1);
+    }
+
+    #[derive(Resource, Default)]
+    struct SendHookCalls(Vec<ClientId>);
+
+    /// The hook modifies the component right before the replication messages are assembled
+    fn personalize(
+        In(client_id): In<ClientId>,
+        mut calls: ResMut<SendHookCalls>,
+        mut query: Query<&mut Component1>,
+    ) {
+        calls.0.push(client_id);
+        for mut component in query.iter_mut() {
+            component.0 = 10.0;
+        }
+    }
+
+    #[test]
+    fn test_pre_replication_send_hook() {
+        let mut config = ServerConfig::default();
+        config.replication.send_interval = Duration::from_millis(40);
+        let mut pair = LightyearTestPair::builder()
+            .protocol(ProtocolPlugin)
+            .clients(2)
+            .server_config(config)
+            .build();
+        pair.server_app
+            .init_resource::<SendHookCalls>()
+            .add_network_hook(PreReplicationSend, personalize);
+        let server_entity = pair
+            .server_world_mut()
+            .spawn((Component1(1.0), Replicate::default()))
+            .id();
+        // one send every 4 frames
+        pair.frame_steps(8);
+        let calls = &pair.server_world().resource::<SendHookCalls>().0;
+        assert_eq!(calls.len(), 4);
+        for index in 0..2 {
+            assert_eq!(
+                calls
+                    .iter()
+                    .filter(|client_id| **client_id == pair.client_id(index))
+                    .count(),
+                2
+            );
+        }
+        // the value set by the hook was replicated
+        pair.frame_steps(4);
+        let client_entity = *pair
+            .client_world(0)
+            .resource::<ConnectionManager>()
+            .replication_receiver
+            .remote_entity_map
+            .get_local(server_entity)
+            .unwrap();
+        assert_eq!(
+            pair.client_world(0).get::<Component1>(client_entity),
+            Some(&Component1(10.0))
+        );
+    }
+
+    fn record_snapshot(entities: Query<(), With<Component1>>, mut calls: ResMut<HookCalls>) {
+        calls.0.push(entities.iter().count());
+    }
+
+    /// The hook runs once, when all the entities that existed when the client connected are spawned
+    #[test]
+    fn test_post_join_snapshot_hook() {
+        let mut pair = LightyearTestPair::builder()
+            .protocol(ProtocolPlugin)
+            .conditioner(LinkConditionerConfig {
+                incoming_latency: Duration::from_millis(30),
+                incoming_jitter: Duration::from_millis(20),
+                incoming_loss: 0.1,
+                seed: None,
+            })
+            .build_disconnected();
+        pair.client_apps[0]
+            .init_resource::<HookCalls>()
+            .add_network_hook(PostJoinSnapshot, record_snapshot);
+        // some entities share a replication group, the others have their own
+        for i in 0..20 {
+            let group = if i < 10 {
+                ReplicationGroup::new_id(i % 2)
+            } else {
+                ReplicationGroup::default()
+            };
+            pair.server_world_mut()
+                .spawn((Component1(i as f32), Replicate { group, ..default() }));
+        }
+        pair.connect();
+        pair.frame_steps(100);
+        assert_eq!(pair.client_world(0).resource::<HookCalls>().0, vec![20]);
+
+        // entities spawned later are not part of the snapshot
+        pair.server_world_mut()
+            .spawn((Component1(0.0), Replicate::default()));
+        pair.frame_steps(20);
+        assert_eq!(pair.client_world(0).resource::<HookCalls>().0, vec![20]);
+    }
+}
@@ -0,0 +1,236 @@
+//! Jitter buffer of the client inputs on the server.
+//!
+//! The inputs of each client are buffered on the server until the tick at which they are applied. The
+//! margin of an input message is the number of ticks between the last tick of the message and the next tick
+//! that the server will simulate when it receives the message: with a negative margin, the input arrived too late
+//! and the server had to fall back to the previous input of the client.
+//!
+//! The server measures the margins of each client over windows of [`InputJitterConfig::window`] input messages,
+//! and exposes their distribution with [`ConnectionManager::input_margin_stats`](crate::server::connection::ConnectionManager::input_margin_stats)
+//! and in the per-client diagnostics of the [`ServerDiagnosticsPlugin`](crate::server::diagnostics::ServerDiagnosticsPlugin).
+//!
+//! With [`InputJitterConfig::auto_tune`], the server also advises the client to change its input delay
+//! by one tick when the p99 margin is persistently negative (inputs are missed) or persistently above
+//! [`InputJitterConfig::max_margin_ticks`] (the inputs are delayed more than needed).
+//! The server never changes how it applies the inputs: the client stays in charge of its input delay,
+//! and only acts on the advice if it uses [`InputDelayConfig::Automatic`](crate::client::prediction::plugin::InputDelayConfig::Automatic).
+//! Clients with a fixed input delay can read the advice with `MessageEvent<InputDelayAdvice>`.
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, error};
+
+use crate::channel::builder::ServerTimingChannel;
+use crate::client::connection::ConnectionManager as ClientConnectionManager;
+use crate::client::events::MessageEvent;
+use crate::prelude::server::ServerConfig;
+use crate::prelude::Tick;
+use crate::server::connection::ConnectionManager as ServerConnectionManager;
+
+/// Configuration of the input jitter buffer. Only used on the server.
+#[derive(Clone, Copy, Debug, Reflect)]
+pub struct InputJitterConfig {
+    /// Number of input messages over which the distribution of the margins is computed
+    pub window: u16,
+    /// If true, the server advises the clients to change their input delay
+    pub auto_tune: bool,
+    /// The input delay is considered too large when the p99 margin is above this number of ticks
+    pub max_margin_ticks: i16,
+    /// Number of consecutive windows that must call for the same change before it is advised to the client
+    pub persistence: u8,
+}
+
+impl Default for InputJitterConfig {
+    fn default() -> Self {
+        Self {
+            window: 60,
+            auto_tune: false,
+            max_margin_ticks: 2,
+            persistence: 3,
+        }
+    }
+}
+
+impl InputJitterConfig {
+    /// Advise the clients to change their input delay
+    pub fn with_auto_tune(mut self, auto_tune: bool) -> Self {
+        self.auto_tune = auto_tune;
+        self
+    }
+}
+
+/// Change of input delay advised by the server to a client
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Reflect)]
+pub enum InputDelayAdvice {
+    /// Increase the input delay by one tick, because some inputs arrived too late
+    Increase,
+    /// Decrease the input delay by one tick, because the inputs arrive much earlier than needed
+    Decrease,
+}
+
+/// Distribution of the margins of the input messages of a client (in ticks), over the last completed window
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Reflect)]
+pub struct InputMarginStats {
+    /// Median margin
+    pub p50: i16,
+    /// Margin that 99% of the input messages arrived with (or more)
+    pub p99: i16,
+    /// Number of input messages that arrived too late
+    pub late: u16,
+}
+
+/// Margins of the input messages received from a client
+#[derive(Debug, Default)]
+pub(crate) struct InputMargins {
+    /// Margins of the current window
+    samples: Vec<i16>,
+    stats: Option<InputMarginStats>,
+    /// True if a window was completed since the last diagnostics flush
+    pub(crate) unflushed: bool,
+    /// Change called for by the last consecutive windows, and the number of these windows
+    streak: Option<(InputDelayAdvice, u8)>,
+}
+
+impl InputMargins {
+    /// Record the margin of an input message with inputs up to `end_tick`, received when the
+    /// server's last simulated tick was `tick`
+    pub(crate) fn record(&mut self, end_tick: Tick, tick: Tick) {
+        self.record_margin(end_tick - (tick + 1));
+    }
+
+    fn record_margin(&mut self, margin: i16) {
+        self.samples.push(margin);
+    }
+
+    pub(crate) fn stats(&self) -> Option<InputMarginStats> {
+        self.stats
+    }
+
+    /// Compute the distribution of the margins once the current window is complete,
+    /// and return the change of input delay that should be advised to the client
+    fn evaluate(&mut self, config: &InputJitterConfig) -> Option<InputDelayAdvice> {
+        if self.samples.len() < config.window.max(1) as usize {
+            return None;
+        }
+        self.samples.sort_unstable();
+        let percentile = |p: f32| self.samples[((self.samples.len() - 1) as f32 * p) as usize];
+        let stats = InputMarginStats {
+            p50: percentile(0.5),
+            p99: percentile(0.01),
+            late: self.samples.iter().filter(|margin| **margin < 0).count() as u16,
+        };
+        self.samples.clear();
+        self.stats = Some(stats);
+        self.unflushed = true;
+
+        let verdict = if stats.p99 < 0 {
+            Some(InputDelayAdvice::Increase)
+        } else if stats.p99 > config.max_margin_ticks {
+            Some(InputDelayAdvice::Decrease)
+        } else {
+            None
+        };
+        self.streak = match (verdict, self.streak) {
+            (Some(verdict), Some((previous, count))) if verdict == previous => {
+                Some((verdict, count + 1))
+            }
+            (Some(verdict), _) => Some((verdict, 1)),
+            (None, _) => None,
+        };
+        let (advice, count) = self.streak?;
+        if count < config.persistence {
+            return None;
+        }
+        // the windows measured before the client applies the advice are not representative anymore
+        self.streak = None;
+        Some(advice)
+    }
+}
+
+/// Server system that computes the margins of the inputs of each client, and sends the advised changes
+/// of input delay to the clients
+pub(crate) fn advise_input_delay(
+    config: Res<ServerConfig>,
+    mut connection_manager: ResMut<ServerConnectionManager>,
+) {
+    let config = config.input_jitter;
+    let advices: Vec<_> = connection_manager
+        .connections
+        .iter_mut()
+        .filter_map(|(client_id, connection)| {
+            let advice = connection.input_margins.evaluate(&config)?;
+            config.auto_tune.then_some((*client_id, advice))
+        })
+        .collect();
+    for (client_id, advice) in advices {
+        debug!(
+            ?client_id,
+            ?advice,
+            "Advise the client to change its input delay"
+        );
+        let _ = connection_manager
+            .send_message::<ServerTimingChannel, _>(client_id, &advice)
+            .inspect_err(|e| error!("Could not send the input delay advice: {e:?}"));
+    }
+}
+
+/// Client system that applies the input delay changes advised by the server
+pub(crate) fn receive_input_delay_advice(
+    mut connection_manager: ResMut<ClientConnectionManager>,
+    mut events: EventReader<MessageEvent<InputDelayAdvice>>,
+) {
+    for event in events.read() {
+        connection_manager
+            .sync_manager
+            .apply_input_delay_advice(event.message);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record_window(margins: &mut InputMargins, config: &InputJitterConfig, values: &[i16]) {
+        for value in values.iter().cycle().take(config.window as usize) {
+            margins.record_margin(*value);
+        }
+    }
+
+    #[test]
+    fn test_input_margins() {
+        let config = InputJitterConfig {
+            window: 100,
+            persistence: 2,
+            ..default()
+        };
+        let mut margins = InputMargins::default();
+        assert_eq!(margins.evaluate(&config), None);
+        assert_eq!(margins.stats(), None);
+
+        // one input out of 50 is late
+        let mut values = vec![2; 49];
+        values.push(-1);
+        record_window(&mut margins, &config, &values);
+        assert_eq!(margins.evaluate(&config), None);
+        assert_eq!(
+            margins.stats(),
+            Some(InputMarginStats {
+                p50: 2,
+                p99: -1,
+                late: 2,
+            })
+        );
+        // the advice is only sent after 2 consecutive windows
+        record_window(&mut margins, &config, &values);
+        assert_eq!(margins.evaluate(&config), Some(InputDelayAdvice::Increase));
+
+        // the streak is reset by a window with a good margin
+        record_window(&mut margins, &config, &[5]);
+        assert_eq!(margins.evaluate(&config), None);
+        record_window(&mut margins, &config, &[1]);
+        assert_eq!(margins.evaluate(&config), None);
+        record_window(&mut margins, &config, &[5]);
+        assert_eq!(margins.evaluate(&config), None);
+        record_window(&mut margins, &config, &[5]);
+        assert_eq!(margins.evaluate(&config), Some(InputDelayAdvice::Decrease));
+    }
+}
@@ -1,3 +1,4 @@
+pub mod jitter;
 pub mod native;
 
 #[cfg(feature = "leafwing")]
@@ -6,6 +6,7 @@ use crate::client::config::ClientConfig;
 use crate::inputs::native::InputMessage;
 use crate::prelude::{MessageRegistry, UserAction};
 use crate::protocol::message::MessageType;
+use crate::protocol::serialize::SerializeFns;
 use crate::server::config::ServerConfig;
 
 pub struct InputPlugin<A> {
@@ -26,7 +27,13 @@ impl<A: UserAction> Plugin for InputPlugin<A> {
     fn finish(&self, app: &mut App) {
         app.world_mut()
             .resource_mut::<MessageRegistry>()
-            .add_message::<InputMessage<A>>(MessageType::NativeInput);
+            .add_message_custom_serde::<InputMessage<A>>(
+                MessageType::NativeInput,
+                SerializeFns {
+                    serialize: InputMessage::serialize_packed,
+                    deserialize: InputMessage::deserialize_packed,
+                },
+            );
         let is_client = app.world().get_resource::<ClientConfig>().is_some();
         let is_server = app.world().get_resource::<ServerConfig>().is_some();
         if is_client {
@@ -0,0 +1,375 @@
+//! Ordering of messages sent on different channels.
+//!
+//! Each channel only guarantees (at best) the ordering of the messages sent on that channel.
+//! If two messages are causally related but sent on different channels (for example "entity X
+//! equipped item Y" on a reliable channel, and the cosmetic details of item Y on an unreliable channel),
+//! the second one could be received before the first one.
+//!
+//! A [`SendGroup`] lets you opt into ordering across channels: every message sent with the same
+//! group is delivered to the application in the order it was buffered on the sender, regardless of
+//! the channel. Later members of the group are held back on the receiver until the earlier members
+//! have arrived.
+//!
+//! ```rust,ignore
+//! let group = connection_manager.new_send_group();
+//! connection_manager.send_message_in_group::<ReliableChannel, _>(&Equip { entity, item }, group)?;
+//! connection_manager.send_message_in_group::<UnreliableChannel, _>(&Cosmetics { item }, group)?;
+//! ```
+//!
+//! Unreliable members can be lost: if an earlier member is still missing after a timeout
+//! (`PacketConfig::message_group_timeout`), the receiver stops waiting for it and delivers the
+//! following members. The receiver never gives up on a reliable member.
+//!
+//! Messages that are delivered in the same frame are emitted as events in the same frame;
+//! the ordering guarantee is that a member is never delivered in an earlier frame than the members
+//! that were buffered before it.
+//!
+//! Grouped messages carry a small header (~6 bytes); messages sent without a group are not affected.
+use std::collections::BTreeMap;
+
+use bevy::utils::{Duration, HashMap};
+use byteorder::{NetworkEndian, ReadBytesExt, WriteBytesExt};
+
+use crate::serialize::reader::Reader;
+use crate::serialize::varint::{varint_len, VarIntReadExt, VarIntWriteExt};
+use crate::serialize::{SerializationError, ToBytes};
+use crate::shared::time_manager::WrappedTime;
+use crate::utils::wrapping_id::wrapping_id;
+
+// Position of a message inside a SendGroup
+wrapping_id!(GroupSequence);
+
+/// Token used to order messages across channels.
+///
+/// All the messages sent with the same [`SendGroup`] are delivered to the remote in the order in which they
+/// were sent, even if they are sent on different channels. Obtain one from the `ConnectionManager`
+/// (`new_send_group`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SendGroup(pub(crate) u16);
+
+/// Header written in front of the messages that are part of a [`SendGroup`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct MessageGroupHeader {
+    pub(crate) group: SendGroup,
+    pub(crate) sequence: GroupSequence,
+    /// Number of members between this message and the latest reliable member sent before it
+    /// (0 if there is none). The receiver never skips over that member.
+    pub(crate) reliable_back: u16,
+}
+
+impl ToBytes for MessageGroupHeader {
+    fn len(&self) -> usize {
+        2 + self.sequence.len() + varint_len(self.reliable_back as u64)
+    }
+
+    fn to_bytes<T: WriteBytesExt>(&self, buffer: &mut T) -> Result<(), SerializationError> {
+        buffer.write_u16::<NetworkEndian>(self.group.0)?;
+        self.sequence.to_bytes(buffer)?;
+        buffer.write_varint(self.reliable_back as u64)?;
+        Ok(())
+    }
+
+    fn from_bytes(buffer: &mut Reader) -> Result<Self, SerializationError>
+    where
+        Self: Sized,
+    {
+        let group = SendGroup(buffer.read_u16::<NetworkEndian>()?);
+        let sequence = GroupSequence::from_bytes(buffer)?;
+        let reliable_back = buffer.read_varint()? as u16;
+        Ok(Self {
+            group,
+            sequence,
+            reliable_back,
+        })
+    }
+}
+
+#[derive(Debug, Default)]
+struct SenderGroupState {
+    next_sequence: GroupSequence,
+    last_reliable: Option<GroupSequence>,
+}
+
+/// Assigns the group headers of the messages sent to a single remote peer
+#[derive(Debug, Default)]
+pub(crate) struct MessageGroupSender {
+    groups: HashMap<SendGroup, SenderGroupState>,
+}
+
+impl MessageGroupSender {
+    /// Get the header for the next message of the group
+    pub(crate) fn next_header(&mut self, group: SendGroup, reliable: bool) -> MessageGroupHeader {
+        let state = self.groups.entry(group).or_default();
+        let sequence = state.next_sequence;
+        state.next_sequence += 1;
+        let reliable_back = state
+            .last_reliable
+            .map_or(0, |last_reliable| (sequence - last_reliable).max(0) as u16);
+        if reliable {
+            state.last_reliable = Some(sequence);
+        }
+        MessageGroupHeader {
+            group,
+            sequence,
+            reliable_back,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct PendingGroupMessage<T> {
+    reliable_back: u16,
+    received_at: WrappedTime,
+    message: T,
+}
+
+#[derive(Debug)]
+struct ReceiverGroupState<T> {
+    next_sequence: GroupSequence,
+    pending: BTreeMap<GroupSequence, PendingGroupMessage<T>>,
+}
+
+impl<T> Default for ReceiverGroupState<T> {
+    fn default() -> Self {
+        Self {
+            next_sequence: GroupSequence::default(),
+            pending: BTreeMap::new(),
+        }
+    }
+}
+
+/// Holds back the grouped messages received from a single remote peer until they can be delivered in order
+#[derive(Debug)]
+pub(crate) struct MessageGroupReceiver<T> {
+    /// How long we wait for a missing unreliable member before delivering the next members
+    timeout: Duration,
+    groups: HashMap<SendGroup, ReceiverGroupState<T>>,
+    /// Messages that are ready to be delivered, in order
+    ready: Vec<T>,
+}
+
+impl<T> MessageGroupReceiver<T> {
+    pub(crate) fn new(timeout: Duration) -> Self {
+        Self {
+            timeout,
+            groups: HashMap::default(),
+            ready: Vec::new(),
+        }
+    }
+
+    /// Buffer a grouped message that was just received
+    pub(crate) fn recv(&mut self, header: MessageGroupHeader, message: T, now: WrappedTime) {
+        let state = self.groups.entry(header.group).or_default();
+        if header.sequence < state.next_sequence {
+            // we already stopped waiting for this member, deliver it immediately
+            self.ready.push(message);
+            return;
+        }
+        state.pending.insert(
+            header.sequence,
+            PendingGroupMessage {
+                reliable_back: header.reliable_back,
+                received_at: now,
+                message,
+            },
+        );
+    }
+
+    /// Return the messages that can be delivered, in the order in which they were sent
+    pub(crate) fn drain_ready(&mut self, now: WrappedTime) -> Vec<T> {
+        for state in self.groups.values_mut() {
+            while let Some((&sequence, first)) = state.pending.first_key_value() {
+                if sequence != state.next_sequence {
+                    // some earlier members are missing: only skip them if none of them is reliable
+                    // and we waited long enough
+                    let missing_reliable = first.reliable_back != 0
+                        && sequence - first.reliable_back >= state.next_sequence;
+                    if missing_reliable || first.received_at + self.timeout > now {
+                        break;
+                    }
+                }
+                let (sequence, pending) = state.pending.pop_first().unwrap();
+                state.next_sequence = sequence;
+                state.next_sequence += 1;
+                self.ready.push(pending.message);
+            }
+        }
+        std::mem::take(&mut self.ready)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::client::{ClientConfig, IoConfig, NetConfig};
+    use crate::prelude::{server, LinkConditionerConfig, SharedConfig, TickConfig};
+    use crate::tests::protocol::{Channel1, Channel3, Message1};
+    use crate::tests::stepper::{BevyStepper, Step, TEST_CLIENT_ID};
+    use bevy::prelude::{default, EventReader, ResMut, Resource, Update};
+
+    const TIMEOUT: Duration = Duration::from_millis(100);
+
+    fn time(millis: u32) -> WrappedTime {
+        WrappedTime::new(millis)
+    }
+
+    /// Send a list of (message, reliable) in the same group and return the headers
+    fn send(sender: &mut MessageGroupSender, reliable: &[bool]) -> Vec<MessageGroupHeader> {
+        reliable
+            .iter()
+            .map(|reliable| sender.next_header(SendGroup(0), *reliable))
+            .collect()
+    }
+
+    #[test]
+    fn test_header_serialization() {
+        let header = MessageGroupHeader {
+            group: SendGroup(3),
+            sequence: GroupSequence(u16::MAX),
+            reliable_back: 2,
+        };
+        let mut writer = vec![];
+        header.to_bytes(&mut writer).unwrap();
+        assert_eq!(writer.len(), header.len());
+        let mut reader = Reader::from(writer);
+        assert_eq!(MessageGroupHeader::from_bytes(&mut reader).unwrap(), header);
+    }
+
+    #[test]
+    fn test_reordered_members_are_held_back() {
+        let mut sender = MessageGroupSender::default();
+        let headers = send(&mut sender, &[true, false, true, false]);
+        let mut receiver = MessageGroupReceiver::new(TIMEOUT);
+
+        // receive in the order 3, 1, 2, 0
+        receiver.recv(headers[3], 3, time(0));
+        receiver.recv(headers[1], 1, time(0));
+        assert!(receiver.drain_ready(time(0)).is_empty());
+        receiver.recv(headers[2], 2, time(10));
+        assert!(receiver.drain_ready(time(10)).is_empty());
+        receiver.recv(headers[0], 0, time(20));
+        assert_eq!(receiver.drain_ready(time(20)), vec![0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn test_lost_unreliable_member_timeout() {
+        let mut sender = MessageGroupSender::default();
+        let headers = send(&mut sender, &[true, false, false]);
+        let mut receiver = MessageGroupReceiver::new(TIMEOUT);
+
+        receiver.recv(headers[0], 0, time(0));
+        assert_eq!(receiver.drain_ready(time(0)), vec![0]);
+        // member 1 (unreliable) is lost
+        receiver.recv(headers[2], 2, time(10));
+        assert!(receiver.drain_ready(time(50)).is_empty());
+        assert_eq!(receiver.drain_ready(time(110)), vec![2]);
+
+        // if the lost member arrives after all, it is delivered immediately
+        receiver.recv(headers[1], 1, time(120));
+        assert_eq!(receiver.drain_ready(time(120)), vec![1]);
+    }
+
+    #[test]
+    fn test_missing_reliable_member_is_never_skipped() {
+        let mut sender = MessageGroupSender::default();
+        let headers = send(&mut sender, &[false, true, false, false]);
+        let mut receiver = MessageGroupReceiver::new(TIMEOUT);
+
+        // member 0 (unreliable) is lost, member 1 (reliable) is delayed
+        receiver.recv(headers[3], 3, time(0));
+        receiver.recv(headers[2], 2, time(0));
+        assert!(receiver.drain_ready(time(500)).is_empty());
+
+        // once the reliable member arrives, we skip the lost unreliable member after the timeout
+        receiver.recv(headers[1], 1, time(500));
+        assert!(receiver.drain_ready(time(550)).is_empty());
+        assert_eq!(receiver.drain_ready(time(600)), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_groups_are_independent() {
+        let mut sender = MessageGroupSender::default();
+        let a = sender.next_header(SendGroup(0), true);
+        let b_0 = sender.next_header(SendGroup(1), true);
+        let b_1 = sender.next_header(SendGroup(1), true);
+        assert_eq!(a.sequence, GroupSequence(0));
+        assert_eq!(b_1.sequence, GroupSequence(1));
+        assert_eq!(b_1.reliable_back, 1);
+
+        let mut receiver = MessageGroupReceiver::new(TIMEOUT);
+        receiver.recv(b_1, "b_1", time(0));
+        receiver.recv(a, "a", time(0));
+        assert_eq!(receiver.drain_ready(time(0)), vec!["a"]);
+        receiver.recv(b_0, "b_0", time(0));
+        assert_eq!(receiver.drain_ready(time(0)), vec!["b_0", "b_1"]);
+    }
+
+    #[derive(Resource, Default)]
+    struct Received(Vec<usize>);
+
+    fn record_messages(
+        mut received: ResMut<Received>,
+        mut events: EventReader<crate::client::events::MessageEvent<Message1>>,
+    ) {
+        for event in events.read() {
+            received.0.push(event.message().0.parse().unwrap());
+        }
+    }
+
+    /// Send members of a group alternately on a reliable and an unreliable channel, with jitter so that
+    /// the packets are reordered, and check that the client receives them in order
+    #[test]
+    fn test_group_order_with_reordering() {
+        let frame_duration = Duration::from_millis(10);
+        let shared_config = SharedConfig {
+            tick: TickConfig::new(Duration::from_millis(10)),
+            ..default()
+        };
+        let client_config = ClientConfig {
+            net: NetConfig::Netcode {
+                auth: default(),
+                config: default(),
+                io: IoConfig::default().with_conditioner(LinkConditionerConfig {
+                    incoming_latency: Duration::from_millis(30),
+                    incoming_jitter: Duration::from_millis(25),
+                    incoming_loss: 0.0,
+                    seed: None,
+                }),
+            },
+            ..default()
+        };
+        let mut stepper = BevyStepper::new(shared_config, client_config, frame_duration);
+        stepper.init();
+        stepper.client_app.init_resource::<Received>();
+        stepper.client_app.add_systems(Update, record_messages);
+
+        let client_id = crate::prelude::ClientId::Netcode(TEST_CLIENT_ID);
+        let group = stepper
+            .server_app
+            .world_mut()
+            .resource_mut::<server::ConnectionManager>()
+            .new_send_group();
+        const NUM_MESSAGES: usize = 40;
+        for i in 0..NUM_MESSAGES {
+            let mut manager = stepper
+                .server_app
+                .world_mut()
+                .resource_mut::<server::ConnectionManager>();
+            let message = Message1(i.to_string());
+            if i % 2 == 0 {
+                manager.send_message_in_group::<Channel3, _>(client_id, &message, group)
+            } else {
+                manager.send_message_in_group::<Channel1, _>(client_id, &message, group)
+            }
+            .unwrap();
+            stepper.frame_step();
+        }
+        for _ in 0..20 {
+            stepper.frame_step();
+        }
+        assert_eq!(
+            stepper.client_app.world().resource::<Received>().0,
+            (0..NUM_MESSAGES).collect::<Vec<_>>()
+        );
+    }
+}
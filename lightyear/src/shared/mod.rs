@@ -1,7 +1,15 @@
 //! Shared code between the server and client.
 
+pub mod action;
+
+pub mod clock;
+
 pub mod config;
 
+pub mod config_check;
+
+pub mod connection_stats;
+
 pub mod events;
 
 pub mod log;
@@ -12,11 +20,21 @@ pub mod plugin;
 
 pub mod replication;
 
+pub mod request;
+
 pub mod sets;
 
 pub mod tick_manager;
 
+pub mod hooks;
 pub mod input;
 pub(crate) mod message;
+pub mod message_group;
+pub mod network_time;
 pub mod run_conditions;
+pub(crate) mod schema;
+pub mod serialized_size;
+pub mod session_summary;
 pub mod time_manager;
+pub mod timeline_message;
+pub mod warnings;
@@ -0,0 +1,328 @@
+//! Mapping between the server ticks and the server's wall-clock time.
+//!
+//! The server periodically tells the clients "tick T started at UNIX time X". Clients combine this
+//! anchor with their existing estimate of the current server time (computed by the
+//! [`SyncManager`](crate::client::sync::SyncManager)), so that they can know the server's UNIX time
+//! without ever reading their own wall-clock, which could be wrong or could jump.
+//! The accuracy of the client's mapping is therefore the accuracy of that estimate, which can be smoothed
+//! with [`SyncConfig::server_time_estimate_smoothing`](crate::client::sync::SyncConfig::server_time_estimate_smoothing).
+//!
+//! Both on the client and the server, the mapping is available via the [`NetworkTime`] resource.
+use bevy::prelude::*;
+use bevy::utils::Duration;
+use serde::{Deserialize, Serialize};
+use tracing::{error, trace};
+
+use crate::channel::builder::ServerTimingChannel;
+use crate::client::connection::ConnectionManager as ClientConnectionManager;
+use crate::client::events::MessageEvent;
+use crate::prelude::server::ServerConfig;
+use crate::prelude::{NetworkTarget, Tick, TickManager, TimeManager};
+use crate::server::connection::ConnectionManager as ServerConnectionManager;
+use crate::server::events::ConnectEvent;
+use crate::shared::clock::NetworkClock;
+
+/// If the server's wall-clock moves by more than this amount compared to our current mapping,
+/// we snap to the new mapping instead of smoothing it
+const MAX_SMOOTHED_CORRECTION: Duration = Duration::from_secs(1);
+
+/// Weight of the previous mapping when a new anchor is received from the server
+const ANCHOR_SMOOTHING: f64 = 0.5;
+
+/// Configuration of the server time broadcast
+#[derive(Clone, Copy, Debug, Reflect)]
+pub struct NetworkTimeConfig {
+    /// How often the server sends its wall-clock time to the clients.
+    /// (a newly connected client always receives it immediately)
+    pub broadcast_interval: Duration,
+}
+
+impl Default for NetworkTimeConfig {
+    fn default() -> Self {
+        Self {
+            broadcast_interval: Duration::from_secs(1),
+        }
+    }
+}
+
+/// Message sent by the server to bind one of its ticks to its wall-clock time
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub(crate) struct ServerTimeMessage {
+    pub(crate) tick: Tick,
+    /// UNIX time (duration since the UNIX epoch) at which `tick` started on the server
+    pub(crate) unix_time: Duration,
+    /// Tick duration of the server, which is authoritative: the client checks it against its own
+    /// before completing the sync, and adopts it if the server changes it at runtime
+    pub(crate) tick_duration: Duration,
+}
+
+/// Bevy [`Resource`] that maps server ticks to the server's UNIX time.
+///
+/// On the server the mapping is exact. On the client it is available once the first
+/// server time message has been received and the client is synced with the server.
+#[derive(Resource, Debug, Default, Clone)]
+pub struct NetworkTime {
+    tick_duration: Duration,
+    /// A server tick along with the UNIX time at which that tick started on the server
+    anchor: Option<(Tick, Duration)>,
+    /// Current server tick, and the fraction of that tick that has elapsed
+    current: Option<(Tick, f32)>,
+    /// Server only: the UNIX time at the server's startup, and the elapsed real time at that moment
+    clock_origin: Option<(Duration, Duration)>,
+    /// Server only: the elapsed real time at which we last broadcast the server time
+    last_broadcast: Option<Duration>,
+}
+
+impl NetworkTime {
+    /// Returns true if the mapping between server ticks and server UNIX time is known
+    pub fn is_ready(&self) -> bool {
+        self.anchor.is_some() && self.current.is_some()
+    }
+
+    /// UNIX time (as a duration since the UNIX epoch) at which the server tick started
+    pub fn tick_to_unix(&self, tick: Tick) -> Option<Duration> {
+        let (anchor_tick, anchor_unix) = self.anchor?;
+        let diff = tick - anchor_tick;
+        let offset = self.tick_duration * diff.unsigned_abs() as u32;
+        if diff >= 0 {
+            Some(anchor_unix + offset)
+        } else {
+            Some(anchor_unix.saturating_sub(offset))
+        }
+    }
+
+    /// Current UNIX time on the server (as a duration since the UNIX epoch).
+    ///
+    /// On the client, this is an estimate that only depends on the server's clock. Use it to display
+    /// times shared across all clients (match start times, timers, etc.)
+    pub fn server_unix_time_now(&self) -> Option<Duration> {
+        let (tick, overstep) = self.current?;
+        Some(self.tick_to_unix(tick)? + self.tick_duration.mul_f32(overstep))
+    }
+
+    /// Server only: send the server time to all the clients during the next update, instead of waiting
+    /// for the broadcast interval
+    pub(crate) fn broadcast_now(&mut self) {
+        self.last_broadcast = None;
+    }
+
+    /// Update the anchor with a new value sent by the server.
+    ///
+    /// Small corrections are smoothed so that the server time does not jitter; large corrections
+    /// (the server's wall-clock was changed) are applied directly.
+    fn receive_anchor(&mut self, tick: Tick, unix_time: Duration) {
+        let Some(predicted) = self.tick_to_unix(tick) else {
+            self.anchor = Some((tick, unix_time));
+            return;
+        };
+        let correction = signed_secs(unix_time, predicted);
+        if correction.abs() > MAX_SMOOTHED_CORRECTION.as_secs_f64() {
+            self.anchor = Some((tick, unix_time));
+            return;
+        }
+        let correction = correction * (1.0 - ANCHOR_SMOOTHING);
+        let smoothed = if correction >= 0.0 {
+            predicted + Duration::from_secs_f64(correction)
+        } else {
+            predicted.saturating_sub(Duration::from_secs_f64(-correction))
+        };
+        self.anchor = Some((tick, smoothed));
+    }
+}
+
+/// Returns `a - b` in seconds
+fn signed_secs(a: Duration, b: Duration) -> f64 {
+    if a >= b {
+        (a - b).as_secs_f64()
+    } else {
+        -(b - a).as_secs_f64()
+    }
+}
+
+/// Server system that updates the [`NetworkTime`] and sends it to the clients.
+///
+/// The server's wall-clock is read only once; afterwards it is advanced with the real time so that
+/// the mapping is monotonic.
+pub(crate) fn send_server_time(
+    config: Res<ServerConfig>,
+    clock: Res<NetworkClock>,
+    real_time: Res<Time<Real>>,
+    tick_manager: Res<TickManager>,
+    time_manager: Res<TimeManager>,
+    mut network_time: ResMut<NetworkTime>,
+    mut connection_manager: ResMut<ServerConnectionManager>,
+    mut connect_events: EventReader<ConnectEvent>,
+) {
+    let elapsed = real_time.elapsed();
+    let (origin_unix, origin_elapsed) = *network_time
+        .clock_origin
+        .get_or_insert_with(|| (clock.unix_time(), elapsed));
+    let tick_duration = tick_manager.config.tick_duration;
+    let overstep = time_manager.overstep();
+    let tick = tick_manager.tick();
+    let unix_time =
+        (origin_unix + (elapsed - origin_elapsed)).saturating_sub(tick_duration.mul_f32(overstep));
+    network_time.tick_duration = tick_duration;
+    network_time.anchor = Some((tick, unix_time));
+    network_time.current = Some((tick, overstep));
+
+    let message = ServerTimeMessage {
+        tick,
+        unix_time,
+        tick_duration,
+    };
+    let connected: Vec<_> = connect_events.read().map(|event| event.client_id).collect();
+    let broadcast = network_time.last_broadcast.map_or(true, |last| {
+        elapsed - last >= config.network_time.broadcast_interval
+    });
+    let target = if broadcast {
+        network_time.last_broadcast = Some(elapsed);
+        NetworkTarget::All
+    } else {
+        NetworkTarget::Only(connected)
+    };
+    if target.is_empty() {
+        return;
+    }
+    trace!(?message, ?target, "Sending server time");
+    let _ = connection_manager
+        .send_message_to_target::<ServerTimingChannel, _>(&message, target)
+        .inspect_err(|e| error!("Could not send server time: {e:?}"));
+}
+
+/// Client system that updates the [`NetworkTime`] using the latest server time message
+/// and the current server time estimate
+pub(crate) fn update_client_network_time(
+    tick_manager: Res<TickManager>,
+    time_manager: Res<TimeManager>,
+    connection_manager: Res<ClientConnectionManager>,
+    mut network_time: ResMut<NetworkTime>,
+    mut events: EventReader<MessageEvent<ServerTimeMessage>>,
+) {
+    for event in events.read() {
+        // the previous anchor is only valid with the tick duration that it was computed with
+        if event.message.tick_duration != network_time.tick_duration {
+            network_time.anchor = None;
+            network_time.tick_duration = event.message.tick_duration;
+        }
+        network_time.receive_anchor(event.message.tick, event.message.unix_time);
+    }
+    let tick_duration = tick_manager.config.tick_duration;
+    if !connection_manager.sync_manager.is_synced() {
+        return;
+    }
+    // the server time estimate is computed from the tick of the latest server packet, so it is
+    // behind the actual server time by the one-way trip time.
+    // It is also advanced by the frame's delta during the sync update, even though the frame's
+    // time had already elapsed when the packets were read, but the packets were received on
+    // average half a frame before they were read, so it is half a frame ahead
+    let server_time = connection_manager.sync_manager.server_time_estimate()
+        + connection_manager.ping_manager.rtt() / 2
+        - time_manager.delta() / 2;
+    network_time.current = Some((
+        server_time.to_tick(tick_duration),
+        server_time.tick_overstep(tick_duration),
+    ));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::client::{ClientConfig, IoConfig, NetConfig, SyncConfig};
+    use crate::prelude::{LinkConditionerConfig, SharedConfig, TickConfig};
+    use crate::tests::stepper::{BevyStepper, Step};
+
+    #[test]
+    fn test_tick_to_unix() {
+        let mut network_time = NetworkTime {
+            tick_duration: Duration::from_millis(10),
+            ..default()
+        };
+        assert_eq!(network_time.tick_to_unix(Tick(0)), None);
+        network_time.receive_anchor(Tick(10), Duration::from_secs(100));
+        assert_eq!(
+            network_time.tick_to_unix(Tick(20)),
+            Some(Duration::from_millis(100_100))
+        );
+        assert_eq!(
+            network_time.tick_to_unix(Tick(5)),
+            Some(Duration::from_millis(99_950))
+        );
+        // ticks wrap around
+        network_time.receive_anchor(Tick(u16::MAX), Duration::from_secs(1000));
+        assert_eq!(
+            network_time.tick_to_unix(Tick(1)),
+            Some(Duration::from_millis(1_000_020))
+        );
+
+        // small corrections are smoothed, big corrections are applied directly
+        network_time.receive_anchor(Tick(u16::MAX), Duration::from_millis(1_000_010));
+        let error = signed_secs(
+            network_time.tick_to_unix(Tick(u16::MAX)).unwrap(),
+            Duration::from_millis(1_000_005),
+        );
+        assert!(error.abs() < 1e-6, "error: {error}");
+        network_time.receive_anchor(Tick(u16::MAX), Duration::from_secs(2000));
+        assert_eq!(
+            network_time.tick_to_unix(Tick(u16::MAX)),
+            Some(Duration::from_secs(2000))
+        );
+    }
+
+    /// With latency and jitter, the client's estimate of the server's UNIX time
+    /// converges to within half a tick of the actual server time
+    #[test]
+    fn test_server_unix_time_accuracy() {
+        let frame_duration = Duration::from_millis(10);
+        let tick_duration = Duration::from_millis(10);
+        let shared_config = SharedConfig {
+            tick: TickConfig::new(tick_duration),
+            ..Default::default()
+        };
+        let client_config = ClientConfig {
+            net: NetConfig::Netcode {
+                auth: Default::default(),
+                config: Default::default(),
+                io: IoConfig::default().with_conditioner(LinkConditionerConfig {
+                    incoming_latency: Duration::from_millis(40),
+                    incoming_jitter: Duration::from_millis(5),
+                    incoming_loss: 0.0,
+                    seed: Some(1),
+                }),
+            },
+            // the network time follows the server time estimate of the sync manager: smooth it over
+            // the jitter of the packets and the frames in which they are read
+            sync: SyncConfig {
+                server_time_estimate_smoothing: 0.9,
+                ..default()
+            },
+            ..default()
+        };
+        let mut stepper = BevyStepper::new(shared_config, client_config, frame_duration);
+        stepper.init();
+        for _ in 0..300 {
+            stepper.frame_step();
+        }
+        let tolerance = tick_duration.as_secs_f64() / 2.0;
+        for _ in 0..200 {
+            stepper.frame_step();
+            let client_time = stepper.client_app.world().resource::<NetworkTime>();
+            let server_time = stepper.server_app.world().resource::<NetworkTime>();
+            assert!(client_time.is_ready());
+            let error = signed_secs(
+                client_time.server_unix_time_now().unwrap(),
+                server_time.server_unix_time_now().unwrap(),
+            );
+            assert!(error.abs() <= tolerance, "error: {error}");
+        }
+
+        let client_time = stepper.client_app.world().resource::<NetworkTime>();
+        let server_time = stepper.server_app.world().resource::<NetworkTime>();
+        let tick = Tick(100);
+        let error = signed_secs(
+            client_time.tick_to_unix(tick).unwrap(),
+            server_time.tick_to_unix(tick).unwrap(),
+        );
+        assert!(error.abs() <= tolerance, "error: {error}");
+    }
+}
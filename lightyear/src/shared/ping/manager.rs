@@ -2,11 +2,12 @@
 use bevy::reflect::Reflect;
 use bevy::time::Stopwatch;
 use bevy::utils::Duration;
-use tracing::{error, trace};
+use tracing::trace;
 
 use crate::shared::ping::message::{Ping, Pong};
 use crate::shared::ping::store::{PingId, PingStore};
 use crate::shared::time_manager::{TimeManager, WrappedTime};
+use crate::shared::warnings::{NetworkWarning, NetworkWarnings};
 use crate::utils::ready_buffer::ReadyBuffer;
 
 /// Config for the ping manager, which sends regular pings to the remote machine in order
@@ -219,15 +220,26 @@ impl PingManager {
     }
 
     /// Received a pong: update
-    /// Returns true if we have enough pongs to finalize the handshake
-    pub(crate) fn process_pong(&mut self, pong: &Pong, current_time: WrappedTime) {
+    /// Returns the round-trip delay measured with this pong, if it is the most recent one
+    pub(crate) fn process_pong(
+        &mut self,
+        pong: &Pong,
+        current_time: WrappedTime,
+        warnings: &mut NetworkWarnings,
+    ) -> Option<Duration> {
         trace!("Received pong: {:?}", pong);
         self.pongs_recv += 1;
         let received_time = current_time;
 
         let Some(ping_sent_time) = self.ping_store.remove(pong.ping_id) else {
-            error!("Received a ping that is not present in the ping-store anymore");
-            return;
+            warnings.report(
+                NetworkWarning::StalePong,
+                format_args!(
+                    "Received a pong for the ping {:?} that is not present in the ping-store anymore",
+                    pong.ping_id
+                ),
+            );
+            return None;
         };
 
         // only update values for the most recent pongs received
@@ -249,7 +261,9 @@ impl PingManager {
 
             // recompute stats whenever we get a new pong
             self.compute_stats();
+            return Some(round_trip_delay);
         }
+        None
     }
 
     /// When we receive a Ping, we prepare a Pong in response.
@@ -276,6 +290,19 @@ impl PingManager {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::shared::clock::{MockClock, NetworkClock};
+
+    /// Advance the mock clock and update the managers like the `TimePlugin` would
+    fn step(
+        clock: &MockClock,
+        time_manager: &mut TimeManager,
+        ping_manager: &mut PingManager,
+        delta: Duration,
+    ) {
+        clock.advance(delta);
+        time_manager.update(delta);
+        ping_manager.update(time_manager);
+    }
 
     #[test]
     fn test_send_pings() {
@@ -283,14 +310,19 @@ mod tests {
             ping_interval: Duration::from_millis(100),
             stats_buffer_duration: Duration::from_secs(4),
         };
+        let clock = MockClock::new();
         let mut ping_manager = PingManager::new(config);
-        let mut time_manager = TimeManager::default();
+        let mut time_manager = TimeManager::default().with_clock(NetworkClock::new(clock.clone()));
+        let mut warnings = NetworkWarnings::default().with_clock(NetworkClock::new(clock.clone()));
 
         assert_eq!(ping_manager.maybe_prepare_ping(&time_manager), None);
 
-        let delta = Duration::from_millis(100);
-        time_manager.update(delta);
-        ping_manager.update(&time_manager);
+        step(
+            &clock,
+            &mut time_manager,
+            &mut ping_manager,
+            Duration::from_millis(100),
+        );
 
         // send pings
         assert_eq!(
@@ -298,21 +330,22 @@ mod tests {
             Some(Ping { id: PingId(0) })
         );
         let delta = Duration::from_millis(60);
-        time_manager.update(delta);
-        ping_manager.update(&time_manager);
+        step(&clock, &mut time_manager, &mut ping_manager, delta);
 
         // ping timer hasn't gone off yet, send nothing
         assert_eq!(ping_manager.maybe_prepare_ping(&time_manager), None);
-        time_manager.update(delta);
-        ping_manager.update(&time_manager);
+        step(&clock, &mut time_manager, &mut ping_manager, delta);
         assert_eq!(
             ping_manager.maybe_prepare_ping(&time_manager),
             Some(Ping { id: PingId(1) })
         );
 
-        let delta = Duration::from_millis(100);
-        time_manager.update(delta);
-        ping_manager.update(&time_manager);
+        step(
+            &clock,
+            &mut time_manager,
+            &mut ping_manager,
+            Duration::from_millis(100),
+        );
         assert_eq!(
             ping_manager.maybe_prepare_ping(&time_manager),
             Some(Ping { id: PingId(2) })
@@ -320,6 +353,12 @@ mod tests {
 
         // we sent all the pings we need
         assert_eq!(ping_manager.maybe_prepare_ping(&time_manager), None);
+        // the real time within the frame follows the clock
+        clock.advance(Duration::from_millis(5));
+        assert_eq!(
+            time_manager.real_time_since_frame_start(),
+            Duration::from_millis(5)
+        );
 
         // check ping store
         assert_eq!(
@@ -336,7 +375,43 @@ mod tests {
         );
 
         // receive pongs
-        // TODO
+        let pong = Pong {
+            ping_id: PingId(0),
+            ping_received_time: WrappedTime::new(150),
+            pong_sent_time: WrappedTime::new(160),
+        };
+        assert_eq!(
+            ping_manager.process_pong(&pong, time_manager.current_time(), &mut warnings),
+            None
+        );
+
+        step(
+            &clock,
+            &mut time_manager,
+            &mut ping_manager,
+            Duration::from_millis(100),
+        );
+        assert_eq!(
+            ping_manager.maybe_prepare_ping(&time_manager),
+            Some(Ping { id: PingId(3) })
+        );
+        step(
+            &clock,
+            &mut time_manager,
+            &mut ping_manager,
+            Duration::from_millis(30),
+        );
+        let pong = Pong {
+            ping_id: PingId(3),
+            ping_received_time: WrappedTime::new(430),
+            pong_sent_time: WrappedTime::new(440),
+        };
+        // the server took 10ms to answer
+        assert_eq!(
+            ping_manager.process_pong(&pong, time_manager.current_time(), &mut warnings),
+            Some(Duration::from_millis(20))
+        );
+        assert_eq!(ping_manager.pongs_recv, 2);
     }
 
     // #[test]
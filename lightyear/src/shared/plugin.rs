@@ -7,12 +7,22 @@ use bevy::utils::Duration;
 
 use crate::prelude::client::ComponentSyncMode;
 use crate::prelude::{
-    AppComponentExt, ChannelDirection, ChannelRegistry, ComponentRegistry, LinkConditionerConfig,
-    MessageRegistry, Mode, ParentSync, PingConfig, PrePredicted, PreSpawnedPlayerObject,
+    AppComponentExt, AppMessageExt, ChannelDirection, ChannelRegistry, ComponentRegistry, LinkConditionerConfig,
+    LocalPlayerId, MessageRegistry, Mode, ParentSync, PingConfig, PrePredicted, PreSpawnedPlayerObject,
     ShouldBePredicted, TickConfig,
 };
+use crate::server::relevance::hint::{InterestRequestMessage, InterestResponseMessage};
+use crate::shared::action::ActionResolutionMessage;
 use crate::shared::config::SharedConfig;
+use crate::shared::config_check::{add_shared_checks, run_config_checks};
+use crate::shared::hooks::JoinSnapshotMessage;
+use crate::shared::input::jitter::InputDelayAdvice;
+use crate::shared::network_time::{NetworkTime, NetworkTimeConfig, ServerTimeMessage};
 use crate::shared::replication::components::{Controlled, ShouldBeInterpolated};
+use crate::shared::replication::handoff::ReplicatedHandoff;
+use crate::shared::replication::lifetime::ReplicatedLifetime;
+use crate::shared::replication::prespawn_ids::PreSpawnIdRangeMessage;
+use crate::shared::schema::SchemaVersionsMessage;
 use crate::shared::tick_manager::TickManagerPlugin;
 use crate::shared::time_manager::TimePlugin;
 use crate::transport::io::{IoState, IoStats};
@@ -99,10 +109,12 @@ impl Plugin for SharedPlugin {
             .register_type::<SharedConfig>()
             .register_type::<TickConfig>()
             .register_type::<PingConfig>()
+            .register_type::<NetworkTimeConfig>()
             .register_type::<IoStats>()
             .register_type::<IoState>()
             .register_type::<LinkConditionerConfig>()
-            .register_type::<CompressionConfig>();
+            .register_type::<CompressionConfig>()
+            .register_type::<LocalPlayerId>();
 
         // RESOURCES
         // the SharedPlugin is called after the ClientConfig is inserted
@@ -117,6 +129,7 @@ impl Plugin for SharedPlugin {
         app.insert_resource(ChannelRegistry::new(input_send_interval));
         app.insert_resource(ComponentRegistry::default());
         app.insert_resource(MessageRegistry::default());
+        app.init_resource::<NetworkTime>();
         // NOTE: this tick duration must be the same as any previous existing fixed timesteps
         app.insert_resource(Time::<Fixed>::from_seconds(
             self.config.tick.tick_duration.as_secs_f64(),
@@ -128,6 +141,9 @@ impl Plugin for SharedPlugin {
             config: self.config.tick,
         });
         app.add_plugins(TimePlugin);
+
+        // CONFIG CHECKS
+        add_shared_checks(app);
     }
 
     fn finish(&self, app: &mut App) {
@@ -146,7 +162,32 @@ impl Plugin for SharedPlugin {
         app.register_component::<Controlled>(ChannelDirection::ServerToClient)
             .add_prediction(ComponentSyncMode::Once)
             .add_interpolation(ComponentSyncMode::Once);
+        app.register_component::<LocalPlayerId>(ChannelDirection::ServerToClient)
+            .add_prediction(ComponentSyncMode::Once)
+            .add_interpolation(ComponentSyncMode::Once);
+        app.register_component::<ReplicatedLifetime>(ChannelDirection::ServerToClient)
+            .add_prediction(ComponentSyncMode::Simple)
+            .add_interpolation(ComponentSyncMode::Simple);
+        app.register_component::<ReplicatedHandoff>(ChannelDirection::ServerToClient)
+            .add_map_entities();
+        app.register_message::<ServerTimeMessage>(ChannelDirection::ServerToClient);
+        app.register_message::<ActionResolutionMessage>(ChannelDirection::ServerToClient);
+        app.register_message::<InterestRequestMessage>(ChannelDirection::ClientToServer);
+        app.register_message::<InterestResponseMessage>(ChannelDirection::ServerToClient);
+        app.register_message::<JoinSnapshotMessage>(ChannelDirection::ServerToClient);
+        app.register_message::<PreSpawnIdRangeMessage>(ChannelDirection::ServerToClient);
+        app.register_message::<InputDelayAdvice>(ChannelDirection::ServerToClient);
+        app.register_message::<SchemaVersionsMessage>(ChannelDirection::Bidirectional);
+        let mut message_registry = app.world_mut().resource_mut::<MessageRegistry>();
+        message_registry.add_message_group_header();
+        message_registry.add_timeline_message_header();
         // check that the protocol was built correctly
         app.world().resource::<ComponentRegistry>().check();
     }
+
+    // This runs after all plugins have run build() and finish(), so that the protocol is complete
+    // and every plugin had the chance to register its config checks
+    fn cleanup(&self, app: &mut App) {
+        run_config_checks(app);
+    }
 }
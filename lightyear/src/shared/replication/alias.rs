@@ -0,0 +1,588 @@
+//! Compression of the entity ids in replication updates.
+//!
+//! Most of the replication updates are about a small set of entities that change every tick (players,
+//! projectiles, etc.), and each of those updates starts with the full id of the entity (2 to 10 bytes).
+//!
+//! When [`ReplicationConfig::entity_aliases`](crate::prelude::ReplicationConfig) is set, the sender assigns
+//! 1-byte aliases to the entities that are updated frequently, and uses the alias instead of the full id
+//! in the [`EntityUpdatesMessage`](super::EntityUpdatesMessage)s:
+//! - an alias is assigned with a reliable control message on the [`EntityAliasChannel`](crate::channel::builder::EntityAliasChannel),
+//!   and is only used once that message has been acked, so the receiver always knows the aliases it reads.
+//!   Until then, the full entity id is used.
+//! - when all the aliases are in use, the least recently used one is invalidated with another control message,
+//!   and can be re-assigned once the invalidation has been acked. The aliases of despawned entities are also invalidated.
+//! - each control message contains the tick of the sender: the receiver only resolves an alias for the update
+//!   messages that were sent while the alias was valid, so an update that arrives late can never be attributed to
+//!   the wrong entity. In the rare case where an update arrives after its alias was re-assigned, it is dropped.
+//!
+//! The bytes saved are reported by the [`ReplicationDiagnosticsPlugin`](super::diagnostics::ReplicationDiagnosticsPlugin).
+use bevy::ecs::entity::EntityHash;
+use bevy::prelude::Entity;
+use bevy::utils::{hashbrown, HashMap};
+use byteorder::{ReadBytesExt, WriteBytesExt};
+use bytes::Bytes;
+use crossbeam_channel::Receiver;
+use tracing::{debug, trace};
+
+use crate::packet::message::MessageId;
+use crate::prelude::Tick;
+use crate::serialize::reader::Reader;
+use crate::serialize::{SerializationError, ToBytes};
+
+type EntityHashMap<K, V> = hashbrown::HashMap<K, V, EntityHash>;
+
+/// Number of update messages that must contain an entity before we assign it an alias
+const MIN_UPDATES_BEFORE_ALIAS: u8 = 4;
+
+/// Number of ticks after which the tick bounds of an alias are discarded, to handle tick wrapping
+const TICK_BOUND_TIMEOUT: i16 = (u16::MAX / 4) as i16;
+
+/// Short identifier used instead of the full entity id in replication updates
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) struct EntityAlias(pub(crate) u8);
+
+impl ToBytes for EntityAlias {
+    fn len(&self) -> usize {
+        1
+    }
+
+    fn to_bytes<T: WriteBytesExt>(&self, buffer: &mut T) -> Result<(), SerializationError> {
+        buffer.write_u8(self.0)?;
+        Ok(())
+    }
+
+    fn from_bytes(buffer: &mut Reader) -> Result<Self, SerializationError>
+    where
+        Self: Sized,
+    {
+        Ok(Self(buffer.read_u8()?))
+    }
+}
+
+/// Control message that establishes or invalidates an alias on the receiver
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum AliasMessage {
+    /// The alias refers to `entity` in the update messages sent after `tick`
+    Assign {
+        alias: EntityAlias,
+        entity: Entity,
+        tick: Tick,
+    },
+    /// The alias is not used anymore in the update messages sent after `tick`
+    Invalidate { alias: EntityAlias, tick: Tick },
+}
+
+impl ToBytes for AliasMessage {
+    fn len(&self) -> usize {
+        match self {
+            AliasMessage::Assign {
+                alias,
+                entity,
+                tick,
+            } => 1 + alias.len() + entity.len() + tick.len(),
+            AliasMessage::Invalidate { alias, tick } => 1 + alias.len() + tick.len(),
+        }
+    }
+
+    fn to_bytes<T: WriteBytesExt>(&self, buffer: &mut T) -> Result<(), SerializationError> {
+        match self {
+            AliasMessage::Assign {
+                alias,
+                entity,
+                tick,
+            } => {
+                buffer.write_u8(0)?;
+                alias.to_bytes(buffer)?;
+                entity.to_bytes(buffer)?;
+                tick.to_bytes(buffer)?;
+            }
+            AliasMessage::Invalidate { alias, tick } => {
+                buffer.write_u8(1)?;
+                alias.to_bytes(buffer)?;
+                tick.to_bytes(buffer)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn from_bytes(buffer: &mut Reader) -> Result<Self, SerializationError>
+    where
+        Self: Sized,
+    {
+        match buffer.read_u8()? {
+            0 => Ok(AliasMessage::Assign {
+                alias: EntityAlias::from_bytes(buffer)?,
+                entity: Entity::from_bytes(buffer)?,
+                tick: Tick::from_bytes(buffer)?,
+            }),
+            1 => Ok(AliasMessage::Invalidate {
+                alias: EntityAlias::from_bytes(buffer)?,
+                tick: Tick::from_bytes(buffer)?,
+            }),
+            _ => Err(SerializationError::InvalidPacketType),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum SlotState {
+    /// The assignment was sent but not acked yet
+    Assigning,
+    /// The receiver knows the alias: it can be used in the update messages sent after `since`
+    Established { since: Option<Tick> },
+    /// The invalidation was sent but not acked yet
+    Invalidating,
+}
+
+#[derive(Debug)]
+struct AliasSlot {
+    entity: Entity,
+    state: SlotState,
+    /// Value of `EntityAliasSender::clock` the last time the alias was used
+    last_used: u64,
+}
+
+/// Assigns aliases to the entities that are frequently updated, for a single remote
+#[derive(Debug)]
+pub(crate) struct EntityAliasSender {
+    slots: Vec<Option<AliasSlot>>,
+    aliases: EntityHashMap<Entity, EntityAlias>,
+    /// Number of update messages that contained each entity that doesn't have an alias yet
+    candidates: EntityHashMap<Entity, u8>,
+    /// Entities that were despawned since the last update, whose alias must be invalidated
+    despawned: Vec<Entity>,
+    /// Control messages that must be sent to the remote
+    pending_messages: Vec<AliasMessage>,
+    /// Alias concerned by each control message that is waiting for an ack
+    unacked: HashMap<MessageId, EntityAlias>,
+    /// Get notified when a control message has been received by the remote
+    ack_receiver: Receiver<MessageId>,
+    /// Incremented every time we prepare the update messages, used to find the least recently used alias
+    clock: u64,
+    /// Number of bytes saved by using aliases instead of full entity ids, since the stats were last flushed
+    pub(crate) saved_bytes: usize,
+}
+
+impl EntityAliasSender {
+    pub(crate) fn new(capacity: u8, ack_receiver: Receiver<MessageId>) -> Self {
+        Self {
+            slots: (0..capacity).map(|_| None).collect(),
+            aliases: EntityHashMap::default(),
+            candidates: EntityHashMap::default(),
+            despawned: Vec::new(),
+            pending_messages: Vec::new(),
+            unacked: HashMap::default(),
+            ack_receiver,
+            clock: 0,
+            saved_bytes: 0,
+        }
+    }
+
+    pub(crate) fn is_enabled(&self) -> bool {
+        !self.slots.is_empty()
+    }
+
+    /// Handle the acks of the control messages, and invalidate the aliases of the despawned entities
+    pub(crate) fn update(&mut self, tick: Tick) {
+        self.clock += 1;
+        self.receive_acks(tick);
+        for entity in std::mem::take(&mut self.despawned) {
+            let Some(alias) = self.aliases.get(&entity).copied() else {
+                continue;
+            };
+            match self.slots[alias.0 as usize]
+                .as_ref()
+                .expect("the aliases map only contains aliases with an existing slot")
+                .state
+            {
+                SlotState::Established { .. } => self.invalidate(alias, tick),
+                // the alias will be invalidated when the assignment is acked
+                _ => {
+                    self.aliases.remove(&entity);
+                }
+            }
+        }
+    }
+
+    fn receive_acks(&mut self, tick: Tick) {
+        while let Ok(message_id) = self.ack_receiver.try_recv() {
+            let Some(alias) = self.unacked.remove(&message_id) else {
+                continue;
+            };
+            let index = alias.0 as usize;
+            let Some(slot) = self.slots[index].as_mut() else {
+                continue;
+            };
+            match slot.state {
+                SlotState::Assigning => {
+                    trace!(?alias, entity = ?slot.entity, "Entity alias established");
+                    slot.state = SlotState::Established { since: Some(tick) };
+                    // the entity was despawned while the alias was being assigned
+                    if self.aliases.get(&slot.entity) != Some(&alias) {
+                        slot.state = SlotState::Invalidating;
+                        self.pending_messages
+                            .push(AliasMessage::Invalidate { alias, tick });
+                    }
+                }
+                SlotState::Invalidating => {
+                    trace!(?alias, entity = ?slot.entity, "Entity alias invalidated");
+                    self.slots[index] = None;
+                }
+                SlotState::Established { .. } => {}
+            }
+        }
+    }
+
+    /// Take the control messages that must be sent
+    pub(crate) fn drain_messages(&mut self) -> impl Iterator<Item = AliasMessage> + '_ {
+        self.pending_messages.drain(..)
+    }
+
+    /// Keep track of the message id of a buffered control message, to get notified when it is acked
+    pub(crate) fn track_message(&mut self, message: &AliasMessage, message_id: MessageId) {
+        let alias = match message {
+            AliasMessage::Assign { alias, .. } => *alias,
+            AliasMessage::Invalidate { alias, .. } => *alias,
+        };
+        self.unacked.insert(message_id, alias);
+    }
+
+    /// Move the updates of the entities that have an established alias out of `updates`.
+    ///
+    /// Also assigns aliases to the entities that are updated frequently; the new aliases will only be used
+    /// once the receiver has acked them.
+    pub(crate) fn alias_updates(
+        &mut self,
+        updates: &mut EntityHashMap<Entity, Vec<Bytes>>,
+        tick: Tick,
+    ) -> Vec<(EntityAlias, Vec<Bytes>)> {
+        let mut aliased = Vec::new();
+        if !self.is_enabled() {
+            return aliased;
+        }
+        let entities: Vec<Entity> = updates.keys().copied().collect();
+        for entity in entities {
+            if let Some(alias) = self.aliases.get(&entity).copied() {
+                let slot = self.slots[alias.0 as usize]
+                    .as_mut()
+                    .expect("the aliases map only contains aliases with an existing slot");
+                if let SlotState::Established { since } = slot.state {
+                    // the alias is only valid for messages sent strictly after the tick where it was established
+                    if since.map_or(true, |since| tick > since) {
+                        slot.last_used = self.clock;
+                        self.saved_bytes += entity.len() - alias.len();
+                        aliased.push((alias, updates.remove(&entity).unwrap()));
+                    }
+                }
+                continue;
+            }
+            let count = self.candidates.entry(entity).or_default();
+            *count = count.saturating_add(1);
+            if *count >= MIN_UPDATES_BEFORE_ALIAS {
+                self.assign(entity, tick);
+            }
+        }
+        aliased
+    }
+
+    /// Try to assign an alias to the entity. If all aliases are used, invalidate the least recently used one
+    /// (it will be available once the invalidation is acked)
+    fn assign(&mut self, entity: Entity, tick: Tick) {
+        if let Some(index) = self.slots.iter().position(Option::is_none) {
+            let alias = EntityAlias(index as u8);
+            debug!(?alias, ?entity, "Assigning entity alias");
+            self.slots[index] = Some(AliasSlot {
+                entity,
+                state: SlotState::Assigning,
+                last_used: self.clock,
+            });
+            self.aliases.insert(entity, alias);
+            self.candidates.remove(&entity);
+            self.pending_messages.push(AliasMessage::Assign {
+                alias,
+                entity,
+                tick,
+            });
+            return;
+        }
+        // only recycle one alias at a time, to avoid invalidating all the aliases when the set of
+        // frequently updated entities is larger than the number of aliases
+        if self
+            .slots
+            .iter()
+            .flatten()
+            .any(|slot| slot.state == SlotState::Invalidating)
+        {
+            return;
+        }
+        let lru = self
+            .slots
+            .iter()
+            .enumerate()
+            .filter_map(|(index, slot)| slot.as_ref().map(|slot| (index, slot)))
+            .filter(|(_, slot)| {
+                matches!(slot.state, SlotState::Established { .. }) && slot.last_used < self.clock
+            })
+            .min_by_key(|(_, slot)| slot.last_used)
+            .map(|(index, _)| index);
+        if let Some(index) = lru {
+            self.invalidate(EntityAlias(index as u8), tick);
+        }
+    }
+
+    fn invalidate(&mut self, alias: EntityAlias, tick: Tick) {
+        let slot = self.slots[alias.0 as usize]
+            .as_mut()
+            .expect("only existing slots are invalidated");
+        debug!(?alias, entity = ?slot.entity, "Invalidating entity alias");
+        if self.aliases.get(&slot.entity) == Some(&alias) {
+            self.aliases.remove(&slot.entity);
+        }
+        slot.state = SlotState::Invalidating;
+        self.pending_messages
+            .push(AliasMessage::Invalidate { alias, tick });
+    }
+
+    /// The entity was despawned: its alias will be recycled
+    pub(crate) fn remove_entity(&mut self, entity: Entity) {
+        self.candidates.remove(&entity);
+        if self.aliases.contains_key(&entity) {
+            self.despawned.push(entity);
+        }
+    }
+
+    /// Handle tick wrapping
+    pub(crate) fn cleanup(&mut self, tick: Tick) {
+        for slot in self.slots.iter_mut().flatten() {
+            if let SlotState::Established { since: Some(since) } = slot.state {
+                if tick - since > TICK_BOUND_TIMEOUT {
+                    slot.state = SlotState::Established { since: None };
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct ReceivedAlias {
+    entity: Entity,
+    /// The alias is valid for the messages sent strictly after this tick
+    valid_from: Option<Tick>,
+    /// The alias is valid for the messages sent at or before this tick
+    valid_until: Option<Tick>,
+}
+
+/// Resolves the aliases used by a single remote
+#[derive(Debug, Default)]
+pub(crate) struct EntityAliasReceiver {
+    aliases: HashMap<EntityAlias, ReceivedAlias>,
+}
+
+impl EntityAliasReceiver {
+    pub(crate) fn receive(&mut self, message: AliasMessage) {
+        trace!(?message, "Received entity alias message");
+        match message {
+            AliasMessage::Assign {
+                alias,
+                entity,
+                tick,
+            } => {
+                self.aliases.insert(
+                    alias,
+                    ReceivedAlias {
+                        entity,
+                        valid_from: Some(tick),
+                        valid_until: None,
+                    },
+                );
+            }
+            AliasMessage::Invalidate { alias, tick } => {
+                if let Some(received) = self.aliases.get_mut(&alias) {
+                    received.valid_until = Some(tick);
+                }
+            }
+        }
+    }
+
+    /// Returns the entity that the alias referred to when the remote sent a message at `remote_tick`
+    pub(crate) fn resolve(&self, alias: EntityAlias, remote_tick: Tick) -> Option<Entity> {
+        self.aliases
+            .get(&alias)
+            .filter(|received| {
+                received.valid_from.map_or(true, |from| remote_tick > from)
+                    && received
+                        .valid_until
+                        .map_or(true, |until| remote_tick <= until)
+            })
+            .map(|received| received.entity)
+    }
+
+    /// Handle tick wrapping
+    pub(crate) fn cleanup(&mut self, tick: Tick) {
+        self.aliases.retain(|_, received| {
+            !received
+                .valid_until
+                .is_some_and(|until| tick - until > TICK_BOUND_TIMEOUT)
+        });
+        for received in self.aliases.values_mut() {
+            if received
+                .valid_from
+                .is_some_and(|from| tick - from > TICK_BOUND_TIMEOUT)
+            {
+                received.valid_from = None;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    use crate::shared::replication::components::ReplicationGroupId;
+    use crate::shared::replication::{EntityUpdatesMessage, SendEntityUpdatesMessage};
+    use crate::shared::warnings::NetworkWarnings;
+
+    use super::*;
+
+    #[test]
+    fn test_alias_message_serialization() {
+        let messages = [
+            AliasMessage::Assign {
+                alias: EntityAlias(3),
+                entity: Entity::from_raw(1000),
+                tick: Tick(65000),
+            },
+            AliasMessage::Invalidate {
+                alias: EntityAlias(255),
+                tick: Tick(2),
+            },
+        ];
+        for message in messages {
+            let mut writer = vec![];
+            message.to_bytes(&mut writer).unwrap();
+            assert_eq!(writer.len(), message.len());
+            let mut reader = Reader::from(writer);
+            assert_eq!(AliasMessage::from_bytes(&mut reader).unwrap(), message);
+        }
+    }
+
+    enum Event {
+        Updates { send_tick: Tick, bytes: Vec<u8> },
+        Control(AliasMessage, MessageId),
+        Ack(MessageId),
+    }
+
+    /// Simulate a lossy link where the update messages are often lost, delayed and reordered,
+    /// and the control messages and their acks arrive late.
+    /// Every update payload contains the id of the entity it was written for: no update
+    /// must ever be applied to another entity.
+    #[test]
+    fn test_aliases_with_loss() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let (ack_sender, ack_receiver) = crossbeam_channel::unbounded();
+        // fewer aliases than hot entities, so that they keep getting recycled
+        let mut sender = EntityAliasSender::new(2, ack_receiver);
+        let mut receiver = EntityAliasReceiver::default();
+        let mut warnings = NetworkWarnings::default();
+
+        let mut entities: Vec<Entity> = (0..5).map(|i| Entity::from_raw(1000 + i)).collect();
+        let mut next_message_id = MessageId(0);
+        let mut in_flight: Vec<(u16, Event)> = vec![];
+        let mut aliased_sent = 0;
+        let mut aliased_resolved = 0;
+
+        for t in 1..2000u16 {
+            let tick = Tick(t);
+            if t == 1000 {
+                // despawn an entity that has an alias, and spawn a new one
+                let despawned = entities.remove(0);
+                sender.remove_entity(despawned);
+                entities.push(Entity::from_raw(2000));
+            }
+
+            // send
+            sender.update(tick);
+            let mut updates = EntityHashMap::default();
+            for entity in &entities {
+                if rng.gen_bool(0.8) {
+                    let payload = Bytes::from(entity.to_bits().to_le_bytes().to_vec());
+                    updates.insert(*entity, vec![payload]);
+                }
+            }
+            let aliased_updates = sender.alias_updates(&mut updates, tick);
+            aliased_sent += aliased_updates.len();
+            let message = SendEntityUpdatesMessage {
+                group_id: ReplicationGroupId(0),
+                last_action_tick: None,
+                updates,
+                aliased_updates,
+            };
+            let mut bytes = vec![];
+            message.to_bytes(&mut bytes).unwrap();
+            if !rng.gen_bool(0.3) {
+                let delay = rng.gen_range(0..15);
+                in_flight.push((
+                    t + delay,
+                    Event::Updates {
+                        send_tick: tick,
+                        bytes,
+                    },
+                ));
+            }
+            let control_messages: Vec<_> = sender.drain_messages().collect();
+            for control in control_messages {
+                sender.track_message(&control, next_message_id);
+                // reliable: always delivered, but possibly after several retransmissions
+                let delay = rng.gen_range(1..40);
+                in_flight.push((t + delay, Event::Control(control, next_message_id)));
+                next_message_id += 1;
+            }
+
+            // receive
+            let (mut arrived, pending): (Vec<_>, Vec<_>) =
+                in_flight.drain(..).partition(|(at, _)| *at <= t);
+            in_flight = pending;
+            // shuffle the events that arrive at the same time
+            for i in (1..arrived.len()).rev() {
+                arrived.swap(i, rng.gen_range(0..=i));
+            }
+            for (_, event) in arrived {
+                match event {
+                    Event::Updates { send_tick, bytes } => {
+                        let full_ids =
+                            SendEntityUpdatesMessage::from_bytes(&mut Reader::from(bytes.clone()))
+                                .unwrap()
+                                .updates
+                                .len();
+                        let message = EntityUpdatesMessage::from_bytes_with_aliases(
+                            &mut Reader::from(bytes),
+                            &receiver,
+                            send_tick,
+                            &mut warnings,
+                        )
+                        .unwrap();
+                        aliased_resolved += message.updates.len() - full_ids;
+                        for (entity, payloads) in message.updates {
+                            let expected = Bytes::from(entity.to_bits().to_le_bytes().to_vec());
+                            assert_eq!(payloads, vec![expected], "update attributed to {entity:?}");
+                        }
+                    }
+                    Event::Control(control, message_id) => {
+                        receiver.receive(control);
+                        let delay = rng.gen_range(1..10);
+                        in_flight.push((t + delay, Event::Ack(message_id)));
+                    }
+                    Event::Ack(message_id) => {
+                        ack_sender.send(message_id).unwrap();
+                    }
+                }
+            }
+        }
+        // the aliases were actually used and resolved
+        assert!(aliased_sent > 0);
+        assert!(aliased_resolved > 0);
+        assert!(sender.saved_bytes > 0);
+    }
+}
@@ -4,6 +4,8 @@ use std::mem;
 use crate::prelude::{ChannelDirection, ComponentRegistry, Replicating};
 use crate::protocol::component::ComponentKind;
 use crate::shared::plugin::Identity;
+use crate::shared::replication::frequency::ReplicationMode;
+use crate::shared::replication::keyframe::KeyframeConfig;
 use bevy::ecs::archetype::ArchetypeEntity;
 use bevy::ecs::component::{ComponentTicks, StorageType};
 use bevy::ecs::storage::{SparseSets, Table};
@@ -67,6 +69,8 @@ pub(crate) struct ReplicatedArchetype {
 pub(crate) struct ReplicatedComponent {
     pub(crate) delta_compression: bool,
     pub(crate) replicate_once: bool,
+    pub(crate) keyframe: Option<KeyframeConfig>,
+    pub(crate) mode: ReplicationMode,
     pub(crate) override_target: Option<ComponentId>,
     pub(crate) id: ComponentId,
     pub(crate) kind: ComponentKind,
@@ -150,9 +154,10 @@ impl<C: Component> ReplicatedArchetypes<C> {
                     let delta_compression = archetype
                         .components()
                         .any(|c| c == replication_metadata.delta_compression_id);
-                    let replicate_once = archetype
-                        .components()
-                        .any(|c| c == replication_metadata.replicate_once_id);
+                    let replicate_once = replication_metadata.mode == ReplicationMode::Once
+                        || archetype
+                            .components()
+                            .any(|c| c == replication_metadata.replicate_once_id);
                     let override_target = archetype
                         .components()
                         .any(|c| c == replication_metadata.override_target_id)
@@ -167,6 +172,8 @@ impl<C: Component> ReplicatedArchetypes<C> {
                     replicated_archetype.components.push(ReplicatedComponent {
                         delta_compression,
                         replicate_once,
+                        keyframe: replication_metadata.keyframe,
+                        mode: replication_metadata.mode,
                         override_target,
                         id: component,
                         kind,
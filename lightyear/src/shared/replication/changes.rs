@@ -0,0 +1,267 @@
+/*! Type-erased stream of the component changes applied or sent by the replication
+
+The typed events ([`ComponentInsertEvent`](crate::client::events::ComponentInsertEvent), ...) need one reader per
+component type and lose the order of the changes. Tools that observe the whole replication (inspectors, recorders,
+editors) can instead add the [`ReplicationChangesPlugin`], which emits a [`ReplicationChange`] for every component
+change, in the order in which the changes happened:
+- on the client, every insert, update and removal applied by the replication received from the server
+  (`ReplicationChange`)
+- on the server, every insert, update and removal sent to a client (`ReplicationChange<ClientId>`, whose context is
+  the client that the change was sent to). An update that is sent again because it was not acked yet is emitted again.
+
+The plugin must be added after the `ClientPlugins` or the `ServerPlugins`. The changes are only recorded
+while the plugin is added, and only the component kinds selected by the [`ReplicationChangeFilter`] are emitted:
+```rust,ignore
+app.add_plugins(ReplicationChangesPlugin::default());
+app.world_mut()
+    .resource_mut::<ReplicationChangeFilter>()
+    .subscribe::<Position>();
+
+fn inspect(registry: Res<ComponentRegistry>, mut changes: EventReader<ReplicationChange>) {
+    for change in changes.read() {
+        info!("{:?} {} {:?} at {:?}", change.entity, registry.name(change.kind), change.change, change.tick);
+    }
+}
+```
+*/
+use bevy::prelude::*;
+use bevy::utils::HashSet;
+use bytes::Bytes;
+
+use crate::client::config::ClientConfig;
+use crate::prelude::{ClientId, ComponentRegistry, Tick};
+use crate::protocol::component::{ComponentKind, ComponentNetId};
+use crate::serialize::reader::Reader;
+use crate::serialize::ToBytes;
+use crate::server::config::ServerConfig;
+use crate::shared::sets::{ClientMarker, InternalMainSet, InternalReplicationSet, ServerMarker};
+
+/// The type of a [`ReplicationChange`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Reflect)]
+pub enum ReplicationChangeType {
+    Inserted,
+    Updated,
+    Removed,
+}
+
+/// A component change applied to (on the client) or sent for (on the server) a replicated entity
+#[derive(Event, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReplicationChange<Ctx = ()> {
+    /// The local entity whose component changed
+    pub entity: Entity,
+    /// The kind of the component, whose name is given by [`ComponentRegistry::name`]
+    pub kind: ComponentKind,
+    pub change: ReplicationChangeType,
+    /// The server tick of the change
+    pub tick: Tick,
+    /// `()` on the client, the client the change was sent to on the server
+    pub context: Ctx,
+}
+
+impl<Ctx> ReplicationChange<Ctx> {
+    /// The name of the type of the component
+    pub fn type_name(&self, component_registry: &ComponentRegistry) -> &'static str {
+        component_registry.name(self.kind)
+    }
+}
+
+/// The component kinds whose [`ReplicationChange`]s are emitted. All the kinds are emitted by default.
+#[derive(Resource, Debug, Clone, Default)]
+pub struct ReplicationChangeFilter {
+    /// `None` if all the kinds are emitted
+    kinds: Option<HashSet<ComponentKind>>,
+}
+
+impl ReplicationChangeFilter {
+    /// Emit the changes of all the component kinds
+    pub fn all() -> Self {
+        Self { kinds: None }
+    }
+
+    /// Emit the changes of no component kind, until some kinds are subscribed to
+    pub fn none() -> Self {
+        Self {
+            kinds: Some(HashSet::default()),
+        }
+    }
+
+    /// Emit the changes of the component `C`.
+    ///
+    /// If all the kinds were emitted, only the subscribed kinds are emitted from now on.
+    pub fn subscribe<C: Component>(&mut self) -> &mut Self {
+        self.kinds
+            .get_or_insert_with(HashSet::default)
+            .insert(ComponentKind::of::<C>());
+        self
+    }
+
+    /// Stop emitting the changes of the component `C`
+    pub fn unsubscribe<C: Component>(&mut self) -> &mut Self {
+        if let Some(kinds) = &mut self.kinds {
+            kinds.remove(&ComponentKind::of::<C>());
+        }
+        self
+    }
+
+    pub fn contains(&self, kind: ComponentKind) -> bool {
+        self.kinds
+            .as_ref()
+            .map_or(true, |kinds| kinds.contains(&kind))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct RecordedChange {
+    pub(crate) entity: Entity,
+    pub(crate) net_id: ComponentNetId,
+    pub(crate) change: ReplicationChangeType,
+    pub(crate) tick: Tick,
+}
+
+/// Ordered changes recorded by a connection, while the [`ReplicationChangesPlugin`] is added
+#[derive(Debug, Default)]
+pub(crate) struct ReplicationChanges {
+    pub(crate) enabled: bool,
+    changes: Vec<RecordedChange>,
+}
+
+impl ReplicationChanges {
+    pub(crate) fn record(
+        &mut self,
+        entity: Entity,
+        net_id: ComponentNetId,
+        change: ReplicationChangeType,
+        tick: Tick,
+    ) {
+        if self.enabled {
+            self.changes.push(RecordedChange {
+                entity,
+                net_id,
+                change,
+                tick,
+            });
+        }
+    }
+
+    /// Record the change of the serialized component, which starts with its net id
+    pub(crate) fn record_payload(
+        &mut self,
+        entity: Entity,
+        payload: &Bytes,
+        change: ReplicationChangeType,
+        tick: Tick,
+    ) {
+        if !self.enabled {
+            return;
+        }
+        if let Ok(net_id) = ComponentNetId::from_bytes(&mut Reader::from(payload.clone())) {
+            self.record(entity, net_id, change, tick);
+        }
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.changes.clear();
+    }
+
+    /// Convert the recorded changes into events, keeping only the kinds selected by the filter
+    pub(crate) fn drain<'a, Ctx: Copy + 'a>(
+        &'a mut self,
+        component_registry: &'a ComponentRegistry,
+        filter: &'a ReplicationChangeFilter,
+        context: Ctx,
+    ) -> impl Iterator<Item = ReplicationChange<Ctx>> + 'a {
+        self.changes.drain(..).filter_map(move |change| {
+            let kind = component_registry.replicated_kind(change.net_id)?;
+            filter.contains(kind).then_some(ReplicationChange {
+                entity: change.entity,
+                kind,
+                change: change.change,
+                tick: change.tick,
+                context,
+            })
+        })
+    }
+}
+
+/// Plugin that emits a [`ReplicationChange`] event for every replicated component change.
+///
+/// It must be added after the `ClientPlugins` or the `ServerPlugins`.
+#[derive(Default)]
+pub struct ReplicationChangesPlugin {
+    /// The initial component kinds whose changes are emitted
+    pub filter: ReplicationChangeFilter,
+}
+
+impl Plugin for ReplicationChangesPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(self.filter.clone());
+        if app.world().contains_resource::<ClientConfig>() {
+            app.add_event::<ReplicationChange>();
+            app.add_systems(
+                PreUpdate,
+                (
+                    client::enable_changes.before(InternalMainSet::<ClientMarker>::Receive),
+                    client::emit_changes.in_set(InternalMainSet::<ClientMarker>::EmitEvents),
+                ),
+            );
+        }
+        if app.world().contains_resource::<ServerConfig>() {
+            app.add_event::<ReplicationChange<ClientId>>();
+            app.add_systems(
+                PostUpdate,
+                (
+                    server::enable_changes.before(InternalReplicationSet::<ServerMarker>::All),
+                    server::emit_changes.after(InternalReplicationSet::<ServerMarker>::All),
+                ),
+            );
+        }
+    }
+}
+
+mod client {
+    use super::*;
+    use crate::client::connection::ConnectionManager;
+
+    pub(super) fn enable_changes(mut connection_manager: ResMut<ConnectionManager>) {
+        connection_manager.events.changes.enabled = true;
+    }
+
+    pub(super) fn emit_changes(
+        component_registry: Res<ComponentRegistry>,
+        filter: Res<ReplicationChangeFilter>,
+        mut connection_manager: ResMut<ConnectionManager>,
+        mut events: EventWriter<ReplicationChange>,
+    ) {
+        events.send_batch(connection_manager.events.changes.drain(
+            &component_registry,
+            &filter,
+            (),
+        ));
+    }
+}
+
+mod server {
+    use super::*;
+    use crate::server::connection::ConnectionManager;
+
+    pub(super) fn enable_changes(mut connection_manager: ResMut<ConnectionManager>) {
+        for connection in connection_manager.connections.values_mut() {
+            connection.replication_sender.changes.enabled = true;
+        }
+    }
+
+    pub(super) fn emit_changes(
+        component_registry: Res<ComponentRegistry>,
+        filter: Res<ReplicationChangeFilter>,
+        mut connection_manager: ResMut<ConnectionManager>,
+        mut events: EventWriter<ReplicationChange<ClientId>>,
+    ) {
+        for (client_id, connection) in connection_manager.connections.iter_mut() {
+            events.send_batch(connection.replication_sender.changes.drain(
+                &component_registry,
+                &filter,
+                *client_id,
+            ));
+        }
+    }
+}
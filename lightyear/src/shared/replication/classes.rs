@@ -0,0 +1,230 @@
+/*! Bandwidth quotas per category of replicated entities
+
+With a bandwidth cap, the replication messages are sent in the order of their accumulated priority, so a few
+high-priority entities can use the whole bandwidth and starve the other entities for a long time.
+
+Replication groups can be given a [`ReplicationClass`] with [`ReplicationGroup::set_class`], and
+[`ReplicationConfig::class_quotas`] reserves a percentage of the bandwidth quota of each connection for each class:
+```rust,ignore
+const PLAYERS: ReplicationClass = ReplicationClass::new(1);
+const WORLD: ReplicationClass = ReplicationClass::new(2);
+
+let replication = ReplicationConfig {
+    class_quotas: ReplicationClassQuotas::default()
+        .with_quota(PLAYERS, 70)
+        .with_quota(WORLD, 30),
+    ..default()
+};
+commands.spawn((loot, Replicate {
+    group: ReplicationGroup::default().set_class(WORLD),
+    ..default()
+}));
+```
+
+When the packets are assembled, the messages that don't belong to a class are selected first, in the order of their
+priority. The bandwidth that is left is then shared by the classes in proportion to their quotas, and the messages
+of each class are sent in the order of their priority. A class that has nothing to send leaves its share to the
+other classes, and the classes without a quota only use the bandwidth that the other classes did not use.
+
+The bytes of the messages sent for each class are available with `ConnectionManager::replication_class_stats`
+and are reported by the [`ReplicationDiagnosticsPlugin`](super::diagnostics::ReplicationDiagnosticsPlugin).
+
+The quotas are only enforced when the bandwidth cap is enabled.
+
+[`ReplicationGroup::set_class`]: crate::prelude::ReplicationGroup::set_class
+[`ReplicationConfig::class_quotas`]: crate::prelude::ReplicationConfig::class_quotas
+*/
+use bevy::prelude::Reflect;
+
+/// Maximum number of [`ReplicationClass`]es
+pub const MAX_REPLICATION_CLASSES: usize = 8;
+
+/// Category of replication groups that share a part of the bandwidth of each connection.
+///
+/// See the [module-level documentation](self) for more details.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Reflect)]
+pub struct ReplicationClass(u8);
+
+impl ReplicationClass {
+    /// Create a class. The index must be lower than [`MAX_REPLICATION_CLASSES`]
+    pub const fn new(index: u8) -> Self {
+        assert!(
+            (index as usize) < MAX_REPLICATION_CLASSES,
+            "the index of a replication class must be lower than MAX_REPLICATION_CLASSES"
+        );
+        Self(index)
+    }
+
+    pub fn index(&self) -> u8 {
+        self.0
+    }
+}
+
+/// Percentage of the bandwidth quota of each connection reserved for each [`ReplicationClass`]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Reflect)]
+pub struct ReplicationClassQuotas {
+    percentages: [u8; MAX_REPLICATION_CLASSES],
+}
+
+impl ReplicationClassQuotas {
+    /// Reserve `percentage` percents of the bandwidth quota for the messages of `class`
+    pub fn with_quota(mut self, class: ReplicationClass, percentage: u8) -> Self {
+        self.percentages[class.0 as usize] = percentage;
+        self
+    }
+
+    /// Percentage of the bandwidth quota reserved for `class`
+    pub fn quota(&self, class: ReplicationClass) -> u8 {
+        self.percentages[class.0 as usize]
+    }
+
+    /// Sum of the percentages of all the classes, which should not exceed 100
+    pub fn total(&self) -> u32 {
+        self.percentages.iter().map(|p| *p as u32).sum()
+    }
+
+    /// Returns true if at least one class has a quota
+    pub fn is_enabled(&self) -> bool {
+        self.percentages.iter().any(|p| *p > 0)
+    }
+}
+
+/// Bytes of the replication messages sent on a connection for each [`ReplicationClass`].
+///
+/// They are only counted when [`ReplicationClassQuotas`] are configured and the bandwidth cap is enabled.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Reflect)]
+pub struct ReplicationClassStats {
+    bytes_sent: [u64; MAX_REPLICATION_CLASSES],
+}
+
+impl ReplicationClassStats {
+    /// Bytes of the replication messages of `class` that were included in packets.
+    /// The retransmissions of the reliable messages are counted
+    pub fn bytes_sent(&self, class: ReplicationClass) -> u64 {
+        self.bytes_sent[class.0 as usize]
+    }
+
+    pub(crate) fn add(&mut self, class: ReplicationClass, bytes: u32) {
+        self.bytes_sent[class.0 as usize] += bytes as u64;
+    }
+
+    /// Add the stats of another connection to these stats
+    pub(crate) fn merge(&mut self, other: &ReplicationClassStats) {
+        for (bytes, other) in self.bytes_sent.iter_mut().zip(other.bytes_sent) {
+            *bytes += other;
+        }
+    }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (ReplicationClass, u64)> + '_ {
+        self.bytes_sent
+            .iter()
+            .enumerate()
+            .map(|(index, bytes)| (ReplicationClass(index as u8), *bytes))
+    }
+}
+
+/// Shares the bandwidth between the classes in proportion to their quotas (start-time fair queuing):
+/// the next message is sent by the class that sent the fewest bytes relative to its quota
+#[derive(Debug)]
+pub(crate) struct ClassScheduler {
+    quotas: ReplicationClassQuotas,
+    /// Bytes sent by each class, divided by its quota
+    finish: [f64; MAX_REPLICATION_CLASSES],
+    /// Start of the last message that was sent, so that a class that had nothing to send for a while
+    /// does not use the whole bandwidth to catch up with the other classes
+    virtual_time: f64,
+}
+
+impl ClassScheduler {
+    pub(crate) fn new(quotas: ReplicationClassQuotas) -> Self {
+        Self {
+            quotas,
+            finish: [0.0; MAX_REPLICATION_CLASSES],
+            virtual_time: 0.0,
+        }
+    }
+
+    fn start(&self, class: ReplicationClass) -> f64 {
+        self.finish[class.0 as usize].max(self.virtual_time)
+    }
+
+    /// Class with a quota that should send the next message, among the classes that have messages to send
+    pub(crate) fn next_class(
+        &self,
+        has_messages: impl Fn(ReplicationClass) -> bool,
+    ) -> Option<ReplicationClass> {
+        (0..MAX_REPLICATION_CLASSES as u8)
+            .map(ReplicationClass)
+            .filter(|class| self.quotas.quota(*class) > 0 && has_messages(*class))
+            .min_by(|a, b| self.start(*a).total_cmp(&self.start(*b)))
+    }
+
+    /// Record that a message of `bytes` was sent for `class`
+    pub(crate) fn record(&mut self, class: ReplicationClass, bytes: u32) {
+        let quota = self.quotas.quota(class);
+        if quota == 0 {
+            return;
+        }
+        let start = self.start(class);
+        self.virtual_time = start;
+        self.finish[class.0 as usize] = start + bytes as f64 / quota as f64;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Send `num_messages` messages of `bytes` bytes, and return the number of messages sent by each class
+    fn schedule(
+        scheduler: &mut ClassScheduler,
+        backlogged: &[ReplicationClass],
+        num_messages: usize,
+        bytes: u32,
+    ) -> [usize; MAX_REPLICATION_CLASSES] {
+        let mut sent = [0; MAX_REPLICATION_CLASSES];
+        for _ in 0..num_messages {
+            let class = scheduler
+                .next_class(|class| backlogged.contains(&class))
+                .unwrap();
+            scheduler.record(class, bytes);
+            sent[class.index() as usize] += 1;
+        }
+        sent
+    }
+
+    #[test]
+    fn test_class_scheduler() {
+        let (players, world, other) = (
+            ReplicationClass::new(1),
+            ReplicationClass::new(2),
+            ReplicationClass::new(3),
+        );
+        let quotas = ReplicationClassQuotas::default()
+            .with_quota(players, 70)
+            .with_quota(world, 30);
+        assert_eq!(quotas.total(), 100);
+        let mut scheduler = ClassScheduler::new(quotas);
+
+        // the classes share the messages in proportion to their quotas
+        let sent = schedule(&mut scheduler, &[players, world, other], 100, 10);
+        assert_eq!((sent[1], sent[2], sent[3]), (70, 30, 0));
+
+        // a class without messages leaves its share to the other classes
+        let sent = schedule(&mut scheduler, &[world], 100, 10);
+        assert_eq!(sent[2], 100);
+
+        // and cannot use the whole bandwidth to catch up once it has messages again
+        let sent = schedule(&mut scheduler, &[players, world], 100, 10);
+        assert!((69..=71).contains(&sent[1]), "{sent:?}");
+
+        // a class without quota is never picked
+        assert_eq!(scheduler.next_class(|class| class == other), None);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_class_index_out_of_bounds() {
+        ReplicationClass::new(MAX_REPLICATION_CLASSES as u8);
+    }
+}
@@ -0,0 +1,173 @@
+//! Sharing the serialized replication updates between the connections of the server.
+//!
+//! When many clients stand in the same area, they receive the same updates for the same entities: the server
+//! would serialize nearly identical [`EntityUpdatesMessage`](super::EntityUpdatesMessage)s once per client.
+//! The component payloads are already serialized once and shared between the connections, so two connections
+//! that buffer an update message with the same group, the same `last_action_tick` and the same payloads
+//! for the same entities (in the same order) would write exactly the same bytes.
+//!
+//! During each replication send, the server keeps the messages that it serialized in a cache, keyed by
+//! a cheap fingerprint of the message: the ids of the entities and the location of their payloads.
+//! The next connections that need the same message reuse the immutable buffer instead of serializing it again.
+//! Only the messages that contain per-client differences (personalized or delta-compressed components, entity
+//! aliases, updates of a group that were not acked yet...) are serialized for each client, so put the entities
+//! that are seen by the same clients in the same [`ReplicationGroup`](crate::prelude::ReplicationGroup)
+//! (for example one group per spatial cell) to maximize the sharing.
+//!
+//! Two [`Bytes`] that are alive at the same time and point to the same memory have the same content, since
+//! they are immutable. All the payloads are prepared before the replication send, and the cache is cleared
+//! at the end of the send, so comparing the location of the payloads is enough to know that two messages
+//! are identical.
+use bevy::utils::HashMap;
+use bytes::Bytes;
+
+use crate::serialize::writer::Writer;
+use crate::serialize::{SerializationError, ToBytes};
+
+use super::SendEntityUpdatesMessage;
+
+/// Serialized [`EntityUpdatesMessage`](super::EntityUpdatesMessage)s that can be reused by all the connections
+/// during a single replication send
+#[derive(Debug, Default)]
+pub(crate) struct UpdateMessageCache {
+    messages: HashMap<Vec<u64>, Bytes>,
+    /// Buffer used to compute the fingerprint of a message, to avoid allocating for each message
+    fingerprint: Vec<u64>,
+}
+
+impl UpdateMessageCache {
+    /// Write in `self.fingerprint` the values that identify the serialized bytes of the message
+    fn compute_fingerprint(&mut self, message: &SendEntityUpdatesMessage) {
+        let fingerprint = &mut self.fingerprint;
+        fingerprint.clear();
+        fingerprint.push(message.group_id.0);
+        fingerprint.push(message.last_action_tick.map_or(0, |tick| tick.0 as u64 + 1));
+        // the updates are serialized in the iteration order of the map, so the fingerprint must follow it too
+        fingerprint.push(message.updates.len() as u64);
+        for (entity, payloads) in message.updates.iter() {
+            fingerprint.push(entity.to_bits());
+            Self::push_payloads(fingerprint, payloads);
+        }
+        fingerprint.push(message.aliased_updates.len() as u64);
+        for (alias, payloads) in message.aliased_updates.iter() {
+            fingerprint.push(alias.0 as u64);
+            Self::push_payloads(fingerprint, payloads);
+        }
+    }
+
+    fn push_payloads(fingerprint: &mut Vec<u64>, payloads: &[Bytes]) {
+        fingerprint.push(payloads.len() as u64);
+        for payload in payloads {
+            fingerprint.push(payload.as_ptr() as u64);
+            fingerprint.push(payload.len() as u64);
+        }
+    }
+
+    /// Return the serialized message, and whether it was serialized by another connection
+    pub(crate) fn serialize(
+        &mut self,
+        message: &SendEntityUpdatesMessage,
+        writer: &mut Writer,
+    ) -> Result<(Bytes, bool), SerializationError> {
+        self.compute_fingerprint(message);
+        if let Some(bytes) = self.messages.get(self.fingerprint.as_slice()) {
+            return Ok((bytes.clone(), true));
+        }
+        message.to_bytes(writer)?;
+        let bytes = writer.split();
+        self.messages
+            .insert(self.fingerprint.clone(), bytes.clone());
+        Ok((bytes, false))
+    }
+
+    /// Forget the messages of the current replication send.
+    ///
+    /// This must be called once all the connections have buffered their messages, because the payloads
+    /// can be dropped afterward.
+    pub(crate) fn clear(&mut self) {
+        self.messages.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::entity::EntityHash;
+    use bevy::prelude::Entity;
+
+    use crate::prelude::Tick;
+    use crate::shared::replication::components::ReplicationGroupId;
+
+    use super::*;
+
+    fn message(
+        updates: impl IntoIterator<Item = (Entity, Vec<Bytes>)>,
+        last_action_tick: Option<Tick>,
+    ) -> SendEntityUpdatesMessage {
+        SendEntityUpdatesMessage {
+            group_id: ReplicationGroupId(0),
+            last_action_tick,
+            updates: hashbrown::HashMap::<_, _, EntityHash>::from_iter(updates),
+            aliased_updates: vec![],
+        }
+    }
+
+    fn serialize(message: &SendEntityUpdatesMessage) -> Bytes {
+        let mut writer = Writer::default();
+        message.to_bytes(&mut writer).unwrap();
+        writer.split()
+    }
+
+    #[test]
+    fn test_identical_messages_are_serialized_once() {
+        let mut cache = UpdateMessageCache::default();
+        let mut writer = Writer::default();
+        let payload = Bytes::from_static(&[1, 2, 3]);
+        let entity = Entity::from_raw(1);
+        let first = message([(entity, vec![payload.clone()])], Some(Tick(2)));
+        let second = message([(entity, vec![payload.clone()])], Some(Tick(2)));
+
+        let (first_bytes, shared) = cache.serialize(&first, &mut writer).unwrap();
+        assert!(!shared);
+        let (second_bytes, shared) = cache.serialize(&second, &mut writer).unwrap();
+        assert!(shared);
+        // the buffer is shared, and identical to the bytes written without the cache
+        assert_eq!(first_bytes.as_ptr(), second_bytes.as_ptr());
+        assert_eq!(second_bytes, serialize(&second));
+
+        cache.clear();
+        let (_, shared) = cache.serialize(&second, &mut writer).unwrap();
+        assert!(!shared);
+    }
+
+    #[test]
+    fn test_different_messages_are_not_shared() {
+        let mut cache = UpdateMessageCache::default();
+        let mut writer = Writer::default();
+        let payload = Bytes::from(vec![1, 2, 3]);
+        // same content, but a different payload (e.g. a personalized component)
+        let other_payload = Bytes::from(vec![1, 2, 3]);
+        let entity = Entity::from_raw(1);
+        let base = message([(entity, vec![payload.clone()])], Some(Tick(2)));
+        cache.serialize(&base, &mut writer).unwrap();
+
+        for different in [
+            message([(entity, vec![other_payload.clone()])], Some(Tick(2))),
+            message([(entity, vec![payload.clone()])], None),
+            message(
+                [(Entity::from_raw(2), vec![payload.clone()])],
+                Some(Tick(2)),
+            ),
+            message(
+                [
+                    (entity, vec![payload.clone()]),
+                    (Entity::from_raw(2), vec![payload.clone()]),
+                ],
+                Some(Tick(2)),
+            ),
+        ] {
+            let (bytes, shared) = cache.serialize(&different, &mut writer).unwrap();
+            assert!(!shared);
+            assert_eq!(bytes, serialize(&different));
+        }
+    }
+}
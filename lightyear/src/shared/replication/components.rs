@@ -8,6 +8,7 @@ use serde::{Deserialize, Serialize};
 use crate::connection::id::ClientId;
 use crate::serialize::reader::Reader;
 use crate::serialize::{SerializationError, ToBytes};
+use crate::shared::replication::classes::ReplicationClass;
 use crate::shared::replication::network_target::NetworkTarget;
 
 /// Marker component that indicates that the entity was spawned via replication
@@ -78,8 +79,18 @@ pub enum TargetEntity {
     /// Instead of spawning a new entity, we will apply the replication updates
     /// to the existing remote entity
     Preexisting(Entity),
+    /// The entity replaces the given local entity, which is despawned at the same time.
+    /// The remote re-uses the entity that it had spawned for the replaced entity.
+    ///
+    /// This is set by [`ReplaceReplicationCommandExt::replicate_as_replacement_of`](crate::prelude::server::ReplaceReplicationCommandExt::replicate_as_replacement_of).
+    Replace(Entity),
 }
 
+/// Marker component added on an entity that is about to be despawned because it is being
+/// replaced by another entity
+#[derive(Component, Clone, Copy, Debug, PartialEq)]
+pub(crate) struct ReplacedBy(pub(crate) Entity);
+
 /// Component that defines how the hierarchy of an entity (parent/children) should be replicated
 ///
 /// If the component is absent, the [`Parent`](bevy::prelude::Parent)/[`Children`](bevy::prelude::Children) components will not be replicated.
@@ -89,7 +100,8 @@ pub struct ReplicateHierarchy {
     /// If true, recursively add `Replicate` and `ParentSync` components to all children to make sure they are replicated
     ///
     /// If false, you can still replicate hierarchies, but in a more fine-grained manner. You will have to add the `Replicate`
-    /// and `ParentSync` components to the children yourself
+    /// component to the children yourself; the `ParentSync` component is added automatically to the replicated entities
+    /// that have a parent
     pub recursive: bool,
 }
 
@@ -188,6 +200,14 @@ pub enum ReplicationGroupIdBuilder {
 ///
 /// If multiple entities are part of the same replication group, they will be sent together in the same message.
 /// It is guaranteed that these entities will be updated at the same time on the remote world.
+/// A message that doesn't fit in a single packet is fragmented, the group is never split.
+///
+/// The remote applies the message of a group at once: the systems never see only some of the entities of the group.
+/// (the observers and hooks of the components are triggered while the message is being applied, so they can still
+/// see an entity of the group whose components were not written yet)
+///
+/// The group id of an entity can be modified after it was replicated: the entity is moved to the new group, and all
+/// its components are sent again in the new group.
 #[derive(Component, Debug, Clone, PartialEq, Reflect)]
 #[reflect(Component)]
 pub struct ReplicationGroup {
@@ -195,6 +215,8 @@ pub struct ReplicationGroup {
     /// the priority of the accumulation group
     /// (priority will get reset to this value every time a message gets sent successfully)
     base_priority: f32,
+    /// The class of the group, which decides which bandwidth quota is used to send its messages
+    class: ReplicationClass,
     /// Keep track of whether we should send replication updates for this group.
     ///
     /// See [`ReplicationGroup::set_send_frequency`] for more information.
@@ -218,6 +240,7 @@ impl Default for ReplicationGroup {
         Self {
             id_builder: ReplicationGroupIdBuilder::FromEntity,
             base_priority: 1.0,
+            class: ReplicationClass::new(0),
             send_frequency: None,
             should_send: true,
         }
@@ -229,6 +252,7 @@ impl ReplicationGroup {
         Self {
             id_builder: ReplicationGroupIdBuilder::FromEntity,
             base_priority: 1.0,
+            class: ReplicationClass::new(0),
             send_frequency: None,
             should_send: true,
         }
@@ -238,6 +262,7 @@ impl ReplicationGroup {
         Self {
             id_builder: ReplicationGroupIdBuilder::Group(id),
             base_priority: 1.0,
+            class: ReplicationClass::new(0),
             send_frequency: None,
             should_send: true,
         }
@@ -261,6 +286,18 @@ impl ReplicationGroup {
         self
     }
 
+    pub(crate) fn class(&self) -> ReplicationClass {
+        self.class
+    }
+
+    /// Sets the [`ReplicationClass`] of the group, which decides which bandwidth quota is used to send its messages.
+    ///
+    /// See [`classes`](crate::shared::replication::classes) for more details.
+    pub fn set_class(mut self, class: ReplicationClass) -> Self {
+        self.class = class;
+        self
+    }
+
     pub fn set_id(mut self, id: u64) -> Self {
         self.id_builder = ReplicationGroupIdBuilder::Group(id);
         self
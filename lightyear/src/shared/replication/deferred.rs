@@ -0,0 +1,155 @@
+//! Components and messages that reference entities that were not spawned locally yet.
+//!
+//! The entities contained in a replicated component or in a message (see
+//! [`MapEntities`](lightyear_macros::MapEntities)) are remote entities, which are mapped to the local entities
+//! with the entity map of the connection. If a referenced entity was not spawned locally yet (for example
+//! because it is replicated in another replication group whose messages were not received yet), the component
+//! or message is not applied with a dangling entity: it is kept, and applied again on the next frames
+//! until the entity is spawned.
+//!
+//! The number of deferred components and messages is bounded: when there are too many of them, the oldest
+//! ones are dropped and a [`NetworkWarning::DeferredOverflow`] is reported.
+use std::collections::VecDeque;
+
+use bevy::prelude::{Entity, World};
+use bytes::Bytes;
+use tracing::debug;
+
+use crate::prelude::{ClientId, Tick};
+use crate::protocol::component::{ComponentError, ComponentNetId, ComponentRegistry};
+use crate::serialize::reader::Reader;
+use crate::serialize::ToBytes;
+use crate::shared::events::connection::ConnectionEvents;
+use crate::shared::replication::entity_map::EntityMap;
+use crate::shared::warnings::{NetworkWarning, NetworkWarnings};
+
+/// Maximum number of component writes that are waiting for their entities, per connection
+pub(crate) const MAX_DEFERRED_COMPONENTS: usize = 256;
+
+/// Maximum number of messages of each type that are waiting for their entities, per connection
+pub(crate) const MAX_DEFERRED_MESSAGES: usize = 256;
+
+#[derive(Debug)]
+struct DeferredComponent {
+    local_entity: Entity,
+    net_id: ComponentNetId,
+    payload: Bytes,
+    remote_tick: Tick,
+}
+
+/// Component writes that are waiting for the entities they reference to be spawned
+#[derive(Debug, Default)]
+pub(crate) struct DeferredComponents {
+    /// Deferred writes, from the oldest to the most recent
+    writes: VecDeque<DeferredComponent>,
+}
+
+fn payload_net_id(payload: &Bytes) -> Option<ComponentNetId> {
+    ComponentNetId::from_bytes(&mut Reader::from(payload.clone())).ok()
+}
+
+impl DeferredComponents {
+    /// Defer the write of the component payload to the local entity.
+    ///
+    /// It replaces the deferred write of the same component on the same entity, if there is one.
+    pub(crate) fn defer(
+        &mut self,
+        local_entity: Entity,
+        payload: Bytes,
+        remote_tick: Tick,
+        warnings: &mut NetworkWarnings,
+    ) {
+        let Some(net_id) = payload_net_id(&payload) else {
+            return;
+        };
+        self.discard(local_entity, net_id);
+        self.writes.push_back(DeferredComponent {
+            local_entity,
+            net_id,
+            payload,
+            remote_tick,
+        });
+        if self.writes.len() > MAX_DEFERRED_COMPONENTS {
+            let dropped = self.writes.pop_front().unwrap();
+            warnings.report(
+                NetworkWarning::DeferredOverflow,
+                format_args!(
+                    "Dropped the component {} of the entity {:?} because too many components are waiting for their entities to be spawned",
+                    dropped.net_id, dropped.local_entity
+                ),
+            );
+        }
+    }
+
+    /// Discard the deferred write of the component, because a more recent value was written or
+    /// the component was removed
+    pub(crate) fn discard(&mut self, local_entity: Entity, net_id: ComponentNetId) {
+        self.writes
+            .retain(|write| write.local_entity != local_entity || write.net_id != net_id);
+    }
+
+    /// Discard the deferred write of the component contained in the payload
+    pub(crate) fn discard_payload(&mut self, local_entity: Entity, payload: &Bytes) {
+        if self.writes.is_empty() {
+            return;
+        }
+        if let Some(net_id) = payload_net_id(payload) {
+            self.discard(local_entity, net_id);
+        }
+    }
+
+    /// Try to write the deferred components again.
+    ///
+    /// The writes whose entity was despawned in the meantime are dropped.
+    pub(crate) fn apply(
+        &mut self,
+        world: &mut World,
+        remote: Option<ClientId>,
+        component_registry: &ComponentRegistry,
+        entity_map: &mut EntityMap,
+        events: &mut ConnectionEvents,
+        warnings: &mut NetworkWarnings,
+    ) {
+        self.writes.retain(|write| {
+            let Some(mut entity_world_mut) = world.get_entity_mut(write.local_entity) else {
+                debug!(
+                    entity = ?write.local_entity,
+                    "Dropped a deferred component write because the entity was despawned"
+                );
+                return false;
+            };
+            let mut reader = Reader::from(write.payload.clone());
+            match component_registry.raw_write(
+                &mut reader,
+                &mut entity_world_mut,
+                write.remote_tick,
+                remote,
+                entity_map,
+                events,
+            ) {
+                Err(ComponentError::UnspawnedEntity(_)) => true,
+                result => {
+                    let _ = result.inspect_err(|e| super::receive::report_write_error(warnings, e));
+                    false
+                }
+            }
+        });
+    }
+}
+
+/// Bound the messages that are waiting for their entities to be spawned, by dropping the oldest ones
+pub(crate) fn bound_deferred_messages<T>(deferred: &mut Vec<T>, warnings: &mut NetworkWarnings) {
+    if deferred.len() <= MAX_DEFERRED_MESSAGES {
+        return;
+    }
+    let dropped = deferred.len() - MAX_DEFERRED_MESSAGES;
+    deferred.drain(..dropped);
+    for _ in 0..dropped {
+        warnings.report(
+            NetworkWarning::DeferredOverflow,
+            format_args!(
+                "Dropped a message because too many messages are waiting for their entities to be spawned"
+            ),
+        );
+    }
+}
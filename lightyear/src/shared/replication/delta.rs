@@ -6,7 +6,7 @@ use crate::shared::replication::components::ReplicationGroupId;
 use bevy::ecs::entity::EntityHash;
 use bevy::prelude::{Component, Entity};
 use bevy::ptr::Ptr;
-use bevy::utils::HashMap;
+use bevy::utils::{HashMap, HashSet};
 
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
@@ -74,6 +74,16 @@ impl<C> Default for DeltaComponentHistory<C> {
     }
 }
 
+impl<C> DeltaComponentHistory<C> {
+    /// Returns true if the history already contains a value more recent than `tick`, in which case
+    /// a value received for `tick` must not overwrite the component
+    pub(crate) fn is_stale(&self, tick: Tick) -> bool {
+        self.buffer
+            .last_key_value()
+            .is_some_and(|(latest, _)| *latest > tick)
+    }
+}
+
 #[derive(Default, Debug)]
 pub struct DeltaManager {
     pub(crate) data: DeltaComponentStore,
@@ -203,15 +213,30 @@ impl DeltaComponentStore {
     ) {
         if let Some(data) = self.data.get_mut(&replication_group) {
             // we can remove all the keys older than the acked key
-            let recent_data = data.split_off(&tick).into_iter().collect();
-            // call drop on all the data that we are removing
-            data.values_mut().for_each(|tick_data| {
-                tick_data.iter().for_each(|(kind, _, owned_ptr)| unsafe {
-                    // SAFETY: the ptr corresponds to the kind
-                    registry.erased_drop(*owned_ptr, *kind).unwrap();
+            let mut recent_data: BTreeMap<_, _> = data.split_off(&tick).into_iter().collect();
+            // a component that was not sent since the older ticks still needs its most recent value,
+            // since it's the baseline from which we compute its next diffs
+            let mut kept: HashSet<(ComponentKind, Entity)> = recent_data
+                .values()
+                .flatten()
+                .map(|(kind, entity, _)| (*kind, *entity))
+                .collect();
+            while let Some((old_tick, tick_data)) = data.pop_last() {
+                tick_data.into_iter().for_each(|(kind, entity, owned_ptr)| {
+                    if kept.insert((kind, entity)) {
+                        recent_data
+                            .entry(old_tick)
+                            .or_default()
+                            .push((kind, entity, owned_ptr));
+                    } else {
+                        // call drop on the data that we are removing
+                        // SAFETY: the ptr corresponds to the kind
+                        unsafe { registry.erased_drop(owned_ptr, kind).unwrap() };
+                    }
                 });
-            });
-            // only keep the data that is more recent (inclusive) than the acked tick
+            }
+            // only keep the data that is more recent (inclusive) than the acked tick,
+            // and the latest value of the other components
             *data = recent_data;
         }
     }
@@ -279,4 +304,52 @@ mod tests {
         let retrieved_component = unsafe { retrieved.deref::<Component6>() };
         assert_eq!(retrieved_component, &component);
     }
+
+    /// The most recent value of a component is kept even if it's older than the acked tick,
+    /// if the component was not sent since
+    #[test]
+    fn test_delete_old_data_keeps_latest_value() {
+        let mut registry = ComponentRegistry::default();
+        registry.register_component::<Component6>();
+        registry.set_delta_compression::<Component6>();
+        let mut store = DeltaComponentStore::default();
+        let entity_1 = Entity::from_raw(0);
+        let entity_2 = Entity::from_raw(1);
+        let replication_group = ReplicationGroupId(0);
+        let component = Component6(vec![1, 2]);
+        let ptr = Ptr::from(&component);
+        let kind = ComponentKind::of::<Component6>();
+
+        store.store_component_value(entity_1, Tick(1), kind, ptr, replication_group, &registry);
+        store.store_component_value(entity_2, Tick(1), kind, ptr, replication_group, &registry);
+        store.store_component_value(entity_1, Tick(2), kind, ptr, replication_group, &registry);
+        store.store_component_value(entity_1, Tick(3), kind, ptr, replication_group, &registry);
+
+        store.delete_old_data(Tick(3), replication_group, &registry);
+
+        assert!(store
+            .get_component_value(entity_1, Tick(1), kind, replication_group)
+            .is_none());
+        assert!(store
+            .get_component_value(entity_1, Tick(2), kind, replication_group)
+            .is_none());
+        assert!(store
+            .get_component_value(entity_1, Tick(3), kind, replication_group)
+            .is_some());
+        // entity_2 was not sent since tick 1
+        assert!(store
+            .get_component_value(entity_2, Tick(1), kind, replication_group)
+            .is_some());
+    }
+
+    /// A value received for a tick older than the latest value of the history must not be applied
+    #[test]
+    fn test_history_is_stale() {
+        let mut history = DeltaComponentHistory::<Component6>::default();
+        assert!(!history.is_stale(Tick(3)));
+        history.buffer.insert(Tick(5), Component6(vec![1]));
+        assert!(history.is_stale(Tick(3)));
+        assert!(!history.is_stale(Tick(5)));
+        assert!(!history.is_stale(Tick(6)));
+    }
 }
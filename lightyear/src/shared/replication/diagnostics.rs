@@ -0,0 +1,97 @@
+//! Compute Diagnostics about the replication bandwidth
+
+use crate::shared::replication::classes::{
+    ReplicationClass, ReplicationClassStats, MAX_REPLICATION_CLASSES,
+};
+use crate::shared::replication::send::ReplicationSendStats;
+use bevy::app::{App, Plugin};
+use bevy::diagnostic::{Diagnostic, DiagnosticPath, Diagnostics, RegisterDiagnostic};
+use bevy::utils::Duration;
+
+/// Plugin to compute some network diagnostics related to replication
+pub struct ReplicationDiagnosticsPlugin {
+    pub history_len: usize,
+    pub flush_interval: Duration,
+}
+
+impl Default for ReplicationDiagnosticsPlugin {
+    fn default() -> Self {
+        Self {
+            history_len: 60,
+            flush_interval: Duration::from_millis(200),
+        }
+    }
+}
+
+impl ReplicationDiagnosticsPlugin {
+    /// Bytes of component updates sent because the component changed
+    pub const UPDATE_BYTES: DiagnosticPath = DiagnosticPath::const_new("replication.update_bytes");
+
+    /// Bytes of component keyframes (full values periodically re-sent even without changes)
+    pub const KEYFRAME_BYTES: DiagnosticPath =
+        DiagnosticPath::const_new("replication.keyframe_bytes");
+
+    /// Bytes saved by referring to frequently updated entities with a 1-byte alias
+    pub const ENTITY_ALIAS_SAVED_BYTES: DiagnosticPath =
+        DiagnosticPath::const_new("replication.entity_alias_saved_bytes");
+
+    /// Bytes of update messages that reused the serialization of an identical message sent to another client
+    pub const COALESCED_BYTES: DiagnosticPath =
+        DiagnosticPath::const_new("replication.coalesced_bytes");
+
+    pub(crate) fn add_measurements(stats: ReplicationSendStats, mut diagnostics: Diagnostics) {
+        diagnostics.add_measurement(&Self::UPDATE_BYTES, || stats.update_bytes as f64);
+        diagnostics.add_measurement(&Self::KEYFRAME_BYTES, || stats.keyframe_bytes as f64);
+        diagnostics.add_measurement(&Self::ENTITY_ALIAS_SAVED_BYTES, || {
+            stats.alias_saved_bytes as f64
+        });
+        diagnostics.add_measurement(&Self::COALESCED_BYTES, || stats.coalesced_bytes as f64);
+    }
+
+    /// Bytes of the replication messages of a [`ReplicationClass`] that were sent, at the path
+    /// `replication.class_<index>.bytes_sent`
+    pub fn class_bytes_sent(class: ReplicationClass) -> DiagnosticPath {
+        DiagnosticPath::new(format!("replication.class_{}.bytes_sent", class.index()))
+    }
+
+    pub(crate) fn add_class_measurements(
+        stats: &ReplicationClassStats,
+        diagnostics: &mut Diagnostics,
+    ) {
+        for (class, bytes) in stats.iter() {
+            diagnostics.add_measurement(&Self::class_bytes_sent(class), || bytes as f64);
+        }
+    }
+}
+
+impl Plugin for ReplicationDiagnosticsPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_diagnostic(
+            Diagnostic::new(Self::UPDATE_BYTES)
+                .with_suffix("bytes")
+                .with_max_history_length(self.history_len),
+        );
+        app.register_diagnostic(
+            Diagnostic::new(Self::KEYFRAME_BYTES)
+                .with_suffix("bytes")
+                .with_max_history_length(self.history_len),
+        );
+        app.register_diagnostic(
+            Diagnostic::new(Self::ENTITY_ALIAS_SAVED_BYTES)
+                .with_suffix("bytes")
+                .with_max_history_length(self.history_len),
+        );
+        app.register_diagnostic(
+            Diagnostic::new(Self::COALESCED_BYTES)
+                .with_suffix("bytes")
+                .with_max_history_length(self.history_len),
+        );
+        for index in 0..MAX_REPLICATION_CLASSES {
+            app.register_diagnostic(
+                Diagnostic::new(Self::class_bytes_sent(ReplicationClass::new(index as u8)))
+                    .with_suffix("bytes")
+                    .with_max_history_length(self.history_len),
+            );
+        }
+    }
+}
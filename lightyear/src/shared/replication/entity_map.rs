@@ -1,5 +1,7 @@
 //! Map between local and remote entities
-use bevy::ecs::entity::{EntityHashMap, EntityMapper};
+use std::collections::VecDeque;
+
+use bevy::ecs::entity::{Entities, EntityHashMap, EntityMapper};
 use bevy::prelude::{Deref, DerefMut, Entity, EntityWorldMut, World};
 use bevy::reflect::Reflect;
 use bevy::utils::hashbrown::hash_map::Entry;
@@ -14,6 +16,90 @@ impl EntityMapper for EntityMap {
     }
 }
 
+/// Wrapper to use a `dyn EntityMapper` where a sized [`EntityMapper`] is expected
+pub(crate) struct DynEntityMapper<'a>(pub(crate) &'a mut dyn EntityMapper);
+
+impl EntityMapper for DynEntityMapper<'_> {
+    fn map_entity(&mut self, entity: Entity) -> Entity {
+        self.0.map_entity(entity)
+    }
+}
+
+/// Mapper that doesn't modify the entities, but finds the first entity that is neither in the
+/// [`EntityMap`] nor alive in the local World.
+///
+/// Such an entity is a remote entity that was not spawned locally yet. The entities that are not in the
+/// map but are alive are assumed to be local entities (for example entities that the remote mapped
+/// with its own entity map before sending them).
+pub(crate) struct UnspawnedEntityProbe<'a> {
+    pub(crate) entity_map: &'a EntityMap,
+    pub(crate) entities: &'a Entities,
+    pub(crate) unspawned: Option<Entity>,
+}
+
+impl EntityMapper for UnspawnedEntityProbe<'_> {
+    fn map_entity(&mut self, entity: Entity) -> Entity {
+        if self.unspawned.is_none()
+            && entity != Entity::PLACEHOLDER
+            && !self.entity_map.contains_key(&entity)
+            && !self.entities.contains(entity)
+        {
+            self.unspawned = Some(entity);
+        }
+        entity
+    }
+}
+
+/// Field of a type that derives [`MapEntities`](lightyear_macros::MapEntities), marked with `#[entities]`.
+///
+/// It is implemented for [`Entity`], for the common containers of entities, and for the types
+/// that derive [`MapEntities`](lightyear_macros::MapEntities).
+pub trait MapEntityField {
+    /// Replace the entities contained in the field with the entities returned by the mapper
+    fn map_entity_field<M: EntityMapper>(&mut self, entity_mapper: &mut M);
+}
+
+impl MapEntityField for Entity {
+    fn map_entity_field<M: EntityMapper>(&mut self, entity_mapper: &mut M) {
+        *self = entity_mapper.map_entity(*self);
+    }
+}
+
+impl<T: MapEntityField> MapEntityField for Option<T> {
+    fn map_entity_field<M: EntityMapper>(&mut self, entity_mapper: &mut M) {
+        if let Some(value) = self {
+            value.map_entity_field(entity_mapper);
+        }
+    }
+}
+
+impl<T: MapEntityField> MapEntityField for Box<T> {
+    fn map_entity_field<M: EntityMapper>(&mut self, entity_mapper: &mut M) {
+        self.as_mut().map_entity_field(entity_mapper);
+    }
+}
+
+impl<T: MapEntityField> MapEntityField for Vec<T> {
+    fn map_entity_field<M: EntityMapper>(&mut self, entity_mapper: &mut M) {
+        self.iter_mut()
+            .for_each(|value| value.map_entity_field(entity_mapper));
+    }
+}
+
+impl<T: MapEntityField> MapEntityField for VecDeque<T> {
+    fn map_entity_field<M: EntityMapper>(&mut self, entity_mapper: &mut M) {
+        self.iter_mut()
+            .for_each(|value| value.map_entity_field(entity_mapper));
+    }
+}
+
+impl<T: MapEntityField, const N: usize> MapEntityField for [T; N] {
+    fn map_entity_field<M: EntityMapper>(&mut self, entity_mapper: &mut M) {
+        self.iter_mut()
+            .for_each(|value| value.map_entity_field(entity_mapper));
+    }
+}
+
 #[derive(Default, Debug, Reflect)]
 /// Map between local and remote entities. (used mostly on client because it's when we receive entity updates)
 pub struct RemoteEntityMap {
@@ -0,0 +1,155 @@
+//! Per-component replication send frequency.
+//!
+//! By default, a replicated component is sent to each client every time it changes (at most once per
+//! [`server_replication_send_interval`](crate::prelude::SharedConfig::server_replication_send_interval)).
+//! The [`ReplicationMode`] of a component lets it be sent less often: for example `Transform` at 30Hz,
+//! `Health` whenever it changes and `Name` only once.
+//!
+//! For [`ReplicationMode::EveryInterval`], the server keeps a timer per client, entity and component.
+//! A change that happens before the interval elapsed is not lost: it is sent as soon as the interval elapses.
+use bevy::ecs::entity::EntityHash;
+use bevy::prelude::Entity;
+use bevy::utils::{hashbrown, Duration, HashMap};
+
+use crate::prelude::Tick;
+use crate::protocol::component::ComponentKind;
+
+type EntityHashMap<K, V> = hashbrown::HashMap<K, V, EntityHash>;
+
+/// How often the updates of a replicated component are sent.
+///
+/// Set it with [`ComponentRegistration::replication_mode`](crate::protocol::component::ComponentRegistration::replication_mode).
+/// The inserts and removals of the component are always replicated immediately.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum ReplicationMode {
+    /// Send the component whenever it changes
+    #[default]
+    OnChange,
+    /// Send the changes of the component to each client at most once per interval.
+    ///
+    /// Only applies to the components replicated by the server.
+    EveryInterval(Duration),
+    /// Send the component only when it is inserted, or when the entity is replicated to a new client
+    /// (a newly connected client, or a client for which the entity becomes relevant again).
+    /// The updates are never sent.
+    ///
+    /// This is equivalent to adding a [`ReplicateOnceComponent`](crate::prelude::ReplicateOnceComponent) to every entity.
+    Once,
+}
+
+impl ReplicationMode {
+    /// Number of ticks between two updates for [`ReplicationMode::EveryInterval`]
+    pub(crate) fn interval_ticks(&self, tick_duration: Duration) -> Option<i16> {
+        let ReplicationMode::EveryInterval(interval) = self else {
+            return None;
+        };
+        let interval_ticks = if tick_duration.is_zero() {
+            1
+        } else {
+            interval.as_nanos().div_ceil(tick_duration.as_nanos())
+        };
+        Some(interval_ticks.clamp(1, i16::MAX as u128) as i16)
+    }
+}
+
+#[derive(Debug)]
+struct SendInterval {
+    /// Tick at which we last sent the component
+    last_send: Tick,
+    /// True if the component changed since we last sent it
+    pending: bool,
+}
+
+/// Keeps track of the components sent with [`ReplicationMode::EveryInterval`] to a single remote
+#[derive(Debug, Default)]
+pub(crate) struct SendIntervalManager {
+    intervals: EntityHashMap<Entity, HashMap<ComponentKind, SendInterval>>,
+}
+
+impl SendIntervalManager {
+    /// Returns true if we should send the entity's component at this tick.
+    ///
+    /// `changed` is true if the component changed since the last update sent for its replication group.
+    /// Changes are accumulated until the interval elapses. The first time we see an entity, we consider
+    /// that the component was just sent (inserts contain the full value).
+    pub(crate) fn should_send(
+        &mut self,
+        entity: Entity,
+        kind: ComponentKind,
+        interval_ticks: i16,
+        tick: Tick,
+        changed: bool,
+    ) -> bool {
+        let interval = self
+            .intervals
+            .entry(entity)
+            .or_default()
+            .entry(kind)
+            .or_insert(SendInterval {
+                last_send: tick,
+                pending: false,
+            });
+        interval.pending |= changed;
+        if !interval.pending || tick - interval.last_send < interval_ticks {
+            return false;
+        }
+        interval.last_send = tick;
+        interval.pending = false;
+        true
+    }
+
+    /// Stop tracking an entity (for example because it was despawned)
+    pub(crate) fn remove_entity(&mut self, entity: Entity) {
+        self.intervals.remove(&entity);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::protocol::Component1;
+
+    #[test]
+    fn test_interval_ticks() {
+        let tick_duration = Duration::from_millis(10);
+        assert_eq!(
+            ReplicationMode::OnChange.interval_ticks(tick_duration),
+            None
+        );
+        assert_eq!(ReplicationMode::Once.interval_ticks(tick_duration), None);
+        assert_eq!(
+            ReplicationMode::EveryInterval(Duration::from_millis(33)).interval_ticks(tick_duration),
+            Some(4)
+        );
+        assert_eq!(
+            ReplicationMode::EveryInterval(Duration::default()).interval_ticks(tick_duration),
+            Some(1)
+        );
+    }
+
+    /// The changes are sent at most once per interval, and a change that happens during the interval
+    /// is sent once the interval elapses
+    #[test]
+    fn test_send_interval() {
+        let mut manager = SendIntervalManager::default();
+        let kind = ComponentKind::of::<Component1>();
+        let entity = Entity::from_raw(1);
+
+        // newly seen entities were just sent
+        assert!(!manager.should_send(entity, kind, 3, Tick(0), true));
+        assert!(!manager.should_send(entity, kind, 3, Tick(1), false));
+        // the change of tick 0 is sent once the interval elapsed
+        assert!(manager.should_send(entity, kind, 3, Tick(3), false));
+        // nothing changed
+        assert!(!manager.should_send(entity, kind, 3, Tick(6), false));
+        // the component changes every tick
+        assert!(manager.should_send(entity, kind, 3, Tick(7), true));
+        assert!(!manager.should_send(entity, kind, 3, Tick(8), true));
+        assert!(!manager.should_send(entity, kind, 3, Tick(9), true));
+        assert!(manager.should_send(entity, kind, 3, Tick(10), true));
+
+        // the entity is tracked again from scratch
+        manager.remove_entity(entity);
+        assert!(!manager.should_send(entity, kind, 3, Tick(11), true));
+    }
+}
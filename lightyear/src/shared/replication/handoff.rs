@@ -0,0 +1,249 @@
+//! Visual continuity between two replicated entities, when one entity takes over from another.
+//!
+//! When a player enters a vehicle, the server usually stops replicating the player entity and the vehicle (or a
+//! "player in vehicle" entity) takes over. On the clients, the two interpolated entities are unrelated: the
+//! player disappears as soon as its despawn is received, while the interpolation timeline still displays an
+//! earlier tick, and the new entity appears whenever it has enough snapshots. This causes frames that show both
+//! entities, or neither.
+//!
+//! [`replicate_handoff(from, to, tick)`](HandoffCommandsExt::replicate_handoff) declares on the server that `to`
+//! visually continues from `from` at `tick`:
+//! - the server despawns `from` at `tick` (with a [`ReplicatedLifetime`]), and replicates a [`ReplicatedHandoff`]
+//!   on `to`.
+//! - the client swaps the two interpolated entities on the frame where its interpolation timeline reaches `tick`:
+//!   the interpolated `from` entity (marked with [`HandoffSource`]) is kept, and keeps being interpolated, until that
+//!   frame even if its despawn was already received. The interpolated `to` entity (marked with [`HandoffTarget`])
+//!   only receives its [`Full`](crate::client::components::ComponentSyncMode::Full) interpolated components on that
+//!   frame, if they weren't already displayed.
+//! - if a [`HandoffFn`] is registered for a component with
+//!   [`add_handoff_fn`](crate::protocol::component::ComponentRegistration::add_handoff_fn), the interpolation of
+//!   `to` is seeded with the recent samples of `from` (transformed by the function, for example to add the
+//!   offset of a seat), so that `to` starts from where `from` was.
+//!
+//! ```rust,ignore
+//! let tick = tick_manager.tick() + 5;
+//! let vehicle_seat = commands.spawn((Position(player_position + SEAT_OFFSET), Replicate::default())).id();
+//! commands.replicate_handoff(player, vehicle_seat, tick);
+//! ```
+//!
+//! The handoff must reach the clients before the despawn of `from`, so `tick` should be a few ticks in the future.
+//! Handoffs only apply to the interpolated entities.
+use bevy::ecs::entity::MapEntities;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use tracing::trace;
+
+use crate::client::components::{Confirmed, SyncComponent};
+use crate::client::connection::ConnectionManager;
+use crate::client::interpolation::interpolate::InterpolateStatus;
+use crate::client::interpolation::interpolation_history::ConfirmedHistory;
+use crate::client::interpolation::resource::InterpolationManager;
+use crate::client::interpolation::Interpolated;
+use crate::protocol::component::ComponentRegistry;
+use crate::shared::replication::lifetime::ReplicatedLifetime;
+use crate::shared::tick_manager::{Tick, TickManager};
+
+/// Function applied to the samples of the source of a handoff to seed the interpolation of the target
+/// (for example to add the offset of a seat)
+pub type HandoffFn<C> = fn(source: &C) -> C;
+
+/// Replicated on the target of a handoff: the entity visually continues from `from` at `tick`.
+///
+/// See the [module-level documentation](crate::shared::replication::handoff) for more details.
+#[derive(Component, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Reflect)]
+#[reflect(Component)]
+pub struct ReplicatedHandoff {
+    /// Entity that the target takes over from
+    pub from: Entity,
+    /// Server tick at which the target replaces `from`
+    pub tick: Tick,
+}
+
+impl MapEntities for ReplicatedHandoff {
+    fn map_entities<M: EntityMapper>(&mut self, entity_mapper: &mut M) {
+        self.from = entity_mapper.map_entity(self.from);
+    }
+}
+
+/// Marker for an interpolated entity that is replaced by `target` when the interpolation timeline reaches `tick`
+#[derive(Component, Clone, Copy, Debug, PartialEq, Reflect)]
+pub struct HandoffSource {
+    /// Interpolated entity that takes over
+    pub target: Entity,
+    pub tick: Tick,
+}
+
+/// Marker for an interpolated entity that replaces `source` when the interpolation timeline reaches `tick`.
+///
+/// The [`Full`](crate::client::components::ComponentSyncMode::Full) interpolated components of the entity are not
+/// inserted until then.
+#[derive(Component, Clone, Copy, Debug, PartialEq, Reflect)]
+pub struct HandoffTarget {
+    /// Interpolated entity that is replaced
+    pub source: Entity,
+    pub tick: Tick,
+}
+
+pub trait HandoffCommandsExt {
+    /// Declare that the replicated entity `to` visually continues from the replicated entity `from` at `tick`.
+    ///
+    /// `from` is despawned at `tick`, and the clients swap the two entities when their interpolation timeline
+    /// reaches `tick`.
+    fn replicate_handoff(&mut self, from: Entity, to: Entity, tick: Tick);
+}
+
+impl HandoffCommandsExt for Commands<'_, '_> {
+    fn replicate_handoff(&mut self, from: Entity, to: Entity, tick: Tick) {
+        self.add(move |world: &mut World| {
+            if let Some(mut entity_mut) = world.get_entity_mut(to) {
+                entity_mut.insert(ReplicatedHandoff { from, tick });
+            }
+            if let Some(mut entity_mut) = world.get_entity_mut(from) {
+                entity_mut.insert(ReplicatedLifetime::new(tick));
+            }
+        });
+    }
+}
+
+/// Link the interpolated entities of the handoffs that were received, if the interpolation timeline didn't
+/// reach the handoff tick yet
+pub(crate) fn link_handoffs(
+    mut commands: Commands,
+    tick_manager: Res<TickManager>,
+    connection: Res<ConnectionManager>,
+    targets: Query<(&Confirmed, &ReplicatedHandoff), Changed<ReplicatedHandoff>>,
+    sources: Query<&Confirmed>,
+) {
+    let interpolation_tick = connection
+        .sync_manager
+        .interpolation_tick(tick_manager.as_ref());
+    for (confirmed, handoff) in targets.iter() {
+        if handoff.tick <= interpolation_tick {
+            continue;
+        }
+        let Some(target) = confirmed.interpolated else {
+            continue;
+        };
+        let Some(source) = sources
+            .get(handoff.from)
+            .ok()
+            .and_then(|confirmed| confirmed.interpolated)
+        else {
+            continue;
+        };
+        trace!(?source, ?target, tick = ?handoff.tick, "Linking the interpolated entities of a handoff");
+        commands.entity(source).insert(HandoffSource {
+            target,
+            tick: handoff.tick,
+        });
+        commands.entity(target).insert(HandoffTarget {
+            source,
+            tick: handoff.tick,
+        });
+    }
+}
+
+/// Seed the interpolation of the targets of new handoffs with the samples of their source.
+///
+/// Only the targets that didn't receive any update since they were spawned are seeded: the samples of the
+/// source that are older than the confirmed tick of the target are transformed with the [`HandoffFn`] of the
+/// component and added to the history of the target.
+pub(crate) fn seed_handoff_history<C: SyncComponent>(
+    component_registry: Res<ComponentRegistry>,
+    mut targets: Query<
+        (
+            &Interpolated,
+            &HandoffTarget,
+            &mut ConfirmedHistory<C>,
+            &mut InterpolateStatus<C>,
+        ),
+        (Added<HandoffTarget>, Without<C>),
+    >,
+    sources: Query<(&ConfirmedHistory<C>, &InterpolateStatus<C>), Without<HandoffTarget>>,
+    confirmed_query: Query<&Confirmed>,
+) {
+    let Some(handoff_fn) = component_registry.handoff_fn::<C>() else {
+        return;
+    };
+    for (interpolated, handoff, mut history, mut status) in targets.iter_mut() {
+        if !history.buffer.is_empty() || status.end.is_some() {
+            continue;
+        }
+        let (Ok((source_history, source_status)), Ok(confirmed)) = (
+            sources.get(handoff.source),
+            confirmed_query.get(interpolated.confirmed_entity),
+        ) else {
+            continue;
+        };
+        // the start of a new interpolated entity is the confirmed value
+        let Some((_, value)) = status.start.take() else {
+            continue;
+        };
+        history.buffer.push(confirmed.tick, value);
+        let samples = [
+            &source_status.previous,
+            &source_status.start,
+            &source_status.end,
+        ]
+        .into_iter()
+        .flatten()
+        .map(|(tick, value)| (*tick, value))
+        .chain(
+            source_history
+                .buffer
+                .heap
+                .iter()
+                .map(|item| (item.key, &item.item)),
+        );
+        for (tick, value) in samples {
+            if tick < confirmed.tick && tick < handoff.tick {
+                history.buffer.push(tick, handoff_fn(value));
+            }
+        }
+    }
+}
+
+/// Swap the interpolated entities of the handoffs whose tick was reached by the interpolation timeline.
+///
+/// The targets got their components in [`PrepareInterpolation`](crate::client::interpolation::plugin::InterpolationSet::PrepareInterpolation)
+/// on the same frame.
+pub(crate) fn complete_handoffs(
+    mut commands: Commands,
+    tick_manager: Res<TickManager>,
+    connection: Res<ConnectionManager>,
+    mut manager: ResMut<InterpolationManager>,
+    sources: Query<(Entity, &Interpolated, &HandoffSource)>,
+    targets: Query<(Entity, &HandoffTarget)>,
+    mut confirmed_query: Query<&mut Confirmed>,
+) {
+    let interpolation_tick = connection
+        .sync_manager
+        .interpolation_tick(tick_manager.as_ref());
+    for (entity, interpolated, handoff) in sources.iter() {
+        if handoff.tick > interpolation_tick {
+            continue;
+        }
+        trace!(
+            ?entity,
+            ?interpolation_tick,
+            "Despawning the source of a handoff"
+        );
+        // unlink the source from its confirmed entity, if it wasn't despawned yet
+        if let Ok(mut confirmed) = confirmed_query.get_mut(interpolated.confirmed_entity) {
+            if confirmed.interpolated == Some(entity) {
+                confirmed.interpolated = None;
+                manager
+                    .interpolated_entity_map
+                    .get_mut()
+                    .confirmed_to_interpolated
+                    .remove(&interpolated.confirmed_entity);
+            }
+        }
+        commands.entity(entity).despawn_recursive();
+    }
+    for (entity, handoff) in targets.iter() {
+        if handoff.tick <= interpolation_tick {
+            commands.entity(entity).remove::<HandoffTarget>();
+        }
+    }
+}
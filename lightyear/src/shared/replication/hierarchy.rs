@@ -1,34 +1,68 @@
 //! This module is responsible for making sure that parent-children hierarchies are replicated correctly.
 use crate::client::prediction::pre_prediction::PrePredictionSet;
 use crate::client::replication::send::ReplicateToServer;
-use bevy::ecs::entity::MapEntities;
+use bevy::ecs::entity::{Entities, MapEntities};
 use bevy::prelude::*;
 use serde::{Deserialize, Serialize};
 
 use crate::prelude::server::ControlledBy;
-use crate::prelude::{MainSet, NetworkRelevanceMode, PrePredicted, Replicating, ReplicationGroup};
+use crate::prelude::{
+    MainSet, NetworkRelevanceMode, PrePredicted, Replicated, Replicating, ReplicationGroup,
+};
 use crate::server::replication::send::SyncTarget;
 use crate::shared::replication::components::{ReplicateHierarchy, ReplicationTarget};
-use crate::shared::replication::{ReplicationPeer, ReplicationSend};
+use crate::shared::replication::{ReplicationReceive, ReplicationSend};
 use crate::shared::sets::{InternalMainSet, InternalReplicationSet};
 
-/// This component can be added to an entity to replicate the entity's hierarchy to the remote world.
+/// This component is used to replicate the entity's hierarchy to the remote world.
+///
+/// It is added automatically to the replicated entities with [`ReplicateHierarchy`] that have a parent.
 /// The `ParentSync` component will be updated automatically when the `Parent` component changes,
 /// and the entity's hierarchy will automatically be updated when the `ParentSync` component changes.
+/// It only contains the parent if the parent is also replicated.
 ///
 /// Updates entity's `Parent` component on change.
 /// Removes the parent if `None`.
+/// If the parent was not received yet (for example because it is in a different [`ReplicationGroup`]),
+/// the parent is set once it is spawned.
 #[derive(Component, Default, Reflect, Clone, Copy, Serialize, Deserialize, Debug, PartialEq)]
-pub struct ParentSync(Option<Entity>);
+pub struct ParentSync {
+    parent: Option<Entity>,
+    /// Entity of the parent in the remote world, before it was mapped to a local entity.
+    ///
+    /// It is only set on the receiving side, to find the local parent once it is spawned
+    #[serde(skip)]
+    remote_parent: Option<Entity>,
+}
+
+impl ParentSync {
+    fn new(parent: Option<Entity>) -> Self {
+        Self {
+            parent,
+            remote_parent: None,
+        }
+    }
+
+    /// Returns the parent of the entity, if it is replicated
+    pub fn parent(&self) -> Option<Entity> {
+        self.parent
+    }
+}
 
 impl MapEntities for ParentSync {
     fn map_entities<M: EntityMapper>(&mut self, entity_mapper: &mut M) {
-        if let Some(entity) = &mut self.0 {
+        // the mapper returns the same entity if it is not mapped, so we keep the remote entity
+        self.remote_parent = self.parent;
+        if let Some(entity) = &mut self.parent {
             *entity = entity_mapper.map_entity(*entity);
         }
     }
 }
 
+/// Marker for the entities whose parent was not received yet
+#[derive(Component, Debug)]
+struct PendingParent;
+
 pub struct HierarchySendPlugin<R> {
     _marker: std::marker::PhantomData<R>,
 }
@@ -93,7 +127,7 @@ impl<R: ReplicationSend> HierarchySendPlugin<R> {
                         // the entire hierarchy is replicated as a single group, that uses the parent's entity as the group id
                         ReplicationGroup::new_id(parent_entity.to_bits()),
                         ReplicateHierarchy { recursive: true },
-                        ParentSync(None),
+                        ParentSync::default(),
                     ));
                     // On the client, we want to add the PrePredicted component to the children
                     // The `client_entity` will be filled in a PrePrediction system
@@ -127,21 +161,44 @@ impl<R: ReplicationSend> HierarchySendPlugin<R> {
         }
     }
 
+    /// Add ParentSync to the replicated entities that have a parent
+    ///
+    /// This only runs on the sending side
+    fn add_parent_sync(
+        mut commands: Commands,
+        query: Query<
+            Entity,
+            (
+                With<Parent>,
+                With<Replicating>,
+                With<ReplicateHierarchy>,
+                Without<ParentSync>,
+            ),
+        >,
+    ) {
+        for entity in query.iter() {
+            commands.entity(entity).insert(ParentSync::default());
+        }
+    }
+
     /// Update ParentSync if the hierarchy changed
     /// (run this in post-update before replicating, to account for any hierarchy changed initiated by the user)
     ///
     /// This only runs on the sending side
     fn update_parent_sync(
-        mut query: Query<(Ref<Parent>, &mut ParentSync), With<ReplicateHierarchy>>,
+        mut query: Query<(&Parent, &mut ParentSync), With<ReplicateHierarchy>>,
+        replicating: Query<(), With<Replicating>>,
     ) {
         for (parent, mut parent_sync) in query.iter_mut() {
-            if parent.is_changed() || parent_sync.is_added() {
+            // the parent can only be mapped on the remote if it is replicated
+            let parent = replicating.contains(**parent).then_some(**parent);
+            if parent_sync.parent != parent {
                 trace!(
                     ?parent,
                     ?parent_sync,
                     "Update parent sync because hierarchy has changed"
                 );
-                parent_sync.set_if_neq(ParentSync(Some(**parent)));
+                *parent_sync = ParentSync::new(parent);
             }
         }
     }
@@ -154,7 +211,7 @@ impl<R: ReplicationSend> HierarchySendPlugin<R> {
         mut hierarchy: Query<&mut ParentSync, With<ReplicateHierarchy>>,
     ) {
         if let Ok(mut parent_sync) = hierarchy.get_mut(trigger.entity()) {
-            parent_sync.0 = None;
+            parent_sync.parent = None;
         }
     }
 }
@@ -167,6 +224,7 @@ impl<R: ReplicationSend> Plugin for HierarchySendPlugin<R> {
             (
                 // we copy PrePredicted to children before we set the correct value of the PrePredicted entity
                 Self::propagate_replicate.before(PrePredictionSet::Fill),
+                Self::add_parent_sync,
                 Self::update_parent_sync,
             )
                 .chain()
@@ -190,25 +248,71 @@ impl<R> Default for HierarchyReceivePlugin<R> {
     }
 }
 
-impl<R> HierarchyReceivePlugin<R> {
-    /// Update parent/children hierarchy if parent_sync changed
+impl<R: ReplicationReceive> HierarchyReceivePlugin<R> {
+    /// Update parent/children hierarchy if parent_sync changed, or if the parent of an entity
+    /// was spawned after the entity
     ///
     /// This only runs on the receiving side
     fn update_parent(
         mut commands: Commands,
-        hierarchy: Query<
-            (Entity, &ParentSync, Option<&Parent>),
-            (Changed<ParentSync>, Without<ReplicationTarget>),
+        manager: Res<R>,
+        entities: &Entities,
+        mut hierarchy: Query<
+            (
+                Entity,
+                &mut ParentSync,
+                Option<&Parent>,
+                Option<&Replicated>,
+                Has<PendingParent>,
+            ),
+            (
+                Or<(Changed<ParentSync>, With<PendingParent>)>,
+                Without<ReplicationTarget>,
+            ),
         >,
     ) {
-        for (entity, parent_sync, parent) in hierarchy.iter() {
+        for (entity, mut parent_sync, parent, replicated, pending) in hierarchy.iter_mut() {
             trace!(
                 "update_parent: entity: {:?}, parent_sync: {:?}, parent: {:?}",
                 entity,
                 parent_sync,
                 parent
             );
-            if let Some(new_parent) = parent_sync.0 {
+            let new_parent = match (parent_sync.remote_parent, replicated) {
+                (Some(remote_parent), Some(replicated)) => {
+                    // the entity was received from a peer handled by another service
+                    let Some(entity_map) = manager.remote_entity_map(replicated.from) else {
+                        continue;
+                    };
+                    let Some(local_parent) = entity_map.get_local(remote_parent).copied() else {
+                        if !pending {
+                            debug!(
+                                ?entity,
+                                ?remote_parent,
+                                "The parent was not received yet, waiting for it to be spawned"
+                            );
+                            commands.entity(entity).insert(PendingParent);
+                        }
+                        continue;
+                    };
+                    // the mapping failed if the parent was spawned after the entity
+                    parent_sync.bypass_change_detection().parent = Some(local_parent);
+                    Some(local_parent)
+                }
+                _ => parent_sync.parent,
+            };
+            if pending {
+                commands.entity(entity).remove::<PendingParent>();
+            }
+            if let Some(new_parent) = new_parent {
+                if !entities.contains(new_parent) {
+                    debug!(
+                        ?entity,
+                        ?new_parent,
+                        "The parent of the entity was despawned"
+                    );
+                    continue;
+                }
                 if parent.filter(|&parent| **parent == new_parent).is_none() {
                     commands.entity(entity).set_parent(new_parent);
                 }
@@ -219,7 +323,7 @@ impl<R> HierarchyReceivePlugin<R> {
     }
 }
 
-impl<R: ReplicationPeer> Plugin for HierarchyReceivePlugin<R> {
+impl<R: ReplicationReceive> Plugin for HierarchyReceivePlugin<R> {
     fn build(&self, app: &mut App) {
         // REFLECTION
         app.register_type::<ParentSync>();
@@ -241,17 +345,46 @@ impl<R: ReplicationPeer> Plugin for HierarchyReceivePlugin<R> {
 mod tests {
     use std::ops::Deref;
 
-    use bevy::hierarchy::{BuildWorldChildren, Children, Parent};
+    use bevy::hierarchy::{BuildWorldChildren, Children, DespawnRecursiveExt, Parent};
     use bevy::prelude::{default, Entity, With};
 
     use crate::prelude::client;
     use crate::prelude::server::Replicate;
-    use crate::prelude::ReplicationGroup;
+    use crate::prelude::{NetworkTarget, ReplicationGroup};
     use crate::shared::replication::components::ReplicateHierarchy;
+    use crate::shared::replication::components::ReplicationTarget;
     use crate::shared::replication::hierarchy::ParentSync;
     use crate::tests::protocol::*;
     use crate::tests::stepper::{BevyStepper, Step};
 
+    fn client_entity(stepper: &BevyStepper, server_entity: Entity) -> Option<Entity> {
+        stepper
+            .client_app
+            .world()
+            .resource::<client::ConnectionManager>()
+            .replication_receiver
+            .remote_entity_map
+            .get_local(server_entity)
+            .copied()
+    }
+
+    fn client_parent(stepper: &BevyStepper, client_entity: Entity) -> Option<Entity> {
+        stepper
+            .client_app
+            .world()
+            .get::<Parent>(client_entity)
+            .map(Parent::get)
+    }
+
+    /// Replicate each entity in its own group, without replicating its children
+    fn replicate_in_group(id: u64) -> Replicate {
+        Replicate {
+            hierarchy: ReplicateHierarchy { recursive: false },
+            group: ReplicationGroup::new_id(id),
+            ..default()
+        }
+    }
+
     fn setup_hierarchy() -> (BevyStepper, Entity, Entity, Entity) {
         let mut stepper = BevyStepper::default();
         let child = stepper.server_app.world_mut().spawn(Component3(0.0)).id();
@@ -308,7 +441,7 @@ mod tests {
             .get_single(stepper.client_app.world())
             .unwrap();
 
-        assert_eq!(client_parent_sync.0, Some(client_grandparent));
+        assert_eq!(client_parent_sync.parent(), Some(client_grandparent));
         assert_eq!(*client_parent_component.deref(), client_grandparent);
 
         // remove the hierarchy on the sender side
@@ -325,8 +458,9 @@ mod tests {
                 .server_app
                 .world_mut()
                 .entity_mut(parent)
-                .get::<ParentSync>(),
-            Some(&ParentSync(None))
+                .get::<ParentSync>()
+                .map(ParentSync::parent),
+            Some(None)
         );
 
         // 2. make sure that the parent has been removed on the receiver side, and that ParentSync has been updated
@@ -335,8 +469,9 @@ mod tests {
                 .client_app
                 .world_mut()
                 .entity_mut(client_parent)
-                .get::<ParentSync>(),
-            Some(&ParentSync(None))
+                .get::<ParentSync>()
+                .map(ParentSync::parent),
+            Some(None)
         );
         assert_eq!(
             stepper
@@ -473,8 +608,166 @@ mod tests {
                 .server_app
                 .world()
                 .get::<ParentSync>(server_child)
-                .unwrap(),
-            &ParentSync(Some(server_parent))
+                .unwrap()
+                .parent(),
+            Some(server_parent)
+        );
+    }
+
+    /// The child is received before its parent: the parent is set once the parent is spawned
+    #[test]
+    fn test_parent_received_after_child() {
+        let mut stepper = BevyStepper::default();
+        let server_parent = stepper
+            .server_app
+            .world_mut()
+            .spawn((
+                Component1(0.0),
+                Replicate {
+                    target: ReplicationTarget {
+                        target: NetworkTarget::None,
+                    },
+                    ..replicate_in_group(1)
+                },
+            ))
+            .id();
+        let server_child = stepper
+            .server_app
+            .world_mut()
+            .spawn((Component2(0.0), replicate_in_group(2)))
+            .set_parent(server_parent)
+            .id();
+        for _ in 0..10 {
+            stepper.frame_step();
+        }
+        // ParentSync is added automatically to the replicated children
+        assert_eq!(
+            stepper
+                .server_app
+                .world()
+                .get::<ParentSync>(server_child)
+                .map(ParentSync::parent),
+            Some(Some(server_parent))
         );
+        let client_child = client_entity(&stepper, server_child).unwrap();
+        assert!(client_entity(&stepper, server_parent).is_none());
+        assert_eq!(client_parent(&stepper, client_child), None);
+
+        stepper
+            .server_app
+            .world_mut()
+            .entity_mut(server_parent)
+            .insert(ReplicationTarget {
+                target: NetworkTarget::All,
+            });
+        for _ in 0..10 {
+            stepper.frame_step();
+        }
+        let client_parent_entity = client_entity(&stepper, server_parent).unwrap();
+        assert_eq!(
+            client_parent(&stepper, client_child),
+            Some(client_parent_entity)
+        );
+        assert_eq!(
+            stepper
+                .client_app
+                .world()
+                .get::<ParentSync>(client_child)
+                .unwrap()
+                .parent(),
+            Some(client_parent_entity)
+        );
+    }
+
+    /// Re-parenting, orphaning and despawning the parent are replicated
+    #[test]
+    fn test_reparent_and_despawn() {
+        let mut stepper = BevyStepper::default();
+        let server_parent_1 = stepper
+            .server_app
+            .world_mut()
+            .spawn((Component1(0.0), replicate_in_group(1)))
+            .id();
+        let server_parent_2 = stepper
+            .server_app
+            .world_mut()
+            .spawn((Component1(1.0), replicate_in_group(2)))
+            .id();
+        // the parent of this entity is not replicated
+        let server_local_parent = stepper.server_app.world_mut().spawn_empty().id();
+        let server_child = stepper
+            .server_app
+            .world_mut()
+            .spawn((Component2(0.0), replicate_in_group(3)))
+            .set_parent(server_parent_1)
+            .id();
+        for _ in 0..10 {
+            stepper.frame_step();
+        }
+        let client_parent_1 = client_entity(&stepper, server_parent_1).unwrap();
+        let client_parent_2 = client_entity(&stepper, server_parent_2).unwrap();
+        let client_child = client_entity(&stepper, server_child).unwrap();
+        assert_eq!(client_parent(&stepper, client_child), Some(client_parent_1));
+
+        // re-parent
+        stepper
+            .server_app
+            .world_mut()
+            .entity_mut(server_child)
+            .set_parent(server_parent_2);
+        for _ in 0..10 {
+            stepper.frame_step();
+        }
+        assert_eq!(client_parent(&stepper, client_child), Some(client_parent_2));
+
+        // a parent that is not replicated orphans the entity on the client
+        stepper
+            .server_app
+            .world_mut()
+            .entity_mut(server_child)
+            .set_parent(server_local_parent);
+        for _ in 0..10 {
+            stepper.frame_step();
+        }
+        assert_eq!(client_parent(&stepper, client_child), None);
+        assert!(stepper
+            .client_app
+            .world()
+            .get::<Children>(client_parent_2)
+            .is_none());
+
+        // despawning the parent despawns the child on the client
+        stepper
+            .server_app
+            .world_mut()
+            .entity_mut(server_child)
+            .set_parent(server_parent_1);
+        for _ in 0..10 {
+            stepper.frame_step();
+        }
+        assert_eq!(client_parent(&stepper, client_child), Some(client_parent_1));
+        stepper
+            .server_app
+            .world_mut()
+            .entity_mut(server_parent_1)
+            .despawn_recursive();
+        for _ in 0..10 {
+            stepper.frame_step();
+        }
+        assert!(stepper
+            .client_app
+            .world()
+            .get_entity(client_parent_1)
+            .is_none());
+        assert!(stepper
+            .client_app
+            .world()
+            .get_entity(client_child)
+            .is_none());
+        assert!(stepper
+            .client_app
+            .world()
+            .get_entity(client_parent_2)
+            .is_some());
     }
 }
@@ -0,0 +1,195 @@
+//! Delta compression of keyed collections (maps), where only the keys that changed are sent.
+//!
+//! A component that wraps a large map (for example an inventory with hundreds of slots) would be sent
+//! entirely every time a single slot changes. A [`KeyedDiffable`] collection computes the list of keys that
+//! were inserted, updated or removed between two states instead, and every [`KeyedDiffable`] type is
+//! [`Diffable`]: registering the component with
+//! [`add_delta_compression`](crate::protocol::component::ComponentRegistration::add_delta_compression)
+//! sends to each client only the keys that changed since the last state that the client acked.
+//!
+//! [`KeyedDiffable`] is implemented for `HashMap` and `BTreeMap`, and can be derived for a component
+//! that wraps one of them:
+//!
+//! ```rust,ignore
+//! #[derive(Component, Serialize, Deserialize, Clone, Default, PartialEq, KeyedDiffable)]
+//! struct Inventory(HashMap<SlotId, ItemStack>);
+//!
+//! app.register_component::<Inventory>(ChannelDirection::ServerToClient)
+//!     .add_delta_compression()
+//!     // re-send the full map every second, in case the client missed some changes
+//!     .add_keyframes(KeyframeConfig::new(Duration::from_secs(1)));
+//! ```
+//!
+//! The keyframes of a delta-compressed component are diffs from the empty collection, so they contain every key
+//! of the map. A change or a keyframe that is received after a more recent state was applied is not applied to
+//! the component.
+use std::collections::BTreeMap;
+use std::hash::{BuildHasher, Hash};
+
+use serde::{Deserialize, Serialize};
+
+use crate::shared::replication::delta::Diffable;
+
+/// A change of a single key of a [`KeyedDiffable`] collection
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum KeyedChange<K, V> {
+    /// The key was inserted, or its value was updated
+    Insert(K, V),
+    /// The key was removed
+    Remove(K),
+}
+
+/// A collection of values identified by a key, for which the delta between two states is the list of keys
+/// that changed
+pub trait KeyedDiffable: Clone + Default {
+    type Key: Clone + Send + Sync + 'static;
+    type Value: Clone + Send + Sync + 'static;
+
+    /// Compute the changes to go from the old state (self) to the new state (new)
+    fn keyed_diff(&self, new: &Self) -> Vec<KeyedChange<Self::Key, Self::Value>>;
+
+    /// Apply a single change to the collection
+    fn apply_keyed_change(&mut self, change: &KeyedChange<Self::Key, Self::Value>);
+}
+
+impl<T: KeyedDiffable> Diffable for T {
+    type Delta = Vec<KeyedChange<T::Key, T::Value>>;
+
+    fn base_value() -> Self {
+        T::default()
+    }
+
+    fn diff(&self, new: &Self) -> Self::Delta {
+        self.keyed_diff(new)
+    }
+
+    fn apply_diff(&mut self, delta: &Self::Delta) {
+        for change in delta {
+            self.apply_keyed_change(change);
+        }
+    }
+}
+
+/// Implementation of the [`KeyedDiffable`] methods for the map types, which all have the same API
+macro_rules! keyed_map_fns {
+    () => {
+        fn keyed_diff(&self, new: &Self) -> Vec<KeyedChange<K, V>> {
+            let removed = self
+                .keys()
+                .filter(|key| !new.contains_key(*key))
+                .map(|key| KeyedChange::Remove(key.clone()));
+            let inserted = new
+                .iter()
+                .filter(|(key, value)| self.get(*key) != Some(*value))
+                .map(|(key, value)| KeyedChange::Insert(key.clone(), value.clone()));
+            removed.chain(inserted).collect()
+        }
+
+        fn apply_keyed_change(&mut self, change: &KeyedChange<K, V>) {
+            match change {
+                KeyedChange::Insert(key, value) => {
+                    self.insert(key.clone(), value.clone());
+                }
+                KeyedChange::Remove(key) => {
+                    self.remove(key);
+                }
+            }
+        }
+    };
+}
+
+impl<K, V, S> KeyedDiffable for std::collections::HashMap<K, V, S>
+where
+    K: Clone + Eq + Hash + Send + Sync + 'static,
+    V: Clone + PartialEq + Send + Sync + 'static,
+    S: BuildHasher + Clone + Default,
+{
+    type Key = K;
+    type Value = V;
+    keyed_map_fns!();
+}
+
+impl<K, V, S> KeyedDiffable for hashbrown::HashMap<K, V, S>
+where
+    K: Clone + Eq + Hash + Send + Sync + 'static,
+    V: Clone + PartialEq + Send + Sync + 'static,
+    S: BuildHasher + Clone + Default,
+{
+    type Key = K;
+    type Value = V;
+    keyed_map_fns!();
+}
+
+impl<K, V> KeyedDiffable for BTreeMap<K, V>
+where
+    K: Clone + Ord + Send + Sync + 'static,
+    V: Clone + PartialEq + Send + Sync + 'static,
+{
+    type Key = K;
+    type Value = V;
+    keyed_map_fns!();
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::utils::HashMap;
+
+    use crate::tests::protocol::Component9;
+
+    use super::*;
+
+    #[test]
+    fn test_keyed_diff_only_contains_changed_keys() {
+        let old: BTreeMap<u16, u32> = (0..200).map(|slot| (slot, 1)).collect();
+        let mut new = old.clone();
+        new.insert(3, 2);
+        new.remove(&4);
+        new.insert(500, 1);
+
+        let delta = old.diff(&new);
+        assert_eq!(
+            delta,
+            vec![
+                KeyedChange::Remove(4),
+                KeyedChange::Insert(3, 2),
+                KeyedChange::Insert(500, 1),
+            ]
+        );
+        let mut applied = old.clone();
+        applied.apply_diff(&delta);
+        assert_eq!(applied, new);
+    }
+
+    #[test]
+    fn test_keyed_diff_hashmap() {
+        let old: HashMap<u16, u32> = (0..10).map(|slot| (slot, 1)).collect();
+        let mut new = old.clone();
+        new.insert(3, 2);
+        new.remove(&4);
+
+        let delta = old.diff(&new);
+        assert_eq!(delta.len(), 2);
+        let mut applied = old.clone();
+        applied.apply_diff(&delta);
+        assert_eq!(applied, new);
+        // a keyframe is the diff from the empty map
+        let mut from_base: HashMap<u16, u32> = HashMap::base_value();
+        from_base.apply_diff(&from_base.clone().diff(&new));
+        assert_eq!(from_base, new);
+    }
+
+    /// The derived implementation delegates to the wrapped collection
+    #[test]
+    fn test_keyed_diffable_derive() {
+        let old = Component9(BTreeMap::from([(1, 1), (2, 2)]));
+        let new = Component9(BTreeMap::from([(2, 3)]));
+        let delta = old.diff(&new);
+        assert_eq!(
+            delta,
+            vec![KeyedChange::Remove(1), KeyedChange::Insert(2, 3)]
+        );
+        let mut applied = old.clone();
+        applied.apply_diff(&delta);
+        assert_eq!(applied, new);
+    }
+}
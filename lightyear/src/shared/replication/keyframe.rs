@@ -0,0 +1,324 @@
+//! Periodic keyframes for component updates.
+//!
+//! Component updates are sent on an unreliable channel, and only when the component changes.
+//! If the packet containing the last change of a component is lost, and the component doesn't change
+//! anymore, the remote could keep a stale value for a long time.
+//!
+//! Keyframes fix this: every [`KeyframeConfig::interval`], the sender re-sends the full value of the
+//! component to each remote, even if the component did not change. To avoid bandwidth spikes, the
+//! number of keyframe bytes sent per interval (per remote and per component kind) is capped;
+//! entities that don't fit in the budget get the first claim on the budget of the next interval.
+use bevy::ecs::entity::EntityHash;
+use bevy::prelude::Entity;
+use bevy::utils::{hashbrown, Duration, HashMap};
+
+use crate::prelude::Tick;
+use crate::protocol::component::ComponentKind;
+
+type EntityHashMap<K, V> = hashbrown::HashMap<K, V, EntityHash>;
+
+type EntityHashSet<K> = hashbrown::HashSet<K, EntityHash>;
+
+/// Configuration of the keyframes for a replicated component.
+///
+/// Enable it with [`ComponentRegistration::add_keyframes`](crate::protocol::component::ComponentRegistration::add_keyframes).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct KeyframeConfig {
+    /// Duration after which the full component value is sent again to each client, even if it didn't change
+    pub interval: Duration,
+    /// Maximum number of keyframe bytes sent to each client during one interval.
+    ///
+    /// The first keyframe of each interval is always sent, even if it is bigger than the budget
+    /// (or if the budget is 0).
+    pub max_bytes_per_interval: usize,
+}
+
+impl KeyframeConfig {
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            max_bytes_per_interval: usize::MAX,
+        }
+    }
+
+    pub fn with_max_bytes_per_interval(mut self, max_bytes_per_interval: usize) -> Self {
+        self.max_bytes_per_interval = max_bytes_per_interval;
+        self
+    }
+
+    /// Convert the config to a [`KeyframeBudget`] expressed in ticks
+    pub(crate) fn budget(&self, tick_duration: Duration) -> KeyframeBudget {
+        let interval_ticks = if tick_duration.is_zero() {
+            1
+        } else {
+            self.interval.as_nanos().div_ceil(tick_duration.as_nanos())
+        };
+        KeyframeBudget {
+            interval_ticks: interval_ticks.clamp(1, i16::MAX as u128) as i16,
+            max_bytes: self.max_bytes_per_interval,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct KeyframeBudget {
+    pub(crate) interval_ticks: i16,
+    pub(crate) max_bytes: usize,
+}
+
+#[derive(Debug, Default)]
+struct KeyframeInterval {
+    start: Tick,
+    bytes_sent: usize,
+    /// Size of the last keyframe sent for this component kind, used to estimate the size of the keyframes
+    /// of the priority entities
+    last_bytes: usize,
+    /// Entities that were due for a keyframe during this interval but didn't fit in the budget
+    starved: EntityHashSet<Entity>,
+    /// Entities that were starved during the previous interval. The budget needed for their keyframes is
+    /// reserved, so that the keyframes are spread across intervals in a round-robin fashion.
+    priority: EntityHashSet<Entity>,
+}
+
+/// Keeps track of the keyframes sent to a single remote
+#[derive(Debug, Default)]
+pub(crate) struct KeyframeManager {
+    /// Current keyframe interval for each component kind
+    intervals: HashMap<ComponentKind, KeyframeInterval>,
+    /// Tick at which we last sent a keyframe for each entity and component kind
+    last_keyframe: EntityHashMap<Entity, HashMap<ComponentKind, Tick>>,
+}
+
+impl KeyframeManager {
+    /// Returns true if we should send a keyframe for the entity's component at this tick.
+    ///
+    /// A keyframe is due if we haven't sent one in the last interval; it is sent only if the byte budget
+    /// of the current interval is not exhausted, except for the first keyframe of the interval. The entities
+    /// that were starved during the previous interval have the first claim on the budget: the other entities
+    /// only use what remains after their keyframes.
+    /// The first time we see an entity, we consider that it was just sent in full (inserts contain the full value).
+    pub(crate) fn should_send_keyframe(
+        &mut self,
+        entity: Entity,
+        kind: ComponentKind,
+        budget: KeyframeBudget,
+        tick: Tick,
+    ) -> bool {
+        let last_keyframe = *self
+            .last_keyframe
+            .entry(entity)
+            .or_default()
+            .entry(kind)
+            .or_insert(tick);
+        let interval = self
+            .intervals
+            .entry(kind)
+            .or_insert_with(|| KeyframeInterval {
+                start: tick,
+                ..Default::default()
+            });
+        if interval_elapsed(tick, interval.start, budget) {
+            interval.start = tick;
+            interval.bytes_sent = 0;
+            interval.priority = std::mem::take(&mut interval.starved);
+        }
+        if !interval_elapsed(tick, last_keyframe, budget) {
+            return false;
+        }
+        let is_priority = interval.priority.contains(&entity);
+        // the first keyframe of the interval is always sent (to a priority entity if there is one)
+        let is_first = interval.bytes_sent == 0 && (is_priority || interval.priority.is_empty());
+        let reserved = if is_priority {
+            0
+        } else {
+            interval.priority.len() * interval.last_bytes
+        };
+        if !is_first && interval.bytes_sent.saturating_add(reserved) >= budget.max_bytes {
+            interval.starved.insert(entity);
+            return false;
+        }
+        true
+    }
+
+    /// Record that a keyframe of `bytes` bytes was sent for the entity's component
+    pub(crate) fn record_keyframe(
+        &mut self,
+        entity: Entity,
+        kind: ComponentKind,
+        tick: Tick,
+        bytes: usize,
+    ) {
+        self.last_keyframe
+            .entry(entity)
+            .or_default()
+            .insert(kind, tick);
+        if let Some(interval) = self.intervals.get_mut(&kind) {
+            interval.bytes_sent += bytes;
+            interval.last_bytes = bytes;
+            interval.priority.remove(&entity);
+        }
+    }
+
+    /// Stop tracking an entity (for example because it was despawned)
+    pub(crate) fn remove_entity(&mut self, entity: Entity) {
+        self.last_keyframe.remove(&entity);
+        for interval in self.intervals.values_mut() {
+            interval.starved.remove(&entity);
+            interval.priority.remove(&entity);
+        }
+    }
+
+    /// Stop tracking the component of an entity (for example because it was removed)
+    pub(crate) fn remove_component(&mut self, entity: Entity, kind: ComponentKind) {
+        if let Some(kinds) = self.last_keyframe.get_mut(&entity) {
+            kinds.remove(&kind);
+            if kinds.is_empty() {
+                self.last_keyframe.remove(&entity);
+            }
+        }
+        if let Some(interval) = self.intervals.get_mut(&kind) {
+            interval.starved.remove(&entity);
+            interval.priority.remove(&entity);
+        }
+    }
+}
+
+/// Returns true if at least one interval elapsed since `since`.
+///
+/// Tick differences wrap around: a negative difference means that more than `i16::MAX` ticks elapsed
+fn interval_elapsed(tick: Tick, since: Tick, budget: KeyframeBudget) -> bool {
+    let elapsed = tick - since;
+    elapsed < 0 || elapsed >= budget.interval_ticks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::protocol::Component1;
+
+    #[test]
+    fn test_keyframe_interval_ticks() {
+        let config = KeyframeConfig::new(Duration::from_millis(45));
+        assert_eq!(config.budget(Duration::from_millis(10)).interval_ticks, 5);
+        assert_eq!(config.budget(Duration::default()).interval_ticks, 1);
+    }
+
+    /// Keyframes are sent once per interval, and are spread across intervals when the
+    /// byte budget is exhausted
+    #[test]
+    fn test_keyframe_budget_round_robin() {
+        let mut manager = KeyframeManager::default();
+        let kind = ComponentKind::of::<Component1>();
+        let budget = KeyframeBudget {
+            interval_ticks: 5,
+            max_bytes: 10,
+        };
+        let entity_1 = Entity::from_raw(1);
+        let entity_2 = Entity::from_raw(2);
+
+        // newly seen entities are not due
+        assert!(!manager.should_send_keyframe(entity_1, kind, budget, Tick(0)));
+        assert!(!manager.should_send_keyframe(entity_2, kind, budget, Tick(0)));
+
+        // both entities are due, but the budget only allows one keyframe
+        assert!(manager.should_send_keyframe(entity_1, kind, budget, Tick(5)));
+        manager.record_keyframe(entity_1, kind, Tick(5), 10);
+        assert!(!manager.should_send_keyframe(entity_2, kind, budget, Tick(5)));
+
+        // the second entity was starved, so it gets its keyframe first in the next interval,
+        // even though the first entity is also due again
+        assert!(!manager.should_send_keyframe(entity_1, kind, budget, Tick(10)));
+        assert!(manager.should_send_keyframe(entity_2, kind, budget, Tick(10)));
+        manager.record_keyframe(entity_2, kind, Tick(10), 10);
+
+        // and then the first entity again
+        assert!(manager.should_send_keyframe(entity_1, kind, budget, Tick(15)));
+        manager.record_keyframe(entity_1, kind, Tick(15), 10);
+        assert!(!manager.should_send_keyframe(entity_2, kind, budget, Tick(15)));
+    }
+    /// The starved entities only reserve the budget of their own keyframes: the other entities
+    /// keep sending while budget remains
+    #[test]
+    fn test_keyframe_budget_priority_reservation() {
+        let mut manager = KeyframeManager::default();
+        let kind = ComponentKind::of::<Component1>();
+        let budget = KeyframeBudget {
+            interval_ticks: 5,
+            max_bytes: 20,
+        };
+        let entity_1 = Entity::from_raw(1);
+        let entity_2 = Entity::from_raw(2);
+        let entity_3 = Entity::from_raw(3);
+        for entity in [entity_1, entity_2, entity_3] {
+            assert!(!manager.should_send_keyframe(entity, kind, budget, Tick(0)));
+        }
+
+        // the third entity doesn't fit in the budget
+        for entity in [entity_1, entity_2] {
+            assert!(manager.should_send_keyframe(entity, kind, budget, Tick(5)));
+            manager.record_keyframe(entity, kind, Tick(5), 10);
+        }
+        assert!(!manager.should_send_keyframe(entity_3, kind, budget, Tick(5)));
+
+        // the budget of the third entity is reserved, the first entity uses the rest
+        assert!(manager.should_send_keyframe(entity_1, kind, budget, Tick(10)));
+        manager.record_keyframe(entity_1, kind, Tick(10), 10);
+        assert!(!manager.should_send_keyframe(entity_2, kind, budget, Tick(10)));
+        assert!(manager.should_send_keyframe(entity_3, kind, budget, Tick(10)));
+        manager.record_keyframe(entity_3, kind, Tick(10), 10);
+
+        // the component of the second entity (starved at tick 10) is removed: it doesn't reserve any budget
+        manager.remove_component(entity_2, kind);
+        assert!(!manager.last_keyframe.contains_key(&entity_2));
+        for entity in [entity_1, entity_3] {
+            assert!(manager.should_send_keyframe(entity, kind, budget, Tick(15)));
+            manager.record_keyframe(entity, kind, Tick(15), 10);
+        }
+    }
+
+    /// The first keyframe of an interval is sent even if the budget is 0
+    #[test]
+    fn test_keyframe_zero_budget() {
+        let mut manager = KeyframeManager::default();
+        let kind = ComponentKind::of::<Component1>();
+        let budget = KeyframeBudget {
+            interval_ticks: 5,
+            max_bytes: 0,
+        };
+        let entity_1 = Entity::from_raw(1);
+        let entity_2 = Entity::from_raw(2);
+        for entity in [entity_1, entity_2] {
+            assert!(!manager.should_send_keyframe(entity, kind, budget, Tick(0)));
+        }
+
+        assert!(manager.should_send_keyframe(entity_1, kind, budget, Tick(5)));
+        manager.record_keyframe(entity_1, kind, Tick(5), 10);
+        assert!(!manager.should_send_keyframe(entity_2, kind, budget, Tick(5)));
+
+        // the starved entity gets the first keyframe of the next interval
+        assert!(!manager.should_send_keyframe(entity_1, kind, budget, Tick(10)));
+        assert!(manager.should_send_keyframe(entity_2, kind, budget, Tick(10)));
+    }
+
+    /// Keyframes are still sent if the component kind or the entity was not checked for more
+    /// than `i16::MAX` ticks
+    #[test]
+    fn test_keyframe_tick_wrap_around() {
+        let mut manager = KeyframeManager::default();
+        let kind = ComponentKind::of::<Component1>();
+        let budget = KeyframeBudget {
+            interval_ticks: 5,
+            max_bytes: 10,
+        };
+        let entity = Entity::from_raw(1);
+        assert!(!manager.should_send_keyframe(entity, kind, budget, Tick(0)));
+
+        // the wrapping difference with the last check is negative
+        let tick = Tick(40000);
+        assert!(tick - Tick(0) < 0);
+        assert!(manager.should_send_keyframe(entity, kind, budget, tick));
+        manager.record_keyframe(entity, kind, tick, 10);
+        assert!(!manager.should_send_keyframe(entity, kind, budget, tick + 1));
+        assert!(manager.should_send_keyframe(entity, kind, budget, tick + 5));
+    }
+}
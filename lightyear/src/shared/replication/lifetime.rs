@@ -0,0 +1,181 @@
+//! Replicated entities that are despawned automatically after a number of ticks, unless their lifetime is refreshed.
+//!
+//! Transient entities (projectiles, hit markers, short-lived pickups) are usually despawned by the server after a
+//! delay. If the despawn message is late (or the client stops receiving packets), the entity lingers on the client.
+//! Adding a [`ReplicatedLifetime`] to the entity lets each peer remove it independently at the tick of expiry:
+//! - the server despawns the entity at `expires_at_tick`. The despawn is replicated to the clients as usual
+//!   and serves as a confirmation.
+//! - the client removes its entities when its timelines reach `expires_at_tick`, even if the despawn message was
+//!   never received: the predicted entity on the prediction timeline (with
+//!   [`prediction_despawn`](crate::client::prediction::despawn::PredictionDespawnCommandsExt)), the interpolated
+//!   entity on the interpolation timeline, and the confirmed entity once the client received a server
+//!   packet for that tick (or once the interpolation timeline reached it, if no packets are received).
+//!   The confirmed entity is cleaned up with the
+//!   [`ClientCleanupPolicy`](crate::client::cleanup::ClientCleanupPolicy) of [`CleanupCause::ServerDespawn`].
+//!
+//! ```rust,ignore
+//! commands.spawn((
+//!     Projectile,
+//!     ReplicatedLifetime::after(tick_manager.tick(), 64),
+//!     Replicate::default(),
+//! ));
+//! ```
+//!
+//! The lifetime is refreshed by modifying the component; the new value is replicated as a normal component update.
+//! The refresh must reach the client before its timelines pass the previous expiry, so refresh the lifetime a few
+//! round-trips before it expires.
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use tracing::trace;
+
+use crate::client::cleanup::{cleanup_entity, CleanupCause};
+use crate::client::components::Confirmed;
+use crate::client::config::ClientConfig;
+use crate::client::connection::ConnectionManager;
+use crate::client::interpolation::Interpolated;
+use crate::client::prediction::despawn::{PredictionDespawnCommandsExt, PredictionDespawnMarker};
+use crate::client::prediction::Predicted;
+use crate::protocol::component::ComponentRegistry;
+use crate::server::replication::send::ServerFilter;
+use crate::shared::replication::components::Replicated;
+use crate::shared::replication::handoff::HandoffSource;
+use crate::shared::tick_manager::{Tick, TickManager};
+
+/// Despawns the replicated entity at `expires_at_tick`, on the server and on the clients.
+///
+/// See the [module-level documentation](crate::shared::replication::lifetime) for more details.
+#[derive(Component, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Reflect)]
+#[reflect(Component)]
+pub struct ReplicatedLifetime {
+    /// Server tick at which the entity is despawned
+    pub expires_at_tick: Tick,
+}
+
+impl ReplicatedLifetime {
+    pub fn new(expires_at_tick: Tick) -> Self {
+        Self { expires_at_tick }
+    }
+
+    /// Lifetime that expires `ticks` ticks after `tick`
+    pub fn after(tick: Tick, ticks: u16) -> Self {
+        Self::new(tick + ticks as i16)
+    }
+
+    /// Push back the expiry to `ticks` ticks after `tick`
+    pub fn refresh(&mut self, tick: Tick, ticks: u16) {
+        self.expires_at_tick = tick + ticks as i16;
+    }
+
+    /// Returns true if the lifetime expired at `tick`
+    pub fn is_expired(&self, tick: Tick) -> bool {
+        tick >= self.expires_at_tick
+    }
+}
+
+/// Despawn the server entities whose lifetime expired.
+///
+/// The despawn is replicated to the clients like any other despawn.
+pub(crate) fn despawn_expired_entities(
+    mut commands: Commands,
+    tick_manager: Res<TickManager>,
+    query: Query<(Entity, &ReplicatedLifetime), ServerFilter>,
+) {
+    let tick = tick_manager.tick();
+    for (entity, lifetime) in query.iter() {
+        if lifetime.is_expired(tick) {
+            trace!(?entity, ?tick, "Despawning expired entity");
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+/// Despawn the predicted entities whose lifetime expired on the prediction timeline.
+///
+/// The despawn is predicted, so the entity can still be restored during a rollback.
+pub(crate) fn expire_predicted_entities(
+    mut commands: Commands,
+    tick_manager: Res<TickManager>,
+    query: Query<
+        (Entity, &ReplicatedLifetime),
+        (With<Predicted>, Without<PredictionDespawnMarker>),
+    >,
+) {
+    let tick = tick_manager.tick();
+    for (entity, lifetime) in query.iter() {
+        if lifetime.is_expired(tick) {
+            trace!(?entity, ?tick, "Despawning expired predicted entity");
+            commands.entity(entity).prediction_despawn();
+        }
+    }
+}
+
+/// Despawn the interpolated entities whose lifetime expired on the interpolation timeline, then clean up
+/// the confirmed entities once the client received a server packet for the tick of expiry.
+///
+/// The interpolation timeline is always behind the server, so it is also used for the confirmed entities
+/// when the server packets stop arriving.
+///
+/// The confirmed entity is kept until its interpolated entity expired, because cleaning it up also
+/// despawns the interpolated entity. The source of a handoff is despawned when the handoff completes instead.
+pub(crate) fn expire_received_entities(
+    mut commands: Commands,
+    config: Res<ClientConfig>,
+    tick_manager: Res<TickManager>,
+    connection: Res<ConnectionManager>,
+    confirmed_query: Query<(Entity, &ReplicatedLifetime, Option<&Confirmed>), With<Replicated>>,
+    interpolated_query: Query<
+        (Entity, &ReplicatedLifetime),
+        (With<Interpolated>, Without<HandoffSource>),
+    >,
+    interpolated_entities: Query<(), With<Interpolated>>,
+) {
+    let interpolation_tick = connection
+        .sync_manager
+        .interpolation_tick(tick_manager.as_ref());
+    let mut expired_interpolated = Vec::new();
+    for (entity, lifetime) in interpolated_query.iter() {
+        if lifetime.is_expired(interpolation_tick) {
+            trace!(
+                ?entity,
+                ?interpolation_tick,
+                "Despawning expired interpolated entity"
+            );
+            commands.entity(entity).despawn_recursive();
+            expired_interpolated.push(entity);
+        }
+    }
+
+    let server_tick = connection
+        .sync_manager
+        .latest_received_server_tick
+        .map_or(interpolation_tick, |tick| tick.max(interpolation_tick));
+    let action = config.cleanup.action(CleanupCause::ServerDespawn);
+    for (entity, lifetime, confirmed) in confirmed_query.iter() {
+        if !lifetime.is_expired(server_tick) {
+            continue;
+        }
+        let interpolated = confirmed.and_then(|confirmed| confirmed.interpolated);
+        if interpolated.is_some_and(|interpolated| {
+            interpolated_entities.contains(interpolated)
+                && !expired_interpolated.contains(&interpolated)
+        }) {
+            continue;
+        }
+        trace!(
+            ?entity,
+            ?server_tick,
+            "Cleaning up expired confirmed entity"
+        );
+        commands.add(move |world: &mut World| {
+            world.resource_scope(|world, component_registry: Mut<ComponentRegistry>| {
+                cleanup_entity(
+                    world,
+                    entity,
+                    CleanupCause::ServerDespawn,
+                    action,
+                    &component_registry,
+                );
+            });
+        });
+    }
+}
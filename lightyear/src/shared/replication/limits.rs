@@ -0,0 +1,338 @@
+//! Limits on the replication received from the remote, to protect the client against a malicious
+//! or buggy server that would replicate an unbounded number of entities, and the server against a client
+//! that spawns an unbounded number of entities with client-authoritative replication.
+//!
+//! When a limit is exceeded, the spawns of new entities are rejected: the rejected entities are never
+//! spawned locally, and all the replication messages that concern them are ignored.
+use bevy::ecs::entity::EntityHash;
+use bevy::prelude::Entity;
+use bevy::reflect::Reflect;
+use bevy::utils::{Duration, HashMap, HashSet};
+use bytes::Bytes;
+use tracing::warn;
+
+use crate::protocol::component::ComponentNetId;
+use crate::serialize::reader::Reader;
+use crate::serialize::ToBytes;
+
+type EntityHashMap<K, V> = hashbrown::HashMap<K, V, EntityHash>;
+
+type EntityHashSet<K> = hashbrown::HashSet<K, EntityHash>;
+
+/// Limits applied to the replication received from the remote.
+///
+/// On the client, they apply to the entities replicated by the server. On the server, they apply separately
+/// to the entities replicated by each client, before the server-side entities are spawned.
+/// All limits are disabled by default.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Reflect)]
+pub struct ReplicationLimits {
+    /// Maximum number of replicated entities that can exist at the same time
+    pub max_entities: Option<usize>,
+    /// Maximum number of replicated components on a single entity.
+    ///
+    /// The insertions of additional components are rejected.
+    pub max_components_per_entity: Option<usize>,
+    /// Maximum number of entity spawns accepted per second.
+    ///
+    /// Like a token bucket, up to `max_spawns_per_second` spawns can be accepted at once.
+    pub max_spawns_per_second: Option<usize>,
+    /// Maximum number of bytes of replication messages (entity actions and updates) received per second.
+    ///
+    /// New entities are not spawned while the budget is exhausted.
+    pub max_bytes_per_second: Option<usize>,
+    /// Extra bytes allowed on top of `max_bytes_per_second` when the connection is established, to account
+    /// for the replication of all the existing entities when the client joins.
+    ///
+    /// More allowance can be declared with
+    /// [`ConnectionManager::expect_replication_snapshot`](crate::client::connection::ConnectionManager::expect_replication_snapshot).
+    pub snapshot_bytes: usize,
+    /// If true, the connection is closed when a limit is exceeded: the client disconnects from the server, or the
+    /// server disconnects the client. Otherwise, the rejected spawns are only dropped.
+    pub disconnect: bool,
+}
+
+impl ReplicationLimits {
+    pub fn with_max_entities(mut self, max_entities: usize) -> Self {
+        self.max_entities = Some(max_entities);
+        self
+    }
+
+    pub fn with_max_components_per_entity(mut self, max_components: usize) -> Self {
+        self.max_components_per_entity = Some(max_components);
+        self
+    }
+
+    pub fn with_max_spawns_per_second(mut self, max_spawns: usize) -> Self {
+        self.max_spawns_per_second = Some(max_spawns);
+        self
+    }
+
+    pub fn with_max_bytes_per_second(mut self, max_bytes: usize) -> Self {
+        self.max_bytes_per_second = Some(max_bytes);
+        self
+    }
+
+    pub fn with_snapshot_bytes(mut self, snapshot_bytes: usize) -> Self {
+        self.snapshot_bytes = snapshot_bytes;
+        self
+    }
+
+    pub fn with_disconnect(mut self, disconnect: bool) -> Self {
+        self.disconnect = disconnect;
+        self
+    }
+}
+
+/// A limit of the [`ReplicationLimits`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Reflect)]
+pub enum ReplicationLimit {
+    /// [`ReplicationLimits::max_entities`]
+    Entities,
+    /// [`ReplicationLimits::max_components_per_entity`]
+    ComponentsPerEntity,
+    /// [`ReplicationLimits::max_spawns_per_second`]
+    SpawnsPerSecond,
+    /// [`ReplicationLimits::max_bytes_per_second`]
+    BytesPerSecond,
+}
+
+/// Counters of what was rejected because of the [`ReplicationLimits`]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ReplicationLimitStats {
+    /// Number of entity spawns that were rejected
+    pub rejected_spawns: u32,
+    /// Number of component insertions that were rejected
+    pub rejected_components: u32,
+}
+
+/// Enforces the [`ReplicationLimits`] on the replication messages received from the remote
+#[derive(Debug, Default)]
+pub(crate) struct ReplicationLimiter {
+    limits: ReplicationLimits,
+    /// Remote entities that are currently spawned
+    entities: EntityHashSet<Entity>,
+    /// Replicated components of each remote entity (only tracked if `max_components_per_entity` is set)
+    components: EntityHashMap<Entity, HashSet<ComponentNetId>>,
+    /// Remote entities whose spawn was rejected
+    rejected: EntityHashSet<Entity>,
+    /// Spawns that can still be accepted before the budget is exhausted
+    spawn_budget: f64,
+    /// Bytes that can still be received before the budget is exhausted. Can be negative.
+    byte_budget: f64,
+    /// Bytes allowed on top of the budget, for the expected snapshots
+    snapshot_allowance: usize,
+    stats: ReplicationLimitStats,
+    /// Number of rejections for each limit since the last call to `drain_exceeded`
+    exceeded: HashMap<ReplicationLimit, u32>,
+    /// Limits that were already logged
+    logged: HashSet<ReplicationLimit>,
+}
+
+impl ReplicationLimiter {
+    pub(crate) fn new(limits: ReplicationLimits) -> Self {
+        Self {
+            limits,
+            spawn_budget: limits.max_spawns_per_second.unwrap_or_default() as f64,
+            byte_budget: limits.max_bytes_per_second.unwrap_or_default() as f64,
+            snapshot_allowance: limits.snapshot_bytes,
+            ..Default::default()
+        }
+    }
+
+    pub(crate) fn limits(&self) -> &ReplicationLimits {
+        &self.limits
+    }
+
+    pub(crate) fn stats(&self) -> &ReplicationLimitStats {
+        &self.stats
+    }
+
+    /// Number of remote entities that are currently spawned
+    pub(crate) fn entities(&self) -> usize {
+        self.entities.len()
+    }
+
+    /// Allow `bytes` more bytes on top of the budget
+    pub(crate) fn expect_snapshot(&mut self, bytes: usize) {
+        self.snapshot_allowance += bytes;
+    }
+
+    /// Replenish the spawn and byte budgets
+    pub(crate) fn update(&mut self, delta: Duration) {
+        if let Some(max_spawns) = self.limits.max_spawns_per_second {
+            self.spawn_budget = (self.spawn_budget + max_spawns as f64 * delta.as_secs_f64())
+                .min(max_spawns as f64);
+        }
+        if let Some(max_bytes) = self.limits.max_bytes_per_second {
+            self.byte_budget =
+                (self.byte_budget + max_bytes as f64 * delta.as_secs_f64()).min(max_bytes as f64);
+        }
+    }
+
+    /// Charge a received replication message to the byte budget
+    pub(crate) fn record_bytes(&mut self, bytes: usize) {
+        if self.limits.max_bytes_per_second.is_none() {
+            return;
+        }
+        let from_allowance = bytes.min(self.snapshot_allowance);
+        self.snapshot_allowance -= from_allowance;
+        self.byte_budget -= (bytes - from_allowance) as f64;
+    }
+
+    /// Returns true if the spawn of the remote entity is allowed
+    pub(crate) fn allow_spawn(&mut self, remote_entity: Entity) -> bool {
+        let exceeded = if self
+            .limits
+            .max_entities
+            .is_some_and(|max| self.entities.len() >= max)
+        {
+            Some(ReplicationLimit::Entities)
+        } else if self.limits.max_spawns_per_second.is_some() && self.spawn_budget < 1.0 {
+            Some(ReplicationLimit::SpawnsPerSecond)
+        } else if self.limits.max_bytes_per_second.is_some() && self.byte_budget < 0.0 {
+            Some(ReplicationLimit::BytesPerSecond)
+        } else {
+            None
+        };
+        if let Some(limit) = exceeded {
+            self.reject(limit);
+            self.stats.rejected_spawns += 1;
+            self.rejected.insert(remote_entity);
+            return false;
+        }
+        if self.limits.max_spawns_per_second.is_some() {
+            self.spawn_budget -= 1.0;
+        }
+        self.entities.insert(remote_entity);
+        true
+    }
+
+    /// Reject the spawn of the remote entity for a reason other than the limits (for example a server-side
+    /// policy), so that its later actions are ignored as well
+    pub(crate) fn reject_spawn(&mut self, remote_entity: Entity) {
+        self.rejected.insert(remote_entity);
+    }
+
+    /// Returns true if the spawn of the remote entity was rejected
+    pub(crate) fn is_rejected(&self, remote_entity: Entity) -> bool {
+        self.rejected.contains(&remote_entity)
+    }
+
+    /// The remote entity was despawned.
+    ///
+    /// Returns true if its spawn had been rejected.
+    pub(crate) fn despawn(&mut self, remote_entity: Entity) -> bool {
+        self.entities.remove(&remote_entity);
+        self.components.remove(&remote_entity);
+        self.rejected.remove(&remote_entity)
+    }
+
+    /// Returns true if the insertion of the serialized component on the remote entity is allowed
+    pub(crate) fn allow_insert(&mut self, remote_entity: Entity, component: &Bytes) -> bool {
+        let Some(max_components) = self.limits.max_components_per_entity else {
+            return true;
+        };
+        let Ok(net_id) = ComponentNetId::from_bytes(&mut Reader::from(component.clone())) else {
+            // the write will fail and be reported
+            return true;
+        };
+        let components = self.components.entry(remote_entity).or_default();
+        if components.contains(&net_id) || components.len() < max_components {
+            components.insert(net_id);
+            return true;
+        }
+        self.reject(ReplicationLimit::ComponentsPerEntity);
+        self.stats.rejected_components += 1;
+        false
+    }
+
+    /// The component was removed from the remote entity
+    pub(crate) fn remove(&mut self, remote_entity: Entity, net_id: ComponentNetId) {
+        if let Some(components) = self.components.get_mut(&remote_entity) {
+            components.remove(&net_id);
+        }
+    }
+
+    fn reject(&mut self, limit: ReplicationLimit) {
+        if self.logged.insert(limit) {
+            warn!(?limit, limits = ?self.limits, "Replication limit exceeded, rejecting the replicated data");
+        }
+        *self.exceeded.entry(limit).or_default() += 1;
+    }
+
+    /// Returns the limits that were exceeded since the last call, with the number of rejections
+    pub(crate) fn drain_exceeded(&mut self) -> impl Iterator<Item = (ReplicationLimit, u32)> + '_ {
+        self.exceeded.drain()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_max_entities() {
+        let mut limiter =
+            ReplicationLimiter::new(ReplicationLimits::default().with_max_entities(2));
+        let entities: Vec<_> = (0..4).map(Entity::from_raw).collect();
+        assert!(limiter.allow_spawn(entities[0]));
+        assert!(limiter.allow_spawn(entities[1]));
+        assert!(!limiter.allow_spawn(entities[2]));
+        assert!(limiter.is_rejected(entities[2]));
+        assert_eq!(limiter.stats().rejected_spawns, 1);
+        assert_eq!(
+            limiter.drain_exceeded().collect::<Vec<_>>(),
+            vec![(ReplicationLimit::Entities, 1)]
+        );
+
+        // a despawn frees a slot
+        assert!(!limiter.despawn(entities[0]));
+        assert!(limiter.despawn(entities[2]));
+        assert!(limiter.allow_spawn(entities[3]));
+        assert_eq!(limiter.drain_exceeded().count(), 0);
+    }
+
+    #[test]
+    fn test_max_spawns_per_second() {
+        let mut limiter =
+            ReplicationLimiter::new(ReplicationLimits::default().with_max_spawns_per_second(10));
+        for i in 0..10 {
+            assert!(limiter.allow_spawn(Entity::from_raw(i)));
+        }
+        assert!(!limiter.allow_spawn(Entity::from_raw(10)));
+
+        // the budget is replenished over time, and despawns do not refill it
+        limiter.despawn(Entity::from_raw(0));
+        limiter.update(Duration::from_millis(250));
+        assert!(limiter.allow_spawn(Entity::from_raw(11)));
+        assert!(limiter.allow_spawn(Entity::from_raw(12)));
+        assert!(!limiter.allow_spawn(Entity::from_raw(13)));
+        assert_eq!(limiter.entities(), 11);
+        assert_eq!(
+            limiter.drain_exceeded().collect::<Vec<_>>(),
+            vec![(ReplicationLimit::SpawnsPerSecond, 2)]
+        );
+    }
+
+    #[test]
+    fn test_max_bytes_per_second() {
+        let mut limiter = ReplicationLimiter::new(
+            ReplicationLimits::default()
+                .with_max_bytes_per_second(1000)
+                .with_snapshot_bytes(5000),
+        );
+        // the snapshot allowance is used first
+        limiter.record_bytes(5500);
+        assert!(limiter.allow_spawn(Entity::from_raw(0)));
+        limiter.record_bytes(600);
+        assert!(!limiter.allow_spawn(Entity::from_raw(1)));
+
+        // the budget is replenished over time
+        limiter.update(Duration::from_millis(200));
+        assert!(limiter.allow_spawn(Entity::from_raw(2)));
+        assert_eq!(
+            limiter.drain_exceeded().collect::<Vec<_>>(),
+            vec![(ReplicationLimit::BytesPerSecond, 1)]
+        );
+    }
+}
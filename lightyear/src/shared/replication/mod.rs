@@ -21,18 +21,35 @@ use crate::shared::events::connection::{
     ClearEvents, IterComponentInsertEvent, IterComponentRemoveEvent, IterComponentUpdateEvent,
     IterEntityDespawnEvent, IterEntitySpawnEvent,
 };
+use crate::shared::replication::alias::{EntityAlias, EntityAliasReceiver};
 use crate::shared::replication::components::ReplicationGroupId;
+use crate::shared::replication::entity_map::RemoteEntityMap;
+use crate::shared::warnings::{NetworkWarning, NetworkWarnings};
 
 pub mod components;
 
+pub(crate) mod alias;
 pub(crate) mod archetypes;
+pub mod changes;
+pub mod classes;
+pub mod coalesce;
+pub(crate) mod deferred;
 pub mod delta;
+pub mod diagnostics;
 pub mod entity_map;
 pub mod error;
+pub mod frequency;
+pub mod handoff;
 pub(crate) mod hierarchy;
+pub mod keyed;
+pub mod keyframe;
+pub mod lifetime;
+pub mod limits;
 pub mod network_target;
+pub mod parallel;
 pub(crate) mod plugin;
 pub(crate) mod prespawn;
+pub mod prespawn_ids;
 pub(crate) mod receive;
 pub(crate) mod resources;
 pub(crate) mod send;
@@ -106,6 +123,18 @@ pub(crate) enum SpawnAction {
     Despawn,
     // the u64 is the entity's bits (we cannot use Entity directly because it doesn't implement Encode/Decode)
     Reuse(Entity),
+    /// Spawn an entity that replaces the given remote entity: the receiver re-uses the local entity
+    /// that was mapped to the replaced entity instead of spawning a new one
+    Replace(Entity),
+    /// Despawn an entity that is being replaced by another entity (see [`SpawnAction::Replace`]).
+    /// The receiver keeps the local entity alive until the replacement is received.
+    DespawnReplaced,
+    /// The entity is not despawned, but it stopped being replicated to the receiver
+    /// (it is not relevant anymore, or the receiver was removed from its replication target)
+    DespawnInterestLost,
+    /// The entity moved to the [`ReplicationGroup`](crate::prelude::ReplicationGroup) of the message: the receiver
+    /// moves it from its previous group, or spawns it if it didn't receive it yet
+    Regroup,
 }
 
 impl ToBytes for SpawnAction {
@@ -115,6 +144,10 @@ impl ToBytes for SpawnAction {
             SpawnAction::Spawn => 1,
             SpawnAction::Despawn => 1,
             SpawnAction::Reuse(entity) => 1 + entity.len(),
+            SpawnAction::Replace(entity) => 1 + entity.len(),
+            SpawnAction::DespawnReplaced => 1,
+            SpawnAction::DespawnInterestLost => 1,
+            SpawnAction::Regroup => 1,
         }
     }
 
@@ -127,6 +160,13 @@ impl ToBytes for SpawnAction {
                 buffer.write_u8(3)?;
                 entity.to_bytes(buffer)?;
             }
+            SpawnAction::Replace(entity) => {
+                buffer.write_u8(4)?;
+                entity.to_bytes(buffer)?;
+            }
+            SpawnAction::DespawnReplaced => buffer.write_u8(5)?,
+            SpawnAction::DespawnInterestLost => buffer.write_u8(6)?,
+            SpawnAction::Regroup => buffer.write_u8(7)?,
         }
         Ok(())
     }
@@ -140,6 +180,10 @@ impl ToBytes for SpawnAction {
             1 => Ok(SpawnAction::Spawn),
             2 => Ok(SpawnAction::Despawn),
             3 => Ok(SpawnAction::Reuse(Entity::from_bytes(buffer)?)),
+            4 => Ok(SpawnAction::Replace(Entity::from_bytes(buffer)?)),
+            5 => Ok(SpawnAction::DespawnReplaced),
+            6 => Ok(SpawnAction::DespawnInterestLost),
+            7 => Ok(SpawnAction::Regroup),
             _ => Err(SerializationError::InvalidPacketType),
         }
     }
@@ -227,6 +271,9 @@ pub struct SendEntityUpdatesMessage {
     last_action_tick: Option<Tick>,
     /// Updates containing the full component data
     pub(crate) updates: HashMap<Entity, Vec<Bytes>, EntityHash>,
+    /// Updates of the entities that have an alias established with the remote.
+    /// They are only written if not empty, so that there is no overhead when aliases are disabled
+    pub(crate) aliased_updates: Vec<(EntityAlias, Vec<Bytes>)>,
     // /// Updates containing diffs with a previous value
     // #[bitcode(with_serde)]
     // diff_updates: Vec<(Entity, Vec<RawData>)>,
@@ -234,13 +281,21 @@ pub struct SendEntityUpdatesMessage {
 
 impl ToBytes for SendEntityUpdatesMessage {
     fn len(&self) -> usize {
-        self.group_id.len() + self.last_action_tick.len() + self.updates.len()
+        let aliased_len = if self.aliased_updates.is_empty() {
+            0
+        } else {
+            self.aliased_updates.len()
+        };
+        self.group_id.len() + self.last_action_tick.len() + self.updates.len() + aliased_len
     }
 
     fn to_bytes<T: WriteBytesExt>(&self, buffer: &mut T) -> Result<(), SerializationError> {
         self.group_id.to_bytes(buffer)?;
         self.last_action_tick.to_bytes(buffer)?;
         self.updates.to_bytes(buffer)?;
+        if !self.aliased_updates.is_empty() {
+            self.aliased_updates.to_bytes(buffer)?;
+        }
         Ok(())
     }
 
@@ -248,10 +303,19 @@ impl ToBytes for SendEntityUpdatesMessage {
     where
         Self: Sized,
     {
+        let group_id = ReplicationGroupId::from_bytes(buffer)?;
+        let last_action_tick = Option::<Tick>::from_bytes(buffer)?;
+        let updates = HashMap::<Entity, Vec<Bytes>, EntityHash>::from_bytes(buffer)?;
+        let aliased_updates = if buffer.has_remaining() {
+            Vec::<(EntityAlias, Vec<Bytes>)>::from_bytes(buffer)?
+        } else {
+            vec![]
+        };
         Ok(Self {
-            group_id: ReplicationGroupId::from_bytes(buffer)?,
-            last_action_tick: Option::<Tick>::from_bytes(buffer)?,
-            updates: HashMap::<Entity, Vec<Bytes>, EntityHash>::from_bytes(buffer)?,
+            group_id,
+            last_action_tick,
+            updates,
+            aliased_updates,
         })
     }
 }
@@ -295,6 +359,35 @@ impl ToBytes for EntityUpdatesMessage {
     }
 }
 
+impl EntityUpdatesMessage {
+    /// Read a message that might contain aliased updates (see [`alias`]).
+    ///
+    /// The aliases are resolved with the aliases that were valid when the remote sent the message at `remote_tick`;
+    /// the updates of aliases that cannot be resolved are dropped and reported in `warnings`.
+    pub(crate) fn from_bytes_with_aliases(
+        buffer: &mut Reader,
+        aliases: &EntityAliasReceiver,
+        remote_tick: Tick,
+        warnings: &mut NetworkWarnings,
+    ) -> Result<Self, SerializationError> {
+        let mut message = Self::from_bytes(buffer)?;
+        if buffer.has_remaining() {
+            for (alias, updates) in Vec::<(EntityAlias, Vec<Bytes>)>::from_bytes(buffer)? {
+                match aliases.resolve(alias, remote_tick) {
+                    Some(entity) => message.updates.push((entity, updates)),
+                    None => warnings.report(
+                        NetworkWarning::UnknownEntityAlias,
+                        format_args!(
+                            "Dropping update for an unknown entity alias {alias:?} at tick {remote_tick:?}"
+                        ),
+                    ),
+                }
+            }
+        }
+        Ok(message)
+    }
+}
+
 /// Trait for a service that participates in replication.
 pub(crate) trait ReplicationPeer: Resource {
     type Events: IterComponentInsertEvent<Self::EventContext>
@@ -320,6 +413,10 @@ pub(crate) trait ReplicationReceive: Resource + ReplicationPeer {
     /// Do some regular cleanup on the internals of replication
     /// - account for tick wrapping by resetting some internal ticks for each replication group
     fn cleanup(&mut self, tick: Tick);
+
+    /// Map between the local entities and the entities replicated by the peer `from` (the server if `None`),
+    /// if the entities of this peer are received by this service
+    fn remote_entity_map(&self, from: Option<ClientId>) -> Option<&RemoteEntityMap>;
 }
 
 #[doc(hidden)]
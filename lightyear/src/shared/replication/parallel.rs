@@ -0,0 +1,208 @@
+//! Deserialization of the received replication messages on the [`ComputeTaskPool`].
+//!
+//! Most of the time spent applying a large snapshot (or a busy tick of updates) goes into deserializing
+//! the component payloads. Deserialization is pure, so all the payloads that are ready to be applied in a
+//! frame are deserialized in parallel first, grouped by component kind so that each task runs the same
+//! deserialization function on consecutive payloads.
+//!
+//! The decoded values are then written to the World on the main thread, in the exact order in which the
+//! messages would have been applied without the parallel decoding: entities are spawned before their components
+//! are written, and the actions and updates of an entity are applied in order.
+//! The entities contained in the components are mapped when the values are written, because the entities
+//! spawned by the same messages are not in the entity map yet when the payloads are deserialized.
+use bevy::reflect::Reflect;
+use bevy::tasks::ComputeTaskPool;
+use bytes::Bytes;
+use tracing::trace;
+
+use crate::protocol::component::{ComponentNetId, ComponentRegistry, DecodedComponent};
+use crate::serialize::reader::Reader;
+use crate::serialize::ToBytes;
+
+use super::{EntityActionsMessage, EntityUpdatesMessage};
+
+/// Configuration of the parallel deserialization of the received replication
+#[derive(Clone, Copy, Debug, PartialEq, Reflect)]
+pub struct ParallelApplyConfig {
+    /// Minimum number of component payloads that are ready to be applied in a frame for them to be
+    /// deserialized in parallel. Below that, the cost of spawning the tasks outweighs the gains.
+    ///
+    /// Set to `usize::MAX` to always deserialize on the main thread.
+    pub min_payloads: usize,
+    /// Number of payloads deserialized by each task
+    pub payloads_per_task: usize,
+}
+
+impl Default for ParallelApplyConfig {
+    fn default() -> Self {
+        Self {
+            min_payloads: 512,
+            payloads_per_task: 256,
+        }
+    }
+}
+
+impl ParallelApplyConfig {
+    /// Always deserialize the payloads on the main thread
+    pub fn disabled() -> Self {
+        Self {
+            min_payloads: usize::MAX,
+            ..Self::default()
+        }
+    }
+}
+
+/// Decoded component payloads of a replication message.
+///
+/// `components[i][j]` is the j-th payload of the i-th entity of the message (for an [`EntityActionsMessage`],
+/// the inserts come before the updates). A missing value means that the payload must be deserialized
+/// when it is written.
+#[derive(Debug, Default)]
+pub(crate) struct DecodedMessage {
+    components: Vec<Vec<Option<DecodedComponent>>>,
+}
+
+impl DecodedMessage {
+    /// Take the decoded value of the j-th payload of the i-th entity of the message
+    pub(crate) fn take(
+        &mut self,
+        entity_idx: usize,
+        payload_idx: usize,
+    ) -> Option<DecodedComponent> {
+        self.components
+            .get_mut(entity_idx)
+            .and_then(|payloads| payloads.get_mut(payload_idx))
+            .and_then(Option::take)
+    }
+
+    fn with_shape(shape: impl Iterator<Item = usize>) -> Self {
+        Self {
+            components: shape.map(|len| (0..len).map(|_| None).collect()).collect(),
+        }
+    }
+}
+
+/// A payload to deserialize, with its position in the messages
+struct Job<'a> {
+    net_id: ComponentNetId,
+    payload: &'a Bytes,
+    message_idx: usize,
+    entity_idx: usize,
+    payload_idx: usize,
+}
+
+impl Job<'_> {
+    fn decode(&self, component_registry: &ComponentRegistry) -> Option<DecodedComponent> {
+        let mut reader = Reader::from(self.payload.clone());
+        // skip the net id, which was already read
+        ComponentNetId::from_bytes(&mut reader).ok()?;
+        // the payloads that cannot be decoded are deserialized again when they are written,
+        // which reports the error
+        component_registry
+            .raw_decode(&mut reader, self.net_id)
+            .ok()
+            .flatten()
+    }
+}
+
+/// Deserialize the payloads of the actions and updates messages that are ready to be applied.
+///
+/// Returns the [`DecodedMessage`] of each actions message and of each updates message, in the same order.
+/// The updates messages that are only kept for the history (`is_history`) are not decoded.
+pub(crate) fn decode_messages<'a>(
+    config: &ParallelApplyConfig,
+    component_registry: &ComponentRegistry,
+    actions: impl ExactSizeIterator<Item = &'a EntityActionsMessage>,
+    updates: impl ExactSizeIterator<Item = (&'a EntityUpdatesMessage, bool)>,
+) -> (Vec<DecodedMessage>, Vec<DecodedMessage>) {
+    let num_actions = actions.len();
+    let mut decoded = Vec::with_capacity(num_actions + updates.len());
+    let mut jobs = Vec::new();
+    for message in actions {
+        let message_idx = decoded.len();
+        for (entity_idx, (_, actions)) in message.actions.iter().enumerate() {
+            push_jobs(
+                &mut jobs,
+                message_idx,
+                entity_idx,
+                actions.insert.iter().chain(actions.updates.iter()),
+            );
+        }
+        decoded.push(DecodedMessage::with_shape(
+            message
+                .actions
+                .iter()
+                .map(|(_, actions)| actions.insert.len() + actions.updates.len()),
+        ));
+    }
+    for (message, is_history) in updates {
+        let message_idx = decoded.len();
+        if is_history {
+            decoded.push(DecodedMessage::default());
+            continue;
+        }
+        for (entity_idx, (_, payloads)) in message.updates.iter().enumerate() {
+            push_jobs(&mut jobs, message_idx, entity_idx, payloads.iter());
+        }
+        decoded.push(DecodedMessage::with_shape(
+            message.updates.iter().map(|(_, payloads)| payloads.len()),
+        ));
+    }
+
+    // the payloads are deserialized on the main thread when they are written
+    let Some(pool) = ComputeTaskPool::try_get() else {
+        return split(decoded, num_actions);
+    };
+    if jobs.len() < config.min_payloads {
+        return split(decoded, num_actions);
+    }
+    trace!(
+        num_payloads = jobs.len(),
+        "Decoding replication payloads in parallel"
+    );
+
+    // shard the payloads by component kind
+    jobs.sort_unstable_by_key(|job| job.net_id);
+    let results = pool.scope(|scope| {
+        for chunk in jobs.chunks(config.payloads_per_task.max(1)) {
+            scope.spawn(async move {
+                chunk
+                    .iter()
+                    .map(|job| job.decode(component_registry))
+                    .collect::<Vec<_>>()
+            });
+        }
+    });
+    for (job, value) in jobs.iter().zip(results.into_iter().flatten()) {
+        decoded[job.message_idx].components[job.entity_idx][job.payload_idx] = value;
+    }
+    split(decoded, num_actions)
+}
+
+fn push_jobs<'a>(
+    jobs: &mut Vec<Job<'a>>,
+    message_idx: usize,
+    entity_idx: usize,
+    payloads: impl Iterator<Item = &'a Bytes>,
+) {
+    for (payload_idx, payload) in payloads.enumerate() {
+        let Ok(net_id) = ComponentNetId::from_bytes(&mut Reader::from(payload.clone())) else {
+            continue;
+        };
+        jobs.push(Job {
+            net_id,
+            payload,
+            message_idx,
+            entity_idx,
+            payload_idx,
+        });
+    }
+}
+
+fn split(
+    mut decoded: Vec<DecodedMessage>,
+    num_actions: usize,
+) -> (Vec<DecodedMessage>, Vec<DecodedMessage>) {
+    let updates = decoded.split_off(num_actions);
+    (decoded, updates)
+}
@@ -1,7 +1,12 @@
 //! This module contains the `ReplicationReceivePlugin` and `ReplicationSendPlugin` plugins, which control
 //! the replication of entities and resources.
 //!
+use crate::server::relevance::immediate::InterestConfig;
+use crate::shared::replication::classes::ReplicationClassQuotas;
 use crate::shared::replication::hierarchy::{HierarchyReceivePlugin, HierarchySendPlugin};
+use crate::shared::replication::limits::ReplicationLimits;
+use crate::shared::replication::parallel::ParallelApplyConfig;
+use crate::shared::replication::prespawn_ids::PreSpawnIdConfig;
 use crate::shared::replication::resources::{
     receive::ResourceReceivePlugin, send::ResourceSendPlugin,
 };
@@ -20,6 +25,37 @@ pub struct ReplicationConfig {
     ///
     /// Set to `Duration::default()` to send updates every frame.
     pub send_interval: Duration,
+    /// Maximum number of entities that can be referred to by a 1-byte alias instead of their full id
+    /// in the replication updates sent to each remote. The aliases are given to the entities that are
+    /// updated most frequently.
+    ///
+    /// Set to 0 to disable the aliases.
+    pub entity_aliases: u8,
+    /// Limits on the replication received from the remote, to protect the client against a server
+    /// that replicates too many entities, or the server against a client that spawns too many entities
+    /// with client-authoritative replication. On the server, the limits apply to each client separately.
+    pub limits: ReplicationLimits,
+    /// How to deserialize the received component payloads in parallel
+    pub parallel_apply: ParallelApplyConfig,
+    /// How the relevance of the entities for each client is kept up-to-date. Only used on the server.
+    pub interest: InterestConfig,
+    /// If true, the update messages that are identical for several clients (same entities, same updates)
+    /// are serialized once and shared between their connections. The bytes sent to each client are
+    /// the same as without coalescing. Only used on the server.
+    ///
+    /// See [`coalesce`](crate::shared::replication::coalesce) for how to group the entities to share more messages.
+    pub coalesce_updates: bool,
+    /// How the ids of the pre-spawned entities are partitioned between the server and the clients.
+    /// Only used on the server.
+    ///
+    /// See [`prespawn_ids`](crate::shared::replication::prespawn_ids) for more details.
+    pub prespawn_ids: PreSpawnIdConfig,
+    /// Percentage of the bandwidth quota of each connection reserved for each
+    /// [`ReplicationClass`](crate::shared::replication::classes::ReplicationClass).
+    /// Only used when the bandwidth cap is enabled.
+    ///
+    /// See [`classes`](crate::shared::replication::classes) for more details.
+    pub class_quotas: ReplicationClassQuotas,
 }
 
 #[derive(Clone, Copy, Debug, Reflect)]
@@ -47,6 +83,13 @@ impl Default for ReplicationConfig {
         Self {
             send_updates_mode: SendUpdatesMode::SinceLastAck,
             send_interval: Duration::default(),
+            entity_aliases: 0,
+            limits: ReplicationLimits::default(),
+            parallel_apply: ParallelApplyConfig::default(),
+            interest: InterestConfig::default(),
+            coalesce_updates: true,
+            prespawn_ids: PreSpawnIdConfig::default(),
+            class_quotas: ReplicationClassQuotas::default(),
         }
     }
 }
@@ -256,6 +299,8 @@ pub(crate) mod shared {
         ShouldBeInterpolated,
     };
     use crate::shared::replication::entity_map::{InterpolatedEntityMap, PredictedEntityMap};
+    use crate::shared::replication::handoff::ReplicatedHandoff;
+    use crate::shared::replication::lifetime::ReplicatedLifetime;
     use crate::shared::replication::network_target::NetworkTarget;
     use bevy::prelude::{App, Plugin};
 
@@ -280,6 +325,8 @@ pub(crate) mod shared {
                 .register_type::<ShouldBeInterpolated>()
                 .register_type::<PrePredicted>()
                 .register_type::<ShouldBePredicted>()
+                .register_type::<ReplicatedLifetime>()
+                .register_type::<ReplicatedHandoff>()
                 .register_type::<RemoteEntityMap>()
                 .register_type::<PredictedEntityMap>()
                 .register_type::<InterpolatedEntityMap>();
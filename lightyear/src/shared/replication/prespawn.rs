@@ -5,6 +5,7 @@ use crate::prelude::{
 };
 use crate::protocol::component::ComponentKind;
 use crate::shared::replication::components::{Controlled, ShouldBeInterpolated};
+use crate::shared::replication::prespawn_ids::PRESPAWN_ID_FLAG;
 use bevy::ecs::archetype::Archetype;
 use bevy::ecs::component::Components;
 use std::any::TypeId;
@@ -71,5 +72,6 @@ pub(crate) fn compute_default_hash(
         salt.hash(&mut hasher);
     }
 
-    hasher.finish()
+    // the top bit is reserved for the ids allocated from the pre-spawned entity id ranges
+    hasher.finish() & !PRESPAWN_ID_FLAG
 }
@@ -0,0 +1,441 @@
+//! Partitioning of the ids of the pre-spawned entities between the server and the clients.
+//!
+//! By default a [`PreSpawnedPlayerObject`](crate::prelude::PreSpawnedPlayerObject) is matched with the server entity
+//! using a hash of its spawn tick and of its components, which is ambiguous when several similar entities are
+//! spawned on the same tick. Instead, the entity can be identified by an id that is unique across all peers:
+//! - the server allocates its ids from its own range
+//! - when a client connects, the server reserves a range of ids for that client and sends it to the client
+//! - the client allocates the id of its pre-spawned entity from its range with [`PreSpawnIdAllocator::allocate`],
+//!   and includes the id in the input or message that triggers the spawn
+//! - if the server accepts the action, it spawns the entity with the id proposed by the client (after checking it
+//!   with [`PreSpawnIdRanges::adopt`]); otherwise it allocates a fresh id from its own range with
+//!   [`PreSpawnIdRanges::allocate`].
+//!
+//! ```rust,ignore
+//! // client
+//! if let Some(id) = allocator.allocate() {
+//!     commands.spawn((Bullet, PreSpawnedPlayerObject::new(id)));
+//! }
+//!
+//! // server
+//! let id = ranges.adopt(client_id, proposed_id).ok().or_else(|| ranges.allocate());
+//! commands.spawn((Bullet, PreSpawnedPlayerObject { hash: id, ..default() }));
+//! ```
+//!
+//! The ids are derived from the tick and from the order of the allocations during the tick, so the same
+//! ids are allocated again when the client re-runs a tick during a rollback.
+//! The default hashes never have the top bit set, so they never collide with the allocated ids.
+//!
+//! A client that replicates a [`PreSpawnedPlayerObject`](crate::prelude::PreSpawnedPlayerObject) with an id
+//! outside its range gets the component stripped from the entity, and the violation is recorded in the
+//! [`ClientUpdateViolations`].
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use tracing::{debug, error, warn};
+
+use crate::channel::builder::PreSpawnIdChannel;
+use crate::client::events::MessageEvent;
+use crate::client::prediction::rollback::Rollback;
+use crate::prelude::{
+    ClientId, NetworkTarget, PreSpawnedPlayerObject, Replicated, Tick, TickManager,
+};
+use crate::server::connection::ConnectionManager as ServerConnectionManager;
+use crate::server::events::{ConnectEvent, DisconnectEvent};
+use crate::server::validation::{ClientUpdateViolations, ValidationVerdict};
+
+/// Bit that is set on all the allocated ids, and never set on the default hashes
+pub(crate) const PRESPAWN_ID_FLAG: u64 = 1 << 63;
+
+/// Index of the range used by the server
+const SERVER_RANGE: u32 = 0;
+
+/// Configuration of the partitioning of the pre-spawned entity ids. Only used on the server.
+#[derive(Clone, Copy, Debug, Reflect)]
+pub struct PreSpawnIdConfig {
+    /// Maximum number of clients that can be given a range at the same time.
+    ///
+    /// A client that connects when all the ranges are in use does not get a range, and has to fall back
+    /// to the default hashes.
+    pub max_client_ranges: u32,
+    /// Maximum number of ids that a peer can allocate during a single tick
+    pub max_ids_per_tick: u16,
+}
+
+impl Default for PreSpawnIdConfig {
+    fn default() -> Self {
+        Self {
+            max_client_ranges: 1024,
+            max_ids_per_tick: 256,
+        }
+    }
+}
+
+/// Range of the pre-spawned entity ids reserved for a peer.
+///
+/// An id is made of the flag bit, the index of the range, the tick of the allocation
+/// and the order of the allocation during that tick.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Reflect)]
+pub struct PreSpawnIdRange {
+    index: u32,
+    max_ids_per_tick: u16,
+}
+
+impl PreSpawnIdRange {
+    pub(crate) fn new(index: u32, max_ids_per_tick: u16) -> Self {
+        debug_assert!(index < 1 << 31);
+        Self {
+            index,
+            max_ids_per_tick,
+        }
+    }
+
+    /// Index of the range. The range 0 is used by the server
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+
+    /// The `seq`-th id allocated during `tick`, or None if more than `max_ids_per_tick` ids were allocated
+    fn id(&self, tick: Tick, seq: u16) -> Option<u64> {
+        (seq < self.max_ids_per_tick).then_some(
+            PRESPAWN_ID_FLAG | (self.index as u64) << 32 | (tick.0 as u64) << 16 | seq as u64,
+        )
+    }
+
+    /// Returns true if the id was allocated from this range
+    pub fn contains(&self, id: u64) -> bool {
+        range_index(id) == Some(self.index) && ((id & 0xFFFF) as u16) < self.max_ids_per_tick
+    }
+}
+
+/// Index of the range that the id was allocated from, or None if the id is a hash
+fn range_index(id: u64) -> Option<u32> {
+    (id & PRESPAWN_ID_FLAG != 0).then_some(((id & !PRESPAWN_ID_FLAG) >> 32) as u32)
+}
+
+/// Error returned when the server cannot adopt the id proposed by a client
+#[derive(thiserror::Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PreSpawnIdError {
+    #[error("the client {0:?} does not have a range of pre-spawned entity ids")]
+    NoRange(ClientId),
+    #[error("the id {id} is not in the range of the client {client_id:?}")]
+    OutOfRange { client_id: ClientId, id: u64 },
+}
+
+/// Message sent by the server to a client when it connects, with the range of ids reserved for the client
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub(crate) struct PreSpawnIdRangeMessage {
+    pub(crate) range: Option<PreSpawnIdRange>,
+}
+
+/// [`Resource`] that allocates the ids of the pre-spawned entities from the range of the local peer.
+///
+/// On the client, the range is received from the server when the client connects.
+#[derive(Resource, Debug, Default)]
+pub struct PreSpawnIdAllocator {
+    range: Option<PreSpawnIdRange>,
+    /// Tick of the current fixed update step (or of the tick being re-run during a rollback)
+    tick: Tick,
+    /// Number of ids allocated during the current tick
+    next: u16,
+}
+
+impl PreSpawnIdAllocator {
+    /// The range that the ids are allocated from
+    pub fn range(&self) -> Option<PreSpawnIdRange> {
+        self.range
+    }
+
+    /// Allocate a new id for an entity spawned during the current tick.
+    ///
+    /// Returns None if there is no range (for example because all the ranges of the server are in use),
+    /// or if the maximum number of ids was already allocated during this tick. In that case the
+    /// entity can still be pre-spawned with the default hash.
+    pub fn allocate(&mut self) -> Option<u64> {
+        let id = self.range?.id(self.tick, self.next)?;
+        self.next += 1;
+        Some(id)
+    }
+
+    fn start_tick(&mut self, tick: Tick) {
+        self.tick = tick;
+        self.next = 0;
+    }
+}
+
+/// Server [`Resource`] that keeps track of the range of pre-spawned entity ids reserved for each client
+#[derive(Resource, Debug)]
+pub struct PreSpawnIdRanges {
+    config: PreSpawnIdConfig,
+    /// Allocator of the server's own range
+    server: PreSpawnIdAllocator,
+    assigned: HashMap<ClientId, PreSpawnIdRange>,
+    /// Ranges that were released by disconnected clients, in the order in which they were released
+    released: VecDeque<u32>,
+    /// Index of the next range that was never assigned
+    next_unused: u32,
+}
+
+impl PreSpawnIdRanges {
+    pub(crate) fn new(config: PreSpawnIdConfig) -> Self {
+        Self {
+            config,
+            server: PreSpawnIdAllocator {
+                range: Some(PreSpawnIdRange::new(SERVER_RANGE, config.max_ids_per_tick)),
+                ..default()
+            },
+            assigned: HashMap::default(),
+            released: VecDeque::default(),
+            next_unused: SERVER_RANGE + 1,
+        }
+    }
+
+    /// The range reserved for a client
+    pub fn range(&self, client_id: ClientId) -> Option<PreSpawnIdRange> {
+        self.assigned.get(&client_id).copied()
+    }
+
+    /// Allocate a fresh id from the server's range, for an entity spawned during the current tick.
+    ///
+    /// Returns None if the maximum number of ids was already allocated during this tick.
+    pub fn allocate(&mut self) -> Option<u64> {
+        self.server.allocate()
+    }
+
+    /// Check that the id proposed by a client was allocated from the range of that client,
+    /// so that the server's entity can use the same id as the client's pre-spawned entity.
+    pub fn adopt(&self, client_id: ClientId, id: u64) -> Result<u64, PreSpawnIdError> {
+        let range = self
+            .range(client_id)
+            .ok_or(PreSpawnIdError::NoRange(client_id))?;
+        if !range.contains(id) {
+            return Err(PreSpawnIdError::OutOfRange { client_id, id });
+        }
+        Ok(id)
+    }
+
+    /// Reserve a range for a newly connected client.
+    ///
+    /// The ranges that were never used are assigned first, and the released ranges are reused
+    /// in the order in which they were released, so that a range is reused as late as possible
+    /// after its previous owner disconnected.
+    fn assign(&mut self, client_id: ClientId) -> Option<PreSpawnIdRange> {
+        if let Some(range) = self.range(client_id) {
+            return Some(range);
+        }
+        let index = if self.next_unused <= self.config.max_client_ranges {
+            self.next_unused += 1;
+            self.next_unused - 1
+        } else {
+            self.released.pop_front()?
+        };
+        let range = PreSpawnIdRange::new(index, self.config.max_ids_per_tick);
+        self.assigned.insert(client_id, range);
+        Some(range)
+    }
+
+    fn release(&mut self, client_id: ClientId) {
+        if let Some(range) = self.assigned.remove(&client_id) {
+            self.released.push_back(range.index);
+        }
+    }
+}
+
+/// Reset the allocations at the start of every fixed update step, including the steps that are
+/// re-run during a rollback, so that the same ids are allocated when a tick is re-run
+pub(crate) fn start_client_tick(
+    tick_manager: Res<TickManager>,
+    rollback: Option<Res<Rollback>>,
+    mut allocator: ResMut<PreSpawnIdAllocator>,
+) {
+    let tick = rollback.map_or(tick_manager.tick(), |rollback| {
+        tick_manager.tick_or_rollback_tick(&rollback)
+    });
+    allocator.start_tick(tick);
+}
+
+/// Client system that stores the range received from the server
+pub(crate) fn receive_range(
+    mut allocator: ResMut<PreSpawnIdAllocator>,
+    mut messages: EventReader<MessageEvent<PreSpawnIdRangeMessage>>,
+) {
+    for message in messages.read() {
+        debug!(range = ?message.message.range, "Received the range of pre-spawned entity ids");
+        allocator.range = message.message.range;
+    }
+}
+
+/// The range of the previous connection must not be used after a reconnection
+pub(crate) fn clear_client_range(mut allocator: ResMut<PreSpawnIdAllocator>) {
+    allocator.range = None;
+}
+
+pub(crate) fn start_server_tick(
+    tick_manager: Res<TickManager>,
+    mut ranges: ResMut<PreSpawnIdRanges>,
+) {
+    ranges.server.start_tick(tick_manager.tick());
+}
+
+/// Reserve a range for the client that just connected, and send it to the client
+pub(crate) fn assign_client_range(
+    trigger: Trigger<ConnectEvent>,
+    mut ranges: ResMut<PreSpawnIdRanges>,
+    mut connection_manager: ResMut<ServerConnectionManager>,
+) {
+    let client_id = trigger.event().client_id;
+    let range = ranges.assign(client_id);
+    if range.is_none() {
+        warn!(
+            ?client_id,
+            "All the ranges of pre-spawned entity ids are in use, the client will use the default hashes"
+        );
+    }
+    let _ = connection_manager
+        .send_message_to_target::<PreSpawnIdChannel, _>(
+            &PreSpawnIdRangeMessage { range },
+            NetworkTarget::Single(client_id),
+        )
+        .inspect_err(|e| error!("Could not send the range of pre-spawned entity ids: {e:?}"));
+}
+
+pub(crate) fn release_client_range(
+    trigger: Trigger<DisconnectEvent>,
+    mut ranges: ResMut<PreSpawnIdRanges>,
+) {
+    ranges.release(trigger.event().client_id);
+}
+
+/// Strip the [`PreSpawnedPlayerObject`] of the entities replicated by a client with an id that was
+/// not allocated from the range of that client
+pub(crate) fn reject_foreign_ids(
+    mut commands: Commands,
+    ranges: Res<PreSpawnIdRanges>,
+    violations: Option<ResMut<ClientUpdateViolations>>,
+    query: Query<(Entity, &Replicated, &PreSpawnedPlayerObject), Added<PreSpawnedPlayerObject>>,
+) {
+    let mut violations = violations;
+    for (entity, replicated, prespawn) in query.iter() {
+        let (Some(client_id), Some(id)) = (replicated.from, prespawn.hash) else {
+            continue;
+        };
+        if range_index(id).is_none() || ranges.adopt(client_id, id).is_ok() {
+            continue;
+        }
+        warn!(
+            ?client_id,
+            ?entity,
+            ?id,
+            "Rejected a pre-spawned entity id outside of the client's range"
+        );
+        commands.entity(entity).remove::<PreSpawnedPlayerObject>();
+        if let Some(violations) = violations.as_mut() {
+            violations.record(client_id, ValidationVerdict::Reject);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn client(id: u64) -> ClientId {
+        ClientId::Netcode(id)
+    }
+
+    #[test]
+    fn test_allocate_is_deterministic_per_tick() {
+        let mut allocator = PreSpawnIdAllocator::default();
+        allocator.start_tick(Tick(5));
+        assert_eq!(allocator.allocate(), None);
+
+        allocator.range = Some(PreSpawnIdRange::new(3, 2));
+        allocator.start_tick(Tick(5));
+        let first = allocator.allocate().unwrap();
+        let second = allocator.allocate().unwrap();
+        assert_ne!(first, second);
+        // the range is exhausted for this tick
+        assert_eq!(allocator.allocate(), None);
+
+        // a rollback re-runs the tick and allocates the same ids
+        allocator.start_tick(Tick(5));
+        assert_eq!(allocator.allocate(), Some(first));
+        assert_eq!(allocator.allocate(), Some(second));
+
+        allocator.start_tick(Tick(6));
+        let third = allocator.allocate().unwrap();
+        assert_ne!(third, first);
+        for id in [first, second, third] {
+            assert!(allocator.range.unwrap().contains(id));
+            assert!(!PreSpawnIdRange::new(4, 2).contains(id));
+            assert_eq!(range_index(id), Some(3));
+        }
+    }
+
+    #[test]
+    fn test_hash_is_not_an_id() {
+        assert_eq!(range_index(1572575978495317502), None);
+        assert!(!PreSpawnIdRange::new(0, 256).contains(1572575978495317502));
+    }
+
+    #[test]
+    fn test_range_exhaustion_and_reuse() {
+        let mut ranges = PreSpawnIdRanges::new(PreSpawnIdConfig {
+            max_client_ranges: 2,
+            max_ids_per_tick: 8,
+        });
+        let range_1 = ranges.assign(client(1)).unwrap();
+        let range_2 = ranges.assign(client(2)).unwrap();
+        assert_ne!(range_1.index(), SERVER_RANGE);
+        assert_ne!(range_2.index(), SERVER_RANGE);
+        assert_ne!(range_1, range_2);
+        // assigning again to the same client returns the same range
+        assert_eq!(ranges.assign(client(1)), Some(range_1));
+
+        // all the ranges are in use
+        assert_eq!(ranges.assign(client(3)), None);
+        assert_eq!(
+            ranges.adopt(client(3), range_1.id(Tick(0), 0).unwrap()),
+            Err(PreSpawnIdError::NoRange(client(3)))
+        );
+
+        // the ranges are reused in the order in which they were released
+        ranges.release(client(2));
+        ranges.release(client(1));
+        assert_eq!(ranges.assign(client(3)), Some(range_2));
+        // a reconnecting client does not necessarily get its previous range back
+        assert_eq!(ranges.assign(client(1)), Some(range_1));
+        ranges.release(client(3));
+        ranges.release(client(1));
+        assert_eq!(ranges.assign(client(1)), Some(range_2));
+
+        // the previous owner of a range cannot use it anymore
+        let id = range_1.id(Tick(0), 0).unwrap();
+        assert_eq!(
+            ranges.adopt(client(1), id),
+            Err(PreSpawnIdError::OutOfRange {
+                client_id: client(1),
+                id
+            })
+        );
+    }
+
+    #[test]
+    fn test_adopt_rejects_foreign_ids() {
+        let mut ranges = PreSpawnIdRanges::new(PreSpawnIdConfig::default());
+        let range_1 = ranges.assign(client(1)).unwrap();
+        let range_2 = ranges.assign(client(2)).unwrap();
+        let id_1 = range_1.id(Tick(10), 0).unwrap();
+        let id_2 = range_2.id(Tick(10), 0).unwrap();
+        assert_eq!(ranges.adopt(client(1), id_1), Ok(id_1));
+        assert!(ranges.adopt(client(1), id_2).is_err());
+
+        // ids from the server's range, hashes, or with a sequence number above the limit are rejected
+        ranges.server.start_tick(Tick(10));
+        let server_id = ranges.allocate().unwrap();
+        assert!(ranges.adopt(client(1), server_id).is_err());
+        assert!(ranges.adopt(client(1), 1572575978495317502).is_err());
+        assert!(ranges.adopt(client(1), id_1 | 0xFFFF).is_err());
+    }
+}
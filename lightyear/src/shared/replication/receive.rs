@@ -2,29 +2,52 @@
 use std::collections::BTreeMap;
 
 use bevy::ecs::entity::EntityHash;
-use bevy::prelude::{DespawnRecursiveExt, Entity, World};
+use bevy::prelude::{DespawnRecursiveExt, Entity, EntityWorldMut, World};
 use bevy::utils::HashSet;
-use tracing::{debug, error, trace, warn};
+use bytes::Bytes;
+use tracing::{debug, trace};
 #[cfg(feature = "trace")]
 use tracing::{instrument, Level};
 
+use crate::client::cleanup::{cleanup_entity, CleanupCause, ClientCleanupPolicy};
 use crate::packet::message::MessageId;
 use crate::prelude::client::Confirmed;
 use crate::prelude::{ClientId, Tick};
-use crate::protocol::component::ComponentRegistry;
+use crate::protocol::component::{
+    ComponentError, ComponentNetId, ComponentRegistry, DecodedComponent,
+};
 use crate::serialize::reader::Reader;
+use crate::serialize::ToBytes;
+use crate::server::validation::validate_client_spawn;
 use crate::shared::events::connection::ConnectionEvents;
+use crate::shared::replication::alias::EntityAliasReceiver;
 use crate::shared::replication::components::{Replicated, ReplicationGroupId};
+use crate::shared::replication::deferred::DeferredComponents;
+use crate::shared::replication::limits::{ReplicationLimiter, ReplicationLimits};
+use crate::shared::replication::parallel::{decode_messages, DecodedMessage, ParallelApplyConfig};
+use crate::shared::warnings::{NetworkWarning, NetworkWarnings};
 #[cfg(test)]
 use crate::utils::captures::Captures;
 
-use super::entity_map::RemoteEntityMap;
-use super::{EntityActionsMessage, EntityUpdatesMessage, SpawnAction};
+use super::entity_map::{EntityMap, RemoteEntityMap};
+use super::{EntityActions, EntityActionsMessage, EntityUpdatesMessage, SpawnAction};
 
 type EntityHashMap<K, V> = hashbrown::HashMap<K, V, EntityHash>;
 
 type EntityHashSet<K> = hashbrown::HashSet<K, EntityHash>;
 
+/// Number of ticks during which we keep the local entity of a replaced remote entity alive,
+/// while waiting for the spawn of its replacement
+const REPLACEMENT_TIMEOUT_TICKS: i16 = 64;
+
+/// Local entity kept alive after receiving a [`SpawnAction::DespawnReplaced`]
+#[derive(Debug)]
+pub(crate) struct PendingReplacement {
+    local_entity: Entity,
+    /// Remote tick of the despawn
+    despawn_tick: Tick,
+}
+
 #[derive(Debug)]
 pub(crate) struct ReplicationReceiver {
     /// Map between local and remote entities. (used mostly on client because it's when we receive entity updates)
@@ -33,9 +56,32 @@ pub(crate) struct ReplicationReceiver {
     /// Map from remote entity to the replication group-id
     pub remote_entity_to_group: EntityHashMap<Entity, ReplicationGroupId>,
 
+    /// Local entities of replaced remote entities, waiting for their replacement to be spawned
+    pending_replacements: EntityHashMap<Entity, PendingReplacement>,
+
     // BOTH
     /// Buffer to so that we have an ordered receiver per group
     pub group_channels: EntityHashMap<ReplicationGroupId, GroupChannel>,
+
+    /// Aliases used by the remote to refer to its entities in the update messages
+    pub(crate) entity_aliases: EntityAliasReceiver,
+
+    /// Enforces the limits on the received replication
+    pub(crate) limiter: ReplicationLimiter,
+
+    /// How to clean up the local entities that stop being replicated.
+    /// If `None`, the entities are despawned.
+    pub(crate) cleanup_policy: Option<ClientCleanupPolicy>,
+
+    /// How to deserialize the received component payloads in parallel
+    parallel_apply: ParallelApplyConfig,
+
+    /// Last actions message of each replication group that contains the entities that existed on the remote
+    /// when we connected, if they have not all been applied yet
+    join_snapshot: Option<Vec<(ReplicationGroupId, MessageId)>>,
+
+    /// Component writes that are waiting for the entities they reference to be spawned
+    deferred: DeferredComponents,
 }
 
 impl ReplicationReceiver {
@@ -44,11 +90,57 @@ impl ReplicationReceiver {
             // RECEIVE
             remote_entity_map: RemoteEntityMap::default(),
             remote_entity_to_group: Default::default(),
+            pending_replacements: Default::default(),
             // BOTH
             group_channels: Default::default(),
+            entity_aliases: EntityAliasReceiver::default(),
+            limiter: ReplicationLimiter::default(),
+            cleanup_policy: None,
+            parallel_apply: ParallelApplyConfig::default(),
+            join_snapshot: None,
+            deferred: DeferredComponents::default(),
         }
     }
 
+    /// Enforce the [`ReplicationLimits`] on the received replication
+    pub(crate) fn with_limits(mut self, limits: ReplicationLimits) -> Self {
+        self.limiter = ReplicationLimiter::new(limits);
+        self
+    }
+
+    /// Clean up the entities that stop being replicated according to the [`ClientCleanupPolicy`]
+    pub(crate) fn with_cleanup_policy(mut self, policy: ClientCleanupPolicy) -> Self {
+        self.cleanup_policy = Some(policy);
+        self
+    }
+
+    /// Deserialize the received component payloads in parallel according to the [`ParallelApplyConfig`]
+    pub(crate) fn with_parallel_apply(mut self, config: ParallelApplyConfig) -> Self {
+        self.parallel_apply = config;
+        self
+    }
+
+    /// Wait for the actions messages of the join snapshot to be applied
+    pub(crate) fn expect_join_snapshot(&mut self, groups: Vec<(ReplicationGroupId, MessageId)>) {
+        self.join_snapshot = Some(groups);
+    }
+
+    /// Returns true on the first call after all the actions messages of the join snapshot have been applied
+    pub(crate) fn take_applied_join_snapshot(&mut self) -> bool {
+        let Some(groups) = &self.join_snapshot else {
+            return false;
+        };
+        let applied = groups.iter().all(|(group_id, message_id)| {
+            self.group_channels
+                .get(group_id)
+                .is_some_and(|channel| channel.actions_pending_recv_message_id > *message_id)
+        });
+        if applied {
+            self.join_snapshot = None;
+        }
+        applied
+    }
+
     /// Buffer a received [`EntityActionsMessage`].
     ///
     /// The remote_tick is the tick at which the message was buffered and sent by the remote client.
@@ -203,6 +295,7 @@ impl ReplicationReceiver {
                 }
             }
         }
+        self.entity_aliases.cleanup(tick);
     }
 }
 
@@ -221,26 +314,66 @@ impl ReplicationReceiver {
         remote_tick: Tick,
         message: EntityActionsMessage,
         events: &mut ConnectionEvents,
+        warnings: &mut NetworkWarnings,
     ) {
         let group_id = message.group_id;
         debug!(?remote_tick, ?message, "Received replication actions");
+        self.regroup_entities(&message);
         // NOTE: order matters here, because some components can depend on other entities.
         // These components could even form a cycle, for example A.HasWeapon(B) and B.HasHolder(A)
         // Our solution is to first handle spawn for all entities separately.
+        // Entities that re-use the local entity of the entity they replace
+        let mut replacements = EntityHashSet::default();
         for (remote_entity, actions) in message.actions.iter() {
             debug!(?remote_entity, "Received entity actions");
             // spawn
             match actions.spawn {
-                SpawnAction::Spawn => {
+                SpawnAction::Spawn | SpawnAction::Replace(_) | SpawnAction::Regroup => {
+                    if self
+                        .remote_entity_to_group
+                        .get(remote_entity)
+                        .is_some_and(|g| *g != group_id)
+                    {
+                        debug!(
+                            ?remote_entity,
+                            "Ignored the spawn of an entity that was moved to another group"
+                        );
+                        continue;
+                    }
                     self.remote_entity_to_group.insert(*remote_entity, group_id);
+                    // the entity that moved to this group was already spawned in its previous group
+                    if actions.spawn == SpawnAction::Regroup
+                        && self.remote_entity_map.get_local(*remote_entity).is_some()
+                    {
+                        debug!(?remote_entity, "Received entity regroup");
+                        continue;
+                    }
                     if let Some(local_entity) = self.remote_entity_map.get_local(*remote_entity) {
                         if world.get_entity(*local_entity).is_some() {
-                            warn!("Received spawn for an entity that already exists");
+                            warnings.report(
+                                NetworkWarning::SpawnExistingEntity,
+                                format_args!("Received spawn for the entity {remote_entity:?} that already exists"),
+                            );
                             continue;
                         }
-                        warn!("Received spawn for an entity that is already in our entity mapping! Not spawning");
+                        warnings.report(
+                            NetworkWarning::SpawnExistingEntity,
+                            format_args!("Received spawn for the entity {remote_entity:?} that is already in our entity mapping! Not spawning"),
+                        );
                         continue;
                     }
+                    if let SpawnAction::Replace(replaced) = actions.spawn {
+                        if let Some(local_entity) = take_replaced_entity(
+                            world,
+                            replaced,
+                            &mut self.remote_entity_map,
+                            &mut self.pending_replacements,
+                        ) {
+                            self.remote_entity_map.insert(*remote_entity, local_entity);
+                            replacements.insert(*remote_entity);
+                            continue;
+                        }
+                    }
                     // TODO: optimization: spawn the bundle of insert components
 
                     // TODO: spawning all entities with Confirmed:
@@ -266,7 +399,10 @@ impl ReplicationReceiver {
                 SpawnAction::Reuse(local_entity) => {
                     let Some(mut entity_mut) = world.get_entity_mut(local_entity) else {
                         // TODO: ignore the entity in the next steps because it does not exist!
-                        error!("Received ReuseEntity({local_entity:?}) but the entity does not exist in the world");
+                        warnings.report(
+                            NetworkWarning::ReuseMissingEntity,
+                            format_args!("Received ReuseEntity({local_entity:?}) but the entity does not exist in the world"),
+                        );
                         continue;
                     };
                     entity_mut.insert(Replicated { from: remote });
@@ -280,32 +416,76 @@ impl ReplicationReceiver {
         for (entity, actions) in message.actions.into_iter() {
             debug!(remote_entity = ?entity, "Received entity actions");
 
+            // the entity was moved to another group
+            if self
+                .remote_entity_to_group
+                .get(&entity)
+                .is_some_and(|g| *g != group_id)
+            {
+                continue;
+            }
+
             // despawn
-            if actions.spawn == SpawnAction::Despawn {
+            if let Some(cause) = cleanup_cause(&actions.spawn) {
                 debug!(remote_entity = ?entity, "Received entity despawn");
                 if let Some(local_entity) = self.remote_entity_map.remove_by_remote(entity) {
                     if let Some(group) = self.group_channels.get_mut(&group_id) {
                         group.remote_entities.remove(&entity);
                     }
-                    // TODO: we despawn all children as well right now, but that might not be what we want?
-                    if let Some(entity_mut) = world.get_entity_mut(local_entity) {
-                        entity_mut.despawn_recursive();
-                    }
+                    cleanup_local_entity(
+                        world,
+                        local_entity,
+                        cause,
+                        self.cleanup_policy.as_ref(),
+                        component_registry,
+                    );
                     events.push_despawn(local_entity);
                     self.remote_entity_to_group.remove(&entity);
                 } else {
-                    error!("Received despawn for an entity that does not exist")
+                    warnings.report(
+                        NetworkWarning::DespawnUnknownEntity,
+                        format_args!(
+                            "Received despawn for the entity {entity:?} that does not exist"
+                        ),
+                    );
+                }
+                continue;
+            }
+            if actions.spawn == SpawnAction::DespawnReplaced {
+                if let Some(group) = self.group_channels.get_mut(&group_id) {
+                    group.remote_entities.remove(&entity);
                 }
+                despawn_replaced(
+                    entity,
+                    remote_tick,
+                    &mut self.remote_entity_map,
+                    &mut self.remote_entity_to_group,
+                    &mut self.pending_replacements,
+                );
                 continue;
             }
 
             // safety: we know by this point that the entity exists
             let Some(mut local_entity_mut) = self.remote_entity_map.get_by_remote(world, entity)
             else {
-                error!("cannot find entity");
+                warnings.report(
+                    NetworkWarning::UnknownEntity,
+                    format_args!("cannot find entity {entity:?}"),
+                );
                 continue;
             };
 
+            // the entity re-uses the local entity of the entity it replaces: remove the components
+            // that the replacement doesn't have
+            if replacements.contains(&entity) {
+                component_registry.remove_missing(
+                    &component_net_ids(&actions),
+                    &mut local_entity_mut,
+                    remote_tick,
+                    events,
+                );
+            }
+
             // NOTE: 2 options
             //  - send the raw data to a separate typed system
             //  -  or just insert it here via function pointers
@@ -322,12 +502,11 @@ impl ReplicationReceiver {
                         &mut reader,
                         &mut local_entity_mut,
                         remote_tick,
+                        remote,
                         &mut self.remote_entity_map.remote_to_local,
                         events,
                     )
-                    .inspect_err(|e| {
-                        error!("could not write the component to the entity: {:?}", e)
-                    });
+                    .inspect_err(|e| report_write_error(warnings, e));
 
                 // TODO: special-case for pre-spawned entities: we receive them from a client, but then we
                 //  we should immediately take ownership of it, so we won't receive a despawn for it
@@ -342,7 +521,11 @@ impl ReplicationReceiver {
             // removals
             trace!(remote_entity = ?entity, ?actions.remove, "Received RemoveComponent");
             for kind in actions.remove {
-                events.push_remove_component(local_entity_mut.id(), kind, Tick(0));
+                if component_registry.kind_map.is_deprecated(kind) {
+                    report_write_error(warnings, &ComponentError::Deprecated);
+                    continue;
+                }
+                events.push_remove_component(local_entity_mut.id(), kind, remote_tick);
                 component_registry.raw_remove(kind, &mut local_entity_mut);
             }
 
@@ -356,12 +539,11 @@ impl ReplicationReceiver {
                         &mut reader,
                         &mut local_entity_mut,
                         remote_tick,
+                        remote,
                         &mut self.remote_entity_map.remote_to_local,
                         events,
                     )
-                    .inspect_err(|e| {
-                        error!("could not write the component to the entity: {:?}", e)
-                    });
+                    .inspect_err(|e| report_write_error(warnings, e));
             }
         }
         self.update_confirmed_tick(world, group_id, remote_tick);
@@ -377,6 +559,7 @@ impl ReplicationReceiver {
         is_history: bool,
         message: EntityUpdatesMessage,
         events: &mut ConnectionEvents,
+        warnings: &mut NetworkWarnings,
     ) {
         let group_id = message.group_id;
         debug!(?remote_tick, ?message, "Received replication updates");
@@ -386,6 +569,14 @@ impl ReplicationReceiver {
         }
         for (entity, components) in message.updates.into_iter() {
             debug!(?components, remote_entity = ?entity, "Received UpdateComponent");
+            // the entity was moved to another group
+            if self
+                .remote_entity_to_group
+                .get(&entity)
+                .is_some_and(|g| *g != group_id)
+            {
+                continue;
+            }
             // update the entity only if it exists
             if let Some(mut local_entity_mut) = self.remote_entity_map.get_by_remote(world, entity)
             {
@@ -396,12 +587,11 @@ impl ReplicationReceiver {
                             &mut reader,
                             &mut local_entity_mut,
                             remote_tick,
+                            remote,
                             &mut self.remote_entity_map.remote_to_local,
                             events,
                         )
-                        .inspect_err(|e| {
-                            error!("could not write the component to the entity: {:?}", e)
-                        });
+                        .inspect_err(|e| report_write_error(warnings, e));
                 }
             } else {
                 // we can get a few buffered updates after the entity has been despawned
@@ -413,6 +603,30 @@ impl ReplicationReceiver {
         self.update_confirmed_tick(world, group_id, remote_tick);
     }
 
+    /// Move the entities of the [`SpawnAction::Regroup`] actions of the message from their previous group
+    /// to the group of the message.
+    ///
+    /// The messages of the previous group that are received afterwards are ignored for these entities.
+    fn regroup_entities(&mut self, message: &EntityActionsMessage) {
+        for (remote_entity, _) in message
+            .actions
+            .iter()
+            .filter(|(_, actions)| actions.spawn == SpawnAction::Regroup)
+        {
+            let Some(previous) = self
+                .remote_entity_to_group
+                .insert(*remote_entity, message.group_id)
+            else {
+                continue;
+            };
+            if previous != message.group_id {
+                if let Some(channel) = self.group_channels.get_mut(&previous) {
+                    channel.remote_entities.remove(remote_entity);
+                }
+            }
+        }
+    }
+
     /// Update the Confirmed tick for all entities in the replication group
     /// so that Predicted/Interpolated entities can be notified
     ///
@@ -463,6 +677,7 @@ impl ReplicationReceiver {
         component_registry: &ComponentRegistry,
         current_tick: Tick,
         events: &mut ConnectionEvents,
+        warnings: &mut NetworkWarnings,
     ) {
         // apply actions first
 
@@ -494,6 +709,9 @@ impl ReplicationReceiver {
         // });
 
         trace!(?current_tick, ?self.group_channels, "applying replication actions messages");
+        // collect the messages that are ready to be applied, so that their payloads can be
+        // deserialized in parallel before they are applied in order
+        let mut ready_actions = Vec::new();
         self.group_channels
             .iter_mut()
             .for_each(|(group_id, channel)| {
@@ -521,20 +739,10 @@ impl ReplicationReceiver {
                 channel.actions_pending_recv_message_id += 1;
                 // Update the latest server tick that we have processed
                 channel.latest_tick = Some(remote_tick);
-
-                channel.apply_actions_message(
-                    world,
-                    remote,
-                    component_registry,
-                    remote_tick,
-                    message,
-                    events,
-                    &mut self.remote_entity_map,
-                    &mut self.remote_entity_to_group,
-                );
+                ready_actions.push((*group_id, remote_tick, message));
             });
 
-        trace!(?self.group_channels, "applying replication updates messages");
+        let mut ready_updates = Vec::new();
         self.group_channels
             .iter_mut()
             .for_each(|(group_id, channel)| {
@@ -556,21 +764,247 @@ impl ReplicationReceiver {
                 while channel.buffered_updates.len() > max_applicable_idx {
                     let (remote_tick, message) = channel.buffered_updates.pop_oldest().unwrap();
                     let is_history = channel.buffered_updates.len() != max_applicable_idx;
-                    channel.apply_updates_message(
-                        world,
-                        remote,
-                        component_registry,
-                        remote_tick,
-                        is_history,
-                        message,
-                        events,
-                        &mut self.remote_entity_map,
-                    );
+                    ready_updates.push((*group_id, remote_tick, is_history, message));
                 }
-            })
+            });
+
+        let (decoded_actions, decoded_updates) = decode_messages(
+            &self.parallel_apply,
+            component_registry,
+            ready_actions.iter().map(|(_, _, message)| message),
+            ready_updates
+                .iter()
+                .map(|(_, _, is_history, message)| (message, *is_history)),
+        );
+
+        for ((group_id, remote_tick, message), mut decoded) in
+            ready_actions.into_iter().zip(decoded_actions)
+        {
+            self.regroup_entities(&message);
+            let channel = self.group_channels.get_mut(&group_id).unwrap();
+            channel.apply_actions_message(
+                world,
+                remote,
+                component_registry,
+                remote_tick,
+                message,
+                &mut decoded,
+                events,
+                warnings,
+                &mut self.remote_entity_map,
+                &mut self.remote_entity_to_group,
+                &mut self.pending_replacements,
+                &mut self.limiter,
+                self.cleanup_policy.as_ref(),
+                &mut self.deferred,
+            );
+        }
+
+        // despawn the replaced entities whose replacement was never received
+        self.pending_replacements.retain(|remote_entity, pending| {
+            if current_tick - pending.despawn_tick <= REPLACEMENT_TIMEOUT_TICKS {
+                return true;
+            }
+            debug!(
+                ?remote_entity,
+                "Did not receive the replacement of the entity, despawning it"
+            );
+            cleanup_local_entity(
+                world,
+                pending.local_entity,
+                CleanupCause::ServerDespawn,
+                self.cleanup_policy.as_ref(),
+                component_registry,
+            );
+            events.push_despawn(pending.local_entity);
+            false
+        });
+
+        trace!(?self.group_channels, "applying replication updates messages");
+        for ((group_id, remote_tick, is_history, message), mut decoded) in
+            ready_updates.into_iter().zip(decoded_updates)
+        {
+            let channel = self.group_channels.get_mut(&group_id).unwrap();
+            channel.apply_updates_message(
+                world,
+                remote,
+                component_registry,
+                remote_tick,
+                is_history,
+                message,
+                &mut decoded,
+                events,
+                warnings,
+                &mut self.remote_entity_map,
+                &self.remote_entity_to_group,
+                &mut self.deferred,
+            );
+        }
+
+        // the entities referenced by the deferred components might have been spawned
+        self.deferred.apply(
+            world,
+            remote,
+            component_registry,
+            &mut self.remote_entity_map.remote_to_local,
+            events,
+            warnings,
+        );
+    }
+}
+
+/// Write a received component payload to the entity, using the value that was decoded ahead of time if there is one
+#[allow(clippy::too_many_arguments)]
+fn write_component(
+    component_registry: &ComponentRegistry,
+    payload: Bytes,
+    decoded: Option<DecodedComponent>,
+    entity_world_mut: &mut EntityWorldMut,
+    remote_tick: Tick,
+    remote: Option<ClientId>,
+    entity_map: &mut EntityMap,
+    events: &mut ConnectionEvents,
+    warnings: &mut NetworkWarnings,
+    deferred: &mut DeferredComponents,
+) {
+    let result = match decoded {
+        Some(decoded) => component_registry.raw_write_decoded(
+            decoded,
+            entity_world_mut,
+            remote_tick,
+            remote,
+            entity_map,
+            events,
+        ),
+        None => {
+            // TODO: reuse a single reader that reads through the entire message
+            let mut reader = Reader::from(payload.clone());
+            component_registry.raw_write(
+                &mut reader,
+                entity_world_mut,
+                remote_tick,
+                remote,
+                entity_map,
+                events,
+            )
+        }
+    };
+    match result {
+        Err(ComponentError::UnspawnedEntity(entity)) => {
+            debug!(
+                ?entity,
+                "Deferred the write of a component that references an entity that was not spawned yet"
+            );
+            deferred.defer(entity_world_mut.id(), payload, remote_tick, warnings);
+        }
+        result => {
+            deferred.discard_payload(entity_world_mut.id(), &payload);
+            let _ = result.inspect_err(|e| report_write_error(warnings, e));
+        }
+    }
+}
+
+/// Report an error encountered while writing a received component to its entity.
+///
+/// The components whose type was removed from the protocol are skipped silently.
+pub(super) fn report_write_error(warnings: &mut NetworkWarnings, error: &ComponentError) {
+    match error {
+        ComponentError::Deprecated => warnings.report(
+            NetworkWarning::DeprecatedKind,
+            format_args!("Skipped a component of a deprecated type"),
+        ),
+        _ => warnings.report(
+            NetworkWarning::ComponentWriteFailed,
+            format_args!("could not write the component to the entity: {error:?}"),
+        ),
     }
 }
 
+/// Cause of the cleanup of the local entity, if the action stops the replication of the entity
+fn cleanup_cause(spawn: &SpawnAction) -> Option<CleanupCause> {
+    match spawn {
+        SpawnAction::Despawn => Some(CleanupCause::ServerDespawn),
+        SpawnAction::DespawnInterestLost => Some(CleanupCause::InterestLoss),
+        _ => None,
+    }
+}
+
+/// Clean up the local entity of a remote entity that stopped being replicated.
+///
+/// Without a [`ClientCleanupPolicy`] (for example on the server), the entity is simply despawned.
+fn cleanup_local_entity(
+    world: &mut World,
+    local_entity: Entity,
+    cause: CleanupCause,
+    policy: Option<&ClientCleanupPolicy>,
+    component_registry: &ComponentRegistry,
+) {
+    match policy {
+        Some(policy) => cleanup_entity(
+            world,
+            local_entity,
+            cause,
+            policy.action(cause),
+            component_registry,
+        ),
+        // TODO: we despawn all children as well right now, but that might not be what we want?
+        None => {
+            if let Some(entity_mut) = world.get_entity_mut(local_entity) {
+                entity_mut.despawn_recursive();
+            }
+        }
+    }
+}
+
+/// Get the local entity that should be re-used by an entity that replaces the remote entity `replaced`
+fn take_replaced_entity(
+    world: &World,
+    replaced: Entity,
+    remote_entity_map: &mut RemoteEntityMap,
+    pending_replacements: &mut EntityHashMap<Entity, PendingReplacement>,
+) -> Option<Entity> {
+    let local_entity = match pending_replacements.remove(&replaced) {
+        Some(pending) => pending.local_entity,
+        // we haven't received the despawn of the replaced entity yet
+        None => remote_entity_map.remove_by_remote(replaced)?,
+    };
+    world.get_entity(local_entity).map(|_| local_entity)
+}
+
+/// Handle the despawn of a remote entity that is being replaced.
+///
+/// If the replacement was not received yet, we keep the local entity alive so that the
+/// replacement can re-use it.
+fn despawn_replaced(
+    remote_entity: Entity,
+    remote_tick: Tick,
+    remote_entity_map: &mut RemoteEntityMap,
+    remote_entity_to_group: &mut EntityHashMap<Entity, ReplicationGroupId>,
+    pending_replacements: &mut EntityHashMap<Entity, PendingReplacement>,
+) {
+    remote_entity_to_group.remove(&remote_entity);
+    // if the replacement was already received, the local entity is already mapped to the replacement
+    if let Some(local_entity) = remote_entity_map.remove_by_remote(remote_entity) {
+        pending_replacements.insert(
+            remote_entity,
+            PendingReplacement {
+                local_entity,
+                despawn_tick: remote_tick,
+            },
+        );
+    }
+}
+
+/// Net ids of the components contained in the entity actions
+fn component_net_ids(actions: &EntityActions) -> Vec<ComponentNetId> {
+    actions
+        .insert
+        .iter()
+        .chain(actions.updates.iter())
+        .filter_map(|bytes| ComponentNetId::from_bytes(&mut Reader::from(bytes.clone())).ok())
+        .collect()
+}
+
 /// Channel to keep track of receiving/sending replication messages for a given Group
 #[derive(Debug)]
 pub struct GroupChannel {
@@ -785,29 +1219,103 @@ impl GroupChannel {
         component_registry: &ComponentRegistry,
         remote_tick: Tick,
         message: EntityActionsMessage,
+        decoded: &mut DecodedMessage,
         events: &mut ConnectionEvents,
+        warnings: &mut NetworkWarnings,
         remote_entity_map: &mut RemoteEntityMap,
         remote_entity_to_group: &mut EntityHashMap<Entity, ReplicationGroupId>,
+        pending_replacements: &mut EntityHashMap<Entity, PendingReplacement>,
+        limiter: &mut ReplicationLimiter,
+        cleanup_policy: Option<&ClientCleanupPolicy>,
+        deferred: &mut DeferredComponents,
     ) {
         let group_id = message.group_id;
         debug!(?remote_tick, ?message, "Received replication actions");
         // NOTE: order matters here, because some components can depend on other entities.
         // These components could even form a cycle, for example A.HasWeapon(B) and B.HasHolder(A)
         // Our solution is to first handle spawn for all entities separately.
+        // Entities that re-use the local entity of the entity they replace
+        let mut replacements = EntityHashSet::default();
         for (remote_entity, actions) in message.actions.iter() {
             debug!(?remote_entity, "Received entity actions");
             // spawn
             match actions.spawn {
-                SpawnAction::Spawn => {
+                SpawnAction::Spawn | SpawnAction::Replace(_) | SpawnAction::Regroup => {
+                    if remote_entity_to_group
+                        .get(remote_entity)
+                        .is_some_and(|g| *g != group_id)
+                    {
+                        debug!(
+                            ?remote_entity,
+                            "Ignored the spawn of an entity that was moved to another group"
+                        );
+                        continue;
+                    }
                     remote_entity_to_group.insert(*remote_entity, group_id);
+                    // the entity that moved to this group was already spawned in its previous group
+                    if actions.spawn == SpawnAction::Regroup
+                        && remote_entity_map.get_local(*remote_entity).is_some()
+                    {
+                        debug!(?remote_entity, "Received entity regroup");
+                        continue;
+                    }
                     if let Some(local_entity) = remote_entity_map.get_local(*remote_entity) {
                         if world.get_entity(*local_entity).is_some() {
-                            warn!("Received spawn for an entity that already exists");
+                            warnings.report(
+                                NetworkWarning::SpawnExistingEntity,
+                                format_args!("Received spawn for the entity {remote_entity:?} that already exists"),
+                            );
                             continue;
                         }
-                        warn!("Received spawn for an entity that is already in our entity mapping! Not spawning");
+                        warnings.report(
+                            NetworkWarning::SpawnExistingEntity,
+                            format_args!("Received spawn for the entity {remote_entity:?} that is already in our entity mapping! Not spawning"),
+                        );
                         continue;
                     }
+                    if let Some(client_id) = remote {
+                        let allowed = validate_client_spawn(world, client_id, || {
+                            component_net_ids(actions)
+                                .into_iter()
+                                .filter_map(|net_id| component_registry.kind_map.kind(net_id))
+                                .copied()
+                                .collect()
+                        });
+                        if !allowed {
+                            debug!(
+                                ?remote_entity,
+                                ?client_id,
+                                "Rejected entity spawn: the client spawn validator rejected it"
+                            );
+                            limiter.reject_spawn(*remote_entity);
+                            continue;
+                        }
+                    }
+                    if !limiter.allow_spawn(*remote_entity) {
+                        debug!(
+                            ?remote_entity,
+                            "Rejected entity spawn: a replication limit was exceeded"
+                        );
+                        continue;
+                    }
+                    if let SpawnAction::Replace(replaced) = actions.spawn {
+                        if let Some(local_entity) = take_replaced_entity(
+                            world,
+                            replaced,
+                            remote_entity_map,
+                            pending_replacements,
+                        ) {
+                            remote_entity_map.insert(*remote_entity, local_entity);
+                            replacements.insert(*remote_entity);
+                            debug!(
+                                ?remote_entity,
+                                ?replaced,
+                                ?local_entity,
+                                "Received entity replacement"
+                            );
+                            continue;
+                        }
+                    }
                     // TODO: optimization: spawn the bundle of insert components
 
                     // TODO: spawning all entities with Confirmed:
@@ -832,7 +1340,10 @@ impl GroupChannel {
                 SpawnAction::Reuse(local_entity) => {
                     let Some(mut entity_mut) = world.get_entity_mut(local_entity) else {
                         // TODO: ignore the entity in the next steps because it does not exist!
-                        error!("Received ReuseEntity({local_entity:?}) but the entity does not exist in the world");
+                        warnings.report(
+                            NetworkWarning::ReuseMissingEntity,
+                            format_args!("Received ReuseEntity({local_entity:?}) but the entity does not exist in the world"),
+                        );
                         continue;
                     };
                     entity_mut.insert(Replicated { from: remote });
@@ -843,32 +1354,91 @@ impl GroupChannel {
             }
         }
 
-        for (entity, actions) in message.actions.into_iter() {
+        for (entity_idx, (entity, actions)) in message.actions.into_iter().enumerate() {
             debug!(remote_entity = ?entity, "Received entity actions");
 
+            // the entity was moved to another group
+            if remote_entity_to_group
+                .get(&entity)
+                .is_some_and(|g| *g != group_id)
+            {
+                continue;
+            }
+
+            // the entity was never spawned because of the replication limits
+            if limiter.is_rejected(entity) {
+                if matches!(
+                    actions.spawn,
+                    SpawnAction::Despawn
+                        | SpawnAction::DespawnReplaced
+                        | SpawnAction::DespawnInterestLost
+                ) {
+                    limiter.despawn(entity);
+                    self.remote_entities.remove(&entity);
+                    remote_entity_to_group.remove(&entity);
+                }
+                continue;
+            }
+
             // despawn
-            if actions.spawn == SpawnAction::Despawn {
-                debug!(remote_entity = ?entity, "Received entity despawn");
+            if let Some(cause) = cleanup_cause(&actions.spawn) {
+                debug!(remote_entity = ?entity, ?cause, "Received entity despawn");
+                limiter.despawn(entity);
                 if let Some(local_entity) = remote_entity_map.remove_by_remote(entity) {
                     self.remote_entities.remove(&entity);
-                    // TODO: we despawn all children as well right now, but that might not be what we want?
-                    if let Some(entity_mut) = world.get_entity_mut(local_entity) {
-                        entity_mut.despawn_recursive();
-                    }
+                    cleanup_local_entity(
+                        world,
+                        local_entity,
+                        cause,
+                        cleanup_policy,
+                        component_registry,
+                    );
                     events.push_despawn(local_entity);
                     remote_entity_to_group.remove(&entity);
                 } else {
-                    error!("Received despawn for an entity that does not exist")
+                    warnings.report(
+                        NetworkWarning::DespawnUnknownEntity,
+                        format_args!(
+                            "Received despawn for the entity {entity:?} that does not exist"
+                        ),
+                    );
                 }
                 continue;
             }
+            if actions.spawn == SpawnAction::DespawnReplaced {
+                debug!(remote_entity = ?entity, "Received despawn of a replaced entity");
+                limiter.despawn(entity);
+                self.remote_entities.remove(&entity);
+                despawn_replaced(
+                    entity,
+                    remote_tick,
+                    remote_entity_map,
+                    remote_entity_to_group,
+                    pending_replacements,
+                );
+                continue;
+            }
 
             // safety: we know by this point that the entity exists
             let Some(mut local_entity_mut) = remote_entity_map.get_by_remote(world, entity) else {
-                error!(?entity, "cannot find entity");
+                warnings.report(
+                    NetworkWarning::UnknownEntity,
+                    format_args!("cannot find entity {entity:?}"),
+                );
                 continue;
             };
 
+            // the entity re-uses the local entity of the entity it replaces: remove the components
+            // that the replacement doesn't have
+            if replacements.contains(&entity) {
+                component_registry.remove_missing(
+                    &component_net_ids(&actions),
+                    &mut local_entity_mut,
+                    remote_tick,
+                    events,
+                );
+            }
+
             // NOTE: 2 options
             //  - send the raw data to a separate typed system
             //  -  or just insert it here via function pointers
@@ -876,20 +1446,27 @@ impl GroupChannel {
             // inserts
             // TODO: remove updates that are duplicate for the same component
             debug!(remote_entity = ?entity, "Received InsertComponent");
-            for component in actions.insert {
-                // TODO: reuse a single reader that reads through the entire message
-                let mut reader = Reader::from(component);
-                let _ = component_registry
-                    .raw_write(
-                        &mut reader,
-                        &mut local_entity_mut,
-                        remote_tick,
-                        &mut remote_entity_map.remote_to_local,
-                        events,
-                    )
-                    .inspect_err(|e| {
-                        error!("could not write the component to the entity: {:?}", e)
-                    });
+            let num_inserts = actions.insert.len();
+            for (payload_idx, component) in actions.insert.into_iter().enumerate() {
+                if !limiter.allow_insert(entity, &component) {
+                    debug!(
+                        remote_entity = ?entity,
+                        "Rejected component insert: a replication limit was exceeded"
+                    );
+                    continue;
+                }
+                write_component(
+                    component_registry,
+                    component,
+                    decoded.take(entity_idx, payload_idx),
+                    &mut local_entity_mut,
+                    remote_tick,
+                    remote,
+                    &mut remote_entity_map.remote_to_local,
+                    events,
+                    warnings,
+                    deferred,
+                );
 
                 // TODO: special-case for pre-spawned entities: we receive them from a client, but then we
                 //  we should immediately take ownership of it, so we won't receive a despawn for it
@@ -904,25 +1481,31 @@ impl GroupChannel {
             // removals
             trace!(remote_entity = ?entity, ?actions.remove, "Received RemoveComponent");
             for kind in actions.remove {
-                events.push_remove_component(local_entity_mut.id(), kind, Tick(0));
+                if component_registry.kind_map.is_deprecated(kind) {
+                    report_write_error(warnings, &ComponentError::Deprecated);
+                    continue;
+                }
+                limiter.remove(entity, kind);
+                deferred.discard(local_entity_mut.id(), kind);
+                events.push_remove_component(local_entity_mut.id(), kind, remote_tick);
                 component_registry.raw_remove(kind, &mut local_entity_mut);
             }
 
             // updates
             debug!(remote_entity = ?entity, "Received UpdateComponent");
-            for component in actions.updates {
-                let mut reader = Reader::from(component);
-                let _ = component_registry
-                    .raw_write(
-                        &mut reader,
-                        &mut local_entity_mut,
-                        remote_tick,
-                        &mut remote_entity_map.remote_to_local,
-                        events,
-                    )
-                    .inspect_err(|e| {
-                        error!("could not write the component to the entity: {:?}", e)
-                    });
+            for (payload_idx, component) in actions.updates.into_iter().enumerate() {
+                write_component(
+                    component_registry,
+                    component,
+                    decoded.take(entity_idx, num_inserts + payload_idx),
+                    &mut local_entity_mut,
+                    remote_tick,
+                    remote,
+                    &mut remote_entity_map.remote_to_local,
+                    events,
+                    warnings,
+                    deferred,
+                );
             }
         }
         self.update_confirmed_tick(world, group_id, remote_tick, remote_entity_map);
@@ -936,8 +1519,12 @@ impl GroupChannel {
         remote_tick: Tick,
         is_history: bool,
         message: EntityUpdatesMessage,
+        decoded: &mut DecodedMessage,
         events: &mut ConnectionEvents,
+        warnings: &mut NetworkWarnings,
         remote_entity_map: &mut RemoteEntityMap,
+        remote_entity_to_group: &EntityHashMap<Entity, ReplicationGroupId>,
+        deferred: &mut DeferredComponents,
     ) {
         let group_id = message.group_id;
         debug!(?remote_tick, ?message, "Received replication updates");
@@ -945,23 +1532,30 @@ impl GroupChannel {
         if is_history {
             return;
         }
-        for (entity, components) in message.updates.into_iter() {
+        for (entity_idx, (entity, components)) in message.updates.into_iter().enumerate() {
             debug!(?components, remote_entity = ?entity, "Received UpdateComponent");
+            // the entity was moved to another group
+            if remote_entity_to_group
+                .get(&entity)
+                .is_some_and(|g| *g != group_id)
+            {
+                continue;
+            }
             // update the entity only if it exists
             if let Some(mut local_entity_mut) = remote_entity_map.get_by_remote(world, entity) {
-                for component in components {
-                    let mut reader = Reader::from(component);
-                    let _ = component_registry
-                        .raw_write(
-                            &mut reader,
-                            &mut local_entity_mut,
-                            remote_tick,
-                            &mut remote_entity_map.remote_to_local,
-                            events,
-                        )
-                        .inspect_err(|e| {
-                            error!("could not write the component to the entity: {:?}", e)
-                        });
+                for (payload_idx, component) in components.into_iter().enumerate() {
+                    write_component(
+                        component_registry,
+                        component,
+                        decoded.take(entity_idx, payload_idx),
+                        &mut local_entity_mut,
+                        remote_tick,
+                        remote,
+                        &mut remote_entity_map.remote_to_local,
+                        events,
+                        warnings,
+                        deferred,
+                    );
                 }
             } else {
                 // we can get a few buffered updates after the entity has been despawned
@@ -1341,6 +1935,7 @@ mod tests {
             Tick(0),
             replication,
             &mut events,
+            &mut NetworkWarnings::default(),
         );
 
         // check that no new entities were spawned
@@ -1351,4 +1946,41 @@ mod tests {
             &local_entity
         );
     }
+
+    /// Despawns for unknown entities are counted instead of being logged one by one
+    #[test]
+    fn test_recv_despawn_unknown_entity_warning() {
+        let mut manager = ReplicationReceiver::new();
+        let mut world = World::new();
+        let component_registry = ComponentRegistry::default();
+        let mut events = ConnectionEvents::default();
+        let mut warnings = NetworkWarnings::default();
+        let replication = EntityActionsMessage {
+            group_id: ReplicationGroupId(0),
+            sequence_id: MessageId(0),
+            actions: (0..100)
+                .map(|i| {
+                    (
+                        Entity::from_raw(1000 + i),
+                        EntityActions {
+                            spawn: SpawnAction::Despawn,
+                            ..Default::default()
+                        },
+                    )
+                })
+                .collect(),
+        };
+        manager.apply_actions_message(
+            &mut world,
+            None,
+            &component_registry,
+            Tick(0),
+            replication,
+            &mut events,
+            &mut warnings,
+        );
+
+        assert_eq!(warnings.count(NetworkWarning::DespawnUnknownEntity), 100);
+        assert_eq!(warnings.count(NetworkWarning::UnknownEntity), 0);
+    }
 }
@@ -1,4 +1,8 @@
 //! Module to handle the replication of bevy [`Resource`]s
+//!
+//! The receiver emits a [`ResourceUpdateEvent`](crate::prelude::client::ResourceUpdateEvent) when a replicated
+//! resource is inserted or updated, and a [`ResourceRemoveEvent`](crate::prelude::client::ResourceRemoveEvent)
+//! when it is removed.
 
 use std::marker::PhantomData;
 
@@ -175,11 +179,13 @@ pub(crate) mod send {
 pub(crate) mod receive {
 
     use crate::protocol::EventContext;
-    use crate::shared::events::components::MessageEvent;
+    use crate::shared::events::components::{
+        MessageEvent, ResourceRemoveEvent, ResourceUpdateEvent,
+    };
     use crate::shared::message::MessageSend;
 
     use crate::shared::replication::ReplicationPeer;
-    use bevy::prelude::{DetectChangesMut, EventReader, Events};
+    use bevy::prelude::{DetectChangesMut, EventWriter, Events};
     use tracing::trace;
 
     use super::*;
@@ -216,6 +222,8 @@ pub(crate) mod receive {
         app: &mut App,
         is_bidirectional: bool,
     ) {
+        app.add_event::<ResourceUpdateEvent<R, S::EventContext>>();
+        app.add_event::<ResourceRemoveEvent<R, S::EventContext>>();
         // If `is_bidirectional` is  true, that means that the resource can be replicated in both directions.
         // In that case, we need to disable change detection or we would get an infinite loop of updates.
         if is_bidirectional {
@@ -236,7 +244,9 @@ pub(crate) mod receive {
     fn handle_resource_message<R: Resource + Message, Ctx: EventContext>(
         mut commands: Commands,
         mut update_message: ResMut<Events<MessageEvent<R, Ctx>>>,
-        mut remove_message: EventReader<MessageEvent<DespawnResource<R>, Ctx>>,
+        mut remove_message: ResMut<Events<MessageEvent<DespawnResource<R>, Ctx>>>,
+        mut update_events: EventWriter<ResourceUpdateEvent<R, Ctx>>,
+        mut remove_events: EventWriter<ResourceRemoveEvent<R, Ctx>>,
         mut resource: Option<ResMut<R>>,
     ) {
         for message in update_message.drain() {
@@ -247,10 +257,12 @@ pub(crate) mod receive {
             } else {
                 commands.insert_resource(message.message);
             }
+            update_events.send(ResourceUpdateEvent::new(message.context));
         }
-        for message in remove_message.read() {
+        for message in remove_message.drain() {
             if resource.is_some() {
                 commands.remove_resource::<R>();
+                remove_events.send(ResourceRemoveEvent::new(message.context));
             }
         }
     }
@@ -258,7 +270,9 @@ pub(crate) mod receive {
     fn handle_resource_message_bidirectional<R: Resource + Message, Ctx: EventContext>(
         mut commands: Commands,
         mut update_message: ResMut<Events<MessageEvent<R, Ctx>>>,
-        mut remove_message: EventReader<MessageEvent<DespawnResource<R>, Ctx>>,
+        mut remove_message: ResMut<Events<MessageEvent<DespawnResource<R>, Ctx>>>,
+        mut update_events: EventWriter<ResourceUpdateEvent<R, Ctx>>,
+        mut remove_events: EventWriter<ResourceRemoveEvent<R, Ctx>>,
         mut resource: Option<ResMut<R>>,
     ) {
         for message in update_message.drain() {
@@ -271,10 +285,12 @@ pub(crate) mod receive {
             } else {
                 commands.insert_resource(message.message);
             }
+            update_events.send(ResourceUpdateEvent::new(message.context));
         }
-        for message in remove_message.read() {
+        for message in remove_message.drain() {
             if resource.is_some() {
                 commands.remove_resource::<R>();
+                remove_events.send(ResourceRemoveEvent::new(message.context));
             }
         }
     }
@@ -298,11 +314,12 @@ pub(crate) mod receive {
 
 #[cfg(test)]
 mod tests {
+    use crate::prelude::client::{ResourceRemoveEvent, ResourceUpdateEvent};
     use crate::shared::replication::network_target::NetworkTarget;
     use crate::shared::replication::resources::ReplicateResourceExt;
     use crate::tests::protocol::{Channel1, Resource1, Resource2};
     use crate::tests::stepper::{BevyStepper, Step};
-    use bevy::prelude::Commands;
+    use bevy::prelude::{Commands, EventReader, ResMut, Resource, Update};
 
     use super::StopReplicateResourceExt;
 
@@ -395,6 +412,65 @@ mod tests {
         assert_eq!(stepper.client_app.world().resource::<Resource1>().0, 1.0);
     }
 
+    #[derive(Resource, Default)]
+    struct ResourceEventCounts {
+        updates: usize,
+        removals: usize,
+    }
+
+    fn count_resource_events(
+        mut counts: ResMut<ResourceEventCounts>,
+        mut updates: EventReader<ResourceUpdateEvent<Resource1>>,
+        mut removals: EventReader<ResourceRemoveEvent<Resource1>>,
+    ) {
+        counts.updates += updates.read().count();
+        counts.removals += removals.read().count();
+    }
+
+    /// Check that the client receives an event when a replicated resource is inserted, updated or removed
+    #[test]
+    fn test_resource_events() {
+        let mut stepper = BevyStepper::default();
+        stepper
+            .client_app
+            .init_resource::<ResourceEventCounts>()
+            .add_systems(Update, count_resource_events);
+        let start_replicate_system =
+            stepper
+                .server_app
+                .world_mut()
+                .register_system(|mut commands: Commands| {
+                    commands.replicate_resource::<Resource1, Channel1>(NetworkTarget::All);
+                });
+        let _ = stepper
+            .server_app
+            .world_mut()
+            .run_system(start_replicate_system);
+        stepper
+            .server_app
+            .world_mut()
+            .insert_resource(Resource1(1.0));
+        stepper.frame_step();
+        stepper.frame_step();
+        let counts = stepper.client_app.world().resource::<ResourceEventCounts>();
+        assert_eq!((counts.updates, counts.removals), (1, 0));
+
+        stepper.server_app.world_mut().resource_mut::<Resource1>().0 = 2.0;
+        stepper.frame_step();
+        stepper.frame_step();
+        let counts = stepper.client_app.world().resource::<ResourceEventCounts>();
+        assert_eq!((counts.updates, counts.removals), (2, 0));
+
+        stepper
+            .server_app
+            .world_mut()
+            .remove_resource::<Resource1>();
+        stepper.frame_step();
+        stepper.frame_step();
+        let counts = stepper.client_app.world().resource::<ResourceEventCounts>();
+        assert_eq!((counts.updates, counts.removals), (2, 1));
+    }
+
     // /// Check that when a client disconnects, every resource that was spawned from replication
     // /// gets despawned.
     // #[test]
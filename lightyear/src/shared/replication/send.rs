@@ -1,7 +1,7 @@
 //! General struct handling replication
 use std::iter::Extend;
 
-use crate::channel::builder::{EntityActionsChannel, EntityUpdatesChannel};
+use crate::channel::builder::{EntityActionsChannel, EntityAliasChannel, EntityUpdatesChannel};
 use bevy::ecs::component::Tick as BevyTick;
 use bevy::ecs::entity::EntityHash;
 use bevy::prelude::Entity;
@@ -19,9 +19,15 @@ use crate::prelude::{ChannelKind, ComponentRegistry, PacketError, Tick, TimeMana
 use crate::protocol::component::{ComponentKind, ComponentNetId};
 use crate::serialize::writer::Writer;
 use crate::serialize::{SerializationError, ToBytes};
+use crate::shared::replication::alias::EntityAliasSender;
+use crate::shared::replication::changes::{ReplicationChangeType, ReplicationChanges};
+use crate::shared::replication::classes::ReplicationClass;
+use crate::shared::replication::coalesce::UpdateMessageCache;
 use crate::shared::replication::components::ReplicationGroupId;
 use crate::shared::replication::delta::DeltaManager;
 use crate::shared::replication::error::ReplicationError;
+use crate::shared::replication::frequency::SendIntervalManager;
+use crate::shared::replication::keyframe::KeyframeManager;
 use crate::shared::replication::plugin::{ReplicationConfig, SendUpdatesMode};
 #[cfg(test)]
 use {
@@ -48,6 +54,20 @@ pub(crate) struct UpdateMessageMetadata {
     tick: Tick,
 }
 
+/// Number of bytes of component updates buffered since the stats were last flushed to the diagnostics
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub(crate) struct ReplicationSendStats {
+    /// Bytes of updates sent because the component changed
+    pub(crate) update_bytes: usize,
+    /// Bytes of keyframes (full component values that are periodically re-sent)
+    pub(crate) keyframe_bytes: usize,
+    /// Bytes saved by referring to entities with an alias in the update messages
+    pub(crate) alias_saved_bytes: usize,
+    /// Bytes of update messages that were not serialized again because another connection
+    /// serialized the same message
+    pub(crate) coalesced_bytes: usize,
+}
+
 #[derive(Debug)]
 pub(crate) struct ReplicationSender {
     /// Get notified whenever a message-id that was sent has been received by the remote
@@ -72,6 +92,25 @@ pub(crate) struct ReplicationSender {
     /// We update the `send_tick` only when the message was actually sent.
     pub message_send_receiver: Receiver<MessageId>,
 
+    // KEYFRAMES
+    pub(crate) keyframe_manager: KeyframeManager,
+    pub(crate) stats: ReplicationSendStats,
+
+    // SEND FREQUENCY
+    /// Timers of the components replicated with [`ReplicationMode::EveryInterval`](super::frequency::ReplicationMode::EveryInterval)
+    pub(crate) send_intervals: SendIntervalManager,
+
+    // PERSONALIZED
+    /// Last serialized value of each personalized component sent to the remote
+    personalized_cache: EntityHashMap<Entity, HashMap<ComponentKind, Bytes>>,
+
+    // ALIASES
+    pub(crate) entity_aliases: EntityAliasSender,
+
+    // CHANGES
+    /// Ordered changes sent to the remote, recorded only for the [`ReplicationChangesPlugin`](super::changes::ReplicationChangesPlugin)
+    pub(crate) changes: ReplicationChanges,
+
     replication_config: ReplicationConfig,
     bandwidth_cap_enabled: bool,
 }
@@ -81,6 +120,7 @@ impl ReplicationSender {
         updates_ack_receiver: Receiver<MessageId>,
         updates_nack_receiver: Receiver<MessageId>,
         message_send_receiver: Receiver<MessageId>,
+        alias_ack_receiver: Receiver<MessageId>,
         replication_config: ReplicationConfig,
         bandwidth_cap_enabled: bool,
     ) -> Self {
@@ -96,6 +136,20 @@ impl ReplicationSender {
             replication_config,
             // PRIORITY
             message_send_receiver,
+            // KEYFRAMES
+            keyframe_manager: KeyframeManager::default(),
+            stats: ReplicationSendStats::default(),
+            // SEND FREQUENCY
+            send_intervals: SendIntervalManager::default(),
+            // PERSONALIZED
+            personalized_cache: EntityHashMap::default(),
+            // ALIASES
+            entity_aliases: EntityAliasSender::new(
+                replication_config.entity_aliases,
+                alias_ack_receiver,
+            ),
+            // CHANGES
+            changes: ReplicationChanges::default(),
             bandwidth_cap_enabled,
         }
     }
@@ -148,16 +202,24 @@ impl ReplicationSender {
             if let Some(UpdateMessageMetadata {
                 group_id,
                 bevy_tick,
-                ..
+                tick,
             }) = self.updates_message_id_to_group_id.remove(&message_id)
             {
-                if let SendUpdatesMode::SinceLastSend = self.replication_config.send_updates_mode {
-                    if let Some(channel) = self.group_channels.get_mut(&group_id) {
+                if let Some(channel) = self.group_channels.get_mut(&group_id) {
+                    // the delta-compressed components only send diffs, so a lost diff is never
+                    // recovered by a later update: we need to send again even in `SinceLastAck` mode
+                    let lost_delta = channel.receive_delta_nack(tick);
+                    if lost_delta
+                        || matches!(
+                            self.replication_config.send_updates_mode,
+                            SendUpdatesMode::SinceLastSend
+                        )
+                    {
                         // when we know an update message has been lost, we need to reset our send_tick
                         // to our previous ack_tick
                         trace!(
-                        "Update channel send_tick back to ack_tick because a message has been lost"
-                    );
+                            "Update channel send_tick back to ack_tick because a message has been lost"
+                        );
                         // only reset the send tick if the bevy_tick of the message that was lost is
                         // newer than the current ack_tick
                         // (otherwise it just means we lost some old message, and we don't need to do anything)
@@ -170,9 +232,9 @@ impl ReplicationSender {
 
                         // TODO: if all clients lost a given message, than we can immediately drop the delta-compression data
                         //  for that tick
-                    } else {
-                        error!("Received an update message-id nack but the corresponding group channel does not exist");
                     }
+                } else {
+                    error!("Received an update message-id nack but the corresponding group channel does not exist");
                 }
             } else {
                 // NOTE: this happens when a message-id is split between multiple packets (fragmented messages)
@@ -239,6 +301,14 @@ impl ReplicationSender {
             }) = self.updates_message_id_to_group_id.remove(&message_id)
             {
                 if let Some(channel) = self.group_channels.get_mut(&group_id) {
+                    // the delta-compressed components keep track of their own ack tick, so even an older
+                    // ack can be used as a more recent baseline for the components it contains
+                    channel.receive_delta_ack(tick);
+                    // acks can arrive out of order: an ack for an older message must not move the
+                    // ack tick back, since the delta data for older ticks might already be dropped
+                    if channel.ack_tick.is_some_and(|ack_tick| ack_tick > tick) {
+                        continue;
+                    }
                     // update the ack tick for the channel
                     debug!(?group_id, ?bevy_tick, ?tick, "Update channel ack_tick");
                     channel.ack_bevy_tick = Some(bevy_tick);
@@ -278,7 +348,21 @@ impl ReplicationSender {
                     group_channel.ack_tick = None;
                 }
             }
+            group_channel
+                .delta_ticks
+                .values_mut()
+                .flat_map(|components| components.values_mut())
+                .for_each(|delta_ticks| {
+                    if delta_ticks
+                        .ack_tick
+                        .is_some_and(|ack_tick| tick - ack_tick > delta)
+                    {
+                        delta_ticks.ack_tick = None;
+                    }
+                    delta_ticks.sent_ticks.retain(|t| tick - *t <= delta);
+                });
         }
+        self.entity_aliases.cleanup(tick);
     }
 }
 
@@ -296,6 +380,11 @@ impl ReplicationSender {
             .base_priority = priority;
     }
 
+    /// Update the [`ReplicationClass`] of a given group
+    pub(crate) fn update_class(&mut self, group_id: ReplicationGroupId, class: ReplicationClass) {
+        self.group_channels.entry(group_id).or_default().class = class;
+    }
+
     // TODO: how can I emit metrics here that contain the channel kind?
     //  use a OnceCell that gets set with the channel name mapping when the protocol is finalized?
     //  the other option is to have wrappers in Connection, but that's pretty ugly
@@ -333,8 +422,38 @@ impl ReplicationSender {
             .spawn = SpawnAction::Reuse(remote_entity);
     }
 
-    #[cfg_attr(feature = "trace", instrument(level = Level::INFO, skip_all))]
-    pub(crate) fn prepare_entity_despawn(&mut self, entity: Entity, group_id: ReplicationGroupId) {
+    /// Host has spawned an entity that replaces `replaced_entity` (which is despawned at the same time).
+    /// The remote will re-use the entity that it had spawned for `replaced_entity`.
+    // #[cfg_attr(feature = "trace", instrument(level = Level::INFO, skip_all))]
+    pub(crate) fn prepare_entity_spawn_replace(
+        &mut self,
+        entity: Entity,
+        group_id: ReplicationGroupId,
+        replaced_entity: Entity,
+    ) {
+        self.group_with_actions.insert(group_id);
+        self.group_channels
+            .entry(group_id)
+            .or_default()
+            .pending_actions
+            .entry(entity)
+            .or_default()
+            .spawn = SpawnAction::Replace(replaced_entity);
+    }
+
+    /// Host moved an entity that the remote already has from the group `previous` to the group `group_id`.
+    /// The components of the entity must be sent as inserts in the same message.
+    pub(crate) fn prepare_entity_regroup(
+        &mut self,
+        entity: Entity,
+        previous: ReplicationGroupId,
+        group_id: ReplicationGroupId,
+    ) {
+        // the entity is not part of the previous group anymore
+        if let Some(channel) = self.group_channels.get_mut(&previous) {
+            channel.pending_updates.remove(&entity);
+            channel.delta_ticks.remove(&entity);
+        }
         self.group_with_actions.insert(group_id);
         self.group_channels
             .entry(group_id)
@@ -342,7 +461,53 @@ impl ReplicationSender {
             .pending_actions
             .entry(entity)
             .or_default()
-            .spawn = SpawnAction::Despawn;
+            .spawn = SpawnAction::Regroup;
+    }
+
+    #[cfg_attr(feature = "trace", instrument(level = Level::INFO, skip_all))]
+    pub(crate) fn prepare_entity_despawn(&mut self, entity: Entity, group_id: ReplicationGroupId) {
+        self.prepare_despawn_action(entity, group_id, SpawnAction::Despawn);
+    }
+
+    /// Host has despawned an entity that is replaced by another entity.
+    /// The remote keeps its entity alive until it receives the replacement.
+    #[cfg_attr(feature = "trace", instrument(level = Level::INFO, skip_all))]
+    pub(crate) fn prepare_entity_despawn_replaced(
+        &mut self,
+        entity: Entity,
+        group_id: ReplicationGroupId,
+    ) {
+        self.prepare_despawn_action(entity, group_id, SpawnAction::DespawnReplaced);
+    }
+
+    /// Host stopped replicating an entity to the remote, without despawning it.
+    #[cfg_attr(feature = "trace", instrument(level = Level::INFO, skip_all))]
+    pub(crate) fn prepare_entity_interest_lost(
+        &mut self,
+        entity: Entity,
+        group_id: ReplicationGroupId,
+    ) {
+        self.prepare_despawn_action(entity, group_id, SpawnAction::DespawnInterestLost);
+    }
+
+    fn prepare_despawn_action(
+        &mut self,
+        entity: Entity,
+        group_id: ReplicationGroupId,
+        spawn: SpawnAction,
+    ) {
+        self.keyframe_manager.remove_entity(entity);
+        self.send_intervals.remove_entity(entity);
+        self.personalized_cache.remove(&entity);
+        self.entity_aliases.remove_entity(entity);
+        self.group_with_actions.insert(group_id);
+        let channel = self.group_channels.entry(group_id).or_default();
+        channel.delta_ticks.remove(&entity);
+        channel
+            .pending_actions
+            .entry(entity)
+            .or_default()
+            .spawn = spawn;
     }
 
     // we want to send all component inserts that happen together for the same entity in a single message
@@ -385,12 +550,97 @@ impl ReplicationSender {
             .push(kind);
     }
 
+    /// Send the value of a personalized component, if it's different from the last value sent to the remote.
+    ///
+    /// `None` means that the remote should not have the component.
+    pub(crate) fn prepare_personalized_component(
+        &mut self,
+        entity: Entity,
+        group_id: ReplicationGroupId,
+        kind: ComponentKind,
+        net_id: ComponentNetId,
+        value: Option<Bytes>,
+        bevy_tick: BevyTick,
+    ) {
+        match value {
+            Some(raw_data) => {
+                let cache = self.personalized_cache.entry(entity).or_default();
+                if cache.get(&kind) == Some(&raw_data) {
+                    return;
+                }
+                cache.insert(kind, raw_data.clone());
+                self.prepare_component_insert(entity, group_id, raw_data, bevy_tick);
+            }
+            None => {
+                let Some(cache) = self.personalized_cache.get_mut(&entity) else {
+                    return;
+                };
+                if cache.remove(&kind).is_some() {
+                    self.prepare_component_remove(entity, group_id, net_id);
+                }
+            }
+        }
+    }
+
     #[cfg_attr(feature = "trace", instrument(level = Level::INFO, skip_all))]
     pub(crate) fn prepare_component_update(
         &mut self,
         entity: Entity,
         group_id: ReplicationGroupId,
         raw_data: Bytes,
+    ) {
+        self.stats.update_bytes += raw_data.len();
+        self.buffer_component_update(entity, group_id, raw_data);
+    }
+
+    /// Buffer a keyframe: the full value of a component, sent even though it didn't change
+    pub(crate) fn prepare_component_keyframe(
+        &mut self,
+        entity: Entity,
+        group_id: ReplicationGroupId,
+        kind: ComponentKind,
+        raw_data: Bytes,
+        tick: Tick,
+    ) {
+        self.stats.keyframe_bytes += raw_data.len();
+        self.keyframe_manager.record_keyframe(entity, kind, tick, raw_data.len());
+        self.buffer_component_update(entity, group_id, raw_data);
+    }
+
+    /// Buffer a keyframe for a delta-compressed component.
+    ///
+    /// The keyframe is a diff from the base value, so that the remote can apply it
+    /// regardless of which previous values it has received.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn prepare_delta_component_keyframe(
+        &mut self,
+        entity: Entity,
+        group_id: ReplicationGroupId,
+        kind: ComponentKind,
+        component_data: Ptr,
+        registry: &ComponentRegistry,
+        writer: &mut Writer,
+        tick: Tick,
+    ) -> Result<(), ReplicationError> {
+        // SAFETY: the component_data is a pointer to a component that corresponds to kind
+        unsafe {
+            registry.serialize_diff_from_base_value(component_data, writer, kind)?;
+        }
+        let raw_data = writer.split();
+        // the remote stores the full value, so it can be used as a baseline for the next diffs
+        self.group_channels
+            .entry(group_id)
+            .or_default()
+            .send_delta(entity, kind, tick);
+        self.prepare_component_keyframe(entity, group_id, kind, raw_data, tick);
+        Ok(())
+    }
+
+    fn buffer_component_update(
+        &mut self,
+        entity: Entity,
+        group_id: ReplicationGroupId,
+        raw_data: Bytes,
     ) {
         self.group_with_updates.insert(group_id);
         self.group_channels
@@ -418,40 +668,40 @@ impl ReplicationSender {
     ) -> Result<(), ReplicationError> {
         let group_channel = self.group_channels.entry(group_id).or_default();
         // Get the latest acked tick for this entity/component
-        let raw_data = group_channel
-            .ack_tick
-            .map(|ack_tick| {
-                // we have an ack tick for this replication group, get the corresponding component value
-                // so we can compute a diff
-                let old_data = delta_manager
-                    .data
-                    .get_component_value(entity, ack_tick, kind, group_id)
-                    .ok_or(ReplicationError::DeltaCompressionError(
-                        "could not find old component value to compute delta".to_string(),
-                    ))
-                    .inspect_err(|e| {
-                        error!(
-                            ?entity,
-                            name = ?registry.name(kind),
-                            "Could not find old component value from tick {:?} to compute delta",
-                            ack_tick
-                        );
-                        error!("DeltaManager data: {:?}", delta_manager.data);
-                    })?;
-                // SAFETY: the component_data and erased_data is a pointer to a component that corresponds to kind
-                unsafe {
-                    registry.serialize_diff(ack_tick, old_data, component_data, writer, kind)?;
-                }
-                Ok::<Bytes, ReplicationError>(writer.split())
-            })
-            .unwrap_or_else(|| {
-                // SAFETY: the component_data is a pointer to a component that corresponds to kind
-                unsafe {
-                    // compute a diff from the base value, and serialize that
-                    registry.serialize_diff_from_base_value(component_data, writer, kind)?;
-                }
-                Ok::<Bytes, ReplicationError>(writer.split())
-            })?;
+        let ack_tick = group_channel
+            .delta_ticks
+            .get(&entity)
+            .and_then(|components| components.get(&kind))
+            .and_then(|delta_ticks| delta_ticks.ack_tick);
+        group_channel.send_delta(entity, kind, tick);
+        // get the component value that the remote acked, so we can compute a diff
+        let old_data = ack_tick.and_then(|ack_tick| {
+            let old_data = delta_manager
+                .data
+                .get_component_value(entity, ack_tick, kind, group_id);
+            if old_data.is_none() {
+                debug!(
+                    ?entity,
+                    name = ?registry.name(kind),
+                    "The component value from tick {:?} was dropped, sending the full value instead of a delta",
+                    ack_tick
+                );
+            }
+            old_data.map(|old_data| (ack_tick, old_data))
+        });
+        match old_data {
+            // SAFETY: the component_data and old_data are pointers to a component that corresponds to kind
+            Some((ack_tick, old_data)) => unsafe {
+                registry.serialize_diff(ack_tick, old_data, component_data, writer, kind)?;
+            },
+            // there is no baseline that the remote is guaranteed to have (first send, or the baseline was dropped):
+            // compute a diff from the base value, which is equivalent to sending the full value
+            // SAFETY: the component_data is a pointer to a component that corresponds to kind
+            None => unsafe {
+                registry.serialize_diff_from_base_value(component_data, writer, kind)?;
+            },
+        }
+        let raw_data = writer.split();
         trace!(?kind, "Inserting pending update!");
         self.prepare_component_update(entity, group_id, raw_data);
         Ok(())
@@ -573,6 +823,31 @@ impl ReplicationSender {
             //      - tick 4: C2 insert. C1 update. (if we send all updates since last_ack) !!!! We need to update the ack from the Insert only AFTER all the Updates are prepared!!!
             //      - tick 5: Before, we would send C1 update again, since we didn't receive an ack for C1 yet. But now we stop sending it because we know that the message from tick 4 will be received.
             channel.ack_tick = Some(tick);
+            if self.changes.enabled {
+                // in the order in which the remote applies them
+                for (entity, entity_actions) in actions.iter() {
+                    for payload in &entity_actions.insert {
+                        self.changes.record_payload(
+                            *entity,
+                            payload,
+                            ReplicationChangeType::Inserted,
+                            tick,
+                        );
+                    }
+                    for net_id in &entity_actions.remove {
+                        self.changes
+                            .record(*entity, *net_id, ReplicationChangeType::Removed, tick);
+                    }
+                    for payload in &entity_actions.updates {
+                        self.changes.record_payload(
+                            *entity,
+                            payload,
+                            ReplicationChangeType::Updated,
+                            tick,
+                        );
+                    }
+                }
+            }
             let priority = channel.accumulated_priority;
             let message_id = channel.actions_next_send_message_id;
             channel.actions_next_send_message_id += 1;
@@ -602,6 +877,11 @@ impl ReplicationSender {
                     priority,
                 )?
                 .expect("The entity actions channels should always return a message_id");
+            message_manager.set_message_class(
+                ChannelKind::of::<EntityActionsChannel>(),
+                message_id,
+                channel.class,
+            );
 
             // restore the hashmap that we took out, so that we can reuse the allocated memory
             channel.pending_actions = message.actions;
@@ -641,6 +921,9 @@ impl ReplicationSender {
     }
 
     /// Buffer the [`EntityUpdatesMessage`](super::EntityUpdatesMessage) to send in the [`MessageManager`]
+    ///
+    /// If a `cache` is provided, the messages that are identical to a message serialized by another connection
+    /// reuse its bytes instead of being serialized again.
     #[cfg_attr(feature = "trace", instrument(level = Level::INFO, skip_all))]
     pub(crate) fn send_updates_messages(
         &mut self,
@@ -648,11 +931,26 @@ impl ReplicationSender {
         bevy_tick: BevyTick,
         writer: &mut Writer,
         message_manager: &mut MessageManager,
+        mut cache: Option<&mut UpdateMessageCache>,
     ) -> Result<(), PacketError> {
+        self.entity_aliases.update(tick);
         self.group_with_updates.drain().try_for_each(|group_id| {
             let channel = self.group_channels.get_mut(&group_id).unwrap();
-            let updates = std::mem::take(&mut channel.pending_updates);
+            let mut updates = std::mem::take(&mut channel.pending_updates);
             trace!(?group_id, "pending updates: {:?}", updates);
+            if self.changes.enabled {
+                for (entity, components) in updates.iter() {
+                    for payload in components {
+                        self.changes.record_payload(
+                            *entity,
+                            payload,
+                            ReplicationChangeType::Updated,
+                            tick,
+                        );
+                    }
+                }
+            }
+            let aliased_updates = self.entity_aliases.alias_updates(&mut updates, tick);
             let priority = channel.accumulated_priority;
             let message = SendEntityUpdatesMessage {
                 group_id,
@@ -661,11 +959,20 @@ impl ReplicationSender {
                 // SAFETY: the last action tick is always set because we send Actions before Updates
                 last_action_tick: channel.last_action_tick,
                 updates,
+                aliased_updates,
             };
 
             // message.emit_send_logs("EntityUpdatesChannel");
-            message.to_bytes(writer).map_err(SerializationError::from)?;
-            let message_bytes = writer.split();
+            let message_bytes = if let Some(cache) = cache.as_deref_mut() {
+                let (message_bytes, shared) = cache.serialize(&message, writer)?;
+                if shared {
+                    self.stats.coalesced_bytes += message_bytes.len();
+                }
+                message_bytes
+            } else {
+                message.to_bytes(writer)?;
+                writer.split()
+            };
             let message_id = message_manager
                 // TODO: use const type_id?
                 .buffer_send_with_priority(
@@ -674,6 +981,11 @@ impl ReplicationSender {
                     priority,
                 )?
                 .expect("The entity actions channels should always return a message_id");
+            message_manager.set_message_class(
+                ChannelKind::of::<EntityUpdatesChannel>(),
+                message_id,
+                channel.class,
+            );
 
             // keep track of the message_id -> group mapping, so we can handle receiving an ACK for that message_id later
             debug!(
@@ -701,9 +1013,22 @@ impl ReplicationSender {
             // restore the hashmap that we took out, so that we can reuse the allocated memory
             channel.pending_updates = message.updates;
             channel.pending_updates.clear();
-            Ok(())
-        })
+            Ok::<(), PacketError>(())
+        })?;
         // TODO: also return for each message a list of the components that have delta-compression data?
+        self.stats.alias_saved_bytes += std::mem::take(&mut self.entity_aliases.saved_bytes);
+
+        // buffer the assignments/invalidations of aliases that were decided while preparing the updates
+        let alias_messages: Vec<_> = self.entity_aliases.drain_messages().collect();
+        for message in alias_messages {
+            message.to_bytes(writer)?;
+            let message_bytes = writer.split();
+            let message_id = message_manager
+                .buffer_send(message_bytes, ChannelKind::of::<EntityAliasChannel>())?
+                .expect("The entity alias channel should always return a message_id");
+            self.entity_aliases.track_message(&message, message_id);
+        }
+        Ok(())
     }
 }
 
@@ -736,6 +1061,11 @@ pub struct GroupChannel {
     pub ack_bevy_tick: Option<BevyTick>,
     /// Used for delta-compression
     pub ack_tick: Option<Tick>,
+    /// Ticks at which we sent the value of each delta-compressed component of the group.
+    ///
+    /// We compute the diffs of a component from the last value that the remote acked for this component,
+    /// since the remote might not have received the values of the other ticks.
+    pub delta_ticks: EntityHashMap<Entity, HashMap<ComponentKind, DeltaTicks>>,
 
     /// Last tick for which we sent an action message. Needed because we want the receiver to only
     /// process Updates if they have processed all Actions that happened before them.
@@ -746,6 +1076,8 @@ pub struct GroupChannel {
     /// for this group because of the bandwidth cap, in which case it will be accumulated.
     pub accumulated_priority: f32,
     pub base_priority: f32,
+    /// The class of the group, whose bandwidth quota is used to send the messages of the group
+    pub class: ReplicationClass,
 }
 
 impl Default for GroupChannel {
@@ -757,13 +1089,75 @@ impl Default for GroupChannel {
             send_tick: None,
             ack_bevy_tick: None,
             ack_tick: None,
+            delta_ticks: EntityHashMap::default(),
             last_action_tick: None,
             accumulated_priority: 0.0,
             base_priority: 1.0,
+            class: ReplicationClass::default(),
         }
     }
 }
 
+/// Ticks at which we sent the value of a delta-compressed component to the remote
+#[derive(Debug, Default)]
+pub struct DeltaTicks {
+    /// Most recent tick for which the remote acked the value of the component
+    pub ack_tick: Option<Tick>,
+    /// Ticks for which we sent the value of the component but didn't receive an ack or a nack yet
+    pub sent_ticks: Vec<Tick>,
+}
+
+impl GroupChannel {
+    /// Remember that we sent the value of the delta-compressed component at `tick`
+    fn send_delta(&mut self, entity: Entity, kind: ComponentKind, tick: Tick) {
+        self.delta_ticks
+            .entry(entity)
+            .or_default()
+            .entry(kind)
+            .or_default()
+            .sent_ticks
+            .push(tick);
+    }
+
+    /// The update message sent at `tick` was acked: every delta-compressed component included in it
+    /// can now compute its diffs from that tick
+    fn receive_delta_ack(&mut self, tick: Tick) {
+        self.delta_ticks
+            .values_mut()
+            .flat_map(|components| components.values_mut())
+            .for_each(|delta_ticks| {
+                if !delta_ticks.sent_ticks.contains(&tick) {
+                    return;
+                }
+                delta_ticks.sent_ticks.retain(|t| *t > tick);
+                if delta_ticks.ack_tick.map_or(true, |ack_tick| ack_tick < tick) {
+                    delta_ticks.ack_tick = Some(tick);
+                }
+            });
+    }
+
+    /// The update message sent at `tick` was lost.
+    ///
+    /// Returns true if the message contained a delta-compressed value that is newer than the one acked
+    /// by the remote, in which case the value needs to be sent again
+    fn receive_delta_nack(&mut self, tick: Tick) -> bool {
+        let mut lost = false;
+        self.delta_ticks
+            .values_mut()
+            .flat_map(|components| components.values_mut())
+            .for_each(|delta_ticks| {
+                if !delta_ticks.sent_ticks.contains(&tick) {
+                    return;
+                }
+                delta_ticks.sent_ticks.retain(|t| *t != tick);
+                if delta_ticks.ack_tick.map_or(true, |ack_tick| ack_tick < tick) {
+                    lost = true;
+                }
+            });
+        lost
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::prelude::server::Replicate;
@@ -910,6 +1304,7 @@ mod tests {
             rx_ack,
             rx_nack,
             rx_send,
+            crossbeam_channel::unbounded().1,
             ReplicationConfig {
                 send_updates_mode: SendUpdatesMode::SinceLastSend,
                 ..default()
@@ -1008,8 +1403,14 @@ mod tests {
         let (tx_ack, rx_ack) = crossbeam_channel::unbounded();
         let (tx_nack, rx_nack) = crossbeam_channel::unbounded();
         let (tx_send, rx_send) = crossbeam_channel::unbounded();
-        let mut sender =
-            ReplicationSender::new(rx_ack, rx_nack, rx_send, ReplicationConfig::default(), true);
+        let mut sender = ReplicationSender::new(
+            rx_ack,
+            rx_nack,
+            rx_send,
+            crossbeam_channel::unbounded().1,
+            ReplicationConfig::default(),
+            true,
+        );
         let group_1 = ReplicationGroupId(0);
         sender
             .group_channels
@@ -1060,6 +1461,7 @@ mod tests {
             rx_ack,
             rx_nack,
             rx_send,
+            crossbeam_channel::unbounded().1,
             ReplicationConfig::default(),
             false,
         );
@@ -0,0 +1,673 @@
+//! Requests that expect a response from the remote.
+//!
+//! Implementing "the client asks the server for its inventory" with plain messages requires a correlation id on
+//! both sides. A [`Request`] is a message with an associated response type: the id is added and matched automatically.
+//!
+//! ```rust,ignore
+//! impl Request for InventoryRequest {
+//!     type Response = InventoryResponse;
+//! }
+//! app.register_request::<InventoryRequest>(ChannelDirection::ClientToServer);
+//!
+//! // client: send the request and keep the handle
+//! let handle = connection.send_request(InventoryRequest)?;
+//!
+//! // server: respond to the request, now or later with `event.responder()`
+//! for event in requests.read() {
+//!     event.respond(InventoryResponse { items: inventory(event.context) });
+//! }
+//!
+//! // client: the handle holds the response once it is received
+//! if let Some(result) = handle.try_take() {
+//!     match result {
+//!         Ok(inventory) => { /* ... */ }
+//!         Err(RequestError::Timeout) => { /* ... */ }
+//!         Err(RequestError::Disconnected) => { /* ... */ }
+//!     }
+//! }
+//! ```
+//!
+//! The [`RequestHandle`] is also a [`Future`] that resolves with the response.
+//!
+//! Requests and responses are sent on the [`RequestChannel`], which is reliable. A request that did not receive
+//! a response before the [`RequestConfig::timeout`] fails with [`RequestError::Timeout`]; a response that arrives
+//! afterwards is dropped. Requests can be sent in both directions: the server sends them with
+//! [`ConnectionManager::send_request`](crate::server::connection::ConnectionManager::send_request).
+use std::any::{Any, TypeId};
+use std::fmt::{Debug, Formatter};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll, Waker};
+
+use bevy::prelude::*;
+use bevy::utils::{Duration, HashMap, Instant};
+use crossbeam_channel::{Receiver, Sender};
+use parking_lot::Mutex;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, error};
+
+use crate::channel::builder::RequestChannel;
+use crate::client::config::ClientConfig;
+use crate::client::connection::ConnectionManager as ClientConnectionManager;
+use crate::client::events::MessageEvent as ClientMessageEvent;
+use crate::prelude::client::is_connected;
+use crate::prelude::server::is_started;
+use crate::prelude::{AppMessageExt, ChannelDirection, ClientId, Message, NetworkTarget};
+use crate::server::config::ServerConfig;
+use crate::server::connection::ConnectionManager as ServerConnectionManager;
+use crate::server::events::MessageEvent as ServerMessageEvent;
+use crate::shared::clock::NetworkClock;
+use crate::shared::sets::{ClientMarker, InternalMainSet, ServerMarker};
+
+/// A message that expects a response of type [`Request::Response`] from the remote
+pub trait Request: Message + Serialize + DeserializeOwned {
+    type Response: Message + Serialize + DeserializeOwned;
+}
+
+/// Identifier of a request, allocated by the peer that sends it
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, Reflect)]
+pub struct RequestId(pub u32);
+
+/// Reason why a request did not get a response
+#[derive(thiserror::Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RequestError {
+    #[error("the remote did not respond before the timeout")]
+    Timeout,
+    #[error("the connection was closed before the remote responded")]
+    Disconnected,
+}
+
+/// Configuration of the requests sent by the client or the server
+#[derive(Clone, Copy, Debug, Reflect)]
+pub struct RequestConfig {
+    /// Duration after which a request that did not receive a response fails with [`RequestError::Timeout`]
+    pub timeout: Duration,
+}
+
+impl Default for RequestConfig {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(5),
+        }
+    }
+}
+
+/// Message that carries a request along with its id
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(bound = "")]
+pub(crate) struct RequestMessage<R: Request> {
+    pub(crate) id: RequestId,
+    pub(crate) request: R,
+}
+
+/// Message that carries the response to the request with the same id
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(bound = "")]
+pub(crate) struct ResponseMessage<R: Request> {
+    pub(crate) id: RequestId,
+    pub(crate) response: R::Response,
+}
+
+/// State of a request, shared between the [`RequestHandle`] and the connection that sent the request
+enum ResponseSlot {
+    Pending(Option<Waker>),
+    Received(Box<dyn Any + Send>),
+    Failed(RequestError),
+    Taken,
+}
+
+impl ResponseSlot {
+    fn resolve(&mut self, slot: ResponseSlot) {
+        if let ResponseSlot::Pending(Some(waker)) = std::mem::replace(self, slot) {
+            waker.wake();
+        }
+    }
+}
+
+impl Debug for ResponseSlot {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ResponseSlot::Pending(_) => f.write_str("Pending"),
+            ResponseSlot::Received(_) => f.write_str("Received"),
+            ResponseSlot::Failed(error) => f.debug_tuple("Failed").field(error).finish(),
+            ResponseSlot::Taken => f.write_str("Taken"),
+        }
+    }
+}
+
+/// Handle to a request that was sent to the remote, which receives the response of type `R`.
+///
+/// The handle can be polled with [`RequestHandle::try_take`], or awaited as a [`Future`].
+/// Like other futures, it panics if it is polled after its result was taken.
+pub struct RequestHandle<R> {
+    id: RequestId,
+    slot: Arc<Mutex<ResponseSlot>>,
+    _marker: std::marker::PhantomData<fn() -> R>,
+}
+
+impl<R> Debug for RequestHandle<R> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RequestHandle")
+            .field("id", &self.id)
+            .field("slot", &*self.slot.lock())
+            .finish()
+    }
+}
+
+impl<R: Send + 'static> RequestHandle<R> {
+    /// Id of the request
+    pub fn id(&self) -> RequestId {
+        self.id
+    }
+
+    /// Returns true if the request is still waiting for its response
+    pub fn is_pending(&self) -> bool {
+        matches!(*self.slot.lock(), ResponseSlot::Pending(_))
+    }
+
+    /// Take the response of the request, or the reason why it failed.
+    ///
+    /// Returns `None` while the request is pending, and once the result was taken.
+    pub fn try_take(&self) -> Option<Result<R, RequestError>> {
+        let mut slot = self.slot.lock();
+        match std::mem::replace(&mut *slot, ResponseSlot::Taken) {
+            ResponseSlot::Received(response) => Some(Ok(*response
+                .downcast::<R>()
+                .expect("the response type is checked when the response is received"))),
+            ResponseSlot::Failed(error) => Some(Err(error)),
+            pending @ ResponseSlot::Pending(_) => {
+                *slot = pending;
+                None
+            }
+            ResponseSlot::Taken => None,
+        }
+    }
+}
+
+impl<R: Send + 'static> Future for RequestHandle<R> {
+    type Output = Result<R, RequestError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        if let Some(result) = self.try_take() {
+            return Poll::Ready(result);
+        }
+        let mut slot = self.slot.lock();
+        match &mut *slot {
+            ResponseSlot::Pending(waker) => {
+                *waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+            _ => panic!("RequestHandle polled after completion"),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct PendingRequest {
+    /// Client that the request was sent to, if it was sent by the server
+    client_id: Option<ClientId>,
+    response_type: TypeId,
+    /// The deadline is set on the first frame after the request was sent
+    deadline: Option<Instant>,
+    slot: Arc<Mutex<ResponseSlot>>,
+}
+
+/// Requests sent by a connection that are waiting for their response
+#[derive(Debug, Default)]
+pub(crate) struct PendingRequests {
+    next_id: u32,
+    pending: HashMap<RequestId, PendingRequest>,
+}
+
+impl PendingRequests {
+    pub(crate) fn next_id(&mut self) -> RequestId {
+        let id = RequestId(self.next_id);
+        self.next_id = self.next_id.wrapping_add(1);
+        id
+    }
+
+    /// Start waiting for the response to the request `id`
+    pub(crate) fn insert<R: Send + 'static>(
+        &mut self,
+        id: RequestId,
+        client_id: Option<ClientId>,
+    ) -> RequestHandle<R> {
+        let slot = Arc::new(Mutex::new(ResponseSlot::Pending(None)));
+        self.pending.insert(
+            id,
+            PendingRequest {
+                client_id,
+                response_type: TypeId::of::<R>(),
+                deadline: None,
+                slot: slot.clone(),
+            },
+        );
+        RequestHandle {
+            id,
+            slot,
+            _marker: std::marker::PhantomData,
+        }
+    }
+
+    /// Give the response received from `client_id` to the handle of the request `id`.
+    ///
+    /// The response is dropped if the request already timed out.
+    pub(crate) fn complete<R: Send + 'static>(
+        &mut self,
+        id: RequestId,
+        client_id: Option<ClientId>,
+        response: R,
+    ) {
+        let expected = self.pending.get(&id).is_some_and(|pending| {
+            pending.client_id == client_id && pending.response_type == TypeId::of::<R>()
+        });
+        if !expected {
+            debug!(?id, "Received the response of an unknown request");
+            return;
+        }
+        let pending = self.pending.remove(&id).unwrap();
+        pending
+            .slot
+            .lock()
+            .resolve(ResponseSlot::Received(Box::new(response)));
+    }
+
+    /// Fail the requests that did not receive a response before the timeout
+    pub(crate) fn expire(&mut self, now: Instant, timeout: Duration) {
+        self.pending.retain(|id, pending| {
+            let deadline = *pending.deadline.get_or_insert(now + timeout);
+            if now < deadline {
+                return true;
+            }
+            debug!(?id, "The request did not receive a response");
+            pending
+                .slot
+                .lock()
+                .resolve(ResponseSlot::Failed(RequestError::Timeout));
+            false
+        });
+    }
+
+    /// Fail the requests sent to a client that disconnected
+    pub(crate) fn disconnect(&mut self, client_id: ClientId) {
+        self.pending.retain(|_, pending| {
+            if pending.client_id != Some(client_id) {
+                return true;
+            }
+            pending
+                .slot
+                .lock()
+                .resolve(ResponseSlot::Failed(RequestError::Disconnected));
+            false
+        });
+    }
+}
+
+impl Drop for PendingRequests {
+    fn drop(&mut self) {
+        for pending in self.pending.values() {
+            pending
+                .slot
+                .lock()
+                .resolve(ResponseSlot::Failed(RequestError::Disconnected));
+        }
+    }
+}
+
+/// Response that was given to a [`ResponseSender`] and that has not been sent yet
+struct QueuedResponse<R: Request> {
+    client_id: Option<ClientId>,
+    id: RequestId,
+    response: R::Response,
+}
+
+/// Sends the response to a request received from the remote.
+///
+/// It can be kept to respond on a later frame. Only the first response to a request is sent,
+/// the following ones are dropped.
+pub struct ResponseSender<R: Request> {
+    id: RequestId,
+    client_id: Option<ClientId>,
+    /// Shared between the clones of the sender, so that the request is only responded once
+    responded: Arc<AtomicBool>,
+    queue: Sender<QueuedResponse<R>>,
+}
+
+impl<R: Request> Clone for ResponseSender<R> {
+    fn clone(&self) -> Self {
+        Self {
+            id: self.id,
+            client_id: self.client_id,
+            responded: self.responded.clone(),
+            queue: self.queue.clone(),
+        }
+    }
+}
+
+impl<R: Request> ResponseSender<R> {
+    /// Id of the request
+    pub fn id(&self) -> RequestId {
+        self.id
+    }
+
+    /// Send the response to the remote that sent the request
+    pub fn respond(self, response: R::Response) {
+        if self.responded.swap(true, Ordering::Relaxed) {
+            debug!(id = ?self.id, "The request was already responded");
+            return;
+        }
+        let _ = self.queue.send(QueuedResponse {
+            client_id: self.client_id,
+            id: self.id,
+            response,
+        });
+    }
+}
+
+/// Bevy [`Event`] emitted when a request is received from the remote
+#[derive(Event)]
+pub struct RequestEvent<R: Request, Ctx = ()> {
+    pub request: R,
+    pub context: Ctx,
+    responder: ResponseSender<R>,
+}
+
+impl<R: Request, Ctx> RequestEvent<R, Ctx> {
+    /// Id of the request
+    pub fn id(&self) -> RequestId {
+        self.responder.id
+    }
+
+    /// Send the response to the remote that sent the request
+    pub fn respond(&self, response: R::Response) {
+        self.responder.clone().respond(response);
+    }
+
+    /// Returns a [`ResponseSender`] to respond to the request later
+    pub fn responder(&self) -> ResponseSender<R> {
+        self.responder.clone()
+    }
+}
+
+/// Queue of the responses given on the client (`M` = [`ClientMarker`]) or on the server (`M` = [`ServerMarker`])
+#[derive(Resource)]
+struct ResponseQueue<R: Request, M> {
+    sender: Sender<QueuedResponse<R>>,
+    receiver: Receiver<QueuedResponse<R>>,
+    _marker: std::marker::PhantomData<M>,
+}
+
+impl<R: Request, M> Default for ResponseQueue<R, M> {
+    fn default() -> Self {
+        let (sender, receiver) = crossbeam_channel::unbounded();
+        Self {
+            sender,
+            receiver,
+            _marker: std::marker::PhantomData,
+        }
+    }
+}
+
+pub trait AppRequestExt {
+    /// Register a [`Request`] that can be sent in the given direction.
+    /// Its response is sent in the opposite direction.
+    fn register_request<R: Request>(&mut self, direction: ChannelDirection);
+}
+
+impl AppRequestExt for App {
+    fn register_request<R: Request>(&mut self, direction: ChannelDirection) {
+        let response_direction = match direction {
+            ChannelDirection::ClientToServer => ChannelDirection::ServerToClient,
+            ChannelDirection::ServerToClient => ChannelDirection::ClientToServer,
+            ChannelDirection::Bidirectional => ChannelDirection::Bidirectional,
+        };
+        self.register_message::<RequestMessage<R>>(direction);
+        self.register_message::<ResponseMessage<R>>(response_direction);
+
+        let is_client = self.world().get_resource::<ClientConfig>().is_some();
+        let is_server = self.world().get_resource::<ServerConfig>().is_some();
+        let client_to_server = direction != ChannelDirection::ServerToClient;
+        let server_to_client = direction != ChannelDirection::ClientToServer;
+        if is_client && server_to_client {
+            self.init_resource::<ResponseQueue<R, ClientMarker>>()
+                .add_event::<RequestEvent<R>>()
+                .add_systems(
+                    PreUpdate,
+                    emit_client_request_events::<R>
+                        .after(InternalMainSet::<ClientMarker>::EmitEvents)
+                        .run_if(is_connected),
+                )
+                .add_systems(
+                    PostUpdate,
+                    send_client_responses::<R>
+                        .before(InternalMainSet::<ClientMarker>::Send)
+                        .run_if(is_connected),
+                );
+        }
+        if is_client && client_to_server {
+            self.add_systems(
+                PreUpdate,
+                receive_client_responses::<R>
+                    .after(InternalMainSet::<ClientMarker>::EmitEvents)
+                    .before(expire_client_requests),
+            );
+        }
+        if is_server && client_to_server {
+            self.init_resource::<ResponseQueue<R, ServerMarker>>()
+                .add_event::<RequestEvent<R, ClientId>>()
+                .add_systems(
+                    PreUpdate,
+                    emit_server_request_events::<R>
+                        .after(InternalMainSet::<ServerMarker>::EmitEvents)
+                        .run_if(is_started),
+                )
+                .add_systems(
+                    PostUpdate,
+                    send_server_responses::<R>
+                        .before(InternalMainSet::<ServerMarker>::Send)
+                        .run_if(is_started),
+                );
+        }
+        if is_server && server_to_client {
+            self.add_systems(
+                PreUpdate,
+                receive_server_responses::<R>
+                    .after(InternalMainSet::<ServerMarker>::EmitEvents)
+                    .before(expire_server_requests),
+            );
+        }
+    }
+}
+
+/// Client system that emits a [`RequestEvent`] for each request received from the server
+fn emit_client_request_events<R: Request>(
+    queue: Res<ResponseQueue<R, ClientMarker>>,
+    mut messages: ResMut<Events<ClientMessageEvent<RequestMessage<R>>>>,
+    mut events: EventWriter<RequestEvent<R>>,
+) {
+    for message in messages.drain().map(|event| event.message) {
+        events.send(RequestEvent {
+            request: message.request,
+            context: (),
+            responder: ResponseSender {
+                id: message.id,
+                client_id: None,
+                responded: Arc::default(),
+                queue: queue.sender.clone(),
+            },
+        });
+    }
+}
+
+/// Client system that sends the responses given since the last run to the server
+fn send_client_responses<R: Request>(
+    queue: Res<ResponseQueue<R, ClientMarker>>,
+    mut connection_manager: ResMut<ClientConnectionManager>,
+) {
+    for queued in queue.receiver.try_iter() {
+        let message = ResponseMessage::<R> {
+            id: queued.id,
+            response: queued.response,
+        };
+        let _ = connection_manager
+            .send_message::<RequestChannel, _>(&message)
+            .inspect_err(|e| error!("Could not send response: {e:?}"));
+    }
+}
+
+/// Client system that gives the responses received from the server to the pending requests
+fn receive_client_responses<R: Request>(
+    mut connection_manager: ResMut<ClientConnectionManager>,
+    mut messages: ResMut<Events<ClientMessageEvent<ResponseMessage<R>>>>,
+) {
+    for message in messages.drain().map(|event| event.message) {
+        connection_manager
+            .requests
+            .complete(message.id, None, message.response);
+    }
+}
+
+/// Client system that fails the requests that did not receive a response before the timeout
+pub(crate) fn expire_client_requests(
+    config: Res<ClientConfig>,
+    clock: Res<NetworkClock>,
+    mut connection_manager: ResMut<ClientConnectionManager>,
+) {
+    connection_manager
+        .requests
+        .expire(clock.now(), config.request.timeout);
+}
+
+/// Server system that emits a [`RequestEvent`] for each request received from a client
+fn emit_server_request_events<R: Request>(
+    queue: Res<ResponseQueue<R, ServerMarker>>,
+    mut messages: ResMut<Events<ServerMessageEvent<RequestMessage<R>>>>,
+    mut events: EventWriter<RequestEvent<R, ClientId>>,
+) {
+    for event in messages.drain() {
+        events.send(RequestEvent {
+            request: event.message.request,
+            context: event.context,
+            responder: ResponseSender {
+                id: event.message.id,
+                client_id: Some(event.context),
+                responded: Arc::default(),
+                queue: queue.sender.clone(),
+            },
+        });
+    }
+}
+
+/// Server system that sends the responses given since the last run to the clients
+fn send_server_responses<R: Request>(
+    queue: Res<ResponseQueue<R, ServerMarker>>,
+    mut connection_manager: ResMut<ServerConnectionManager>,
+) {
+    for queued in queue.receiver.try_iter() {
+        let Some(client_id) = queued.client_id else {
+            continue;
+        };
+        let message = ResponseMessage::<R> {
+            id: queued.id,
+            response: queued.response,
+        };
+        // the client could have disconnected
+        let _ = connection_manager
+            .send_message_to_target::<RequestChannel, _>(&message, NetworkTarget::Single(client_id))
+            .inspect_err(|e| debug!("Could not send response: {e:?}"));
+    }
+}
+
+/// Server system that gives the responses received from the clients to the pending requests
+fn receive_server_responses<R: Request>(
+    mut connection_manager: ResMut<ServerConnectionManager>,
+    mut messages: ResMut<Events<ServerMessageEvent<ResponseMessage<R>>>>,
+) {
+    for event in messages.drain() {
+        connection_manager.requests.complete(
+            event.message.id,
+            Some(event.context),
+            event.message.response,
+        );
+    }
+}
+
+/// Server system that fails the requests that did not receive a response before the timeout
+pub(crate) fn expire_server_requests(
+    config: Res<ServerConfig>,
+    clock: Res<NetworkClock>,
+    mut connection_manager: ResMut<ServerConnectionManager>,
+) {
+    connection_manager
+        .requests
+        .expire(clock.now(), config.request.timeout);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+    struct Ping;
+
+    impl Request for Ping {
+        type Response = u32;
+    }
+
+    #[test]
+    fn test_response_dropped_after_timeout() {
+        let mut requests = PendingRequests::default();
+        let now = Instant::now();
+        let timeout = Duration::from_millis(100);
+        let id = requests.next_id();
+        let handle = requests.insert::<u32>(id, None);
+        requests.expire(now, timeout);
+        assert!(handle.is_pending());
+
+        requests.expire(now + timeout, timeout);
+        assert_eq!(handle.try_take(), Some(Err(RequestError::Timeout)));
+        // the late response is ignored
+        requests.complete(id, None, 1u32);
+        assert_eq!(handle.try_take(), None);
+    }
+
+    #[test]
+    fn test_response_type_and_sender_checked() {
+        let mut requests = PendingRequests::default();
+        let id = requests.next_id();
+        let handle = requests.insert::<u32>(id, Some(ClientId::Netcode(1)));
+        requests.complete(id, Some(ClientId::Netcode(2)), 1u32);
+        requests.complete(id, Some(ClientId::Netcode(1)), 1.0f32);
+        assert!(handle.is_pending());
+
+        requests.complete(id, Some(ClientId::Netcode(1)), 2u32);
+        assert_eq!(handle.try_take(), Some(Ok(2)));
+    }
+
+    #[test]
+    fn test_respond_once() {
+        let queue = ResponseQueue::<Ping, ClientMarker>::default();
+        let responder = ResponseSender::<Ping> {
+            id: RequestId(0),
+            client_id: None,
+            responded: Arc::default(),
+            queue: queue.sender.clone(),
+        };
+        responder.clone().respond(1);
+        responder.respond(2);
+        let responses: Vec<_> = queue.receiver.try_iter().map(|q| q.response).collect();
+        assert_eq!(responses, vec![1]);
+    }
+
+    #[test]
+    fn test_dropped_requests_fail() {
+        let mut requests = PendingRequests::default();
+        let id = requests.next_id();
+        let handle = requests.insert::<u32>(id, None);
+        drop(requests);
+        assert_eq!(handle.try_take(), Some(Err(RequestError::Disconnected)));
+    }
+}
@@ -0,0 +1,239 @@
+//! Negotiation of the versions of the schemas of the components and messages whose schema can evolve
+//! (see [`versioned`](crate::serialize::versioned)).
+//!
+//! When a client connects, the client and the server send each other the version of the schema of each of these
+//! types, with the fingerprints of the fields added in each version. The negotiated version of a type is the
+//! latest version known by both peers. If the peers disagree on the fields added in a version that they both know
+//! (for example a field added in version 2 was later retyped), the values cannot be decoded: the server disconnects
+//! the client.
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use serde::{Deserialize, Serialize};
+use tracing::error;
+
+use crate::channel::builder::SchemaChannel;
+use crate::client::connection::ConnectionManager as ClientConnectionManager;
+use crate::client::events::MessageEvent as ClientMessageEvent;
+use crate::prelude::server::ConnectEvent;
+use crate::prelude::{ComponentRegistry, MessageRegistry, NetworkTarget};
+use crate::protocol::component::ComponentKind;
+use crate::protocol::message::MessageKind;
+use crate::protocol::registry::NetId;
+use crate::serialize::versioned::Schema;
+use crate::server::connection::ConnectionManager as ServerConnectionManager;
+use crate::server::events::MessageEvent as ServerMessageEvent;
+
+/// Version of the schema of a type, and the fingerprints of the fields added in each version after the first
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct SchemaVersion {
+    version: u8,
+    fingerprints: Vec<u64>,
+}
+
+impl From<&Schema> for SchemaVersion {
+    fn from(schema: &Schema) -> Self {
+        Self {
+            version: schema.version,
+            fingerprints: schema.fingerprints(),
+        }
+    }
+}
+
+/// Message sent by each peer when a client connects, with the schemas of the components and messages
+/// whose schema can evolve
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub(crate) struct SchemaVersionsMessage {
+    components: Vec<(NetId, SchemaVersion)>,
+    messages: Vec<(NetId, SchemaVersion)>,
+}
+
+impl SchemaVersionsMessage {
+    pub(crate) fn new(components: &ComponentRegistry, messages: &MessageRegistry) -> Self {
+        Self {
+            components: components
+                .schemas()
+                .map(|(net_id, _, schema)| (net_id, schema.into()))
+                .collect(),
+            messages: messages
+                .schemas()
+                .map(|(net_id, _, schema)| (net_id, schema.into()))
+                .collect(),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.components.is_empty() && self.messages.is_empty()
+    }
+}
+
+/// Negotiate the version of each local schema with the versions of the remote peer.
+///
+/// Returns the name of the type if the peers disagree on the fields added in a version that they both know.
+fn negotiate<'a>(
+    local: impl Iterator<Item = (NetId, &'static str, &'a Schema)>,
+    remote: &[(NetId, SchemaVersion)],
+) -> Result<HashMap<NetId, u8>, &'static str> {
+    let mut versions = HashMap::default();
+    for (net_id, type_name, schema) in local {
+        // the types are the same on both peers, since the protocol hash was checked during the handshake
+        let Some((_, remote)) = remote.iter().find(|(id, _)| *id == net_id) else {
+            continue;
+        };
+        let version = schema.version.min(remote.version);
+        let shared = version as usize - 1;
+        if remote.fingerprints.get(..shared) != schema.fingerprints().get(..shared) {
+            return Err(type_name);
+        }
+        versions.insert(net_id, version);
+    }
+    Ok(versions)
+}
+
+/// Versions of the schemas negotiated with the remote peer
+#[derive(Debug, Default, Clone, PartialEq)]
+pub(crate) struct NegotiatedSchemas {
+    components: HashMap<ComponentKind, u8>,
+    messages: HashMap<MessageKind, u8>,
+}
+
+impl NegotiatedSchemas {
+    /// Negotiate the versions with the schemas received from the remote peer.
+    ///
+    /// Returns the name of the type if the peers disagree on the fields added in a version that they both know.
+    pub(crate) fn negotiate(
+        components: &ComponentRegistry,
+        messages: &MessageRegistry,
+        remote: &SchemaVersionsMessage,
+    ) -> Result<Self, &'static str> {
+        Ok(Self {
+            components: negotiate(components.schemas(), &remote.components)?
+                .into_iter()
+                .filter_map(|(net_id, version)| Some((*components.kind_map.kind(net_id)?, version)))
+                .collect(),
+            messages: negotiate(messages.schemas(), &remote.messages)?
+                .into_iter()
+                .filter_map(|(net_id, version)| Some((*messages.kind_map.kind(net_id)?, version)))
+                .collect(),
+        })
+    }
+
+    /// Versions of the local client in host-server mode: it shares the registries of the server, so the
+    /// negotiated versions are the latest ones
+    fn local(components: &ComponentRegistry, messages: &MessageRegistry) -> Self {
+        Self {
+            components: components
+                .schemas()
+                .filter_map(|(net_id, _, schema)| {
+                    Some((*components.kind_map.kind(net_id)?, schema.version))
+                })
+                .collect(),
+            messages: messages
+                .schemas()
+                .filter_map(|(net_id, _, schema)| {
+                    Some((*messages.kind_map.kind(net_id)?, schema.version))
+                })
+                .collect(),
+        }
+    }
+
+    /// Negotiated version of the schema of the component or message `T`
+    pub(crate) fn version<T: 'static>(&self) -> Option<u8> {
+        self.components
+            .get(&ComponentKind::of::<T>())
+            .or_else(|| self.messages.get(&MessageKind::of::<T>()))
+            .copied()
+    }
+}
+
+/// Server observer that sends the schemas to the client that just connected.
+///
+/// There is nothing to negotiate with the local client in host-server mode: the versions are set directly.
+pub(crate) fn send_server_schemas(
+    trigger: Trigger<ConnectEvent>,
+    component_registry: Res<ComponentRegistry>,
+    message_registry: Res<MessageRegistry>,
+    mut connection_manager: ResMut<ServerConnectionManager>,
+    client_manager: Option<ResMut<ClientConnectionManager>>,
+) {
+    let client_id = trigger.event().client_id;
+    if let Ok(connection) = connection_manager.connection_mut(client_id) {
+        if connection.is_local_client() {
+            let schemas = NegotiatedSchemas::local(&component_registry, &message_registry);
+            if let Some(mut client_manager) = client_manager {
+                client_manager.schemas = schemas.clone();
+            }
+            connection.schemas = schemas;
+            return;
+        }
+    }
+    let message = SchemaVersionsMessage::new(&component_registry, &message_registry);
+    if message.is_empty() {
+        return;
+    }
+    let _ = connection_manager
+        .send_message_to_target::<SchemaChannel, _>(&message, NetworkTarget::Single(client_id))
+        .inspect_err(|e| error!("Could not send the versions of the schemas: {e:?}"));
+}
+
+/// Server system that negotiates the versions of the schemas with each client, and disconnects the clients
+/// that are not compatible
+pub(crate) fn receive_client_schemas(
+    component_registry: Res<ComponentRegistry>,
+    message_registry: Res<MessageRegistry>,
+    mut connection_manager: ResMut<ServerConnectionManager>,
+    mut events: EventReader<ServerMessageEvent<SchemaVersionsMessage>>,
+) {
+    for event in events.read() {
+        let client_id = *event.context();
+        match NegotiatedSchemas::negotiate(&component_registry, &message_registry, event.message())
+        {
+            Ok(schemas) => {
+                if let Ok(connection) = connection_manager.connection_mut(client_id) {
+                    connection.schemas = schemas;
+                }
+            }
+            Err(type_name) => {
+                error!(
+                    ?client_id,
+                    "The schema of {type_name} is not compatible with the client, disconnecting it"
+                );
+                let _ = connection_manager.disconnect_client(
+                    client_id,
+                    Some(format!("incompatible schema of {type_name}")),
+                );
+            }
+        }
+    }
+}
+
+/// Client system that sends the schemas to the server when the client connects
+pub(crate) fn send_client_schemas(
+    component_registry: Res<ComponentRegistry>,
+    message_registry: Res<MessageRegistry>,
+    mut connection_manager: ResMut<ClientConnectionManager>,
+) {
+    let message = SchemaVersionsMessage::new(&component_registry, &message_registry);
+    if message.is_empty() {
+        return;
+    }
+    let _ = connection_manager
+        .send_message::<SchemaChannel, _>(&message)
+        .inspect_err(|e| error!("Could not send the versions of the schemas: {e:?}"));
+}
+
+/// Client system that negotiates the versions of the schemas with the server
+pub(crate) fn receive_server_schemas(
+    component_registry: Res<ComponentRegistry>,
+    message_registry: Res<MessageRegistry>,
+    mut connection_manager: ResMut<ClientConnectionManager>,
+    mut events: EventReader<ClientMessageEvent<SchemaVersionsMessage>>,
+) {
+    for event in events.read() {
+        match NegotiatedSchemas::negotiate(&component_registry, &message_registry, event.message())
+        {
+            Ok(schemas) => connection_manager.schemas = schemas,
+            // the server disconnects the client
+            Err(type_name) => error!("The schema of {type_name} is not compatible with the server"),
+        }
+    }
+}
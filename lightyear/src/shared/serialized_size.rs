@@ -0,0 +1,523 @@
+//! Statistics of the serialized size of each message and component type
+//!
+//! When [`SerializedSizeConfig::enabled`] is true, the connections record the number of bytes of every message and
+//! component value that they serialize, which helps to catch the types that are much bigger than expected
+//! (for example a replicated component that contains a `Vec<String>`). The [`SerializedSizes`] can be read from the
+//! connection manager with `serialized_sizes()`, or via the [`SerializedSizeDiagnosticsPlugin`].
+//!
+//! A single serialization bigger than the warning threshold of its type (see [`SerializedSizeConfig::warning_threshold`]
+//! and `with_size_warning_threshold` when registering the type) is reported as a
+//! [`NetworkWarning::OversizedSerialization`], with the type name and, for components, the entity.
+//!
+//! The sizes include the network id of the type. The updates of delta-compressed components only contain a diff,
+//! so they are not recorded.
+use bevy::app::{App, Plugin};
+use bevy::diagnostic::{Diagnostic, DiagnosticMeasurement, DiagnosticPath, DiagnosticsStore};
+use bevy::ecs::entity::Entity;
+use bevy::ecs::system::Resource;
+use bevy::reflect::Reflect;
+use bevy::utils::{Duration, HashMap, Instant};
+
+use crate::protocol::component::{ComponentKind, ComponentRegistry};
+use crate::protocol::message::{MessageKind, MessageRegistry};
+use crate::shared::warnings::{NetworkWarning, NetworkWarnings};
+
+/// Configuration of the tracking of the serialized sizes
+#[derive(Clone, Copy, Debug, Default, PartialEq, Reflect)]
+pub struct SerializedSizeConfig {
+    /// If false, the sizes are not recorded and the warning thresholds are not checked
+    pub enabled: bool,
+    /// Size in bytes above which a serialization is reported as a [`NetworkWarning::OversizedSerialization`].
+    ///
+    /// The threshold set with `with_size_warning_threshold` when registering a type takes precedence.
+    pub warning_threshold: Option<usize>,
+}
+
+impl SerializedSizeConfig {
+    /// Record the serialized sizes, and report the serializations bigger than `warning_threshold` bytes
+    pub fn with_warning_threshold(mut self, warning_threshold: usize) -> Self {
+        self.enabled = true;
+        self.warning_threshold = Some(warning_threshold);
+        self
+    }
+}
+
+/// Sizes smaller than this have their own bucket in the [`SizeHistogram`]
+const EXACT_SIZES: usize = 16;
+/// Number of buckets per power of two above [`EXACT_SIZES`], i.e. the sizes are rounded up by at most 12.5%
+const SUB_BUCKETS: usize = 8;
+const NUM_BUCKETS: usize = EXACT_SIZES + (32 - EXACT_SIZES.trailing_zeros() as usize) * SUB_BUCKETS;
+
+/// Histogram of the sizes with logarithmic buckets.
+///
+/// The serialized size of a type often takes a single value, with a few spikes: the streaming quantile
+/// estimators interpolate between the spikes and the common value, whereas the histogram stays exact
+/// for the small sizes and within 12.5% for the bigger ones.
+#[derive(Debug, Clone)]
+struct SizeHistogram(Box<[u32; NUM_BUCKETS]>);
+
+impl Default for SizeHistogram {
+    fn default() -> Self {
+        Self(Box::new([0; NUM_BUCKETS]))
+    }
+}
+
+impl SizeHistogram {
+    fn bucket(size: usize) -> usize {
+        let size = size.min(u32::MAX as usize);
+        if size < EXACT_SIZES {
+            return size;
+        }
+        let exponent = size.ilog2() as usize;
+        let shift = exponent - SUB_BUCKETS.trailing_zeros() as usize;
+        let sub_bucket = (size >> shift) - SUB_BUCKETS;
+        EXACT_SIZES + (exponent - EXACT_SIZES.trailing_zeros() as usize) * SUB_BUCKETS + sub_bucket
+    }
+
+    /// Biggest size that falls in the bucket
+    fn upper_bound(bucket: usize) -> usize {
+        if bucket < EXACT_SIZES {
+            return bucket;
+        }
+        let exponent = (bucket - EXACT_SIZES) / SUB_BUCKETS + EXACT_SIZES.trailing_zeros() as usize;
+        let sub_bucket = (bucket - EXACT_SIZES) % SUB_BUCKETS;
+        let shift = exponent - SUB_BUCKETS.trailing_zeros() as usize;
+        ((SUB_BUCKETS + sub_bucket + 1) << shift) - 1
+    }
+
+    fn observe(&mut self, size: usize) {
+        let bucket = &mut self.0[Self::bucket(size)];
+        *bucket = bucket.saturating_add(1);
+    }
+
+    /// Smallest bucket upper bound that at least `rank` observations are smaller or equal to
+    fn rank(&self, rank: u64) -> usize {
+        let mut seen = 0;
+        for (bucket, &count) in self.0.iter().enumerate() {
+            seen += count as u64;
+            if seen >= rank {
+                return Self::upper_bound(bucket);
+            }
+        }
+        Self::upper_bound(NUM_BUCKETS - 1)
+    }
+}
+
+/// Statistics of the serialized size of one message or component type, since the connection was established
+#[derive(Debug, Clone)]
+pub struct SerializedSizeStats {
+    name: &'static str,
+    count: u64,
+    total_bytes: u64,
+    max: usize,
+    histogram: SizeHistogram,
+}
+
+impl SerializedSizeStats {
+    fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            count: 0,
+            total_bytes: 0,
+            max: 0,
+            histogram: SizeHistogram::default(),
+        }
+    }
+
+    fn observe(&mut self, bytes: usize) {
+        self.count += 1;
+        self.total_bytes += bytes as u64;
+        self.max = self.max.max(bytes);
+        self.histogram.observe(bytes);
+    }
+
+    /// Type name of the message or component
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Number of serializations
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Mean size, in bytes
+    pub fn mean(&self) -> f64 {
+        self.total_bytes as f64 / self.count.max(1) as f64
+    }
+
+    /// Size that a fraction `q` (in `[0, 1]`) of the serializations are smaller or equal to, in bytes.
+    ///
+    /// It is exact below 16 bytes, and can be over-estimated by up to 12.5% above.
+    pub fn quantile(&self, q: f64) -> usize {
+        if self.count == 0 {
+            return 0;
+        }
+        let rank = ((q.clamp(0.0, 1.0) * self.count as f64).ceil() as u64).max(1);
+        self.histogram.rank(rank).min(self.max)
+    }
+
+    /// Size that 99% of the serializations are smaller or equal to, in bytes
+    pub fn p99(&self) -> usize {
+        self.quantile(0.99)
+    }
+
+    /// Size of the biggest serialization, in bytes
+    pub fn max(&self) -> usize {
+        self.max
+    }
+}
+
+/// Serialized size of each message and component type sent by a connection
+#[derive(Debug, Default)]
+pub struct SerializedSizes {
+    config: SerializedSizeConfig,
+    components: HashMap<ComponentKind, SerializedSizeStats>,
+    messages: HashMap<MessageKind, SerializedSizeStats>,
+}
+
+impl SerializedSizes {
+    pub(crate) fn new(config: SerializedSizeConfig) -> Self {
+        Self {
+            config,
+            ..Default::default()
+        }
+    }
+
+    /// Statistics of the component `C`, if it was serialized at least once
+    pub fn component<C: 'static>(&self) -> Option<&SerializedSizeStats> {
+        self.components.get(&ComponentKind::of::<C>())
+    }
+
+    /// Statistics of the message `M`, if it was serialized at least once
+    pub fn message<M: 'static>(&self) -> Option<&SerializedSizeStats> {
+        self.messages.get(&MessageKind::of::<M>())
+    }
+
+    /// Statistics of all the components that were serialized
+    pub fn components(&self) -> impl Iterator<Item = &SerializedSizeStats> {
+        self.components.values()
+    }
+
+    /// Statistics of all the messages that were serialized
+    pub fn messages(&self) -> impl Iterator<Item = &SerializedSizeStats> {
+        self.messages.values()
+    }
+
+    /// Record the serialization of a component value of `entity`
+    pub(crate) fn record_component(
+        &mut self,
+        kind: ComponentKind,
+        registry: &ComponentRegistry,
+        bytes: usize,
+        entity: Entity,
+        warnings: &mut NetworkWarnings,
+    ) {
+        if !self.config.enabled {
+            return;
+        }
+        let Some((name, threshold)) = registry.serialized_size_info(&kind) else {
+            return;
+        };
+        self.components
+            .entry(kind)
+            .or_insert_with(|| SerializedSizeStats::new(name))
+            .observe(bytes);
+        if threshold
+            .or(self.config.warning_threshold)
+            .is_some_and(|threshold| bytes > threshold)
+        {
+            warnings.report(
+                NetworkWarning::OversizedSerialization,
+                format_args!(
+                    "Component {name} of entity {entity:?} was serialized to {bytes} bytes"
+                ),
+            );
+        }
+    }
+
+    /// Record the serialization of a message
+    pub(crate) fn record_message(
+        &mut self,
+        kind: MessageKind,
+        registry: &MessageRegistry,
+        bytes: usize,
+        warnings: &mut NetworkWarnings,
+    ) {
+        if !self.config.enabled {
+            return;
+        }
+        let Some((name, threshold)) = registry.serialized_size_info(&kind) else {
+            return;
+        };
+        self.messages
+            .entry(kind)
+            .or_insert_with(|| SerializedSizeStats::new(name))
+            .observe(bytes);
+        if threshold
+            .or(self.config.warning_threshold)
+            .is_some_and(|threshold| bytes > threshold)
+        {
+            warnings.report(
+                NetworkWarning::OversizedSerialization,
+                format_args!("Message {name} was serialized to {bytes} bytes"),
+            );
+        }
+    }
+}
+
+/// Plugin to expose the [`SerializedSizes`] as diagnostics.
+///
+/// The statistics of each type are at the paths `serialized_size.component.<type name>.<stat>` and
+/// `serialized_size.message.<type name>.<stat>`, where the stat is `count`, `mean`, `p99` or `max`.
+/// The diagnostics of a type are registered the first time it is serialized.
+///
+/// The client and server diagnostics plugins add it with the default settings; add it before them to use other settings.
+#[derive(Clone, Copy, Debug)]
+pub struct SerializedSizeDiagnosticsPlugin {
+    pub history_len: usize,
+    pub flush_interval: Duration,
+}
+
+/// Settings of the [`SerializedSizeDiagnosticsPlugin`], with the time of the last flush
+#[derive(Resource, Debug)]
+pub(crate) struct SerializedSizeDiagnosticsSettings {
+    history_len: usize,
+    flush_interval: Duration,
+    last_flush: Option<Instant>,
+}
+
+impl Default for SerializedSizeDiagnosticsPlugin {
+    fn default() -> Self {
+        Self {
+            history_len: 60,
+            flush_interval: Duration::from_millis(200),
+        }
+    }
+}
+
+impl SerializedSizeDiagnosticsPlugin {
+    /// Path of the diagnostic of the `stat` of a component type
+    pub fn component_path(name: &str, stat: &str) -> DiagnosticPath {
+        Self::path("component", name, stat)
+    }
+
+    /// Path of the diagnostic of the `stat` of a message type
+    pub fn message_path(name: &str, stat: &str) -> DiagnosticPath {
+        Self::path("message", name, stat)
+    }
+
+    fn path(category: &str, name: &str, stat: &str) -> DiagnosticPath {
+        DiagnosticPath::new(format!("serialized_size.{category}.{name}.{stat}"))
+    }
+
+    /// Write the measurements directly to the store, since the diagnostics of the new types have to be
+    /// registered at the same time.
+    ///
+    /// The measurements are only written once per `flush_interval`.
+    pub(crate) fn add_measurements(
+        sizes: &SerializedSizes,
+        settings: &mut SerializedSizeDiagnosticsSettings,
+        now: Instant,
+        store: &mut DiagnosticsStore,
+    ) {
+        if settings.last_flush.is_some_and(|last_flush| {
+            now.saturating_duration_since(last_flush) < settings.flush_interval
+        }) {
+            return;
+        }
+        settings.last_flush = Some(now);
+        let history_len = settings.history_len;
+        let components = sizes.components().map(|stats| ("component", stats));
+        let messages = sizes.messages().map(|stats| ("message", stats));
+        for (category, stats) in components.chain(messages) {
+            for (stat, value, suffix) in [
+                ("count", stats.count() as f64, ""),
+                ("mean", stats.mean(), "B"),
+                ("p99", stats.p99() as f64, "B"),
+                ("max", stats.max() as f64, "B"),
+            ] {
+                let path = Self::path(category, stats.name(), stat);
+                if store.get(&path).is_none() {
+                    store.add(
+                        Diagnostic::new(path.clone())
+                            .with_suffix(suffix)
+                            .with_max_history_length(history_len),
+                    );
+                }
+                let Some(diagnostic) = store.get_mut(&path).filter(|d| d.is_enabled) else {
+                    continue;
+                };
+                diagnostic.add_measurement(DiagnosticMeasurement { time: now, value });
+            }
+        }
+    }
+}
+
+impl Plugin for SerializedSizeDiagnosticsPlugin {
+    // the diagnostics are registered when the types are first serialized
+    fn build(&self, app: &mut App) {
+        app.insert_resource(SerializedSizeDiagnosticsSettings {
+            history_len: self.history_len,
+            flush_interval: self.flush_interval,
+            last_flush: None,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::protocol::{Component1, Component3};
+
+    fn registry() -> ComponentRegistry {
+        let mut registry = ComponentRegistry::default();
+        registry.register_component::<Component1>();
+        registry.register_component::<Component3>();
+        registry.set_size_warning_threshold::<Component3>(8);
+        registry
+    }
+
+    #[test]
+    fn test_histogram_buckets() {
+        let mut previous = 0;
+        for size in (0..100_000).chain([u32::MAX as usize, usize::MAX]) {
+            let bucket = SizeHistogram::bucket(size);
+            assert!(bucket >= previous && bucket < NUM_BUCKETS);
+            previous = bucket;
+            let upper_bound = SizeHistogram::upper_bound(bucket);
+            if size < EXACT_SIZES {
+                assert_eq!(upper_bound, size);
+            } else if size <= u32::MAX as usize {
+                assert!(
+                    upper_bound >= size && upper_bound <= size + size / 8,
+                    "{size}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_disabled_by_default() {
+        let registry = registry();
+        let mut warnings = NetworkWarnings::default();
+        let mut sizes = SerializedSizes::default();
+        sizes.record_component(
+            ComponentKind::of::<Component3>(),
+            &registry,
+            100,
+            Entity::PLACEHOLDER,
+            &mut warnings,
+        );
+        assert!(sizes.component::<Component3>().is_none());
+        assert_eq!(warnings.count(NetworkWarning::OversizedSerialization), 0);
+    }
+
+    #[test]
+    fn test_statistics() {
+        let registry = registry();
+        let mut warnings = NetworkWarnings::default();
+        let mut sizes = SerializedSizes::new(SerializedSizeConfig {
+            enabled: true,
+            warning_threshold: None,
+        });
+        // 1000 serializations of 1 to 100 bytes
+        for i in 0..1000 {
+            sizes.record_component(
+                ComponentKind::of::<Component1>(),
+                &registry,
+                (i * 37) % 100 + 1,
+                Entity::PLACEHOLDER,
+                &mut warnings,
+            );
+        }
+        let stats = sizes.component::<Component1>().unwrap();
+        assert_eq!(stats.name(), std::any::type_name::<Component1>());
+        assert_eq!(stats.count(), 1000);
+        assert_eq!(stats.mean(), 50.5);
+        assert_eq!(stats.max(), 100);
+        // 99 is in the bucket [96, 103], which is clamped to the max
+        assert_eq!(stats.p99(), 100);
+        assert_eq!(stats.quantile(0.0), 1);
+        assert_eq!(stats.quantile(0.1), 10);
+        // no threshold
+        assert_eq!(warnings.count(NetworkWarning::OversizedSerialization), 0);
+    }
+
+    #[test]
+    fn test_warning_thresholds() {
+        let registry = registry();
+        let mut warnings = NetworkWarnings::default();
+        let mut sizes =
+            SerializedSizes::new(SerializedSizeConfig::default().with_warning_threshold(16));
+        let mut record = |kind, bytes| {
+            sizes.record_component(kind, &registry, bytes, Entity::PLACEHOLDER, &mut warnings)
+        };
+        // the global threshold applies to Component1
+        record(ComponentKind::of::<Component1>(), 16);
+        record(ComponentKind::of::<Component1>(), 17);
+        // the threshold of Component3 takes precedence
+        record(ComponentKind::of::<Component3>(), 8);
+        record(ComponentKind::of::<Component3>(), 9);
+        assert_eq!(warnings.count(NetworkWarning::OversizedSerialization), 2);
+    }
+
+    /// The diagnostics use the history length of the plugin, and are only written once per flush interval
+    #[test]
+    fn test_diagnostics_settings() {
+        let registry = registry();
+        let mut warnings = NetworkWarnings::default();
+        let mut sizes = SerializedSizes::new(SerializedSizeConfig {
+            enabled: true,
+            warning_threshold: None,
+        });
+        sizes.record_component(
+            ComponentKind::of::<Component1>(),
+            &registry,
+            4,
+            Entity::PLACEHOLDER,
+            &mut warnings,
+        );
+
+        let mut app = App::new();
+        app.add_plugins(SerializedSizeDiagnosticsPlugin {
+            history_len: 3,
+            flush_interval: Duration::from_millis(100),
+        });
+        let mut settings = app
+            .world_mut()
+            .remove_resource::<SerializedSizeDiagnosticsSettings>()
+            .unwrap();
+        let mut store = DiagnosticsStore::default();
+        let start = Instant::now();
+        for millis in (0..1000).step_by(50) {
+            let now = start + Duration::from_millis(millis);
+            SerializedSizeDiagnosticsPlugin::add_measurements(
+                &sizes,
+                &mut settings,
+                now,
+                &mut store,
+            );
+        }
+
+        let name = sizes.component::<Component1>().unwrap().name();
+        let diagnostic = store
+            .get(&SerializedSizeDiagnosticsPlugin::component_path(
+                name, "max",
+            ))
+            .unwrap();
+        assert_eq!(diagnostic.get_max_history_length(), 3);
+        assert_eq!(diagnostic.history_len(), 3);
+        // 20 calls 50ms apart: one flush every 100ms
+        let times: Vec<_> = diagnostic.measurements().map(|m| m.time - start).collect();
+        assert_eq!(
+            times,
+            vec![
+                Duration::from_millis(700),
+                Duration::from_millis(800),
+                Duration::from_millis(900)
+            ]
+        );
+        assert_eq!(diagnostic.value(), Some(4.0));
+    }
+}
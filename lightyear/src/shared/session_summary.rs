@@ -0,0 +1,228 @@
+//! Summary of the network statistics of a whole session, for example to send to an analytics service
+//! when a connection ends.
+//!
+//! Each connection accumulates its statistics as packets are sent and received (client-side for the
+//! connection to the server, server-side for each client). The summary is attached to the `DisconnectEvent`,
+//! and can also be retrieved at any time during the session with `session_summary()` on the connection.
+use bevy::utils::Duration;
+
+use crate::packet::stats_manager::packet::PacketStatsManager;
+use crate::utils::quantile::P2Quantile;
+
+/// Round-trip time statistics over a whole session
+///
+/// The percentiles are estimated with a fixed-size sketch, so they are approximate.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct RttSummary {
+    /// Number of round-trip time measurements (one per pong received)
+    pub samples: u64,
+    pub mean: Duration,
+    pub p50: Duration,
+    pub p90: Duration,
+    pub p99: Duration,
+    pub max: Duration,
+}
+
+/// Network statistics of a connection since it was established
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SessionSummary {
+    /// Time spent connected, in the virtual time of the app
+    pub duration: Duration,
+    /// Number of bytes sent to the remote, without the netcode headers
+    pub bytes_sent: u64,
+    /// Number of bytes received from the remote, without the netcode headers
+    pub bytes_received: u64,
+    /// Number of packets sent to the remote
+    pub packets_sent: u64,
+    /// Number of packets received from the remote
+    pub packets_received: u64,
+    /// Number of sent packets that were not acknowledged in time and were considered lost
+    pub packets_lost: u64,
+    pub rtt: RttSummary,
+    /// Number of rollbacks of the predicted entities (client only)
+    pub rollbacks: u32,
+    /// Largest number of ticks replayed in a single rollback (client only)
+    pub max_rollback_depth: u32,
+    /// Number of entity spawns sent to the client because the entity became relevant to it (server only)
+    pub relevance_spawns: u32,
+    /// Number of entity despawns sent to the client because the entity stopped being relevant to it (server only)
+    pub relevance_despawns: u32,
+    /// Why the connection was closed, if it is known (client only)
+    pub disconnect_reason: Option<String>,
+}
+
+impl SessionSummary {
+    /// Fraction of the sent packets that were lost
+    pub fn packet_loss(&self) -> f32 {
+        if self.packets_sent == 0 {
+            return 0.0;
+        }
+        self.packets_lost as f32 / self.packets_sent as f32
+    }
+}
+
+/// Accumulates the [`SessionSummary`] of a connection
+#[derive(Debug, Clone)]
+pub(crate) struct SessionStats {
+    duration: Duration,
+    bytes_sent: u64,
+    bytes_received: u64,
+    rtt_sum: Duration,
+    rtt_max: Duration,
+    /// Sketches for the p50, p90 and p99 of the rtt, in seconds
+    rtt_quantiles: [P2Quantile; 3],
+    rollbacks: u32,
+    max_rollback_depth: u32,
+    relevance_spawns: u32,
+    relevance_despawns: u32,
+}
+
+impl Default for SessionStats {
+    fn default() -> Self {
+        Self {
+            duration: Duration::default(),
+            bytes_sent: 0,
+            bytes_received: 0,
+            rtt_sum: Duration::default(),
+            rtt_max: Duration::default(),
+            rtt_quantiles: [0.5, 0.9, 0.99].map(P2Quantile::new),
+            rollbacks: 0,
+            max_rollback_depth: 0,
+            relevance_spawns: 0,
+            relevance_despawns: 0,
+        }
+    }
+}
+
+impl SessionStats {
+    pub(crate) fn update(&mut self, delta: Duration) {
+        self.duration += delta;
+    }
+
+    pub(crate) fn record_sent(&mut self, bytes: usize) {
+        self.bytes_sent += bytes as u64;
+    }
+
+    pub(crate) fn record_received(&mut self, bytes: usize) {
+        self.bytes_received += bytes as u64;
+    }
+
+    pub(crate) fn record_rtt(&mut self, rtt: Duration) {
+        self.rtt_sum += rtt;
+        self.rtt_max = self.rtt_max.max(rtt);
+        self.rtt_quantiles
+            .iter_mut()
+            .for_each(|quantile| quantile.observe(rtt.as_secs_f64()));
+    }
+
+    pub(crate) fn record_rollback(&mut self, depth: u32) {
+        self.rollbacks += 1;
+        self.max_rollback_depth = self.max_rollback_depth.max(depth);
+    }
+
+    pub(crate) fn record_relevance_spawn(&mut self) {
+        self.relevance_spawns += 1;
+    }
+
+    pub(crate) fn record_relevance_despawn(&mut self) {
+        self.relevance_despawns += 1;
+    }
+
+    /// Build the summary, using the packet counters of the connection
+    pub(crate) fn summary(&self, packet_stats: &PacketStatsManager) -> SessionSummary {
+        let samples = self.rtt_quantiles[0].count();
+        let estimate = |index: usize| {
+            self.rtt_quantiles[index]
+                .estimate()
+                .map_or(Duration::default(), Duration::from_secs_f64)
+        };
+        let [p50, p90, p99] = [estimate(0), estimate(1), estimate(2)];
+        SessionSummary {
+            duration: self.duration,
+            bytes_sent: self.bytes_sent,
+            bytes_received: self.bytes_received,
+            packets_sent: packet_stats.total_sent_packets(),
+            packets_received: packet_stats.total_received_packets(),
+            packets_lost: packet_stats.total_sent_packets_lost(),
+            rtt: RttSummary {
+                samples,
+                mean: if samples == 0 {
+                    Duration::default()
+                } else {
+                    self.rtt_sum.div_f64(samples as f64)
+                },
+                p50,
+                p90,
+                p99,
+                max: self.rtt_max,
+            },
+            rollbacks: self.rollbacks,
+            max_rollback_depth: self.max_rollback_depth,
+            relevance_spawns: self.relevance_spawns,
+            relevance_despawns: self.relevance_despawns,
+            disconnect_reason: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_session_summary() {
+        let mut stats = SessionStats::default();
+        let mut packet_stats = PacketStatsManager::default();
+
+        // 10 seconds of traffic at 100 frames per second: 50 bytes sent and 200 bytes received per frame,
+        // and one packet out of 20 is lost
+        for frame in 0..1000 {
+            stats.update(Duration::from_millis(10));
+            stats.record_sent(50);
+            packet_stats.sent_packet();
+            if frame % 20 == 0 {
+                packet_stats.sent_packet_lost();
+            } else {
+                packet_stats.sent_packet_acked();
+            }
+            stats.record_received(200);
+            packet_stats.received_packet();
+        }
+        // one pong every 10 frames: the rtt is 40ms to 59ms uniformly, with a 200ms spike every 50 pongs
+        for i in 0..100u64 {
+            let rtt = if i % 50 == 49 { 200 } else { 40 + (i * 7) % 20 };
+            stats.record_rtt(Duration::from_millis(rtt));
+        }
+        for depth in [2, 5, 3] {
+            stats.record_rollback(depth);
+        }
+        stats.record_relevance_spawn();
+        stats.record_relevance_despawn();
+        stats.record_relevance_spawn();
+
+        let summary = stats.summary(&packet_stats);
+        assert_eq!(summary.duration, Duration::from_secs(10));
+        assert_eq!(summary.bytes_sent, 50_000);
+        assert_eq!(summary.bytes_received, 200_000);
+        assert_eq!(summary.packets_sent, 1000);
+        assert_eq!(summary.packets_received, 1000);
+        assert_eq!(summary.packets_lost, 50);
+        assert_eq!(summary.packet_loss(), 0.05);
+        assert_eq!(summary.rtt.samples, 100);
+        assert_eq!(summary.rtt.max, Duration::from_millis(200));
+        // 98 samples with a mean of 49.5ms, and 2 samples of 200ms
+        let mean_ms = summary.rtt.mean.as_secs_f64() * 1000.0;
+        assert!((mean_ms - 52.5).abs() < 0.5, "mean rtt: {mean_ms}ms");
+        let p50_ms = summary.rtt.p50.as_secs_f64() * 1000.0;
+        assert!((p50_ms - 50.0).abs() < 3.0, "p50 rtt: {p50_ms}ms");
+        let p90_ms = summary.rtt.p90.as_secs_f64() * 1000.0;
+        assert!((p90_ms - 58.0).abs() < 3.0, "p90 rtt: {p90_ms}ms");
+        assert!(summary.rtt.p99 <= summary.rtt.max);
+        assert!(summary.rtt.p99 > summary.rtt.p90);
+        assert_eq!(summary.rollbacks, 3);
+        assert_eq!(summary.max_rollback_depth, 5);
+        assert_eq!(summary.relevance_spawns, 2);
+        assert_eq!(summary.relevance_despawns, 1);
+        assert_eq!(summary.disconnect_reason, None);
+    }
+}
@@ -22,6 +22,7 @@ use chrono::Duration as ChronoDuration;
 pub use wrapped_time::WrappedTime;
 
 use crate::prelude::Tick;
+use crate::shared::clock::NetworkClock;
 
 /// Plugin that will centralize information about the various times (real, virtual, fixed)
 /// as well as track when we should send updates to the remote
@@ -30,7 +31,11 @@ pub(crate) struct TimePlugin;
 impl Plugin for TimePlugin {
     fn build(&self, app: &mut App) {
         // RESOURCES
-        app.insert_resource(TimeManager::default());
+        let clock = app
+            .world_mut()
+            .get_resource_or_insert_with(NetworkClock::default)
+            .clone();
+        app.insert_resource(TimeManager::default().with_clock(clock));
         // SYSTEMS
         app.add_systems(
             RunFixedMainLoop,
@@ -62,6 +67,8 @@ pub struct TimeManager {
     pub(crate) sync_relative_speed: f32,
     /// Instant at the start of the frame
     frame_start: Option<Instant>,
+    /// Clock used to read the current instant
+    clock: NetworkClock,
 }
 
 impl Default for TimeManager {
@@ -80,9 +87,21 @@ impl TimeManager {
             base_relative_speed: 1.0,
             sync_relative_speed: 1.0,
             frame_start: None,
+            clock: NetworkClock::default(),
         }
     }
 
+    /// Read the current instant from the `clock`
+    pub fn with_clock(mut self, clock: NetworkClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// The clock used to read the current instant
+    pub fn clock(&self) -> &NetworkClock {
+        &self.clock
+    }
+
     pub fn delta(&self) -> Duration {
         self.delta
     }
@@ -104,7 +123,7 @@ impl TimeManager {
     pub(crate) fn update(&mut self, delta: Duration) {
         self.delta = delta;
         self.wrapped_time.elapsed += delta;
-        self.frame_start = Some(Instant::now());
+        self.frame_start = Some(self.clock.now());
     }
 
     // TODO: reuse time-real for this?
@@ -112,7 +131,7 @@ impl TimeManager {
     /// (useful for
     pub(crate) fn real_time_since_frame_start(&self) -> Duration {
         self.frame_start
-            .map(|start| Instant::now() - start)
+            .map(|start| self.clock.now() - start)
             .unwrap_or_default()
     }
 
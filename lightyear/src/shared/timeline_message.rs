@@ -0,0 +1,222 @@
+//! Messages applied at a tick of one of the visual timelines of the client.
+//!
+//! The server sometimes needs to tell a client "play this effect where entity E was at tick T": a hit
+//! confirmation should be rendered on the interpolated timeline, when the client displays the interpolated
+//! entities at tick T, and not as soon as the message is received.
+//!
+//! ```rust,ignore
+//! connection_manager.send_message_at_tick::<EffectChannel, _>(
+//!     &HitConfirmed { target },
+//!     tick_manager.tick(),
+//!     VisualTimeline::Interpolated,
+//!     NetworkTarget::All,
+//! )?;
+//! ```
+//!
+//! The client holds the message back and emits the `MessageEvent` on the first frame where the selected
+//! [`VisualTimeline`] reached the tick:
+//! - [`VisualTimeline::Interpolated`]: the frame where the interpolated components display the state of the
+//!   server at that tick. Use it for most cosmetic events.
+//! - [`VisualTimeline::Predicted`]: the frame that simulates that tick on the client, so that the `FixedUpdate`
+//!   systems can react to the message during that tick. Use it for gameplay reactions.
+//!
+//! If the timeline had already passed the tick when the message was received, the message is emitted
+//! immediately and flagged as late (see [`TimelineDelivery`]).
+//!
+//! Timeline messages carry a small header (~3 bytes); the local client of a host-server receives them
+//! immediately, without [`TimelineDelivery`].
+use bevy::reflect::Reflect;
+use byteorder::{ReadBytesExt, WriteBytesExt};
+
+use crate::serialize::reader::Reader;
+use crate::serialize::{SerializationError, ToBytes};
+use crate::shared::tick_manager::Tick;
+
+/// Timeline of the client on which a message sent with `send_message_at_tick` is applied
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Reflect)]
+pub enum VisualTimeline {
+    /// Timeline of the interpolated entities, which is behind the server
+    Interpolated,
+    /// Timeline of the predicted entities (the tick of the client), which is ahead of the server
+    Predicted,
+}
+
+/// Header written in front of the messages that are applied at a tick of a [`VisualTimeline`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct TimelineMessageHeader {
+    pub(crate) tick: Tick,
+    pub(crate) timeline: VisualTimeline,
+}
+
+impl ToBytes for TimelineMessageHeader {
+    fn len(&self) -> usize {
+        self.tick.len() + 1
+    }
+
+    fn to_bytes<T: WriteBytesExt>(&self, buffer: &mut T) -> Result<(), SerializationError> {
+        self.tick.to_bytes(buffer)?;
+        buffer.write_u8(match self.timeline {
+            VisualTimeline::Interpolated => 0,
+            VisualTimeline::Predicted => 1,
+        })?;
+        Ok(())
+    }
+
+    fn from_bytes(buffer: &mut Reader) -> Result<Self, SerializationError>
+    where
+        Self: Sized,
+    {
+        let tick = Tick::from_bytes(buffer)?;
+        let timeline = match buffer.read_u8()? {
+            0 => VisualTimeline::Interpolated,
+            1 => VisualTimeline::Predicted,
+            _ => return Err(SerializationError::InvalidValue),
+        };
+        Ok(Self { tick, timeline })
+    }
+}
+
+/// How a message sent with `send_message_at_tick` was delivered
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimelineDelivery {
+    /// Tick of the timeline at which the message is applied
+    pub tick: Tick,
+    pub timeline: VisualTimeline,
+    /// True if the timeline had already passed the tick when the message was received: the message was
+    /// emitted immediately instead of on the frame where the timeline reached the tick
+    pub late: bool,
+}
+
+#[derive(Debug)]
+struct PendingTimelineMessage<T> {
+    header: TimelineMessageHeader,
+    /// True once the message was compared with the timelines, so it can no longer be late
+    checked: bool,
+    message: T,
+}
+
+/// Holds back the timeline messages received from the server until their timeline reaches their tick
+#[derive(Debug)]
+pub(crate) struct TimelineMessageBuffer<T> {
+    pending: Vec<PendingTimelineMessage<T>>,
+}
+
+impl<T> Default for TimelineMessageBuffer<T> {
+    fn default() -> Self {
+        Self {
+            pending: Vec::new(),
+        }
+    }
+}
+
+impl<T> TimelineMessageBuffer<T> {
+    /// Buffer a timeline message that was just received
+    pub(crate) fn recv(&mut self, header: TimelineMessageHeader, message: T) {
+        self.pending.push(PendingTimelineMessage {
+            header,
+            checked: false,
+            message,
+        });
+    }
+
+    /// Return the messages whose timeline reached their tick, in the order in which they were received.
+    ///
+    /// `predicted_tick` is the next tick simulated by the client, and `interpolation_tick` the tick
+    /// displayed by the interpolated entities.
+    pub(crate) fn drain_ready(
+        &mut self,
+        predicted_tick: Tick,
+        interpolation_tick: Tick,
+    ) -> Vec<(T, TimelineDelivery)> {
+        let mut ready = Vec::new();
+        let mut pending = Vec::with_capacity(self.pending.len());
+        for mut message in self.pending.drain(..) {
+            let TimelineMessageHeader { tick, timeline } = message.header;
+            let current = match timeline {
+                VisualTimeline::Interpolated => interpolation_tick,
+                VisualTimeline::Predicted => predicted_tick,
+            };
+            if current < tick {
+                message.checked = true;
+                pending.push(message);
+                continue;
+            }
+            let delivery = TimelineDelivery {
+                tick,
+                timeline,
+                late: !message.checked && current > tick,
+            };
+            ready.push((message.message, delivery));
+        }
+        self.pending = pending;
+        ready
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(tick: u16, timeline: VisualTimeline) -> TimelineMessageHeader {
+        TimelineMessageHeader {
+            tick: Tick(tick),
+            timeline,
+        }
+    }
+
+    #[test]
+    fn test_header_serialization() {
+        let header = header(u16::MAX, VisualTimeline::Predicted);
+        let mut writer = Vec::new();
+        header.to_bytes(&mut writer).unwrap();
+        assert_eq!(writer.len(), header.len());
+        let mut reader = Reader::from(writer);
+        assert_eq!(
+            TimelineMessageHeader::from_bytes(&mut reader).unwrap(),
+            header
+        );
+    }
+
+    #[test]
+    fn test_drain_ready() {
+        let mut buffer = TimelineMessageBuffer::default();
+        buffer.recv(header(10, VisualTimeline::Interpolated), 'a');
+        buffer.recv(header(30, VisualTimeline::Predicted), 'b');
+        buffer.recv(header(5, VisualTimeline::Interpolated), 'c');
+
+        // the tick of `c` was already passed when it was received
+        let ready = buffer.drain_ready(Tick(20), Tick(8));
+        assert_eq!(
+            ready,
+            vec![(
+                'c',
+                TimelineDelivery {
+                    tick: Tick(5),
+                    timeline: VisualTimeline::Interpolated,
+                    late: true,
+                }
+            )]
+        );
+
+        // the interpolated timeline skipped over tick 10 during a single frame: `a` is not late
+        let ready = buffer.drain_ready(Tick(25), Tick(11));
+        assert_eq!(
+            ready,
+            vec![(
+                'a',
+                TimelineDelivery {
+                    tick: Tick(10),
+                    timeline: VisualTimeline::Interpolated,
+                    late: false,
+                }
+            )]
+        );
+
+        assert!(buffer.drain_ready(Tick(29), Tick(20)).is_empty());
+        let ready = buffer.drain_ready(Tick(30), Tick(21));
+        assert_eq!(ready.len(), 1);
+        assert_eq!(ready[0].0, 'b');
+        assert!(!ready[0].1.late);
+        assert!(buffer.pending.is_empty());
+    }
+}
@@ -0,0 +1,273 @@
+//! Rate-limited reporting of the recoverable errors that happen inside the networking internals
+//!
+//! Some errors (for example receiving a despawn for an entity that we don't know about) are not fatal,
+//! but can happen for every packet once a connection is in a bad state, which would flood the logs.
+//! Instead, each connection counts the [`NetworkWarning`]s it encounters, and logs each category at most once
+//! per `warning_log_interval` (see [`PacketConfig`](crate::prelude::client::PacketConfig)), along with the
+//! number of similar warnings that were suppressed since the last log.
+//!
+//! The counters can be read from the connection, or via the [`NetworkWarningsDiagnosticsPlugin`].
+use std::fmt;
+
+use bevy::app::{App, Plugin};
+use bevy::diagnostic::{Diagnostic, DiagnosticPath, Diagnostics, RegisterDiagnostic};
+use bevy::reflect::Reflect;
+use bevy::utils::{Duration, Instant};
+use tracing::{debug, error, warn};
+
+use crate::shared::clock::NetworkClock;
+
+/// Category of a recoverable error encountered while processing the packets of a connection
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Reflect)]
+#[non_exhaustive]
+pub enum NetworkWarning {
+    /// Received a spawn for a remote entity that is already in our entity mapping
+    SpawnExistingEntity,
+    /// Received a spawn that re-uses a local entity that does not exist
+    ReuseMissingEntity,
+    /// Received a despawn for a remote entity that is not in our entity mapping
+    DespawnUnknownEntity,
+    /// Received actions for a remote entity that is not in our entity mapping
+    UnknownEntity,
+    /// A replicated component could not be written to its entity
+    ComponentWriteFailed,
+    /// Received an update for an entity alias that could not be resolved
+    UnknownEntityAlias,
+    /// Received a pong for a ping that is not in the ping store anymore
+    StalePong,
+    /// An input message could not be deserialized
+    InvalidInputMessage,
+    /// Received a component or message whose type was removed from the protocol; it was skipped.
+    /// These are only logged at the debug level
+    DeprecatedKind,
+    /// Dropped a component or message that references an entity that was not spawned yet, because
+    /// too many of them were waiting for their entities
+    DeferredOverflow,
+    /// A message or component was serialized to more bytes than its warning threshold
+    /// (see [`serialized_size`](crate::shared::serialized_size))
+    OversizedSerialization,
+}
+
+const NUM_WARNINGS: usize = NetworkWarning::ALL.len();
+
+impl NetworkWarning {
+    /// All the warning categories
+    pub const ALL: [NetworkWarning; 11] = [
+        NetworkWarning::SpawnExistingEntity,
+        NetworkWarning::ReuseMissingEntity,
+        NetworkWarning::DespawnUnknownEntity,
+        NetworkWarning::UnknownEntity,
+        NetworkWarning::ComponentWriteFailed,
+        NetworkWarning::UnknownEntityAlias,
+        NetworkWarning::StalePong,
+        NetworkWarning::InvalidInputMessage,
+        NetworkWarning::DeprecatedKind,
+        NetworkWarning::DeferredOverflow,
+        NetworkWarning::OversizedSerialization,
+    ];
+
+    fn index(self) -> usize {
+        self as usize
+    }
+
+    /// Path of the diagnostic that counts the warnings of this category
+    pub const fn diagnostic_path(self) -> DiagnosticPath {
+        DiagnosticPath::const_new(match self {
+            NetworkWarning::SpawnExistingEntity => "network_warnings.spawn_existing_entity",
+            NetworkWarning::ReuseMissingEntity => "network_warnings.reuse_missing_entity",
+            NetworkWarning::DespawnUnknownEntity => "network_warnings.despawn_unknown_entity",
+            NetworkWarning::UnknownEntity => "network_warnings.unknown_entity",
+            NetworkWarning::ComponentWriteFailed => "network_warnings.component_write_failed",
+            NetworkWarning::UnknownEntityAlias => "network_warnings.unknown_entity_alias",
+            NetworkWarning::StalePong => "network_warnings.stale_pong",
+            NetworkWarning::InvalidInputMessage => "network_warnings.invalid_input_message",
+            NetworkWarning::DeprecatedKind => "network_warnings.deprecated_kind",
+            NetworkWarning::DeferredOverflow => "network_warnings.deferred_overflow",
+            NetworkWarning::OversizedSerialization => "network_warnings.oversized_serialization",
+        })
+    }
+
+    /// Warnings that indicate that some data was lost are logged as errors
+    fn is_error(self) -> bool {
+        !matches!(
+            self,
+            NetworkWarning::SpawnExistingEntity
+                | NetworkWarning::UnknownEntityAlias
+                | NetworkWarning::StalePong
+                | NetworkWarning::DeprecatedKind
+                | NetworkWarning::OversizedSerialization
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct WarningState {
+    total: u64,
+    /// Number of warnings that were not logged since the last log
+    suppressed: u64,
+    last_logged: Option<Instant>,
+}
+
+/// Counts the [`NetworkWarning`]s of a connection, and logs them with rate-limiting
+#[derive(Debug, Clone)]
+pub struct NetworkWarnings {
+    log_interval: Duration,
+    states: [WarningState; NUM_WARNINGS],
+    clock: NetworkClock,
+}
+
+impl Default for NetworkWarnings {
+    fn default() -> Self {
+        Self::new(Duration::from_secs(5))
+    }
+}
+
+impl NetworkWarnings {
+    pub(crate) fn new(log_interval: Duration) -> Self {
+        Self {
+            log_interval,
+            states: [WarningState::default(); NUM_WARNINGS],
+            clock: NetworkClock::default(),
+        }
+    }
+
+    pub(crate) fn with_clock(mut self, clock: NetworkClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Total number of warnings of this category encountered by the connection
+    pub fn count(&self, warning: NetworkWarning) -> u64 {
+        self.states[warning.index()].total
+    }
+
+    /// Total number of warnings encountered by the connection, for each category
+    pub fn counts(&self) -> impl Iterator<Item = (NetworkWarning, u64)> + '_ {
+        NetworkWarning::ALL
+            .into_iter()
+            .map(|warning| (warning, self.count(warning)))
+    }
+
+    /// Count the warning, and log it unless a warning of the same category was logged recently
+    pub(crate) fn report(&mut self, warning: NetworkWarning, details: fmt::Arguments) {
+        let Some(suppressed) = self.record(warning, self.clock.now()) else {
+            return;
+        };
+        if warning == NetworkWarning::DeprecatedKind {
+            debug!(?warning, suppressed, "{details}");
+        } else if warning.is_error() {
+            error!(?warning, suppressed, "{details}");
+        } else {
+            warn!(?warning, suppressed, "{details}");
+        }
+    }
+
+    /// Count the warning.
+    ///
+    /// Returns the number of suppressed warnings of that category since the last log if the warning should be logged
+    fn record(&mut self, warning: NetworkWarning, now: Instant) -> Option<u64> {
+        let state = &mut self.states[warning.index()];
+        state.total += 1;
+        if state
+            .last_logged
+            .is_some_and(|last| now.saturating_duration_since(last) < self.log_interval)
+        {
+            state.suppressed += 1;
+            return None;
+        }
+        state.last_logged = Some(now);
+        Some(std::mem::take(&mut state.suppressed))
+    }
+
+    /// Add the counts of another connection to these counts
+    pub(crate) fn merge_counts(&mut self, other: &NetworkWarnings) {
+        for (state, other) in self.states.iter_mut().zip(other.states.iter()) {
+            state.total += other.total;
+        }
+    }
+}
+
+/// Plugin to expose the [`NetworkWarnings`] counters as diagnostics
+pub struct NetworkWarningsDiagnosticsPlugin {
+    pub history_len: usize,
+    pub flush_interval: Duration,
+}
+
+impl Default for NetworkWarningsDiagnosticsPlugin {
+    fn default() -> Self {
+        Self {
+            history_len: 60,
+            flush_interval: Duration::from_millis(200),
+        }
+    }
+}
+
+impl NetworkWarningsDiagnosticsPlugin {
+    pub(crate) fn add_measurements(warnings: &NetworkWarnings, mut diagnostics: Diagnostics) {
+        for (warning, count) in warnings.counts() {
+            diagnostics.add_measurement(&warning.diagnostic_path(), || count as f64);
+        }
+    }
+}
+
+impl Plugin for NetworkWarningsDiagnosticsPlugin {
+    fn build(&self, app: &mut App) {
+        for warning in NetworkWarning::ALL {
+            app.register_diagnostic(
+                Diagnostic::new(warning.diagnostic_path())
+                    .with_max_history_length(self.history_len),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_flood_is_rate_limited() {
+        let interval = Duration::from_secs(1);
+        let mut warnings = NetworkWarnings::new(interval);
+        let start = Instant::now();
+
+        // 10_000 warnings over 5 seconds
+        let mut logged = vec![];
+        for i in 0..10_000u64 {
+            let now = start + Duration::from_micros(i * 500);
+            if let Some(suppressed) = warnings.record(NetworkWarning::UnknownEntity, now) {
+                logged.push(suppressed);
+            }
+        }
+        // one log per interval
+        assert_eq!(logged.len(), 5);
+        assert_eq!(logged[0], 0);
+        assert_eq!(logged[1], 1999);
+        // every warning is either logged or counted as suppressed
+        let pending = warnings.states[NetworkWarning::UnknownEntity.index()].suppressed;
+        assert_eq!(
+            logged.len() as u64 + logged.iter().sum::<u64>() + pending,
+            10_000
+        );
+        assert_eq!(warnings.count(NetworkWarning::UnknownEntity), 10_000);
+        assert_eq!(warnings.count(NetworkWarning::StalePong), 0);
+    }
+
+    #[test]
+    fn test_categories_are_rate_limited_separately() {
+        let mut warnings = NetworkWarnings::new(Duration::from_secs(1));
+        let now = Instant::now();
+        assert_eq!(warnings.record(NetworkWarning::StalePong, now), Some(0));
+        assert_eq!(warnings.record(NetworkWarning::StalePong, now), None);
+        assert_eq!(
+            warnings.record(NetworkWarning::ComponentWriteFailed, now),
+            Some(0)
+        );
+        assert_eq!(
+            warnings.record(NetworkWarning::StalePong, now + Duration::from_secs(1)),
+            Some(1)
+        );
+        assert_eq!(warnings.count(NetworkWarning::StalePong), 3);
+        assert_eq!(warnings.count(NetworkWarning::ComponentWriteFailed), 1);
+    }
+}
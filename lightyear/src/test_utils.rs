@@ -0,0 +1,470 @@
+/*! A server and clients connected in memory, to write tests and tools
+
+This module is only compiled with the `test_utils` feature, which should not be enabled in shipping builds.
+
+[`LightyearTestPair`] builds a server `App` and one or more client `App`s connected with the in-memory
+channels transport, with netcode authentication already set up. All the apps share a [`MockClock`], so their time
+only advances when the pair is stepped, and the tests are deterministic:
+
+```rust,ignore
+use lightyear::prelude::*;
+
+let mut pair = LightyearTestPair::builder()
+    .protocol(ProtocolPlugin)
+    .tick_rate(64.0)
+    .conditioner(LinkConditionerConfig::average_condition())
+    .clients(2)
+    .build();
+// the clients are connected and synced with the server
+let entity = pair.server_world_mut().spawn(server::Replicate::default()).id();
+pair.frame_steps(10);
+assert!(pair.client_world(0).resource::<client::ConnectionManager>().is_synced());
+```
+
+With the `test_utils` feature, the [`LinkConditioner`](crate::transport::middleware::conditioner::LinkConditioner)
+delays the packets with the mock time of the pair instead of the system time.
+*/
+use std::io::Write;
+use std::net::{Ipv4Addr, SocketAddr};
+
+use bevy::ecs::system::RunSystemOnce;
+use bevy::prelude::{default, App, Commands, Plugin, Real, Time, World};
+use bevy::state::app::StatesPlugin;
+use bevy::utils::Duration;
+use bevy::MinimalPlugins;
+use bytes::Bytes;
+
+use crate::channel::builder::EntityUpdatesChannel;
+use crate::channel::receivers::ChannelReceive;
+
+use crate::client::networking::ClientCommands;
+use crate::connection::id::ClientId;
+use crate::connection::netcode::{generate_key, MAX_PACKET_SIZE};
+use crate::packet::message_manager::MessageManager;
+use crate::packet::priority_manager::PriorityConfig;
+use crate::prelude::client::{Authentication, ClientConfig, ClientTransport};
+use crate::prelude::server::{ServerCommands, ServerConfig, ServerTransport};
+use crate::prelude::{
+    client, server, ChannelKind, ChannelRegistry, LinkConditionerConfig, PingConfig, Tick,
+    TickConfig,
+};
+use crate::serialize::writer::Writer;
+use crate::shared::clock::{Clock, MockClock};
+use crate::transport::LOCAL_SOCKET;
+
+/// Maximum number of frames to wait for the clients to be connected and synced
+const MAX_CONNECTION_FRAMES: usize = 200;
+
+type ProtocolFn = Box<dyn Fn(&mut App)>;
+
+/// Builder of a [`LightyearTestPair`]
+pub struct LightyearTestPairBuilder {
+    protocol: Option<ProtocolFn>,
+    tick_duration: Duration,
+    frame_duration: Option<Duration>,
+    conditioner: Option<LinkConditionerConfig>,
+    num_clients: usize,
+    client_config: ClientConfig,
+    server_config: ServerConfig,
+}
+
+impl Default for LightyearTestPairBuilder {
+    fn default() -> Self {
+        Self {
+            protocol: None,
+            tick_duration: Duration::from_millis(10),
+            frame_duration: None,
+            conditioner: None,
+            num_clients: 1,
+            client_config: ClientConfig::default(),
+            server_config: ServerConfig::default(),
+        }
+    }
+}
+
+impl LightyearTestPairBuilder {
+    /// Plugin that registers the protocol (channels, messages, components, inputs).
+    /// It is added to the server and to every client.
+    pub fn protocol<P: Plugin + Clone>(mut self, protocol: P) -> Self {
+        self.protocol = Some(Box::new(move |app: &mut App| {
+            app.add_plugins(protocol.clone());
+        }));
+        self
+    }
+
+    /// Number of ticks per second of the server and the clients (100 by default)
+    pub fn tick_rate(self, hz: f64) -> Self {
+        self.tick_duration(Duration::from_secs_f64(1.0 / hz))
+    }
+
+    /// Duration of a tick of the server and the clients (10ms by default)
+    pub fn tick_duration(mut self, tick_duration: Duration) -> Self {
+        self.tick_duration = tick_duration;
+        self
+    }
+
+    /// Duration of a frame, i.e. the time that passes in [`LightyearTestPair::frame_step`].
+    /// By default it is equal to the tick duration.
+    pub fn frame_duration(mut self, frame_duration: Duration) -> Self {
+        self.frame_duration = Some(frame_duration);
+        self
+    }
+
+    /// Simulate network conditions on the packets received by the server and by the clients
+    pub fn conditioner(mut self, conditioner: LinkConditionerConfig) -> Self {
+        self.conditioner = Some(conditioner);
+        self
+    }
+
+    /// Number of clients connected to the server (1 by default)
+    pub fn clients(mut self, num_clients: usize) -> Self {
+        self.num_clients = num_clients;
+        self
+    }
+
+    /// Configuration of the clients.
+    ///
+    /// The `shared` and `net` fields are overridden by the pair, and the pings are sent every frame.
+    pub fn client_config(mut self, client_config: ClientConfig) -> Self {
+        self.client_config = client_config;
+        self
+    }
+
+    /// Configuration of the server.
+    ///
+    /// The `shared` and `net` fields are overridden by the pair, and the pings are sent every frame.
+    pub fn server_config(mut self, server_config: ServerConfig) -> Self {
+        self.server_config = server_config;
+        self
+    }
+
+    /// Build the apps, start the server and connect the clients.
+    ///
+    /// The pair is stepped until all the clients are synced with the server.
+    pub fn build(self) -> LightyearTestPair {
+        let mut pair = self.build_disconnected();
+        pair.connect();
+        pair
+    }
+
+    /// Build the apps without starting the server or connecting the clients, for example to add systems
+    /// or resources before the plugins are finished. Call [`LightyearTestPair::connect`] afterwards.
+    pub fn build_disconnected(self) -> LightyearTestPair {
+        let clock = MockClock::new();
+        let now = clock.now();
+        let shared = crate::prelude::SharedConfig {
+            tick: TickConfig::new(self.tick_duration),
+            ..self.client_config.shared
+        };
+        // send pings every frame, so that the acks are received every frame
+        let ping = PingConfig {
+            ping_interval: Duration::default(),
+            ..default()
+        };
+        let protocol_id = 0;
+        let private_key = generate_key();
+
+        let new_app = || {
+            let mut app = App::new();
+            app.add_plugins((MinimalPlugins, StatesPlugin));
+            clock.install(&mut app);
+            app
+        };
+        let add_protocol = |app: &mut App| {
+            if let Some(protocol) = &self.protocol {
+                protocol(app);
+            }
+            // Initialize Real time (needed only for the first TimeSystem run)
+            app.world_mut()
+                .resource_mut::<Time<Real>>()
+                .update_with_instant(now);
+        };
+
+        let mut client_apps = Vec::with_capacity(self.num_clients);
+        let mut channels = Vec::with_capacity(self.num_clients);
+        for index in 0..self.num_clients {
+            let (from_server_send, from_server_recv) = crossbeam_channel::unbounded();
+            let (to_server_send, to_server_recv) = crossbeam_channel::unbounded();
+            // each client needs a different address on the server
+            let addr = SocketAddr::from((Ipv4Addr::LOCALHOST, 1234 + index as u16));
+            channels.push((addr, to_server_recv, from_server_send));
+            let mut io = client::IoConfig::from_transport(ClientTransport::LocalChannel {
+                send: to_server_send,
+                recv: from_server_recv,
+            });
+            if let Some(conditioner) = &self.conditioner {
+                io = io.with_conditioner(conditioner.clone());
+            }
+            let config = ClientConfig {
+                shared,
+                net: client::NetConfig::Netcode {
+                    auth: Authentication::Manual {
+                        server_addr: LOCAL_SOCKET,
+                        protocol_id,
+                        private_key,
+                        client_id: LightyearTestPair::netcode_id(index),
+                    },
+                    config: client::NetcodeConfig::default(),
+                    io,
+                },
+                ping,
+                ..self.client_config.clone()
+            };
+            let mut client_app = new_app();
+            client_app.add_plugins(client::ClientPlugins::new(config));
+            add_protocol(&mut client_app);
+            client_apps.push(client_app);
+        }
+
+        let mut io = server::IoConfig::from_transport(ServerTransport::Channels { channels });
+        if let Some(conditioner) = &self.conditioner {
+            io = io.with_conditioner(conditioner.clone());
+        }
+        let config = ServerConfig {
+            shared,
+            net: vec![server::NetConfig::Netcode {
+                config: server::NetcodeConfig::default()
+                    .with_protocol_id(protocol_id)
+                    .with_key(private_key),
+                io,
+            }],
+            ping,
+            ..self.server_config
+        };
+        let mut server_app = new_app();
+        server_app.add_plugins(server::ServerPlugins::new(config));
+        add_protocol(&mut server_app);
+
+        LightyearTestPair {
+            server_app,
+            client_apps,
+            frame_duration: self.frame_duration.unwrap_or(self.tick_duration),
+            tick_duration: self.tick_duration,
+            clock,
+        }
+    }
+}
+
+/// A server and clients connected in memory, see the [module-level documentation](self)
+pub struct LightyearTestPair {
+    pub server_app: App,
+    pub client_apps: Vec<App>,
+    pub frame_duration: Duration,
+    pub tick_duration: Duration,
+    /// Clock shared by all the apps
+    pub clock: MockClock,
+}
+
+impl LightyearTestPair {
+    pub fn builder() -> LightyearTestPairBuilder {
+        LightyearTestPairBuilder::default()
+    }
+
+    fn netcode_id(index: usize) -> u64 {
+        index as u64 + 1
+    }
+
+    /// [`ClientId`] of the client at the given index
+    pub fn client_id(&self, index: usize) -> ClientId {
+        ClientId::Netcode(Self::netcode_id(index))
+    }
+
+    /// Finish the plugins, start the server and connect the clients, then step until all the clients
+    /// are synced with the server
+    pub fn connect(&mut self) {
+        self.server_app.finish();
+        self.server_app.cleanup();
+        self.server_app
+            .world_mut()
+            .run_system_once(|mut commands: Commands| commands.start_server());
+        for client_app in self.client_apps.iter_mut() {
+            client_app.finish();
+            client_app.cleanup();
+            client_app
+                .world_mut()
+                .run_system_once(|mut commands: Commands| commands.connect_client());
+        }
+        for _ in 0..MAX_CONNECTION_FRAMES {
+            if self.client_apps.iter().all(|client_app| {
+                client_app
+                    .world()
+                    .resource::<client::ConnectionManager>()
+                    .is_synced()
+            }) {
+                return;
+            }
+            self.frame_step();
+        }
+    }
+
+    /// Advance the time of all the apps, without updating them
+    pub fn advance_time(&mut self, duration: Duration) {
+        self.clock.advance(duration);
+    }
+
+    /// Update the clients, then the server
+    pub fn update(&mut self) {
+        for client_app in self.client_apps.iter_mut() {
+            client_app.update();
+        }
+        self.server_app.update();
+    }
+
+    /// Advance the time by one frame and update all the apps
+    pub fn frame_step(&mut self) {
+        self.advance_time(self.frame_duration);
+        self.update();
+    }
+
+    /// Run `n` frames
+    pub fn frame_steps(&mut self, n: usize) {
+        for _ in 0..n {
+            self.frame_step();
+        }
+    }
+
+    /// Advance the time by one tick and update all the apps
+    pub fn tick_step(&mut self) {
+        self.advance_time(self.tick_duration);
+        self.update();
+    }
+
+    /// Run `n` ticks
+    pub fn tick_steps(&mut self, n: usize) {
+        for _ in 0..n {
+            self.tick_step();
+        }
+    }
+
+    pub fn server_world(&self) -> &World {
+        self.server_app.world()
+    }
+
+    pub fn server_world_mut(&mut self) -> &mut World {
+        self.server_app.world_mut()
+    }
+
+    /// World of the client at the given index
+    pub fn client_world(&self, index: usize) -> &World {
+        self.client_apps[index].world()
+    }
+
+    /// World of the client at the given index
+    pub fn client_world_mut(&mut self, index: usize) -> &mut World {
+        self.client_apps[index].world_mut()
+    }
+}
+
+/// Number of allocations made on the send side of a [`MessageManager`] while sending the messages of
+/// `measured_ticks` ticks, after `warmup_ticks` ticks during which the buffers get allocated.
+///
+/// Every tick, `messages_per_tick` small messages are sent on the [`EntityUpdatesChannel`], the packets are
+/// received and acked by a second `MessageManager`, then the payloads are recycled if `reuse_buffers` is true.
+/// `allocations` returns the number of allocations made so far, for example by a counting global allocator
+/// (which has to be registered in the test binary).
+pub fn message_send_allocations(
+    reuse_buffers: bool,
+    warmup_ticks: u16,
+    measured_ticks: u16,
+    messages_per_tick: u32,
+    allocations: impl Fn() -> usize,
+) -> usize {
+    let registry = ChannelRegistry::new(Duration::default());
+    let mut sender = MessageManager::new(&registry, 1.5, PriorityConfig::default());
+    let mut receiver = MessageManager::new(&registry, 1.5, PriorityConfig::default());
+    if !reuse_buffers {
+        sender.disable_buffer_reuse();
+    }
+    let mut writer = Writer::with_capacity(MAX_PACKET_SIZE);
+    let channel_kind = ChannelKind::of::<EntityUpdatesChannel>();
+    let mut total = 0;
+    for tick in 0..warmup_ticks + measured_ticks {
+        let start = allocations();
+        for i in 0..messages_per_tick {
+            writer.write_all(&i.to_le_bytes()).unwrap();
+            writer.write_all(&[i as u8; 20]).unwrap();
+            sender.buffer_send(writer.split(), channel_kind).unwrap();
+        }
+        let payloads = sender.send_packets(Tick(tick)).unwrap();
+        let mut tick_allocations = allocations() - start;
+
+        // the receive side is not measured
+        for payload in &payloads {
+            receiver
+                .recv_packet(Bytes::copy_from_slice(payload))
+                .unwrap();
+        }
+        for channel in receiver.channels.values_mut() {
+            while channel.receiver.read_message().is_some() {}
+        }
+        receiver
+            .buffer_send(Bytes::from_static(&[0]), channel_kind)
+            .unwrap();
+        for payload in receiver.send_packets(Tick(tick)).unwrap() {
+            sender.recv_packet(payload.into()).unwrap();
+        }
+
+        let start = allocations();
+        if reuse_buffers {
+            sender.recycle_payloads(payloads);
+            writer.end_frame();
+        } else {
+            drop(payloads);
+        }
+        tick_allocations += allocations() - start;
+        if tick >= warmup_ticks {
+            total += tick_allocations;
+        }
+    }
+    total
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::server::Replicate;
+    use crate::tests::protocol::*;
+
+    /// The clients are connected, and receive the entities replicated by the server
+    #[test]
+    fn test_pair_replication() {
+        let mut pair = LightyearTestPair::builder()
+            .protocol(ProtocolPlugin)
+            .tick_rate(64.0)
+            .conditioner(LinkConditionerConfig {
+                incoming_latency: Duration::from_millis(20),
+                incoming_jitter: Duration::default(),
+                incoming_loss: 0.0,
+                seed: None,
+            })
+            .clients(2)
+            .build();
+        assert_eq!(pair.client_apps.len(), 2);
+        assert_eq!(
+            pair.server_world()
+                .resource::<server::ConnectionManager>()
+                .connected_clients()
+                .count(),
+            2
+        );
+
+        let entity = pair
+            .server_world_mut()
+            .spawn((Component1(1.0), Replicate::default()))
+            .id();
+        pair.frame_steps(20);
+        for index in 0..2 {
+            let client_entity = *pair
+                .client_world(index)
+                .resource::<client::ConnectionManager>()
+                .replication_receiver
+                .remote_entity_map
+                .get_local(entity)
+                .expect("the entity was not replicated");
+            assert_eq!(
+                pair.client_world(index).get::<Component1>(client_entity),
+                Some(&Component1(1.0))
+            );
+        }
+    }
+}
@@ -6,7 +6,6 @@ use bevy::ecs::system::RunSystemOnce;
 use bevy::input::InputPlugin;
 use bevy::prelude::{default, App, Commands, Mut, PluginGroup, Real, Time, World};
 use bevy::state::app::StatesPlugin;
-use bevy::time::TimeUpdateStrategy;
 use bevy::utils::Duration;
 use bevy::MinimalPlugins;
 
@@ -17,6 +16,7 @@ use crate::prelude::client::{
 };
 use crate::prelude::server::{NetcodeConfig, ServerCommands, ServerConfig, ServerTransport};
 use crate::prelude::*;
+use crate::shared::clock::MockClock;
 use crate::shared::time_manager::WrappedTime;
 use crate::tests::protocol::*;
 use crate::transport::LOCAL_SOCKET;
@@ -41,6 +41,8 @@ pub struct HostServerStepper {
     pub frame_duration: Duration,
     pub tick_duration: Duration,
     pub current_time: bevy::utils::Instant,
+    /// clock shared by all the apps
+    pub clock: MockClock,
 }
 
 impl Default for HostServerStepper {
@@ -59,7 +61,6 @@ impl Default for HostServerStepper {
     }
 }
 
-// Do not forget to use --features mock_time when using the LinkConditioner
 impl HostServerStepper {
     pub fn new(
         shared_config: SharedConfig,
@@ -97,8 +98,10 @@ impl HostServerStepper {
         let private_key = generate_key();
 
         // Setup server
+        let clock = MockClock::new();
         let mut server_app = App::new();
         server_app.add_plugins((MinimalPlugins, StatesPlugin));
+        clock.install(&mut server_app);
         let net_config = server::NetConfig::Netcode {
             config: NetcodeConfig::default()
                 .with_protocol_id(protocol_id)
@@ -138,6 +141,7 @@ impl HostServerStepper {
         // Setup external client
         let mut client_app = App::new();
         client_app.add_plugins((MinimalPlugins, StatesPlugin));
+        clock.install(&mut client_app);
         let net_config = NetConfig::Netcode {
             auth: Authentication::Manual {
                 server_addr: addr,
@@ -170,7 +174,7 @@ impl HostServerStepper {
         }
 
         // Initialize Real time (needed only for the first TimeSystem run)
-        let now = bevy::utils::Instant::now();
+        let now = clock.now();
         client_app
             .world_mut()
             .get_resource_mut::<Time<Real>>()
@@ -188,6 +192,7 @@ impl HostServerStepper {
             frame_duration,
             tick_duration: shared_config.tick.tick_duration,
             current_time: now,
+            clock,
         }
     }
 
@@ -306,11 +311,7 @@ impl HostServerStepper {
 
     pub(crate) fn advance_time(&mut self, duration: Duration) {
         self.current_time += duration;
-        self.client_app
-            .insert_resource(TimeUpdateStrategy::ManualInstant(self.current_time));
-        self.server_app
-            .insert_resource(TimeUpdateStrategy::ManualInstant(self.current_time));
-        mock_instant::MockClock::advance(duration);
+        self.clock.advance(duration);
     }
 }
 
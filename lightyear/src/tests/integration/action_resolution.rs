@@ -0,0 +1,131 @@
+//! Tests of the resolution of the client actions by the server
+use bevy::prelude::*;
+use bevy::utils::Duration;
+
+use crate::prelude::client::{ActionResolvedEvent, ClientConfig};
+use crate::prelude::server::ConnectionManager as ServerConnectionManager;
+use crate::prelude::*;
+use crate::tests::stepper::{BevyStepper, Step, TEST_CLIENT_ID};
+
+const TIMEOUT: Duration = Duration::from_millis(200);
+
+#[derive(Resource, Default)]
+struct ResolvedActions(Vec<ActionResolvedEvent>);
+
+fn record_resolved_actions(
+    mut recorded: ResMut<ResolvedActions>,
+    mut events: EventReader<ActionResolvedEvent>,
+) {
+    recorded.0.extend(events.read().cloned());
+}
+
+fn setup() -> BevyStepper {
+    let frame_duration = Duration::from_millis(10);
+    let shared_config = SharedConfig {
+        tick: TickConfig::new(frame_duration),
+        ..default()
+    };
+    let client_config = ClientConfig {
+        action: ActionConfig { timeout: TIMEOUT },
+        ..default()
+    };
+    let mut stepper = BevyStepper::new(shared_config, client_config, frame_duration);
+    stepper.client_app.init_resource::<ResolvedActions>();
+    stepper
+        .client_app
+        .add_systems(Update, record_resolved_actions);
+    stepper.init();
+    stepper
+}
+
+fn begin_action(stepper: &mut BevyStepper) -> ActionId {
+    stepper
+        .client_app
+        .world_mut()
+        .resource_mut::<ActionTracker>()
+        .begin_action()
+}
+
+fn acknowledge(stepper: &mut BevyStepper, action_id: ActionId, verdict: ActionVerdict) {
+    stepper
+        .server_app
+        .world_mut()
+        .resource_mut::<ServerConnectionManager>()
+        .acknowledge_action(ClientId::Netcode(TEST_CLIENT_ID), action_id, verdict)
+        .unwrap();
+}
+
+fn resolved(stepper: &BevyStepper) -> &[ActionResolvedEvent] {
+    &stepper.client_app.world().resource::<ResolvedActions>().0
+}
+
+#[test]
+fn test_accepted_and_denied_actions() {
+    let mut stepper = setup();
+    let accepted = begin_action(&mut stepper);
+    let denied = begin_action(&mut stepper);
+    assert_ne!(accepted, denied);
+
+    let tick = stepper.server_tick();
+    acknowledge(&mut stepper, accepted, ActionVerdict::Accepted);
+    acknowledge(&mut stepper, denied, ActionVerdict::Denied);
+    for _ in 0..5 {
+        stepper.frame_step();
+    }
+
+    let resolved = resolved(&stepper);
+    assert_eq!(resolved.len(), 2);
+    for (action_id, verdict) in [
+        (accepted, ActionVerdict::Accepted),
+        (denied, ActionVerdict::Denied),
+    ] {
+        let event = resolved
+            .iter()
+            .find(|event| event.action_id == action_id)
+            .expect("the action was not resolved");
+        assert_eq!(event.verdict, verdict);
+        // the verdict is sent with the tick at which the server processed the action
+        assert!(event.server_tick.is_some_and(|t| t > tick));
+    }
+    assert_eq!(
+        stepper
+            .client_app
+            .world()
+            .resource::<ActionTracker>()
+            .num_pending(),
+        0
+    );
+}
+
+#[test]
+fn test_unresolved_action_times_out() {
+    let mut stepper = setup();
+    let action_id = begin_action(&mut stepper);
+
+    stepper.frame_step();
+    assert!(stepper
+        .client_app
+        .world()
+        .resource::<ActionTracker>()
+        .is_pending(action_id));
+    assert!(resolved(&stepper).is_empty());
+
+    for _ in 0..30 {
+        stepper.frame_step();
+    }
+    assert_eq!(
+        resolved(&stepper),
+        &[ActionResolvedEvent {
+            action_id,
+            verdict: ActionVerdict::Unresolved,
+            server_tick: None,
+        }]
+    );
+
+    // a late verdict from the server is ignored
+    acknowledge(&mut stepper, action_id, ActionVerdict::Accepted);
+    for _ in 0..5 {
+        stepper.frame_step();
+    }
+    assert_eq!(resolved(&stepper).len(), 1);
+}
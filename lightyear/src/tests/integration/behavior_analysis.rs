@@ -0,0 +1,123 @@
+//! Tests of the analysis of the behavior of the clients on the server
+use bevy::prelude::*;
+use bevy::utils::Duration;
+
+use crate::prelude::client::{InputManager, InputSystemSet};
+use crate::prelude::server::{
+    AppClientBehaviorExt, BehaviorAnalysisConfig, BehaviorAnalysisPlugin, ClientBehaviorHistory,
+    SuspicionEvent,
+};
+use crate::prelude::*;
+use crate::protocol::component::ComponentKind;
+use crate::tests::protocol::*;
+
+#[derive(Resource, Default)]
+struct Suspicions(Vec<SuspicionEvent>);
+
+fn record_suspicions(mut suspicions: ResMut<Suspicions>, mut events: EventReader<SuspicionEvent>) {
+    suspicions.0.extend(events.read().cloned());
+}
+
+fn buffer_inputs(mut input_manager: ResMut<InputManager<MyInput>>, tick_manager: Res<TickManager>) {
+    input_manager.add_input(MyInput(1), tick_manager.tick());
+}
+
+fn build_pair() -> LightyearTestPair {
+    let mut pair = LightyearTestPair::builder()
+        .protocol(ProtocolPlugin)
+        .build_disconnected();
+    pair.server_app
+        .add_plugins(BehaviorAnalysisPlugin::new(
+            BehaviorAnalysisConfig::default()
+                .with_history_ticks(100)
+                .with_analysis_interval(Duration::from_millis(50)),
+        ))
+        .add_max_displacement_analyzer::<Component1>(2.0, |a, b| (a.0 - b.0).abs())
+        .add_input_rate_analyzer(10)
+        .init_resource::<Suspicions>()
+        .add_systems(Last, record_suspicions);
+    pair.client_apps[0].add_systems(
+        FixedPreUpdate,
+        buffer_inputs.in_set(InputSystemSet::BufferInputs),
+    );
+    pair.connect();
+    pair
+}
+
+fn suspicions(pair: &LightyearTestPair) -> &[SuspicionEvent] {
+    &pair.server_world().resource::<Suspicions>().0
+}
+
+/// The server records the inputs and the component updates of the client, and suspects the client
+/// when it teleports an entity
+#[test]
+fn test_max_displacement() {
+    let mut pair = build_pair();
+    let client_id = pair.client_id(0);
+    let entity = pair
+        .client_world_mut(0)
+        .spawn((client::Replicate::default(), Component1(0.0)))
+        .id();
+    pair.frame_steps(5);
+    // move by one unit per tick
+    for _ in 0..20 {
+        pair.client_world_mut(0)
+            .get_mut::<Component1>(entity)
+            .unwrap()
+            .0 += 1.0;
+        pair.frame_step();
+    }
+    pair.frame_steps(10);
+    assert!(suspicions(&pair).is_empty(), "{:?}", suspicions(&pair));
+
+    let history = pair.server_world().resource::<ClientBehaviorHistory>();
+    let client_history = history.get(client_id).unwrap();
+    assert!(client_history.inputs().len() > 0);
+    assert!(client_history
+        .updates()
+        .all(|record| record.kind == ComponentKind::of::<Component1>()));
+    assert_eq!(
+        client_history
+            .values::<Component1>()
+            .last()
+            .map(|record| record.value.0),
+        Some(20.0)
+    );
+    assert!(history.memory_usage() > 0);
+
+    // teleport
+    pair.client_world_mut(0)
+        .get_mut::<Component1>(entity)
+        .unwrap()
+        .0 = 1000.0;
+    pair.frame_steps(10);
+    let suspicions = suspicions(&pair);
+    assert!(!suspicions.is_empty());
+    assert!(suspicions
+        .iter()
+        .all(|event| event.client_id == client_id && event.rule == "max_displacement"));
+    // the entity moved by 980 units in about 10 ticks
+    assert!(suspicions[0].score > 10.0, "{suspicions:?}");
+}
+
+/// The history of a client is removed when it disconnects
+#[test]
+fn test_disconnect() {
+    let mut pair = build_pair();
+    let client_id = pair.client_id(0);
+    pair.frame_steps(5);
+    assert!(pair
+        .server_world()
+        .resource::<ClientBehaviorHistory>()
+        .get(client_id)
+        .is_some());
+
+    pair.server_world_mut()
+        .resource_mut::<server::ConnectionManager>()
+        .disconnect_client(client_id, None)
+        .unwrap();
+    pair.frame_steps(5);
+    let history = pair.server_world().resource::<ClientBehaviorHistory>();
+    assert!(history.get(client_id).is_none());
+    assert_eq!(history.memory_usage(), 0);
+}
@@ -0,0 +1,108 @@
+//! Tests of the channel settings that the server overrides for a single client
+use bevy::prelude::*;
+use bevy::utils::Duration;
+
+use crate::prelude::client::{InterpolationConfig, PredictionConfig, SyncConfig};
+use crate::prelude::server::{AppChannelSettingsExt, ConnectionManager};
+use crate::prelude::*;
+use crate::tests::multi_stepper::{MultiBevyStepper, TEST_CLIENT_ID_1, TEST_CLIENT_ID_2};
+use crate::tests::protocol::*;
+
+const CLIENT_1: ClientId = ClientId::Netcode(TEST_CLIENT_ID_1);
+const CLIENT_2: ClientId = ClientId::Netcode(TEST_CLIENT_ID_2);
+
+/// Resend the unacked reliable messages after a fixed delay, whatever the RTT
+fn resend_after(delay: Duration) -> ChannelSettingsOverride {
+    ChannelSettingsOverride {
+        rtt_resend_factor: Some(0.0),
+        rtt_resend_min_delay: Some(delay),
+        ..default()
+    }
+}
+
+fn setup() -> MultiBevyStepper {
+    let frame_duration = Duration::from_millis(10);
+    let shared_config = SharedConfig {
+        tick: TickConfig::new(frame_duration),
+        ..default()
+    };
+    let mut stepper = MultiBevyStepper::new(
+        shared_config,
+        SyncConfig::default().speedup_factor(1.0),
+        PredictionConfig::default(),
+        InterpolationConfig::default(),
+        frame_duration,
+    );
+    // the first client gets its overrides when it connects
+    stepper
+        .server_app
+        .set_channel_settings_override_hook(|client_id| {
+            if client_id == CLIENT_1 {
+                vec![(
+                    ChannelKind::of::<Channel3>(),
+                    resend_after(Duration::from_millis(25)),
+                )]
+            } else {
+                vec![]
+            }
+        });
+    stepper.init();
+    stepper
+}
+
+fn messages_resent(stepper: &MultiBevyStepper, client_id: ClientId) -> u64 {
+    stepper
+        .server_app
+        .world()
+        .resource::<ConnectionManager>()
+        .channel_stats(client_id, ChannelKind::of::<Channel3>())
+        .unwrap()
+        .messages_resent
+}
+
+#[test]
+fn test_channel_settings_override_resends() {
+    let mut stepper = setup();
+    let kind = ChannelKind::of::<Channel3>();
+    let mut manager = stepper
+        .server_app
+        .world_mut()
+        .resource_mut::<ConnectionManager>();
+    assert_eq!(
+        manager.channel_settings_override(CLIENT_1, kind),
+        Some(&resend_after(Duration::from_millis(25)))
+    );
+    assert_eq!(manager.channel_settings_override(CLIENT_2, kind), None);
+
+    // the second client is overridden at runtime
+    manager
+        .set_channel_settings_override(CLIENT_2, kind, resend_after(Duration::from_millis(100)))
+        .unwrap();
+    manager
+        .send_message_to_target::<Channel3, _>(&Message1("hello".to_string()), NetworkTarget::All)
+        .unwrap();
+
+    // the clients don't update, so the message is never acked and the server keeps resending it
+    for _ in 0..20 {
+        stepper.advance_time(stepper.frame_duration);
+        stepper.server_app.update();
+    }
+    let resent_1 = messages_resent(&stepper, CLIENT_1);
+    let resent_2 = messages_resent(&stepper, CLIENT_2);
+    assert!(resent_2 >= 1, "the message was never resent to client 2");
+    // the message is resent about 4 times more often to the first client
+    assert!(
+        resent_1 >= 3 * resent_2,
+        "resent {resent_1} times to client 1 and {resent_2} times to client 2"
+    );
+
+    // removing the override restores the settings of the protocol
+    let mut manager = stepper
+        .server_app
+        .world_mut()
+        .resource_mut::<ConnectionManager>();
+    manager
+        .set_channel_settings_override(CLIENT_1, kind, ChannelSettingsOverride::default())
+        .unwrap();
+    assert_eq!(manager.channel_settings_override(CLIENT_1, kind), None);
+}
@@ -0,0 +1,285 @@
+//! Tests of the cleanup of the entities received from the server, for each [`CleanupCause`]
+use bevy::ecs::system::RunSystemOnce;
+use bevy::prelude::*;
+use bevy::utils::Duration;
+
+use crate::client::prediction::predicted_history::PredictionHistory;
+use crate::prelude::client::{
+    CleanupAction, CleanupCause, ClientCleanupPolicy, ClientCommands, ClientConfig, Confirmed,
+    ConfirmedHistory, ConnectionManager, EntityCleanupEvent, Interpolated, Predicted,
+};
+use crate::prelude::server::{Replicate, SyncTarget};
+use crate::prelude::*;
+use crate::tests::protocol::*;
+use crate::tests::stepper::{BevyStepper, Step};
+
+/// Component added by the user to the entities received from the server
+#[derive(Component, Debug, PartialEq)]
+struct LocalMarker;
+
+#[derive(Resource, Default)]
+struct CleanupEvents(Vec<EntityCleanupEvent>);
+
+fn record_cleanup_events(
+    mut recorded: ResMut<CleanupEvents>,
+    mut events: EventReader<EntityCleanupEvent>,
+) {
+    recorded.0.extend(events.read().cloned());
+}
+
+fn setup(policy: ClientCleanupPolicy) -> BevyStepper {
+    let frame_duration = Duration::from_millis(10);
+    let shared_config = SharedConfig {
+        tick: TickConfig::new(frame_duration),
+        ..default()
+    };
+    let client_config = ClientConfig {
+        cleanup: policy,
+        ..default()
+    };
+    let mut stepper = BevyStepper::new(shared_config, client_config, frame_duration);
+    stepper.client_app.init_resource::<CleanupEvents>();
+    stepper
+        .client_app
+        .add_systems(Update, record_cleanup_events);
+    stepper.init();
+    stepper
+}
+
+/// Entities spawned on the client for a replicated server entity
+struct ClientEntities {
+    confirmed: Entity,
+    predicted: Entity,
+    interpolated: Entity,
+}
+
+impl ClientEntities {
+    fn all(&self) -> [Entity; 3] {
+        [self.confirmed, self.predicted, self.interpolated]
+    }
+}
+
+/// Replicate an entity with prediction and interpolation, then add a [`LocalMarker`] to all the client entities
+fn spawn_replicated(stepper: &mut BevyStepper) -> (Entity, ClientEntities) {
+    let server_entity = stepper
+        .server_app
+        .world_mut()
+        .spawn((
+            Component1(1.0),
+            Replicate {
+                sync: SyncTarget {
+                    prediction: NetworkTarget::All,
+                    interpolation: NetworkTarget::All,
+                },
+                ..default()
+            },
+        ))
+        .id();
+    for _ in 0..10 {
+        stepper.frame_step();
+    }
+    let confirmed = *stepper
+        .client_app
+        .world()
+        .resource::<ConnectionManager>()
+        .replication_receiver
+        .remote_entity_map
+        .get_local(server_entity)
+        .expect("entity was not replicated to client");
+    let confirmed_component = stepper
+        .client_app
+        .world()
+        .get::<Confirmed>(confirmed)
+        .expect("Confirmed component missing");
+    let entities = ClientEntities {
+        confirmed,
+        predicted: confirmed_component
+            .predicted
+            .expect("the predicted entity was not spawned"),
+        interpolated: confirmed_component
+            .interpolated
+            .expect("the interpolated entity was not spawned"),
+    };
+    for entity in entities.all() {
+        stepper
+            .client_app
+            .world_mut()
+            .entity_mut(entity)
+            .insert(LocalMarker);
+    }
+    (server_entity, entities)
+}
+
+/// Check that the entities only kept their local components
+fn assert_stripped(stepper: &BevyStepper, entities: &ClientEntities) {
+    let world = stepper.client_app.world();
+    for entity in entities.all() {
+        let entity_ref = world
+            .get_entity(entity)
+            .expect("the entity should not be despawned");
+        assert!(entity_ref.contains::<LocalMarker>());
+        assert!(!entity_ref.contains::<Component1>());
+        assert!(!entity_ref.contains::<Replicated>());
+        assert!(!entity_ref.contains::<Confirmed>());
+        assert!(!entity_ref.contains::<Predicted>());
+        assert!(!entity_ref.contains::<Interpolated>());
+    }
+    assert!(world
+        .get::<PredictionHistory<Component1>>(entities.predicted)
+        .is_none());
+    assert!(world
+        .get::<ConfirmedHistory<Component1>>(entities.interpolated)
+        .is_none());
+}
+
+fn assert_despawned(stepper: &BevyStepper, entities: &ClientEntities) {
+    for entity in entities.all() {
+        assert!(stepper.client_app.world().get_entity(entity).is_none());
+    }
+}
+
+fn assert_cleanup_event(
+    stepper: &BevyStepper,
+    entities: &ClientEntities,
+    cause: CleanupCause,
+    action: CleanupAction,
+) {
+    assert_eq!(
+        stepper.client_app.world().resource::<CleanupEvents>().0,
+        vec![EntityCleanupEvent {
+            entity: entities.confirmed,
+            predicted: Some(entities.predicted),
+            interpolated: Some(entities.interpolated),
+            cause,
+            action,
+        }]
+    );
+}
+
+fn remove_client_from_target(stepper: &mut BevyStepper, server_entity: Entity) {
+    stepper
+        .server_app
+        .world_mut()
+        .get_mut::<ReplicationTarget>(server_entity)
+        .unwrap()
+        .target = NetworkTarget::None;
+}
+
+#[test]
+fn test_strip_on_interest_loss() {
+    let mut stepper = setup(ClientCleanupPolicy::all(
+        CleanupAction::StripNetworkedComponents,
+    ));
+    let (server_entity, entities) = spawn_replicated(&mut stepper);
+
+    remove_client_from_target(&mut stepper, server_entity);
+    for _ in 0..10 {
+        stepper.frame_step();
+    }
+    assert_stripped(&stepper, &entities);
+    assert_cleanup_event(
+        &stepper,
+        &entities,
+        CleanupCause::InterestLoss,
+        CleanupAction::StripNetworkedComponents,
+    );
+}
+
+#[test]
+fn test_strip_on_server_despawn() {
+    let mut stepper = setup(ClientCleanupPolicy::all(
+        CleanupAction::StripNetworkedComponents,
+    ));
+    let (server_entity, entities) = spawn_replicated(&mut stepper);
+
+    stepper.server_app.world_mut().despawn(server_entity);
+    for _ in 0..10 {
+        stepper.frame_step();
+    }
+    assert_stripped(&stepper, &entities);
+    assert_cleanup_event(
+        &stepper,
+        &entities,
+        CleanupCause::ServerDespawn,
+        CleanupAction::StripNetworkedComponents,
+    );
+}
+
+#[test]
+fn test_strip_on_disconnect() {
+    let mut stepper = setup(ClientCleanupPolicy::all(
+        CleanupAction::StripNetworkedComponents,
+    ));
+    let (_, entities) = spawn_replicated(&mut stepper);
+
+    stepper
+        .client_app
+        .world_mut()
+        .run_system_once(|mut commands: Commands| commands.disconnect_client());
+    for _ in 0..10 {
+        stepper.frame_step();
+    }
+    assert_stripped(&stepper, &entities);
+    assert_cleanup_event(
+        &stepper,
+        &entities,
+        CleanupCause::Disconnect,
+        CleanupAction::StripNetworkedComponents,
+    );
+}
+
+#[test]
+fn test_strip_on_reconnect() {
+    let mut stepper = setup(ClientCleanupPolicy::all(
+        CleanupAction::StripNetworkedComponents,
+    ));
+    let (_, entities) = spawn_replicated(&mut stepper);
+
+    // start a new connection without disconnecting first
+    stepper
+        .client_app
+        .world_mut()
+        .run_system_once(|mut commands: Commands| commands.connect_client());
+    stepper.frame_step();
+    assert_stripped(&stepper, &entities);
+    assert_cleanup_event(
+        &stepper,
+        &entities,
+        CleanupCause::Reconnect,
+        CleanupAction::StripNetworkedComponents,
+    );
+}
+
+/// Each cause uses its own action
+#[test]
+fn test_action_per_cause() {
+    let mut stepper = setup(ClientCleanupPolicy::default().with_action(
+        CleanupCause::InterestLoss,
+        CleanupAction::StripNetworkedComponents,
+    ));
+    let (server_entity, entities) = spawn_replicated(&mut stepper);
+    remove_client_from_target(&mut stepper, server_entity);
+    for _ in 0..10 {
+        stepper.frame_step();
+    }
+    assert_stripped(&stepper, &entities);
+
+    stepper
+        .client_app
+        .world_mut()
+        .resource_mut::<CleanupEvents>()
+        .0
+        .clear();
+    let (server_entity, entities) = spawn_replicated(&mut stepper);
+    stepper.server_app.world_mut().despawn(server_entity);
+    for _ in 0..10 {
+        stepper.frame_step();
+    }
+    assert_despawned(&stepper, &entities);
+    assert_cleanup_event(
+        &stepper,
+        &entities,
+        CleanupCause::ServerDespawn,
+        CleanupAction::DespawnEntities,
+    );
+}
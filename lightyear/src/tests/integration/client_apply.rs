@@ -0,0 +1,135 @@
+//! Tests of the client-side apply functions, that store the replicated data instead of inserting components
+use bevy::prelude::*;
+use bevy::utils::Duration;
+
+use crate::prelude::client::{ComponentInsertEvent, ComponentUpdateEvent, MessageEvent};
+use crate::prelude::server::{ConnectionManager, Replicate};
+use crate::prelude::*;
+use crate::tests::protocol::*;
+use crate::tests::stepper::{BevyStepper, Step};
+
+/// Values stored by the apply functions
+#[derive(Resource, Default)]
+struct Applied {
+    components: Vec<(Entity, f32, Tick)>,
+    messages: Vec<String>,
+}
+
+/// Events that should never be emitted for the data handled by the apply functions
+#[derive(Resource, Default)]
+struct EmittedEvents(usize);
+
+fn apply_component1(world: &mut World, entity: Entity, value: Component1, tick: Tick) {
+    world
+        .resource_mut::<Applied>()
+        .components
+        .push((entity, value.0, tick));
+}
+
+fn apply_message1(world: &mut World, message: Message1, _tick: Tick) {
+    world.resource_mut::<Applied>().messages.push(message.0);
+}
+
+fn record_events(
+    mut emitted: ResMut<EmittedEvents>,
+    mut inserts: EventReader<ComponentInsertEvent<Component1>>,
+    mut updates: EventReader<ComponentUpdateEvent<Component1>>,
+    mut messages: EventReader<MessageEvent<Message1>>,
+) {
+    emitted.0 += inserts.read().count() + updates.read().count() + messages.read().count();
+}
+
+fn client_component1_count(stepper: &mut BevyStepper) -> usize {
+    stepper
+        .client_app
+        .world_mut()
+        .query_filtered::<(), With<Component1>>()
+        .iter(stepper.client_app.world())
+        .count()
+}
+
+/// The values received from the server (including the ones of the join snapshot) are passed to the
+/// apply functions, and the component is never inserted
+#[test]
+fn test_client_apply() {
+    let frame_duration = Duration::from_millis(10);
+    let shared_config = SharedConfig {
+        tick: TickConfig::new(frame_duration),
+        ..default()
+    };
+    let mut stepper = BevyStepper::new(
+        shared_config,
+        client::ClientConfig::default(),
+        frame_duration,
+    );
+    stepper.client_app.init_resource::<Applied>();
+    stepper.client_app.init_resource::<EmittedEvents>();
+    stepper.client_app.add_systems(Update, record_events);
+    stepper
+        .client_app
+        .add_client_apply_fn::<Component1>(apply_component1);
+    stepper
+        .client_app
+        .register_message::<Message1>(ChannelDirection::Bidirectional)
+        .add_client_apply(apply_message1);
+
+    // the entity is part of the join snapshot
+    let server_entity = stepper
+        .server_app
+        .world_mut()
+        .spawn((Component1(1.0), Replicate::default()))
+        .id();
+    stepper.build();
+    stepper.start();
+    for _ in 0..10 {
+        stepper.frame_step();
+        assert_eq!(client_component1_count(&mut stepper), 0);
+    }
+
+    let client_entity = *stepper
+        .client_app
+        .world()
+        .resource::<client::ConnectionManager>()
+        .replication_receiver
+        .remote_entity_map
+        .get_local(server_entity)
+        .unwrap();
+    assert_eq!(
+        stepper
+            .client_app
+            .world()
+            .resource::<Applied>()
+            .components
+            .iter()
+            .map(|(entity, value, _)| (*entity, *value))
+            .collect::<Vec<_>>(),
+        vec![(client_entity, 1.0)]
+    );
+
+    // the updates are passed to the apply function, with the tick of the server
+    stepper
+        .server_app
+        .world_mut()
+        .get_mut::<Component1>(server_entity)
+        .unwrap()
+        .0 = 2.0;
+    stepper.frame_step();
+    let server_tick = stepper.server_tick();
+    stepper
+        .server_app
+        .world_mut()
+        .resource_mut::<ConnectionManager>()
+        .send_message_to_target::<Channel1, _>(&Message1("hello".to_string()), NetworkTarget::All)
+        .unwrap();
+    for _ in 0..10 {
+        stepper.frame_step();
+        assert_eq!(client_component1_count(&mut stepper), 0);
+    }
+
+    let client_world = stepper.client_app.world();
+    let applied = client_world.resource::<Applied>();
+    assert_eq!(applied.components.len(), 2);
+    assert_eq!(applied.components[1], (client_entity, 2.0, server_tick));
+    assert_eq!(applied.messages, vec!["hello".to_string()]);
+    assert_eq!(client_world.resource::<EmittedEvents>().0, 0);
+}
@@ -0,0 +1,94 @@
+//! Tests of the conditioner applied by the server to the packets of a single client
+use bevy::diagnostic::DiagnosticsStore;
+use bevy::prelude::*;
+use bevy::utils::Duration;
+
+use crate::prelude::*;
+use crate::server::diagnostics::ServerDiagnosticsPlugin;
+use crate::tests::protocol::*;
+
+fn rtt(pair: &LightyearTestPair, index: usize) -> Duration {
+    pair.server_world()
+        .resource::<server::ConnectionManager>()
+        .connection(pair.client_id(index))
+        .unwrap()
+        .rtt()
+}
+
+fn conditioner_latency(pair: &LightyearTestPair, index: usize) -> Option<f64> {
+    pair.server_world()
+        .resource::<DiagnosticsStore>()
+        .get(&ServerDiagnosticsPlugin::conditioner_latency(
+            pair.client_id(index),
+        ))
+        .unwrap()
+        .value()
+}
+
+fn set_conditioner(pair: &mut LightyearTestPair, index: usize, latency_ms: Option<u64>) {
+    let client_id = pair.client_id(index);
+    let config = latency_ms.map(|latency| {
+        LinkConditionerConfig::new(Duration::from_millis(latency), Duration::ZERO, 0.0)
+    });
+    pair.server_world_mut()
+        .resource_mut::<server::ConnectionManager>()
+        .set_conditioner(client_id, config)
+        .unwrap();
+}
+
+/// A client with 50ms of added latency in each direction has an RTT about 100ms higher than a client
+/// connected to the same server without conditioner
+#[test]
+fn test_conditioned_client_rtt() {
+    let mut pair = LightyearTestPair::builder()
+        .protocol(ProtocolPlugin)
+        .clients(2)
+        .build();
+    pair.frame_steps(50);
+    let initial_rtt = rtt(&pair, 0);
+    assert!(initial_rtt < Duration::from_millis(50), "{initial_rtt:?}");
+
+    set_conditioner(&mut pair, 0, Some(50));
+    pair.frame_steps(200);
+    let conditioned_rtt = rtt(&pair, 0);
+    let unconditioned_rtt = rtt(&pair, 1);
+    let difference = conditioned_rtt.saturating_sub(unconditioned_rtt);
+    assert!(
+        (Duration::from_millis(80)..Duration::from_millis(120)).contains(&difference),
+        "{conditioned_rtt:?} {unconditioned_rtt:?}"
+    );
+    assert!(unconditioned_rtt < Duration::from_millis(50));
+    // the conditioner is visible in the diagnostics of the client only
+    assert_eq!(
+        pair.server_world()
+            .resource::<server::ConnectionManager>()
+            .conditioner(pair.client_id(0))
+            .map(|config| config.incoming_latency),
+        Some(Duration::from_millis(50))
+    );
+    assert_eq!(conditioner_latency(&pair, 0), Some(50.0));
+    assert_eq!(conditioner_latency(&pair, 1), Some(0.0));
+
+    // the conditioner can be replaced and removed while the client is connected
+    set_conditioner(&mut pair, 0, Some(10));
+    pair.frame_steps(200);
+    let difference = rtt(&pair, 0).saturating_sub(rtt(&pair, 1));
+    assert!(
+        (Duration::from_millis(10)..Duration::from_millis(30)).contains(&difference),
+        "{difference:?}"
+    );
+    set_conditioner(&mut pair, 0, None);
+    pair.frame_steps(200);
+    assert!(
+        rtt(&pair, 0) < Duration::from_millis(50),
+        "{:?}",
+        rtt(&pair, 0)
+    );
+    assert_eq!(conditioner_latency(&pair, 0), Some(0.0));
+    assert_eq!(
+        pair.client_world(0)
+            .resource::<State<client::NetworkingState>>()
+            .get(),
+        &client::NetworkingState::Connected
+    );
+}
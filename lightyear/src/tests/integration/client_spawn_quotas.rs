@@ -0,0 +1,162 @@
+//! Tests of the server-side limits on the entities spawned by the clients with client-authoritative replication
+use bevy::diagnostic::DiagnosticsStore;
+use bevy::prelude::*;
+
+use crate::prelude::server::{ReplicationLimitExceededEvent, ServerConfig};
+use crate::prelude::*;
+use crate::server::diagnostics::ServerDiagnosticsPlugin;
+use crate::tests::protocol::*;
+
+const MAX_ENTITIES: usize = 100;
+
+#[derive(Resource, Default)]
+struct LimitEvents(Vec<ReplicationLimitExceededEvent>);
+
+fn record_limit_events(
+    mut recorded: ResMut<LimitEvents>,
+    mut events: EventReader<ReplicationLimitExceededEvent>,
+) {
+    recorded.0.extend(events.read().cloned());
+}
+
+fn build_pair(limits: ReplicationLimits) -> LightyearTestPair {
+    let mut pair = LightyearTestPair::builder()
+        .protocol(ProtocolPlugin)
+        .server_config(ServerConfig {
+            replication: ReplicationConfig {
+                limits,
+                ..default()
+            },
+            ..default()
+        })
+        .build_disconnected();
+    pair.server_app
+        .init_resource::<LimitEvents>()
+        .add_systems(Update, record_limit_events);
+    pair.connect();
+    pair
+}
+
+fn spawn_flood(pair: &mut LightyearTestPair, count: usize) -> Vec<Entity> {
+    (0..count)
+        .map(|i| {
+            pair.client_world_mut(0)
+                .spawn((client::Replicate::default(), Component1(i as f32)))
+                .id()
+        })
+        .collect()
+}
+
+fn server_entities(pair: &mut LightyearTestPair) -> usize {
+    pair.server_world_mut()
+        .query_filtered::<(), With<Replicated>>()
+        .iter(pair.server_world())
+        .count()
+}
+
+fn rejected(pair: &LightyearTestPair) -> u32 {
+    pair.server_world()
+        .resource::<LimitEvents>()
+        .0
+        .iter()
+        .map(|event| event.rejected)
+        .sum()
+}
+
+/// A client that spawns 10,000 entities only gets its quota of entities spawned on the server,
+/// and the quota is freed when the entities are despawned
+#[test]
+fn test_client_entity_quota() {
+    let mut pair = build_pair(ReplicationLimits::default().with_max_entities(MAX_ENTITIES));
+    let client_id = pair.client_id(0);
+    let entities = spawn_flood(&mut pair, 10_000);
+    pair.frame_steps(20);
+
+    assert_eq!(server_entities(&mut pair), MAX_ENTITIES);
+    assert_eq!(rejected(&pair), 10_000 - MAX_ENTITIES as u32);
+    let events = &pair.server_world().resource::<LimitEvents>().0;
+    assert!(events
+        .iter()
+        .all(|event| event.client_id == client_id && event.limit == ReplicationLimit::Entities));
+    let connection_manager = pair.server_world().resource::<server::ConnectionManager>();
+    assert_eq!(
+        connection_manager.client_replicated_entities(client_id),
+        Some(MAX_ENTITIES)
+    );
+    assert_eq!(
+        connection_manager
+            .replication_limit_stats(client_id)
+            .unwrap()
+            .rejected_spawns,
+        10_000 - MAX_ENTITIES as u32
+    );
+
+    // the quota is visible in the per-client diagnostics
+    pair.frame_steps(50);
+    let store = pair.server_world().resource::<DiagnosticsStore>();
+    let value = |path| store.get(&path).unwrap().value().unwrap();
+    assert_eq!(
+        value(ServerDiagnosticsPlugin::client_replicated_entities(
+            client_id
+        )),
+        MAX_ENTITIES as f64
+    );
+    assert_eq!(
+        value(ServerDiagnosticsPlugin::client_rejected_spawns(client_id)),
+        (10_000 - MAX_ENTITIES) as f64
+    );
+
+    // the despawns free the quota
+    for entity in entities {
+        pair.client_world_mut(0).despawn(entity);
+    }
+    pair.frame_steps(20);
+    assert_eq!(server_entities(&mut pair), 0);
+    assert_eq!(
+        pair.server_world()
+            .resource::<server::ConnectionManager>()
+            .client_replicated_entities(client_id),
+        Some(0)
+    );
+    spawn_flood(&mut pair, 10);
+    pair.frame_steps(20);
+    assert_eq!(server_entities(&mut pair), 10);
+    assert!(pair
+        .server_world()
+        .resource::<server::ConnectionManager>()
+        .connection(client_id)
+        .is_ok());
+}
+
+/// A client that spawns entities faster than allowed is disconnected when the action is to disconnect
+#[test]
+fn test_client_spawn_rate_disconnect() {
+    let mut pair = build_pair(
+        ReplicationLimits::default()
+            .with_max_spawns_per_second(20)
+            .with_disconnect(true),
+    );
+    let client_id = pair.client_id(0);
+    spawn_flood(&mut pair, 50);
+    pair.frame_steps(20);
+
+    assert!(server_entities(&mut pair) <= 20);
+    let events = &pair.server_world().resource::<LimitEvents>().0;
+    assert!(!events.is_empty());
+    assert!(events
+        .iter()
+        .all(|event| event.client_id == client_id
+            && event.limit == ReplicationLimit::SpawnsPerSecond));
+    assert!(pair
+        .server_world()
+        .resource::<server::ConnectionManager>()
+        .connection(client_id)
+        .is_err());
+    pair.frame_steps(10);
+    assert_eq!(
+        pair.client_world(0)
+            .resource::<State<client::NetworkingState>>()
+            .get(),
+        &client::NetworkingState::Disconnected
+    );
+}
@@ -0,0 +1,138 @@
+//! Tests of the update messages that are serialized once and shared between the clients that need them
+use bevy::prelude::*;
+use bevy::utils::Duration;
+
+use crate::prelude::client::{ClientConfig, SyncConfig};
+use crate::prelude::server::{ConnectionManager, Replicate, ServerConfig};
+use crate::prelude::*;
+use crate::tests::protocol::*;
+
+#[derive(Resource)]
+struct Moving(bool);
+
+fn update_components(moving: Res<Moving>, mut query: Query<&mut Component1>) {
+    if moving.0 {
+        for mut c in query.iter_mut() {
+            c.0 += 1.0;
+        }
+    }
+}
+
+/// Both clients see the entities of the same cell, and the first client also sees an entity
+/// that is only replicated to it
+fn setup(coalesce_updates: bool) -> (LightyearTestPair, Vec<Entity>, Entity) {
+    let mut pair = LightyearTestPair::builder()
+        .protocol(ProtocolPlugin)
+        .tick_duration(Duration::from_millis(10))
+        .clients(2)
+        .client_config(ClientConfig {
+            sync: SyncConfig::default().speedup_factor(1.0),
+            ..default()
+        })
+        .server_config(ServerConfig {
+            replication: ReplicationConfig {
+                coalesce_updates,
+                ..default()
+            },
+            ..default()
+        })
+        .build();
+    pair.server_app
+        .insert_resource(Moving(true))
+        .add_systems(FixedUpdate, update_components);
+
+    let cell: Vec<Entity> = (0..3)
+        .map(|i| {
+            pair.server_world_mut()
+                .spawn((
+                    Component1(i as f32 * 100.0),
+                    Replicate {
+                        group: ReplicationGroup::new_id(1),
+                        ..default()
+                    },
+                ))
+                .id()
+        })
+        .collect();
+    let first_client = pair.client_id(0);
+    let personal = pair
+        .server_world_mut()
+        .spawn((
+            Component1(-100.0),
+            Replicate {
+                target: ReplicationTarget {
+                    target: NetworkTarget::Single(first_client),
+                },
+                ..default()
+            },
+        ))
+        .id();
+    pair.frame_steps(50);
+    // stop the updates so that the clients catch up with the server
+    pair.server_app.insert_resource(Moving(false));
+    pair.frame_steps(10);
+    (pair, cell, personal)
+}
+
+fn coalesced_bytes(pair: &LightyearTestPair) -> usize {
+    let manager = pair.server_world().resource::<ConnectionManager>();
+    (0..2)
+        .map(|index| {
+            let client_id = pair.client_id(index);
+            manager
+                .connection(client_id)
+                .unwrap()
+                .replication_sender
+                .stats
+                .coalesced_bytes
+        })
+        .sum()
+}
+
+fn client_value(client_world: &World, server_entity: Entity) -> Option<Component1> {
+    let client_entity = client_world
+        .resource::<client::ConnectionManager>()
+        .replication_receiver
+        .remote_entity_map
+        .get_local(server_entity)?;
+    client_world.get::<Component1>(*client_entity).cloned()
+}
+
+/// The clients receive the same values whether the update messages are shared or not
+#[test]
+fn test_coalesced_updates() {
+    let (pair, cell, personal) = setup(true);
+    assert!(coalesced_bytes(&pair) > 0);
+    let (baseline, baseline_cell, baseline_personal) = setup(false);
+    assert_eq!(coalesced_bytes(&baseline), 0);
+
+    for (pair, cell) in [(&pair, cell), (&baseline, baseline_cell)] {
+        for server_entity in cell {
+            let server_value = pair
+                .server_world()
+                .get::<Component1>(server_entity)
+                .cloned();
+            assert!(server_value.is_some());
+            for index in 0..2 {
+                assert_eq!(
+                    client_value(pair.client_world(index), server_entity),
+                    server_value
+                );
+            }
+        }
+    }
+
+    // the per-client entity is only sent to the first client
+    assert_eq!(
+        client_value(pair.client_world(0), personal),
+        pair.server_world().get::<Component1>(personal).cloned()
+    );
+    assert_eq!(client_value(pair.client_world(1), personal), None);
+    assert_eq!(
+        client_value(baseline.client_world(0), baseline_personal),
+        baseline
+            .server_world()
+            .get::<Component1>(baseline_personal)
+            .cloned()
+    );
+}
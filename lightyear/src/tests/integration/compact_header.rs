@@ -0,0 +1,116 @@
+use bevy::prelude::*;
+use bevy::utils::Duration;
+
+use crate::prelude::client::{InputManager, InputSystemSet};
+use crate::prelude::server::{InputEvent, Replicate};
+use crate::prelude::*;
+use crate::tests::protocol::*;
+use crate::tests::stepper::{BevyStepper, Step, TEST_CLIENT_ID};
+
+#[derive(Resource, Default)]
+struct Counter(usize);
+
+fn press_input(mut input_manager: ResMut<InputManager<MyInput>>, tick_manager: Res<TickManager>) {
+    input_manager.add_input(MyInput(0), tick_manager.tick());
+}
+
+fn increment(mut query: Query<&mut Component1>, mut ev: EventReader<InputEvent<MyInput>>) {
+    for _ in ev.read() {
+        for mut c in query.iter_mut() {
+            c.0 += 1.0;
+        }
+    }
+}
+
+fn count_messages(
+    mut counter: ResMut<Counter>,
+    mut events: EventReader<crate::client::events::MessageEvent<Message1>>,
+) {
+    counter.0 += events.read().count();
+}
+
+/// This test checks that replication, inputs and reliable messages still work when the packets
+/// use compact headers, after the 8-bit packet ids have wrapped around
+#[test]
+fn test_compact_header() {
+    let tick_duration = Duration::from_millis(10);
+    let shared_config = SharedConfig {
+        tick: TickConfig::new(tick_duration),
+        ..default()
+    };
+    let mut stepper = BevyStepper::new(
+        shared_config,
+        client::ClientConfig::default(),
+        tick_duration,
+    );
+    stepper
+        .client_app
+        .set_packet_header_mode(PacketHeaderMode::Compact);
+    stepper
+        .server_app
+        .set_packet_header_mode(PacketHeaderMode::Compact);
+    stepper.init();
+    assert!(stepper
+        .client_app
+        .world()
+        .resource::<client::ConnectionManager>()
+        .is_synced());
+
+    stepper.client_app.add_systems(
+        FixedPreUpdate,
+        press_input.in_set(InputSystemSet::BufferInputs),
+    );
+    stepper.server_app.add_systems(FixedUpdate, increment);
+    stepper.client_app.init_resource::<Counter>();
+    stepper.client_app.add_systems(Update, count_messages);
+
+    let server_entity = stepper
+        .server_app
+        .world_mut()
+        .spawn((Component1(0.0), Replicate::default()))
+        .id();
+
+    // send more than 256 packets so that the compact packet ids wrap around
+    for _ in 0..300 {
+        stepper
+            .server_app
+            .world_mut()
+            .resource_mut::<server::ConnectionManager>()
+            .send_message::<Channel3, Message1>(
+                ClientId::Netcode(TEST_CLIENT_ID),
+                &Message1("a".to_string()),
+            )
+            .unwrap();
+        stepper.frame_step();
+    }
+    for _ in 0..10 {
+        stepper.frame_step();
+    }
+
+    // all the reliable messages were received
+    assert_eq!(stepper.client_app.world().resource::<Counter>().0, 300);
+
+    // the inputs were received by the server, and the updates were replicated back to the client
+    let server_value = stepper
+        .server_app
+        .world()
+        .get::<Component1>(server_entity)
+        .unwrap()
+        .0;
+    let client_entity = *stepper
+        .client_app
+        .world()
+        .resource::<client::ConnectionManager>()
+        .replication_receiver
+        .remote_entity_map
+        .get_local(server_entity)
+        .unwrap();
+    let client_value = stepper
+        .client_app
+        .world()
+        .get::<Component1>(client_entity)
+        .unwrap()
+        .0;
+    assert!(client_value > 0.0);
+    assert!(client_value <= server_value);
+}
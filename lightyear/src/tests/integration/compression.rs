@@ -0,0 +1,103 @@
+//! Tests of the compression negotiated between a client and a server with different compression configs
+use bevy::prelude::*;
+use bevy::utils::Duration;
+
+use crate::prelude::client::{ClientConfig, ConnectionManager};
+use crate::prelude::server::{Replicate, ServerConfig};
+use crate::prelude::*;
+use crate::tests::protocol::*;
+use crate::tests::stepper::{BevyStepper, Step};
+
+fn setup(client: CompressionConfig, server: CompressionConfig) -> BevyStepper {
+    let frame_duration = Duration::from_millis(10);
+    let shared_config = SharedConfig {
+        tick: TickConfig::new(frame_duration),
+        ..default()
+    };
+    let mut stepper = BevyStepper::new(shared_config, ClientConfig::default(), frame_duration);
+    {
+        let mut client_config = stepper
+            .client_app
+            .world_mut()
+            .resource_mut::<ClientConfig>();
+        let client::NetConfig::Netcode { io, .. } = &mut client_config.net else {
+            panic!("the client should use netcode");
+        };
+        io.compression = client;
+    }
+    {
+        let mut server_config = stepper
+            .server_app
+            .world_mut()
+            .resource_mut::<ServerConfig>();
+        #[allow(irrefutable_let_patterns)]
+        let server::NetConfig::Netcode { io, .. } = &mut server_config.net[0] else {
+            panic!("the server should use netcode");
+        };
+        io.compression = server;
+    }
+    stepper.init();
+    stepper
+}
+
+/// Check that the client is connected and that the replication works
+fn check_replication(stepper: &mut BevyStepper) {
+    assert!(stepper
+        .client_app
+        .world()
+        .resource::<ConnectionManager>()
+        .is_synced());
+    let server_entity = stepper
+        .server_app
+        .world_mut()
+        .spawn((Component1(1.0), Replicate::default()))
+        .id();
+    for _ in 0..10 {
+        stepper.frame_step();
+    }
+    let client_entity = *stepper
+        .client_app
+        .world()
+        .resource::<ConnectionManager>()
+        .replication_receiver
+        .remote_entity_map
+        .get_local(server_entity)
+        .unwrap();
+    assert_eq!(
+        stepper.client_app.world().get::<Component1>(client_entity),
+        Some(&Component1(1.0))
+    );
+}
+
+#[test]
+fn test_uncompressed() {
+    let mut stepper = setup(CompressionConfig::None, CompressionConfig::None);
+    check_replication(&mut stepper);
+}
+
+#[cfg(feature = "zstd")]
+#[test]
+fn test_compressed_server_uncompressed_client() {
+    let mut stepper = setup(
+        CompressionConfig::None,
+        CompressionConfig::Zstd { level: 3 },
+    );
+    check_replication(&mut stepper);
+}
+
+#[cfg(feature = "zstd")]
+#[test]
+fn test_uncompressed_server_compressed_client() {
+    let mut stepper = setup(
+        CompressionConfig::Zstd { level: 3 },
+        CompressionConfig::None,
+    );
+    check_replication(&mut stepper);
+}
+
+#[cfg(feature = "lz4")]
+#[test]
+fn test_lz4_server() {
+    let mut stepper = setup(CompressionConfig::None, CompressionConfig::Lz4);
+    check_replication(&mut stepper);
+}
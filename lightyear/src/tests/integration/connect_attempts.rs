@@ -0,0 +1,316 @@
+//! Tests related to the timeout, cancellation and retries of the connection attempts
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+
+use bevy::ecs::system::RunSystemOnce;
+use bevy::prelude::*;
+use bevy::utils::Duration;
+
+use crate::connection::client::NetClientDispatch;
+use crate::connection::netcode::ClientState;
+use crate::prelude::client::{
+    ClientCommands, ClientConfig, ClientConnection, ConnectAttempt, ConnectConfig, ConnectError,
+    ConnectRetryConfig, ConnectTokenProvider, ConnectionFailedEvent, NetClient, NetConfig,
+    NetworkingState,
+};
+use crate::prelude::server::ServerCommands;
+use crate::prelude::*;
+use crate::tests::stepper::{BevyStepper, Step};
+
+#[derive(Resource, Default)]
+struct ConnectionFailures(Vec<ConnectError>);
+
+fn record_failures(
+    mut failures: ResMut<ConnectionFailures>,
+    mut events: EventReader<ConnectionFailedEvent>,
+) {
+    failures
+        .0
+        .extend(events.read().map(|event| event.error.clone()));
+}
+
+fn setup(connect: ConnectConfig) -> BevyStepper {
+    let frame_duration = Duration::from_millis(10);
+    let shared_config = SharedConfig {
+        tick: TickConfig::new(frame_duration),
+        ..default()
+    };
+    let client_config = ClientConfig {
+        connect,
+        ..default()
+    };
+    let mut stepper = BevyStepper::new(shared_config, client_config, frame_duration);
+    stepper.client_app.init_resource::<ConnectionFailures>();
+    stepper.client_app.add_systems(Update, record_failures);
+    stepper.build();
+    stepper
+}
+
+fn start_server(stepper: &mut BevyStepper) {
+    stepper
+        .server_app
+        .world_mut()
+        .run_system_once(|mut commands: Commands| commands.start_server());
+}
+
+fn connect(stepper: &mut BevyStepper) {
+    stepper
+        .client_app
+        .world_mut()
+        .run_system_once(|mut commands: Commands| commands.connect_client());
+}
+
+fn cancel_connect(stepper: &mut BevyStepper) {
+    stepper
+        .client_app
+        .world_mut()
+        .run_system_once(|mut commands: Commands| commands.cancel_connect());
+}
+
+fn networking_state(stepper: &BevyStepper) -> NetworkingState {
+    *stepper
+        .client_app
+        .world()
+        .resource::<State<NetworkingState>>()
+        .get()
+}
+
+fn netcode_state(stepper: &BevyStepper) -> ClientState {
+    match &stepper
+        .client_app
+        .world()
+        .resource::<ClientConnection>()
+        .client
+    {
+        NetClientDispatch::Netcode(client) => client.client.state(),
+        _ => panic!("the client should use netcode"),
+    }
+}
+
+fn failures(stepper: &BevyStepper) -> Vec<ConnectError> {
+    stepper
+        .client_app
+        .world()
+        .resource::<ConnectionFailures>()
+        .0
+        .clone()
+}
+
+/// Cancel the connection attempt and check that the client cleanly goes back to `Disconnected`
+fn cancel_and_check(stepper: &mut BevyStepper) {
+    assert_eq!(networking_state(stepper), NetworkingState::Connecting);
+    cancel_connect(stepper);
+    stepper.frame_step();
+    assert_eq!(networking_state(stepper), NetworkingState::Disconnected);
+    assert_eq!(failures(stepper), vec![ConnectError::Cancelled]);
+    // the transport was torn down
+    assert!(stepper
+        .client_app
+        .world()
+        .resource::<ClientConnection>()
+        .io()
+        .is_none());
+
+    // the attempt is not retried, and the server does not keep the client
+    for _ in 0..20 {
+        stepper.frame_step();
+    }
+    assert_eq!(networking_state(stepper), NetworkingState::Disconnected);
+    assert_eq!(failures(stepper), vec![ConnectError::Cancelled]);
+    assert!(stepper
+        .server_app
+        .world()
+        .resource::<server::ConnectionManager>()
+        .connections
+        .is_empty());
+}
+
+#[test]
+fn test_cancel_connect_after_socket_bind() {
+    let mut stepper = setup(ConnectConfig::default().with_retry(ConnectRetryConfig {
+        attempts: 3,
+        backoff: Duration::from_millis(10),
+    }));
+    start_server(&mut stepper);
+    connect(&mut stepper);
+    // only update the client: the transport is created, but no packet was sent yet
+    stepper.advance_time(stepper.frame_duration);
+    stepper.client_app.update();
+    assert!(stepper
+        .client_app
+        .world()
+        .resource::<ClientConnection>()
+        .io()
+        .is_some());
+    assert_eq!(
+        netcode_state(&stepper),
+        ClientState::SendingConnectionRequest
+    );
+    cancel_and_check(&mut stepper);
+}
+
+#[test]
+fn test_cancel_connect_after_request_sent() {
+    let mut stepper = setup(ConnectConfig::default());
+    start_server(&mut stepper);
+    connect(&mut stepper);
+    // only update the client: the connection request is sent, but the server does not answer
+    for _ in 0..3 {
+        stepper.advance_time(stepper.frame_duration);
+        stepper.client_app.update();
+    }
+    assert_eq!(
+        netcode_state(&stepper),
+        ClientState::SendingConnectionRequest
+    );
+    cancel_and_check(&mut stepper);
+}
+
+#[test]
+fn test_cancel_connect_after_challenge_received() {
+    let mut stepper = setup(ConnectConfig::default());
+    start_server(&mut stepper);
+    connect(&mut stepper);
+    for _ in 0..10 {
+        stepper.frame_step();
+        if netcode_state(&stepper) == ClientState::SendingChallengeResponse {
+            break;
+        }
+    }
+    assert_eq!(
+        netcode_state(&stepper),
+        ClientState::SendingChallengeResponse
+    );
+    cancel_and_check(&mut stepper);
+}
+
+#[test]
+fn test_cancel_pending_retry() {
+    let mut stepper = setup(
+        ConnectConfig::default()
+            .with_timeout(Duration::from_millis(50))
+            .with_retry(ConnectRetryConfig {
+                attempts: 3,
+                backoff: Duration::from_secs(10),
+            }),
+    );
+    // the server is not running
+    connect(&mut stepper);
+    for _ in 0..10 {
+        stepper.frame_step();
+    }
+    assert!(stepper
+        .client_app
+        .world()
+        .resource::<ConnectAttempt>()
+        .is_retry_pending());
+
+    cancel_connect(&mut stepper);
+    stepper.frame_step();
+    assert!(!stepper
+        .client_app
+        .world()
+        .resource::<ConnectAttempt>()
+        .is_retry_pending());
+    assert_eq!(failures(&stepper), vec![ConnectError::Cancelled]);
+}
+
+#[test]
+fn test_connect_timeout() {
+    let timeout = Duration::from_millis(100);
+    let mut stepper = setup(ConnectConfig::default().with_timeout(timeout));
+    // the server is not running, so the handshake cannot complete
+    connect(&mut stepper);
+    for _ in 0..5 {
+        stepper.frame_step();
+    }
+    assert_eq!(networking_state(&stepper), NetworkingState::Connecting);
+    for _ in 0..10 {
+        stepper.frame_step();
+    }
+    assert_eq!(networking_state(&stepper), NetworkingState::Disconnected);
+    assert_eq!(failures(&stepper), vec![ConnectError::Timeout(timeout)]);
+}
+
+#[test]
+fn test_connect_retry_with_token_provider() {
+    // the timeout leaves enough time for the netcode handshake to complete once the server is running
+    let mut stepper = setup(
+        ConnectConfig::default()
+            .with_timeout(Duration::from_millis(200))
+            .with_retry(ConnectRetryConfig {
+                attempts: 3,
+                backoff: Duration::from_millis(20),
+            }),
+    );
+    let NetConfig::Netcode { auth, .. } = stepper
+        .client_app
+        .world()
+        .resource::<ClientConfig>()
+        .net
+        .clone()
+    else {
+        panic!("the client should use netcode");
+    };
+    let tokens = Arc::new(AtomicU32::new(0));
+    let provided_tokens = tokens.clone();
+    stepper
+        .client_app
+        .insert_resource(ConnectTokenProvider::new(move || {
+            provided_tokens.fetch_add(1, Ordering::Relaxed);
+            Some(auth.clone())
+        }));
+
+    // all the attempts fail while the server is not running
+    connect(&mut stepper);
+    for _ in 0..80 {
+        stepper.frame_step();
+    }
+    assert_eq!(
+        failures(&stepper),
+        vec![ConnectError::Timeout(Duration::from_millis(200))]
+    );
+    assert_eq!(
+        stepper
+            .client_app
+            .world()
+            .resource::<ConnectAttempt>()
+            .attempt(),
+        3
+    );
+    // each attempt asked for a new token
+    assert_eq!(tokens.load(Ordering::Relaxed), 3);
+
+    // a retry succeeds once the server is running
+    connect(&mut stepper);
+    for _ in 0..25 {
+        stepper.frame_step();
+        if stepper
+            .client_app
+            .world()
+            .resource::<ConnectAttempt>()
+            .is_retry_pending()
+        {
+            break;
+        }
+    }
+    assert!(stepper
+        .client_app
+        .world()
+        .resource::<ConnectAttempt>()
+        .is_retry_pending());
+    start_server(&mut stepper);
+    for _ in 0..50 {
+        stepper.frame_step();
+    }
+    assert_eq!(networking_state(&stepper), NetworkingState::Connected);
+    assert_eq!(
+        stepper
+            .client_app
+            .world()
+            .resource::<ConnectAttempt>()
+            .attempt(),
+        2
+    );
+    assert_eq!(failures(&stepper).len(), 1);
+}
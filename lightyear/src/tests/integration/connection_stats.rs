@@ -0,0 +1,92 @@
+//! Tests of the live statistics of the connections, that can be displayed in a network HUD
+use bevy::prelude::*;
+use bevy::utils::Duration;
+
+use crate::prelude::*;
+use crate::tests::protocol::*;
+
+/// The client sends a reliable message every frame
+fn send_messages(mut connection_manager: ResMut<client::ConnectionManager>) {
+    let _ = connection_manager.send_message::<Channel3, _>(&Message1("a".to_string()));
+}
+
+fn build_pair(incoming_loss: f32) -> LightyearTestPair {
+    let mut pair = LightyearTestPair::builder()
+        .protocol(ProtocolPlugin)
+        .tick_duration(Duration::from_millis(10))
+        .conditioner(LinkConditionerConfig {
+            incoming_latency: Duration::from_millis(30),
+            incoming_jitter: Duration::from_millis(0),
+            incoming_loss,
+            seed: None,
+        })
+        .build_disconnected();
+    pair.client_apps[0].add_systems(Update, send_messages);
+    pair.connect();
+    pair
+}
+
+fn client_stats(pair: &LightyearTestPair) -> ConnectionStats {
+    *pair
+        .client_world(0)
+        .resource::<client::ConnectionManager>()
+        .connection_stats()
+}
+
+fn server_stats(pair: &LightyearTestPair) -> ConnectionStats {
+    *pair
+        .server_world()
+        .resource::<server::ConnectionManager>()
+        .connection_stats(pair.client_id(0))
+        .unwrap()
+}
+
+/// Both sides measure the RTT and the bandwidth of the connection
+#[test]
+fn test_connection_stats() {
+    let mut pair = build_pair(0.0);
+    pair.frame_steps(200);
+    for stats in [client_stats(&pair), server_stats(&pair)] {
+        // 30ms of latency in each direction, plus up to one frame to send the packets
+        assert!(
+            (Duration::from_millis(55)..Duration::from_millis(90)).contains(&stats.rtt),
+            "{stats:?}"
+        );
+        assert!(stats.kbps_sent > 0.0, "{stats:?}");
+        assert!(stats.kbps_received > 0.0, "{stats:?}");
+        assert_eq!(stats.packet_loss, 0.0, "{stats:?}");
+        assert_eq!(stats.packets_resent, 0, "{stats:?}");
+    }
+    // the client sends more than what it receives, and both sides agree on the bandwidth
+    let (client, server) = (client_stats(&pair), server_stats(&pair));
+    assert!(client.kbps_sent > client.kbps_received, "{client:?}");
+    assert!(
+        (client.kbps_sent - server.kbps_received).abs() < 0.2 * client.kbps_sent,
+        "client: {client:?}, server: {server:?}"
+    );
+
+    // the RTT keeps being measured after the sync
+    let pongs = pair
+        .client_world(0)
+        .resource::<client::ConnectionManager>()
+        .ping_manager
+        .pongs_recv;
+    pair.frame_steps(50);
+    assert!(
+        pair.client_world(0)
+            .resource::<client::ConnectionManager>()
+            .ping_manager
+            .pongs_recv
+            > pongs
+    );
+}
+
+/// The lost packets are reflected in the packet loss and the resent reliable messages
+#[test]
+fn test_connection_stats_with_loss() {
+    let mut pair = build_pair(0.2);
+    pair.frame_steps(300);
+    let stats = client_stats(&pair);
+    assert!((5.0..40.0).contains(&stats.packet_loss), "{stats:?}");
+    assert!(stats.packets_resent > 0, "{stats:?}");
+}
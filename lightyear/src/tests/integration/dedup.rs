@@ -0,0 +1,76 @@
+//! Tests of the messages sent with a dedup key, that replace the queued messages with the same key
+use bevy::prelude::*;
+
+use crate::prelude::*;
+use crate::tests::protocol::*;
+
+#[derive(Resource, Default)]
+struct ReceivedMessages(Vec<Message1>);
+
+fn receive_messages(
+    mut received: ResMut<ReceivedMessages>,
+    mut events: EventReader<client::MessageEvent<Message1>>,
+) {
+    received
+        .0
+        .extend(events.read().map(|event| event.message().clone()));
+}
+
+/// The link is blocked: a single message can be in flight, and the client does not ack it.
+/// Only the last of a burst of messages with the same key is delivered once the link reopens.
+#[test]
+fn test_dedup_key_burst_on_blocked_link() {
+    let mut pair = LightyearTestPair::builder()
+        .protocol(ProtocolPlugin)
+        .build();
+    let client_id = pair.client_id(0);
+    let kind = ChannelKind::of::<Channel3>();
+    pair.client_apps[0]
+        .init_resource::<ReceivedMessages>()
+        .add_systems(Update, receive_messages);
+    let mut manager = pair
+        .server_world_mut()
+        .resource_mut::<server::ConnectionManager>();
+    manager
+        .set_channel_settings_override(
+            client_id,
+            kind,
+            ChannelSettingsOverride {
+                max_in_flight: Some(1),
+                ..default()
+            },
+        )
+        .unwrap();
+    manager
+        .send_message::<Channel3, _>(client_id, &Message1("blocker".to_string()))
+        .unwrap();
+    pair.advance_time(pair.frame_duration);
+    pair.server_app.update();
+
+    // only the server is updated, so the message in flight is not acked
+    for frame in 0..10 {
+        let mut manager = pair
+            .server_world_mut()
+            .resource_mut::<server::ConnectionManager>();
+        for i in frame * 10..(frame + 1) * 10 {
+            manager
+                .send_message_with_key::<Channel3, _>(client_id, &Message1(format!("v{i}")), 0)
+                .unwrap();
+        }
+        pair.advance_time(pair.frame_duration);
+        pair.server_app.update();
+    }
+    let stats = pair
+        .server_world()
+        .resource::<server::ConnectionManager>()
+        .channel_stats(client_id, kind)
+        .unwrap();
+    assert_eq!(stats.messages_replaced, 99);
+
+    // the link reopens
+    pair.frame_steps(20);
+    assert_eq!(
+        pair.client_world(0).resource::<ReceivedMessages>().0,
+        vec![Message1("blocker".to_string()), Message1("v99".to_string())]
+    );
+}
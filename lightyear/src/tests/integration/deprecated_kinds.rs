@@ -0,0 +1,104 @@
+//! Tests of the components and messages that were removed from the protocol, but are still sent by older peers
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::prelude::server::Replicate;
+use crate::prelude::*;
+use crate::shared::warnings::NetworkWarning;
+use crate::tests::protocol::*;
+
+/// The types of the previous release, which were removed in the next release
+mod old {
+    use super::*;
+
+    #[derive(Component, Serialize, Deserialize, Clone, Debug, PartialEq)]
+    pub struct Armor(pub u16);
+
+    #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+    pub struct Emote(pub String);
+}
+
+fn register_old(app: &mut App) {
+    app.register_component::<old::Armor>(ChannelDirection::ServerToClient);
+    app.register_message::<old::Emote>(ChannelDirection::Bidirectional);
+}
+
+fn register_new(app: &mut App) {
+    app.deprecate_component(std::any::type_name::<old::Armor>());
+    app.deprecate_message(std::any::type_name::<old::Emote>());
+}
+
+fn build_pair(register_client: fn(&mut App), register_server: fn(&mut App)) -> LightyearTestPair {
+    let mut pair = LightyearTestPair::builder()
+        .protocol(ProtocolPlugin)
+        .build_disconnected();
+    register_client(&mut pair.client_apps[0]);
+    register_server(&mut pair.server_app);
+    pair.connect();
+    pair
+}
+
+fn emote() -> old::Emote {
+    old::Emote("wave".to_string())
+}
+
+/// The new client skips the deprecated component and message sent by the old server
+#[test]
+fn test_new_client_old_server() {
+    let mut pair = build_pair(register_new, register_old);
+    let client_report = pair.client_apps[0].net_id_report();
+    assert_eq!(client_report.hash(), pair.server_app.net_id_report().hash());
+    assert!(client_report.entries.iter().any(|entry| entry.deprecated
+        && entry.registry == "component"
+        && entry.protocol_name == std::any::type_name::<old::Armor>()));
+    assert!(client_report.to_string().contains(" deprecated\n"));
+
+    let server_entity = pair
+        .server_world_mut()
+        .spawn((old::Armor(10), Component1(1.0), Replicate::default()))
+        .id();
+    pair.server_world_mut()
+        .resource_mut::<server::ConnectionManager>()
+        .send_message_to_target::<Channel1, _>(&emote(), NetworkTarget::All)
+        .unwrap();
+    pair.frame_steps(10);
+
+    assert_eq!(
+        pair.client_world(0)
+            .resource::<State<client::NetworkingState>>()
+            .get(),
+        &client::NetworkingState::Connected
+    );
+    let client_manager = pair.client_world(0).resource::<client::ConnectionManager>();
+    let client_entity = *client_manager
+        .replication_receiver
+        .remote_entity_map
+        .get_local(server_entity)
+        .expect("the entity was not replicated");
+    let warnings = client_manager.warnings();
+    assert_eq!(warnings.count(NetworkWarning::DeprecatedKind), 2);
+    assert_eq!(warnings.count(NetworkWarning::ComponentWriteFailed), 0);
+    assert_eq!(
+        pair.client_world(0).get::<Component1>(client_entity),
+        Some(&Component1(1.0))
+    );
+}
+
+/// The new server skips the deprecated message sent by the old client
+#[test]
+fn test_old_client_new_server() {
+    let mut pair = build_pair(register_old, register_new);
+    let client_id = pair.client_id(0);
+    pair.client_world_mut(0)
+        .resource_mut::<client::ConnectionManager>()
+        .send_message::<Channel1, _>(&emote())
+        .unwrap();
+    pair.frame_steps(10);
+
+    let server_manager = pair.server_world().resource::<server::ConnectionManager>();
+    let connection = server_manager.connection(client_id).unwrap();
+    assert_eq!(
+        connection.warnings().count(NetworkWarning::DeprecatedKind),
+        1
+    );
+}
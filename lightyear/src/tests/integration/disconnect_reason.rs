@@ -0,0 +1,112 @@
+//! Tests of the reasons of the disconnections, that are reported immediately to the other side
+use bevy::prelude::*;
+
+use crate::prelude::client::ClientCommands;
+use crate::prelude::server::ServerConnections;
+use crate::prelude::*;
+use crate::tests::protocol::*;
+
+#[derive(Resource, Default)]
+struct ServerDisconnections(Vec<(ClientId, server::DisconnectReason)>);
+
+fn record_server_disconnections(
+    mut disconnections: ResMut<ServerDisconnections>,
+    mut events: EventReader<server::DisconnectEvent>,
+) {
+    disconnections.0.extend(
+        events
+            .read()
+            .map(|event| (event.client_id, event.reason.clone())),
+    );
+}
+
+/// Reasons provided by the server when it kicked the client
+#[derive(Resource, Default)]
+struct ClientKicks(Vec<Option<String>>);
+
+fn record_client_kicks(
+    mut kicks: ResMut<ClientKicks>,
+    mut events: EventReader<client::DisconnectEvent>,
+) {
+    kicks
+        .0
+        .extend(events.read().filter_map(|event| match &event.reason {
+            Some(client::DisconnectReason::Kicked(reason)) => Some(reason.clone()),
+            _ => None,
+        }));
+}
+
+fn build_pair() -> LightyearTestPair {
+    let mut pair = LightyearTestPair::builder()
+        .protocol(ProtocolPlugin)
+        .build_disconnected();
+    pair.server_app
+        .init_resource::<ServerDisconnections>()
+        .add_systems(Update, record_server_disconnections);
+    pair.client_apps[0]
+        .init_resource::<ClientKicks>()
+        .add_systems(Update, record_client_kicks);
+    pair.connect();
+    pair
+}
+
+fn server_disconnections(pair: &LightyearTestPair) -> &[(ClientId, server::DisconnectReason)] {
+    &pair.server_world().resource::<ServerDisconnections>().0
+}
+
+/// The server is notified right away when the client disconnects, instead of waiting for the timeout
+#[test]
+fn test_client_requested_disconnect() {
+    let mut pair = build_pair();
+    let client_id = pair.client_id(0);
+    pair.client_apps[0]
+        .world_mut()
+        .commands()
+        .disconnect_client();
+    pair.frame_steps(5);
+    assert_eq!(
+        server_disconnections(&pair),
+        &[(client_id, server::DisconnectReason::ClientRequested)]
+    );
+    assert!(pair.client_world(0).resource::<ClientKicks>().0.is_empty());
+}
+
+/// The reason of the kick is sent to the client
+#[test]
+fn test_server_kick_with_reason() {
+    let mut pair = build_pair();
+    let client_id = pair.client_id(0);
+    pair.server_world_mut()
+        .resource_mut::<ServerConnections>()
+        .disconnect_with_reason(client_id, "banned")
+        .unwrap();
+    pair.frame_steps(5);
+    assert_eq!(
+        server_disconnections(&pair),
+        &[(
+            client_id,
+            server::DisconnectReason::ServerKicked(Some("banned".to_string()))
+        )]
+    );
+    assert_eq!(
+        pair.client_world(0).resource::<ClientKicks>().0,
+        vec![Some("banned".to_string())]
+    );
+}
+
+/// A client that stops sending packets is disconnected when it times out
+#[test]
+fn test_timeout() {
+    let mut pair = build_pair();
+    let client_id = pair.client_id(0);
+    // the client crashes
+    let _client = pair.client_apps.pop().unwrap();
+    // the default timeout is 3 seconds
+    pair.frame_steps(100);
+    assert!(server_disconnections(&pair).is_empty());
+    pair.frame_steps(300);
+    assert_eq!(
+        server_disconnections(&pair),
+        &[(client_id, server::DisconnectReason::Timeout)]
+    );
+}
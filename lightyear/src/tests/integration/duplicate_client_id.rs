@@ -0,0 +1,208 @@
+//! Tests of the [`DuplicateIdPolicy`] applied when a client connects with the id of a client that is
+//! still connected, for example because its previous client crashed
+use bevy::ecs::system::RunSystemOnce;
+use bevy::prelude::*;
+use bevy::utils::Duration;
+
+use crate::connection::server::DeniedReason;
+use crate::prelude::client::{
+    Authentication, ClientCommands, ConnectError, ConnectionFailedEvent, NetConfig, NetworkingState,
+};
+use crate::prelude::server::{
+    ConnectEvent, DisconnectEvent, DuplicateClientIdEvent, DuplicateIdPolicy, DuplicateSession,
+};
+use crate::prelude::*;
+use crate::tests::protocol::*;
+
+#[derive(Resource, Default)]
+struct ServerEvents {
+    duplicates: Vec<DuplicateClientIdEvent>,
+    connections: Vec<ClientId>,
+    disconnections: Vec<ClientId>,
+}
+
+fn record_server_events(
+    mut events: ResMut<ServerEvents>,
+    mut duplicates: EventReader<DuplicateClientIdEvent>,
+    mut connections: EventReader<ConnectEvent>,
+    mut disconnections: EventReader<DisconnectEvent>,
+) {
+    events.duplicates.extend(duplicates.read().copied());
+    events
+        .connections
+        .extend(connections.read().map(|event| event.client_id));
+    events
+        .disconnections
+        .extend(disconnections.read().map(|event| event.client_id));
+}
+
+#[derive(Resource, Default)]
+struct ConnectionFailures(Vec<ConnectError>);
+
+fn record_failures(
+    mut failures: ResMut<ConnectionFailures>,
+    mut events: EventReader<ConnectionFailedEvent>,
+) {
+    failures
+        .0
+        .extend(events.read().map(|event| event.error.clone()));
+}
+
+/// The first client is connected, then stops being updated as if it crashed.
+/// Returns the pair with only the second client, which uses the same id as the first one, and the crashed client.
+fn setup(policy: DuplicateIdPolicy) -> (LightyearTestPair, App) {
+    let mut pair = LightyearTestPair::builder()
+        .protocol(ProtocolPlugin)
+        .clients(2)
+        .build_disconnected();
+    #[allow(irrefutable_let_patterns)]
+    let server::NetConfig::Netcode { config, .. } = &mut pair
+        .server_world_mut()
+        .resource_mut::<server::ServerConfig>()
+        .net[0]
+    else {
+        unreachable!()
+    };
+    config.duplicate_id_policy = policy;
+    pair.server_app
+        .init_resource::<ServerEvents>()
+        .add_systems(Update, record_server_events);
+
+    let ClientId::Netcode(netcode_id) = pair.client_id(0) else {
+        unreachable!()
+    };
+    let mut second = pair.client_apps.pop().unwrap();
+    let NetConfig::Netcode {
+        auth: Authentication::Manual { client_id, .. },
+        ..
+    } = &mut second
+        .world_mut()
+        .resource_mut::<client::ClientConfig>()
+        .net
+    else {
+        unreachable!()
+    };
+    *client_id = netcode_id;
+    second
+        .init_resource::<ConnectionFailures>()
+        .add_systems(Update, record_failures);
+    second.finish();
+    second.cleanup();
+
+    pair.connect();
+    let crashed = pair.client_apps.remove(0);
+    pair.client_apps.push(second);
+    (pair, crashed)
+}
+
+fn networking_state(pair: &LightyearTestPair) -> NetworkingState {
+    *pair
+        .client_world(0)
+        .resource::<State<NetworkingState>>()
+        .get()
+}
+
+/// Start connecting the client, and step until the connection attempt succeeds or fails
+fn connect(pair: &mut LightyearTestPair) {
+    pair.client_world_mut(0)
+        .run_system_once(|mut commands: Commands| commands.connect_client());
+    pair.frame_step();
+    for _ in 0..100 {
+        if networking_state(pair) != NetworkingState::Connecting {
+            return;
+        }
+        pair.frame_step();
+    }
+}
+
+/// The new connection is denied, and the client gets the reason
+#[test]
+fn test_reject_new() {
+    let (mut pair, _crashed) = setup(DuplicateIdPolicy::RejectNew);
+    let client_id = pair.client_id(0);
+    connect(&mut pair);
+
+    assert_eq!(networking_state(&pair), NetworkingState::Disconnected);
+    assert_eq!(
+        pair.client_world(0).resource::<ConnectionFailures>().0,
+        vec![ConnectError::Denied(DeniedReason::AlreadyConnected)]
+    );
+    let events = pair.server_world().resource::<ServerEvents>();
+    assert_eq!(
+        events.duplicates,
+        vec![DuplicateClientIdEvent {
+            client_id,
+            policy: DuplicateIdPolicy::RejectNew,
+            winner: DuplicateSession::Old,
+        }]
+    );
+    assert!(events.disconnections.is_empty());
+    // the previous session is still connected
+    assert!(pair
+        .server_world()
+        .resource::<server::ConnectionManager>()
+        .connection(client_id)
+        .is_ok());
+}
+
+/// The previous session is disconnected and replaced by the new connection
+#[test]
+fn test_disconnect_old() {
+    let (mut pair, _crashed) = setup(DuplicateIdPolicy::DisconnectOld);
+    let client_id = pair.client_id(0);
+    connect(&mut pair);
+
+    assert_eq!(networking_state(&pair), NetworkingState::Connected);
+    let events = pair.server_world().resource::<ServerEvents>();
+    assert_eq!(
+        events.duplicates,
+        vec![DuplicateClientIdEvent {
+            client_id,
+            policy: DuplicateIdPolicy::DisconnectOld,
+            winner: DuplicateSession::New,
+        }]
+    );
+    assert_eq!(events.disconnections, vec![client_id]);
+    // the first connection of the pair, then the new session
+    assert_eq!(events.connections, vec![client_id, client_id]);
+}
+
+/// The previous session is replaced only once it stopped sending packets for the idle threshold
+#[test]
+fn test_replace_if_stale() {
+    let policy = DuplicateIdPolicy::ReplaceIfStale {
+        idle_threshold: Duration::from_millis(500),
+    };
+    let (mut pair, _crashed) = setup(policy);
+    let client_id = pair.client_id(0);
+
+    // the previous session sent packets recently
+    connect(&mut pair);
+    assert_eq!(networking_state(&pair), NetworkingState::Disconnected);
+    assert_eq!(
+        pair.client_world(0).resource::<ConnectionFailures>().0,
+        vec![ConnectError::Denied(DeniedReason::AlreadyConnected)]
+    );
+
+    // the previous session is stale, but did not time out yet
+    pair.frame_steps(50);
+    connect(&mut pair);
+    assert_eq!(networking_state(&pair), NetworkingState::Connected);
+    let events = pair.server_world().resource::<ServerEvents>();
+    assert_eq!(
+        events.duplicates,
+        vec![
+            DuplicateClientIdEvent {
+                client_id,
+                policy,
+                winner: DuplicateSession::Old,
+            },
+            DuplicateClientIdEvent {
+                client_id,
+                policy,
+                winner: DuplicateSession::New,
+            }
+        ]
+    );
+    assert_eq!(events.disconnections, vec![client_id]);
+}
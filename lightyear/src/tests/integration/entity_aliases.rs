@@ -0,0 +1,79 @@
+use bevy::diagnostic::DiagnosticsStore;
+use bevy::prelude::*;
+use bevy::utils::Duration;
+
+use crate::prelude::server::{Replicate, ServerConfig};
+use crate::prelude::*;
+use crate::shared::replication::diagnostics::ReplicationDiagnosticsPlugin;
+use crate::tests::protocol::*;
+
+#[derive(Resource)]
+struct Moving(bool);
+
+fn update_components(moving: Res<Moving>, mut query: Query<&mut Component1>) {
+    if moving.0 {
+        for mut c in query.iter_mut() {
+            c.0 += 1.0;
+        }
+    }
+}
+
+/// This test checks that the entities that are updated every tick get an alias, and that the
+/// updates sent with the alias are applied to the correct entities
+#[test]
+fn test_entity_aliases() {
+    let mut pair = LightyearTestPair::builder()
+        .protocol(ProtocolPlugin)
+        .tick_duration(Duration::from_millis(10))
+        .server_config(ServerConfig {
+            replication: ReplicationConfig {
+                // fewer aliases than updated entities, so that they get recycled
+                entity_aliases: 2,
+                ..default()
+            },
+            ..default()
+        })
+        .build();
+
+    pair.server_app
+        .insert_resource(Moving(true))
+        .add_systems(FixedUpdate, update_components);
+    let server_entities: Vec<Entity> = (0..3)
+        .map(|i| {
+            pair.server_world_mut()
+                .spawn((Component1(i as f32 * 1000.0), Replicate::default()))
+                .id()
+        })
+        .collect();
+    pair.frame_steps(100);
+    pair.server_app.insert_resource(Moving(false));
+    pair.frame_steps(10);
+
+    // the replication stats are flushed to the diagnostics periodically
+    let saved_bytes: f64 = pair
+        .server_world()
+        .resource::<DiagnosticsStore>()
+        .get(&ReplicationDiagnosticsPlugin::ENTITY_ALIAS_SAVED_BYTES)
+        .unwrap()
+        .values()
+        .sum();
+    assert!(saved_bytes > 0.0);
+
+    for server_entity in server_entities {
+        let client_entity = *pair
+            .client_world(0)
+            .resource::<client::ConnectionManager>()
+            .replication_receiver
+            .remote_entity_map
+            .get_local(server_entity)
+            .unwrap();
+        assert_eq!(
+            pair.client_world(0)
+                .get::<Component1>(client_entity)
+                .unwrap(),
+            pair.server_world()
+                .get::<Component1>(server_entity)
+                .unwrap()
+        );
+    }
+}
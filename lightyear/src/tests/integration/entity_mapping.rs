@@ -0,0 +1,215 @@
+//! Tests of the mapping of the entities contained in replicated components and messages
+use bevy::prelude::*;
+use lightyear_macros::MapEntitiesInternal;
+use serde::{Deserialize, Serialize};
+
+use crate::prelude::server::Replicate;
+use crate::prelude::*;
+use crate::shared::replication::deferred::MAX_DEFERRED_MESSAGES;
+use crate::shared::warnings::NetworkWarning;
+use crate::tests::protocol::*;
+
+#[derive(Component, Serialize, Deserialize, Clone, Debug, PartialEq, MapEntitiesInternal)]
+struct Projectile {
+    #[entities]
+    owner: Entity,
+    #[entities]
+    targets: Vec<Entity>,
+    #[entities]
+    launcher: Option<Entity>,
+    speed: f32,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, MapEntitiesInternal)]
+enum Order {
+    Attack {
+        #[entities]
+        target: Entity,
+    },
+    Follow(u32, #[entities] Entity),
+    Stop,
+}
+
+#[derive(Resource, Default)]
+struct ReceivedOrders(Vec<Order>);
+
+fn record_orders(
+    mut orders: ResMut<ReceivedOrders>,
+    mut events: EventReader<ClientMessageEvent<Order>>,
+) {
+    orders
+        .0
+        .extend(events.read().map(|event| event.message().clone()));
+}
+
+fn register(app: &mut App) {
+    app.register_component::<Projectile>(ChannelDirection::ServerToClient)
+        .add_map_entities();
+    app.register_message::<Order>(ChannelDirection::ServerToClient)
+        .add_map_entities();
+}
+
+fn build_pair() -> LightyearTestPair {
+    let mut pair = LightyearTestPair::builder()
+        .protocol(ProtocolPlugin)
+        .build_disconnected();
+    register(&mut pair.client_apps[0]);
+    register(&mut pair.server_app);
+    pair.client_apps[0]
+        .init_resource::<ReceivedOrders>()
+        .add_systems(Update, record_orders);
+    pair.connect();
+    // the server entities should not be confused with the entities of the client
+    pair.server_world_mut().spawn_batch((0..1000).map(|_| ()));
+    pair
+}
+
+fn client_entity(pair: &LightyearTestPair, server_entity: Entity) -> Option<Entity> {
+    pair.client_world(0)
+        .resource::<client::ConnectionManager>()
+        .replication_receiver
+        .remote_entity_map
+        .get_local(server_entity)
+        .copied()
+}
+
+fn received_orders(pair: &LightyearTestPair) -> &[Order] {
+    &pair.client_world(0).resource::<ReceivedOrders>().0
+}
+
+fn send_order(pair: &mut LightyearTestPair, order: Order) {
+    pair.server_world_mut()
+        .resource_mut::<server::ConnectionManager>()
+        .send_message_to_target::<Channel1, _>(&order, NetworkTarget::All)
+        .unwrap();
+}
+
+/// A projectile whose owner is not replicated yet is only inserted once the owner is spawned on the client
+#[test]
+fn test_component_deferred_until_entity_is_spawned() {
+    let mut pair = build_pair();
+    let server_owner = pair.server_world_mut().spawn(Component1(1.0)).id();
+    let server_target = pair
+        .server_world_mut()
+        .spawn((Component1(2.0), Replicate::default()))
+        .id();
+    let server_projectile = pair
+        .server_world_mut()
+        .spawn((
+            Projectile {
+                owner: server_owner,
+                targets: vec![server_target],
+                launcher: None,
+                speed: 1.0,
+            },
+            Component2(1.0),
+            Replicate::default(),
+        ))
+        .id();
+    pair.frame_steps(10);
+    let projectile = client_entity(&pair, server_projectile).unwrap();
+    // the other components of the entity are not delayed
+    assert_eq!(
+        pair.client_world(0).get::<Component2>(projectile),
+        Some(&Component2(1.0))
+    );
+    assert!(pair.client_world(0).get::<Projectile>(projectile).is_none());
+
+    // the owner starts being replicated
+    pair.server_world_mut()
+        .entity_mut(server_owner)
+        .insert(Replicate::default());
+    pair.frame_steps(10);
+    let owner = client_entity(&pair, server_owner).unwrap();
+    let target = client_entity(&pair, server_target).unwrap();
+    assert_eq!(
+        pair.client_world(0).get::<Projectile>(projectile),
+        Some(&Projectile {
+            owner,
+            targets: vec![target],
+            launcher: None,
+            speed: 1.0,
+        })
+    );
+
+    // the updates are mapped as well
+    pair.server_world_mut()
+        .get_mut::<Projectile>(server_projectile)
+        .unwrap()
+        .launcher = Some(server_target);
+    pair.frame_steps(10);
+    assert_eq!(
+        pair.client_world(0)
+            .get::<Projectile>(projectile)
+            .unwrap()
+            .launcher,
+        Some(target)
+    );
+    let warnings = pair
+        .client_world(0)
+        .resource::<client::ConnectionManager>()
+        .warnings();
+    assert_eq!(warnings.count(NetworkWarning::ComponentWriteFailed), 0);
+}
+
+/// A message that references an entity that is not replicated yet is received once the entity is spawned
+#[test]
+fn test_message_deferred_until_entity_is_spawned() {
+    let mut pair = build_pair();
+    let server_target = pair.server_world_mut().spawn(Component1(1.0)).id();
+    send_order(&mut pair, Order::Stop);
+    send_order(
+        &mut pair,
+        Order::Attack {
+            target: server_target,
+        },
+    );
+    send_order(&mut pair, Order::Follow(3, server_target));
+    pair.frame_steps(10);
+    assert_eq!(received_orders(&pair), &[Order::Stop]);
+
+    pair.server_world_mut()
+        .entity_mut(server_target)
+        .insert(Replicate::default());
+    pair.frame_steps(10);
+    let target = client_entity(&pair, server_target).unwrap();
+    assert_eq!(
+        received_orders(&pair),
+        &[
+            Order::Stop,
+            Order::Attack { target },
+            Order::Follow(3, target)
+        ]
+    );
+}
+
+/// The oldest deferred messages are dropped when too many messages are waiting for their entities
+#[test]
+fn test_deferred_messages_overflow() {
+    let mut pair = build_pair();
+    let server_target = pair.server_world_mut().spawn(Component1(1.0)).id();
+    for i in 0..MAX_DEFERRED_MESSAGES + 10 {
+        send_order(
+            &mut pair,
+            Order::Attack {
+                target: server_target,
+            },
+        );
+        if i % 20 == 0 {
+            pair.frame_step();
+        }
+    }
+    pair.frame_steps(10);
+    assert!(received_orders(&pair).is_empty());
+    let warnings = pair
+        .client_world(0)
+        .resource::<client::ConnectionManager>()
+        .warnings();
+    assert_eq!(warnings.count(NetworkWarning::DeferredOverflow), 10);
+
+    pair.server_world_mut()
+        .entity_mut(server_target)
+        .insert(Replicate::default());
+    pair.frame_steps(10);
+    assert_eq!(received_orders(&pair).len(), MAX_DEFERRED_MESSAGES);
+}
@@ -0,0 +1,134 @@
+//! Tests of the replication, prediction and interpolation of a component made of fixed-point numbers
+use bevy::prelude::*;
+use bevy::utils::Duration;
+use lightyear_macros::LerpInternal;
+use serde::{Deserialize, Serialize};
+
+use crate::prelude::client::{
+    ClientConfig, ComponentSyncMode, Confirmed, InterpolationDelay, Predicted,
+};
+use crate::prelude::server::{Replicate, SyncTarget};
+use crate::prelude::*;
+use crate::tests::protocol::*;
+
+#[derive(Component, Serialize, Deserialize, Clone, Debug, PartialEq, Reflect, LerpInternal)]
+struct FixedPosition(FixedVec2);
+
+#[derive(Clone)]
+struct FixedProtocolPlugin;
+
+impl Plugin for FixedProtocolPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(ProtocolPlugin);
+        app.register_component::<FixedPosition>(ChannelDirection::ServerToClient)
+            .add_prediction(ComponentSyncMode::Full)
+            .add_interpolation(ComponentSyncMode::Full)
+            .add_lerp_interpolation_fn();
+    }
+}
+
+/// Displacement per tick, which is not a multiple of 2^-16: the position accumulates rounded products
+fn velocity() -> FixedVec2 {
+    FixedVec2::from_vec(Vec2::new(0.1, -0.37)) * Fixed32::from_f32(1.3)
+}
+
+fn move_server(mut query: Query<&mut FixedPosition>) {
+    for mut position in query.iter_mut() {
+        position.0 += velocity();
+    }
+}
+
+fn move_predicted(mut query: Query<&mut FixedPosition, With<Predicted>>) {
+    for mut position in query.iter_mut() {
+        position.0 += velocity();
+    }
+}
+
+fn rollbacks(pair: &LightyearTestPair) -> u32 {
+    pair.client_world(0)
+        .resource::<client::ConnectionManager>()
+        .session_summary()
+        .rollbacks
+}
+
+/// The client predicts the movement with the same fixed-point arithmetic as the server, so the predictions
+/// always match the confirmed states and there are no rollbacks once the predicted entity caught up.
+/// The interpolated entity moves between the confirmed positions.
+#[test]
+fn test_fixed_point_prediction_and_interpolation() {
+    let mut client_config = ClientConfig::default();
+    // the interpolation timeline stays behind the latest server snapshot despite the jitter
+    client_config.interpolation.delay =
+        InterpolationDelay::default().with_min_delay(Duration::from_millis(50));
+    let mut pair = LightyearTestPair::builder()
+        .protocol(FixedProtocolPlugin)
+        .tick_duration(Duration::from_millis(10))
+        .client_config(client_config)
+        .conditioner(LinkConditionerConfig {
+            incoming_latency: Duration::from_millis(30),
+            incoming_jitter: Duration::from_millis(5),
+            incoming_loss: 0.0,
+            seed: None,
+        })
+        .build_disconnected();
+    pair.server_app.add_systems(FixedUpdate, move_server);
+    pair.client_apps[0].add_systems(FixedUpdate, move_predicted);
+    pair.connect();
+
+    let server_entity = pair
+        .server_world_mut()
+        .spawn((
+            FixedPosition(FixedVec2::ZERO),
+            Replicate {
+                sync: SyncTarget {
+                    prediction: NetworkTarget::All,
+                    interpolation: NetworkTarget::All,
+                },
+                ..default()
+            },
+        ))
+        .id();
+    pair.frame_steps(50);
+    let confirmed = *pair
+        .client_world(0)
+        .resource::<client::ConnectionManager>()
+        .replication_receiver
+        .remote_entity_map
+        .get_local(server_entity)
+        .expect("entity was not replicated to client");
+    let confirmed = pair.client_world(0).get::<Confirmed>(confirmed).unwrap();
+    let predicted = confirmed.predicted.unwrap();
+    let interpolated = confirmed.interpolated.unwrap();
+
+    let warmup_rollbacks = rollbacks(&pair);
+    let start = pair
+        .client_world(0)
+        .get::<FixedPosition>(interpolated)
+        .unwrap()
+        .0;
+    let mut previous = None;
+    for _ in 0..100 {
+        pair.frame_step();
+        let server = pair
+            .server_world()
+            .get::<FixedPosition>(server_entity)
+            .unwrap()
+            .0;
+        let position = pair
+            .client_world(0)
+            .get::<FixedPosition>(interpolated)
+            .unwrap()
+            .0;
+        // the interpolated entity lags behind the server, and moves forward at every frame
+        assert!(position.x <= server.x && position.y >= server.y);
+        if let Some(previous) = previous.replace(position) {
+            assert!(position.x >= previous.x && position.y <= previous.y);
+        }
+    }
+    assert!(previous.unwrap().x > start.x);
+    assert_eq!(rollbacks(&pair), warmup_rollbacks);
+    assert!(pair
+        .client_world(0)
+        .get::<FixedPosition>(predicted)
+        .is_some());
+}
@@ -0,0 +1,165 @@
+//! Tests of the fragmented messages on every channel mode: the messages bigger than a packet are split in fragments
+//! and reassembled by the receiver, and the ordering and sequencing of the channel apply to the reassembled messages
+use bevy::prelude::*;
+use bevy::utils::Duration;
+use lightyear_macros::ChannelInternal;
+
+use crate::prelude::*;
+use crate::tests::protocol::*;
+
+/// Number of bytes of the big messages: about 85 fragments
+const BIG_MESSAGE_LEN: usize = 100_000;
+
+fn big_message(i: u8) -> Message1 {
+    Message1(char::from(b'a' + i).to_string().repeat(BIG_MESSAGE_LEN))
+}
+
+#[derive(ChannelInternal, Reflect)]
+struct SequencedUnreliableChannel;
+
+#[derive(ChannelInternal, Reflect)]
+struct SequencedReliableChannel;
+
+#[derive(ChannelInternal, Reflect)]
+struct OrderedReliableChannel;
+
+/// Protocol with a channel for every [`ChannelMode`]
+#[derive(Clone)]
+struct FragmentationProtocolPlugin;
+
+impl Plugin for FragmentationProtocolPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(ProtocolPlugin);
+        app.add_channel::<SequencedUnreliableChannel>(ChannelSettings {
+            mode: ChannelMode::SequencedUnreliable,
+            ..default()
+        });
+        app.add_channel::<SequencedReliableChannel>(ChannelSettings {
+            mode: ChannelMode::SequencedReliable(ReliableSettings::default()),
+            ..default()
+        });
+        app.add_channel::<OrderedReliableChannel>(ChannelSettings {
+            mode: ChannelMode::OrderedReliable(ReliableSettings::default()),
+            ..default()
+        });
+    }
+}
+
+#[derive(Resource, Default)]
+struct Received(Vec<String>);
+
+fn record_messages(
+    mut received: ResMut<Received>,
+    mut messages: EventReader<client::MessageEvent<Message1>>,
+) {
+    received
+        .0
+        .extend(messages.read().map(|event| event.message().0.clone()));
+}
+
+fn build_pair(incoming_loss: f32) -> LightyearTestPair {
+    let mut pair = LightyearTestPair::builder()
+        .protocol(FragmentationProtocolPlugin)
+        .tick_duration(Duration::from_millis(10))
+        .conditioner(LinkConditionerConfig {
+            incoming_latency: Duration::from_millis(20),
+            incoming_jitter: Duration::from_millis(5),
+            incoming_loss,
+            seed: None,
+        })
+        .build_disconnected();
+    pair.client_apps[0]
+        .init_resource::<Received>()
+        .add_systems(Update, record_messages);
+    pair.connect();
+    pair
+}
+
+/// Send the big messages from the server to the client on the channel `C`, every `interval` frames, and
+/// return the messages received by the client
+fn send_big_messages<C: Channel>(
+    incoming_loss: f32,
+    num_messages: u8,
+    interval: usize,
+) -> Vec<String> {
+    let mut pair = build_pair(incoming_loss);
+    let client_id = pair.client_id(0);
+    for i in 0..num_messages {
+        pair.server_world_mut()
+            .resource_mut::<server::ConnectionManager>()
+            .send_message::<C, _>(client_id, &big_message(i))
+            .unwrap();
+        pair.frame_steps(interval);
+    }
+    pair.frame_steps(300);
+    std::mem::take(&mut pair.client_world_mut(0).resource_mut::<Received>().0)
+}
+
+/// Index of the big message, checking that it was not corrupted
+fn message_index(message: &str) -> u8 {
+    assert_eq!(message.len(), BIG_MESSAGE_LEN);
+    let index = message.as_bytes()[0] - b'a';
+    assert_eq!(message, big_message(index).0);
+    index
+}
+
+const RELIABLE_LOSS: f32 = 0.2;
+const RELIABLE_MESSAGES: u8 = 3;
+
+/// The unreliable channels only receive a message if none of its fragments was lost, so the loss is low
+/// and many messages are sent: about 40% of them are received
+const UNRELIABLE_LOSS: f32 = 0.01;
+const UNRELIABLE_MESSAGES: u8 = 26;
+const UNRELIABLE_INTERVAL: usize = 10;
+
+#[test]
+fn test_fragmented_ordered_reliable() {
+    let received = send_big_messages::<OrderedReliableChannel>(RELIABLE_LOSS, RELIABLE_MESSAGES, 1);
+    let indices: Vec<_> = received.iter().map(|m| message_index(m)).collect();
+    assert_eq!(indices, (0..RELIABLE_MESSAGES).collect::<Vec<_>>());
+}
+
+#[test]
+fn test_fragmented_unordered_reliable() {
+    let received = send_big_messages::<Channel3>(RELIABLE_LOSS, RELIABLE_MESSAGES, 1);
+    let mut indices: Vec<_> = received.iter().map(|m| message_index(m)).collect();
+    indices.sort();
+    assert_eq!(indices, (0..RELIABLE_MESSAGES).collect::<Vec<_>>());
+}
+
+/// The older messages can be skipped if a more recent message was reassembled first, but the most recent
+/// message is always received, exactly once
+#[test]
+fn test_fragmented_sequenced_reliable() {
+    let received =
+        send_big_messages::<SequencedReliableChannel>(RELIABLE_LOSS, RELIABLE_MESSAGES, 1);
+    let indices: Vec<_> = received.iter().map(|m| message_index(m)).collect();
+    assert!(indices.windows(2).all(|w| w[0] < w[1]), "{indices:?}");
+    assert_eq!(indices.last(), Some(&(RELIABLE_MESSAGES - 1)));
+}
+
+#[test]
+fn test_fragmented_unordered_unreliable() {
+    for received in [
+        send_big_messages::<Channel1>(UNRELIABLE_LOSS, UNRELIABLE_MESSAGES, UNRELIABLE_INTERVAL),
+        send_big_messages::<Channel2>(UNRELIABLE_LOSS, UNRELIABLE_MESSAGES, UNRELIABLE_INTERVAL),
+    ] {
+        let mut indices: Vec<_> = received.iter().map(|m| message_index(m)).collect();
+        assert!(!indices.is_empty());
+        let len = indices.len();
+        indices.dedup();
+        assert_eq!(indices.len(), len, "duplicate messages: {indices:?}");
+    }
+}
+
+#[test]
+fn test_fragmented_sequenced_unreliable() {
+    let received = send_big_messages::<SequencedUnreliableChannel>(
+        UNRELIABLE_LOSS,
+        UNRELIABLE_MESSAGES,
+        UNRELIABLE_INTERVAL,
+    );
+    let indices: Vec<_> = received.iter().map(|m| message_index(m)).collect();
+    assert!(!indices.is_empty());
+    assert!(indices.windows(2).all(|w| w[0] < w[1]), "{indices:?}");
+}
@@ -0,0 +1,203 @@
+//! Tests of the handoffs between two interpolated entities: the target visually continues from the source at the
+//! handoff tick
+use bevy::ecs::system::RunSystemOnce;
+use bevy::prelude::*;
+use bevy::utils::Duration;
+
+use crate::prelude::client::{
+    ClientConfig, Confirmed, HandoffTarget, InterpolateStatus, InterpolationDelay,
+};
+use crate::prelude::server::{HandoffCommandsExt, Replicate, SyncTarget};
+use crate::prelude::*;
+use crate::tests::protocol::*;
+
+/// Offset between the player and the seat of the vehicle
+const SEAT_OFFSET: f32 = 1000.0;
+/// Number of ticks between the declaration of the handoff and the handoff tick
+const HANDOFF_DELAY: i16 = 10;
+
+fn seat_offset(player: &Component1) -> Component1 {
+    Component1(player.0 + SEAT_OFFSET)
+}
+
+#[derive(Clone)]
+struct HandoffProtocolPlugin;
+
+impl Plugin for HandoffProtocolPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(ProtocolPlugin);
+        app.add_handoff_fn::<Component1>(seat_offset);
+    }
+}
+
+fn move_entities(mut query: Query<&mut Component1>) {
+    for mut component in query.iter_mut() {
+        component.0 += 1.0;
+    }
+}
+
+fn interpolated_replicate() -> Replicate {
+    Replicate {
+        sync: SyncTarget {
+            interpolation: NetworkTarget::All,
+            ..default()
+        },
+        ..default()
+    }
+}
+
+/// Interpolated entity of the server entity on the client, if it was spawned
+fn interpolated_entity(pair: &LightyearTestPair, server_entity: Entity) -> Option<Entity> {
+    let confirmed = *pair
+        .client_world(0)
+        .resource::<client::ConnectionManager>()
+        .replication_receiver
+        .remote_entity_map
+        .get_local(server_entity)?;
+    pair.client_world(0)
+        .get::<Confirmed>(confirmed)?
+        .interpolated
+}
+
+/// Value displayed by the interpolated entity, if it is displayed
+fn displayed(pair: &LightyearTestPair, entity: Entity) -> Option<f32> {
+    pair.client_world(0)
+        .get::<Component1>(entity)
+        .map(|component| component.0)
+}
+
+/// Interpolation tick that was displayed by the interpolated entity during the last frame
+fn displayed_tick(pair: &LightyearTestPair, entity: Entity) -> Tick {
+    pair.client_world(0)
+        .get::<InterpolateStatus<Component1>>(entity)
+        .unwrap()
+        .current_tick
+}
+
+/// Spawn a moving player that is interpolated by the client, and wait until it is displayed
+fn setup() -> (LightyearTestPair, Entity, Entity) {
+    let mut client_config = ClientConfig::default();
+    // the interpolation timeline is 5 ticks behind the latest server snapshot
+    client_config.interpolation.delay =
+        InterpolationDelay::default().with_min_delay(Duration::from_millis(50));
+    let mut pair = LightyearTestPair::builder()
+        .protocol(HandoffProtocolPlugin)
+        .tick_duration(Duration::from_millis(10))
+        .client_config(client_config)
+        .conditioner(LinkConditionerConfig {
+            incoming_latency: Duration::from_millis(20),
+            incoming_jitter: Duration::from_millis(5),
+            incoming_loss: 0.0,
+            seed: None,
+        })
+        .build_disconnected();
+    pair.server_app.add_systems(FixedUpdate, move_entities);
+    pair.connect();
+
+    let player = pair
+        .server_world_mut()
+        .spawn((Component1(0.0), interpolated_replicate()))
+        .id();
+    pair.frame_steps(50);
+    let player_interpolated = interpolated_entity(&pair, player).unwrap();
+    assert!(displayed(&pair, player_interpolated).is_some());
+    (pair, player, player_interpolated)
+}
+
+/// Spawn the seat of a vehicle next to the player, and hand the player off to the seat
+fn enter_vehicle(pair: &mut LightyearTestPair, player: Entity) -> (Entity, Tick) {
+    let tick = pair.server_world().resource::<TickManager>().tick() + HANDOFF_DELAY;
+    let position = pair.server_world().get::<Component1>(player).unwrap().0;
+    let seat = pair
+        .server_world_mut()
+        .spawn((Component1(position + SEAT_OFFSET), interpolated_replicate()))
+        .id();
+    pair.server_world_mut()
+        .run_system_once(move |mut commands: Commands| {
+            commands.replicate_handoff(player, seat, tick);
+        });
+    (seat, tick)
+}
+
+/// Every frame displays exactly one of the player and the seat, and they are swapped on the frame where the
+/// interpolation timeline reaches the handoff tick
+#[test]
+fn test_handoff_continuity() {
+    let (mut pair, player, player_interpolated) = setup();
+    let (seat, tick) = enter_vehicle(&mut pair, player);
+
+    let mut last_player_position = None;
+    let mut swapped = false;
+    for _ in 0..100 {
+        pair.frame_step();
+        let player_position = displayed(&pair, player_interpolated);
+        let seat_interpolated = interpolated_entity(&pair, seat);
+        let seat_position = seat_interpolated.and_then(|entity| displayed(&pair, entity));
+        match (player_position, seat_position) {
+            (Some(position), None) => {
+                assert!(displayed_tick(&pair, player_interpolated) < tick);
+                last_player_position = Some(position);
+            }
+            (None, Some(position)) => {
+                assert!(displayed_tick(&pair, seat_interpolated.unwrap()) >= tick);
+                if !swapped {
+                    // the seat continues from the last position of the player
+                    let expected = last_player_position.unwrap() + SEAT_OFFSET;
+                    assert!(
+                        (position - expected).abs() <= 3.0,
+                        "seat: {position}, expected: {expected}"
+                    );
+                    swapped = true;
+                }
+            }
+            _ => panic!("player: {player_position:?}, seat: {seat_position:?}"),
+        }
+    }
+    assert!(swapped);
+    assert!(pair.server_world().get_entity(player).is_none());
+    assert!(pair
+        .client_world(0)
+        .get_entity(player_interpolated)
+        .is_none());
+    assert!(pair
+        .client_world(0)
+        .resource::<client::ConnectionManager>()
+        .replication_receiver
+        .remote_entity_map
+        .get_local(player)
+        .is_none());
+}
+
+/// The interpolation of the seat is seeded with the samples of the player, transformed with the handoff function
+#[test]
+fn test_handoff_seeds_interpolation() {
+    let (mut pair, player, player_interpolated) = setup();
+    let (seat, _) = enter_vehicle(&mut pair, player);
+
+    let seat_interpolated = loop {
+        pair.frame_step();
+        if let Some(entity) = interpolated_entity(&pair, seat) {
+            break entity;
+        }
+    };
+    let world = pair.client_world(0);
+    let handoff = world.get::<HandoffTarget>(seat_interpolated).unwrap();
+    assert_eq!(handoff.source, player_interpolated);
+    assert!(world.get::<Component1>(seat_interpolated).is_none());
+
+    let player_status = world
+        .get::<InterpolateStatus<Component1>>(player_interpolated)
+        .unwrap();
+    let seat_status = world
+        .get::<InterpolateStatus<Component1>>(seat_interpolated)
+        .unwrap();
+    // the seat interpolates between the samples of the player
+    let seeded = |sample: &Option<(Tick, Component1)>| {
+        sample
+            .as_ref()
+            .map(|(tick, player)| (*tick, seat_offset(player)))
+    };
+    assert!(player_status.start.is_some() && player_status.end.is_some());
+    assert_eq!(seat_status.start, seeded(&player_status.start));
+    assert_eq!(seat_status.end, seeded(&player_status.end));
+}
@@ -0,0 +1,202 @@
+//! Tests for a server embedded in a tokio application with a [`HeadlessServer`]
+use std::net::SocketAddr;
+
+use bevy::ecs::system::RunSystemOnce;
+use bevy::prelude::*;
+use bevy::state::app::StatesPlugin;
+use bevy::utils::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_util::sync::CancellationToken;
+
+use crate::connection::netcode::generate_key;
+use crate::prelude::client::{
+    Authentication, ClientCommands, ClientConfig, ClientTransport, NetworkingState,
+};
+use crate::prelude::server::{NetcodeConfig, Replicate, ServerConfig, ServerTransport};
+use crate::prelude::*;
+use crate::server::headless::{HeadlessServer, ServerHandle, ServerHandleError};
+use crate::tests::protocol::*;
+use crate::tests::stepper::TEST_CLIENT_ID;
+use crate::transport::LOCAL_SOCKET;
+
+const TICK_DURATION: Duration = Duration::from_millis(10);
+
+#[derive(Resource, Default)]
+struct ReceivedMessages(Vec<Message1>);
+
+fn receive_messages(
+    mut received: ResMut<ReceivedMessages>,
+    mut events: EventReader<client::MessageEvent<Message1>>,
+) {
+    received
+        .0
+        .extend(events.read().map(|event| event.message().clone()));
+}
+
+/// Minimal HTTP endpoint that reports the number of clients connected to the server
+async fn serve_health(listener: TcpListener, handle: ServerHandle) {
+    while let Ok((mut stream, _)) = listener.accept().await {
+        let mut request = [0; 1024];
+        let _ = stream.read(&mut request).await;
+        let response = match handle.diagnostics().await {
+            Ok(diagnostics) => {
+                let body = format!("clients={}", diagnostics.clients.len());
+                format!(
+                    "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n{}",
+                    body.len(),
+                    body
+                )
+            }
+            Err(_) => "HTTP/1.1 503 Service Unavailable\r\nContent-Length: 0\r\n\r\n".to_string(),
+        };
+        let _ = stream.write_all(response.as_bytes()).await;
+    }
+}
+
+async fn get_health(addr: SocketAddr) -> String {
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    stream
+        .write_all(b"GET /health HTTP/1.1\r\nHost: localhost\r\n\r\n")
+        .await
+        .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+    response
+}
+
+/// Update the client in real time until `condition` is true
+async fn update_until(client_app: &mut App, mut condition: impl FnMut(&mut App) -> bool) {
+    for _ in 0..500 {
+        if condition(client_app) {
+            return;
+        }
+        client_app.update();
+        tokio::time::sleep(TICK_DURATION).await;
+    }
+    panic!("the condition was not reached in time");
+}
+
+fn num_replicated(client_app: &mut App) -> usize {
+    client_app
+        .world_mut()
+        .query::<&Component1>()
+        .iter(client_app.world())
+        .count()
+}
+
+#[test]
+fn test_headless_server_in_tokio_runtime() {
+    let shared_config = SharedConfig {
+        tick: TickConfig::new(TICK_DURATION),
+        ..default()
+    };
+    let private_key = generate_key();
+    let (from_server_send, from_server_recv) = crossbeam_channel::unbounded();
+    let (to_server_send, to_server_recv) = crossbeam_channel::unbounded();
+
+    let server_config = ServerConfig {
+        shared: shared_config,
+        net: vec![server::NetConfig::Netcode {
+            config: NetcodeConfig::default().with_key(private_key),
+            io: server::IoConfig::from_transport(ServerTransport::Channels {
+                channels: vec![(LOCAL_SOCKET, to_server_recv, from_server_send)],
+            }),
+        }],
+        ..default()
+    };
+    let server = HeadlessServer::new(server_config).with_setup(|app| {
+        app.add_plugins(ProtocolPlugin);
+    });
+    let handle = server.handle();
+
+    // the client runs on the test thread
+    let mut client_app = App::new();
+    client_app.add_plugins((MinimalPlugins, StatesPlugin));
+    let client_config = ClientConfig {
+        shared: shared_config,
+        net: client::NetConfig::Netcode {
+            auth: Authentication::Manual {
+                server_addr: LOCAL_SOCKET,
+                protocol_id: 0,
+                private_key,
+                client_id: TEST_CLIENT_ID,
+            },
+            config: default(),
+            io: client::IoConfig::from_transport(ClientTransport::LocalChannel {
+                send: to_server_send,
+                recv: from_server_recv,
+            }),
+        },
+        ..default()
+    };
+    client_app.add_plugins((client::ClientPlugins::new(client_config), ProtocolPlugin));
+    client_app.init_resource::<ReceivedMessages>();
+    client_app.add_systems(Update, receive_messages);
+    client_app.finish();
+    client_app.cleanup();
+
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    runtime.block_on(async move {
+        let shutdown = CancellationToken::new();
+        let server_task = tokio::spawn(server.run(shutdown.clone()));
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let health_addr = listener.local_addr().unwrap();
+        tokio::spawn(serve_health(listener, handle.clone()));
+
+        client_app
+            .world_mut()
+            .run_system_once(|mut commands: Commands| commands.connect_client());
+        update_until(&mut client_app, |app| {
+            app.world().resource::<State<NetworkingState>>().get() == &NetworkingState::Connected
+        })
+        .await;
+        assert!(get_health(health_addr).await.ends_with("clients=1"));
+
+        // send a message
+        handle
+            .send_message::<Channel3, _>(Message1("hello".to_string()), NetworkTarget::All)
+            .unwrap();
+        update_until(&mut client_app, |app| {
+            app.world().resource::<ReceivedMessages>().0 == vec![Message1("hello".to_string())]
+        })
+        .await;
+
+        // spawn and despawn a replicated entity
+        let entity = handle
+            .spawn((Replicate::default(), Component1(1.0)))
+            .await
+            .unwrap();
+        update_until(&mut client_app, |app| num_replicated(app) == 1).await;
+        handle.despawn(entity).unwrap();
+        update_until(&mut client_app, |app| num_replicated(app) == 0).await;
+
+        // kick the client
+        handle.kick(ClientId::Netcode(TEST_CLIENT_ID)).unwrap();
+        update_until(&mut client_app, |app| {
+            app.world().resource::<State<NetworkingState>>().get() == &NetworkingState::Disconnected
+        })
+        .await;
+        let mut diagnostics = handle.diagnostics().await.unwrap();
+        for _ in 0..100 {
+            if diagnostics.clients.is_empty() {
+                break;
+            }
+            tokio::time::sleep(TICK_DURATION).await;
+            diagnostics = handle.diagnostics().await.unwrap();
+        }
+        assert!(diagnostics.clients.is_empty());
+        assert!(diagnostics.tick.0 > 0);
+
+        // stop the server
+        shutdown.cancel();
+        server_task.await.unwrap().unwrap();
+        assert_eq!(handle.diagnostics().await, Err(ServerHandleError::Stopped));
+        assert!(get_health(health_addr)
+            .await
+            .starts_with("HTTP/1.1 503 Service Unavailable"));
+    });
+}
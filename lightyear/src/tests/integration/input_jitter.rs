@@ -0,0 +1,129 @@
+//! Tests of the input jitter buffer: the server measures the margins of the inputs and advises the client
+//! to change its input delay
+use bevy::diagnostic::DiagnosticsStore;
+use bevy::prelude::*;
+use bevy::utils::Duration;
+
+use crate::prelude::client::{InputDelayConfig, InputManager, InputSystemSet, PredictionConfig};
+use crate::prelude::server::{InputJitterConfig, ServerConfig};
+use crate::prelude::*;
+use crate::server::diagnostics::ServerDiagnosticsPlugin;
+use crate::tests::protocol::*;
+
+fn buffer_inputs(mut input_manager: ResMut<InputManager<MyInput>>, tick_manager: Res<TickManager>) {
+    input_manager.add_input(MyInput(1), tick_manager.tick());
+}
+
+/// Advices received by the client
+#[derive(Resource, Default)]
+struct Advices(Vec<InputDelayAdvice>);
+
+fn record_advices(
+    mut advices: ResMut<Advices>,
+    mut events: EventReader<client::MessageEvent<InputDelayAdvice>>,
+) {
+    advices.0.extend(events.read().map(|event| event.message));
+}
+
+fn build_pair(auto_tune: bool) -> LightyearTestPair {
+    let mut pair = LightyearTestPair::builder()
+        .protocol(ProtocolPlugin)
+        .tick_duration(Duration::from_millis(10))
+        .conditioner(LinkConditionerConfig {
+            incoming_latency: Duration::from_millis(30),
+            incoming_jitter: Duration::from_millis(20),
+            incoming_loss: 0.0,
+            // the margins, and therefore the advised delays, do not depend on the run
+            seed: Some(7),
+        })
+        .client_config(client::ClientConfig {
+            prediction: PredictionConfig::default().with_input_delay(InputDelayConfig::Automatic {
+                max_ticks: 30,
+                // the input delay computed from the RTT is much larger than needed
+                rtt_margin: Duration::from_millis(100),
+            }),
+            ..default()
+        })
+        .server_config(ServerConfig {
+            input_jitter: InputJitterConfig {
+                window: 50,
+                persistence: 2,
+                ..default()
+            }
+            .with_auto_tune(auto_tune),
+            ..default()
+        })
+        .build_disconnected();
+    pair.client_apps[0]
+        .init_resource::<Advices>()
+        .add_systems(Update, record_advices)
+        .add_systems(
+            FixedPreUpdate,
+            buffer_inputs.in_set(InputSystemSet::BufferInputs),
+        );
+    pair.connect();
+    pair
+}
+
+fn input_delay(pair: &LightyearTestPair) -> u16 {
+    pair.client_world(0)
+        .resource::<client::ConnectionManager>()
+        .input_delay_ticks(pair.client_world(0).resource::<TickManager>())
+}
+
+fn margin_stats(pair: &LightyearTestPair) -> InputMarginStats {
+    pair.server_world()
+        .resource::<server::ConnectionManager>()
+        .input_margin_stats(pair.client_id(0))
+        .unwrap()
+}
+
+/// With a jittery connection, the advised input delay converges to a value that does not miss any input
+#[test]
+fn test_input_delay_advice_converges() {
+    let mut pair = build_pair(true);
+    pair.frame_steps(100);
+    let initial_delay = input_delay(&pair);
+    let mut delays = vec![];
+    let mut late = vec![];
+    for _ in 0..40 {
+        pair.frame_steps(50);
+        delays.push(input_delay(&pair));
+        late.push(margin_stats(&pair).late);
+    }
+    // the client followed the advice of the server
+    assert!(!pair.client_world(0).resource::<Advices>().0.is_empty());
+    assert!(delays.last().unwrap() < &initial_delay, "{delays:?}");
+    // the delay is stable, and no input is missed
+    let (_, converged) = delays.split_at(25);
+    assert!(
+        converged.iter().all(|delay| *delay == converged[0]),
+        "{delays:?}"
+    );
+    assert!(late[25..].iter().all(|late| *late == 0), "{late:?}");
+    let stats = margin_stats(&pair);
+    assert!((0..=2).contains(&stats.p99), "{stats:?}");
+
+    // the margins are reported in the per-client diagnostics
+    let store = pair.server_world().resource::<DiagnosticsStore>();
+    for path in [
+        ServerDiagnosticsPlugin::input_margin_p50(pair.client_id(0)),
+        ServerDiagnosticsPlugin::input_margin_p99(pair.client_id(0)),
+    ] {
+        assert!(store.get(&path).unwrap().value().is_some(), "{path:?}");
+    }
+}
+
+/// Without auto-tuning, the margins are measured but the input delay is not changed
+#[test]
+fn test_input_delay_without_auto_tune() {
+    let mut pair = build_pair(false);
+    let initial_delay = input_delay(&pair);
+    pair.frame_steps(1000);
+    assert!(pair.client_world(0).resource::<Advices>().0.is_empty());
+    assert_eq!(input_delay(&pair), initial_delay);
+    // the jitter of ±20ms (±2 ticks) spreads the margins, and the large input delay avoids late inputs
+    let stats = margin_stats(&pair);
+    assert!((1..=3).contains(&(stats.p50 - stats.p99)), "{stats:?}");
+    assert_eq!(stats.late, 0, "{stats:?}");
+}
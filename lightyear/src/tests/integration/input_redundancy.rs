@@ -0,0 +1,75 @@
+//! Tests of the redundant input messages, which contain the inputs of the last ticks to survive packet loss
+use bevy::prelude::*;
+use bevy::utils::Duration;
+
+use crate::prelude::client::{InputManager, InputSystemSet, PredictionConfig};
+use crate::prelude::server::InputEvent;
+use crate::prelude::*;
+use crate::tests::protocol::*;
+
+/// Every tick, the client adds an input whose value is the tick at which it was sampled
+fn buffer_inputs(mut input_manager: ResMut<InputManager<MyInput>>, tick_manager: Res<TickManager>) {
+    input_manager.add_input(MyInput(tick_manager.tick().0 as i16), tick_manager.tick());
+}
+
+/// Inputs received by the server, for each server tick
+#[derive(Resource, Default)]
+struct ReceivedInputs(Vec<(Tick, Option<MyInput>)>);
+
+fn record_inputs(
+    mut received: ResMut<ReceivedInputs>,
+    tick_manager: Res<TickManager>,
+    mut events: EventReader<InputEvent<MyInput>>,
+) {
+    let tick = tick_manager.tick();
+    received
+        .0
+        .extend(events.read().map(|event| (tick, event.input().clone())));
+}
+
+/// With 20% packet loss, the input buffer of the server has no gaps
+#[test]
+fn test_no_input_gaps_with_packet_loss() {
+    let mut pair = LightyearTestPair::builder()
+        .protocol(ProtocolPlugin)
+        .tick_duration(Duration::from_millis(10))
+        .conditioner(LinkConditionerConfig {
+            incoming_latency: Duration::from_millis(20),
+            incoming_jitter: Duration::from_millis(0),
+            incoming_loss: 0.2,
+            seed: None,
+        })
+        // the input delay gives the time to the next messages to bring the inputs of a lost message
+        .client_config(client::ClientConfig {
+            prediction: PredictionConfig::default().with_minimum_input_delay_ticks(8),
+            ..default()
+        })
+        .build_disconnected();
+    pair.client_apps[0].add_systems(
+        FixedPreUpdate,
+        buffer_inputs.in_set(InputSystemSet::BufferInputs),
+    );
+    pair.server_app
+        .init_resource::<ReceivedInputs>()
+        .add_systems(FixedUpdate, record_inputs);
+    pair.connect();
+    pair.frame_steps(100);
+    pair.server_world_mut()
+        .resource_mut::<ReceivedInputs>()
+        .0
+        .clear();
+
+    pair.frame_steps(500);
+    let received = &pair.server_world().resource::<ReceivedInputs>().0;
+    assert!(received.len() >= 400);
+    // consecutive server ticks received the inputs of consecutive client ticks: no input was missed
+    for window in received.windows(2) {
+        let [(_, previous), (tick, input)] = window else {
+            unreachable!()
+        };
+        let (Some(previous), Some(input)) = (previous, input) else {
+            panic!("missing input at {tick:?}");
+        };
+        assert_eq!(input.0, previous.0.wrapping_add(1), "{tick:?}");
+    }
+}
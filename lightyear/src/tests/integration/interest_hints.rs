@@ -0,0 +1,198 @@
+//! Tests of the interest hints sent by the client to receive entities that the interest management would hide
+use bevy::prelude::*;
+use bevy::utils::Duration;
+
+use crate::prelude::client::{ClientConfig, ConnectionManager, InterestHintEvent};
+use crate::prelude::server::{AppInterestHintExt, Replicate, ServerConfig};
+use crate::prelude::*;
+use crate::tests::protocol::*;
+use crate::tests::stepper::{BevyStepper, Step};
+
+const HINT_TTL: Duration = Duration::from_millis(300);
+
+/// Marker for the entities that the clients are not allowed to request
+#[derive(Component)]
+struct Secret;
+
+#[derive(Resource, Default)]
+struct HintEvents(Vec<InterestHintEvent>);
+
+fn record_hint_events(
+    mut recorded: ResMut<HintEvents>,
+    mut events: EventReader<InterestHintEvent>,
+) {
+    recorded.0.extend(events.read().cloned());
+}
+
+fn setup() -> BevyStepper {
+    let frame_duration = Duration::from_millis(10);
+    let shared_config = SharedConfig {
+        tick: TickConfig::new(frame_duration),
+        ..default()
+    };
+    let mut stepper = BevyStepper::new(shared_config, ClientConfig::default(), frame_duration);
+    stepper
+        .server_app
+        .world_mut()
+        .resource_mut::<ServerConfig>()
+        .replication
+        .interest
+        .hint_ttl = HINT_TTL;
+    stepper
+        .server_app
+        .set_interest_hint_authorization(|entity_ref, _| !entity_ref.contains::<Secret>());
+    stepper.client_app.init_resource::<HintEvents>();
+    stepper.client_app.add_systems(Update, record_hint_events);
+    stepper.init();
+    stepper
+}
+
+/// Spawn an entity that is not in any room of the client, so it is not relevant to the client
+fn spawn_far_away(stepper: &mut BevyStepper, secret: bool) -> Entity {
+    let mut entity = stepper.server_app.world_mut().spawn((
+        Component1(1.0),
+        Replicate {
+            relevance_mode: NetworkRelevanceMode::InterestManagement,
+            ..default()
+        },
+    ));
+    if secret {
+        entity.insert(Secret);
+    }
+    entity.id()
+}
+
+fn request_interest(stepper: &mut BevyStepper, server_entity: Entity, enable: bool) {
+    stepper
+        .client_app
+        .world_mut()
+        .resource_mut::<ConnectionManager>()
+        .request_interest(NetworkEntityId(server_entity), enable)
+        .unwrap();
+}
+
+fn client_entity(stepper: &BevyStepper, server_entity: Entity) -> Option<Entity> {
+    stepper
+        .client_app
+        .world()
+        .resource::<ConnectionManager>()
+        .replication_receiver
+        .remote_entity_map
+        .get_local(server_entity)
+        .copied()
+        .filter(|entity| stepper.client_app.world().get_entity(*entity).is_some())
+}
+
+fn take_hint_events(stepper: &mut BevyStepper) -> Vec<InterestHintEvent> {
+    std::mem::take(
+        &mut stepper
+            .client_app
+            .world_mut()
+            .resource_mut::<HintEvents>()
+            .0,
+    )
+}
+
+#[test]
+fn test_interest_hint_granted_then_expires() {
+    let mut stepper = setup();
+    let server_entity = spawn_far_away(&mut stepper, false);
+    for _ in 0..10 {
+        stepper.frame_step();
+    }
+    assert!(client_entity(&stepper, server_entity).is_none());
+
+    request_interest(&mut stepper, server_entity, true);
+    for _ in 0..10 {
+        stepper.frame_step();
+    }
+    assert_eq!(
+        take_hint_events(&mut stepper),
+        vec![InterestHintEvent {
+            entity: NetworkEntityId(server_entity),
+            outcome: InterestHintOutcome::Granted,
+        }]
+    );
+    let client_entity_id =
+        client_entity(&stepper, server_entity).expect("the hinted entity was not replicated");
+    assert_eq!(
+        stepper
+            .client_app
+            .world()
+            .get::<Component1>(client_entity_id),
+        Some(&Component1(1.0))
+    );
+
+    // the hinted entity receives the updates
+    stepper
+        .server_app
+        .world_mut()
+        .get_mut::<Component1>(server_entity)
+        .unwrap()
+        .0 = 2.0;
+    for _ in 0..5 {
+        stepper.frame_step();
+    }
+    assert_eq!(
+        stepper
+            .client_app
+            .world()
+            .get::<Component1>(client_entity_id),
+        Some(&Component1(2.0))
+    );
+
+    // the hint is not refreshed: it expires, and the entity is despawned on the client
+    for _ in 0..40 {
+        stepper.frame_step();
+    }
+    assert_eq!(
+        take_hint_events(&mut stepper),
+        vec![InterestHintEvent {
+            entity: NetworkEntityId(server_entity),
+            outcome: InterestHintOutcome::Expired,
+        }]
+    );
+    assert!(client_entity(&stepper, server_entity).is_none());
+}
+
+#[test]
+fn test_interest_hint_refresh_keeps_entity() {
+    let mut stepper = setup();
+    let server_entity = spawn_far_away(&mut stepper, false);
+    // refresh the hint more often than the TTL
+    for _ in 0..6 {
+        request_interest(&mut stepper, server_entity, true);
+        for _ in 0..20 {
+            stepper.frame_step();
+        }
+        assert!(client_entity(&stepper, server_entity).is_some());
+    }
+    assert!(take_hint_events(&mut stepper)
+        .iter()
+        .all(|event| event.outcome == InterestHintOutcome::Granted));
+
+    // disabling the hint makes the entity irrelevant right away
+    request_interest(&mut stepper, server_entity, false);
+    for _ in 0..10 {
+        stepper.frame_step();
+    }
+    assert!(client_entity(&stepper, server_entity).is_none());
+}
+
+#[test]
+fn test_interest_hint_denied() {
+    let mut stepper = setup();
+    let server_entity = spawn_far_away(&mut stepper, true);
+    request_interest(&mut stepper, server_entity, true);
+    for _ in 0..10 {
+        stepper.frame_step();
+    }
+    assert_eq!(
+        take_hint_events(&mut stepper),
+        vec![InterestHintEvent {
+            entity: NetworkEntityId(server_entity),
+            outcome: InterestHintOutcome::Denied,
+        }]
+    );
+    assert!(client_entity(&stepper, server_entity).is_none());
+}
@@ -0,0 +1,82 @@
+//! Tests of the keyed collections that are replicated by sending only the keys that changed
+use bevy::prelude::*;
+use bevy::utils::Duration;
+
+use crate::prelude::server::Replicate;
+use crate::prelude::*;
+use crate::tests::protocol::*;
+
+#[derive(Resource)]
+struct Moving(bool);
+
+/// Add and remove the same key on every tick, and update another slot
+fn update_inventory(
+    moving: Res<Moving>,
+    tick_manager: Res<TickManager>,
+    mut query: Query<&mut Component9>,
+) {
+    if !moving.0 {
+        return;
+    }
+    let tick = tick_manager.tick().0;
+    for mut inventory in query.iter_mut() {
+        if tick % 2 == 0 {
+            inventory.0.insert(1000, tick as u32);
+        } else {
+            inventory.0.remove(&1000);
+        }
+        inventory.0.insert(tick % 200, tick as u32);
+    }
+}
+
+fn client_value(pair: &LightyearTestPair, server_entity: Entity) -> Option<Component9> {
+    let client_entity = pair
+        .client_world(0)
+        .resource::<client::ConnectionManager>()
+        .replication_receiver
+        .remote_entity_map
+        .get_local(server_entity)?;
+    pair.client_world(0)
+        .get::<Component9>(*client_entity)
+        .cloned()
+}
+
+/// With packet loss and reordering, the client converges to the map of the server
+#[test]
+fn test_keyed_collection_converges_with_packet_loss() {
+    let mut pair = LightyearTestPair::builder()
+        .protocol(ProtocolPlugin)
+        .tick_duration(Duration::from_millis(10))
+        .conditioner(LinkConditionerConfig {
+            incoming_latency: Duration::from_millis(20),
+            incoming_jitter: Duration::from_millis(15),
+            incoming_loss: 0.2,
+            seed: None,
+        })
+        .build();
+    // the keyframes recover from the lost updates
+    pair.server_world_mut()
+        .resource_mut::<ComponentRegistry>()
+        .set_keyframes::<Component9>(KeyframeConfig::new(Duration::from_millis(100)));
+    pair.server_app
+        .insert_resource(Moving(true))
+        .add_systems(FixedUpdate, update_inventory);
+
+    let server_entity = pair
+        .server_world_mut()
+        .spawn((
+            Component9((0..200).map(|slot| (slot, 0)).collect()),
+            DeltaCompression::<Component9>::default(),
+            Replicate::default(),
+        ))
+        .id();
+    pair.frame_steps(200);
+    assert!(client_value(&pair, server_entity).is_some());
+
+    pair.server_app.insert_resource(Moving(false));
+    pair.frame_steps(50);
+    assert_eq!(
+        client_value(&pair, server_entity).as_ref(),
+        pair.server_world().get::<Component9>(server_entity)
+    );
+}
@@ -0,0 +1,248 @@
+//! Tests of the server API to kick and ban clients
+use bevy::ecs::system::RunSystemOnce;
+use bevy::prelude::*;
+
+use crate::connection::server::DeniedReason;
+use crate::prelude::client::{
+    ClientCommands, ConnectError, ConnectionFailedEvent, NetworkingState,
+};
+use crate::prelude::server::{ControlledBy, KickPolicy, Lifetime, Replicate, ServerError};
+use crate::prelude::*;
+use crate::tests::protocol::*;
+
+#[derive(Resource, Default)]
+struct ServerDisconnections(Vec<(ClientId, server::DisconnectReason)>);
+
+fn record_server_disconnections(
+    mut disconnections: ResMut<ServerDisconnections>,
+    mut events: EventReader<server::DisconnectEvent>,
+) {
+    disconnections.0.extend(
+        events
+            .read()
+            .map(|event| (event.client_id, event.reason.clone())),
+    );
+}
+
+#[derive(Resource, Default)]
+struct ClientEvents {
+    kicks: Vec<Option<String>>,
+    failures: Vec<ConnectError>,
+}
+
+fn record_client_events(
+    mut events: ResMut<ClientEvents>,
+    mut disconnections: EventReader<client::DisconnectEvent>,
+    mut failures: EventReader<ConnectionFailedEvent>,
+) {
+    events.kicks.extend(
+        disconnections
+            .read()
+            .filter_map(|event| match &event.reason {
+                Some(client::DisconnectReason::Kicked(reason)) => Some(reason.clone()),
+                _ => None,
+            }),
+    );
+    events
+        .failures
+        .extend(failures.read().map(|event| event.error.clone()));
+}
+
+fn build_pair(kick_policy: KickPolicy) -> LightyearTestPair {
+    let mut pair = LightyearTestPair::builder()
+        .protocol(ProtocolPlugin)
+        .build_disconnected();
+    pair.server_world_mut()
+        .resource_mut::<server::ServerConfig>()
+        .kick_policy = kick_policy;
+    pair.server_app
+        .init_resource::<ServerDisconnections>()
+        .add_systems(Update, record_server_disconnections);
+    pair.client_apps[0]
+        .init_resource::<ClientEvents>()
+        .add_systems(Update, record_client_events);
+    pair.connect();
+    pair
+}
+
+/// Entities controlled by the client with each [`Lifetime`], and an entity only replicated to the client
+struct Entities {
+    session_based: Entity,
+    persistent: Entity,
+    replicated: Entity,
+}
+
+fn spawn_entities(pair: &mut LightyearTestPair) -> Entities {
+    let client_id = pair.client_id(0);
+    let mut spawn_controlled = |lifetime| {
+        pair.server_world_mut()
+            .spawn(Replicate {
+                controlled_by: ControlledBy {
+                    target: NetworkTarget::Single(client_id),
+                    lifetime,
+                    ..default()
+                },
+                ..default()
+            })
+            .id()
+    };
+    let session_based = spawn_controlled(Lifetime::SessionBased);
+    let persistent = spawn_controlled(Lifetime::Persistent);
+    let replicated = pair
+        .server_world_mut()
+        .spawn(Replicate {
+            target: ReplicationTarget {
+                target: NetworkTarget::Single(client_id),
+            },
+            ..default()
+        })
+        .id();
+    pair.frame_steps(5);
+    Entities {
+        session_based,
+        persistent,
+        replicated,
+    }
+}
+
+fn networking_state(pair: &LightyearTestPair) -> NetworkingState {
+    *pair
+        .client_world(0)
+        .resource::<State<NetworkingState>>()
+        .get()
+}
+
+/// Start connecting the client, and step until the connection attempt succeeds or fails
+fn reconnect(pair: &mut LightyearTestPair) {
+    pair.client_world_mut(0)
+        .run_system_once(|mut commands: Commands| commands.connect_client());
+    pair.frame_step();
+    for _ in 0..100 {
+        if networking_state(pair) != NetworkingState::Connecting {
+            return;
+        }
+        pair.frame_step();
+    }
+}
+
+/// The kicked client receives the reason, and all the entities that it controlled are despawned
+#[test]
+fn test_kick_despawns_owned() {
+    let mut pair = build_pair(KickPolicy::DespawnOwned);
+    let client_id = pair.client_id(0);
+    let entities = spawn_entities(&mut pair);
+
+    pair.server_world_mut()
+        .resource_mut::<server::ConnectionManager>()
+        .disconnect_client(client_id, Some("cheating".to_string()))
+        .unwrap();
+    pair.frame_steps(5);
+
+    assert_eq!(
+        pair.server_world().resource::<ServerDisconnections>().0,
+        vec![(
+            client_id,
+            server::DisconnectReason::ServerKicked(Some("cheating".to_string()))
+        )]
+    );
+    assert_eq!(
+        pair.client_world(0).resource::<ClientEvents>().kicks,
+        vec![Some("cheating".to_string())]
+    );
+    assert_eq!(networking_state(&pair), NetworkingState::Disconnected);
+    let server_world = pair.server_world();
+    assert!(server_world
+        .resource::<server::ConnectionManager>()
+        .connection(client_id)
+        .is_err());
+    // the persistent entities are despawned as well
+    assert!(server_world.get_entity(entities.session_based).is_none());
+    assert!(server_world.get_entity(entities.persistent).is_none());
+    // the client is removed from the replication targets
+    assert_eq!(
+        server_world
+            .get::<ReplicationTarget>(entities.replicated)
+            .unwrap()
+            .target,
+        NetworkTarget::None
+    );
+
+    // the client is not connected anymore
+    assert!(matches!(
+        pair.server_world_mut()
+            .resource_mut::<server::ConnectionManager>()
+            .disconnect_client(client_id, None),
+        Err(ServerError::ClientIdNotFound(_))
+    ));
+}
+
+/// The entities controlled by the kicked client are kept, but not controlled by the client anymore
+#[test]
+fn test_kick_keeps_owned() {
+    let mut pair = build_pair(KickPolicy::KeepOwned);
+    let client_id = pair.client_id(0);
+    let entities = spawn_entities(&mut pair);
+
+    pair.server_world_mut()
+        .resource_mut::<server::ConnectionManager>()
+        .disconnect_client(client_id, None)
+        .unwrap();
+    pair.frame_steps(5);
+
+    assert_eq!(
+        pair.server_world().resource::<ServerDisconnections>().0,
+        vec![(client_id, server::DisconnectReason::ServerKicked(None))]
+    );
+    for entity in [entities.session_based, entities.persistent] {
+        assert_eq!(
+            pair.server_world()
+                .get::<ControlledBy>(entity)
+                .unwrap()
+                .target,
+            NetworkTarget::None
+        );
+    }
+}
+
+/// A banned client is disconnected, and cannot connect again until it is unbanned
+#[test]
+fn test_ban() {
+    let mut pair = build_pair(KickPolicy::default());
+    let client_id = pair.client_id(0);
+
+    pair.server_world_mut()
+        .resource_mut::<server::ConnectionManager>()
+        .ban_client(client_id, Some("banned".to_string()));
+    pair.frame_steps(5);
+    assert_eq!(
+        pair.client_world(0).resource::<ClientEvents>().kicks,
+        vec![Some("banned".to_string())]
+    );
+    // the deny list is shared with the server config, so that it is kept when the server restarts
+    assert!(pair
+        .server_world()
+        .resource::<server::ServerConfig>()
+        .deny_list
+        .contains(client_id));
+
+    reconnect(&mut pair);
+    assert_eq!(networking_state(&pair), NetworkingState::Disconnected);
+    assert_eq!(
+        pair.client_world(0).resource::<ClientEvents>().failures,
+        vec![ConnectError::Denied(DeniedReason::Banned)]
+    );
+    assert_eq!(
+        pair.server_world()
+            .resource::<ServerDisconnections>()
+            .0
+            .len(),
+        1
+    );
+
+    assert!(pair
+        .server_world_mut()
+        .resource_mut::<server::ConnectionManager>()
+        .unban_client(client_id));
+    reconnect(&mut pair);
+    assert_eq!(networking_state(&pair), NetworkingState::Connected);
+}
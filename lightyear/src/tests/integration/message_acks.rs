@@ -0,0 +1,166 @@
+//! Tests of the [`MessageAckEvent`](crate::prelude::server::MessageAckEvent)s emitted when the remote acks a message
+use bevy::prelude::*;
+use bevy::utils::Duration;
+
+use crate::prelude::*;
+use crate::tests::protocol::*;
+
+/// Number of bytes of the big messages: about 30 fragments
+const BIG_MESSAGE_LEN: usize = 35_000;
+
+#[derive(Resource, Default)]
+struct ClientRecorded {
+    messages: Vec<String>,
+    acks: Vec<client::MessageAckEvent>,
+}
+
+fn record_client(
+    mut recorded: ResMut<ClientRecorded>,
+    mut messages: EventReader<client::MessageEvent<Message1>>,
+    mut acks: EventReader<client::MessageAckEvent>,
+) {
+    recorded
+        .messages
+        .extend(messages.read().map(|event| event.message().0.clone()));
+    recorded.acks.extend(acks.read().copied());
+}
+
+#[derive(Resource, Default)]
+struct ServerRecorded {
+    messages: Vec<String>,
+    acks: Vec<server::MessageAckEvent>,
+}
+
+fn record_server(
+    mut recorded: ResMut<ServerRecorded>,
+    mut messages: EventReader<server::MessageEvent<Message1>>,
+    mut acks: EventReader<server::MessageAckEvent>,
+) {
+    recorded
+        .messages
+        .extend(messages.read().map(|event| event.message().0.clone()));
+    recorded.acks.extend(acks.read().copied());
+}
+
+fn build_pair(incoming_loss: f32) -> LightyearTestPair {
+    let mut pair = LightyearTestPair::builder()
+        .protocol(ProtocolPlugin)
+        .tick_duration(Duration::from_millis(10))
+        .conditioner(LinkConditionerConfig {
+            incoming_latency: Duration::from_millis(20),
+            incoming_jitter: Duration::from_millis(0),
+            incoming_loss,
+            seed: None,
+        })
+        .build_disconnected();
+    pair.client_apps[0]
+        .init_resource::<ClientRecorded>()
+        .add_systems(Update, record_client);
+    pair.server_app
+        .init_resource::<ServerRecorded>()
+        .add_systems(Update, record_server);
+    pair.connect();
+    pair
+}
+
+/// The server is notified when the client acks a message sent on a reliable channel,
+/// and the messages of the internal channels are not reported
+#[test]
+fn test_server_message_ack() {
+    let mut pair = build_pair(0.0);
+    let client_id = pair.client_id(0);
+    pair.frame_steps(20);
+    pair.server_world_mut()
+        .resource_mut::<ServerRecorded>()
+        .acks
+        .clear();
+
+    let message_id = pair
+        .server_world_mut()
+        .resource_mut::<server::ConnectionManager>()
+        .send_message::<Channel3, _>(client_id, &Message1("a".to_string()))
+        .unwrap()
+        .unwrap();
+    pair.frame_steps(20);
+
+    assert_eq!(
+        pair.client_world(0).resource::<ClientRecorded>().messages,
+        vec!["a".to_string()]
+    );
+    assert_eq!(
+        pair.server_world().resource::<ServerRecorded>().acks,
+        vec![server::MessageAckEvent {
+            client_id,
+            message_id,
+            channel_kind: ChannelKind::of::<Channel3>(),
+        }]
+    );
+
+    // nothing is sent to a client that is not connected
+    assert_eq!(
+        pair.server_world_mut()
+            .resource_mut::<server::ConnectionManager>()
+            .send_message::<Channel3, _>(ClientId::Netcode(u64::MAX), &Message1("b".to_string()))
+            .unwrap(),
+        None
+    );
+}
+
+/// A fragmented message sent by the client over a lossy link is only reported as acked
+/// once all its fragments were acked, i.e. after the server received the message
+#[test]
+fn test_client_fragmented_message_ack() {
+    let mut pair = build_pair(0.2);
+    pair.frame_steps(20);
+    pair.client_world_mut(0)
+        .resource_mut::<ClientRecorded>()
+        .acks
+        .clear();
+
+    let message = "a".repeat(BIG_MESSAGE_LEN);
+    let message_id = pair
+        .client_world_mut(0)
+        .resource_mut::<client::ConnectionManager>()
+        .send_message::<Channel3, _>(&Message1(message.clone()))
+        .unwrap()
+        .unwrap();
+    let mut received_frame = None;
+    let mut acked_frame = None;
+    for frame in 0..300 {
+        pair.frame_step();
+        if received_frame.is_none()
+            && !pair
+                .server_world()
+                .resource::<ServerRecorded>()
+                .messages
+                .is_empty()
+        {
+            received_frame = Some(frame);
+        }
+        if acked_frame.is_none()
+            && !pair
+                .client_world(0)
+                .resource::<ClientRecorded>()
+                .acks
+                .is_empty()
+        {
+            acked_frame = Some(frame);
+        }
+    }
+
+    assert_eq!(
+        pair.server_world().resource::<ServerRecorded>().messages,
+        vec![message]
+    );
+    assert_eq!(
+        pair.client_world(0).resource::<ClientRecorded>().acks,
+        vec![client::MessageAckEvent {
+            message_id,
+            channel_kind: ChannelKind::of::<Channel3>(),
+        }]
+    );
+    assert!(
+        received_frame.unwrap() < acked_frame.unwrap(),
+        "received at {received_frame:?}, acked at {acked_frame:?}"
+    );
+}
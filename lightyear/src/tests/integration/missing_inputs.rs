@@ -0,0 +1,126 @@
+//! Tests of the prediction of the missing inputs on the server, and of the inputs that arrive too late
+use bevy::prelude::*;
+use bevy::utils::Duration;
+
+use crate::prelude::client::{InputManager, InputSystemSet};
+use crate::prelude::server::{InputEvent, LateInputEvent, MissingInputPolicy, ServerConfig};
+use crate::prelude::*;
+use crate::tests::protocol::*;
+
+/// Every tick, the client adds the same input
+fn buffer_inputs(mut input_manager: ResMut<InputManager<MyInput>>, tick_manager: Res<TickManager>) {
+    input_manager.add_input(MyInput(1), tick_manager.tick());
+}
+
+/// Inputs applied by the server on each tick, with the predicted flag
+#[derive(Resource, Default)]
+struct AppliedInputs(Vec<(Tick, Option<MyInput>, bool)>);
+
+fn record_inputs(
+    mut applied: ResMut<AppliedInputs>,
+    tick_manager: Res<TickManager>,
+    mut events: EventReader<InputEvent<MyInput>>,
+) {
+    let tick = tick_manager.tick();
+    applied.0.extend(
+        events
+            .read()
+            .map(|event| (tick, event.input().clone(), event.is_predicted())),
+    );
+}
+
+#[derive(Resource, Default)]
+struct LateInputs(Vec<LateInputEvent>);
+
+fn record_late_inputs(mut late: ResMut<LateInputs>, mut events: EventReader<LateInputEvent>) {
+    late.0.extend(events.read().copied());
+}
+
+/// With packet loss, some inputs arrive after their tick was simulated: the server predicts them
+/// according to the policy, then emits a [`LateInputEvent`] when they arrive
+fn run_with_loss(policy: MissingInputPolicy) -> LightyearTestPair {
+    let mut pair = LightyearTestPair::builder()
+        .protocol(ProtocolPlugin)
+        .tick_duration(Duration::from_millis(10))
+        .conditioner(LinkConditionerConfig {
+            incoming_latency: Duration::from_millis(20),
+            incoming_jitter: Duration::from_millis(0),
+            incoming_loss: 0.4,
+            seed: None,
+        })
+        .server_config(ServerConfig {
+            missing_input: policy,
+            ..default()
+        })
+        .build_disconnected();
+    pair.client_apps[0].add_systems(
+        FixedPreUpdate,
+        buffer_inputs.in_set(InputSystemSet::BufferInputs),
+    );
+    pair.server_app
+        .init_resource::<AppliedInputs>()
+        .init_resource::<LateInputs>()
+        .add_systems(FixedUpdate, record_inputs)
+        .add_systems(Update, record_late_inputs);
+    pair.connect();
+    pair.frame_steps(100);
+    pair.server_world_mut()
+        .resource_mut::<AppliedInputs>()
+        .0
+        .clear();
+    pair.server_world_mut()
+        .resource_mut::<LateInputs>()
+        .0
+        .clear();
+    pair.frame_steps(500);
+    // the ticks predicted before the clear, while the client was still starting to send its inputs,
+    // can have their real input arrive after the clear
+    let first_tick = pair.server_world().resource::<AppliedInputs>().0[0].0;
+    pair.server_world_mut()
+        .resource_mut::<LateInputs>()
+        .0
+        .retain(|event| event.tick >= first_tick);
+    pair
+}
+
+#[test]
+fn test_repeat_last_input() {
+    let pair = run_with_loss(MissingInputPolicy::RepeatLast);
+    let client_id = pair.client_id(0);
+    let applied = &pair.server_world().resource::<AppliedInputs>().0;
+    let late = &pair.server_world().resource::<LateInputs>().0;
+
+    // the predicted ticks repeat the last input, which is the same as the real input
+    assert!(applied.iter().any(|(_, _, predicted)| *predicted));
+    assert!(applied
+        .iter()
+        .all(|(_, input, _)| input == &Some(MyInput(1))));
+    assert!(!late.is_empty());
+    for event in late {
+        assert_eq!(event.client_id, client_id);
+        assert!(event.matched, "{event:?}");
+        assert!(applied
+            .iter()
+            .any(|(tick, _, predicted)| *tick == event.tick && *predicted));
+    }
+}
+
+#[test]
+fn test_no_predicted_input() {
+    let pair = run_with_loss(MissingInputPolicy::None);
+    let applied = &pair.server_world().resource::<AppliedInputs>().0;
+    let late = &pair.server_world().resource::<LateInputs>().0;
+
+    // the predicted ticks have no input, which is different from the real input
+    assert!(applied.iter().any(|(_, _, predicted)| *predicted));
+    for (tick, input, predicted) in applied {
+        assert_eq!(input.is_none(), *predicted, "{tick:?}");
+    }
+    assert!(!late.is_empty());
+    for event in late {
+        assert!(!event.matched, "{event:?}");
+        assert!(applied
+            .iter()
+            .any(|(tick, _, predicted)| *tick == event.tick && *predicted));
+    }
+}
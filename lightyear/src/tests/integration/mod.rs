@@ -1,2 +1,53 @@
+mod action_resolution;
+mod behavior_analysis;
+mod channel_settings;
+mod cleanup_policy;
+mod client_apply;
+mod client_conditioner;
+mod client_spawn_quotas;
+mod coalesced_updates;
+mod compact_header;
+mod compression;
+mod connect_attempts;
+mod connection_stats;
+mod dedup;
+mod deprecated_kinds;
+mod disconnect_reason;
+mod duplicate_client_id;
+mod entity_aliases;
+mod entity_mapping;
+mod fixed_point;
+mod fragmentation;
+mod handoff;
+mod headless;
+mod input_jitter;
+mod input_redundancy;
+mod interest_hints;
+mod keyed_collections;
+mod kick_ban;
+mod message_acks;
+mod missing_inputs;
 mod multi_transport;
+mod parallel_apply;
+mod prespawn_ids;
+mod prespawn_match;
+mod priority_interest;
+mod receive_queue;
+mod replicate_mutations;
+mod replicated_lifetime;
+mod replication_changes;
+mod replication_classes;
+mod replication_groups;
+mod replication_limits;
+mod replication_predicates;
+mod requests;
+mod rollback_window;
+mod schema_evolution;
+mod serialized_size;
+mod session_summary;
+mod tick_debt;
+mod tick_rate;
 mod tick_wrapping;
+mod timeline_messages;
+mod transfers;
+mod transport_migration;
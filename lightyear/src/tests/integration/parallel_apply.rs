@@ -0,0 +1,118 @@
+//! Tests of the parallel deserialization of the replication received by the client
+use bevy::prelude::*;
+use bevy::utils::Duration;
+
+use crate::prelude::client::{ClientConfig, ConnectionManager};
+use crate::prelude::server::Replicate;
+use crate::prelude::*;
+use crate::tests::protocol::*;
+use crate::tests::stepper::{BevyStepper, Step};
+
+const NUM_ENTITIES: usize = 200;
+
+fn setup(parallel_apply: ParallelApplyConfig) -> BevyStepper {
+    let frame_duration = Duration::from_millis(10);
+    let shared_config = SharedConfig {
+        tick: TickConfig::new(frame_duration),
+        ..default()
+    };
+    let client_config = ClientConfig {
+        replication: ReplicationConfig {
+            parallel_apply,
+            ..default()
+        },
+        ..default()
+    };
+    let mut stepper = BevyStepper::new(shared_config, client_config, frame_duration);
+    stepper.init();
+    stepper
+}
+
+fn client_entity(stepper: &BevyStepper, server_entity: Entity) -> Entity {
+    *stepper
+        .client_app
+        .world()
+        .resource::<ConnectionManager>()
+        .replication_receiver
+        .remote_entity_map
+        .get_local(server_entity)
+        .expect("entity was not replicated to client")
+}
+
+/// Spawn pairs of entities in the same replication group, where the second entity of the pair
+/// refers to the first one, and check that the client receives the same state
+fn check_replication(parallel_apply: ParallelApplyConfig) {
+    let mut stepper = setup(parallel_apply);
+    let pairs: Vec<(Entity, Entity)> = (0..NUM_ENTITIES)
+        .map(|i| {
+            let replicate = Replicate {
+                group: ReplicationGroup::new_id(i as u64),
+                ..default()
+            };
+            let target = stepper
+                .server_app
+                .world_mut()
+                .spawn((Component1(i as f32), replicate.clone()))
+                .id();
+            let holder = stepper
+                .server_app
+                .world_mut()
+                .spawn((Component1(-(i as f32)), Component4(target), replicate))
+                .id();
+            (target, holder)
+        })
+        .collect();
+    stepper.frame_step();
+    stepper.frame_step();
+
+    for (i, (target, holder)) in pairs.iter().enumerate() {
+        let client_target = client_entity(&stepper, *target);
+        let client_holder = client_entity(&stepper, *holder);
+        let world = stepper.client_app.world();
+        assert_eq!(
+            world.get::<Component1>(client_target),
+            Some(&Component1(i as f32))
+        );
+        assert_eq!(
+            world.get::<Component1>(client_holder),
+            Some(&Component1(-(i as f32)))
+        );
+        // the entity is mapped even though it was spawned by the same message
+        assert_eq!(
+            world.get::<Component4>(client_holder),
+            Some(&Component4(client_target))
+        );
+    }
+
+    // updates are decoded in parallel as well
+    for (i, (target, _)) in pairs.iter().enumerate() {
+        stepper
+            .server_app
+            .world_mut()
+            .get_mut::<Component1>(*target)
+            .unwrap()
+            .0 = (i * 10) as f32;
+    }
+    stepper.frame_step();
+    stepper.frame_step();
+    for (i, (target, _)) in pairs.iter().enumerate() {
+        let client_target = client_entity(&stepper, *target);
+        assert_eq!(
+            stepper.client_app.world().get::<Component1>(client_target),
+            Some(&Component1((i * 10) as f32))
+        );
+    }
+}
+
+#[test]
+fn test_parallel_apply() {
+    check_replication(ParallelApplyConfig {
+        min_payloads: 0,
+        payloads_per_task: 16,
+    });
+}
+
+#[test]
+fn test_parallel_apply_disabled() {
+    check_replication(ParallelApplyConfig::disabled());
+}
@@ -0,0 +1,266 @@
+//! Tests of the partitioning of the pre-spawned entity ids between the server and the clients
+use bevy::ecs::system::RunSystemOnce;
+use bevy::prelude::*;
+
+use crate::prelude::client::{ClientCommands, Confirmed, NetworkingState, Predicted};
+use crate::prelude::server::{ClientUpdateViolations, PreSpawnIdRanges, Replicate, SyncTarget};
+use crate::prelude::*;
+use crate::tests::protocol::*;
+use crate::tests::stepper::{BevyStepper, Step, TEST_CLIENT_ID};
+
+fn client_range(stepper: &BevyStepper) -> Option<PreSpawnIdRange> {
+    stepper
+        .client_app
+        .world()
+        .resource::<PreSpawnIdAllocator>()
+        .range()
+}
+
+fn server_range(stepper: &BevyStepper) -> Option<PreSpawnIdRange> {
+    stepper
+        .server_app
+        .world()
+        .resource::<PreSpawnIdRanges>()
+        .range(ClientId::Netcode(TEST_CLIENT_ID))
+}
+
+fn allocate_client_id(stepper: &mut BevyStepper) -> u64 {
+    stepper
+        .client_app
+        .world_mut()
+        .resource_mut::<PreSpawnIdAllocator>()
+        .allocate()
+        .unwrap()
+}
+
+/// The client receives its range when it connects
+#[test]
+fn test_range_sent_on_connect() {
+    let stepper = BevyStepper::default();
+    let range = client_range(&stepper).expect("the client did not receive a range");
+    assert_eq!(Some(range), server_range(&stepper));
+    assert_ne!(range.index(), 0);
+}
+
+/// Similar entities spawned on the same tick are matched using their ids instead of their hashes
+#[test]
+fn test_match_with_adopted_ids() {
+    let mut stepper = BevyStepper::default();
+    let client_id = ClientId::Netcode(TEST_CLIENT_ID);
+    let id_1 = allocate_client_id(&mut stepper);
+    let id_2 = allocate_client_id(&mut stepper);
+    let client_entity_1 = stepper
+        .client_app
+        .world_mut()
+        .spawn((Component1(1.0), PreSpawnedPlayerObject::new(id_1)))
+        .id();
+    let client_entity_2 = stepper
+        .client_app
+        .world_mut()
+        .spawn((Component1(1.0), PreSpawnedPlayerObject::new(id_2)))
+        .id();
+    stepper.frame_step();
+
+    // the server accepts the actions of the client and spawns the entities in a different order
+    let ranges = stepper.server_app.world().resource::<PreSpawnIdRanges>();
+    let (server_id_1, server_id_2) = (
+        ranges.adopt(client_id, id_1).unwrap(),
+        ranges.adopt(client_id, id_2).unwrap(),
+    );
+    let server_entity_2 = stepper
+        .server_app
+        .world_mut()
+        .spawn((
+            Component1(1.0),
+            PreSpawnedPlayerObject::new(server_id_2),
+            Replicate {
+                sync: SyncTarget {
+                    prediction: NetworkTarget::All,
+                    ..default()
+                },
+                ..default()
+            },
+        ))
+        .id();
+    let server_entity_1 = stepper
+        .server_app
+        .world_mut()
+        .spawn((
+            Component1(1.0),
+            PreSpawnedPlayerObject::new(server_id_1),
+            Replicate {
+                sync: SyncTarget {
+                    prediction: NetworkTarget::All,
+                    ..default()
+                },
+                ..default()
+            },
+        ))
+        .id();
+    for _ in 0..5 {
+        stepper.frame_step();
+    }
+
+    for (client_entity, server_entity) in [
+        (client_entity_1, server_entity_1),
+        (client_entity_2, server_entity_2),
+    ] {
+        let confirmed_entity = stepper
+            .client_app
+            .world()
+            .resource::<client::ConnectionManager>()
+            .replication_receiver
+            .remote_entity_map
+            .get_local(server_entity)
+            .copied()
+            .expect("entity was not replicated to the client");
+        assert_eq!(
+            stepper
+                .client_app
+                .world()
+                .get::<Predicted>(client_entity)
+                .unwrap()
+                .confirmed_entity,
+            Some(confirmed_entity)
+        );
+        assert_eq!(
+            stepper
+                .client_app
+                .world()
+                .get::<Confirmed>(confirmed_entity)
+                .unwrap()
+                .predicted,
+            Some(client_entity)
+        );
+    }
+}
+
+/// A client cannot use an id outside of its range
+#[test]
+fn test_reject_ids_outside_of_range() {
+    let mut stepper = BevyStepper::default();
+    let client_id = ClientId::Netcode(TEST_CLIENT_ID);
+    let server_id = stepper
+        .server_app
+        .world_mut()
+        .resource_mut::<PreSpawnIdRanges>()
+        .allocate()
+        .unwrap();
+    assert!(matches!(
+        stepper
+            .server_app
+            .world()
+            .resource::<PreSpawnIdRanges>()
+            .adopt(client_id, server_id),
+        Err(PreSpawnIdError::OutOfRange { .. })
+    ));
+
+    // the client replicates an entity that claims an id from the server's range
+    let own_id = allocate_client_id(&mut stepper);
+    let foreign = stepper
+        .client_app
+        .world_mut()
+        .spawn((
+            client::Replicate::default(),
+            Component1(1.0),
+            PreSpawnedPlayerObject::new(server_id),
+        ))
+        .id();
+    let own = stepper
+        .client_app
+        .world_mut()
+        .spawn((
+            client::Replicate::default(),
+            Component1(2.0),
+            PreSpawnedPlayerObject::new(own_id),
+        ))
+        .id();
+    // the client despawns its pre-spawned entities if they are not matched with a server entity,
+    // so only step until they are replicated
+    let mut server_entities = None;
+    for _ in 0..10 {
+        stepper.frame_step();
+        let entity_map = &stepper
+            .server_app
+            .world()
+            .resource::<server::ConnectionManager>()
+            .connection(client_id)
+            .unwrap()
+            .replication_receiver
+            .remote_entity_map;
+        if let (Some(server_foreign), Some(server_own)) =
+            (entity_map.get_local(foreign), entity_map.get_local(own))
+        {
+            server_entities = Some((*server_foreign, *server_own));
+            break;
+        }
+    }
+    let (server_foreign, server_own) =
+        server_entities.expect("the entities were not replicated to the server");
+    assert!(stepper
+        .server_app
+        .world()
+        .get::<PreSpawnedPlayerObject>(server_foreign)
+        .is_none());
+    assert_eq!(
+        stepper
+            .server_app
+            .world()
+            .get::<PreSpawnedPlayerObject>(server_own)
+            .and_then(|prespawn| prespawn.hash),
+        Some(own_id)
+    );
+    assert_eq!(
+        stepper
+            .server_app
+            .world()
+            .resource::<ClientUpdateViolations>()
+            .get(client_id)
+            .rejected,
+        1
+    );
+}
+
+/// A reconnecting client gets a new range, and cannot use the ids of its previous range anymore
+#[test]
+fn test_reconnect() {
+    let mut stepper = BevyStepper::default();
+    let client_id = ClientId::Netcode(TEST_CLIENT_ID);
+    let previous_range = client_range(&stepper).unwrap();
+    let previous_id = allocate_client_id(&mut stepper);
+
+    stepper
+        .client_app
+        .world_mut()
+        .run_system_once(|mut commands: Commands| commands.disconnect_client());
+    for _ in 0..10 {
+        stepper.frame_step();
+    }
+    assert_eq!(client_range(&stepper), None);
+    assert_eq!(server_range(&stepper), None);
+
+    stepper
+        .client_app
+        .world_mut()
+        .run_system_once(|mut commands: Commands| commands.connect_client());
+    for _ in 0..50 {
+        stepper.frame_step();
+    }
+    assert_eq!(
+        stepper
+            .client_app
+            .world()
+            .resource::<State<NetworkingState>>()
+            .get(),
+        &NetworkingState::Connected
+    );
+    let range = client_range(&stepper).expect("the client did not receive a range");
+    assert_eq!(Some(range), server_range(&stepper));
+    assert_ne!(range, previous_range);
+    assert!(stepper
+        .server_app
+        .world()
+        .resource::<PreSpawnIdRanges>()
+        .adopt(client_id, previous_id)
+        .is_err());
+}
@@ -0,0 +1,208 @@
+//! Tests of the matching of the entities pre-spawned by the client with the entities spawned by the server,
+//! using the default hash (spawn tick + components)
+use bevy::prelude::*;
+
+use crate::client::prediction::resource::PredictionManager;
+use crate::prelude::client::{
+    Confirmed, InputManager, InputSystemSet, Predicted, PredictionConfig,
+};
+use crate::prelude::server::{Replicate, SyncTarget};
+use crate::prelude::*;
+use crate::tests::protocol::*;
+
+/// Shoot a single bullet on the next tick
+#[derive(Resource)]
+struct Shoot(bool);
+
+/// The input that shoots a bullet. The other ticks have an explicit input, because the server repeats the last
+/// input on the ticks without inputs
+const SHOOT: MyInput = MyInput(1);
+
+fn buffer_inputs(
+    mut shoot: ResMut<Shoot>,
+    mut input_manager: ResMut<InputManager<MyInput>>,
+    tick_manager: Res<TickManager>,
+) {
+    let input = if std::mem::take(&mut shoot.0) {
+        SHOOT
+    } else {
+        MyInput(0)
+    };
+    input_manager.add_input(input, tick_manager.tick());
+}
+
+/// The client spawns the bullet as soon as the input is applied
+fn client_shoot(mut commands: Commands, mut events: EventReader<client::InputEvent<MyInput>>) {
+    for event in events.read() {
+        if event.input() == &Some(SHOOT) {
+            commands.spawn((Component1(1.0), PreSpawnedPlayerObject::default()));
+        }
+    }
+}
+
+/// Components of the bullets spawned by the server
+#[derive(Resource, Clone, Copy)]
+struct ServerBullet {
+    /// Spawn the bullet with a `Component2`, so that its hash doesn't match the hash of the client bullet
+    mismatch: bool,
+}
+
+fn server_shoot(
+    mut commands: Commands,
+    bullet: Res<ServerBullet>,
+    mut events: EventReader<server::InputEvent<MyInput>>,
+) {
+    for event in events.read() {
+        if event.input() != &Some(SHOOT) {
+            continue;
+        }
+        let mut entity_commands = commands.spawn((
+            Component1(1.0),
+            PreSpawnedPlayerObject::default(),
+            Replicate {
+                sync: SyncTarget {
+                    prediction: NetworkTarget::All,
+                    ..default()
+                },
+                ..default()
+            },
+        ));
+        if bullet.mismatch {
+            entity_commands.insert(Component2(1.0));
+        }
+    }
+}
+
+fn build_pair(
+    server_bullet: Option<ServerBullet>,
+    prediction: PredictionConfig,
+) -> LightyearTestPair {
+    let mut pair = LightyearTestPair::builder()
+        .protocol(ProtocolPlugin)
+        .client_config(client::ClientConfig {
+            prediction,
+            ..default()
+        })
+        .build_disconnected();
+    pair.client_apps[0]
+        .insert_resource(Shoot(false))
+        .add_systems(
+            FixedPreUpdate,
+            buffer_inputs.in_set(InputSystemSet::BufferInputs),
+        )
+        .add_systems(FixedUpdate, client_shoot);
+    if let Some(server_bullet) = server_bullet {
+        pair.server_app
+            .insert_resource(server_bullet)
+            .add_systems(FixedUpdate, server_shoot);
+    }
+    pair.connect();
+    pair.frame_steps(10);
+    pair
+}
+
+fn shoot(pair: &mut LightyearTestPair) {
+    pair.client_world_mut(0).resource_mut::<Shoot>().0 = true;
+}
+
+/// The bullets of the client, whether they are pre-spawned, predicted or confirmed
+fn client_bullets(pair: &mut LightyearTestPair) -> Vec<Entity> {
+    pair.client_world_mut(0)
+        .query_filtered::<Entity, With<Component1>>()
+        .iter(pair.client_world(0))
+        .collect()
+}
+
+/// The bullet spawned by the server is matched with the bullet pre-spawned by the client, which becomes its
+/// predicted entity instead of being duplicated
+#[test]
+fn test_prespawned_entity_matched() {
+    let mut pair = build_pair(
+        Some(ServerBullet { mismatch: false }),
+        PredictionConfig::default(),
+    );
+    shoot(&mut pair);
+    pair.frame_step();
+    let bullets = client_bullets(&mut pair);
+    assert_eq!(bullets.len(), 1);
+    let prespawned = bullets[0];
+    assert!(pair
+        .client_world(0)
+        .get::<PreSpawnedPlayerObject>(prespawned)
+        .is_some());
+
+    pair.frame_steps(30);
+    let predicted = pair.client_world(0).get::<Predicted>(prespawned).unwrap();
+    let confirmed = predicted.confirmed_entity.unwrap();
+    assert!(pair
+        .client_world(0)
+        .get::<PreSpawnedPlayerObject>(prespawned)
+        .is_none());
+    assert_eq!(
+        pair.client_world(0)
+            .get::<Confirmed>(confirmed)
+            .unwrap()
+            .predicted,
+        Some(prespawned)
+    );
+    let mut bullets = client_bullets(&mut pair);
+    bullets.sort();
+    let mut expected = vec![prespawned, confirmed];
+    expected.sort();
+    assert_eq!(bullets, expected);
+}
+
+/// A pre-spawned bullet that the server never spawns is despawned
+#[test]
+fn test_unmatched_prespawned_entity_cleaned_up() {
+    let mut pair = build_pair(None, PredictionConfig::default());
+    shoot(&mut pair);
+    pair.frame_step();
+    assert_eq!(client_bullets(&mut pair).len(), 1);
+
+    pair.frame_steps(50);
+    assert!(client_bullets(&mut pair).is_empty());
+    assert!(pair
+        .client_world(0)
+        .resource::<PredictionManager>()
+        .prespawn_hash_to_entities
+        .is_empty());
+}
+
+/// The unmatched pre-spawned entities can be despawned after a fixed number of ticks
+#[test]
+fn test_prespawn_cleanup_ticks() {
+    let mut pair = build_pair(
+        None,
+        PredictionConfig::default().with_prespawn_cleanup_ticks(100),
+    );
+    shoot(&mut pair);
+    pair.frame_step();
+    pair.frame_steps(90);
+    assert_eq!(client_bullets(&mut pair).len(), 1);
+    pair.frame_steps(20);
+    assert!(client_bullets(&mut pair).is_empty());
+}
+
+/// When the bullet spawned by the server doesn't match the pre-spawned bullet, the server bullet is predicted
+/// normally and the pre-spawned bullet is despawned: only one bullet is left
+#[test]
+fn test_mismatched_prespawned_entity() {
+    let mut pair = build_pair(
+        Some(ServerBullet { mismatch: true }),
+        PredictionConfig::default(),
+    );
+    shoot(&mut pair);
+    pair.frame_step();
+    let prespawned = client_bullets(&mut pair)[0];
+
+    pair.frame_steps(50);
+    assert!(pair.client_world(0).get_entity(prespawned).is_none());
+    let predicted: Vec<_> = pair
+        .client_world_mut(0)
+        .query_filtered::<Entity, (With<Component1>, With<Predicted>)>()
+        .iter(pair.client_world(0))
+        .collect();
+    assert_eq!(predicted.len(), 1);
+    assert_eq!(client_bullets(&mut pair).len(), 2);
+}
@@ -0,0 +1,165 @@
+//! Tests related to per-entity priorities combined with interest management, under a bandwidth cap
+use bevy::prelude::*;
+use bevy::utils::{Duration, HashMap};
+
+use crate::prelude::client::{ClientConfig, SyncConfig};
+use crate::prelude::server::{Replicate, RoomId, RoomManager, ServerConfig};
+use crate::prelude::*;
+use crate::tests::protocol::*;
+
+const NUM_CLIENTS: u64 = 4;
+/// The grid is split in 4 rooms of `ROOM_WIDTH * ROOM_HEIGHT` entities
+const GRID_WIDTH: u32 = 50;
+const GRID_HEIGHT: u32 = 20;
+const ROOM_WIDTH: u32 = 25;
+const ROOM_HEIGHT: u32 = 10;
+const BANDWIDTH_CAP: u32 = 56_000;
+const WINDOW: Duration = Duration::from_secs(10);
+/// The closest entities must be updated at least this many times more often than the farthest ones
+const MIN_UPDATE_RATIO: f32 = 4.0;
+
+fn room(x: u32, y: u32) -> u64 {
+    (x / ROOM_WIDTH + 2 * (y / ROOM_HEIGHT)) as u64
+}
+
+/// Distance from the entity to the client of its room, who sits at the corner of the room
+fn distance(x: u32, y: u32) -> f32 {
+    ((x % ROOM_WIDTH) as f32).hypot((y % ROOM_HEIGHT) as f32)
+}
+
+fn max_distance() -> f32 {
+    distance(ROOM_WIDTH - 1, ROOM_HEIGHT - 1)
+}
+
+/// Every entity changes on every frame, so that the demand is much higher than the bandwidth cap
+fn update_entities(mut query: Query<&mut Component1, With<Replicating>>) {
+    for mut component in query.iter_mut() {
+        component.0 += 1.0;
+    }
+}
+
+fn build_pair() -> LightyearTestPair {
+    let mut pair = LightyearTestPair::builder()
+        .protocol(ProtocolPlugin)
+        .clients(NUM_CLIENTS as usize)
+        .tick_duration(Duration::from_millis(10))
+        .client_config(ClientConfig {
+            sync: SyncConfig::default().speedup_factor(1.0),
+            ..default()
+        })
+        .server_config(ServerConfig {
+            packet: server::PacketConfig::default()
+                .with_send_bandwidth_bytes_per_second_cap(BANDWIDTH_CAP)
+                .enable_bandwidth_cap(),
+            ..default()
+        })
+        .build_disconnected();
+    pair.server_app.add_systems(Update, update_entities);
+    pair.connect();
+    pair
+}
+
+fn receive_stats(pair: &LightyearTestPair, client_index: usize) -> &client::ReceiveStats {
+    pair.client_world(client_index)
+        .resource::<client::ConnectionManager>()
+        .receive_stats()
+}
+
+/// 1000 entities on a grid, split in 4 rooms with one client each.
+/// The priority of each entity decreases with its distance to the client of its room, and the
+/// server has a bandwidth cap per client.
+///
+/// This test checks that:
+/// - each client only receives the entities of its room
+/// - the closest entities are updated much more often than the farthest ones
+/// - no client receives more than its bandwidth cap
+#[test]
+fn test_priority_with_rooms() {
+    let mut pair = build_pair();
+    for index in 0..NUM_CLIENTS as usize {
+        let client_id = pair.client_id(index);
+        pair.server_world_mut()
+            .resource_mut::<RoomManager>()
+            .add_client(client_id, RoomId(index as u64));
+    }
+    for x in 0..GRID_WIDTH {
+        for y in 0..GRID_HEIGHT {
+            let entity = pair
+                .server_world_mut()
+                .spawn((
+                    Component1(0.0),
+                    Component3(distance(x, y)),
+                    Component5(room(x, y) as f32),
+                    Replicate {
+                        relevance_mode: NetworkRelevanceMode::InterestManagement,
+                        group: ReplicationGroup::default()
+                            .set_priority(1.0 / (1.0 + distance(x, y))),
+                        ..default()
+                    },
+                ))
+                .id();
+            pair.server_world_mut()
+                .resource_mut::<RoomManager>()
+                .add_entity(entity, RoomId(room(x, y)));
+        }
+    }
+
+    // let the spawns get replicated, and the bandwidth quota get used up
+    pair.frame_steps(200);
+    let start: Vec<(u64, HashMap<Entity, u32>)> = (0..NUM_CLIENTS as usize)
+        .map(|i| {
+            let stats = receive_stats(&pair, i);
+            (stats.bytes, stats.iter_entity_updates().collect())
+        })
+        .collect();
+    pair.frame_steps((WINDOW.as_millis() / pair.frame_duration.as_millis()) as usize);
+
+    let room_size = (ROOM_WIDTH * ROOM_HEIGHT) as usize;
+    for (i, (start_bytes, start_updates)) in start.into_iter().enumerate() {
+        let entities: Vec<(Entity, f32, f32)> = pair
+            .client_world_mut(i)
+            .query::<(Entity, &Component3, &Component5)>()
+            .iter(pair.client_world(i))
+            .map(|(entity, distance, room)| (entity, distance.0, room.0))
+            .collect();
+        // the client only received the entities of its room
+        assert_eq!(entities.len(), room_size);
+        assert!(entities.iter().all(|(_, _, room)| *room == i as f32));
+
+        // the closest entities were updated more often than the farthest ones
+        let stats = receive_stats(&pair, i);
+        let mean_updates = |filter: &dyn Fn(f32) -> bool| {
+            let updates: Vec<u32> = entities
+                .iter()
+                .filter(|(_, distance, _)| filter(*distance))
+                .map(|(entity, _, _)| {
+                    stats.entity_updates(*entity)
+                        - start_updates.get(entity).copied().unwrap_or_default()
+                })
+                .collect();
+            assert!(!updates.is_empty());
+            updates.iter().sum::<u32>() as f32 / updates.len() as f32
+        };
+        let closest = mean_updates(&|distance| distance < 3.0);
+        let farthest = mean_updates(&|distance| distance > max_distance() - 3.0);
+        assert!(
+            closest >= MIN_UPDATE_RATIO * farthest,
+            "client {i}: closest entities got {closest} updates, farthest entities got {farthest} updates"
+        );
+        // the farthest entities still get updated: their priority accumulates over time
+        assert!(farthest > 0.0);
+
+        // the bandwidth cap is respected; the quota can hold a few bytes at the start of the window
+        let bytes = stats.bytes - start_bytes;
+        let cap = BANDWIDTH_CAP as u64 * WINDOW.as_secs();
+        assert!(
+            bytes <= cap + BANDWIDTH_CAP as u64 / 10,
+            "client {i} received {bytes} bytes, cap is {cap} bytes"
+        );
+        // the demand is much higher than the cap, so the whole quota is used
+        assert!(
+            bytes >= cap * 8 / 10,
+            "client {i} received {bytes} bytes, cap is {cap} bytes"
+        );
+    }
+}
@@ -0,0 +1,110 @@
+//! Tests of the [`ReceiveQueue`](crate::packet::receive_queue::ReceiveQueue) between the netcode layer and the channels
+use bevy::prelude::*;
+use bevy::utils::Duration;
+
+use crate::prelude::client::ClientConfig;
+use crate::prelude::*;
+use crate::tests::protocol::*;
+
+/// Each message is big enough to be sent in its own packet
+const MESSAGE_LEN: usize = 1000;
+const MESSAGES_PER_FRAME: usize = 5;
+const SEND_FRAMES: usize = 10;
+
+#[derive(Resource, Default)]
+struct Received(Vec<String>);
+
+fn record_messages(
+    mut received: ResMut<Received>,
+    mut messages: EventReader<client::MessageEvent<Message1>>,
+) {
+    received
+        .0
+        .extend(messages.read().map(|event| event.message().0.clone()));
+}
+
+/// The client processes fewer packets per frame than it receives: the packets are deferred to the next
+/// frames, which shows up in the queue metrics, and none of them is lost as long as the queue is not full
+#[test]
+fn test_receive_queue_backlog() {
+    let mut client_config = ClientConfig::default();
+    client_config.packet.receive_queue =
+        ReceiveQueueConfig::default().with_max_packets_per_frame(2);
+    let mut pair = LightyearTestPair::builder()
+        .protocol(ProtocolPlugin)
+        .tick_duration(Duration::from_millis(10))
+        .client_config(client_config)
+        .build_disconnected();
+    pair.client_apps[0]
+        .init_resource::<Received>()
+        .add_systems(Update, record_messages);
+    pair.connect();
+    pair.frame_steps(20);
+    let client_id = pair.client_id(0);
+
+    let mut max_deferred = 0;
+    for frame in 0..SEND_FRAMES {
+        for i in 0..MESSAGES_PER_FRAME {
+            let message = format!("{frame}-{i}-").repeat(MESSAGE_LEN / 4);
+            pair.server_world_mut()
+                .resource_mut::<server::ConnectionManager>()
+                .send_message::<Channel1, _>(client_id, &Message1(message))
+                .unwrap();
+        }
+        pair.frame_step();
+        let stats = *pair
+            .client_world(0)
+            .resource::<client::ConnectionManager>()
+            .receive_queue_stats();
+        assert!(stats.processed <= 2);
+        max_deferred = max_deferred.max(stats.deferred);
+    }
+    // the backlog grows while the server sends more packets than the client can process
+    assert!(max_deferred >= 20, "max deferred: {max_deferred}");
+
+    // the backlog is processed once the server stops sending
+    pair.frame_steps(40);
+    let stats = *pair
+        .client_world(0)
+        .resource::<client::ConnectionManager>()
+        .receive_queue_stats();
+    assert_eq!(stats.deferred, 0);
+    assert_eq!(stats.dropped, 0);
+    assert!(stats.max_depth as u64 >= max_deferred);
+    assert_eq!(
+        pair.client_world(0).resource::<Received>().0.len(),
+        MESSAGES_PER_FRAME * SEND_FRAMES
+    );
+}
+
+/// When the queue is full, the packets are dropped according to the overflow policy and counted
+#[test]
+fn test_receive_queue_overflow() {
+    let mut client_config = ClientConfig::default();
+    client_config.packet.receive_queue = ReceiveQueueConfig::default()
+        .with_capacity(8)
+        .with_max_packets_per_frame(2);
+    let mut pair = LightyearTestPair::builder()
+        .protocol(ProtocolPlugin)
+        .tick_duration(Duration::from_millis(10))
+        .client_config(client_config)
+        .build();
+    pair.frame_steps(20);
+    let client_id = pair.client_id(0);
+
+    for _ in 0..SEND_FRAMES {
+        for _ in 0..MESSAGES_PER_FRAME {
+            pair.server_world_mut()
+                .resource_mut::<server::ConnectionManager>()
+                .send_message::<Channel1, _>(client_id, &Message1("a".repeat(MESSAGE_LEN)))
+                .unwrap();
+        }
+        pair.frame_step();
+    }
+    let stats = *pair
+        .client_world(0)
+        .resource::<client::ConnectionManager>()
+        .receive_queue_stats();
+    assert_eq!(stats.max_depth, 8);
+    assert!(stats.dropped > 0);
+}
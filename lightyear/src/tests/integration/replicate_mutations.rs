@@ -0,0 +1,268 @@
+//! Tests that mutate the fields of the [`Replicate`] bundle after the entity was replicated,
+//! and check the outcome on the client
+use bevy::prelude::*;
+
+use crate::prelude::client::{Confirmed, ConfirmedHistory, Predicted};
+use crate::prelude::server::{ControlledBy, Replicate, SyncTarget};
+use crate::prelude::*;
+use crate::server::replication::send::ActiveReplicationGroup;
+use crate::shared::replication::components::{Controlled, ReplicationGroupId};
+use crate::tests::protocol::*;
+use crate::tests::stepper::{BevyStepper, Step};
+
+fn step(stepper: &mut BevyStepper, frames: usize) {
+    for _ in 0..frames {
+        stepper.frame_step();
+    }
+}
+
+fn client_entity(stepper: &BevyStepper, server_entity: Entity) -> Option<Entity> {
+    stepper
+        .client_app
+        .world()
+        .resource::<client::ConnectionManager>()
+        .replication_receiver
+        .remote_entity_map
+        .get_local(server_entity)
+        .copied()
+}
+
+/// Spawn an entity with `Component1` that is replicated to the client, and return the server and client entities
+fn spawn_replicated(stepper: &mut BevyStepper, replicate: Replicate) -> (Entity, Entity) {
+    let server_entity = stepper
+        .server_app
+        .world_mut()
+        .spawn((Component1(1.0), replicate))
+        .id();
+    step(stepper, 5);
+    let client_entity =
+        client_entity(stepper, server_entity).expect("entity was not replicated to client");
+    (server_entity, client_entity)
+}
+
+#[test]
+fn test_mutate_replication_target() {
+    let mut stepper = BevyStepper::default();
+    let server_entity = stepper
+        .server_app
+        .world_mut()
+        .spawn((
+            Component1(1.0),
+            Replicate {
+                target: ReplicationTarget {
+                    target: NetworkTarget::None,
+                },
+                ..default()
+            },
+        ))
+        .id();
+    step(&mut stepper, 5);
+    assert!(client_entity(&stepper, server_entity).is_none());
+
+    // adding the client to the target spawns the entity on the client
+    stepper
+        .server_app
+        .world_mut()
+        .entity_mut(server_entity)
+        .insert(ReplicationTarget {
+            target: NetworkTarget::All,
+        });
+    step(&mut stepper, 5);
+    let client_entity =
+        client_entity(&stepper, server_entity).expect("entity was not replicated to client");
+    assert_eq!(
+        stepper.client_app.world().get::<Component1>(client_entity),
+        Some(&Component1(1.0))
+    );
+
+    // removing the client from the target despawns the entity on the client
+    stepper
+        .server_app
+        .world_mut()
+        .entity_mut(server_entity)
+        .insert(ReplicationTarget {
+            target: NetworkTarget::None,
+        });
+    step(&mut stepper, 5);
+    assert!(stepper
+        .client_app
+        .world()
+        .get_entity(client_entity)
+        .is_none());
+}
+
+#[test]
+fn test_mutate_prediction_target() {
+    let mut stepper = BevyStepper::default();
+    let (server_entity, confirmed_entity) = spawn_replicated(&mut stepper, Replicate::default());
+    assert!(stepper
+        .client_app
+        .world()
+        .get::<Confirmed>(confirmed_entity)
+        .is_none());
+
+    // adding the client to the prediction target spawns a predicted entity, with the existing components
+    stepper
+        .server_app
+        .world_mut()
+        .get_mut::<SyncTarget>(server_entity)
+        .unwrap()
+        .prediction = NetworkTarget::All;
+    step(&mut stepper, 5);
+    let predicted_entity = stepper
+        .client_app
+        .world()
+        .get::<Confirmed>(confirmed_entity)
+        .expect("Confirmed was not added")
+        .predicted
+        .expect("the predicted entity was not spawned");
+    assert!(stepper
+        .client_app
+        .world()
+        .get::<Predicted>(predicted_entity)
+        .is_some());
+    assert_eq!(
+        stepper
+            .client_app
+            .world()
+            .get::<Component1>(predicted_entity),
+        Some(&Component1(1.0))
+    );
+
+    // removing the client from the prediction target is not supported: the predicted entity is kept
+    stepper
+        .server_app
+        .world_mut()
+        .get_mut::<SyncTarget>(server_entity)
+        .unwrap()
+        .prediction = NetworkTarget::None;
+    step(&mut stepper, 5);
+    assert_eq!(
+        stepper
+            .client_app
+            .world()
+            .get::<Confirmed>(confirmed_entity)
+            .unwrap()
+            .predicted,
+        Some(predicted_entity)
+    );
+}
+
+#[test]
+fn test_mutate_interpolation_target() {
+    let mut stepper = BevyStepper::default();
+    let (server_entity, confirmed_entity) = spawn_replicated(&mut stepper, Replicate::default());
+
+    // adding the client to the interpolation target spawns an interpolated entity, with the existing components
+    stepper
+        .server_app
+        .world_mut()
+        .get_mut::<SyncTarget>(server_entity)
+        .unwrap()
+        .interpolation = NetworkTarget::All;
+    step(&mut stepper, 5);
+    let interpolated_entity = stepper
+        .client_app
+        .world()
+        .get::<Confirmed>(confirmed_entity)
+        .expect("Confirmed was not added")
+        .interpolated
+        .expect("the interpolated entity was not spawned");
+    assert!(stepper
+        .client_app
+        .world()
+        .get::<ConfirmedHistory<Component1>>(interpolated_entity)
+        .is_some());
+}
+
+#[test]
+fn test_mutate_controlled_by() {
+    let mut stepper = BevyStepper::default();
+    let (server_entity, client_entity) = spawn_replicated(&mut stepper, Replicate::default());
+    assert!(stepper
+        .client_app
+        .world()
+        .get::<Controlled>(client_entity)
+        .is_none());
+
+    // the client gains control of the entity
+    stepper
+        .server_app
+        .world_mut()
+        .entity_mut(server_entity)
+        .insert(ControlledBy {
+            target: NetworkTarget::All,
+            ..default()
+        });
+    step(&mut stepper, 5);
+    assert!(stepper
+        .client_app
+        .world()
+        .get::<Controlled>(client_entity)
+        .is_some());
+
+    // the client loses control of the entity
+    stepper
+        .server_app
+        .world_mut()
+        .entity_mut(server_entity)
+        .insert(ControlledBy::default());
+    step(&mut stepper, 5);
+    assert!(stepper
+        .client_app
+        .world()
+        .get::<Controlled>(client_entity)
+        .is_none());
+}
+
+#[test]
+fn test_mutate_replication_group() {
+    let mut stepper = BevyStepper::default();
+    let (server_entity, client_entity) = spawn_replicated(&mut stepper, Replicate::default());
+
+    // the entity is moved to the new group, so that the updates and the despawn keep reaching the client
+    stepper
+        .server_app
+        .world_mut()
+        .entity_mut(server_entity)
+        .insert(ReplicationGroup::new_id(42));
+    step(&mut stepper, 5);
+    assert_eq!(
+        stepper
+            .server_app
+            .world()
+            .get::<ActiveReplicationGroup>(server_entity)
+            .unwrap()
+            .group_id,
+        ReplicationGroupId(42)
+    );
+    assert_eq!(
+        stepper
+            .client_app
+            .world()
+            .resource::<client::ConnectionManager>()
+            .replication_receiver
+            .get_replication_group_id(client_entity),
+        Some(ReplicationGroupId(42))
+    );
+
+    stepper
+        .server_app
+        .world_mut()
+        .get_mut::<Component1>(server_entity)
+        .unwrap()
+        .0 = 2.0;
+    step(&mut stepper, 5);
+    assert_eq!(
+        stepper.client_app.world().get::<Component1>(client_entity),
+        Some(&Component1(2.0))
+    );
+
+    stepper.server_app.world_mut().despawn(server_entity);
+    step(&mut stepper, 5);
+    assert!(stepper
+        .client_app
+        .world()
+        .get_entity(client_entity)
+        .is_none());
+}
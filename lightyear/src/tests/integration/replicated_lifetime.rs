@@ -0,0 +1,125 @@
+//! Tests of the entities that are despawned on the server and on the client when their [`ReplicatedLifetime`] expires
+use bevy::prelude::*;
+use bevy::utils::Duration;
+
+use crate::prelude::client::{Interpolated, Predicted};
+use crate::prelude::server::{Replicate, SyncTarget};
+use crate::prelude::*;
+use crate::tests::protocol::*;
+
+/// Number of entities on the client that were received from the server
+fn client_entities(pair: &LightyearTestPair) -> usize {
+    pair.client_world(0)
+        .iter_entities()
+        .filter(|entity| {
+            entity.contains::<Replicated>()
+                || entity.contains::<Predicted>()
+                || entity.contains::<Interpolated>()
+        })
+        .count()
+}
+
+fn server_tick(pair: &LightyearTestPair) -> Tick {
+    pair.server_world().resource::<TickManager>().tick()
+}
+
+/// Spawn an entity on the server that is predicted or interpolated by the client, and expires after `ticks` ticks
+fn spawn_transient(pair: &mut LightyearTestPair, ticks: u16, predicted: bool) -> Entity {
+    let lifetime = ReplicatedLifetime::after(server_tick(pair), ticks);
+    let sync = if predicted {
+        SyncTarget {
+            prediction: NetworkTarget::All,
+            ..default()
+        }
+    } else {
+        SyncTarget {
+            interpolation: NetworkTarget::All,
+            ..default()
+        }
+    };
+    pair.server_world_mut()
+        .spawn((Component1(1.0), lifetime, Replicate { sync, ..default() }))
+        .id()
+}
+
+/// With heavy packet loss, the client doesn't keep any of the expired entities
+#[test]
+fn test_no_ghost_entities_with_packet_loss() {
+    let mut pair = LightyearTestPair::builder()
+        .protocol(ProtocolPlugin)
+        .tick_duration(Duration::from_millis(10))
+        .conditioner(LinkConditionerConfig {
+            incoming_latency: Duration::from_millis(20),
+            incoming_jitter: Duration::from_millis(10),
+            incoming_loss: 0.4,
+            seed: None,
+        })
+        .build();
+    let server_entities: Vec<Entity> = (0..10)
+        .map(|i| spawn_transient(&mut pair, 60, i % 2 == 0))
+        .collect();
+    pair.frame_steps(30);
+    assert!(client_entities(&pair) > 0);
+
+    // the entities expire at the tick 60 on the server, and a few ticks later on the interpolation timeline
+    pair.frame_steps(80);
+    for entity in server_entities {
+        assert!(pair.server_world().get_entity(entity).is_none());
+    }
+    assert_eq!(client_entities(&pair), 0);
+}
+
+/// The client removes the expired entities even if it doesn't receive the despawn from the server
+#[test]
+fn test_expiry_without_despawn_message() {
+    let mut pair = LightyearTestPair::builder()
+        .protocol(ProtocolPlugin)
+        .tick_duration(Duration::from_millis(10))
+        .build();
+    spawn_transient(&mut pair, 40, true);
+    spawn_transient(&mut pair, 40, false);
+    pair.frame_steps(10);
+    // confirmed + predicted, confirmed + interpolated
+    assert_eq!(client_entities(&pair), 4);
+
+    // drop all the packets sent by the server
+    let client_id = pair.client_id(0);
+    pair.server_world_mut()
+        .resource_mut::<server::ConnectionManager>()
+        .set_conditioner(
+            client_id,
+            Some(LinkConditionerConfig::new(
+                Duration::ZERO,
+                Duration::ZERO,
+                1.0,
+            )),
+        )
+        .unwrap();
+    pair.frame_steps(60);
+    assert_eq!(client_entities(&pair), 0);
+}
+
+/// Refreshing the lifetime on the server keeps the entity alive on the client
+#[test]
+fn test_refresh_lifetime() {
+    let mut pair = LightyearTestPair::builder()
+        .protocol(ProtocolPlugin)
+        .tick_duration(Duration::from_millis(10))
+        .build();
+    let server_entity = spawn_transient(&mut pair, 30, false);
+    pair.frame_steps(20);
+
+    let tick = server_tick(&pair);
+    pair.server_world_mut()
+        .get_mut::<ReplicatedLifetime>(server_entity)
+        .unwrap()
+        .refresh(tick, 60);
+    pair.frame_steps(30);
+    // the entity would have expired without the refresh
+    assert!(pair.server_world().get_entity(server_entity).is_some());
+    assert_eq!(client_entities(&pair), 2);
+
+    pair.frame_steps(60);
+    assert!(pair.server_world().get_entity(server_entity).is_none());
+    assert_eq!(client_entities(&pair), 0);
+}
@@ -0,0 +1,198 @@
+//! Tests of the type-erased stream of the replicated component changes
+use bevy::prelude::*;
+
+use crate::prelude::server::Replicate;
+use crate::prelude::*;
+use crate::tests::protocol::*;
+
+#[derive(Resource)]
+struct RecordedChanges<Ctx: Send + Sync + 'static>(Vec<ReplicationChange<Ctx>>);
+
+impl<Ctx: Send + Sync + 'static> Default for RecordedChanges<Ctx> {
+    fn default() -> Self {
+        Self(Vec::new())
+    }
+}
+
+fn record_changes<Ctx: Copy + Send + Sync + 'static>(
+    mut recorded: ResMut<RecordedChanges<Ctx>>,
+    mut events: EventReader<ReplicationChange<Ctx>>,
+) {
+    recorded.0.extend(events.read().copied());
+}
+
+fn add_changes_plugin<Ctx: Copy + Send + Sync + 'static>(app: &mut App) {
+    let mut filter = ReplicationChangeFilter::none();
+    filter
+        .subscribe::<Component1>()
+        .subscribe::<Component3>()
+        .subscribe::<Component6>();
+    app.add_plugins(ReplicationChangesPlugin { filter })
+        .init_resource::<RecordedChanges<Ctx>>()
+        .add_systems(Update, record_changes::<Ctx>);
+}
+
+fn build_pair() -> LightyearTestPair {
+    let mut pair = LightyearTestPair::builder()
+        .protocol(ProtocolPlugin)
+        .build_disconnected();
+    add_changes_plugin::<()>(&mut pair.client_apps[0]);
+    add_changes_plugin::<ClientId>(&mut pair.server_app);
+    pair.connect();
+    pair
+}
+
+fn client_changes(pair: &LightyearTestPair) -> &[ReplicationChange] {
+    &pair.client_world(0).resource::<RecordedChanges<()>>().0
+}
+
+fn server_changes(pair: &LightyearTestPair) -> &[ReplicationChange<ClientId>] {
+    &pair
+        .server_world()
+        .resource::<RecordedChanges<ClientId>>()
+        .0
+}
+
+/// The changes emitted on the client are exactly the scripted server mutations, and the changes emitted on
+/// the server are the ones that were sent (including the updates sent again until they are acked)
+#[test]
+fn test_changes_mirror_server_mutations() {
+    let mut pair = build_pair();
+    let server_entity = pair
+        .server_world_mut()
+        .spawn((Component1(1.0), Component2(1.0), Replicate::default()))
+        .id();
+    pair.frame_steps(5);
+    pair.server_world_mut()
+        .get_mut::<Component1>(server_entity)
+        .unwrap()
+        .0 = 2.0;
+    pair.frame_steps(5);
+    pair.server_world_mut()
+        .entity_mut(server_entity)
+        .insert((Component3(1.0), Component6(vec![1])));
+    pair.frame_steps(5);
+    pair.server_world_mut()
+        .entity_mut(server_entity)
+        .remove::<Component1>();
+    pair.frame_steps(5);
+    pair.server_world_mut()
+        .get_mut::<Component6>(server_entity)
+        .unwrap()
+        .0
+        .push(2);
+    pair.frame_steps(5);
+
+    let client_entity = *pair
+        .client_world(0)
+        .resource::<client::ConnectionManager>()
+        .replication_receiver
+        .remote_entity_map
+        .get_local(server_entity)
+        .unwrap();
+    let expected = [
+        (
+            ComponentKind::of::<Component1>(),
+            ReplicationChangeType::Inserted,
+        ),
+        (
+            ComponentKind::of::<Component1>(),
+            ReplicationChangeType::Updated,
+        ),
+        (
+            ComponentKind::of::<Component3>(),
+            ReplicationChangeType::Inserted,
+        ),
+        (
+            ComponentKind::of::<Component6>(),
+            ReplicationChangeType::Inserted,
+        ),
+        (
+            ComponentKind::of::<Component1>(),
+            ReplicationChangeType::Removed,
+        ),
+        (
+            ComponentKind::of::<Component6>(),
+            ReplicationChangeType::Updated,
+        ),
+    ];
+
+    let client_changes = client_changes(&pair);
+    let mut applied: Vec<_> = client_changes
+        .iter()
+        .map(|change| (change.kind, change.change))
+        .collect();
+    // the components inserted in the same message can be applied in any order
+    applied[2..4].sort_by_key(|(kind, _)| *kind != ComponentKind::of::<Component3>());
+    assert_eq!(applied, expected);
+    assert!(client_changes
+        .iter()
+        .all(|change| change.entity == client_entity));
+    assert!(client_changes
+        .windows(2)
+        .all(|changes| changes[0].tick <= changes[1].tick));
+
+    // every applied change was sent by the server at the same tick
+    let client_id = pair.client_id(0);
+    let server_changes = server_changes(&pair);
+    assert!(server_changes
+        .iter()
+        .all(|change| change.entity == server_entity && change.context == client_id));
+    for change in client_changes {
+        assert!(server_changes.iter().any(|sent| sent.kind == change.kind
+            && sent.change == change.change
+            && sent.tick == change.tick));
+    }
+    let mut sent: Vec<_> = server_changes
+        .iter()
+        .map(|change| (change.kind, change.change))
+        .collect();
+    sent.dedup();
+    sent[2..4].sort_by_key(|(kind, _)| *kind != ComponentKind::of::<Component3>());
+    assert_eq!(sent, expected);
+
+    let component_registry = pair.client_world(0).resource::<ComponentRegistry>();
+    assert_eq!(
+        client_changes[5].type_name(component_registry),
+        std::any::type_name::<Component6>()
+    );
+}
+
+/// The kinds can be subscribed and unsubscribed at runtime
+#[test]
+fn test_changes_filter() {
+    let mut pair = build_pair();
+    pair.client_world_mut(0)
+        .resource_mut::<ReplicationChangeFilter>()
+        .unsubscribe::<Component1>();
+    let server_entity = pair
+        .server_world_mut()
+        .spawn((Component1(1.0), Component3(1.0), Replicate::default()))
+        .id();
+    pair.frame_steps(5);
+    assert_eq!(
+        client_changes(&pair)
+            .iter()
+            .map(|change| change.kind)
+            .collect::<Vec<_>>(),
+        vec![ComponentKind::of::<Component3>()]
+    );
+
+    pair.client_world_mut(0)
+        .resource_mut::<ReplicationChangeFilter>()
+        .subscribe::<Component1>();
+    pair.server_world_mut()
+        .get_mut::<Component1>(server_entity)
+        .unwrap()
+        .0 = 2.0;
+    pair.frame_steps(5);
+    assert_eq!(
+        client_changes(&pair)
+            .last()
+            .map(|change| (change.kind, change.change)),
+        Some((
+            ComponentKind::of::<Component1>(),
+            ReplicationChangeType::Updated
+        ))
+    );
+}
@@ -0,0 +1,134 @@
+//! Tests of the bandwidth quotas of the replication classes, under a bandwidth cap
+use bevy::diagnostic::DiagnosticsStore;
+use bevy::prelude::*;
+use bevy::utils::Duration;
+use governor::Quota;
+use nonzero_ext::nonzero;
+
+use crate::prelude::server::{PacketConfig, Replicate, ServerConfig};
+use crate::prelude::*;
+use crate::shared::replication::diagnostics::ReplicationDiagnosticsPlugin;
+use crate::tests::protocol::*;
+
+const PLAYERS: ReplicationClass = ReplicationClass::new(1);
+const WORLD: ReplicationClass = ReplicationClass::new(2);
+const NUM_ENTITIES_PER_CLASS: usize = 30;
+/// Maximum difference between the measured share of a class and its quota
+const TOLERANCE: f64 = 0.05;
+
+/// Every entity changes on every tick, so that the demand is much higher than the bandwidth cap
+fn update_entities(mut query: Query<&mut Component1, With<Replicating>>) {
+    for mut component in query.iter_mut() {
+        component.0 += 1.0;
+    }
+}
+
+fn class_bytes(pair: &LightyearTestPair) -> (u64, u64) {
+    let stats = pair
+        .server_world()
+        .resource::<server::ConnectionManager>()
+        .replication_class_stats(pair.client_id(0))
+        .unwrap();
+    (stats.bytes_sent(PLAYERS), stats.bytes_sent(WORLD))
+}
+
+/// The players have a much higher priority than the world entities, but the world entities
+/// still get their share of the bandwidth
+#[test]
+fn test_class_quotas_split() {
+    let mut pair = LightyearTestPair::builder()
+        .protocol(ProtocolPlugin)
+        .tick_duration(Duration::from_millis(10))
+        .server_config(ServerConfig {
+            packet: PacketConfig::default()
+                .with_send_bandwidth_cap(Quota::per_second(nonzero!(10_000u32)))
+                .enable_bandwidth_cap(),
+            replication: ReplicationConfig {
+                class_quotas: ReplicationClassQuotas::default()
+                    .with_quota(PLAYERS, 70)
+                    .with_quota(WORLD, 30),
+                ..default()
+            },
+            ..default()
+        })
+        .build();
+    pair.server_app.add_systems(FixedUpdate, update_entities);
+    for (class, priority) in [(PLAYERS, 10.0), (WORLD, 1.0)] {
+        for i in 0..NUM_ENTITIES_PER_CLASS {
+            pair.server_world_mut().spawn((
+                Component1(i as f32),
+                Replicate {
+                    group: ReplicationGroup::default()
+                        .set_priority(priority)
+                        .set_class(class),
+                    ..default()
+                },
+            ));
+        }
+    }
+    // wait for the entities to be spawned on the client
+    pair.frame_steps(100);
+
+    let (players_start, world_start) = class_bytes(&pair);
+    pair.frame_steps(300);
+    let (players_end, world_end) = class_bytes(&pair);
+    let (players, world) = (players_end - players_start, world_end - world_start);
+    let players_share = players as f64 / (players + world) as f64;
+    assert!(
+        (players_share - 0.7).abs() < TOLERANCE,
+        "players: {players} bytes, world: {world} bytes"
+    );
+
+    // the bytes of each class are reported in the diagnostics
+    let diagnostic_bytes = |class| -> f64 {
+        pair.server_world()
+            .resource::<DiagnosticsStore>()
+            .get(&ReplicationDiagnosticsPlugin::class_bytes_sent(class))
+            .unwrap()
+            .values()
+            .sum()
+    };
+    assert!(diagnostic_bytes(PLAYERS) > 0.0);
+    assert!(diagnostic_bytes(WORLD) > 0.0);
+}
+
+/// The budget that a class does not use is used by the other classes
+#[test]
+fn test_unused_quota_spills_over() {
+    let mut pair = LightyearTestPair::builder()
+        .protocol(ProtocolPlugin)
+        .tick_duration(Duration::from_millis(10))
+        .server_config(ServerConfig {
+            packet: PacketConfig::default()
+                .with_send_bandwidth_cap(Quota::per_second(nonzero!(10_000u32)))
+                .enable_bandwidth_cap(),
+            replication: ReplicationConfig {
+                class_quotas: ReplicationClassQuotas::default()
+                    .with_quota(PLAYERS, 70)
+                    .with_quota(WORLD, 30),
+                ..default()
+            },
+            ..default()
+        })
+        .build();
+    pair.server_app.add_systems(FixedUpdate, update_entities);
+    // only the world entities have something to send
+    for i in 0..2 * NUM_ENTITIES_PER_CLASS {
+        pair.server_world_mut().spawn((
+            Component1(i as f32),
+            Replicate {
+                group: ReplicationGroup::default().set_class(WORLD),
+                ..default()
+            },
+        ));
+    }
+    pair.frame_steps(100);
+
+    let (_, world_start) = class_bytes(&pair);
+    pair.frame_steps(300);
+    let (players_end, world_end) = class_bytes(&pair);
+    assert_eq!(players_end, 0);
+    // 3 seconds at 10KB/s, minus the packet headers and the other messages
+    let world = world_end - world_start;
+    assert!(world > 15_000, "world: {world} bytes");
+}
@@ -0,0 +1,150 @@
+//! Tests of the replication of entities that are part of the same [`ReplicationGroup`]
+use bevy::prelude::*;
+
+use crate::prelude::server::Replicate;
+use crate::prelude::*;
+use crate::shared::replication::components::ReplicationGroupId;
+use crate::tests::protocol::*;
+
+fn client_entity(pair: &LightyearTestPair, server_entity: Entity) -> Option<Entity> {
+    pair.client_world(0)
+        .resource::<client::ConnectionManager>()
+        .replication_receiver
+        .remote_entity_map
+        .get_local(server_entity)
+        .copied()
+}
+
+fn client_group(pair: &LightyearTestPair, client_entity: Entity) -> Option<ReplicationGroupId> {
+    pair.client_world(0)
+        .resource::<client::ConnectionManager>()
+        .replication_receiver
+        .get_replication_group_id(client_entity)
+}
+
+fn replicate_in_group(id: u64) -> Replicate {
+    Replicate {
+        group: ReplicationGroup::new_id(id),
+        ..default()
+    }
+}
+
+/// A player and its weapon that references it: the client never sees one without the other
+#[test]
+fn test_group_spawned_together() {
+    let mut pair = LightyearTestPair::builder()
+        .protocol(ProtocolPlugin)
+        .build();
+    let server_player = pair
+        .server_world_mut()
+        .spawn((Component1(1.0), replicate_in_group(7)))
+        .id();
+    let server_weapon = pair
+        .server_world_mut()
+        .spawn((Component4(server_player), replicate_in_group(7)))
+        .id();
+
+    for _ in 0..10 {
+        pair.frame_step();
+        let player = client_entity(&pair, server_player);
+        let weapon = client_entity(&pair, server_weapon);
+        assert_eq!(player.is_some(), weapon.is_some());
+        let (Some(player), Some(weapon)) = (player, weapon) else {
+            continue;
+        };
+        assert_eq!(
+            pair.client_world(0).get::<Component1>(player),
+            Some(&Component1(1.0))
+        );
+        assert_eq!(
+            pair.client_world(0).get::<Component4>(weapon),
+            Some(&Component4(player))
+        );
+    }
+    let weapon = client_entity(&pair, server_weapon).expect("the group was not replicated");
+    assert_eq!(client_group(&pair, weapon), Some(ReplicationGroupId(7)));
+}
+
+/// A group that doesn't fit in a single packet is fragmented instead of being split
+#[test]
+fn test_group_larger_than_mtu() {
+    let mut pair = LightyearTestPair::builder()
+        .protocol(ProtocolPlugin)
+        .build();
+    // about 6KB of data
+    let inventory = Component6((1..3000).collect());
+    let server_player = pair
+        .server_world_mut()
+        .spawn((inventory.clone(), replicate_in_group(7)))
+        .id();
+    let server_weapon = pair
+        .server_world_mut()
+        .spawn((Component4(server_player), replicate_in_group(7)))
+        .id();
+
+    for _ in 0..20 {
+        pair.frame_step();
+        assert_eq!(
+            client_entity(&pair, server_player).is_some(),
+            client_entity(&pair, server_weapon).is_some()
+        );
+    }
+    let player = client_entity(&pair, server_player).expect("the group was not replicated");
+    assert_eq!(
+        pair.client_world(0).get::<Component6>(player),
+        Some(&inventory)
+    );
+    let weapon = client_entity(&pair, server_weapon).unwrap();
+    assert_eq!(
+        pair.client_world(0).get::<Component4>(weapon),
+        Some(&Component4(player))
+    );
+}
+
+/// An entity whose group is modified at runtime is moved to the new group on the client
+#[test]
+fn test_change_group() {
+    let mut pair = LightyearTestPair::builder()
+        .protocol(ProtocolPlugin)
+        .build();
+    let server_player = pair
+        .server_world_mut()
+        .spawn((Component1(1.0), replicate_in_group(7)))
+        .id();
+    let server_weapon = pair
+        .server_world_mut()
+        .spawn((Component2(1.0), replicate_in_group(8)))
+        .id();
+    pair.frame_steps(10);
+    let player = client_entity(&pair, server_player).unwrap();
+    let weapon = client_entity(&pair, server_weapon).unwrap();
+    assert_eq!(client_group(&pair, weapon), Some(ReplicationGroupId(8)));
+
+    // the player picks up the weapon
+    pair.server_world_mut()
+        .entity_mut(server_weapon)
+        .insert((Component4(server_player), ReplicationGroup::new_id(7)));
+    pair.frame_steps(10);
+    // the client keeps the same entity
+    assert_eq!(client_entity(&pair, server_weapon), Some(weapon));
+    assert_eq!(client_group(&pair, weapon), Some(ReplicationGroupId(7)));
+    assert_eq!(
+        pair.client_world(0).get::<Component4>(weapon),
+        Some(&Component4(player))
+    );
+
+    // the updates and the despawn are sent in the new group
+    pair.server_world_mut()
+        .get_mut::<Component2>(server_weapon)
+        .unwrap()
+        .0 = 2.0;
+    pair.frame_steps(10);
+    assert_eq!(
+        pair.client_world(0).get::<Component2>(weapon),
+        Some(&Component2(2.0))
+    );
+    pair.server_world_mut().despawn(server_weapon);
+    pair.frame_steps(10);
+    assert!(pair.client_world(0).get_entity(weapon).is_none());
+    assert!(pair.client_world(0).get_entity(player).is_some());
+}
@@ -0,0 +1,228 @@
+//! Tests of the client-side limits on the replication received from the server
+use bevy::prelude::*;
+use bevy::utils::Duration;
+
+use crate::prelude::client::{
+    ClientConfig, ConnectionManager, NetworkingState, ReplicationLimitExceededEvent,
+};
+use crate::prelude::server::Replicate;
+use crate::prelude::*;
+use crate::tests::protocol::*;
+use crate::tests::stepper::{BevyStepper, Step};
+
+#[derive(Resource, Default)]
+struct LimitEvents(Vec<ReplicationLimitExceededEvent>);
+
+fn record_limit_events(
+    mut recorded: ResMut<LimitEvents>,
+    mut events: EventReader<ReplicationLimitExceededEvent>,
+) {
+    recorded.0.extend(events.read().cloned());
+}
+
+/// Create a stepper where the client uses the `limits`. The client is not connected yet.
+fn setup(limits: ReplicationLimits) -> BevyStepper {
+    let frame_duration = Duration::from_millis(10);
+    let shared_config = SharedConfig {
+        tick: TickConfig::new(frame_duration),
+        ..default()
+    };
+    let client_config = ClientConfig {
+        replication: ReplicationConfig {
+            limits,
+            ..default()
+        },
+        ..default()
+    };
+    let mut stepper = BevyStepper::new(shared_config, client_config, frame_duration);
+    stepper.client_app.init_resource::<LimitEvents>();
+    stepper.client_app.add_systems(Update, record_limit_events);
+    stepper
+}
+
+fn spawn_flood(stepper: &mut BevyStepper, count: usize) -> Vec<Entity> {
+    (0..count)
+        .map(|i| {
+            stepper
+                .server_app
+                .world_mut()
+                .spawn((Component1(i as f32), Replicate::default()))
+                .id()
+        })
+        .collect()
+}
+
+fn replicated_entities(stepper: &mut BevyStepper) -> usize {
+    stepper
+        .client_app
+        .world_mut()
+        .query_filtered::<(), With<Replicated>>()
+        .iter(stepper.client_app.world())
+        .count()
+}
+
+fn rejected(stepper: &BevyStepper, limit: ReplicationLimit) -> u32 {
+    stepper
+        .client_app
+        .world()
+        .resource::<LimitEvents>()
+        .0
+        .iter()
+        .filter(|event| event.limit == limit)
+        .map(|event| event.rejected)
+        .sum()
+}
+
+fn limit_stats(stepper: &BevyStepper) -> ReplicationLimitStats {
+    *stepper
+        .client_app
+        .world()
+        .resource::<ConnectionManager>()
+        .replication_limit_stats()
+}
+
+#[test]
+fn test_spawn_flood_max_entities() {
+    let mut stepper = setup(ReplicationLimits::default().with_max_entities(10));
+    stepper.init();
+
+    let server_entities = spawn_flood(&mut stepper, 50);
+    for _ in 0..10 {
+        stepper.frame_step();
+    }
+    assert_eq!(replicated_entities(&mut stepper), 10);
+    assert_eq!(rejected(&stepper, ReplicationLimit::Entities), 40);
+    assert_eq!(limit_stats(&stepper).rejected_spawns, 40);
+    assert_eq!(
+        stepper
+            .client_app
+            .world()
+            .resource::<State<NetworkingState>>()
+            .get(),
+        &NetworkingState::Connected
+    );
+
+    // the updates of the rejected entities are ignored
+    for entity in &server_entities {
+        stepper
+            .server_app
+            .world_mut()
+            .get_mut::<Component1>(*entity)
+            .unwrap()
+            .0 += 100.0;
+    }
+    for _ in 0..10 {
+        stepper.frame_step();
+    }
+    assert_eq!(replicated_entities(&mut stepper), 10);
+
+    // despawning all the entities frees the slots for new spawns
+    for entity in server_entities {
+        stepper.server_app.world_mut().despawn(entity);
+    }
+    for _ in 0..10 {
+        stepper.frame_step();
+    }
+    assert_eq!(replicated_entities(&mut stepper), 0);
+    spawn_flood(&mut stepper, 5);
+    for _ in 0..10 {
+        stepper.frame_step();
+    }
+    assert_eq!(replicated_entities(&mut stepper), 5);
+    assert_eq!(limit_stats(&stepper).rejected_spawns, 40);
+}
+
+#[test]
+fn test_max_components_per_entity() {
+    let mut stepper = setup(ReplicationLimits::default().with_max_components_per_entity(2));
+    stepper.init();
+
+    let server_entity = stepper
+        .server_app
+        .world_mut()
+        .spawn((Component1(1.0), Component3(1.0), Replicate::default()))
+        .id();
+    for _ in 0..10 {
+        stepper.frame_step();
+    }
+    stepper
+        .server_app
+        .world_mut()
+        .entity_mut(server_entity)
+        .insert(Component5(1.0));
+    for _ in 0..10 {
+        stepper.frame_step();
+    }
+    let client_entity = *stepper
+        .client_app
+        .world()
+        .resource::<ConnectionManager>()
+        .replication_receiver
+        .remote_entity_map
+        .get_local(server_entity)
+        .unwrap();
+    let client_world = stepper.client_app.world();
+    assert!(client_world.get::<Component1>(client_entity).is_some());
+    assert!(client_world.get::<Component3>(client_entity).is_some());
+    assert!(client_world.get::<Component5>(client_entity).is_none());
+    assert_eq!(rejected(&stepper, ReplicationLimit::ComponentsPerEntity), 1);
+    assert_eq!(limit_stats(&stepper).rejected_components, 1);
+}
+
+/// The entities that exist on the server when the client connects are replicated all at once,
+/// which can exceed the bytes budget
+#[test]
+fn test_late_join_snapshot() {
+    let limits = ReplicationLimits::default().with_max_bytes_per_second(200);
+
+    // without any allowance for the snapshot, some spawns are rejected
+    let mut stepper = setup(limits);
+    stepper.build();
+    spawn_flood(&mut stepper, 50);
+    stepper.start();
+    for _ in 0..10 {
+        stepper.frame_step();
+    }
+    assert!(replicated_entities(&mut stepper) < 50);
+    assert!(rejected(&stepper, ReplicationLimit::BytesPerSecond) > 0);
+
+    // the snapshot allowance accounts for the snapshot
+    let mut stepper = setup(limits.with_snapshot_bytes(10_000));
+    stepper.build();
+    spawn_flood(&mut stepper, 50);
+    stepper.start();
+    for _ in 0..10 {
+        stepper.frame_step();
+    }
+    assert_eq!(replicated_entities(&mut stepper), 50);
+    assert!(stepper
+        .client_app
+        .world()
+        .resource::<LimitEvents>()
+        .0
+        .is_empty());
+}
+
+#[test]
+fn test_disconnect_on_limit_exceeded() {
+    let mut stepper = setup(
+        ReplicationLimits::default()
+            .with_max_entities(5)
+            .with_disconnect(true),
+    );
+    stepper.init();
+
+    spawn_flood(&mut stepper, 10);
+    for _ in 0..10 {
+        stepper.frame_step();
+    }
+    assert_eq!(
+        stepper
+            .client_app
+            .world()
+            .resource::<State<NetworkingState>>()
+            .get(),
+        &NetworkingState::Disconnected
+    );
+    assert_eq!(rejected(&stepper, ReplicationLimit::Entities), 5);
+}
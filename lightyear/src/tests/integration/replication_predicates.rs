@@ -0,0 +1,344 @@
+//! Tests of the replication predicates, which hide entities or components from some clients
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+
+use bevy::ecs::system::RunSystemOnce;
+use bevy::prelude::*;
+use bevy::state::app::StatesPlugin;
+use bevy::utils::Duration;
+
+use crate::connection::netcode::generate_key;
+use crate::prelude::client::{
+    Authentication, ClientCommands, ClientConfig, ClientTransport, NetConfig, SyncConfig,
+};
+use crate::prelude::server::{
+    NetcodeConfig, PredicateScope, Replicate, RoomId, RoomManager, ServerCommands, ServerConfig,
+    ServerTransport,
+};
+use crate::prelude::*;
+use crate::shared::clock::MockClock;
+use crate::tests::protocol::*;
+use crate::transport::LOCAL_SOCKET;
+
+const NUM_CLIENTS: u64 = 3;
+const ENEMY: ClientId = ClientId::Netcode(3);
+
+/// Stealthed entities are only replicated to the clients of their team
+#[derive(Component)]
+struct Stealth {
+    team: Vec<ClientId>,
+}
+
+/// Marker for the entities whose [`Component3`] is revealed to every client
+#[derive(Component)]
+struct Revealed;
+
+struct TeamStepper {
+    server_app: App,
+    client_apps: Vec<App>,
+    frame_duration: Duration,
+    clock: MockClock,
+}
+
+impl TeamStepper {
+    fn new() -> Self {
+        let frame_duration = Duration::from_millis(10);
+        let clock = MockClock::new();
+        let shared_config = SharedConfig {
+            tick: TickConfig::new(frame_duration),
+            ..default()
+        };
+        let protocol_id = 0;
+        let private_key = generate_key();
+
+        let mut server_channels = vec![];
+        let mut client_apps = vec![];
+        for client_id in 1..=NUM_CLIENTS {
+            let (from_server_send, from_server_recv) = crossbeam_channel::unbounded();
+            let (to_server_send, to_server_recv) = crossbeam_channel::unbounded();
+            let client_addr =
+                SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 1000 + client_id as u16);
+            server_channels.push((client_addr, to_server_recv, from_server_send));
+
+            let mut client_app = App::new();
+            client_app.add_plugins((MinimalPlugins, StatesPlugin));
+            clock.install(&mut client_app);
+            let config = ClientConfig {
+                shared: shared_config,
+                net: NetConfig::Netcode {
+                    auth: Authentication::Manual {
+                        server_addr: LOCAL_SOCKET,
+                        protocol_id,
+                        private_key,
+                        client_id,
+                    },
+                    config: client::NetcodeConfig::default(),
+                    io: client::IoConfig::from_transport(ClientTransport::LocalChannel {
+                        recv: from_server_recv,
+                        send: to_server_send,
+                    }),
+                },
+                sync: SyncConfig::default().speedup_factor(1.0),
+                ..default()
+            };
+            client_app.add_plugins((client::ClientPlugins::new(config), ProtocolPlugin));
+            client_app
+                .world_mut()
+                .resource_mut::<Time<Real>>()
+                .update_with_instant(clock.now());
+            client_apps.push(client_app);
+        }
+
+        let mut server_app = App::new();
+        server_app.add_plugins((MinimalPlugins, StatesPlugin));
+        clock.install(&mut server_app);
+        let config = ServerConfig {
+            shared: shared_config,
+            net: vec![server::NetConfig::Netcode {
+                config: NetcodeConfig::default()
+                    .with_protocol_id(protocol_id)
+                    .with_key(private_key),
+                io: server::IoConfig::from_transport(ServerTransport::Channels {
+                    channels: server_channels,
+                }),
+            }],
+            ..default()
+        };
+        server_app.add_plugins((server::ServerPlugins::new(config), ProtocolPlugin));
+        server_app
+            .world_mut()
+            .resource_mut::<Time<Real>>()
+            .update_with_instant(clock.now());
+
+        // the stealthed entities are hidden from the other teams
+        server_app.register_replication_predicate::<Stealth>(|entity_ref, client_id| {
+            entity_ref
+                .get::<Stealth>()
+                .map_or(true, |stealth| stealth.team.contains(&client_id))
+        });
+        // Component3 is only sent to the enemy once it is revealed
+        server_app.register_replication_predicate_with_scope::<Component3>(
+            PredicateScope::Component,
+            |entity_ref, client_id| client_id != ENEMY || entity_ref.contains::<Revealed>(),
+        );
+
+        let mut stepper = Self {
+            server_app,
+            client_apps,
+            frame_duration,
+            clock,
+        };
+        stepper.init();
+        stepper
+    }
+
+    fn init(&mut self) {
+        self.server_app.finish();
+        self.server_app.cleanup();
+        self.server_app
+            .world_mut()
+            .run_system_once(|mut commands: Commands| commands.start_server());
+        for client_app in self.client_apps.iter_mut() {
+            client_app.finish();
+            client_app.cleanup();
+            client_app
+                .world_mut()
+                .run_system_once(|mut commands: Commands| commands.connect_client());
+        }
+        for _ in 0..200 {
+            if self.client_apps.iter().all(|app| {
+                app.world()
+                    .resource::<client::ConnectionManager>()
+                    .is_synced()
+            }) {
+                return;
+            }
+            self.frame_step();
+        }
+        panic!("the clients could not connect");
+    }
+
+    fn frame_step(&mut self) {
+        self.clock.advance(self.frame_duration);
+        for client_app in self.client_apps.iter_mut() {
+            client_app.update();
+        }
+        self.server_app.update();
+    }
+
+    fn frame_steps(&mut self, n: usize) {
+        for _ in 0..n {
+            self.frame_step();
+        }
+    }
+
+    /// The entity of client `client_id` that replicates `server_entity`
+    fn client_entity(&self, client_id: u64, server_entity: Entity) -> Option<Entity> {
+        let client_app = &self.client_apps[client_id as usize - 1];
+        client_app
+            .world()
+            .resource::<client::ConnectionManager>()
+            .replication_receiver
+            .remote_entity_map
+            .get_local(server_entity)
+            .copied()
+            .filter(|entity| client_app.world().get_entity(*entity).is_some())
+    }
+
+    fn client_component<C: Component + Clone>(
+        &self,
+        client_id: u64,
+        server_entity: Entity,
+    ) -> Option<C> {
+        let entity = self.client_entity(client_id, server_entity)?;
+        self.client_apps[client_id as usize - 1]
+            .world()
+            .get::<C>(entity)
+            .cloned()
+    }
+}
+
+/// Clients 1 and 2 are in the same team, client 3 is their enemy.
+/// The stealthed player of client 1 is replicated to client 2 but never to client 3,
+/// until the stealth ends.
+#[test]
+fn test_team_stealth() {
+    let mut stepper = TeamStepper::new();
+    let team = vec![ClientId::Netcode(1), ClientId::Netcode(2)];
+    let player = stepper
+        .server_app
+        .world_mut()
+        .spawn((
+            Component1(1.0),
+            Stealth { team: team.clone() },
+            Replicate::default(),
+        ))
+        .id();
+
+    // updates to the stealthed entity are not sent to the enemy either
+    for i in 0..5 {
+        stepper
+            .server_app
+            .world_mut()
+            .get_mut::<Component1>(player)
+            .unwrap()
+            .0 = i as f32;
+        stepper.frame_steps(2);
+        assert!(stepper.client_entity(3, player).is_none());
+    }
+    for teammate in [1, 2] {
+        assert_eq!(
+            stepper.client_component::<Component1>(teammate, player),
+            Some(Component1(4.0))
+        );
+    }
+
+    // the stealth ends: the enemy receives the entity
+    stepper
+        .server_app
+        .world_mut()
+        .entity_mut(player)
+        .remove::<Stealth>();
+    stepper.frame_steps(3);
+    assert_eq!(
+        stepper.client_component::<Component1>(3, player),
+        Some(Component1(4.0))
+    );
+
+    // the stealth starts again: the entity is despawned for the enemy only
+    stepper
+        .server_app
+        .world_mut()
+        .entity_mut(player)
+        .insert(Stealth { team });
+    stepper.frame_steps(3);
+    assert!(stepper.client_entity(3, player).is_none());
+    assert!(stepper.client_entity(1, player).is_some());
+    assert!(stepper.client_entity(2, player).is_some());
+}
+
+/// The predicates only run for the clients that the rooms did not exclude already
+#[test]
+fn test_stealth_with_rooms() {
+    let mut stepper = TeamStepper::new();
+    let player = stepper
+        .server_app
+        .world_mut()
+        .spawn((
+            Component1(1.0),
+            Stealth {
+                team: vec![ClientId::Netcode(1), ClientId::Netcode(2)],
+            },
+            Replicate {
+                relevance_mode: NetworkRelevanceMode::InterestManagement,
+                ..default()
+            },
+        ))
+        .id();
+    // clients 2 and 3 are in the room of the player
+    let mut room_manager = stepper.server_app.world_mut().resource_mut::<RoomManager>();
+    room_manager.add_client(ClientId::Netcode(2), RoomId(0));
+    room_manager.add_client(ENEMY, RoomId(0));
+    room_manager.add_entity(player, RoomId(0));
+    stepper.frame_steps(3);
+
+    // client 1 passes the predicate but is not in the room
+    assert!(stepper.client_entity(1, player).is_none());
+    assert!(stepper.client_entity(2, player).is_some());
+    assert!(stepper.client_entity(3, player).is_none());
+
+    // leaving the room still despawns the entity for the teammate
+    stepper
+        .server_app
+        .world_mut()
+        .resource_mut::<RoomManager>()
+        .remove_client(ClientId::Netcode(2), RoomId(0));
+    stepper.frame_steps(3);
+    assert!(stepper.client_entity(2, player).is_none());
+}
+
+/// A component-scoped predicate only hides the component, not the entity
+#[test]
+fn test_component_predicate() {
+    let mut stepper = TeamStepper::new();
+    let entity = stepper
+        .server_app
+        .world_mut()
+        .spawn((Component1(1.0), Component3(2.0), Replicate::default()))
+        .id();
+    stepper.frame_steps(3);
+    assert_eq!(
+        stepper.client_component::<Component3>(1, entity),
+        Some(Component3(2.0))
+    );
+    assert_eq!(
+        stepper.client_component::<Component1>(3, entity),
+        Some(Component1(1.0))
+    );
+    assert!(stepper.client_component::<Component3>(3, entity).is_none());
+
+    // updates of the hidden component are not sent either
+    stepper
+        .server_app
+        .world_mut()
+        .get_mut::<Component3>(entity)
+        .unwrap()
+        .0 = 3.0;
+    stepper.frame_steps(3);
+    assert_eq!(
+        stepper.client_component::<Component3>(1, entity),
+        Some(Component3(3.0))
+    );
+    assert!(stepper.client_component::<Component3>(3, entity).is_none());
+
+    // once revealed, the component is inserted on the enemy's entity
+    stepper
+        .server_app
+        .world_mut()
+        .entity_mut(entity)
+        .insert(Revealed);
+    stepper.frame_steps(3);
+    assert_eq!(
+        stepper.client_component::<Component3>(3, entity),
+        Some(Component3(3.0))
+    );
+}
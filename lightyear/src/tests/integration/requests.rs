@@ -0,0 +1,194 @@
+//! Tests of the requests sent by the client and by the server, and of their responses
+use bevy::prelude::*;
+use bevy::utils::Duration;
+use serde::{Deserialize, Serialize};
+
+use crate::prelude::client::ClientConfig;
+use crate::prelude::server::ServerConfig;
+use crate::prelude::*;
+use crate::tests::protocol::*;
+
+const TIMEOUT: Duration = Duration::from_millis(200);
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct InventoryRequest;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct InventoryResponse {
+    items: Vec<u32>,
+}
+
+impl Request for InventoryRequest {
+    type Response = InventoryResponse;
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct ReadyCheck(u32);
+
+impl Request for ReadyCheck {
+    type Response = bool;
+}
+
+fn register_requests(app: &mut App) {
+    app.register_request::<InventoryRequest>(ChannelDirection::ClientToServer);
+    app.register_request::<ReadyCheck>(ChannelDirection::ServerToClient);
+}
+
+fn respond_inventory(mut requests: EventReader<server::RequestEvent<InventoryRequest>>) {
+    for event in requests.read() {
+        event.respond(InventoryResponse {
+            items: vec![event.context.to_bits() as u32, 7],
+        });
+    }
+}
+
+fn respond_ready_check(mut requests: EventReader<client::RequestEvent<ReadyCheck>>) {
+    for event in requests.read() {
+        event.respond(event.request.0 % 2 == 0);
+    }
+}
+
+/// Responders kept by the server to respond on a later frame
+#[derive(Resource, Default)]
+struct HeldResponders(Vec<ResponseSender<InventoryRequest>>);
+
+fn hold_inventory_requests(
+    mut held: ResMut<HeldResponders>,
+    mut requests: EventReader<server::RequestEvent<InventoryRequest>>,
+) {
+    held.0
+        .extend(requests.read().map(|event| event.responder()));
+}
+
+fn build_pair() -> LightyearTestPair {
+    let mut pair = LightyearTestPair::builder()
+        .protocol(ProtocolPlugin)
+        .tick_duration(Duration::from_millis(10))
+        .client_config(ClientConfig {
+            request: RequestConfig { timeout: TIMEOUT },
+            ..default()
+        })
+        .server_config(ServerConfig {
+            request: RequestConfig { timeout: TIMEOUT },
+            ..default()
+        })
+        .build_disconnected();
+    register_requests(&mut pair.client_apps[0]);
+    register_requests(&mut pair.server_app);
+    pair
+}
+
+fn send_inventory_request(pair: &mut LightyearTestPair) -> RequestHandle<InventoryResponse> {
+    pair.client_world_mut(0)
+        .resource_mut::<client::ConnectionManager>()
+        .send_request(InventoryRequest)
+        .unwrap()
+}
+
+#[test]
+fn test_client_request() {
+    let mut pair = build_pair();
+    pair.server_app.add_systems(Update, respond_inventory);
+    pair.connect();
+
+    let handle = send_inventory_request(&mut pair);
+    let other_handle = send_inventory_request(&mut pair);
+    assert_ne!(handle.id(), other_handle.id());
+    assert!(handle.is_pending());
+    pair.frame_steps(10);
+
+    let expected = InventoryResponse {
+        items: vec![pair.client_id(0).to_bits() as u32, 7],
+    };
+    assert_eq!(handle.try_take(), Some(Ok(expected.clone())));
+    assert_eq!(handle.try_take(), None);
+    // the handle can also be awaited
+    assert_eq!(bevy::tasks::block_on(other_handle), Ok(expected));
+}
+
+#[test]
+fn test_server_request() {
+    let mut pair = build_pair();
+    pair.client_apps[0].add_systems(Update, respond_ready_check);
+    pair.connect();
+
+    let client_id = pair.client_id(0);
+    let handles: Vec<_> = (0..4)
+        .map(|i| {
+            pair.server_world_mut()
+                .resource_mut::<server::ConnectionManager>()
+                .send_request(client_id, ReadyCheck(i))
+                .unwrap()
+        })
+        .collect();
+    pair.frame_steps(10);
+
+    let responses: Vec<_> = handles
+        .iter()
+        .map(|handle| handle.try_take().unwrap().unwrap())
+        .collect();
+    assert_eq!(responses, vec![true, false, true, false]);
+}
+
+/// A request fails if the remote does not respond before the timeout
+#[test]
+fn test_request_timeout() {
+    let mut pair = build_pair();
+    pair.connect();
+
+    let handle = send_inventory_request(&mut pair);
+    pair.frame_steps(10);
+    assert!(handle.is_pending());
+
+    pair.frame_steps(20);
+    assert_eq!(handle.try_take(), Some(Err(RequestError::Timeout)));
+}
+
+/// A response that arrives after the timeout is dropped
+#[test]
+fn test_late_response_dropped() {
+    let mut pair = build_pair();
+    pair.server_app
+        .init_resource::<HeldResponders>()
+        .add_systems(Update, hold_inventory_requests);
+    pair.connect();
+
+    let handle = send_inventory_request(&mut pair);
+    pair.frame_steps(30);
+    assert_eq!(handle.try_take(), Some(Err(RequestError::Timeout)));
+
+    let responders =
+        std::mem::take(&mut pair.server_world_mut().resource_mut::<HeldResponders>().0);
+    assert_eq!(responders.len(), 1);
+    for responder in responders {
+        responder.respond(InventoryResponse { items: vec![] });
+    }
+    pair.frame_steps(10);
+    assert_eq!(handle.try_take(), None);
+
+    // new requests still work
+    pair.server_app.add_systems(Update, respond_inventory);
+    let handle = send_inventory_request(&mut pair);
+    pair.frame_steps(10);
+    assert!(matches!(handle.try_take(), Some(Ok(_))));
+}
+
+/// The requests sent to a client fail when the client disconnects
+#[test]
+fn test_request_fails_on_disconnect() {
+    let mut pair = build_pair();
+    pair.connect();
+
+    let client_id = pair.client_id(0);
+    let handle = pair
+        .server_world_mut()
+        .resource_mut::<server::ConnectionManager>()
+        .send_request(client_id, ReadyCheck(0))
+        .unwrap();
+    pair.server_world_mut()
+        .resource_mut::<server::ConnectionManager>()
+        .disconnect_client(client_id, None)
+        .unwrap();
+    pair.frame_steps(5);
+    assert_eq!(handle.try_take(), Some(Err(RequestError::Disconnected)));
+}
@@ -0,0 +1,140 @@
+//! Tests of the confirmed updates that arrive for a tick older than the prediction history
+use bevy::prelude::*;
+use bevy::utils::Duration;
+
+use crate::prelude::client::{
+    ClientConfig, Confirmed, ConnectionManager, PredictionConfig, RollbackWindowExceededEvent,
+};
+use crate::prelude::server::{Replicate, SyncTarget};
+use crate::prelude::*;
+use crate::tests::protocol::*;
+
+#[derive(Resource)]
+struct Moving(bool);
+
+fn update_component(moving: Res<Moving>, mut query: Query<&mut Component1>) {
+    if moving.0 {
+        for mut c in query.iter_mut() {
+            c.0 += 1.0;
+        }
+    }
+}
+
+#[derive(Resource, Default)]
+struct WindowExceededEvents(Vec<RollbackWindowExceededEvent>);
+
+fn record_events(
+    mut recorded: ResMut<WindowExceededEvents>,
+    mut events: EventReader<RollbackWindowExceededEvent>,
+) {
+    recorded.0.extend(events.read().cloned());
+}
+
+/// The packets are delayed by 50ms in each direction, so the confirmed updates are received
+/// more than 10 ticks after the tick at which the client predicted them
+fn setup(max_rollback_ticks: u16) -> (LightyearTestPair, Entity, Entity) {
+    let mut pair = LightyearTestPair::builder()
+        .protocol(ProtocolPlugin)
+        .tick_duration(Duration::from_millis(10))
+        .conditioner(LinkConditionerConfig {
+            incoming_latency: Duration::from_millis(50),
+            incoming_jitter: Duration::default(),
+            incoming_loss: 0.0,
+            seed: None,
+        })
+        .client_config(ClientConfig {
+            prediction: PredictionConfig::default().with_max_rollback_ticks(max_rollback_ticks),
+            ..default()
+        })
+        .build();
+    pair.client_apps[0]
+        .init_resource::<WindowExceededEvents>()
+        .add_systems(Update, record_events);
+    pair.server_app
+        .insert_resource(Moving(true))
+        .add_systems(FixedUpdate, update_component);
+
+    let server_entity = pair
+        .server_world_mut()
+        .spawn((
+            Component1(0.0),
+            Replicate {
+                sync: SyncTarget {
+                    prediction: NetworkTarget::All,
+                    ..default()
+                },
+                ..default()
+            },
+        ))
+        .id();
+    pair.frame_steps(30);
+    let confirmed = *pair
+        .client_world(0)
+        .resource::<ConnectionManager>()
+        .replication_receiver
+        .remote_entity_map
+        .get_local(server_entity)
+        .expect("entity was not replicated to client");
+    let predicted = pair
+        .client_world(0)
+        .get::<Confirmed>(confirmed)
+        .unwrap()
+        .predicted
+        .expect("the predicted entity was not spawned");
+    (pair, server_entity, predicted)
+}
+
+/// Stop the updates on the server, and return the number of frames until the predicted entity
+/// has the same value as the server entity
+fn frames_to_converge(
+    pair: &mut LightyearTestPair,
+    server_entity: Entity,
+    predicted: Entity,
+) -> usize {
+    pair.server_app.insert_resource(Moving(false));
+    let server_value = pair
+        .server_world()
+        .get::<Component1>(server_entity)
+        .cloned();
+    (1..=50)
+        .find(|_| {
+            pair.frame_step();
+            pair.client_world(0).get::<Component1>(predicted) == server_value.as_ref()
+        })
+        .expect("the predicted entity did not converge to the server state")
+}
+
+/// With a rollback window smaller than the delay of the confirmed updates, the predicted entity is snapped
+/// to the confirmed state and still converges to the server state
+#[test]
+fn test_rollback_window_exceeded() {
+    let (mut pair, server_entity, predicted) = setup(3);
+    pair.frame_steps(50);
+    let events = std::mem::take(
+        &mut pair
+            .client_world_mut(0)
+            .resource_mut::<WindowExceededEvents>()
+            .0,
+    );
+    assert!(!events.is_empty());
+    for event in events {
+        assert_eq!(event.entity, predicted);
+        assert!(event.confirmed_tick < event.earliest_history_tick);
+    }
+    // the updates are received after ~11 ticks
+    assert!(frames_to_converge(&mut pair, server_entity, predicted) <= 20);
+}
+
+/// With the default rollback window, the late updates are handled with regular rollbacks
+#[test]
+fn test_rollback_window_not_exceeded() {
+    let (mut pair, server_entity, predicted) =
+        setup(PredictionConfig::default().max_rollback_ticks);
+    pair.frame_steps(50);
+    assert!(pair
+        .client_world(0)
+        .resource::<WindowExceededEvents>()
+        .0
+        .is_empty());
+    assert!(frames_to_converge(&mut pair, server_entity, predicted) <= 20);
+}
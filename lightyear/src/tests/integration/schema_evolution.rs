@@ -0,0 +1,258 @@
+//! Tests of the compatibility between peers that use different versions of the schema of a component
+use bevy::prelude::*;
+use lightyear_macros::VersionedInternal;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::connection::server::DeniedReason;
+use crate::prelude::client::{ConnectError, ConnectionFailedEvent};
+use crate::prelude::server::Replicate;
+use crate::prelude::*;
+use crate::tests::host_server_stepper::{
+    HostServerStepper, Step, EXTERNAL_CLIENT_ID, LOCAL_CLIENT_ID,
+};
+use crate::tests::protocol::*;
+
+/// The component before the update of the game
+mod old {
+    use super::*;
+
+    #[derive(Component, Serialize, Deserialize, Clone, Debug, PartialEq, VersionedInternal)]
+    pub struct Armor {
+        pub value: u16,
+    }
+}
+
+/// The component after the update of the game, with an added field
+mod new {
+    use super::*;
+
+    #[derive(Component, Serialize, Deserialize, Clone, Debug, PartialEq, VersionedInternal)]
+    pub struct Armor {
+        pub value: u16,
+        #[net(added_in = 2, default)]
+        pub shield: u16,
+    }
+}
+
+/// Another update that added a field of a different type in the same version
+mod incompatible {
+    use super::*;
+
+    #[derive(Component, Serialize, Deserialize, Clone, Debug, PartialEq, VersionedInternal)]
+    pub struct Armor {
+        pub value: u16,
+        #[net(added_in = 2, default)]
+        pub shield: String,
+    }
+}
+
+/// The field of the first version was retyped
+mod retyped {
+    use super::*;
+
+    #[derive(Component, Serialize, Deserialize, Clone, Debug, PartialEq, VersionedInternal)]
+    pub struct Armor {
+        pub value: u32,
+    }
+}
+
+/// Register a version of the component; all the versions have the same net id
+fn register<C: Component + Message + Versioned + Serialize + DeserializeOwned + PartialEq>(
+    app: &mut App,
+) {
+    app.register_component::<C>(ChannelDirection::ServerToClient)
+        .with_protocol_name("Armor")
+        .add_schema_evolution();
+}
+
+#[derive(Resource, Default)]
+struct ClientEvents {
+    kicks: Vec<Option<String>>,
+    failures: Vec<ConnectError>,
+}
+
+fn record_client_events(
+    mut events: ResMut<ClientEvents>,
+    mut disconnections: EventReader<client::DisconnectEvent>,
+    mut failures: EventReader<ConnectionFailedEvent>,
+) {
+    events.kicks.extend(
+        disconnections
+            .read()
+            .filter_map(|event| match &event.reason {
+                Some(client::DisconnectReason::Kicked(reason)) => Some(reason.clone()),
+                _ => None,
+            }),
+    );
+    events
+        .failures
+        .extend(failures.read().map(|event| event.error.clone()));
+}
+
+fn build_pair(register_client: fn(&mut App), register_server: fn(&mut App)) -> LightyearTestPair {
+    let mut pair = LightyearTestPair::builder()
+        .protocol(ProtocolPlugin)
+        .build_disconnected();
+    register_client(&mut pair.client_apps[0]);
+    register_server(&mut pair.server_app);
+    pair.client_apps[0]
+        .init_resource::<ClientEvents>()
+        .add_systems(Update, record_client_events);
+    pair.connect();
+    pair
+}
+
+/// Spawn an entity with the component on the server, and return the component received by the client
+fn replicate<S: Component, C: Component + Clone>(pair: &mut LightyearTestPair, component: S) -> C {
+    let server_entity = pair
+        .server_world_mut()
+        .spawn((component, Replicate::default()))
+        .id();
+    pair.frame_steps(10);
+    let client_entity = *pair
+        .client_world(0)
+        .resource::<client::ConnectionManager>()
+        .replication_receiver
+        .remote_entity_map
+        .get_local(server_entity)
+        .expect("the entity was not replicated");
+    pair.client_world(0)
+        .get::<C>(client_entity)
+        .expect("the component was not replicated")
+        .clone()
+}
+
+fn schema_versions<C: 'static>(pair: &LightyearTestPair) -> (Option<u8>, Option<u8>) {
+    let client_id = pair.client_id(0);
+    (
+        pair.client_world(0)
+            .resource::<client::ConnectionManager>()
+            .schema_version::<C>(),
+        pair.server_world()
+            .resource::<server::ConnectionManager>()
+            .schema_version::<C>(client_id),
+    )
+}
+
+/// The client of the previous release ignores the fields added to the component
+#[test]
+fn test_old_client_new_server() {
+    let mut pair = build_pair(register::<old::Armor>, register::<new::Armor>);
+    assert_eq!(
+        pair.client_apps[0].net_id_report().hash(),
+        pair.server_app.net_id_report().hash()
+    );
+    let armor: old::Armor = replicate(
+        &mut pair,
+        new::Armor {
+            value: 10,
+            shield: 5,
+        },
+    );
+    assert_eq!(armor, old::Armor { value: 10 });
+    assert_eq!(schema_versions::<old::Armor>(&pair).0, Some(1));
+    assert_eq!(schema_versions::<new::Armor>(&pair).1, Some(1));
+}
+
+/// The client of the next release fills the fields that the server doesn't send with their default value
+#[test]
+fn test_new_client_old_server() {
+    let mut pair = build_pair(register::<new::Armor>, register::<old::Armor>);
+    let armor: new::Armor = replicate(&mut pair, old::Armor { value: 10 });
+    assert_eq!(
+        armor,
+        new::Armor {
+            value: 10,
+            shield: 0,
+        }
+    );
+    assert_eq!(schema_versions::<new::Armor>(&pair).0, Some(1));
+    assert_eq!(schema_versions::<old::Armor>(&pair).1, Some(1));
+}
+
+#[test]
+fn test_same_version() {
+    let mut pair = build_pair(register::<new::Armor>, register::<new::Armor>);
+    let armor = new::Armor {
+        value: 10,
+        shield: 5,
+    };
+    assert_eq!(replicate::<_, new::Armor>(&mut pair, armor.clone()), armor);
+    assert_eq!(schema_versions::<new::Armor>(&pair), (Some(2), Some(2)));
+    // the schemas of the other components cannot evolve
+    assert_eq!(schema_versions::<Component1>(&pair), (None, None));
+}
+
+/// The peers disagree on the fields added in the second version: the server disconnects the client
+#[test]
+fn test_incompatible_added_field() {
+    let mut pair = build_pair(register::<incompatible::Armor>, register::<new::Armor>);
+    pair.frame_steps(10);
+    let kicks = &pair.client_world(0).resource::<ClientEvents>().kicks;
+    assert_eq!(kicks.len(), 1);
+    assert!(
+        kicks[0]
+            .as_ref()
+            .is_some_and(|reason| reason.starts_with("incompatible schema of")),
+        "{kicks:?}"
+    );
+}
+
+/// Retyping a field of the first version changes the protocol hash: the client cannot connect
+#[test]
+fn test_retyped_field() {
+    let pair = build_pair(register::<retyped::Armor>, register::<old::Armor>);
+    assert_ne!(
+        pair.client_apps[0].net_id_report().hash(),
+        pair.server_app.net_id_report().hash()
+    );
+    assert_eq!(
+        pair.client_world(0).resource::<ClientEvents>().failures,
+        vec![ConnectError::Denied(DeniedReason::ProtocolMismatch)]
+    );
+}
+
+/// The local client of a host-server shares the schemas of the server, and the external clients negotiate them
+#[test]
+fn test_host_server() {
+    let mut stepper = HostServerStepper::new(
+        SharedConfig {
+            tick: TickConfig::new(bevy::utils::Duration::from_millis(10)),
+            ..default()
+        },
+        client::ClientConfig::default(),
+        bevy::utils::Duration::from_millis(10),
+    );
+    register::<new::Armor>(&mut stepper.server_app);
+    register::<old::Armor>(&mut stepper.client_app);
+    stepper.init();
+    for _ in 0..10 {
+        stepper.frame_step();
+    }
+
+    let server_world = stepper.server_app.world();
+    let server_manager = server_world.resource::<server::ConnectionManager>();
+    assert_eq!(
+        server_manager.schema_version::<new::Armor>(ClientId::Local(LOCAL_CLIENT_ID)),
+        Some(2)
+    );
+    assert_eq!(
+        server_world
+            .resource::<client::ConnectionManager>()
+            .schema_version::<new::Armor>(),
+        Some(2)
+    );
+    assert_eq!(
+        server_manager.schema_version::<new::Armor>(ClientId::Netcode(EXTERNAL_CLIENT_ID)),
+        Some(1)
+    );
+    assert_eq!(
+        stepper
+            .client_app
+            .world()
+            .resource::<client::ConnectionManager>()
+            .schema_version::<old::Armor>(),
+        Some(1)
+    );
+}
@@ -0,0 +1,113 @@
+//! Tests of the tracking of the serialized size of the messages and components that are sent
+use bevy::prelude::*;
+use bevy::utils::Duration;
+use serde::{Deserialize, Serialize};
+
+use crate::prelude::client::ClientConfig;
+use crate::prelude::server::{Replicate, ServerConfig};
+use crate::prelude::*;
+use crate::tests::protocol::*;
+
+/// Component that usually holds a single short item, but sometimes gets bloated
+#[derive(Component, Serialize, Deserialize, Clone, Debug, PartialEq)]
+struct Inventory(Vec<String>);
+
+const WARNING_THRESHOLD: usize = 256;
+/// Tick at which the server fills the inventory
+const BLOATED_TICK: u32 = 100;
+
+#[derive(Clone)]
+struct InventoryProtocolPlugin;
+
+impl Plugin for InventoryProtocolPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(ProtocolPlugin);
+        app.register_component::<Inventory>(ChannelDirection::ServerToClient)
+            .with_size_warning_threshold(WARNING_THRESHOLD);
+    }
+}
+
+#[derive(Resource, Default)]
+struct Updates(u32);
+
+/// Change the inventory at every tick; it contains 100 long items once
+fn update_inventory(mut updates: ResMut<Updates>, mut query: Query<&mut Inventory>) {
+    updates.0 += 1;
+    for mut inventory in query.iter_mut() {
+        inventory.0 = if updates.0 == BLOATED_TICK {
+            (0..100).map(|i| format!("{i:0>32}")).collect()
+        } else {
+            vec![format!("{:04}", updates.0)]
+        };
+    }
+}
+
+/// A single bloated serialization is reported, and shows up in the max but not in the p99
+#[test]
+fn test_bloated_component() {
+    let mut server_config = ServerConfig::default();
+    server_config.packet.serialized_size.enabled = true;
+    let mut pair = LightyearTestPair::builder()
+        .protocol(InventoryProtocolPlugin)
+        .tick_duration(Duration::from_millis(10))
+        .server_config(server_config)
+        .build_disconnected();
+    pair.server_app
+        .init_resource::<Updates>()
+        .add_systems(FixedUpdate, update_inventory);
+    pair.connect();
+
+    pair.server_world_mut()
+        .spawn((Inventory(vec![]), Replicate::default()));
+    pair.frame_steps(300);
+
+    let manager = pair.server_world().resource::<server::ConnectionManager>();
+    let stats = manager.serialized_sizes().component::<Inventory>().unwrap();
+    assert_eq!(stats.name(), std::any::type_name::<Inventory>());
+    assert!(stats.count() >= 200, "count: {}", stats.count());
+    // 100 items of 32 bytes
+    assert!(stats.max() > 3300, "max: {}", stats.max());
+    // the other serializations contain one item of 4 bytes, and all have the same size
+    assert!(stats.p99() < 16, "p99: {}", stats.p99());
+    assert_eq!(stats.p99(), stats.quantile(0.5));
+    assert!(stats.mean() < 16.0 + stats.max() as f64 / stats.count() as f64);
+    assert_eq!(
+        manager
+            .warnings()
+            .count(NetworkWarning::OversizedSerialization),
+        1
+    );
+    assert!(manager.serialized_sizes().message::<Message1>().is_none());
+}
+
+/// The messages sent by the client are checked against the global threshold
+#[test]
+fn test_message_global_threshold() {
+    let mut client_config = ClientConfig::default();
+    client_config.packet.serialized_size =
+        SerializedSizeConfig::default().with_warning_threshold(WARNING_THRESHOLD);
+    let mut pair = LightyearTestPair::builder()
+        .protocol(ProtocolPlugin)
+        .tick_duration(Duration::from_millis(10))
+        .client_config(client_config)
+        .build();
+    pair.frame_steps(20);
+
+    for len in [10, 100, 1000] {
+        pair.client_world_mut(0)
+            .resource_mut::<client::ConnectionManager>()
+            .send_message::<Channel1, _>(&Message1("a".repeat(len)))
+            .unwrap();
+    }
+    let manager = pair.client_world(0).resource::<client::ConnectionManager>();
+    let stats = manager.serialized_sizes().message::<Message1>().unwrap();
+    assert_eq!(stats.count(), 3);
+    // the size includes the net id and the length of the string
+    assert!((1000..1010).contains(&stats.max()), "max: {}", stats.max());
+    assert_eq!(
+        manager
+            .warnings()
+            .count(NetworkWarning::OversizedSerialization),
+        1
+    );
+}
@@ -0,0 +1,174 @@
+//! Tests related to the network statistics accumulated over a whole session
+use bevy::ecs::system::RunSystemOnce;
+use bevy::prelude::*;
+
+use crate::prelude::client::ClientCommands;
+use crate::prelude::server::{Replicate, RoomId, RoomManager};
+use crate::prelude::*;
+use crate::tests::protocol::*;
+use crate::tests::stepper::{BevyStepper, Step, TEST_CLIENT_ID};
+
+#[derive(Resource, Default)]
+struct Summaries {
+    client: Option<SessionSummary>,
+    server: Option<SessionSummary>,
+}
+
+fn record_client_summary(
+    mut summaries: ResMut<Summaries>,
+    mut events: EventReader<client::DisconnectEvent>,
+) {
+    for event in events.read() {
+        summaries.client = Some(event.summary.clone());
+    }
+}
+
+fn record_server_summary(
+    mut summaries: ResMut<Summaries>,
+    mut events: EventReader<server::DisconnectEvent>,
+) {
+    for event in events.read() {
+        summaries.server = Some(event.summary.clone());
+    }
+}
+
+fn client_summary(stepper: &BevyStepper) -> SessionSummary {
+    stepper
+        .client_app
+        .world()
+        .resource::<client::ConnectionManager>()
+        .session_summary()
+}
+
+fn server_summary(stepper: &BevyStepper) -> SessionSummary {
+    stepper
+        .server_app
+        .world()
+        .resource::<server::ConnectionManager>()
+        .connection(ClientId::Netcode(TEST_CLIENT_ID))
+        .unwrap()
+        .session_summary()
+}
+
+/// The client enters and leaves the room of an entity, and the session is then closed by the client.
+///
+/// This test checks that:
+/// - the summaries of both sides agree on the traffic that was exchanged
+/// - the interest-driven spawns and despawns are counted on the server
+/// - the summaries are delivered with the `DisconnectEvent` on both sides
+#[test]
+fn test_session_summary() {
+    let mut stepper = BevyStepper::default();
+    stepper.client_app.init_resource::<Summaries>();
+    stepper
+        .client_app
+        .add_systems(Update, record_client_summary);
+    stepper.server_app.init_resource::<Summaries>();
+    stepper
+        .server_app
+        .add_systems(Update, record_server_summary);
+
+    let client_id = ClientId::Netcode(TEST_CLIENT_ID);
+    let entity = stepper
+        .server_app
+        .world_mut()
+        .spawn((
+            Component1(0.0),
+            Replicate {
+                relevance_mode: NetworkRelevanceMode::InterestManagement,
+                ..default()
+            },
+        ))
+        .id();
+    stepper
+        .server_app
+        .world_mut()
+        .resource_mut::<RoomManager>()
+        .add_entity(entity, RoomId(0));
+
+    // the entity becomes relevant, then not relevant, then relevant again
+    for join in [true, false, true] {
+        let mut room_manager = stepper.server_app.world_mut().resource_mut::<RoomManager>();
+        if join {
+            room_manager.add_client(client_id, RoomId(0));
+        } else {
+            room_manager.remove_client(client_id, RoomId(0));
+        }
+        for _ in 0..10 {
+            stepper.frame_step();
+        }
+    }
+
+    let start = client_summary(&stepper);
+    for _ in 0..100 {
+        stepper.frame_step();
+    }
+    let client = client_summary(&stepper);
+    let server = server_summary(&stepper);
+
+    // the summary is accumulated over the whole session; the client's virtual time can be slightly
+    // sped up or slowed down to stay in sync with the server
+    let elapsed = (client.duration - start.duration).as_secs_f32();
+    assert!((elapsed - 1.0).abs() < 0.1, "elapsed: {elapsed}s");
+    assert!(client.bytes_sent > start.bytes_sent);
+    assert!(client.bytes_received > start.bytes_received);
+    // the client sends before the server receives on every frame, so everything it sent was received
+    assert_eq!(server.bytes_received, client.bytes_sent);
+    assert_eq!(server.packets_received, client.packets_sent);
+    // the packets sent by the server on the last frame have not been received yet
+    assert!(client.bytes_received <= server.bytes_sent);
+    assert!(client.packets_received <= server.packets_sent);
+    let receive_stats = stepper
+        .client_app
+        .world()
+        .resource::<client::ConnectionManager>()
+        .receive_stats();
+    assert_eq!(client.bytes_received, receive_stats.bytes);
+    assert_eq!(client.packets_received, receive_stats.packets);
+    // the local channels never drop packets
+    assert_eq!(client.packets_lost, 0);
+    assert_eq!(server.packets_lost, 0);
+    assert_eq!(client.packet_loss(), 0.0);
+
+    // the rtt is measured with the pings sent every 100ms
+    for rtt in [client.rtt, server.rtt] {
+        assert!(rtt.samples >= 10);
+        assert!([rtt.mean, rtt.p50, rtt.p90, rtt.p99]
+            .iter()
+            .all(|value| *value <= rtt.max));
+    }
+
+    assert_eq!(server.relevance_spawns, 2);
+    assert_eq!(server.relevance_despawns, 1);
+    assert_eq!(client.relevance_spawns, 0);
+    assert_eq!(server.rollbacks, 0);
+
+    // the summaries are delivered when the session ends
+    stepper
+        .client_app
+        .world_mut()
+        .run_system_once(|mut commands: Commands| commands.disconnect_client());
+    for _ in 0..10 {
+        stepper.frame_step();
+    }
+    let client_final = stepper
+        .client_app
+        .world()
+        .resource::<Summaries>()
+        .client
+        .clone()
+        .expect("the client should have received a DisconnectEvent");
+    assert!(client_final.duration >= client.duration);
+    assert!(client_final.bytes_sent >= client.bytes_sent);
+    let server_final = stepper
+        .server_app
+        .world()
+        .resource::<Summaries>()
+        .server
+        .clone()
+        .expect("the server should have received a DisconnectEvent");
+    assert!(server_final.duration >= server.duration);
+    assert_eq!(server_final.relevance_spawns, 2);
+    assert_eq!(server_final.relevance_despawns, 1);
+    assert_eq!(server_final.disconnect_reason, None);
+}
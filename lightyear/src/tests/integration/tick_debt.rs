@@ -0,0 +1,177 @@
+//! Tests of the catch-up ticks simulated when a frame lasts longer than a tick
+use bevy::prelude::*;
+use bevy::utils::Duration;
+
+use crate::prelude::client::{
+    CatchUpInputs, ClientConfig, InputConfig, InputManager, InputSystemSet, SyncConfig,
+    TickStallEvent,
+};
+use crate::prelude::server::InputEvent;
+use crate::prelude::*;
+use crate::shared::tick_manager::TickEvent;
+use crate::tests::protocol::*;
+
+const MAX_CATCH_UP_TICKS: u16 = 4;
+
+/// Number of ticks simulated by the client
+#[derive(Resource, Default)]
+struct FixedRuns(u32);
+
+fn count_fixed_runs(mut runs: ResMut<FixedRuns>) {
+    runs.0 += 1;
+}
+
+#[derive(Resource, Default)]
+struct Stalls(Vec<TickStallEvent>);
+
+fn record_stalls(mut stalls: ResMut<Stalls>, mut events: EventReader<TickStallEvent>) {
+    stalls.0.extend(events.read().cloned());
+}
+
+#[derive(Resource, Default)]
+struct TickSnaps(u32);
+
+/// Every tick, the client adds an input whose value is the tick
+fn buffer_inputs(mut input_manager: ResMut<InputManager<MyInput>>, tick_manager: Res<TickManager>) {
+    input_manager.add_input(MyInput(tick_manager.tick().0 as i16), tick_manager.tick());
+}
+
+/// Inputs received by the server, for each server tick
+#[derive(Resource, Default)]
+struct ReceivedInputs(Vec<Option<MyInput>>);
+
+fn record_inputs(
+    mut received: ResMut<ReceivedInputs>,
+    mut events: EventReader<InputEvent<MyInput>>,
+) {
+    received
+        .0
+        .extend(events.read().map(|event| event.input().clone()));
+}
+
+fn build_pair(frame_duration: Duration, input: InputConfig) -> LightyearTestPair {
+    let mut pair = LightyearTestPair::builder()
+        .protocol(ProtocolPlugin)
+        .frame_duration(frame_duration)
+        .client_config(ClientConfig {
+            sync: SyncConfig::default().max_catch_up_ticks(MAX_CATCH_UP_TICKS),
+            input,
+            ..default()
+        })
+        .build_disconnected();
+    pair.client_apps[0]
+        .init_resource::<FixedRuns>()
+        .init_resource::<Stalls>()
+        .init_resource::<TickSnaps>()
+        .add_systems(FixedUpdate, count_fixed_runs)
+        .add_systems(Update, record_stalls)
+        .add_systems(
+            FixedPreUpdate,
+            buffer_inputs.in_set(InputSystemSet::BufferInputs),
+        )
+        .observe(
+            |trigger: Trigger<TickEvent>, mut snaps: ResMut<TickSnaps>| {
+                if matches!(trigger.event(), TickEvent::TickSnap { .. }) {
+                    snaps.0 += 1;
+                }
+            },
+        );
+    pair.server_app
+        .init_resource::<ReceivedInputs>()
+        .add_systems(FixedUpdate, record_inputs);
+    pair.connect();
+    pair
+}
+
+/// Number of ticks that the client is ahead of the server
+fn tick_offset(pair: &LightyearTestPair) -> i16 {
+    pair.client_world(0).resource::<TickManager>().tick()
+        - pair.server_world().resource::<TickManager>().tick()
+}
+
+/// A 300ms stall of the client main thread only simulates the maximum number of catch-up ticks,
+/// then the client tick snaps back to the sync objective
+#[test]
+fn test_stall_bounds_catch_up_ticks() {
+    let mut pair = build_pair(Duration::from_millis(10), InputConfig::default());
+    pair.frame_steps(100);
+    let offset = tick_offset(&pair);
+    let snaps = pair.client_world(0).resource::<TickSnaps>().0;
+    let runs = pair.client_world(0).resource::<FixedRuns>().0;
+
+    // the server keeps running while the client is stalled
+    for _ in 0..30 {
+        pair.advance_time(Duration::from_millis(10));
+        pair.server_app.update();
+    }
+    pair.client_apps[0].update();
+
+    let client_world = pair.client_world(0);
+    assert!(client_world.resource::<FixedRuns>().0 - runs <= MAX_CATCH_UP_TICKS as u32);
+    let stalls = &client_world.resource::<Stalls>().0;
+    assert_eq!(stalls.len(), 1);
+    assert_eq!(stalls[0].frame_duration, Duration::from_millis(300));
+    assert!(stalls[0].skipped_ticks >= 30 - MAX_CATCH_UP_TICKS);
+    assert_eq!(client_world.resource::<TickSnaps>().0, snaps + 1);
+
+    // the client is back at the same distance from the server, without further snaps
+    pair.server_app.update();
+    pair.frame_steps(50);
+    assert!((tick_offset(&pair) - offset).abs() <= 1);
+    assert_eq!(pair.client_world(0).resource::<TickSnaps>().0, snaps + 1);
+    assert_eq!(pair.client_world(0).resource::<Stalls>().0.len(), 1);
+}
+
+/// The frames that simulate several ticks send a single input message that contains the inputs of all these
+/// ticks, and the inputs sampled during the catch-up ticks are repeated from the first tick of the frame
+#[test]
+fn test_catch_up_inputs_repeated() {
+    let mut pair = build_pair(
+        Duration::from_millis(40),
+        InputConfig {
+            packet_redundancy: 1,
+            catch_up_inputs: CatchUpInputs::Repeat,
+            ..default()
+        },
+    );
+    pair.frame_steps(20);
+    pair.server_world_mut()
+        .resource_mut::<ReceivedInputs>()
+        .0
+        .clear();
+    pair.frame_steps(20);
+
+    let received = &pair.server_world().resource::<ReceivedInputs>().0;
+    assert_eq!(received.len(), 80);
+    assert!(received.iter().all(Option::is_some), "{received:?}");
+    // a new input every 4 ticks
+    let mut distinct = received.clone();
+    distinct.dedup();
+    assert!((20..=21).contains(&distinct.len()), "{received:?}");
+    assert!(pair.client_world(0).resource::<Stalls>().0.is_empty());
+}
+
+/// With a single packet of redundancy, the input message sent after a frame that simulated several ticks still
+/// contains the inputs of all these ticks
+#[test]
+fn test_catch_up_inputs_sent() {
+    let mut pair = build_pair(
+        Duration::from_millis(40),
+        InputConfig {
+            packet_redundancy: 1,
+            ..default()
+        },
+    );
+    pair.frame_steps(20);
+    pair.server_world_mut()
+        .resource_mut::<ReceivedInputs>()
+        .0
+        .clear();
+    pair.frame_steps(20);
+
+    // the server repeats the last input on the ticks whose input is missing
+    let received = &pair.server_world().resource::<ReceivedInputs>().0;
+    let mut distinct = received.clone();
+    distinct.dedup();
+    assert_eq!(distinct.len(), 80, "{received:?}");
+}
@@ -0,0 +1,229 @@
+//! Tests related to a server that runs with a different tick duration than the client
+use bevy::ecs::system::RunSystemOnce;
+use bevy::prelude::*;
+use bevy::utils::Duration;
+
+use crate::client::error::ConnectError;
+use crate::connection::client::DisconnectReason;
+use crate::prelude::client::{ClientConfig, PredictionConfig, SyncConfig};
+use crate::prelude::server::ServerCommands;
+use crate::prelude::*;
+use crate::tests::stepper::{BevyStepper, Step};
+
+const CLIENT_TICK: Duration = Duration::from_millis(10);
+const SERVER_TICK: Duration = Duration::from_millis(20);
+
+/// The connection errors received with the `DisconnectEvent`s of the client
+#[derive(Resource, Default)]
+struct ConnectErrors(Vec<Option<ConnectError>>);
+
+fn record_connect_errors(
+    mut errors: ResMut<ConnectErrors>,
+    mut events: EventReader<client::DisconnectEvent>,
+) {
+    for event in events.read() {
+        errors.0.push(match &event.reason {
+            Some(DisconnectReason::Connect(error)) => Some(error.clone()),
+            _ => None,
+        });
+    }
+}
+
+/// Client configured with a 10ms tick, connected to a server that ticks every `server_tick`
+fn setup(accept_server_tick_rate: bool, server_tick: Duration) -> BevyStepper {
+    let shared_config = SharedConfig {
+        tick: TickConfig::new(CLIENT_TICK),
+        ..default()
+    };
+    let client_config = ClientConfig {
+        sync: SyncConfig::default().accept_server_tick_rate(accept_server_tick_rate),
+        prediction: PredictionConfig {
+            minimum_input_delay_ticks: 4,
+            maximum_input_delay_before_prediction: 6,
+            maximum_predicted_ticks: 100,
+            ..default()
+        },
+        ..default()
+    };
+    let mut stepper = BevyStepper::new(shared_config, client_config, Duration::from_millis(10));
+    let world = stepper.server_app.world_mut();
+    world.resource_mut::<server::ServerConfig>().shared.tick = TickConfig::new(server_tick);
+    world.resource_mut::<TickManager>().config = TickConfig::new(server_tick);
+    world
+        .resource_mut::<Time<Fixed>>()
+        .set_timestep(server_tick);
+    stepper.client_app.init_resource::<ConnectErrors>();
+    stepper
+        .client_app
+        .add_systems(Update, record_connect_errors);
+    stepper.init();
+    stepper
+}
+
+#[test]
+fn test_adopt_server_tick_rate() {
+    let mut stepper = setup(true, SERVER_TICK);
+    assert!(stepper
+        .client_app
+        .world()
+        .resource::<client::ConnectionManager>()
+        .is_synced());
+
+    // the fixed timestep and the tick settings now use the server's tick duration
+    let world = stepper.client_app.world();
+    assert_eq!(
+        world.resource::<TickManager>().config.tick_duration,
+        SERVER_TICK
+    );
+    assert_eq!(world.resource::<Time<Fixed>>().timestep(), SERVER_TICK);
+    let config = world.resource::<ClientConfig>();
+    assert_eq!(config.shared.tick.tick_duration, SERVER_TICK);
+    // the input delay settings keep the same duration
+    assert_eq!(config.prediction.minimum_input_delay_ticks, 2);
+    assert_eq!(config.prediction.maximum_input_delay_before_prediction, 3);
+    assert_eq!(config.prediction.maximum_predicted_ticks, 50);
+
+    // the client advances at the same tick rate as the server
+    let (client_start, server_start) = (stepper.client_tick(), stepper.server_tick());
+    for _ in 0..100 {
+        stepper.frame_step();
+    }
+    let client_ticks = stepper.client_tick() - client_start;
+    let server_ticks = stepper.server_tick() - server_start;
+    assert_eq!(server_ticks, 50);
+    assert!(
+        (client_ticks - server_ticks).abs() <= 3,
+        "client ticks: {client_ticks}, server ticks: {server_ticks}"
+    );
+    assert!(stepper
+        .client_app
+        .world()
+        .resource::<ConnectErrors>()
+        .0
+        .is_empty());
+}
+
+#[test]
+fn test_reject_server_tick_rate() {
+    // the same client config is accepted by a server with the same tick duration
+    let stepper = setup(false, CLIENT_TICK);
+    assert!(stepper
+        .client_app
+        .world()
+        .resource::<client::ConnectionManager>()
+        .is_synced());
+
+    let stepper = setup(false, SERVER_TICK);
+    let world = stepper.client_app.world();
+    assert!(!world.resource::<client::ConnectionManager>().is_synced());
+    assert_eq!(
+        world.resource::<State<client::NetworkingState>>().get(),
+        &client::NetworkingState::Disconnected
+    );
+    // the client kept its own tick duration
+    assert_eq!(
+        world.resource::<TickManager>().config.tick_duration,
+        CLIENT_TICK
+    );
+    assert_eq!(
+        world.resource::<ConnectErrors>().0,
+        vec![Some(ConnectError::TickRateMismatch {
+            client: CLIENT_TICK,
+            server: SERVER_TICK,
+        })]
+    );
+}
+
+fn set_server_tick_duration(stepper: &mut BevyStepper, tick_duration: Duration) {
+    stepper
+        .server_app
+        .world_mut()
+        .run_system_once(move |mut commands: Commands| {
+            commands.set_tick_duration(tick_duration);
+        });
+}
+
+fn interpolation_tick(stepper: &BevyStepper) -> Tick {
+    let world = stepper.client_app.world();
+    world
+        .resource::<client::ConnectionManager>()
+        .sync_manager
+        .interpolation_tick(world.resource::<TickManager>())
+}
+
+/// Returns the number of ticks simulated by the client and the server during 100 frames
+fn ticks_in_100_frames(stepper: &mut BevyStepper) -> (i16, i16) {
+    let (client_start, server_start) = (stepper.client_tick(), stepper.server_tick());
+    for _ in 0..100 {
+        stepper.frame_step();
+    }
+    (
+        stepper.client_tick() - client_start,
+        stepper.server_tick() - server_start,
+    )
+}
+
+/// The server changes its tick duration at runtime (e.g. between a lobby and a match): the client adopts it
+/// even if it does not accept a different tick duration when connecting, and re-syncs with the server
+#[test]
+fn test_change_tick_duration_at_runtime() {
+    let mut stepper = setup(false, CLIENT_TICK);
+    for (tick_duration, expected_ticks, input_delay) in
+        [(SERVER_TICK, 50, 2), (CLIENT_TICK, 100, 4)]
+    {
+        set_server_tick_duration(&mut stepper, tick_duration);
+        // the interpolation timeline does not jump when the tick duration changes
+        let mut current = interpolation_tick(&stepper);
+        for _ in 0..20 {
+            stepper.frame_step();
+            let previous = std::mem::replace(&mut current, interpolation_tick(&stepper));
+            assert!(
+                (0..=2).contains(&(current - previous)),
+                "interpolation tick went from {previous:?} to {current:?}"
+            );
+        }
+        let world = stepper.client_app.world();
+        assert!(world.resource::<client::ConnectionManager>().is_synced());
+        assert_eq!(
+            world.resource::<TickManager>().config.tick_duration,
+            tick_duration
+        );
+        assert_eq!(world.resource::<Time<Fixed>>().timestep(), tick_duration);
+        let config = world.resource::<ClientConfig>();
+        assert_eq!(config.shared.tick.tick_duration, tick_duration);
+        assert_eq!(config.prediction.minimum_input_delay_ticks, input_delay);
+
+        // the client advances at the new tick rate, still ahead of the server
+        let (client_ticks, server_ticks) = ticks_in_100_frames(&mut stepper);
+        assert_eq!(server_ticks, expected_ticks);
+        assert!(
+            (client_ticks - server_ticks).abs() <= 3,
+            "client ticks: {client_ticks}, server ticks: {server_ticks}"
+        );
+        let ahead = stepper.client_tick() - stepper.server_tick();
+        assert!((0..=10).contains(&ahead), "client ahead by {ahead} ticks");
+
+        // the server ticks are mapped to the server's UNIX time with the new tick duration
+        let tick = stepper.server_tick() - 10;
+        let client_unix = stepper
+            .client_app
+            .world()
+            .resource::<NetworkTime>()
+            .tick_to_unix(tick)
+            .unwrap();
+        let server_unix = stepper
+            .server_app
+            .world()
+            .resource::<NetworkTime>()
+            .tick_to_unix(tick)
+            .unwrap();
+        let error = client_unix.max(server_unix) - client_unix.min(server_unix);
+        assert!(error <= Duration::from_millis(20), "error: {error:?}");
+    }
+    assert!(stepper
+        .client_app
+        .world()
+        .resource::<ConnectErrors>()
+        .0
+        .is_empty());
+}
@@ -0,0 +1,166 @@
+//! Tests of the messages sent by the server at a tick of a visual timeline of the client
+use bevy::prelude::*;
+use bevy::utils::Duration;
+
+use crate::prelude::client::{
+    ClientConfig, Interpolated, InterpolationConfig, InterpolationDelay, InterpolationSet,
+    MessageEvent,
+};
+use crate::prelude::server::{ConnectionManager, Replicate, SyncTarget};
+use crate::prelude::*;
+use crate::tests::protocol::*;
+
+/// What the client displayed during each frame
+#[derive(Resource, Default)]
+struct Frames(Vec<Frame>);
+
+struct Frame {
+    tick: Tick,
+    /// Value of the interpolated `Component1`
+    interpolated: Option<f32>,
+    deliveries: Vec<TimelineDelivery>,
+}
+
+/// The server sets `Component1` to the current tick, so the interpolated value displays the interpolation tick
+fn set_tick_value(tick_manager: Res<TickManager>, mut query: Query<&mut Component1>) {
+    for mut component in query.iter_mut() {
+        component.0 = tick_manager.tick().0 as f32;
+    }
+}
+
+fn record_frame(
+    tick_manager: Res<TickManager>,
+    mut frames: ResMut<Frames>,
+    query: Query<&Component1, With<Interpolated>>,
+    mut events: EventReader<MessageEvent<Message1>>,
+) {
+    frames.0.push(Frame {
+        tick: tick_manager.tick(),
+        interpolated: query.get_single().ok().map(|component| component.0),
+        deliveries: events
+            .read()
+            .map(|event| *event.timeline().unwrap())
+            .collect(),
+    });
+}
+
+fn build_pair() -> LightyearTestPair {
+    let mut pair = LightyearTestPair::builder()
+        .protocol(ProtocolPlugin)
+        // the interpolated entities are 10 ticks behind the server
+        .client_config(ClientConfig {
+            interpolation: InterpolationConfig::default().with_delay(
+                InterpolationDelay::default().with_min_delay(Duration::from_millis(100)),
+            ),
+            ..default()
+        })
+        .build_disconnected();
+    pair.server_app.add_systems(FixedUpdate, set_tick_value);
+    pair.client_apps[0]
+        .init_resource::<Frames>()
+        .add_systems(Update, record_frame.after(InterpolationSet::Interpolate));
+    pair.connect();
+    pair.server_world_mut().spawn((
+        Component1(0.0),
+        Replicate {
+            sync: SyncTarget {
+                interpolation: NetworkTarget::All,
+                ..default()
+            },
+            ..default()
+        },
+    ));
+    pair.frame_steps(50);
+    pair
+}
+
+fn send_at_tick(pair: &mut LightyearTestPair, tick: Tick, timeline: VisualTimeline) {
+    pair.server_world_mut()
+        .resource_mut::<ConnectionManager>()
+        .send_message_at_tick::<Channel1, _>(
+            &Message1("effect".to_string()),
+            tick,
+            timeline,
+            NetworkTarget::All,
+        )
+        .unwrap();
+}
+
+/// Steps frames and returns the index of the frame during which the message was emitted
+fn delivery_frame(pair: &mut LightyearTestPair) -> (usize, TimelineDelivery) {
+    pair.client_world_mut(0).resource_mut::<Frames>().0.clear();
+    pair.frame_steps(50);
+    let frames = &pair.client_world(0).resource::<Frames>().0;
+    let deliveries: Vec<_> = frames
+        .iter()
+        .enumerate()
+        .flat_map(|(i, frame)| frame.deliveries.iter().map(move |delivery| (i, *delivery)))
+        .collect();
+    assert_eq!(deliveries.len(), 1);
+    deliveries[0]
+}
+
+/// A message sent at tick T on the interpolated timeline is emitted on the frame where the interpolated
+/// entities display the state of the server at tick T
+#[test]
+fn test_interpolated_timeline() {
+    let mut pair = build_pair();
+    let tick = pair.server_world().resource::<TickManager>().tick();
+    send_at_tick(&mut pair, tick, VisualTimeline::Interpolated);
+
+    let (index, delivery) = delivery_frame(&mut pair);
+    assert_eq!(
+        delivery,
+        TimelineDelivery {
+            tick,
+            timeline: VisualTimeline::Interpolated,
+            late: false,
+        }
+    );
+    // the message was held back until the interpolation reached the tick
+    assert!(index > 0);
+    let frames = &pair.client_world(0).resource::<Frames>().0;
+    let displayed = frames[index].interpolated.unwrap();
+    let previous = frames[index - 1].interpolated.unwrap();
+    let tick_value = tick.0 as f32;
+    assert!(
+        previous < tick_value && tick_value <= displayed,
+        "{previous} {displayed}"
+    );
+}
+
+/// A message sent at tick T on the predicted timeline is emitted on the frame during which the client
+/// simulates tick T
+#[test]
+fn test_predicted_timeline() {
+    let mut pair = build_pair();
+    let tick = pair.client_world(0).resource::<TickManager>().tick() + 20;
+    send_at_tick(&mut pair, tick, VisualTimeline::Predicted);
+
+    let (index, delivery) = delivery_frame(&mut pair);
+    assert_eq!(delivery.timeline, VisualTimeline::Predicted);
+    assert!(!delivery.late);
+    assert!(index > 0);
+    let frames = &pair.client_world(0).resource::<Frames>().0;
+    assert!(frames[index - 1].tick < tick && tick <= frames[index].tick);
+}
+
+/// A message whose tick was already passed by its timeline is emitted as soon as it is received, and flagged
+#[test]
+fn test_late_timeline_message() {
+    let mut pair = build_pair();
+    let tick = pair.server_world().resource::<TickManager>().tick() - 30;
+    send_at_tick(&mut pair, tick, VisualTimeline::Interpolated);
+
+    let (index, delivery) = delivery_frame(&mut pair);
+    assert_eq!(
+        delivery,
+        TimelineDelivery {
+            tick,
+            timeline: VisualTimeline::Interpolated,
+            late: true,
+        }
+    );
+    // received with the next packet of the server
+    assert!(index <= 2);
+}
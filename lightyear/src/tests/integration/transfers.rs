@@ -0,0 +1,250 @@
+//! Tests of the transfers of fragmented messages: their progress can be tracked, and they can be
+//! cancelled by either side without disrupting the next messages of the channel
+use bevy::prelude::*;
+use bevy::utils::Duration;
+use governor::Quota;
+use nonzero_ext::nonzero;
+
+use crate::prelude::server::{PacketConfig, ServerConfig};
+use crate::prelude::*;
+use crate::tests::protocol::*;
+
+/// Number of bytes of the big messages: about 30 fragments
+const BIG_MESSAGE_LEN: usize = 35_000;
+
+fn big_message(i: u8) -> Message1 {
+    Message1(char::from(b'a' + i).to_string().repeat(BIG_MESSAGE_LEN))
+}
+
+#[derive(Resource, Default)]
+struct ClientReceived {
+    messages: Vec<String>,
+    transfers: Vec<client::TransferEvent>,
+}
+
+fn record_client(
+    mut received: ResMut<ClientReceived>,
+    mut messages: EventReader<client::MessageEvent<Message1>>,
+    mut transfers: EventReader<client::TransferEvent>,
+) {
+    received
+        .messages
+        .extend(messages.read().map(|event| event.message().0.clone()));
+    received.transfers.extend(transfers.read().copied());
+}
+
+#[derive(Resource, Default)]
+struct ServerTransferEvents(Vec<server::TransferEvent>);
+
+fn record_server(
+    mut received: ResMut<ServerTransferEvents>,
+    mut transfers: EventReader<server::TransferEvent>,
+) {
+    received.0.extend(transfers.read().copied());
+}
+
+/// The server sends at 20kB/s over a lossy link, so that a big message takes more than a second to be delivered
+fn build_pair() -> LightyearTestPair {
+    let mut pair = LightyearTestPair::builder()
+        .protocol(ProtocolPlugin)
+        .tick_duration(Duration::from_millis(10))
+        .conditioner(LinkConditionerConfig {
+            incoming_latency: Duration::from_millis(20),
+            incoming_jitter: Duration::from_millis(0),
+            incoming_loss: 0.1,
+            seed: None,
+        })
+        .server_config(ServerConfig {
+            packet: PacketConfig::default()
+                .with_send_bandwidth_cap(Quota::per_second(nonzero!(20_000u32)))
+                .enable_bandwidth_cap(),
+            ..default()
+        })
+        .build_disconnected();
+    pair.client_apps[0]
+        .init_resource::<ClientReceived>()
+        .add_systems(Update, record_client);
+    pair.server_app
+        .init_resource::<ServerTransferEvents>()
+        .add_systems(Update, record_server);
+    pair.connect();
+    pair
+}
+
+fn send_message(pair: &mut LightyearTestPair, message: &Message1) {
+    let client_id = pair.client_id(0);
+    pair.server_world_mut()
+        .resource_mut::<server::ConnectionManager>()
+        .send_message::<Channel3, _>(client_id, message)
+        .unwrap();
+}
+
+fn server_transfers(pair: &LightyearTestPair) -> Vec<TransferProgress> {
+    pair.server_world()
+        .resource::<server::ConnectionManager>()
+        .transfers(pair.client_id(0))
+}
+
+fn client_transfers(pair: &LightyearTestPair) -> Vec<TransferProgress> {
+    pair.client_world(0)
+        .resource::<client::ConnectionManager>()
+        .transfers()
+}
+
+/// Step until the transfer of the server reached the given fraction of its fragments
+fn wait_for_progress(pair: &mut LightyearTestPair, fraction: f32) -> TransferProgress {
+    for _ in 0..500 {
+        pair.frame_steps(1);
+        if let Some(progress) = server_transfers(pair)
+            .into_iter()
+            .find(|progress| progress.fraction() >= fraction)
+        {
+            return progress;
+        }
+    }
+    panic!("the transfer never reached {fraction}");
+}
+
+/// A message that is not cancelled is delivered, and its progress is reported on both sides
+#[test]
+fn test_transfer_completes() {
+    let mut pair = build_pair();
+    send_message(&mut pair, &big_message(0));
+    let progress = wait_for_progress(&mut pair, 0.5);
+    assert_eq!(progress.handle.direction(), TransferDirection::Send);
+    assert_eq!(progress.handle.channel(), ChannelKind::of::<Channel3>());
+    assert_eq!(progress.num_fragments, 30);
+    assert!(progress.eta.is_some(), "{progress:?}");
+    let receiving = client_transfers(&pair);
+    assert_eq!(receiving.len(), 1);
+    assert_eq!(receiving[0].handle.direction(), TransferDirection::Receive);
+    assert!(receiving[0].fraction() >= progress.fraction());
+    let client_id = pair.client_id(0);
+    assert_eq!(
+        pair.server_world()
+            .resource::<server::ConnectionManager>()
+            .channel_stats(client_id, ChannelKind::of::<Channel3>())
+            .unwrap()
+            .active_transfers,
+        1
+    );
+
+    pair.frame_steps(300);
+    assert_eq!(
+        pair.client_world(0).resource::<ClientReceived>().messages,
+        vec![big_message(0).0]
+    );
+    assert_eq!(
+        pair.client_world(0).resource::<ClientReceived>().transfers,
+        vec![client::TransferEvent {
+            handle: receiving[0].handle,
+            outcome: TransferOutcome::Completed,
+        }]
+    );
+    assert_eq!(
+        pair.server_world().resource::<ServerTransferEvents>().0,
+        vec![server::TransferEvent {
+            client_id,
+            handle: progress.handle,
+            outcome: TransferOutcome::Completed,
+        }]
+    );
+    assert!(server_transfers(&pair).is_empty());
+    assert!(client_transfers(&pair).is_empty());
+}
+
+/// The sender cancels the transfer at various stages: the receiver discards the fragments, and the
+/// next messages of the channel are received intact
+#[test]
+fn test_sender_cancels_transfer() {
+    let mut pair = build_pair();
+    let client_id = pair.client_id(0);
+    for (i, fraction) in [(0, 0.0), (1, 0.25), (2, 0.5), (3, 0.75)] {
+        send_message(&mut pair, &big_message(i));
+        let progress = wait_for_progress(&mut pair, fraction);
+        assert!(pair
+            .server_world_mut()
+            .resource_mut::<server::ConnectionManager>()
+            .cancel_transfer(client_id, progress.handle)
+            .unwrap());
+        // the transfer is not listed anymore, and cannot be cancelled twice
+        assert!(server_transfers(&pair).is_empty());
+        assert!(!pair
+            .server_world_mut()
+            .resource_mut::<server::ConnectionManager>()
+            .cancel_transfer(client_id, progress.handle)
+            .unwrap());
+        send_message(&mut pair, &Message1(format!("after {i}")));
+        send_message(&mut pair, &big_message(10 + i));
+        pair.frame_steps(400);
+        assert!(client_transfers(&pair).is_empty());
+    }
+
+    // only the messages that were not cancelled were received
+    let received = &pair.client_world(0).resource::<ClientReceived>();
+    let mut messages = received.messages.clone();
+    messages.sort();
+    let mut expected: Vec<_> = (0..4u8)
+        .flat_map(|i| [format!("after {i}"), big_message(10 + i).0])
+        .collect();
+    expected.sort();
+    assert_eq!(messages, expected);
+
+    // the client was told about the cancellations of the transfers that it had started to receive
+    let client_cancelled = received
+        .transfers
+        .iter()
+        .filter(|event| event.outcome == TransferOutcome::Cancelled)
+        .count();
+    assert!((3..=4).contains(&client_cancelled), "{client_cancelled}");
+    let server_events = &pair.server_world().resource::<ServerTransferEvents>().0;
+    let count = |outcome| {
+        server_events
+            .iter()
+            .filter(|event| event.outcome == outcome)
+            .count()
+    };
+    assert_eq!(count(TransferOutcome::Cancelled), 4);
+    assert_eq!(count(TransferOutcome::Completed), 4);
+    assert_eq!(
+        pair.server_world()
+            .resource::<server::ConnectionManager>()
+            .channel_stats(client_id, ChannelKind::of::<Channel3>())
+            .unwrap()
+            .active_transfers,
+        0
+    );
+}
+
+/// The receiver cancels the transfer: the sender stops sending the remaining fragments
+#[test]
+fn test_receiver_cancels_transfer() {
+    let mut pair = build_pair();
+    send_message(&mut pair, &big_message(0));
+    wait_for_progress(&mut pair, 0.25);
+    let receiving = client_transfers(&pair);
+    assert_eq!(receiving.len(), 1);
+    assert!(pair.client_apps[0]
+        .world_mut()
+        .resource_mut::<client::ConnectionManager>()
+        .cancel_transfer(receiving[0].handle)
+        .unwrap());
+    pair.frame_steps(50);
+    assert!(server_transfers(&pair).is_empty());
+    assert_eq!(
+        pair.server_world().resource::<ServerTransferEvents>().0[0].outcome,
+        TransferOutcome::Cancelled
+    );
+
+    send_message(&mut pair, &Message1("after".to_string()));
+    pair.frame_steps(300);
+    let received = pair.client_world(0).resource::<ClientReceived>();
+    assert_eq!(received.messages, vec!["after".to_string()]);
+    assert_eq!(
+        received.transfers,
+        vec![client::TransferEvent {
+            handle: receiving[0].handle,
+            outcome: TransferOutcome::Cancelled,
+        }]
+    );
+}
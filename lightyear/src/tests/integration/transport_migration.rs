@@ -0,0 +1,212 @@
+//! Tests related to the client switching to a fallback transport when its current transport stops working
+use std::net::SocketAddr;
+
+use bevy::ecs::system::RunSystemOnce;
+use bevy::prelude::*;
+use bevy::utils::Duration;
+use crossbeam_channel::{Receiver, Sender};
+
+use crate::prelude::client::{ClientCommands, ClientTransport, FallbackTransport, NetConfig};
+use crate::prelude::server::{ServerCommands, ServerTransport};
+use crate::prelude::*;
+use crate::tests::protocol::*;
+use crate::tests::stepper::{BevyStepper, TEST_CLIENT_ID};
+use crate::transport::LOCAL_SOCKET;
+
+/// Address of the client on the fallback transport, as seen by the server
+const FALLBACK_ADDR: SocketAddr = SocketAddr::new(
+    std::net::IpAddr::V4(std::net::Ipv4Addr::new(127, 0, 0, 2)),
+    0,
+);
+
+/// Relays the packets of the primary transport, until it is severed
+struct Link {
+    client_to_server: (Receiver<Vec<u8>>, Sender<Vec<u8>>),
+    server_to_client: (Receiver<Vec<u8>>, Sender<Vec<u8>>),
+    severed: bool,
+}
+
+impl Link {
+    fn pump(&self) {
+        for (recv, send) in [&self.client_to_server, &self.server_to_client] {
+            for packet in recv.try_iter() {
+                if !self.severed {
+                    send.send(packet).unwrap();
+                }
+            }
+        }
+    }
+}
+
+#[derive(Resource, Default)]
+struct Migrations {
+    client: Vec<SocketAddr>,
+    server: Vec<ClientId>,
+    messages: usize,
+}
+
+fn record_client_migrations(
+    mut migrations: ResMut<Migrations>,
+    mut events: EventReader<client::TransportMigrationEvent>,
+) {
+    migrations
+        .client
+        .extend(events.read().map(|event| event.server_addr));
+}
+
+fn record_server_migrations(
+    mut migrations: ResMut<Migrations>,
+    mut events: EventReader<server::TransportMigrationEvent>,
+    mut messages: EventReader<server::MessageEvent<Message1>>,
+) {
+    migrations
+        .server
+        .extend(events.read().map(|event| event.client_id));
+    migrations.messages += messages.read().count();
+}
+
+fn step(stepper: &mut BevyStepper, link: &Link) {
+    stepper.advance_time(stepper.frame_duration);
+    stepper.client_app.update();
+    link.pump();
+    stepper.server_app.update();
+    link.pump();
+}
+
+/// This test checks that the session moves to the fallback transport when the primary transport
+/// is severed, and that the reliable messages lost on the primary transport are still delivered
+#[test]
+fn test_transport_migration() {
+    let frame_duration = Duration::from_millis(10);
+    let shared_config = SharedConfig {
+        tick: TickConfig::new(frame_duration),
+        ..default()
+    };
+    let mut stepper = BevyStepper::new(
+        shared_config,
+        client::ClientConfig::default(),
+        frame_duration,
+    );
+
+    // primary transport: relayed by the test so that it can be severed
+    let (client_send, relay_to_server_recv) = crossbeam_channel::unbounded();
+    let (relay_to_server_send, server_recv) = crossbeam_channel::unbounded();
+    let (server_send, relay_to_client_recv) = crossbeam_channel::unbounded();
+    let (relay_to_client_send, client_recv) = crossbeam_channel::unbounded();
+    let mut link = Link {
+        client_to_server: (relay_to_server_recv, relay_to_server_send),
+        server_to_client: (relay_to_client_recv, relay_to_client_send),
+        severed: false,
+    };
+    // fallback transport
+    let (fallback_client_send, fallback_server_recv) = crossbeam_channel::unbounded();
+    let (fallback_server_send, fallback_client_recv) = crossbeam_channel::unbounded();
+
+    // the same server io serves both transports
+    let mut server_config = stepper
+        .server_app
+        .world_mut()
+        .resource_mut::<server::ServerConfig>();
+    #[allow(irrefutable_let_patterns)]
+    let server::NetConfig::Netcode { io, .. } = &mut server_config.net[0] else {
+        unreachable!()
+    };
+    *io = server::IoConfig::from_transport(ServerTransport::Channels {
+        channels: vec![
+            (LOCAL_SOCKET, server_recv, server_send),
+            (FALLBACK_ADDR, fallback_server_recv, fallback_server_send),
+        ],
+    });
+    let mut client_config = stepper
+        .client_app
+        .world_mut()
+        .resource_mut::<client::ClientConfig>();
+    let NetConfig::Netcode { config, io, .. } = &mut client_config.net else {
+        unreachable!()
+    };
+    *io = client::IoConfig::from_transport(ClientTransport::LocalChannel {
+        send: client_send,
+        recv: client_recv,
+    });
+    config.fallback_transports = vec![FallbackTransport::new(client::IoConfig::from_transport(
+        ClientTransport::LocalChannel {
+            send: fallback_client_send,
+            recv: fallback_client_recv,
+        },
+    ))];
+    config.fallback_unhealthy_after = Duration::from_millis(100);
+    config.fallback_probe_interval = Duration::from_millis(20);
+
+    stepper.client_app.init_resource::<Migrations>();
+    stepper
+        .client_app
+        .add_systems(Update, record_client_migrations);
+    stepper.server_app.init_resource::<Migrations>();
+    stepper
+        .server_app
+        .add_systems(Update, record_server_migrations);
+
+    stepper.build();
+    stepper
+        .server_app
+        .world_mut()
+        .run_system_once(|mut commands: Commands| commands.start_server());
+    stepper
+        .client_app
+        .world_mut()
+        .run_system_once(|mut commands: Commands| commands.connect_client());
+    for _ in 0..100 {
+        step(&mut stepper, &link);
+    }
+    assert!(stepper
+        .client_app
+        .world()
+        .resource::<client::ConnectionManager>()
+        .is_synced());
+    // the fallback transport is probed, but not used while the primary transport works
+    assert!(stepper
+        .client_app
+        .world()
+        .resource::<Migrations>()
+        .client
+        .is_empty());
+
+    // sever the primary transport, and send a reliable message that will be lost on it
+    link.severed = true;
+    stepper
+        .client_app
+        .world_mut()
+        .resource_mut::<client::ConnectionManager>()
+        .send_message::<Channel3, Message1>(&Message1("a".to_string()))
+        .unwrap();
+    for _ in 0..50 {
+        step(&mut stepper, &link);
+    }
+
+    assert_eq!(
+        stepper.client_app.world().resource::<Migrations>().client,
+        vec![LOCAL_SOCKET]
+    );
+    let server_migrations = stepper.server_app.world().resource::<Migrations>();
+    assert_eq!(
+        server_migrations.server,
+        vec![ClientId::Netcode(TEST_CLIENT_ID)]
+    );
+    // the message was resent on the fallback transport
+    assert_eq!(server_migrations.messages, 1);
+    // the session survived the migration
+    assert!(stepper
+        .client_app
+        .world()
+        .resource::<client::ConnectionManager>()
+        .is_synced());
+    assert_eq!(
+        stepper
+            .server_app
+            .world()
+            .resource::<server::ConnectionManager>()
+            .connected_clients()
+            .collect::<Vec<_>>(),
+        vec![ClientId::Netcode(TEST_CLIENT_ID)]
+    );
+}
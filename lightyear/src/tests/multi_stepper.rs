@@ -7,7 +7,6 @@ use bevy::prelude::{
 };
 use bevy::state::app::StatesPlugin;
 use bevy::tasks::available_parallelism;
-use bevy::time::TimeUpdateStrategy;
 use bevy::utils::Duration;
 use bevy::MinimalPlugins;
 
@@ -19,6 +18,7 @@ use crate::prelude::client::{
 };
 use crate::prelude::server::{NetcodeConfig, ServerCommands, ServerConfig, ServerTransport};
 use crate::prelude::*;
+use crate::shared::clock::MockClock;
 use crate::tests::protocol::*;
 use crate::tests::stepper::{BevyStepper, Step};
 use crate::transport::LOCAL_SOCKET;
@@ -36,6 +36,8 @@ pub struct MultiBevyStepper {
     /// fixed timestep duration
     pub tick_duration: Duration,
     pub current_time: bevy::utils::Instant,
+    /// clock shared by all the apps
+    pub clock: MockClock,
 }
 
 impl Default for MultiBevyStepper {
@@ -69,7 +71,8 @@ impl MultiBevyStepper {
         interpolation_config: InterpolationConfig,
         frame_duration: Duration,
     ) -> Self {
-        let now = bevy::utils::Instant::now();
+        let clock = MockClock::new();
+        let now = clock.now();
 
         // both clients will use the same client id
         let server_addr = LOCAL_SOCKET;
@@ -131,6 +134,7 @@ impl MultiBevyStepper {
         // build server with two distinct transports
         let mut server_app = App::new();
         server_app.add_plugins((MinimalPlugins, StatesPlugin));
+        clock.install(&mut server_app);
         let netcode_config = NetcodeConfig::default()
             .with_protocol_id(protocol_id)
             .with_key(private_key);
@@ -160,6 +164,7 @@ impl MultiBevyStepper {
         let build_client = |net_config: NetConfig| -> App {
             let mut client_app = App::new();
             client_app.add_plugins((MinimalPlugins, StatesPlugin));
+            clock.install(&mut client_app);
 
             let config = ClientConfig {
                 shared: shared_config,
@@ -187,6 +192,7 @@ impl MultiBevyStepper {
             frame_duration,
             tick_duration: shared_config.tick.tick_duration,
             current_time: now,
+            clock,
         }
     }
 
@@ -228,13 +234,7 @@ impl MultiBevyStepper {
 
     pub(crate) fn advance_time(&mut self, duration: Duration) {
         self.current_time += duration;
-        self.client_app_1
-            .insert_resource(TimeUpdateStrategy::ManualInstant(self.current_time));
-        self.client_app_2
-            .insert_resource(TimeUpdateStrategy::ManualInstant(self.current_time));
-        self.server_app
-            .insert_resource(TimeUpdateStrategy::ManualInstant(self.current_time));
-        mock_instant::MockClock::advance(duration);
+        self.clock.advance(duration);
     }
 }
 
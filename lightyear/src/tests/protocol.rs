@@ -1,3 +1,4 @@
+use std::collections::BTreeMap;
 use std::ops::{Add, Mul};
 
 use bevy::app::{App, Plugin};
@@ -6,7 +7,7 @@ use bevy::prelude::{default, Component, Entity, EntityMapper, Reflect, Resource}
 use bevy::utils::HashSet;
 use byteorder::{NetworkEndian, ReadBytesExt, WriteBytesExt};
 use cfg_if::cfg_if;
-use lightyear_macros::ChannelInternal;
+use lightyear_macros::{ChannelInternal, KeyedDiffableInternal, LerpInternal};
 use serde::{Deserialize, Serialize};
 
 use crate::client::components::ComponentSyncMode;
@@ -144,6 +145,26 @@ impl Diffable for Component7 {
     }
 }
 
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Reflect)]
+pub enum Stance {
+    Standing,
+    Crouching,
+}
+
+/// Component where only some of the fields can be interpolated
+#[derive(Component, Serialize, Deserialize, Clone, Debug, PartialEq, Reflect, LerpInternal)]
+pub struct Component8 {
+    pub position: f32,
+    #[lerp(snap)]
+    pub stance: Stance,
+}
+
+/// Keyed collection that is replicated by sending only the keys that changed
+#[derive(
+    Component, Serialize, Deserialize, Clone, Debug, Default, PartialEq, KeyedDiffableInternal,
+)]
+pub struct Component9(pub BTreeMap<u16, u32>);
+
 // Resources
 #[derive(Resource, Serialize, Deserialize, Debug, PartialEq, Clone, Reflect)]
 pub struct Resource1(pub f32);
@@ -193,8 +214,12 @@ pub struct Channel1;
 #[derive(ChannelInternal, Reflect)]
 pub struct Channel2;
 
+#[derive(ChannelInternal, Reflect)]
+pub struct Channel3;
+
 // Protocol
 
+#[derive(Clone)]
 pub(crate) struct ProtocolPlugin;
 impl Plugin for ProtocolPlugin {
     fn build(&self, app: &mut App) {
@@ -237,6 +262,13 @@ impl Plugin for ProtocolPlugin {
         app.register_component::<Component7>(ChannelDirection::ServerToClient)
             .add_delta_compression();
 
+        app.register_component::<Component8>(ChannelDirection::ServerToClient)
+            .add_interpolation(ComponentSyncMode::Full)
+            .add_lerp_interpolation_fn();
+
+        app.register_component::<Component9>(ChannelDirection::ServerToClient)
+            .add_delta_compression();
+
         // resources
         app.register_resource::<Resource1>(ChannelDirection::ServerToClient);
         app.register_resource_custom_serde::<Resource2>(
@@ -255,5 +287,9 @@ impl Plugin for ProtocolPlugin {
             mode: ChannelMode::UnorderedUnreliableWithAcks,
             ..default()
         });
+        app.add_channel::<Channel3>(ChannelSettings {
+            mode: ChannelMode::UnorderedReliable(ReliableSettings::default()),
+            ..default()
+        });
     }
 }
@@ -5,7 +5,6 @@ use bevy::ecs::system::RunSystemOnce;
 use bevy::input::InputPlugin;
 use bevy::prelude::{default, App, Commands, Mut, PluginGroup, Real, Time, World};
 use bevy::state::app::StatesPlugin;
-use bevy::time::TimeUpdateStrategy;
 use bevy::utils::Duration;
 use bevy::MinimalPlugins;
 
@@ -16,6 +15,7 @@ use crate::prelude::client::{
 };
 use crate::prelude::server::{NetcodeConfig, ServerCommands, ServerConfig, ServerTransport};
 use crate::prelude::*;
+use crate::shared::clock::MockClock;
 use crate::shared::time_manager::WrappedTime;
 use crate::tests::protocol::*;
 use crate::transport::LOCAL_SOCKET;
@@ -38,6 +38,8 @@ pub struct BevyStepper {
     /// fixed timestep duration
     pub tick_duration: Duration,
     pub current_time: bevy::utils::Instant,
+    /// clock shared by all the apps
+    pub clock: MockClock,
 }
 
 impl Default for BevyStepper {
@@ -56,7 +58,6 @@ impl Default for BevyStepper {
     }
 }
 
-// Do not forget to use --features mock_time when using the LinkConditioner
 impl BevyStepper {
     pub fn new(
         shared_config: SharedConfig,
@@ -94,8 +95,10 @@ impl BevyStepper {
         let private_key = generate_key();
 
         // Setup server
+        let clock = MockClock::new();
         let mut server_app = App::new();
         server_app.add_plugins((MinimalPlugins, StatesPlugin));
+        clock.install(&mut server_app);
         let net_config = server::NetConfig::Netcode {
             config: NetcodeConfig::default()
                 .with_protocol_id(protocol_id)
@@ -118,6 +121,7 @@ impl BevyStepper {
         // Setup client
         let mut client_app = App::new();
         client_app.add_plugins((MinimalPlugins, StatesPlugin));
+        clock.install(&mut client_app);
         let net_config = client::NetConfig::Netcode {
             auth: Authentication::Manual {
                 server_addr: addr,
@@ -150,7 +154,7 @@ impl BevyStepper {
         }
 
         // Initialize Real time (needed only for the first TimeSystem run)
-        let now = bevy::utils::Instant::now();
+        let now = clock.now();
         client_app
             .world_mut()
             .get_resource_mut::<Time<Real>>()
@@ -168,6 +172,7 @@ impl BevyStepper {
             frame_duration,
             tick_duration: shared_config.tick.tick_duration,
             current_time: now,
+            clock,
         }
     }
 
@@ -281,11 +286,7 @@ impl BevyStepper {
 
     pub(crate) fn advance_time(&mut self, duration: Duration) {
         self.current_time += duration;
-        self.client_app
-            .insert_resource(TimeUpdateStrategy::ManualInstant(self.current_time));
-        self.server_app
-            .insert_resource(TimeUpdateStrategy::ManualInstant(self.current_time));
-        mock_instant::MockClock::advance(duration);
+        self.clock.advance(duration);
     }
 }
 
@@ -1,3 +1,5 @@
+use crate::transport::middleware::compression::CompressionAlgorithm;
+
 pub type Result<T> = std::result::Result<T, Error>;
 
 #[derive(thiserror::Error, Debug)]
@@ -10,6 +12,10 @@ pub enum Error {
     Channel(String),
     #[error("requested by user")]
     UserRequest,
+    #[error("invalid compression header")]
+    InvalidCompressionHeader,
+    #[error("compression algorithm {0:?} is not supported by this build")]
+    UnsupportedCompression(CompressionAlgorithm),
     #[cfg(feature = "lz4")]
     #[error("lz4 compression error")]
     CompressError(#[from] lz4_flex::block::CompressError),
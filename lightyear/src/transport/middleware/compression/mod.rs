@@ -7,6 +7,13 @@ pub(crate) mod zstd;
 #[cfg(feature = "lz4")]
 pub(crate) mod lz4;
 
+pub(crate) mod negotiation;
+
+/// Compression applied to the packets.
+///
+/// The algorithm is negotiated for each connection: the server uses its configured algorithm with the clients
+/// whose build supports it, and no compression with the others. On the client, the algorithm is the one picked by
+/// the server, and this config only provides the zstd compression level.
 #[derive(Clone, Copy, Debug, Default, Reflect, Serialize, Deserialize)]
 pub enum CompressionConfig {
     #[default]
@@ -16,3 +23,95 @@ pub enum CompressionConfig {
     #[cfg(feature = "lz4")]
     Lz4,
 }
+
+impl CompressionConfig {
+    /// The algorithm used by this config
+    pub fn algorithm(&self) -> CompressionAlgorithm {
+        match self {
+            CompressionConfig::None => CompressionAlgorithm::None,
+            #[cfg(feature = "zstd")]
+            CompressionConfig::Zstd { .. } => CompressionAlgorithm::Zstd,
+            #[cfg(feature = "lz4")]
+            CompressionConfig::Lz4 => CompressionAlgorithm::Lz4,
+        }
+    }
+}
+
+/// A compression algorithm, which might not be supported by the current build
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Reflect)]
+pub enum CompressionAlgorithm {
+    #[default]
+    None,
+    Zstd,
+    Lz4,
+}
+
+impl CompressionAlgorithm {
+    pub(crate) fn id(self) -> u8 {
+        match self {
+            CompressionAlgorithm::None => 0,
+            CompressionAlgorithm::Zstd => 1,
+            CompressionAlgorithm::Lz4 => 2,
+        }
+    }
+
+    pub(crate) fn from_id(id: u8) -> Option<Self> {
+        match id {
+            0 => Some(CompressionAlgorithm::None),
+            1 => Some(CompressionAlgorithm::Zstd),
+            2 => Some(CompressionAlgorithm::Lz4),
+            _ => None,
+        }
+    }
+}
+
+/// Set of compression algorithms that a peer can use
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Reflect)]
+pub struct CompressionCapabilities(u8);
+
+impl CompressionCapabilities {
+    /// Only uncompressed packets
+    pub fn none() -> Self {
+        Self(0)
+    }
+
+    /// The algorithms enabled in the current build
+    pub fn supported() -> Self {
+        #[allow(unused_mut)]
+        let mut capabilities = Self::none();
+        #[cfg(feature = "zstd")]
+        {
+            capabilities = capabilities.with(CompressionAlgorithm::Zstd);
+        }
+        #[cfg(feature = "lz4")]
+        {
+            capabilities = capabilities.with(CompressionAlgorithm::Lz4);
+        }
+        capabilities
+    }
+
+    pub fn with(mut self, algorithm: CompressionAlgorithm) -> Self {
+        if algorithm != CompressionAlgorithm::None {
+            self.0 |= 1 << (algorithm.id() - 1);
+        }
+        self
+    }
+
+    pub fn contains(&self, algorithm: CompressionAlgorithm) -> bool {
+        algorithm == CompressionAlgorithm::None || self.0 & (1 << (algorithm.id() - 1)) != 0
+    }
+
+    pub(crate) fn bits(&self) -> u8 {
+        self.0
+    }
+
+    pub(crate) fn from_bits(bits: u8) -> Self {
+        Self(bits)
+    }
+}
+
+impl Default for CompressionCapabilities {
+    fn default() -> Self {
+        Self::supported()
+    }
+}
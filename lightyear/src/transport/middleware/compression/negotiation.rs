@@ -0,0 +1,616 @@
+//! Negotiation of the compression used on each connection.
+//!
+//! The client and the server can be built with different compression features (for example a wasm client
+//! without `zstd`), so the compression is chosen for each connection during the handshake:
+//! - every packet starts with a header byte that identifies the algorithm used to compress it
+//! - until it knows which algorithm to use, the client sends its packets uncompressed, and advertises the
+//!   algorithms it supports in an extra header byte. The first packets that it sends are the handshake packets.
+//! - the server picks its configured algorithm if the client supports it, and no compression otherwise. The packets
+//!   that it sends to the client are flagged with this choice, and the client then uses the same algorithm.
+//!
+//! The server keys the chosen algorithm by the address of the remote peer.
+use std::collections::VecDeque;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use bevy::utils::HashMap;
+use parking_lot::Mutex;
+use tracing::{debug, warn};
+
+use crate::connection::netcode::MAX_PKT_BUF_SIZE;
+use crate::transport::error::{Error, Result};
+#[cfg(feature = "zstd")]
+use crate::transport::middleware::compression::zstd::{
+    compression::ZstdCompressor, decompression::ZstdDecompressor,
+};
+use crate::transport::middleware::compression::{
+    CompressionAlgorithm, CompressionCapabilities, CompressionConfig,
+};
+use crate::transport::{BoxedReceiver, BoxedSender, PacketReceiver, PacketSender};
+
+/// The header is followed by a byte containing the [`CompressionCapabilities`] of the sender
+const CAPABILITIES_FLAG: u8 = 0b1000_0000;
+/// The algorithm of the header is the one that was chosen by the server for this connection
+const NEGOTIATED_FLAG: u8 = 0b0100_0000;
+const ALGORITHM_MASK: u8 = 0b0000_1111;
+
+/// Maximum number of remote peers for which the chosen algorithm is kept.
+///
+/// Any address can send a packet that advertises capabilities, so the oldest entries are evicted; the packets
+/// sent to an evicted peer are uncompressed, which the peer can always decode.
+const MAX_NEGOTIATED_PEERS: usize = 4096;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum CompressionRole {
+    /// Advertises its capabilities, and uses the algorithm chosen by the server
+    Client,
+    /// Chooses the algorithm for each client
+    Server,
+}
+
+/// Negotiation state, shared between the sending and the receiving halves of the io
+#[derive(Debug)]
+struct Negotiation {
+    role: CompressionRole,
+    config: CompressionConfig,
+    capabilities: CompressionCapabilities,
+    /// Algorithm used to send packets to each remote peer
+    peers: HashMap<SocketAddr, CompressionAlgorithm>,
+    /// Insertion order of the peers
+    order: VecDeque<SocketAddr>,
+}
+
+impl Negotiation {
+    /// Returns the algorithm to use for a packet sent to `address`, and the header of the packet
+    fn send_header(&self, address: &SocketAddr) -> (CompressionAlgorithm, u8) {
+        let chosen = self.peers.get(address).copied();
+        match (self.role, chosen) {
+            (CompressionRole::Client, Some(algorithm)) => (algorithm, algorithm.id()),
+            (CompressionRole::Client, None) => (CompressionAlgorithm::None, CAPABILITIES_FLAG),
+            (CompressionRole::Server, Some(algorithm)) => {
+                (algorithm, algorithm.id() | NEGOTIATED_FLAG)
+            }
+            (CompressionRole::Server, None) => (CompressionAlgorithm::None, 0),
+        }
+    }
+
+    /// Update the negotiation with the header of a packet received from `address`
+    fn receive_header(
+        &mut self,
+        header: u8,
+        capabilities: Option<CompressionCapabilities>,
+        address: SocketAddr,
+    ) {
+        match self.role {
+            CompressionRole::Server => {
+                let Some(capabilities) = capabilities else {
+                    return;
+                };
+                let preferred = self.config.algorithm();
+                let algorithm =
+                    if capabilities.contains(preferred) && self.capabilities.contains(preferred) {
+                        preferred
+                    } else {
+                        CompressionAlgorithm::None
+                    };
+                if self.choose(address, algorithm) && algorithm != preferred {
+                    warn!(
+                        ?address,
+                        ?preferred,
+                        ?capabilities,
+                        "The client does not support the configured compression, packets will not be compressed"
+                    );
+                }
+            }
+            CompressionRole::Client => {
+                if header & NEGOTIATED_FLAG == 0 {
+                    return;
+                }
+                if let Some(algorithm) = CompressionAlgorithm::from_id(header & ALGORITHM_MASK) {
+                    self.choose(address, algorithm);
+                }
+            }
+        }
+    }
+
+    /// Use `algorithm` for the packets sent to `address`. Returns true if the algorithm changed.
+    fn choose(&mut self, address: SocketAddr, algorithm: CompressionAlgorithm) -> bool {
+        match self.peers.insert(address, algorithm) {
+            Some(previous) => {
+                if previous == algorithm {
+                    return false;
+                }
+            }
+            None => {
+                self.order.push_back(address);
+                if self.order.len() > MAX_NEGOTIATED_PEERS {
+                    if let Some(evicted) = self.order.pop_front() {
+                        self.peers.remove(&evicted);
+                    }
+                }
+            }
+        }
+        debug!(?address, ?algorithm, "Negotiated compression");
+        true
+    }
+}
+
+/// Compressors for all the algorithms of the build
+struct Encoders {
+    #[cfg(feature = "zstd")]
+    zstd_level: i32,
+    #[cfg(feature = "zstd")]
+    zstd: Option<ZstdCompressor>,
+    #[cfg(feature = "lz4")]
+    lz4: crate::transport::middleware::compression::lz4::Compressor,
+}
+
+impl Encoders {
+    #[allow(unused_variables)]
+    fn new(config: CompressionConfig) -> Self {
+        Self {
+            #[cfg(feature = "zstd")]
+            zstd_level: match config {
+                CompressionConfig::Zstd { level } => level,
+                _ => ::zstd::DEFAULT_COMPRESSION_LEVEL,
+            },
+            #[cfg(feature = "zstd")]
+            zstd: None,
+            #[cfg(feature = "lz4")]
+            lz4: Default::default(),
+        }
+    }
+
+    /// Append the payload compressed with `algorithm` to `out`
+    fn compress(
+        &mut self,
+        algorithm: CompressionAlgorithm,
+        payload: &[u8],
+        out: &mut Vec<u8>,
+    ) -> Result<()> {
+        match algorithm {
+            CompressionAlgorithm::None => out.extend_from_slice(payload),
+            #[cfg(feature = "zstd")]
+            CompressionAlgorithm::Zstd => {
+                let level = self.zstd_level;
+                let compressor = self.zstd.get_or_insert_with(|| ZstdCompressor::new(level));
+                out.extend_from_slice(compressor.compress(payload)?);
+            }
+            #[cfg(feature = "lz4")]
+            CompressionAlgorithm::Lz4 => out.extend_from_slice(self.lz4.compress(payload)?),
+            #[allow(unreachable_patterns)]
+            _ => return Err(Error::UnsupportedCompression(algorithm)),
+        }
+        Ok(())
+    }
+}
+
+/// Decompressors for all the algorithms of the build
+struct Decoders {
+    #[cfg(feature = "zstd")]
+    zstd: Option<ZstdDecompressor>,
+    #[cfg(feature = "lz4")]
+    lz4: crate::transport::middleware::compression::lz4::Decompressor,
+}
+
+impl Decoders {
+    fn new() -> Self {
+        Self {
+            #[cfg(feature = "zstd")]
+            zstd: None,
+            #[cfg(feature = "lz4")]
+            lz4: Default::default(),
+        }
+    }
+
+    /// Write the data decompressed with `algorithm` to `out`
+    fn decompress(
+        &mut self,
+        algorithm: CompressionAlgorithm,
+        data: &[u8],
+        out: &mut Vec<u8>,
+    ) -> Result<()> {
+        out.clear();
+        match algorithm {
+            CompressionAlgorithm::None => out.extend_from_slice(data),
+            #[cfg(feature = "zstd")]
+            CompressionAlgorithm::Zstd => out.extend_from_slice(
+                self.zstd
+                    .get_or_insert_with(ZstdDecompressor::new)
+                    .decompress(data)?,
+            ),
+            #[cfg(feature = "lz4")]
+            CompressionAlgorithm::Lz4 => out.extend_from_slice(self.lz4.decompress(data)?),
+            #[allow(unreachable_patterns)]
+            _ => return Err(Error::UnsupportedCompression(algorithm)),
+        }
+        Ok(())
+    }
+}
+
+/// Wrap the sender and the receiver of an io with the negotiated compression.
+///
+/// `capabilities` are the algorithms that can be used by this peer, usually [`CompressionCapabilities::supported`].
+pub(crate) fn wrap(
+    sender: BoxedSender,
+    receiver: BoxedReceiver,
+    config: CompressionConfig,
+    role: CompressionRole,
+    capabilities: CompressionCapabilities,
+) -> (BoxedSender, BoxedReceiver) {
+    let negotiation = Arc::new(Mutex::new(Negotiation {
+        role,
+        config,
+        capabilities,
+        peers: HashMap::default(),
+        order: VecDeque::default(),
+    }));
+    let sender = NegotiatedCompressionSender {
+        inner: sender,
+        negotiation: negotiation.clone(),
+        capabilities,
+        encoders: Encoders::new(config),
+        buffer: Vec::with_capacity(MAX_PKT_BUF_SIZE),
+    };
+    let receiver = NegotiatedCompressionReceiver {
+        inner: receiver,
+        negotiation,
+        decoders: Decoders::new(),
+        buffer: Vec::with_capacity(MAX_PKT_BUF_SIZE),
+    };
+    (Box::new(sender), Box::new(receiver))
+}
+
+struct NegotiatedCompressionSender {
+    inner: BoxedSender,
+    negotiation: Arc<Mutex<Negotiation>>,
+    capabilities: CompressionCapabilities,
+    encoders: Encoders,
+    buffer: Vec<u8>,
+}
+
+impl PacketSender for NegotiatedCompressionSender {
+    fn send(&mut self, payload: &[u8], address: &SocketAddr) -> Result<()> {
+        let (algorithm, header) = self.negotiation.lock().send_header(address);
+        self.buffer.clear();
+        self.buffer.push(header);
+        if header & CAPABILITIES_FLAG != 0 {
+            self.buffer.push(self.capabilities.bits());
+        }
+        self.encoders
+            .compress(algorithm, payload, &mut self.buffer)?;
+        self.inner.send(&self.buffer, address)
+    }
+}
+
+struct NegotiatedCompressionReceiver {
+    inner: BoxedReceiver,
+    negotiation: Arc<Mutex<Negotiation>>,
+    decoders: Decoders,
+    buffer: Vec<u8>,
+}
+
+impl NegotiatedCompressionReceiver {
+    /// Decode the packet received from `address` into `buffer`
+    fn decode(
+        negotiation: &Mutex<Negotiation>,
+        decoders: &mut Decoders,
+        buffer: &mut Vec<u8>,
+        packet: &[u8],
+        address: SocketAddr,
+    ) -> Result<()> {
+        let (&header, mut data) = packet
+            .split_first()
+            .ok_or(Error::InvalidCompressionHeader)?;
+        let mut capabilities = None;
+        if header & CAPABILITIES_FLAG != 0 {
+            let (&bits, rest) = data.split_first().ok_or(Error::InvalidCompressionHeader)?;
+            capabilities = Some(CompressionCapabilities::from_bits(bits));
+            data = rest;
+        }
+        let algorithm = CompressionAlgorithm::from_id(header & ALGORITHM_MASK)
+            .ok_or(Error::InvalidCompressionHeader)?;
+        decoders.decompress(algorithm, data, buffer)?;
+        negotiation
+            .lock()
+            .receive_header(header, capabilities, address);
+        Ok(())
+    }
+}
+
+impl PacketReceiver for NegotiatedCompressionReceiver {
+    fn recv(&mut self) -> Result<Option<(&mut [u8], SocketAddr)>> {
+        // packets that cannot be decoded are dropped, so that they don't block the packets from other peers
+        let address = loop {
+            let Some((packet, address)) = self.inner.recv()? else {
+                return Ok(None);
+            };
+            match Self::decode(
+                &self.negotiation,
+                &mut self.decoders,
+                &mut self.buffer,
+                packet,
+                address,
+            ) {
+                Ok(()) => break address,
+                Err(e) => debug!(
+                    ?address,
+                    "Dropping packet that could not be decompressed: {e}"
+                ),
+            }
+        };
+        Ok(Some((self.buffer.as_mut_slice(), address)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crossbeam_channel::{Receiver, Sender};
+
+    use super::*;
+
+    const CLIENT_ADDR: SocketAddr = SocketAddr::new(
+        std::net::IpAddr::V4(std::net::Ipv4Addr::new(127, 0, 0, 1)),
+        1000,
+    );
+    const SERVER_ADDR: SocketAddr = SocketAddr::new(
+        std::net::IpAddr::V4(std::net::Ipv4Addr::new(127, 0, 0, 1)),
+        2000,
+    );
+
+    /// Sends the packets to the remote, tagged with the address of the local peer
+    struct ChannelSender {
+        local_addr: SocketAddr,
+        send: Sender<(Vec<u8>, SocketAddr)>,
+    }
+
+    impl PacketSender for ChannelSender {
+        fn send(&mut self, payload: &[u8], _: &SocketAddr) -> Result<()> {
+            self.send.send((payload.to_vec(), self.local_addr))?;
+            Ok(())
+        }
+    }
+
+    struct ChannelReceiver {
+        recv: Receiver<(Vec<u8>, SocketAddr)>,
+        buffer: Vec<u8>,
+    }
+
+    impl PacketReceiver for ChannelReceiver {
+        fn recv(&mut self) -> Result<Option<(&mut [u8], SocketAddr)>> {
+            match self.recv.try_recv() {
+                Ok((packet, address)) => {
+                    self.buffer = packet;
+                    Ok(Some((self.buffer.as_mut_slice(), address)))
+                }
+                Err(_) => Ok(None),
+            }
+        }
+    }
+
+    struct Peer {
+        sender: BoxedSender,
+        receiver: BoxedReceiver,
+        /// Packets sent by this peer, as they were sent on the wire
+        wire: Receiver<(Vec<u8>, SocketAddr)>,
+        /// Packets received by this peer
+        inject: Sender<(Vec<u8>, SocketAddr)>,
+        remote_addr: SocketAddr,
+    }
+
+    impl Peer {
+        fn send(&mut self, payload: &[u8]) {
+            self.sender.send(payload, &self.remote_addr).unwrap();
+        }
+
+        /// Deliver the next packet sent by this peer to the remote, and return its header
+        fn relay_to(&mut self, remote: &mut Peer, expected: &[u8]) -> u8 {
+            let (packet, address) = self.wire.try_recv().unwrap();
+            let header = packet[0];
+            remote.inject.send((packet, address)).unwrap();
+            let (data, _) = remote.receiver.recv().unwrap().unwrap();
+            assert_eq!(data, expected);
+            header
+        }
+    }
+
+    fn peers(
+        client: (CompressionConfig, CompressionCapabilities),
+        server: (CompressionConfig, CompressionCapabilities),
+    ) -> (Peer, Peer) {
+        let peer = |local_addr, remote_addr, (config, capabilities), role| {
+            let (wire_send, wire_recv) = crossbeam_channel::unbounded();
+            let (inject_send, inject_recv) = crossbeam_channel::unbounded();
+            let (sender, receiver) = wrap(
+                Box::new(ChannelSender {
+                    local_addr,
+                    send: wire_send,
+                }),
+                Box::new(ChannelReceiver {
+                    recv: inject_recv,
+                    buffer: vec![],
+                }),
+                config,
+                role,
+                capabilities,
+            );
+            Peer {
+                sender,
+                receiver,
+                wire: wire_recv,
+                inject: inject_send,
+                remote_addr,
+            }
+        };
+        (
+            peer(CLIENT_ADDR, SERVER_ADDR, client, CompressionRole::Client),
+            peer(SERVER_ADDR, CLIENT_ADDR, server, CompressionRole::Server),
+        )
+    }
+
+    /// Run a handshake, and return the algorithm used by the client and the server after it
+    fn handshake(
+        client: &mut Peer,
+        server: &mut Peer,
+    ) -> (CompressionAlgorithm, CompressionAlgorithm) {
+        let payload = [7u8; 200];
+        // connection request
+        client.send(&payload);
+        let header = client.relay_to(server, &payload);
+        assert_eq!(header, CAPABILITIES_FLAG);
+        // challenge
+        server.send(&payload);
+        let server_header = server.relay_to(client, &payload);
+        assert_ne!(server_header & NEGOTIATED_FLAG, 0);
+        // challenge response
+        client.send(&payload);
+        let client_header = client.relay_to(server, &payload);
+        assert_eq!(client_header & CAPABILITIES_FLAG, 0);
+        (
+            CompressionAlgorithm::from_id(client_header & ALGORITHM_MASK).unwrap(),
+            CompressionAlgorithm::from_id(server_header & ALGORITHM_MASK).unwrap(),
+        )
+    }
+
+    #[test]
+    fn test_no_compression() {
+        let (mut client, mut server) = peers(
+            (
+                CompressionConfig::None,
+                CompressionCapabilities::supported(),
+            ),
+            (
+                CompressionConfig::None,
+                CompressionCapabilities::supported(),
+            ),
+        );
+        assert_eq!(
+            handshake(&mut client, &mut server),
+            (CompressionAlgorithm::None, CompressionAlgorithm::None)
+        );
+    }
+
+    #[test]
+    fn test_invalid_packets_are_dropped() {
+        let (mut client, mut server) = peers(
+            (
+                CompressionConfig::None,
+                CompressionCapabilities::supported(),
+            ),
+            (
+                CompressionConfig::None,
+                CompressionCapabilities::supported(),
+            ),
+        );
+        handshake(&mut client, &mut server);
+        client.inject.send((vec![], SERVER_ADDR)).unwrap();
+        client
+            .inject
+            .send((vec![ALGORITHM_MASK], SERVER_ADDR))
+            .unwrap();
+        client
+            .inject
+            .send((vec![NEGOTIATED_FLAG, 1, 2], SERVER_ADDR))
+            .unwrap();
+        let (data, _) = client.receiver.recv().unwrap().unwrap();
+        assert_eq!(data, &[1, 2]);
+        assert!(client.receiver.recv().unwrap().is_none());
+    }
+
+    /// The server uses zstd, but the client was built without it
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn test_zstd_server_plain_client() {
+        let (mut client, mut server) = peers(
+            (CompressionConfig::None, CompressionCapabilities::none()),
+            (
+                CompressionConfig::Zstd { level: 3 },
+                CompressionCapabilities::supported(),
+            ),
+        );
+        assert_eq!(
+            handshake(&mut client, &mut server),
+            (CompressionAlgorithm::None, CompressionAlgorithm::None)
+        );
+    }
+
+    /// The client supports zstd, but the server was built without it
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn test_plain_server_zstd_client() {
+        let (mut client, mut server) = peers(
+            (
+                CompressionConfig::Zstd { level: 3 },
+                CompressionCapabilities::supported(),
+            ),
+            (CompressionConfig::None, CompressionCapabilities::none()),
+        );
+        assert_eq!(
+            handshake(&mut client, &mut server),
+            (CompressionAlgorithm::None, CompressionAlgorithm::None)
+        );
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn test_zstd_negotiated() {
+        let (mut client, mut server) = peers(
+            (
+                CompressionConfig::None,
+                CompressionCapabilities::supported(),
+            ),
+            (
+                CompressionConfig::Zstd { level: 3 },
+                CompressionCapabilities::supported(),
+            ),
+        );
+        assert_eq!(
+            handshake(&mut client, &mut server),
+            (CompressionAlgorithm::Zstd, CompressionAlgorithm::Zstd)
+        );
+        // the packets are compressed
+        let payload = [1u8; 500];
+        server.send(&payload);
+        let (packet, _) = server.wire.try_recv().unwrap();
+        assert!(packet.len() < payload.len());
+        client.inject.send((packet, SERVER_ADDR)).unwrap();
+        let (data, _) = client.receiver.recv().unwrap().unwrap();
+        assert_eq!(data, payload.as_slice());
+    }
+
+    /// A zstd server and a lz4-only client fall back to no compression
+    #[cfg(all(feature = "zstd", feature = "lz4"))]
+    #[test]
+    fn test_no_common_algorithm() {
+        let (mut client, mut server) = peers(
+            (
+                CompressionConfig::Lz4,
+                CompressionCapabilities::none().with(CompressionAlgorithm::Lz4),
+            ),
+            (
+                CompressionConfig::Zstd { level: 3 },
+                CompressionCapabilities::supported(),
+            ),
+        );
+        assert_eq!(
+            handshake(&mut client, &mut server),
+            (CompressionAlgorithm::None, CompressionAlgorithm::None)
+        );
+    }
+
+    #[cfg(feature = "lz4")]
+    #[test]
+    fn test_lz4_negotiated() {
+        let (mut client, mut server) = peers(
+            (
+                CompressionConfig::None,
+                CompressionCapabilities::supported(),
+            ),
+            (CompressionConfig::Lz4, CompressionCapabilities::supported()),
+        );
+        assert_eq!(
+            handshake(&mut client, &mut server),
+            (CompressionAlgorithm::Lz4, CompressionAlgorithm::Lz4)
+        );
+    }
+}
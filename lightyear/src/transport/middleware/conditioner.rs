@@ -1,27 +1,36 @@
 //! Contains the `LinkConditioner` struct which can be used to simulate network conditions
+//!
+//! A [`LinkConditionerConfig`] can be built from one of the [`NetworkPreset`]s, and tweaked with its builder methods:
+//! ```rust
+//! use bevy::utils::Duration;
+//! use lightyear::prelude::{LinkConditionerConfig, NetworkPreset};
+//!
+//! let config = LinkConditionerConfig::preset(NetworkPreset::FourG).with_loss(0.05);
+//! let custom = LinkConditionerConfig::default()
+//!     .with_latency(Duration::from_millis(80))
+//!     .with_jitter(Duration::from_millis(10));
+//! ```
+//!
+//! A [`TimeVaryingConditioner`] interpolates between two configs over a schedule, for soak tests.
 use bevy::reflect::Reflect;
+use serde::{Deserialize, Serialize};
 use std::net::SocketAddr;
+use std::str::FromStr;
 
-use bevy::utils::Duration;
-use cfg_if::cfg_if;
-use rand;
-use rand::{thread_rng, Rng};
+use bevy::utils::{Duration, Instant};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 
+use crate::shared::clock::NetworkClock;
 use crate::transport::error::Result;
 use crate::transport::middleware::PacketReceiverWrapper;
 use crate::transport::PacketReceiver;
 use crate::utils::ready_buffer::ReadyBuffer;
 
-cfg_if! {
-    if #[cfg(test)] {
-        use mock_instant::Instant;
-    } else {
-        use bevy::utils::Instant;
-    }
-}
-
 /// Contains configuration required to initialize a LinkConditioner
-#[derive(Clone, Debug, Reflect)]
+///
+/// The default config does not alter the packets.
+#[derive(Clone, Debug, Default, PartialEq, Reflect)]
 pub struct LinkConditionerConfig {
     /// Delay to receive incoming messages in milliseconds (half the RTT)
     pub incoming_latency: Duration,
@@ -32,6 +41,9 @@ pub struct LinkConditionerConfig {
     /// The % chance that an incoming packet will be dropped.
     /// Represented as a value between 0 and 1
     pub incoming_loss: f32,
+    /// Seed of the random number generator that draws the jitter and the losses, to make the conditioning
+    /// reproducible (for example in tests). If `None`, the generator is seeded from the OS entropy
+    pub seed: Option<u64>,
 }
 
 pub(crate) type PacketLinkConditioner = LinkConditioner<(SocketAddr, Box<[u8]>)>;
@@ -40,29 +52,47 @@ pub(crate) struct LinkConditioner<P: Eq> {
     config: LinkConditionerConfig,
     pub time_queue: ReadyBuffer<Instant, P>,
     last_packet: Option<P>,
+    /// Clock used to delay the packets
+    clock: NetworkClock,
+    rng: StdRng,
 }
 
 impl<P: Eq> LinkConditioner<P> {
-    pub fn new(config: LinkConditionerConfig) -> Self {
+    pub fn new(config: LinkConditionerConfig, clock: NetworkClock) -> Self {
+        let rng = config
+            .seed
+            .map_or_else(StdRng::from_entropy, StdRng::seed_from_u64);
         LinkConditioner {
             config,
             time_queue: ReadyBuffer::new(),
             last_packet: None,
+            clock,
+            rng,
         }
     }
 
+    /// Replace the configuration; the packets that are already delayed keep their delay.
+    ///
+    /// The random number generator is not re-seeded.
+    pub(crate) fn set_config(&mut self, config: LinkConditionerConfig) {
+        self.config = config;
+    }
+
+    /// Returns true if no packet is delayed
+    pub(crate) fn is_empty(&self) -> bool {
+        self.time_queue.heap.is_empty()
+    }
+
     /// Add latency/jitter/loss to a packet
-    fn condition_packet(&mut self, packet: P) {
-        let mut rng = thread_rng();
-        if rng.gen_range(0.0..1.0) <= self.config.incoming_loss {
+    pub(crate) fn condition_packet(&mut self, packet: P) {
+        if self.rng.gen_range(0.0..1.0) <= self.config.incoming_loss {
             return;
         }
         let mut latency: i32 = self.config.incoming_latency.as_millis() as i32;
-        // TODO: how can i use the virtual time here?
-        let mut packet_timestamp = Instant::now();
+        let mut packet_timestamp = self.clock.now();
         if self.config.incoming_jitter > Duration::default() {
             let jitter: i32 = self.config.incoming_jitter.as_millis() as i32;
-            latency += rng.gen_range(-jitter..jitter);
+            latency += self.rng.gen_range(-jitter..jitter);
         }
         if latency > 0 {
             packet_timestamp += Duration::from_millis(latency as u64);
@@ -71,9 +101,9 @@ impl<P: Eq> LinkConditioner<P> {
     }
 
     /// Check if a packet is ready to be returned
-    fn pop_packet(&mut self) -> Option<P> {
+    pub(crate) fn pop_packet(&mut self) -> Option<P> {
         self.time_queue
-            .pop_item(&Instant::now())
+            .pop_item(&self.clock.now())
             .map(|(_, packet)| packet)
     }
 }
@@ -123,12 +153,71 @@ impl<T: PacketReceiver> PacketReceiver for ConditionedPacketReceiver<T, (SocketA
 }
 
 impl LinkConditionerConfig {
+    /// Creates the config that simulates one of the [`NetworkPreset`]s
+    pub fn preset(preset: NetworkPreset) -> Self {
+        let (latency_ms, jitter_ms, loss) = match preset {
+            NetworkPreset::Wifi => (15, 5, 0.005),
+            NetworkPreset::FourG => (35, 15, 0.01),
+            NetworkPreset::CrossRegion => (75, 5, 0.001),
+            NetworkPreset::Satellite => (300, 30, 0.01),
+            NetworkPreset::TerribleConference => (120, 80, 0.05),
+        };
+        LinkConditionerConfig {
+            incoming_latency: Duration::from_millis(latency_ms),
+            incoming_jitter: Duration::from_millis(jitter_ms),
+            incoming_loss: loss,
+            seed: None,
+        }
+    }
+
+    /// Set the latency added to the incoming packets
+    pub fn with_latency(mut self, latency: Duration) -> Self {
+        self.incoming_latency = latency;
+        self
+    }
+
+    /// Set the maximum jitter added to or subtracted from the latency of the incoming packets
+    pub fn with_jitter(mut self, jitter: Duration) -> Self {
+        self.incoming_jitter = jitter;
+        self
+    }
+
+    /// Set the probability (between 0 and 1) that an incoming packet is dropped
+    pub fn with_loss(mut self, loss: f32) -> Self {
+        self.incoming_loss = loss.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Seed the random number generator of the conditioner, so that the same packets get the same
+    /// jitter and losses on every run
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Linear interpolation between this config (`t = 0.0`) and `other` (`t = 1.0`)
+    pub fn lerp(&self, other: &Self, t: f32) -> Self {
+        let t = t.clamp(0.0, 1.0);
+        let lerp_duration = |a: Duration, b: Duration| {
+            Duration::from_secs_f64(
+                a.as_secs_f64() + (b.as_secs_f64() - a.as_secs_f64()) * t as f64,
+            )
+        };
+        LinkConditionerConfig {
+            incoming_latency: lerp_duration(self.incoming_latency, other.incoming_latency),
+            incoming_jitter: lerp_duration(self.incoming_jitter, other.incoming_jitter),
+            incoming_loss: self.incoming_loss + (other.incoming_loss - self.incoming_loss) * t,
+            seed: self.seed,
+        }
+    }
+
     /// Creates a new LinkConditionerConfig
     pub fn new(incoming_latency: Duration, incoming_jitter: Duration, incoming_loss: f32) -> Self {
         LinkConditionerConfig {
             incoming_latency,
             incoming_jitter,
             incoming_loss,
+            seed: None,
         }
     }
 
@@ -139,6 +228,7 @@ impl LinkConditionerConfig {
             incoming_latency: Duration::from_millis(40),
             incoming_jitter: Duration::from_millis(6),
             incoming_loss: 0.002,
+            seed: None,
         }
     }
 
@@ -149,6 +239,7 @@ impl LinkConditionerConfig {
             incoming_latency: Duration::from_millis(170),
             incoming_jitter: Duration::from_millis(45),
             incoming_loss: 0.02,
+            seed: None,
         }
     }
 
@@ -159,6 +250,271 @@ impl LinkConditionerConfig {
             incoming_latency: Duration::from_millis(300),
             incoming_jitter: Duration::from_millis(84),
             incoming_loss: 0.04,
+            seed: None,
+        }
+    }
+}
+
+/// Network conditions commonly encountered by players, used with [`LinkConditionerConfig::preset`].
+///
+/// The values are one-way (the latency is about half of the RTT) and apply to the packets received by the peer
+/// that adds the conditioner: conditioning both the client and the server doubles the added RTT.
+/// They are rounded orders of magnitude, not measurements of a specific network.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Reflect, Serialize, Deserialize)]
+pub enum NetworkPreset {
+    /// Home Wi-Fi, with a server in the same region: 15ms ± 5ms, 0.5% loss.
+    ///
+    /// Consumer broadband adds 10-30ms of RTT to a nearby server, and the wireless link adds a few ms of jitter
+    /// and occasional losses from interference.
+    Wifi,
+    /// Mobile 4G/LTE: 35ms ± 15ms, 1% loss.
+    ///
+    /// The latency reports of mobile network analytics firms (OpenSignal, Ookla) usually put 4G RTTs between 40ms
+    /// and 70ms; the radio scheduling makes the latency much less stable than on a wired link.
+    FourG,
+    /// Server on another continent: 75ms ± 5ms, 0.1% loss.
+    ///
+    /// Light travels at about 200 000km/s in fiber, so a transatlantic or US coast-to-coast route
+    /// (about 6000km, plus the detours of the cables) takes 60-80ms one way. Backbone links are stable and rarely drop packets.
+    CrossRegion,
+    /// Geostationary satellite internet: 300ms ± 30ms, 1% loss.
+    ///
+    /// The satellites orbit at about 36 000km, so a packet travels at least 72 000km each way (240ms at the speed of
+    /// light) before any processing: geostationary links have RTTs of 550-650ms.
+    Satellite,
+    /// Congested home connection shared with a video call: 120ms ± 80ms, 5% loss.
+    ///
+    /// The upload of the video fills the buffers of the router (bufferbloat), which delays the other packets
+    /// by a variable amount and drops them when the buffers overflow.
+    TerribleConference,
+}
+
+impl NetworkPreset {
+    /// All the presets
+    pub const ALL: [NetworkPreset; 5] = [
+        NetworkPreset::Wifi,
+        NetworkPreset::FourG,
+        NetworkPreset::CrossRegion,
+        NetworkPreset::Satellite,
+        NetworkPreset::TerribleConference,
+    ];
+
+    /// The name of the preset, which is the name of the variant
+    pub fn name(&self) -> &'static str {
+        match self {
+            NetworkPreset::Wifi => "Wifi",
+            NetworkPreset::FourG => "FourG",
+            NetworkPreset::CrossRegion => "CrossRegion",
+            NetworkPreset::Satellite => "Satellite",
+            NetworkPreset::TerribleConference => "TerribleConference",
+        }
+    }
+}
+
+/// The name does not match any [`NetworkPreset`]
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+#[error("unknown network preset: {0}")]
+pub struct UnknownNetworkPreset(pub String);
+
+impl FromStr for NetworkPreset {
+    type Err = UnknownNetworkPreset;
+
+    /// Parse the name of a preset (case-insensitive)
+    fn from_str(name: &str) -> std::result::Result<Self, Self::Err> {
+        NetworkPreset::ALL
+            .into_iter()
+            .find(|preset| preset.name().eq_ignore_ascii_case(name))
+            .ok_or_else(|| UnknownNetworkPreset(name.to_string()))
+    }
+}
+
+/// A step of a [`TimeVaryingConditioner`]: the blend moves linearly to `blend` during `duration`
+#[derive(Clone, Copy, Debug, PartialEq, Reflect)]
+pub struct ConditionerTransition {
+    pub duration: Duration,
+    /// 0.0 for the `from` config, 1.0 for the `to` config
+    pub blend: f32,
+}
+
+/// A [`LinkConditionerConfig`] that changes over time, by interpolating between two configs over a schedule.
+///
+/// For example, to degrade a connection from Wi-Fi to 4G over 30 seconds, keep it degraded for 10 seconds and
+/// recover over 5 seconds:
+/// ```rust,ignore
+/// let conditioner = TimeVaryingConditioner::new(
+///     LinkConditionerConfig::preset(NetworkPreset::Wifi),
+///     LinkConditionerConfig::preset(NetworkPreset::FourG),
+/// )
+/// .degrade(Duration::from_secs(30))
+/// .hold(Duration::from_secs(10))
+/// .recover(Duration::from_secs(5));
+///
+/// // apply the conditions of the schedule to a client on the server
+/// fn update_conditioner(time: Res<Time>, mut manager: ResMut<ServerConnectionManager>) {
+///     let config = conditioner.config_at(time.elapsed());
+///     manager.set_conditioner(client_id, Some(config)).unwrap();
+/// }
+/// ```
+#[derive(Clone, Debug, PartialEq, Reflect)]
+pub struct TimeVaryingConditioner {
+    pub from: LinkConditionerConfig,
+    pub to: LinkConditionerConfig,
+    schedule: Vec<ConditionerTransition>,
+    /// If true, the schedule starts again once it is over
+    repeat: bool,
+}
+
+impl TimeVaryingConditioner {
+    /// Creates a conditioner that stays on the `from` config until transitions are added
+    pub fn new(from: LinkConditionerConfig, to: LinkConditionerConfig) -> Self {
+        Self {
+            from,
+            to,
+            schedule: vec![],
+            repeat: false,
         }
     }
+
+    /// Move linearly to the given blend (clamped between 0.0 and 1.0) during `duration`
+    pub fn transition(mut self, duration: Duration, blend: f32) -> Self {
+        self.schedule.push(ConditionerTransition {
+            duration,
+            blend: blend.clamp(0.0, 1.0),
+        });
+        self
+    }
+
+    /// Move linearly to the `to` config during `duration`
+    pub fn degrade(self, duration: Duration) -> Self {
+        self.transition(duration, 1.0)
+    }
+
+    /// Move linearly back to the `from` config during `duration`
+    pub fn recover(self, duration: Duration) -> Self {
+        self.transition(duration, 0.0)
+    }
+
+    /// Keep the current config during `duration`
+    pub fn hold(self, duration: Duration) -> Self {
+        let blend = self.schedule.last().map_or(0.0, |step| step.blend);
+        self.transition(duration, blend)
+    }
+
+    /// Start the schedule again once it is over
+    pub fn repeat(mut self) -> Self {
+        self.repeat = true;
+        self
+    }
+
+    /// Total duration of the schedule
+    pub fn duration(&self) -> Duration {
+        self.schedule.iter().map(|step| step.duration).sum()
+    }
+
+    /// The blend between the `from` (0.0) and the `to` (1.0) configs, `elapsed` after the start of the schedule
+    pub fn blend_at(&self, elapsed: Duration) -> f32 {
+        let total = self.duration();
+        let mut elapsed = elapsed;
+        if self.repeat && !total.is_zero() {
+            elapsed = Duration::from_nanos((elapsed.as_nanos() % total.as_nanos()) as u64);
+        }
+        let mut blend = 0.0;
+        for step in &self.schedule {
+            if elapsed < step.duration {
+                let progress = elapsed.as_secs_f32() / step.duration.as_secs_f32();
+                return blend + (step.blend - blend) * progress;
+            }
+            elapsed -= step.duration;
+            blend = step.blend;
+        }
+        blend
+    }
+
+    /// The config to apply `elapsed` after the start of the schedule
+    pub fn config_at(&self, elapsed: Duration) -> LinkConditionerConfig {
+        self.from.lerp(&self.to, self.blend_at(elapsed))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shared::clock::MockClock;
+
+    #[test]
+    fn test_presets() {
+        for preset in NetworkPreset::ALL {
+            assert_eq!(preset.name().parse::<NetworkPreset>(), Ok(preset));
+            let config = LinkConditionerConfig::preset(preset);
+            assert!(config.incoming_jitter < config.incoming_latency);
+            assert!((0.0..=0.05).contains(&config.incoming_loss));
+        }
+        assert_eq!("fourg".parse::<NetworkPreset>(), Ok(NetworkPreset::FourG));
+        assert_eq!(
+            "5G".parse::<NetworkPreset>(),
+            Err(UnknownNetworkPreset("5G".to_string()))
+        );
+        assert_eq!(
+            LinkConditionerConfig::preset(NetworkPreset::Satellite),
+            LinkConditionerConfig::new(Duration::from_millis(300), Duration::from_millis(30), 0.01)
+        );
+    }
+
+    #[test]
+    fn test_builder() {
+        let config = LinkConditionerConfig::preset(NetworkPreset::Wifi)
+            .with_latency(Duration::from_millis(50))
+            .with_loss(2.0);
+        assert_eq!(config.incoming_latency, Duration::from_millis(50));
+        assert_eq!(config.incoming_jitter, Duration::from_millis(5));
+        assert_eq!(config.incoming_loss, 1.0);
+        assert_eq!(
+            LinkConditionerConfig::default(),
+            LinkConditionerConfig::new(Duration::ZERO, Duration::ZERO, 0.0)
+        );
+    }
+
+    #[test]
+    fn test_seed() {
+        let config = LinkConditionerConfig::preset(NetworkPreset::TerribleConference).with_seed(3);
+        // the time does not advance while the packets are conditioned
+        let clock = NetworkClock::new(MockClock::new());
+        let received = |config: &LinkConditionerConfig| {
+            let mut conditioner = LinkConditioner::new(config.clone(), clock.clone());
+            (0..100).for_each(|i| conditioner.condition_packet(i));
+            let later = clock.now() + Duration::from_secs(1);
+            std::iter::from_fn(|| conditioner.time_queue.pop_item(&later))
+                .map(|(_, packet)| packet)
+                .collect::<Vec<_>>()
+        };
+        // the same packets are dropped and reordered with the same seed
+        assert_eq!(received(&config), received(&config));
+        assert_ne!(received(&config), received(&config.clone().with_seed(4)));
+    }
+
+    #[test]
+    fn test_time_varying() {
+        let from = LinkConditionerConfig::default();
+        let to = LinkConditionerConfig::default()
+            .with_latency(Duration::from_millis(100))
+            .with_loss(0.1);
+        let conditioner = TimeVaryingConditioner::new(from.clone(), to.clone())
+            .degrade(Duration::from_secs(30))
+            .hold(Duration::from_secs(10))
+            .recover(Duration::from_secs(10));
+        assert_eq!(conditioner.duration(), Duration::from_secs(50));
+        assert_eq!(conditioner.config_at(Duration::ZERO), from);
+        let degrading = conditioner.config_at(Duration::from_secs(15));
+        assert_eq!(degrading.incoming_latency, Duration::from_millis(50));
+        assert!((degrading.incoming_loss - 0.05).abs() < 1e-6);
+        assert_eq!(conditioner.config_at(Duration::from_secs(30)), to);
+        assert_eq!(conditioner.config_at(Duration::from_secs(39)), to);
+        assert_eq!(conditioner.blend_at(Duration::from_secs(45)), 0.5);
+        assert_eq!(conditioner.config_at(Duration::from_secs(50)), from);
+        assert_eq!(conditioner.config_at(Duration::from_secs(80)), from);
+
+        let repeating = conditioner.repeat();
+        assert_eq!(repeating.blend_at(Duration::from_secs(65)), 0.5);
+        assert_eq!(repeating.config_at(Duration::from_secs(80)), to);
+    }
 }
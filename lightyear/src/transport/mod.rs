@@ -14,7 +14,10 @@ use crate::transport::channels::Channels;
 use crate::transport::dummy::DummyIo;
 use crate::transport::local::LocalChannel;
 use crate::transport::udp::UdpSocket;
-
+#[cfg(feature = "webtransport")]
+use crate::transport::webtransport::WebTransportClientSocket;
+#[cfg(all(feature = "websocket", not(target_family = "wasm")))]
+use crate::transport::websocket::server::WebSocketServerSocket;
 
 /// io is a wrapper around the underlying transport layer
 pub mod io;
@@ -28,6 +31,14 @@ pub(crate) mod udp;
 /// The transport is a map of channels (used for server, during testing)
 pub(crate) mod channels;
 
+/// The transport is a WebTransport connection
+#[cfg(feature = "webtransport")]
+pub(crate) mod webtransport;
+
+/// The transport is a WebSocket connection
+#[cfg(feature = "websocket")]
+pub(crate) mod websocket;
+
 pub(crate) mod middleware;
 
 pub mod config;
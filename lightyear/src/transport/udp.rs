@@ -172,7 +172,7 @@ mod tests {
 
     #[test]
     fn test_udp_socket_with_conditioner() {
-        use mock_instant::MockClock;
+        use crate::shared::clock::{MockClock, NetworkClock};
 
         // let the OS assign a port
         let local_addr = SocketAddr::from_str("127.0.0.1:0").unwrap();
@@ -189,11 +189,16 @@ mod tests {
         let server_addr = server_socket.local_addr();
         let (_, server_receiver) = server_socket.split();
 
-        let mut conditioned_server_receiver = LinkConditioner::new(LinkConditionerConfig {
-            incoming_latency: Duration::from_millis(100),
-            incoming_jitter: Duration::from_millis(0),
-            incoming_loss: 0.0,
-        })
+        let clock = MockClock::new();
+        let mut conditioned_server_receiver = LinkConditioner::new(
+            LinkConditionerConfig {
+                incoming_latency: Duration::from_millis(100),
+                incoming_jitter: Duration::from_millis(0),
+                incoming_loss: 0.0,
+                seed: None,
+            },
+            NetworkClock::new(clock.clone()),
+        )
         .wrap(server_receiver);
 
         let msg = b"hello world";
@@ -203,19 +208,19 @@ mod tests {
         // sleep a little to give time to the message to arrive in the socket
         std::thread::sleep(Duration::from_millis(10));
 
-        // we don't receive the packet yet because the mock clock is still at 0s
+        // we don't receive the packet yet because the mock clock didn't advance
         // so we add the packet to the time queue
         let None = conditioned_server_receiver.recv().unwrap() else {
             panic!("no packets should have arrived yet");
         };
 
         // advance a small amount, but not enough to receive the packet in the queue
-        MockClock::advance(Duration::from_millis(50));
+        clock.advance(Duration::from_millis(50));
         let None = conditioned_server_receiver.recv().unwrap() else {
             panic!("no packets should have arrived yet");
         };
 
-        MockClock::advance(Duration::from_secs(1));
+        clock.advance(Duration::from_secs(1));
         // now the packet should be available (read from the time queue)
         let Ok(Some((recv_msg, address))) = conditioned_server_receiver.recv() else {
             panic!("expected to receive a packet");
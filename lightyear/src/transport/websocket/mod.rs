@@ -0,0 +1,5 @@
+//! The transport is a WebSocket connection (binary frames over TCP)
+//!
+//! WebSockets are supported by every browser, so this is the fallback for the clients that cannot use WebTransport.
+#[cfg(not(target_family = "wasm"))]
+pub(crate) mod server;
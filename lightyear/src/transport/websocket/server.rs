@@ -0,0 +1,263 @@
+//! WebSocket server, using [`tokio_tungstenite`]
+//!
+//! Each accepted WebSocket connection is assigned a fake [`SocketAddr`], which identifies the client in the
+//! netcode layer. The real address of the peer cannot be used: clients behind the same NAT share the same
+//! ip, and a reverse proxy in front of the server would make every client appear with the proxy's address.
+use std::net::{IpAddr, Ipv6Addr, SocketAddr};
+use std::sync::{Arc, Mutex};
+
+use async_compat::Compat;
+use bevy::tasks::{block_on, IoTaskPool};
+use bevy::utils::HashMap;
+use futures::{SinkExt, StreamExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tokio_tungstenite::tungstenite::Message;
+use tracing::{debug, error, info, trace};
+
+use crate::server::io::transport::{ServerTransportBuilder, ServerTransportEnum};
+use crate::server::io::{ServerIoEvent, ServerIoEventReceiver, ServerNetworkEventSender};
+use crate::transport::error::Result;
+use crate::transport::io::IoState;
+use crate::transport::{BoxedReceiver, BoxedSender, PacketReceiver, PacketSender, Transport, MTU};
+
+/// Senders of the binary frames to each connected client, indexed by the fake address of the client
+type ClientboundTxMap = Arc<Mutex<HashMap<SocketAddr, mpsc::UnboundedSender<Message>>>>;
+
+/// Builder for the server-side WebSocket listener
+pub(crate) struct WebSocketServerSocketBuilder {
+    pub(crate) server_addr: SocketAddr,
+}
+
+impl ServerTransportBuilder for WebSocketServerSocketBuilder {
+    fn start(
+        self,
+    ) -> Result<(
+        ServerTransportEnum,
+        IoState,
+        Option<ServerIoEventReceiver>,
+        Option<ServerNetworkEventSender>,
+    )> {
+        // bind the listener right away, so that `local_addr` returns the bound address.
+        // tokio's sockets need to run inside a tokio runtime
+        let listener = block_on(Compat::new(TcpListener::bind(self.server_addr)))?;
+        let local_addr = listener.local_addr()?;
+        info!(%local_addr, "Starting server WebSocket task");
+
+        let (serverbound_tx, serverbound_rx) = mpsc::unbounded_channel();
+        let clientbound_tx_map = ClientboundTxMap::default();
+        // used to notify the netcode layer when a client's socket is closed
+        let (status_tx, status_rx) = async_channel::unbounded();
+        // used by the netcode layer to close the socket of a client, or to stop the server
+        let (close_tx, close_rx) = async_channel::unbounded();
+        IoTaskPool::get()
+            .spawn(Compat::new(accept_loop(
+                listener,
+                serverbound_tx,
+                clientbound_tx_map.clone(),
+                status_tx,
+                close_rx,
+            )))
+            .detach();
+
+        let sender = WebSocketServerSocketSender { clientbound_tx_map };
+        let receiver = WebSocketServerSocketReceiver {
+            serverbound_rx,
+            buffer: [0; MTU],
+        };
+        Ok((
+            ServerTransportEnum::WebSocketServer(WebSocketServerSocket {
+                local_addr,
+                sender,
+                receiver,
+            }),
+            IoState::Connected,
+            Some(ServerIoEventReceiver(status_rx)),
+            Some(ServerNetworkEventSender(close_tx)),
+        ))
+    }
+}
+
+/// Fake address of the `n`-th accepted connection, in the ipv6 unique local range `fd00::/8`.
+///
+/// The addresses are never reused, so a client that reconnects cannot be mistaken for its previous connection.
+fn fake_addr(n: u64) -> SocketAddr {
+    let ip = Ipv6Addr::from((0xfd00_u128 << 112) | n as u128);
+    SocketAddr::new(IpAddr::V6(ip), 0)
+}
+
+async fn accept_loop(
+    listener: TcpListener,
+    serverbound_tx: mpsc::UnboundedSender<(SocketAddr, Vec<u8>)>,
+    clientbound_tx_map: ClientboundTxMap,
+    status_tx: async_channel::Sender<ServerIoEvent>,
+    close_rx: async_channel::Receiver<ServerIoEvent>,
+) {
+    let mut num_connections = 0;
+    loop {
+        tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok((stream, peer_addr)) => {
+                    num_connections += 1;
+                    let addr = fake_addr(num_connections);
+                    debug!(%peer_addr, %addr, "New WebSocket connection");
+                    IoTaskPool::get()
+                        .spawn(Compat::new(handle_connection(
+                            stream,
+                            addr,
+                            serverbound_tx.clone(),
+                            clientbound_tx_map.clone(),
+                            status_tx.clone(),
+                        )))
+                        .detach();
+                }
+                Err(e) => {
+                    error!(?e, "Could not accept WebSocket connection");
+                }
+            },
+            event = close_rx.recv() => match event {
+                Ok(ServerIoEvent::ClientDisconnected(addr)) => {
+                    // dropping the sender stops the connection's send task, which closes the socket
+                    if clientbound_tx_map.lock().unwrap().remove(&addr).is_some() {
+                        debug!(%addr, "Closing the WebSocket connection of a disconnected client");
+                    }
+                }
+                Ok(ServerIoEvent::ServerDisconnected(_)) | Err(_) => {
+                    info!("Stopping the WebSocket server");
+                    clientbound_tx_map.lock().unwrap().clear();
+                    return;
+                }
+                Ok(ServerIoEvent::ServerConnected) => {}
+            }
+        }
+    }
+}
+
+async fn handle_connection(
+    stream: TcpStream,
+    addr: SocketAddr,
+    serverbound_tx: mpsc::UnboundedSender<(SocketAddr, Vec<u8>)>,
+    clientbound_tx_map: ClientboundTxMap,
+    status_tx: async_channel::Sender<ServerIoEvent>,
+) {
+    let ws_stream = match tokio_tungstenite::accept_async(stream).await {
+        Ok(ws_stream) => ws_stream,
+        Err(e) => {
+            debug!(?e, %addr, "WebSocket handshake failed");
+            return;
+        }
+    };
+    let (mut write, mut read) = ws_stream.split();
+    let (clientbound_tx, mut clientbound_rx) = mpsc::unbounded_channel();
+    clientbound_tx_map
+        .lock()
+        .unwrap()
+        .insert(addr, clientbound_tx);
+
+    // NOTE: we receive and send the frames in two separate tasks, so that a pending
+    // future is never dropped and recreated, which could lose frames
+    let send_handle = IoTaskPool::get().spawn(Compat::new(async move {
+        while let Some(message) = clientbound_rx.recv().await {
+            if let Err(e) = write.send(message).await {
+                debug!(?e, %addr, "Could not send WebSocket frame");
+                break;
+            }
+        }
+        let _ = write.close().await;
+    }));
+
+    while let Some(message) = read.next().await {
+        match message {
+            Ok(Message::Binary(payload)) => {
+                trace!(%addr, "receive frame from client: {:?}", &payload);
+                if serverbound_tx.send((addr, payload)).is_err() {
+                    break;
+                }
+            }
+            Ok(Message::Close(_)) => break,
+            // pings are answered by tungstenite, and the text frames are not part of the protocol
+            Ok(_) => {}
+            Err(e) => {
+                debug!(?e, %addr, "Stopped receiving WebSocket frames");
+                break;
+            }
+        }
+    }
+
+    // if the netcode layer closed the connection, the client is already disconnected
+    let closed_by_peer = clientbound_tx_map.lock().unwrap().remove(&addr).is_some();
+    send_handle.await;
+    if closed_by_peer {
+        info!(%addr, "WebSocket connection closed by the client");
+        let _ = status_tx
+            .send(ServerIoEvent::ClientDisconnected(addr))
+            .await;
+    }
+}
+
+/// WebSocket listener that accepts the connections of the clients
+pub struct WebSocketServerSocket {
+    local_addr: SocketAddr,
+    sender: WebSocketServerSocketSender,
+    receiver: WebSocketServerSocketReceiver,
+}
+
+impl Transport for WebSocketServerSocket {
+    fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    fn split(self) -> (BoxedSender, BoxedReceiver) {
+        (Box::new(self.sender), Box::new(self.receiver))
+    }
+}
+
+struct WebSocketServerSocketSender {
+    clientbound_tx_map: ClientboundTxMap,
+}
+
+impl PacketSender for WebSocketServerSocketSender {
+    fn send(&mut self, payload: &[u8], address: &SocketAddr) -> Result<()> {
+        // like with UDP, packets sent to a client whose socket is closed are lost
+        let sent = self
+            .clientbound_tx_map
+            .lock()
+            .unwrap()
+            .get(address)
+            .is_some_and(|tx| tx.send(Message::Binary(payload.to_vec())).is_ok());
+        if !sent {
+            trace!(%address, "dropping packet for a closed WebSocket connection");
+        }
+        Ok(())
+    }
+}
+
+struct WebSocketServerSocketReceiver {
+    serverbound_rx: mpsc::UnboundedReceiver<(SocketAddr, Vec<u8>)>,
+    buffer: [u8; MTU],
+}
+
+impl PacketReceiver for WebSocketServerSocketReceiver {
+    fn recv(&mut self) -> Result<Option<(&mut [u8], SocketAddr)>> {
+        match self.serverbound_rx.try_recv() {
+            Ok((addr, data)) => {
+                let len = data.len().min(MTU);
+                self.buffer[..len].copy_from_slice(&data[..len]);
+                Ok(Some((&mut self.buffer[..len], addr)))
+            }
+            Err(_) => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Two connections from the same peer address get different addresses
+    #[test]
+    fn test_fake_addr() {
+        assert_ne!(fake_addr(1), fake_addr(2));
+        assert_eq!(fake_addr(1).ip().to_string(), "fd00::1");
+    }
+}
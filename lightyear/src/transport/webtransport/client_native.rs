@@ -0,0 +1,201 @@
+//! WebTransport client for native targets, using [`wtransport`]
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use async_compat::Compat;
+use bevy::tasks::{block_on, IoTaskPool};
+use tokio::sync::mpsc;
+use tracing::{debug, error, info, trace};
+use wtransport::endpoint::endpoint_side;
+use wtransport::tls::Sha256Digest;
+use wtransport::{ClientConfig, Endpoint, VarInt};
+
+use crate::client::io::transport::{ClientTransportBuilder, ClientTransportEnum};
+use crate::client::io::{ClientIoEvent, ClientIoEventReceiver, ClientNetworkEventSender};
+use crate::transport::error::Result;
+use crate::transport::io::IoState;
+use crate::transport::{BoxedReceiver, BoxedSender, PacketReceiver, PacketSender, Transport, MTU};
+
+use super::{parse_certificate_digest, WebTransportClientSocketBuilder};
+
+impl ClientTransportBuilder for WebTransportClientSocketBuilder {
+    fn connect(
+        self,
+    ) -> Result<(
+        ClientTransportEnum,
+        IoState,
+        Option<ClientIoEventReceiver>,
+        Option<ClientNetworkEventSender>,
+    )> {
+        let config = ClientConfig::builder().with_bind_address(self.client_addr);
+        let config = if self.certificate_digest.is_empty() {
+            // accept the self-signed certificates of local development servers
+            config.with_no_cert_validation()
+        } else {
+            let digest = parse_certificate_digest(&self.certificate_digest)?;
+            config.with_server_certificate_hashes([Sha256Digest::new(digest)])
+        }
+        .build();
+        // bind the socket right away, so that `local_addr` returns the bound address.
+        // quinn needs to run inside a tokio runtime
+        let endpoint = block_on(Compat::new(async { Endpoint::client(config) }))?;
+        let local_addr = endpoint.local_addr()?;
+
+        let server_url = format!("https://{}", self.server_addr);
+        info!(%server_url, %local_addr, "Starting client WebTransport task");
+        let (to_server_sender, to_server_receiver) = mpsc::unbounded_channel();
+        let (from_server_sender, from_server_receiver) = mpsc::unbounded_channel();
+        // used by the client to close the connection
+        let (close_tx, close_rx) = async_channel::bounded(1);
+        // used to notify the client when the connection is established or lost
+        let (status_tx, status_rx) = async_channel::bounded(1);
+        IoTaskPool::get()
+            .spawn(Compat::new(run_connection(
+                endpoint,
+                server_url,
+                to_server_receiver,
+                from_server_sender,
+                close_rx,
+                status_tx,
+            )))
+            .detach();
+
+        let sender = WebTransportClientPacketSender { to_server_sender };
+        let receiver = WebTransportClientPacketReceiver {
+            server_addr: self.server_addr,
+            from_server_receiver,
+            buffer: [0; MTU],
+        };
+        Ok((
+            ClientTransportEnum::WebTransportClient(WebTransportClientSocket {
+                local_addr,
+                sender,
+                receiver,
+            }),
+            IoState::Connecting,
+            Some(ClientIoEventReceiver(status_rx)),
+            Some(ClientNetworkEventSender(close_tx)),
+        ))
+    }
+}
+
+async fn run_connection(
+    endpoint: Endpoint<endpoint_side::Client>,
+    server_url: String,
+    mut to_server_receiver: mpsc::UnboundedReceiver<Box<[u8]>>,
+    from_server_sender: mpsc::UnboundedSender<Vec<u8>>,
+    close_rx: async_channel::Receiver<ClientIoEvent>,
+    status_tx: async_channel::Sender<ClientIoEvent>,
+) {
+    let connection = tokio::select! {
+        connection = endpoint.connect(&server_url) => match connection {
+            Ok(connection) => connection,
+            Err(e) => {
+                error!(?e, "Could not connect to the WebTransport server");
+                let _ = status_tx
+                    .send(ClientIoEvent::Disconnected(std::io::Error::other(e).into()))
+                    .await;
+                return;
+            }
+        },
+        _ = close_rx.recv() => {
+            info!("WebTransport connection cancelled by the client");
+            return;
+        }
+    };
+    let _ = status_tx.send(ClientIoEvent::Connected).await;
+    let connection = Arc::new(connection);
+
+    // NOTE: we receive and send the datagrams in two separate tasks. With a single `select!` in a loop,
+    // the future of the branch that is not selected would be dropped and recreated, which can lose datagrams
+    let connection_recv = connection.clone();
+    let recv_handle = IoTaskPool::get().spawn(Compat::new(async move {
+        loop {
+            match connection_recv.receive_datagram().await {
+                Ok(datagram) => {
+                    trace!("receive datagram from server: {:?}", &datagram);
+                    if from_server_sender.send(datagram.to_vec()).is_err() {
+                        break;
+                    }
+                }
+                Err(e) => {
+                    debug!(?e, "Stopped receiving WebTransport datagrams");
+                    break;
+                }
+            }
+        }
+    }));
+    let connection_send = connection.clone();
+    let send_handle = IoTaskPool::get().spawn(Compat::new(async move {
+        while let Some(payload) = to_server_receiver.recv().await {
+            trace!("send datagram to server: {:?}", &payload);
+            if let Err(e) = connection_send.send_datagram(payload) {
+                error!(?e, "Could not send WebTransport datagram");
+            }
+        }
+    }));
+
+    // wait until the connection is lost, or the client closes it
+    tokio::select! {
+        reason = connection.closed() => {
+            info!(?reason, "WebTransport connection closed");
+            let _ = status_tx
+                .send(ClientIoEvent::Disconnected(std::io::Error::other(reason).into()))
+                .await;
+        }
+        _ = close_rx.recv() => {
+            info!("WebTransport connection closed by the client");
+        }
+    }
+    recv_handle.cancel().await;
+    send_handle.cancel().await;
+    connection.close(VarInt::from_u32(0), &[]);
+}
+
+/// WebTransport connection to the server
+pub struct WebTransportClientSocket {
+    local_addr: SocketAddr,
+    sender: WebTransportClientPacketSender,
+    receiver: WebTransportClientPacketReceiver,
+}
+
+impl Transport for WebTransportClientSocket {
+    fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    fn split(self) -> (BoxedSender, BoxedReceiver) {
+        (Box::new(self.sender), Box::new(self.receiver))
+    }
+}
+
+struct WebTransportClientPacketSender {
+    to_server_sender: mpsc::UnboundedSender<Box<[u8]>>,
+}
+
+impl PacketSender for WebTransportClientPacketSender {
+    fn send(&mut self, payload: &[u8], _: &SocketAddr) -> Result<()> {
+        self.to_server_sender.send(payload.into())?;
+        Ok(())
+    }
+}
+
+struct WebTransportClientPacketReceiver {
+    server_addr: SocketAddr,
+    from_server_receiver: mpsc::UnboundedReceiver<Vec<u8>>,
+    buffer: [u8; MTU],
+}
+
+impl PacketReceiver for WebTransportClientPacketReceiver {
+    fn recv(&mut self) -> Result<Option<(&mut [u8], SocketAddr)>> {
+        // the loss of the connection is reported through the io events
+        match self.from_server_receiver.try_recv() {
+            Ok(data) => {
+                let len = data.len().min(MTU);
+                self.buffer[..len].copy_from_slice(&data[..len]);
+                Ok(Some((&mut self.buffer[..len], self.server_addr)))
+            }
+            Err(_) => Ok(None),
+        }
+    }
+}
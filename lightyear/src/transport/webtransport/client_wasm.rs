@@ -0,0 +1,210 @@
+//! WebTransport client for wasm targets, using the browser's WebTransport API
+use std::net::SocketAddr;
+use std::rc::Rc;
+
+use futures::future::{select, Either};
+use futures::pin_mut;
+use tokio::sync::mpsc;
+use tracing::{debug, error, info, trace};
+use wasm_bindgen_futures::JsFuture;
+use xwt_core::prelude::*;
+
+use crate::client::io::transport::{ClientTransportBuilder, ClientTransportEnum};
+use crate::client::io::{ClientIoEvent, ClientIoEventReceiver, ClientNetworkEventSender};
+use crate::transport::error::Result;
+use crate::transport::io::IoState;
+use crate::transport::{BoxedReceiver, BoxedSender, PacketReceiver, PacketSender, Transport, MTU};
+
+use super::{parse_certificate_digest, WebTransportClientSocketBuilder};
+
+impl ClientTransportBuilder for WebTransportClientSocketBuilder {
+    fn connect(
+        self,
+    ) -> Result<(
+        ClientTransportEnum,
+        IoState,
+        Option<ClientIoEventReceiver>,
+        Option<ClientNetworkEventSender>,
+    )> {
+        // the browser only accepts self-signed certificates if their digest is provided
+        let server_certificate_hashes = if self.certificate_digest.is_empty() {
+            vec![]
+        } else {
+            vec![xwt_web_sys::CertificateHash {
+                algorithm: xwt_web_sys::HashAlgorithm::Sha256,
+                value: parse_certificate_digest(&self.certificate_digest)?.to_vec(),
+            }]
+        };
+        let options = xwt_web_sys::WebTransportOptions {
+            server_certificate_hashes,
+            ..Default::default()
+        };
+        let endpoint = xwt_web_sys::Endpoint {
+            options: options.to_js(),
+        };
+
+        let server_url = format!("https://{}", self.server_addr);
+        info!(%server_url, "Starting client WebTransport task");
+        let (to_server_sender, to_server_receiver) = mpsc::unbounded_channel();
+        let (from_server_sender, from_server_receiver) = mpsc::unbounded_channel();
+        // used by the client to close the connection
+        let (close_tx, close_rx) = async_channel::bounded(1);
+        // used to notify the client when the connection is established or lost
+        let (status_tx, status_rx) = async_channel::bounded(1);
+        wasm_bindgen_futures::spawn_local(run_connection(
+            endpoint,
+            server_url,
+            to_server_receiver,
+            from_server_sender,
+            close_rx,
+            status_tx,
+        ));
+
+        let sender = WebTransportClientPacketSender { to_server_sender };
+        let receiver = WebTransportClientPacketReceiver {
+            server_addr: self.server_addr,
+            from_server_receiver,
+            buffer: [0; MTU],
+        };
+        Ok((
+            // the browser does not expose the address of the socket
+            ClientTransportEnum::WebTransportClient(WebTransportClientSocket {
+                local_addr: self.client_addr,
+                sender,
+                receiver,
+            }),
+            IoState::Connecting,
+            Some(ClientIoEventReceiver(status_rx)),
+            Some(ClientNetworkEventSender(close_tx)),
+        ))
+    }
+}
+
+fn js_error(e: impl std::fmt::Debug) -> crate::transport::error::Error {
+    std::io::Error::other(format!("{e:?}")).into()
+}
+
+async fn run_connection(
+    endpoint: xwt_web_sys::Endpoint,
+    server_url: String,
+    mut to_server_receiver: mpsc::UnboundedReceiver<Box<[u8]>>,
+    from_server_sender: mpsc::UnboundedSender<Vec<u8>>,
+    close_rx: async_channel::Receiver<ClientIoEvent>,
+    status_tx: async_channel::Sender<ClientIoEvent>,
+) {
+    let connecting = match endpoint.connect(&server_url).await {
+        Ok(connecting) => connecting,
+        Err(e) => {
+            error!(?e, "Could not connect to the WebTransport server");
+            let _ = status_tx
+                .send(ClientIoEvent::Disconnected(js_error(e)))
+                .await;
+            return;
+        }
+    };
+    let connection = match connecting.wait_connect().await {
+        Ok(connection) => Rc::new(connection),
+        Err(e) => {
+            error!(?e, "Could not connect to the WebTransport server");
+            let _ = status_tx
+                .send(ClientIoEvent::Disconnected(js_error(e)))
+                .await;
+            return;
+        }
+    };
+    let _ = status_tx.send(ClientIoEvent::Connected).await;
+
+    // NOTE: we receive and send the datagrams in two separate tasks, so that a pending
+    // future is never dropped and recreated, which could lose datagrams
+    let connection_recv = connection.clone();
+    wasm_bindgen_futures::spawn_local(async move {
+        loop {
+            match connection_recv.receive_datagram().await {
+                Ok(datagram) => {
+                    trace!("receive datagram from server: {:?}", &datagram);
+                    if from_server_sender.send(datagram.to_vec()).is_err() {
+                        break;
+                    }
+                }
+                Err(e) => {
+                    debug!(?e, "Stopped receiving WebTransport datagrams");
+                    break;
+                }
+            }
+        }
+    });
+    let connection_send = connection.clone();
+    wasm_bindgen_futures::spawn_local(async move {
+        while let Some(payload) = to_server_receiver.recv().await {
+            trace!("send datagram to server: {:?}", &payload);
+            if let Err(e) = connection_send.send_datagram(payload).await {
+                error!(?e, "Could not send WebTransport datagram");
+            }
+        }
+    });
+
+    // wait until the connection is lost, or the client closes it
+    let closed = JsFuture::from(connection.transport.closed());
+    let close_requested = close_rx.recv();
+    pin_mut!(closed, close_requested);
+    match select(closed, close_requested).await {
+        Either::Left((reason, _)) => {
+            info!(?reason, "WebTransport connection closed");
+            let _ = status_tx
+                .send(ClientIoEvent::Disconnected(js_error(reason)))
+                .await;
+        }
+        Either::Right(_) => {
+            info!("WebTransport connection closed by the client");
+            connection.transport.close();
+        }
+    }
+}
+
+/// WebTransport connection to the server
+pub struct WebTransportClientSocket {
+    local_addr: SocketAddr,
+    sender: WebTransportClientPacketSender,
+    receiver: WebTransportClientPacketReceiver,
+}
+
+impl Transport for WebTransportClientSocket {
+    fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    fn split(self) -> (BoxedSender, BoxedReceiver) {
+        (Box::new(self.sender), Box::new(self.receiver))
+    }
+}
+
+struct WebTransportClientPacketSender {
+    to_server_sender: mpsc::UnboundedSender<Box<[u8]>>,
+}
+
+impl PacketSender for WebTransportClientPacketSender {
+    fn send(&mut self, payload: &[u8], _: &SocketAddr) -> Result<()> {
+        self.to_server_sender.send(payload.into())?;
+        Ok(())
+    }
+}
+
+struct WebTransportClientPacketReceiver {
+    server_addr: SocketAddr,
+    from_server_receiver: mpsc::UnboundedReceiver<Vec<u8>>,
+    buffer: [u8; MTU],
+}
+
+impl PacketReceiver for WebTransportClientPacketReceiver {
+    fn recv(&mut self) -> Result<Option<(&mut [u8], SocketAddr)>> {
+        // the loss of the connection is reported through the io events
+        match self.from_server_receiver.try_recv() {
+            Ok(data) => {
+                let len = data.len().min(MTU);
+                self.buffer[..len].copy_from_slice(&data[..len]);
+                Ok(Some((&mut self.buffer[..len], self.server_addr)))
+            }
+            Err(_) => Ok(None),
+        }
+    }
+}
@@ -0,0 +1,62 @@
+//! The transport is a WebTransport connection (datagrams over QUIC/HTTP3)
+//!
+//! On native, the connection uses [`wtransport`]; on wasm, it uses the browser's WebTransport API.
+use crate::transport::error::{Error, Result};
+
+cfg_if::cfg_if! {
+    if #[cfg(target_family = "wasm")] {
+        mod client_wasm;
+        pub(crate) use client_wasm::*;
+    } else {
+        mod client_native;
+        pub(crate) use client_native::*;
+    }
+}
+
+/// Builder for the client-side WebTransport connection
+pub(crate) struct WebTransportClientSocketBuilder {
+    pub(crate) client_addr: std::net::SocketAddr,
+    pub(crate) server_addr: std::net::SocketAddr,
+    pub(crate) certificate_digest: String,
+}
+
+/// Parse the hex-encoded SHA-256 digest of the server certificate.
+///
+/// The bytes can be separated by colons (`AB:CD:..`), which is how most tools print certificate digests.
+pub(crate) fn parse_certificate_digest(digest: &str) -> Result<[u8; 32]> {
+    let hex: String = digest.chars().filter(|c| *c != ':').collect();
+    let invalid = || {
+        Error::Io(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("invalid certificate digest: {digest}"),
+        ))
+    };
+    if hex.len() != 64 {
+        return Err(invalid());
+    }
+    let mut bytes = [0; 32];
+    for (i, byte) in bytes.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(hex.get(2 * i..2 * i + 2).ok_or_else(invalid)?, 16)
+            .map_err(|_| invalid())?;
+    }
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_certificate_digest() {
+        let digest = "01:23:45:67:89:AB:CD:EF:01:23:45:67:89:ab:cd:ef:01:23:45:67:89:AB:CD:EF:01:23:45:67:89:ab:cd:ef";
+        let bytes = parse_certificate_digest(digest).unwrap();
+        assert_eq!(bytes[..8], [0x01, 0x23, 0x45, 0x67, 0x89, 0xab, 0xcd, 0xef]);
+        assert_eq!(
+            parse_certificate_digest(&digest.replace(':', "")).unwrap(),
+            bytes
+        );
+
+        assert!(parse_certificate_digest("0123").is_err());
+        assert!(parse_certificate_digest(&"zz".repeat(32)).is_err());
+    }
+}
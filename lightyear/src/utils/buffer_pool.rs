@@ -0,0 +1,230 @@
+//! Pools of buffers that are reused across frames, so that the send path does not allocate
+//! new buffers for every message and packet.
+//!
+//! The amount of memory retained by a pool follows the high-water mark of the recent frames:
+//! it grows immediately when the traffic increases, and is released progressively after a burst.
+use std::collections::VecDeque;
+
+/// Number of frames after which a [`HighWaterMark`] drops to the peak of the last window
+const WINDOW_FRAMES: u32 = 256;
+
+/// Largest value recorded over the recent frames.
+///
+/// The mark increases immediately, but it only decreases at the end of a window of
+/// [`WINDOW_FRAMES`] frames, to the peak of that window.
+#[derive(Debug, Default, Clone)]
+pub(crate) struct HighWaterMark {
+    mark: usize,
+    window_peak: usize,
+    window_frames: u32,
+}
+
+impl HighWaterMark {
+    pub(crate) fn get(&self) -> usize {
+        self.mark
+    }
+
+    pub(crate) fn record(&mut self, value: usize) {
+        self.window_peak = self.window_peak.max(value);
+        self.mark = self.mark.max(value);
+    }
+
+    /// Returns true if the mark decreased
+    pub(crate) fn end_frame(&mut self) -> bool {
+        self.window_frames += 1;
+        if self.window_frames < WINDOW_FRAMES {
+            return false;
+        }
+        let previous = self.mark;
+        self.mark = self.window_peak;
+        self.window_peak = 0;
+        self.window_frames = 0;
+        self.mark < previous
+    }
+}
+
+/// A buffer that can be emptied and reused
+pub(crate) trait Recycle {
+    fn clear(&mut self);
+
+    fn capacity(&self) -> usize;
+}
+
+impl<T> Recycle for Vec<T> {
+    fn clear(&mut self) {
+        Vec::clear(self)
+    }
+
+    fn capacity(&self) -> usize {
+        Vec::capacity(self)
+    }
+}
+
+impl<T> Recycle for VecDeque<T> {
+    fn clear(&mut self) {
+        VecDeque::clear(self)
+    }
+
+    fn capacity(&self) -> usize {
+        VecDeque::capacity(self)
+    }
+}
+
+/// Stack of buffers that can be taken and given back.
+///
+/// The pool retains at most as many buffers as were in use at the same time in the recent frames.
+/// Buffers that grew beyond `max_capacity` are freed instead of being retained.
+#[derive(Debug)]
+pub(crate) struct BufferPool<T> {
+    free: Vec<T>,
+    /// Number of buffers that were taken and not given back yet
+    in_use: usize,
+    high_water_mark: HighWaterMark,
+    max_capacity: usize,
+    enabled: bool,
+}
+
+impl<T> Default for BufferPool<T> {
+    fn default() -> Self {
+        Self::new(usize::MAX)
+    }
+}
+
+impl<T> BufferPool<T> {
+    pub(crate) fn new(max_capacity: usize) -> Self {
+        Self {
+            free: Vec::new(),
+            in_use: 0,
+            high_water_mark: HighWaterMark::default(),
+            max_capacity,
+            enabled: true,
+        }
+    }
+
+    /// A pool that never retains buffers: every buffer taken is newly allocated
+    #[cfg(any(test, feature = "test_utils"))]
+    pub(crate) fn disabled() -> Self {
+        Self {
+            enabled: false,
+            ..Self::default()
+        }
+    }
+
+    /// Number of buffers that are ready to be reused
+    #[cfg(test)]
+    pub(crate) fn len(&self) -> usize {
+        self.free.len()
+    }
+}
+
+impl<T: Recycle> BufferPool<T> {
+    /// Take an empty buffer from the pool, or create one with `init` if the pool is empty
+    pub(crate) fn take_or_else(&mut self, init: impl FnOnce() -> T) -> T {
+        self.in_use += 1;
+        self.high_water_mark.record(self.in_use);
+        self.free.pop().unwrap_or_else(init)
+    }
+
+    /// Give back a buffer so that its allocation can be reused
+    pub(crate) fn give_back(&mut self, mut buffer: T) {
+        self.in_use = self.in_use.saturating_sub(1);
+        if self.enabled
+            && self.free.len() < self.high_water_mark.get()
+            && buffer.capacity() <= self.max_capacity
+        {
+            buffer.clear();
+            self.free.push(buffer);
+        }
+    }
+
+    /// Release the buffers that were not needed during the recent frames
+    pub(crate) fn end_frame(&mut self) {
+        if self.high_water_mark.end_frame() {
+            self.free.truncate(self.high_water_mark.get());
+        }
+    }
+}
+
+impl<T: Recycle + Default> BufferPool<T> {
+    pub(crate) fn take(&mut self) -> T {
+        self.take_or_else(T::default)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_high_water_mark() {
+        let mut mark = HighWaterMark::default();
+        mark.record(10);
+        mark.record(3);
+        assert_eq!(mark.get(), 10);
+        // the mark only decreases at the end of the window
+        for _ in 0..WINDOW_FRAMES {
+            assert!(!mark.end_frame());
+        }
+        assert_eq!(mark.get(), 10);
+        // the next window has a lower peak
+        mark.record(4);
+        for _ in 1..WINDOW_FRAMES {
+            mark.end_frame();
+        }
+        assert!(mark.end_frame());
+        assert_eq!(mark.get(), 4);
+    }
+
+    #[test]
+    fn test_reuse_buffers() {
+        let mut pool = BufferPool::<Vec<u8>>::new(100);
+        let mut first = pool.take_or_else(|| Vec::with_capacity(50));
+        first.extend_from_slice(&[1, 2, 3]);
+        let ptr = first.as_ptr();
+        let second = pool.take();
+        pool.give_back(first);
+        pool.give_back(second);
+        assert_eq!(pool.len(), 2);
+
+        // the buffers are cleared but keep their allocation
+        let second = pool.take();
+        let first = pool.take();
+        assert!(first.is_empty());
+        assert_eq!(first.as_ptr(), ptr);
+        assert_eq!(first.capacity(), 50);
+
+        // buffers that grew too much are not retained
+        pool.give_back(Vec::with_capacity(200));
+        pool.give_back(first);
+        assert_eq!(pool.len(), 1);
+        drop(second);
+    }
+
+    #[test]
+    fn test_release_unused_buffers() {
+        let mut pool = BufferPool::<Vec<u8>>::default();
+        let buffers = (0..8).map(|_| pool.take()).collect::<Vec<_>>();
+        for buffer in buffers {
+            pool.give_back(buffer);
+        }
+        assert_eq!(pool.len(), 8);
+
+        // after a burst, the pool only keeps the buffers that are still needed
+        for _ in 0..2 * WINDOW_FRAMES {
+            let buffers = [pool.take(), pool.take()];
+            for buffer in buffers {
+                pool.give_back(buffer);
+            }
+            pool.end_frame();
+        }
+        assert_eq!(pool.len(), 2);
+    }
+
+    #[test]
+    fn test_disabled_pool() {
+        let mut pool = BufferPool::<Vec<u8>>::disabled();
+        let buffer = pool.take();
+        pool.give_back(buffer);
+        assert_eq!(pool.len(), 0);
+    }
+}
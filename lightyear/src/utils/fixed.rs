@@ -0,0 +1,594 @@
+//! Fixed-point numbers for deterministic simulations.
+//!
+//! The result of a float operation can differ between platforms: for example `a * b + c` is fused into a single
+//! rounding on some ARM targets and not on x86. A client predicting the simulation of a server on another platform
+//! then sees tiny differences between its predicted values and the confirmed values, and rolls back constantly.
+//! A lockstep simulation, where every peer simulates the whole state from the inputs, drifts apart for the same reason.
+//!
+//! [`Fixed32`], [`FixedVec2`] and [`FixedVec3`] only use integer arithmetic, so they give bit-identical results
+//! on every platform. They are the recommended representation of the simulated state when the simulation must be
+//! deterministic:
+//! - they implement the traits needed to replicate and predict a component with [`ComponentSyncMode::Full`]
+//!   (serde, `Clone`, `PartialEq`), and [`Lerp`] for the interpolation and the correction
+//! - they implement `Hash`, to compare the state of two peers with a hash
+//! - they can be bit-packed with [`#[quantize]`](crate::serialize::quantize): a fixed-point value is encoded
+//!   exactly if `2^bits - 1` is at least the number of fixed-point steps in the range
+//!   (i.e. `(max - min) * 65536`, so 27 bits for `-1000.0..1000.0`)
+//!
+//! Convert the state to floats with [`Fixed32::to_f32`] or `Vec2::from` only to render it.
+//!
+//! ```rust,ignore
+//! #[derive(Component, Serialize, Deserialize, Clone, PartialEq, Debug, Lerp)]
+//! struct Position(FixedVec2);
+//!
+//! app.register_component::<Position>(ChannelDirection::ServerToClient)
+//!     .add_prediction(ComponentSyncMode::Full)
+//!     .add_interpolation(ComponentSyncMode::Full)
+//!     .add_lerp_interpolation_fn();
+//! ```
+//!
+//! [`ComponentSyncMode::Full`]: crate::prelude::ComponentSyncMode::Full
+use std::fmt;
+use std::ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Sub, SubAssign};
+
+use bevy::math::{Vec2, Vec3};
+use bevy::reflect::Reflect;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::protocol::component::Lerp;
+use crate::serialize::quantize::{BitReader, BitWriter, Quantization, QuantizedFloat};
+use crate::serialize::SerializationError;
+
+/// Signed fixed-point number with 16 integer bits and 16 fractional bits.
+///
+/// The arithmetic wraps on overflow, like the `wrapping_*` methods of the integers, so that debug and release
+/// builds give the same results. The division by zero panics.
+#[derive(Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Reflect)]
+pub struct Fixed32(i32);
+
+impl Fixed32 {
+    /// Number of fractional bits
+    pub const FRAC_BITS: u32 = 16;
+    pub const ZERO: Self = Self(0);
+    pub const ONE: Self = Self(1 << Self::FRAC_BITS);
+    pub const MIN: Self = Self(i32::MIN);
+    pub const MAX: Self = Self(i32::MAX);
+    /// Smallest positive value, 2^-16
+    pub const EPSILON: Self = Self(1);
+
+    /// Fixed-point number whose raw representation is `bits`
+    pub const fn from_bits(bits: i32) -> Self {
+        Self(bits)
+    }
+
+    /// Raw representation of the number, i.e. the number multiplied by 2^16
+    pub const fn to_bits(self) -> i32 {
+        self.0
+    }
+
+    pub const fn from_int(value: i32) -> Self {
+        Self(value.wrapping_shl(Self::FRAC_BITS))
+    }
+
+    /// Closest fixed-point number to the float. The conversion is exact for the floats that are multiples of 2^-16,
+    /// and saturates outside of the range of `Fixed32`; NaN is converted to zero.
+    pub fn from_f32(value: f32) -> Self {
+        // multiplying by a power of two is exact, and the rounding is the same on every platform
+        Self((value * Self::ONE.0 as f32).round() as i32)
+    }
+
+    pub fn to_f32(self) -> f32 {
+        self.0 as f32 / Self::ONE.0 as f32
+    }
+
+    pub fn abs(self) -> Self {
+        Self(self.0.wrapping_abs())
+    }
+
+    /// Largest integer smaller than or equal to the number
+    pub fn floor(self) -> Self {
+        Self(self.0 & !(Self::ONE.0 - 1))
+    }
+
+    /// Square root of the number, rounded down. Returns zero for negative numbers.
+    pub fn sqrt(self) -> Self {
+        if self.0 <= 0 {
+            return Self::ZERO;
+        }
+        // sqrt(x * 2^16) * 2^8 = sqrt(x * 2^32)
+        Self(isqrt((self.0 as u64) << Self::FRAC_BITS) as i32)
+    }
+}
+
+/// Integer square root, rounded down, computed bit by bit
+fn isqrt(value: u64) -> u64 {
+    let mut remainder = value;
+    let mut root = 0;
+    let mut bit = 1u64 << 62;
+    while bit > value {
+        bit >>= 2;
+    }
+    while bit != 0 {
+        if remainder >= root + bit {
+            remainder -= root + bit;
+            root = (root >> 1) + bit;
+        } else {
+            root >>= 1;
+        }
+        bit >>= 2;
+    }
+    root
+}
+
+impl fmt::Debug for Fixed32 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Fixed32({})", self.to_f32())
+    }
+}
+
+impl fmt::Display for Fixed32 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.to_f32(), f)
+    }
+}
+
+impl From<i32> for Fixed32 {
+    fn from(value: i32) -> Self {
+        Self::from_int(value)
+    }
+}
+
+impl From<Fixed32> for f32 {
+    fn from(value: Fixed32) -> Self {
+        value.to_f32()
+    }
+}
+
+impl Add for Fixed32 {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        Self(self.0.wrapping_add(rhs.0))
+    }
+}
+
+impl Sub for Fixed32 {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        Self(self.0.wrapping_sub(rhs.0))
+    }
+}
+
+impl Mul for Fixed32 {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self {
+        Self(((self.0 as i64 * rhs.0 as i64) >> Self::FRAC_BITS) as i32)
+    }
+}
+
+impl Div for Fixed32 {
+    type Output = Self;
+
+    fn div(self, rhs: Self) -> Self {
+        Self((((self.0 as i64) << Self::FRAC_BITS) / rhs.0 as i64) as i32)
+    }
+}
+
+impl Neg for Fixed32 {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Self(self.0.wrapping_neg())
+    }
+}
+
+macro_rules! impl_assign_ops {
+    ($ty:ty, $rhs:ty) => {
+        impl AddAssign<$rhs> for $ty {
+            fn add_assign(&mut self, rhs: $rhs) {
+                *self = *self + rhs;
+            }
+        }
+
+        impl SubAssign<$rhs> for $ty {
+            fn sub_assign(&mut self, rhs: $rhs) {
+                *self = *self - rhs;
+            }
+        }
+    };
+}
+
+impl_assign_ops!(Fixed32, Fixed32);
+
+impl MulAssign for Fixed32 {
+    fn mul_assign(&mut self, rhs: Self) {
+        *self = *self * rhs;
+    }
+}
+
+impl DivAssign for Fixed32 {
+    fn div_assign(&mut self, rhs: Self) {
+        *self = *self / rhs;
+    }
+}
+
+/// Serialized as the 4 bytes of its raw representation
+impl Serialize for Fixed32 {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.0.to_le_bytes().serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Fixed32 {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        <[u8; 4]>::deserialize(deserializer).map(|bytes| Self(i32::from_le_bytes(bytes)))
+    }
+}
+
+/// The interpolation factor is converted to a fixed-point number, so the result only depends on `t`
+impl Lerp for Fixed32 {
+    fn lerp(start: &Self, other: &Self, t: f32) -> Self {
+        *start + (*other - *start) * Self::from_f32(t)
+    }
+}
+
+/// The range of the quantization is converted to fixed-point numbers, and the value is encoded with integer
+/// arithmetic, so it is decoded identically on every platform
+impl QuantizedFloat for Fixed32 {
+    fn write_quantized(
+        &self,
+        quantization: Quantization,
+        writer: &mut BitWriter,
+    ) -> Result<(), SerializationError> {
+        let (min, span, levels) = quantization_steps(quantization);
+        debug_assert!(
+            (min..=min + span as i64).contains(&(self.0 as i64)),
+            "the value {self} is outside of the quantization range {}..{}",
+            quantization.min(),
+            quantization.max()
+        );
+        let offset = (self.0 as i64 - min).clamp(0, span as i64) as u128;
+        let encoded = (offset * levels as u128 + span as u128 / 2) / span as u128;
+        writer.write_bits(encoded as u32, quantization.bits())
+    }
+
+    fn read_quantized(
+        quantization: Quantization,
+        reader: &mut BitReader,
+    ) -> Result<Self, SerializationError> {
+        let (min, span, levels) = quantization_steps(quantization);
+        let level = (reader.read_bits(quantization.bits())? as u64).min(levels) as u128;
+        let offset = (level * span as u128 + levels as u128 / 2) / levels as u128;
+        Ok(Self((min + offset as i64) as i32))
+    }
+}
+
+/// Raw value of the start of the range, number of fixed-point steps in the range, and largest encoded value
+fn quantization_steps(quantization: Quantization) -> (i64, u64, u64) {
+    let min = Fixed32::from_f32(quantization.min()).0 as i64;
+    let max = Fixed32::from_f32(quantization.max()).0 as i64;
+    let span = (max - min).max(1) as u64;
+    let levels = (1u64 << quantization.bits()) - 1;
+    (min, span, levels)
+}
+
+macro_rules! impl_fixed_vec {
+    ($name:ident, $vec:ty, $($field:ident),+) => {
+        impl $name {
+            pub const ZERO: Self = Self { $($field: Fixed32::ZERO),+ };
+
+            pub const fn new($($field: Fixed32),+) -> Self {
+                Self { $($field),+ }
+            }
+
+            /// Vector with all the coordinates equal to `value`
+            pub const fn splat(value: Fixed32) -> Self {
+                Self { $($field: value),+ }
+            }
+
+            /// Closest fixed-point vector, see [`Fixed32::from_f32`]
+            pub fn from_vec(vec: $vec) -> Self {
+                Self { $($field: Fixed32::from_f32(vec.$field)),+ }
+            }
+
+            pub fn to_vec(self) -> $vec {
+                <$vec>::new($(self.$field.to_f32()),+)
+            }
+
+            pub fn dot(self, rhs: Self) -> Fixed32 {
+                Fixed32::ZERO $(+ self.$field * rhs.$field)+
+            }
+
+            pub fn length_squared(self) -> Fixed32 {
+                self.dot(self)
+            }
+
+            pub fn length(self) -> Fixed32 {
+                self.length_squared().sqrt()
+            }
+        }
+
+        impl From<$name> for $vec {
+            fn from(value: $name) -> Self {
+                value.to_vec()
+            }
+        }
+
+        impl Add for $name {
+            type Output = Self;
+
+            fn add(self, rhs: Self) -> Self {
+                Self { $($field: self.$field + rhs.$field),+ }
+            }
+        }
+
+        impl Sub for $name {
+            type Output = Self;
+
+            fn sub(self, rhs: Self) -> Self {
+                Self { $($field: self.$field - rhs.$field),+ }
+            }
+        }
+
+        impl Mul<Fixed32> for $name {
+            type Output = Self;
+
+            fn mul(self, rhs: Fixed32) -> Self {
+                Self { $($field: self.$field * rhs),+ }
+            }
+        }
+
+        impl Div<Fixed32> for $name {
+            type Output = Self;
+
+            fn div(self, rhs: Fixed32) -> Self {
+                Self { $($field: self.$field / rhs),+ }
+            }
+        }
+
+        impl Neg for $name {
+            type Output = Self;
+
+            fn neg(self) -> Self {
+                Self { $($field: -self.$field),+ }
+            }
+        }
+
+        impl_assign_ops!($name, $name);
+
+        impl MulAssign<Fixed32> for $name {
+            fn mul_assign(&mut self, rhs: Fixed32) {
+                *self = *self * rhs;
+            }
+        }
+
+        impl Lerp for $name {
+            fn lerp(start: &Self, other: &Self, t: f32) -> Self {
+                Self { $($field: Fixed32::lerp(&start.$field, &other.$field, t)),+ }
+            }
+        }
+
+        /// Every coordinate is quantized with the same range
+        impl QuantizedFloat for $name {
+            fn write_quantized(
+                &self,
+                quantization: Quantization,
+                writer: &mut BitWriter,
+            ) -> Result<(), SerializationError> {
+                $(self.$field.write_quantized(quantization, writer)?;)+
+                Ok(())
+            }
+
+            fn read_quantized(
+                quantization: Quantization,
+                reader: &mut BitReader,
+            ) -> Result<Self, SerializationError> {
+                Ok(Self { $($field: Fixed32::read_quantized(quantization, reader)?),+ })
+            }
+        }
+    };
+}
+
+/// 2D vector of [`Fixed32`]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Reflect, Serialize, Deserialize)]
+pub struct FixedVec2 {
+    pub x: Fixed32,
+    pub y: Fixed32,
+}
+
+impl_fixed_vec!(FixedVec2, Vec2, x, y);
+
+/// 3D vector of [`Fixed32`]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Reflect, Serialize, Deserialize)]
+pub struct FixedVec3 {
+    pub x: Fixed32,
+    pub y: Fixed32,
+    pub z: Fixed32,
+}
+
+impl_fixed_vec!(FixedVec3, Vec3, x, y, z);
+
+impl FixedVec3 {
+    pub fn cross(self, rhs: Self) -> Self {
+        Self {
+            x: self.y * rhs.z - self.z * rhs.y,
+            y: self.z * rhs.x - self.x * rhs.z,
+            z: self.x * rhs.y - self.y * rhs.x,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::hash::{DefaultHasher, Hash, Hasher};
+
+    use lightyear_macros::QuantizeInternal;
+
+    use crate::serialize::quantize::{serialize_field, Quantize};
+    use crate::serialize::reader::Reader;
+    use crate::serialize::writer::Writer;
+
+    use super::*;
+
+    fn fixed(value: f32) -> Fixed32 {
+        Fixed32::from_f32(value)
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        assert_eq!(fixed(1.5) + fixed(2.25), fixed(3.75));
+        assert_eq!(fixed(1.5) - fixed(2.25), fixed(-0.75));
+        assert_eq!(fixed(1.5) * fixed(-2.25), fixed(-3.375));
+        assert_eq!(fixed(-3.375) / fixed(1.5), fixed(-2.25));
+        assert_eq!(Fixed32::from_int(3), fixed(3.0));
+        assert_eq!(fixed(-2.5).abs(), fixed(2.5));
+        assert_eq!(fixed(-2.5).floor(), fixed(-3.0));
+        assert_eq!(fixed(6.25).sqrt(), fixed(2.5));
+        assert_eq!(Fixed32::from_int(2).sqrt(), Fixed32::from_bits(92681));
+        assert_eq!(fixed(-1.0).sqrt(), Fixed32::ZERO);
+        // the multiplication rounds towards negative infinity
+        assert_eq!(Fixed32::EPSILON * fixed(0.5), Fixed32::ZERO);
+        assert_eq!(-Fixed32::EPSILON * fixed(0.5), -Fixed32::EPSILON);
+        // overflows wrap
+        assert_eq!(Fixed32::MAX + Fixed32::EPSILON, Fixed32::MIN);
+        // the conversions saturate
+        assert_eq!(fixed(1e9), Fixed32::MAX);
+        assert_eq!(fixed(f32::NAN), Fixed32::ZERO);
+        assert_eq!(fixed(0.1).to_f32(), 6554.0 / 65536.0);
+
+        let a = FixedVec3::from_vec(Vec3::new(1.0, 2.0, 3.0));
+        let b = FixedVec3::from_vec(Vec3::new(-2.0, 0.5, 4.0));
+        assert_eq!(a.dot(b), fixed(11.0));
+        assert_eq!(a.cross(b).to_vec(), Vec3::new(1.0 * 2.0 + 4.5, -10.0, 4.5));
+        assert_eq!((a + b * fixed(2.0)).to_vec(), Vec3::new(-3.0, 3.0, 11.0));
+        assert_eq!(FixedVec2::new(fixed(3.0), fixed(4.0)).length(), fixed(5.0));
+    }
+
+    #[test]
+    fn test_lerp() {
+        let start = FixedVec2::from_vec(Vec2::new(0.0, 10.0));
+        let end = FixedVec2::from_vec(Vec2::new(4.0, -10.0));
+        assert_eq!(FixedVec2::lerp(&start, &end, 0.0), start);
+        assert_eq!(FixedVec2::lerp(&start, &end, 1.0), end);
+        assert_eq!(
+            FixedVec2::lerp(&start, &end, 0.25).to_vec(),
+            Vec2::new(1.0, 5.0)
+        );
+    }
+
+    #[test]
+    fn test_serde_round_trip() {
+        let value = FixedVec3::from_vec(Vec3::new(-1234.5678, 0.1, 30000.0));
+        let mut writer = Writer::default();
+        serialize_field(&value, &mut writer).unwrap();
+        let bytes = writer.to_bytes();
+        assert_eq!(bytes.len(), 12);
+        let read: FixedVec3 =
+            crate::serialize::quantize::deserialize_field(&mut Reader::from(bytes)).unwrap();
+        assert_eq!(read, value);
+    }
+
+    #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, QuantizeInternal)]
+    struct Body {
+        // 2000 * 2^16 steps fit in 27 bits: the positions are encoded exactly
+        #[quantize(range = "-1000.0..1000.0", bits = 27)]
+        position: FixedVec2,
+        #[quantize(range = "-50.0..50.0", bits = 12)]
+        velocity: FixedVec2,
+    }
+
+    #[test]
+    fn test_quantize() {
+        let body = Body {
+            position: FixedVec2::new(Fixed32::from_bits(-1000 << 16), fixed(123.456)),
+            velocity: FixedVec2::from_vec(Vec2::new(50.0, -12.3)),
+        };
+        let mut writer = Writer::default();
+        body.quantize(&mut writer).unwrap();
+        let bytes = writer.to_bytes();
+        // 27 * 2 + 12 * 2 = 78 bits
+        assert_eq!(bytes.len(), 10);
+        let read = Body::dequantize(&mut Reader::from(bytes)).unwrap();
+        assert_eq!(read.position, body.position);
+        assert_eq!(read.velocity.x, body.velocity.x);
+        assert!((read.velocity.y - body.velocity.y).abs() < fixed(0.02));
+    }
+
+    /// Operations whose float result depends on the platform
+    trait Scalar: Copy {
+        fn from_f32(value: f32) -> Self;
+
+        /// `self * a + b`, fused in a single rounding or not, like on different platforms
+        fn mul_add(self, a: Self, b: Self, fused: bool) -> Self;
+
+        fn hash_bits<H: Hasher>(&self, state: &mut H);
+    }
+
+    impl Scalar for f32 {
+        fn from_f32(value: f32) -> Self {
+            value
+        }
+
+        fn mul_add(self, a: Self, b: Self, fused: bool) -> Self {
+            if fused {
+                f32::mul_add(self, a, b)
+            } else {
+                self * a + b
+            }
+        }
+
+        fn hash_bits<H: Hasher>(&self, state: &mut H) {
+            self.to_bits().hash(state);
+        }
+    }
+
+    impl Scalar for Fixed32 {
+        fn from_f32(value: f32) -> Self {
+            Fixed32::from_f32(value)
+        }
+
+        /// The integer arithmetic has no fused variant
+        fn mul_add(self, a: Self, b: Self, _: bool) -> Self {
+            self * a + b
+        }
+
+        fn hash_bits<H: Hasher>(&self, state: &mut H) {
+            self.hash(state);
+        }
+    }
+
+    /// Simulate a projectile with drag and gravity, and return the hash of its state at every tick
+    fn simulate<S: Scalar>(fused: bool) -> Vec<u64> {
+        let dt = S::from_f32(1.0 / 64.0);
+        let drag = S::from_f32(0.993);
+        let gravity = S::from_f32(-9.81);
+        let mut position = [S::from_f32(0.0), S::from_f32(1.0)];
+        let mut velocity = [S::from_f32(12.3), S::from_f32(25.7)];
+        (0..600)
+            .map(|_| {
+                velocity[0] = velocity[0].mul_add(drag, S::from_f32(0.0), fused);
+                velocity[1] =
+                    velocity[1].mul_add(drag, gravity.mul_add(dt, S::from_f32(0.0), false), fused);
+                for i in 0..2 {
+                    position[i] = velocity[i].mul_add(dt, position[i], fused);
+                }
+                let mut hasher = DefaultHasher::new();
+                position
+                    .iter()
+                    .chain(velocity.iter())
+                    .for_each(|v| v.hash_bits(&mut hasher));
+                hasher.finish()
+            })
+            .collect()
+    }
+
+    /// The float simulation diverges between a platform that fuses the multiplications and additions and one that
+    /// doesn't, but the fixed-point simulation gives the same state on both
+    #[test]
+    fn test_cross_platform_determinism() {
+        assert_ne!(simulate::<f32>(true), simulate::<f32>(false));
+        assert_eq!(simulate::<Fixed32>(true), simulate::<Fixed32>(false));
+    }
+}
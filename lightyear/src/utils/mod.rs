@@ -12,6 +12,9 @@ pub mod bevy;
 #[cfg(feature = "avian2d")]
 pub mod avian2d;
 
+pub(crate) mod buffer_pool;
 pub(crate) mod captures;
+pub mod fixed;
 pub(crate) mod pool;
+pub(crate) mod quantile;
 pub mod wrapping_id;
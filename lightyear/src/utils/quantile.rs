@@ -0,0 +1,161 @@
+//! Streaming estimation of a quantile with constant memory, using the P² algorithm
+//!
+//! See: R. Jain and I. Chlamtac, "The P² algorithm for dynamic calculation of quantiles and
+//! histograms without storing observations", 1985.
+
+/// Estimates one quantile of a stream of observations with 5 markers, without storing the observations.
+///
+/// The estimate is exact while fewer than 5 values have been observed.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct P2Quantile {
+    /// The quantile to estimate, in `[0, 1]`
+    p: f64,
+    /// Number of observations
+    count: u64,
+    /// Heights of the markers; the first observations are stored here until we have 5 of them
+    heights: [f64; 5],
+    /// Actual positions of the markers
+    positions: [f64; 5],
+    /// Desired positions of the markers
+    desired: [f64; 5],
+    /// Increments of the desired positions for each observation
+    increments: [f64; 5],
+}
+
+impl P2Quantile {
+    pub(crate) fn new(p: f64) -> Self {
+        assert!((0.0..=1.0).contains(&p), "the quantile must be in [0, 1]");
+        Self {
+            p,
+            count: 0,
+            heights: [0.0; 5],
+            positions: [0.0, 1.0, 2.0, 3.0, 4.0],
+            desired: [0.0, 2.0 * p, 4.0 * p, 2.0 + 2.0 * p, 4.0],
+            increments: [0.0, p / 2.0, p, (1.0 + p) / 2.0, 1.0],
+        }
+    }
+
+    pub(crate) fn count(&self) -> u64 {
+        self.count
+    }
+
+    pub(crate) fn observe(&mut self, value: f64) {
+        if self.count < 5 {
+            self.heights[self.count as usize] = value;
+            self.count += 1;
+            if self.count == 5 {
+                self.heights.sort_by(f64::total_cmp);
+            }
+            return;
+        }
+        self.count += 1;
+
+        // find the cell that contains the value, extending the extreme markers if needed
+        let cell = if value < self.heights[0] {
+            self.heights[0] = value;
+            0
+        } else if value >= self.heights[4] {
+            self.heights[4] = value;
+            3
+        } else {
+            (0..4).find(|&i| value < self.heights[i + 1]).unwrap_or(3)
+        };
+        for position in &mut self.positions[cell + 1..] {
+            *position += 1.0;
+        }
+        for (desired, increment) in self.desired.iter_mut().zip(self.increments) {
+            *desired += increment;
+        }
+
+        // move the middle markers towards their desired positions
+        for i in 1..4 {
+            let delta = self.desired[i] - self.positions[i];
+            if (delta >= 1.0 && self.positions[i + 1] - self.positions[i] > 1.0)
+                || (delta <= -1.0 && self.positions[i - 1] - self.positions[i] < -1.0)
+            {
+                let step = delta.signum();
+                let height = self.parabolic(i, step);
+                self.heights[i] = if self.heights[i - 1] < height && height < self.heights[i + 1] {
+                    height
+                } else {
+                    self.linear(i, step)
+                };
+                self.positions[i] += step;
+            }
+        }
+    }
+
+    /// Current estimate of the quantile, or `None` if nothing was observed
+    pub(crate) fn estimate(&self) -> Option<f64> {
+        match self.count {
+            0 => None,
+            1..=4 => {
+                let mut values = self.heights;
+                let values = &mut values[..self.count as usize];
+                values.sort_by(f64::total_cmp);
+                let index = (self.p * (self.count - 1) as f64).round() as usize;
+                Some(values[index])
+            }
+            _ => Some(self.heights[2]),
+        }
+    }
+
+    fn parabolic(&self, i: usize, step: f64) -> f64 {
+        let (q, n) = (&self.heights, &self.positions);
+        q[i] + step / (n[i + 1] - n[i - 1])
+            * ((n[i] - n[i - 1] + step) * (q[i + 1] - q[i]) / (n[i + 1] - n[i])
+                + (n[i + 1] - n[i] - step) * (q[i] - q[i - 1]) / (n[i] - n[i - 1]))
+    }
+
+    fn linear(&self, i: usize, step: f64) -> f64 {
+        let (q, n) = (&self.heights, &self.positions);
+        let j = if step > 0.0 { i + 1 } else { i - 1 };
+        q[i] + step * (q[j] - q[i]) / (n[j] - n[i])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_few_observations_are_exact() {
+        let mut median = P2Quantile::new(0.5);
+        assert_eq!(median.estimate(), None);
+        for value in [5.0, 1.0, 3.0] {
+            median.observe(value);
+        }
+        assert_eq!(median.estimate(), Some(3.0));
+    }
+
+    #[test]
+    fn test_uniform_distribution() {
+        let mut quantiles = [0.5, 0.9, 0.99].map(P2Quantile::new);
+        // deterministic permutation of 0..10_000
+        for i in 0..10_000u64 {
+            let value = ((i * 7919) % 10_000) as f64;
+            quantiles.iter_mut().for_each(|q| q.observe(value));
+        }
+        for (quantile, expected) in quantiles.iter().zip([5_000.0, 9_000.0, 9_900.0]) {
+            let estimate = quantile.estimate().unwrap();
+            assert!(
+                (estimate - expected).abs() < 100.0,
+                "estimated {estimate}, expected {expected}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_outliers() {
+        // 95% of the values are 10, 5% are 100
+        let mut p90 = P2Quantile::new(0.9);
+        let mut p99 = P2Quantile::new(0.99);
+        for i in 0..2_000 {
+            let value = if i % 20 == 0 { 100.0 } else { 10.0 };
+            p90.observe(value);
+            p99.observe(value);
+        }
+        assert!((p90.estimate().unwrap() - 10.0).abs() < 1.0);
+        assert!((p99.estimate().unwrap() - 100.0).abs() < 1.0);
+    }
+}
@@ -176,18 +176,18 @@ impl<K: Ord, T: PartialEq> Ord for ItemWithReadyKey<K, T> {
 
 #[cfg(test)]
 mod tests {
-    use bevy::utils::Duration;
-    use mock_instant::Instant;
-    use mock_instant::MockClock;
+    use bevy::utils::{Duration, Instant};
 
+    use crate::shared::clock::{Clock, MockClock};
     use crate::shared::tick_manager::Tick;
 
     use super::*;
 
     #[test]
     fn test_time_heap() {
+        let clock = MockClock::new();
         let mut heap = ReadyBuffer::<Instant, u64>::new();
-        let now = Instant::now();
+        let now = clock.now();
 
         // can insert items in any order of time
         heap.push(now + Duration::from_secs(2), 2);
@@ -195,13 +195,13 @@ mod tests {
         heap.push(now + Duration::from_secs(3), 3);
 
         // no items are visible
-        assert!(!heap.has_item(&Instant::now()));
+        assert!(!heap.has_item(&clock.now()));
 
         // we move the clock to 2, 2 items should be visible, in order of insertion
-        MockClock::advance(Duration::from_secs(2));
-        matches!(heap.pop_item(&Instant::now()), Some((_, 1)));
-        matches!(heap.pop_item(&Instant::now()), Some((_, 2)));
-        assert_eq!(heap.pop_item(&Instant::now()), None);
+        clock.advance(Duration::from_secs(2));
+        matches!(heap.pop_item(&clock.now()), Some((_, 1)));
+        matches!(heap.pop_item(&clock.now()), Some((_, 2)));
+        assert_eq!(heap.pop_item(&clock.now()), None);
         assert_eq!(heap.len(), 1);
     }
 
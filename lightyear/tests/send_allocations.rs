@@ -0,0 +1,76 @@
+//! Checks that once the send buffers have been allocated, sending the messages of a tick (almost) does not
+//! allocate anymore.
+//!
+//! The counting allocator is registered as the `#[global_allocator]` of this binary only, so that it does not
+//! slow down the other tests. It counts the allocations of each thread separately, so the allocations of the
+//! tests running in parallel are not included.
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+use lightyear::test_utils::message_send_allocations;
+
+const WARMUP_TICKS: u16 = 20;
+const MEASURED_TICKS: u16 = 50;
+const NUM_MESSAGES: u32 = 200;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+struct CountingAllocator;
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        count_allocation();
+        System.alloc(layout)
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        count_allocation();
+        System.alloc_zeroed(layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        count_allocation();
+        System.realloc(ptr, layout, new_size)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+fn count_allocation() {
+    // the thread-local can be unavailable while the thread is being destroyed
+    let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+}
+
+/// Number of allocations (including re-allocations) made by the current thread so far
+fn allocations() -> usize {
+    ALLOCATIONS.with(Cell::get)
+}
+
+#[test]
+fn send_buffer_reuse_allocations() {
+    let pooled = message_send_allocations(
+        true,
+        WARMUP_TICKS,
+        MEASURED_TICKS,
+        NUM_MESSAGES,
+        allocations,
+    );
+    let baseline = message_send_allocations(
+        false,
+        WARMUP_TICKS,
+        MEASURED_TICKS,
+        NUM_MESSAGES,
+        allocations,
+    );
+    assert!(
+        pooled * 10 <= baseline,
+        "{pooled} allocations with buffer reuse, {baseline} without"
+    );
+}
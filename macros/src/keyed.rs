@@ -0,0 +1,54 @@
+use proc_macro2::TokenStream;
+use quote::{quote, quote_spanned};
+use syn::spanned::Spanned;
+use syn::{parse_macro_input, Data, DeriveInput, Index};
+
+pub fn keyed_diffable_impl(
+    input: proc_macro::TokenStream,
+    shared_crate_name: TokenStream,
+) -> proc_macro::TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let struct_name = &input.ident;
+    let (impl_generics, type_generics, where_clause) = &input.generics.split_for_impl();
+
+    // the wrapper delegates to its only field
+    let field = match &input.data {
+        Data::Struct(data) if data.fields.len() == 1 => data.fields.iter().next().unwrap(),
+        _ => {
+            return syn::Error::new_spanned(
+                &input.ident,
+                "`KeyedDiffable` can only be derived on structs with a single field, such as `struct Inventory(HashMap<SlotId, ItemStack>)`",
+            )
+            .to_compile_error()
+            .into();
+        }
+    };
+    let member = match &field.ident {
+        Some(ident) => quote! { #ident },
+        None => {
+            let index = Index::from(0);
+            quote! { #index }
+        }
+    };
+    let ty = &field.ty;
+    // use the span of the field type so that a field that is not a keyed collection is reported on the field
+    let keyed = quote_spanned! { ty.span() =>
+        <#ty as #shared_crate_name::prelude::KeyedDiffable>
+    };
+
+    let gen = quote! {
+        impl #impl_generics #shared_crate_name::prelude::KeyedDiffable for #struct_name #type_generics #where_clause {
+            type Key = #keyed::Key;
+            type Value = #keyed::Value;
+
+            fn keyed_diff(&self, new: &Self) -> ::std::vec::Vec<#shared_crate_name::prelude::KeyedChange<Self::Key, Self::Value>> {
+                #keyed::keyed_diff(&self.#member, &new.#member)
+            }
+
+            fn apply_keyed_change(&mut self, change: &#shared_crate_name::prelude::KeyedChange<Self::Key, Self::Value>) {
+                #keyed::apply_keyed_change(&mut self.#member, change)
+            }
+        }
+    };
+    proc_macro::TokenStream::from(gen)
+}
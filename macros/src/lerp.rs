@@ -0,0 +1,136 @@
+use proc_macro2::TokenStream;
+use quote::{quote, quote_spanned};
+use syn::spanned::Spanned;
+use syn::{parse_macro_input, Attribute, Data, DeriveInput, Field, Index, LitStr, Path};
+
+enum FieldMode {
+    /// Interpolate the field with its own `Lerp` implementation
+    Lerp,
+    /// Keep the value of `start` until the end of the interpolation, then switch to `other`
+    Snap,
+    /// Interpolate the field with a function `fn(start: &T, other: &T, t: f32) -> T`
+    With(Path),
+}
+
+fn lerp_attrs(attrs: &[Attribute]) -> impl Iterator<Item = &Attribute> {
+    attrs.iter().filter(|attr| attr.path().is_ident("lerp"))
+}
+
+/// Parse the value of `with = "path::to::fn"`
+fn parse_with(meta: &syn::meta::ParseNestedMeta) -> syn::Result<Path> {
+    meta.value()?.parse::<LitStr>()?.parse()
+}
+
+fn field_mode(field: &Field) -> syn::Result<FieldMode> {
+    let mut mode = FieldMode::Lerp;
+    for attr in lerp_attrs(&field.attrs) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("snap") {
+                mode = FieldMode::Snap;
+                Ok(())
+            } else if meta.path.is_ident("field") {
+                mode = FieldMode::Lerp;
+                Ok(())
+            } else if meta.path.is_ident("with") {
+                mode = FieldMode::With(parse_with(&meta)?);
+                Ok(())
+            } else {
+                Err(meta.error(
+                    "expected `#[lerp(field)]`, `#[lerp(snap)]` or `#[lerp(with = \"path::to::fn\")]`",
+                ))
+            }
+        })?;
+    }
+    Ok(mode)
+}
+
+/// Function set with `#[lerp(with = "path::to::fn")]` on the type itself
+fn type_lerp_fn(input: &DeriveInput) -> syn::Result<Option<Path>> {
+    let mut function = None;
+    for attr in lerp_attrs(&input.attrs) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("with") {
+                function = Some(parse_with(&meta)?);
+                Ok(())
+            } else {
+                Err(meta.error("expected `#[lerp(with = \"path::to::fn\")]`"))
+            }
+        })?;
+    }
+    Ok(function)
+}
+
+pub fn lerp_impl(
+    input: proc_macro::TokenStream,
+    shared_crate_name: TokenStream,
+) -> proc_macro::TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let struct_name = &input.ident;
+    let (impl_generics, type_generics, where_clause) = &input.generics.split_for_impl();
+
+    // the whole type is interpolated with the user-provided function
+    match type_lerp_fn(&input) {
+        Ok(Some(function)) => {
+            let gen = quote! {
+                impl #impl_generics #shared_crate_name::prelude::Lerp for #struct_name #type_generics #where_clause {
+                    fn lerp(start: &Self, other: &Self, t: f32) -> Self {
+                        #function(start, other, t)
+                    }
+                }
+            };
+            return proc_macro::TokenStream::from(gen);
+        }
+        Ok(None) => {}
+        Err(e) => return e.to_compile_error().into(),
+    }
+
+    let Data::Struct(data) = &input.data else {
+        return syn::Error::new_spanned(
+            &input.ident,
+            "`Lerp` can only be derived on structs, unless the type has a `#[lerp(with = \"path::to::fn\")]` attribute",
+        )
+            .to_compile_error()
+            .into();
+    };
+
+    let mut fields = vec![];
+    for (i, field) in data.fields.iter().enumerate() {
+        let member = match &field.ident {
+            Some(ident) => quote! { #ident },
+            None => {
+                let index = Index::from(i);
+                quote! { #index }
+            }
+        };
+        let ty = &field.ty;
+        let value = match field_mode(field) {
+            // use the span of the field type so that non-lerpable fields are reported on the field
+            Ok(FieldMode::Lerp) => quote_spanned! { ty.span() =>
+                <#ty as #shared_crate_name::prelude::Lerp>::lerp(&start.#member, &other.#member, t)
+            },
+            Ok(FieldMode::Snap) => quote! {
+                if t < 1.0 {
+                    ::core::clone::Clone::clone(&start.#member)
+                } else {
+                    ::core::clone::Clone::clone(&other.#member)
+                }
+            },
+            Ok(FieldMode::With(function)) => quote! {
+                #function(&start.#member, &other.#member, t)
+            },
+            Err(e) => return e.to_compile_error().into(),
+        };
+        fields.push(quote! { #member: #value });
+    }
+
+    let gen = quote! {
+        impl #impl_generics #shared_crate_name::prelude::Lerp for #struct_name #type_generics #where_clause {
+            fn lerp(start: &Self, other: &Self, t: f32) -> Self {
+                Self {
+                    #(#fields),*
+                }
+            }
+        }
+    };
+    proc_macro::TokenStream::from(gen)
+}
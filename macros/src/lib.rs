@@ -9,9 +9,19 @@ use quote::{format_ident, quote};
 use syn::{parse_macro_input, ItemEnum};
 
 use channel::channel_impl;
+use keyed::keyed_diffable_impl;
+use lerp::lerp_impl;
+use map_entities::map_entities_impl;
+use quantize::quantize_impl;
+use versioned::versioned_impl;
 
 mod channel;
+mod keyed;
+mod lerp;
+mod map_entities;
+mod quantize;
 mod shared;
+mod versioned;
 
 // Channel
 #[doc(hidden)]
@@ -27,3 +37,192 @@ pub fn channel_derive(input: proc_macro::TokenStream) -> proc_macro::TokenStream
     let shared_crate_name = quote! { lightyear };
     channel_impl(input, shared_crate_name)
 }
+
+// Lerp
+#[doc(hidden)]
+#[proc_macro_derive(LerpInternal, attributes(lerp))]
+pub fn lerp_derive_internal(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let shared_crate_name = quote! { crate };
+    lerp_impl(input, shared_crate_name)
+}
+
+/// Derives the `Lerp` trait for a struct, by interpolating each field separately.
+///
+/// Fields are interpolated with their own `Lerp` implementation, unless they are marked with:
+/// - `#[lerp(snap)]`: the field keeps its start value and switches to the end value at the end of the
+///   interpolation (for enums, flags, ids... that cannot be interpolated)
+/// - `#[lerp(field)]`: the field is interpolated (this is the default)
+/// - `#[lerp(with = "path::to::fn")]`: the field is interpolated with a function
+///   `fn(start: &T, end: &T, t: f32) -> T`, for example an easing function
+///
+/// The whole type can also be interpolated with a custom function (for example a cubic hermite
+/// interpolation of the position), by putting `#[lerp(with = "path::to::fn")]` on the type itself.
+/// This also works for enums.
+///
+/// ```rust,ignore
+/// #[derive(Component, Serialize, Deserialize, Clone, PartialEq, Lerp)]
+/// struct PlayerState {
+///     position: Vec2,
+///     #[lerp(with = "ease_in_out")]
+///     scale: f32,
+///     #[lerp(snap)]
+///     stance: Stance,
+/// }
+///
+/// #[derive(Component, Serialize, Deserialize, Clone, PartialEq, Lerp)]
+/// #[lerp(with = "Rotation::slerp")]
+/// struct Rotation(Quat);
+/// ```
+#[proc_macro_derive(Lerp, attributes(lerp))]
+pub fn lerp_derive(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let shared_crate_name = quote! { lightyear };
+    lerp_impl(input, shared_crate_name)
+}
+
+// KeyedDiffable
+#[doc(hidden)]
+#[proc_macro_derive(KeyedDiffableInternal)]
+pub fn keyed_diffable_derive_internal(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let shared_crate_name = quote! { crate };
+    keyed_diffable_impl(input, shared_crate_name)
+}
+
+/// Derives the `KeyedDiffable` trait for a wrapper around a keyed collection (`HashMap`, `BTreeMap`...),
+/// by delegating to its only field.
+///
+/// The component can then be replicated with delta compression, which only sends the keys that changed.
+///
+/// ```rust,ignore
+/// #[derive(Component, Serialize, Deserialize, Clone, Default, PartialEq, KeyedDiffable)]
+/// struct Inventory(HashMap<SlotId, ItemStack>);
+///
+/// app.register_component::<Inventory>(ChannelDirection::ServerToClient)
+///     .add_delta_compression();
+/// ```
+#[proc_macro_derive(KeyedDiffable)]
+pub fn keyed_diffable_derive(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let shared_crate_name = quote! { lightyear };
+    keyed_diffable_impl(input, shared_crate_name)
+}
+
+// MapEntities
+#[doc(hidden)]
+#[proc_macro_derive(MapEntitiesInternal, attributes(entities))]
+pub fn map_entities_derive_internal(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let shared_crate_name = quote! { crate };
+    map_entities_impl(input, shared_crate_name)
+}
+
+/// Derives bevy's `MapEntities` trait, by mapping the fields marked with `#[entities]`.
+///
+/// The marked fields can be an `Entity`, an `Option`, `Vec`, `VecDeque`, `Box` or array of entities, or another
+/// type that derives `MapEntities` (see `MapEntityField`). Structs and enums are supported.
+///
+/// The component or message must also be registered with `add_map_entities`, so that the entities it contains
+/// are mapped to the local entities when it is received. If one of the entities was not spawned locally yet,
+/// the component or message is applied later, once the entity is spawned.
+///
+/// ```rust,ignore
+/// #[derive(Component, Serialize, Deserialize, Clone, PartialEq, MapEntities)]
+/// struct Projectile {
+///     #[entities]
+///     owner: Entity,
+///     #[entities]
+///     targets: Vec<Entity>,
+///     speed: f32,
+/// }
+///
+/// app.register_component::<Projectile>(ChannelDirection::ServerToClient)
+///     .add_map_entities();
+/// ```
+#[proc_macro_derive(MapEntities, attributes(entities))]
+pub fn map_entities_derive(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let shared_crate_name = quote! { lightyear };
+    map_entities_impl(input, shared_crate_name)
+}
+
+// Quantize
+#[doc(hidden)]
+#[proc_macro_derive(QuantizeInternal, attributes(quantize))]
+pub fn quantize_derive_internal(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let shared_crate_name = quote! { crate };
+    quantize_impl(input, shared_crate_name)
+}
+
+/// Derives the `Quantize` trait for a struct, to encode some of its float fields on fewer bits.
+///
+/// The fields marked with `#[quantize(range = "min..max", bits = N)]` are encoded on `N` bits (between 1 and 32),
+/// and are packed together before the other fields. The other fields are serialized with serde as usual.
+/// The attribute can be applied to `f32`, `f64`, `Vec2`, `Vec3` and `Vec4` fields; every coordinate of a vector
+/// is encoded with the same range.
+///
+/// ```rust,ignore
+/// #[derive(Component, Serialize, Deserialize, Clone, PartialEq, Quantize)]
+/// struct Position {
+///     #[quantize(range = "-1000.0..1000.0", bits = 16)]
+///     value: Vec2,
+///     sector: u8,
+/// }
+///
+/// app.register_component_custom_serde::<Position>(ChannelDirection::ServerToClient, SerializeFns::quantized());
+/// ```
+///
+/// The attribute cannot be applied to a field that is not a float:
+///
+/// ```rust,compile_fail
+/// #[derive(lightyear_macros::Quantize)]
+/// struct Health {
+///     #[quantize(range = "0.0..100.0", bits = 7)]
+///     value: u32,
+/// }
+/// ```
+#[proc_macro_derive(Quantize, attributes(quantize))]
+pub fn quantize_derive(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let shared_crate_name = quote! { lightyear };
+    quantize_impl(input, shared_crate_name)
+}
+
+// Versioned
+#[doc(hidden)]
+#[proc_macro_derive(VersionedInternal, attributes(net))]
+pub fn versioned_derive_internal(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let shared_crate_name = quote! { crate };
+    versioned_impl(input, shared_crate_name)
+}
+
+/// Derives the `Versioned` trait for a struct, so that fields can be added to it without breaking the
+/// compatibility with the peers that use an older version of the struct.
+///
+/// The fields added after the first version are marked with `#[net(added_in = N, default)]`, where `N` (2 or more)
+/// is the version of the schema that introduced the field. The peers with an older schema don't send the field,
+/// so it is filled with `Default::default()`, or with the function given by `default = "path"`.
+/// The fields are serialized with serde.
+///
+/// ```rust,ignore
+/// #[derive(Component, Serialize, Deserialize, Clone, PartialEq, Versioned)]
+/// struct Health {
+///     current: u16,
+///     max: u16,
+///     #[net(added_in = 2, default)]
+///     shield: u16,
+/// }
+///
+/// app.register_component::<Health>(ChannelDirection::ServerToClient)
+///     .add_schema_evolution();
+/// ```
+///
+/// The added fields must have a default value:
+///
+/// ```rust,compile_fail
+/// #[derive(lightyear_macros::Versioned)]
+/// struct Health {
+///     current: u16,
+///     #[net(added_in = 2)]
+///     shield: u16,
+/// }
+/// ```
+#[proc_macro_derive(Versioned, attributes(net))]
+pub fn versioned_derive(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let shared_crate_name = quote! { lightyear };
+    versioned_impl(input, shared_crate_name)
+}
@@ -0,0 +1,142 @@
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote, quote_spanned};
+use syn::spanned::Spanned;
+use syn::{parse_macro_input, Data, DeriveInput, Field, Fields, Index};
+
+/// Returns true if the field is marked with `#[entities]`
+fn is_entities_field(field: &Field) -> syn::Result<bool> {
+    let mut entities = false;
+    for attr in field
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("entities"))
+    {
+        attr.meta.require_path_only()?;
+        entities = true;
+    }
+    Ok(entities)
+}
+
+/// Map the field that is accessed with `value` (a `&mut` to the field)
+fn map_field(field: &Field, value: TokenStream, shared_crate_name: &TokenStream) -> TokenStream {
+    // use the span of the field type so that a field that cannot contain entities is reported on the field
+    quote_spanned! { field.ty.span() =>
+        #shared_crate_name::prelude::MapEntityField::map_entity_field(#value, entity_mapper);
+    }
+}
+
+/// Statements that map the `#[entities]` fields of a struct
+fn map_struct_fields(
+    fields: &Fields,
+    shared_crate_name: &TokenStream,
+) -> syn::Result<Vec<TokenStream>> {
+    let mut statements = Vec::new();
+    for (i, field) in fields.iter().enumerate() {
+        if !is_entities_field(field)? {
+            continue;
+        }
+        let member = match &field.ident {
+            Some(ident) => quote! { #ident },
+            None => {
+                let index = Index::from(i);
+                quote! { #index }
+            }
+        };
+        statements.push(map_field(
+            field,
+            quote! { &mut self.#member },
+            shared_crate_name,
+        ));
+    }
+    Ok(statements)
+}
+
+/// Match arms that map the `#[entities]` fields of each variant of an enum
+fn map_variant_fields(
+    input: &DeriveInput,
+    variants: &syn::punctuated::Punctuated<syn::Variant, syn::token::Comma>,
+    shared_crate_name: &TokenStream,
+) -> syn::Result<Vec<TokenStream>> {
+    let enum_name = &input.ident;
+    let mut arms = Vec::new();
+    for variant in variants {
+        let variant_name = &variant.ident;
+        let mut bindings = Vec::new();
+        let mut statements = Vec::new();
+        for (i, field) in variant.fields.iter().enumerate() {
+            if !is_entities_field(field)? {
+                continue;
+            }
+            let binding = format_ident!("__field_{}", i);
+            let member = match &field.ident {
+                Some(ident) => quote! { #ident },
+                None => {
+                    let index = Index::from(i);
+                    quote! { #index }
+                }
+            };
+            bindings.push(quote! { #member: #binding });
+            statements.push(map_field(field, quote! { #binding }, shared_crate_name));
+        }
+        arms.push(quote! {
+            #enum_name::#variant_name { #(#bindings,)* .. } => {
+                #(#statements)*
+            }
+        });
+    }
+    Ok(arms)
+}
+
+pub fn map_entities_impl(
+    input: proc_macro::TokenStream,
+    shared_crate_name: TokenStream,
+) -> proc_macro::TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let struct_name = &input.ident;
+    let (impl_generics, type_generics, where_clause) = &input.generics.split_for_impl();
+
+    let body = match &input.data {
+        Data::Struct(data) => {
+            map_struct_fields(&data.fields, &shared_crate_name).map(|statements| {
+                quote! { #(#statements)* }
+            })
+        }
+        Data::Enum(data) => {
+            map_variant_fields(&input, &data.variants, &shared_crate_name).map(|arms| {
+                if arms.is_empty() {
+                    quote! {}
+                } else {
+                    quote! {
+                        match self {
+                            #(#arms)*
+                        }
+                    }
+                }
+            })
+        }
+        Data::Union(_) => Err(syn::Error::new_spanned(
+            &input.ident,
+            "`MapEntities` cannot be derived on unions",
+        )),
+    };
+    let body = match body {
+        Ok(body) => body,
+        Err(e) => return e.to_compile_error().into(),
+    };
+
+    let gen = quote! {
+        impl #impl_generics #shared_crate_name::prelude::MapEntities for #struct_name #type_generics #where_clause {
+            #[allow(unused_variables)]
+            fn map_entities<M: #shared_crate_name::prelude::EntityMapper>(&mut self, entity_mapper: &mut M) {
+                #body
+            }
+        }
+
+        impl #impl_generics #shared_crate_name::prelude::MapEntityField for #struct_name #type_generics #where_clause {
+            fn map_entity_field<M: #shared_crate_name::prelude::EntityMapper>(&mut self, entity_mapper: &mut M) {
+                #shared_crate_name::prelude::MapEntities::map_entities(self, entity_mapper);
+            }
+        }
+    };
+    proc_macro::TokenStream::from(gen)
+}
@@ -0,0 +1,167 @@
+use proc_macro2::{Literal, TokenStream};
+use quote::{format_ident, quote, quote_spanned};
+use syn::spanned::Spanned;
+use syn::{parse_macro_input, Data, DeriveInput, Field, Fields, Index, LitInt, LitStr};
+
+/// Parameters of a `#[quantize(range = "min..max", bits = N)]` attribute
+struct Quantization {
+    min: f32,
+    max: f32,
+    bits: u8,
+}
+
+fn parse_range(lit: &LitStr) -> syn::Result<(f32, f32)> {
+    let value = lit.value();
+    let error =
+        || syn::Error::new_spanned(lit, "expected a range of floats like \"-1000.0..1000.0\"");
+    let (min, max) = value.split_once("..").ok_or_else(error)?;
+    let min = min.trim().parse::<f32>().map_err(|_| error())?;
+    let max = max.trim().parse::<f32>().map_err(|_| error())?;
+    if min.partial_cmp(&max) != Some(std::cmp::Ordering::Less) {
+        return Err(syn::Error::new_spanned(
+            lit,
+            "the quantization range must not be empty",
+        ));
+    }
+    Ok((min, max))
+}
+
+fn field_quantization(field: &Field) -> syn::Result<Option<Quantization>> {
+    let mut quantization = None;
+    for attr in field
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("quantize"))
+    {
+        let mut range = None;
+        let mut bits = None;
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("range") {
+                range = Some(parse_range(&meta.value()?.parse::<LitStr>()?)?);
+                Ok(())
+            } else if meta.path.is_ident("bits") {
+                let lit = meta.value()?.parse::<LitInt>()?;
+                let value = lit.base10_parse::<u8>()?;
+                if !(1..=32).contains(&value) {
+                    return Err(syn::Error::new_spanned(
+                        lit,
+                        "a value can be quantized on 1 to 32 bits",
+                    ));
+                }
+                bits = Some(value);
+                Ok(())
+            } else {
+                Err(meta.error("expected `#[quantize(range = \"min..max\", bits = N)]`"))
+            }
+        })?;
+        let (Some((min, max)), Some(bits)) = (range, bits) else {
+            return Err(syn::Error::new_spanned(
+                attr,
+                "expected `#[quantize(range = \"min..max\", bits = N)]`",
+            ));
+        };
+        quantization = Some(Quantization { min, max, bits });
+    }
+    Ok(quantization)
+}
+
+/// Literals cannot be negative, so the sign is emitted separately
+fn float_literal(value: f32) -> TokenStream {
+    let literal = Literal::f32_suffixed(value.abs());
+    if value.is_sign_negative() {
+        quote! { -#literal }
+    } else {
+        quote! { #literal }
+    }
+}
+
+pub fn quantize_impl(
+    input: proc_macro::TokenStream,
+    shared_crate_name: TokenStream,
+) -> proc_macro::TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let struct_name = &input.ident;
+    let (impl_generics, type_generics, where_clause) = &input.generics.split_for_impl();
+
+    let Data::Struct(data) = &input.data else {
+        return syn::Error::new_spanned(&input.ident, "`Quantize` can only be derived on structs")
+            .to_compile_error()
+            .into();
+    };
+
+    let quantize = quote! { #shared_crate_name::serialize::quantize };
+    // the quantized fields are packed first, then the other fields are serialized with serde
+    let mut write_quantized = vec![];
+    let mut read_quantized = vec![];
+    let mut write_fields = vec![];
+    let mut read_fields = vec![];
+    let mut members = vec![];
+    let mut variables = vec![];
+    for (i, field) in data.fields.iter().enumerate() {
+        let member = match &field.ident {
+            Some(ident) => quote! { #ident },
+            None => {
+                let index = Index::from(i);
+                quote! { #index }
+            }
+        };
+        let variable = format_ident!("__field_{}", i);
+        let ty = &field.ty;
+        match field_quantization(field) {
+            Ok(Some(Quantization { min, max, bits })) => {
+                let (min, max) = (float_literal(min), float_literal(max));
+                let quantization = quote! { #quantize::Quantization::new(#min, #max, #bits) };
+                // use the span of the field type so that non-float fields are reported on the field
+                write_quantized.push(quote_spanned! { ty.span() =>
+                    <#ty as #quantize::QuantizedFloat>::write_quantized(&self.#member, #quantization, &mut bits)?;
+                });
+                read_quantized.push(quote_spanned! { ty.span() =>
+                    let #variable = <#ty as #quantize::QuantizedFloat>::read_quantized(#quantization, &mut bits)?;
+                });
+            }
+            Ok(None) => {
+                write_fields.push(quote! {
+                    #quantize::serialize_field(&self.#member, writer)?;
+                });
+                read_fields.push(quote! {
+                    let #variable: #ty = #quantize::deserialize_field(reader)?;
+                });
+            }
+            Err(e) => return e.to_compile_error().into(),
+        }
+        members.push(member);
+        variables.push(variable);
+    }
+    let constructor = match &data.fields {
+        Fields::Named(_) => quote! { Self { #(#members: #variables),* } },
+        Fields::Unnamed(_) => quote! { Self(#(#variables),*) },
+        Fields::Unit => quote! { Self },
+    };
+
+    let gen = quote! {
+        impl #impl_generics #quantize::Quantize for #struct_name #type_generics #where_clause {
+            fn quantize(
+                &self,
+                writer: &mut #shared_crate_name::serialize::writer::Writer,
+            ) -> ::core::result::Result<(), #shared_crate_name::serialize::SerializationError> {
+                #[allow(unused_mut)]
+                let mut bits = #quantize::BitWriter::new(writer);
+                #(#write_quantized)*
+                bits.finish()?;
+                #(#write_fields)*
+                Ok(())
+            }
+
+            fn dequantize(
+                reader: &mut #shared_crate_name::serialize::reader::Reader,
+            ) -> ::core::result::Result<Self, #shared_crate_name::serialize::SerializationError> {
+                #[allow(unused_mut)]
+                let mut bits = #quantize::BitReader::new(reader);
+                #(#read_quantized)*
+                #(#read_fields)*
+                Ok(#constructor)
+            }
+        }
+    };
+    proc_macro::TokenStream::from(gen)
+}
@@ -0,0 +1,196 @@
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote, ToTokens};
+use syn::{parse_macro_input, Data, DeriveInput, Field, Fields, Index, LitInt, LitStr, Path};
+
+/// Parameters of a `#[net(added_in = N, default)]` attribute
+struct AddedField {
+    added_in: u8,
+    /// Expression that builds the value of the field when the peer did not send it
+    default: TokenStream,
+}
+
+fn field_added_in(field: &Field) -> syn::Result<Option<AddedField>> {
+    let mut added = None;
+    for attr in field
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("net"))
+    {
+        let mut added_in = None;
+        let mut default = None;
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("added_in") {
+                let lit = meta.value()?.parse::<LitInt>()?;
+                let value = lit.base10_parse::<u8>()?;
+                if value < 2 {
+                    return Err(syn::Error::new_spanned(
+                        lit,
+                        "the fields of the first version don't need `added_in`, the versions added later start at 2",
+                    ));
+                }
+                added_in = Some(value);
+                Ok(())
+            } else if meta.path.is_ident("default") {
+                default = Some(if meta.input.peek(syn::Token![=]) {
+                    let path = meta.value()?.parse::<LitStr>()?.parse::<Path>()?;
+                    quote! { #path() }
+                } else {
+                    quote! { ::core::default::Default::default() }
+                });
+                Ok(())
+            } else {
+                Err(meta.error("expected `#[net(added_in = N, default)]`"))
+            }
+        })?;
+        let (Some(added_in), Some(default)) = (added_in, default) else {
+            return Err(syn::Error::new_spanned(
+                attr,
+                "expected `#[net(added_in = N, default)]`: the peers with an older schema don't send the field, \
+                so it needs a default value",
+            ));
+        };
+        added = Some(AddedField { added_in, default });
+    }
+    Ok(added)
+}
+
+pub fn versioned_impl(
+    input: proc_macro::TokenStream,
+    shared_crate_name: TokenStream,
+) -> proc_macro::TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let struct_name = &input.ident;
+    let (impl_generics, type_generics, where_clause) = &input.generics.split_for_impl();
+
+    let Data::Struct(data) = &input.data else {
+        return syn::Error::new_spanned(&input.ident, "`Versioned` can only be derived on structs")
+            .to_compile_error()
+            .into();
+    };
+
+    let versioned = quote! { #shared_crate_name::serialize::versioned };
+    let mut schema_fields = vec![];
+    let mut base_fields = vec![];
+    // fields added after the first version: (version, member, variable, type, default)
+    let mut added_fields = vec![];
+    let mut members = vec![];
+    let mut variables = vec![];
+    for (i, field) in data.fields.iter().enumerate() {
+        let (member, name) = match &field.ident {
+            Some(ident) => (quote! { #ident }, ident.to_string()),
+            None => {
+                let index = Index::from(i);
+                (quote! { #index }, i.to_string())
+            }
+        };
+        let variable = format_ident!("__field_{}", i);
+        let ty = &field.ty;
+        let type_name = ty.to_token_stream().to_string();
+        let added = match field_added_in(field) {
+            Ok(added) => added,
+            Err(e) => return e.to_compile_error().into(),
+        };
+        let added_in = added.as_ref().map_or(1, |added| added.added_in);
+        schema_fields.push(quote! {
+            #versioned::FieldSchema {
+                name: #name,
+                type_name: #type_name,
+                added_in: #added_in,
+            }
+        });
+        match added {
+            Some(AddedField { added_in, default }) => {
+                added_fields.push((added_in, member.clone(), variable.clone(), ty, default));
+            }
+            None => base_fields.push((member.clone(), variable.clone(), ty)),
+        }
+        members.push(member);
+        variables.push(variable);
+    }
+    let version = added_fields
+        .iter()
+        .map(|(added_in, ..)| *added_in)
+        .max()
+        .unwrap_or(1);
+
+    let write_base = base_fields.iter().map(|(member, ..)| {
+        quote! { #versioned::serialize_field(&self.#member, writer)?; }
+    });
+    let read_base = base_fields.iter().map(|(_, variable, ty)| {
+        quote! { let #variable: #ty = #versioned::deserialize_field(reader)?; }
+    });
+    // every version after the first is a group of fields prefixed by its length, even if it is empty
+    let mut write_groups = vec![];
+    let mut read_groups = vec![];
+    for group_version in 2..=version {
+        let group: Vec<_> = added_fields
+            .iter()
+            .filter(|(added_in, ..)| *added_in == group_version)
+            .collect();
+        let write = group.iter().map(|(_, member, ..)| {
+            quote! { #versioned::serialize_field(&self.#member, &mut group)?; }
+        });
+        write_groups.push(quote! {
+            #[allow(unused_mut)]
+            let mut group = #shared_crate_name::serialize::writer::Writer::default();
+            #(#write)*
+            #versioned::write_group(writer, group)?;
+        });
+        if group.is_empty() {
+            read_groups.push(quote! {
+                if version >= #group_version {
+                    #versioned::read_group(reader)?;
+                }
+            });
+        } else {
+            let group_variables = group.iter().map(|(_, _, variable, ..)| variable);
+            let read = group.iter().map(|(_, _, _, ty, _)| {
+                quote! { #versioned::deserialize_field::<#ty>(&mut group)? }
+            });
+            let defaults = group.iter().map(|(.., default)| default);
+            read_groups.push(quote! {
+                let (#(#group_variables,)*) = if version >= #group_version {
+                    let mut group = #versioned::read_group(reader)?;
+                    (#(#read,)*)
+                } else {
+                    (#(#defaults,)*)
+                };
+            });
+        }
+    }
+    let constructor = match &data.fields {
+        Fields::Named(_) => quote! { Self { #(#members: #variables),* } },
+        Fields::Unnamed(_) => quote! { Self(#(#variables),*) },
+        Fields::Unit => quote! { Self },
+    };
+
+    let gen = quote! {
+        impl #impl_generics #versioned::Versioned for #struct_name #type_generics #where_clause {
+            const SCHEMA: #versioned::Schema = #versioned::Schema {
+                version: #version,
+                fields: &[#(#schema_fields),*],
+            };
+
+            fn serialize_versioned(
+                &self,
+                writer: &mut #shared_crate_name::serialize::writer::Writer,
+            ) -> ::core::result::Result<(), #shared_crate_name::serialize::SerializationError> {
+                #versioned::write_version(writer, #version)?;
+                #(#write_base)*
+                #(#write_groups)*
+                Ok(())
+            }
+
+            fn deserialize_versioned(
+                reader: &mut #shared_crate_name::serialize::reader::Reader,
+            ) -> ::core::result::Result<Self, #shared_crate_name::serialize::SerializationError> {
+                let version = #versioned::read_version(reader)?;
+                #(#read_base)*
+                #(#read_groups)*
+                #versioned::skip_groups(reader, version, #version)?;
+                Ok(#constructor)
+            }
+        }
+    };
+    proc_macro::TokenStream::from(gen)
+}
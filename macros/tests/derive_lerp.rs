@@ -0,0 +1,56 @@
+pub mod some_component {
+    use lightyear_macros::Lerp;
+
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    pub enum Stance {
+        Standing,
+        Crouching,
+    }
+
+    #[derive(Debug, PartialEq, Lerp)]
+    pub struct PlayerState {
+        pub position: f32,
+        #[lerp(field)]
+        pub speed: f64,
+        #[lerp(snap)]
+        pub stance: Stance,
+    }
+
+    #[derive(Debug, PartialEq, Lerp)]
+    pub struct Wrapper(pub f32, #[lerp(snap)] pub u8);
+}
+
+#[cfg(test)]
+mod tests {
+    use lightyear::prelude::Lerp;
+
+    use super::some_component::*;
+
+    #[test]
+    fn test_lerp_derive() {
+        let start = PlayerState {
+            position: 0.0,
+            speed: 1.0,
+            stance: Stance::Standing,
+        };
+        let end = PlayerState {
+            position: 4.0,
+            speed: 3.0,
+            stance: Stance::Crouching,
+        };
+        assert_eq!(
+            PlayerState::lerp(&start, &end, 0.5),
+            PlayerState {
+                position: 2.0,
+                speed: 2.0,
+                stance: Stance::Standing,
+            }
+        );
+        assert_eq!(PlayerState::lerp(&start, &end, 1.0), end);
+
+        assert_eq!(
+            Wrapper::lerp(&Wrapper(0.0, 1), &Wrapper(1.0, 2), 0.25),
+            Wrapper(0.25, 1)
+        );
+    }
+}
@@ -0,0 +1,49 @@
+pub mod some_component {
+    use bevy::math::Vec3;
+    use lightyear_macros::Quantize;
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize, Quantize)]
+    pub struct Transform {
+        #[quantize(range = "-1000.0..1000.0", bits = 16)]
+        pub position: Vec3,
+        #[quantize(range = "0.0..1.0", bits = 8)]
+        pub scale: f64,
+        pub id: u32,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize, Quantize)]
+    pub struct Wrapper(#[quantize(range = "-1.0..1.0", bits = 2)] pub f32, pub bool);
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::math::Vec3;
+    use lightyear::prelude::{Quantization, Quantize};
+    use lightyear::serialize::reader::Reader;
+
+    use super::some_component::*;
+
+    fn dequantize<T: Quantize>(bytes: Vec<u8>) -> T {
+        T::dequantize(&mut Reader::from(bytes)).unwrap()
+    }
+
+    #[test]
+    fn test_quantize_derive() {
+        // the 2 bits of the quantized field are packed on one byte, followed by the bool
+        assert_eq!(dequantize::<Wrapper>(vec![0, 1]), Wrapper(-1.0, true));
+        assert_eq!(dequantize::<Wrapper>(vec![3, 0]), Wrapper(1.0, false));
+
+        // 3 * 16 + 8 bits packed on 7 bytes, followed by the u32
+        let bytes = vec![0, 0, 0xFF, 0x7F, 0xFF, 0xFF, 0xFF, 7];
+        let quantization = Quantization::new(-1000.0, 1000.0, 16);
+        assert_eq!(
+            dequantize::<Transform>(bytes),
+            Transform {
+                position: Vec3::new(-1000.0, quantization.decode(0x7FFF), 1000.0),
+                scale: 1.0,
+                id: 7,
+            }
+        );
+    }
+}
@@ -0,0 +1,62 @@
+pub mod some_component {
+    use lightyear_macros::Versioned;
+    use serde::{Deserialize, Serialize};
+
+    pub fn default_speed() -> f32 {
+        1.0
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize, Versioned)]
+    pub struct Unit {
+        pub id: u32,
+        #[net(added_in = 2, default)]
+        pub name: String,
+        #[net(added_in = 3, default = "default_speed")]
+        pub speed: f32,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize, Versioned)]
+    pub struct Wrapper(pub u8, #[net(added_in = 2, default)] pub bool);
+}
+
+#[cfg(test)]
+mod tests {
+    use lightyear::prelude::Versioned;
+    use lightyear::serialize::reader::Reader;
+
+    use super::some_component::*;
+
+    fn deserialize<T: Versioned>(bytes: Vec<u8>) -> T {
+        T::deserialize_versioned(&mut Reader::from(bytes)).unwrap()
+    }
+
+    #[test]
+    fn test_versioned_derive() {
+        assert_eq!(Unit::SCHEMA.version, 3);
+        assert_eq!(Wrapper::SCHEMA.fields[1].name, "1");
+
+        // sent by a peer with the first version: the added fields get their default value
+        assert_eq!(
+            deserialize::<Unit>(vec![1, 7]),
+            Unit {
+                id: 7,
+                name: String::new(),
+                speed: 1.0,
+            }
+        );
+        // sent by a peer with the second version
+        assert_eq!(
+            deserialize::<Unit>(vec![2, 7, 2, 1, b'a']),
+            Unit {
+                id: 7,
+                name: "a".to_string(),
+                speed: 1.0,
+            }
+        );
+        // sent by a peer with a third version: the fields of the third version are skipped
+        assert_eq!(
+            deserialize::<Wrapper>(vec![3, 4, 1, 1, 2, 0, 0]),
+            Wrapper(4, true)
+        );
+    }
+}